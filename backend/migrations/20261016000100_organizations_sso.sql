@@ -0,0 +1,74 @@
+-- Organizations, memberships and per-org SSO configuration
+
+CREATE TABLE IF NOT EXISTS organizations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(100) NOT NULL,
+    slug VARCHAR(60) NOT NULL UNIQUE,
+    sso_enforced BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS org_memberships (
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role VARCHAR(20) NOT NULL DEFAULT 'member', -- owner, admin, member
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (org_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_org_memberships_user ON org_memberships(user_id);
+
+CREATE TABLE IF NOT EXISTS org_sso_configs (
+    org_id UUID PRIMARY KEY REFERENCES organizations(id) ON DELETE CASCADE,
+    protocol VARCHAR(10) NOT NULL, -- oidc, saml
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    -- OIDC
+    issuer_url TEXT,
+    client_id TEXT,
+    client_secret TEXT,
+    -- SAML
+    idp_entity_id TEXT,
+    idp_sso_url TEXT,
+    idp_public_key_pem TEXT,
+    -- Provisioning
+    role_mapping JSONB NOT NULL DEFAULT '{}',
+    default_role VARCHAR(20) NOT NULL DEFAULT 'member',
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS sso_login_states (
+    state VARCHAR(64) PRIMARY KEY,
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    nonce VARCHAR(64) NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+-- SAML AuthnRequests awaiting a response; each can be answered once
+CREATE TABLE IF NOT EXISTS saml_requests (
+    id VARCHAR(64) PRIMARY KEY,
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+-- IDs of accepted SAML assertions, kept until they expire so they cannot be replayed
+CREATE TABLE IF NOT EXISTS saml_used_assertions (
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    assertion_id VARCHAR(256) NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (org_id, assertion_id)
+);
+
+-- Email domains an org has proven it owns; only addresses on them can be provisioned
+CREATE TABLE IF NOT EXISTS org_domains (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    domain VARCHAR(253) NOT NULL,
+    verification_token VARCHAR(64) NOT NULL,
+    verified_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (org_id, domain)
+);
+
+-- A domain can only be verified by one org at a time
+CREATE UNIQUE INDEX IF NOT EXISTS idx_org_domains_verified
+    ON org_domains(domain) WHERE verified_at IS NOT NULL;
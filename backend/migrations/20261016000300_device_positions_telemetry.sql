@@ -0,0 +1,23 @@
+-- Persisted telemetry and last known device position
+
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS last_latitude DOUBLE PRECISION;
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS last_longitude DOUBLE PRECISION;
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS last_altitude DOUBLE PRECISION;
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS position_updated_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_devices_last_position ON devices(last_latitude, last_longitude)
+    WHERE last_latitude IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS device_telemetry (
+    id BIGSERIAL PRIMARY KEY,
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    recorded_at TIMESTAMPTZ NOT NULL,
+    battery_level SMALLINT NOT NULL,
+    latitude DOUBLE PRECISION NOT NULL,
+    longitude DOUBLE PRECISION NOT NULL,
+    altitude DOUBLE PRECISION,
+    payload JSONB NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_telemetry_device_time ON device_telemetry(device_id, recorded_at DESC);
@@ -0,0 +1,43 @@
+-- Persisted auth events, step-up challenges and the outbound email queue
+
+CREATE TABLE IF NOT EXISTS auth_events (
+    id BIGSERIAL PRIMARY KEY,
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    event VARCHAR(50) NOT NULL,
+    success BOOLEAN NOT NULL,
+    ip VARCHAR(64),
+    country VARCHAR(2),
+    latitude DOUBLE PRECISION,
+    longitude DOUBLE PRECISION,
+    user_agent TEXT,
+    fingerprint VARCHAR(64),
+    risk_score SMALLINT NOT NULL DEFAULT 0,
+    risk_reasons JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_auth_events_user_time ON auth_events(user_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS step_up_challenges (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    auth_event_id BIGINT NOT NULL REFERENCES auth_events(id) ON DELETE CASCADE,
+    auth_method VARCHAR(20) NOT NULL, -- password, sso
+    org_id UUID REFERENCES organizations(id) ON DELETE CASCADE, -- org of an SSO login
+    code_hash VARCHAR(64) NOT NULL,
+    attempts SMALLINT NOT NULL DEFAULT 0,
+    expires_at TIMESTAMPTZ NOT NULL,
+    verified_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS email_outbox (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    to_address VARCHAR(255) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    body TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    sent_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_outbox_unsent ON email_outbox(created_at) WHERE sent_at IS NULL;
@@ -0,0 +1,21 @@
+-- Persisted device commands with hardware acknowledgements, and device API keys
+
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS api_key_hash VARCHAR(64) UNIQUE;
+
+CREATE TABLE IF NOT EXISTS device_commands (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    command VARCHAR(50) NOT NULL,
+    parameters JSONB NOT NULL DEFAULT '{}',
+    status VARCHAR(20) NOT NULL DEFAULT 'sent', -- sent, succeeded, failed
+    estimated_duration_ms BIGINT NOT NULL,
+    estimated_battery_drain REAL NOT NULL,
+    actual_duration_ms BIGINT,
+    actual_battery_drain REAL,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    acked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_commands_device_time ON device_commands(device_id, created_at DESC);
@@ -0,0 +1,17 @@
+-- Claim-code device provisioning
+
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS hardware_id VARCHAR(128) UNIQUE;
+
+CREATE TABLE IF NOT EXISTS device_claim_codes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    code_hash VARCHAR(64) NOT NULL UNIQUE,
+    device_name VARCHAR(100),
+    device_type VARCHAR(20),
+    expires_at TIMESTAMPTZ NOT NULL,
+    claimed_at TIMESTAMPTZ,
+    device_id UUID REFERENCES devices(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_claim_codes_user ON device_claim_codes(user_id, created_at DESC);
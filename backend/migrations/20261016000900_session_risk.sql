@@ -0,0 +1,25 @@
+-- Risk-scored login sessions and per-org session risk policy
+
+ALTER TABLE organizations ADD COLUMN IF NOT EXISTS risk_step_up_threshold SMALLINT NOT NULL DEFAULT 50;
+ALTER TABLE organizations ADD COLUMN IF NOT EXISTS risk_block_threshold SMALLINT; -- NULL never blocks
+
+CREATE TABLE IF NOT EXISTS user_sessions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    auth_event_id BIGINT REFERENCES auth_events(id) ON DELETE SET NULL,
+    auth_method VARCHAR(20) NOT NULL, -- password, sso
+    ip VARCHAR(64),
+    country VARCHAR(2),
+    fingerprint VARCHAR(64),
+    risk_score SMALLINT NOT NULL DEFAULT 0,
+    risk_reasons JSONB NOT NULL DEFAULT '[]',
+    step_up_verified BOOLEAN NOT NULL DEFAULT FALSE,
+    status VARCHAR(20) NOT NULL DEFAULT 'active', -- active, revoked
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ,
+    revoked_by UUID REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_sessions_user ON user_sessions(user_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_user_sessions_risk ON user_sessions(risk_score DESC, created_at DESC) WHERE status = 'active';
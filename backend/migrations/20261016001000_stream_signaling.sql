@@ -0,0 +1,26 @@
+-- WebRTC signaling for device camera streams
+
+CREATE TABLE IF NOT EXISTS stream_sessions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    status VARCHAR(20) NOT NULL DEFAULT 'offered', -- offered, answered, closed
+    offer_sdp TEXT NOT NULL,
+    answer_sdp TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    answered_at TIMESTAMPTZ,
+    closed_at TIMESTAMPTZ,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_stream_sessions_device ON stream_sessions(device_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS stream_ice_candidates (
+    id BIGSERIAL PRIMARY KEY,
+    session_id UUID NOT NULL REFERENCES stream_sessions(id) ON DELETE CASCADE,
+    sender VARCHAR(10) NOT NULL, -- operator, device
+    candidate JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_stream_ice_candidates_session ON stream_ice_candidates(session_id, id);
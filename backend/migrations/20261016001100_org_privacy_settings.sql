@@ -0,0 +1,4 @@
+-- Per-org PII redaction applied to webhook payloads and data exports
+-- Shape: {"mask_emails": bool, "mask_wallets": bool, "gps_decimals": int | null}
+
+ALTER TABLE organizations ADD COLUMN IF NOT EXISTS privacy_settings JSONB NOT NULL DEFAULT '{}';
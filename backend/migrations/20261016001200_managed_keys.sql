@@ -0,0 +1,20 @@
+-- Versioned signing/encryption keys (JWT, webhook HMAC, column encryption, device CA)
+-- Key material is stored wrapped with AES-256-GCM under KEY_ENCRYPTION_KEY.
+
+CREATE TABLE IF NOT EXISTS managed_keys (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    purpose VARCHAR(32) NOT NULL, -- jwt, webhook_hmac, column_encryption, device_ca
+    version INTEGER NOT NULL,
+    algorithm VARCHAR(32) NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'active', -- active, retired, destroyed
+    wrapped_key BYTEA, -- NULL once destroyed
+    nonce BYTEA,
+    rotated_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    retired_at TIMESTAMPTZ,
+    verify_until TIMESTAMPTZ, -- retired keys keep verifying/decrypting until this; NULL = indefinitely
+    destroyed_at TIMESTAMPTZ,
+    UNIQUE (purpose, version)
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_managed_keys_active ON managed_keys(purpose) WHERE status = 'active';
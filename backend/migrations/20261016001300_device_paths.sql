@@ -0,0 +1,20 @@
+-- Named GPS paths recorded from telemetry, replayable as queued movement commands
+
+CREATE TABLE IF NOT EXISTS device_paths (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE, -- device the track was recorded from
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    points JSONB NOT NULL, -- [{latitude, longitude, altitude, recorded_at}]
+    point_count INTEGER NOT NULL,
+    distance_m DOUBLE PRECISION NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL,
+    ended_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_paths_user ON device_paths(user_id, created_at DESC);
+
+-- Commands generated by a replay keep their place in the sequence
+ALTER TABLE device_commands ADD COLUMN IF NOT EXISTS path_id UUID REFERENCES device_paths(id) ON DELETE SET NULL;
+ALTER TABLE device_commands ADD COLUMN IF NOT EXISTS sequence INTEGER;
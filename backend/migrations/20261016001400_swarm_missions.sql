@@ -0,0 +1,27 @@
+-- Fleet-level swarm missions decomposed into per-device assignments
+
+CREATE TABLE IF NOT EXISTS swarm_missions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    command VARCHAR(50) NOT NULL, -- survey
+    parameters JSONB NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'dispatched', -- dispatched, cancelled
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_swarm_missions_user ON swarm_missions(user_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS swarm_assignments (
+    mission_id UUID NOT NULL REFERENCES swarm_missions(id) ON DELETE CASCADE,
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    region JSONB NOT NULL, -- the device's exclusive sub-area
+    transit_altitude DOUBLE PRECISION,
+    start_offset_ms BIGINT NOT NULL DEFAULT 0,
+    command_count INTEGER NOT NULL,
+    estimated_duration_ms BIGINT NOT NULL,
+    PRIMARY KEY (mission_id, device_id)
+);
+
+-- Commands may be held until a scheduled start so swarm members don't cross paths
+ALTER TABLE device_commands ADD COLUMN IF NOT EXISTS swarm_mission_id UUID REFERENCES swarm_missions(id) ON DELETE SET NULL;
+ALTER TABLE device_commands ADD COLUMN IF NOT EXISTS not_before TIMESTAMPTZ;
@@ -0,0 +1,47 @@
+-- Signed firmware releases: org signing keys, verified artifacts and device install reports
+
+CREATE TABLE IF NOT EXISTS firmware_signing_keys (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    public_key TEXT NOT NULL, -- base64 Ed25519 public key
+    fingerprint VARCHAR(64) NOT NULL, -- sha256 hex of the raw public key
+    endorsement TEXT NOT NULL, -- platform device CA signature over the key
+    endorsed_by VARCHAR(50) NOT NULL, -- device CA kid, e.g. device_ca-v1
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    revoked_at TIMESTAMPTZ,
+    UNIQUE (org_id, fingerprint)
+);
+
+CREATE TABLE IF NOT EXISTS firmware_releases (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    device_type VARCHAR(20) NOT NULL,
+    version VARCHAR(32) NOT NULL,
+    sha256 VARCHAR(64) NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    signature TEXT NOT NULL, -- base64 detached Ed25519 signature over the artifact
+    signing_key_id UUID REFERENCES firmware_signing_keys(id) ON DELETE SET NULL,
+    verification_status VARCHAR(20) NOT NULL, -- verified, rejected
+    verification_error TEXT,
+    artifact BYTEA, -- only kept for verified uploads
+    uploaded_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_firmware_releases_verified_version
+    ON firmware_releases(org_id, device_type, version) WHERE verification_status = 'verified';
+CREATE INDEX IF NOT EXISTS idx_firmware_releases_org ON firmware_releases(org_id, created_at DESC);
+
+-- What each device reported after checking the signature chain and installing
+CREATE TABLE IF NOT EXISTS firmware_installations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    release_id UUID NOT NULL REFERENCES firmware_releases(id) ON DELETE CASCADE,
+    status VARCHAR(20) NOT NULL, -- installed, rejected, failed
+    reason TEXT,
+    reported_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_firmware_installations_device ON firmware_installations(device_id, reported_at DESC);
@@ -0,0 +1,24 @@
+-- Export compliance: allowlist overrides for geo-IP blocking.
+-- Blocked attempts themselves are recorded in audit_logs (action 'compliance.geo_blocked').
+
+CREATE TABLE IF NOT EXISTS geo_block_allowlist (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    ip VARCHAR(64),
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    email VARCHAR(255), -- contact for the review
+    reason TEXT NOT NULL,
+    blocked_audit_id BIGINT REFERENCES audit_logs(id) ON DELETE SET NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending', -- pending, approved, rejected
+    reviewed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    reviewed_at TIMESTAMPTZ,
+    expires_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (ip IS NOT NULL OR user_id IS NOT NULL)
+);
+
+CREATE INDEX IF NOT EXISTS idx_geo_block_allowlist_status ON geo_block_allowlist(status, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_geo_block_allowlist_ip ON geo_block_allowlist(ip) WHERE status = 'approved';
+CREATE INDEX IF NOT EXISTS idx_geo_block_allowlist_user ON geo_block_allowlist(user_id) WHERE status = 'approved';
+CREATE UNIQUE INDEX IF NOT EXISTS idx_geo_block_allowlist_appeal
+    ON geo_block_allowlist(blocked_audit_id) WHERE blocked_audit_id IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_audit_logs_action_time ON audit_logs(action, created_at DESC);
@@ -0,0 +1,34 @@
+-- Multi-leg missions whose legs run on different devices, handing off at rendezvous points
+
+CREATE TABLE IF NOT EXISTS fleet_missions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'awaiting_handoff', -- active, awaiting_handoff, completed, failed, cancelled
+    current_leg INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_fleet_missions_user ON fleet_missions(user_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS mission_legs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    mission_id UUID NOT NULL REFERENCES fleet_missions(id) ON DELETE CASCADE,
+    leg_index INTEGER NOT NULL,
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    target JSONB NOT NULL, -- where the leg ends; the next leg picks up here
+    pickup_actions JSONB NOT NULL DEFAULT '[]', -- run at the previous leg's target
+    actions JSONB NOT NULL DEFAULT '[]', -- run at this leg's target
+    speed REAL NOT NULL DEFAULT 0.5,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending', -- pending, dispatched, completed, failed, cancelled
+    readiness JSONB, -- latest readiness check report
+    command_count INTEGER NOT NULL DEFAULT 0,
+    dispatched_at TIMESTAMPTZ,
+    completed_at TIMESTAMPTZ,
+    UNIQUE (mission_id, leg_index)
+);
+
+ALTER TABLE device_commands ADD COLUMN IF NOT EXISTS mission_leg_id UUID REFERENCES mission_legs(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_device_commands_mission_leg ON device_commands(mission_leg_id) WHERE mission_leg_id IS NOT NULL;
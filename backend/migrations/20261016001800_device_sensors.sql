@@ -0,0 +1,19 @@
+-- Per-device sensor definitions; telemetry may only carry readings from declared, enabled sensors
+
+CREATE TABLE IF NOT EXISTS device_sensors (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    sensor_type VARCHAR(32) NOT NULL, -- matches SensorReading.sensor_type in telemetry
+    unit VARCHAR(16) NOT NULL,
+    sampling_rate_hz REAL NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (device_id, sensor_type)
+);
+
+-- Existing devices keep the temperature/humidity pair they have been reporting
+INSERT INTO device_sensors (device_id, sensor_type, unit, sampling_rate_hz)
+SELECT d.id, s.sensor_type, s.unit, 1.0
+FROM devices d CROSS JOIN (VALUES ('temperature', '°C'), ('humidity', '%')) AS s(sensor_type, unit)
+ON CONFLICT (device_id, sensor_type) DO NOTHING;
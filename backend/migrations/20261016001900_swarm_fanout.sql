@@ -0,0 +1,4 @@
+-- Direct fleet commands fanned out to many devices, with the per-device outcome report
+
+ALTER TABLE swarm_missions ADD COLUMN IF NOT EXISTS report JSONB;
+CREATE INDEX IF NOT EXISTS idx_devices_tags ON devices USING GIN ((metadata -> 'tags'));
@@ -0,0 +1,6 @@
+-- Per-device command transport
+
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS transport VARCHAR(20) NOT NULL DEFAULT 'long_poll'; -- mqtt, websocket, long_poll, simulated
+
+CREATE INDEX IF NOT EXISTS idx_device_commands_pending ON device_commands(device_id, created_at)
+    WHERE acked_at IS NULL;
@@ -0,0 +1,36 @@
+-- Command macros and the workflow promoting simulated missions/macros to real hardware
+
+CREATE TABLE IF NOT EXISTS command_macros (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    device_type VARCHAR(20) NOT NULL,
+    steps JSONB NOT NULL, -- [{command, parameters}]
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_command_macros_user ON command_macros(user_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS sim_promotions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    source_kind VARCHAR(20) NOT NULL, -- macro, fleet_mission
+    source_id UUID NOT NULL,
+    device_map JSONB NOT NULL, -- {simulated device id: production device id}
+    geofence JSONB, -- operating bounds every step must stay inside
+    status VARCHAR(20) NOT NULL DEFAULT 'pending_approval', -- invalid, pending_approval, approved, rejected, executed
+    checks JSONB NOT NULL, -- validation results against the production devices
+    diff JSONB NOT NULL, -- simulated vs production device and plan differences
+    reviewed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    reviewed_at TIMESTAMPTZ,
+    review_note TEXT,
+    executed_ref UUID, -- fleet mission created, when the source was a mission
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_sim_promotions_user ON sim_promotions(user_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_sim_promotions_pending ON sim_promotions(created_at) WHERE status = 'pending_approval';
+
+-- Commands queued by a macro run, so simulated runs can be checked before promotion
+ALTER TABLE device_commands ADD COLUMN IF NOT EXISTS macro_id UUID REFERENCES command_macros(id) ON DELETE SET NULL;
@@ -0,0 +1,23 @@
+-- User-supplied WASM modules run on incoming telemetry before it is stored
+
+CREATE TABLE IF NOT EXISTS telemetry_processors (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    description TEXT,
+    module BYTEA NOT NULL,
+    module_sha256 CHAR(64) NOT NULL,
+    module_size INTEGER NOT NULL,
+    device_ids UUID[], -- NULL runs on every device of the owner
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    invocations BIGINT NOT NULL DEFAULT 0,
+    consecutive_failures INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    last_run_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_telemetry_processors_user_enabled
+    ON telemetry_processors(user_id) WHERE enabled;
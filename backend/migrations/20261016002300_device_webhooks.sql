@@ -0,0 +1,101 @@
+-- Webhooks for device status changes and finished commands, with a delivery log.
+-- Triggers queue deliveries so every writer of devices.status / device_commands.status is covered.
+
+CREATE TABLE IF NOT EXISTS device_webhooks (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    description TEXT,
+    events TEXT[] NOT NULL, -- device.online, device.offline, device.maintenance, command.completed
+    device_ids UUID[], -- NULL covers every device of the owner
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_webhooks_user ON device_webhooks(user_id) WHERE enabled;
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    webhook_id UUID NOT NULL REFERENCES device_webhooks(id) ON DELETE CASCADE,
+    event VARCHAR(32) NOT NULL,
+    payload JSONB NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending', -- pending, delivered, failed
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_status_code INTEGER,
+    last_error TEXT,
+    delivered_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_due ON webhook_deliveries(next_attempt_at) WHERE status = 'pending';
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_webhook ON webhook_deliveries(webhook_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS webhook_delivery_attempts (
+    id BIGSERIAL PRIMARY KEY,
+    delivery_id UUID NOT NULL REFERENCES webhook_deliveries(id) ON DELETE CASCADE,
+    attempt INTEGER NOT NULL,
+    status_code INTEGER,
+    error TEXT,
+    duration_ms INTEGER NOT NULL,
+    attempted_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_delivery_attempts_delivery ON webhook_delivery_attempts(delivery_id);
+
+CREATE OR REPLACE FUNCTION queue_webhook_deliveries(owner UUID, device UUID, event TEXT, data JSONB)
+RETURNS VOID AS $$
+BEGIN
+    INSERT INTO webhook_deliveries (webhook_id, event, payload)
+    SELECT w.id, event,
+           jsonb_build_object('id', gen_random_uuid(), 'event', event, 'occurred_at', NOW(), 'data', data)
+    FROM device_webhooks w
+    WHERE w.user_id = owner AND w.enabled AND event = ANY(w.events)
+      AND (w.device_ids IS NULL OR device = ANY(w.device_ids));
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION device_status_webhooks() RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.status IS DISTINCT FROM OLD.status THEN
+        PERFORM queue_webhook_deliveries(NEW.user_id, NEW.id, 'device.' || NEW.status, jsonb_build_object(
+            'device_id', NEW.id,
+            'device_name', NEW.device_name,
+            'device_type', NEW.device_type,
+            'previous_status', OLD.status,
+            'status', NEW.status,
+            'last_seen', NEW.last_seen,
+            'latitude', NEW.last_latitude,
+            'longitude', NEW.last_longitude
+        ));
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION command_completed_webhooks() RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.status IS DISTINCT FROM OLD.status AND NEW.status IN ('succeeded', 'failed') THEN
+        PERFORM queue_webhook_deliveries(NEW.user_id, NEW.device_id, 'command.completed', jsonb_build_object(
+            'device_id', NEW.device_id,
+            'command_id', NEW.id,
+            'command', NEW.command,
+            'status', NEW.status,
+            'error', NEW.error,
+            'actual_duration_ms', NEW.actual_duration_ms,
+            'actual_battery_drain', NEW.actual_battery_drain,
+            'acked_at', NEW.acked_at
+        ));
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trg_device_status_webhooks ON devices;
+CREATE TRIGGER trg_device_status_webhooks AFTER UPDATE OF status ON devices
+    FOR EACH ROW EXECUTE FUNCTION device_status_webhooks();
+
+DROP TRIGGER IF EXISTS trg_command_completed_webhooks ON device_commands;
+CREATE TRIGGER trg_command_completed_webhooks AFTER UPDATE OF status ON device_commands
+    FOR EACH ROW EXECUTE FUNCTION command_completed_webhooks();
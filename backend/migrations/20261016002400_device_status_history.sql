@@ -0,0 +1,31 @@
+-- Every device status transition, for uptime and SLA reporting.
+-- Recorded by trigger so status changes from any code path are captured.
+
+CREATE TABLE IF NOT EXISTS device_status_history (
+    id BIGSERIAL PRIMARY KEY,
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    previous_status VARCHAR(20),
+    status VARCHAR(20) NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_status_history_device_time ON device_status_history(device_id, changed_at);
+
+CREATE OR REPLACE FUNCTION record_device_status() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' OR NEW.status IS DISTINCT FROM OLD.status THEN
+        INSERT INTO device_status_history (device_id, previous_status, status)
+        VALUES (NEW.id, CASE WHEN TG_OP = 'UPDATE' THEN OLD.status END, NEW.status);
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trg_record_device_status ON devices;
+CREATE TRIGGER trg_record_device_status AFTER INSERT OR UPDATE OF status ON devices
+    FOR EACH ROW EXECUTE FUNCTION record_device_status();
+
+-- History starts now for existing devices; earlier time is reported as unknown
+INSERT INTO device_status_history (device_id, status)
+SELECT id, status FROM devices
+WHERE NOT EXISTS (SELECT 1 FROM device_status_history h WHERE h.device_id = devices.id);
@@ -0,0 +1,83 @@
+-- Rule-based automations: a trigger feeding a graph of conditions and actions.
+-- Every edit creates a new immutable version; runs record the version they executed.
+
+CREATE TABLE IF NOT EXISTS automations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    current_version INTEGER NOT NULL DEFAULT 1,
+    trigger_kind VARCHAR(20) NOT NULL, -- telemetry, schedule, webhook, payment_completed (of the current version)
+    hook_token_hash CHAR(64) NOT NULL UNIQUE, -- sha256 of the inbound webhook token
+    last_triggered_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_automations_trigger ON automations(user_id, trigger_kind) WHERE enabled;
+
+CREATE TABLE IF NOT EXISTS automation_versions (
+    automation_id UUID NOT NULL REFERENCES automations(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    graph JSONB NOT NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (automation_id, version)
+);
+
+CREATE TABLE IF NOT EXISTS automation_runs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    automation_id UUID NOT NULL REFERENCES automations(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    trigger_kind VARCHAR(20) NOT NULL,
+    event JSONB NOT NULL,
+    dry_run BOOLEAN NOT NULL DEFAULT FALSE,
+    status VARCHAR(16) NOT NULL, -- succeeded, failed, skipped
+    steps JSONB NOT NULL DEFAULT '[]',
+    error TEXT,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_automation_runs_automation ON automation_runs(automation_id, started_at DESC);
+
+-- Switches automations can flip, readable by the user's tooling
+CREATE TABLE IF NOT EXISTS user_feature_flags (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    flag VARCHAR(64) NOT NULL,
+    enabled BOOLEAN NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, flag)
+);
+
+-- Events from other subsystems waiting to be matched against automations
+CREATE TABLE IF NOT EXISTS automation_events (
+    id BIGSERIAL PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind VARCHAR(20) NOT NULL,
+    payload JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    processed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_automation_events_pending ON automation_events(id) WHERE processed_at IS NULL;
+
+CREATE OR REPLACE FUNCTION payment_completed_automations() RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.status = 'completed' AND (TG_OP = 'INSERT' OR OLD.status IS DISTINCT FROM 'completed') THEN
+        INSERT INTO automation_events (user_id, kind, payload)
+        VALUES (NEW.user_id, 'payment_completed', jsonb_build_object(
+            'transaction_id', NEW.id,
+            'amount', NEW.amount,
+            'currency', NEW.currency,
+            'payment_method', NEW.payment_method,
+            'product_type', NEW.product_type
+        ));
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trg_payment_completed_automations ON transactions;
+CREATE TRIGGER trg_payment_completed_automations AFTER INSERT OR UPDATE OF status ON transactions
+    FOR EACH ROW EXECUTE FUNCTION payment_completed_automations();
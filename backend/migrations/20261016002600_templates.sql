@@ -0,0 +1,68 @@
+-- Template marketplace: automations, fleet missions and command macros published for others
+-- to import, with ratings, moderation and provenance.
+
+CREATE TABLE IF NOT EXISTS templates (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    publisher_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    kind VARCHAR(20) NOT NULL, -- automation, fleet_mission, macro
+    name VARCHAR(100) NOT NULL,
+    description TEXT,
+    content JSONB NOT NULL, -- with ${placeholder} markers
+    placeholders JSONB NOT NULL DEFAULT '[]',
+    content_sha256 CHAR(64) NOT NULL,
+    -- Provenance: what it was published from and the template that item was imported from
+    source_id UUID NOT NULL,
+    source_version INTEGER,
+    parent_template_id UUID REFERENCES templates(id) ON DELETE SET NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'published', -- pending_review, published, hidden, removed, withdrawn
+    moderation_flags TEXT[] NOT NULL DEFAULT '{}',
+    moderated_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    moderated_at TIMESTAMPTZ,
+    moderation_note TEXT,
+    rating_count INTEGER NOT NULL DEFAULT 0,
+    rating_total INTEGER NOT NULL DEFAULT 0,
+    import_count INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_templates_browse ON templates(kind, created_at DESC) WHERE status = 'published';
+CREATE INDEX IF NOT EXISTS idx_templates_publisher ON templates(publisher_id);
+
+-- Every import, so an item can be traced back to the template (and content) it came from
+CREATE TABLE IF NOT EXISTS template_imports (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    template_id UUID NOT NULL REFERENCES templates(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    target_kind VARCHAR(20) NOT NULL,
+    target_id UUID NOT NULL,
+    content_sha256 CHAR(64) NOT NULL,
+    imported_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_template_imports_user ON template_imports(user_id, imported_at DESC);
+CREATE INDEX IF NOT EXISTS idx_template_imports_target ON template_imports(target_id);
+
+CREATE TABLE IF NOT EXISTS template_ratings (
+    template_id UUID NOT NULL REFERENCES templates(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    stars SMALLINT NOT NULL CHECK (stars BETWEEN 1 AND 5),
+    review TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (template_id, user_id)
+);
+
+CREATE TABLE IF NOT EXISTS template_reports (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    template_id UUID NOT NULL REFERENCES templates(id) ON DELETE CASCADE,
+    reporter_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    reason TEXT NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'open', -- open, resolved
+    resolved_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    resolved_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (template_id, reporter_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_template_reports_open ON template_reports(template_id) WHERE status = 'open';
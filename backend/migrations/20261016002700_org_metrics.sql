@@ -0,0 +1,27 @@
+-- Org-defined metrics: formulas over telemetry fields rolled up per device and hour
+
+CREATE TABLE IF NOT EXISTS org_metrics (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    name VARCHAR(64) NOT NULL,
+    description TEXT,
+    unit VARCHAR(32),
+    formula TEXT NOT NULL,
+    -- End of the last hourly bucket the aggregation job has computed
+    computed_through TIMESTAMPTZ,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (org_id, name)
+);
+
+CREATE TABLE IF NOT EXISTS metric_values (
+    metric_id UUID NOT NULL REFERENCES org_metrics(id) ON DELETE CASCADE,
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    bucket_start TIMESTAMPTZ NOT NULL,
+    value DOUBLE PRECISION NOT NULL,
+    samples INTEGER NOT NULL,
+    PRIMARY KEY (metric_id, device_id, bucket_start)
+);
+
+CREATE INDEX IF NOT EXISTS idx_metric_values_bucket ON metric_values(metric_id, bucket_start DESC);
@@ -0,0 +1,24 @@
+-- Hourly telemetry rollups kept after raw samples expire
+
+CREATE TABLE IF NOT EXISTS telemetry_hourly (
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    bucket_start TIMESTAMPTZ NOT NULL,
+    samples INTEGER NOT NULL,
+    first_recorded_at TIMESTAMPTZ NOT NULL,
+    last_recorded_at TIMESTAMPTZ NOT NULL,
+    battery_avg DOUBLE PRECISION NOT NULL,
+    battery_min SMALLINT NOT NULL,
+    battery_max SMALLINT NOT NULL,
+    latitude_avg DOUBLE PRECISION NOT NULL,
+    longitude_avg DOUBLE PRECISION NOT NULL,
+    altitude_avg DOUBLE PRECISION,
+    cpu_temp_avg DOUBLE PRECISION,
+    signal_strength_avg DOUBLE PRECISION,
+    PRIMARY KEY (device_id, bucket_start)
+);
+
+CREATE INDEX IF NOT EXISTS idx_telemetry_hourly_bucket ON telemetry_hourly(bucket_start);
+
+-- Rollups and pruning scan raw telemetry by time across all devices
+CREATE INDEX IF NOT EXISTS idx_device_telemetry_recorded_at ON device_telemetry(recorded_at);
+CREATE INDEX IF NOT EXISTS idx_metric_values_bucket_start ON metric_values(bucket_start);
@@ -0,0 +1,17 @@
+-- Peripherals mounted on a device; some commands are only accepted while one is attached
+
+CREATE TABLE IF NOT EXISTS device_attachments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    attachment_type VARCHAR(16) NOT NULL, -- gripper, lidar, sprayer
+    name VARCHAR(64) NOT NULL,
+    model VARCHAR(64),
+    serial_number VARCHAR(64),
+    config JSONB NOT NULL DEFAULT '{}',
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (device_id, name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_attachments_device ON device_attachments(device_id);
@@ -0,0 +1,15 @@
+-- Calls to deprecated routes per consumer, flushed periodically from in-memory counters
+
+CREATE TABLE IF NOT EXISTS deprecated_route_usage (
+    method VARCHAR(8) NOT NULL,
+    path VARCHAR(255) NOT NULL, -- route pattern, e.g. /api/orgs/{org_id}/export
+    consumer VARCHAR(128) NOT NULL, -- user:<uuid> or ip:<addr>
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    user_agent VARCHAR(255),
+    calls BIGINT NOT NULL DEFAULT 0,
+    first_seen_at TIMESTAMPTZ NOT NULL,
+    last_seen_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (method, path, consumer)
+);
+
+CREATE INDEX IF NOT EXISTS idx_deprecated_route_usage_last_seen ON deprecated_route_usage(last_seen_at);
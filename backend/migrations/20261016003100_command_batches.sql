@@ -0,0 +1,5 @@
+-- Commands enqueued together through the batch endpoint share a batch id and run in sequence order
+
+ALTER TABLE device_commands ADD COLUMN IF NOT EXISTS batch_id UUID;
+
+CREATE INDEX IF NOT EXISTS idx_device_commands_batch ON device_commands(batch_id, sequence) WHERE batch_id IS NOT NULL;
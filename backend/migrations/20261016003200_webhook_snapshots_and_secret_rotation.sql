@@ -0,0 +1,16 @@
+-- What each webhook delivery attempt sent and received, and per-webhook signing secret rotation.
+-- During a rotation's grace period deliveries carry signatures under both the new and previous secret.
+
+ALTER TABLE webhook_delivery_attempts
+    ADD COLUMN IF NOT EXISTS manual BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN IF NOT EXISTS request_headers JSONB,
+    ADD COLUMN IF NOT EXISTS request_body TEXT,
+    ADD COLUMN IF NOT EXISTS response_headers JSONB,
+    ADD COLUMN IF NOT EXISTS response_body TEXT;
+
+ALTER TABLE device_webhooks
+    ADD COLUMN IF NOT EXISTS secret_version INTEGER NOT NULL DEFAULT 1,
+    ADD COLUMN IF NOT EXISTS previous_secret_version INTEGER,
+    ADD COLUMN IF NOT EXISTS previous_secret_expires_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_webhook_status ON webhook_deliveries(webhook_id, status, created_at DESC);
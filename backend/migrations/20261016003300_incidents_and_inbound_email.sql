@@ -0,0 +1,54 @@
+-- Incidents opened by critical telemetry alerts, the conversation on each, and inbound email.
+-- Alert emails carry a signed reply-to address; replies acknowledge the incident or add a message.
+
+ALTER TABLE email_outbox ADD COLUMN IF NOT EXISTS reply_to VARCHAR(255);
+
+CREATE TABLE IF NOT EXISTS incidents (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    processor_id UUID REFERENCES telemetry_processors(id) ON DELETE SET NULL,
+    severity VARCHAR(16) NOT NULL,
+    title VARCHAR(255) NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'open', -- open, acknowledged, resolved
+    -- Further alerts from the same source while the incident is unresolved
+    alert_count INTEGER NOT NULL DEFAULT 1,
+    last_alert_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    acknowledged_at TIMESTAMPTZ,
+    acknowledged_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    acknowledged_via VARCHAR(16), -- api, email
+    resolved_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- One unresolved incident per device and processor
+CREATE UNIQUE INDEX IF NOT EXISTS idx_incidents_unresolved_source
+    ON incidents(device_id, processor_id) WHERE status <> 'resolved';
+CREATE INDEX IF NOT EXISTS idx_incidents_user ON incidents(user_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS incident_messages (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    incident_id UUID NOT NULL REFERENCES incidents(id) ON DELETE CASCADE,
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    source VARCHAR(16) NOT NULL, -- api, email
+    from_address VARCHAR(255),
+    body TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_incident_messages_incident ON incident_messages(incident_id, created_at);
+
+-- Every inbound email webhook call, deduplicated by the provider's message id
+CREATE TABLE IF NOT EXISTS inbound_emails (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    provider VARCHAR(16) NOT NULL, -- mailgun, ses
+    message_id VARCHAR(255) NOT NULL,
+    sender VARCHAR(255) NOT NULL,
+    recipient VARCHAR(255) NOT NULL,
+    subject VARCHAR(255),
+    incident_id UUID REFERENCES incidents(id) ON DELETE SET NULL,
+    outcome VARCHAR(32) NOT NULL DEFAULT 'received', -- acknowledged, message, ignored, rejected
+    detail TEXT,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (provider, message_id)
+);
@@ -0,0 +1,72 @@
+-- Planned maintenance windows and on-call rotations, published as signed iCal feeds, with
+-- maintenance windows kept in step with a connected Google Calendar.
+
+CREATE TABLE IF NOT EXISTS maintenance_windows (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- NULL covers the whole fleet
+    device_id UUID REFERENCES devices(id) ON DELETE CASCADE,
+    title VARCHAR(255) NOT NULL,
+    notes TEXT,
+    starts_at TIMESTAMPTZ NOT NULL,
+    ends_at TIMESTAMPTZ NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'scheduled', -- scheduled, cancelled
+    -- iCal SEQUENCE, bumped on every change so calendar clients replace their copy
+    sequence INTEGER NOT NULL DEFAULT 0,
+    updated_via VARCHAR(16) NOT NULL DEFAULT 'api', -- api, google
+    google_event_id VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (ends_at > starts_at)
+);
+
+CREATE INDEX IF NOT EXISTS idx_maintenance_windows_user ON maintenance_windows(user_id, starts_at);
+
+-- Members take `shift_hours` shifts in turn, starting with the first member at `starts_at`
+CREATE TABLE IF NOT EXISTS on_call_rotations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    members TEXT[] NOT NULL,
+    shift_hours INTEGER NOT NULL,
+    starts_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_on_call_rotations_user ON on_call_rotations(user_id);
+
+-- Subscribable feeds. The URL is signed over (id, url_version); bumping the version revokes it.
+CREATE TABLE IF NOT EXISTS calendar_feeds (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind VARCHAR(16) NOT NULL, -- maintenance, on_call
+    -- Narrow a maintenance feed to one device, or an on-call feed to one rotation
+    device_id UUID REFERENCES devices(id) ON DELETE CASCADE,
+    rotation_id UUID REFERENCES on_call_rotations(id) ON DELETE CASCADE,
+    url_version INTEGER NOT NULL DEFAULT 1,
+    last_fetched_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_calendar_feeds_user ON calendar_feeds(user_id);
+
+-- One Google Calendar per user, watched through a push notification channel
+CREATE TABLE IF NOT EXISTS google_calendar_connections (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL UNIQUE REFERENCES users(id) ON DELETE CASCADE,
+    calendar_id VARCHAR(255) NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending', -- pending, active, error
+    oauth_state VARCHAR(64) UNIQUE,
+    oauth_state_expires_at TIMESTAMPTZ,
+    -- Sealed with the column encryption key
+    refresh_token TEXT,
+    channel_id UUID UNIQUE,
+    channel_token_hash VARCHAR(64),
+    resource_id VARCHAR(255),
+    channel_expires_at TIMESTAMPTZ,
+    sync_token TEXT,
+    last_synced_at TIMESTAMPTZ,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,14 @@
+-- Sign-In with Ethereum (EIP-4361). Nonces are issued server-side and consumed by the first
+-- successful sign-in, so a signed message cannot be replayed.
+
+CREATE TABLE IF NOT EXISTS wallet_login_nonces (
+    nonce VARCHAR(64) PRIMARY KEY,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_wallet_login_nonces_expires ON wallet_login_nonces (expires_at);
+
+-- A wallet signs in to exactly one account
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_wallet_address
+    ON users (LOWER(wallet_address)) WHERE wallet_address IS NOT NULL;
@@ -0,0 +1,27 @@
+-- Payment provider webhooks settle pending transactions; a completed transaction unlocks the
+-- purchased product for its buyer.
+
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS completed_at TIMESTAMPTZ;
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS failure_reason TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_transactions_payment ON transactions (payment_method, payment_id);
+
+-- Every webhook event handled, so redelivered events are acknowledged without effect
+CREATE TABLE IF NOT EXISTS payment_provider_events (
+    provider VARCHAR(20) NOT NULL, -- stripe
+    event_id VARCHAR(255) NOT NULL,
+    event_type VARCHAR(100) NOT NULL,
+    transaction_id UUID REFERENCES transactions(id) ON DELETE SET NULL,
+    outcome VARCHAR(20) NOT NULL, -- completed, failed, ignored, mismatch
+    received_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (provider, event_id)
+);
+
+CREATE TABLE IF NOT EXISTS product_entitlements (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    product_type VARCHAR(50) NOT NULL,
+    transaction_id UUID REFERENCES transactions(id) ON DELETE SET NULL,
+    granted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, product_type)
+);
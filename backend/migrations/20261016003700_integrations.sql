@@ -0,0 +1,122 @@
+-- No-code integrations (Zapier, IFTTT and the like): long-lived API tokens, polling triggers and
+-- REST hook subscriptions. Each trigger's items are built by one SQL function, so a polled item
+-- and a pushed one always have the same shape.
+
+CREATE TABLE IF NOT EXISTS integration_tokens (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    token_prefix VARCHAR(12) NOT NULL, -- shown so users can tell tokens apart
+    last_used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_integration_tokens_user ON integration_tokens(user_id);
+
+-- Revoking a token unsubscribes the hooks made with it
+CREATE TABLE IF NOT EXISTS integration_hooks (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_id UUID NOT NULL REFERENCES integration_tokens(id) ON DELETE CASCADE,
+    event VARCHAR(32) NOT NULL, -- new_device, new_alert, command_completed
+    target_url TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_integration_hooks_user_event ON integration_hooks(user_id, event);
+
+-- Pending pushes; rows are removed once delivered or given up on
+CREATE TABLE IF NOT EXISTS integration_hook_deliveries (
+    id BIGSERIAL PRIMARY KEY,
+    hook_id UUID NOT NULL REFERENCES integration_hooks(id) ON DELETE CASCADE,
+    payload JSONB NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_integration_hook_deliveries_due ON integration_hook_deliveries(next_attempt_at);
+
+CREATE OR REPLACE FUNCTION integration_device_item(d devices) RETURNS JSONB AS $$
+    SELECT jsonb_build_object(
+        'id', d.id,
+        'device_name', d.device_name,
+        'device_type', d.device_type,
+        'firmware_version', d.firmware_version,
+        'status', d.status,
+        'last_seen', d.last_seen,
+        'created_at', d.created_at
+    );
+$$ LANGUAGE sql STABLE;
+
+CREATE OR REPLACE FUNCTION integration_alert_item(n notifications) RETURNS JSONB AS $$
+    SELECT jsonb_build_object(
+        'id', n.id,
+        'title', n.title,
+        'message', n.body,
+        'severity', n.data->>'severity',
+        'device_id', n.data->>'device_id',
+        'device_name', (SELECT device_name FROM devices WHERE id::text = n.data->>'device_id'),
+        'created_at', n.created_at
+    );
+$$ LANGUAGE sql STABLE;
+
+CREATE OR REPLACE FUNCTION integration_command_item(c device_commands) RETURNS JSONB AS $$
+    SELECT jsonb_build_object(
+        'id', c.id,
+        'device_id', c.device_id,
+        'device_name', (SELECT device_name FROM devices WHERE id = c.device_id),
+        'command', c.command,
+        'parameters', c.parameters,
+        'status', c.status,
+        'error', c.error,
+        'actual_duration_ms', c.actual_duration_ms,
+        'created_at', c.created_at,
+        'completed_at', c.acked_at
+    );
+$$ LANGUAGE sql STABLE;
+
+CREATE OR REPLACE FUNCTION queue_integration_hooks(owner UUID, hook_event TEXT, item JSONB)
+RETURNS VOID AS $$
+BEGIN
+    INSERT INTO integration_hook_deliveries (hook_id, payload)
+    SELECT h.id, item FROM integration_hooks h WHERE h.user_id = owner AND h.event = hook_event;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION new_device_integration_hooks() RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM queue_integration_hooks(NEW.user_id, 'new_device', integration_device_item(NEW));
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION new_alert_integration_hooks() RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM queue_integration_hooks(NEW.user_id, 'new_alert', integration_alert_item(NEW));
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION command_completed_integration_hooks() RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.status IS DISTINCT FROM OLD.status AND NEW.status IN ('succeeded', 'failed') THEN
+        PERFORM queue_integration_hooks(NEW.user_id, 'command_completed', integration_command_item(NEW));
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trg_new_device_integration_hooks ON devices;
+CREATE TRIGGER trg_new_device_integration_hooks AFTER INSERT ON devices
+    FOR EACH ROW EXECUTE FUNCTION new_device_integration_hooks();
+
+DROP TRIGGER IF EXISTS trg_new_alert_integration_hooks ON notifications;
+CREATE TRIGGER trg_new_alert_integration_hooks AFTER INSERT ON notifications
+    FOR EACH ROW WHEN (NEW.kind = 'telemetry_alert') EXECUTE FUNCTION new_alert_integration_hooks();
+
+DROP TRIGGER IF EXISTS trg_command_completed_integration_hooks ON device_commands;
+CREATE TRIGGER trg_command_completed_integration_hooks AFTER UPDATE OF status ON device_commands
+    FOR EACH ROW EXECUTE FUNCTION command_completed_integration_hooks();
@@ -0,0 +1,52 @@
+-- Declarative fleet configuration (PUT /api/robotics/config): named device groups, geofences and
+-- threshold alert rules, managed by name alongside device tags and automations.
+
+CREATE TABLE IF NOT EXISTS fleets (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    description TEXT,
+    device_ids UUID[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_fleets_devices ON fleets USING GIN (device_ids);
+
+-- Devices reporting a position outside the bounds raise an alert
+CREATE TABLE IF NOT EXISTS geofences (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    fleet_id UUID REFERENCES fleets(id), -- NULL covers every device of the owner
+    bounds JSONB NOT NULL,
+    severity VARCHAR(16) NOT NULL DEFAULT 'warning',
+    cooldown_minutes INTEGER NOT NULL DEFAULT 15,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, name)
+);
+
+-- A telemetry condition, evaluated on every stored sample of the covered devices
+CREATE TABLE IF NOT EXISTS alert_rules (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    fleet_id UUID REFERENCES fleets(id), -- NULL covers every device of the owner
+    condition JSONB NOT NULL,
+    severity VARCHAR(16) NOT NULL DEFAULT 'warning',
+    cooldown_minutes INTEGER NOT NULL DEFAULT 15,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, name)
+);
+
+-- When a geofence or alert rule last alerted about a device, for its cooldown
+CREATE TABLE IF NOT EXISTS fleet_alert_state (
+    source_id UUID NOT NULL, -- geofence or alert rule
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    last_alert_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (source_id, device_id)
+);
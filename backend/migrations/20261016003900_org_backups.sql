@@ -0,0 +1,29 @@
+-- Logical org backups: a JSON document of the members' devices, fleet configuration and
+-- automations plus the org's metrics and privacy settings, stored in object storage.
+-- Raw telemetry, device credentials and secrets are never included.
+
+CREATE TABLE IF NOT EXISTS org_backups (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    object_key TEXT NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    sha256 CHAR(64) NOT NULL,
+    counts JSONB NOT NULL DEFAULT '{}', -- records per kind, for listing without a download
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_org_backups_org ON org_backups(org_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS org_restores (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    backup_id UUID REFERENCES org_backups(id) ON DELETE SET NULL, -- NULL for an uploaded document
+    source_org_id UUID, -- org the document was taken from, possibly in another environment
+    requested_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    strategy VARCHAR(16) NOT NULL, -- skip, overwrite, fail
+    report JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_org_restores_org ON org_restores(org_id, created_at DESC);
@@ -0,0 +1,16 @@
+-- Idempotency-Key support (POST /api/blockchain/payment): the first response for a key is
+-- stored and replayed to retries of the same request until the key expires.
+
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    idempotency_key VARCHAR(255) NOT NULL,
+    request_hash CHAR(64) NOT NULL, -- method, path and body the key was first used with
+    response_status INTEGER, -- NULL while the first request is in flight
+    response_body BYTEA,
+    content_type TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (user_id, idempotency_key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_idempotency_keys_expires ON idempotency_keys(expires_at);
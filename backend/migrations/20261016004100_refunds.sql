@@ -0,0 +1,20 @@
+-- Refunds of completed transactions, through the payment provider or, by an admin, recorded
+-- as made outside it. A transaction refunded in full loses the product it unlocked.
+
+CREATE TABLE IF NOT EXISTS refunds (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    transaction_id UUID NOT NULL REFERENCES transactions(id) ON DELETE CASCADE,
+    amount DOUBLE PRECISION NOT NULL CHECK (amount > 0),
+    currency VARCHAR(10) NOT NULL,
+    provider VARCHAR(20), -- stripe, razorpay; NULL for manual refunds
+    provider_refund_id VARCHAR(255),
+    status VARCHAR(20) NOT NULL DEFAULT 'requested', -- requested, pending, succeeded, failed
+    manual BOOLEAN NOT NULL DEFAULT FALSE,
+    reason TEXT,
+    failure_reason TEXT,
+    requested_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_refunds_transaction ON refunds(transaction_id);
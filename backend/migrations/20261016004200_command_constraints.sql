@@ -0,0 +1,25 @@
+-- Cross-device dependency constraints checked before a command is dispatched, e.g. a drone
+-- may only take off once its rover is docked
+
+CREATE TABLE IF NOT EXISTS command_constraints (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(64) NOT NULL,
+    -- The command being guarded
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    command VARCHAR(50) NOT NULL,
+    -- The device whose state it waits on
+    depends_on_device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    condition VARCHAR(20) NOT NULL, -- status, last_command, metadata
+    field VARCHAR(64), -- metadata key, for metadata conditions
+    expected VARCHAR(100) NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, name),
+    CHECK (device_id <> depends_on_device_id),
+    CHECK ((condition = 'metadata') = (field IS NOT NULL))
+);
+
+CREATE INDEX IF NOT EXISTS idx_command_constraints_guard ON command_constraints(device_id, command) WHERE enabled;
+CREATE INDEX IF NOT EXISTS idx_command_constraints_dependency ON command_constraints(depends_on_device_id);
@@ -0,0 +1,71 @@
+-- Subscription billing: plans, one live subscription per user, and the invoices that start,
+-- renew and prorate it. Invoices are paid through Stripe PaymentIntents; the payment webhook
+-- settles them, and a live subscription makes the account premium.
+
+CREATE TABLE IF NOT EXISTS subscription_plans (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    code VARCHAR(50) NOT NULL UNIQUE,
+    name VARCHAR(100) NOT NULL,
+    amount DOUBLE PRECISION NOT NULL CHECK (amount > 0),
+    currency VARCHAR(10) NOT NULL,
+    billing_interval VARCHAR(10) NOT NULL, -- month, year
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO subscription_plans (code, name, amount, currency, billing_interval) VALUES
+    ('pro_monthly', 'Pro (monthly)', 9.99, 'usd', 'month'),
+    ('pro_yearly', 'Pro (yearly)', 99.0, 'usd', 'year')
+ON CONFLICT (code) DO NOTHING;
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS stripe_customer_id VARCHAR(255);
+
+CREATE TABLE IF NOT EXISTS subscriptions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    plan_id UUID NOT NULL REFERENCES subscription_plans(id),
+    status VARCHAR(20) NOT NULL DEFAULT 'incomplete', -- incomplete, active, past_due, canceled, incomplete_expired
+    current_period_start TIMESTAMPTZ,
+    current_period_end TIMESTAMPTZ,
+    cancel_at_period_end BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Left over from downgrades, taken off the next renewal
+    credit_balance DOUBLE PRECISION NOT NULL DEFAULT 0,
+    -- Saved by the first payment for charging renewals off-session
+    stripe_payment_method_id VARCHAR(255),
+    canceled_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_subscriptions_live ON subscriptions(user_id)
+    WHERE status IN ('incomplete', 'active', 'past_due');
+CREATE INDEX IF NOT EXISTS idx_subscriptions_renewal ON subscriptions(current_period_end) WHERE status = 'active';
+
+CREATE TABLE IF NOT EXISTS subscription_invoices (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    subscription_id UUID NOT NULL REFERENCES subscriptions(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind VARCHAR(20) NOT NULL, -- initial, renewal, proration
+    amount DOUBLE PRECISION NOT NULL CHECK (amount >= 0),
+    currency VARCHAR(10) NOT NULL,
+    period_start TIMESTAMPTZ NOT NULL,
+    period_end TIMESTAMPTZ NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'open', -- open, paid, void, uncollectible
+    attempt_count INTEGER NOT NULL DEFAULT 0,
+    -- When the renewal job next charges it; NULL while a charge is in flight or settled
+    next_attempt_at TIMESTAMPTZ,
+    paid_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_subscription_invoices_renewal
+    ON subscription_invoices(subscription_id, period_start)
+    WHERE kind = 'renewal';
+CREATE INDEX IF NOT EXISTS idx_subscription_invoices_due ON subscription_invoices(next_attempt_at)
+    WHERE status = 'open' AND next_attempt_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_subscription_invoices_user ON subscription_invoices(user_id, created_at DESC);
+
+-- Each charge attempt is a transaction; this ties it back to the invoice it pays
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS subscription_invoice_id UUID
+    REFERENCES subscription_invoices(id) ON DELETE SET NULL;
@@ -0,0 +1,32 @@
+-- Open data: users who opt in can publish anonymized telemetry datasets, which are listed
+-- in a public catalog and downloaded straight from object storage.
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS open_data_opt_in_at TIMESTAMPTZ;
+
+CREATE TABLE IF NOT EXISTS open_datasets (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    slug VARCHAR(120) NOT NULL UNIQUE,
+    title VARCHAR(200) NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    license VARCHAR(20) NOT NULL,
+    device_ids UUID[] NOT NULL,
+    device_types VARCHAR(50)[] NOT NULL DEFAULT '{}',
+    period_start TIMESTAMPTZ NOT NULL,
+    period_end TIMESTAMPTZ NOT NULL,
+    location_decimals SMALLINT NOT NULL CHECK (location_decimals BETWEEN 0 AND 4),
+    status VARCHAR(20) NOT NULL DEFAULT 'building', -- building, published, failed, withdrawn
+    row_count BIGINT,
+    size_bytes BIGINT,
+    object_key TEXT,
+    sha256 VARCHAR(64),
+    failure_reason TEXT,
+    published_at TIMESTAMPTZ,
+    withdrawn_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (period_start < period_end)
+);
+
+CREATE INDEX IF NOT EXISTS idx_open_datasets_published ON open_datasets(published_at DESC) WHERE status = 'published';
+CREATE INDEX IF NOT EXISTS idx_open_datasets_user ON open_datasets(user_id, created_at DESC);
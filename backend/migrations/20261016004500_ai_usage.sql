@@ -0,0 +1,17 @@
+-- AI spend per request, summed per calendar month against the user's budget. Requests are
+-- routed to a cheaper model when little budget is left; the decision is kept with the usage.
+
+CREATE TABLE IF NOT EXISTS ai_usage (
+    id BIGSERIAL PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    requested_model VARCHAR(100) NOT NULL,
+    model VARCHAR(100) NOT NULL,
+    routing_reason VARCHAR(20) NOT NULL, -- requested, low_budget
+    prompt_tokens INTEGER NOT NULL,
+    completion_tokens INTEGER NOT NULL,
+    estimated_cost_usd DOUBLE PRECISION NOT NULL,
+    cost_usd DOUBLE PRECISION NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_ai_usage_user_time ON ai_usage(user_id, created_at);
@@ -0,0 +1,9 @@
+-- On-chain confirmation tracking for crypto transactions: the watcher records the block the
+-- transaction was mined in and how deep it is, and completes it at the required depth.
+
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS confirmations INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS block_number BIGINT;
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS chain_checked_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_transactions_awaiting_chain ON transactions(chain_checked_at NULLS FIRST)
+    WHERE status = 'pending' AND blockchain_tx_hash IS NOT NULL;
@@ -0,0 +1,42 @@
+-- Org-defined geographic regions. Devices are assigned to every region of their owner's orgs
+-- whose polygon contains their last reported position; each region may have a team that
+-- receives the alerts of devices inside it.
+
+CREATE TABLE IF NOT EXISTS regions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    description TEXT,
+    -- Vertices as [{"latitude": .., "longitude": ..}], implicitly closed
+    polygon JSONB NOT NULL,
+    -- Bounding box of the polygon, the prefilter before the exact containment test
+    min_lat DOUBLE PRECISION NOT NULL,
+    max_lat DOUBLE PRECISION NOT NULL,
+    min_lng DOUBLE PRECISION NOT NULL,
+    max_lng DOUBLE PRECISION NOT NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (org_id, name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_regions_bounds ON regions(org_id, min_lat, max_lat);
+
+CREATE TABLE IF NOT EXISTS device_regions (
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    region_id UUID NOT NULL REFERENCES regions(id) ON DELETE CASCADE,
+    entered_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (device_id, region_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_regions_region ON device_regions(region_id);
+
+-- The regional team: notified of alerts raised by devices in the region
+CREATE TABLE IF NOT EXISTS region_members (
+    region_id UUID NOT NULL REFERENCES regions(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (region_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_region_members_user ON region_members(user_id);
@@ -0,0 +1,12 @@
+-- Multi-chain crypto: the chain a wallet signs in on and the chain a transaction was sent on.
+-- Transactions recorded before chains were tracked were all sent on Ethereum mainnet.
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS wallet_chain_id BIGINT;
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS chain_id BIGINT;
+
+UPDATE users SET wallet_chain_id = 1 WHERE wallet_address IS NOT NULL AND wallet_chain_id IS NULL;
+UPDATE transactions SET chain_id = 1 WHERE blockchain_tx_hash IS NOT NULL AND chain_id IS NULL;
+
+DROP INDEX IF EXISTS idx_transactions_awaiting_chain;
+CREATE INDEX IF NOT EXISTS idx_transactions_awaiting_chain ON transactions(chain_id, chain_checked_at NULLS FIRST)
+    WHERE status = 'pending' AND blockchain_tx_hash IS NOT NULL;
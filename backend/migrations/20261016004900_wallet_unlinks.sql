@@ -0,0 +1,13 @@
+-- Wallets removed from accounts. Within the configured cooldown neither the account nor the
+-- address may link a wallet again, so a wallet cannot be hopped between accounts quickly.
+
+CREATE TABLE IF NOT EXISTS wallet_unlinks (
+    id BIGSERIAL PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    address VARCHAR(42) NOT NULL,
+    chain_id BIGINT,
+    unlinked_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_wallet_unlinks_user ON wallet_unlinks(user_id, unlinked_at DESC);
+CREATE INDEX IF NOT EXISTS idx_wallet_unlinks_address ON wallet_unlinks(LOWER(address), unlinked_at DESC);
@@ -0,0 +1,57 @@
+-- Support tickets: a threaded conversation between a user and support staff, optionally about
+-- a device, incident or transaction. SLA due times are fixed from the user's support tier when
+-- the ticket is opened.
+
+CREATE TABLE IF NOT EXISTS support_tickets (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    subject VARCHAR(200) NOT NULL,
+    category VARCHAR(32) NOT NULL DEFAULT 'general', -- general, device, billing, account, incident
+    priority VARCHAR(16) NOT NULL DEFAULT 'normal', -- low, normal, high, urgent
+    status VARCHAR(24) NOT NULL DEFAULT 'open', -- open, waiting_on_customer, resolved, closed
+    support_tier VARCHAR(16) NOT NULL, -- standard, priority
+    device_id UUID REFERENCES devices(id) ON DELETE SET NULL,
+    incident_id UUID REFERENCES incidents(id) ON DELETE SET NULL,
+    transaction_id UUID REFERENCES transactions(id) ON DELETE SET NULL,
+    assigned_to UUID REFERENCES users(id) ON DELETE SET NULL,
+    first_response_due_at TIMESTAMPTZ NOT NULL,
+    resolution_due_at TIMESTAMPTZ NOT NULL,
+    first_responded_at TIMESTAMPTZ,
+    resolved_at TIMESTAMPTZ,
+    -- Set by the SLA job once a missed target has been reported
+    first_response_breached_at TIMESTAMPTZ,
+    resolution_breached_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_support_tickets_user ON support_tickets(user_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_support_tickets_queue ON support_tickets(status, resolution_due_at)
+    WHERE status IN ('open', 'waiting_on_customer');
+CREATE INDEX IF NOT EXISTS idx_support_tickets_assignee ON support_tickets(assigned_to) WHERE assigned_to IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS support_ticket_messages (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    ticket_id UUID NOT NULL REFERENCES support_tickets(id) ON DELETE CASCADE,
+    author_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    from_staff BOOLEAN NOT NULL DEFAULT FALSE,
+    body TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_support_ticket_messages_ticket ON support_ticket_messages(ticket_id, created_at);
+
+-- Files are kept in object storage under support/<ticket>/<attachment>
+CREATE TABLE IF NOT EXISTS support_ticket_attachments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    ticket_id UUID NOT NULL REFERENCES support_tickets(id) ON DELETE CASCADE,
+    message_id UUID NOT NULL REFERENCES support_ticket_messages(id) ON DELETE CASCADE,
+    uploaded_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    file_name VARCHAR(255) NOT NULL,
+    content_type VARCHAR(100) NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    object_key VARCHAR(512) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_support_ticket_attachments_ticket ON support_ticket_attachments(ticket_id, created_at);
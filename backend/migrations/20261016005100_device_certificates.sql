@@ -0,0 +1,11 @@
+-- Device ownership certificates: an ERC-721 token per device, minted to the owner's wallet.
+-- The token id is the device id read as an integer, kept in decimal.
+
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS nft_chain_id BIGINT;
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS nft_contract VARCHAR(42);
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS nft_token_id VARCHAR(78);
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS nft_mint_tx_hash VARCHAR(66);
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS nft_minted_at TIMESTAMPTZ;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_devices_nft_token ON devices(nft_chain_id, LOWER(nft_contract), nft_token_id)
+    WHERE nft_token_id IS NOT NULL;
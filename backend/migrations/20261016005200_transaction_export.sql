@@ -0,0 +1,3 @@
+-- Transaction exports page through a user's history by (created_at, id)
+
+CREATE INDEX IF NOT EXISTS idx_transactions_user_created ON transactions(user_id, created_at, id);
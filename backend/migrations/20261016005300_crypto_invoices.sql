@@ -0,0 +1,37 @@
+-- Crypto payment invoices: an exact token amount to pay to the deposit wallet before the invoice
+-- expires. Open invoices on a token never share an amount, so a transfer of that amount to the
+-- deposit wallet identifies the invoice it pays.
+
+CREATE TABLE IF NOT EXISTS crypto_invoices (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    transaction_id UUID NOT NULL REFERENCES transactions(id) ON DELETE CASCADE,
+    chain_id BIGINT NOT NULL,
+    token_contract VARCHAR(42) NOT NULL,
+    deposit_address VARCHAR(42) NOT NULL,
+    -- In whole tokens, and in the token's smallest unit as a decimal integer
+    amount VARCHAR(80) NOT NULL,
+    raw_amount VARCHAR(78) NOT NULL,
+    symbol VARCHAR(20) NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'open', -- open, paid, expired
+    payer_address VARCHAR(42),
+    tx_hash VARCHAR(66),
+    log_index BIGINT,
+    expires_at TIMESTAMPTZ NOT NULL,
+    paid_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_crypto_invoices_open_amount
+    ON crypto_invoices(chain_id, token_contract, raw_amount) WHERE status = 'open';
+CREATE UNIQUE INDEX IF NOT EXISTS idx_crypto_invoices_payment ON crypto_invoices(chain_id, tx_hash, log_index)
+    WHERE tx_hash IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_crypto_invoices_user ON crypto_invoices(user_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_crypto_invoices_expiry ON crypto_invoices(expires_at) WHERE status = 'open';
+
+-- Last block scanned for deposits, per chain
+CREATE TABLE IF NOT EXISTS crypto_invoice_scans (
+    chain_id BIGINT PRIMARY KEY,
+    last_block BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
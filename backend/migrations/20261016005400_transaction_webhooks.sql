@@ -0,0 +1,41 @@
+-- Webhook events for transactions: transaction.pending when one is created, and
+-- transaction.completed / transaction.failed when it settles. Transaction events concern no
+-- device, so a webhook's device filter does not hold them back.
+
+CREATE OR REPLACE FUNCTION queue_webhook_deliveries(owner UUID, device UUID, event TEXT, data JSONB)
+RETURNS VOID AS $$
+BEGIN
+    INSERT INTO webhook_deliveries (webhook_id, event, payload)
+    SELECT w.id, event,
+           jsonb_build_object('id', gen_random_uuid(), 'event', event, 'occurred_at', NOW(), 'data', data)
+    FROM device_webhooks w
+    WHERE w.user_id = owner AND w.enabled AND event = ANY(w.events)
+      AND (w.device_ids IS NULL OR device IS NULL OR device = ANY(w.device_ids));
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION transaction_status_webhooks() RETURNS TRIGGER AS $$
+DECLARE
+    previous_status TEXT := CASE WHEN TG_OP = 'UPDATE' THEN OLD.status END;
+BEGIN
+    IF NEW.status IS DISTINCT FROM previous_status AND NEW.status IN ('pending', 'completed', 'failed') THEN
+        PERFORM queue_webhook_deliveries(NEW.user_id, NULL, 'transaction.' || NEW.status, jsonb_build_object(
+            'transaction_id', NEW.id,
+            'previous_status', previous_status,
+            'status', NEW.status,
+            'amount', NEW.amount,
+            'currency', NEW.currency,
+            'payment_method', NEW.payment_method,
+            'product_type', NEW.product_type,
+            'chain_id', NEW.chain_id,
+            'blockchain_tx_hash', NEW.blockchain_tx_hash,
+            'failure_reason', NEW.failure_reason
+        ));
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trg_transaction_status_webhooks ON transactions;
+CREATE TRIGGER trg_transaction_status_webhooks AFTER INSERT OR UPDATE OF status ON transactions
+    FOR EACH ROW EXECUTE FUNCTION transaction_status_webhooks();
@@ -0,0 +1,50 @@
+-- Escrow between platform users: the buyer's tokens are held by the relayer account until the
+-- seller fulfills, then paid out to the seller (or back to the buyer). Every state change is
+-- kept in escrow_events.
+
+CREATE TABLE IF NOT EXISTS escrows (
+    id UUID PRIMARY KEY,
+    buyer_id UUID NOT NULL REFERENCES users(id) ON DELETE RESTRICT,
+    seller_id UUID NOT NULL REFERENCES users(id) ON DELETE RESTRICT,
+    description TEXT NOT NULL,
+    -- A device the seller hands over; fulfillment requires the buyer to own it
+    device_id UUID REFERENCES devices(id) ON DELETE SET NULL,
+    chain_id BIGINT NOT NULL,
+    amount VARCHAR(80) NOT NULL,
+    symbol VARCHAR(20) NOT NULL,
+    -- awaiting_funding, funding, funded, fulfilled, disputed, released, refunded, cancelled
+    status VARCHAR(20) NOT NULL DEFAULT 'awaiting_funding',
+    funding_transaction_id UUID REFERENCES transactions(id) ON DELETE SET NULL,
+    payout_transaction_id UUID REFERENCES transactions(id) ON DELETE SET NULL,
+    dispute_reason TEXT,
+    disputed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    funded_at TIMESTAMPTZ,
+    fulfilled_at TIMESTAMPTZ,
+    -- Released to the seller then unless the buyer releases or disputes first
+    auto_release_at TIMESTAMPTZ,
+    settled_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (buyer_id <> seller_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_escrows_buyer ON escrows(buyer_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_escrows_seller ON escrows(seller_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_escrows_funding ON escrows(funding_transaction_id);
+CREATE INDEX IF NOT EXISTS idx_escrows_payout ON escrows(payout_transaction_id);
+CREATE INDEX IF NOT EXISTS idx_escrows_auto_release ON escrows(auto_release_at) WHERE status = 'fulfilled';
+CREATE INDEX IF NOT EXISTS idx_escrows_disputed ON escrows(updated_at) WHERE status = 'disputed';
+
+CREATE TABLE IF NOT EXISTS escrow_events (
+    id BIGSERIAL PRIMARY KEY,
+    escrow_id UUID NOT NULL REFERENCES escrows(id) ON DELETE CASCADE,
+    action VARCHAR(32) NOT NULL,
+    from_status VARCHAR(20), -- NULL for the escrow's creation
+    to_status VARCHAR(20) NOT NULL,
+    -- NULL for the system
+    actor_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    note TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_escrow_events_escrow ON escrow_events(escrow_id, id);
@@ -0,0 +1,4 @@
+-- Primary ENS name of each linked wallet, cached from a reverse lookup verified by forward
+-- resolution; NULL when the wallet has none
+ALTER TABLE users ADD COLUMN IF NOT EXISTS ens_name VARCHAR(255);
+ALTER TABLE users ADD COLUMN IF NOT EXISTS ens_resolved_at TIMESTAMPTZ;
@@ -0,0 +1,37 @@
+-- Transfer events of each chain's token and device certificate contracts, indexed in the
+-- background so queries need no RPC calls. Only blocks with the chain's required
+-- confirmations are indexed, so rows are final.
+
+CREATE TABLE IF NOT EXISTS onchain_events (
+    id BIGSERIAL PRIMARY KEY,
+    chain_id BIGINT NOT NULL,
+    contract_address VARCHAR(42) NOT NULL,
+    -- token_transfer, device_minted, device_transferred, device_burned
+    event VARCHAR(32) NOT NULL,
+    block_number BIGINT NOT NULL,
+    tx_hash VARCHAR(66) NOT NULL,
+    log_index BIGINT NOT NULL,
+    from_address VARCHAR(42) NOT NULL,
+    to_address VARCHAR(42) NOT NULL,
+    -- Token transfers: the value in base units
+    amount VARCHAR(78),
+    -- Certificate events: the token id, and the device it certifies when registered here
+    token_id VARCHAR(78),
+    device_id UUID REFERENCES devices(id) ON DELETE SET NULL,
+    indexed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (chain_id, tx_hash, log_index)
+);
+
+CREATE INDEX IF NOT EXISTS idx_onchain_events_contract ON onchain_events(chain_id, contract_address, block_number);
+CREATE INDEX IF NOT EXISTS idx_onchain_events_from ON onchain_events(from_address);
+CREATE INDEX IF NOT EXISTS idx_onchain_events_to ON onchain_events(to_address);
+CREATE INDEX IF NOT EXISTS idx_onchain_events_device ON onchain_events(device_id) WHERE device_id IS NOT NULL;
+
+-- The last block indexed per contract
+CREATE TABLE IF NOT EXISTS onchain_index_cursors (
+    chain_id BIGINT NOT NULL,
+    contract_address VARCHAR(42) NOT NULL,
+    last_block BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (chain_id, contract_address)
+);
@@ -0,0 +1,10 @@
+-- Admin payment reconciliation: staff flag transactions whose records disagree with the
+-- provider's, and list transactions across all users newest first.
+
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS discrepancy_note TEXT;
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS discrepancy_flagged_by UUID REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS discrepancy_flagged_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_transactions_created ON transactions(created_at, id);
+CREATE INDEX IF NOT EXISTS idx_transactions_discrepancies ON transactions(discrepancy_flagged_at)
+    WHERE discrepancy_note IS NOT NULL;
@@ -0,0 +1,80 @@
+-- Payouts of platform credits. Credits users earn (e.g. from leasing out devices) are entries
+-- in credit_entries, whose sum is the balance; a payout request takes its amount off the
+-- balance and is put back when the payout is rejected, cancelled or fails. Requests need a
+-- verified identity, are approved by an admin, then sent by bank transfer, Stripe Connect or
+-- on chain. Every state change is kept in payout_events.
+
+CREATE TABLE IF NOT EXISTS kyc_verifications (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    -- pending, verified, rejected
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',
+    legal_name VARCHAR(200) NOT NULL,
+    -- ISO 3166-1 alpha-2
+    country CHAR(2) NOT NULL,
+    -- passport, national_id, driving_license
+    document_type VARCHAR(32) NOT NULL,
+    -- The identity provider's reference for the checked document; the document is not stored
+    document_reference VARCHAR(200) NOT NULL,
+    rejection_reason TEXT,
+    reviewed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    reviewed_at TIMESTAMPTZ,
+    submitted_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_kyc_verifications_pending ON kyc_verifications(submitted_at) WHERE status = 'pending';
+
+CREATE TABLE IF NOT EXISTS payouts (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE RESTRICT,
+    amount DOUBLE PRECISION NOT NULL CHECK (amount > 0),
+    currency VARCHAR(10) NOT NULL,
+    -- bank, stripe, crypto
+    method VARCHAR(20) NOT NULL,
+    -- Bank account reference, Stripe connected account (acct_...) or wallet address
+    destination VARCHAR(200) NOT NULL,
+    -- requested, approved, processing, paid, failed, rejected, cancelled
+    status VARCHAR(20) NOT NULL DEFAULT 'requested',
+    -- Bank transfer reference or Stripe transfer id once paid
+    provider_reference VARCHAR(200),
+    -- The on-chain transfer of a crypto payout
+    transaction_id UUID REFERENCES transactions(id) ON DELETE SET NULL,
+    failure_reason TEXT,
+    reviewed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_payouts_user ON payouts(user_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_payouts_status ON payouts(status, created_at);
+CREATE INDEX IF NOT EXISTS idx_payouts_transaction ON payouts(transaction_id);
+
+CREATE TABLE IF NOT EXISTS payout_events (
+    id BIGSERIAL PRIMARY KEY,
+    payout_id UUID NOT NULL REFERENCES payouts(id) ON DELETE CASCADE,
+    action VARCHAR(32) NOT NULL,
+    from_status VARCHAR(20), -- NULL for the request
+    to_status VARCHAR(20) NOT NULL,
+    -- NULL for the system
+    actor_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    note TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_payout_events_payout ON payout_events(payout_id, id);
+
+CREATE TABLE IF NOT EXISTS credit_entries (
+    id BIGSERIAL PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- Positive when earned or put back, negative when paid out
+    amount DOUBLE PRECISION NOT NULL CHECK (amount <> 0),
+    currency VARCHAR(10) NOT NULL,
+    -- What the entry is for, e.g. device_lease, adjustment, payout, payout_reversal
+    source VARCHAR(32) NOT NULL,
+    payout_id UUID REFERENCES payouts(id) ON DELETE SET NULL,
+    note TEXT,
+    -- The admin who made an adjustment
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_credit_entries_user ON credit_entries(user_id, id DESC);
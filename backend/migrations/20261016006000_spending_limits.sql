@@ -0,0 +1,36 @@
+-- Spend limits and payment screening. Payments over a user's daily or monthly limit are refused;
+-- payments matching a fraud rule are held as a payment review for an admin instead of going
+-- through. Limits default to SPEND_LIMIT_DAILY_USD / SPEND_LIMIT_MONTHLY_USD and may be
+-- overridden per user.
+
+CREATE TABLE IF NOT EXISTS spending_limits (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    -- In USD; NULL for the platform default, 0 for no limit
+    daily_limit DOUBLE PRECISION CHECK (daily_limit >= 0),
+    monthly_limit DOUBLE PRECISION CHECK (monthly_limit >= 0),
+    updated_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS payment_reviews (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- card, crypto_invoice, token_transfer, escrow_funding
+    kind VARCHAR(32) NOT NULL,
+    amount DOUBLE PRECISION NOT NULL,
+    currency VARCHAR(10) NOT NULL,
+    amount_usd DOUBLE PRECISION,
+    -- ISO 3166-1 alpha-2 the attempt came from, when the edge reported one
+    country CHAR(2),
+    -- [{"rule": "rapid_retries" | "failed_cards" | "geo_mismatch", ...}] for each rule the attempt matched
+    flags JSONB NOT NULL,
+    -- open, approved, rejected
+    status VARCHAR(20) NOT NULL DEFAULT 'open',
+    review_note TEXT,
+    reviewed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    reviewed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_payment_reviews_user ON payment_reviews(user_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_payment_reviews_open ON payment_reviews(created_at) WHERE status = 'open';
@@ -0,0 +1,144 @@
+-- Double-entry ledger alongside transactions and credit entries. Every movement of money the
+-- platform is answerable for is a journal entry whose lines, one per account, sum to zero:
+-- debits positive, credits negative. Card and crypto payments debit cash and credit revenue,
+-- refunds debit refunds and credit cash, credits users earn are liabilities to them, held in
+-- payouts_pending while a payout is open and taken out of cash once it is paid. Token transfers
+-- and escrows move users' own tokens and are not posted.
+
+CREATE TABLE IF NOT EXISTS accounts (
+    id BIGSERIAL PRIMARY KEY,
+    -- e.g. cash:stripe, revenue:premium_features, refunds, credits:<user id>, payouts_pending
+    code VARCHAR(200) NOT NULL,
+    -- asset, liability, revenue, expense
+    kind VARCHAR(20) NOT NULL,
+    currency VARCHAR(10) NOT NULL,
+    -- The user a credits account belongs to
+    user_id UUID REFERENCES users(id) ON DELETE RESTRICT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (code, currency)
+);
+
+CREATE INDEX IF NOT EXISTS idx_accounts_user ON accounts(user_id) WHERE user_id IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS journal_entries (
+    id BIGSERIAL PRIMARY KEY,
+    -- The event posted, e.g. transaction:<id>:completed, refund:<id>, credit_entry:<id>;
+    -- posting the same event again is a no-op
+    reference VARCHAR(200) NOT NULL UNIQUE,
+    description TEXT NOT NULL,
+    transaction_id UUID REFERENCES transactions(id) ON DELETE SET NULL,
+    payout_id UUID REFERENCES payouts(id) ON DELETE SET NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS journal_lines (
+    id BIGSERIAL PRIMARY KEY,
+    entry_id BIGINT NOT NULL REFERENCES journal_entries(id) ON DELETE RESTRICT,
+    account_id BIGINT NOT NULL REFERENCES accounts(id) ON DELETE RESTRICT,
+    -- Debit when positive, credit when negative
+    amount DOUBLE PRECISION NOT NULL CHECK (amount <> 0)
+);
+
+CREATE INDEX IF NOT EXISTS idx_journal_lines_entry ON journal_lines(entry_id);
+CREATE INDEX IF NOT EXISTS idx_journal_lines_account ON journal_lines(account_id, entry_id DESC);
+
+-- Checked when the inserting transaction commits, once all of an entry's lines are in
+CREATE OR REPLACE FUNCTION journal_entry_balanced() RETURNS TRIGGER AS $$
+BEGIN
+    IF ABS((SELECT COALESCE(SUM(amount), 0) FROM journal_lines WHERE entry_id = NEW.entry_id)) > 0.000001 THEN
+        RAISE EXCEPTION 'journal entry % does not balance', NEW.entry_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trg_journal_entry_balanced ON journal_lines;
+CREATE CONSTRAINT TRIGGER trg_journal_entry_balanced AFTER INSERT ON journal_lines
+    DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW EXECUTE FUNCTION journal_entry_balanced();
+
+-- Backfill what happened before the ledger. Accounts first, then one entry per event, then its
+-- two lines: (reference, debit account, credit account, amount).
+
+CREATE TEMPORARY TABLE ledger_backfill (
+    reference VARCHAR(200) NOT NULL,
+    description TEXT NOT NULL,
+    transaction_id UUID,
+    payout_id UUID,
+    created_by UUID,
+    created_at TIMESTAMPTZ NOT NULL,
+    currency VARCHAR(10) NOT NULL,
+    debit_code VARCHAR(200) NOT NULL,
+    debit_kind VARCHAR(20) NOT NULL,
+    debit_user UUID,
+    credit_code VARCHAR(200) NOT NULL,
+    credit_kind VARCHAR(20) NOT NULL,
+    credit_user UUID,
+    amount DOUBLE PRECISION NOT NULL
+) ON COMMIT DROP;
+
+INSERT INTO ledger_backfill
+SELECT 'transaction:' || t.id || ':completed', 'Payment for ' || t.product_type, t.id, NULL, NULL,
+       COALESCE(t.completed_at, t.created_at), t.currency,
+       'cash:' || t.payment_method, 'asset', NULL, 'revenue:' || t.product_type, 'revenue', NULL, t.amount
+FROM transactions t
+WHERE t.status IN ('completed', 'partially_refunded', 'refunded') AND t.amount > 0
+  AND t.product_type NOT IN ('token_transfer', 'escrow_funding', 'escrow_payout', 'credit_payout');
+
+INSERT INTO ledger_backfill
+SELECT 'refund:' || r.id, 'Refund for ' || t.product_type, t.id, NULL, r.requested_by, r.created_at, t.currency,
+       'refunds', 'expense', NULL, 'cash:' || t.payment_method, 'asset', NULL, r.amount
+FROM refunds r JOIN transactions t ON t.id = r.transaction_id
+WHERE r.status <> 'failed';
+
+-- Credits earned or taken back
+INSERT INTO ledger_backfill
+SELECT 'credit_entry:' || c.id, 'Credits: ' || c.source, NULL, c.payout_id, c.created_by, c.created_at, c.currency,
+       CASE WHEN c.amount > 0 THEN 'credits_issued' ELSE 'credits:' || c.user_id END,
+       CASE WHEN c.amount > 0 THEN 'expense' ELSE 'liability' END,
+       CASE WHEN c.amount > 0 THEN NULL ELSE c.user_id END,
+       CASE WHEN c.amount > 0 THEN 'credits:' || c.user_id ELSE 'credits_issued' END,
+       CASE WHEN c.amount > 0 THEN 'liability' ELSE 'expense' END,
+       CASE WHEN c.amount > 0 THEN c.user_id END,
+       ABS(c.amount)
+FROM credit_entries c
+WHERE c.source NOT IN ('payout', 'payout_reversal');
+
+-- Credits held for a payout, and put back when it did not go out
+INSERT INTO ledger_backfill
+SELECT 'credit_entry:' || c.id, 'Credits: ' || c.source, NULL, c.payout_id, c.created_by, c.created_at, c.currency,
+       CASE WHEN c.source = 'payout' THEN 'credits:' || c.user_id ELSE 'payouts_pending' END, 'liability',
+       CASE WHEN c.source = 'payout' THEN c.user_id END,
+       CASE WHEN c.source = 'payout' THEN 'payouts_pending' ELSE 'credits:' || c.user_id END, 'liability',
+       CASE WHEN c.source = 'payout' THEN NULL ELSE c.user_id END,
+       ABS(c.amount)
+FROM credit_entries c
+WHERE c.source IN ('payout', 'payout_reversal');
+
+INSERT INTO ledger_backfill
+SELECT 'payout:' || p.id || ':paid', 'Payout by ' || p.method, p.transaction_id, p.id, p.reviewed_by, p.updated_at,
+       p.currency, 'payouts_pending', 'liability', NULL, 'cash:' || p.method, 'asset', NULL, p.amount
+FROM payouts p
+WHERE p.status = 'paid';
+
+INSERT INTO accounts (code, kind, currency, user_id)
+SELECT DISTINCT debit_code, debit_kind, currency, debit_user FROM ledger_backfill
+UNION
+SELECT DISTINCT credit_code, credit_kind, currency, credit_user FROM ledger_backfill
+ON CONFLICT (code, currency) DO NOTHING;
+
+INSERT INTO journal_entries (reference, description, transaction_id, payout_id, created_by, created_at)
+SELECT reference, description, transaction_id, payout_id, created_by, created_at FROM ledger_backfill
+ON CONFLICT (reference) DO NOTHING;
+
+INSERT INTO journal_lines (entry_id, account_id, amount)
+SELECT e.id, a.id, b.amount
+FROM ledger_backfill b
+JOIN journal_entries e ON e.reference = b.reference
+JOIN accounts a ON a.code = b.debit_code AND a.currency = b.currency
+UNION ALL
+SELECT e.id, a.id, -b.amount
+FROM ledger_backfill b
+JOIN journal_entries e ON e.reference = b.reference
+JOIN accounts a ON a.code = b.credit_code AND a.currency = b.currency;
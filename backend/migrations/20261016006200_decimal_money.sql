@@ -0,0 +1,31 @@
+-- Money as exact decimals. Amounts were DOUBLE PRECISION, so sums of many payments drifted by
+-- fractions of a cent; they are NUMERIC now and read as rust_decimal::Decimal. Token amounts
+-- keep every decimal the chain has. A double converts to the shortest decimal that reads back
+-- as the same double, which is the amount that was written.
+
+ALTER TABLE transactions ALTER COLUMN amount TYPE NUMERIC USING amount::NUMERIC;
+ALTER TABLE refunds ALTER COLUMN amount TYPE NUMERIC USING amount::NUMERIC;
+
+ALTER TABLE subscription_plans ALTER COLUMN amount TYPE NUMERIC USING amount::NUMERIC;
+ALTER TABLE subscriptions ALTER COLUMN credit_balance TYPE NUMERIC USING credit_balance::NUMERIC;
+ALTER TABLE subscription_invoices ALTER COLUMN amount TYPE NUMERIC USING amount::NUMERIC;
+
+ALTER TABLE credit_entries ALTER COLUMN amount TYPE NUMERIC USING amount::NUMERIC;
+ALTER TABLE payouts ALTER COLUMN amount TYPE NUMERIC USING amount::NUMERIC;
+
+ALTER TABLE spending_limits ALTER COLUMN daily_limit TYPE NUMERIC USING daily_limit::NUMERIC;
+ALTER TABLE spending_limits ALTER COLUMN monthly_limit TYPE NUMERIC USING monthly_limit::NUMERIC;
+ALTER TABLE payment_reviews ALTER COLUMN amount TYPE NUMERIC USING amount::NUMERIC;
+ALTER TABLE payment_reviews ALTER COLUMN amount_usd TYPE NUMERIC USING amount_usd::NUMERIC;
+
+ALTER TABLE journal_lines ALTER COLUMN amount TYPE NUMERIC USING amount::NUMERIC;
+
+-- Exact amounts balance exactly
+CREATE OR REPLACE FUNCTION journal_entry_balanced() RETURNS TRIGGER AS $$
+BEGIN
+    IF (SELECT COALESCE(SUM(amount), 0) FROM journal_lines WHERE entry_id = NEW.entry_id) <> 0 THEN
+        RAISE EXCEPTION 'journal entry % does not balance', NEW.entry_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
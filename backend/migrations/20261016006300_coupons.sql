@@ -0,0 +1,42 @@
+-- Coupon codes taking a percentage or a fixed amount off a product's price. A coupon may be
+-- limited in how many payments use it, in total and per user, and may expire. A redemption is
+-- kept per payment the coupon was applied to; one whose payment failed no longer counts.
+
+CREATE TABLE IF NOT EXISTS coupons (
+    id UUID PRIMARY KEY,
+    -- Upper-case, matched case-insensitively
+    code VARCHAR(40) NOT NULL UNIQUE,
+    description TEXT,
+    -- percentage, fixed
+    kind VARCHAR(20) NOT NULL CHECK (kind IN ('percentage', 'fixed')),
+    -- Percent off for percentage coupons, an amount of `currency` for fixed ones
+    value NUMERIC NOT NULL CHECK (value > 0),
+    currency VARCHAR(10),
+    -- Product types the coupon applies to; NULL for all
+    product_types TEXT[],
+    -- NULL for no limit
+    max_redemptions INTEGER CHECK (max_redemptions > 0),
+    max_per_user INTEGER CHECK (max_per_user > 0),
+    expires_at TIMESTAMPTZ,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (kind <> 'percentage' OR value <= 100),
+    CHECK (kind <> 'fixed' OR currency IS NOT NULL)
+);
+
+CREATE TABLE IF NOT EXISTS coupon_redemptions (
+    id BIGSERIAL PRIMARY KEY,
+    coupon_id UUID NOT NULL REFERENCES coupons(id) ON DELETE RESTRICT,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    transaction_id UUID NOT NULL UNIQUE REFERENCES transactions(id) ON DELETE CASCADE,
+    discount NUMERIC NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_coupon_redemptions_coupon ON coupon_redemptions(coupon_id, user_id);
+
+-- What a payment was discounted by; `amount` is what was charged after it
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS coupon_id UUID REFERENCES coupons(id) ON DELETE SET NULL;
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS discount_amount NUMERIC NOT NULL DEFAULT 0;
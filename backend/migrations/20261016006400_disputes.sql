@@ -0,0 +1,37 @@
+-- Chargebacks. A dispute the card network opens against a payment is recorded against its
+-- transaction, and what the payment unlocked is frozen until the dispute closes: a won dispute
+-- thaws it, a lost one takes it back and marks the transaction charged_back.
+
+CREATE TABLE IF NOT EXISTS disputes (
+    id UUID PRIMARY KEY,
+    transaction_id UUID NOT NULL REFERENCES transactions(id) ON DELETE RESTRICT,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    provider VARCHAR(20) NOT NULL,
+    -- e.g. dp_... for Stripe
+    provider_dispute_id VARCHAR(255) NOT NULL,
+    amount NUMERIC NOT NULL,
+    currency VARCHAR(10) NOT NULL,
+    -- The cardholder's reason as the provider reports it, e.g. fraudulent, product_not_received
+    reason VARCHAR(64),
+    -- The provider's own status, e.g. needs_response, under_review
+    provider_status VARCHAR(32),
+    -- open, evidence_submitted, won, lost
+    status VARCHAR(20) NOT NULL DEFAULT 'open',
+    evidence_due_by TIMESTAMPTZ,
+    evidence_note TEXT,
+    evidence_submitted_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    evidence_submitted_at TIMESTAMPTZ,
+    resolution_note TEXT,
+    resolved_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    resolved_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (provider, provider_dispute_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_disputes_transaction ON disputes(transaction_id);
+CREATE INDEX IF NOT EXISTS idx_disputes_open ON disputes(evidence_due_by NULLS LAST)
+    WHERE status IN ('open', 'evidence_submitted');
+
+-- Set while a dispute over the purchase is open; a frozen entitlement grants nothing
+ALTER TABLE product_entitlements ADD COLUMN IF NOT EXISTS frozen_at TIMESTAMPTZ;
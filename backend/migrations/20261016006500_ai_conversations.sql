@@ -0,0 +1,32 @@
+-- AI chat history. A chat request naming a conversation is answered with the conversation's
+-- earlier messages as context; one naming none starts a new conversation.
+
+CREATE TABLE IF NOT EXISTS conversations (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- From the first user message
+    title VARCHAR(100) NOT NULL,
+    -- The model that answered last
+    model VARCHAR(100),
+    -- The latest system message sent, reused by requests that send none
+    system_prompt TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_conversations_user ON conversations(user_id, updated_at DESC);
+
+CREATE TABLE IF NOT EXISTS messages (
+    -- Messages of one request share a timestamp, so the id keeps them in order
+    id BIGSERIAL PRIMARY KEY,
+    conversation_id UUID NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+    role VARCHAR(20) NOT NULL, -- user, assistant
+    content TEXT NOT NULL,
+    -- Set on assistant messages
+    model VARCHAR(100),
+    prompt_tokens INTEGER,
+    completion_tokens INTEGER,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_messages_conversation ON messages(conversation_id, id);
@@ -0,0 +1,3 @@
+-- Which provider answered; requests before providers were selectable all went to OpenAI
+
+ALTER TABLE ai_usage ADD COLUMN IF NOT EXISTS provider VARCHAR(20) NOT NULL DEFAULT 'openai';
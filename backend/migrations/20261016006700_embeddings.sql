@@ -0,0 +1,22 @@
+-- Documents users index for semantic search, with their embeddings (pgvector). The column has
+-- no fixed dimension since the embedding model is configurable; searches only compare vectors
+-- made by the same model.
+
+CREATE EXTENSION IF NOT EXISTS vector;
+
+CREATE TABLE IF NOT EXISTS embeddings (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    collection VARCHAR(100) NOT NULL DEFAULT 'default',
+    -- Where the document came from, e.g. a file name or URL
+    source VARCHAR(500),
+    content TEXT NOT NULL,
+    metadata JSONB NOT NULL DEFAULT '{}',
+    -- provider:model, e.g. openai:text-embedding-ada-002
+    model VARCHAR(150) NOT NULL,
+    dimensions INTEGER NOT NULL,
+    embedding vector NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_embeddings_user_collection ON embeddings(user_id, collection, model);
@@ -0,0 +1,19 @@
+-- Documentation users upload for retrieval-augmented answers (manuals, datasheets). Each file is
+-- split into overlapping chunks stored as embeddings; deleting the file deletes its chunks.
+
+CREATE TABLE IF NOT EXISTS ai_documents (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    collection VARCHAR(100) NOT NULL DEFAULT 'default',
+    file_name VARCHAR(255) NOT NULL,
+    content_type VARCHAR(100) NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    chunk_count INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_ai_documents_user ON ai_documents(user_id, created_at DESC);
+
+ALTER TABLE embeddings ADD COLUMN IF NOT EXISTS document_id UUID REFERENCES ai_documents(id) ON DELETE CASCADE;
+ALTER TABLE embeddings ADD COLUMN IF NOT EXISTS chunk_index INTEGER;
+CREATE INDEX IF NOT EXISTS idx_embeddings_document ON embeddings(document_id);
@@ -0,0 +1,13 @@
+-- AI health summaries of device telemetry, generated at most once per device per hour
+
+CREATE TABLE IF NOT EXISTS device_insights (
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    hour_start TIMESTAMPTZ NOT NULL,
+    health VARCHAR(20) NOT NULL,
+    summary TEXT NOT NULL,
+    recommendations JSONB NOT NULL DEFAULT '[]',
+    model VARCHAR(100) NOT NULL,
+    provider VARCHAR(50) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (device_id, hour_start)
+);
@@ -0,0 +1,34 @@
+-- Fine-tuning of chat models on users' own examples. Training files are uploaded to the
+-- provider; a job trains a model from one, and once it succeeds the model can be chatted with by
+-- the user who trained it.
+
+CREATE TABLE IF NOT EXISTS ai_training_files (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    provider VARCHAR(20) NOT NULL,
+    provider_file_id VARCHAR(100) NOT NULL,
+    file_name VARCHAR(255) NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    example_count INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_ai_training_files_user ON ai_training_files(user_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS ai_fine_tunes (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    training_file_id UUID NOT NULL REFERENCES ai_training_files(id) ON DELETE CASCADE,
+    provider VARCHAR(20) NOT NULL,
+    provider_job_id VARCHAR(100) NOT NULL,
+    base_model VARCHAR(100) NOT NULL,
+    suffix VARCHAR(40),
+    status VARCHAR(30) NOT NULL,
+    fine_tuned_model VARCHAR(255) UNIQUE,
+    trained_tokens BIGINT,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_ai_fine_tunes_user ON ai_fine_tunes(user_id, created_at DESC);
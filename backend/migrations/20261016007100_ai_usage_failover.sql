@@ -0,0 +1,3 @@
+-- The route a request failed over from, as provider:model; NULL when the first route answered
+
+ALTER TABLE ai_usage ADD COLUMN IF NOT EXISTS failover_from VARCHAR(150);
@@ -0,0 +1,172 @@
+//! Registry of the EVM chains payments, balances and wallets may use.
+//!
+//! Each known chain is configured from the environment by its upper-cased key, e.g.
+//! `POLYGON_RPC_URL`, `POLYGON_TOKEN_CONTRACT`, `POLYGON_NFT_CONTRACT` and
+//! `POLYGON_CONFIRMATIONS`. A chain without an RPC URL is still recognized (wallets may sign in
+//! on it) but cannot be queried. Ethereum falls back to `WEB3_PROVIDER_URL`, `CONTRACT_ADDRESS`,
+//! `DEVICE_NFT_CONTRACT` and `CRYPTO_REQUIRED_CONFIRMATIONS`.
+//! `ENABLED_CHAINS` limits the registry to a comma-separated list of keys.
+
+use serde::{Deserialize, Serialize};
+
+pub const ETHEREUM_CHAIN_ID: u64 = 1;
+
+struct KnownChain {
+    key: &'static str,
+    chain_id: u64,
+    name: &'static str,
+    native_symbol: &'static str,
+    confirmations: u32,
+    testnet: bool,
+}
+
+const KNOWN_CHAINS: &[KnownChain] = &[
+    KnownChain {
+        key: "ethereum",
+        chain_id: 1,
+        name: "Ethereum",
+        native_symbol: "ETH",
+        confirmations: 12,
+        testnet: false,
+    },
+    KnownChain {
+        key: "polygon",
+        chain_id: 137,
+        name: "Polygon",
+        native_symbol: "POL",
+        confirmations: 64,
+        testnet: false,
+    },
+    KnownChain {
+        key: "base",
+        chain_id: 8453,
+        name: "Base",
+        native_symbol: "ETH",
+        confirmations: 10,
+        testnet: false,
+    },
+    KnownChain {
+        key: "sepolia",
+        chain_id: 11155111,
+        name: "Sepolia",
+        native_symbol: "ETH",
+        confirmations: 3,
+        testnet: true,
+    },
+    KnownChain {
+        key: "polygon-amoy",
+        chain_id: 80002,
+        name: "Polygon Amoy",
+        native_symbol: "POL",
+        confirmations: 5,
+        testnet: true,
+    },
+    KnownChain {
+        key: "base-sepolia",
+        chain_id: 84532,
+        name: "Base Sepolia",
+        native_symbol: "ETH",
+        confirmations: 3,
+        testnet: true,
+    },
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainConfig {
+    pub chain_id: u64,
+    pub key: String,
+    pub name: String,
+    pub native_symbol: String,
+    pub testnet: bool,
+    /// JSON-RPC endpoint; may embed an API key, so it is never serialized
+    #[serde(skip_serializing, default)]
+    pub rpc_url: Option<String>,
+    /// ERC-20 token balances are read from
+    pub token_contract: Option<String>,
+    /// ERC-721 contract device ownership certificates are minted in
+    pub nft_contract: Option<String>,
+    /// Blocks that must confirm a payment on this chain before it completes
+    pub required_confirmations: u32,
+}
+
+impl ChainConfig {
+    /// Whether the chain can be queried
+    pub fn has_provider(&self) -> bool {
+        self.rpc_url.as_deref().is_some_and(|url| !url.is_empty() && !url.contains("YOUR_KEY"))
+    }
+}
+
+/// Build the registry, reading variables through `var`
+pub fn registry(var: impl Fn(&str) -> Option<String>) -> Vec<ChainConfig> {
+    let var = |name: &str| var(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    let enabled: Option<Vec<String>> = var("ENABLED_CHAINS")
+        .map(|list| list.split(',').map(|k| k.trim().to_lowercase()).filter(|k| !k.is_empty()).collect());
+
+    KNOWN_CHAINS
+        .iter()
+        .filter(|known| enabled.as_ref().is_none_or(|keys| keys.iter().any(|k| k == known.key)))
+        .map(|known| {
+            let prefix = known.key.to_uppercase().replace('-', "_");
+            let legacy = |name: &str| if known.chain_id == ETHEREUM_CHAIN_ID { var(name) } else { None };
+            ChainConfig {
+                chain_id: known.chain_id,
+                key: known.key.to_string(),
+                name: known.name.to_string(),
+                native_symbol: known.native_symbol.to_string(),
+                testnet: known.testnet,
+                rpc_url: var(&format!("{}_RPC_URL", prefix)).or_else(|| legacy("WEB3_PROVIDER_URL")),
+                token_contract: var(&format!("{}_TOKEN_CONTRACT", prefix)).or_else(|| legacy("CONTRACT_ADDRESS")),
+                nft_contract: var(&format!("{}_NFT_CONTRACT", prefix)).or_else(|| legacy("DEVICE_NFT_CONTRACT")),
+                required_confirmations: var(&format!("{}_CONFIRMATIONS", prefix))
+                    .or_else(|| legacy("CRYPTO_REQUIRED_CONFIRMATIONS"))
+                    .and_then(|n| n.parse().ok())
+                    .filter(|n| *n > 0)
+                    .unwrap_or(known.confirmations),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn build(vars: &[(&str, &str)]) -> Vec<ChainConfig> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        registry(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_registry() {
+        let chains = build(&[
+            ("WEB3_PROVIDER_URL", "https://eth.example"),
+            ("CRYPTO_REQUIRED_CONFIRMATIONS", "20"),
+            ("POLYGON_AMOY_RPC_URL", "https://amoy.example"),
+            ("BASE_CONFIRMATIONS", "not-a-number"),
+            ("DEVICE_NFT_CONTRACT", "0x00000000000000000000000000000000000000aa"),
+            ("BASE_NFT_CONTRACT", "0x00000000000000000000000000000000000000bb"),
+        ]);
+        assert_eq!(chains.len(), KNOWN_CHAINS.len());
+        let chain = |id: u64| chains.iter().find(|c| c.chain_id == id).unwrap();
+
+        // Ethereum takes the legacy single-chain settings
+        assert_eq!(chain(1).rpc_url.as_deref(), Some("https://eth.example"));
+        assert_eq!(chain(1).required_confirmations, 20);
+        assert!(chain(80002).has_provider());
+        assert!(!chain(137).has_provider());
+        assert_eq!(chain(8453).required_confirmations, 10);
+        assert!(chain(1).nft_contract.as_deref().is_some_and(|c| c.ends_with("aa")));
+        assert!(chain(8453).nft_contract.as_deref().is_some_and(|c| c.ends_with("bb")));
+        assert_eq!(chain(137).nft_contract, None);
+        // RPC URLs can carry API keys
+        assert!(!serde_json::to_string(chain(1)).unwrap().contains("eth.example"));
+    }
+
+    #[test]
+    fn test_enabled_chains() {
+        let chains = build(&[("ENABLED_CHAINS", "Ethereum, base")]);
+        let keys: Vec<&str> = chains.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(keys, ["ethereum", "base"]);
+    }
+}
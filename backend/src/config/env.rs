@@ -1,15 +1,16 @@
-use serde::{Deserialize, Serialize};
+use secrecy::SecretString;
+use serde::Deserialize;
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct EnvConfig {
     pub host: String,
     pub port: u16,
     pub database_url: String,
-    pub jwt_secret: String,
+    pub jwt_secret: SecretString,
     pub jwt_expiration: i64,
-    pub stripe_secret_key: Option<String>,
+    pub stripe_secret_key: Option<SecretString>,
     pub razorpay_key_id: Option<String>,
-    pub razorpay_key_secret: Option<String>,
+    pub razorpay_key_secret: Option<SecretString>,
     pub web3_provider_url: String,
     pub contract_address: Option<String>,
     pub frontend_url: String,
@@ -27,14 +28,15 @@ impl EnvConfig {
             database_url: std::env::var("DATABASE_URL")
                 .expect("DATABASE_URL must be set"),
             jwt_secret: std::env::var("JWT_SECRET")
-                .expect("JWT_SECRET must be set"),
+                .expect("JWT_SECRET must be set")
+                .into(),
             jwt_expiration: std::env::var("JWT_EXPIRATION")
                 .unwrap_or_else(|_| "86400".to_string())
                 .parse()
                 .unwrap_or(86400),
-            stripe_secret_key: std::env::var("STRIPE_SECRET_KEY").ok(),
+            stripe_secret_key: std::env::var("STRIPE_SECRET_KEY").ok().map(SecretString::from),
             razorpay_key_id: std::env::var("RAZORPAY_KEY_ID").ok(),
-            razorpay_key_secret: std::env::var("RAZORPAY_KEY_SECRET").ok(),
+            razorpay_key_secret: std::env::var("RAZORPAY_KEY_SECRET").ok().map(SecretString::from),
             web3_provider_url: std::env::var("WEB3_PROVIDER_URL")
                 .unwrap_or_else(|_| "https://mainnet.infura.io/v3/YOUR_KEY".to_string()),
             contract_address: std::env::var("CONTRACT_ADDRESS").ok(),
@@ -1,27 +1,158 @@
+pub mod chains;
 pub mod db;
 pub mod env;
 
+use rust_decimal::Decimal;
+use secrecy::SecretString;
 use serde::Deserialize;
+use std::collections::HashMap;
+use crate::utils::privacy::PrivacyParams;
+use chains::ChainConfig;
 
+/// A chat route, `provider` or `provider:model`, and the one tried when it fails; without a
+/// model a route matches any model of the provider, or falls back to its default model
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AiFailoverRoute {
+    pub provider: String,
+    pub model: Option<String>,
+    pub fallback_provider: String,
+    pub fallback_model: Option<String>,
+}
+
+/// Credentials are held as `SecretString`: their `Debug` output is redacted and the
+/// memory is zeroized on drop. Read them with `ExposeSecret::expose_secret()`.
 #[derive(Debug, Clone, Deserialize)]
 #[allow(dead_code)]
 pub struct AppConfig {
     pub host: String,
     pub port: u16,
     pub database_url: String,
-    pub jwt_secret: String,
+    pub jwt_secret: SecretString,
     pub jwt_expiration: i64,
     pub frontend_url: String,
-    pub stripe_secret_key: String,
+    pub api_base_url: String,
+    pub stripe_secret_key: SecretString,
+    /// Signing secret (`whsec_...`) of the Stripe webhook endpoint; the webhook is off without it
+    pub stripe_webhook_secret: Option<SecretString>,
     pub razorpay_key_id: String,
-    pub razorpay_key_secret: String,
+    pub razorpay_key_secret: SecretString,
+    pub razorpay_webhook_secret: Option<SecretString>,
     pub web3_provider_url: String,
     pub contract_address: String,
-    pub product_price_usd: f64,
+    /// EVM chains crypto payments, balances and wallets may use (see [`chains`])
+    pub chains: Vec<ChainConfig>,
+    /// Chain assumed when a request or a stored transaction names none
+    pub default_chain_id: u64,
+    /// Hours after unlinking a wallet before the account or the address may be linked again
+    pub wallet_relink_cooldown_hours: u32,
+    /// Hex private key of the account that signs and pays for transactions the platform sends
+    pub relayer_private_key: Option<SecretString>,
+    pub product_price_usd: Decimal,
+    /// Wallet crypto invoices are paid to; invoices are unavailable without it
+    pub crypto_deposit_address: Option<String>,
+    /// Price of a product in whole tokens when paid with the chain's token
+    pub crypto_product_price: String,
+    /// Minutes a crypto invoice stays payable
+    pub crypto_invoice_ttl_minutes: u32,
+    /// CoinGecko-compatible API fiat prices are read from (`{url}/simple/price`)
+    pub exchange_rate_api_url: String,
+    pub exchange_rate_api_key: Option<SecretString>,
+    /// Asset id at the rate source by upper-case symbol, e.g. `ETH` -> `ethereum`
+    pub exchange_rate_assets: HashMap<String, String>,
+    /// Whether token and device certificate contract events are indexed into `onchain_events`
+    pub event_indexer_enabled: bool,
+    /// Blocks before the head a contract's first indexing run starts from
+    pub event_indexer_backfill_blocks: u64,
+    /// Seller named on payment receipts, with its postal address and tax registration number
+    pub receipt_issuer: String,
+    pub receipt_issuer_address: Option<String>,
+    pub receipt_tax_id: Option<String>,
+    /// Tax rate included in prices, in percent, itemized on receipts
+    pub receipt_tax_rate_percent: Decimal,
+    /// Currency platform credits are counted and paid out in (lower-case, e.g. `usd`)
+    pub credits_currency: String,
+    /// Smallest payout a user may request, in credits
+    pub payout_min_amount: Decimal,
+    /// Tokens of the default chain paid per credit on crypto payouts; crypto payouts are off at 0
+    pub payout_tokens_per_credit: Decimal,
+    /// Most a user may spend per UTC day, valued in USD; no limit at 0
+    pub spend_limit_daily_usd: Decimal,
+    /// Most a user may spend per calendar month, valued in USD; no limit at 0
+    pub spend_limit_monthly_usd: Decimal,
+    pub webrtc_ice_servers: Vec<String>,
+    pub webrtc_turn_username: Option<String>,
+    pub webrtc_turn_credential: Option<SecretString>,
+    pub key_encryption_key: Option<SecretString>,
+    /// ISO 3166-1 country codes refused on registration and payment endpoints
+    pub blocked_countries: Vec<String>,
+    /// ISO 3166-2 subdivision codes (e.g. `UA-43`) refused the same way
+    pub blocked_regions: Vec<String>,
+    /// Broker for devices using the MQTT transport (`mqtt://host:1883` or `mqtts://...`)
+    pub mqtt_broker_url: Option<String>,
+    pub mqtt_username: Option<String>,
+    pub mqtt_password: Option<SecretString>,
+    /// `redis://host:port`; only its reachability is tracked for now
+    pub redis_url: Option<String>,
+    /// Days raw telemetry samples are kept before only their hourly rollups remain
+    pub telemetry_raw_retention_days: u32,
+    /// Days hourly telemetry rollups and metric values are kept; never shorter than the raw window
+    pub telemetry_aggregate_retention_days: u32,
+    /// Oldest app version accepted per client platform (lower-cased, e.g. `ios` -> `2.4.0`)
+    pub min_client_versions: HashMap<String, String>,
+    /// Domain alert-email replies are addressed to; replies are off without it and the secret
+    pub inbound_email_domain: Option<String>,
+    /// Signs reply-to addresses so a reply can only land on the incident it was sent for
+    pub inbound_email_secret: Option<SecretString>,
+    /// Verifies Mailgun's signature on inbound webhooks
+    pub mailgun_signing_key: Option<SecretString>,
+    /// Shared token in the SNS subscription URL for SES inbound notifications
+    pub ses_inbound_token: Option<SecretString>,
+    /// Signs iCal feed URLs; feeds are unavailable without it
+    pub calendar_feed_secret: Option<SecretString>,
+    /// OAuth client for syncing maintenance windows with Google Calendar
+    pub google_calendar_client_id: Option<String>,
+    pub google_calendar_client_secret: Option<SecretString>,
+    /// S3-compatible storage for org backups, addressed path-style (`{endpoint}/{bucket}/{key}`);
+    /// backups are unavailable without the endpoint, bucket and keys
+    pub backup_s3_endpoint: Option<String>,
+    pub backup_s3_bucket: Option<String>,
+    pub backup_s3_region: String,
+    pub backup_s3_access_key_id: Option<String>,
+    pub backup_s3_secret_access_key: Option<SecretString>,
+    /// Bucket on the backup storage endpoint, with the same keys, holding published open-data
+    /// datasets; publishing is unavailable without it
+    pub open_data_s3_bucket: Option<String>,
+    /// Bucket for files attached to support tickets, on the backup endpoint
+    pub support_s3_bucket: Option<String>,
+    /// Monthly AI spend allowed per user, in USD
+    pub ai_free_monthly_budget_usd: f64,
+    pub ai_premium_monthly_budget_usd: f64,
+    /// Model chat requests are routed to once a user's remaining budget runs low
+    pub ai_economy_model: String,
+    /// Fraction of the monthly budget below which the economy model is used
+    pub ai_economy_below_fraction: f64,
+    /// Check user messages and system prompts with the moderation provider before they reach a model
+    pub ai_moderate_inputs: bool,
+    /// Check model answers before they are returned
+    pub ai_moderate_outputs: bool,
+    /// Replace flagged content with a placeholder instead of rejecting the request
+    pub ai_moderation_redact: bool,
+    /// Provider that moderates; it must support moderation
+    pub ai_moderation_provider: String,
+    /// Voice speech is synthesized in unless a request picks one
+    pub ai_speech_voice: String,
+    /// Where chat requests go when their provider fails or times out
+    pub ai_failover_routes: Vec<AiFailoverRoute>,
+    /// How long a provider with a failover route has to answer before the request fails over
+    pub ai_failover_timeout_secs: u64,
+    /// Differential privacy of the public stats and open-data aggregates
+    pub public_stats_privacy: PrivacyParams,
 }
 
 impl AppConfig {
     pub fn from_env() -> Self {
+        let raw_days = days_var("TELEMETRY_RAW_RETENTION_DAYS", 30);
+        let chains = chains::registry(|var| std::env::var(var).ok());
         Self {
             host: std::env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
             port: std::env::var("PORT")
@@ -31,24 +162,409 @@ impl AppConfig {
             database_url: std::env::var("DATABASE_URL")
                 .expect("DATABASE_URL must be set"),
             jwt_secret: std::env::var("JWT_SECRET")
-                .expect("JWT_SECRET must be set"),
+                .expect("JWT_SECRET must be set")
+                .into(),
             jwt_expiration: std::env::var("JWT_EXPIRATION")
                 .unwrap_or_else(|_| "86400".to_string())
                 .parse()
                 .unwrap_or(86400),
             frontend_url: std::env::var("FRONTEND_URL")
                 .unwrap_or_else(|_| "http://localhost:3000".to_string()),
+            api_base_url: std::env::var("API_BASE_URL")
+                .unwrap_or_else(|_| "http://localhost:8080".to_string()),
             stripe_secret_key: std::env::var("STRIPE_SECRET_KEY")
-                .unwrap_or_default(),
+                .unwrap_or_default()
+                .into(),
+            stripe_webhook_secret: secret_var("STRIPE_WEBHOOK_SECRET"),
             razorpay_key_id: std::env::var("RAZORPAY_KEY_ID")
                 .unwrap_or_default(),
             razorpay_key_secret: std::env::var("RAZORPAY_KEY_SECRET")
-                .unwrap_or_default(),
+                .unwrap_or_default()
+                .into(),
+            razorpay_webhook_secret: secret_var("RAZORPAY_WEBHOOK_SECRET"),
             web3_provider_url: std::env::var("WEB3_PROVIDER_URL")
                 .unwrap_or_else(|_| "https://mainnet.infura.io/v3/YOUR_KEY".to_string()),
             contract_address: std::env::var("CONTRACT_ADDRESS")
                 .unwrap_or_default(),
-            product_price_usd: 1.6,
+            chains: chains.clone(),
+            default_chain_id: std::env::var("DEFAULT_CHAIN_ID")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .filter(|id| chains.iter().any(|c| c.chain_id == *id))
+                .or_else(|| chains.first().map(|c| c.chain_id))
+                .expect("ENABLED_CHAINS must name at least one known chain"),
+            wallet_relink_cooldown_hours: std::env::var("WALLET_RELINK_COOLDOWN_HOURS")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(24),
+            relayer_private_key: secret_var("RELAYER_PRIVATE_KEY"),
+            product_price_usd: Decimal::new(16, 1),
+            crypto_deposit_address: std::env::var("CRYPTO_DEPOSIT_ADDRESS")
+                .ok()
+                .map(|a| a.trim().to_ascii_lowercase())
+                .filter(|a| !a.is_empty()),
+            crypto_product_price: std::env::var("CRYPTO_PRODUCT_PRICE")
+                .ok()
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .unwrap_or_else(|| "1.6".to_string()),
+            crypto_invoice_ttl_minutes: std::env::var("CRYPTO_INVOICE_TTL_MINUTES")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .filter(|m| *m > 0)
+                .unwrap_or(30),
+            exchange_rate_api_url: std::env::var("EXCHANGE_RATE_API_URL")
+                .ok()
+                .map(|u| u.trim().trim_end_matches('/').to_string())
+                .filter(|u| !u.is_empty())
+                .unwrap_or_else(|| "https://api.coingecko.com/api/v3".to_string()),
+            exchange_rate_api_key: secret_var("EXCHANGE_RATE_API_KEY"),
+            exchange_rate_assets: asset_map(
+                &std::env::var("EXCHANGE_RATE_ASSETS")
+                    .unwrap_or_else(|_| "ETH=ethereum,POL=polygon-ecosystem-token".to_string()),
+            ),
+            event_indexer_enabled: std::env::var("EVENT_INDEXER_ENABLED")
+                .map(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "yes"))
+                .unwrap_or(false),
+            event_indexer_backfill_blocks: std::env::var("EVENT_INDEXER_BACKFILL_BLOCKS")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(10_000),
+            receipt_issuer: std::env::var("RECEIPT_ISSUER")
+                .ok()
+                .map(|n| n.trim().to_string())
+                .filter(|n| !n.is_empty())
+                .unwrap_or_else(|| "RoboVeda".to_string()),
+            receipt_issuer_address: std::env::var("RECEIPT_ISSUER_ADDRESS")
+                .ok()
+                .map(|a| a.trim().to_string())
+                .filter(|a| !a.is_empty()),
+            receipt_tax_id: std::env::var("RECEIPT_TAX_ID")
+                .ok()
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty()),
+            receipt_tax_rate_percent: money_var("RECEIPT_TAX_RATE_PERCENT", Decimal::ZERO),
+            credits_currency: std::env::var("CREDITS_CURRENCY")
+                .ok()
+                .map(|c| c.trim().to_lowercase())
+                .filter(|c| !c.is_empty())
+                .unwrap_or_else(|| "usd".to_string()),
+            payout_min_amount: money_var("PAYOUT_MIN_AMOUNT", Decimal::TEN),
+            payout_tokens_per_credit: money_var("PAYOUT_TOKENS_PER_CREDIT", Decimal::ZERO),
+            spend_limit_daily_usd: money_var("SPEND_LIMIT_DAILY_USD", Decimal::from(1_000)),
+            spend_limit_monthly_usd: money_var("SPEND_LIMIT_MONTHLY_USD", Decimal::from(5_000)),
+            webrtc_ice_servers: std::env::var("WEBRTC_ICE_SERVERS")
+                .unwrap_or_else(|_| "stun:stun.l.google.com:19302".to_string())
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            webrtc_turn_username: std::env::var("WEBRTC_TURN_USERNAME").ok(),
+            webrtc_turn_credential: std::env::var("WEBRTC_TURN_CREDENTIAL").ok().map(SecretString::from),
+            key_encryption_key: std::env::var("KEY_ENCRYPTION_KEY").ok()
+                .filter(|k| !k.is_empty())
+                .map(SecretString::from),
+            blocked_countries: code_list("BLOCKED_COUNTRIES", "CU,IR,KP,SY"),
+            blocked_regions: code_list("BLOCKED_REGIONS", "UA-43,UA-14,UA-09"),
+            mqtt_broker_url: std::env::var("MQTT_BROKER_URL").ok().filter(|u| !u.is_empty()),
+            mqtt_username: std::env::var("MQTT_USERNAME").ok().filter(|u| !u.is_empty()),
+            mqtt_password: std::env::var("MQTT_PASSWORD").ok().filter(|p| !p.is_empty()).map(SecretString::from),
+            redis_url: std::env::var("REDIS_URL").ok().filter(|u| !u.is_empty()),
+            telemetry_raw_retention_days: raw_days,
+            telemetry_aggregate_retention_days: days_var("TELEMETRY_AGGREGATE_RETENTION_DAYS", 365).max(raw_days),
+            min_client_versions: version_map(&std::env::var("MIN_CLIENT_VERSIONS").unwrap_or_default()),
+            inbound_email_domain: std::env::var("INBOUND_EMAIL_DOMAIN")
+                .ok()
+                .map(|d| d.trim().to_lowercase())
+                .filter(|d| !d.is_empty()),
+            inbound_email_secret: secret_var("INBOUND_EMAIL_SECRET"),
+            mailgun_signing_key: secret_var("MAILGUN_WEBHOOK_SIGNING_KEY"),
+            ses_inbound_token: secret_var("SES_INBOUND_TOKEN"),
+            calendar_feed_secret: secret_var("CALENDAR_FEED_SECRET"),
+            google_calendar_client_id: std::env::var("GOOGLE_CALENDAR_CLIENT_ID").ok().filter(|id| !id.is_empty()),
+            google_calendar_client_secret: secret_var("GOOGLE_CALENDAR_CLIENT_SECRET"),
+            backup_s3_endpoint: std::env::var("BACKUP_S3_ENDPOINT")
+                .ok()
+                .map(|u| u.trim().trim_end_matches('/').to_string())
+                .filter(|u| !u.is_empty()),
+            backup_s3_bucket: std::env::var("BACKUP_S3_BUCKET").ok().filter(|b| !b.is_empty()),
+            backup_s3_region: std::env::var("BACKUP_S3_REGION")
+                .ok()
+                .filter(|r| !r.is_empty())
+                .unwrap_or_else(|| "us-east-1".to_string()),
+            backup_s3_access_key_id: std::env::var("BACKUP_S3_ACCESS_KEY_ID").ok().filter(|k| !k.is_empty()),
+            backup_s3_secret_access_key: secret_var("BACKUP_S3_SECRET_ACCESS_KEY"),
+            open_data_s3_bucket: std::env::var("OPEN_DATA_S3_BUCKET").ok().filter(|b| !b.is_empty()),
+            support_s3_bucket: std::env::var("SUPPORT_S3_BUCKET").ok().filter(|b| !b.is_empty()),
+            ai_free_monthly_budget_usd: amount_var("AI_FREE_MONTHLY_BUDGET_USD", 1.0),
+            ai_premium_monthly_budget_usd: amount_var("AI_PREMIUM_MONTHLY_BUDGET_USD", 20.0),
+            ai_economy_model: std::env::var("AI_ECONOMY_MODEL")
+                .ok()
+                .map(|m| m.trim().to_string())
+                .filter(|m| !m.is_empty())
+                .unwrap_or_else(|| "gpt-4o-mini".to_string()),
+            ai_economy_below_fraction: amount_var("AI_ECONOMY_BELOW_FRACTION", 0.2).min(1.0),
+            ai_moderate_inputs: flag_var("AI_MODERATE_INPUTS"),
+            ai_moderate_outputs: flag_var("AI_MODERATE_OUTPUTS"),
+            ai_moderation_redact: std::env::var("AI_MODERATION_ACTION")
+                .map(|a| a.trim().eq_ignore_ascii_case("redact"))
+                .unwrap_or(false),
+            ai_moderation_provider: std::env::var("AI_MODERATION_PROVIDER")
+                .ok()
+                .map(|p| p.trim().to_lowercase())
+                .filter(|p| !p.is_empty())
+                .unwrap_or_else(|| "openai".to_string()),
+            ai_speech_voice: std::env::var("AI_SPEECH_VOICE")
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .unwrap_or_else(|| "alloy".to_string()),
+            ai_failover_routes: failover_routes(&std::env::var("AI_FAILOVER_ROUTES").unwrap_or_default()),
+            ai_failover_timeout_secs: std::env::var("AI_FAILOVER_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .filter(|s| *s > 0)
+                .unwrap_or(30),
+            public_stats_privacy: PrivacyParams {
+                epsilon: Some(amount_var("PUBLIC_STATS_EPSILON", 1.0)).filter(|e| *e > 0.0).unwrap_or(1.0),
+                min_cohort: std::env::var("PUBLIC_STATS_MIN_COHORT")
+                    .ok()
+                    .and_then(|v| v.trim().parse().ok())
+                    .filter(|c| *c > 0)
+                    .unwrap_or(10),
+            },
+        }
+    }
+
+    /// The registry entry of an enabled chain
+    pub fn chain(&self, chain_id: u64) -> Option<&ChainConfig> {
+        self.chains.iter().find(|c| c.chain_id == chain_id)
+    }
+}
+
+/// A non-empty secret, or `None` when unset
+fn secret_var(var: &str) -> Option<SecretString> {
+    std::env::var(var).ok().filter(|v| !v.is_empty()).map(SecretString::from)
+}
+
+/// Whether a switch is on (`1`, `true` or `yes`); off when unset
+fn flag_var(var: &str) -> bool {
+    std::env::var(var).map(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "yes")).unwrap_or(false)
+}
+
+/// A positive number of days, falling back to the default when unset or invalid
+fn days_var(var: &str, default: u32) -> u32 {
+    std::env::var(var)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|d| *d > 0)
+        .unwrap_or(default)
+}
+
+/// A non-negative amount, falling back to the default when unset or invalid
+fn amount_var(var: &str, default: f64) -> f64 {
+    std::env::var(var)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|a: &f64| a.is_finite() && *a >= 0.0)
+        .unwrap_or(default)
+}
+
+/// A non-negative exact amount, falling back to the default when unset or invalid
+fn money_var(var: &str, default: Decimal) -> Decimal {
+    std::env::var(var)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|a: &Decimal| !a.is_sign_negative())
+        .unwrap_or(default)
+}
+
+/// Comma-separated, upper-cased codes; an empty variable disables the list
+fn code_list(var: &str, default: &str) -> Vec<String> {
+    std::env::var(var)
+        .unwrap_or_else(|_| default.to_string())
+        .split(',')
+        .map(|s| s.trim().to_uppercase())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// `platform=version` pairs separated by commas, e.g. `ios=2.4.0,android=2.4.0`; malformed pairs are skipped
+fn version_map(value: &str) -> HashMap<String, String> {
+    value
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(platform, version)| (platform.trim().to_lowercase(), version.trim().to_string()))
+        .filter(|(platform, version)| !platform.is_empty() && !version.is_empty())
+        .collect()
+}
+
+/// `SYMBOL=asset-id` pairs separated by commas, e.g. `ETH=ethereum`; symbols are upper-cased
+/// and malformed pairs skipped
+fn asset_map(value: &str) -> HashMap<String, String> {
+    value
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(symbol, id)| (symbol.trim().to_uppercase(), id.trim().to_lowercase()))
+        .filter(|(symbol, id)| !symbol.is_empty() && !id.is_empty())
+        .collect()
+}
+
+/// `route=fallback` pairs separated by commas, each side `provider` or `provider:model`, e.g.
+/// `openai:gpt-4o=anthropic:claude-3-5-sonnet-latest,azure_openai=openai`; malformed pairs are skipped
+fn failover_routes(value: &str) -> Vec<AiFailoverRoute> {
+    let side = |s: &str| {
+        let (provider, model) = s.split_once(':').unwrap_or((s, ""));
+        let model = Some(model.trim().to_string()).filter(|m| !m.is_empty());
+        (provider.trim().to_lowercase(), model)
+    };
+    value
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(route, fallback)| {
+            let ((provider, model), (fallback_provider, fallback_model)) = (side(route), side(fallback));
+            AiFailoverRoute { provider, model, fallback_provider, fallback_model }
+        })
+        .filter(|r| !r.provider.is_empty() && !r.fallback_provider.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secrecy::ExposeSecret;
+
+    #[test]
+    fn test_debug_redacts_secrets() {
+        let config = AppConfig {
+            host: "127.0.0.1".to_string(),
+            port: 8080,
+            database_url: "postgres://localhost/test".to_string(),
+            jwt_secret: "jwt-secret-value".into(),
+            jwt_expiration: 3600,
+            frontend_url: "http://localhost:3000".to_string(),
+            api_base_url: "http://localhost:8080".to_string(),
+            stripe_secret_key: "sk_test_stripe_value".into(),
+            stripe_webhook_secret: Some("whsec_stripe_value".into()),
+            razorpay_key_id: "rzp_test_id".to_string(),
+            razorpay_key_secret: "razorpay-secret-value".into(),
+            razorpay_webhook_secret: Some("razorpay-webhook-value".into()),
+            web3_provider_url: "http://localhost:8545".to_string(),
+            contract_address: String::new(),
+            chains: chains::registry(|_| None),
+            default_chain_id: chains::ETHEREUM_CHAIN_ID,
+            wallet_relink_cooldown_hours: 24,
+            relayer_private_key: Some("relayer-key-value".into()),
+            product_price_usd: Decimal::new(16, 1),
+            crypto_deposit_address: None,
+            crypto_product_price: "1.6".to_string(),
+            crypto_invoice_ttl_minutes: 30,
+            exchange_rate_api_url: "https://api.coingecko.com/api/v3".to_string(),
+            exchange_rate_api_key: Some("rate-api-key-value".into()),
+            exchange_rate_assets: HashMap::new(),
+            event_indexer_enabled: false,
+            event_indexer_backfill_blocks: 10_000,
+            receipt_issuer: "RoboVeda".to_string(),
+            receipt_issuer_address: None,
+            receipt_tax_id: Some("GB123456789".to_string()),
+            receipt_tax_rate_percent: Decimal::from(20),
+            credits_currency: "usd".to_string(),
+            payout_min_amount: Decimal::TEN,
+            payout_tokens_per_credit: Decimal::ZERO,
+            spend_limit_daily_usd: Decimal::from(1_000),
+            spend_limit_monthly_usd: Decimal::from(5_000),
+            webrtc_ice_servers: vec!["turn:turn.example.com".to_string()],
+            webrtc_turn_username: Some("turn-user".to_string()),
+            webrtc_turn_credential: Some("turn-credential-value".into()),
+            key_encryption_key: Some("ab".repeat(32).into()),
+            blocked_countries: Vec::new(),
+            blocked_regions: Vec::new(),
+            mqtt_broker_url: Some("mqtt://broker.example.com:1883".to_string()),
+            mqtt_username: Some("roboveda".to_string()),
+            mqtt_password: Some("mqtt-password-value".into()),
+            redis_url: None,
+            telemetry_raw_retention_days: 30,
+            telemetry_aggregate_retention_days: 365,
+            min_client_versions: HashMap::new(),
+            inbound_email_domain: Some("reply.example.com".to_string()),
+            inbound_email_secret: Some("inbound-secret-value".into()),
+            mailgun_signing_key: Some("mailgun-key-value".into()),
+            ses_inbound_token: None,
+            calendar_feed_secret: Some("calendar-secret-value".into()),
+            google_calendar_client_id: Some("google-client-id".to_string()),
+            google_calendar_client_secret: Some("google-client-secret-value".into()),
+            backup_s3_endpoint: Some("https://s3.example.com".to_string()),
+            backup_s3_bucket: Some("backups".to_string()),
+            backup_s3_region: "us-east-1".to_string(),
+            backup_s3_access_key_id: Some("backup-access-key".to_string()),
+            backup_s3_secret_access_key: Some("backup-secret-key-value".into()),
+            open_data_s3_bucket: Some("open-data".to_string()),
+            support_s3_bucket: Some("support".to_string()),
+            ai_free_monthly_budget_usd: 1.0,
+            ai_premium_monthly_budget_usd: 20.0,
+            ai_economy_model: "gpt-4o-mini".to_string(),
+            ai_economy_below_fraction: 0.2,
+            ai_moderate_inputs: false,
+            ai_moderate_outputs: false,
+            ai_moderation_redact: false,
+            ai_moderation_provider: "openai".to_string(),
+            ai_speech_voice: "alloy".to_string(),
+            ai_failover_routes: Vec::new(),
+            ai_failover_timeout_secs: 30,
+            public_stats_privacy: PrivacyParams { epsilon: 1.0, min_cohort: 10 },
+        };
+
+        let debug = format!("{:?}", config.clone());
+        for secret in [
+            "jwt-secret-value",
+            "sk_test_stripe_value",
+            "whsec_stripe_value",
+            "razorpay-secret-value",
+            "razorpay-webhook-value",
+            "turn-credential-value",
+            "mqtt-password-value",
+            "inbound-secret-value",
+            "mailgun-key-value",
+            "calendar-secret-value",
+            "google-client-secret-value",
+            "backup-secret-key-value",
+            "relayer-key-value",
+            "rate-api-key-value",
+        ] {
+            assert!(!debug.contains(secret), "{} leaked into Debug output", secret);
         }
+        assert!(!debug.contains(&"ab".repeat(32)));
+        assert!(debug.contains("REDACTED"));
+        // Non-secret settings stay readable, and secrets remain usable
+        assert!(debug.contains("rzp_test_id"));
+        assert_eq!(config.jwt_secret.expose_secret(), "jwt-secret-value");
+    }
+
+    #[test]
+    fn test_version_map() {
+        let versions = version_map(" iOS = 2.4.0 ,android=2.3.1,web,=1.0,desktop=");
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions["ios"], "2.4.0");
+        assert_eq!(versions["android"], "2.3.1");
+        assert!(version_map("").is_empty());
+    }
+
+    #[test]
+    fn test_asset_map() {
+        let assets = asset_map(" eth = Ethereum ,POL=polygon-ecosystem-token,RBV,=usd-coin");
+        assert_eq!(assets.len(), 2);
+        assert_eq!(assets["ETH"], "ethereum");
+        assert_eq!(assets["POL"], "polygon-ecosystem-token");
+    }
+
+    #[test]
+    fn test_failover_routes() {
+        let routes = failover_routes(" OpenAI:ft:gpt-4o-mini:x1 = anthropic ,azure_openai=openai:gpt-4o,=ollama,x");
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].provider, "openai");
+        assert_eq!(routes[0].model.as_deref(), Some("ft:gpt-4o-mini:x1"));
+        assert_eq!((routes[0].fallback_provider.as_str(), routes[0].fallback_model.as_deref()), ("anthropic", None));
+        assert_eq!(routes[1].model, None);
+        assert_eq!(routes[1].fallback_model.as_deref(), Some("gpt-4o"));
     }
 }
@@ -0,0 +1,35 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::config::AppConfig;
+use crate::errors::{ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::services::ai_routing_services;
+use crate::services::ai_services::AIService;
+use crate::services::estimate_services::{self, EstimateRequest};
+
+/// The current user's AI budget this month: what is left, whether requests are being routed
+/// to the economy model, and the per-model prices costs are estimated with
+/// GET /api/ai/budget
+pub async fn get_budget(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+) -> ApiResult<HttpResponse> {
+    let budget = ai_routing_services::budget(pool.get_ref(), &config, user.user_id).await?;
+    Ok(ApiResponse::success(budget))
+}
+
+/// What a chat request would cost on each model available, before it is sent
+/// POST /api/ai/estimate
+pub async fn estimate_cost(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    ai: web::Data<Arc<AIService>>,
+    body: web::Json<EstimateRequest>,
+) -> ApiResult<HttpResponse> {
+    let estimate =
+        estimate_services::estimate(pool.get_ref(), &config, ai.get_ref(), user.user_id, body.into_inner()).await?;
+    Ok(ApiResponse::success(estimate))
+}
@@ -0,0 +1,152 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::attachment::{CreateAttachmentRequest, DeviceAttachment, UpdateAttachmentRequest};
+use crate::services::attachment_services::{
+    validate_attachment_type, validate_config, validate_label, ATTACHMENT_COLUMNS, MAX_ATTACHMENTS_PER_DEVICE,
+};
+use crate::services::device_services::get_owned_device;
+
+/// Trim optional text fields and check their length
+fn label(field: &str, value: Option<&str>) -> ApiResult<Option<String>> {
+    value
+        .map(|v| {
+            let v = v.trim();
+            validate_label(field, v).map(|_| v.to_string())
+        })
+        .transpose()
+}
+
+/// Attachments mounted on a device
+/// GET /api/robotics/devices/{device_id}/attachments
+pub async fn list_attachments(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+
+    let attachments = sqlx::query_as::<_, DeviceAttachment>(&format!(
+        "SELECT {} FROM device_attachments WHERE device_id = $1 ORDER BY attachment_type, name",
+        ATTACHMENT_COLUMNS
+    ))
+    .bind(device.id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(attachments))
+}
+
+/// Mount an attachment; commands that need it (e.g. `grab` for a gripper) are accepted from then on
+/// POST /api/robotics/devices/{device_id}/attachments
+pub async fn create_attachment(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<CreateAttachmentRequest>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    validate_attachment_type(&body.attachment_type)?;
+    let name = label("name", body.name.as_deref())?.unwrap_or_else(|| body.attachment_type.clone());
+    let model = label("model", body.model.as_deref())?;
+    let serial_number = label("serial_number", body.serial_number.as_deref())?;
+    let config = body.config.clone().unwrap_or_else(|| serde_json::json!({}));
+    validate_config(&config)?;
+
+    let (mounted, exists): (i64, bool) = sqlx::query_as(
+        "SELECT COUNT(*), COALESCE(BOOL_OR(name = $2), FALSE) FROM device_attachments WHERE device_id = $1",
+    )
+    .bind(device.id)
+    .bind(&name)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+    if exists {
+        return Err(ApiError::Conflict(format!("Attachment {} is already mounted", name)));
+    }
+    if mounted as usize >= MAX_ATTACHMENTS_PER_DEVICE {
+        return Err(ApiError::ValidationError(format!(
+            "A device may mount at most {} attachments",
+            MAX_ATTACHMENTS_PER_DEVICE
+        )));
+    }
+
+    let attachment = sqlx::query_as::<_, DeviceAttachment>(&format!(
+        "INSERT INTO device_attachments (device_id, attachment_type, name, model, serial_number, config, enabled) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING {}",
+        ATTACHMENT_COLUMNS
+    ))
+    .bind(device.id)
+    .bind(&body.attachment_type)
+    .bind(&name)
+    .bind(model)
+    .bind(serial_number)
+    .bind(sqlx::types::Json(&config))
+    .bind(body.enabled.unwrap_or(true))
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::created(attachment))
+}
+
+/// Rename, reconfigure, enable or disable an attachment
+/// PATCH /api/robotics/devices/{device_id}/attachments/{attachment_id}
+pub async fn update_attachment(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+    body: web::Json<UpdateAttachmentRequest>,
+) -> ApiResult<HttpResponse> {
+    let (device_id, attachment_id) = path.into_inner();
+    let device = get_owned_device(pool.get_ref(), device_id, user.user_id).await?;
+    let name = label("name", body.name.as_deref())?;
+    let model = label("model", body.model.as_deref())?;
+    let serial_number = label("serial_number", body.serial_number.as_deref())?;
+    if let Some(config) = &body.config {
+        validate_config(config)?;
+    }
+
+    let attachment = sqlx::query_as::<_, DeviceAttachment>(&format!(
+        "UPDATE device_attachments SET name = COALESCE($3, name), model = COALESCE($4, model), \
+         serial_number = COALESCE($5, serial_number), config = COALESCE($6, config), \
+         enabled = COALESCE($7, enabled), updated_at = NOW() \
+         WHERE id = $1 AND device_id = $2 RETURNING {}",
+        ATTACHMENT_COLUMNS
+    ))
+    .bind(attachment_id)
+    .bind(device.id)
+    .bind(name)
+    .bind(model)
+    .bind(serial_number)
+    .bind(body.config.as_ref().map(sqlx::types::Json))
+    .bind(body.enabled)
+    .fetch_optional(pool.get_ref().as_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Attachment not found".to_string()))?;
+
+    Ok(ApiResponse::success(attachment))
+}
+
+/// Unmount an attachment; commands that need it are rejected from then on
+/// DELETE /api/robotics/devices/{device_id}/attachments/{attachment_id}
+pub async fn delete_attachment(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (device_id, attachment_id) = path.into_inner();
+    let device = get_owned_device(pool.get_ref(), device_id, user.user_id).await?;
+
+    let deleted = sqlx::query("DELETE FROM device_attachments WHERE id = $1 AND device_id = $2")
+        .bind(attachment_id)
+        .bind(device.id)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Attachment not found".to_string()));
+    }
+
+    Ok(crate::errors::success_message("Attachment removed"))
+}
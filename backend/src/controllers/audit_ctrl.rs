@@ -0,0 +1,296 @@
+use actix_web::{http::header::CONTENT_DISPOSITION, web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::device::TelemetryRecord;
+use crate::models::org::{AuditLog, AuditQuery, ExportQuery};
+use crate::models::transaction::Transaction;
+use crate::services::audit_services::{self, to_csv, AuditEntry};
+use crate::services::org_services::{privacy_policy, require_org_permission};
+use crate::services::policy_services::OrgAction;
+use crate::utils::redaction::{redact_str, round_coordinate, Redacted};
+
+/// Upper bound on rows written to a single export
+const MAX_EXPORT_ROWS: i64 = 10_000;
+
+const AUDIT_COLUMNS: &str = "id, org_id, actor_id, action, resource_type, resource_id, details, created_at";
+
+const TRANSACTION_COLUMNS: &str = "t.id, t.user_id, t.amount, t.currency, t.payment_method, t.payment_id, \
+     t.status, t.product_type, t.blockchain_tx_hash, t.chain_id, t.confirmations, t.block_number, t.coupon_id, \
+     t.discount_amount, t.created_at";
+
+const TELEMETRY_COLUMNS: &str = "dt.id, dt.device_id, dt.recorded_at, dt.battery_level, dt.latitude, \
+     dt.longitude, dt.altitude, dt.payload, dt.received_at";
+
+/// Restricts rows to users with an active membership in the org bound as $1
+const ORG_MEMBER_FILTER: &str =
+    "IN (SELECT user_id FROM org_memberships WHERE org_id = $1 AND active)";
+
+/// List an org's audit trail, newest first
+/// GET /api/orgs/{org_id}/audit-logs
+pub async fn list_audit_logs(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    query: web::Query<AuditQuery>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ReadAuditLogs).await?;
+
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    let logs = sqlx::query_as::<_, AuditLog>(&format!(
+        "SELECT {} FROM audit_logs \
+         WHERE org_id = $1 \
+           AND ($2::timestamptz IS NULL OR created_at >= $2) \
+           AND ($3::timestamptz IS NULL OR created_at < $3) \
+           AND ($4::varchar IS NULL OR action = $4) \
+         ORDER BY created_at DESC LIMIT $5 OFFSET $6",
+        AUDIT_COLUMNS
+    ))
+    .bind(org_id)
+    .bind(query.from)
+    .bind(query.to)
+    .bind(&query.action)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(logs))
+}
+
+/// List transactions made by the org's members
+/// GET /api/orgs/{org_id}/transactions
+pub async fn list_transactions(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    query: web::Query<AuditQuery>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ReadTransactions).await?;
+
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    let transactions = sqlx::query_as::<_, Transaction>(&format!(
+        "SELECT {} FROM transactions t \
+         WHERE t.user_id {} \
+           AND ($2::timestamptz IS NULL OR t.created_at >= $2) \
+           AND ($3::timestamptz IS NULL OR t.created_at < $3) \
+         ORDER BY t.created_at DESC LIMIT $4 OFFSET $5",
+        TRANSACTION_COLUMNS, ORG_MEMBER_FILTER
+    ))
+    .bind(org_id)
+    .bind(query.from)
+    .bind(query.to)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(transactions))
+}
+
+/// Telemetry history of a device owned by one of the org's members
+/// GET /api/orgs/{org_id}/devices/{device_id}/history
+pub async fn get_device_history(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+    query: web::Query<AuditQuery>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, device_id) = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ReadDeviceHistory).await?;
+
+    let in_org: bool = sqlx::query_scalar(&format!(
+        "SELECT EXISTS (SELECT 1 FROM devices WHERE id = $2 AND user_id {})",
+        ORG_MEMBER_FILTER
+    ))
+    .bind(org_id)
+    .bind(device_id)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+    if !in_org {
+        return Err(ApiError::NotFound("Device not found".to_string()));
+    }
+
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    let history = sqlx::query_as::<_, TelemetryRecord>(&format!(
+        "SELECT {} FROM device_telemetry dt \
+         WHERE dt.device_id = $1 \
+           AND ($2::timestamptz IS NULL OR dt.recorded_at >= $2) \
+           AND ($3::timestamptz IS NULL OR dt.recorded_at < $3) \
+         ORDER BY dt.recorded_at DESC LIMIT $4 OFFSET $5",
+        TELEMETRY_COLUMNS
+    ))
+    .bind(device_id)
+    .bind(query.from)
+    .bind(query.to)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(history))
+}
+
+/// Export audit logs, transactions or device history across the org as CSV
+/// GET /api/orgs/{org_id}/export?resource=audit_logs|transactions|device_history
+pub async fn export(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    query: web::Query<ExportQuery>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    let membership = require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ExportData).await?;
+
+    // Exporting a resource also requires permission to read it
+    let read_action = match query.resource.as_str() {
+        "audit_logs" => OrgAction::ReadAuditLogs,
+        "transactions" => OrgAction::ReadTransactions,
+        "device_history" => OrgAction::ReadDeviceHistory,
+        other => return Err(ApiError::ValidationError(format!("Unknown export resource: {}", other))),
+    };
+    require_org_permission(pool.get_ref(), org_id, &user, read_action).await?;
+    let privacy = privacy_policy(pool.get_ref(), org_id).await?;
+    let coordinate = |v: f64| privacy.gps_decimals.map_or(v, |d| round_coordinate(v, d));
+
+    let db = pool.get_ref().as_ref();
+    let body = match read_action {
+        OrgAction::ReadAuditLogs => {
+            let rows = sqlx::query_as::<_, AuditLog>(&format!(
+                "SELECT {} FROM audit_logs \
+                 WHERE org_id = $1 \
+                   AND ($2::timestamptz IS NULL OR created_at >= $2) \
+                   AND ($3::timestamptz IS NULL OR created_at < $3) \
+                 ORDER BY created_at LIMIT $4",
+                AUDIT_COLUMNS
+            ))
+            .bind(org_id)
+            .bind(query.from)
+            .bind(query.to)
+            .bind(MAX_EXPORT_ROWS)
+            .fetch_all(db)
+            .await?;
+
+            to_csv(
+                &["id", "created_at", "actor_id", "action", "resource_type", "resource_id", "details"],
+                rows.into_iter().map(|r| {
+                    vec![
+                        r.id.to_string(),
+                        r.created_at.to_rfc3339(),
+                        r.actor_id.map(|id| id.to_string()).unwrap_or_default(),
+                        r.action,
+                        r.resource_type,
+                        redact_str(&r.resource_id.unwrap_or_default(), &privacy).into_owned(),
+                        Redacted::new(&r.details, privacy).to_string(),
+                    ]
+                }),
+            )?
+        }
+        OrgAction::ReadTransactions => {
+            let rows = sqlx::query_as::<_, Transaction>(&format!(
+                "SELECT {} FROM transactions t \
+                 WHERE t.user_id {} \
+                   AND ($2::timestamptz IS NULL OR t.created_at >= $2) \
+                   AND ($3::timestamptz IS NULL OR t.created_at < $3) \
+                 ORDER BY t.created_at LIMIT $4",
+                TRANSACTION_COLUMNS, ORG_MEMBER_FILTER
+            ))
+            .bind(org_id)
+            .bind(query.from)
+            .bind(query.to)
+            .bind(MAX_EXPORT_ROWS)
+            .fetch_all(db)
+            .await?;
+
+            to_csv(
+                &[
+                    "id", "created_at", "user_id", "amount", "currency", "payment_method", "status",
+                    "product_type", "blockchain_tx_hash",
+                ],
+                rows.into_iter().map(|t| {
+                    vec![
+                        t.id.to_string(),
+                        t.created_at.to_rfc3339(),
+                        t.user_id.to_string(),
+                        t.amount.to_string(),
+                        t.currency,
+                        t.payment_method,
+                        t.status,
+                        t.product_type,
+                        t.blockchain_tx_hash.unwrap_or_default(),
+                    ]
+                }),
+            )?
+        }
+        _ => {
+            let rows = sqlx::query_as::<_, TelemetryRecord>(&format!(
+                "SELECT {} FROM device_telemetry dt \
+                 JOIN devices d ON d.id = dt.device_id \
+                 WHERE d.user_id {} \
+                   AND ($2::timestamptz IS NULL OR dt.recorded_at >= $2) \
+                   AND ($3::timestamptz IS NULL OR dt.recorded_at < $3) \
+                 ORDER BY dt.recorded_at LIMIT $4",
+                TELEMETRY_COLUMNS, ORG_MEMBER_FILTER
+            ))
+            .bind(org_id)
+            .bind(query.from)
+            .bind(query.to)
+            .bind(MAX_EXPORT_ROWS)
+            .fetch_all(db)
+            .await?;
+
+            to_csv(
+                &["id", "device_id", "recorded_at", "battery_level", "latitude", "longitude", "altitude"],
+                rows.into_iter().map(|r| {
+                    vec![
+                        r.id.to_string(),
+                        r.device_id.to_string(),
+                        r.recorded_at.to_rfc3339(),
+                        r.battery_level.to_string(),
+                        coordinate(r.latitude).to_string(),
+                        coordinate(r.longitude).to_string(),
+                        r.altitude.map(|a| a.to_string()).unwrap_or_default(),
+                    ]
+                }),
+            )?
+        }
+    };
+
+    let mut conn = db.acquire().await?;
+    audit_services::record(
+        &mut conn,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "data.exported",
+            resource_type: "export",
+            resource_id: None,
+            details: serde_json::json!({
+                "resource": query.resource,
+                "role": membership.role,
+                "privacy": privacy,
+                "from": query.from,
+                "to": query.to,
+            }),
+        },
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv")
+        .insert_header((
+            CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}.csv\"", query.resource),
+        ))
+        .body(body))
+}
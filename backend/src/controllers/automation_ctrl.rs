@@ -0,0 +1,289 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::automation::{
+    Automation, AutomationRun, AutomationVersion, CreateAutomationRequest, DryRunRequest, FeatureFlag,
+    UpdateAutomationRequest,
+};
+use crate::services::automation_services::{self, check_graph, new_hook_token, AUTOMATION_COLUMNS, RUN_COLUMNS};
+use crate::services::transport_services::TransportRegistry;
+use crate::utils::crypto::sha256_hash;
+
+async fn owned_automation(pool: &PgPool, automation_id: Uuid, user_id: Uuid) -> ApiResult<Automation> {
+    sqlx::query_as::<_, Automation>(&format!(
+        "SELECT {} FROM automations WHERE id = $1 AND user_id = $2",
+        AUTOMATION_COLUMNS
+    ))
+    .bind(automation_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Automation not found".to_string()))
+}
+
+fn validate_name(name: &str) -> ApiResult<()> {
+    if name.is_empty() || name.len() > 100 {
+        return Err(ApiError::ValidationError("name must be 1-100 characters".to_string()));
+    }
+    Ok(())
+}
+
+/// GET /api/automations
+pub async fn list_automations(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let automations = sqlx::query_as::<_, Automation>(&format!(
+        "SELECT {} FROM automations WHERE user_id = $1 ORDER BY created_at",
+        AUTOMATION_COLUMNS
+    ))
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(automations))
+}
+
+/// Save a rule graph as version 1; the response includes the token for its inbound hook
+/// POST /api/automations
+pub async fn create_automation(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<CreateAutomationRequest>,
+) -> ApiResult<HttpResponse> {
+    let name = body.name.trim();
+    validate_name(name)?;
+    check_graph(pool.get_ref(), user.user_id, &body.graph).await?;
+
+    let (automation, token) = automation_services::create_automation(
+        pool.get_ref(),
+        user.user_id,
+        name,
+        &body.graph,
+        body.enabled.unwrap_or(true),
+    )
+    .await?;
+
+    Ok(ApiResponse::created(serde_json::json!({
+        "automation": automation,
+        "graph": body.graph,
+        "hook_token": token,
+    })))
+}
+
+/// The automation with the graph of its current version
+/// GET /api/automations/{automation_id}
+pub async fn get_automation(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let automation = owned_automation(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let graph = automation_services::load_graph(pool.get_ref(), automation.id, automation.current_version).await?;
+
+    Ok(ApiResponse::success(serde_json::json!({ "automation": automation, "graph": graph })))
+}
+
+/// Rename or enable/disable an automation; a new graph is stored as the next version
+/// PATCH /api/automations/{automation_id}
+pub async fn update_automation(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateAutomationRequest>,
+) -> ApiResult<HttpResponse> {
+    let automation = owned_automation(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let name = body.name.as_deref().map(str::trim);
+    if let Some(name) = name {
+        validate_name(name)?;
+    }
+    if let Some(graph) = &body.graph {
+        check_graph(pool.get_ref(), user.user_id, graph).await?;
+    }
+
+    let mut tx = pool.begin().await?;
+    // Lock the row so concurrent edits get distinct version numbers
+    let version: i32 = sqlx::query_scalar("SELECT current_version FROM automations WHERE id = $1 FOR UPDATE")
+        .bind(automation.id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let version = match &body.graph {
+        Some(graph) => {
+            sqlx::query(
+                "INSERT INTO automation_versions (automation_id, version, graph, created_by) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(automation.id)
+            .bind(version + 1)
+            .bind(sqlx::types::Json(graph))
+            .bind(user.user_id)
+            .execute(&mut *tx)
+            .await?;
+            version + 1
+        }
+        None => version,
+    };
+    let automation = sqlx::query_as::<_, Automation>(&format!(
+        "UPDATE automations SET name = COALESCE($2, name), enabled = COALESCE($3, enabled), \
+         current_version = $4, trigger_kind = COALESCE($5, trigger_kind), updated_at = NOW() \
+         WHERE id = $1 RETURNING {}",
+        AUTOMATION_COLUMNS
+    ))
+    .bind(automation.id)
+    .bind(name)
+    .bind(body.enabled)
+    .bind(version)
+    .bind(body.graph.as_ref().map(|g| g.trigger.kind()))
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::success(automation))
+}
+
+/// Remove an automation with its versions and run history
+/// DELETE /api/automations/{automation_id}
+pub async fn delete_automation(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let deleted = sqlx::query("DELETE FROM automations WHERE id = $1 AND user_id = $2")
+        .bind(path.into_inner())
+        .bind(user.user_id)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Automation not found".to_string()));
+    }
+
+    Ok(crate::errors::success_message("Automation deleted"))
+}
+
+/// Every saved version of the rule graph, newest first
+/// GET /api/automations/{automation_id}/versions
+pub async fn list_versions(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let automation = owned_automation(pool.get_ref(), path.into_inner(), user.user_id).await?;
+
+    let versions = sqlx::query_as::<_, AutomationVersion>(
+        "SELECT automation_id, version, graph, created_by, created_at FROM automation_versions \
+         WHERE automation_id = $1 ORDER BY version DESC",
+    )
+    .bind(automation.id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(versions))
+}
+
+/// Recent runs, including dry runs
+/// GET /api/automations/{automation_id}/runs
+pub async fn list_runs(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let automation = owned_automation(pool.get_ref(), path.into_inner(), user.user_id).await?;
+
+    let runs = sqlx::query_as::<_, AutomationRun>(&format!(
+        "SELECT {} FROM automation_runs WHERE automation_id = $1 ORDER BY started_at DESC LIMIT 100",
+        RUN_COLUMNS
+    ))
+    .bind(automation.id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(runs))
+}
+
+/// Evaluate a version against a sample event and show which actions would run; nothing is performed
+/// POST /api/automations/{automation_id}/dry-run
+pub async fn dry_run(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    transports: web::Data<Arc<TransportRegistry>>,
+    path: web::Path<Uuid>,
+    body: web::Json<DryRunRequest>,
+) -> ApiResult<HttpResponse> {
+    let automation = owned_automation(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let version = body.version.unwrap_or(automation.current_version);
+    let graph = automation_services::load_graph(pool.get_ref(), automation.id, version).await?;
+
+    let run = automation_services::execute(
+        pool.get_ref(),
+        transports.get_ref(),
+        &automation,
+        version,
+        &graph,
+        &body.event,
+        true,
+    )
+    .await?;
+
+    Ok(ApiResponse::success(run))
+}
+
+/// Replace the inbound hook token; the old one stops working immediately
+/// POST /api/automations/{automation_id}/hook-token
+pub async fn rotate_hook_token(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let automation = owned_automation(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let (token, token_hash) = new_hook_token();
+
+    sqlx::query("UPDATE automations SET hook_token_hash = $2, updated_at = NOW() WHERE id = $1")
+        .bind(automation.id)
+        .bind(&token_hash)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+
+    Ok(ApiResponse::success(serde_json::json!({ "hook_token": token })))
+}
+
+/// Inbound trigger for webhook automations; the token in the path authenticates the caller
+/// and the JSON body becomes the event
+/// POST /api/automations/hooks/{token}
+pub async fn receive_hook(
+    pool: web::Data<Arc<PgPool>>,
+    transports: web::Data<Arc<TransportRegistry>>,
+    path: web::Path<String>,
+    body: Option<web::Json<serde_json::Value>>,
+) -> ApiResult<HttpResponse> {
+    let automation = sqlx::query_as::<_, Automation>(&format!(
+        "SELECT {} FROM automations WHERE hook_token_hash = $1 AND trigger_kind = 'webhook' AND enabled",
+        AUTOMATION_COLUMNS
+    ))
+    .bind(sha256_hash(path.into_inner().as_bytes()))
+    .fetch_optional(pool.get_ref().as_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Automation not found".to_string()))?;
+
+    let event = body.map(|b| b.into_inner()).unwrap_or_else(|| serde_json::json!({}));
+    let run = automation_services::on_webhook(pool.get_ref(), transports.get_ref(), &automation, &event).await?;
+
+    Ok(ApiResponse::success(serde_json::json!({ "run_id": run.id, "status": run.status })))
+}
+
+/// Feature flags set by the caller's automations
+/// GET /api/automations/flags
+pub async fn list_flags(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let flags = sqlx::query_as::<_, FeatureFlag>(
+        "SELECT flag, enabled, updated_at FROM user_feature_flags WHERE user_id = $1 ORDER BY flag",
+    )
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(flags))
+}
@@ -0,0 +1,112 @@
+use actix_web::{http::header::CONTENT_DISPOSITION, web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::backup::{OrgBackup, RestoreRequest};
+use crate::services::backup_services::{self, parse_document, BACKUP_COLUMNS};
+use crate::services::object_storage_services::ObjectStore;
+use crate::services::org_services::require_org_permission;
+use crate::services::policy_services::OrgAction;
+
+fn backup_store(config: &AppConfig) -> ApiResult<ObjectStore> {
+    ObjectStore::for_backups(config)
+        .ok_or_else(|| ApiError::ServiceUnavailable("Backup storage is not configured".to_string()))
+}
+
+/// List the org's backups, newest first
+/// GET /api/orgs/{org_id}/backups
+pub async fn list_backups(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageBackups).await?;
+
+    let backups = sqlx::query_as::<_, OrgBackup>(&format!(
+        "SELECT {} FROM org_backups WHERE org_id = $1 ORDER BY created_at DESC",
+        BACKUP_COLUMNS
+    ))
+    .bind(org_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(backups))
+}
+
+/// Take a logical backup of the org's devices, fleet configuration, automations, metrics and
+/// privacy settings and store it in object storage. Telemetry is not included.
+/// POST /api/orgs/{org_id}/backups
+pub async fn create_backup(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageBackups).await?;
+    let store = backup_store(&config)?;
+
+    let backup = backup_services::create_backup(pool.get_ref(), &store, org_id, user.user_id).await?;
+    Ok(ApiResponse::created(backup))
+}
+
+/// Download a backup's document, e.g. to restore it in another environment
+/// GET /api/orgs/{org_id}/backups/{backup_id}
+pub async fn download_backup(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, backup_id) = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageBackups).await?;
+    let store = backup_store(&config)?;
+
+    let body = backup_services::fetch_backup(pool.get_ref(), &store, org_id, backup_id).await?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header((CONTENT_DISPOSITION, format!("attachment; filename=\"backup-{}.json\"", backup_id)))
+        .body(body))
+}
+
+/// Restore one of the org's backups. `strategy` settles records that exist and differ:
+/// `skip` keeps them, `overwrite` replaces them and `fail` aborts the restore.
+/// POST /api/orgs/{org_id}/backups/{backup_id}/restore
+pub async fn restore_backup(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<(Uuid, Uuid)>,
+    body: web::Json<RestoreRequest>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, backup_id) = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageBackups).await?;
+    let store = backup_store(&config)?;
+
+    let document = parse_document(&backup_services::fetch_backup(pool.get_ref(), &store, org_id, backup_id).await?)?;
+    let report =
+        backup_services::restore(pool.get_ref(), org_id, user.user_id, Some(backup_id), document, body.strategy)
+            .await?;
+    Ok(ApiResponse::success(report))
+}
+
+/// Restore a downloaded backup document, possibly taken from another org or environment
+/// POST /api/orgs/{org_id}/restore?strategy=skip|overwrite|fail
+pub async fn restore_document(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    query: web::Query<RestoreRequest>,
+    body: web::Bytes,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageBackups).await?;
+
+    let document = parse_document(&body)?;
+    let report = backup_services::restore(pool.get_ref(), org_id, user.user_id, None, document, query.strategy).await?;
+    Ok(ApiResponse::success(report))
+}
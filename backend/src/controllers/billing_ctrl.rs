@@ -0,0 +1,151 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::{AdminUser, AuthenticatedUser};
+use crate::models::subscription::{
+    ChangePlanRequest, CreatePlanRequest, SubscribeRequest, SubscriptionInvoice, SubscriptionPlan, UpdatePlanRequest,
+};
+use crate::services::subscription_services::{self, validate_plan, INVOICE_COLUMNS, PLAN_COLUMNS};
+
+/// Plans on offer
+/// GET /api/billing/plans
+pub async fn list_plans(pool: web::Data<Arc<PgPool>>) -> ApiResult<HttpResponse> {
+    let plans = sqlx::query_as::<_, SubscriptionPlan>(&format!(
+        "SELECT {} FROM subscription_plans WHERE active ORDER BY amount",
+        PLAN_COLUMNS
+    ))
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(plans))
+}
+
+/// Add a plan (admin only)
+/// POST /api/billing/plans
+pub async fn create_plan(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<CreatePlanRequest>,
+) -> ApiResult<HttpResponse> {
+    let name = body.name.trim();
+    validate_plan(&body.code, name, body.amount, &body.currency, &body.billing_interval)?;
+
+    let plan = sqlx::query_as::<_, SubscriptionPlan>(&format!(
+        "INSERT INTO subscription_plans (code, name, amount, currency, billing_interval) \
+         VALUES ($1, $2, $3, $4, $5) RETURNING {}",
+        PLAN_COLUMNS
+    ))
+    .bind(&body.code)
+    .bind(name)
+    .bind(body.amount)
+    .bind(&body.currency)
+    .bind(&body.billing_interval)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::created(plan))
+}
+
+/// Rename, reprice or retire a plan (admin only). A new price applies from each subscriber's
+/// next renewal; a retired plan keeps its subscribers but takes no new ones.
+/// PATCH /api/billing/plans/{plan_id}
+pub async fn update_plan(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdatePlanRequest>,
+) -> ApiResult<HttpResponse> {
+    let existing = sqlx::query_as::<_, SubscriptionPlan>(&format!(
+        "SELECT {} FROM subscription_plans WHERE id = $1",
+        PLAN_COLUMNS
+    ))
+    .bind(path.into_inner())
+    .fetch_optional(pool.get_ref().as_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Plan not found".to_string()))?;
+    let name = body.name.as_deref().map(str::trim).unwrap_or(&existing.name);
+    let amount = body.amount.unwrap_or(existing.amount);
+    validate_plan(&existing.code, name, amount, &existing.currency, &existing.billing_interval)?;
+
+    let plan = sqlx::query_as::<_, SubscriptionPlan>(&format!(
+        "UPDATE subscription_plans SET name = $2, amount = $3, active = COALESCE($4, active), updated_at = NOW() \
+         WHERE id = $1 RETURNING {}",
+        PLAN_COLUMNS
+    ))
+    .bind(existing.id)
+    .bind(name)
+    .bind(amount)
+    .bind(body.active)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(plan))
+}
+
+/// The current user's subscription with its plan and any unpaid invoice; `null` if they
+/// have never subscribed
+/// GET /api/billing/subscription
+pub async fn get_subscription(user: AuthenticatedUser, pool: web::Data<Arc<PgPool>>) -> ApiResult<HttpResponse> {
+    let subscription = subscription_services::current_subscription(pool.get_ref(), user.user_id).await?;
+    Ok(ApiResponse::success(subscription))
+}
+
+/// Subscribe to a plan. The response's `client_secret` confirms the first payment with Stripe;
+/// the subscription becomes active when Stripe reports it paid.
+/// POST /api/billing/subscription
+pub async fn subscribe(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<SubscribeRequest>,
+) -> ApiResult<HttpResponse> {
+    let response = subscription_services::subscribe(pool.get_ref(), &config, user.user_id, &body.plan_code).await?;
+    Ok(ApiResponse::created(response))
+}
+
+/// Switch plans now, with the difference prorated over the rest of the period
+/// PATCH /api/billing/subscription
+pub async fn change_plan(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<ChangePlanRequest>,
+) -> ApiResult<HttpResponse> {
+    let details = subscription_services::change_plan(pool.get_ref(), &config, user.user_id, &body.plan_code).await?;
+    Ok(ApiResponse::success(details))
+}
+
+/// Cancel at the end of the current period; a subscription not yet paid for ends at once
+/// DELETE /api/billing/subscription
+pub async fn cancel_subscription(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+) -> ApiResult<HttpResponse> {
+    let subscription = subscription_services::cancel(pool.get_ref(), &config, user.user_id).await?;
+    Ok(ApiResponse::success(subscription))
+}
+
+/// Undo a cancellation before the period ends
+/// POST /api/billing/subscription/resume
+pub async fn resume_subscription(user: AuthenticatedUser, pool: web::Data<Arc<PgPool>>) -> ApiResult<HttpResponse> {
+    let subscription = subscription_services::resume(pool.get_ref(), user.user_id).await?;
+    Ok(ApiResponse::success(subscription))
+}
+
+/// The current user's subscription invoices, newest first
+/// GET /api/billing/invoices
+pub async fn list_invoices(user: AuthenticatedUser, pool: web::Data<Arc<PgPool>>) -> ApiResult<HttpResponse> {
+    let invoices = sqlx::query_as::<_, SubscriptionInvoice>(&format!(
+        "SELECT {} FROM subscription_invoices WHERE user_id = $1 ORDER BY created_at DESC LIMIT 100",
+        INVOICE_COLUMNS
+    ))
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(invoices))
+}
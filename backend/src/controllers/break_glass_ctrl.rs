@@ -0,0 +1,425 @@
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use sqlx::{PgConnection, PgPool};
+use secrecy::ExposeSecret;
+use std::sync::Arc;
+use uuid::Uuid;
+use validator::Validate;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::org::{
+    BreakGlassActivationRequest, BreakGlassCredential, BreakGlassSession, CreateBreakGlassCredentialRequest,
+    OrgMembership,
+};
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::break_glass_services::{validate_activation, PENDING_TTL_MINUTES};
+use crate::services::notification_services::{notify_org_owners, notify_user};
+use crate::services::org_services::{get_membership, require_org_permission, require_org_role, role_at_least};
+use crate::services::policy_services::OrgAction;
+use crate::utils::{create_break_glass_token, generate_random_hex, log_auth_event, sha256_hash};
+
+const CREDENTIAL_COLUMNS: &str = "id, org_id, label, created_by, created_at, used_at, revoked_at";
+
+const SESSION_COLUMNS: &str = "id, org_id, credential_id, requested_by, approved_by, reason, duration_minutes, \
+     status, requested_at, activated_at, expires_at, revoked_at";
+
+/// Break-glass exists for when normal sign-in is broken (e.g. an IdP outage), so these
+/// checks look at membership directly instead of going through SSO enforcement.
+async fn require_direct_membership(pool: &PgPool, org_id: Uuid, user_id: Uuid) -> ApiResult<OrgMembership> {
+    get_membership(pool, org_id, user_id)
+        .await?
+        .filter(|m| m.active)
+        .ok_or_else(|| ApiError::Forbidden("Not a member of this organization".to_string()))
+}
+
+async fn load_session(conn: &mut PgConnection, org_id: Uuid, session_id: Uuid) -> ApiResult<BreakGlassSession> {
+    sqlx::query_as::<_, BreakGlassSession>(&format!(
+        "SELECT {} FROM break_glass_sessions WHERE id = $1 AND org_id = $2 FOR UPDATE",
+        SESSION_COLUMNS
+    ))
+    .bind(session_id)
+    .bind(org_id)
+    .fetch_optional(conn)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Break-glass session not found".to_string()))
+}
+
+fn audit_entry(org_id: Uuid, actor_id: Uuid, action: &str, session_id: Uuid, details: serde_json::Value) -> AuditEntry<'_> {
+    AuditEntry {
+        org_id: Some(org_id),
+        actor_id: Some(actor_id),
+        action,
+        resource_type: "break_glass_session",
+        resource_id: Some(session_id.to_string()),
+        details,
+    }
+}
+
+/// Provision a sealed emergency credential (secret is shown once)
+/// POST /api/orgs/{org_id}/break-glass/credentials
+pub async fn create_credential(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<CreateBreakGlassCredentialRequest>,
+) -> ApiResult<HttpResponse> {
+    body.validate()?;
+    let org_id = path.into_inner();
+    require_org_role(pool.get_ref(), org_id, &user, "owner").await?;
+
+    let secret = format!("bg_{}", generate_random_hex(32));
+    let mut tx = pool.begin().await?;
+    let credential = sqlx::query_as::<_, BreakGlassCredential>(&format!(
+        "INSERT INTO break_glass_credentials (org_id, label, secret_hash, created_by) \
+         VALUES ($1, $2, $3, $4) RETURNING {}",
+        CREDENTIAL_COLUMNS
+    ))
+    .bind(org_id)
+    .bind(body.label.trim())
+    .bind(sha256_hash(secret.as_bytes()))
+    .bind(user.user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "break_glass.credential_created",
+            resource_type: "break_glass_credential",
+            resource_id: Some(credential.id.to_string()),
+            details: serde_json::json!({ "label": credential.label }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::created(serde_json::json!({
+        "credential": credential,
+        "secret": secret,
+    })))
+}
+
+/// List an org's emergency credentials
+/// GET /api/orgs/{org_id}/break-glass/credentials
+pub async fn list_credentials(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_role(pool.get_ref(), org_id, &user, "owner").await?;
+
+    let credentials = sqlx::query_as::<_, BreakGlassCredential>(&format!(
+        "SELECT {} FROM break_glass_credentials WHERE org_id = $1 ORDER BY created_at DESC",
+        CREDENTIAL_COLUMNS
+    ))
+    .bind(org_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(credentials))
+}
+
+/// Revoke an unused emergency credential
+/// DELETE /api/orgs/{org_id}/break-glass/credentials/{credential_id}
+pub async fn revoke_credential(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, credential_id) = path.into_inner();
+    require_org_role(pool.get_ref(), org_id, &user, "owner").await?;
+
+    let mut tx = pool.begin().await?;
+    let revoked = sqlx::query(
+        "UPDATE break_glass_credentials SET revoked_at = NOW() \
+         WHERE id = $1 AND org_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(credential_id)
+    .bind(org_id)
+    .execute(&mut *tx)
+    .await?;
+    if revoked.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Credential not found".to_string()));
+    }
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "break_glass.credential_revoked",
+            resource_type: "break_glass_credential",
+            resource_id: Some(credential_id.to_string()),
+            details: serde_json::json!({}),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(crate::errors::success_message("Credential revoked"))
+}
+
+/// Open a break-glass request with a sealed credential; a second person must approve it
+/// POST /api/orgs/{org_id}/break-glass/sessions
+pub async fn request_access(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<BreakGlassActivationRequest>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_direct_membership(pool.get_ref(), org_id, user.user_id).await?;
+    let duration = validate_activation(&body.reason, body.duration_minutes)?;
+
+    let credential_id: Uuid = match sqlx::query_scalar(
+        "SELECT id FROM break_glass_credentials \
+         WHERE org_id = $1 AND secret_hash = $2 AND used_at IS NULL AND revoked_at IS NULL",
+    )
+    .bind(org_id)
+    .bind(sha256_hash(body.secret.expose_secret().trim().as_bytes()))
+    .fetch_optional(pool.get_ref().as_ref())
+    .await?
+    {
+        Some(id) => id,
+        None => {
+            log_auth_event("break_glass_request", Some(&user.user_id.to_string()), false, Some("invalid credential"));
+            return Err(ApiError::Unauthorized("Invalid or already used break-glass credential".to_string()));
+        }
+    };
+
+    let mut tx = pool.begin().await?;
+    let session = sqlx::query_as::<_, BreakGlassSession>(&format!(
+        "INSERT INTO break_glass_sessions (org_id, credential_id, requested_by, reason, duration_minutes, expires_at) \
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING {}",
+        SESSION_COLUMNS
+    ))
+    .bind(org_id)
+    .bind(credential_id)
+    .bind(user.user_id)
+    .bind(body.reason.trim())
+    .bind(duration)
+    .bind(Utc::now() + Duration::minutes(PENDING_TTL_MINUTES))
+    .fetch_one(&mut *tx)
+    .await?;
+
+    audit_services::record(
+        &mut tx,
+        audit_entry(
+            org_id,
+            user.user_id,
+            "break_glass.requested",
+            session.id,
+            serde_json::json!({ "reason": session.reason, "duration_minutes": duration }),
+        ),
+    )
+    .await?;
+    notify_org_owners(
+        &mut tx,
+        org_id,
+        "break_glass",
+        "Break-glass access requested",
+        &format!("Emergency access was requested: {}", session.reason),
+        serde_json::json!({ "session_id": session.id, "requested_by": user.user_id }),
+    )
+    .await?;
+    tx.commit().await?;
+
+    log_auth_event("break_glass_request", Some(&user.user_id.to_string()), true, Some(&session.id.to_string()));
+
+    Ok(ApiResponse::created(session))
+}
+
+/// Second-person approval; starts the time-boxed session and consumes the credential
+/// POST /api/orgs/{org_id}/break-glass/sessions/{session_id}/approve
+pub async fn approve_session(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, session_id) = path.into_inner();
+    let approver = require_direct_membership(pool.get_ref(), org_id, user.user_id).await?;
+    if !role_at_least(&approver.role, "admin") {
+        return Err(ApiError::Forbidden("Organization admin role required to approve".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+    let session = load_session(&mut tx, org_id, session_id).await?;
+    if session.requested_by == user.user_id {
+        return Err(ApiError::Forbidden("Break-glass access must be approved by a second person".to_string()));
+    }
+    if session.status != "pending" || session.expires_at <= Utc::now() {
+        return Err(ApiError::Conflict("Break-glass request is no longer pending".to_string()));
+    }
+
+    let consumed = sqlx::query(
+        "UPDATE break_glass_credentials SET used_at = NOW() \
+         WHERE id = $1 AND used_at IS NULL AND revoked_at IS NULL",
+    )
+    .bind(session.credential_id)
+    .execute(&mut *tx)
+    .await?;
+    if consumed.rows_affected() == 0 {
+        return Err(ApiError::Conflict("Break-glass credential was revoked or already used".to_string()));
+    }
+
+    let session = sqlx::query_as::<_, BreakGlassSession>(&format!(
+        "UPDATE break_glass_sessions SET status = 'active', approved_by = $2, activated_at = NOW(), \
+         expires_at = NOW() + make_interval(mins => duration_minutes) \
+         WHERE id = $1 RETURNING {}",
+        SESSION_COLUMNS
+    ))
+    .bind(session_id)
+    .bind(user.user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    audit_services::record(
+        &mut tx,
+        audit_entry(
+            org_id,
+            user.user_id,
+            "break_glass.activated",
+            session.id,
+            serde_json::json!({ "requested_by": session.requested_by, "expires_at": session.expires_at }),
+        ),
+    )
+    .await?;
+    notify_org_owners(
+        &mut tx,
+        org_id,
+        "break_glass",
+        "Break-glass access activated",
+        &format!(
+            "Emergency superadmin access is active until {}. Reason: {}",
+            session.expires_at.to_rfc3339(),
+            session.reason
+        ),
+        serde_json::json!({
+            "session_id": session.id,
+            "requested_by": session.requested_by,
+            "approved_by": user.user_id,
+        }),
+    )
+    .await?;
+    notify_user(
+        &mut tx,
+        session.requested_by,
+        "break_glass",
+        "Break-glass request approved",
+        "Your emergency access request was approved; exchange it for a session token.",
+        serde_json::json!({ "session_id": session.id }),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::success(session))
+}
+
+/// Exchange an active session for a superadmin token that expires with it (requester only)
+/// POST /api/orgs/{org_id}/break-glass/sessions/{session_id}/token
+pub async fn issue_token(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, session_id) = path.into_inner();
+
+    let mut tx = pool.begin().await?;
+    let session = load_session(&mut tx, org_id, session_id).await?;
+    if session.requested_by != user.user_id {
+        return Err(ApiError::Forbidden("Only the requester can use this session".to_string()));
+    }
+    if session.status != "active" || session.expires_at <= Utc::now() {
+        return Err(ApiError::Conflict("Break-glass session is not active".to_string()));
+    }
+
+    let token = create_break_glass_token(
+        &user.user_id.to_string(),
+        session.id,
+        config.jwt_secret.expose_secret(),
+        session.expires_at.timestamp(),
+    )?;
+
+    audit_services::record(
+        &mut tx,
+        audit_entry(org_id, user.user_id, "break_glass.token_issued", session.id, serde_json::json!({})),
+    )
+    .await?;
+    tx.commit().await?;
+
+    log_auth_event("break_glass_token", Some(&user.user_id.to_string()), true, Some(&session.id.to_string()));
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "token": token,
+        "expires_at": session.expires_at,
+    })))
+}
+
+/// End a pending or active session early (requester or any org admin)
+/// POST /api/orgs/{org_id}/break-glass/sessions/{session_id}/revoke
+pub async fn revoke_session(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, session_id) = path.into_inner();
+    let membership = require_direct_membership(pool.get_ref(), org_id, user.user_id).await?;
+
+    let mut tx = pool.begin().await?;
+    let session = load_session(&mut tx, org_id, session_id).await?;
+    if session.requested_by != user.user_id && !role_at_least(&membership.role, "admin") {
+        return Err(ApiError::Forbidden("Organization admin role required".to_string()));
+    }
+    if session.status == "revoked" {
+        return Err(ApiError::Conflict("Break-glass session is already revoked".to_string()));
+    }
+
+    sqlx::query("UPDATE break_glass_sessions SET status = 'revoked', revoked_at = NOW() WHERE id = $1")
+        .bind(session_id)
+        .execute(&mut *tx)
+        .await?;
+
+    audit_services::record(
+        &mut tx,
+        audit_entry(org_id, user.user_id, "break_glass.revoked", session_id, serde_json::json!({})),
+    )
+    .await?;
+    notify_org_owners(
+        &mut tx,
+        org_id,
+        "break_glass",
+        "Break-glass access revoked",
+        "An emergency access session was ended.",
+        serde_json::json!({ "session_id": session_id, "revoked_by": user.user_id }),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(crate::errors::success_message("Break-glass session revoked"))
+}
+
+/// List break-glass sessions for review
+/// GET /api/orgs/{org_id}/break-glass/sessions
+pub async fn list_sessions(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ReadAuditLogs).await?;
+
+    let sessions = sqlx::query_as::<_, BreakGlassSession>(&format!(
+        "SELECT {} FROM break_glass_sessions WHERE org_id = $1 ORDER BY requested_at DESC LIMIT 100",
+        SESSION_COLUMNS
+    ))
+    .bind(org_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(sessions))
+}
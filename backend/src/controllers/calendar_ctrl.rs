@@ -0,0 +1,568 @@
+use actix_web::{http::header::LOCATION, web, HttpRequest, HttpResponse};
+use chrono::{Duration, Utc};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::calendar::{
+    CalendarFeed, CreateFeedRequest, CreateMaintenanceWindowRequest, CreateRotationRequest, FeedQuery,
+    GoogleCalendarConnection, GoogleCallbackQuery, GoogleConnectRequest, MaintenanceWindow, MaintenanceWindowQuery,
+    OnCallRotation, ShiftQuery, UpdateMaintenanceWindowRequest,
+};
+use crate::services::calendar_services::{
+    feed_url, render_feed, rotation_shifts, validate_rotation, validate_window, verify_feed_signature, FEED_COLUMNS,
+    FEED_KINDS, MAX_FEEDS_PER_USER, ROTATION_COLUMNS, WINDOW_COLUMNS,
+};
+use crate::services::device_services::get_owned_device;
+use crate::services::google_calendar_services::{
+    self, complete_connection, disconnect, notification_user, spawn_push_window, GoogleOAuth, CONNECTION_COLUMNS,
+    OAUTH_STATE_MINUTES,
+};
+use crate::services::key_services::KeyManager;
+use crate::utils::generate_random_hex;
+
+const DEFAULT_SHIFT_DAYS: i64 = 14;
+
+async fn owned_window(pool: &PgPool, window_id: Uuid, user_id: Uuid) -> ApiResult<MaintenanceWindow> {
+    sqlx::query_as::<_, MaintenanceWindow>(&format!(
+        "SELECT {} FROM maintenance_windows WHERE id = $1 AND user_id = $2",
+        WINDOW_COLUMNS
+    ))
+    .bind(window_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Maintenance window not found".to_string()))
+}
+
+async fn owned_rotation(pool: &PgPool, rotation_id: Uuid, user_id: Uuid) -> ApiResult<OnCallRotation> {
+    sqlx::query_as::<_, OnCallRotation>(&format!(
+        "SELECT {} FROM on_call_rotations WHERE id = $1 AND user_id = $2",
+        ROTATION_COLUMNS
+    ))
+    .bind(rotation_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Rotation not found".to_string()))
+}
+
+async fn owned_feed(pool: &PgPool, feed_id: Uuid, user_id: Uuid) -> ApiResult<CalendarFeed> {
+    sqlx::query_as::<_, CalendarFeed>(&format!(
+        "SELECT {} FROM calendar_feeds WHERE id = $1 AND user_id = $2",
+        FEED_COLUMNS
+    ))
+    .bind(feed_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Calendar feed not found".to_string()))
+}
+
+fn feed_secret(config: &AppConfig) -> ApiResult<&[u8]> {
+    config
+        .calendar_feed_secret
+        .as_ref()
+        .map(|s| s.expose_secret().as_bytes())
+        .ok_or_else(|| ApiError::ServiceUnavailable("Calendar feeds are not configured".to_string()))
+}
+
+fn feed_json(config: &AppConfig, secret: &[u8], feed: &CalendarFeed) -> serde_json::Value {
+    let mut value = serde_json::json!(feed);
+    value["url"] = serde_json::json!(feed_url(&config.api_base_url, secret, feed));
+    value
+}
+
+/// GET /api/calendar/maintenance?from=&to=&device_id=&include_cancelled=false
+pub async fn list_windows(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<MaintenanceWindowQuery>,
+) -> ApiResult<HttpResponse> {
+    let windows = sqlx::query_as::<_, MaintenanceWindow>(&format!(
+        "SELECT {} FROM maintenance_windows WHERE user_id = $1 \
+         AND ($2::timestamptz IS NULL OR ends_at > $2) AND ($3::timestamptz IS NULL OR starts_at < $3) \
+         AND ($4::uuid IS NULL OR device_id = $4 OR device_id IS NULL) AND ($5 OR status = 'scheduled') \
+         ORDER BY starts_at LIMIT 500",
+        WINDOW_COLUMNS
+    ))
+    .bind(user.user_id)
+    .bind(query.from)
+    .bind(query.to)
+    .bind(query.device_id)
+    .bind(query.include_cancelled.unwrap_or(false))
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(windows))
+}
+
+/// Schedule planned downtime for a device, or the whole fleet without `device_id`. It shows up
+/// in maintenance feeds and, when connected, in the owner's Google Calendar.
+/// POST /api/calendar/maintenance
+pub async fn create_window(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    keys: web::Data<Arc<dyn KeyManager>>,
+    body: web::Json<CreateMaintenanceWindowRequest>,
+) -> ApiResult<HttpResponse> {
+    let title = body.title.trim();
+    validate_window(title, body.starts_at, body.ends_at)?;
+    if let Some(device_id) = body.device_id {
+        get_owned_device(pool.get_ref(), device_id, user.user_id).await?;
+    }
+
+    let window = sqlx::query_as::<_, MaintenanceWindow>(&format!(
+        "INSERT INTO maintenance_windows (user_id, device_id, title, notes, starts_at, ends_at) \
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING {}",
+        WINDOW_COLUMNS
+    ))
+    .bind(user.user_id)
+    .bind(body.device_id)
+    .bind(title)
+    .bind(body.notes.as_deref().map(str::trim).filter(|n| !n.is_empty()))
+    .bind(body.starts_at)
+    .bind(body.ends_at)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    spawn_push_window(pool.get_ref().clone(), config.get_ref().clone(), keys.get_ref().clone(), window.id);
+    Ok(ApiResponse::created(window))
+}
+
+/// PATCH /api/calendar/maintenance/{window_id}
+pub async fn update_window(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    keys: web::Data<Arc<dyn KeyManager>>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateMaintenanceWindowRequest>,
+) -> ApiResult<HttpResponse> {
+    let window = owned_window(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    if window.status == "cancelled" {
+        return Err(ApiError::Conflict("Cancelled maintenance windows cannot be changed".to_string()));
+    }
+    let title = body.title.as_deref().map(str::trim).unwrap_or(&window.title);
+    let starts_at = body.starts_at.unwrap_or(window.starts_at);
+    let ends_at = body.ends_at.unwrap_or(window.ends_at);
+    validate_window(title, starts_at, ends_at)?;
+    let notes = match &body.notes {
+        Some(notes) => Some(notes.trim()).filter(|n| !n.is_empty()),
+        None => window.notes.as_deref(),
+    };
+
+    let window = sqlx::query_as::<_, MaintenanceWindow>(&format!(
+        "UPDATE maintenance_windows SET title = $2, notes = $3, starts_at = $4, ends_at = $5, \
+         sequence = sequence + 1, updated_via = 'api', updated_at = NOW() WHERE id = $1 RETURNING {}",
+        WINDOW_COLUMNS
+    ))
+    .bind(window.id)
+    .bind(title)
+    .bind(notes)
+    .bind(starts_at)
+    .bind(ends_at)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    spawn_push_window(pool.get_ref().clone(), config.get_ref().clone(), keys.get_ref().clone(), window.id);
+    Ok(ApiResponse::success(window))
+}
+
+/// Cancel a window; feeds keep it, marked cancelled, so subscribed calendars drop it
+/// DELETE /api/calendar/maintenance/{window_id}
+pub async fn cancel_window(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    keys: web::Data<Arc<dyn KeyManager>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let window = owned_window(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let updated = sqlx::query(
+        "UPDATE maintenance_windows SET status = 'cancelled', sequence = sequence + 1, updated_via = 'api', \
+         updated_at = NOW() WHERE id = $1 AND status <> 'cancelled'",
+    )
+    .bind(window.id)
+    .execute(pool.get_ref().as_ref())
+    .await?;
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::Conflict("Maintenance window is already cancelled".to_string()));
+    }
+
+    spawn_push_window(pool.get_ref().clone(), config.get_ref().clone(), keys.get_ref().clone(), window.id);
+    Ok(crate::errors::success_message("Maintenance window cancelled"))
+}
+
+/// GET /api/calendar/on-call
+pub async fn list_rotations(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let rotations = sqlx::query_as::<_, OnCallRotation>(&format!(
+        "SELECT {} FROM on_call_rotations WHERE user_id = $1 ORDER BY name",
+        ROTATION_COLUMNS
+    ))
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    let now = Utc::now();
+    let rotations: Vec<serde_json::Value> = rotations
+        .iter()
+        .map(|rotation| {
+            let current = rotation_shifts(rotation, now, now + Duration::seconds(1)).into_iter().next();
+            let mut value = serde_json::json!(rotation);
+            value["on_call"] = serde_json::json!(current);
+            value
+        })
+        .collect();
+    Ok(ApiResponse::success(rotations))
+}
+
+/// Members take `shift_hours` shifts in the given order, starting at `starts_at`
+/// POST /api/calendar/on-call
+pub async fn create_rotation(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<CreateRotationRequest>,
+) -> ApiResult<HttpResponse> {
+    let name = body.name.trim();
+    let members: Vec<String> = body.members.iter().map(|m| m.trim().to_string()).collect();
+    validate_rotation(name, &members, body.shift_hours)?;
+
+    let rotation = sqlx::query_as::<_, OnCallRotation>(&format!(
+        "INSERT INTO on_call_rotations (user_id, name, members, shift_hours, starts_at) \
+         VALUES ($1, $2, $3, $4, $5) RETURNING {}",
+        ROTATION_COLUMNS
+    ))
+    .bind(user.user_id)
+    .bind(name)
+    .bind(&members)
+    .bind(body.shift_hours)
+    .bind(body.starts_at)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::created(rotation))
+}
+
+/// GET /api/calendar/on-call/{rotation_id}/shifts?from=&to=
+pub async fn list_shifts(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    query: web::Query<ShiftQuery>,
+) -> ApiResult<HttpResponse> {
+    let rotation = owned_rotation(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let from = query.from.unwrap_or_else(Utc::now);
+    let to = query.to.unwrap_or(from + Duration::days(DEFAULT_SHIFT_DAYS));
+    if to <= from {
+        return Err(ApiError::ValidationError("`from` must be before `to`".to_string()));
+    }
+
+    Ok(ApiResponse::success(rotation_shifts(&rotation, from, to)))
+}
+
+/// DELETE /api/calendar/on-call/{rotation_id}
+pub async fn delete_rotation(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let deleted = sqlx::query("DELETE FROM on_call_rotations WHERE id = $1 AND user_id = $2")
+        .bind(path.into_inner())
+        .bind(user.user_id)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Rotation not found".to_string()));
+    }
+
+    Ok(crate::errors::success_message("Rotation deleted"))
+}
+
+/// GET /api/calendar/feeds
+pub async fn list_feeds(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+) -> ApiResult<HttpResponse> {
+    let secret = feed_secret(&config)?;
+    let feeds = sqlx::query_as::<_, CalendarFeed>(&format!(
+        "SELECT {} FROM calendar_feeds WHERE user_id = $1 ORDER BY created_at",
+        FEED_COLUMNS
+    ))
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    let feeds: Vec<serde_json::Value> = feeds.iter().map(|feed| feed_json(&config, secret, feed)).collect();
+    Ok(ApiResponse::success(feeds))
+}
+
+/// Create a subscribable feed of maintenance windows (optionally for one device) or on-call
+/// shifts (optionally for one rotation). Anyone holding the returned URL can read the feed.
+/// POST /api/calendar/feeds
+pub async fn create_feed(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<CreateFeedRequest>,
+) -> ApiResult<HttpResponse> {
+    let secret = feed_secret(&config)?;
+    let kind = body.kind.as_str();
+    if !FEED_KINDS.contains(&kind) {
+        return Err(ApiError::ValidationError(format!("kind must be one of {}", FEED_KINDS.join(", "))));
+    }
+    match (kind, body.device_id, body.rotation_id) {
+        ("maintenance", device_id, None) => {
+            if let Some(device_id) = device_id {
+                get_owned_device(pool.get_ref(), device_id, user.user_id).await?;
+            }
+        }
+        ("on_call", None, rotation_id) => {
+            if let Some(rotation_id) = rotation_id {
+                owned_rotation(pool.get_ref(), rotation_id, user.user_id).await?;
+            }
+        }
+        _ => {
+            return Err(ApiError::ValidationError(
+                "device_id applies to maintenance feeds and rotation_id to on_call feeds".to_string(),
+            ));
+        }
+    }
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM calendar_feeds WHERE user_id = $1")
+        .bind(user.user_id)
+        .fetch_one(pool.get_ref().as_ref())
+        .await?;
+    if count >= MAX_FEEDS_PER_USER {
+        return Err(ApiError::ValidationError(format!(
+            "At most {} calendar feeds per account",
+            MAX_FEEDS_PER_USER
+        )));
+    }
+
+    let feed = sqlx::query_as::<_, CalendarFeed>(&format!(
+        "INSERT INTO calendar_feeds (user_id, kind, device_id, rotation_id) VALUES ($1, $2, $3, $4) RETURNING {}",
+        FEED_COLUMNS
+    ))
+    .bind(user.user_id)
+    .bind(kind)
+    .bind(body.device_id)
+    .bind(body.rotation_id)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::created(feed_json(&config, secret, &feed)))
+}
+
+/// Issue a new URL for a feed; the old one stops working immediately
+/// POST /api/calendar/feeds/{feed_id}/rotate
+pub async fn rotate_feed_url(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let secret = feed_secret(&config)?;
+    let feed = owned_feed(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let feed = sqlx::query_as::<_, CalendarFeed>(&format!(
+        "UPDATE calendar_feeds SET url_version = url_version + 1 WHERE id = $1 RETURNING {}",
+        FEED_COLUMNS
+    ))
+    .bind(feed.id)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(feed_json(&config, secret, &feed)))
+}
+
+/// DELETE /api/calendar/feeds/{feed_id}
+pub async fn delete_feed(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let deleted = sqlx::query("DELETE FROM calendar_feeds WHERE id = $1 AND user_id = $2")
+        .bind(path.into_inner())
+        .bind(user.user_id)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Calendar feed not found".to_string()));
+    }
+
+    Ok(crate::errors::success_message("Calendar feed deleted"))
+}
+
+/// The iCal document calendar apps poll. Authenticated by the URL signature alone; unknown
+/// feeds and bad signatures are indistinguishable.
+/// GET /api/calendar/feeds/{feed_id}.ics?sig=
+pub async fn get_feed_ics(
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+    query: web::Query<FeedQuery>,
+) -> ApiResult<HttpResponse> {
+    let secret = feed_secret(&config)?;
+    let not_found = || ApiError::NotFound("Calendar feed not found".to_string());
+    let feed = sqlx::query_as::<_, CalendarFeed>(&format!("SELECT {} FROM calendar_feeds WHERE id = $1", FEED_COLUMNS))
+        .bind(path.into_inner())
+        .fetch_optional(pool.get_ref().as_ref())
+        .await?
+        .ok_or_else(not_found)?;
+    if !verify_feed_signature(secret, &feed, query.sig.as_deref().unwrap_or_default()) {
+        return Err(not_found());
+    }
+
+    let calendar = render_feed(pool.get_ref(), &feed).await?;
+    sqlx::query("UPDATE calendar_feeds SET last_fetched_at = NOW() WHERE id = $1")
+        .bind(feed.id)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/calendar; charset=utf-8")
+        .insert_header(("Cache-Control", "private, max-age=300"))
+        .body(calendar))
+}
+
+/// GET /api/calendar/google
+pub async fn get_google_connection(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let connection = sqlx::query_as::<_, GoogleCalendarConnection>(&format!(
+        "SELECT {} FROM google_calendar_connections WHERE user_id = $1",
+        CONNECTION_COLUMNS
+    ))
+    .bind(user.user_id)
+    .fetch_optional(pool.get_ref().as_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Google Calendar is not connected".to_string()))?;
+
+    Ok(ApiResponse::success(connection))
+}
+
+/// Start connecting a Google Calendar; the client sends the user to the returned consent URL.
+/// Reconnecting replaces the previous calendar.
+/// POST /api/calendar/google/connect
+pub async fn connect_google(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: Option<web::Json<GoogleConnectRequest>>,
+) -> ApiResult<HttpResponse> {
+    let oauth = GoogleOAuth::from_config(&config)
+        .ok_or_else(|| ApiError::ServiceUnavailable("Google Calendar sync is not configured".to_string()))?;
+    let calendar_id = body
+        .as_ref()
+        .and_then(|b| b.calendar_id.as_deref())
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .unwrap_or("primary");
+    if calendar_id.len() > 255 {
+        return Err(ApiError::ValidationError("calendar_id must be at most 255 characters".to_string()));
+    }
+
+    let state = generate_random_hex(24);
+    sqlx::query(
+        "INSERT INTO google_calendar_connections (user_id, calendar_id, oauth_state, oauth_state_expires_at) \
+         VALUES ($1, $2, $3, $4) ON CONFLICT (user_id) DO UPDATE SET \
+             calendar_id = EXCLUDED.calendar_id, oauth_state = EXCLUDED.oauth_state, \
+             oauth_state_expires_at = EXCLUDED.oauth_state_expires_at",
+    )
+    .bind(user.user_id)
+    .bind(calendar_id)
+    .bind(&state)
+    .bind(Utc::now() + Duration::minutes(OAUTH_STATE_MINUTES))
+    .execute(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "authorization_url": oauth.authorization_url(&state),
+        "expires_in": OAUTH_STATE_MINUTES * 60,
+    })))
+}
+
+/// Google's consent redirect; sends the browser back to the app's calendar settings
+/// GET /api/calendar/google/callback?code=&state=
+pub async fn google_callback(
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    keys: web::Data<Arc<dyn KeyManager>>,
+    query: web::Query<GoogleCallbackQuery>,
+) -> ApiResult<HttpResponse> {
+    let outcome = match (&query.code, &query.error) {
+        (Some(code), None) => {
+            match complete_connection(pool.get_ref(), &config, keys.get_ref().as_ref(), &query.state, code).await {
+                Ok(user_id) => {
+                    tracing::info!(%user_id, "Connected Google Calendar");
+                    "connected"
+                }
+                Err(e) => {
+                    tracing::warn!("Connecting Google Calendar failed: {}", e);
+                    "failed"
+                }
+            }
+        }
+        _ => "denied",
+    };
+
+    let location = format!("{}/settings/calendar?google={}", config.frontend_url.trim_end_matches('/'), outcome);
+    Ok(HttpResponse::Found().insert_header((LOCATION, location)).finish())
+}
+
+/// DELETE /api/calendar/google
+pub async fn disconnect_google(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    keys: web::Data<Arc<dyn KeyManager>>,
+) -> ApiResult<HttpResponse> {
+    if !disconnect(pool.get_ref(), &config, keys.get_ref().as_ref(), user.user_id).await? {
+        return Err(ApiError::NotFound("Google Calendar is not connected".to_string()));
+    }
+
+    Ok(crate::errors::success_message("Google Calendar disconnected"))
+}
+
+/// Google Calendar push notification. It carries no event data, only that the watched calendar
+/// changed; the changes are fetched in the background so Google gets a prompt answer.
+/// POST /api/calendar/google/notifications
+pub async fn google_notification(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    keys: web::Data<Arc<dyn KeyManager>>,
+) -> ApiResult<HttpResponse> {
+    let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    let channel_id: Uuid = header("X-Goog-Channel-ID")
+        .parse()
+        .map_err(|_| ApiError::BadRequest("Missing or invalid X-Goog-Channel-ID".to_string()))?;
+    let Some(user_id) = notification_user(pool.get_ref(), channel_id, header("X-Goog-Channel-Token")).await? else {
+        return Err(ApiError::Unauthorized("Unknown notification channel".to_string()));
+    };
+
+    // `sync` only confirms the channel was opened
+    if header("X-Goog-Resource-State") != "sync" {
+        let (pool, config, keys) = (pool.get_ref().clone(), config.get_ref().clone(), keys.get_ref().clone());
+        tokio::spawn(async move {
+            match google_calendar_services::sync_user(&pool, &config, keys.as_ref(), user_id).await {
+                Ok(applied) if applied > 0 => {
+                    tracing::info!(%user_id, applied, "Applied Google Calendar changes to maintenance windows")
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!(%user_id, "Google Calendar sync failed: {}", e),
+            }
+        });
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
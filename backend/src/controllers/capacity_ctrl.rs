@@ -0,0 +1,44 @@
+use actix_web::{http::header::CONTENT_DISPOSITION, web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::org::CapacityQuery;
+use crate::services::audit_services::to_csv;
+use crate::services::capacity_services::{self, report_rows, REPORT_CSV_HEADERS};
+use crate::services::org_services::require_org_permission;
+use crate::services::policy_services::OrgAction;
+use crate::services::region_services::require_org_filter;
+
+/// Project fleet utilization, charging peaks and maintenance load 30 and 90 days ahead
+/// GET /api/orgs/{org_id}/capacity-report?format=json|csv&chargers=N&region_id=
+pub async fn get_capacity_report(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    query: web::Query<CapacityQuery>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ReadDeviceHistory).await?;
+
+    let format = query.format.as_deref().unwrap_or("json");
+    if !matches!(format, "json" | "csv") {
+        return Err(ApiError::ValidationError(format!("Unknown report format: {}", format)));
+    }
+
+    let region_id = require_org_filter(pool.get_ref(), org_id, query.region_id).await?;
+    let report = capacity_services::capacity_report(pool.get_ref(), org_id, region_id, query.chargers).await?;
+    if format == "json" {
+        return Ok(ApiResponse::success(report));
+    }
+
+    let body = to_csv(REPORT_CSV_HEADERS, report_rows(&report))?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv")
+        .insert_header((
+            CONTENT_DISPOSITION,
+            format!("attachment; filename=\"capacity-{}.csv\"", report.generated_at.format("%Y-%m-%d")),
+        ))
+        .body(body))
+}
@@ -0,0 +1,140 @@
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::services::crypto_services::BlockchainService;
+use crate::services::gas_services;
+use crate::services::rate_services::{self, DEFAULT_CURRENCY};
+
+#[derive(Debug, Deserialize)]
+pub struct ChainBalanceQuery {
+    /// Defaults to the caller's linked wallet
+    pub address: Option<String>,
+    /// Fiat currency to value the balances in as well, e.g. `eur`
+    pub currency: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RatesQuery {
+    /// Comma-separated token symbols; every priced asset when absent
+    pub symbols: Option<String>,
+    pub currency: Option<String>, // usd (default)
+}
+
+/// The chains payments and wallets may use, with whether each can be queried
+/// GET /api/blockchain/chains
+pub async fn list_chains(config: web::Data<AppConfig>) -> ApiResult<HttpResponse> {
+    let chains: Vec<serde_json::Value> = config
+        .chains
+        .iter()
+        .map(|chain| {
+            let mut entry = serde_json::to_value(chain).unwrap_or_default();
+            entry["available"] = chain.has_provider().into();
+            entry["default"] = (chain.chain_id == config.default_chain_id).into();
+            entry
+        })
+        .collect();
+    Ok(ApiResponse::success(chains))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GasQuery {
+    /// Defaults to the configured default chain
+    pub chain_id: Option<u64>,
+}
+
+/// Next block's base fee, slow/standard/fast priority fees and what a token transfer costs at each
+/// GET /api/blockchain/gas
+pub async fn get_gas(config: web::Data<AppConfig>, query: web::Query<GasQuery>) -> ApiResult<HttpResponse> {
+    let chain_id = query.chain_id.unwrap_or(config.default_chain_id);
+    let chain = BlockchainService::for_chain_id(&config, chain_id)?;
+    if !chain.has_provider() {
+        return Err(ApiError::ServiceUnavailable(format!("Chain {} has no RPC provider", chain_id)));
+    }
+
+    let estimate = gas_services::gas_estimate(&chain).await?;
+    Ok(ApiResponse::success(estimate))
+}
+
+/// Native coin and, when the chain has a token contract, token balance of an address
+/// GET /api/blockchain/chains/{chain_id}/balance
+pub async fn get_balance(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<u64>,
+    query: web::Query<ChainBalanceQuery>,
+) -> ApiResult<HttpResponse> {
+    let chain = BlockchainService::for_chain_id(&config, path.into_inner())?;
+    let address = match query.address.as_deref().map(str::trim) {
+        Some(address) => address.to_string(),
+        None => sqlx::query_scalar::<_, Option<String>>("SELECT wallet_address FROM users WHERE id = $1")
+            .bind(user.user_id)
+            .fetch_optional(pool.get_ref().as_ref())
+            .await?
+            .flatten()
+            .ok_or_else(|| ApiError::ValidationError("No wallet linked; pass an address".to_string()))?,
+    };
+
+    let native = chain.get_native_balance(&address).await?;
+    let token = if chain.is_configured() { Some(chain.get_token_balance(&address).await?) } else { None };
+
+    // Prices are a nicety; the balances stand without them
+    let fiat = match query.currency.as_deref() {
+        Some(currency) => {
+            let mut symbols = vec![native.symbol.clone()];
+            symbols.extend(token.as_ref().map(|t| t.symbol.clone()));
+            match rate_services::rates(&config, &symbols, currency).await {
+                Ok(rates) => Some(serde_json::json!({
+                    "currency": rates.currency,
+                    "native": rates.value_of(&native.symbol, &native.balance),
+                    "token": token.as_ref().and_then(|t| rates.value_of(&t.symbol, &t.balance)),
+                    "stale": rates.stale,
+                })),
+                Err(ApiError::ValidationError(message)) => return Err(ApiError::ValidationError(message)),
+                Err(e) => {
+                    tracing::warn!("Balances are shown without fiat values: {}", e);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+    Ok(ApiResponse::success(serde_json::json!({ "native": native, "token": token, "fiat": fiat })))
+}
+
+/// Fiat prices of tokens, cached for a minute
+/// GET /api/blockchain/rates?symbols=ETH,POL&currency=eur
+pub async fn get_rates(config: web::Data<AppConfig>, query: web::Query<RatesQuery>) -> ApiResult<HttpResponse> {
+    let symbols: Vec<String> = query
+        .symbols
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    let rates =
+        rate_services::rates(&config, &symbols, query.currency.as_deref().unwrap_or(DEFAULT_CURRENCY)).await?;
+    Ok(ApiResponse::success(rates))
+}
+
+/// Where a transaction stands on a given chain
+/// GET /api/blockchain/chains/{chain_id}/transactions/{tx_hash}
+pub async fn verify_transaction(
+    _user: AuthenticatedUser,
+    config: web::Data<AppConfig>,
+    path: web::Path<(u64, String)>,
+) -> ApiResult<HttpResponse> {
+    let (chain_id, tx_hash) = path.into_inner();
+    let chain = BlockchainService::for_chain_id(&config, chain_id)?;
+    if !chain.has_provider() {
+        return Err(ApiError::ServiceUnavailable(format!("Chain {} has no RPC provider", chain_id)));
+    }
+
+    let status = chain.verify_transaction(&tx_hash).await?;
+    Ok(ApiResponse::success(serde_json::json!({ "chain_id": chain_id, "transaction": status })))
+}
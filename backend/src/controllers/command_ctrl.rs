@@ -0,0 +1,300 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures::StreamExt;
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::{AuthenticatedDevice, AuthenticatedUser};
+use crate::models::device::{
+    CommandAckRequest, CommandBatchRequest, DeviceCommand, DeviceCommandRecord, PendingCommandsQuery,
+    UpdateTransportRequest,
+};
+use crate::services::command_services;
+use crate::services::device_services::get_owned_device;
+use crate::services::mission_services;
+use crate::services::robotics_services::RoboticsService;
+use crate::services::transport_services::{TransportKind, TransportRegistry, MAX_LONG_POLL_SECS};
+
+const COMMAND_COLUMNS: &str = "id, device_id, user_id, command, parameters, status, estimated_duration_ms, \
+     estimated_battery_drain, actual_duration_ms, actual_battery_drain, error, path_id, mission_leg_id, macro_id, \
+     batch_id, sequence, created_at, acked_at";
+
+/// Only admins may dispatch past cross-device constraints
+fn require_override_permission(user: &AuthenticatedUser, override_constraints: bool) -> ApiResult<()> {
+    if override_constraints && user.claims.role.as_deref() != Some("admin") {
+        return Err(ApiError::Forbidden("Only admins can override command constraints".to_string()));
+    }
+    Ok(())
+}
+
+/// Validate a command, record it with its estimates and push it through the device's transport.
+/// With `dry_run` the command is only checked and estimated, and the response describes what would happen.
+/// Commands blocked by a cross-device constraint are refused with the constraints that failed.
+/// POST /api/robotics/devices/{device_id}/command
+pub async fn send_command(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    transports: web::Data<Arc<TransportRegistry>>,
+    path: web::Path<Uuid>,
+    body: web::Json<DeviceCommand>,
+) -> ApiResult<HttpResponse> {
+    require_override_permission(&user, body.override_constraints)?;
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    if body.dry_run {
+        let preview = command_services::preview_command(
+            pool.get_ref(),
+            user.user_id,
+            &device,
+            &body.command,
+            &body.parameters,
+            body.geofence.as_ref(),
+            body.override_constraints,
+        )
+        .await?;
+        return Ok(ApiResponse::success(preview));
+    }
+
+    let result = command_services::issue_command(
+        pool.get_ref(),
+        transports.get_ref(),
+        user.user_id,
+        &device,
+        &body.command,
+        &body.parameters,
+        body.override_constraints,
+    )
+    .await?;
+
+    Ok(ApiResponse::success(result))
+}
+
+/// Enqueue an ordered list of commands. Every command is validated before any is recorded and
+/// they are stored in one transaction, so the batch is accepted whole or rejected whole.
+/// POST /api/robotics/devices/{device_id}/commands/batch
+pub async fn send_command_batch(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    transports: web::Data<Arc<TransportRegistry>>,
+    path: web::Path<Uuid>,
+    body: web::Json<CommandBatchRequest>,
+) -> ApiResult<HttpResponse> {
+    require_override_permission(&user, body.override_constraints)?;
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let batch = command_services::issue_batch(
+        pool.get_ref(),
+        transports.get_ref(),
+        user.user_id,
+        &device,
+        &body.commands,
+        body.override_constraints,
+    )
+    .await?;
+
+    Ok(ApiResponse::created(batch))
+}
+
+/// Command history for a device, with estimated and reported execution figures
+/// GET /api/robotics/devices/{device_id}/commands
+pub async fn list_commands(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+
+    let commands = sqlx::query_as::<_, DeviceCommandRecord>(&format!(
+        "SELECT {} FROM device_commands WHERE device_id = $1 ORDER BY created_at DESC LIMIT 100",
+        COMMAND_COLUMNS
+    ))
+    .bind(device.id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(commands))
+}
+
+/// Device-reported execution result for a command (device-authenticated)
+/// POST /api/robotics/devices/{device_id}/commands/{command_id}/ack
+pub async fn ack_command(
+    device: AuthenticatedDevice,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+    body: web::Json<CommandAckRequest>,
+) -> ApiResult<HttpResponse> {
+    let (device_id, command_id) = path.into_inner();
+    if device.device_id != device_id {
+        return Err(ApiError::Forbidden("Device key does not match this device".to_string()));
+    }
+    RoboticsService::new().validate_ack(&body)?;
+
+    let mut tx = pool.begin().await?;
+
+    let command = sqlx::query_as::<_, DeviceCommandRecord>(&format!(
+        "UPDATE device_commands SET status = $3, actual_duration_ms = $4, actual_battery_drain = $5, \
+         error = $6, acked_at = NOW() \
+         WHERE id = $1 AND device_id = $2 AND acked_at IS NULL RETURNING {}",
+        COMMAND_COLUMNS
+    ))
+    .bind(command_id)
+    .bind(device_id)
+    .bind(if body.success { "succeeded" } else { "failed" })
+    .bind(body.actual_duration_ms as i64)
+    .bind(body.actual_battery_drain)
+    .bind(body.error.as_deref().map(str::trim))
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let command = match command {
+        Some(command) => command,
+        None => {
+            let exists: bool = sqlx::query_scalar(
+                "SELECT EXISTS (SELECT 1 FROM device_commands WHERE id = $1 AND device_id = $2)",
+            )
+            .bind(command_id)
+            .bind(device_id)
+            .fetch_one(&mut *tx)
+            .await?;
+            return Err(if exists {
+                ApiError::Conflict("Command has already been acknowledged".to_string())
+            } else {
+                ApiError::NotFound("Command not found".to_string())
+            });
+        }
+    };
+
+    sqlx::query("UPDATE devices SET last_seen = NOW() WHERE id = $1")
+        .bind(device_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    // The ack has landed either way; a handoff that can't proceed yet is retried via /advance
+    if let Some(leg_id) = command.mission_leg_id
+        && let Err(e) = mission_services::record_leg_ack(pool.get_ref(), leg_id).await
+    {
+        tracing::warn!(%leg_id, error = %e, "Could not advance fleet mission after command ack");
+    }
+
+    Ok(ApiResponse::success(command))
+}
+
+/// Choose how commands reach a device
+/// PATCH /api/robotics/devices/{device_id}/transport
+pub async fn update_transport(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    transports: web::Data<Arc<TransportRegistry>>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateTransportRequest>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let kind = TransportKind::parse(body.transport.trim())?;
+    // Refuse a transport this instance can't dispatch through
+    transports.get(kind)?;
+
+    sqlx::query("UPDATE devices SET transport = $2 WHERE id = $1")
+        .bind(device.id)
+        .bind(kind.as_str())
+        .execute(pool.get_ref().as_ref())
+        .await?;
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "device_id": device.id,
+        "transport": kind.as_str(),
+    })))
+}
+
+async fn pending_commands(pool: &PgPool, device_id: Uuid) -> ApiResult<Vec<DeviceCommandRecord>> {
+    Ok(sqlx::query_as::<_, DeviceCommandRecord>(&format!(
+        "SELECT {} FROM device_commands \
+         WHERE device_id = $1 AND acked_at IS NULL AND (not_before IS NULL OR not_before <= NOW()) \
+         ORDER BY created_at, sequence NULLS FIRST LIMIT 100",
+        COMMAND_COLUMNS
+    ))
+    .bind(device_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Commands awaiting execution (device-authenticated). With `?wait=N` the request is held
+/// open up to N seconds until a command is dispatched.
+/// GET /api/robotics/devices/{device_id}/commands/pending
+pub async fn poll_commands(
+    device: AuthenticatedDevice,
+    pool: web::Data<Arc<PgPool>>,
+    transports: web::Data<Arc<TransportRegistry>>,
+    path: web::Path<Uuid>,
+    query: web::Query<PendingCommandsQuery>,
+) -> ApiResult<HttpResponse> {
+    let device_id = path.into_inner();
+    if device.device_id != device_id {
+        return Err(ApiError::Forbidden("Device key does not match this device".to_string()));
+    }
+    let wait = query.wait.unwrap_or(0).min(MAX_LONG_POLL_SECS);
+
+    // Register interest before querying so a dispatch in between isn't missed
+    let waiter = transports.hub.waiter(device_id);
+    let dispatched = waiter.notified();
+
+    let mut commands = pending_commands(pool.get_ref(), device_id).await?;
+    if commands.is_empty() && wait > 0 && tokio::time::timeout(Duration::from_secs(wait), dispatched).await.is_ok() {
+        commands = pending_commands(pool.get_ref(), device_id).await?;
+    }
+
+    sqlx::query("UPDATE devices SET last_seen = NOW() WHERE id = $1")
+        .bind(device_id)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+
+    Ok(ApiResponse::success(commands))
+}
+
+/// Command push channel for devices on the WebSocket transport (device-authenticated).
+/// Commands arrive as JSON text frames; acks still go to the ack endpoint.
+/// GET /api/robotics/devices/{device_id}/commands/ws
+pub async fn command_socket(
+    device: AuthenticatedDevice,
+    transports: web::Data<Arc<TransportRegistry>>,
+    path: web::Path<Uuid>,
+    req: HttpRequest,
+    stream: web::Payload,
+) -> ApiResult<HttpResponse> {
+    let device_id = path.into_inner();
+    if device.device_id != device_id {
+        return Err(ApiError::Forbidden("Device key does not match this device".to_string()));
+    }
+
+    let (response, mut session, mut incoming) = actix_ws::handle(&req, stream)
+        .map_err(|e| ApiError::BadRequest(format!("WebSocket upgrade failed: {}", e)))?;
+    let hub = transports.hub.clone();
+    let mut outgoing = hub.connect(device_id);
+
+    actix_web::rt::spawn(async move {
+        loop {
+            tokio::select! {
+                Some(message) = outgoing.recv() => {
+                    if session.text(message).await.is_err() {
+                        break;
+                    }
+                }
+                frame = incoming.next() => match frame {
+                    Some(Ok(actix_ws::Message::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Ok(actix_ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                },
+                else => break,
+            }
+        }
+        drop(outgoing);
+        hub.disconnect(device_id);
+        let _ = session.close(None).await;
+    });
+
+    Ok(response)
+}
@@ -0,0 +1,247 @@
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use validator::Validate;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::{AdminUser, OptionalUser};
+use crate::models::compliance::{
+    AllowlistQuery, CreateAllowlistRequest, GeoAllowlistEntry, GeoBlockAppealRequest, ReviewAllowlistRequest,
+};
+use crate::models::org::AuditLog;
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::compliance_services::{DEFAULT_OVERRIDE_DAYS, GEO_BLOCKED_ACTION, MAX_OVERRIDE_DAYS};
+
+const ALLOWLIST_COLUMNS: &str = "id, ip, user_id, email, reason, blocked_audit_id, status, reviewed_by, \
+     reviewed_at, expires_at, created_at";
+
+fn override_expiry(days: Option<i64>) -> ApiResult<chrono::DateTime<Utc>> {
+    let days = days.unwrap_or(DEFAULT_OVERRIDE_DAYS);
+    if !(1..=MAX_OVERRIDE_DAYS).contains(&days) {
+        return Err(ApiError::ValidationError(format!(
+            "expires_in_days must be between 1 and {}",
+            MAX_OVERRIDE_DAYS
+        )));
+    }
+    Ok(Utc::now() + Duration::days(days))
+}
+
+/// Ask for a blocked request to be reviewed. Creates a pending override for the IP
+/// (and user) recorded with the block, which an admin approves or rejects.
+/// POST /api/compliance/appeals
+pub async fn submit_appeal(
+    user: OptionalUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<GeoBlockAppealRequest>,
+) -> ApiResult<HttpResponse> {
+    body.validate()?;
+
+    let block = sqlx::query_as::<_, AuditLog>(
+        "SELECT id, org_id, actor_id, action, resource_type, resource_id, details, created_at \
+         FROM audit_logs WHERE id = $1 AND action = $2",
+    )
+    .bind(body.reference)
+    .bind(GEO_BLOCKED_ACTION)
+    .fetch_optional(pool.get_ref().as_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Unknown block reference".to_string()))?;
+
+    // A signed-in requester may only appeal their own or anonymous blocks
+    if let (Some(requester), Some(actor)) = (user.0.as_ref(), block.actor_id)
+        && requester.user_id != actor
+    {
+        return Err(ApiError::NotFound("Unknown block reference".to_string()));
+    }
+
+    let already: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM geo_block_allowlist WHERE blocked_audit_id = $1)")
+        .bind(block.id)
+        .fetch_one(pool.get_ref().as_ref())
+        .await?;
+    if already {
+        return Err(ApiError::Conflict("A review has already been requested for this reference".to_string()));
+    }
+
+    let ip = block.details.get("ip").and_then(|v| v.as_str());
+    let user_id = block.actor_id;
+    if ip.is_none() && user_id.is_none() {
+        return Err(ApiError::BadRequest("This block cannot be overridden".to_string()));
+    }
+
+    let entry = sqlx::query_as::<_, GeoAllowlistEntry>(&format!(
+        "INSERT INTO geo_block_allowlist (ip, user_id, email, reason, blocked_audit_id) \
+         VALUES ($1, $2, $3, $4, $5) RETURNING {}",
+        ALLOWLIST_COLUMNS
+    ))
+    .bind(ip)
+    .bind(user_id)
+    .bind(body.email.trim())
+    .bind(body.reason.trim())
+    .bind(block.id)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::created(serde_json::json!({
+        "id": entry.id,
+        "status": entry.status,
+    })))
+}
+
+/// Recent refused requests
+/// GET /api/admin/geo-blocks
+pub async fn list_blocked_attempts(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let blocks = sqlx::query_as::<_, AuditLog>(
+        "SELECT id, org_id, actor_id, action, resource_type, resource_id, details, created_at \
+         FROM audit_logs WHERE action = $1 ORDER BY created_at DESC LIMIT 200",
+    )
+    .bind(GEO_BLOCKED_ACTION)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(blocks))
+}
+
+/// Overrides, optionally filtered by status (e.g. `?status=pending` for the review queue)
+/// GET /api/admin/geo-allowlist
+pub async fn list_allowlist(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<AllowlistQuery>,
+) -> ApiResult<HttpResponse> {
+    let entries = sqlx::query_as::<_, GeoAllowlistEntry>(&format!(
+        "SELECT {} FROM geo_block_allowlist WHERE ($1::text IS NULL OR status = $1) \
+         ORDER BY created_at DESC LIMIT 200",
+        ALLOWLIST_COLUMNS
+    ))
+    .bind(&query.status)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(entries))
+}
+
+/// Add an approved override directly
+/// POST /api/admin/geo-allowlist
+pub async fn create_allowlist_entry(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<CreateAllowlistRequest>,
+) -> ApiResult<HttpResponse> {
+    body.validate()?;
+    let ip = body.ip.as_deref().map(str::trim).filter(|ip| !ip.is_empty());
+    if ip.is_none() && body.user_id.is_none() {
+        return Err(ApiError::ValidationError("Provide an ip or a user_id".to_string()));
+    }
+    if ip.is_some_and(|ip| ip.parse::<std::net::IpAddr>().is_err()) {
+        return Err(ApiError::ValidationError("ip must be an IPv4 or IPv6 address".to_string()));
+    }
+    let expires_at = override_expiry(body.expires_in_days)?;
+
+    let mut tx = pool.begin().await?;
+    let entry = sqlx::query_as::<_, GeoAllowlistEntry>(&format!(
+        "INSERT INTO geo_block_allowlist (ip, user_id, reason, status, reviewed_by, reviewed_at, expires_at) \
+         VALUES ($1, $2, $3, 'approved', $4, NOW(), $5) RETURNING {}",
+        ALLOWLIST_COLUMNS
+    ))
+    .bind(ip)
+    .bind(body.user_id)
+    .bind(body.reason.trim())
+    .bind(admin.0.user_id)
+    .bind(expires_at)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin.0.user_id),
+            action: "compliance.override_created",
+            resource_type: "geo_block_allowlist",
+            resource_id: Some(entry.id.to_string()),
+            details: serde_json::json!({ "ip": entry.ip, "user_id": entry.user_id, "expires_at": entry.expires_at }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::created(entry))
+}
+
+async fn review(
+    admin: &AdminUser,
+    pool: &PgPool,
+    entry_id: Uuid,
+    approve: bool,
+    expires_in_days: Option<i64>,
+) -> ApiResult<GeoAllowlistEntry> {
+    let expires_at = if approve { Some(override_expiry(expires_in_days)?) } else { None };
+    let (status, from_statuses) = if approve {
+        ("approved", "('pending')")
+    } else {
+        // Rejecting an approved override revokes it
+        ("rejected", "('pending', 'approved')")
+    };
+
+    let mut tx = pool.begin().await?;
+    let entry = sqlx::query_as::<_, GeoAllowlistEntry>(&format!(
+        "UPDATE geo_block_allowlist SET status = $2, reviewed_by = $3, reviewed_at = NOW(), expires_at = $4 \
+         WHERE id = $1 AND status IN {} RETURNING {}",
+        from_statuses, ALLOWLIST_COLUMNS
+    ))
+    .bind(entry_id)
+    .bind(status)
+    .bind(admin.0.user_id)
+    .bind(expires_at)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("No reviewable override with that id".to_string()))?;
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin.0.user_id),
+            action: if approve { "compliance.override_approved" } else { "compliance.override_rejected" },
+            resource_type: "geo_block_allowlist",
+            resource_id: Some(entry.id.to_string()),
+            details: serde_json::json!({
+                "ip": entry.ip,
+                "user_id": entry.user_id,
+                "blocked_audit_id": entry.blocked_audit_id,
+                "expires_at": entry.expires_at,
+            }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(entry)
+}
+
+/// Approve a pending override
+/// POST /api/admin/geo-allowlist/{entry_id}/approve
+pub async fn approve_override(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: Option<web::Json<ReviewAllowlistRequest>>,
+) -> ApiResult<HttpResponse> {
+    let body = body.map(|b| b.into_inner()).unwrap_or_default();
+    let entry = review(&admin, pool.get_ref(), path.into_inner(), true, body.expires_in_days).await?;
+    Ok(ApiResponse::success(entry))
+}
+
+/// Reject a pending override, or revoke an approved one
+/// POST /api/admin/geo-allowlist/{entry_id}/reject
+pub async fn reject_override(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let entry = review(&admin, pool.get_ref(), path.into_inner(), false, None).await?;
+    Ok(ApiResponse::success(entry))
+}
@@ -0,0 +1,144 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::constraint::{CommandConstraint, CreateConstraintRequest, UpdateConstraintRequest};
+use crate::services::constraint_services::{
+    validate_condition, validate_guarded_command, validate_name, CONSTRAINT_COLUMNS, MAX_CONSTRAINTS_PER_USER,
+};
+use crate::services::device_services::get_owned_device;
+
+async fn get_owned_constraint(pool: &PgPool, constraint_id: Uuid, user_id: Uuid) -> ApiResult<CommandConstraint> {
+    sqlx::query_as::<_, CommandConstraint>(&format!(
+        "SELECT {} FROM command_constraints WHERE id = $1 AND user_id = $2",
+        CONSTRAINT_COLUMNS
+    ))
+    .bind(constraint_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Constraint not found".to_string()))
+}
+
+/// The user's command constraints
+/// GET /api/robotics/constraints
+pub async fn list_constraints(user: AuthenticatedUser, pool: web::Data<Arc<PgPool>>) -> ApiResult<HttpResponse> {
+    let constraints = sqlx::query_as::<_, CommandConstraint>(&format!(
+        "SELECT {} FROM command_constraints WHERE user_id = $1 ORDER BY name",
+        CONSTRAINT_COLUMNS
+    ))
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(constraints))
+}
+
+/// Declare that `command` on `device_id` may only run while `depends_on_device_id` is in the
+/// expected state, e.g. `{"condition": "metadata", "field": "dock_state", "expected": "docked"}`
+/// POST /api/robotics/constraints
+pub async fn create_constraint(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<CreateConstraintRequest>,
+) -> ApiResult<HttpResponse> {
+    let name = body.name.trim();
+    validate_name(name)?;
+    validate_condition(&body.condition, body.field.as_deref(), &body.expected)?;
+    if body.device_id == body.depends_on_device_id {
+        return Err(ApiError::ValidationError("A device cannot depend on itself".to_string()));
+    }
+    let device = get_owned_device(pool.get_ref(), body.device_id, user.user_id).await?;
+    let dependency = get_owned_device(pool.get_ref(), body.depends_on_device_id, user.user_id).await?;
+    validate_guarded_command(&device.device_type, &body.command)?;
+
+    let declared: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM command_constraints WHERE user_id = $1")
+        .bind(user.user_id)
+        .fetch_one(pool.get_ref().as_ref())
+        .await?;
+    if declared >= MAX_CONSTRAINTS_PER_USER {
+        return Err(ApiError::ValidationError(format!(
+            "At most {} constraints can be declared",
+            MAX_CONSTRAINTS_PER_USER
+        )));
+    }
+
+    let constraint = sqlx::query_as::<_, CommandConstraint>(&format!(
+        "INSERT INTO command_constraints \
+         (user_id, name, device_id, command, depends_on_device_id, condition, field, expected, enabled) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING {}",
+        CONSTRAINT_COLUMNS
+    ))
+    .bind(user.user_id)
+    .bind(name)
+    .bind(device.id)
+    .bind(&body.command)
+    .bind(dependency.id)
+    .bind(&body.condition)
+    .bind(&body.field)
+    .bind(&body.expected)
+    .bind(body.enabled.unwrap_or(true))
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::created(constraint))
+}
+
+/// Rename, change the condition of, enable or disable a constraint
+/// PATCH /api/robotics/constraints/{constraint_id}
+pub async fn update_constraint(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateConstraintRequest>,
+) -> ApiResult<HttpResponse> {
+    let existing = get_owned_constraint(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let name = body.name.as_deref().map(str::trim).unwrap_or(&existing.name);
+    validate_name(name)?;
+    let condition = body.condition.as_deref().unwrap_or(&existing.condition);
+    // Moving off a metadata condition drops its key
+    let field = match (&body.field, condition) {
+        (Some(field), _) => Some(field.as_str()),
+        (None, "metadata") => existing.field.as_deref(),
+        (None, _) => None,
+    };
+    let expected = body.expected.as_deref().unwrap_or(&existing.expected);
+    validate_condition(condition, field, expected)?;
+
+    let constraint = sqlx::query_as::<_, CommandConstraint>(&format!(
+        "UPDATE command_constraints SET name = $2, condition = $3, field = $4, expected = $5, \
+         enabled = COALESCE($6, enabled), updated_at = NOW() WHERE id = $1 RETURNING {}",
+        CONSTRAINT_COLUMNS
+    ))
+    .bind(existing.id)
+    .bind(name)
+    .bind(condition)
+    .bind(field)
+    .bind(expected)
+    .bind(body.enabled)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(constraint))
+}
+
+/// Remove a constraint; the command it guarded is no longer checked against it
+/// DELETE /api/robotics/constraints/{constraint_id}
+pub async fn delete_constraint(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let deleted = sqlx::query("DELETE FROM command_constraints WHERE id = $1 AND user_id = $2")
+        .bind(path.into_inner())
+        .bind(user.user_id)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Constraint not found".to_string()));
+    }
+
+    Ok(crate::errors::success_message("Constraint removed"))
+}
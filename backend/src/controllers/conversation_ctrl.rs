@@ -0,0 +1,35 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{success_message, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::services::conversation_services;
+
+/// The caller's AI conversations, most recently active first
+/// GET /api/ai/conversations
+pub async fn list_conversations(user: AuthenticatedUser, pool: web::Data<Arc<PgPool>>) -> ApiResult<HttpResponse> {
+    let conversations = conversation_services::list(pool.get_ref(), user.user_id).await?;
+    Ok(ApiResponse::success(conversations))
+}
+
+/// A conversation with its messages, oldest first
+/// GET /api/ai/conversations/{conversation_id}
+pub async fn get_conversation(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let details = conversation_services::details(pool.get_ref(), user.user_id, path.into_inner()).await?;
+    Ok(ApiResponse::success(details))
+}
+
+/// DELETE /api/ai/conversations/{conversation_id}
+pub async fn delete_conversation(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    conversation_services::delete(pool.get_ref(), user.user_id, path.into_inner()).await?;
+    Ok(success_message("Conversation deleted"))
+}
@@ -0,0 +1,101 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiResponse, ApiResult};
+use crate::middleware::{AdminUser, AuthenticatedUser};
+use crate::models::coupon::{CouponQuery, CreateCouponRequest, UpdateCouponRequest, ValidateCouponRequest};
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::coupon_services;
+use crate::services::payment_services::validate_product_type;
+
+/// What a coupon would take off a product's price for the caller, without using it
+/// POST /api/blockchain/coupons/validate
+pub async fn validate_coupon(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<ValidateCouponRequest>,
+) -> ApiResult<HttpResponse> {
+    validate_product_type(&body.product_type)?;
+    let mut conn = pool.acquire().await?;
+    let quote = coupon_services::quote(
+        &mut conn,
+        user.user_id,
+        &body.code,
+        &body.product_type,
+        config.product_price_usd,
+        "usd",
+    )
+    .await?;
+    Ok(ApiResponse::success(quote))
+}
+
+/// Coupons with how often each was used, newest first
+/// GET /api/admin/coupons
+pub async fn list_coupons(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<CouponQuery>,
+) -> ApiResult<HttpResponse> {
+    let coupons = coupon_services::list(pool.get_ref(), query.active).await?;
+    Ok(ApiResponse::success(coupons))
+}
+
+/// POST /api/admin/coupons
+pub async fn create_coupon(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<CreateCouponRequest>,
+) -> ApiResult<HttpResponse> {
+    let coupon = coupon_services::create(pool.get_ref(), admin.0.user_id, &body).await?;
+
+    let mut tx = pool.begin().await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin.0.user_id),
+            action: "coupon.created",
+            resource_type: "coupon",
+            resource_id: Some(coupon.id.to_string()),
+            details: serde_json::json!({ "code": coupon.code, "kind": coupon.kind, "value": coupon.value }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(ApiResponse::created(coupon))
+}
+
+/// Turn a coupon off or on, or change its limits and expiry
+/// PATCH /api/admin/coupons/{coupon_id}
+pub async fn update_coupon(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateCouponRequest>,
+) -> ApiResult<HttpResponse> {
+    let coupon = coupon_services::update(pool.get_ref(), path.into_inner(), &body).await?;
+
+    let mut tx = pool.begin().await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin.0.user_id),
+            action: "coupon.updated",
+            resource_type: "coupon",
+            resource_id: Some(coupon.id.to_string()),
+            details: serde_json::json!({
+                "active": body.active,
+                "max_redemptions": body.max_redemptions,
+                "max_per_user": body.max_per_user,
+                "expires_at": body.expires_at,
+            }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(ApiResponse::success(coupon))
+}
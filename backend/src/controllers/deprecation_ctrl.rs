@@ -0,0 +1,31 @@
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AdminUser;
+use crate::models::compliance::DeprecationReportQuery;
+use crate::services::deprecation_services::{self, DeprecationTracker};
+
+const DEFAULT_REPORT_DAYS: i64 = 30;
+const MAX_REPORT_DAYS: i64 = 365;
+
+/// Deprecated routes and the clients still calling them
+/// GET /api/admin/deprecations?days=30
+pub async fn get_deprecation_report(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    tracker: web::Data<Arc<DeprecationTracker>>,
+    query: web::Query<DeprecationReportQuery>,
+) -> ApiResult<HttpResponse> {
+    let days = query.days.unwrap_or(DEFAULT_REPORT_DAYS);
+    if !(1..=MAX_REPORT_DAYS).contains(&days) {
+        return Err(ApiError::ValidationError(format!("days must be between 1 and {}", MAX_REPORT_DAYS)));
+    }
+
+    // Include calls counted since the last periodic flush
+    deprecation_services::flush(pool.get_ref(), tracker.get_ref()).await?;
+    let report = deprecation_services::usage_report(pool.get_ref(), Utc::now() - Duration::days(days)).await?;
+
+    Ok(ApiResponse::success(report))
+}
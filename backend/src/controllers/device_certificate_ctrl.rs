@@ -0,0 +1,277 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::device::{Device, MintCertificateRequest, TransferDeviceRequest};
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::crypto_services::BlockchainService;
+use crate::services::device_services::{get_owned_device, DEVICE_COLUMNS};
+use crate::services::nft_services;
+use crate::services::notification_services::notify_user;
+use crate::services::relayer_services::Relayer;
+use crate::utils::log_blockchain_event;
+
+/// Stored certificate of a device: (chain id, contract, token id, mint tx hash, minted at)
+type CertificateRow = (Option<i64>, Option<String>, Option<String>, Option<String>, Option<DateTime<Utc>>);
+
+async fn certificate_row(pool: &PgPool, device_id: Uuid) -> ApiResult<CertificateRow> {
+    let row = sqlx::query_as(
+        "SELECT nft_chain_id, nft_contract, nft_token_id, nft_mint_tx_hash, nft_minted_at FROM devices WHERE id = $1",
+    )
+    .bind(device_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(row)
+}
+
+/// Mint the device's ownership certificate to the owner's linked wallet. The relayer pays the
+/// gas; the token id is the device id.
+/// POST /api/robotics/devices/{device_id}/certificate
+pub async fn mint_certificate(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+    body: Option<web::Json<MintCertificateRequest>>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    if device.nft_token_id.is_some() {
+        return Err(ApiError::Conflict("This device already has an ownership certificate".to_string()));
+    }
+    let wallet: Option<String> = sqlx::query_scalar("SELECT wallet_address FROM users WHERE id = $1")
+        .bind(user.user_id)
+        .fetch_one(pool.get_ref().as_ref())
+        .await?;
+    let wallet = wallet.ok_or_else(|| {
+        ApiError::ValidationError("Link a wallet to receive the device's certificate".to_string())
+    })?;
+
+    let chain_id = body.and_then(|b| b.chain_id).unwrap_or(config.default_chain_id);
+    let chain = BlockchainService::for_chain_id(&config, chain_id)?;
+    let contract = config.chain(chain_id).and_then(|c| c.nft_contract.clone()).ok_or_else(|| {
+        ApiError::ServiceUnavailable(format!("Device certificates are not enabled on chain {}", chain_id))
+    })?;
+    let relayer = Relayer::from_config(&config)?;
+    let token_id = nft_services::token_id(device.id);
+    let token_uri = nft_services::token_uri(&config.api_base_url, device.id);
+    let calldata = nft_services::mint_calldata(&wallet, device.id, &token_uri)?;
+
+    // Claim the device first so two concurrent requests cannot both mint
+    let claimed = sqlx::query(
+        "UPDATE devices SET nft_chain_id = $2, nft_contract = $3, nft_token_id = $4 \
+         WHERE id = $1 AND nft_token_id IS NULL",
+    )
+    .bind(device.id)
+    .bind(chain_id as i64)
+    .bind(&contract)
+    .bind(&token_id)
+    .execute(pool.get_ref().as_ref())
+    .await?;
+    if claimed.rows_affected() == 0 {
+        return Err(ApiError::Conflict("This device already has an ownership certificate".to_string()));
+    }
+
+    let tx_hash = match relayer.send(&chain, &contract, calldata, 0).await {
+        Ok(tx_hash) => tx_hash,
+        Err(e) => {
+            sqlx::query(
+                "UPDATE devices SET nft_chain_id = NULL, nft_contract = NULL, nft_token_id = NULL WHERE id = $1",
+            )
+            .bind(device.id)
+            .execute(pool.get_ref().as_ref())
+            .await?;
+            log_blockchain_event("device_certificate_mint", None, None, "failed");
+            return Err(e);
+        }
+    };
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE devices SET nft_mint_tx_hash = $2, nft_minted_at = NOW() WHERE id = $1")
+        .bind(device.id)
+        .bind(&tx_hash)
+        .execute(&mut *tx)
+        .await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(user.user_id),
+            action: "device.certificate_minted",
+            resource_type: "device",
+            resource_id: Some(device.id.to_string()),
+            details: serde_json::json!({
+                "chain_id": chain_id,
+                "contract": contract,
+                "token_id": token_id,
+                "to": wallet,
+                "relayer": relayer.address(),
+                "tx_hash": tx_hash,
+            }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    log_blockchain_event("device_certificate_mint", Some(&tx_hash), None, "submitted");
+
+    Ok(ApiResponse::created(serde_json::json!({
+        "device_id": device.id,
+        "chain_id": chain_id,
+        "contract": contract,
+        "token_id": token_id,
+        "owner": wallet,
+        "tx_hash": tx_hash,
+    })))
+}
+
+/// The device's certificate and who holds it on-chain right now
+/// GET /api/robotics/devices/{device_id}/certificate
+pub async fn get_certificate(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let (Some(chain_id), Some(contract), Some(token_id), tx_hash, minted_at) =
+        certificate_row(pool.get_ref(), device.id).await?
+    else {
+        return Err(ApiError::NotFound("This device has no ownership certificate".to_string()));
+    };
+
+    // Until the mint is mined, or without a provider, the holder is unknown
+    let chain = BlockchainService::for_chain_id(&config, chain_id as u64)?;
+    let holder = if chain.has_provider() {
+        nft_services::owner_of(&chain, &contract, device.id)
+            .await
+            .inspect_err(|e| tracing::warn!("ownerOf for device {} failed: {}", device.id, e))
+            .ok()
+    } else {
+        None
+    };
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "device_id": device.id,
+        "chain_id": chain_id,
+        "contract": contract,
+        "token_id": token_id,
+        "mint_tx_hash": tx_hash,
+        "minted_at": minted_at,
+        "holder": holder,
+        "token_uri": nft_services::token_uri(&config.api_base_url, device.id),
+    })))
+}
+
+/// Hand the device to another account. A device with a certificate moves only after the
+/// recipient's linked wallet holds the token on-chain, so the certificate and the account
+/// stay in agreement.
+/// POST /api/robotics/devices/{device_id}/transfer
+pub async fn transfer_device(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+    body: web::Json<TransferDeviceRequest>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let (recipient_id, recipient_wallet): (Uuid, Option<String>) =
+        sqlx::query_as("SELECT id, wallet_address FROM users WHERE LOWER(email) = LOWER($1)")
+            .bind(body.recipient_email.trim())
+            .fetch_optional(pool.get_ref().as_ref())
+            .await?
+            .ok_or_else(|| ApiError::NotFound("No account has that email".to_string()))?;
+    if recipient_id == user.user_id {
+        return Err(ApiError::ValidationError("You already own this device".to_string()));
+    }
+
+    let (chain_id, contract, _, _, _) = certificate_row(pool.get_ref(), device.id).await?;
+    let verified_holder = match (chain_id, contract) {
+        (Some(chain_id), Some(contract)) => {
+            let recipient_wallet = recipient_wallet.ok_or_else(|| {
+                ApiError::Conflict("The recipient must link a wallet to receive a certified device".to_string())
+            })?;
+            let chain = BlockchainService::for_chain_id(&config, chain_id as u64)?;
+            if !chain.has_provider() {
+                return Err(ApiError::ServiceUnavailable(format!("Chain {} has no RPC provider", chain_id)));
+            }
+            let holder = nft_services::owner_of(&chain, &contract, device.id).await?;
+            if !holder.eq_ignore_ascii_case(&recipient_wallet) {
+                log_blockchain_event("device_transfer", None, None, "holder_mismatch");
+                return Err(ApiError::Conflict(format!(
+                    "Transfer the certificate (token {}) to the recipient's wallet first; it is held by {}",
+                    nft_services::token_id(device.id),
+                    holder
+                )));
+            }
+            Some(holder)
+        }
+        _ => None,
+    };
+
+    let mut tx = pool.begin().await?;
+    let device = sqlx::query_as::<_, Device>(&format!(
+        "UPDATE devices SET user_id = $3 WHERE id = $1 AND user_id = $2 RETURNING {}",
+        DEVICE_COLUMNS
+    ))
+    .bind(device.id)
+    .bind(user.user_id)
+    .bind(recipient_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Device not found".to_string()))?;
+    notify_user(
+        &mut tx,
+        recipient_id,
+        "device.transferred",
+        &format!("{} is now yours", device.device_name),
+        "A device was transferred to your account.",
+        serde_json::json!({ "device_id": device.id, "from": user.user_id }),
+    )
+    .await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(user.user_id),
+            action: "device.transferred",
+            resource_type: "device",
+            resource_id: Some(device.id.to_string()),
+            details: serde_json::json!({
+                "from": user.user_id,
+                "to": recipient_id,
+                "certificate_holder": verified_holder,
+            }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "device_id": device.id,
+        "owner_id": recipient_id,
+        "certificate_verified": verified_holder.is_some(),
+    })))
+}
+
+/// Public ERC-721 metadata of a device's certificate, served as its token URI
+/// GET /api/blockchain/nft/devices/{device_id}
+pub async fn get_token_metadata(
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    // Only certified devices are published
+    let device = sqlx::query_as::<_, Device>(&format!(
+        "SELECT {} FROM devices WHERE id = $1 AND nft_token_id IS NOT NULL",
+        DEVICE_COLUMNS
+    ))
+    .bind(path.into_inner())
+    .fetch_optional(pool.get_ref().as_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Token not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(nft_services::metadata(&device, &config.frontend_url)))
+}
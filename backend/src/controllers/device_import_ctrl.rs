@@ -0,0 +1,127 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::{Acquire, PgPool};
+use std::sync::Arc;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::device::{BulkImportResponse, ImportRowResult, RegisterDeviceRequest};
+use crate::services::robotics_services::{RoboticsService, MAX_IMPORT_ROWS};
+use crate::utils::log_device_event;
+
+/// Bulk device registration from a CSV file or a JSON array
+/// POST /api/robotics/devices/import
+pub async fn import_devices(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> ApiResult<HttpResponse> {
+    let service = RoboticsService::new();
+    let payload = std::str::from_utf8(&body)
+        .map_err(|_| ApiError::BadRequest("Import payload must be UTF-8".to_string()))?;
+
+    let is_csv = req
+        .headers()
+        .get(actix_web::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.starts_with("text/csv"))
+        .unwrap_or(false);
+
+    let rows: Vec<ApiResult<RegisterDeviceRequest>> = if is_csv {
+        service.parse_import_csv(payload)
+    } else {
+        let values: Vec<serde_json::Value> = serde_json::from_str(payload)
+            .map_err(|e| ApiError::BadRequest(format!("Expected a JSON array of devices: {}", e)))?;
+        values
+            .into_iter()
+            .map(|v| {
+                serde_json::from_value(v)
+                    .map_err(|e| ApiError::ValidationError(format!("Malformed device entry: {}", e)))
+            })
+            .collect()
+    };
+
+    if rows.is_empty() {
+        return Err(ApiError::BadRequest("Import contains no devices".to_string()));
+    }
+    if rows.len() > MAX_IMPORT_ROWS {
+        return Err(ApiError::BadRequest(format!(
+            "Import is limited to {} devices per request",
+            MAX_IMPORT_ROWS
+        )));
+    }
+
+    let total = rows.len();
+    let mut results = Vec::with_capacity(total);
+    let mut tx = pool.begin().await?;
+
+    for (index, row) in rows.into_iter().enumerate() {
+        // Rows are 1-based to match what users see in their spreadsheet
+        let row_number = index + 1;
+
+        let device = match row.and_then(|d| service.validate_registration(&d).map(|_| d)) {
+            Ok(device) => device,
+            Err(e) => {
+                results.push(ImportRowResult {
+                    row: row_number,
+                    success: false,
+                    device_id: None,
+                    device_name: None,
+                    error: Some(e.to_string()),
+                });
+                continue;
+            }
+        };
+
+        // A savepoint per row keeps one failing insert from aborting the whole import
+        let mut savepoint = tx.begin().await?;
+        let inserted = sqlx::query_scalar::<_, uuid::Uuid>(
+            "INSERT INTO devices (user_id, device_name, device_type, firmware_version, status, metadata) \
+             VALUES ($1, $2, $3, $4, 'offline', '{}') RETURNING id",
+        )
+        .bind(user.user_id)
+        .bind(device.device_name.trim())
+        .bind(&device.device_type)
+        .bind(device.firmware_version.trim())
+        .fetch_one(&mut *savepoint)
+        .await;
+
+        match inserted {
+            Ok(device_id) => {
+                savepoint.commit().await?;
+                results.push(ImportRowResult {
+                    row: row_number,
+                    success: true,
+                    device_id: Some(device_id),
+                    device_name: Some(device.device_name),
+                    error: None,
+                });
+            }
+            Err(e) => {
+                savepoint.rollback().await?;
+                results.push(ImportRowResult {
+                    row: row_number,
+                    success: false,
+                    device_id: None,
+                    device_name: Some(device.device_name),
+                    error: Some(ApiError::from(e).to_string()),
+                });
+            }
+        }
+    }
+
+    tx.commit().await?;
+
+    let imported = results.iter().filter(|r| r.success).count();
+    log_device_event(
+        &user.user_id.to_string(),
+        "bulk_import",
+        Some(&format!("{} of {} devices imported", imported, total)),
+    );
+
+    Ok(ApiResponse::success(BulkImportResponse {
+        total,
+        imported,
+        failed: total - imported,
+        results,
+    }))
+}
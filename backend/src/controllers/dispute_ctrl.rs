@@ -0,0 +1,98 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AdminUser;
+use crate::models::dispute::{DisputeEvidenceRequest, DisputeQuery, ResolveDisputeRequest};
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::dispute_services;
+
+/// Disputes in `status`, every open one by default, soonest evidence deadline first
+/// GET /api/admin/disputes
+pub async fn list_disputes(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<DisputeQuery>,
+) -> ApiResult<HttpResponse> {
+    let disputes = dispute_services::queue(pool.get_ref(), query.status.as_deref()).await?;
+    Ok(ApiResponse::success(disputes))
+}
+
+/// GET /api/admin/disputes/{dispute_id}
+pub async fn get_dispute(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let dispute = dispute_services::get(pool.get_ref(), path.into_inner()).await?;
+    Ok(ApiResponse::success(dispute))
+}
+
+/// Record the evidence sent to the provider for an open dispute
+/// POST /api/admin/disputes/{dispute_id}/evidence
+pub async fn submit_evidence(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<DisputeEvidenceRequest>,
+) -> ApiResult<HttpResponse> {
+    let dispute =
+        dispute_services::submit_evidence(pool.get_ref(), admin.0.user_id, path.into_inner(), &body.note).await?;
+
+    let mut tx = pool.begin().await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin.0.user_id),
+            action: "dispute.evidence_submitted",
+            resource_type: "dispute",
+            resource_id: Some(dispute.id.to_string()),
+            details: serde_json::json!({ "transaction_id": dispute.transaction_id, "note": dispute.evidence_note }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(ApiResponse::success(dispute))
+}
+
+/// Close an open dispute as won, thawing what the payment unlocked, or lost, taking it back
+/// POST /api/admin/disputes/{dispute_id}/resolve
+pub async fn resolve_dispute(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<ResolveDisputeRequest>,
+) -> ApiResult<HttpResponse> {
+    let won = match body.outcome.as_str() {
+        "won" => true,
+        "lost" => false,
+        _ => return Err(ApiError::ValidationError("outcome must be won or lost".to_string())),
+    };
+    let dispute =
+        dispute_services::resolve(pool.get_ref(), admin.0.user_id, path.into_inner(), won, body.note.as_deref())
+            .await?;
+
+    let mut tx = pool.begin().await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin.0.user_id),
+            action: "dispute.resolved",
+            resource_type: "dispute",
+            resource_id: Some(dispute.id.to_string()),
+            details: serde_json::json!({
+                "outcome": dispute.status,
+                "note": body.note,
+                "transaction_id": dispute.transaction_id,
+                "amount": dispute.amount,
+                "currency": dispute.currency,
+            }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(ApiResponse::success(dispute))
+}
@@ -0,0 +1,40 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::errors::{ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::embedding::{IndexRequest, SearchRequest};
+use crate::services::ai_services::AIService;
+use crate::services::embedding_services;
+
+/// Embed documents and store them for semantic search
+/// POST /api/ai/index
+pub async fn index_documents(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    ai: web::Data<Arc<AIService>>,
+    body: web::Json<IndexRequest>,
+) -> ApiResult<HttpResponse> {
+    let indexed = embedding_services::index(pool.get_ref(), ai.get_ref(), user.user_id, &body).await?;
+    Ok(ApiResponse::created(indexed))
+}
+
+/// The caller's indexed documents most similar to a query
+/// POST /api/ai/search
+pub async fn search(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    ai: web::Data<Arc<AIService>>,
+    body: web::Json<SearchRequest>,
+) -> ApiResult<HttpResponse> {
+    let results = embedding_services::search(
+        pool.get_ref(),
+        ai.get_ref(),
+        user.user_id,
+        &body.query,
+        body.collection.as_deref(),
+        body.limit,
+    )
+    .await?;
+    Ok(ApiResponse::success(results))
+}
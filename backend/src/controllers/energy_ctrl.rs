@@ -0,0 +1,46 @@
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::device::EnergyQuery;
+use crate::services::device_services::get_owned_device;
+use crate::services::energy_services::{
+    battery_capacity_wh, build_report, energy_buckets, validate_tariff, DEFAULT_CURRENCY, DEFAULT_PRICE_PER_KWH,
+};
+
+const DEFAULT_REPORT_DAYS: i64 = 30;
+const MAX_REPORT_DAYS: i64 = 366;
+
+/// Battery drain per command and per day over a window, converted to grid energy and priced at
+/// the given tariff, with the quietest hours of the day suggested as a charging window.
+/// Battery capacity comes from `metadata.battery_capacity_wh` or the device type's default.
+/// GET /api/robotics/devices/{device_id}/energy?from=&to=&price_per_kwh=0.15&currency=USD
+pub async fn get_energy_report(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    query: web::Query<EnergyQuery>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - Duration::days(DEFAULT_REPORT_DAYS));
+    if from >= to || to - from > Duration::days(MAX_REPORT_DAYS) {
+        return Err(ApiError::ValidationError(format!(
+            "`from` must be before `to` and the window at most {} days",
+            MAX_REPORT_DAYS
+        )));
+    }
+    let price_per_kwh = query.price_per_kwh.unwrap_or(DEFAULT_PRICE_PER_KWH);
+    let currency = query.currency.as_deref().unwrap_or(DEFAULT_CURRENCY).trim().to_uppercase();
+    validate_tariff(price_per_kwh, &currency)?;
+
+    let buckets = energy_buckets(pool.get_ref(), device.id, from, to).await?;
+    let capacity_wh = battery_capacity_wh(&device.device_type, &device.metadata);
+    let report = build_report(device.id, (from, to), &buckets, capacity_wh, price_per_kwh, &currency);
+
+    Ok(ApiResponse::success(report))
+}
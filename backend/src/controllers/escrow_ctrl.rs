@@ -0,0 +1,234 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::{AdminUser, AuthenticatedUser};
+use crate::models::escrow::{
+    CreateEscrowRequest, DisputeEscrowRequest, EscrowListQuery, EscrowNoteRequest, EscrowQueueQuery,
+    ResolveEscrowRequest,
+};
+use crate::models::typed_data::SignedTypedData;
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::escrow_services::{self, Action, Role};
+use crate::services::security_services;
+
+/// Open an escrow with another user as the seller. The seller must have a linked wallet, and
+/// a `device_id` must be one of the seller's devices.
+/// POST /api/escrows
+pub async fn create_escrow(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<CreateEscrowRequest>,
+) -> ApiResult<HttpResponse> {
+    let escrow = escrow_services::create(pool.get_ref(), &config, user.user_id, &body).await?;
+    Ok(ApiResponse::created(escrow))
+}
+
+/// The user's escrows, newest first. Filter with `role` (buyer, seller) and `status`.
+/// GET /api/escrows
+pub async fn list_escrows(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<EscrowListQuery>,
+) -> ApiResult<HttpResponse> {
+    let escrows = escrow_services::list(pool.get_ref(), user.user_id, &query).await?;
+    Ok(ApiResponse::success(escrows))
+}
+
+/// An escrow the user is party to, with its history
+/// GET /api/escrows/{escrow_id}
+pub async fn get_escrow(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let (escrow, _) = escrow_services::party_escrow(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let details = escrow_services::details(pool.get_ref(), escrow).await?;
+    Ok(ApiResponse::success(details))
+}
+
+/// Check the buyer's wallet can fund the escrow and issue the typed data authorizing the transfer.
+/// The wallet must have approved the relayer as a spender of at least the amount.
+/// POST /api/escrows/{escrow_id}/fund/prepare
+pub async fn prepare_funding(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let prepared = escrow_services::prepare_funding(pool.get_ref(), &config, path.into_inner(), user.user_id).await?;
+    Ok(ApiResponse::success(prepared))
+}
+
+/// Fund the escrow with the typed data from the prepare step, signed by the buyer's wallet. The
+/// escrow stays `funding` until the chain confirms the transfer.
+/// POST /api/escrows/{escrow_id}/fund
+pub async fn fund_escrow(
+    req: HttpRequest,
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+    body: web::Json<SignedTypedData>,
+) -> ApiResult<HttpResponse> {
+    let country = security_services::edge_country(&req);
+    let escrow =
+        escrow_services::fund(pool.get_ref(), &config, path.into_inner(), user.user_id, &body, country.as_deref())
+            .await?;
+    Ok(ApiResponse::success(escrow))
+}
+
+/// Take a party's action that moves no tokens
+async fn party_action(
+    pool: &PgPool,
+    escrow_id: Uuid,
+    user_id: Uuid,
+    action: Action,
+    note: Option<&str>,
+) -> ApiResult<HttpResponse> {
+    let (escrow, role) = escrow_services::party_escrow(pool, escrow_id, user_id).await?;
+    let escrow = escrow_services::act(pool, escrow.id, action, role, user_id, note).await?;
+    Ok(ApiResponse::success(escrow))
+}
+
+/// The seller marks the order fulfilled, starting the buyer's window to release or dispute. A
+/// device escrow needs the device transferred to the buyer first.
+/// POST /api/escrows/{escrow_id}/fulfill
+pub async fn fulfill_escrow(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<EscrowNoteRequest>,
+) -> ApiResult<HttpResponse> {
+    party_action(pool.get_ref(), path.into_inner(), user.user_id, Action::Fulfill, body.note.as_deref()).await
+}
+
+/// Either party disputes a funded escrow, freezing it until an admin resolves it
+/// POST /api/escrows/{escrow_id}/dispute
+pub async fn dispute_escrow(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<DisputeEscrowRequest>,
+) -> ApiResult<HttpResponse> {
+    party_action(pool.get_ref(), path.into_inner(), user.user_id, Action::Dispute, Some(&body.reason)).await
+}
+
+/// Either party calls off an escrow that has not been funded
+/// POST /api/escrows/{escrow_id}/cancel
+pub async fn cancel_escrow(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<EscrowNoteRequest>,
+) -> ApiResult<HttpResponse> {
+    party_action(pool.get_ref(), path.into_inner(), user.user_id, Action::Cancel, body.note.as_deref()).await
+}
+
+/// Pay out a party's settlement: the buyer releases to the seller, the seller refunds the buyer
+async fn party_settlement(
+    pool: &PgPool,
+    config: &AppConfig,
+    escrow_id: Uuid,
+    user_id: Uuid,
+    action: Action,
+    note: Option<&str>,
+) -> ApiResult<HttpResponse> {
+    let (escrow, role) = escrow_services::party_escrow(pool, escrow_id, user_id).await?;
+    let escrow = escrow_services::settle(pool, config, escrow.id, action, role, Some(user_id), note).await?;
+    Ok(ApiResponse::success(escrow))
+}
+
+/// The buyer releases the escrowed tokens to the seller
+/// POST /api/escrows/{escrow_id}/release
+pub async fn release_escrow(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+    body: web::Json<EscrowNoteRequest>,
+) -> ApiResult<HttpResponse> {
+    let note = body.note.as_deref();
+    party_settlement(pool.get_ref(), &config, path.into_inner(), user.user_id, Action::Release, note).await
+}
+
+/// The seller refunds the escrowed tokens to the buyer
+/// POST /api/escrows/{escrow_id}/refund
+pub async fn refund_escrow(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+    body: web::Json<EscrowNoteRequest>,
+) -> ApiResult<HttpResponse> {
+    let note = body.note.as_deref();
+    party_settlement(pool.get_ref(), &config, path.into_inner(), user.user_id, Action::Refund, note).await
+}
+
+/// Escrows in `status`, disputed by default, longest waiting first
+/// GET /api/admin/escrows
+pub async fn list_queue(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<EscrowQueueQuery>,
+) -> ApiResult<HttpResponse> {
+    let escrows = escrow_services::queue(pool.get_ref(), query.status.as_deref()).await?;
+    Ok(ApiResponse::success(escrows))
+}
+
+/// Any escrow with its history
+/// GET /api/admin/escrows/{escrow_id}
+pub async fn admin_get_escrow(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let escrow = escrow_services::get_escrow(pool.get_ref(), path.into_inner()).await?;
+    let details = escrow_services::details(pool.get_ref(), escrow).await?;
+    Ok(ApiResponse::success(details))
+}
+
+/// Resolve a disputed escrow by releasing it to the seller or refunding the buyer
+/// POST /api/admin/escrows/{escrow_id}/resolve
+pub async fn resolve_escrow(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+    body: web::Json<ResolveEscrowRequest>,
+) -> ApiResult<HttpResponse> {
+    let action = match body.outcome.as_str() {
+        "release" => Action::Release,
+        "refund" => Action::Refund,
+        _ => return Err(ApiError::ValidationError("outcome must be release or refund".to_string())),
+    };
+    let escrow = escrow_services::settle(
+        pool.get_ref(),
+        &config,
+        path.into_inner(),
+        action,
+        Role::Admin,
+        Some(admin.0.user_id),
+        body.note.as_deref(),
+    )
+    .await?;
+
+    let mut tx = pool.begin().await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin.0.user_id),
+            action: "escrow.resolved",
+            resource_type: "escrow",
+            resource_id: Some(escrow.id.to_string()),
+            details: serde_json::json!({ "outcome": body.outcome, "note": body.note }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(ApiResponse::success(escrow))
+}
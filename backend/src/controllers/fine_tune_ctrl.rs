@@ -0,0 +1,56 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::fine_tune::{StartFineTuneRequest, TrainingFileQuery};
+use crate::services::ai_services::AIService;
+use crate::services::fine_tune_services;
+
+/// Upload JSONL chat examples (raw body, file name in the query) to train models on
+/// POST /api/ai/fine-tunes/files?file_name=...&provider=...
+pub async fn upload_training_file(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    ai: web::Data<Arc<AIService>>,
+    query: web::Query<TrainingFileQuery>,
+    body: web::Bytes,
+) -> ApiResult<HttpResponse> {
+    let file = fine_tune_services::upload(pool.get_ref(), ai.get_ref(), user.user_id, &query, body.to_vec()).await?;
+    Ok(ApiResponse::created(file))
+}
+
+/// Start fine-tuning a base model on an uploaded training file
+/// POST /api/ai/fine-tunes
+pub async fn start_fine_tune(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    ai: web::Data<Arc<AIService>>,
+    body: web::Json<StartFineTuneRequest>,
+) -> ApiResult<HttpResponse> {
+    let fine_tune =
+        fine_tune_services::start(pool.get_ref(), config.get_ref(), ai.get_ref(), user.user_id, &body).await?;
+    Ok(ApiResponse::created(fine_tune))
+}
+
+/// The caller's fine-tuning jobs
+/// GET /api/ai/fine-tunes
+pub async fn list_fine_tunes(user: AuthenticatedUser, pool: web::Data<Arc<PgPool>>) -> ApiResult<HttpResponse> {
+    let fine_tunes = fine_tune_services::list(pool.get_ref(), user.user_id).await?;
+    Ok(ApiResponse::success(fine_tunes))
+}
+
+/// A fine-tuning job's current status, with the model to chat with once it has succeeded
+/// GET /api/ai/fine-tunes/{fine_tune_id}
+pub async fn get_fine_tune(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    ai: web::Data<Arc<AIService>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let fine_tune = fine_tune_services::poll(pool.get_ref(), ai.get_ref(), user.user_id, path.into_inner()).await?;
+    Ok(ApiResponse::success(fine_tune))
+}
@@ -0,0 +1,483 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::{FromRow, PgPool};
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::{AuthenticatedDevice, AuthenticatedUser};
+use crate::models::firmware::{
+    CreateSigningKeyRequest, FirmwareRelease, FirmwareReportRequest, FirmwareSigningKey, FirmwareUploadQuery,
+};
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::firmware_services::{
+    artifact_sha256, ca_public_key, compare_versions, endorse_signing_key, key_fingerprint, parse_public_key,
+    parse_signature, validate_version, verify_artifact, FIRMWARE_SIGNATURE_HEADER, MAX_FIRMWARE_BYTES,
+};
+use crate::services::key_services::{KeyManager, KeyPurpose};
+use crate::services::org_services::require_org_permission;
+use crate::services::policy_services::OrgAction;
+use crate::utils::{base64_encode, log_device_event, log_security_event};
+
+const SIGNING_KEY_COLUMNS: &str = "id, org_id, name, public_key, fingerprint, endorsement, endorsed_by, \
+     created_by, created_at, revoked_at";
+
+const RELEASE_COLUMNS: &str = "id, org_id, device_type, version, sha256, size_bytes, signature, signing_key_id, \
+     verification_status, verification_error, uploaded_by, created_at";
+
+/// Releases a device may install: verified, signed by a key that is still trusted, for its
+/// type, and published by an org its owner belongs to. Binds $1 owner id, $2 device type.
+const DEVICE_RELEASE_FILTER: &str = "r.verification_status = 'verified' AND k.revoked_at IS NULL \
+     AND r.device_type = $2 \
+     AND r.org_id IN (SELECT org_id FROM org_memberships WHERE user_id = $1 AND active)";
+
+/// A release together with the signing key a device needs to verify it
+#[derive(FromRow)]
+struct DeviceReleaseRow {
+    id: Uuid,
+    org_id: Uuid,
+    version: String,
+    sha256: String,
+    size_bytes: i64,
+    signature: String,
+    public_key: String,
+    fingerprint: String,
+    endorsement: String,
+    endorsed_by: String,
+}
+
+fn require_same_device(device: &AuthenticatedDevice, device_id: Uuid) -> ApiResult<()> {
+    if device.device_id != device_id {
+        return Err(ApiError::Forbidden("Device key does not match this device".to_string()));
+    }
+    Ok(())
+}
+
+/// List an org's firmware signing keys, including revoked ones
+/// GET /api/orgs/{org_id}/firmware/signing-keys
+pub async fn list_signing_keys(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageFirmware).await?;
+
+    let keys = sqlx::query_as::<_, FirmwareSigningKey>(&format!(
+        "SELECT {} FROM firmware_signing_keys WHERE org_id = $1 ORDER BY created_at DESC",
+        SIGNING_KEY_COLUMNS
+    ))
+    .bind(org_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(keys))
+}
+
+/// Register an Ed25519 public key allowed to sign the org's firmware.
+/// The platform device CA endorses it so devices can chain it to their pinned trust root.
+/// POST /api/orgs/{org_id}/firmware/signing-keys
+pub async fn add_signing_key(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    keys: web::Data<Arc<dyn KeyManager>>,
+    path: web::Path<Uuid>,
+    body: web::Json<CreateSigningKeyRequest>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageFirmware).await?;
+
+    let name = body.name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return Err(ApiError::ValidationError("Key name must be 1-100 characters".to_string()));
+    }
+    let public_key = parse_public_key(&body.public_key)?;
+    let fingerprint = key_fingerprint(&public_key);
+
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM firmware_signing_keys WHERE org_id = $1 AND fingerprint = $2)",
+    )
+    .bind(org_id)
+    .bind(&fingerprint)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+    if exists {
+        return Err(ApiError::Conflict("This signing key is already registered".to_string()));
+    }
+
+    let ca = keys.active_key(KeyPurpose::DeviceCa).await?;
+    let endorsement = endorse_signing_key(&ca, org_id, &fingerprint)?;
+
+    let mut tx = pool.begin().await?;
+    let key = sqlx::query_as::<_, FirmwareSigningKey>(&format!(
+        "INSERT INTO firmware_signing_keys (org_id, name, public_key, fingerprint, endorsement, endorsed_by, created_by) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING {}",
+        SIGNING_KEY_COLUMNS
+    ))
+    .bind(org_id)
+    .bind(name)
+    .bind(base64_encode(public_key.as_bytes()))
+    .bind(&fingerprint)
+    .bind(&endorsement)
+    .bind(ca.kid())
+    .bind(user.user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "firmware.signing_key_added",
+            resource_type: "firmware_signing_key",
+            resource_id: Some(key.id.to_string()),
+            details: serde_json::json!({ "name": key.name, "fingerprint": key.fingerprint }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::created(key))
+}
+
+/// Revoke a signing key; releases it signed stop being offered to devices
+/// DELETE /api/orgs/{org_id}/firmware/signing-keys/{key_id}
+pub async fn revoke_signing_key(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, key_id) = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageFirmware).await?;
+
+    let mut tx = pool.begin().await?;
+    let revoked = sqlx::query(
+        "UPDATE firmware_signing_keys SET revoked_at = NOW() WHERE id = $1 AND org_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(key_id)
+    .bind(org_id)
+    .execute(&mut *tx)
+    .await?;
+    if revoked.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Active signing key not found".to_string()));
+    }
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "firmware.signing_key_revoked",
+            resource_type: "firmware_signing_key",
+            resource_id: Some(key_id.to_string()),
+            details: serde_json::json!({}),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(crate::errors::success_message("Signing key revoked"))
+}
+
+/// Upload a firmware artifact (raw body) with its detached signature in `X-Firmware-Signature`.
+/// The signature must verify against one of the org's active signing keys; rejected uploads
+/// are recorded with the reason but their artifact is discarded.
+/// POST /api/orgs/{org_id}/firmware?device_type=drone&version=1.4.0
+pub async fn upload_firmware(
+    user: AuthenticatedUser,
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    query: web::Query<FirmwareUploadQuery>,
+    body: web::Bytes,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageFirmware).await?;
+
+    if !["drone", "robot", "rover"].contains(&query.device_type.as_str()) {
+        return Err(ApiError::ValidationError(format!("Unknown device type: {}", query.device_type)));
+    }
+    validate_version(&query.version)?;
+    if body.is_empty() || body.len() > MAX_FIRMWARE_BYTES {
+        return Err(ApiError::ValidationError(format!(
+            "Firmware artifact must be 1-{} bytes",
+            MAX_FIRMWARE_BYTES
+        )));
+    }
+    let signature_b64 = req
+        .headers()
+        .get(FIRMWARE_SIGNATURE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| ApiError::ValidationError(format!("Missing {} header", FIRMWARE_SIGNATURE_HEADER)))?;
+    let signature = parse_signature(signature_b64)?;
+
+    let duplicate: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM firmware_releases \
+         WHERE org_id = $1 AND device_type = $2 AND version = $3 AND verification_status = 'verified')",
+    )
+    .bind(org_id)
+    .bind(&query.device_type)
+    .bind(&query.version)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+    if duplicate {
+        return Err(ApiError::Conflict(format!(
+            "Version {} for {} devices already exists",
+            query.version, query.device_type
+        )));
+    }
+
+    let signing_keys = sqlx::query_as::<_, FirmwareSigningKey>(&format!(
+        "SELECT {} FROM firmware_signing_keys WHERE org_id = $1 AND revoked_at IS NULL",
+        SIGNING_KEY_COLUMNS
+    ))
+    .bind(org_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+    if signing_keys.is_empty() {
+        return Err(ApiError::ValidationError("Register a firmware signing key first".to_string()));
+    }
+
+    let signer = signing_keys.iter().find(|key| {
+        parse_public_key(&key.public_key).is_ok_and(|public| verify_artifact(&public, &body, &signature))
+    });
+    let (status, error) = match signer {
+        Some(_) => ("verified", None),
+        None => ("rejected", Some("Signature does not match any active signing key for this org")),
+    };
+    let sha256 = artifact_sha256(&body);
+
+    let mut tx = pool.begin().await?;
+    let release = sqlx::query_as::<_, FirmwareRelease>(&format!(
+        "INSERT INTO firmware_releases \
+         (org_id, device_type, version, sha256, size_bytes, signature, signing_key_id, \
+          verification_status, verification_error, artifact, uploaded_by) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING {}",
+        RELEASE_COLUMNS
+    ))
+    .bind(org_id)
+    .bind(&query.device_type)
+    .bind(&query.version)
+    .bind(&sha256)
+    .bind(body.len() as i64)
+    .bind(signature_b64.trim())
+    .bind(signer.map(|k| k.id))
+    .bind(status)
+    .bind(error)
+    .bind(signer.map(|_| body.as_ref()))
+    .bind(user.user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: if signer.is_some() { "firmware.uploaded" } else { "firmware.rejected" },
+            resource_type: "firmware_release",
+            resource_id: Some(release.id.to_string()),
+            details: serde_json::json!({
+                "device_type": release.device_type,
+                "version": release.version,
+                "sha256": release.sha256,
+                "signing_key": signer.map(|k| &k.fingerprint),
+            }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    if let Some(error) = error {
+        log_security_event(
+            "firmware_signature_rejected",
+            None,
+            &format!("org {} version {} sha256 {}", org_id, release.version, release.sha256),
+        );
+        return Err(ApiError::ValidationError(error.to_string()));
+    }
+
+    Ok(ApiResponse::created(release))
+}
+
+/// Uploads and their verification results, newest first
+/// GET /api/orgs/{org_id}/firmware
+pub async fn list_releases(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageFirmware).await?;
+
+    let releases = sqlx::query_as::<_, FirmwareRelease>(&format!(
+        "SELECT {} FROM firmware_releases WHERE org_id = $1 ORDER BY created_at DESC LIMIT 200",
+        RELEASE_COLUMNS
+    ))
+    .bind(org_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(releases))
+}
+
+/// Device CA public key for devices to pin at provisioning (device-authenticated)
+/// GET /api/robotics/firmware/trust-root
+pub async fn get_trust_root(
+    _device: AuthenticatedDevice,
+    keys: web::Data<Arc<dyn KeyManager>>,
+) -> ApiResult<HttpResponse> {
+    let ca = keys.active_key(KeyPurpose::DeviceCa).await?;
+    let public_key = ca_public_key(&ca)?;
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "kid": ca.kid(),
+        "algorithm": KeyPurpose::DeviceCa.algorithm(),
+        "public_key": base64_encode(public_key.as_bytes()),
+    })))
+}
+
+/// Newest release newer than what the device runs, with everything needed to verify
+/// the signature chain before installing (device-authenticated)
+/// GET /api/robotics/devices/{device_id}/firmware
+pub async fn get_firmware_update(
+    device: AuthenticatedDevice,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let device_id = path.into_inner();
+    require_same_device(&device, device_id)?;
+
+    let current: String = sqlx::query_scalar("SELECT firmware_version FROM devices WHERE id = $1")
+        .bind(device_id)
+        .fetch_one(pool.get_ref().as_ref())
+        .await?;
+
+    let candidates = sqlx::query_as::<_, DeviceReleaseRow>(&format!(
+        "SELECT r.id, r.org_id, r.version, r.sha256, r.size_bytes, r.signature, \
+                k.public_key, k.fingerprint, k.endorsement, k.endorsed_by \
+         FROM firmware_releases r JOIN firmware_signing_keys k ON k.id = r.signing_key_id \
+         WHERE {} ORDER BY r.created_at DESC LIMIT 100",
+        DEVICE_RELEASE_FILTER
+    ))
+    .bind(device.user_id)
+    .bind(&device.device_type)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    let latest = candidates
+        .into_iter()
+        .filter(|r| compare_versions(&r.version, &current).is_gt())
+        .max_by(|a, b| compare_versions(&a.version, &b.version));
+
+    let Some(release) = latest else {
+        return Ok(ApiResponse::success(serde_json::json!({
+            "update_available": false,
+            "current_version": current,
+        })));
+    };
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "update_available": true,
+        "current_version": current,
+        "release": {
+            "id": release.id,
+            "org_id": release.org_id,
+            "version": release.version,
+            "sha256": release.sha256,
+            "size_bytes": release.size_bytes,
+            "signature": release.signature,
+            "download_path": format!("/api/robotics/devices/{}/firmware/{}/artifact", device_id, release.id),
+        },
+        "signing_key": {
+            "public_key": release.public_key,
+            "fingerprint": release.fingerprint,
+            "endorsement": release.endorsement,
+            "endorsed_by": release.endorsed_by,
+        },
+    })))
+}
+
+/// Raw artifact bytes for a release the device is allowed to install (device-authenticated)
+/// GET /api/robotics/devices/{device_id}/firmware/{release_id}/artifact
+pub async fn download_artifact(
+    device: AuthenticatedDevice,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (device_id, release_id) = path.into_inner();
+    require_same_device(&device, device_id)?;
+
+    let artifact: Option<Vec<u8>> = sqlx::query_scalar(&format!(
+        "SELECT r.artifact FROM firmware_releases r JOIN firmware_signing_keys k ON k.id = r.signing_key_id \
+         WHERE r.id = $3 AND {}",
+        DEVICE_RELEASE_FILTER
+    ))
+    .bind(device.user_id)
+    .bind(&device.device_type)
+    .bind(release_id)
+    .fetch_optional(pool.get_ref().as_ref())
+    .await?
+    .flatten();
+
+    let artifact = artifact.ok_or_else(|| ApiError::NotFound("Firmware release not found".to_string()))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .body(artifact))
+}
+
+/// Device's result after verifying and (maybe) installing a release (device-authenticated).
+/// `rejected` means the signature chain or hash check failed on the device.
+/// POST /api/robotics/devices/{device_id}/firmware/{release_id}/report
+pub async fn report_installation(
+    device: AuthenticatedDevice,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+    body: web::Json<FirmwareReportRequest>,
+) -> ApiResult<HttpResponse> {
+    let (device_id, release_id) = path.into_inner();
+    require_same_device(&device, device_id)?;
+
+    if !["installed", "rejected", "failed"].contains(&body.status.as_str()) {
+        return Err(ApiError::ValidationError("status must be installed, rejected or failed".to_string()));
+    }
+    let reason = body.reason.as_deref().map(str::trim).filter(|r| !r.is_empty());
+    if reason.is_some_and(|r| r.len() > 500) {
+        return Err(ApiError::ValidationError("reason must be at most 500 characters".to_string()));
+    }
+
+    let version: String = sqlx::query_scalar(
+        "SELECT version FROM firmware_releases WHERE id = $1 AND device_type = $2 AND verification_status = 'verified'",
+    )
+    .bind(release_id)
+    .bind(&device.device_type)
+    .fetch_optional(pool.get_ref().as_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Firmware release not found".to_string()))?;
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("INSERT INTO firmware_installations (device_id, release_id, status, reason) VALUES ($1, $2, $3, $4)")
+        .bind(device_id)
+        .bind(release_id)
+        .bind(&body.status)
+        .bind(reason)
+        .execute(&mut *tx)
+        .await?;
+    if body.status == "installed" {
+        sqlx::query("UPDATE devices SET firmware_version = $2 WHERE id = $1")
+            .bind(device_id)
+            .bind(&version)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    let details = format!("firmware {} {}", version, body.status);
+    if body.status == "rejected" {
+        log_security_event("firmware_rejected_by_device", None, &format!("device {}: {}", device_id, details));
+    } else {
+        log_device_event(&device_id.to_string(), "firmware_report", Some(&details));
+    }
+
+    Ok(crate::errors::success_message("Firmware report recorded"))
+}
@@ -0,0 +1,54 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::fleet_config::{ApplyConfigQuery, ExportConfigQuery};
+use crate::services::fleet_config_services::{self, export, load_state};
+
+/// The current fleet configuration as a document, in JSON or YAML. Applying it unchanged
+/// plans nothing.
+/// GET /api/robotics/config
+pub async fn get_config(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<ExportConfigQuery>,
+) -> ApiResult<HttpResponse> {
+    let mut conn = pool.acquire().await?;
+    let config = export(load_state(&mut conn, user.user_id).await?);
+
+    match query.format.as_deref() {
+        None | Some("json") => Ok(ApiResponse::success(config)),
+        Some("yaml") => {
+            let yaml = serde_yaml::to_string(&config)
+                .map_err(|e| ApiError::InternalError(format!("Failed to encode configuration: {}", e)))?;
+            Ok(HttpResponse::Ok().content_type("application/yaml").body(yaml))
+        }
+        Some(_) => Err(ApiError::ValidationError("format must be json or yaml".to_string())),
+    }
+}
+
+/// Declaratively set fleets, device tags, geofences, alert rules and automations from a JSON
+/// or YAML (`Content-Type: application/yaml`) document. Returns the plan; with `?dry_run=true`
+/// nothing is applied, otherwise the whole plan is applied in one transaction.
+/// PUT /api/robotics/config
+pub async fn apply_config(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    req: HttpRequest,
+    query: web::Query<ApplyConfigQuery>,
+    body: web::Bytes,
+) -> ApiResult<HttpResponse> {
+    let content_type = req
+        .headers()
+        .get(actix_web::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let config = fleet_config_services::parse_document(content_type, &body)?;
+    let plan = fleet_config_services::apply_config(pool.get_ref(), user.user_id, &config, query.dry_run).await?;
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "applied": !query.dry_run && !plan.is_empty(),
+        "plan": plan,
+    })))
+}
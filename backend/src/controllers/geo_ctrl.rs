@@ -0,0 +1,76 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::device::{NearbyDevice, NearbyQuery};
+use crate::services::region_services::{region_filter, require_user_filter};
+use crate::utils::geo::{bounding_box, is_valid_coordinate, EARTH_RADIUS_M};
+
+/// Largest search radius accepted by the nearby query (100 km)
+const MAX_RADIUS_M: f64 = 100_000.0;
+
+/// Find the user's devices closest to a point, using their last reported position
+/// GET /api/robotics/devices/nearby?lat=&lng=&radius_m=&region_id=
+pub async fn get_nearby_devices(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<NearbyQuery>,
+) -> ApiResult<HttpResponse> {
+    if !is_valid_coordinate(query.lat, query.lng) {
+        return Err(ApiError::ValidationError("lat/lng are out of range".to_string()));
+    }
+    if !(query.radius_m > 0.0 && query.radius_m <= MAX_RADIUS_M) {
+        return Err(ApiError::ValidationError(format!(
+            "radius_m must be between 0 and {}",
+            MAX_RADIUS_M
+        )));
+    }
+
+    let region_id = require_user_filter(pool.get_ref(), &user, query.region_id).await?;
+
+    let bbox = bounding_box(query.lat, query.lng, query.radius_m);
+    let [(west_min, west_max), (east_min, east_max)] = bbox.lng_ranges;
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+
+    // Haversine distance in SQL; the bounding box keeps the scan on the position index. The
+    // longitude ranges are split when the box crosses the antimeridian
+    let devices = sqlx::query_as::<_, NearbyDevice>(&format!(
+        "SELECT * FROM ( \
+            SELECT id, device_name, device_type, status, last_latitude, last_longitude, last_altitude, \
+                   position_updated_at, \
+                   2 * $9 * ASIN(SQRT( \
+                       POWER(SIN(RADIANS(last_latitude - $2) / 2), 2) + \
+                       COS(RADIANS($2)) * COS(RADIANS(last_latitude)) * \
+                       POWER(SIN(RADIANS(last_longitude - $3) / 2), 2) \
+                   )) AS distance_m \
+            FROM devices d \
+            WHERE user_id = $1 AND {} \
+              AND last_latitude BETWEEN $4 AND $5 \
+              AND (last_longitude BETWEEN $6 AND $7 OR last_longitude BETWEEN $12 AND $13) \
+              AND ($8::text IS NULL OR device_type = $8) \
+         ) nearby \
+         WHERE distance_m <= $10 \
+         ORDER BY distance_m \
+         LIMIT $11",
+        region_filter("d.id", 14)
+    ))
+    .bind(user.user_id)
+    .bind(query.lat)
+    .bind(query.lng)
+    .bind(bbox.min_lat)
+    .bind(bbox.max_lat)
+    .bind(west_min)
+    .bind(west_max)
+    .bind(&query.device_type)
+    .bind(EARTH_RADIUS_M)
+    .bind(query.radius_m)
+    .bind(limit)
+    .bind(east_min)
+    .bind(east_max)
+    .bind(region_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(devices))
+}
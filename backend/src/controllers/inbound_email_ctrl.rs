@@ -0,0 +1,98 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use secrecy::ExposeSecret;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::services::inbound_email_services::{
+    mailgun_email, parse_sns, process_inbound, verify_mailgun_signature, InboundEmail, SnsMessage,
+};
+use crate::utils::secure_compare;
+
+#[derive(Debug, Deserialize)]
+pub struct SesInboundQuery {
+    pub token: Option<String>,
+}
+
+/// The reply-to signing secret and domain, without which no reply can be matched
+fn reply_settings(config: &AppConfig) -> ApiResult<(&[u8], &str)> {
+    match (&config.inbound_email_secret, &config.inbound_email_domain) {
+        (Some(secret), Some(domain)) => Ok((secret.expose_secret().as_bytes(), domain.as_str())),
+        _ => Err(ApiError::ServiceUnavailable("Inbound email is not configured".to_string())),
+    }
+}
+
+async fn process(pool: &PgPool, config: &AppConfig, email: &InboundEmail) -> ApiResult<HttpResponse> {
+    let (secret, domain) = reply_settings(config)?;
+    let outcome = process_inbound(pool, secret, domain, email).await?;
+    Ok(ApiResponse::success(outcome))
+}
+
+/// Mailgun route target (`forward("https://.../api/inbound/email/mailgun")`). Posts are
+/// URL-encoded; routes must not forward attachments, which Mailgun sends as multipart.
+/// Anything other than a bad signature is answered 200 so Mailgun does not retry it.
+/// POST /api/inbound/email/mailgun
+pub async fn mailgun_inbound(
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    form: web::Form<HashMap<String, String>>,
+) -> ApiResult<HttpResponse> {
+    let key = config
+        .mailgun_signing_key
+        .as_ref()
+        .ok_or_else(|| ApiError::ServiceUnavailable("Mailgun inbound email is not configured".to_string()))?;
+    let field = |name: &str| form.get(name).map(String::as_str).unwrap_or_default();
+    let now = Utc::now().timestamp();
+    let (timestamp, token, signature) = (field("timestamp"), field("token"), field("signature"));
+    if !verify_mailgun_signature(key.expose_secret().as_bytes(), timestamp, token, signature, now) {
+        return Err(ApiError::Unauthorized("Invalid Mailgun signature".to_string()));
+    }
+
+    let email = mailgun_email(&form)?;
+    process(pool.get_ref(), &config, &email).await
+}
+
+/// SNS subscription for an SES receipt rule's SNS action, authenticated by the shared token in
+/// the subscription URL. Subscription confirmations are confirmed straight away.
+/// POST /api/inbound/email/ses?token=
+pub async fn ses_inbound(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    query: web::Query<SesInboundQuery>,
+    body: web::Bytes,
+) -> ApiResult<HttpResponse> {
+    let expected = config
+        .ses_inbound_token
+        .as_ref()
+        .ok_or_else(|| ApiError::ServiceUnavailable("SES inbound email is not configured".to_string()))?;
+    let token = query.token.as_deref().unwrap_or_default();
+    if !secure_compare(token, expected.expose_secret()) {
+        return Err(ApiError::Unauthorized("Invalid inbound email token".to_string()));
+    }
+
+    match parse_sns(&body)? {
+        SnsMessage::SubscriptionConfirmation { subscribe_url } => {
+            let topic = req.headers().get("x-amz-sns-topic-arn").and_then(|v| v.to_str().ok()).unwrap_or("unknown");
+            let response = reqwest::get(&subscribe_url)
+                .await
+                .map_err(|e| ApiError::ExternalServiceError(format!("SNS confirmation failed: {}", e)))?;
+            if !response.status().is_success() {
+                return Err(ApiError::ExternalServiceError(format!(
+                    "SNS confirmation returned {}",
+                    response.status()
+                )));
+            }
+            tracing::info!(topic, "Confirmed SNS subscription for inbound email");
+            Ok(ApiResponse::success(serde_json::json!({ "outcome": "subscribed" })))
+        }
+        SnsMessage::Notification(email) => process(pool.get_ref(), &config, &email).await,
+        SnsMessage::Skipped(reason) => {
+            tracing::info!(reason = %reason, "Skipped SNS inbound email notification");
+            Ok(ApiResponse::success(serde_json::json!({ "outcome": "ignored", "detail": reason })))
+        }
+    }
+}
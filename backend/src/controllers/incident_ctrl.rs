@@ -0,0 +1,139 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::incident::{Incident, IncidentMessage, IncidentMessageRequest, IncidentQuery};
+use crate::services::incident_services::{
+    acknowledge, add_message, INCIDENT_COLUMNS, INCIDENT_STATUSES, MAX_MESSAGE_CHARS, MESSAGE_COLUMNS,
+};
+
+async fn owned_incident(pool: &PgPool, incident_id: Uuid, user_id: Uuid) -> ApiResult<Incident> {
+    sqlx::query_as::<_, Incident>(&format!(
+        "SELECT {} FROM incidents WHERE id = $1 AND user_id = $2",
+        INCIDENT_COLUMNS
+    ))
+    .bind(incident_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Incident not found".to_string()))
+}
+
+/// Incidents opened by critical telemetry alerts, newest first
+/// GET /api/robotics/incidents?status=open&device_id=
+pub async fn list_incidents(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<IncidentQuery>,
+) -> ApiResult<HttpResponse> {
+    if let Some(status) = &query.status
+        && !INCIDENT_STATUSES.contains(&status.as_str())
+    {
+        return Err(ApiError::ValidationError(format!(
+            "status must be one of {}",
+            INCIDENT_STATUSES.join(", ")
+        )));
+    }
+
+    let incidents = sqlx::query_as::<_, Incident>(&format!(
+        "SELECT {} FROM incidents WHERE user_id = $1 AND ($2::text IS NULL OR status = $2) \
+         AND ($3::uuid IS NULL OR device_id = $3) ORDER BY created_at DESC LIMIT 200",
+        INCIDENT_COLUMNS
+    ))
+    .bind(user.user_id)
+    .bind(&query.status)
+    .bind(query.device_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(incidents))
+}
+
+/// An incident with its messages, including replies to the alert email
+/// GET /api/robotics/incidents/{incident_id}
+pub async fn get_incident(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let incident = owned_incident(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let messages = sqlx::query_as::<_, IncidentMessage>(&format!(
+        "SELECT {} FROM incident_messages WHERE incident_id = $1 ORDER BY created_at",
+        MESSAGE_COLUMNS
+    ))
+    .bind(incident.id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "incident": incident,
+        "messages": messages,
+    })))
+}
+
+/// POST /api/robotics/incidents/{incident_id}/acknowledge
+pub async fn acknowledge_incident(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let incident = owned_incident(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let mut conn = pool.acquire().await?;
+    if !acknowledge(&mut conn, incident.id, user.user_id, "api").await? {
+        return Err(ApiError::Conflict(format!("Incident is already {}", incident.status)));
+    }
+
+    Ok(crate::errors::success_message("Incident acknowledged"))
+}
+
+/// Resolve an incident; the next critical alert from the same processor opens a new one
+/// POST /api/robotics/incidents/{incident_id}/resolve
+pub async fn resolve_incident(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let incident = owned_incident(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let updated = sqlx::query(
+        "UPDATE incidents SET status = 'resolved', resolved_at = NOW() WHERE id = $1 AND status <> 'resolved'",
+    )
+    .bind(incident.id)
+    .execute(pool.get_ref().as_ref())
+    .await?;
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::Conflict("Incident is already resolved".to_string()));
+    }
+
+    Ok(crate::errors::success_message("Incident resolved"))
+}
+
+/// POST /api/robotics/incidents/{incident_id}/messages
+pub async fn add_incident_message(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<IncidentMessageRequest>,
+) -> ApiResult<HttpResponse> {
+    let incident = owned_incident(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let text = body.body.trim();
+    if text.is_empty() || text.chars().count() > MAX_MESSAGE_CHARS {
+        return Err(ApiError::ValidationError(format!(
+            "body must be 1-{} characters",
+            MAX_MESSAGE_CHARS
+        )));
+    }
+
+    let mut conn = pool.acquire().await?;
+    let id = add_message(&mut conn, incident.id, Some(user.user_id), "api", None, text).await?;
+    let message = sqlx::query_as::<_, IncidentMessage>(&format!(
+        "SELECT {} FROM incident_messages WHERE id = $1",
+        MESSAGE_COLUMNS
+    ))
+    .bind(id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(ApiResponse::created(message))
+}
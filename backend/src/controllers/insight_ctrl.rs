@@ -0,0 +1,24 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::services::ai_services::AIService;
+use crate::services::insight_services;
+
+/// Health summary and recommendations from the device's last day of telemetry, cached per hour
+/// POST /api/ai/insights/{device_id}
+pub async fn device_insight(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    ai: web::Data<Arc<AIService>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let insight =
+        insight_services::insight(pool.get_ref(), config.get_ref(), ai.get_ref(), user.user_id, path.into_inner())
+            .await?;
+    Ok(ApiResponse::success(insight))
+}
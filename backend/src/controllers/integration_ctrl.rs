@@ -0,0 +1,247 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::{AuthenticatedUser, IntegrationUser};
+use crate::models::incident::Incident;
+use crate::models::integration::{
+    AcknowledgeIncidentAction, CreateIntegrationTokenRequest, IntegrationHook, IntegrationToken, SendCommandAction,
+    SubscribeHookRequest,
+};
+use crate::services::command_services;
+use crate::services::device_services::get_owned_device;
+use crate::services::incident_services::{acknowledge, INCIDENT_COLUMNS};
+use crate::services::integration_services::{
+    trigger_items, validate_event, MAX_HOOKS_PER_USER, MAX_TOKENS_PER_USER, TOKEN_PREFIX_LEN,
+};
+use crate::services::transport_services::TransportRegistry;
+use crate::services::webhook_services::validate_url;
+use crate::utils::{generate_api_key, sha256_hash};
+
+/// GET /api/integrations/tokens
+pub async fn list_tokens(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let tokens = sqlx::query_as::<_, IntegrationToken>(
+        "SELECT id, name, token_prefix, last_used_at, created_at FROM integration_tokens \
+         WHERE user_id = $1 ORDER BY created_at",
+    )
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(tokens))
+}
+
+/// Create an API key for a no-code integration. The key is only ever returned here.
+/// POST /api/integrations/tokens
+pub async fn create_token(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<CreateIntegrationTokenRequest>,
+) -> ApiResult<HttpResponse> {
+    let name = body.name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return Err(ApiError::ValidationError("name must be 1-100 characters".to_string()));
+    }
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM integration_tokens WHERE user_id = $1")
+        .bind(user.user_id)
+        .fetch_one(pool.get_ref().as_ref())
+        .await?;
+    if count >= MAX_TOKENS_PER_USER {
+        return Err(ApiError::ValidationError(format!(
+            "At most {} integration tokens per account",
+            MAX_TOKENS_PER_USER
+        )));
+    }
+
+    let token = generate_api_key();
+    let record = sqlx::query_as::<_, IntegrationToken>(
+        "INSERT INTO integration_tokens (user_id, name, token_hash, token_prefix) VALUES ($1, $2, $3, $4) \
+         RETURNING id, name, token_prefix, last_used_at, created_at",
+    )
+    .bind(user.user_id)
+    .bind(name)
+    .bind(sha256_hash(token.as_bytes()))
+    .bind(&token[..TOKEN_PREFIX_LEN])
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::created(serde_json::json!({
+        "token": token,
+        "integration_token": record,
+    })))
+}
+
+/// Revoke a token; hooks subscribed with it are removed too
+/// DELETE /api/integrations/tokens/{token_id}
+pub async fn delete_token(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let deleted = sqlx::query("DELETE FROM integration_tokens WHERE id = $1 AND user_id = $2")
+        .bind(path.into_inner())
+        .bind(user.user_id)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Integration token not found".to_string()));
+    }
+
+    Ok(crate::errors::success_message("Integration token revoked"))
+}
+
+/// Connection test; integrations show the username as the connected account's label.
+/// The endpoints under `/zapier` answer with bare JSON, as Zapier expects.
+/// GET /api/integrations/zapier/me
+pub async fn me(
+    user: IntegrationUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let (username, email): (String, String) = sqlx::query_as("SELECT username, email FROM users WHERE id = $1")
+        .bind(user.user_id)
+        .fetch_one(pool.get_ref().as_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "id": user.user_id,
+        "username": username,
+        "email": email,
+    })))
+}
+
+async fn poll(user: IntegrationUser, pool: web::Data<Arc<PgPool>>, event: &str) -> ApiResult<HttpResponse> {
+    let items = trigger_items(pool.get_ref(), user.user_id, event).await?;
+    Ok(HttpResponse::Ok().json(items))
+}
+
+/// GET /api/integrations/zapier/triggers/new-devices
+pub async fn poll_new_devices(user: IntegrationUser, pool: web::Data<Arc<PgPool>>) -> ApiResult<HttpResponse> {
+    poll(user, pool, "new_device").await
+}
+
+/// Telemetry processor alerts
+/// GET /api/integrations/zapier/triggers/new-alerts
+pub async fn poll_new_alerts(user: IntegrationUser, pool: web::Data<Arc<PgPool>>) -> ApiResult<HttpResponse> {
+    poll(user, pool, "new_alert").await
+}
+
+/// Commands that succeeded or failed
+/// GET /api/integrations/zapier/triggers/completed-commands
+pub async fn poll_completed_commands(
+    user: IntegrationUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    poll(user, pool, "command_completed").await
+}
+
+/// REST hook subscription: each new item of `event` is POSTed to the target URL, shaped like the
+/// matching polling trigger's items. Receivers answering 410 are unsubscribed.
+/// POST /api/integrations/zapier/hooks
+pub async fn subscribe_hook(
+    user: IntegrationUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<SubscribeHookRequest>,
+) -> ApiResult<HttpResponse> {
+    validate_event(&body.event)?;
+    validate_url(&body.target_url)?;
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM integration_hooks WHERE user_id = $1")
+        .bind(user.user_id)
+        .fetch_one(pool.get_ref().as_ref())
+        .await?;
+    if count >= MAX_HOOKS_PER_USER {
+        return Err(ApiError::ValidationError(format!(
+            "At most {} hook subscriptions per account",
+            MAX_HOOKS_PER_USER
+        )));
+    }
+
+    let hook = sqlx::query_as::<_, IntegrationHook>(
+        "INSERT INTO integration_hooks (user_id, token_id, event, target_url) VALUES ($1, $2, $3, $4) \
+         RETURNING id, event, target_url, created_at",
+    )
+    .bind(user.user_id)
+    .bind(user.token_id)
+    .bind(&body.event)
+    .bind(&body.target_url)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(HttpResponse::Created().json(hook))
+}
+
+/// DELETE /api/integrations/zapier/hooks/{hook_id}
+pub async fn unsubscribe_hook(
+    user: IntegrationUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let hook_id = path.into_inner();
+    let deleted = sqlx::query("DELETE FROM integration_hooks WHERE id = $1 AND user_id = $2")
+        .bind(hook_id)
+        .bind(user.user_id)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Hook subscription not found".to_string()));
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "id": hook_id })))
+}
+
+/// Action: send a command to a device, with the same checks as the app
+/// POST /api/integrations/zapier/actions/send-command
+pub async fn send_command(
+    user: IntegrationUser,
+    pool: web::Data<Arc<PgPool>>,
+    transports: web::Data<Arc<TransportRegistry>>,
+    body: web::Json<SendCommandAction>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), body.device_id, user.user_id).await?;
+    let parameters = match &body.parameters {
+        serde_json::Value::Null => serde_json::json!({}),
+        parameters => parameters.clone(),
+    };
+    let result = command_services::issue_command(
+        pool.get_ref(),
+        transports.get_ref(),
+        user.user_id,
+        &device,
+        &body.command,
+        &parameters,
+        false,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// Action: acknowledge an open incident
+/// POST /api/integrations/zapier/actions/acknowledge-incident
+pub async fn acknowledge_incident(
+    user: IntegrationUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<AcknowledgeIncidentAction>,
+) -> ApiResult<HttpResponse> {
+    let mut conn = pool.acquire().await?;
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM incidents WHERE id = $1 AND user_id = $2)")
+        .bind(body.incident_id)
+        .bind(user.user_id)
+        .fetch_one(&mut *conn)
+        .await?;
+    if !exists {
+        return Err(ApiError::NotFound("Incident not found".to_string()));
+    }
+    if !acknowledge(&mut conn, body.incident_id, user.user_id, "integration").await? {
+        return Err(ApiError::Conflict("Incident is not open".to_string()));
+    }
+
+    let incident = sqlx::query_as::<_, Incident>(&format!("SELECT {} FROM incidents WHERE id = $1", INCIDENT_COLUMNS))
+        .bind(body.incident_id)
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(HttpResponse::Ok().json(incident))
+}
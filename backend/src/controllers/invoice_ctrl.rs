@@ -0,0 +1,55 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::transaction::{CreateInvoiceRequest, InvoiceQuery};
+use crate::services::{invoice_services, security_services};
+
+/// Open a crypto invoice for a product: the exact token amount to send to the deposit wallet,
+/// an EIP-681 `payment_uri` to render as a QR code, and when the invoice expires. The payment
+/// is picked up on-chain; poll the invoice or its transaction for the outcome. With `currency`
+/// the amount is valued in that fiat currency too.
+/// POST /api/blockchain/invoices
+pub async fn create_invoice(
+    req: HttpRequest,
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<CreateInvoiceRequest>,
+) -> ApiResult<HttpResponse> {
+    let chain_id = body.chain_id.unwrap_or(config.default_chain_id);
+    let country = security_services::edge_country(&req);
+    let invoice = invoice_services::create_invoice(
+        pool.get_ref(),
+        &config,
+        user.user_id,
+        &body.product_type,
+        chain_id,
+        country.as_deref(),
+    )
+    .await?;
+    let invoice = match body.currency.as_deref() {
+        Some(currency) => invoice_services::with_fiat(&config, invoice, currency).await?,
+        None => invoice,
+    };
+    Ok(ApiResponse::created(invoice))
+}
+
+/// GET /api/blockchain/invoices/{invoice_id}?currency=
+pub async fn get_invoice(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+    query: web::Query<InvoiceQuery>,
+) -> ApiResult<HttpResponse> {
+    let invoice = invoice_services::get_invoice(pool.get_ref(), user.user_id, path.into_inner()).await?;
+    let invoice = match query.currency.as_deref() {
+        Some(currency) => invoice_services::with_fiat(&config, invoice, currency).await?,
+        None => invoice,
+    };
+    Ok(ApiResponse::success(invoice))
+}
@@ -0,0 +1,49 @@
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use std::sync::Arc;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AdminUser;
+use crate::services::key_services::{is_rotation_due, KeyManager, KeyPurpose};
+
+/// Inventory of every managed key version with its age and rotation schedule
+/// GET /api/admin/keys
+pub async fn list_keys(
+    _admin: AdminUser,
+    keys: web::Data<Arc<dyn KeyManager>>,
+) -> ApiResult<HttpResponse> {
+    let now = Utc::now();
+    let inventory: Vec<serde_json::Value> = keys
+        .inventory()
+        .await?
+        .into_iter()
+        .map(|key| {
+            let purpose = KeyPurpose::parse(&key.purpose);
+            let active = key.status == "active";
+            serde_json::json!({
+                "kid": format!("{}-v{}", key.purpose, key.version),
+                "age_days": (now - key.created_at).num_days(),
+                "rotation_due_at": purpose.filter(|_| active).map(|p| key.created_at + p.rotation_interval()),
+                "rotation_overdue": active && purpose.is_some_and(|p| is_rotation_due(p, key.created_at, now)),
+                "key": key,
+            })
+        })
+        .collect();
+
+    Ok(ApiResponse::success(inventory))
+}
+
+/// Rotate a key purpose immediately (e.g. after suspected compromise)
+/// POST /api/admin/keys/{purpose}/rotate
+pub async fn rotate_key(
+    admin: AdminUser,
+    keys: web::Data<Arc<dyn KeyManager>>,
+    path: web::Path<String>,
+) -> ApiResult<HttpResponse> {
+    let purpose = KeyPurpose::parse(&path.into_inner())
+        .ok_or_else(|| ApiError::NotFound("Unknown key purpose".to_string()))?;
+
+    let key = keys.rotate(purpose, Some(admin.0.user_id)).await?;
+    tracing::warn!(purpose = purpose.as_str(), version = key.version, admin = %admin.0.user_id, "Manual key rotation");
+
+    Ok(ApiResponse::success(key))
+}
@@ -0,0 +1,72 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::{AdminUser, AuthenticatedUser};
+use crate::models::payout::{KycQueueQuery, ReviewKycRequest, SubmitKycRequest};
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::kyc_services;
+
+/// The caller's identity verification
+/// GET /api/kyc
+pub async fn get_kyc(user: AuthenticatedUser, pool: web::Data<Arc<PgPool>>) -> ApiResult<HttpResponse> {
+    let verification = kyc_services::get(pool.get_ref(), user.user_id).await?;
+    Ok(ApiResponse::success(verification))
+}
+
+/// Submit identity details for review; payouts need them verified
+/// POST /api/kyc
+pub async fn submit_kyc(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<SubmitKycRequest>,
+) -> ApiResult<HttpResponse> {
+    let verification = kyc_services::submit(pool.get_ref(), user.user_id, &body).await?;
+    Ok(ApiResponse::created(verification))
+}
+
+/// Identity verifications in `status`, pending by default, oldest first
+/// GET /api/admin/kyc
+pub async fn list_queue(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<KycQueueQuery>,
+) -> ApiResult<HttpResponse> {
+    let verifications = kyc_services::queue(pool.get_ref(), query.status.as_deref()).await?;
+    Ok(ApiResponse::success(verifications))
+}
+
+/// Verify or reject a user's pending identity verification
+/// POST /api/admin/kyc/{user_id}/review
+pub async fn review_kyc(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<ReviewKycRequest>,
+) -> ApiResult<HttpResponse> {
+    let verify = match body.decision.as_str() {
+        "verify" => true,
+        "reject" => false,
+        _ => return Err(ApiError::ValidationError("decision must be verify or reject".to_string())),
+    };
+    let verification =
+        kyc_services::review(pool.get_ref(), admin.0.user_id, path.into_inner(), verify, body.reason.as_deref())
+            .await?;
+
+    let mut tx = pool.begin().await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin.0.user_id),
+            action: "kyc.reviewed",
+            resource_type: "user",
+            resource_id: Some(verification.user_id.to_string()),
+            details: serde_json::json!({ "decision": body.decision, "reason": body.reason }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(ApiResponse::success(verification))
+}
@@ -0,0 +1,39 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::errors::{ApiResponse, ApiResult};
+use crate::middleware::{AdminUser, AuthenticatedUser};
+use crate::models::ledger::LedgerLineQuery;
+use crate::services::ledger_services;
+
+/// The caller's credits per currency, computed from the ledger
+/// GET /api/ledger/balance
+pub async fn get_balance(user: AuthenticatedUser, pool: web::Data<Arc<PgPool>>) -> ApiResult<HttpResponse> {
+    let balances = ledger_services::balances(pool.get_ref(), user.user_id).await?;
+    Ok(ApiResponse::success(balances))
+}
+
+/// Ledger postings to the caller's credits, newest first; page with `before`
+/// GET /api/ledger/entries
+pub async fn list_lines(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<LedgerLineQuery>,
+) -> ApiResult<HttpResponse> {
+    let lines = ledger_services::lines(pool.get_ref(), user.user_id, &query).await?;
+    Ok(ApiResponse::success(lines))
+}
+
+/// Every ledger account with its balance, and whether debits match credits per currency
+/// GET /api/admin/ledger/trial-balance
+pub async fn trial_balance(_admin: AdminUser, pool: web::Data<Arc<PgPool>>) -> ApiResult<HttpResponse> {
+    let trial_balance = ledger_services::trial_balance(pool.get_ref()).await?;
+    Ok(ApiResponse::success(trial_balance))
+}
+
+/// Credit balances, payments, refunds and payouts the ledger does not agree with
+/// GET /api/admin/ledger/reconcile
+pub async fn reconcile(_admin: AdminUser, pool: web::Data<Arc<PgPool>>) -> ApiResult<HttpResponse> {
+    let discrepancies = ledger_services::reconcile(pool.get_ref()).await?;
+    Ok(ApiResponse::success(discrepancies))
+}
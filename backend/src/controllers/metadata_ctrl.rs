@@ -0,0 +1,69 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::services::device_services::get_owned_device;
+use crate::services::metadata_services::{merge_patch, metadata_schema, validate_metadata};
+
+/// JSON Schema that metadata of a device type must satisfy
+/// GET /api/robotics/device-types/{device_type}/metadata-schema
+pub async fn get_metadata_schema(_user: AuthenticatedUser, path: web::Path<String>) -> ApiResult<HttpResponse> {
+    let device_type = path.into_inner();
+    let schema = metadata_schema(&device_type)
+        .ok_or_else(|| ApiError::NotFound(format!("Unknown device type: {}", device_type)))?;
+
+    Ok(HttpResponse::Ok().content_type("application/schema+json").json(schema))
+}
+
+/// Replace a device's metadata
+/// PUT /api/robotics/devices/{device_id}/metadata
+pub async fn replace_metadata(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<serde_json::Value>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let metadata = body.into_inner();
+    validate_metadata(&device.device_type, &metadata)?;
+
+    sqlx::query("UPDATE devices SET metadata = $2 WHERE id = $1")
+        .bind(device.id)
+        .bind(&metadata)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+
+    Ok(ApiResponse::success(metadata))
+}
+
+/// Update a device's metadata with a JSON Merge Patch (`null` removes a key); the merged
+/// document is validated as a whole
+/// PATCH /api/robotics/devices/{device_id}/metadata
+pub async fn patch_metadata(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<serde_json::Value>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+
+    // Lock the row so concurrent patches merge instead of overwriting each other
+    let mut tx = pool.begin().await?;
+    let mut metadata: serde_json::Value = sqlx::query_scalar("SELECT metadata FROM devices WHERE id = $1 FOR UPDATE")
+        .bind(device.id)
+        .fetch_one(&mut *tx)
+        .await?;
+    merge_patch(&mut metadata, &body);
+    validate_metadata(&device.device_type, &metadata)?;
+
+    sqlx::query("UPDATE devices SET metadata = $2 WHERE id = $1")
+        .bind(device.id)
+        .bind(&metadata)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::success(metadata))
+}
@@ -0,0 +1,376 @@
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::metric::{
+    AggregatePoint, CreateMetricRequest, DeviceValue, MetricPoint, MetricSummary, OrgMetric, PreviewMetricRequest,
+    SeriesQuery, UpdateMetricRequest,
+};
+use crate::models::region::RegionFilter;
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::metric_services::{self, device_in_org, validate_formula, MAX_METRICS_PER_ORG, METRIC_COLUMNS};
+use crate::services::org_services::require_org_permission;
+use crate::services::policy_services::OrgAction;
+use crate::services::region_services::{region_filter, require_org_filter};
+
+/// Longest range a single series request may cover
+const MAX_SERIES_DAYS: i64 = 31;
+const MAX_PREVIEW_HOURS: i64 = 7 * 24;
+
+async fn org_metric(pool: &PgPool, org_id: Uuid, metric_id: Uuid) -> ApiResult<OrgMetric> {
+    sqlx::query_as::<_, OrgMetric>(&format!(
+        "SELECT {} FROM org_metrics WHERE id = $1 AND org_id = $2",
+        METRIC_COLUMNS
+    ))
+    .bind(metric_id)
+    .bind(org_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Metric not found".to_string()))
+}
+
+fn validate_labels(description: Option<&str>, unit: Option<&str>) -> ApiResult<()> {
+    if description.is_some_and(|d| d.len() > 500) {
+        return Err(ApiError::ValidationError("description must be at most 500 characters".to_string()));
+    }
+    if unit.is_some_and(|u| u.len() > 32) {
+        return Err(ApiError::ValidationError("unit must be at most 32 characters".to_string()));
+    }
+    Ok(())
+}
+
+/// List an org's metric definitions
+/// GET /api/orgs/{org_id}/metrics
+pub async fn list_metrics(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ReadDeviceHistory).await?;
+
+    let metrics = sqlx::query_as::<_, OrgMetric>(&format!(
+        "SELECT {} FROM org_metrics WHERE org_id = $1 ORDER BY name",
+        METRIC_COLUMNS
+    ))
+    .bind(org_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(metrics))
+}
+
+/// Define a metric; the aggregation job backfills the last day on its next pass
+/// POST /api/orgs/{org_id}/metrics
+pub async fn create_metric(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<CreateMetricRequest>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageMetrics).await?;
+
+    let name = body.name.trim();
+    if name.is_empty() || name.len() > 64 {
+        return Err(ApiError::ValidationError("name must be 1-64 characters".to_string()));
+    }
+    validate_labels(body.description.as_deref(), body.unit.as_deref())?;
+    validate_formula(&body.formula)?;
+
+    let mut tx = pool.begin().await?;
+    let (count, taken): (i64, bool) = sqlx::query_as(
+        "SELECT COUNT(*), COALESCE(BOOL_OR(name = $2), FALSE) FROM org_metrics WHERE org_id = $1",
+    )
+    .bind(org_id)
+    .bind(name)
+    .fetch_one(&mut *tx)
+    .await?;
+    if taken {
+        return Err(ApiError::Conflict(format!("A metric named '{}' already exists", name)));
+    }
+    if count >= MAX_METRICS_PER_ORG {
+        return Err(ApiError::ValidationError(format!(
+            "An organization can define at most {} metrics",
+            MAX_METRICS_PER_ORG
+        )));
+    }
+
+    let metric = sqlx::query_as::<_, OrgMetric>(&format!(
+        "INSERT INTO org_metrics (org_id, name, description, unit, formula, created_by) \
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING {}",
+        METRIC_COLUMNS
+    ))
+    .bind(org_id)
+    .bind(name)
+    .bind(&body.description)
+    .bind(&body.unit)
+    .bind(body.formula.trim())
+    .bind(user.user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "metric.created",
+            resource_type: "org_metric",
+            resource_id: Some(metric.id.to_string()),
+            details: serde_json::json!({ "name": metric.name, "formula": metric.formula }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::created(metric))
+}
+
+/// Edit a metric. A new formula discards the values computed with the old one and restarts
+/// the backfill.
+/// PATCH /api/orgs/{org_id}/metrics/{metric_id}
+pub async fn update_metric(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+    body: web::Json<UpdateMetricRequest>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, metric_id) = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageMetrics).await?;
+    let existing = org_metric(pool.get_ref(), org_id, metric_id).await?;
+
+    validate_labels(body.description.as_deref(), body.unit.as_deref())?;
+    let formula = body.formula.as_deref().map(str::trim).filter(|f| *f != existing.formula);
+    if let Some(formula) = formula {
+        validate_formula(formula)?;
+    }
+
+    let mut tx = pool.begin().await?;
+    if formula.is_some() {
+        sqlx::query("DELETE FROM metric_values WHERE metric_id = $1")
+            .bind(metric_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    let metric = sqlx::query_as::<_, OrgMetric>(&format!(
+        "UPDATE org_metrics SET description = COALESCE($2, description), unit = COALESCE($3, unit), \
+         formula = COALESCE($4, formula), \
+         computed_through = CASE WHEN $4::text IS NULL THEN computed_through END, updated_at = NOW() \
+         WHERE id = $1 RETURNING {}",
+        METRIC_COLUMNS
+    ))
+    .bind(metric_id)
+    .bind(&body.description)
+    .bind(&body.unit)
+    .bind(formula)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "metric.updated",
+            resource_type: "org_metric",
+            resource_id: Some(metric_id.to_string()),
+            details: serde_json::json!({
+                "name": metric.name,
+                "previous_formula": formula.map(|_| existing.formula),
+                "formula": formula,
+            }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::success(metric))
+}
+
+/// Remove a metric and its computed values; automations triggered by it stop firing
+/// DELETE /api/orgs/{org_id}/metrics/{metric_id}
+pub async fn delete_metric(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, metric_id) = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageMetrics).await?;
+
+    let mut tx = pool.begin().await?;
+    let name: Option<String> = sqlx::query_scalar("DELETE FROM org_metrics WHERE id = $1 AND org_id = $2 RETURNING name")
+        .bind(metric_id)
+        .bind(org_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let Some(name) = name else {
+        return Err(ApiError::NotFound("Metric not found".to_string()));
+    };
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "metric.deleted",
+            resource_type: "org_metric",
+            resource_id: Some(metric_id.to_string()),
+            details: serde_json::json!({ "name": name }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(crate::errors::success_message("Metric deleted"))
+}
+
+/// Evaluate a formula over one device's recent telemetry, hour by hour, without saving it
+/// POST /api/orgs/{org_id}/metrics/preview
+pub async fn preview_metric(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<PreviewMetricRequest>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ReadDeviceHistory).await?;
+
+    let expr = validate_formula(&body.formula)?;
+    if !device_in_org(pool.get_ref(), org_id, body.device_id).await? {
+        return Err(ApiError::NotFound("Device not found".to_string()));
+    }
+    let hours = body.hours.unwrap_or(24).clamp(1, MAX_PREVIEW_HOURS);
+    let points = metric_services::preview(pool.get_ref(), org_id, &expr, body.device_id, hours).await?;
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "formula": body.formula,
+        "device_id": body.device_id,
+        "points": points,
+    })))
+}
+
+/// Hourly values of a metric for one device, or min/avg/max across the org's devices
+/// GET /api/orgs/{org_id}/metrics/{metric_id}/series
+pub async fn get_series(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+    query: web::Query<SeriesQuery>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, metric_id) = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ReadDeviceHistory).await?;
+    let metric = org_metric(pool.get_ref(), org_id, metric_id).await?;
+
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - Duration::hours(24));
+    if from >= to || to - from > Duration::days(MAX_SERIES_DAYS) {
+        return Err(ApiError::ValidationError(format!(
+            "from must be before to and the range at most {} days",
+            MAX_SERIES_DAYS
+        )));
+    }
+
+    let series = match query.device_id {
+        Some(device_id) => {
+            if !device_in_org(pool.get_ref(), org_id, device_id).await? {
+                return Err(ApiError::NotFound("Device not found".to_string()));
+            }
+            let points = sqlx::query_as::<_, MetricPoint>(
+                "SELECT bucket_start, value, samples FROM metric_values \
+                 WHERE metric_id = $1 AND device_id = $2 AND bucket_start >= $3 AND bucket_start < $4 \
+                 ORDER BY bucket_start",
+            )
+            .bind(metric_id)
+            .bind(device_id)
+            .bind(from)
+            .bind(to)
+            .fetch_all(pool.get_ref().as_ref())
+            .await?;
+            serde_json::json!(points)
+        }
+        None => {
+            let region_id = require_org_filter(pool.get_ref(), org_id, query.region_id).await?;
+            let points = sqlx::query_as::<_, AggregatePoint>(&format!(
+                "SELECT bucket_start, AVG(value) AS avg, MIN(value) AS min, MAX(value) AS max, COUNT(*) AS devices \
+                 FROM metric_values WHERE metric_id = $1 AND bucket_start >= $2 AND bucket_start < $3 AND {} \
+                 GROUP BY bucket_start ORDER BY bucket_start",
+                region_filter("device_id", 4)
+            ))
+            .bind(metric_id)
+            .bind(from)
+            .bind(to)
+            .bind(region_id)
+            .fetch_all(pool.get_ref().as_ref())
+            .await?;
+            serde_json::json!(points)
+        }
+    };
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "metric": metric,
+        "device_id": query.device_id,
+        "region_id": query.device_id.is_none().then_some(query.region_id).flatten(),
+        "from": from,
+        "to": to,
+        "points": series,
+    })))
+}
+
+/// The latest computed hour of every metric: the org-wide spread and each device's value.
+/// With `region_id`, only the devices currently in that region count.
+/// GET /api/orgs/{org_id}/metrics/dashboard
+pub async fn get_dashboard(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    query: web::Query<RegionFilter>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ReadDeviceHistory).await?;
+    let region_id = require_org_filter(pool.get_ref(), org_id, query.region_id).await?;
+
+    let metrics = sqlx::query_as::<_, OrgMetric>(&format!(
+        "SELECT {} FROM org_metrics WHERE org_id = $1 ORDER BY name",
+        METRIC_COLUMNS
+    ))
+    .bind(org_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    let mut tiles = Vec::with_capacity(metrics.len());
+    for metric in metrics {
+        let latest = sqlx::query_as::<_, AggregatePoint>(&format!(
+            "SELECT bucket_start, AVG(value) AS avg, MIN(value) AS min, MAX(value) AS max, COUNT(*) AS devices \
+             FROM metric_values WHERE metric_id = $1 AND {} \
+               AND bucket_start = (SELECT MAX(bucket_start) FROM metric_values WHERE metric_id = $1) \
+             GROUP BY bucket_start",
+            region_filter("device_id", 2)
+        ))
+        .bind(metric.id)
+        .bind(region_id)
+        .fetch_optional(pool.get_ref().as_ref())
+        .await?;
+        let devices = match &latest {
+            Some(point) => {
+                sqlx::query_as::<_, DeviceValue>(&format!(
+                    "SELECT device_id, value, samples FROM metric_values \
+                     WHERE metric_id = $1 AND bucket_start = $2 AND {} ORDER BY value DESC",
+                    region_filter("device_id", 3)
+                ))
+                .bind(metric.id)
+                .bind(point.bucket_start)
+                .bind(region_id)
+                .fetch_all(pool.get_ref().as_ref())
+                .await?
+            }
+            None => Vec::new(),
+        };
+        tiles.push(MetricSummary { metric, latest, devices });
+    }
+
+    Ok(ApiResponse::success(tiles))
+}
@@ -0,0 +1,168 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::mission::{CreateFleetMissionRequest, FleetMission, MissionLeg};
+use crate::services::mission_services::{self, validate_legs, LEG_COLUMNS, MISSION_COLUMNS};
+
+async fn owned_mission(pool: &PgPool, mission_id: Uuid, user_id: Uuid) -> ApiResult<FleetMission> {
+    sqlx::query_as::<_, FleetMission>(&format!(
+        "SELECT {} FROM fleet_missions WHERE id = $1 AND user_id = $2",
+        MISSION_COLUMNS
+    ))
+    .bind(mission_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Mission not found".to_string()))
+}
+
+/// Create a mission whose legs run on different devices, and dispatch the first leg
+/// if its device is ready
+/// POST /api/robotics/fleet-missions
+pub async fn create_mission(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<CreateFleetMissionRequest>,
+) -> ApiResult<HttpResponse> {
+    let name = body.name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return Err(ApiError::ValidationError("name must be 1-100 characters".to_string()));
+    }
+    validate_legs(&body.legs)?;
+    mission_services::check_leg_devices(pool.get_ref(), user.user_id, &body.legs).await?;
+
+    let mission = mission_services::insert_mission(pool.get_ref(), user.user_id, name, &body.legs).await?;
+    let readiness = mission_services::try_advance(pool.get_ref(), mission.id, user.user_id).await?;
+    let mission = owned_mission(pool.get_ref(), mission.id, user.user_id).await?;
+
+    Ok(ApiResponse::created(serde_json::json!({
+        "mission": mission,
+        "readiness": readiness,
+    })))
+}
+
+/// Multi-leg missions issued by the caller, newest first
+/// GET /api/robotics/fleet-missions
+pub async fn list_missions(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let missions = sqlx::query_as::<_, FleetMission>(&format!(
+        "SELECT {} FROM fleet_missions WHERE user_id = $1 ORDER BY created_at DESC LIMIT 100",
+        MISSION_COLUMNS
+    ))
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(missions))
+}
+
+/// A mission with each leg's state, latest readiness report and combined progress
+/// GET /api/robotics/fleet-missions/{mission_id}
+pub async fn get_mission(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let mission = owned_mission(pool.get_ref(), path.into_inner(), user.user_id).await?;
+
+    let legs = sqlx::query_as::<_, MissionLeg>(&format!(
+        "SELECT {} FROM mission_legs WHERE mission_id = $1 ORDER BY leg_index",
+        LEG_COLUMNS
+    ))
+    .bind(mission.id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    let acked: Vec<(Uuid, i64)> = sqlx::query_as(
+        "SELECT c.mission_leg_id, COUNT(*) FROM device_commands c \
+         JOIN mission_legs l ON l.id = c.mission_leg_id \
+         WHERE l.mission_id = $1 AND c.status = 'succeeded' GROUP BY c.mission_leg_id",
+    )
+    .bind(mission.id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    let leg_progress: Vec<(String, i64, i64)> = legs
+        .iter()
+        .map(|leg| {
+            let done = acked.iter().find(|(id, _)| *id == leg.id).map_or(0, |(_, n)| *n);
+            (leg.status.clone(), leg.command_count as i64, done)
+        })
+        .collect();
+    let legs: Vec<serde_json::Value> = legs
+        .into_iter()
+        .zip(&leg_progress)
+        .map(|(leg, (_, total, done))| {
+            serde_json::json!({ "leg": leg, "commands_total": total, "commands_succeeded": done })
+        })
+        .collect();
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "mission": mission,
+        "progress": mission_services::mission_progress(&leg_progress),
+        "legs": legs,
+    })))
+}
+
+/// Re-run the readiness checks for a mission waiting on a handoff, dispatching the
+/// next leg if they now pass
+/// POST /api/robotics/fleet-missions/{mission_id}/advance
+pub async fn advance_mission(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let mission_id = path.into_inner();
+    let checks = mission_services::try_advance(pool.get_ref(), mission_id, user.user_id).await?;
+    let mission = owned_mission(pool.get_ref(), mission_id, user.user_id).await?;
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "mission": mission,
+        "dispatched": checks.iter().all(|c| c.passed),
+        "readiness": checks,
+    })))
+}
+
+/// Cancel a mission: pending legs are dropped and queued commands of the running leg withdrawn
+/// POST /api/robotics/fleet-missions/{mission_id}/cancel
+pub async fn cancel_mission(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let mut tx = pool.begin().await?;
+
+    let mission = sqlx::query_as::<_, FleetMission>(&format!(
+        "UPDATE fleet_missions SET status = 'cancelled', updated_at = NOW() \
+         WHERE id = $1 AND user_id = $2 AND status IN ('active', 'awaiting_handoff') RETURNING {}",
+        MISSION_COLUMNS
+    ))
+    .bind(path.into_inner())
+    .bind(user.user_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("No running mission with that id".to_string()))?;
+
+    sqlx::query(
+        "UPDATE device_commands SET status = 'cancelled', acked_at = NOW() \
+         WHERE acked_at IS NULL AND mission_leg_id IN \
+         (SELECT id FROM mission_legs WHERE mission_id = $1 AND status = 'dispatched')",
+    )
+    .bind(mission.id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "UPDATE mission_legs SET status = 'cancelled' WHERE mission_id = $1 AND status IN ('pending', 'dispatched')",
+    )
+    .bind(mission.id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::success(mission))
+}
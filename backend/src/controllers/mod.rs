@@ -0,0 +1,77 @@
+pub mod ai_ctrl;
+pub mod auth_ctrl;
+pub mod blockchain_ctrl;
+pub mod dashboard_ctrl;
+pub mod robotics_ctrl;
+pub mod device_import_ctrl;
+pub mod org_ctrl;
+pub mod sso_ctrl;
+pub mod scim_ctrl;
+pub mod telemetry_ctrl;
+pub mod geo_ctrl;
+pub mod audit_ctrl;
+pub mod break_glass_ctrl;
+pub mod notification_ctrl;
+pub mod security_ctrl;
+pub mod command_ctrl;
+pub mod provisioning_ctrl;
+pub mod session_ctrl;
+pub mod stream_ctrl;
+pub mod key_ctrl;
+pub mod path_ctrl;
+pub mod swarm_ctrl;
+pub mod firmware_ctrl;
+pub mod compliance_ctrl;
+pub mod mission_ctrl;
+pub mod sensor_ctrl;
+pub mod attachment_ctrl;
+pub mod metadata_ctrl;
+pub mod promotion_ctrl;
+pub mod processor_ctrl;
+pub mod webhook_ctrl;
+pub mod uptime_ctrl;
+pub mod automation_ctrl;
+pub mod template_ctrl;
+pub mod metric_ctrl;
+pub mod capacity_ctrl;
+pub mod deprecation_ctrl;
+pub mod energy_ctrl;
+pub mod incident_ctrl;
+pub mod inbound_email_ctrl;
+pub mod calendar_ctrl;
+pub mod siwe_ctrl;
+pub mod payment_webhook_ctrl;
+pub mod integration_ctrl;
+pub mod fleet_config_ctrl;
+pub mod backup_ctrl;
+pub mod constraint_ctrl;
+pub mod billing_ctrl;
+pub mod open_data_ctrl;
+pub mod ai_budget_ctrl;
+pub mod public_stats_ctrl;
+pub mod region_ctrl;
+pub mod chain_ctrl;
+pub mod wallet_ctrl;
+pub mod support_ctrl;
+pub mod device_certificate_ctrl;
+pub mod transfer_ctrl;
+pub mod transaction_export_ctrl;
+pub mod invoice_ctrl;
+pub mod escrow_ctrl;
+pub mod receipt_ctrl;
+pub mod onchain_event_ctrl;
+pub mod transaction_ctrl;
+pub mod reconciliation_ctrl;
+pub mod kyc_ctrl;
+pub mod payout_ctrl;
+pub mod spending_ctrl;
+pub mod ledger_ctrl;
+pub mod coupon_ctrl;
+pub mod dispute_ctrl;
+pub mod conversation_ctrl;
+pub mod embedding_ctrl;
+pub mod rag_ctrl;
+pub mod vision_ctrl;
+pub mod speech_ctrl;
+pub mod insight_ctrl;
+pub mod fine_tune_ctrl;
@@ -0,0 +1,52 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::notification::{Notification, NotificationQuery};
+
+/// List the current user's notifications, newest first
+/// GET /api/notifications
+pub async fn list_notifications(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<NotificationQuery>,
+) -> ApiResult<HttpResponse> {
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+
+    let notifications = sqlx::query_as::<_, Notification>(
+        "SELECT id, user_id, kind, title, body, data, read_at, created_at FROM notifications \
+         WHERE user_id = $1 AND (NOT $2 OR read_at IS NULL) \
+         ORDER BY created_at DESC LIMIT $3",
+    )
+    .bind(user.user_id)
+    .bind(query.unread_only.unwrap_or(false))
+    .bind(limit)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(notifications))
+}
+
+/// Mark a notification as read
+/// POST /api/notifications/{notification_id}/read
+pub async fn mark_read(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let updated = sqlx::query(
+        "UPDATE notifications SET read_at = COALESCE(read_at, NOW()) WHERE id = $1 AND user_id = $2",
+    )
+    .bind(path.into_inner())
+    .bind(user.user_id)
+    .execute(pool.get_ref().as_ref())
+    .await?;
+
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Notification not found".to_string()));
+    }
+
+    Ok(crate::errors::success_message("Notification marked as read"))
+}
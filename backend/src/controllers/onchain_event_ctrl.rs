@@ -0,0 +1,20 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::errors::{ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::onchain_event::OnchainEventQuery;
+use crate::services::event_indexer_services;
+
+/// Indexed token transfers and device certificate events, newest first. Filter by `chain_id`,
+/// `contract`, `event`, `address` (sender or recipient), `device_id` and block range; page
+/// with `before` set to the last id seen.
+/// GET /api/blockchain/events
+pub async fn list_events(
+    _user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<OnchainEventQuery>,
+) -> ApiResult<HttpResponse> {
+    let events = event_indexer_services::query(pool.get_ref(), &query).await?;
+    Ok(ApiResponse::success(events))
+}
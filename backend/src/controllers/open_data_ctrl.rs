@@ -0,0 +1,135 @@
+use actix_web::{http::header, web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::open_data::{CatalogQuery, OpenDataset, PublishDatasetRequest};
+use crate::services::object_storage_services::ObjectStore;
+use crate::services::open_data_services::{self, DATASET_COLUMNS, DOWNLOAD_URL_TTL_SECS, LICENSES};
+
+fn open_data_store(config: &AppConfig) -> ApiResult<ObjectStore> {
+    ObjectStore::for_open_data(config)
+        .ok_or_else(|| ApiError::ServiceUnavailable("Open data storage is not configured".to_string()))
+}
+
+/// Licenses datasets can be published under
+/// GET /api/open-data/licenses
+pub async fn list_licenses() -> ApiResult<HttpResponse> {
+    Ok(ApiResponse::success(LICENSES))
+}
+
+/// The public catalog, newest first. Filter with `license`, `device_type` and `q`; page with
+/// `limit` and `offset`.
+/// GET /api/open-data/datasets
+pub async fn list_datasets(
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<CatalogQuery>,
+) -> ApiResult<HttpResponse> {
+    let page = open_data_services::catalog(pool.get_ref(), &query).await?;
+    Ok(ApiResponse::success(page))
+}
+
+/// Catalog-wide counts, with noise and small groups withheld so no publisher stands out
+/// GET /api/open-data/stats
+pub async fn get_stats(pool: web::Data<Arc<PgPool>>, config: web::Data<AppConfig>) -> ApiResult<HttpResponse> {
+    let stats = open_data_services::catalog_stats(pool.get_ref(), config.public_stats_privacy).await?;
+    Ok(ApiResponse::success(stats))
+}
+
+/// A published dataset with a download link valid for a few minutes
+/// GET /api/open-data/datasets/{slug}
+pub async fn get_dataset(
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<String>,
+) -> ApiResult<HttpResponse> {
+    let store = open_data_store(&config)?;
+    let entry = open_data_services::catalog_entry(pool.get_ref(), &store, &path).await?;
+    Ok(ApiResponse::success(entry))
+}
+
+/// Redirect to the dataset's CSV, for bulk downloads with `curl -L` and the like
+/// GET /api/open-data/datasets/{slug}/download
+pub async fn download_dataset(
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<String>,
+) -> ApiResult<HttpResponse> {
+    let store = open_data_store(&config)?;
+    let entry = open_data_services::catalog_entry(pool.get_ref(), &store, &path).await?;
+    let url = entry.download_url.unwrap_or_default();
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, url))
+        .insert_header((header::CACHE_CONTROL, format!("private, max-age={}", DOWNLOAD_URL_TTL_SECS / 2)))
+        .finish())
+}
+
+/// Join the open data program, allowing the user to publish datasets
+/// POST /api/open-data/opt-in
+pub async fn opt_in(user: AuthenticatedUser, pool: web::Data<Arc<PgPool>>) -> ApiResult<HttpResponse> {
+    let opted_in_at: chrono::DateTime<chrono::Utc> = sqlx::query_scalar(
+        "UPDATE users SET open_data_opt_in_at = COALESCE(open_data_opt_in_at, NOW()) WHERE id = $1 \
+         RETURNING open_data_opt_in_at",
+    )
+    .bind(user.user_id)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(serde_json::json!({ "opted_in_at": opted_in_at })))
+}
+
+/// Leave the program, withdrawing every dataset the user published
+/// DELETE /api/open-data/opt-in
+pub async fn opt_out(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+) -> ApiResult<HttpResponse> {
+    let store = ObjectStore::for_open_data(&config);
+    let withdrawn = open_data_services::opt_out(pool.get_ref(), store.as_ref(), user.user_id).await?;
+    Ok(ApiResponse::success(serde_json::json!({ "withdrawn_datasets": withdrawn })))
+}
+
+/// The user's datasets in every state, newest first
+/// GET /api/open-data/my-datasets
+pub async fn list_my_datasets(user: AuthenticatedUser, pool: web::Data<Arc<PgPool>>) -> ApiResult<HttpResponse> {
+    let datasets = sqlx::query_as::<_, OpenDataset>(&format!(
+        "SELECT {} FROM open_datasets WHERE user_id = $1 ORDER BY created_at DESC",
+        DATASET_COLUMNS
+    ))
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(datasets))
+}
+
+/// Publish an anonymized dataset of the user's telemetry. It is built in the background and
+/// appears in the catalog once uploaded; the user is notified either way.
+/// POST /api/open-data/my-datasets
+pub async fn publish_dataset(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<PublishDatasetRequest>,
+) -> ApiResult<HttpResponse> {
+    let store = open_data_store(&config)?;
+    let dataset = open_data_services::publish(pool.get_ref().clone(), store, user.user_id, &body).await?;
+    Ok(ApiResponse::created(dataset))
+}
+
+/// Take a dataset out of the catalog and delete its file
+/// DELETE /api/open-data/my-datasets/{dataset_id}
+pub async fn withdraw_dataset(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let store = ObjectStore::for_open_data(&config);
+    let dataset =
+        open_data_services::withdraw(pool.get_ref(), store.as_ref(), user.user_id, path.into_inner()).await?;
+    Ok(ApiResponse::success(dataset))
+}
@@ -0,0 +1,135 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use validator::Validate;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::org::{CreateOrgRequest, Organization};
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::org_services::{privacy_policy, require_org_permission};
+use crate::services::policy_services::OrgAction;
+use crate::utils::redaction::{RedactionPolicy, MAX_GPS_DECIMALS};
+
+/// Create an organization; the caller becomes its owner
+/// POST /api/orgs
+pub async fn create_org(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<CreateOrgRequest>,
+) -> ApiResult<HttpResponse> {
+    body.validate()?;
+
+    let slug = body.slug.trim().to_lowercase();
+    if !slug.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(ApiError::ValidationError("Slug may only contain letters, digits and dashes".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let org = sqlx::query_as::<_, Organization>(
+        "INSERT INTO organizations (name, slug) VALUES ($1, $2) \
+         RETURNING id, name, slug, sso_enforced, created_at",
+    )
+    .bind(body.name.trim())
+    .bind(&slug)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query("INSERT INTO org_memberships (org_id, user_id, role) VALUES ($1, $2, 'owner')")
+        .bind(org.id)
+        .bind(user.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(ApiResponse::created(org))
+}
+
+/// List organizations the current user belongs to
+/// GET /api/orgs
+pub async fn list_orgs(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let rows: Vec<(uuid::Uuid, String, String, bool, String)> = sqlx::query_as(
+        "SELECT o.id, o.name, o.slug, o.sso_enforced, m.role \
+         FROM organizations o JOIN org_memberships m ON m.org_id = o.id \
+         WHERE m.user_id = $1 ORDER BY o.name",
+    )
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    let orgs: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|(id, name, slug, sso_enforced, role)| {
+            serde_json::json!({
+                "id": id,
+                "name": name,
+                "slug": slug,
+                "sso_enforced": sso_enforced,
+                "role": role,
+            })
+        })
+        .collect();
+
+    Ok(ApiResponse::success(orgs))
+}
+
+/// PII redaction applied to the org's webhook payloads and exports
+/// GET /api/orgs/{org_id}/privacy
+pub async fn get_privacy_settings(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<uuid::Uuid>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ViewMembers).await?;
+
+    Ok(ApiResponse::success(privacy_policy(pool.get_ref(), org_id).await?))
+}
+
+/// Configure which PII is masked in the org's webhook payloads and exports
+/// PUT /api/orgs/{org_id}/privacy
+pub async fn update_privacy_settings(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<uuid::Uuid>,
+    body: web::Json<RedactionPolicy>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManagePrivacy).await?;
+
+    if body.gps_decimals.is_some_and(|d| d > MAX_GPS_DECIMALS) {
+        return Err(ApiError::ValidationError(format!(
+            "gps_decimals must be at most {}",
+            MAX_GPS_DECIMALS
+        )));
+    }
+    let settings = serde_json::to_value(*body)
+        .map_err(|e| ApiError::InternalError(format!("Failed to encode privacy settings: {}", e)))?;
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE organizations SET privacy_settings = $2 WHERE id = $1")
+        .bind(org_id)
+        .bind(&settings)
+        .execute(&mut *tx)
+        .await?;
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "privacy.updated",
+            resource_type: "organization",
+            resource_id: Some(org_id.to_string()),
+            details: settings.clone(),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::success(settings))
+}
@@ -0,0 +1,307 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::device::{DevicePath, PathPoint, PathSummary, RecordPathRequest, ReplayPathRequest, TrackQuery};
+use crate::services::device_services::get_owned_device;
+use crate::services::path_services::{
+    path_length_m, path_to_commands, simplify_track, track_feature, DEFAULT_MIN_SPACING_M, MAX_PATH_POINTS,
+};
+use crate::services::robotics_services::RoboticsService;
+
+const PATH_COLUMNS: &str =
+    "id, device_id, user_id, name, points, point_count, distance_m, started_at, ended_at, created_at";
+
+/// Most commands a single replay may enqueue
+const MAX_REPLAY_COMMANDS: usize = 500;
+
+/// Longest window a track may cover, and most raw samples read for it
+const MAX_TRACK_DAYS: i64 = 31;
+const MAX_TRACK_SAMPLES: i64 = 50_000;
+
+async fn track_samples(
+    pool: &PgPool,
+    device_id: Uuid,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> ApiResult<Vec<PathPoint>> {
+    let rows: Vec<(f64, f64, Option<f64>, DateTime<Utc>)> = sqlx::query_as(
+        "SELECT latitude, longitude, altitude, recorded_at FROM device_telemetry \
+         WHERE device_id = $1 AND recorded_at >= $2 AND recorded_at <= $3 \
+         ORDER BY recorded_at LIMIT $4",
+    )
+    .bind(device_id)
+    .bind(from)
+    .bind(to)
+    .bind(MAX_TRACK_SAMPLES)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(latitude, longitude, altitude, recorded_at)| PathPoint { latitude, longitude, altitude, recorded_at })
+        .collect())
+}
+
+fn validate_spacing(spacing: f64) -> ApiResult<()> {
+    if !(0.0..=1000.0).contains(&spacing) {
+        return Err(ApiError::ValidationError("min_spacing_m must be between 0 and 1000".to_string()));
+    }
+    Ok(())
+}
+
+async fn load_path(pool: &PgPool, path_id: Uuid, user_id: Uuid) -> ApiResult<DevicePath> {
+    sqlx::query_as::<_, DevicePath>(&format!(
+        "SELECT {} FROM device_paths WHERE id = $1 AND user_id = $2",
+        PATH_COLUMNS
+    ))
+    .bind(path_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Path not found".to_string()))
+}
+
+/// Record the device's GPS track over a time window as a named path
+/// POST /api/robotics/devices/{device_id}/paths
+pub async fn record_path(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<RecordPathRequest>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+
+    let name = body.name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return Err(ApiError::ValidationError("Path name must be 1-100 characters".to_string()));
+    }
+    if body.from >= body.to {
+        return Err(ApiError::ValidationError("`from` must be before `to`".to_string()));
+    }
+    let spacing = body.min_spacing_m.unwrap_or(DEFAULT_MIN_SPACING_M);
+    validate_spacing(spacing)?;
+
+    let samples = track_samples(pool.get_ref(), device.id, body.from, body.to).await?;
+    let points = simplify_track(&samples, spacing);
+    if points.len() < 2 {
+        return Err(ApiError::ValidationError("Not enough movement in that window to record a path".to_string()));
+    }
+    if points.len() > MAX_PATH_POINTS {
+        return Err(ApiError::ValidationError(format!(
+            "Path has {} points; increase min_spacing_m or shorten the window (max {})",
+            points.len(),
+            MAX_PATH_POINTS
+        )));
+    }
+
+    let recorded = sqlx::query_as::<_, DevicePath>(&format!(
+        "INSERT INTO device_paths (device_id, user_id, name, points, point_count, distance_m, started_at, ended_at) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING {}",
+        PATH_COLUMNS
+    ))
+    .bind(device.id)
+    .bind(user.user_id)
+    .bind(name)
+    .bind(sqlx::types::Json(&points))
+    .bind(points.len() as i32)
+    .bind(path_length_m(&points))
+    .bind(points[0].recorded_at)
+    .bind(points[points.len() - 1].recorded_at)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::created(recorded))
+}
+
+/// The device's positions over a time window as a GeoJSON LineString Feature, served as
+/// `application/geo+json` so it can be added to a Leaflet or Mapbox map as is.
+/// `format=json` returns the simplified points in the usual envelope instead.
+/// GET /api/robotics/devices/{device_id}/track
+pub async fn get_track(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    query: web::Query<TrackQuery>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - Duration::hours(24));
+    if from >= to || to - from > Duration::days(MAX_TRACK_DAYS) {
+        return Err(ApiError::ValidationError(format!(
+            "`from` must be before `to` and the window at most {} days",
+            MAX_TRACK_DAYS
+        )));
+    }
+    let spacing = query.min_spacing_m.unwrap_or(DEFAULT_MIN_SPACING_M);
+    validate_spacing(spacing)?;
+    let format = query.format.as_deref().unwrap_or("geojson");
+    if !matches!(format, "geojson" | "json") {
+        return Err(ApiError::ValidationError("format must be geojson or json".to_string()));
+    }
+
+    let samples = track_samples(pool.get_ref(), device.id, from, to).await?;
+    // A full read means the window was cut short; the track ends at the last sample read
+    let truncated = samples.len() as i64 == MAX_TRACK_SAMPLES;
+    let points = simplify_track(&samples, spacing);
+
+    if format == "json" {
+        return Ok(ApiResponse::success(serde_json::json!({
+            "device_id": device.id,
+            "from": from,
+            "to": to,
+            "truncated": truncated,
+            "distance_m": path_length_m(&points),
+            "points": points,
+        })));
+    }
+    let feature = track_feature(
+        &points,
+        serde_json::json!({
+            "device_id": device.id,
+            "device_name": device.device_name,
+            "from": from,
+            "to": to,
+            "truncated": truncated,
+        }),
+    );
+    Ok(HttpResponse::Ok().content_type("application/geo+json").json(feature))
+}
+
+/// Paths recorded from a device (without their points)
+/// GET /api/robotics/devices/{device_id}/paths
+pub async fn list_paths(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+
+    let paths = sqlx::query_as::<_, PathSummary>(
+        "SELECT id, name, point_count, distance_m, started_at, ended_at, created_at FROM device_paths \
+         WHERE device_id = $1 AND user_id = $2 ORDER BY created_at DESC",
+    )
+    .bind(device.id)
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(paths))
+}
+
+/// A recorded path with its points
+/// GET /api/robotics/devices/{device_id}/paths/{path_id}
+pub async fn get_path(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (device_id, path_id) = path.into_inner();
+    let recorded = load_path(pool.get_ref(), path_id, user.user_id).await?;
+    if recorded.device_id != device_id {
+        return Err(ApiError::NotFound("Path not found".to_string()));
+    }
+
+    Ok(ApiResponse::success(recorded))
+}
+
+/// DELETE /api/robotics/devices/{device_id}/paths/{path_id}
+pub async fn delete_path(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (device_id, path_id) = path.into_inner();
+    let result = sqlx::query("DELETE FROM device_paths WHERE id = $1 AND device_id = $2 AND user_id = $3")
+        .bind(path_id)
+        .bind(device_id)
+        .bind(user.user_id)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Path not found".to_string()));
+    }
+
+    Ok(crate::errors::success_message("Path deleted"))
+}
+
+/// Replay a recorded path on a device by queueing the equivalent movement commands.
+/// The path may have been recorded by any of the caller's devices.
+/// POST /api/robotics/devices/{device_id}/replay/{path_id}
+pub async fn replay_path(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+    body: Option<web::Json<ReplayPathRequest>>,
+) -> ApiResult<HttpResponse> {
+    let (device_id, path_id) = path.into_inner();
+    let options = body.map(|b| b.into_inner()).unwrap_or_default();
+
+    let device = get_owned_device(pool.get_ref(), device_id, user.user_id).await?;
+    if device.status == "offline" {
+        return Err(ApiError::BadRequest("Device is offline".to_string()));
+    }
+    let recorded = load_path(pool.get_ref(), path_id, user.user_id).await?;
+
+    let commands = path_to_commands(
+        &device.device_type,
+        &recorded.points,
+        options.speed.unwrap_or(0.5),
+        options.initial_heading,
+    )?;
+    if commands.len() > MAX_REPLAY_COMMANDS {
+        return Err(ApiError::ValidationError(format!(
+            "Replay would queue {} commands (max {})",
+            commands.len(),
+            MAX_REPLAY_COMMANDS
+        )));
+    }
+
+    let service = RoboticsService::new();
+    let mut tx = pool.begin().await?;
+    let mut total_duration_ms = 0u64;
+    let mut total_battery_drain = 0f32;
+    let mut command_ids = Vec::with_capacity(commands.len());
+
+    for (sequence, (command, parameters)) in commands.iter().enumerate() {
+        service.validate_command(&device.device_type, &device.firmware_version, command)?;
+        let params = service.parse_command_params(command, parameters)?;
+        let estimated_duration_ms = service.estimate_duration_ms(&params);
+        let estimated_battery_drain = service.estimate_battery_drain(command, &params);
+        total_duration_ms += estimated_duration_ms;
+        total_battery_drain += estimated_battery_drain;
+
+        let command_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO device_commands \
+             (device_id, user_id, command, parameters, estimated_duration_ms, estimated_battery_drain, path_id, sequence) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
+        )
+        .bind(device.id)
+        .bind(user.user_id)
+        .bind(command)
+        .bind(parameters)
+        .bind(estimated_duration_ms as i64)
+        .bind(estimated_battery_drain)
+        .bind(recorded.id)
+        .bind(sequence as i32)
+        .fetch_one(&mut *tx)
+        .await?;
+        command_ids.push(command_id);
+    }
+
+    tx.commit().await?;
+
+    Ok(ApiResponse::created(serde_json::json!({
+        "path_id": recorded.id,
+        "device_id": device.id,
+        "queued_commands": command_ids.len(),
+        "command_ids": command_ids,
+        "estimated_duration_ms": total_duration_ms,
+        "estimated_battery_drain": total_battery_drain,
+    })))
+}
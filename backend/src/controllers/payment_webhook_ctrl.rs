@@ -0,0 +1,239 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use rust_decimal::Decimal;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::transaction::{
+    CreateRazorpayOrderRequest, ProductEntitlement, RazorpayPaymentCallback, RefundRequest, Transaction,
+};
+use crate::services::payment_services::{complete_transaction, minor_units, validate_product_type, TRANSACTION_COLUMNS};
+use crate::services::fraud_services::{self, PaymentAttempt};
+use crate::services::{coupon_services, razorpay_services, refund_services, security_services};
+use crate::services::stripe_services::{self, StripeEvent};
+
+/// Stripe webhook endpoint, authenticated by the `Stripe-Signature` header over the raw body.
+/// Events it does not act on are acknowledged so Stripe does not retry them.
+/// POST /api/blockchain/webhooks/stripe
+pub async fn stripe_webhook(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Bytes,
+) -> ApiResult<HttpResponse> {
+    let secret = config
+        .stripe_webhook_secret
+        .as_ref()
+        .ok_or_else(|| ApiError::ServiceUnavailable("Stripe webhooks are not configured".to_string()))?;
+    let header = req.headers().get("Stripe-Signature").and_then(|v| v.to_str().ok()).unwrap_or_default();
+    let now = Utc::now().timestamp();
+    if !stripe_services::verify_signature(secret.expose_secret().as_bytes(), header, &body, now) {
+        return Err(ApiError::Unauthorized("Invalid Stripe signature".to_string()));
+    }
+
+    let event: StripeEvent = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid Stripe event: {}", e)))?;
+    let outcome = stripe_services::process_event(pool.get_ref(), &event).await?;
+    tracing::info!(event_id = %event.id, event_type = %event.event_type, outcome, "Handled Stripe webhook");
+
+    Ok(ApiResponse::success(serde_json::json!({ "received": true, "outcome": outcome })))
+}
+
+/// Open a Razorpay order for a product, less a coupon when one is given; the response carries
+/// what Razorpay Checkout needs. A coupon covering the whole price completes the payment at once.
+/// POST /api/blockchain/razorpay/orders
+pub async fn create_razorpay_order(
+    req: HttpRequest,
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<CreateRazorpayOrderRequest>,
+) -> ApiResult<HttpResponse> {
+    if !razorpay_services::is_configured(&config) {
+        return Err(ApiError::ServiceUnavailable("Razorpay is not configured".to_string()));
+    }
+    validate_product_type(&body.product_type)?;
+
+    let transaction_id = Uuid::new_v4();
+    let currency = "usd";
+    let price = config.product_price_usd;
+    let coupon_code = body.coupon_code.as_deref().map(str::trim).filter(|c| !c.is_empty());
+    // Quoted once, under the coupon's lock, so the order is opened for the discount that is stored
+    let mut tx = pool.begin().await?;
+    let quote = match coupon_code {
+        Some(code) => {
+            Some(coupon_services::quote(&mut tx, user.user_id, code, &body.product_type, price, currency).await?)
+        }
+        None => None,
+    };
+    let amount = quote.as_ref().map_or(price, |q| q.total);
+    let country = security_services::edge_country(&req);
+    let attempt = PaymentAttempt { user_id: user.user_id, kind: "card", amount, currency, country: country.as_deref() };
+    fraud_services::screen(pool.get_ref(), &config, &attempt).await?;
+    let free = minor_units(amount, currency) == 0;
+    let order = if free {
+        None
+    } else {
+        let order = razorpay_services::create_order(
+            &config,
+            minor_units(amount, currency),
+            currency,
+            &transaction_id.to_string(),
+        )
+        .await?;
+        Some(order)
+    };
+
+    let (payment_method, payment_id) = match &order {
+        Some(order) => (razorpay_services::PROVIDER, order.id.clone()),
+        None => (coupon_services::FREE_PAYMENT_METHOD, transaction_id.to_string()),
+    };
+    let transaction = sqlx::query_as::<_, Transaction>(&format!(
+        "INSERT INTO transactions (id, user_id, amount, currency, payment_method, payment_id, status, product_type, \
+         coupon_id, discount_amount) VALUES ($1, $2, $3, $4, $5, $6, 'pending', $7, $8, $9) RETURNING {}",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(transaction_id)
+    .bind(user.user_id)
+    .bind(amount)
+    .bind(currency)
+    .bind(payment_method)
+    .bind(&payment_id)
+    .bind(&body.product_type)
+    .bind(quote.as_ref().map(|q| q.coupon_id))
+    .bind(quote.as_ref().map_or(Decimal::ZERO, |q| q.discount))
+    .fetch_one(&mut *tx)
+    .await?;
+    if let Some(quote) = &quote {
+        coupon_services::redeem(&mut tx, quote, user.user_id, transaction_id).await?;
+    }
+    let Some(order) = order else {
+        complete_transaction(&mut tx, &transaction).await?;
+        tx.commit().await?;
+        return Ok(ApiResponse::created(serde_json::json!({
+            "transaction_id": transaction_id,
+            "status": "completed",
+            "amount": 0,
+            "currency": currency,
+            "discount": quote.map(|q| q.discount),
+        })));
+    };
+    tx.commit().await?;
+
+    Ok(ApiResponse::created(serde_json::json!({
+        "transaction_id": transaction_id,
+        "order_id": order.id,
+        "amount": order.amount,
+        "currency": order.currency,
+        "key_id": config.razorpay_key_id,
+        "discount": quote.map(|q| q.discount),
+    })))
+}
+
+/// Razorpay Checkout callback, authenticated by `razorpay_signature` over the order and
+/// payment ids. Once Razorpay confirms the payment was captured for the order's amount, completes
+/// the order's transaction and unlocks the product.
+/// POST /api/blockchain/razorpay/verify
+pub async fn razorpay_callback(
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<RazorpayPaymentCallback>,
+) -> ApiResult<HttpResponse> {
+    if !razorpay_services::is_configured(&config) {
+        return Err(ApiError::ServiceUnavailable("Razorpay is not configured".to_string()));
+    }
+    if !razorpay_services::verify_payment_signature(
+        config.razorpay_key_secret.expose_secret().as_bytes(),
+        &body.razorpay_order_id,
+        &body.razorpay_payment_id,
+        &body.razorpay_signature,
+    ) {
+        return Err(ApiError::Unauthorized("Invalid Razorpay signature".to_string()));
+    }
+
+    let payment = razorpay_services::fetch_payment(&config, &body.razorpay_payment_id).await?;
+    if payment.order_id.as_deref() != Some(body.razorpay_order_id.as_str()) {
+        return Err(ApiError::BadRequest("Razorpay payment is not for this order".to_string()));
+    }
+    let (transaction_id, outcome) = razorpay_services::settle_payment(pool.get_ref(), &payment).await?;
+    tracing::info!(%transaction_id, payment_id = %payment.id, outcome, "Handled Razorpay payment");
+
+    Ok(ApiResponse::success(serde_json::json!({ "transaction_id": transaction_id, "outcome": outcome })))
+}
+
+/// Razorpay webhook endpoint, authenticated by the `X-Razorpay-Signature` header over the raw
+/// body. `payment.captured` and `order.paid` settle the order as the Checkout callback does, so
+/// whichever arrives second is a duplicate; other events are acknowledged and ignored.
+/// POST /api/blockchain/webhooks/razorpay
+pub async fn razorpay_webhook(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Bytes,
+) -> ApiResult<HttpResponse> {
+    let secret = config
+        .razorpay_webhook_secret
+        .as_ref()
+        .ok_or_else(|| ApiError::ServiceUnavailable("Razorpay webhooks are not configured".to_string()))?;
+    let signature = req.headers().get("X-Razorpay-Signature").and_then(|v| v.to_str().ok()).unwrap_or_default();
+    if !razorpay_services::verify_webhook_signature(secret.expose_secret().as_bytes(), &body, signature) {
+        return Err(ApiError::Unauthorized("Invalid Razorpay signature".to_string()));
+    }
+
+    let event: razorpay_services::RazorpayWebhookEvent = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid Razorpay event: {}", e)))?;
+    let outcome = match (event.event.as_str(), event.payment()) {
+        ("payment.captured" | "order.paid", Some(payment)) => {
+            match razorpay_services::settle_payment(pool.get_ref(), payment).await {
+                Ok((_, outcome)) => outcome,
+                // Payments for orders opened elsewhere on the same Razorpay account are not ours
+                Err(ApiError::NotFound(_) | ApiError::BadRequest(_)) => "unknown_order",
+                Err(e) => return Err(e),
+            }
+        }
+        _ => "ignored",
+    };
+    tracing::info!(event_type = %event.event, outcome, "Handled Razorpay webhook");
+
+    Ok(ApiResponse::success(serde_json::json!({ "received": true, "outcome": outcome })))
+}
+
+/// Products the current user has unlocked
+/// GET /api/blockchain/entitlements
+pub async fn list_entitlements(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let entitlements = sqlx::query_as::<_, ProductEntitlement>(
+        "SELECT id, product_type, transaction_id, granted_at, frozen_at FROM product_entitlements \
+         WHERE user_id = $1 ORDER BY granted_at",
+    )
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(entitlements))
+}
+
+/// Refund a completed transaction through the provider it was paid with. Buyers can refund
+/// their own purchases for a limited time; admins any purchase, and they alone can record a
+/// `manual` refund made outside the provider. A full refund revokes the product.
+/// POST /api/blockchain/transactions/{transaction_id}/refund
+pub async fn refund_transaction(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+    body: web::Json<RefundRequest>,
+) -> ApiResult<HttpResponse> {
+    let is_admin = user.claims.role.as_deref() == Some("admin");
+    let refund =
+        refund_services::refund_transaction(pool.get_ref(), &config, path.into_inner(), user.user_id, is_admin, &body)
+            .await?;
+
+    Ok(ApiResponse::created(refund))
+}
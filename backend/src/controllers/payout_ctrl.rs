@@ -0,0 +1,178 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::{AdminUser, AuthenticatedUser};
+use crate::models::payout::{
+    CreatePayoutRequest, CreditAdjustmentRequest, MarkPayoutPaidRequest, PayoutQueueQuery, ReviewPayoutRequest,
+};
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::payout_services;
+
+/// The caller's credit balance and latest credit entries
+/// GET /api/payouts/credits
+pub async fn get_credits(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+) -> ApiResult<HttpResponse> {
+    let credits = payout_services::credits(pool.get_ref(), &config, user.user_id).await?;
+    Ok(ApiResponse::success(credits))
+}
+
+/// The caller's payouts, newest first
+/// GET /api/payouts
+pub async fn list_payouts(user: AuthenticatedUser, pool: web::Data<Arc<PgPool>>) -> ApiResult<HttpResponse> {
+    let payouts = payout_services::list(pool.get_ref(), user.user_id).await?;
+    Ok(ApiResponse::success(payouts))
+}
+
+/// Request a payout of credits by bank transfer, to a Stripe connected account or to the
+/// linked wallet. Needs a verified identity; the credits are held until an admin reviews it.
+/// POST /api/payouts
+pub async fn request_payout(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<CreatePayoutRequest>,
+) -> ApiResult<HttpResponse> {
+    let payout = payout_services::request(pool.get_ref(), &config, user.user_id, &body).await?;
+    Ok(ApiResponse::created(payout))
+}
+
+/// One of the caller's payouts with its history
+/// GET /api/payouts/{payout_id}
+pub async fn get_payout(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let payout = payout_services::user_payout(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let details = payout_services::details(pool.get_ref(), payout).await?;
+    Ok(ApiResponse::success(details))
+}
+
+/// Cancel a payout not yet reviewed, putting the credits back
+/// POST /api/payouts/{payout_id}/cancel
+pub async fn cancel_payout(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let payout = payout_services::cancel(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    Ok(ApiResponse::success(payout))
+}
+
+/// Payouts in `status`, requested by default, longest waiting first
+/// GET /api/admin/payouts
+pub async fn list_queue(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<PayoutQueueQuery>,
+) -> ApiResult<HttpResponse> {
+    let payouts = payout_services::queue(pool.get_ref(), query.status.as_deref()).await?;
+    Ok(ApiResponse::success(payouts))
+}
+
+/// Any payout with its history
+/// GET /api/admin/payouts/{payout_id}
+pub async fn admin_get_payout(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let payout = payout_services::get_payout(pool.get_ref(), path.into_inner()).await?;
+    let details = payout_services::details(pool.get_ref(), payout).await?;
+    Ok(ApiResponse::success(details))
+}
+
+async fn audit(
+    pool: &PgPool,
+    admin_id: Uuid,
+    action: &str,
+    payout_id: Uuid,
+    details: serde_json::Value,
+) -> ApiResult<()> {
+    let mut tx = pool.begin().await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin_id),
+            action,
+            resource_type: "payout",
+            resource_id: Some(payout_id.to_string()),
+            details,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Approve a requested payout, sending Stripe and crypto payouts at once, or reject it
+/// POST /api/admin/payouts/{payout_id}/review
+pub async fn review_payout(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+    body: web::Json<ReviewPayoutRequest>,
+) -> ApiResult<HttpResponse> {
+    let approve = match body.decision.as_str() {
+        "approve" => true,
+        "reject" => false,
+        _ => return Err(ApiError::ValidationError("decision must be approve or reject".to_string())),
+    };
+    let payout_id = path.into_inner();
+    let payout =
+        payout_services::review(pool.get_ref(), &config, admin.0.user_id, payout_id, approve, body.note.as_deref())
+            .await?;
+    let details = serde_json::json!({ "decision": body.decision, "note": body.note });
+    audit(pool.get_ref(), admin.0.user_id, "payout.reviewed", payout.id, details).await?;
+    Ok(ApiResponse::success(payout))
+}
+
+/// Mark an approved bank payout paid with the bank's transfer reference
+/// POST /api/admin/payouts/{payout_id}/paid
+pub async fn mark_paid(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<MarkPayoutPaidRequest>,
+) -> ApiResult<HttpResponse> {
+    let payout =
+        payout_services::mark_paid(pool.get_ref(), admin.0.user_id, path.into_inner(), &body.reference).await?;
+    let details = serde_json::json!({ "reference": payout.provider_reference });
+    audit(pool.get_ref(), admin.0.user_id, "payout.marked_paid", payout.id, details).await?;
+    Ok(ApiResponse::success(payout))
+}
+
+/// Grant a user credits, or take them back with a negative amount
+/// POST /api/admin/credits
+pub async fn adjust_credits(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<CreditAdjustmentRequest>,
+) -> ApiResult<HttpResponse> {
+    let entry = payout_services::adjust(pool.get_ref(), &config, admin.0.user_id, &body).await?;
+
+    let mut tx = pool.begin().await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin.0.user_id),
+            action: "credits.adjusted",
+            resource_type: "user",
+            resource_id: Some(entry.user_id.to_string()),
+            details: serde_json::json!({ "amount": entry.amount, "source": entry.source, "note": entry.note }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(ApiResponse::created(entry))
+}
@@ -0,0 +1,186 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::processor::{
+    CreateProcessorRequest, TelemetryProcessor, TestProcessorRequest, UpdateProcessorRequest,
+};
+use crate::services::device_services::owned_device_scope;
+use crate::services::processor_services::{
+    self, decode_module, module_sha256, ProcessorRuntime, MAX_PROCESSORS_PER_USER, PROCESSOR_COLUMNS,
+};
+
+async fn owned_processor(pool: &PgPool, processor_id: Uuid, user_id: Uuid) -> ApiResult<TelemetryProcessor> {
+    sqlx::query_as::<_, TelemetryProcessor>(&format!(
+        "SELECT {} FROM telemetry_processors WHERE id = $1 AND user_id = $2",
+        PROCESSOR_COLUMNS
+    ))
+    .bind(processor_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Processor not found".to_string()))
+}
+
+fn validate_name(name: &str) -> ApiResult<()> {
+    if name.is_empty() || name.len() > 100 {
+        return Err(ApiError::ValidationError("name must be 1-100 characters".to_string()));
+    }
+    Ok(())
+}
+
+/// GET /api/robotics/processors
+pub async fn list_processors(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let processors = sqlx::query_as::<_, TelemetryProcessor>(&format!(
+        "SELECT {} FROM telemetry_processors WHERE user_id = $1 ORDER BY created_at",
+        PROCESSOR_COLUMNS
+    ))
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(processors))
+}
+
+/// Upload a WASM module to run on incoming telemetry; it is checked against the processor ABI first
+/// POST /api/robotics/processors
+pub async fn create_processor(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    runtime: web::Data<Arc<ProcessorRuntime>>,
+    body: web::Json<CreateProcessorRequest>,
+) -> ApiResult<HttpResponse> {
+    let name = body.name.trim();
+    validate_name(name)?;
+    let module = decode_module(&body.module)?;
+    runtime.compile(&module)?;
+    let device_ids = owned_device_scope(pool.get_ref(), user.user_id, body.device_ids.as_deref()).await?;
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM telemetry_processors WHERE user_id = $1")
+        .bind(user.user_id)
+        .fetch_one(pool.get_ref().as_ref())
+        .await?;
+    if count >= MAX_PROCESSORS_PER_USER {
+        return Err(ApiError::ValidationError(format!(
+            "At most {} processors per account",
+            MAX_PROCESSORS_PER_USER
+        )));
+    }
+
+    let processor = sqlx::query_as::<_, TelemetryProcessor>(&format!(
+        "INSERT INTO telemetry_processors \
+         (user_id, name, description, module, module_sha256, module_size, device_ids, enabled) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING {}",
+        PROCESSOR_COLUMNS
+    ))
+    .bind(user.user_id)
+    .bind(name)
+    .bind(body.description.as_deref().map(str::trim))
+    .bind(&module)
+    .bind(module_sha256(&module))
+    .bind(module.len() as i32)
+    .bind(device_ids)
+    .bind(body.enabled.unwrap_or(true))
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::created(processor))
+}
+
+/// GET /api/robotics/processors/{processor_id}
+pub async fn get_processor(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let processor = owned_processor(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    Ok(ApiResponse::success(processor))
+}
+
+/// Rename, rescope, enable/disable or replace the module of a processor
+/// PATCH /api/robotics/processors/{processor_id}
+pub async fn update_processor(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    runtime: web::Data<Arc<ProcessorRuntime>>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateProcessorRequest>,
+) -> ApiResult<HttpResponse> {
+    let processor = owned_processor(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let name = body.name.as_deref().map(str::trim);
+    if let Some(name) = name {
+        validate_name(name)?;
+    }
+    let module = body.module.as_deref().map(decode_module).transpose()?;
+    if let Some(module) = &module {
+        runtime.compile(module)?;
+    }
+    let rescope = body.device_ids.is_some();
+    let device_ids = owned_device_scope(pool.get_ref(), user.user_id, body.device_ids.as_deref()).await?;
+
+    let processor = sqlx::query_as::<_, TelemetryProcessor>(&format!(
+        "UPDATE telemetry_processors SET name = COALESCE($2, name), description = COALESCE($3, description), \
+         module = COALESCE($4, module), module_sha256 = COALESCE($5, module_sha256), \
+         module_size = COALESCE($6, module_size), \
+         device_ids = CASE WHEN $7 THEN $8 ELSE device_ids END, enabled = COALESCE($9, enabled), \
+         consecutive_failures = CASE WHEN $4 IS NOT NULL OR $9 THEN 0 ELSE consecutive_failures END, \
+         updated_at = NOW() WHERE id = $1 RETURNING {}",
+        PROCESSOR_COLUMNS
+    ))
+    .bind(processor.id)
+    .bind(name)
+    .bind(body.description.as_deref().map(str::trim))
+    .bind(module.as_deref())
+    .bind(module.as_deref().map(module_sha256))
+    .bind(module.as_ref().map(|m| m.len() as i32))
+    .bind(rescope)
+    .bind(device_ids)
+    .bind(body.enabled)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(processor))
+}
+
+/// DELETE /api/robotics/processors/{processor_id}
+pub async fn delete_processor(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let deleted = sqlx::query("DELETE FROM telemetry_processors WHERE id = $1 AND user_id = $2")
+        .bind(path.into_inner())
+        .bind(user.user_id)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Processor not found".to_string()));
+    }
+
+    Ok(crate::errors::success_message("Processor deleted"))
+}
+
+/// Run a processor on a sample document and return its output; nothing is stored or notified
+/// POST /api/robotics/processors/{processor_id}/test
+pub async fn test_processor(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    runtime: web::Data<Arc<ProcessorRuntime>>,
+    path: web::Path<Uuid>,
+    body: web::Json<TestProcessorRequest>,
+) -> ApiResult<HttpResponse> {
+    let processor = owned_processor(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let module =
+        processor_services::load_module(pool.get_ref(), runtime.get_ref(), processor.id, &processor.module_sha256)
+            .await?;
+
+    let input = serde_json::json!({ "device_id": null, "telemetry": body.telemetry });
+    let output = runtime.run(module, &input).await.map_err(ApiError::BadRequest)?;
+
+    Ok(ApiResponse::success(output))
+}
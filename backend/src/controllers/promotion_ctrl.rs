@@ -0,0 +1,374 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::{AdminUser, AuthenticatedUser};
+use crate::models::promotion::{
+    CommandMacro, CreateMacroRequest, CreatePromotionRequest, ReviewPromotionRequest, RunMacroRequest, SimPromotion,
+};
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::mission_services;
+use crate::services::promotion_services::{
+    self, validate_geofence, validate_macro_steps, MACRO_COLUMNS, PROMOTION_COLUMNS,
+};
+use crate::services::transport_services::TransportRegistry;
+
+async fn get_promotion_by_id(pool: &PgPool, promotion_id: Uuid) -> ApiResult<SimPromotion> {
+    sqlx::query_as::<_, SimPromotion>(&format!("SELECT {} FROM sim_promotions WHERE id = $1", PROMOTION_COLUMNS))
+        .bind(promotion_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Promotion not found".to_string()))
+}
+
+/// Save a command sequence for a device type
+/// POST /api/robotics/macros
+pub async fn create_macro(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<CreateMacroRequest>,
+) -> ApiResult<HttpResponse> {
+    let name = body.name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return Err(ApiError::ValidationError("name must be 1-100 characters".to_string()));
+    }
+    validate_macro_steps(&body.device_type, &body.steps)?;
+
+    let command_macro =
+        promotion_services::insert_macro(pool.get_ref(), user.user_id, name, &body.device_type, &body.steps).await?;
+
+    Ok(ApiResponse::created(command_macro))
+}
+
+/// GET /api/robotics/macros
+pub async fn list_macros(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let macros = sqlx::query_as::<_, CommandMacro>(&format!(
+        "SELECT {} FROM command_macros WHERE user_id = $1 ORDER BY name",
+        MACRO_COLUMNS
+    ))
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(macros))
+}
+
+/// DELETE /api/robotics/macros/{macro_id}
+pub async fn delete_macro(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let deleted = sqlx::query("DELETE FROM command_macros WHERE id = $1 AND user_id = $2")
+        .bind(path.into_inner())
+        .bind(user.user_id)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Macro not found".to_string()));
+    }
+
+    Ok(crate::errors::success_message("Macro deleted"))
+}
+
+/// Run a macro on a simulated device; successful runs are what a promotion is checked against
+/// POST /api/robotics/macros/{macro_id}/run
+pub async fn run_macro(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    transports: web::Data<Arc<TransportRegistry>>,
+    path: web::Path<Uuid>,
+    body: web::Json<RunMacroRequest>,
+) -> ApiResult<HttpResponse> {
+    let command_macro = promotion_services::owned_macro(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let mut profiles = promotion_services::device_profiles(pool.get_ref(), user.user_id, &[body.device_id]).await?;
+    let device = profiles
+        .remove(&body.device_id)
+        .ok_or_else(|| ApiError::NotFound("Device not found".to_string()))?;
+
+    if !device.is_simulated() {
+        return Err(ApiError::BadRequest(
+            "Macros run directly only on simulated devices; promote them to reach real hardware".to_string(),
+        ));
+    }
+    if device.device_type != command_macro.device_type {
+        return Err(ApiError::ValidationError(format!(
+            "Macro is for {} devices, not {}",
+            command_macro.device_type, device.device_type
+        )));
+    }
+
+    let command_ids =
+        promotion_services::queue_macro(pool.get_ref(), transports.get_ref(), user.user_id, &command_macro, &device)
+            .await?;
+
+    Ok(ApiResponse::created(serde_json::json!({
+        "macro_id": command_macro.id,
+        "device_id": device.id,
+        "command_ids": command_ids,
+    })))
+}
+
+/// Validate a simulated macro or mission against production devices and submit it for approval.
+/// The request is stored either way; failed checks leave it `invalid` with the reasons attached.
+/// POST /api/robotics/promotions
+pub async fn create_promotion(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<CreatePromotionRequest>,
+) -> ApiResult<HttpResponse> {
+    if let Some(geofence) = &body.geofence {
+        validate_geofence(geofence)?;
+    }
+    let evaluation = promotion_services::evaluate(
+        pool.get_ref(),
+        user.user_id,
+        &body.source_kind,
+        body.source_id,
+        &body.device_map,
+        body.geofence.as_ref(),
+    )
+    .await?;
+
+    let promotion = sqlx::query_as::<_, SimPromotion>(&format!(
+        "INSERT INTO sim_promotions (user_id, source_kind, source_id, device_map, geofence, status, checks, diff) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING {}",
+        PROMOTION_COLUMNS
+    ))
+    .bind(user.user_id)
+    .bind(&body.source_kind)
+    .bind(body.source_id)
+    .bind(sqlx::types::Json(&body.device_map))
+    .bind(body.geofence.as_ref().map(sqlx::types::Json))
+    .bind(if evaluation.passed { "pending_approval" } else { "invalid" })
+    .bind(sqlx::types::Json(&evaluation.checks))
+    .bind(sqlx::types::Json(&evaluation.diff))
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::created(promotion))
+}
+
+/// GET /api/robotics/promotions
+pub async fn list_promotions(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let promotions = sqlx::query_as::<_, SimPromotion>(&format!(
+        "SELECT {} FROM sim_promotions WHERE user_id = $1 ORDER BY created_at DESC LIMIT 100",
+        PROMOTION_COLUMNS
+    ))
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(promotions))
+}
+
+/// Promotions waiting on an admin
+/// GET /api/robotics/promotions/pending
+pub async fn list_pending_promotions(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let promotions = sqlx::query_as::<_, SimPromotion>(&format!(
+        "SELECT {} FROM sim_promotions WHERE status = 'pending_approval' ORDER BY created_at",
+        PROMOTION_COLUMNS
+    ))
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(promotions))
+}
+
+/// GET /api/robotics/promotions/{promotion_id}
+pub async fn get_promotion(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let promotion = get_promotion_by_id(pool.get_ref(), path.into_inner()).await?;
+    if promotion.user_id != user.user_id && user.claims.role.as_deref() != Some("admin") {
+        return Err(ApiError::NotFound("Promotion not found".to_string()));
+    }
+
+    Ok(ApiResponse::success(promotion))
+}
+
+/// Approve a promotion and run it on the production devices. Checks are re-run first,
+/// since the devices may have changed since the request was made.
+/// POST /api/robotics/promotions/{promotion_id}/approve
+pub async fn approve_promotion(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    transports: web::Data<Arc<TransportRegistry>>,
+    path: web::Path<Uuid>,
+    body: Option<web::Json<ReviewPromotionRequest>>,
+) -> ApiResult<HttpResponse> {
+    let note = body.map(|b| b.into_inner()).unwrap_or_default().note;
+    let promotion = get_promotion_by_id(pool.get_ref(), path.into_inner()).await?;
+    if promotion.status != "pending_approval" {
+        return Err(ApiError::Conflict(format!("Promotion is {}", promotion.status)));
+    }
+    if promotion.user_id == admin.0.user_id {
+        return Err(ApiError::Forbidden("A promotion must be approved by someone other than its requester".to_string()));
+    }
+
+    let device_map: HashMap<Uuid, Uuid> = serde_json::from_value(promotion.device_map.clone())
+        .map_err(|e| ApiError::InternalError(format!("Stored device map is unreadable: {}", e)))?;
+    let geofence = promotion.geofence.as_ref().map(|g| &g.0);
+    let evaluation = promotion_services::evaluate(
+        pool.get_ref(),
+        promotion.user_id,
+        &promotion.source_kind,
+        promotion.source_id,
+        &device_map,
+        geofence,
+    )
+    .await?;
+
+    if !evaluation.passed {
+        let promotion = sqlx::query_as::<_, SimPromotion>(&format!(
+            "UPDATE sim_promotions SET status = 'invalid', checks = $2, diff = $3 \
+             WHERE id = $1 AND status = 'pending_approval' RETURNING {}",
+            PROMOTION_COLUMNS
+        ))
+        .bind(promotion.id)
+        .bind(sqlx::types::Json(&evaluation.checks))
+        .bind(sqlx::types::Json(&evaluation.diff))
+        .fetch_optional(pool.get_ref().as_ref())
+        .await?
+        .ok_or_else(|| ApiError::Conflict("Promotion was reviewed concurrently".to_string()))?;
+        return Ok(ApiResponse::success(serde_json::json!({ "promotion": promotion, "executed": false })));
+    }
+
+    // Claim the promotion before touching hardware so it cannot run twice
+    let mut tx = pool.begin().await?;
+    let claimed = sqlx::query(
+        "UPDATE sim_promotions SET status = 'approved', checks = $2, diff = $3, reviewed_by = $4, \
+         reviewed_at = NOW(), review_note = $5 WHERE id = $1 AND status = 'pending_approval'",
+    )
+    .bind(promotion.id)
+    .bind(sqlx::types::Json(&evaluation.checks))
+    .bind(sqlx::types::Json(&evaluation.diff))
+    .bind(admin.0.user_id)
+    .bind(note.as_deref().map(str::trim))
+    .execute(&mut *tx)
+    .await?;
+    if claimed.rows_affected() == 0 {
+        return Err(ApiError::Conflict("Promotion was reviewed concurrently".to_string()));
+    }
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin.0.user_id),
+            action: "robotics.promotion_approved",
+            resource_type: "sim_promotion",
+            resource_id: Some(promotion.id.to_string()),
+            details: serde_json::json!({
+                "requested_by": promotion.user_id,
+                "source_kind": promotion.source_kind,
+                "source_id": promotion.source_id,
+                "device_map": promotion.device_map,
+            }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    let (executed_ref, command_ids) = match promotion.source_kind.as_str() {
+        "macro" => {
+            let command_macro =
+                promotion_services::owned_macro(pool.get_ref(), promotion.source_id, promotion.user_id).await?;
+            let targets: Vec<Uuid> = device_map.values().copied().collect();
+            let profiles = promotion_services::device_profiles(pool.get_ref(), promotion.user_id, &targets).await?;
+            let mut command_ids = Vec::new();
+            for device in profiles.values() {
+                command_ids.extend(
+                    promotion_services::queue_macro(
+                        pool.get_ref(),
+                        transports.get_ref(),
+                        promotion.user_id,
+                        &command_macro,
+                        device,
+                    )
+                    .await?,
+                );
+            }
+            (None, command_ids)
+        }
+        _ => {
+            let (name, legs) =
+                promotion_services::mission_legs(pool.get_ref(), promotion.source_id, promotion.user_id).await?;
+            let legs = promotion_services::remap_legs(&legs, &device_map)?;
+            let mut name = format!("{} (production)", name);
+            name.truncate(100);
+            let mission = mission_services::insert_mission(pool.get_ref(), promotion.user_id, &name, &legs).await?;
+            mission_services::try_advance(pool.get_ref(), mission.id, promotion.user_id).await?;
+            (Some(mission.id), Vec::new())
+        }
+    };
+
+    let promotion = sqlx::query_as::<_, SimPromotion>(&format!(
+        "UPDATE sim_promotions SET status = 'executed', executed_ref = $2 WHERE id = $1 RETURNING {}",
+        PROMOTION_COLUMNS
+    ))
+    .bind(promotion.id)
+    .bind(executed_ref)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "promotion": promotion,
+        "executed": true,
+        "command_ids": command_ids,
+    })))
+}
+
+/// POST /api/robotics/promotions/{promotion_id}/reject
+pub async fn reject_promotion(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<ReviewPromotionRequest>,
+) -> ApiResult<HttpResponse> {
+    let note = body.note.as_deref().map(str::trim).filter(|n| !n.is_empty());
+    let Some(note) = note else {
+        return Err(ApiError::ValidationError("A note explaining the rejection is required".to_string()));
+    };
+
+    let mut tx = pool.begin().await?;
+    let promotion = sqlx::query_as::<_, SimPromotion>(&format!(
+        "UPDATE sim_promotions SET status = 'rejected', reviewed_by = $2, reviewed_at = NOW(), review_note = $3 \
+         WHERE id = $1 AND status = 'pending_approval' RETURNING {}",
+        PROMOTION_COLUMNS
+    ))
+    .bind(path.into_inner())
+    .bind(admin.0.user_id)
+    .bind(note)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("No pending promotion with that id".to_string()))?;
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin.0.user_id),
+            action: "robotics.promotion_rejected",
+            resource_type: "sim_promotion",
+            resource_id: Some(promotion.id.to_string()),
+            details: serde_json::json!({ "requested_by": promotion.user_id, "note": note }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::success(promotion))
+}
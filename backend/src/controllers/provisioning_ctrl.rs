@@ -0,0 +1,231 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::device::{ClaimCode, CreateClaimCodeRequest, ProvisionDeviceRequest, RegisterDeviceRequest};
+use crate::services::device_services::get_owned_device;
+use crate::services::provisioning_services::{
+    generate_claim_code, is_valid_hardware_id, normalize_claim_code, CLAIM_CODE_TTL_HOURS,
+};
+use crate::services::robotics_services::RoboticsService;
+use crate::utils::{generate_random_hex, log_security_event, sha256_hash};
+
+const CLAIM_CODE_COLUMNS: &str = "id, user_id, device_name, device_type, expires_at, claimed_at, device_id, created_at";
+
+fn new_device_key() -> (String, String) {
+    let key = format!("dk_{}", generate_random_hex(32));
+    let hash = sha256_hash(key.as_bytes());
+    (key, hash)
+}
+
+/// Issue (or rotate) the API key a device uses to authenticate itself; shown once
+/// POST /api/robotics/devices/{device_id}/credentials
+pub async fn rotate_device_key(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+
+    let (key, key_hash) = new_device_key();
+    sqlx::query("UPDATE devices SET api_key_hash = $2 WHERE id = $1")
+        .bind(device.id)
+        .bind(&key_hash)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+
+    Ok(ApiResponse::created(serde_json::json!({
+        "device_id": device.id,
+        "device_key": key,
+    })))
+}
+
+/// Pre-generate a claim code that a physical device can use to bind itself to the caller
+/// POST /api/robotics/claim-codes
+pub async fn create_claim_code(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<CreateClaimCodeRequest>,
+) -> ApiResult<HttpResponse> {
+    if let Some(device_type) = &body.device_type
+        && !["drone", "robot", "rover"].contains(&device_type.as_str())
+    {
+        return Err(ApiError::ValidationError(format!("Unknown device type: {}", device_type)));
+    }
+    let device_name = body.device_name.as_deref().map(str::trim).filter(|n| !n.is_empty());
+    if device_name.is_some_and(|n| n.len() > 100) {
+        return Err(ApiError::ValidationError("Device name must be 1-100 characters".to_string()));
+    }
+
+    let code = generate_claim_code();
+    let claim = sqlx::query_as::<_, ClaimCode>(&format!(
+        "INSERT INTO device_claim_codes (user_id, code_hash, device_name, device_type, expires_at) \
+         VALUES ($1, $2, $3, $4, $5) RETURNING {}",
+        CLAIM_CODE_COLUMNS
+    ))
+    .bind(user.user_id)
+    .bind(sha256_hash(normalize_claim_code(&code).as_bytes()))
+    .bind(device_name)
+    .bind(&body.device_type)
+    .bind(Utc::now() + Duration::hours(CLAIM_CODE_TTL_HOURS))
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::created(serde_json::json!({
+        "claim_code": code,
+        "claim": claim,
+    })))
+}
+
+/// List the caller's claim codes
+/// GET /api/robotics/claim-codes
+pub async fn list_claim_codes(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let claims = sqlx::query_as::<_, ClaimCode>(&format!(
+        "SELECT {} FROM device_claim_codes WHERE user_id = $1 ORDER BY created_at DESC LIMIT 100",
+        CLAIM_CODE_COLUMNS
+    ))
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(claims))
+}
+
+/// Revoke an unclaimed code
+/// DELETE /api/robotics/claim-codes/{claim_id}
+pub async fn revoke_claim_code(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let deleted = sqlx::query("DELETE FROM device_claim_codes WHERE id = $1 AND user_id = $2 AND claimed_at IS NULL")
+        .bind(path.into_inner())
+        .bind(user.user_id)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Claim code not found".to_string()));
+    }
+
+    Ok(crate::errors::success_message("Claim code revoked"))
+}
+
+/// Called by the physical device with its claim code and hardware ID. Binds the device
+/// to the claiming user's account and returns the device's API key (shown once).
+/// POST /api/robotics/provision
+pub async fn provision_device(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<ProvisionDeviceRequest>,
+) -> ApiResult<HttpResponse> {
+    let ip = req.connection_info().realip_remote_addr().map(String::from);
+    let hardware_id = body.hardware_id.trim();
+    if !is_valid_hardware_id(hardware_id) {
+        return Err(ApiError::ValidationError("Invalid hardware_id".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let claim = sqlx::query_as::<_, ClaimCode>(&format!(
+        "SELECT {} FROM device_claim_codes \
+         WHERE code_hash = $1 AND claimed_at IS NULL AND expires_at > NOW() FOR UPDATE",
+        CLAIM_CODE_COLUMNS
+    ))
+    .bind(sha256_hash(normalize_claim_code(&body.claim_code).as_bytes()))
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let claim = match claim {
+        Some(claim) => claim,
+        None => {
+            log_security_event("invalid_claim_code", ip.as_deref(), &format!("hardware_id={}", hardware_id));
+            return Err(ApiError::Unauthorized("Invalid or expired claim code".to_string()));
+        }
+    };
+
+    if let Some(expected) = &claim.device_type
+        && expected != &body.device_type
+    {
+        return Err(ApiError::ValidationError(format!(
+            "This claim code is for a {}, not a {}",
+            expected, body.device_type
+        )));
+    }
+
+    let suffix: String = hardware_id.chars().rev().take(6).collect::<Vec<_>>().into_iter().rev().collect();
+    let registration = RegisterDeviceRequest {
+        device_name: body
+            .device_name
+            .clone()
+            .or_else(|| claim.device_name.clone())
+            .unwrap_or_else(|| format!("{}-{}", body.device_type, suffix)),
+        device_type: body.device_type.clone(),
+        firmware_version: body.firmware_version.clone(),
+    };
+    RoboticsService::new().validate_registration(&registration)?;
+
+    let existing: Option<(Uuid, Uuid)> = sqlx::query_as("SELECT id, user_id FROM devices WHERE hardware_id = $1 FOR UPDATE")
+        .bind(hardware_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+    let (key, key_hash) = new_device_key();
+    let device_id: Uuid = match existing {
+        // Re-provisioning hardware already on the account (e.g. after a factory reset)
+        Some((device_id, owner)) if owner == claim.user_id => {
+            sqlx::query(
+                "UPDATE devices SET firmware_version = $2, api_key_hash = $3, status = 'online', last_seen = NOW() \
+                 WHERE id = $1",
+            )
+            .bind(device_id)
+            .bind(registration.firmware_version.trim())
+            .bind(&key_hash)
+            .execute(&mut *tx)
+            .await?;
+            device_id
+        }
+        Some(_) => {
+            log_security_event(
+                "claim_hardware_conflict",
+                ip.as_deref(),
+                &format!("hardware_id={} claim_id={}", hardware_id, claim.id),
+            );
+            return Err(ApiError::Conflict("This hardware is already registered to another account".to_string()));
+        }
+        None => {
+            sqlx::query_scalar(
+                "INSERT INTO devices \
+                 (user_id, device_name, device_type, firmware_version, status, metadata, hardware_id, api_key_hash, last_seen) \
+                 VALUES ($1, $2, $3, $4, 'online', '{}', $5, $6, NOW()) RETURNING id",
+            )
+            .bind(claim.user_id)
+            .bind(registration.device_name.trim())
+            .bind(&registration.device_type)
+            .bind(registration.firmware_version.trim())
+            .bind(hardware_id)
+            .bind(&key_hash)
+            .fetch_one(&mut *tx)
+            .await?
+        }
+    };
+
+    sqlx::query("UPDATE device_claim_codes SET claimed_at = NOW(), device_id = $2 WHERE id = $1")
+        .bind(claim.id)
+        .bind(device_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(ApiResponse::created(serde_json::json!({
+        "device_id": device_id,
+        "device_key": key,
+    })))
+}
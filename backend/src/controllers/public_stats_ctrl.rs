@@ -0,0 +1,14 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::config::AppConfig;
+use crate::errors::{ApiResponse, ApiResult};
+use crate::services::public_stats_services;
+
+/// Platform-wide figures with differential-privacy noise; groups with too few contributors
+/// are `null`. The same release is served for an hour.
+/// GET /api/dashboard/public-stats
+pub async fn get_public_stats(pool: web::Data<Arc<PgPool>>, config: web::Data<AppConfig>) -> ApiResult<HttpResponse> {
+    let stats = public_stats_services::public_stats(pool.get_ref(), config.public_stats_privacy).await?;
+    Ok(ApiResponse::success(stats))
+}
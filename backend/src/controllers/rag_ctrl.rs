@@ -0,0 +1,74 @@
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::embedding::{AskRequest, DocumentQuery, DocumentUploadQuery};
+use crate::services::ai_services::AIService;
+use crate::services::rag_services;
+
+/// Upload a text document (raw body, file name in the query) and index it in chunks
+/// POST /api/ai/documents?file_name=...&collection=...
+pub async fn upload_document(
+    user: AuthenticatedUser,
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    ai: web::Data<Arc<AIService>>,
+    query: web::Query<DocumentUploadQuery>,
+    body: web::Bytes,
+) -> ApiResult<HttpResponse> {
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("text/plain");
+
+    let document = rag_services::upload(
+        pool.get_ref(),
+        ai.get_ref(),
+        user.user_id,
+        &query.file_name,
+        query.collection.as_deref(),
+        content_type,
+        body.to_vec(),
+    )
+    .await?;
+    Ok(ApiResponse::created(document))
+}
+
+/// The caller's uploaded documents
+/// GET /api/ai/documents
+pub async fn list_documents(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<DocumentQuery>,
+) -> ApiResult<HttpResponse> {
+    let documents = rag_services::list(pool.get_ref(), user.user_id, query.collection.as_deref()).await?;
+    Ok(ApiResponse::success(documents))
+}
+
+/// Delete an uploaded document and its indexed chunks
+/// DELETE /api/ai/documents/{document_id}
+pub async fn delete_document(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    rag_services::delete(pool.get_ref(), user.user_id, path.into_inner()).await?;
+    Ok(crate::errors::success_message("Document deleted"))
+}
+
+/// Answer a question from the caller's documents, citing the chunks used
+/// POST /api/ai/ask
+pub async fn ask(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    ai: web::Data<Arc<AIService>>,
+    body: web::Json<AskRequest>,
+) -> ApiResult<HttpResponse> {
+    let answer = rag_services::ask(pool.get_ref(), config.get_ref(), ai.get_ref(), user.user_id, &body).await?;
+    Ok(ApiResponse::success(answer))
+}
@@ -0,0 +1,26 @@
+use actix_web::{http::header::CONTENT_DISPOSITION, web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::ApiResult;
+use crate::middleware::AuthenticatedUser;
+use crate::services::receipt_services;
+
+/// Download the PDF receipt of a completed purchase: buyer, product, amount with the tax it
+/// includes, and how it was paid
+/// GET /api/blockchain/transactions/{transaction_id}/receipt.pdf
+pub async fn download_receipt(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let (file_name, pdf) =
+        receipt_services::receipt(pool.get_ref(), &config, user.user_id, path.into_inner()).await?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/pdf")
+        .insert_header((CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)))
+        .insert_header(("Cache-Control", "private, no-store"))
+        .body(pdf))
+}
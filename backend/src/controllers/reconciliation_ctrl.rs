@@ -0,0 +1,118 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiResponse, ApiResult};
+use crate::middleware::AdminUser;
+use crate::models::transaction::{AdminTransactionQuery, DiscrepancyRequest, RecheckStuckRequest};
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::reconciliation_services;
+
+/// Transactions across all users, newest first. Filter with `user_id`, `payment_method`,
+/// `status`, `from`, `to` and `discrepancy`.
+/// GET /api/admin/transactions
+pub async fn list_transactions(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<AdminTransactionQuery>,
+) -> ApiResult<HttpResponse> {
+    let transactions = reconciliation_services::list(pool.get_ref(), &query).await?;
+    Ok(ApiResponse::success(transactions))
+}
+
+/// Any transaction with its settlement and discrepancy details
+/// GET /api/admin/transactions/{transaction_id}
+pub async fn get_transaction(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let transaction = reconciliation_services::get(pool.get_ref(), path.into_inner()).await?;
+    Ok(ApiResponse::success(transaction))
+}
+
+/// Flag a transaction whose records disagree with the provider's
+/// PUT /api/admin/transactions/{transaction_id}/discrepancy
+pub async fn flag_discrepancy(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<DiscrepancyRequest>,
+) -> ApiResult<HttpResponse> {
+    let transaction =
+        reconciliation_services::flag_discrepancy(pool.get_ref(), admin.0.user_id, path.into_inner(), &body.note)
+            .await?;
+    Ok(ApiResponse::success(transaction))
+}
+
+/// Clear the flag once the transaction is reconciled
+/// DELETE /api/admin/transactions/{transaction_id}/discrepancy
+pub async fn clear_discrepancy(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let transaction =
+        reconciliation_services::clear_discrepancy(pool.get_ref(), admin.0.user_id, path.into_inner()).await?;
+    Ok(ApiResponse::success(transaction))
+}
+
+/// Ask the provider about a pending transaction and settle it on the answer
+/// POST /api/admin/transactions/{transaction_id}/recheck
+pub async fn recheck_transaction(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let result = reconciliation_services::recheck(pool.get_ref(), &config, path.into_inner()).await?;
+
+    let mut tx = pool.begin().await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin.0.user_id),
+            action: "transaction.rechecked",
+            resource_type: "transaction",
+            resource_id: Some(result.transaction_id.to_string()),
+            details: serde_json::json!({ "provider_status": result.provider_status, "outcome": result.outcome }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(ApiResponse::success(result))
+}
+
+/// Recheck every transaction pending for longer than `older_than_minutes` (30 by default)
+/// POST /api/admin/transactions/recheck
+pub async fn recheck_stuck(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<RecheckStuckRequest>,
+) -> ApiResult<HttpResponse> {
+    let results = reconciliation_services::recheck_stuck(pool.get_ref(), &config, body.older_than_minutes).await?;
+
+    let settled = results.iter().filter(|r| matches!(r.outcome, "completed" | "failed" | "mismatch")).count();
+    let mut tx = pool.begin().await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin.0.user_id),
+            action: "transaction.bulk_rechecked",
+            resource_type: "transaction",
+            resource_id: None,
+            details: serde_json::json!({
+                "older_than_minutes": body.older_than_minutes,
+                "checked": results.len(),
+                "settled": settled,
+            }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(ApiResponse::success(results))
+}
@@ -0,0 +1,268 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::region::{CreateRegionRequest, Region, RegionSummary, RegionTeamRequest, UpdateRegionRequest};
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::org_services::require_org_permission;
+use crate::services::policy_services::OrgAction;
+use crate::services::region_services::{
+    self, org_region, validate_name, validate_polygon, MAX_REGIONS_PER_ORG, REGION_COLUMNS,
+};
+use crate::utils::geo::polygon_bounds;
+
+fn validate_description(description: Option<&str>) -> ApiResult<()> {
+    if description.is_some_and(|d| d.len() > 500) {
+        return Err(ApiError::ValidationError("description must be at most 500 characters".to_string()));
+    }
+    Ok(())
+}
+
+/// List an org's regions with their device counts and teams
+/// GET /api/orgs/{org_id}/regions
+pub async fn list_regions(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ReadDeviceHistory).await?;
+
+    let regions = sqlx::query_as::<_, RegionSummary>(&format!(
+        "SELECT {}, \
+                (SELECT COUNT(*) FROM device_regions dr WHERE dr.region_id = regions.id) AS device_count, \
+                ARRAY(SELECT rm.user_id FROM region_members rm WHERE rm.region_id = regions.id \
+                      ORDER BY rm.created_at) AS team \
+         FROM regions WHERE org_id = $1 ORDER BY name",
+        REGION_COLUMNS
+    ))
+    .bind(org_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(regions))
+}
+
+/// Define a region; devices of the org already inside it are assigned right away
+/// POST /api/orgs/{org_id}/regions
+pub async fn create_region(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<CreateRegionRequest>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageRegions).await?;
+
+    let name = validate_name(&body.name)?;
+    validate_description(body.description.as_deref())?;
+    let (min_lat, max_lat, min_lng, max_lng) = polygon_bounds(&validate_polygon(&body.polygon)?);
+
+    let mut tx = pool.begin().await?;
+    let (count, taken): (i64, bool) =
+        sqlx::query_as("SELECT COUNT(*), COALESCE(BOOL_OR(name = $2), FALSE) FROM regions WHERE org_id = $1")
+            .bind(org_id)
+            .bind(name)
+            .fetch_one(&mut *tx)
+            .await?;
+    if taken {
+        return Err(ApiError::Conflict(format!("A region named '{}' already exists", name)));
+    }
+    if count >= MAX_REGIONS_PER_ORG {
+        return Err(ApiError::ValidationError(format!(
+            "An organization can define at most {} regions",
+            MAX_REGIONS_PER_ORG
+        )));
+    }
+
+    let region = sqlx::query_as::<_, Region>(&format!(
+        "INSERT INTO regions (org_id, name, description, polygon, min_lat, max_lat, min_lng, max_lng, created_by) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING {}",
+        REGION_COLUMNS
+    ))
+    .bind(org_id)
+    .bind(name)
+    .bind(&body.description)
+    .bind(sqlx::types::Json(&body.polygon))
+    .bind(min_lat)
+    .bind(max_lat)
+    .bind(min_lng)
+    .bind(max_lng)
+    .bind(user.user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    let devices = region_services::reassign_region(&mut tx, region.id).await?;
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "region.created",
+            resource_type: "region",
+            resource_id: Some(region.id.to_string()),
+            details: serde_json::json!({ "name": region.name, "vertices": body.polygon.len(), "devices": devices }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::created(region))
+}
+
+/// Rename, describe or reshape a region. A new polygon reassigns the org's devices.
+/// PATCH /api/orgs/{org_id}/regions/{region_id}
+pub async fn update_region(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+    body: web::Json<UpdateRegionRequest>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, region_id) = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageRegions).await?;
+    let existing = org_region(pool.get_ref(), org_id, region_id).await?;
+
+    let name = body.name.as_deref().map(validate_name).transpose()?;
+    validate_description(body.description.as_deref())?;
+    let bounds = body.polygon.as_deref().map(validate_polygon).transpose()?.map(|vertices| polygon_bounds(&vertices));
+
+    let mut tx = pool.begin().await?;
+    if let Some(name) = name.filter(|n| *n != existing.name) {
+        let taken: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM regions WHERE org_id = $1 AND name = $2)")
+            .bind(org_id)
+            .bind(name)
+            .fetch_one(&mut *tx)
+            .await?;
+        if taken {
+            return Err(ApiError::Conflict(format!("A region named '{}' already exists", name)));
+        }
+    }
+
+    let region = sqlx::query_as::<_, Region>(&format!(
+        "UPDATE regions SET name = COALESCE($2, name), description = COALESCE($3, description), \
+         polygon = COALESCE($4, polygon), min_lat = COALESCE($5, min_lat), max_lat = COALESCE($6, max_lat), \
+         min_lng = COALESCE($7, min_lng), max_lng = COALESCE($8, max_lng), updated_at = NOW() \
+         WHERE id = $1 RETURNING {}",
+        REGION_COLUMNS
+    ))
+    .bind(region_id)
+    .bind(name)
+    .bind(&body.description)
+    .bind(body.polygon.as_ref().map(sqlx::types::Json))
+    .bind(bounds.map(|b| b.0))
+    .bind(bounds.map(|b| b.1))
+    .bind(bounds.map(|b| b.2))
+    .bind(bounds.map(|b| b.3))
+    .fetch_one(&mut *tx)
+    .await?;
+    let devices = match bounds {
+        Some(_) => Some(region_services::reassign_region(&mut tx, region_id).await?),
+        None => None,
+    };
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "region.updated",
+            resource_type: "region",
+            resource_id: Some(region_id.to_string()),
+            details: serde_json::json!({
+                "name": region.name,
+                "previous_name": name.filter(|n| *n != existing.name).map(|_| &existing.name),
+                "reshaped": bounds.is_some(),
+                "devices": devices,
+            }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::success(region))
+}
+
+/// Remove a region; its devices and team are released
+/// DELETE /api/orgs/{org_id}/regions/{region_id}
+pub async fn delete_region(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, region_id) = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageRegions).await?;
+
+    let mut tx = pool.begin().await?;
+    let name: Option<String> = sqlx::query_scalar("DELETE FROM regions WHERE id = $1 AND org_id = $2 RETURNING name")
+        .bind(region_id)
+        .bind(org_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let Some(name) = name else {
+        return Err(ApiError::NotFound("Region not found".to_string()));
+    };
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "region.deleted",
+            resource_type: "region",
+            resource_id: Some(region_id.to_string()),
+            details: serde_json::json!({ "name": name }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(crate::errors::success_message("Region deleted"))
+}
+
+/// Set the org members who receive alerts raised by devices in the region
+/// PUT /api/orgs/{org_id}/regions/{region_id}/team
+pub async fn set_team(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+    body: web::Json<RegionTeamRequest>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, region_id) = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageRegions).await?;
+    let region = org_region(pool.get_ref(), org_id, region_id).await?;
+
+    let mut tx = pool.begin().await?;
+    let team = region_services::set_team(&mut tx, &region, &body.user_ids).await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "region.team_updated",
+            resource_type: "region",
+            resource_id: Some(region_id.to_string()),
+            details: serde_json::json!({ "name": region.name, "team": team }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::success(serde_json::json!({ "region_id": region_id, "team": team })))
+}
+
+/// Status, battery and freshness of the devices currently in a region
+/// GET /api/orgs/{org_id}/regions/{region_id}/dashboard
+pub async fn get_dashboard(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, region_id) = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ReadDeviceHistory).await?;
+    let region = org_region(pool.get_ref(), org_id, region_id).await?;
+
+    let dashboard = region_services::dashboard(pool.get_ref(), region).await?;
+    Ok(ApiResponse::success(dashboard))
+}
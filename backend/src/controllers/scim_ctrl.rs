@@ -0,0 +1,715 @@
+use actix_web::{http::header::AUTHORIZATION, web, HttpRequest, HttpResponse, ResponseError};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use sqlx::{PgConnection, PgPool};
+use std::fmt;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::org_services::{create_external_user, email_domain_verified, require_org_permission};
+use crate::services::policy_services::OrgAction;
+use crate::services::scim_services::{
+    self, error_body, group_changes, group_resource, list_response, page_bounds, parse_filter,
+    patched_active, user_resource, GroupChange, ScimGroupRequest, ScimListQuery, ScimPatchRequest,
+    ScimUserRequest,
+};
+use crate::services::sso_services::SsoService;
+use crate::utils::{generate_random_hex, log_auth_event, sha256_hash};
+
+const SCIM_CONTENT_TYPE: &str = "application/scim+json";
+
+/// SCIM clients expect errors in the SCIM error schema rather than our usual envelope
+#[derive(Debug)]
+pub struct ScimError(ApiError);
+
+impl fmt::Display for ScimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl ResponseError for ScimError {
+    fn error_response(&self) -> HttpResponse {
+        let status = self.0.error_response().status();
+        HttpResponse::build(status)
+            .content_type(SCIM_CONTENT_TYPE)
+            .json(error_body(status.as_u16(), &self.0.to_string()))
+    }
+}
+
+impl From<ApiError> for ScimError {
+    fn from(err: ApiError) -> Self {
+        ScimError(err)
+    }
+}
+
+impl From<sqlx::Error> for ScimError {
+    fn from(err: sqlx::Error) -> Self {
+        ScimError(err.into())
+    }
+}
+
+type ScimResult<T> = Result<T, ScimError>;
+
+/// Resolve the org a SCIM bearer token belongs to
+async fn authenticate(req: &HttpRequest, pool: &PgPool) -> ScimResult<Uuid> {
+    let token = req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or_else(|| ApiError::Unauthorized("Missing SCIM bearer token".to_string()))?;
+
+    let org_id: Option<Uuid> = sqlx::query_scalar(
+        "UPDATE scim_tokens SET last_used_at = NOW() WHERE token_hash = $1 RETURNING org_id",
+    )
+    .bind(sha256_hash(token.as_bytes()))
+    .fetch_optional(pool)
+    .await?;
+
+    org_id.ok_or_else(|| ApiError::Unauthorized("Invalid SCIM token".to_string()).into())
+}
+
+/// SCIM payloads arrive as `application/scim+json`, which `web::Json` rejects
+fn parse_body<T: DeserializeOwned>(body: &web::Bytes) -> ScimResult<T> {
+    serde_json::from_slice(body)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid SCIM payload: {}", e)).into())
+}
+
+fn scim_response(status: actix_web::http::StatusCode, body: serde_json::Value) -> HttpResponse {
+    HttpResponse::build(status).content_type(SCIM_CONTENT_TYPE).json(body)
+}
+
+fn base_url(config: &AppConfig) -> &str {
+    config.api_base_url.trim_end_matches('/')
+}
+
+async fn load_user(conn: &mut PgConnection, config: &AppConfig, org_id: Uuid, user_id: Uuid) -> ScimResult<serde_json::Value> {
+    let row: Option<(String, bool, DateTime<Utc>)> = sqlx::query_as(
+        "SELECT u.email, m.active, m.created_at FROM org_memberships m \
+         JOIN users u ON u.id = m.user_id WHERE m.org_id = $1 AND m.user_id = $2",
+    )
+    .bind(org_id)
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let (email, active, created_at) = row.ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+    Ok(user_resource(base_url(config), user_id, &email, active, created_at))
+}
+
+async fn load_group(conn: &mut PgConnection, config: &AppConfig, org_id: Uuid, group_id: Uuid) -> ScimResult<serde_json::Value> {
+    let group: Option<(String, DateTime<Utc>)> = sqlx::query_as(
+        "SELECT display_name, created_at FROM scim_groups WHERE id = $1 AND org_id = $2",
+    )
+    .bind(group_id)
+    .bind(org_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let (display_name, created_at) = group.ok_or_else(|| ApiError::NotFound("Group not found".to_string()))?;
+
+    let members: Vec<(Uuid, String)> = sqlx::query_as(
+        "SELECT u.id, u.email FROM scim_group_members gm JOIN users u ON u.id = gm.user_id \
+         WHERE gm.group_id = $1 ORDER BY u.email",
+    )
+    .bind(group_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(group_resource(base_url(config), group_id, &display_name, &members, created_at))
+}
+
+async fn set_active(conn: &mut PgConnection, org_id: Uuid, user_id: Uuid, active: bool) -> ScimResult<()> {
+    let role: String =
+        sqlx::query_scalar("SELECT role FROM org_memberships WHERE org_id = $1 AND user_id = $2 FOR UPDATE")
+            .bind(org_id)
+            .bind(user_id)
+            .fetch_optional(&mut *conn)
+            .await?
+            .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+    // As with deprovisioning, the IdP cannot lock an org out of its owners
+    if !active && role == "owner" {
+        return Err(ApiError::Conflict("Organization owners cannot be deactivated through SCIM".to_string()).into());
+    }
+
+    sqlx::query("UPDATE org_memberships SET active = $3 WHERE org_id = $1 AND user_id = $2")
+        .bind(org_id)
+        .bind(user_id)
+        .bind(active)
+        .execute(&mut *conn)
+        .await?;
+
+    let event = if active { "scim_reactivate" } else { "scim_deactivate" };
+    log_auth_event(event, Some(&user_id.to_string()), true, Some(&org_id.to_string()));
+    Ok(())
+}
+
+/// Re-derive a member's org role from their SCIM groups and the org's SSO role mapping
+async fn sync_member_role(conn: &mut PgConnection, org_id: Uuid, user_id: Uuid) -> ScimResult<()> {
+    let groups: Vec<String> = sqlx::query_scalar(
+        "SELECT g.display_name FROM scim_groups g JOIN scim_group_members gm ON gm.group_id = g.id \
+         WHERE g.org_id = $1 AND gm.user_id = $2",
+    )
+    .bind(org_id)
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mapping: Option<(serde_json::Value, String)> = sqlx::query_as(
+        "SELECT role_mapping, default_role FROM org_sso_configs WHERE org_id = $1",
+    )
+    .bind(org_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let (role_mapping, default_role) = mapping.unwrap_or_else(|| (serde_json::json!({}), "member".to_string()));
+
+    let role = SsoService::map_role(&role_mapping, &groups, &default_role);
+    sqlx::query(
+        "UPDATE org_memberships SET role = $3 WHERE org_id = $1 AND user_id = $2 AND role <> 'owner'",
+    )
+    .bind(org_id)
+    .bind(user_id)
+    .bind(&role)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Apply membership changes to a group and resync the roles of everyone affected
+async fn apply_group_changes(
+    conn: &mut PgConnection,
+    org_id: Uuid,
+    group_id: Uuid,
+    changes: Vec<GroupChange>,
+) -> ScimResult<()> {
+    let mut affected: Vec<Uuid> = Vec::new();
+
+    for change in changes {
+        match change {
+            GroupChange::Rename(name) => {
+                sqlx::query("UPDATE scim_groups SET display_name = $2 WHERE id = $1")
+                    .bind(group_id)
+                    .bind(name)
+                    .execute(&mut *conn)
+                    .await?;
+                // A new name may map to a different role for every member
+                let members: Vec<Uuid> = sqlx::query_scalar("SELECT user_id FROM scim_group_members WHERE group_id = $1")
+                    .bind(group_id)
+                    .fetch_all(&mut *conn)
+                    .await?;
+                affected.extend(members);
+            }
+            GroupChange::ReplaceMembers(ids) => {
+                let previous: Vec<Uuid> = sqlx::query_scalar("DELETE FROM scim_group_members WHERE group_id = $1 RETURNING user_id")
+                    .bind(group_id)
+                    .fetch_all(&mut *conn)
+                    .await?;
+                affected.extend(previous);
+                add_members(conn, org_id, group_id, &ids).await?;
+                affected.extend(ids);
+            }
+            GroupChange::AddMembers(ids) => {
+                add_members(conn, org_id, group_id, &ids).await?;
+                affected.extend(ids);
+            }
+            GroupChange::RemoveMembers(ids) => {
+                sqlx::query("DELETE FROM scim_group_members WHERE group_id = $1 AND user_id = ANY($2)")
+                    .bind(group_id)
+                    .bind(&ids)
+                    .execute(&mut *conn)
+                    .await?;
+                affected.extend(ids);
+            }
+        }
+    }
+
+    affected.sort();
+    affected.dedup();
+    for user_id in affected {
+        sync_member_role(conn, org_id, user_id).await?;
+    }
+    Ok(())
+}
+
+async fn add_members(conn: &mut PgConnection, org_id: Uuid, group_id: Uuid, ids: &[Uuid]) -> ScimResult<()> {
+    // A member may be listed more than once
+    let mut ids = ids.to_vec();
+    ids.sort();
+    ids.dedup();
+
+    // Only existing org members may be placed in a group
+    let known: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM org_memberships WHERE org_id = $1 AND user_id = ANY($2)",
+    )
+    .bind(org_id)
+    .bind(&ids)
+    .fetch_one(&mut *conn)
+    .await?;
+    if known != ids.len() as i64 {
+        return Err(ApiError::BadRequest("Group members must be provisioned users of this organization".to_string()).into());
+    }
+
+    sqlx::query(
+        "INSERT INTO scim_group_members (group_id, user_id) SELECT $1, UNNEST($2::uuid[]) ON CONFLICT DO NOTHING",
+    )
+    .bind(group_id)
+    .bind(&ids)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Issue a SCIM bearer token for an org (shown once)
+/// POST /api/orgs/{org_id}/scim-tokens
+pub async fn create_token(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageIntegrations).await?;
+
+    let token = format!("scim_{}", generate_random_hex(32));
+    let mut tx = pool.begin().await?;
+    let id: Uuid = sqlx::query_scalar(
+        "INSERT INTO scim_tokens (org_id, token_hash, created_by) VALUES ($1, $2, $3) RETURNING id",
+    )
+    .bind(org_id)
+    .bind(sha256_hash(token.as_bytes()))
+    .bind(user.user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "scim.token_created",
+            resource_type: "scim_token",
+            resource_id: Some(id.to_string()),
+            details: serde_json::json!({}),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::created(serde_json::json!({
+        "id": id,
+        "token": token,
+    })))
+}
+
+/// Revoke a SCIM bearer token
+/// DELETE /api/orgs/{org_id}/scim-tokens/{token_id}
+pub async fn revoke_token(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, token_id) = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageIntegrations).await?;
+
+    let mut tx = pool.begin().await?;
+    let deleted = sqlx::query("DELETE FROM scim_tokens WHERE id = $1 AND org_id = $2")
+        .bind(token_id)
+        .bind(org_id)
+        .execute(&mut *tx)
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("SCIM token not found".to_string()));
+    }
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "scim.token_revoked",
+            resource_type: "scim_token",
+            resource_id: Some(token_id.to_string()),
+            details: serde_json::json!({}),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(crate::errors::success_message("SCIM token revoked"))
+}
+
+/// GET /scim/v2/Users
+pub async fn list_users(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    query: web::Query<ScimListQuery>,
+) -> ScimResult<HttpResponse> {
+    let org_id = authenticate(&req, &pool).await?;
+    let (offset, limit) = page_bounds(&query);
+
+    let email_filter = match query.filter.as_deref() {
+        Some(filter) => {
+            let filter = parse_filter(filter)?;
+            if filter.attribute != "userName" && filter.attribute != "emails.value" {
+                return Err(ApiError::BadRequest(format!("Filtering on {} is not supported", filter.attribute)).into());
+            }
+            Some(filter.value.to_lowercase())
+        }
+        None => None,
+    };
+
+    let total: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM org_memberships m JOIN users u ON u.id = m.user_id \
+         WHERE m.org_id = $1 AND ($2::text IS NULL OR LOWER(u.email) = $2)",
+    )
+    .bind(org_id)
+    .bind(&email_filter)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    let rows: Vec<(Uuid, String, bool, DateTime<Utc>)> = sqlx::query_as(
+        "SELECT u.id, u.email, m.active, m.created_at FROM org_memberships m JOIN users u ON u.id = m.user_id \
+         WHERE m.org_id = $1 AND ($2::text IS NULL OR LOWER(u.email) = $2) \
+         ORDER BY m.created_at, u.id OFFSET $3 LIMIT $4",
+    )
+    .bind(org_id)
+    .bind(&email_filter)
+    .bind(offset)
+    .bind(limit)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    let resources = rows
+        .into_iter()
+        .map(|(id, email, active, created_at)| user_resource(base_url(&config), id, &email, active, created_at))
+        .collect();
+
+    Ok(scim_response(actix_web::http::StatusCode::OK, list_response(resources, total, offset + 1)))
+}
+
+/// GET /scim/v2/Users/{id}
+pub async fn get_user(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+) -> ScimResult<HttpResponse> {
+    let org_id = authenticate(&req, &pool).await?;
+    let mut conn = pool.acquire().await?;
+    let user = load_user(&mut conn, &config, org_id, path.into_inner()).await?;
+    Ok(scim_response(actix_web::http::StatusCode::OK, user))
+}
+
+/// POST /scim/v2/Users
+pub async fn create_user(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Bytes,
+) -> ScimResult<HttpResponse> {
+    let org_id = authenticate(&req, &pool).await?;
+    let request: ScimUserRequest = parse_body(&body)?;
+    let email = request.email()?;
+
+    // SCIM only creates accounts on the org's verified domains; existing accounts are never adopted
+    let mut tx = pool.begin().await?;
+    if !email_domain_verified(&mut tx, org_id, &email).await? {
+        return Err(ApiError::Forbidden("Email domain is not verified for this organization".to_string()).into());
+    }
+    let user_id = create_external_user(&mut tx, &email).await?;
+
+    let default_role: String = sqlx::query_scalar("SELECT default_role FROM org_sso_configs WHERE org_id = $1")
+        .bind(org_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .unwrap_or_else(|| "member".to_string());
+
+    let inserted = sqlx::query(
+        "INSERT INTO org_memberships (org_id, user_id, role, active) VALUES ($1, $2, $3, $4) \
+         ON CONFLICT (org_id, user_id) DO NOTHING",
+    )
+    .bind(org_id)
+    .bind(user_id)
+    .bind(&default_role)
+    .bind(request.active.unwrap_or(true))
+    .execute(&mut *tx)
+    .await?;
+    if inserted.rows_affected() == 0 {
+        return Err(ApiError::Conflict("User is already provisioned in this organization".to_string()).into());
+    }
+
+    let user = load_user(&mut tx, &config, org_id, user_id).await?;
+    tx.commit().await?;
+
+    log_auth_event("scim_provision", Some(&user_id.to_string()), true, Some(&org_id.to_string()));
+    Ok(scim_response(actix_web::http::StatusCode::CREATED, user))
+}
+
+/// PUT /scim/v2/Users/{id} — only `active` is managed by the IdP
+pub async fn replace_user(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+    body: web::Bytes,
+) -> ScimResult<HttpResponse> {
+    let org_id = authenticate(&req, &pool).await?;
+    let user_id = path.into_inner();
+    let request: ScimUserRequest = parse_body(&body)?;
+
+    let mut tx = pool.begin().await?;
+    set_active(&mut tx, org_id, user_id, request.active.unwrap_or(true)).await?;
+    let user = load_user(&mut tx, &config, org_id, user_id).await?;
+    tx.commit().await?;
+
+    Ok(scim_response(actix_web::http::StatusCode::OK, user))
+}
+
+/// PATCH /scim/v2/Users/{id}
+pub async fn patch_user(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+    body: web::Bytes,
+) -> ScimResult<HttpResponse> {
+    let org_id = authenticate(&req, &pool).await?;
+    let user_id = path.into_inner();
+    let patch: ScimPatchRequest = parse_body(&body)?;
+
+    let mut tx = pool.begin().await?;
+    if let Some(active) = patched_active(&patch.operations)? {
+        set_active(&mut tx, org_id, user_id, active).await?;
+    }
+    let user = load_user(&mut tx, &config, org_id, user_id).await?;
+    tx.commit().await?;
+
+    Ok(scim_response(actix_web::http::StatusCode::OK, user))
+}
+
+/// DELETE /scim/v2/Users/{id} — removes the user from the org, not from RoboVeda
+pub async fn delete_user(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ScimResult<HttpResponse> {
+    let org_id = authenticate(&req, &pool).await?;
+    let user_id = path.into_inner();
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "DELETE FROM scim_group_members WHERE user_id = $2 \
+         AND group_id IN (SELECT id FROM scim_groups WHERE org_id = $1)",
+    )
+    .bind(org_id)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let deleted = sqlx::query("DELETE FROM org_memberships WHERE org_id = $1 AND user_id = $2 AND role <> 'owner'")
+        .bind(org_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("User not found or is an organization owner".to_string()).into());
+    }
+    tx.commit().await?;
+
+    log_auth_event("scim_deprovision", Some(&user_id.to_string()), true, Some(&org_id.to_string()));
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// GET /scim/v2/Groups
+pub async fn list_groups(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    query: web::Query<ScimListQuery>,
+) -> ScimResult<HttpResponse> {
+    let org_id = authenticate(&req, &pool).await?;
+    let (offset, limit) = page_bounds(&query);
+
+    let name_filter = match query.filter.as_deref() {
+        Some(filter) => {
+            let filter = parse_filter(filter)?;
+            if filter.attribute != "displayName" {
+                return Err(ApiError::BadRequest(format!("Filtering on {} is not supported", filter.attribute)).into());
+            }
+            Some(filter.value)
+        }
+        None => None,
+    };
+
+    let total: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM scim_groups WHERE org_id = $1 AND ($2::text IS NULL OR display_name = $2)",
+    )
+    .bind(org_id)
+    .bind(&name_filter)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    let ids: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM scim_groups WHERE org_id = $1 AND ($2::text IS NULL OR display_name = $2) \
+         ORDER BY created_at, id OFFSET $3 LIMIT $4",
+    )
+    .bind(org_id)
+    .bind(&name_filter)
+    .bind(offset)
+    .bind(limit)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    let mut conn = pool.acquire().await?;
+    let mut resources = Vec::with_capacity(ids.len());
+    for id in ids {
+        resources.push(load_group(&mut conn, &config, org_id, id).await?);
+    }
+
+    Ok(scim_response(actix_web::http::StatusCode::OK, list_response(resources, total, offset + 1)))
+}
+
+/// GET /scim/v2/Groups/{id}
+pub async fn get_group(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+) -> ScimResult<HttpResponse> {
+    let org_id = authenticate(&req, &pool).await?;
+    let mut conn = pool.acquire().await?;
+    let group = load_group(&mut conn, &config, org_id, path.into_inner()).await?;
+    Ok(scim_response(actix_web::http::StatusCode::OK, group))
+}
+
+/// POST /scim/v2/Groups
+pub async fn create_group(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Bytes,
+) -> ScimResult<HttpResponse> {
+    let org_id = authenticate(&req, &pool).await?;
+    let request: ScimGroupRequest = parse_body(&body)?;
+
+    let mut tx = pool.begin().await?;
+    let group_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO scim_groups (org_id, display_name, external_id) VALUES ($1, $2, $3) RETURNING id",
+    )
+    .bind(org_id)
+    .bind(&request.display_name)
+    .bind(&request.external_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let members = request.members.iter().map(|m| m.value).collect();
+    apply_group_changes(&mut tx, org_id, group_id, vec![GroupChange::AddMembers(members)]).await?;
+    let group = load_group(&mut tx, &config, org_id, group_id).await?;
+    tx.commit().await?;
+
+    Ok(scim_response(actix_web::http::StatusCode::CREATED, group))
+}
+
+/// PUT /scim/v2/Groups/{id}
+pub async fn replace_group(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+    body: web::Bytes,
+) -> ScimResult<HttpResponse> {
+    let org_id = authenticate(&req, &pool).await?;
+    let group_id = path.into_inner();
+    let request: ScimGroupRequest = parse_body(&body)?;
+
+    let mut tx = pool.begin().await?;
+    load_group(&mut tx, &config, org_id, group_id).await?;
+    let changes = vec![
+        GroupChange::Rename(request.display_name.clone()),
+        GroupChange::ReplaceMembers(request.members.iter().map(|m| m.value).collect()),
+    ];
+    apply_group_changes(&mut tx, org_id, group_id, changes).await?;
+    let group = load_group(&mut tx, &config, org_id, group_id).await?;
+    tx.commit().await?;
+
+    Ok(scim_response(actix_web::http::StatusCode::OK, group))
+}
+
+/// PATCH /scim/v2/Groups/{id}
+pub async fn patch_group(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+    body: web::Bytes,
+) -> ScimResult<HttpResponse> {
+    let org_id = authenticate(&req, &pool).await?;
+    let group_id = path.into_inner();
+    let patch: ScimPatchRequest = parse_body(&body)?;
+    let changes = group_changes(&patch.operations)?;
+
+    let mut tx = pool.begin().await?;
+    load_group(&mut tx, &config, org_id, group_id).await?;
+    apply_group_changes(&mut tx, org_id, group_id, changes).await?;
+    let group = load_group(&mut tx, &config, org_id, group_id).await?;
+    tx.commit().await?;
+
+    Ok(scim_response(actix_web::http::StatusCode::OK, group))
+}
+
+/// DELETE /scim/v2/Groups/{id}
+pub async fn delete_group(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ScimResult<HttpResponse> {
+    let org_id = authenticate(&req, &pool).await?;
+    let group_id = path.into_inner();
+
+    let mut tx = pool.begin().await?;
+    let members: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT gm.user_id FROM scim_group_members gm JOIN scim_groups g ON g.id = gm.group_id \
+         WHERE g.id = $1 AND g.org_id = $2",
+    )
+    .bind(group_id)
+    .bind(org_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let deleted = sqlx::query("DELETE FROM scim_groups WHERE id = $1 AND org_id = $2")
+        .bind(group_id)
+        .bind(org_id)
+        .execute(&mut *tx)
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Group not found".to_string()).into());
+    }
+
+    for user_id in members {
+        sync_member_role(&mut tx, org_id, user_id).await?;
+    }
+    tx.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// GET /scim/v2/ServiceProviderConfig
+pub async fn service_provider_config() -> HttpResponse {
+    scim_response(actix_web::http::StatusCode::OK, serde_json::json!({
+        "schemas": ["urn:ietf:params:scim:schemas:core:2.0:ServiceProviderConfig"],
+        "patch": { "supported": true },
+        "bulk": { "supported": false, "maxOperations": 0, "maxPayloadSize": 0 },
+        "filter": { "supported": true, "maxResults": scim_services::MAX_PAGE_SIZE },
+        "changePassword": { "supported": false },
+        "sort": { "supported": false },
+        "etag": { "supported": false },
+        "authenticationSchemes": [{
+            "type": "oauthbearertoken",
+            "name": "OAuth Bearer Token",
+            "description": "Per-organization SCIM token issued from /api/orgs/{org_id}/scim-tokens"
+        }]
+    }))
+}
@@ -0,0 +1,93 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+use secrecy::ExposeSecret;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::security::{AuthEvent, StepUpVerifyRequest};
+use crate::services::security_services::{create_session, record_auth_event, LoginContext, STEP_UP_MAX_ATTEMPTS};
+use crate::utils::{create_session_token, secure_compare, sha256_hash};
+
+/// Complete a challenged login with the emailed code and receive the session token
+/// POST /api/auth/step-up/verify
+pub async fn verify_step_up(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<StepUpVerifyRequest>,
+) -> ApiResult<HttpResponse> {
+    let context = LoginContext::from_request(&req);
+    let mut tx = pool.begin().await?;
+
+    let challenge: Option<(Uuid, i64, String, Option<Uuid>, String, i16)> = sqlx::query_as(
+        "SELECT user_id, auth_event_id, auth_method, org_id, code_hash, attempts FROM step_up_challenges \
+         WHERE id = $1 AND verified_at IS NULL AND expires_at > NOW() FOR UPDATE",
+    )
+    .bind(body.challenge_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let (user_id, auth_event_id, auth_method, org_id, code_hash, attempts) = challenge
+        .ok_or_else(|| ApiError::Unauthorized("Verification challenge is invalid or has expired".to_string()))?;
+    if attempts >= STEP_UP_MAX_ATTEMPTS {
+        return Err(ApiError::Unauthorized("Too many attempts; sign in again".to_string()));
+    }
+
+    if !secure_compare(&sha256_hash(body.code.expose_secret().trim().as_bytes()), &code_hash) {
+        sqlx::query("UPDATE step_up_challenges SET attempts = attempts + 1 WHERE id = $1")
+            .bind(body.challenge_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        record_auth_event(pool.get_ref(), Some(user_id), "step_up_failed", false, &context).await?;
+        return Err(ApiError::Unauthorized("Invalid verification code".to_string()));
+    }
+
+    sqlx::query("UPDATE step_up_challenges SET verified_at = NOW() WHERE id = $1")
+        .bind(body.challenge_id)
+        .execute(&mut *tx)
+        .await?;
+    // The verified login now counts towards the user's known locations and devices
+    sqlx::query("UPDATE auth_events SET success = TRUE WHERE id = $1")
+        .bind(auth_event_id)
+        .execute(&mut *tx)
+        .await?;
+    let session_id = create_session(&mut tx, user_id, auth_event_id, &auth_method, true, config.jwt_expiration).await?;
+    tx.commit().await?;
+
+    record_auth_event(pool.get_ref(), Some(user_id), "step_up_verified", true, &context).await?;
+
+    let token = create_session_token(
+        &user_id.to_string(),
+        config.jwt_secret.expose_secret(),
+        config.jwt_expiration,
+        &auth_method,
+        session_id,
+        org_id,
+    )?;
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "token": token,
+        "expires_in": config.jwt_expiration,
+    })))
+}
+
+/// Recent sign-in activity for the current user
+/// GET /api/auth/events
+pub async fn list_auth_events(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let events = sqlx::query_as::<_, AuthEvent>(
+        "SELECT id, user_id, event, success, ip, country, latitude, longitude, user_agent, fingerprint, \
+         risk_score, risk_reasons, created_at FROM auth_events \
+         WHERE user_id = $1 ORDER BY created_at DESC LIMIT 100",
+    )
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(events))
+}
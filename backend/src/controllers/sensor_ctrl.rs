@@ -0,0 +1,136 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::sensor::{CreateSensorRequest, DeviceSensor, UpdateSensorRequest};
+use crate::services::device_services::get_owned_device;
+use crate::services::sensor_services::{
+    validate_sampling_rate, validate_sensor_type, validate_unit, MAX_SENSORS_PER_DEVICE, SENSOR_COLUMNS,
+};
+
+/// Sensors declared for a device
+/// GET /api/robotics/devices/{device_id}/sensors
+pub async fn list_sensors(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+
+    let sensors = sqlx::query_as::<_, DeviceSensor>(&format!(
+        "SELECT {} FROM device_sensors WHERE device_id = $1 ORDER BY sensor_type",
+        SENSOR_COLUMNS
+    ))
+    .bind(device.id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(sensors))
+}
+
+/// Declare a sensor; telemetry readings of its type are accepted from then on
+/// POST /api/robotics/devices/{device_id}/sensors
+pub async fn create_sensor(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<CreateSensorRequest>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    validate_sensor_type(&body.sensor_type)?;
+    let unit = body.unit.trim();
+    validate_unit(unit)?;
+    validate_sampling_rate(body.sampling_rate_hz)?;
+
+    let (declared, exists): (i64, bool) = sqlx::query_as(
+        "SELECT COUNT(*), COALESCE(BOOL_OR(sensor_type = $2), FALSE) FROM device_sensors WHERE device_id = $1",
+    )
+    .bind(device.id)
+    .bind(&body.sensor_type)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+    if exists {
+        return Err(ApiError::Conflict(format!("Sensor {} is already declared", body.sensor_type)));
+    }
+    if declared as usize >= MAX_SENSORS_PER_DEVICE {
+        return Err(ApiError::ValidationError(format!(
+            "A device may declare at most {} sensors",
+            MAX_SENSORS_PER_DEVICE
+        )));
+    }
+
+    let sensor = sqlx::query_as::<_, DeviceSensor>(&format!(
+        "INSERT INTO device_sensors (device_id, sensor_type, unit, sampling_rate_hz, enabled) \
+         VALUES ($1, $2, $3, $4, $5) RETURNING {}",
+        SENSOR_COLUMNS
+    ))
+    .bind(device.id)
+    .bind(&body.sensor_type)
+    .bind(unit)
+    .bind(body.sampling_rate_hz)
+    .bind(body.enabled.unwrap_or(true))
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::created(sensor))
+}
+
+/// Change a sensor's unit, sampling rate or enabled flag
+/// PATCH /api/robotics/devices/{device_id}/sensors/{sensor_id}
+pub async fn update_sensor(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+    body: web::Json<UpdateSensorRequest>,
+) -> ApiResult<HttpResponse> {
+    let (device_id, sensor_id) = path.into_inner();
+    let device = get_owned_device(pool.get_ref(), device_id, user.user_id).await?;
+    let unit = body.unit.as_deref().map(str::trim);
+    if let Some(unit) = unit {
+        validate_unit(unit)?;
+    }
+    if let Some(hz) = body.sampling_rate_hz {
+        validate_sampling_rate(hz)?;
+    }
+
+    let sensor = sqlx::query_as::<_, DeviceSensor>(&format!(
+        "UPDATE device_sensors SET unit = COALESCE($3, unit), sampling_rate_hz = COALESCE($4, sampling_rate_hz), \
+         enabled = COALESCE($5, enabled), updated_at = NOW() \
+         WHERE id = $1 AND device_id = $2 RETURNING {}",
+        SENSOR_COLUMNS
+    ))
+    .bind(sensor_id)
+    .bind(device.id)
+    .bind(unit)
+    .bind(body.sampling_rate_hz)
+    .bind(body.enabled)
+    .fetch_optional(pool.get_ref().as_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Sensor not found".to_string()))?;
+
+    Ok(ApiResponse::success(sensor))
+}
+
+/// Remove a sensor; later readings of its type are rejected
+/// DELETE /api/robotics/devices/{device_id}/sensors/{sensor_id}
+pub async fn delete_sensor(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (device_id, sensor_id) = path.into_inner();
+    let device = get_owned_device(pool.get_ref(), device_id, user.user_id).await?;
+
+    let deleted = sqlx::query("DELETE FROM device_sensors WHERE id = $1 AND device_id = $2")
+        .bind(sensor_id)
+        .bind(device.id)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Sensor not found".to_string()));
+    }
+
+    Ok(crate::errors::success_message("Sensor removed"))
+}
@@ -0,0 +1,158 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::security::{RiskySessionQuery, UpdateRiskPolicyRequest, UserSession};
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::org_services::require_org_permission;
+use crate::services::policy_services::OrgAction;
+use crate::services::security_services::RiskPolicy;
+
+const SESSION_COLUMNS: &str = "s.id, s.user_id, s.auth_method, s.ip, s.country, s.fingerprint, s.risk_score, \
+     s.risk_reasons, s.step_up_verified, s.status, s.created_at, s.expires_at, s.revoked_at";
+
+/// Sessions of org members at or above a risk score, riskiest first
+/// GET /api/orgs/{org_id}/sessions?min_risk=
+pub async fn list_risky_sessions(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    query: web::Query<RiskySessionQuery>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ReviewSessions).await?;
+
+    let sessions = sqlx::query_as::<_, UserSession>(&format!(
+        "SELECT {} FROM user_sessions s \
+         JOIN org_memberships m ON m.user_id = s.user_id AND m.org_id = $1 \
+         WHERE s.risk_score >= $2 AND s.expires_at > NOW() AND ($3 OR s.status = 'active') \
+         ORDER BY s.risk_score DESC, s.created_at DESC LIMIT $4",
+        SESSION_COLUMNS
+    ))
+    .bind(org_id)
+    .bind(query.min_risk.unwrap_or(1))
+    .bind(query.include_revoked.unwrap_or(false))
+    .bind(query.limit.unwrap_or(50).clamp(1, 200))
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(sessions))
+}
+
+/// Revoke an org member's session; its token stops working immediately
+/// POST /api/orgs/{org_id}/sessions/{session_id}/revoke
+pub async fn revoke_session(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, session_id) = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageSessions).await?;
+
+    let mut tx = pool.begin().await?;
+    let revoked: Option<Uuid> = sqlx::query_scalar(
+        "UPDATE user_sessions s SET status = 'revoked', revoked_at = NOW(), revoked_by = $3 \
+         FROM org_memberships m \
+         WHERE s.id = $1 AND s.status = 'active' AND m.org_id = $2 AND m.user_id = s.user_id \
+         RETURNING s.user_id",
+    )
+    .bind(session_id)
+    .bind(org_id)
+    .bind(user.user_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let session_user = revoked.ok_or_else(|| ApiError::NotFound("Active session not found".to_string()))?;
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "session.revoked",
+            resource_type: "user_session",
+            resource_id: Some(session_id.to_string()),
+            details: serde_json::json!({ "user_id": session_user }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(crate::errors::success_message("Session revoked"))
+}
+
+/// Get the org's session risk policy
+/// GET /api/orgs/{org_id}/session-policy
+pub async fn get_risk_policy(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ReviewSessions).await?;
+
+    let policy = sqlx::query_as::<_, RiskPolicy>(
+        "SELECT risk_step_up_threshold AS step_up_threshold, risk_block_threshold AS block_threshold \
+         FROM organizations WHERE id = $1",
+    )
+    .bind(org_id)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "step_up_threshold": policy.step_up_threshold,
+        "block_threshold": policy.block_threshold,
+    })))
+}
+
+/// Set when org members' logins require step-up verification or are blocked
+/// PUT /api/orgs/{org_id}/session-policy
+pub async fn update_risk_policy(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateRiskPolicyRequest>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageSessions).await?;
+
+    if !(0..=100).contains(&body.step_up_threshold) {
+        return Err(ApiError::ValidationError("step_up_threshold must be between 0 and 100".to_string()));
+    }
+    if let Some(block) = body.block_threshold
+        && !(body.step_up_threshold < block && block <= 100)
+    {
+        return Err(ApiError::ValidationError(
+            "block_threshold must be above step_up_threshold and at most 100".to_string(),
+        ));
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE organizations SET risk_step_up_threshold = $2, risk_block_threshold = $3 WHERE id = $1")
+        .bind(org_id)
+        .bind(body.step_up_threshold)
+        .bind(body.block_threshold)
+        .execute(&mut *tx)
+        .await?;
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "session_policy.updated",
+            resource_type: "organization",
+            resource_id: Some(org_id.to_string()),
+            details: serde_json::json!({
+                "step_up_threshold": body.step_up_threshold,
+                "block_threshold": body.block_threshold,
+            }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(crate::errors::success_message("Session policy updated"))
+}
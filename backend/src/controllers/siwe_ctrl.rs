@@ -0,0 +1,135 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::models::user::{SiweLoginRequest, SiweNonceRequest};
+use crate::services::crypto_services::BlockchainService;
+use crate::services::ens_services;
+use crate::services::security_services::{evaluate_login, LoginContext, LoginDecision};
+use crate::services::siwe_services::{
+    consume_nonce, expected_domain, find_or_create_wallet_user, issue_nonce, recover_signer, SiweMessage,
+    DEFAULT_STATEMENT, MAX_MESSAGE_BYTES,
+};
+use crate::utils::{create_session_token, log_auth_event};
+
+fn require_supported_chain(config: &AppConfig, chain_id: u64) -> ApiResult<()> {
+    match config.chain(chain_id) {
+        Some(_) => Ok(()),
+        None => Err(ApiError::ValidationError(format!("Chain {} is not supported", chain_id))),
+    }
+}
+
+/// Issue a single-use nonce for a Sign-In with Ethereum message
+/// POST /api/auth/siwe/nonce
+pub async fn get_nonce(
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: Option<web::Json<SiweNonceRequest>>,
+) -> ApiResult<HttpResponse> {
+    let body = body.map(web::Json::into_inner).unwrap_or_default();
+    let address = match body.address.as_deref().map(str::trim) {
+        Some(name) if ens_services::is_ens_name(name) => Some(ens_services::resolve(&config, name).await?),
+        address => address.map(str::to_string),
+    };
+    if let Some(address) = &address {
+        BlockchainService::validate_address(address)?;
+    }
+    let chain_id = body.chain_id.unwrap_or(config.default_chain_id);
+    require_supported_chain(&config, chain_id)?;
+
+    let (nonce, expires_at) = issue_nonce(pool.get_ref()).await?;
+    let domain = expected_domain(&config);
+    let message = address.as_deref().map(|address| {
+        SiweMessage {
+            scheme: None,
+            domain: domain.clone(),
+            address: BlockchainService::to_checksum_address(address),
+            statement: Some(DEFAULT_STATEMENT.to_string()),
+            uri: config.frontend_url.clone(),
+            version: "1".to_string(),
+            chain_id,
+            nonce: nonce.clone(),
+            issued_at: Utc::now(),
+            expiration_time: Some(expires_at),
+            not_before: None,
+            request_id: None,
+            resources: Vec::new(),
+        }
+        .to_string()
+    });
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "nonce": nonce,
+        "expires_at": expires_at,
+        "domain": domain,
+        "uri": config.frontend_url,
+        "message": message,
+    })))
+}
+
+/// Sign in with a signed EIP-4361 message. The wallet's account is created on its first
+/// sign-in; the login then goes through the same risk checks as a password login.
+/// POST /api/auth/siwe/verify
+pub async fn verify(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<SiweLoginRequest>,
+) -> ApiResult<HttpResponse> {
+    if body.message.len() > MAX_MESSAGE_BYTES {
+        return Err(ApiError::ValidationError("Sign-in message is too long".to_string()));
+    }
+    let message = SiweMessage::parse(&body.message)?;
+    message.check(&expected_domain(&config), Utc::now())?;
+    require_supported_chain(&config, message.chain_id)?;
+
+    let signer = recover_signer(&body.message, body.signature.trim())?;
+    if !signer.eq_ignore_ascii_case(&message.address) {
+        log_auth_event("wallet_login", None, false, Some("signature does not match address"));
+        return Err(ApiError::Unauthorized("Signature does not match the address".to_string()));
+    }
+
+    // Consuming the nonce with the signature checked makes each signed message single-use
+    let mut tx = pool.begin().await?;
+    if !consume_nonce(&mut tx, &message.nonce).await? {
+        log_auth_event("wallet_login", None, false, Some("nonce invalid, expired or reused"));
+        return Err(ApiError::Unauthorized("Sign-in nonce is invalid or has expired".to_string()));
+    }
+    let (user_id, created) =
+        find_or_create_wallet_user(&mut tx, &signer, message.chain_id, config.wallet_relink_cooldown_hours).await?;
+    tx.commit().await?;
+    ens_services::spawn_refresh(pool.get_ref().clone(), config.get_ref().clone(), user_id);
+
+    let context = LoginContext::from_request(&req);
+    let decision = evaluate_login(pool.get_ref(), user_id, "wallet", None, &context, config.jwt_expiration).await?;
+    match decision {
+        LoginDecision::Allow { session_id } => {
+            let token = create_session_token(
+                &user_id.to_string(),
+                config.jwt_secret.expose_secret(),
+                config.jwt_expiration,
+                "wallet",
+                session_id,
+                None,
+            )?;
+            log_auth_event("wallet_login", Some(&user_id.to_string()), true, Some(&message.address));
+
+            Ok(ApiResponse::success(serde_json::json!({
+                "token": token,
+                "expires_in": config.jwt_expiration,
+                "user_id": user_id,
+                "address": message.address,
+                "chain_id": message.chain_id,
+                "created": created,
+            })))
+        }
+        LoginDecision::StepUp { challenge_id } => Ok(ApiResponse::success(serde_json::json!({
+            "step_up_required": true,
+            "challenge_id": challenge_id,
+        }))),
+        LoginDecision::Blocked => Err(ApiError::Forbidden("Sign-in was blocked by your security policy".to_string())),
+    }
+}
@@ -0,0 +1,56 @@
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use futures::StreamExt;
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::config::AppConfig;
+use crate::errors::{ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::speech::{SpeakRequest, TranscribeQuery};
+use crate::services::ai_services::AIService;
+use crate::services::{speech_services, transcription_services};
+
+/// Transcribe a recording sent as the raw body, with text and timed segments
+/// POST /api/ai/transcribe?language=...&prompt=...
+pub async fn transcribe(
+    user: AuthenticatedUser,
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    ai: web::Data<Arc<AIService>>,
+    query: web::Query<TranscribeQuery>,
+    body: web::Bytes,
+) -> ApiResult<HttpResponse> {
+    let content_type = req.headers().get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    let transcription = transcription_services::transcribe(
+        pool.get_ref(),
+        config.get_ref(),
+        ai.get_ref(),
+        user.user_id,
+        content_type,
+        body.to_vec(),
+        &query,
+    )
+    .await?;
+    Ok(ApiResponse::success(transcription))
+}
+
+/// Read text or an assistant answer out loud, streaming the audio as it is synthesized
+/// POST /api/ai/speak
+pub async fn speak(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    ai: web::Data<Arc<AIService>>,
+    body: web::Json<SpeakRequest>,
+) -> ApiResult<HttpResponse> {
+    let user_id = user.user_id;
+    let (media_type, audio) =
+        speech_services::speak(pool.get_ref(), config.get_ref(), ai.get_ref(), user_id, &body).await?;
+    let chunks = audio.stream.map(move |chunk| {
+        // Headers are already sent; all that is left is to cut the audio short
+        chunk
+            .inspect_err(|e| tracing::error!("Speech stream for {} failed: {}", user_id, e))
+            .map_err(actix_web::Error::from)
+    });
+    Ok(HttpResponse::Ok().content_type(media_type).streaming(chunks))
+}
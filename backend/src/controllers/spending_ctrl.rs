@@ -0,0 +1,105 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::{AdminUser, AuthenticatedUser};
+use crate::models::spending::{PaymentReviewQuery, ResolvePaymentReviewRequest, SetSpendingLimitsRequest};
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::{fraud_services, spending_services};
+
+/// The caller's spend this UTC day and month against their limits, in USD
+/// GET /api/blockchain/spending
+pub async fn get_spending(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+) -> ApiResult<HttpResponse> {
+    let status = spending_services::status(pool.get_ref(), &config, user.user_id).await?;
+    Ok(ApiResponse::success(status))
+}
+
+/// A user's spend against their limits
+/// GET /api/admin/spending-limits/{user_id}
+pub async fn admin_get_spending(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let status = spending_services::status(pool.get_ref(), &config, path.into_inner()).await?;
+    Ok(ApiResponse::success(status))
+}
+
+/// Override a user's daily and monthly limits; a limit left out goes back to the default
+/// PUT /api/admin/spending-limits/{user_id}
+pub async fn set_limits(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<SetSpendingLimitsRequest>,
+) -> ApiResult<HttpResponse> {
+    let limits = spending_services::set_limits(pool.get_ref(), admin.0.user_id, path.into_inner(), &body).await?;
+
+    let mut tx = pool.begin().await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin.0.user_id),
+            action: "spending_limits.updated",
+            resource_type: "user",
+            resource_id: Some(limits.user_id.to_string()),
+            details: serde_json::json!({ "daily_limit": limits.daily_limit, "monthly_limit": limits.monthly_limit }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(ApiResponse::success(limits))
+}
+
+/// Payments held by the fraud rules in `status`, open by default, oldest first
+/// GET /api/admin/payment-reviews
+pub async fn list_reviews(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<PaymentReviewQuery>,
+) -> ApiResult<HttpResponse> {
+    let reviews = fraud_services::queue(pool.get_ref(), query.status.as_deref()).await?;
+    Ok(ApiResponse::success(reviews))
+}
+
+/// Approve a held payment, letting the user retry it, or reject it
+/// POST /api/admin/payment-reviews/{review_id}/resolve
+pub async fn resolve_review(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<ResolvePaymentReviewRequest>,
+) -> ApiResult<HttpResponse> {
+    let approve = match body.decision.as_str() {
+        "approve" => true,
+        "reject" => false,
+        _ => return Err(ApiError::ValidationError("decision must be approve or reject".to_string())),
+    };
+    let review =
+        fraud_services::resolve(pool.get_ref(), admin.0.user_id, path.into_inner(), approve, body.note.as_deref())
+            .await?;
+
+    let mut tx = pool.begin().await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin.0.user_id),
+            action: "payment_review.resolved",
+            resource_type: "payment_review",
+            resource_id: Some(review.id.to_string()),
+            details: serde_json::json!({ "decision": body.decision, "note": body.note, "user_id": review.user_id }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(ApiResponse::success(review))
+}
@@ -0,0 +1,641 @@
+use actix_web::{http::header::LOCATION, web, HttpRequest, HttpResponse};
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use secrecy::ExposeSecret;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::org::{
+    AddOrgDomainRequest, OidcCallbackQuery, OrgDomain, Organization, SamlAcsForm, SsoConfig,
+    UpdateSsoConfigRequest, ORG_ROLES,
+};
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::org_services::{
+    create_external_user, email_domain_verified, find_user_by_email, require_org_permission,
+};
+use crate::services::policy_services::OrgAction;
+use crate::services::security_services::{evaluate_login, LoginContext, LoginDecision};
+use crate::services::sso_services::{
+    normalize_domain, SamlSettings, SsoIdentity, SsoService, DOMAIN_VERIFICATION_PATH,
+};
+use crate::utils::{create_session_token, generate_random_hex, log_auth_event};
+
+const SSO_CONFIG_COLUMNS: &str = "org_id, protocol, enabled, issuer_url, client_id, client_secret, \
+     idp_entity_id, idp_sso_url, idp_public_key_pem, role_mapping, default_role, updated_at";
+
+const ORG_DOMAIN_COLUMNS: &str = "id, org_id, domain, verification_token, verified_at, created_at";
+
+/// SAML audience / entity ID this service presents for an org
+fn sp_entity_id(slug: &str) -> String {
+    format!("urn:roboveda:org:{}", slug)
+}
+
+fn oidc_redirect_uri(config: &AppConfig, slug: &str) -> String {
+    format!("{}/api/sso/{}/oidc/callback", config.api_base_url.trim_end_matches('/'), slug)
+}
+
+fn saml_acs_url(config: &AppConfig, slug: &str) -> String {
+    format!("{}/api/sso/{}/saml/acs", config.api_base_url.trim_end_matches('/'), slug)
+}
+
+async fn load_enabled_sso(pool: &PgPool, slug: &str) -> ApiResult<(Organization, SsoConfig)> {
+    let org = sqlx::query_as::<_, Organization>(
+        "SELECT id, name, slug, sso_enforced, created_at FROM organizations WHERE slug = $1",
+    )
+    .bind(slug)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Organization not found".to_string()))?;
+
+    let sso = sqlx::query_as::<_, SsoConfig>(&format!(
+        "SELECT {} FROM org_sso_configs WHERE org_id = $1 AND enabled",
+        SSO_CONFIG_COLUMNS
+    ))
+    .bind(org.id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("SSO is not enabled for this organization".to_string()))?;
+
+    Ok((org, sso))
+}
+
+/// Resolve the user for an SSO identity and sync their org role.
+/// The IdP may only assert addresses on the org's verified domains. It can create
+/// brand-new accounts, but only signs in existing ones that are already active members.
+async fn provision_user(pool: &PgPool, org: &Organization, sso: &SsoConfig, identity: &SsoIdentity) -> ApiResult<Uuid> {
+    let role = SsoService::map_role(&sso.role_mapping, &identity.groups, &sso.default_role);
+    let mut tx = pool.begin().await?;
+
+    if !email_domain_verified(&mut tx, org.id, &identity.email).await? {
+        log_auth_event("sso_login", None, false, Some("unverified email domain"));
+        return Err(ApiError::Forbidden(
+            "This organization has not verified the domain of your email address".to_string(),
+        ));
+    }
+
+    let user_id = match find_user_by_email(&mut tx, &identity.email).await? {
+        Some(user_id) => {
+            let active: Option<bool> = sqlx::query_scalar(
+                "SELECT active FROM org_memberships WHERE org_id = $1 AND user_id = $2 FOR UPDATE",
+            )
+            .bind(org.id)
+            .bind(user_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            match active {
+                Some(true) => {}
+                Some(false) => {
+                    return Err(ApiError::Forbidden("Your access to this organization has been deactivated".to_string()));
+                }
+                None => {
+                    log_auth_event("sso_login", Some(&user_id.to_string()), false, Some("existing account is not a member"));
+                    return Err(ApiError::Forbidden(
+                        "An account with this email already exists and is not a member of this organization".to_string(),
+                    ));
+                }
+            }
+
+            // The IdP is authoritative for roles, except that owners are never demoted by SSO
+            sqlx::query(
+                "UPDATE org_memberships SET role = CASE WHEN role = 'owner' THEN 'owner' ELSE $3 END \
+                 WHERE org_id = $1 AND user_id = $2",
+            )
+            .bind(org.id)
+            .bind(user_id)
+            .bind(&role)
+            .execute(&mut *tx)
+            .await?;
+
+            user_id
+        }
+        None => {
+            let user_id = create_external_user(&mut tx, &identity.email).await?;
+            sqlx::query("INSERT INTO org_memberships (org_id, user_id, role) VALUES ($1, $2, $3)")
+                .bind(org.id)
+                .bind(user_id)
+                .bind(&role)
+                .execute(&mut *tx)
+                .await?;
+            user_id
+        }
+    };
+
+    tx.commit().await?;
+    Ok(user_id)
+}
+
+/// Issue a session token and hand it to the frontend via the URL fragment.
+/// Unusual logins are sent to the step-up page with a challenge instead.
+async fn finish_login(
+    req: &HttpRequest,
+    pool: &PgPool,
+    config: &AppConfig,
+    user_id: Uuid,
+    org: &Organization,
+) -> ApiResult<HttpResponse> {
+    let context = LoginContext::from_request(req);
+    let decision = evaluate_login(pool, user_id, "sso", Some(org.id), &context, config.jwt_expiration).await?;
+    let location = match decision {
+        LoginDecision::Allow { session_id } => {
+            let token = create_session_token(
+                &user_id.to_string(),
+                config.jwt_secret.expose_secret(),
+                config.jwt_expiration,
+                "sso",
+                session_id,
+                Some(org.id),
+            )?;
+            log_auth_event("sso_login", Some(&user_id.to_string()), true, Some(&org.slug));
+            format!("{}/sso/callback#token={}", config.frontend_url, token)
+        }
+        LoginDecision::StepUp { challenge_id } => {
+            format!("{}/sso/step-up#challenge={}", config.frontend_url, challenge_id)
+        }
+        LoginDecision::Blocked => {
+            format!("{}/sso/callback#error=login_blocked", config.frontend_url)
+        }
+    };
+
+    Ok(HttpResponse::Found().insert_header((LOCATION, location)).finish())
+}
+
+/// Get an org's SSO configuration
+/// GET /api/sso/orgs/{org_id}/config
+pub async fn get_config(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageSso).await?;
+
+    let slug: String = sqlx::query_scalar("SELECT slug FROM organizations WHERE id = $1")
+        .bind(org_id)
+        .fetch_one(pool.get_ref().as_ref())
+        .await?;
+
+    let sso = sqlx::query_as::<_, SsoConfig>(&format!(
+        "SELECT {} FROM org_sso_configs WHERE org_id = $1",
+        SSO_CONFIG_COLUMNS
+    ))
+    .bind(org_id)
+    .fetch_optional(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "config": sso,
+        "service_provider": {
+            "entity_id": sp_entity_id(&slug),
+            "acs_url": saml_acs_url(&config, &slug),
+            "oidc_redirect_uri": oidc_redirect_uri(&config, &slug),
+        }
+    })))
+}
+
+/// Create or replace an org's SSO configuration
+/// PUT /api/sso/orgs/{org_id}/config
+pub async fn update_config(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateSsoConfigRequest>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageSso).await?;
+
+    let missing = |field: &Option<String>| field.as_deref().map(str::trim).unwrap_or_default().is_empty();
+    match body.protocol.as_str() {
+        "oidc" if missing(&body.issuer_url) || missing(&body.client_id) || body.client_secret.as_ref().is_none_or(|s| s.expose_secret().trim().is_empty()) => {
+            return Err(ApiError::ValidationError(
+                "OIDC requires issuer_url, client_id and client_secret".to_string(),
+            ));
+        }
+        "saml" if missing(&body.idp_entity_id) || missing(&body.idp_public_key_pem) => {
+            return Err(ApiError::ValidationError(
+                "SAML requires idp_entity_id and idp_public_key_pem".to_string(),
+            ));
+        }
+        "oidc" | "saml" => {}
+        other => return Err(ApiError::ValidationError(format!("Unsupported SSO protocol: {}", other))),
+    }
+
+    if body.enforced && !body.enabled {
+        return Err(ApiError::ValidationError("SSO must be enabled before it can be enforced".to_string()));
+    }
+
+    let default_role = body.default_role.clone().unwrap_or_else(|| "member".to_string());
+    let role_mapping = body.role_mapping.clone().unwrap_or_else(|| serde_json::json!({}));
+    let mapped_roles_valid = role_mapping
+        .as_object()
+        .map(|m| m.values().all(|r| r.as_str().is_some_and(|r| ORG_ROLES.contains(&r))))
+        .unwrap_or(false);
+    if !ORG_ROLES.contains(&default_role.as_str()) || !mapped_roles_valid {
+        return Err(ApiError::ValidationError(format!("Roles must be one of {:?}", ORG_ROLES)));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let sso = sqlx::query_as::<_, SsoConfig>(&format!(
+        "INSERT INTO org_sso_configs \
+         (org_id, protocol, enabled, issuer_url, client_id, client_secret, idp_entity_id, idp_sso_url, \
+          idp_public_key_pem, role_mapping, default_role, updated_at) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, NOW()) \
+         ON CONFLICT (org_id) DO UPDATE SET \
+         protocol = EXCLUDED.protocol, enabled = EXCLUDED.enabled, issuer_url = EXCLUDED.issuer_url, \
+         client_id = EXCLUDED.client_id, client_secret = EXCLUDED.client_secret, \
+         idp_entity_id = EXCLUDED.idp_entity_id, idp_sso_url = EXCLUDED.idp_sso_url, \
+         idp_public_key_pem = EXCLUDED.idp_public_key_pem, role_mapping = EXCLUDED.role_mapping, \
+         default_role = EXCLUDED.default_role, updated_at = NOW() \
+         RETURNING {}",
+        SSO_CONFIG_COLUMNS
+    ))
+    .bind(org_id)
+    .bind(&body.protocol)
+    .bind(body.enabled)
+    .bind(&body.issuer_url)
+    .bind(&body.client_id)
+    .bind(body.client_secret.as_ref().map(|s| s.expose_secret()))
+    .bind(&body.idp_entity_id)
+    .bind(&body.idp_sso_url)
+    .bind(&body.idp_public_key_pem)
+    .bind(&role_mapping)
+    .bind(&default_role)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query("UPDATE organizations SET sso_enforced = $2 WHERE id = $1")
+        .bind(org_id)
+        .bind(body.enforced)
+        .execute(&mut *tx)
+        .await?;
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "sso.config_updated",
+            resource_type: "sso_config",
+            resource_id: Some(org_id.to_string()),
+            details: serde_json::json!({
+                "protocol": body.protocol,
+                "enabled": body.enabled,
+                "enforced": body.enforced,
+            }),
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(ApiResponse::success(sso))
+}
+
+/// List the email domains an org has claimed
+/// GET /api/sso/orgs/{org_id}/domains
+pub async fn list_domains(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageSso).await?;
+
+    let domains = sqlx::query_as::<_, OrgDomain>(&format!(
+        "SELECT {} FROM org_domains WHERE org_id = $1 ORDER BY domain",
+        ORG_DOMAIN_COLUMNS
+    ))
+    .bind(org_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(domains))
+}
+
+/// Claim an email domain; it stays unverified until its token is published
+/// POST /api/sso/orgs/{org_id}/domains
+pub async fn add_domain(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<AddOrgDomainRequest>,
+) -> ApiResult<HttpResponse> {
+    let org_id = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageSso).await?;
+    let domain = normalize_domain(&body.domain)?;
+
+    let mut tx = pool.begin().await?;
+    let claimed = sqlx::query_as::<_, OrgDomain>(&format!(
+        "INSERT INTO org_domains (org_id, domain, verification_token) VALUES ($1, $2, $3) \
+         ON CONFLICT (org_id, domain) DO NOTHING RETURNING {}",
+        ORG_DOMAIN_COLUMNS
+    ))
+    .bind(org_id)
+    .bind(&domain)
+    .bind(format!("roboveda-verification={}", generate_random_hex(24)))
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::Conflict("Domain is already claimed by this organization".to_string()))?;
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "sso.domain_added",
+            resource_type: "org_domain",
+            resource_id: Some(claimed.id.to_string()),
+            details: serde_json::json!({ "domain": domain }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::created(serde_json::json!({
+        "domain": claimed,
+        "instructions": format!(
+            "Serve the verification token as a line of https://{}{} and then verify the domain",
+            domain, DOMAIN_VERIFICATION_PATH
+        ),
+    })))
+}
+
+/// Check that the domain serves its verification token and mark it verified
+/// POST /api/sso/orgs/{org_id}/domains/{domain_id}/verify
+pub async fn verify_domain(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, domain_id) = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageSso).await?;
+
+    let claimed = sqlx::query_as::<_, OrgDomain>(&format!(
+        "SELECT {} FROM org_domains WHERE id = $1 AND org_id = $2",
+        ORG_DOMAIN_COLUMNS
+    ))
+    .bind(domain_id)
+    .bind(org_id)
+    .fetch_optional(pool.get_ref().as_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Domain not found".to_string()))?;
+
+    if claimed.verified_at.is_some() {
+        return Ok(ApiResponse::success(claimed));
+    }
+
+    if !SsoService::new()
+        .check_domain_ownership(&claimed.domain, &claimed.verification_token)
+        .await?
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Verification token not found at https://{}{}",
+            claimed.domain, DOMAIN_VERIFICATION_PATH
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+    let taken: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM org_domains WHERE domain = $1 AND verified_at IS NOT NULL)",
+    )
+    .bind(&claimed.domain)
+    .fetch_one(&mut *tx)
+    .await?;
+    if taken {
+        return Err(ApiError::Conflict("Domain is already verified by another organization".to_string()));
+    }
+
+    let verified = sqlx::query_as::<_, OrgDomain>(&format!(
+        "UPDATE org_domains SET verified_at = NOW() WHERE id = $1 RETURNING {}",
+        ORG_DOMAIN_COLUMNS
+    ))
+    .bind(domain_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "sso.domain_verified",
+            resource_type: "org_domain",
+            resource_id: Some(domain_id.to_string()),
+            details: serde_json::json!({ "domain": verified.domain }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::success(verified))
+}
+
+/// Release a claimed domain; its addresses can no longer be asserted by the org's IdP
+/// DELETE /api/sso/orgs/{org_id}/domains/{domain_id}
+pub async fn delete_domain(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (org_id, domain_id) = path.into_inner();
+    require_org_permission(pool.get_ref(), org_id, &user, OrgAction::ManageSso).await?;
+
+    let mut tx = pool.begin().await?;
+    let domain: String = sqlx::query_scalar("DELETE FROM org_domains WHERE id = $1 AND org_id = $2 RETURNING domain")
+        .bind(domain_id)
+        .bind(org_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Domain not found".to_string()))?;
+
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user.user_id),
+            action: "sso.domain_removed",
+            resource_type: "org_domain",
+            resource_id: Some(domain_id.to_string()),
+            details: serde_json::json!({ "domain": domain }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(crate::errors::success_message("Domain removed"))
+}
+
+/// Begin an SSO login by redirecting to the org's identity provider
+/// GET /api/sso/{org_slug}/login
+pub async fn start_login(
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<String>,
+) -> ApiResult<HttpResponse> {
+    let slug = path.into_inner();
+    let (org, sso) = load_enabled_sso(pool.get_ref(), &slug).await?;
+
+    let location = match sso.protocol.as_str() {
+        "oidc" => {
+            let issuer = sso.issuer_url.as_deref().unwrap_or_default();
+            let discovery = SsoService::new().discover(issuer).await?;
+            let state = generate_random_hex(24);
+            let nonce = generate_random_hex(24);
+
+            sqlx::query(
+                "INSERT INTO sso_login_states (state, org_id, nonce, expires_at) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(&state)
+            .bind(org.id)
+            .bind(&nonce)
+            .bind(Utc::now() + Duration::minutes(10))
+            .execute(pool.get_ref().as_ref())
+            .await?;
+
+            SsoService::authorization_url(
+                &discovery,
+                sso.client_id.as_deref().unwrap_or_default(),
+                &oidc_redirect_uri(&config, &slug),
+                &state,
+                &nonce,
+            )?
+        }
+        // SAML: post an AuthnRequest to the IdP; the response must answer it
+        _ => {
+            let idp_sso_url = sso.idp_sso_url
+                .as_deref()
+                .ok_or_else(|| ApiError::BadRequest("SAML IdP SSO URL is not configured".to_string()))?;
+            let request_id = format!("_{}", generate_random_hex(20));
+
+            sqlx::query("INSERT INTO saml_requests (id, org_id, expires_at) VALUES ($1, $2, $3)")
+                .bind(&request_id)
+                .bind(org.id)
+                .bind(Utc::now() + Duration::minutes(10))
+                .execute(pool.get_ref().as_ref())
+                .await?;
+
+            let saml_request = SsoService::saml_authn_request(
+                &request_id,
+                &sp_entity_id(&slug),
+                &saml_acs_url(&config, &slug),
+                idp_sso_url,
+                Utc::now(),
+            );
+            return Ok(HttpResponse::Ok()
+                .content_type("text/html; charset=utf-8")
+                .body(SsoService::saml_post_form(idp_sso_url, &saml_request)));
+        }
+    };
+
+    Ok(HttpResponse::Found().insert_header((LOCATION, location)).finish())
+}
+
+/// Complete an OIDC authorization code login
+/// GET /api/sso/{org_slug}/oidc/callback
+pub async fn oidc_callback(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<String>,
+    query: web::Query<OidcCallbackQuery>,
+) -> ApiResult<HttpResponse> {
+    let slug = path.into_inner();
+    let (org, sso) = load_enabled_sso(pool.get_ref(), &slug).await?;
+    if sso.protocol != "oidc" {
+        return Err(ApiError::BadRequest("Organization is not configured for OIDC".to_string()));
+    }
+
+    // States are single-use to prevent replay of the callback
+    let nonce: String = sqlx::query_scalar(
+        "DELETE FROM sso_login_states WHERE state = $1 AND org_id = $2 AND expires_at > NOW() RETURNING nonce",
+    )
+    .bind(&query.state)
+    .bind(org.id)
+    .fetch_optional(pool.get_ref().as_ref())
+    .await?
+    .ok_or_else(|| ApiError::Unauthorized("SSO login state is invalid or expired".to_string()))?;
+
+    let service = SsoService::new();
+    let discovery = service.discover(sso.issuer_url.as_deref().unwrap_or_default()).await?;
+    let identity = service
+        .complete_oidc_login(
+            &discovery,
+            sso.client_id.as_deref().unwrap_or_default(),
+            sso.client_secret.as_deref().unwrap_or_default(),
+            &query.code,
+            &oidc_redirect_uri(&config, &slug),
+            &nonce,
+        )
+        .await
+        .inspect_err(|e| log_auth_event("sso_login", None, false, Some(&e.to_string())))?;
+
+    let user_id = provision_user(pool.get_ref(), &org, &sso, &identity).await?;
+    finish_login(&req, pool.get_ref(), &config, user_id, &org).await
+}
+
+/// SAML assertion consumer service (HTTP-POST binding)
+/// POST /api/sso/{org_slug}/saml/acs
+pub async fn saml_acs(
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<String>,
+    form: web::Form<SamlAcsForm>,
+) -> ApiResult<HttpResponse> {
+    let slug = path.into_inner();
+    let (org, sso) = load_enabled_sso(pool.get_ref(), &slug).await?;
+    if sso.protocol != "saml" {
+        return Err(ApiError::BadRequest("Organization is not configured for SAML".to_string()));
+    }
+
+    let audience = sp_entity_id(&slug);
+    let acs_url = saml_acs_url(&config, &slug);
+    let settings = SamlSettings {
+        idp_entity_id: sso.idp_entity_id.as_deref().unwrap_or_default(),
+        sp_entity_id: &audience,
+        idp_public_key_pem: sso.idp_public_key_pem.as_deref().unwrap_or_default(),
+        acs_url: &acs_url,
+    };
+    let assertion = SsoService::new()
+        .verify_saml_response(&form.saml_response, &settings)
+        .inspect_err(|e| log_auth_event("sso_login", None, false, Some(&e.to_string())))?;
+
+    // Each AuthnRequest is answered once, and each assertion is accepted once until it expires
+    let mut tx = pool.begin().await?;
+    let answered = sqlx::query("DELETE FROM saml_requests WHERE id = $1 AND org_id = $2 AND expires_at > NOW()")
+        .bind(&assertion.in_response_to)
+        .bind(org.id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if answered == 0 {
+        log_auth_event("sso_login", None, false, Some("SAML response to unknown request"));
+        return Err(ApiError::Unauthorized("SAML response does not answer a pending login".to_string()));
+    }
+
+    sqlx::query("DELETE FROM saml_used_assertions WHERE expires_at <= NOW()")
+        .execute(&mut *tx)
+        .await?;
+    let first_use = sqlx::query(
+        "INSERT INTO saml_used_assertions (org_id, assertion_id, expires_at) VALUES ($1, $2, $3) \
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(org.id)
+    .bind(&assertion.assertion_id)
+    .bind(assertion.expires_at)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if first_use == 0 {
+        log_auth_event("sso_login", None, false, Some("replayed SAML assertion"));
+        return Err(ApiError::Unauthorized("SAML assertion has already been used".to_string()));
+    }
+    tx.commit().await?;
+
+    let user_id = provision_user(pool.get_ref(), &org, &sso, &assertion.identity).await?;
+    finish_login(&req, pool.get_ref(), &config, user_id, &org).await
+}
@@ -0,0 +1,269 @@
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::{AuthenticatedDevice, AuthenticatedUser, OptionalUser};
+use crate::models::device::{CandidateQuery, IceCandidate, IceCandidateRecord, SdpRequest, StreamSession};
+use crate::services::device_services::get_owned_device;
+use crate::services::stream_services::{
+    has_camera, ice_servers, validate_candidate, validate_sdp, STREAM_SESSION_TTL_MINUTES,
+};
+
+const SESSION_COLUMNS: &str =
+    "id, device_id, user_id, status, offer_sdp, answer_sdp, created_at, answered_at, closed_at, expires_at";
+
+async fn load_session(pool: &PgPool, device_id: Uuid, session_id: Uuid) -> ApiResult<StreamSession> {
+    sqlx::query_as::<_, StreamSession>(&format!(
+        "SELECT {} FROM stream_sessions WHERE id = $1 AND device_id = $2",
+        SESSION_COLUMNS
+    ))
+    .bind(session_id)
+    .bind(device_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Stream session not found".to_string()))
+}
+
+fn ensure_open(session: &StreamSession) -> ApiResult<()> {
+    if session.status == "closed" || session.expires_at <= Utc::now() {
+        return Err(ApiError::Conflict("Stream session is closed".to_string()));
+    }
+    Ok(())
+}
+
+/// Which side of the session the caller is: the operator who made the offer, or the device itself
+fn resolve_sender(
+    session: &StreamSession,
+    user: &OptionalUser,
+    device: &Option<AuthenticatedDevice>,
+) -> ApiResult<&'static str> {
+    if let Some(device) = device
+        && device.device_id == session.device_id
+    {
+        return Ok("device");
+    }
+    if let Some(user) = &user.0
+        && user.user_id == session.user_id
+    {
+        return Ok("operator");
+    }
+    Err(ApiError::Unauthorized("Not a participant in this stream session".to_string()))
+}
+
+/// Start a camera stream by posting the browser's SDP offer
+/// POST /api/robotics/devices/{device_id}/stream/offer
+pub async fn create_offer(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+    body: web::Json<SdpRequest>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    if !has_camera(&device) {
+        return Err(ApiError::BadRequest("Device has no camera".to_string()));
+    }
+    if device.status == "offline" {
+        return Err(ApiError::BadRequest("Device is offline".to_string()));
+    }
+    validate_sdp(&body.sdp)?;
+
+    let mut tx = pool.begin().await?;
+
+    // A device streams to one operator at a time; a new offer supersedes any pending one
+    sqlx::query(
+        "UPDATE stream_sessions SET status = 'closed', closed_at = NOW() \
+         WHERE device_id = $1 AND status <> 'closed'",
+    )
+    .bind(device.id)
+    .execute(&mut *tx)
+    .await?;
+
+    let session = sqlx::query_as::<_, StreamSession>(&format!(
+        "INSERT INTO stream_sessions (device_id, user_id, offer_sdp, expires_at) \
+         VALUES ($1, $2, $3, $4) RETURNING {}",
+        SESSION_COLUMNS
+    ))
+    .bind(device.id)
+    .bind(user.user_id)
+    .bind(&body.sdp)
+    .bind(Utc::now() + Duration::minutes(STREAM_SESSION_TTL_MINUTES))
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(ApiResponse::created(serde_json::json!({
+        "session_id": session.id,
+        "status": session.status,
+        "expires_at": session.expires_at,
+        "ice_servers": ice_servers(config.get_ref()),
+    })))
+}
+
+/// Pending offer for the device to answer (device-authenticated)
+/// GET /api/robotics/devices/{device_id}/stream/offer
+pub async fn get_pending_offer(
+    device: AuthenticatedDevice,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let device_id = path.into_inner();
+    if device.device_id != device_id {
+        return Err(ApiError::Forbidden("Device key does not match this device".to_string()));
+    }
+
+    let session = sqlx::query_as::<_, StreamSession>(&format!(
+        "SELECT {} FROM stream_sessions \
+         WHERE device_id = $1 AND status = 'offered' AND expires_at > NOW() \
+         ORDER BY created_at DESC LIMIT 1",
+        SESSION_COLUMNS
+    ))
+    .bind(device_id)
+    .fetch_optional(pool.get_ref().as_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("No pending stream offer".to_string()))?;
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "session_id": session.id,
+        "offer_sdp": session.offer_sdp,
+        "expires_at": session.expires_at,
+        "ice_servers": ice_servers(config.get_ref()),
+    })))
+}
+
+/// Device's SDP answer to an offer (device-authenticated)
+/// POST /api/robotics/devices/{device_id}/stream/{session_id}/answer
+pub async fn post_answer(
+    device: AuthenticatedDevice,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+    body: web::Json<SdpRequest>,
+) -> ApiResult<HttpResponse> {
+    let (device_id, session_id) = path.into_inner();
+    if device.device_id != device_id {
+        return Err(ApiError::Forbidden("Device key does not match this device".to_string()));
+    }
+    validate_sdp(&body.sdp)?;
+
+    let session = load_session(pool.get_ref(), device_id, session_id).await?;
+    ensure_open(&session)?;
+    if session.status != "offered" {
+        return Err(ApiError::Conflict("Stream session has already been answered".to_string()));
+    }
+
+    let session = sqlx::query_as::<_, StreamSession>(&format!(
+        "UPDATE stream_sessions SET status = 'answered', answer_sdp = $2, answered_at = NOW() \
+         WHERE id = $1 AND status = 'offered' RETURNING {}",
+        SESSION_COLUMNS
+    ))
+    .bind(session_id)
+    .bind(&body.sdp)
+    .fetch_optional(pool.get_ref().as_ref())
+    .await?
+    .ok_or_else(|| ApiError::Conflict("Stream session has already been answered".to_string()))?;
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "session_id": session.id,
+        "status": session.status,
+    })))
+}
+
+/// Session state for the operator, including the device's answer once it arrives
+/// GET /api/robotics/devices/{device_id}/stream/{session_id}
+pub async fn get_session(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (device_id, session_id) = path.into_inner();
+    let session = load_session(pool.get_ref(), device_id, session_id).await?;
+    if session.user_id != user.user_id {
+        return Err(ApiError::NotFound("Stream session not found".to_string()));
+    }
+
+    Ok(ApiResponse::success(session))
+}
+
+/// Relay an ICE candidate to the other peer (operator token or device key)
+/// POST /api/robotics/devices/{device_id}/stream/{session_id}/candidates
+pub async fn add_candidate(
+    user: OptionalUser,
+    device: Option<AuthenticatedDevice>,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+    body: web::Json<IceCandidate>,
+) -> ApiResult<HttpResponse> {
+    let (device_id, session_id) = path.into_inner();
+    let session = load_session(pool.get_ref(), device_id, session_id).await?;
+    let sender = resolve_sender(&session, &user, &device)?;
+    ensure_open(&session)?;
+    validate_candidate(&body)?;
+
+    let id: i64 = sqlx::query_scalar(
+        "INSERT INTO stream_ice_candidates (session_id, sender, candidate) VALUES ($1, $2, $3) RETURNING id",
+    )
+    .bind(session.id)
+    .bind(sender)
+    .bind(sqlx::types::Json(body.into_inner()))
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::created(serde_json::json!({ "id": id })))
+}
+
+/// Candidates sent by the other peer, polled with the `after` cursor
+/// GET /api/robotics/devices/{device_id}/stream/{session_id}/candidates
+pub async fn list_candidates(
+    user: OptionalUser,
+    device: Option<AuthenticatedDevice>,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+    query: web::Query<CandidateQuery>,
+) -> ApiResult<HttpResponse> {
+    let (device_id, session_id) = path.into_inner();
+    let session = load_session(pool.get_ref(), device_id, session_id).await?;
+    let sender = resolve_sender(&session, &user, &device)?;
+    let remote = if sender == "device" { "operator" } else { "device" };
+
+    let candidates = sqlx::query_as::<_, IceCandidateRecord>(
+        "SELECT id, sender, candidate, created_at FROM stream_ice_candidates \
+         WHERE session_id = $1 AND sender = $2 AND id > $3 ORDER BY id LIMIT 100",
+    )
+    .bind(session.id)
+    .bind(remote)
+    .bind(query.after.unwrap_or(0))
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "status": session.status,
+        "candidates": candidates,
+    })))
+}
+
+/// Hang up a stream session (either peer)
+/// DELETE /api/robotics/devices/{device_id}/stream/{session_id}
+pub async fn close_session(
+    user: OptionalUser,
+    device: Option<AuthenticatedDevice>,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (device_id, session_id) = path.into_inner();
+    let session = load_session(pool.get_ref(), device_id, session_id).await?;
+    resolve_sender(&session, &user, &device)?;
+
+    sqlx::query(
+        "UPDATE stream_sessions SET status = 'closed', closed_at = NOW() WHERE id = $1 AND status <> 'closed'",
+    )
+    .bind(session.id)
+    .execute(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(crate::errors::success_message("Stream session closed"))
+}
@@ -0,0 +1,283 @@
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::{AdminUser, AuthenticatedUser};
+use crate::models::support::{
+    AssignTicketRequest, AttachmentUploadQuery, CreateTicketRequest, SupportTicket, TicketMessageRequest,
+    TicketQuery, TicketQueueQuery, UpdateTicketRequest,
+};
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::notification_services::notify_user;
+use crate::services::object_storage_services::ObjectStore;
+use crate::services::support_services::{self, TICKET_COLUMNS};
+
+fn support_store(config: &AppConfig) -> ApiResult<ObjectStore> {
+    ObjectStore::for_support(config)
+        .ok_or_else(|| ApiError::ServiceUnavailable("Support attachment storage is not configured".to_string()))
+}
+
+/// Open a ticket, optionally about one of the user's devices, incidents or transactions. SLA
+/// targets follow the user's plan.
+/// POST /api/support/tickets
+pub async fn create_ticket(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<CreateTicketRequest>,
+) -> ApiResult<HttpResponse> {
+    let mut tx = pool.begin().await?;
+    let ticket = support_services::create_ticket(&mut tx, user.user_id, &body).await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::created(ticket))
+}
+
+/// The user's tickets, newest first
+/// GET /api/support/tickets
+pub async fn list_tickets(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<TicketQuery>,
+) -> ApiResult<HttpResponse> {
+    if let Some(status) = &query.status {
+        support_services::validate_status(status)?;
+    }
+    let tickets = sqlx::query_as::<_, SupportTicket>(&format!(
+        "SELECT {} FROM support_tickets WHERE user_id = $1 AND ($2::text IS NULL OR status = $2) \
+         ORDER BY created_at DESC LIMIT 100",
+        TICKET_COLUMNS
+    ))
+    .bind(user.user_id)
+    .bind(&query.status)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(tickets))
+}
+
+/// A ticket with its conversation and attachment download links
+/// GET /api/support/tickets/{ticket_id}
+pub async fn get_ticket(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let ticket = support_services::owned_ticket(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let store = ObjectStore::for_support(&config);
+    let details = support_services::details(pool.get_ref(), store.as_ref(), ticket).await?;
+    Ok(ApiResponse::success(details))
+}
+
+/// Reply to a ticket; reopens it if support had answered or resolved it
+/// POST /api/support/tickets/{ticket_id}/messages
+pub async fn add_message(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<TicketMessageRequest>,
+) -> ApiResult<HttpResponse> {
+    let ticket = support_services::owned_ticket(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let mut tx = pool.begin().await?;
+    let message = support_services::add_message(&mut tx, &ticket, user.user_id, false, &body.body).await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::created(message))
+}
+
+/// Attach a file to a ticket. The body is the raw file, `Content-Type` its type and
+/// `?file_name=` its name.
+/// POST /api/support/tickets/{ticket_id}/attachments
+pub async fn upload_attachment(
+    user: AuthenticatedUser,
+    req: HttpRequest,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+    query: web::Query<AttachmentUploadQuery>,
+    body: web::Bytes,
+) -> ApiResult<HttpResponse> {
+    let store = support_store(&config)?;
+    let ticket = support_services::owned_ticket(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("application/octet-stream");
+
+    let attachment = support_services::add_attachment(
+        pool.get_ref(),
+        &store,
+        &ticket,
+        user.user_id,
+        false,
+        &query.file_name,
+        content_type,
+        body.to_vec(),
+        query.note.as_deref(),
+    )
+    .await?;
+    Ok(ApiResponse::created(attachment))
+}
+
+/// Close a ticket the user no longer needs help with
+/// POST /api/support/tickets/{ticket_id}/close
+pub async fn close_ticket(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let ticket = support_services::owned_ticket(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    if ticket.status == "closed" {
+        return Ok(ApiResponse::success(ticket));
+    }
+    let mut tx = pool.begin().await?;
+    let ticket = support_services::set_status(&mut tx, ticket.id, "closed").await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::success(ticket))
+}
+
+/// The staff queue: open and waiting tickets by default, soonest resolution due first. Filter
+/// with `status`, `assigned_to`, `unassigned` and `breached`.
+/// GET /api/admin/support/tickets
+pub async fn list_queue(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<TicketQueueQuery>,
+) -> ApiResult<HttpResponse> {
+    let tickets = support_services::queue(pool.get_ref(), &query).await?;
+    Ok(ApiResponse::success(tickets))
+}
+
+/// Any ticket with its conversation
+/// GET /api/admin/support/tickets/{ticket_id}
+pub async fn admin_get_ticket(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let ticket = support_services::get_ticket(pool.get_ref(), path.into_inner()).await?;
+    let store = ObjectStore::for_support(&config);
+    let details = support_services::details(pool.get_ref(), store.as_ref(), ticket).await?;
+    Ok(ApiResponse::success(details))
+}
+
+/// Assign a ticket to a staff member, who is notified, or unassign it with `assignee_id: null`
+/// POST /api/admin/support/tickets/{ticket_id}/assign
+pub async fn assign_ticket(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<AssignTicketRequest>,
+) -> ApiResult<HttpResponse> {
+    let ticket = support_services::get_ticket(pool.get_ref(), path.into_inner()).await?;
+    if let Some(assignee) = body.assignee_id {
+        let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM users WHERE id = $1)")
+            .bind(assignee)
+            .fetch_one(pool.get_ref().as_ref())
+            .await?;
+        if !exists {
+            return Err(ApiError::ValidationError("assignee_id does not name a user".to_string()));
+        }
+    }
+
+    let mut tx = pool.begin().await?;
+    let ticket = sqlx::query_as::<_, SupportTicket>(&format!(
+        "UPDATE support_tickets SET assigned_to = $2, updated_at = NOW() WHERE id = $1 RETURNING {}",
+        TICKET_COLUMNS
+    ))
+    .bind(ticket.id)
+    .bind(body.assignee_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if let Some(assignee) = body.assignee_id.filter(|a| *a != admin.0.user_id) {
+        notify_user(
+            &mut tx,
+            assignee,
+            "support.assigned",
+            &format!("Ticket assigned to you: {}", ticket.subject),
+            &format!("{} {} ticket, resolution due {}", ticket.support_tier, ticket.priority, ticket.resolution_due_at),
+            serde_json::json!({ "ticket_id": ticket.id }),
+        )
+        .await?;
+    }
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin.0.user_id),
+            action: "support.ticket_assigned",
+            resource_type: "support_ticket",
+            resource_id: Some(ticket.id.to_string()),
+            details: serde_json::json!({ "assignee_id": body.assignee_id }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::success(ticket))
+}
+
+/// Reply as support. The first staff reply meets the first response target.
+/// POST /api/admin/support/tickets/{ticket_id}/messages
+pub async fn staff_reply(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<TicketMessageRequest>,
+) -> ApiResult<HttpResponse> {
+    let ticket = support_services::get_ticket(pool.get_ref(), path.into_inner()).await?;
+    let mut tx = pool.begin().await?;
+    let message = support_services::add_message(&mut tx, &ticket, admin.0.user_id, true, &body.body).await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::created(message))
+}
+
+/// Change a ticket's status or priority
+/// PATCH /api/admin/support/tickets/{ticket_id}
+pub async fn update_ticket(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateTicketRequest>,
+) -> ApiResult<HttpResponse> {
+    let mut ticket = support_services::get_ticket(pool.get_ref(), path.into_inner()).await?;
+    if let Some(priority) = &body.priority {
+        support_services::validate_priority(priority)?;
+    }
+
+    let mut tx = pool.begin().await?;
+    if let Some(status) = &body.status {
+        ticket = support_services::set_status(&mut tx, ticket.id, status).await?;
+    }
+    if let Some(priority) = &body.priority {
+        ticket = sqlx::query_as::<_, SupportTicket>(&format!(
+            "UPDATE support_tickets SET priority = $2, updated_at = NOW() WHERE id = $1 RETURNING {}",
+            TICKET_COLUMNS
+        ))
+        .bind(ticket.id)
+        .bind(priority)
+        .fetch_one(&mut *tx)
+        .await?;
+    }
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin.0.user_id),
+            action: "support.ticket_updated",
+            resource_type: "support_ticket",
+            resource_id: Some(ticket.id.to_string()),
+            details: serde_json::json!({ "status": body.status, "priority": body.priority }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::success(ticket))
+}
@@ -0,0 +1,431 @@
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use futures::stream::{self, StreamExt};
+use sqlx::{FromRow, PgPool};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::swarm::{SwarmAssignment, SwarmCommandRequest, SwarmFanOutRequest, SwarmMission};
+use crate::services::attachment_services::{require_attachment, ATTACHED_TYPES_SQL};
+use crate::services::path_services::path_to_commands;
+use crate::services::region_services::require_user_filter;
+use crate::services::robotics_services::RoboticsService;
+use crate::services::swarm_services::{
+    fanout_parallelism, plan_survey, resolve_min_success, stop_command, DeviceOutcome, FanOutReport, FanOutStatus,
+    SwarmDevice, MAX_SWARM_SIZE,
+};
+
+const MISSION_COLUMNS: &str = "id, user_id, command, parameters, status, report, created_at";
+
+/// Commands one device may receive from a single swarm mission
+const MAX_COMMANDS_PER_DEVICE: usize = 500;
+
+#[derive(FromRow)]
+struct SwarmMemberRow {
+    id: Uuid,
+    device_type: String,
+    firmware_version: String,
+    status: String,
+    last_latitude: Option<f64>,
+    last_longitude: Option<f64>,
+}
+
+#[derive(FromRow)]
+struct FanOutTarget {
+    id: Uuid,
+    device_type: String,
+    firmware_version: String,
+    status: String,
+    last_altitude: Option<f64>,
+    attachments: Vec<String>,
+}
+
+/// Decompose a fleet-level command into per-device missions and queue their commands
+/// POST /api/robotics/swarm/missions
+pub async fn create_mission(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<SwarmCommandRequest>,
+) -> ApiResult<HttpResponse> {
+    if body.command != "survey" {
+        return Err(ApiError::ValidationError(format!("Unsupported swarm command: {}", body.command)));
+    }
+    let unique: HashSet<Uuid> = body.device_ids.iter().copied().collect();
+    if unique.len() != body.device_ids.len() {
+        return Err(ApiError::ValidationError("device_ids contains duplicates".to_string()));
+    }
+    if unique.is_empty() || unique.len() > MAX_SWARM_SIZE {
+        return Err(ApiError::ValidationError(format!("A swarm needs 1-{} devices", MAX_SWARM_SIZE)));
+    }
+
+    let rows = sqlx::query_as::<_, SwarmMemberRow>(
+        "SELECT id, device_type, firmware_version, status, last_latitude, last_longitude FROM devices \
+         WHERE user_id = $1 AND id = ANY($2)",
+    )
+    .bind(user.user_id)
+    .bind(&body.device_ids)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+    if rows.len() != unique.len() {
+        return Err(ApiError::NotFound("One or more devices not found".to_string()));
+    }
+
+    let mut devices = Vec::with_capacity(rows.len());
+    let mut firmware: HashMap<Uuid, String> = HashMap::with_capacity(rows.len());
+    for row in rows {
+        if row.status == "offline" {
+            return Err(ApiError::BadRequest(format!("Device {} is offline", row.id)));
+        }
+        let (Some(latitude), Some(longitude)) = (row.last_latitude, row.last_longitude) else {
+            return Err(ApiError::BadRequest(format!("Device {} has no known position", row.id)));
+        };
+        firmware.insert(row.id, row.firmware_version);
+        devices.push(SwarmDevice { id: row.id, device_type: row.device_type, latitude, longitude });
+    }
+
+    let speed = body.speed.unwrap_or(0.5);
+    let missions = plan_survey(
+        &devices,
+        &body.area,
+        body.lane_spacing_m.unwrap_or(30.0),
+        body.altitude.unwrap_or(40.0),
+        speed,
+    )?;
+
+    let service = RoboticsService::new();
+    let now = Utc::now();
+    let mut tx = pool.begin().await?;
+
+    let mission = sqlx::query_as::<_, SwarmMission>(&format!(
+        "INSERT INTO swarm_missions (user_id, command, parameters) VALUES ($1, $2, $3) RETURNING {}",
+        MISSION_COLUMNS
+    ))
+    .bind(user.user_id)
+    .bind(&body.command)
+    .bind(serde_json::json!({
+        "area": body.area,
+        "altitude": body.altitude,
+        "lane_spacing_m": body.lane_spacing_m,
+        "speed": speed,
+        "device_ids": body.device_ids,
+    }))
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let mut assignments = Vec::with_capacity(missions.len());
+    for planned in &missions {
+        let device_type = &devices.iter().find(|d| d.id == planned.device_id).expect("planned device").device_type;
+        let firmware_version = &firmware[&planned.device_id];
+        let commands = path_to_commands(device_type, &planned.points, speed, None)?;
+        if commands.len() > MAX_COMMANDS_PER_DEVICE {
+            return Err(ApiError::ValidationError(
+                "Survey needs too many commands per device; increase lane_spacing_m or add devices".to_string(),
+            ));
+        }
+
+        let not_before = now + Duration::milliseconds(planned.start_offset_ms as i64);
+        let mut total_duration_ms = 0u64;
+        for (sequence, (command, parameters)) in commands.iter().enumerate() {
+            service.validate_command(device_type, firmware_version, command)?;
+            let params = service.parse_command_params(command, parameters)?;
+            let estimated_duration_ms = service.estimate_duration_ms(&params);
+            total_duration_ms += estimated_duration_ms;
+
+            sqlx::query(
+                "INSERT INTO device_commands \
+                 (device_id, user_id, command, parameters, estimated_duration_ms, estimated_battery_drain, \
+                  sequence, swarm_mission_id, not_before) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+            )
+            .bind(planned.device_id)
+            .bind(user.user_id)
+            .bind(command)
+            .bind(parameters)
+            .bind(estimated_duration_ms as i64)
+            .bind(service.estimate_battery_drain(command, &params))
+            .bind(sequence as i32)
+            .bind(mission.id)
+            .bind(not_before)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let assignment = sqlx::query_as::<_, SwarmAssignment>(
+            "INSERT INTO swarm_assignments \
+             (mission_id, device_id, region, transit_altitude, start_offset_ms, command_count, estimated_duration_ms) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7) \
+             RETURNING device_id, region, transit_altitude, start_offset_ms, command_count, estimated_duration_ms",
+        )
+        .bind(mission.id)
+        .bind(planned.device_id)
+        .bind(sqlx::types::Json(planned.region))
+        .bind(planned.transit_altitude)
+        .bind(planned.start_offset_ms as i64)
+        .bind(commands.len() as i32)
+        .bind((planned.start_offset_ms + total_duration_ms) as i64)
+        .fetch_one(&mut *tx)
+        .await?;
+        assignments.push(assignment);
+    }
+
+    tx.commit().await?;
+
+    Ok(ApiResponse::created(serde_json::json!({
+        "mission": mission,
+        "assignments": assignments,
+    })))
+}
+
+/// Swarm missions issued by the caller, newest first
+/// GET /api/robotics/swarm/missions
+pub async fn list_missions(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let missions = sqlx::query_as::<_, SwarmMission>(&format!(
+        "SELECT {} FROM swarm_missions WHERE user_id = $1 ORDER BY created_at DESC LIMIT 100",
+        MISSION_COLUMNS
+    ))
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(missions))
+}
+
+/// A swarm mission with each device's assignment and command progress
+/// GET /api/robotics/swarm/missions/{mission_id}
+pub async fn get_mission(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let mission = sqlx::query_as::<_, SwarmMission>(&format!(
+        "SELECT {} FROM swarm_missions WHERE id = $1 AND user_id = $2",
+        MISSION_COLUMNS
+    ))
+    .bind(path.into_inner())
+    .bind(user.user_id)
+    .fetch_optional(pool.get_ref().as_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Swarm mission not found".to_string()))?;
+
+    let assignments = sqlx::query_as::<_, SwarmAssignment>(
+        "SELECT device_id, region, transit_altitude, start_offset_ms, command_count, estimated_duration_ms \
+         FROM swarm_assignments WHERE mission_id = $1 ORDER BY start_offset_ms, device_id",
+    )
+    .bind(mission.id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    let progress: Vec<(Uuid, String, i64)> = sqlx::query_as(
+        "SELECT device_id, status, COUNT(*) FROM device_commands WHERE swarm_mission_id = $1 \
+         GROUP BY device_id, status",
+    )
+    .bind(mission.id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    let assignments: Vec<serde_json::Value> = assignments
+        .into_iter()
+        .map(|a| {
+            let counts: serde_json::Map<String, serde_json::Value> = progress
+                .iter()
+                .filter(|(device_id, _, _)| *device_id == a.device_id)
+                .map(|(_, status, count)| (status.clone(), serde_json::json!(count)))
+                .collect();
+            serde_json::json!({ "assignment": a, "commands": counts })
+        })
+        .collect();
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "mission": mission,
+        "assignments": assignments,
+    })))
+}
+
+fn outcome(device_id: Uuid, status: FanOutStatus, command_id: Option<Uuid>, error: Option<String>) -> DeviceOutcome {
+    DeviceOutcome { device_id, status, command_id, error, compensation_command_id: None }
+}
+
+/// Validate and queue the fan-out command for one device
+async fn dispatch_one(
+    pool: &PgPool,
+    user_id: Uuid,
+    mission_id: Uuid,
+    body: &SwarmFanOutRequest,
+    device: &FanOutTarget,
+) -> DeviceOutcome {
+    if device.status == "offline" {
+        return outcome(device.id, FanOutStatus::Rejected, None, Some("Device is offline".to_string()));
+    }
+    let service = RoboticsService::new();
+    let params = match service
+        .validate_command(&device.device_type, &device.firmware_version, &body.command)
+        .and_then(|_| require_attachment(&device.device_type, &body.command, &device.attachments))
+        .and_then(|_| service.parse_command_params(&body.command, &body.parameters))
+    {
+        Ok(params) => params,
+        Err(e) => return outcome(device.id, FanOutStatus::Rejected, None, Some(e.to_string())),
+    };
+
+    let inserted = sqlx::query_scalar::<_, Uuid>(
+        "INSERT INTO device_commands \
+         (device_id, user_id, command, parameters, estimated_duration_ms, estimated_battery_drain, swarm_mission_id) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id",
+    )
+    .bind(device.id)
+    .bind(user_id)
+    .bind(&body.command)
+    .bind(&body.parameters)
+    .bind(service.estimate_duration_ms(&params) as i64)
+    .bind(service.estimate_battery_drain(&body.command, &params))
+    .bind(mission_id)
+    .fetch_one(pool)
+    .await;
+
+    match inserted {
+        Ok(command_id) => outcome(device.id, FanOutStatus::Dispatched, Some(command_id), None),
+        Err(e) => outcome(device.id, FanOutStatus::Failed, None, Some(ApiError::from(e).to_string())),
+    }
+}
+
+/// Withdraw a dispatched command that hasn't run yet and queue a stop in case it already started
+async fn compensate(
+    pool: &PgPool,
+    user_id: Uuid,
+    mission_id: Uuid,
+    device: &FanOutTarget,
+    command_id: Uuid,
+) -> ApiResult<Uuid> {
+    let (stop, parameters) = stop_command(&device.device_type, device.last_altitude);
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "UPDATE device_commands SET status = 'cancelled', acked_at = NOW() WHERE id = $1 AND acked_at IS NULL",
+    )
+    .bind(command_id)
+    .execute(&mut *tx)
+    .await?;
+    let stop_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO device_commands \
+         (device_id, user_id, command, parameters, estimated_duration_ms, estimated_battery_drain, swarm_mission_id) \
+         VALUES ($1, $2, $3, $4, 0, 0, $5) RETURNING id",
+    )
+    .bind(device.id)
+    .bind(user_id)
+    .bind(stop)
+    .bind(parameters)
+    .bind(mission_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(stop_id)
+}
+
+/// Send one command to a set of devices (by id, tag and/or region) concurrently. If fewer than
+/// `min_success` devices accept it, every device that did is sent a compensating stop.
+/// POST /api/robotics/swarm/commands
+pub async fn fan_out_command(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<SwarmFanOutRequest>,
+) -> ApiResult<HttpResponse> {
+    let tag = body.tag.as_deref().map(str::trim);
+    if tag.is_some_and(|t| t.is_empty() || t.len() > 50) {
+        return Err(ApiError::ValidationError("tag must be 1-50 characters".to_string()));
+    }
+    if body.device_ids.is_empty() && tag.is_none() && body.region_id.is_none() {
+        return Err(ApiError::ValidationError("Provide device_ids, a tag, a region_id, or a mix".to_string()));
+    }
+    let region_id = require_user_filter(pool.get_ref(), &user, body.region_id).await?;
+    let parallelism = fanout_parallelism(body.max_parallel)?;
+
+    let targets = sqlx::query_as::<_, FanOutTarget>(&format!(
+        "SELECT d.id, d.device_type, d.firmware_version, d.status, d.last_altitude, {} AS attachments \
+         FROM devices d \
+         WHERE d.user_id = $1 \
+           AND (d.id = ANY($2) OR ($3::text IS NOT NULL AND d.metadata -> 'tags' @> jsonb_build_array($3::text)) \
+                OR d.id IN (SELECT device_id FROM device_regions WHERE region_id = $4)) \
+         ORDER BY d.id",
+        ATTACHED_TYPES_SQL
+    ))
+    .bind(user.user_id)
+    .bind(&body.device_ids)
+    .bind(tag)
+    .bind(region_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    let requested: HashSet<Uuid> = body.device_ids.iter().copied().collect();
+    if requested.iter().any(|id| !targets.iter().any(|t| t.id == *id)) {
+        return Err(ApiError::NotFound("One or more devices not found".to_string()));
+    }
+    if targets.is_empty() {
+        return Err(ApiError::NotFound("No devices carry that tag or are in that region".to_string()));
+    }
+    if targets.len() > MAX_SWARM_SIZE {
+        return Err(ApiError::ValidationError(format!("A swarm needs 1-{} devices", MAX_SWARM_SIZE)));
+    }
+    let min_success = resolve_min_success(body.min_success, targets.len())?;
+
+    let mission = sqlx::query_as::<_, SwarmMission>(&format!(
+        "INSERT INTO swarm_missions (user_id, command, parameters) VALUES ($1, $2, $3) RETURNING {}",
+        MISSION_COLUMNS
+    ))
+    .bind(user.user_id)
+    .bind(&body.command)
+    .bind(serde_json::json!({
+        "parameters": body.parameters,
+        "device_ids": body.device_ids,
+        "tag": tag,
+        "region_id": region_id,
+        "min_success": min_success,
+    }))
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    let outcomes: Vec<DeviceOutcome> = stream::iter(&targets)
+        .map(|device| dispatch_one(pool.get_ref(), user.user_id, mission.id, &body, device))
+        .buffer_unordered(parallelism)
+        .collect()
+        .await;
+    let mut report = FanOutReport::new(mission.id, &body.command, min_success, outcomes);
+
+    if !report.threshold_met {
+        let db: &PgPool = pool.get_ref();
+        let to_compensate: Vec<(usize, Uuid)> = report
+            .outcomes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, o)| o.command_id.map(|id| (i, id)))
+            .collect();
+        let stops: Vec<(usize, ApiResult<Uuid>)> = stream::iter(to_compensate)
+            .map(|(i, command_id)| {
+                let device = targets.iter().find(|t| t.id == report.outcomes[i].device_id).expect("targeted device");
+                async move { (i, compensate(db, user.user_id, mission.id, device, command_id).await) }
+            })
+            .buffer_unordered(parallelism)
+            .collect()
+            .await;
+        for (i, stop) in stops {
+            match stop {
+                Ok(stop_id) => report.outcomes[i].compensation_command_id = Some(stop_id),
+                Err(e) => report.outcomes[i].error = Some(format!("Compensating stop failed: {}", e)),
+            }
+        }
+        report.compensated = true;
+    }
+
+    let report_json = serde_json::to_value(&report)
+        .map_err(|e| ApiError::InternalError(format!("Failed to encode fan-out report: {}", e)))?;
+    sqlx::query("UPDATE swarm_missions SET status = $2, report = $3 WHERE id = $1")
+        .bind(mission.id)
+        .bind(if report.compensated { "compensated" } else { "dispatched" })
+        .bind(&report_json)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+
+    Ok(ApiResponse::created(report))
+}
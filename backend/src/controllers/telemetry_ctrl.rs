@@ -0,0 +1,299 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::device::SimulateBatteryRequest;
+use crate::models::sensor::DeviceSensor;
+use crate::services::automation_services;
+use crate::services::fleet_config_services;
+use crate::services::incident_services::send_alert_email;
+use crate::services::device_services::get_owned_device;
+use crate::services::mission_services::{device_plan, validate_legs, CommandPlan};
+use crate::services::processor_services::{self, ProcessorRuntime};
+use crate::services::promotion_services::{device_profiles, macro_plan, owned_macro, validate_macro_steps};
+use crate::services::region_services;
+use crate::services::robotics_services::{
+    BatteryForecast, BatterySample, DeviceTelemetry, DrainObservation, RoboticsService, BATTERY_RESERVE_LEVEL,
+};
+use crate::services::sensor_services::{validate_readings, SENSOR_COLUMNS};
+use crate::services::transport_services::TransportRegistry;
+use crate::utils::geo::is_valid_coordinate;
+
+/// Store a telemetry sample reported for a device and update its last known position.
+/// The owner's telemetry processors run first and may add derived metrics or drop the sample;
+/// telemetry automations, alert rules and geofences are evaluated on stored samples in the
+/// background. Critical processor alerts open an incident, and the owner is emailed about each
+/// new one.
+/// POST /api/robotics/devices/{device_id}/telemetry
+pub async fn ingest_telemetry(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    processors: web::Data<Arc<ProcessorRuntime>>,
+    transports: web::Data<Arc<TransportRegistry>>,
+    path: web::Path<Uuid>,
+    body: web::Json<DeviceTelemetry>,
+) -> ApiResult<HttpResponse> {
+    let device_id = path.into_inner();
+    get_owned_device(pool.get_ref(), device_id, user.user_id).await?;
+
+    let telemetry = body.into_inner();
+    if !is_valid_coordinate(telemetry.position.latitude, telemetry.position.longitude) {
+        return Err(ApiError::ValidationError("Telemetry position is out of range".to_string()));
+    }
+    if telemetry.battery_level > 100 {
+        return Err(ApiError::ValidationError("Battery level must be between 0 and 100".to_string()));
+    }
+    if telemetry.timestamp > Utc::now() + Duration::minutes(5) {
+        return Err(ApiError::ValidationError("Telemetry timestamp is in the future".to_string()));
+    }
+
+    let sensors = sqlx::query_as::<_, DeviceSensor>(&format!(
+        "SELECT {} FROM device_sensors WHERE device_id = $1",
+        SENSOR_COLUMNS
+    ))
+    .bind(device_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+    validate_readings(&telemetry.sensors, &sensors)?;
+
+    let mut payload = serde_json::to_value(&telemetry)
+        .map_err(|e| ApiError::InternalError(format!("Failed to encode telemetry: {}", e)))?;
+    let pipeline =
+        processor_services::apply(pool.get_ref(), processors.get_ref(), user.user_id, device_id, &mut payload).await?;
+    for incident in &pipeline.opened_incidents {
+        // The incident is recorded either way; a lost email should not reject the sample
+        if let Err(e) = send_alert_email(pool.get_ref(), &config, incident.id, &incident.message).await {
+            tracing::warn!("Alert email for incident {} failed: {}", incident.id, e);
+        }
+    }
+    if pipeline.dropped_by.is_some() {
+        return Ok(ApiResponse::success(serde_json::json!({
+            "device_id": device_id,
+            "stored": false,
+            "processors": pipeline,
+        })));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO device_telemetry (device_id, recorded_at, battery_level, latitude, longitude, altitude, payload) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(device_id)
+    .bind(telemetry.timestamp)
+    .bind(telemetry.battery_level as i16)
+    .bind(telemetry.position.latitude)
+    .bind(telemetry.position.longitude)
+    .bind(telemetry.position.altitude)
+    .bind(&payload)
+    .execute(&mut *tx)
+    .await?;
+
+    // Late-arriving samples must not move the device backwards in time
+    let moved = sqlx::query(
+        "UPDATE devices SET last_latitude = $2, last_longitude = $3, last_altitude = $4, \
+         position_updated_at = $5, last_seen = GREATEST(COALESCE(last_seen, $5), $5) \
+         WHERE id = $1 AND (position_updated_at IS NULL OR position_updated_at <= $5)",
+    )
+    .bind(device_id)
+    .bind(telemetry.position.latitude)
+    .bind(telemetry.position.longitude)
+    .bind(telemetry.position.altitude)
+    .bind(telemetry.timestamp)
+    .execute(&mut *tx)
+    .await?;
+    if moved.rows_affected() > 0 {
+        region_services::assign_device(&mut tx, device_id, telemetry.position.latitude, telemetry.position.longitude)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    let (pool, transports, user_id) = (pool.get_ref().clone(), transports.get_ref().clone(), user.user_id);
+    tokio::spawn(async move {
+        if let Err(e) = automation_services::on_telemetry(&pool, &transports, user_id, device_id, &payload).await {
+            tracing::warn!("Telemetry automations for device {} failed: {}", device_id, e);
+        }
+        if let Err(e) = fleet_config_services::evaluate_alerts(&pool, user_id, device_id, &payload).await {
+            tracing::warn!("Alert rules for device {} failed: {}", device_id, e);
+        }
+    });
+
+    Ok(ApiResponse::created(serde_json::json!({
+        "device_id": device_id,
+        "recorded_at": telemetry.timestamp,
+        "stored": true,
+        "processors": pipeline,
+    })))
+}
+
+/// The last week of battery readings, newest first
+async fn battery_samples(pool: &PgPool, device_id: Uuid) -> ApiResult<Vec<BatterySample>> {
+    let rows: Vec<(DateTime<Utc>, i16)> = sqlx::query_as(
+        "SELECT recorded_at, battery_level FROM device_telemetry \
+         WHERE device_id = $1 AND recorded_at > NOW() - INTERVAL '7 days' \
+         ORDER BY recorded_at DESC LIMIT 10000",
+    )
+    .bind(device_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|&(recorded_at, level)| BatterySample { recorded_at, level: level as f64 })
+        .collect())
+}
+
+/// Remaining runtime from the device's learned discharge curve, and whether its queued commands fit in it
+/// GET /api/robotics/devices/{device_id}/battery/forecast
+pub async fn get_battery_forecast(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let service = RoboticsService::new();
+
+    let samples = battery_samples(pool.get_ref(), device.id).await?;
+    let latest = samples.first().copied();
+    let model = service.learn_discharge_curve(&samples);
+
+    // Commands still awaiting an ack make up the queued mission; older ones are treated as abandoned
+    let queued: Vec<(f32, i64)> = sqlx::query_as(
+        "SELECT estimated_battery_drain, estimated_duration_ms FROM device_commands \
+         WHERE device_id = $1 AND status = 'sent' AND created_at > NOW() - INTERVAL '24 hours'",
+    )
+    .bind(device.id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+    let queued: Vec<(f32, u64)> = queued.into_iter().map(|(d, ms)| (d, ms.max(0) as u64)).collect();
+
+    // How far reality has strayed from the drain estimates on recently acked commands
+    let correction: Option<f64> = sqlx::query_scalar(
+        "SELECT SUM(actual_battery_drain)::float8 / NULLIF(SUM(estimated_battery_drain), 0)::float8 \
+         FROM (SELECT actual_battery_drain, estimated_battery_drain FROM device_commands \
+               WHERE device_id = $1 AND status = 'succeeded' AND actual_battery_drain IS NOT NULL \
+               ORDER BY acked_at DESC LIMIT 50) recent",
+    )
+    .bind(device.id)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+    let correction = correction.unwrap_or(1.0).clamp(0.5, 3.0);
+
+    let current_level = latest.map(|s| s.level);
+    let forecast = BatteryForecast {
+        device_id: device.id,
+        current_level,
+        as_of: latest.map(|s| s.recorded_at),
+        remaining_runtime_minutes: current_level
+            .and_then(|level| model.remaining_runtime_hours(level, BATTERY_RESERVE_LEVEL))
+            .map(|hours| hours * 60.0),
+        reserve_level: BATTERY_RESERVE_LEVEL,
+        queued_mission: current_level.map(|level| service.forecast_mission(&model, level, &queued, correction)),
+        model,
+    };
+
+    Ok(ApiResponse::success(forecast))
+}
+
+/// What-if battery run of a command sequence, saved macro or planned fleet mission, using the
+/// drain this device has actually shown per command and its learned discharge curve rather
+/// than the static estimator. Returns a 90% interval for the end level and a go/no-go call.
+/// POST /api/robotics/devices/{device_id}/battery/simulate
+pub async fn simulate_battery(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<SimulateBatteryRequest>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let service = RoboticsService::new();
+
+    let (source, plan): (&str, CommandPlan) = match (&body.steps, body.macro_id, &body.legs) {
+        (Some(steps), None, None) => {
+            validate_macro_steps(&device.device_type, steps)?;
+            ("steps", macro_plan(steps))
+        }
+        (None, Some(macro_id), None) => {
+            let command_macro = owned_macro(pool.get_ref(), macro_id, user.user_id).await?;
+            if command_macro.device_type != device.device_type {
+                return Err(ApiError::ValidationError(format!(
+                    "Macro is for {} devices, not {}",
+                    command_macro.device_type, device.device_type
+                )));
+            }
+            ("macro", macro_plan(&command_macro.steps))
+        }
+        (None, None, Some(legs)) => {
+            validate_legs(legs)?;
+            let profile = device_profiles(pool.get_ref(), user.user_id, &[device.id]).await?.remove(&device.id);
+            let position = profile
+                .and_then(|p| p.position())
+                .ok_or_else(|| ApiError::BadRequest("Device has not reported a position yet".to_string()))?;
+            let plan = device_plan(&device.device_type, device.id, position, legs)?;
+            if plan.is_empty() {
+                return Err(ApiError::ValidationError("No leg of the mission uses this device".to_string()));
+            }
+            ("mission", plan)
+        }
+        _ => {
+            return Err(ApiError::ValidationError(
+                "Provide exactly one of steps, macro_id or legs".to_string(),
+            ));
+        }
+    };
+
+    let mut steps = Vec::with_capacity(plan.len());
+    let mut static_drain = 0.0;
+    for (i, (command, parameters)) in plan.iter().enumerate() {
+        let params = service
+            .validate_command(&device.device_type, &device.firmware_version, command)
+            .and_then(|_| service.parse_command_params(command, parameters))
+            .map_err(|e| ApiError::ValidationError(format!("Step {} ({}): {}", i, command, e)))?;
+        static_drain += service.estimate_battery_drain(command, &params) as f64;
+        steps.push((command.clone(), service.estimate_duration_ms(&params)));
+    }
+
+    let samples = battery_samples(pool.get_ref(), device.id).await?;
+    let start_level = match body.start_level {
+        Some(level) if !(0.0..=100.0).contains(&level) => {
+            return Err(ApiError::ValidationError("start_level must be between 0 and 100".to_string()));
+        }
+        Some(level) => level,
+        None => samples
+            .first()
+            .map(|s| s.level)
+            .ok_or_else(|| ApiError::BadRequest("No recent battery reading; pass start_level".to_string()))?,
+    };
+    let model = service.learn_discharge_curve(&samples);
+
+    let history = sqlx::query_as::<_, DrainObservation>(
+        "SELECT command, actual_battery_drain::float8 AS drain, actual_duration_ms AS duration_ms \
+         FROM device_commands \
+         WHERE device_id = $1 AND status = 'succeeded' AND actual_battery_drain IS NOT NULL \
+           AND actual_duration_ms > 0 AND acked_at > NOW() - INTERVAL '90 days' \
+         ORDER BY acked_at DESC LIMIT 2000",
+    )
+    .bind(device.id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    let simulation = service.simulate_battery(&model, &history, start_level, &steps);
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "device_id": device.id,
+        "source": source,
+        "simulation": simulation,
+        // For comparison: what the static per-command estimator would have predicted
+        "static_estimate": {
+            "drain": static_drain,
+            "projected_level": start_level - static_drain,
+        },
+        "model": model,
+    })))
+}
@@ -0,0 +1,457 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::{AdminUser, AuthenticatedUser};
+use crate::models::template::{
+    ImportTemplateRequest, ModerateTemplateRequest, PublishTemplateRequest, RateTemplateRequest,
+    ReportTemplateRequest, Template, TemplateImport, TemplateQuery, TemplateRating, TemplateReport,
+};
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::notification_services::notify_user;
+use crate::services::template_services::{
+    self, content_sha256, fill, resolve_values, screen, templatize_devices, validate_kind, validate_placeholders,
+    REPORT_HIDE_THRESHOLD, TEMPLATE_COLUMNS,
+};
+
+const IMPORT_COLUMNS: &str = "id, template_id, user_id, target_kind, target_id, content_sha256, imported_at";
+
+fn is_admin(user: &AuthenticatedUser) -> bool {
+    user.claims.role.as_deref() == Some("admin")
+}
+
+fn validate_name(name: &str) -> ApiResult<()> {
+    if name.is_empty() || name.len() > 100 {
+        return Err(ApiError::ValidationError("name must be 1-100 characters".to_string()));
+    }
+    Ok(())
+}
+
+/// Browse published templates
+/// GET /api/templates
+pub async fn list_templates(
+    _user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<TemplateQuery>,
+) -> ApiResult<HttpResponse> {
+    if let Some(kind) = &query.kind {
+        validate_kind(kind)?;
+    }
+    let order = match query.sort.as_deref().unwrap_or("recent") {
+        "recent" => "created_at DESC",
+        "rating" => "rating DESC NULLS LAST, rating_count DESC",
+        "imports" => "import_count DESC, created_at DESC",
+        _ => return Err(ApiError::ValidationError("sort must be recent, rating or imports".to_string())),
+    };
+    let pattern = query.q.as_deref().map(|q| format!("%{}%", q.trim().replace('%', "\\%").replace('_', "\\_")));
+
+    let templates = sqlx::query_as::<_, Template>(&format!(
+        "SELECT {} FROM templates WHERE status = 'published' AND ($1::text IS NULL OR kind = $1) \
+         AND ($2::text IS NULL OR name ILIKE $2 OR description ILIKE $2) ORDER BY {} LIMIT 100",
+        TEMPLATE_COLUMNS, order
+    ))
+    .bind(&query.kind)
+    .bind(pattern)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(templates))
+}
+
+/// Publish one of the caller's automations, completed fleet missions or macros. Device ids
+/// become placeholders; content tripping a moderation check waits for admin review.
+/// POST /api/templates
+pub async fn publish_template(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    body: web::Json<PublishTemplateRequest>,
+) -> ApiResult<HttpResponse> {
+    validate_kind(&body.kind)?;
+    let name = body.name.trim();
+    validate_name(name)?;
+
+    let (content, device_ids, source_version) =
+        template_services::source_content(pool.get_ref(), user.user_id, &body.kind, body.source_id).await?;
+    let (content, mut placeholders) = templatize_devices(&content, &device_ids);
+    placeholders.extend(body.placeholders.iter().cloned());
+    validate_placeholders(&content, &placeholders)?;
+
+    let flags = screen(&body.kind, &content);
+    let status = if flags.is_empty() { "published" } else { "pending_review" };
+    let parent = template_services::imported_from(pool.get_ref(), user.user_id, body.source_id).await?;
+
+    let template = sqlx::query_as::<_, Template>(&format!(
+        "INSERT INTO templates (publisher_id, kind, name, description, content, placeholders, content_sha256, \
+                                source_id, source_version, parent_template_id, status, moderation_flags) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) RETURNING {}",
+        TEMPLATE_COLUMNS
+    ))
+    .bind(user.user_id)
+    .bind(&body.kind)
+    .bind(name)
+    .bind(body.description.as_deref().map(str::trim))
+    .bind(&content)
+    .bind(sqlx::types::Json(&placeholders))
+    .bind(content_sha256(&content))
+    .bind(body.source_id)
+    .bind(source_version)
+    .bind(parent)
+    .bind(status)
+    .bind(&flags)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::created(template))
+}
+
+/// GET /api/templates/{template_id}
+pub async fn get_template(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let template =
+        template_services::visible_template(pool.get_ref(), path.into_inner(), user.user_id, is_admin(&user)).await?;
+    Ok(ApiResponse::success(template))
+}
+
+/// Take a template off the marketplace; earlier imports and their provenance are kept
+/// DELETE /api/templates/{template_id}
+pub async fn withdraw_template(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let withdrawn = sqlx::query(
+        "UPDATE templates SET status = 'withdrawn', updated_at = NOW() \
+         WHERE id = $1 AND publisher_id = $2 AND status <> 'removed'",
+    )
+    .bind(path.into_inner())
+    .bind(user.user_id)
+    .execute(pool.get_ref().as_ref())
+    .await?;
+    if withdrawn.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Template not found".to_string()));
+    }
+
+    Ok(crate::errors::success_message("Template withdrawn"))
+}
+
+/// Fill in the placeholders and create the caller's own copy; with `preview` only the
+/// filled-in content is returned
+/// POST /api/templates/{template_id}/import
+pub async fn import_template(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<ImportTemplateRequest>,
+) -> ApiResult<HttpResponse> {
+    let template = template_services::visible_template(pool.get_ref(), path.into_inner(), user.user_id, false).await?;
+    if template.status != "published" && template.publisher_id != Some(user.user_id) {
+        return Err(ApiError::NotFound("Template not found".to_string()));
+    }
+    let name = body.name.as_deref().map(str::trim).unwrap_or(&template.name);
+    validate_name(name)?;
+
+    let values = resolve_values(&template.placeholders, &body.values)?;
+    let content = fill(&template.content, &values);
+    if body.preview {
+        return Ok(ApiResponse::success(serde_json::json!({ "kind": template.kind, "content": content })));
+    }
+
+    let created = template_services::instantiate(pool.get_ref(), user.user_id, &template.kind, name, &content).await?;
+
+    let mut tx = pool.begin().await?;
+    let import = sqlx::query_as::<_, TemplateImport>(&format!(
+        "INSERT INTO template_imports (template_id, user_id, target_kind, target_id, content_sha256) \
+         VALUES ($1, $2, $3, $4, $5) RETURNING {}",
+        IMPORT_COLUMNS
+    ))
+    .bind(template.id)
+    .bind(user.user_id)
+    .bind(created.target_kind)
+    .bind(created.target_id)
+    .bind(&template.content_sha256)
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query("UPDATE templates SET import_count = import_count + 1 WHERE id = $1")
+        .bind(template.id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::created(serde_json::json!({ "import": import, "created": created.created })))
+}
+
+/// Templates the caller has imported and what each import created
+/// GET /api/templates/imports
+pub async fn list_imports(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let imports = sqlx::query_as::<_, TemplateImport>(&format!(
+        "SELECT {} FROM template_imports WHERE user_id = $1 ORDER BY imported_at DESC LIMIT 100",
+        IMPORT_COLUMNS
+    ))
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(imports))
+}
+
+/// The chain of templates this one was derived from, back to the original
+/// GET /api/templates/{template_id}/provenance
+pub async fn get_provenance(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let template =
+        template_services::visible_template(pool.get_ref(), path.into_inner(), user.user_id, is_admin(&user)).await?;
+    let chain = template_services::provenance(pool.get_ref(), template.id).await?;
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "source_id": template.source_id,
+        "source_version": template.source_version,
+        "chain": chain,
+    })))
+}
+
+/// GET /api/templates/{template_id}/ratings
+pub async fn list_ratings(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let template =
+        template_services::visible_template(pool.get_ref(), path.into_inner(), user.user_id, is_admin(&user)).await?;
+
+    let ratings = sqlx::query_as::<_, TemplateRating>(
+        "SELECT user_id, stars, review, updated_at FROM template_ratings WHERE template_id = $1 \
+         ORDER BY updated_at DESC LIMIT 100",
+    )
+    .bind(template.id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(ratings))
+}
+
+/// Rate a template the caller has imported; rating again replaces the earlier rating
+/// PUT /api/templates/{template_id}/rating
+pub async fn rate_template(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<RateTemplateRequest>,
+) -> ApiResult<HttpResponse> {
+    if !(1..=5).contains(&body.stars) {
+        return Err(ApiError::ValidationError("stars must be between 1 and 5".to_string()));
+    }
+    let review = body.review.as_deref().map(str::trim).filter(|r| !r.is_empty());
+    if review.is_some_and(|r| r.len() > 2000) {
+        return Err(ApiError::ValidationError("review must be at most 2000 characters".to_string()));
+    }
+    let template = template_services::visible_template(pool.get_ref(), path.into_inner(), user.user_id, false).await?;
+    if template.publisher_id == Some(user.user_id) {
+        return Err(ApiError::BadRequest("You cannot rate your own template".to_string()));
+    }
+    let imported: bool =
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM template_imports WHERE template_id = $1 AND user_id = $2)")
+            .bind(template.id)
+            .bind(user.user_id)
+            .fetch_one(pool.get_ref().as_ref())
+            .await?;
+    if !imported {
+        return Err(ApiError::BadRequest("Import a template before rating it".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO template_ratings (template_id, user_id, stars, review) VALUES ($1, $2, $3, $4) \
+         ON CONFLICT (template_id, user_id) DO UPDATE SET stars = EXCLUDED.stars, review = EXCLUDED.review, \
+         updated_at = NOW()",
+    )
+    .bind(template.id)
+    .bind(user.user_id)
+    .bind(body.stars)
+    .bind(review)
+    .execute(&mut *tx)
+    .await?;
+    let template = sqlx::query_as::<_, Template>(&format!(
+        "UPDATE templates SET \
+         rating_count = (SELECT COUNT(*) FROM template_ratings WHERE template_id = $1), \
+         rating_total = (SELECT COALESCE(SUM(stars), 0) FROM template_ratings WHERE template_id = $1) \
+         WHERE id = $1 RETURNING {}",
+        TEMPLATE_COLUMNS
+    ))
+    .bind(template.id)
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(ApiResponse::success(template))
+}
+
+/// Flag a template for moderators; enough open reports hide it until reviewed
+/// POST /api/templates/{template_id}/report
+pub async fn report_template(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<ReportTemplateRequest>,
+) -> ApiResult<HttpResponse> {
+    let reason = body.reason.trim();
+    if reason.is_empty() || reason.len() > 1000 {
+        return Err(ApiError::ValidationError("reason must be 1-1000 characters".to_string()));
+    }
+    let template = template_services::visible_template(pool.get_ref(), path.into_inner(), user.user_id, false).await?;
+
+    let mut tx = pool.begin().await?;
+    let inserted = sqlx::query(
+        "INSERT INTO template_reports (template_id, reporter_id, reason) VALUES ($1, $2, $3) \
+         ON CONFLICT (template_id, reporter_id) DO NOTHING",
+    )
+    .bind(template.id)
+    .bind(user.user_id)
+    .bind(reason)
+    .execute(&mut *tx)
+    .await?;
+    if inserted.rows_affected() == 0 {
+        return Err(ApiError::Conflict("You have already reported this template".to_string()));
+    }
+
+    let open: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM template_reports WHERE template_id = $1 AND status = 'open'")
+            .bind(template.id)
+            .fetch_one(&mut *tx)
+            .await?;
+    if open >= REPORT_HIDE_THRESHOLD {
+        let hidden = sqlx::query(
+            "UPDATE templates SET status = 'hidden', updated_at = NOW(), \
+             moderation_flags = array_append(array_remove(moderation_flags, 'reported'), 'reported') \
+             WHERE id = $1 AND status = 'published'",
+        )
+        .bind(template.id)
+        .execute(&mut *tx)
+        .await?;
+        if hidden.rows_affected() > 0 {
+            audit_services::record(
+                &mut tx,
+                AuditEntry {
+                    org_id: None,
+                    actor_id: None,
+                    action: "templates.auto_hidden",
+                    resource_type: "template",
+                    resource_id: Some(template.id.to_string()),
+                    details: serde_json::json!({ "open_reports": open }),
+                },
+            )
+            .await?;
+        }
+    }
+    tx.commit().await?;
+
+    Ok(crate::errors::success_message("Template reported"))
+}
+
+/// Templates waiting on a moderator: held at publish or with open reports
+/// GET /api/admin/templates/review
+pub async fn list_review_queue(
+    _admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let templates = sqlx::query_as::<_, Template>(&format!(
+        "SELECT {} FROM templates WHERE status = 'pending_review' \
+         OR id IN (SELECT template_id FROM template_reports WHERE status = 'open') \
+         ORDER BY created_at LIMIT 100",
+        TEMPLATE_COLUMNS
+    ))
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+    let ids: Vec<Uuid> = templates.iter().map(|t| t.id).collect();
+    let reports = sqlx::query_as::<_, TemplateReport>(
+        "SELECT id, template_id, reporter_id, reason, status, created_at FROM template_reports \
+         WHERE template_id = ANY($1) AND status = 'open' ORDER BY created_at",
+    )
+    .bind(&ids)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    let queue: Vec<serde_json::Value> = templates
+        .into_iter()
+        .map(|template| {
+            let open: Vec<&TemplateReport> = reports.iter().filter(|r| r.template_id == template.id).collect();
+            serde_json::json!({ "template": template, "reports": open })
+        })
+        .collect();
+
+    Ok(ApiResponse::success(queue))
+}
+
+/// Publish, hide or remove a template, resolving its open reports
+/// POST /api/admin/templates/{template_id}/moderate
+pub async fn moderate_template(
+    admin: AdminUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<ModerateTemplateRequest>,
+) -> ApiResult<HttpResponse> {
+    let status = match body.action.as_str() {
+        "publish" => "published",
+        "hide" => "hidden",
+        "remove" => "removed",
+        _ => return Err(ApiError::ValidationError("action must be publish, hide or remove".to_string())),
+    };
+    let note = body.note.as_deref().map(str::trim).filter(|n| !n.is_empty());
+
+    let mut tx = pool.begin().await?;
+    let template = sqlx::query_as::<_, Template>(&format!(
+        "UPDATE templates SET status = $2, moderated_by = $3, moderated_at = NOW(), moderation_note = $4, \
+         updated_at = NOW() WHERE id = $1 AND status <> 'withdrawn' RETURNING {}",
+        TEMPLATE_COLUMNS
+    ))
+    .bind(path.into_inner())
+    .bind(status)
+    .bind(admin.0.user_id)
+    .bind(note)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Template not found".to_string()))?;
+    sqlx::query(
+        "UPDATE template_reports SET status = 'resolved', resolved_by = $2, resolved_at = NOW() \
+         WHERE template_id = $1 AND status = 'open'",
+    )
+    .bind(template.id)
+    .bind(admin.0.user_id)
+    .execute(&mut *tx)
+    .await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin.0.user_id),
+            action: "templates.moderated",
+            resource_type: "template",
+            resource_id: Some(template.id.to_string()),
+            details: serde_json::json!({ "status": status, "note": note, "flags": template.moderation_flags }),
+        },
+    )
+    .await?;
+    if let Some(publisher_id) = template.publisher_id {
+        notify_user(
+            &mut tx,
+            publisher_id,
+            "template_moderated",
+            &format!("Template \"{}\" was {}", template.name, status.replace('_', " ")),
+            note.unwrap_or("A moderator reviewed your template."),
+            serde_json::json!({ "template_id": template.id, "status": status }),
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(ApiResponse::success(template))
+}
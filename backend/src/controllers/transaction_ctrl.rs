@@ -0,0 +1,19 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::errors::{ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::transaction::TransactionListQuery;
+use crate::services::transaction_services;
+
+/// The caller's transactions, newest first, with the total count and whether more follow.
+/// Page with `before` (the last id of a page) or `after` (the first id) instead of offsets.
+/// GET /api/blockchain/transactions?before=&after=&limit=
+pub async fn get_transactions(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<TransactionListQuery>,
+) -> ApiResult<HttpResponse> {
+    let page = transaction_services::list(pool.get_ref(), user.user_id, &query).await?;
+    Ok(ApiResponse::success(page))
+}
@@ -0,0 +1,43 @@
+use actix_web::{http::header::CONTENT_DISPOSITION, web, HttpResponse};
+use chrono::Utc;
+use futures::StreamExt;
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::errors::{ApiError, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::transaction::TransactionExportQuery;
+use crate::services::transaction_export_services;
+
+/// Download the caller's transactions created in `[from, to)` for their records. The file is
+/// streamed as it is read, so long histories are not capped.
+/// GET /api/blockchain/transactions/export?from=&to=&format=csv
+pub async fn export_transactions(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    query: web::Query<TransactionExportQuery>,
+) -> ApiResult<HttpResponse> {
+    let format = query.format.as_deref().unwrap_or("csv");
+    if format != "csv" {
+        return Err(ApiError::ValidationError(format!("Unsupported export format: {}", format)));
+    }
+    if matches!((query.from, query.to), (Some(from), Some(to)) if from >= to) {
+        return Err(ApiError::ValidationError("from must be before to".to_string()));
+    }
+
+    let user_id = user.user_id;
+    let chunks = transaction_export_services::export_csv(pool.get_ref().clone(), user_id, query.from, query.to)
+        .map(move |chunk| {
+            // Headers are already sent; all that is left is to cut the download short
+            chunk
+                .inspect_err(|e| tracing::error!("Transaction export for {} failed: {}", user_id, e))
+                .map_err(actix_web::Error::from)
+        });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv")
+        .insert_header((
+            CONTENT_DISPOSITION,
+            format!("attachment; filename=\"transactions-{}.csv\"", Utc::now().format("%Y-%m-%d")),
+        ))
+        .streaming(chunks))
+}
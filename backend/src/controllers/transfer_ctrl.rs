@@ -0,0 +1,43 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::config::AppConfig;
+use crate::errors::{ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::transaction::{RelayedTransferRequest, TransferRequest};
+use crate::services::{security_services, transfer_services};
+
+/// Check a transfer and issue the EIP-712 typed data that authorizes it. The sender's wallet must
+/// have approved the returned relayer as a spender of at least the amount.
+/// POST /api/blockchain/transfer/prepare
+pub async fn prepare(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<TransferRequest>,
+) -> ApiResult<HttpResponse> {
+    let prepared = transfer_services::prepare(pool.get_ref(), &config, user.user_id, &body).await?;
+    Ok(ApiResponse::success(prepared))
+}
+
+/// Submit a prepared transfer. The relayer calls `transferFrom` on the token and pays the gas;
+/// the returned transaction stays pending until the chain confirms it.
+/// POST /api/blockchain/transfer
+pub async fn transfer(
+    req: HttpRequest,
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<RelayedTransferRequest>,
+) -> ApiResult<HttpResponse> {
+    let transaction = transfer_services::relay(
+        pool.get_ref(),
+        &config,
+        user.user_id,
+        &body,
+        transfer_services::PRODUCT_TYPE,
+        security_services::edge_country(&req).as_deref(),
+    )
+    .await?;
+    Ok(ApiResponse::created(transaction))
+}
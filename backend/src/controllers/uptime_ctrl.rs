@@ -0,0 +1,29 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::device::UptimeQuery;
+use crate::services::device_services::get_owned_device;
+use crate::services::uptime_services::{self, parse_period, DEFAULT_PERIOD};
+
+/// Uptime percentage, downtime incidents, MTBF and MTTR from the device's status history
+/// GET /api/robotics/devices/{device_id}/uptime?period=30d
+pub async fn get_uptime(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    query: web::Query<UptimeQuery>,
+) -> ApiResult<HttpResponse> {
+    let device = get_owned_device(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let period = parse_period(query.period.as_deref().unwrap_or(DEFAULT_PERIOD))?;
+
+    let report = uptime_services::device_uptime(pool.get_ref(), device.id, period).await?;
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "device_id": device.id,
+        "status": device.status,
+        "report": report,
+    })))
+}
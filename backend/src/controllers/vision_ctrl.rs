@@ -0,0 +1,70 @@
+use actix_multipart::Multipart;
+use actix_web::{web, Either, HttpResponse};
+use futures::StreamExt;
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::vision::VisionRequest;
+use crate::services::ai_services::AIService;
+use crate::services::vision_services::{self, MAX_IMAGE_BYTES};
+
+/// Text fields of the form are short
+const MAX_FIELD_BYTES: usize = 16 * 1024;
+
+/// The request fields and uploaded `image` of a multipart form
+async fn read_form(mut form: Multipart) -> ApiResult<(VisionRequest, Option<Vec<u8>>)> {
+    let mut request = VisionRequest::default();
+    let mut image = None;
+    while let Some(field) = form.next().await {
+        let mut field = field.map_err(|e| ApiError::BadRequest(format!("Invalid form: {}", e)))?;
+        let name = field.name().unwrap_or_default().to_string();
+        let limit = if name == "image" { MAX_IMAGE_BYTES } else { MAX_FIELD_BYTES };
+        let data = field
+            .bytes(limit)
+            .await
+            .map_err(|_| ApiError::ValidationError(format!("{} must be at most {} bytes", name, limit)))?
+            .map_err(|e| ApiError::BadRequest(format!("Invalid form: {}", e)))?;
+        if name == "image" {
+            image = Some(data.to_vec());
+            continue;
+        }
+        let text = String::from_utf8(data.to_vec())
+            .map_err(|_| ApiError::ValidationError(format!("{} must be UTF-8 text", name)))?;
+        match name.as_str() {
+            "prompt" => request.prompt = text,
+            "image_url" => request.image_url = Some(text),
+            "model" => request.model = Some(text),
+            "provider" => request.provider = Some(text),
+            "max_tokens" => {
+                let max_tokens = text.trim().parse().map_err(|_| {
+                    ApiError::ValidationError("max_tokens must be a positive number".to_string())
+                })?;
+                request.max_tokens = Some(max_tokens);
+            }
+            _ => {}
+        }
+    }
+    Ok((request, image))
+}
+
+/// Analyze an image with a vision model: a JSON body with `image_url`, or a multipart form
+/// uploading the image as `image`, with a `prompt` either way
+/// POST /api/ai/vision
+pub async fn analyze_image(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    ai: web::Data<Arc<AIService>>,
+    body: Either<web::Json<VisionRequest>, Multipart>,
+) -> ApiResult<HttpResponse> {
+    let (request, upload) = match body {
+        Either::Left(json) => (json.into_inner(), None),
+        Either::Right(form) => read_form(form).await?,
+    };
+    let response =
+        vision_services::analyze(pool.get_ref(), config.get_ref(), ai.get_ref(), user.user_id, request, upload)
+            .await?;
+    Ok(ApiResponse::success(response))
+}
@@ -0,0 +1,167 @@
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::typed_data::SignedTypedData;
+use crate::models::user::{WalletAction, WalletAuthorizationRequest};
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::crypto_services::BlockchainService;
+use crate::services::eip712_services;
+use crate::services::ens_services;
+use crate::services::siwe_services::{consume_nonce, issue_nonce};
+use crate::services::wallet_services;
+use crate::utils::log_blockchain_event;
+
+/// Issue the typed data a wallet signs to be linked to the account, or to unlink the linked
+/// wallet. Sign it with `eth_signTypedData_v4` on the chain it names.
+/// POST /api/blockchain/wallet/authorization
+pub async fn prepare_authorization(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<WalletAuthorizationRequest>,
+) -> ApiResult<HttpResponse> {
+    let (linked, linked_chain_id): (Option<String>, Option<i64>) =
+        sqlx::query_as("SELECT wallet_address, wallet_chain_id FROM users WHERE id = $1")
+            .bind(user.user_id)
+            .fetch_one(pool.get_ref().as_ref())
+            .await?;
+    let wallet = match (body.action, linked) {
+        (WalletAction::Link, Some(_)) => {
+            return Err(ApiError::Conflict("Unlink the current wallet before linking another".to_string()));
+        }
+        (WalletAction::Link, None) => {
+            let address = body
+                .address
+                .as_deref()
+                .map(str::trim)
+                .ok_or_else(|| ApiError::ValidationError("address is required to link a wallet".to_string()))?;
+            BlockchainService::validate_address(address)?;
+            address.to_ascii_lowercase()
+        }
+        (WalletAction::Unlink, Some(wallet)) => wallet,
+        (WalletAction::Unlink, None) => {
+            return Err(ApiError::NotFound("No wallet is linked to this account".to_string()));
+        }
+    };
+    let chain_id = body
+        .chain_id
+        .or(linked_chain_id.map(|id| id as u64))
+        .unwrap_or(config.default_chain_id);
+    if config.chain(chain_id).is_none() {
+        return Err(ApiError::ValidationError(format!("Chain {} is not supported", chain_id)));
+    }
+
+    let (nonce, expires_at) = issue_nonce(pool.get_ref()).await?;
+    let typed_data = wallet_services::authorization(body.action, chain_id, &wallet, user.user_id, &nonce, expires_at);
+    Ok(ApiResponse::success(serde_json::json!({
+        "typed_data": typed_data,
+        "expires_at": expires_at,
+    })))
+}
+
+/// Link the wallet that signed a `link` authorization to the account, which must have none
+/// POST /api/blockchain/link-wallet
+pub async fn link_wallet(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<SignedTypedData>,
+) -> ApiResult<HttpResponse> {
+    let wallet = eip712_services::message_str(&body.typed_data, "wallet")?.to_ascii_lowercase();
+    BlockchainService::validate_address(&wallet)?;
+    let checked = wallet_services::check_authorization(WalletAction::Link, user.user_id, &wallet, &body, Utc::now());
+    let (nonce, chain_id) = checked.inspect_err(|_| log_blockchain_event("wallet_link", None, None, "rejected"))?;
+    if config.chain(chain_id).is_none() {
+        return Err(ApiError::ValidationError(format!("Chain {} is not supported", chain_id)));
+    }
+
+    let mut tx = pool.begin().await?;
+    if !consume_nonce(&mut tx, &nonce).await? {
+        return Err(ApiError::Unauthorized("Nonce is invalid or has expired".to_string()));
+    }
+    wallet_services::link(&mut tx, user.user_id, &wallet, chain_id, config.wallet_relink_cooldown_hours).await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(user.user_id),
+            action: "wallet.linked",
+            resource_type: "user",
+            resource_id: Some(user.user_id.to_string()),
+            details: serde_json::json!({ "address": wallet, "chain_id": chain_id }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    log_blockchain_event("wallet_link", None, None, "success");
+    ens_services::spawn_refresh(pool.get_ref().clone(), config.get_ref().clone(), user.user_id);
+
+    Ok(ApiResponse::success(serde_json::json!({
+        "address": BlockchainService::to_checksum_address(&wallet),
+        "chain_id": chain_id,
+    })))
+}
+
+/// Remove the linked wallet. Needs an `unlink` authorization signed by that wallet; afterwards
+/// no wallet can be linked to the account, nor this wallet to any account, until the cooldown
+/// has passed.
+/// DELETE /api/blockchain/wallet
+pub async fn unlink_wallet(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<AppConfig>,
+    body: web::Json<SignedTypedData>,
+) -> ApiResult<HttpResponse> {
+    let (wallet, chain_id, email): (Option<String>, Option<i64>, String) =
+        sqlx::query_as("SELECT wallet_address, wallet_chain_id, email FROM users WHERE id = $1")
+            .bind(user.user_id)
+            .fetch_one(pool.get_ref().as_ref())
+            .await?;
+    let Some(wallet) = wallet else {
+        return Err(ApiError::NotFound("No wallet is linked to this account".to_string()));
+    };
+    // Accounts created by wallet sign-in have no other way to sign in
+    if email.ends_with("@wallet.invalid") {
+        return Err(ApiError::Conflict(
+            "Add an email address and password before unlinking the wallet you sign in with".to_string(),
+        ));
+    }
+
+    let checked = wallet_services::check_authorization(WalletAction::Unlink, user.user_id, &wallet, &body, Utc::now());
+    let (nonce, _) = checked.inspect_err(|_| log_blockchain_event("wallet_unlink", None, None, "rejected"))?;
+
+    let mut tx = pool.begin().await?;
+    if !consume_nonce(&mut tx, &nonce).await? {
+        return Err(ApiError::Unauthorized("Nonce is invalid or has expired".to_string()));
+    }
+    let revoked_sessions = wallet_services::unlink(&mut tx, user.user_id, &wallet, chain_id).await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(user.user_id),
+            action: "wallet.unlinked",
+            resource_type: "user",
+            resource_id: Some(user.user_id.to_string()),
+            details: serde_json::json!({
+                "address": wallet,
+                "chain_id": chain_id,
+                "revoked_sessions": revoked_sessions,
+            }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    log_blockchain_event("wallet_unlink", None, None, "success");
+
+    let relink_after = Utc::now() + chrono::Duration::hours(config.wallet_relink_cooldown_hours as i64);
+    Ok(ApiResponse::success(serde_json::json!({
+        "unlinked": wallet,
+        "revoked_sessions": revoked_sessions,
+        "relink_available_at": relink_after,
+    })))
+}
@@ -0,0 +1,380 @@
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResponse, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::webhook::{
+    CreateWebhookRequest, DeliveryListQuery, DeliverySummaryQuery, DeviceWebhook, RotateSecretRequest,
+    UpdateWebhookRequest, WebhookDelivery, WebhookDeliveryAttempt,
+};
+use crate::services::device_services::owned_device_scope;
+use crate::services::key_services::{KeyManager, KeyPurpose};
+use crate::services::webhook_services::{
+    self, signing_key, signing_secret, validate_events, validate_grace_period, validate_url, ATTEMPT_COLUMNS,
+    DEFAULT_GRACE_PERIOD_HOURS, DELIVERY_COLUMNS, DELIVERY_STATUSES, MAX_WEBHOOKS_PER_USER, WEBHOOK_COLUMNS,
+};
+
+const DEFAULT_DELIVERY_PAGE: i64 = 50;
+const MAX_DELIVERY_PAGE: i64 = 200;
+const MAX_SUMMARY_HOURS: i64 = 24 * 30;
+
+async fn owned_webhook(pool: &PgPool, webhook_id: Uuid, user_id: Uuid) -> ApiResult<DeviceWebhook> {
+    sqlx::query_as::<_, DeviceWebhook>(&format!(
+        "SELECT {} FROM device_webhooks WHERE id = $1 AND user_id = $2",
+        WEBHOOK_COLUMNS
+    ))
+    .bind(webhook_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Webhook not found".to_string()))
+}
+
+/// The webhook's current signing secret under the active key version, with the key id deliveries carry
+async fn secret_for(keys: &dyn KeyManager, webhook: &DeviceWebhook) -> ApiResult<serde_json::Value> {
+    let key = signing_key(&keys.active_key(KeyPurpose::WebhookHmac).await?, webhook.id, webhook.secret_version);
+    Ok(serde_json::json!({
+        "key_id": key.kid(),
+        "secret": signing_secret(&key),
+        "secret_version": webhook.secret_version,
+        "previous_secret_expires_at": webhook.previous_secret_expires_at.filter(|at| *at > Utc::now()),
+    }))
+}
+
+async fn delivery_attempts(pool: &PgPool, ids: &[Uuid]) -> ApiResult<Vec<WebhookDeliveryAttempt>> {
+    Ok(sqlx::query_as::<_, WebhookDeliveryAttempt>(&format!(
+        "SELECT {} FROM webhook_delivery_attempts WHERE delivery_id = ANY($1) ORDER BY attempt",
+        ATTEMPT_COLUMNS
+    ))
+    .bind(ids)
+    .fetch_all(pool)
+    .await?)
+}
+
+/// GET /api/robotics/webhooks
+pub async fn list_webhooks(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+) -> ApiResult<HttpResponse> {
+    let webhooks = sqlx::query_as::<_, DeviceWebhook>(&format!(
+        "SELECT {} FROM device_webhooks WHERE user_id = $1 ORDER BY created_at",
+        WEBHOOK_COLUMNS
+    ))
+    .bind(user.user_id)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(webhooks))
+}
+
+/// Register a webhook; the response includes the secret its deliveries are signed with
+/// POST /api/robotics/webhooks
+pub async fn create_webhook(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    keys: web::Data<Arc<dyn KeyManager>>,
+    body: web::Json<CreateWebhookRequest>,
+) -> ApiResult<HttpResponse> {
+    let url = body.url.trim();
+    validate_url(url)?;
+    validate_events(&body.events)?;
+    let device_ids = owned_device_scope(pool.get_ref(), user.user_id, body.device_ids.as_deref()).await?;
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM device_webhooks WHERE user_id = $1")
+        .bind(user.user_id)
+        .fetch_one(pool.get_ref().as_ref())
+        .await?;
+    if count >= MAX_WEBHOOKS_PER_USER {
+        return Err(ApiError::ValidationError(format!(
+            "At most {} webhooks per account",
+            MAX_WEBHOOKS_PER_USER
+        )));
+    }
+
+    let webhook = sqlx::query_as::<_, DeviceWebhook>(&format!(
+        "INSERT INTO device_webhooks (user_id, url, description, events, device_ids) \
+         VALUES ($1, $2, $3, $4, $5) RETURNING {}",
+        WEBHOOK_COLUMNS
+    ))
+    .bind(user.user_id)
+    .bind(url)
+    .bind(body.description.as_deref().map(str::trim))
+    .bind(&body.events)
+    .bind(device_ids)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+    let signing = secret_for(keys.get_ref().as_ref(), &webhook).await?;
+
+    Ok(ApiResponse::created(serde_json::json!({
+        "webhook": webhook,
+        "signing": signing,
+    })))
+}
+
+/// GET /api/robotics/webhooks/{webhook_id}
+pub async fn get_webhook(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let webhook = owned_webhook(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    Ok(ApiResponse::success(webhook))
+}
+
+/// PATCH /api/robotics/webhooks/{webhook_id}
+pub async fn update_webhook(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateWebhookRequest>,
+) -> ApiResult<HttpResponse> {
+    let webhook = owned_webhook(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let url = body.url.as_deref().map(str::trim);
+    if let Some(url) = url {
+        validate_url(url)?;
+    }
+    if let Some(events) = &body.events {
+        validate_events(events)?;
+    }
+    let rescope = body.device_ids.is_some();
+    let device_ids = owned_device_scope(pool.get_ref(), user.user_id, body.device_ids.as_deref()).await?;
+
+    let webhook = sqlx::query_as::<_, DeviceWebhook>(&format!(
+        "UPDATE device_webhooks SET url = COALESCE($2, url), description = COALESCE($3, description), \
+         events = COALESCE($4, events), device_ids = CASE WHEN $5 THEN $6 ELSE device_ids END, \
+         enabled = COALESCE($7, enabled), updated_at = NOW() WHERE id = $1 RETURNING {}",
+        WEBHOOK_COLUMNS
+    ))
+    .bind(webhook.id)
+    .bind(url)
+    .bind(body.description.as_deref().map(str::trim))
+    .bind(&body.events)
+    .bind(rescope)
+    .bind(device_ids)
+    .bind(body.enabled)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(webhook))
+}
+
+/// Remove a webhook along with its delivery log
+/// DELETE /api/robotics/webhooks/{webhook_id}
+pub async fn delete_webhook(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let deleted = sqlx::query("DELETE FROM device_webhooks WHERE id = $1 AND user_id = $2")
+        .bind(path.into_inner())
+        .bind(user.user_id)
+        .execute(pool.get_ref().as_ref())
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Webhook not found".to_string()));
+    }
+
+    Ok(crate::errors::success_message("Webhook deleted"))
+}
+
+/// Current signing secret; it changes whenever the webhook key is rotated, and deliveries
+/// name the key version they were signed with
+/// GET /api/robotics/webhooks/{webhook_id}/secret
+pub async fn get_signing_secret(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    keys: web::Data<Arc<dyn KeyManager>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let webhook = owned_webhook(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    Ok(ApiResponse::success(secret_for(keys.get_ref().as_ref(), &webhook).await?))
+}
+
+/// Issue a new signing secret. Deliveries carry a signature under both the new and the previous
+/// secret for the grace period, so receivers can switch over without dropping events.
+/// POST /api/robotics/webhooks/{webhook_id}/secret/rotate
+pub async fn rotate_signing_secret(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    keys: web::Data<Arc<dyn KeyManager>>,
+    path: web::Path<Uuid>,
+    body: Option<web::Json<RotateSecretRequest>>,
+) -> ApiResult<HttpResponse> {
+    let webhook = owned_webhook(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let grace_hours = body.and_then(|b| b.grace_period_hours).unwrap_or(DEFAULT_GRACE_PERIOD_HOURS);
+    validate_grace_period(grace_hours)?;
+
+    let webhook = sqlx::query_as::<_, DeviceWebhook>(&format!(
+        "UPDATE device_webhooks SET previous_secret_version = secret_version, \
+         previous_secret_expires_at = NOW() + make_interval(hours => $2), secret_version = secret_version + 1, \
+         updated_at = NOW() WHERE id = $1 RETURNING {}",
+        WEBHOOK_COLUMNS
+    ))
+    .bind(webhook.id)
+    .bind(grace_hours as i32)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::success(secret_for(keys.get_ref().as_ref(), &webhook).await?))
+}
+
+/// Recent deliveries, newest first, with every attempt made for each including what was sent
+/// and received. Filter by `status` or `event`; page back with `before`.
+/// GET /api/robotics/webhooks/{webhook_id}/deliveries?status=failed&limit=50
+pub async fn list_deliveries(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    query: web::Query<DeliveryListQuery>,
+) -> ApiResult<HttpResponse> {
+    let webhook = owned_webhook(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    if let Some(status) = query.status.as_deref()
+        && !DELIVERY_STATUSES.contains(&status)
+    {
+        return Err(ApiError::ValidationError(format!(
+            "status must be one of {}",
+            DELIVERY_STATUSES.join(", ")
+        )));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_DELIVERY_PAGE).clamp(1, MAX_DELIVERY_PAGE);
+
+    let deliveries = sqlx::query_as::<_, WebhookDelivery>(&format!(
+        "SELECT {} FROM webhook_deliveries WHERE webhook_id = $1 \
+         AND ($2::text IS NULL OR status = $2) AND ($3::text IS NULL OR event = $3) \
+         AND ($4::timestamptz IS NULL OR created_at < $4) \
+         ORDER BY created_at DESC LIMIT $5",
+        DELIVERY_COLUMNS
+    ))
+    .bind(webhook.id)
+    .bind(&query.status)
+    .bind(&query.event)
+    .bind(query.before)
+    .bind(limit)
+    .fetch_all(pool.get_ref().as_ref())
+    .await?;
+    let ids: Vec<Uuid> = deliveries.iter().map(|d| d.id).collect();
+    let attempts = delivery_attempts(pool.get_ref(), &ids).await?;
+
+    let deliveries: Vec<serde_json::Value> = deliveries
+        .into_iter()
+        .map(|delivery| {
+            let log: Vec<&WebhookDeliveryAttempt> = attempts.iter().filter(|a| a.delivery_id == delivery.id).collect();
+            serde_json::json!({ "delivery": delivery, "attempts": log })
+        })
+        .collect();
+
+    Ok(ApiResponse::success(deliveries))
+}
+
+/// One delivery with its full attempt log
+/// GET /api/robotics/webhooks/{webhook_id}/deliveries/{delivery_id}
+pub async fn get_delivery(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (webhook_id, delivery_id) = path.into_inner();
+    let webhook = owned_webhook(pool.get_ref(), webhook_id, user.user_id).await?;
+
+    let delivery = sqlx::query_as::<_, WebhookDelivery>(&format!(
+        "SELECT {} FROM webhook_deliveries WHERE id = $1 AND webhook_id = $2",
+        DELIVERY_COLUMNS
+    ))
+    .bind(delivery_id)
+    .bind(webhook.id)
+    .fetch_optional(pool.get_ref().as_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Delivery not found".to_string()))?;
+    let attempts = delivery_attempts(pool.get_ref(), &[delivery.id]).await?;
+
+    Ok(ApiResponse::success(serde_json::json!({ "delivery": delivery, "attempts": attempts })))
+}
+
+/// Delivery counts by status, success rate and receiver latency over the last `hours`
+/// GET /api/robotics/webhooks/{webhook_id}/deliveries/summary?hours=24
+pub async fn get_delivery_summary(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+    query: web::Query<DeliverySummaryQuery>,
+) -> ApiResult<HttpResponse> {
+    let webhook = owned_webhook(pool.get_ref(), path.into_inner(), user.user_id).await?;
+    let hours = query.hours.unwrap_or(24);
+    if !(1..=MAX_SUMMARY_HOURS).contains(&hours) {
+        return Err(ApiError::ValidationError(format!("hours must be between 1 and {}", MAX_SUMMARY_HOURS)));
+    }
+
+    let summary =
+        webhook_services::delivery_summary(pool.get_ref(), webhook.id, Utc::now() - Duration::hours(hours)).await?;
+
+    Ok(ApiResponse::success(summary))
+}
+
+/// Send a failed delivery again now and report how the receiver answered. The attempt is
+/// logged like any other; if it fails the delivery stays failed.
+/// POST /api/robotics/webhooks/{webhook_id}/deliveries/{delivery_id}/replay
+pub async fn replay_delivery(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    keys: web::Data<Arc<dyn KeyManager>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (webhook_id, delivery_id) = path.into_inner();
+    let webhook = owned_webhook(pool.get_ref(), webhook_id, user.user_id).await?;
+    if !webhook.enabled {
+        return Err(ApiError::BadRequest("Enable the webhook before replaying deliveries".to_string()));
+    }
+
+    let result =
+        webhook_services::replay_delivery(pool.get_ref(), keys.get_ref().as_ref(), webhook.id, delivery_id).await?;
+
+    Ok(ApiResponse::success(result))
+}
+
+/// Queue a failed delivery to be sent again with a fresh attempt budget
+/// POST /api/robotics/webhooks/{webhook_id}/deliveries/{delivery_id}/retry
+pub async fn retry_delivery(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> ApiResult<HttpResponse> {
+    let (webhook_id, delivery_id) = path.into_inner();
+    let webhook = owned_webhook(pool.get_ref(), webhook_id, user.user_id).await?;
+
+    let delivery = sqlx::query_as::<_, WebhookDelivery>(&format!(
+        "UPDATE webhook_deliveries SET status = 'pending', attempts = 0, next_attempt_at = NOW() \
+         WHERE id = $1 AND webhook_id = $2 AND status = 'failed' RETURNING {}",
+        DELIVERY_COLUMNS
+    ))
+    .bind(delivery_id)
+    .bind(webhook.id)
+    .fetch_optional(pool.get_ref().as_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("No failed delivery with that id".to_string()))?;
+
+    Ok(ApiResponse::success(delivery))
+}
+
+/// Queue a test event to check the receiver end to end
+/// POST /api/robotics/webhooks/{webhook_id}/test
+pub async fn test_webhook(
+    user: AuthenticatedUser,
+    pool: web::Data<Arc<PgPool>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let webhook = owned_webhook(pool.get_ref(), path.into_inner(), user.user_id).await?;
+
+    let delivery = sqlx::query_as::<_, WebhookDelivery>(&format!(
+        "INSERT INTO webhook_deliveries (webhook_id, event, payload) \
+         VALUES ($1, 'webhook.test', jsonb_build_object('id', gen_random_uuid(), 'event', 'webhook.test', \
+                 'occurred_at', NOW(), 'data', jsonb_build_object('webhook_id', $1::uuid))) RETURNING {}",
+        DELIVERY_COLUMNS
+    ))
+    .bind(webhook.id)
+    .fetch_one(pool.get_ref().as_ref())
+    .await?;
+
+    Ok(ApiResponse::created(delivery))
+}
@@ -28,10 +28,18 @@ pub enum ApiError {
     BlockchainError(String),
     AIServiceError(String),
     
+    // Client errors
+    /// The calling app is older than the minimum version supported for its platform
+    UpgradeRequired { platform: String, current_version: String, minimum_version: String },
+    /// Cross-device constraints that must hold before the command may run
+    ConstraintBlocked(Vec<crate::models::constraint::ConstraintViolation>),
+
     // General errors
     InternalError(String),
     RateLimited,
     ServiceUnavailable(String),
+    /// A subsystem the request needs is down or not configured (limited mode)
+    CapabilityUnavailable { capability: &'static str, reason: String },
 }
 
 impl fmt::Display for ApiError {
@@ -51,9 +59,26 @@ impl fmt::Display for ApiError {
             ApiError::PaymentError(msg) => write!(f, "Payment error: {}", msg),
             ApiError::BlockchainError(msg) => write!(f, "Blockchain error: {}", msg),
             ApiError::AIServiceError(msg) => write!(f, "AI service error: {}", msg),
+            ApiError::UpgradeRequired { platform, current_version, minimum_version } => write!(
+                f,
+                "Upgrade required: {} app {} is no longer supported, update to {} or later",
+                platform, current_version, minimum_version
+            ),
+            ApiError::ConstraintBlocked(violations) => write!(
+                f,
+                "Command blocked by constraint {}",
+                violations
+                    .iter()
+                    .map(|v| format!("'{}' ({})", v.name, v.describe()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
             ApiError::InternalError(msg) => write!(f, "Internal error: {}", msg),
             ApiError::RateLimited => write!(f, "Rate limit exceeded"),
             ApiError::ServiceUnavailable(msg) => write!(f, "Service unavailable: {}", msg),
+            ApiError::CapabilityUnavailable { capability, reason } => {
+                write!(f, "Service unavailable: {} is unavailable ({})", capability, reason)
+            }
         }
     }
 }
@@ -75,18 +100,37 @@ impl ResponseError for ApiError {
             ApiError::PaymentError(_) => (actix_web::http::StatusCode::PAYMENT_REQUIRED, "payment_error"),
             ApiError::BlockchainError(_) => (actix_web::http::StatusCode::BAD_GATEWAY, "blockchain_error"),
             ApiError::AIServiceError(_) => (actix_web::http::StatusCode::BAD_GATEWAY, "ai_service_error"),
+            ApiError::UpgradeRequired { .. } => (actix_web::http::StatusCode::UPGRADE_REQUIRED, "upgrade_required"),
+            ApiError::ConstraintBlocked(_) => (actix_web::http::StatusCode::CONFLICT, "constraint_blocked"),
             ApiError::InternalError(_) => (actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, "internal_error"),
             ApiError::RateLimited => (actix_web::http::StatusCode::TOO_MANY_REQUESTS, "rate_limited"),
             ApiError::ServiceUnavailable(_) => (actix_web::http::StatusCode::SERVICE_UNAVAILABLE, "service_unavailable"),
+            ApiError::CapabilityUnavailable { .. } => {
+                (actix_web::http::StatusCode::SERVICE_UNAVAILABLE, "capability_unavailable")
+            }
         };
 
-        HttpResponse::build(status).json(serde_json::json!({
+        let mut body = serde_json::json!({
             "error": {
                 "type": error_type,
                 "message": self.to_string()
             },
             "success": false
-        }))
+        });
+        // Machine-readable details so apps can show an update prompt
+        if let ApiError::UpgradeRequired { platform, current_version, minimum_version } = self {
+            body["error"]["platform"] = serde_json::json!(platform);
+            body["error"]["current_version"] = serde_json::json!(current_version);
+            body["error"]["minimum_version"] = serde_json::json!(minimum_version);
+        }
+        if let ApiError::ConstraintBlocked(violations) = self {
+            body["error"]["constraints"] = serde_json::json!(violations);
+        }
+        if let ApiError::CapabilityUnavailable { capability, .. } = self {
+            body["error"]["capability"] = serde_json::json!(capability);
+        }
+
+        HttpResponse::build(status).json(body)
     }
 }
 
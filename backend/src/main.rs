@@ -10,6 +10,7 @@ mod middleware;
 use actix_web::{web, App, HttpServer, middleware as actix_middleware, HttpResponse};
 use actix_cors::Cors;
 use actix_governor::{Governor, GovernorConfigBuilder};
+use secrecy::ExposeSecret;
 use sqlx::PgPool;
 use std::sync::Arc;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -43,6 +44,76 @@ async fn main() -> std::io::Result<()> {
         }
     };
     
+    // Centralized signing/encryption keys, rotated in the background
+    let key_manager: Option<Arc<services::key_services::PgKeyManager>> = match &pool {
+        Some(p) => {
+            let manager = services::key_services::PgKeyManager::new(
+                p.clone(),
+                config.key_encryption_key.as_ref().map(|k| k.expose_secret()),
+                config.jwt_secret.expose_secret(),
+            )
+            .expect("Invalid key management configuration");
+            match manager.bootstrap(config.jwt_secret.expose_secret()).await {
+                Ok(()) => {
+                    let manager = Arc::new(manager);
+                    services::key_services::spawn_rotation_job(manager.clone());
+                    services::webhook_services::spawn_delivery_job(p.clone(), manager.clone());
+                    services::google_calendar_services::spawn_calendar_sync_job(
+                        p.clone(),
+                        config.clone(),
+                        manager.clone(),
+                    );
+                    Some(manager)
+                }
+                Err(e) => {
+                    tracing::warn!("⚠️ Key manager unavailable: {}", e);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    // Command transports (MQTT only when a broker is configured)
+    let transports = Arc::new(
+        services::transport_services::TransportRegistry::from_config(&config)
+            .expect("Invalid device transport configuration"),
+    );
+    if let Some(p) = &pool {
+        services::automation_services::spawn_automation_job(p.clone(), transports.clone());
+        services::metric_services::spawn_metric_job(p.clone());
+        services::integration_services::spawn_hook_delivery_job(p.clone());
+        services::subscription_services::spawn_renewal_job(p.clone(), config.clone());
+        services::chain_watch_services::spawn_confirmation_watcher(p.clone(), config.clone());
+        services::invoice_services::spawn_invoice_watcher(p.clone(), config.clone());
+        services::escrow_services::spawn_auto_release_job(p.clone(), config.clone());
+        services::payout_services::spawn_stripe_reconcile_job(p.clone(), config.clone());
+        services::event_indexer_services::spawn_indexer(p.clone(), config.clone());
+        services::support_services::spawn_sla_job(p.clone());
+        services::retention_services::spawn_retention_job(
+            p.clone(),
+            services::retention_services::RetentionPolicy::from_config(&config),
+        );
+    }
+    // Which subsystems are usable; routes needing a missing one answer 503 (limited mode)
+    let capabilities =
+        Arc::new(services::capability_services::CapabilityRegistry::from_config(&config, pool.is_some()));
+    services::capability_services::spawn_probe_job(
+        capabilities.clone(),
+        pool.clone(),
+        transports.clone(),
+        config.clone(),
+    );
+    // Per-consumer usage of deprecated routes, counted in memory and flushed periodically
+    let deprecations = Arc::new(services::deprecation_services::DeprecationTracker::new());
+    if let Some(p) = &pool {
+        services::deprecation_services::spawn_usage_flush_job(p.clone(), deprecations.clone());
+    }
+    // Sandbox for user-uploaded telemetry processors
+    let processors = Arc::new(services::processor_services::ProcessorRuntime::new());
+    // AI providers configured in the environment
+    let ai = Arc::new(services::ai_services::AIService::new());
+
     // Rate limiter: 100 requests per minute per IP
     let governor_conf = GovernorConfigBuilder::default()
         .per_second(1)
@@ -54,7 +125,7 @@ async fn main() -> std::io::Result<()> {
     let port = config.port;
 
     tracing::info!("🚀 Server starting on {}:{}", host, port);
-    tracing::info!("📚 API documentation available at http://{}:{}/api/health", host, port);
+    tracing::info!("📚 API documentation available at http://{}:{}/api/version", host, port);
 
     HttpServer::new(move || {
         // Configure CORS
@@ -71,6 +142,11 @@ async fn main() -> std::io::Result<()> {
         
         let mut app = App::new()
             .app_data(web::Data::new(config.clone()))
+            .app_data(web::Data::new(transports.clone()))
+            .app_data(web::Data::new(processors.clone()))
+            .app_data(web::Data::new(deprecations.clone()))
+            .app_data(web::Data::new(capabilities.clone()))
+            .app_data(web::Data::new(ai.clone()))
             .app_data(web::JsonConfig::default()
                 .limit(4096 * 1024) // 4MB max JSON payload
                 .error_handler(|err, _req| {
@@ -82,6 +158,15 @@ async fn main() -> std::io::Result<()> {
                         }))
                     ).into()
                 }))
+            // Answers 426 to apps older than the configured minimum for their platform
+            .wrap(actix_middleware::from_fn(middleware::client_version_gate))
+            // Refuses registration/payment from sanctioned regions
+            .wrap(actix_middleware::from_fn(middleware::geo_block))
+            // Audits and re-validates every request made under a break-glass session
+            .wrap(actix_middleware::from_fn(middleware::break_glass_audit))
+            .wrap(actix_middleware::from_fn(middleware::session_guard))
+            // Answers 503 naming the capability when a route needs a subsystem that is down
+            .wrap(actix_middleware::from_fn(middleware::capability_gate))
             .wrap(cors)
             .wrap(actix_middleware::Logger::new("%a \"%r\" %s %b \"%{Referer}i\" \"%{User-Agent}i\" %T"))
             .wrap(Governor::new(&governor_conf))
@@ -96,13 +181,23 @@ async fn main() -> std::io::Result<()> {
             )
             // Health check endpoints
             .route("/health", web::get().to(health_check))
-            .route("/api/health", web::get().to(health_check))
+            .route(
+                "/api/health",
+                middleware::deprecated(
+                    web::get().to(health_check),
+                    &services::deprecation_services::API_HEALTH_ALIAS,
+                ),
+            )
             .route("/api/version", web::get().to(version_info));
         
         // Add database pool if available
         if let Some(ref p) = pool {
             app = app.app_data(web::Data::new(p.clone()));
         }
+        if let Some(ref keys) = key_manager {
+            let keys: Arc<dyn services::key_services::KeyManager> = keys.clone();
+            app = app.app_data(web::Data::new(keys));
+        }
         
         // Configure API routes
         app.configure(routes::auth::configure)
@@ -110,6 +205,23 @@ async fn main() -> std::io::Result<()> {
             .configure(routes::robotics::configure)
             .configure(routes::blockchain::configure)
             .configure(routes::dashboard::configure)
+            .configure(routes::orgs::configure)
+            .configure(routes::sso::configure)
+            .configure(routes::scim::configure)
+            .configure(routes::notifications::configure)
+            .configure(routes::admin::configure)
+            .configure(routes::compliance::configure)
+            .configure(routes::automations::configure)
+            .configure(routes::templates::configure)
+            .configure(routes::inbound::configure)
+            .configure(routes::calendar::configure)
+            .configure(routes::integrations::configure)
+            .configure(routes::billing::configure)
+            .configure(routes::open_data::configure)
+            .configure(routes::support::configure)
+            .configure(routes::escrows::configure)
+            .configure(routes::payouts::configure)
+            .configure(routes::ledger::configure)
             // 404 handler
             .default_service(web::route().to(not_found))
     })
@@ -119,13 +231,16 @@ async fn main() -> std::io::Result<()> {
     .await
 }
 
-/// Health check endpoint
-async fn health_check() -> HttpResponse {
+/// Health check endpoint; `degraded` while a configured capability is unavailable
+async fn health_check(
+    capabilities: web::Data<Arc<services::capability_services::CapabilityRegistry>>,
+) -> HttpResponse {
     HttpResponse::Ok().json(serde_json::json!({
-        "status": "ok",
+        "status": if capabilities.is_degraded() { "degraded" } else { "ok" },
         "service": "RoboVeda API",
         "version": env!("CARGO_PKG_VERSION"),
-        "timestamp": chrono::Utc::now().to_rfc3339()
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "capabilities": capabilities.snapshot()
     }))
 }
 
@@ -141,7 +256,15 @@ async fn version_info() -> HttpResponse {
             "ai": "/api/ai",
             "robotics": "/api/robotics",
             "blockchain": "/api/blockchain",
-            "dashboard": "/api/dashboard"
+            "dashboard": "/api/dashboard",
+            "orgs": "/api/orgs",
+            "sso": "/api/sso",
+            "scim": "/scim/v2",
+            "notifications": "/api/notifications",
+            "admin": "/api/admin",
+            "compliance": "/api/compliance",
+            "automations": "/api/automations",
+            "templates": "/api/templates"
         }
     }))
 }
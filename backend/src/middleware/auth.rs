@@ -134,6 +134,9 @@ mod tests {
             exp: 0,
             iat: 0,
             role: None,
+            auth_method: None,
+            session_id: None,
+            sso_org_id: None,
         };
         let user = AuthenticatedUser {
             user_id: Uuid::new_v4(),
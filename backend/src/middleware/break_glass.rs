@@ -0,0 +1,77 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::errors::ApiError;
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::break_glass_services::active_session_org;
+use crate::utils::extract_claims_from_request;
+
+/// Enhanced audit for break-glass sessions.
+/// Every request made with a break-glass token is checked against the live session
+/// (so revocation takes effect immediately) and written to the audit log before it runs;
+/// the response status is attached once it completes.
+pub async fn break_glass_audit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let claims = match extract_claims_from_request(req.request()) {
+        Some(claims) if claims.auth_method.as_deref() == Some("break_glass") => claims,
+        _ => return next.call(req).await,
+    };
+
+    let pool = req
+        .app_data::<web::Data<Arc<PgPool>>>()
+        .cloned()
+        .ok_or_else(|| ApiError::ServiceUnavailable("Database not available".to_string()))?;
+    let session_id = claims
+        .session_id
+        .ok_or_else(|| ApiError::InvalidToken("Break-glass token has no session".to_string()))?;
+    let org_id = active_session_org(&pool, session_id)
+        .await?
+        .ok_or_else(|| ApiError::Unauthorized("Break-glass session has expired or was revoked".to_string()))?;
+
+    let actor_id = uuid::Uuid::parse_str(&claims.sub).ok();
+    let method = req.method().to_string();
+    let path = req.path().to_string();
+    let ip = req.connection_info().realip_remote_addr().map(String::from);
+
+    // Fail closed: nothing runs under break-glass unless it has been recorded
+    let mut conn = pool.acquire().await.map_err(ApiError::from)?;
+    let audit_id = audit_services::record(
+        &mut conn,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id,
+            action: "break_glass.request",
+            resource_type: "break_glass_session",
+            resource_id: Some(session_id.to_string()),
+            details: serde_json::json!({
+                "method": method,
+                "path": path,
+                "query": req.query_string(),
+                "ip": ip,
+            }),
+        },
+    )
+    .await?;
+    drop(conn);
+
+    let res = next.call(req).await?;
+
+    let status = res.status().as_u16();
+    if let Err(e) = sqlx::query(
+        "UPDATE audit_logs SET details = details || jsonb_build_object('status', $2::int) WHERE id = $1",
+    )
+    .bind(audit_id)
+    .bind(status as i32)
+    .execute(pool.get_ref().as_ref())
+    .await
+    {
+        tracing::error!(%session_id, "Failed to record break-glass response status: {}", e);
+    }
+
+    Ok(res)
+}
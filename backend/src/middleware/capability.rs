@@ -0,0 +1,18 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use std::sync::Arc;
+use crate::services::capability_services::{required_capabilities, CapabilityRegistry};
+
+/// Answers 503 with the missing capability's name when a route needs a subsystem this
+/// instance cannot currently use, e.g. any database-backed route while running without one
+pub async fn capability_gate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if let Some(registry) = req.app_data::<web::Data<Arc<CapabilityRegistry>>>() {
+        registry.require(required_capabilities(req.path()))?;
+    }
+    next.call(req).await
+}
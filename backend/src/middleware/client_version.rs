@@ -0,0 +1,88 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use std::collections::HashMap;
+use crate::config::AppConfig;
+use crate::errors::ApiError;
+use crate::services::firmware_services::compare_versions;
+
+pub const CLIENT_VERSION_HEADER: &str = "X-Client-Version";
+
+/// Reachable from any app version, so outdated clients can still discover what they need
+const UNGATED_PATHS: &[&str] = &["/health", "/api/health", "/api/version"];
+
+/// `<platform>/<version>`, e.g. `ios/2.4.0`; the platform is lower-cased
+pub fn parse_client_version(header: &str) -> Option<(String, &str)> {
+    let (platform, version) = header.trim().split_once('/')?;
+    let version = version.trim();
+    let numeric = version.trim_start_matches(['v', 'V']).starts_with(|c: char| c.is_ascii_digit());
+    (!platform.trim().is_empty() && numeric).then(|| (platform.trim().to_lowercase(), version))
+}
+
+/// Refuse clients older than the configured minimum for their platform. Requests without
+/// the header (devices, integrations) and platforms without a minimum are not gated.
+pub fn check_client_version(minimums: &HashMap<String, String>, header: Option<&str>) -> Result<(), ApiError> {
+    let Some((platform, version)) = header.and_then(parse_client_version) else {
+        return Ok(());
+    };
+    match minimums.get(&platform) {
+        Some(minimum) if compare_versions(version, minimum).is_lt() => Err(ApiError::UpgradeRequired {
+            platform,
+            current_version: version.to_string(),
+            minimum_version: minimum.clone(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Answers 426 Upgrade Required to apps below the minimum version for their platform, so
+/// breaking protocol changes (e.g. the realtime command socket) can roll out safely
+pub async fn client_version_gate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if UNGATED_PATHS.contains(&req.path()) {
+        return next.call(req).await;
+    }
+    if let Some(config) = req.app_data::<web::Data<AppConfig>>()
+        && !config.min_client_versions.is_empty()
+    {
+        let header = req.headers().get(CLIENT_VERSION_HEADER).and_then(|v| v.to_str().ok());
+        check_client_version(&config.min_client_versions, header)?;
+    }
+    next.call(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_client_version() {
+        assert_eq!(parse_client_version("iOS/2.4.0"), Some(("ios".to_string(), "2.4.0")));
+        assert_eq!(parse_client_version(" android / v3.0 "), Some(("android".to_string(), "v3.0")));
+        assert_eq!(parse_client_version("2.4.0"), None);
+        assert_eq!(parse_client_version("/2.4.0"), None);
+        assert_eq!(parse_client_version("ios/latest"), None);
+    }
+
+    #[test]
+    fn test_check_client_version() {
+        let minimums = HashMap::from([("ios".to_string(), "2.4.0".to_string())]);
+
+        assert!(check_client_version(&minimums, Some("ios/2.4.0")).is_ok());
+        assert!(check_client_version(&minimums, Some("ios/2.10.1")).is_ok());
+        assert!(check_client_version(&minimums, Some("android/1.0.0")).is_ok());
+        assert!(check_client_version(&minimums, None).is_ok());
+
+        match check_client_version(&minimums, Some("ios/2.3.9")) {
+            Err(ApiError::UpgradeRequired { platform, current_version, minimum_version }) => {
+                assert_eq!(platform, "ios");
+                assert_eq!(current_version, "2.3.9");
+                assert_eq!(minimum_version, "2.4.0");
+            }
+            other => panic!("expected upgrade required, got {:?}", other),
+        }
+    }
+}
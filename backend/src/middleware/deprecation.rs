@@ -0,0 +1,38 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::{from_fn, Next};
+use actix_web::{web, Error, Route};
+use chrono::Utc;
+use std::sync::Arc;
+use crate::services::deprecation_services::{Deprecation, DeprecationTracker};
+use crate::utils::extract_claims_from_request;
+
+/// Mark a route deprecated in the route table:
+/// `.route("/old", deprecated(web::get().to(handler), &NOTICE))`
+pub fn deprecated(route: Route, notice: &'static Deprecation) -> Route {
+    route.wrap(from_fn(move |req, next| deprecation_notice(notice, req, next)))
+}
+
+/// Counts the call against its consumer and adds `Deprecation`/`Sunset`/`Link` headers to the
+/// response, errors included, so clients notice before the sunset date
+async fn deprecation_notice(
+    notice: &'static Deprecation,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if let Some(tracker) = req.app_data::<web::Data<Arc<DeprecationTracker>>>() {
+        let user_id = extract_claims_from_request(req.request()).and_then(|c| uuid::Uuid::parse_str(&c.sub).ok());
+        let user_agent = req.headers().get("User-Agent").and_then(|v| v.to_str().ok());
+        let ip = req.connection_info().realip_remote_addr().map(String::from);
+        tracker.record(notice, user_id, ip.as_deref(), user_agent, Utc::now());
+    }
+
+    let mut res = next.call(req).await?;
+    for (name, value) in notice.headers() {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            res.headers_mut().insert(HeaderName::from_static(name), value);
+        }
+    }
+    Ok(res)
+}
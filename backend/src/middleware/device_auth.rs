@@ -0,0 +1,60 @@
+use actix_web::{web, Error, FromRequest, HttpRequest};
+use actix_web::dev::Payload;
+use futures::future::LocalBoxFuture;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use zeroize::Zeroizing;
+use crate::errors::ApiError;
+use crate::utils::sha256_hash;
+
+/// A physical device authenticated by its API key
+/// (`Authorization: Device <key>` or `X-Device-Key: <key>`)
+#[derive(Debug, Clone)]
+pub struct AuthenticatedDevice {
+    pub device_id: Uuid,
+    pub user_id: Uuid,
+    pub device_type: String,
+}
+
+fn device_key(req: &HttpRequest) -> Option<Zeroizing<String>> {
+    let headers = req.headers();
+    if let Some(auth) = headers.get("Authorization").and_then(|v| v.to_str().ok())
+        && let Some(key) = auth.strip_prefix("Device ")
+    {
+        return Some(Zeroizing::new(key.trim().to_string()));
+    }
+    headers
+        .get("X-Device-Key")
+        .and_then(|v| v.to_str().ok())
+        .map(|k| Zeroizing::new(k.trim().to_string()))
+}
+
+impl FromRequest for AuthenticatedDevice {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let key = device_key(req);
+        let pool = req.app_data::<web::Data<Arc<PgPool>>>().cloned();
+
+        Box::pin(async move {
+            let key = key
+                .filter(|k| !k.is_empty())
+                .ok_or_else(|| ApiError::Unauthorized("Missing device key".to_string()))?;
+            let pool = pool.ok_or_else(|| ApiError::ServiceUnavailable("Database not available".to_string()))?;
+
+            let device: Option<(Uuid, Uuid, String)> =
+                sqlx::query_as("SELECT id, user_id, device_type FROM devices WHERE api_key_hash = $1")
+                    .bind(sha256_hash(key.as_bytes()))
+                    .fetch_optional(pool.get_ref().as_ref())
+                    .await
+                    .map_err(ApiError::from)?;
+
+            let (device_id, user_id, device_type) =
+                device.ok_or_else(|| ApiError::Unauthorized("Invalid device key".to_string()))?;
+
+            Ok(AuthenticatedDevice { device_id, user_id, device_type })
+        })
+    }
+}
@@ -0,0 +1,85 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::config::AppConfig;
+use crate::errors::ApiError;
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::compliance_services::{
+    blocked_location, is_allowlisted, is_restricted_endpoint, GEO_BLOCKED_ACTION,
+};
+use crate::services::security_services::edge_country;
+use crate::utils::{extract_claims_from_request, log_security_event};
+
+/// Refuses registration and payment requests from blocked countries/regions (per the edge
+/// proxy's geo-IP headers) unless an approved allowlist override covers the IP or user.
+/// Every refusal is audited; its audit id is the reference for requesting a review.
+pub async fn geo_block(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if !is_restricted_endpoint(req.method().as_str(), req.path()) {
+        return next.call(req).await;
+    }
+    let Some(config) = req.app_data::<web::Data<AppConfig>>().cloned() else {
+        return next.call(req).await;
+    };
+
+    let country = edge_country(req.request());
+    let region = req
+        .headers()
+        .get("CF-Region-Code")
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+    let Some(location) = blocked_location(
+        &config.blocked_countries,
+        &config.blocked_regions,
+        country.as_deref(),
+        region.as_deref(),
+    ) else {
+        return next.call(req).await;
+    };
+
+    // Fail closed: a blocked location is only let through on a verified override
+    let pool = req
+        .app_data::<web::Data<Arc<PgPool>>>()
+        .cloned()
+        .ok_or_else(|| ApiError::ServiceUnavailable("Database not available".to_string()))?;
+    let ip = req.connection_info().realip_remote_addr().map(String::from);
+    let user_id = extract_claims_from_request(req.request()).and_then(|c| uuid::Uuid::parse_str(&c.sub).ok());
+
+    if is_allowlisted(&pool, ip.as_deref(), user_id).await? {
+        tracing::info!(location = %location, path = %req.path(), "Geo-block bypassed by allowlist override");
+        return next.call(req).await;
+    }
+
+    let endpoint = format!("{} {}", req.method(), req.path());
+    let mut conn = pool.acquire().await.map_err(ApiError::from)?;
+    let reference = audit_services::record(
+        &mut conn,
+        AuditEntry {
+            org_id: None,
+            actor_id: user_id,
+            action: GEO_BLOCKED_ACTION,
+            resource_type: "endpoint",
+            resource_id: Some(endpoint.clone()),
+            details: serde_json::json!({
+                "location": location,
+                "country": country,
+                "region": region,
+                "ip": ip,
+            }),
+        },
+    )
+    .await?;
+    log_security_event("geo_blocked", ip.as_deref(), &format!("{} from {}", endpoint, location));
+
+    Err(ApiError::Forbidden(format!(
+        "This service is not available in your region (reference {}). \
+         If you believe this is a mistake, request a review at /api/compliance/appeals",
+        reference
+    ))
+    .into())
+}
@@ -0,0 +1,203 @@
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+use sqlx::{FromRow, PgPool};
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::ApiError;
+use crate::utils::{extract_claims_from_request, sha256_hash};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+/// Set on responses replayed from an earlier request with the same key
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+/// How long a key's response is kept for replay
+const KEY_TTL_HOURS: i64 = 24;
+/// A request holding a key this long without finishing is presumed lost and may be retried
+const IN_FLIGHT_TIMEOUT_SECS: i64 = 300;
+
+/// 1-255 visible ASCII characters, e.g. a UUID
+pub fn validate_key(key: &str) -> Result<(), ApiError> {
+    if key.is_empty() || key.len() > 255 || !key.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err(ApiError::ValidationError(format!(
+            "{} must be 1-255 visible ASCII characters",
+            IDEMPOTENCY_KEY_HEADER
+        )));
+    }
+    Ok(())
+}
+
+/// What a key was first used with; reusing it for another request is refused
+pub fn request_fingerprint(method: &str, path: &str, body: &[u8]) -> String {
+    let mut data = Vec::with_capacity(method.len() + path.len() + body.len() + 2);
+    data.extend_from_slice(method.as_bytes());
+    data.push(b'\n');
+    data.extend_from_slice(path.as_bytes());
+    data.push(b'\n');
+    data.extend_from_slice(body);
+    sha256_hash(&data)
+}
+
+#[derive(FromRow)]
+struct StoredKey {
+    request_hash: String,
+    response_status: Option<i32>,
+    response_body: Option<Vec<u8>>,
+    content_type: Option<String>,
+}
+
+/// Makes retries of a request carrying an `Idempotency-Key` header safe: the first request
+/// runs and its response is stored per user and key; retries of the same request get that
+/// response back instead of running again. Server errors are not stored, so the request can
+/// be retried. Requests without the header, or without a user, pass through untouched.
+pub async fn idempotency(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let Some(key) = req.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    let key = key
+        .to_str()
+        .map_err(|_| ApiError::ValidationError(format!("Invalid {} header", IDEMPOTENCY_KEY_HEADER)))?
+        .to_string();
+    validate_key(&key)?;
+    // Anonymous requests are left for the handler to reject
+    let Some(user_id) = extract_claims_from_request(req.request()).and_then(|c| Uuid::parse_str(&c.sub).ok()) else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    let pool = req
+        .app_data::<web::Data<Arc<PgPool>>>()
+        .cloned()
+        .ok_or_else(|| ApiError::ServiceUnavailable("Database not available".to_string()))?;
+
+    // Read the body to fingerprint it, then hand it back for the handler
+    let body = req.extract::<web::Bytes>().await?;
+    let fingerprint = request_fingerprint(req.method().as_str(), req.path(), &body);
+    req.set_payload(Payload::from(body));
+
+    sqlx::query("DELETE FROM idempotency_keys WHERE user_id = $1 AND expires_at <= NOW()")
+        .bind(user_id)
+        .execute(pool.get_ref().as_ref())
+        .await
+        .map_err(ApiError::from)?;
+    let claimed = sqlx::query(
+        "INSERT INTO idempotency_keys (user_id, idempotency_key, request_hash, expires_at) \
+         VALUES ($1, $2, $3, NOW() + make_interval(hours => $4)) \
+         ON CONFLICT (user_id, idempotency_key) DO UPDATE SET created_at = NOW() \
+         WHERE idempotency_keys.response_status IS NULL AND idempotency_keys.request_hash = EXCLUDED.request_hash \
+         AND idempotency_keys.created_at < NOW() - make_interval(secs => $5)",
+    )
+    .bind(user_id)
+    .bind(&key)
+    .bind(&fingerprint)
+    .bind(KEY_TTL_HOURS as i32)
+    .bind(IN_FLIGHT_TIMEOUT_SECS as f64)
+    .execute(pool.get_ref().as_ref())
+    .await
+    .map_err(ApiError::from)?
+    .rows_affected()
+        == 1;
+
+    if !claimed {
+        let stored = sqlx::query_as::<_, StoredKey>(
+            "SELECT request_hash, response_status, response_body, content_type FROM idempotency_keys \
+             WHERE user_id = $1 AND idempotency_key = $2",
+        )
+        .bind(user_id)
+        .bind(&key)
+        .fetch_optional(pool.get_ref().as_ref())
+        .await
+        .map_err(ApiError::from)?;
+        let stored = match stored {
+            Some(stored) if stored.request_hash != fingerprint => {
+                return Err(ApiError::ValidationError(format!(
+                    "{} was already used for a different request",
+                    IDEMPOTENCY_KEY_HEADER
+                ))
+                .into());
+            }
+            Some(StoredKey { response_status: Some(status), response_body, content_type, .. }) => {
+                (status, response_body, content_type)
+            }
+            _ => {
+                return Err(ApiError::Conflict(format!(
+                    "A request with this {} is still in progress",
+                    IDEMPOTENCY_KEY_HEADER
+                ))
+                .into());
+            }
+        };
+
+        let (status, body, content_type) = stored;
+        let mut response = HttpResponse::build(StatusCode::from_u16(status as u16).unwrap_or(StatusCode::OK));
+        if let Some(content_type) = content_type {
+            response.content_type(content_type);
+        }
+        let response = response.insert_header((IDEMPOTENT_REPLAYED_HEADER, "true")).body(body.unwrap_or_default());
+        return Ok(req.into_response(response));
+    }
+
+    let res = next.call(req).await?;
+    let status = res.status();
+    let content_type = res
+        .headers()
+        .get(actix_web::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let body = actix_web::body::to_bytes(body)
+        .await
+        .map_err(|_| ApiError::InternalError("Failed to read response body".to_string()))?;
+
+    let stored = if status.is_server_error() {
+        sqlx::query("DELETE FROM idempotency_keys WHERE user_id = $1 AND idempotency_key = $2")
+            .bind(user_id)
+            .bind(&key)
+            .execute(pool.get_ref().as_ref())
+            .await
+    } else {
+        sqlx::query(
+            "UPDATE idempotency_keys SET response_status = $3, response_body = $4, content_type = $5 \
+             WHERE user_id = $1 AND idempotency_key = $2",
+        )
+        .bind(user_id)
+        .bind(&key)
+        .bind(status.as_u16() as i32)
+        .bind(body.as_ref())
+        .bind(&content_type)
+        .execute(pool.get_ref().as_ref())
+        .await
+    };
+    if let Err(e) = stored {
+        tracing::error!(%user_id, "Failed to store idempotent response: {}", e);
+    }
+
+    Ok(ServiceResponse::new(req, res.set_body(body).map_into_boxed_body()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_key() {
+        assert!(validate_key("3f1c9a52-6b1e-4a8e-9c59-2f4d1e0b7a11").is_ok());
+        assert!(validate_key("").is_err());
+        assert!(validate_key("has space").is_err());
+        assert!(validate_key("ключ").is_err());
+        assert!(validate_key(&"k".repeat(256)).is_err());
+    }
+
+    #[test]
+    fn test_request_fingerprint() {
+        let body = br#"{"amount":1.6}"#;
+        let fingerprint = request_fingerprint("POST", "/api/blockchain/payment", body);
+        assert_eq!(fingerprint, request_fingerprint("POST", "/api/blockchain/payment", body));
+        assert_ne!(fingerprint, request_fingerprint("POST", "/api/blockchain/payment", br#"{"amount":3.2}"#));
+        assert_ne!(fingerprint, request_fingerprint("POST", "/api/blockchain/razorpay/orders", body));
+    }
+}
@@ -0,0 +1,49 @@
+use actix_web::{web, Error, FromRequest, HttpRequest};
+use actix_web::dev::Payload;
+use futures::future::LocalBoxFuture;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use zeroize::Zeroizing;
+use crate::errors::ApiError;
+use crate::utils::sha256_hash;
+
+/// A user acting through a no-code integration, authenticated by an integration token
+/// (`X-API-Key: <token>`)
+#[derive(Debug, Clone)]
+pub struct IntegrationUser {
+    pub user_id: Uuid,
+    pub token_id: Uuid,
+}
+
+impl FromRequest for IntegrationUser {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let token = req
+            .headers()
+            .get("X-API-Key")
+            .and_then(|v| v.to_str().ok())
+            .map(|t| Zeroizing::new(t.trim().to_string()));
+        let pool = req.app_data::<web::Data<Arc<PgPool>>>().cloned();
+
+        Box::pin(async move {
+            let token = token
+                .filter(|t| !t.is_empty())
+                .ok_or_else(|| ApiError::Unauthorized("Missing API key".to_string()))?;
+            let pool = pool.ok_or_else(|| ApiError::ServiceUnavailable("Database not available".to_string()))?;
+
+            let found: Option<(Uuid, Uuid)> = sqlx::query_as(
+                "UPDATE integration_tokens SET last_used_at = NOW() WHERE token_hash = $1 RETURNING id, user_id",
+            )
+            .bind(sha256_hash(token.as_bytes()))
+            .fetch_optional(pool.get_ref().as_ref())
+            .await
+            .map_err(ApiError::from)?;
+
+            let (token_id, user_id) = found.ok_or_else(|| ApiError::Unauthorized("Invalid API key".to_string()))?;
+            Ok(IntegrationUser { user_id, token_id })
+        })
+    }
+}
@@ -1,3 +1,21 @@
 pub mod auth;
+pub mod break_glass;
+pub mod capability;
+pub mod client_version;
+pub mod deprecation;
+pub mod device_auth;
+pub mod geo_block;
+pub mod idempotency;
+pub mod integration_auth;
+pub mod session_guard;
 
 pub use auth::{AuthenticatedUser, OptionalUser, AdminUser};
+pub use break_glass::break_glass_audit;
+pub use capability::capability_gate;
+pub use client_version::client_version_gate;
+pub use deprecation::deprecated;
+pub use device_auth::AuthenticatedDevice;
+pub use geo_block::geo_block;
+pub use idempotency::idempotency;
+pub use integration_auth::IntegrationUser;
+pub use session_guard::session_guard;
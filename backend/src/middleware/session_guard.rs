@@ -0,0 +1,41 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::errors::ApiError;
+use crate::utils::extract_claims_from_request;
+
+/// Rejects tokens whose server-side login session was revoked (e.g. by an org admin
+/// reviewing risky sessions). Break-glass tokens are validated by `break_glass_audit`.
+pub async fn session_guard(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let session_id = match extract_claims_from_request(req.request()) {
+        Some(claims) if claims.auth_method.as_deref() != Some("break_glass") => claims.session_id,
+        _ => None,
+    };
+
+    if let Some(session_id) = session_id {
+        let pool = req
+            .app_data::<web::Data<Arc<PgPool>>>()
+            .cloned()
+            .ok_or_else(|| ApiError::ServiceUnavailable("Database not available".to_string()))?;
+
+        let active: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM user_sessions WHERE id = $1 AND status = 'active' AND expires_at > NOW())",
+        )
+        .bind(session_id)
+        .fetch_one(pool.get_ref().as_ref())
+        .await
+        .map_err(ApiError::from)?;
+
+        if !active {
+            return Err(ApiError::Unauthorized("Session has been revoked".to_string()).into());
+        }
+    }
+
+    next.call(req).await
+}
@@ -0,0 +1,41 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// A peripheral mounted on a device, such as a gripper or LiDAR unit
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct DeviceAttachment {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub attachment_type: String,
+    pub name: String,
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+    pub config: Json<serde_json::Value>,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAttachmentRequest {
+    pub attachment_type: String,
+    /// Defaults to the attachment type; unique per device
+    pub name: Option<String>,
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+    pub config: Option<serde_json::Value>,
+    pub enabled: Option<bool>,
+}
+
+/// Partial update; the attachment type is fixed once mounted
+#[derive(Debug, Deserialize)]
+pub struct UpdateAttachmentRequest {
+    pub name: Option<String>,
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+    pub config: Option<serde_json::Value>,
+    pub enabled: Option<bool>,
+}
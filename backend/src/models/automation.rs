@@ -0,0 +1,169 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// What starts a run
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Trigger {
+    /// A telemetry sample matching the condition; `device_id` narrows it to one device
+    Telemetry {
+        device_id: Option<Uuid>,
+        condition: Condition,
+        /// Minimum gap between runs while the condition keeps matching
+        #[serde(default)]
+        cooldown_minutes: Option<u32>,
+    },
+    Schedule { every_minutes: u32 },
+    /// A POST to the automation's inbound hook URL
+    Webhook,
+    PaymentCompleted {
+        product_type: Option<String>,
+        min_amount: Option<f64>,
+    },
+    /// An hourly org metric value; the condition sees `value`, `device_id` and `bucket_start`
+    Metric {
+        metric_id: Uuid,
+        device_id: Option<Uuid>,
+        condition: Condition,
+    },
+}
+
+impl Trigger {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Trigger::Telemetry { .. } => "telemetry",
+            Trigger::Schedule { .. } => "schedule",
+            Trigger::Webhook => "webhook",
+            Trigger::PaymentCompleted { .. } => "payment_completed",
+            Trigger::Metric { .. } => "metric",
+        }
+    }
+}
+
+/// `field` is a dotted path into the event, e.g. `battery_level` or `sensors.0.value`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Condition {
+    pub field: String,
+    pub op: String, // eq, ne, lt, lte, gt, gte, contains, exists
+    #[serde(default)]
+    pub value: serde_json::Value,
+}
+
+/// Text fields accept `{{path}}` placeholders filled from the event
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Action {
+    SendCommand {
+        device_id: Uuid,
+        command: String,
+        #[serde(default)]
+        parameters: serde_json::Value,
+    },
+    Notify { title: String, body: String },
+    CallWebhook {
+        url: String,
+        #[serde(default)]
+        body: Option<serde_json::Value>,
+    },
+    ToggleFeature { flag: String, enabled: bool },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NodeKind {
+    Condition { condition: Condition },
+    Action { action: Action },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuleNode {
+    pub id: String,
+    #[serde(flatten)]
+    pub kind: NodeKind,
+}
+
+/// `from` is a node id or `trigger`. Edges leaving a condition carry the branch they follow.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuleEdge {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub when: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuleGraph {
+    pub trigger: Trigger,
+    pub nodes: Vec<RuleNode>,
+    pub edges: Vec<RuleEdge>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct Automation {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub enabled: bool,
+    pub current_version: i32,
+    pub trigger_kind: String,
+    pub last_triggered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct AutomationVersion {
+    pub automation_id: Uuid,
+    pub version: i32,
+    pub graph: sqlx::types::Json<RuleGraph>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct AutomationRun {
+    pub id: Uuid,
+    pub automation_id: Uuid,
+    pub version: i32,
+    pub trigger_kind: String,
+    pub event: serde_json::Value,
+    pub dry_run: bool,
+    pub status: String, // succeeded, failed, skipped
+    pub steps: serde_json::Value,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAutomationRequest {
+    pub name: String,
+    pub graph: RuleGraph,
+    pub enabled: Option<bool>,
+}
+
+/// A new graph creates a new version
+#[derive(Debug, Deserialize)]
+pub struct UpdateAutomationRequest {
+    pub name: Option<String>,
+    pub graph: Option<RuleGraph>,
+    pub enabled: Option<bool>,
+}
+
+/// Evaluate against a sample event without performing any action
+#[derive(Debug, Deserialize)]
+pub struct DryRunRequest {
+    pub event: serde_json::Value,
+    /// Defaults to the current version
+    pub version: Option<i32>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct FeatureFlag {
+    pub flag: String,
+    pub enabled: bool,
+    pub updated_at: DateTime<Utc>,
+}
@@ -0,0 +1,137 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use crate::models::fleet_config::FleetConfig;
+
+/// Version of the backup document layout; restores refuse other versions
+pub const BACKUP_FORMAT: u32 = 1;
+
+/// Logical dump of an org. Devices and fleet configuration are grouped by member, who is
+/// identified by email so the document can be restored into another environment.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BackupDocument {
+    pub format: u32,
+    pub org_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub privacy_settings: serde_json::Value,
+    pub metrics: Vec<MetricBackup>,
+    pub members: Vec<MemberBackup>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, FromRow)]
+#[serde(deny_unknown_fields)]
+pub struct MetricBackup {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub unit: Option<String>,
+    pub formula: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MemberBackup {
+    pub email: String,
+    pub role: String,
+    pub devices: Vec<DeviceBackup>,
+    /// Fleets, tags, geofences, alert rules and automations, as `GET /api/robotics/config` exports them
+    pub config: FleetConfig,
+}
+
+/// A device without its credentials, hardware id or telemetry
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, FromRow)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceBackup {
+    pub id: Uuid,
+    pub device_name: String,
+    pub device_type: String,
+    pub firmware_version: String,
+    pub transport: String,
+    pub metadata: serde_json::Value,
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub sensors: Vec<SensorBackup>,
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub attachments: Vec<AttachmentBackup>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, FromRow)]
+#[serde(deny_unknown_fields)]
+pub struct SensorBackup {
+    pub sensor_type: String,
+    pub unit: String,
+    pub sampling_rate_hz: f32,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, FromRow)]
+#[serde(deny_unknown_fields)]
+pub struct AttachmentBackup {
+    pub attachment_type: String,
+    pub name: String,
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+    pub config: serde_json::Value,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct OrgBackup {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub created_by: Option<Uuid>,
+    pub size_bytes: i64,
+    pub sha256: String,
+    pub counts: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What to do with records that already exist and differ from the backup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictStrategy {
+    /// Keep the existing record
+    Skip,
+    /// Replace it with the backup's
+    Overwrite,
+    /// Abort the restore without changing anything
+    Fail,
+}
+
+impl ConflictStrategy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ConflictStrategy::Skip => "skip",
+            ConflictStrategy::Overwrite => "overwrite",
+            ConflictStrategy::Fail => "fail",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RestoreRequest {
+    pub strategy: ConflictStrategy,
+}
+
+/// Outcome of a restore; also stored with it
+#[derive(Debug, Default, Serialize)]
+pub struct RestoreReport {
+    pub devices_created: usize,
+    pub devices_updated: usize,
+    pub devices_unchanged: usize,
+    pub devices_skipped: usize,
+    /// Backup device id -> new id, for devices whose id is taken outside the org
+    pub devices_remapped: Vec<(Uuid, Uuid)>,
+    pub metrics_created: usize,
+    pub metrics_updated: usize,
+    pub metrics_skipped: usize,
+    pub privacy_settings_restored: bool,
+    pub config_changes: usize,
+    /// Automations left out because they command devices the member does not own here
+    pub automations_skipped: Vec<String>,
+    /// Emails in the backup that are not active members of the org
+    pub members_skipped: Vec<String>,
+}
@@ -0,0 +1,125 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct MaintenanceWindow {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// `None` covers the whole fleet
+    pub device_id: Option<Uuid>,
+    pub title: String,
+    pub notes: Option<String>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub status: String, // scheduled, cancelled
+    pub sequence: i32,
+    pub updated_via: String, // api, google
+    pub google_event_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateMaintenanceWindowRequest {
+    pub device_id: Option<Uuid>,
+    pub title: String,
+    pub notes: Option<String>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateMaintenanceWindowRequest {
+    pub title: Option<String>,
+    pub notes: Option<String>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct MaintenanceWindowQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub device_id: Option<Uuid>,
+    /// Include cancelled windows
+    pub include_cancelled: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct OnCallRotation {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub members: Vec<String>,
+    pub shift_hours: i32,
+    pub starts_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRotationRequest {
+    pub name: String,
+    /// Names or email addresses, in shift order
+    pub members: Vec<String>,
+    pub shift_hours: i32,
+    pub starts_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ShiftQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct CalendarFeed {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: String, // maintenance, on_call
+    pub device_id: Option<Uuid>,
+    pub rotation_id: Option<Uuid>,
+    #[serde(skip_serializing)]
+    pub url_version: i32,
+    pub last_fetched_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateFeedRequest {
+    pub kind: String,
+    pub device_id: Option<Uuid>,
+    pub rotation_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FeedQuery {
+    pub sig: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct GoogleCalendarConnection {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub calendar_id: String,
+    pub status: String, // pending, active, error
+    pub channel_expires_at: Option<DateTime<Utc>>,
+    pub last_synced_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GoogleConnectRequest {
+    /// `primary` or a calendar's id from its settings page
+    pub calendar_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GoogleCallbackQuery {
+    pub code: Option<String>,
+    pub state: String,
+    pub error: Option<String>,
+}
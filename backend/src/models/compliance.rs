@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use validator::Validate;
+
+/// An override letting an IP or user past geo-IP blocking
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct GeoAllowlistEntry {
+    pub id: Uuid,
+    pub ip: Option<String>,
+    pub user_id: Option<Uuid>,
+    pub email: Option<String>,
+    pub reason: String,
+    pub blocked_audit_id: Option<i64>,
+    pub status: String, // pending, approved, rejected
+    pub reviewed_by: Option<Uuid>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Review request from someone blocked in error
+#[derive(Debug, Deserialize, Validate)]
+pub struct GeoBlockAppealRequest {
+    /// Reference from the blocked response
+    pub reference: i64,
+    #[validate(email(message = "A valid contact email is required"))]
+    pub email: String,
+    #[validate(length(min = 10, max = 2000, message = "Reason must be 10-2000 characters"))]
+    pub reason: String,
+}
+
+/// Admin-created override, approved immediately
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateAllowlistRequest {
+    pub ip: Option<String>,
+    pub user_id: Option<Uuid>,
+    #[validate(length(min = 1, max = 2000, message = "Reason must be 1-2000 characters"))]
+    pub reason: String,
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReviewAllowlistRequest {
+    /// Override lifetime when approving
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AllowlistQuery {
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeprecationReportQuery {
+    /// Only consumers seen within this many days (default 30)
+    pub days: Option<i64>,
+}
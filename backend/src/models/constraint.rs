@@ -0,0 +1,79 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// A command on one device that may only run while another device is in a given state,
+/// e.g. drone A may `takeoff` only once rover B reports `dock_state = docked`
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct CommandConstraint {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub device_id: Uuid,
+    pub command: String,
+    pub depends_on_device_id: Uuid,
+    /// What of the dependency is compared: `status`, `last_command` or `metadata`
+    pub condition: String,
+    /// Metadata key compared by `metadata` conditions
+    pub field: Option<String>,
+    pub expected: String,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateConstraintRequest {
+    pub name: String,
+    pub device_id: Uuid,
+    pub command: String,
+    pub depends_on_device_id: Uuid,
+    pub condition: String,
+    pub field: Option<String>,
+    pub expected: String,
+    pub enabled: Option<bool>,
+}
+
+/// Partial update; the guarded command and its dependency are fixed once created
+#[derive(Debug, Deserialize)]
+pub struct UpdateConstraintRequest {
+    pub name: Option<String>,
+    pub condition: Option<String>,
+    pub field: Option<String>,
+    pub expected: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+/// A constraint that blocks a command, with what the dependency actually reported
+#[derive(Debug, Clone, Serialize)]
+pub struct ConstraintViolation {
+    pub constraint_id: Uuid,
+    pub name: String,
+    pub command: String,
+    pub depends_on_device_id: Uuid,
+    pub depends_on_device_name: String,
+    pub condition: String,
+    pub field: Option<String>,
+    pub expected: String,
+    /// `None` when the dependency has never reported the compared state
+    pub actual: Option<String>,
+}
+
+impl ConstraintViolation {
+    /// e.g. `takeoff needs rover-b metadata.dock_state = docked, is deployed`
+    pub fn describe(&self) -> String {
+        let subject = match &self.field {
+            Some(field) => format!("{}.{}", self.condition, field),
+            None => self.condition.clone(),
+        };
+        format!(
+            "{} needs {} {} = {}, is {}",
+            self.command,
+            self.depends_on_device_name,
+            subject,
+            self.expected,
+            self.actual.as_deref().unwrap_or("unknown")
+        )
+    }
+}
@@ -0,0 +1,35 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Conversation {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub title: String,
+    pub model: Option<String>,
+    pub system_prompt: Option<String>,
+    pub message_count: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ConversationMessage {
+    pub id: i64,
+    pub conversation_id: Uuid,
+    pub role: String, // user, assistant
+    pub content: String,
+    pub model: Option<String>,
+    pub prompt_tokens: Option<i32>,
+    pub completion_tokens: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A conversation with all its messages, oldest first
+#[derive(Debug, Serialize)]
+pub struct ConversationDetails {
+    pub conversation: Conversation,
+    pub messages: Vec<ConversationMessage>,
+}
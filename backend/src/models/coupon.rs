@@ -0,0 +1,76 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Coupon {
+    pub id: Uuid,
+    pub code: String,
+    pub description: Option<String>,
+    /// percentage, fixed
+    pub kind: String,
+    /// Percent off, or an amount of `currency` off
+    pub value: Decimal,
+    pub currency: Option<String>,
+    /// `None` when the coupon applies to every product
+    pub product_types: Option<Vec<String>>,
+    pub max_redemptions: Option<i32>,
+    pub max_per_user: Option<i32>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub active: bool,
+    /// Payments the coupon was applied to that have not failed
+    pub redemptions: i64,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCouponRequest {
+    pub code: String,
+    pub description: Option<String>,
+    pub kind: String, // percentage, fixed
+    pub value: Decimal,
+    /// Required for fixed coupons
+    pub currency: Option<String>,
+    pub product_types: Option<Vec<String>>,
+    pub max_redemptions: Option<i32>,
+    pub max_per_user: Option<i32>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Fields left out are kept
+#[derive(Debug, Deserialize)]
+pub struct UpdateCouponRequest {
+    pub active: Option<bool>,
+    pub description: Option<String>,
+    pub max_redemptions: Option<i32>,
+    pub max_per_user: Option<i32>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CouponQuery {
+    /// Only active coupons when true, only inactive ones when false
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ValidateCouponRequest {
+    pub code: String,
+    pub product_type: String,
+}
+
+/// What a coupon takes off a product's price
+#[derive(Debug, Serialize)]
+pub struct CouponQuote {
+    pub coupon_id: Uuid,
+    pub code: String,
+    pub price: Decimal,
+    pub discount: Decimal,
+    /// What is charged
+    pub total: Decimal,
+    pub currency: String,
+}
@@ -14,10 +14,42 @@ pub struct Device {
     pub status: String, // online, offline, maintenance
     pub last_seen: Option<DateTime<Utc>>,
     pub metadata: serde_json::Value,
+    /// How commands reach the device: mqtt, websocket, long_poll, simulated
+    #[sqlx(default)]
+    pub transport: String,
+    /// Chain and token id of the ownership certificate, once minted
+    #[sqlx(default)]
+    pub nft_chain_id: Option<i64>,
+    #[sqlx(default)]
+    pub nft_token_id: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
+/// Chain to mint the ownership certificate on; the default chain when omitted
+#[derive(Debug, Default, Deserialize)]
+pub struct MintCertificateRequest {
+    pub chain_id: Option<u64>,
+}
+
+/// Hand a device to another account. A device with a certificate moves only once the
+/// recipient's wallet holds the token.
 #[derive(Debug, Deserialize)]
+pub struct TransferDeviceRequest {
+    pub recipient_email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateTransportRequest {
+    pub transport: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PendingCommandsQuery {
+    /// Seconds to hold the request open when nothing is pending
+    pub wait: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
 #[allow(dead_code)]
 pub struct RegisterDeviceRequest {
     pub device_name: String,
@@ -30,4 +62,282 @@ pub struct RegisterDeviceRequest {
 pub struct DeviceCommand {
     pub command: String,
     pub parameters: serde_json::Value,
+    /// Run every check and estimate but do not record or dispatch the command
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Area the command must keep the device inside; checked on dry runs
+    pub geofence: Option<crate::models::swarm::GeoBounds>,
+    /// Dispatch even if cross-device constraints do not hold; admins only
+    #[serde(default)]
+    pub override_constraints: bool,
+}
+
+/// Commands to enqueue together, in execution order
+#[derive(Debug, Deserialize)]
+pub struct CommandBatchRequest {
+    pub commands: Vec<crate::models::mission::LegAction>,
+    /// Dispatch even if cross-device constraints do not hold; admins only
+    #[serde(default)]
+    pub override_constraints: bool,
+}
+
+/// Outcome of a single row in a bulk device import
+#[derive(Debug, Serialize)]
+pub struct ImportRowResult {
+    pub row: usize,
+    pub success: bool,
+    pub device_id: Option<Uuid>,
+    pub device_name: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkImportResponse {
+    pub total: usize,
+    pub imported: usize,
+    pub failed: usize,
+    pub results: Vec<ImportRowResult>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UptimeQuery {
+    /// Reporting window ending now, e.g. `30d` or `12h`
+    pub period: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct NearbyQuery {
+    pub lat: f64,
+    pub lng: f64,
+    pub radius_m: f64,
+    pub device_type: Option<String>,
+    /// Only devices currently in this region
+    pub region_id: Option<Uuid>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct NearbyDevice {
+    pub id: Uuid,
+    pub device_name: String,
+    pub device_type: String,
+    pub status: String,
+    pub last_latitude: f64,
+    pub last_longitude: f64,
+    pub last_altitude: Option<f64>,
+    pub position_updated_at: Option<DateTime<Utc>>,
+    pub distance_m: f64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct TelemetryRecord {
+    pub id: i64,
+    pub device_id: Uuid,
+    pub recorded_at: DateTime<Utc>,
+    pub battery_level: i16,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+    pub payload: serde_json::Value,
+    pub received_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct DeviceCommandRecord {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub user_id: Uuid,
+    pub command: String,
+    pub parameters: serde_json::Value,
+    pub status: String, // sent, succeeded, failed, cancelled
+    pub estimated_duration_ms: i64,
+    pub estimated_battery_drain: f32,
+    pub actual_duration_ms: Option<i64>,
+    pub actual_battery_drain: Option<f32>,
+    pub error: Option<String>,
+    pub path_id: Option<Uuid>,
+    pub mission_leg_id: Option<Uuid>,
+    pub macro_id: Option<Uuid>,
+    pub batch_id: Option<Uuid>,
+    pub sequence: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub acked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct CommandAckRequest {
+    pub success: bool,
+    pub actual_duration_ms: u64,
+    pub actual_battery_drain: f32,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct DevicePath {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub points: sqlx::types::Json<Vec<PathPoint>>,
+    pub point_count: i32,
+    pub distance_m: f64,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A recorded path without its points, for listings
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct PathSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub point_count: i32,
+    pub distance_m: f64,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct RecordPathRequest {
+    pub name: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Drop samples closer than this to the previous kept point (meters)
+    pub min_spacing_m: Option<f64>,
+}
+
+/// Work to simulate: exactly one of a command sequence, a saved macro or a planned fleet
+/// mission (only the legs assigned to this device are simulated)
+#[derive(Debug, Deserialize)]
+pub struct SimulateBatteryRequest {
+    pub steps: Option<Vec<crate::models::mission::LegAction>>,
+    pub macro_id: Option<Uuid>,
+    pub legs: Option<Vec<crate::models::mission::CreateLegRequest>>,
+    /// Defaults to the latest reported battery level
+    pub start_level: Option<f64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TrackQuery {
+    /// Defaults to 24 hours before `to`
+    pub from: Option<DateTime<Utc>>,
+    /// Defaults to now
+    pub to: Option<DateTime<Utc>>,
+    pub format: Option<String>, // geojson (default), json
+    /// Drop samples closer than this to the previous kept point (meters)
+    pub min_spacing_m: Option<f64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct EnergyQuery {
+    /// Defaults to 30 days before `to`
+    pub from: Option<DateTime<Utc>>,
+    /// Defaults to now
+    pub to: Option<DateTime<Utc>>,
+    /// Electricity tariff used to price recharging
+    pub price_per_kwh: Option<f64>,
+    pub currency: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[allow(dead_code)]
+pub struct ReplayPathRequest {
+    /// Fraction of the device's top speed, 0.0-1.0
+    pub speed: Option<f64>,
+    /// Device heading at the path origin in degrees from north; defaults to the first leg's bearing
+    pub initial_heading: Option<f64>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct ClaimCode {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub device_name: Option<String>,
+    pub device_type: Option<String>,
+    pub expires_at: DateTime<Utc>,
+    pub claimed_at: Option<DateTime<Utc>>,
+    pub device_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct CreateClaimCodeRequest {
+    pub device_name: Option<String>,
+    pub device_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct ProvisionDeviceRequest {
+    pub claim_code: String,
+    pub hardware_id: String,
+    pub device_type: String,
+    pub firmware_version: String,
+    pub device_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct StreamSession {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub user_id: Uuid,
+    pub status: String, // offered, answered, closed
+    pub offer_sdp: String,
+    pub answer_sdp: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub answered_at: Option<DateTime<Utc>>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct IceCandidateRecord {
+    pub id: i64,
+    pub sender: String, // operator, device
+    pub candidate: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct SdpRequest {
+    pub sdp: String,
+}
+
+/// Mirrors the browser's RTCIceCandidateInit
+#[derive(Debug, Serialize, Deserialize)]
+#[allow(dead_code)]
+pub struct IceCandidate {
+    pub candidate: String,
+    #[serde(rename = "sdpMid")]
+    pub sdp_mid: Option<String>,
+    #[serde(rename = "sdpMLineIndex")]
+    pub sdp_mline_index: Option<u16>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct CandidateQuery {
+    pub after: Option<i64>,
 }
@@ -0,0 +1,61 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Dispute {
+    pub id: Uuid,
+    pub transaction_id: Uuid,
+    pub user_id: Uuid,
+    pub provider: String,
+    pub provider_dispute_id: String,
+    pub amount: Decimal,
+    pub currency: String,
+    pub reason: Option<String>,
+    pub provider_status: Option<String>,
+    /// open, evidence_submitted, won, lost
+    pub status: String,
+    pub evidence_due_by: Option<DateTime<Utc>>,
+    pub evidence_note: Option<String>,
+    pub evidence_submitted_by: Option<Uuid>,
+    pub evidence_submitted_at: Option<DateTime<Utc>>,
+    pub resolution_note: Option<String>,
+    pub resolved_by: Option<Uuid>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DisputeQuery {
+    /// Open and evidence_submitted disputes when left out
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DisputeEvidenceRequest {
+    /// What was sent to the provider, e.g. the delivery logs and license activation record
+    pub note: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResolveDisputeRequest {
+    pub outcome: String, // won, lost
+    pub note: Option<String>,
+}
+
+/// A dispute as the provider's webhook reports it
+#[derive(Debug, PartialEq)]
+pub struct ProviderDispute {
+    pub id: String,
+    /// The provider's id of the disputed payment, as stored in `transactions.payment_id`
+    pub payment_id: String,
+    /// In the currency's smallest unit
+    pub amount: i64,
+    pub currency: String,
+    pub reason: Option<String>,
+    pub status: String,
+    pub evidence_due_by: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,88 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct IndexDocument {
+    pub content: String,
+    pub source: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IndexRequest {
+    /// `default` when left out
+    pub collection: Option<String>,
+    pub documents: Vec<IndexDocument>,
+}
+
+/// A stored document; the vector itself is not returned
+#[derive(Debug, Serialize, FromRow)]
+pub struct IndexedDocument {
+    pub id: Uuid,
+    pub collection: String,
+    pub source: Option<String>,
+    pub model: String,
+    pub dimensions: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchRequest {
+    pub query: String,
+    /// Every collection when left out
+    pub collection: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct SearchResult {
+    pub id: Uuid,
+    pub collection: String,
+    /// Set for chunks of an uploaded document
+    pub document_id: Option<Uuid>,
+    pub chunk_index: Option<i32>,
+    pub source: Option<String>,
+    pub content: String,
+    pub metadata: serde_json::Value,
+    /// Cosine similarity to the query, 1 for the same direction
+    pub score: f64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A file uploaded for retrieval, stored as embedded chunks
+#[derive(Debug, Serialize, FromRow)]
+pub struct RagDocument {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub collection: String,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub chunk_count: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DocumentUploadQuery {
+    pub file_name: String,
+    /// `default` when left out
+    pub collection: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DocumentQuery {
+    pub collection: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AskRequest {
+    pub question: String,
+    /// Every collection when left out
+    pub collection: Option<String>,
+    /// Chunks put in front of the model
+    pub top_k: Option<i64>,
+    pub model: Option<String>,
+    pub provider: Option<String>,
+}
@@ -0,0 +1,90 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Escrow {
+    pub id: Uuid,
+    pub buyer_id: Uuid,
+    pub seller_id: Uuid,
+    pub description: String,
+    pub device_id: Option<Uuid>,
+    pub chain_id: i64,
+    /// In whole tokens
+    pub amount: String,
+    pub symbol: String,
+    /// awaiting_funding, funding, funded, fulfilled, disputed, released, refunded, cancelled
+    pub status: String,
+    pub funding_transaction_id: Option<Uuid>,
+    pub payout_transaction_id: Option<Uuid>,
+    pub dispute_reason: Option<String>,
+    pub disputed_by: Option<Uuid>,
+    pub funded_at: Option<DateTime<Utc>>,
+    pub fulfilled_at: Option<DateTime<Utc>>,
+    pub auto_release_at: Option<DateTime<Utc>>,
+    pub settled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct EscrowEvent {
+    pub id: i64,
+    pub escrow_id: Uuid,
+    pub action: String,
+    /// `None` for the escrow's creation
+    pub from_status: Option<String>,
+    pub to_status: String,
+    /// `None` for changes the platform made
+    pub actor_id: Option<Uuid>,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An escrow with its state history
+#[derive(Debug, Serialize)]
+pub struct EscrowDetails {
+    pub escrow: Escrow,
+    pub events: Vec<EscrowEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateEscrowRequest {
+    pub seller_email: String,
+    pub description: String,
+    /// In whole tokens of the chain's token
+    pub amount: String,
+    /// Defaults to the configured default chain
+    pub chain_id: Option<u64>,
+    /// A device of the seller's that the escrow pays for
+    pub device_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EscrowNoteRequest {
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DisputeEscrowRequest {
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResolveEscrowRequest {
+    pub outcome: String, // release, refund
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EscrowListQuery {
+    pub role: Option<String>, // buyer, seller
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EscrowQueueQuery {
+    /// Defaults to disputed
+    pub status: Option<String>,
+}
@@ -0,0 +1,51 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Fine-tuning examples uploaded to a provider
+#[derive(Debug, Serialize, FromRow)]
+pub struct TrainingFile {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub provider: String,
+    pub provider_file_id: String,
+    pub file_name: String,
+    pub size_bytes: i64,
+    pub example_count: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A fine-tuning job; `fine_tuned_model` is set once it succeeds and names the model in chat
+/// requests
+#[derive(Debug, Serialize, FromRow)]
+pub struct FineTune {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub training_file_id: Uuid,
+    pub provider: String,
+    pub provider_job_id: String,
+    pub base_model: String,
+    pub suffix: Option<String>,
+    pub status: String,
+    pub fine_tuned_model: Option<String>,
+    pub trained_tokens: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Options of a training file upload; the JSONL examples are the raw request body
+#[derive(Debug, Deserialize)]
+pub struct TrainingFileQuery {
+    pub file_name: String,
+    pub provider: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StartFineTuneRequest {
+    pub training_file_id: Uuid,
+    pub base_model: String,
+    /// Up to 40 letters, digits, `-` or `_`, included in the new model's ID
+    pub suffix: Option<String>,
+}
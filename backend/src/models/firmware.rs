@@ -0,0 +1,58 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct FirmwareSigningKey {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub name: String,
+    pub public_key: String,
+    pub fingerprint: String,
+    pub endorsement: String,
+    pub endorsed_by: String,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSigningKeyRequest {
+    pub name: String,
+    /// Base64 Ed25519 public key (32 bytes)
+    pub public_key: String,
+}
+
+/// Upload parameters; the artifact is the raw request body and its detached
+/// signature is sent in the `X-Firmware-Signature` header
+#[derive(Debug, Deserialize)]
+pub struct FirmwareUploadQuery {
+    pub device_type: String,
+    pub version: String,
+}
+
+/// A firmware upload and the result of verifying its signature (artifact bytes omitted)
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct FirmwareRelease {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub device_type: String,
+    pub version: String,
+    pub sha256: String,
+    pub size_bytes: i64,
+    pub signature: String,
+    pub signing_key_id: Option<Uuid>,
+    pub verification_status: String, // verified, rejected
+    pub verification_error: Option<String>,
+    pub uploaded_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FirmwareReportRequest {
+    pub status: String, // installed, rejected, failed
+    pub reason: Option<String>,
+}
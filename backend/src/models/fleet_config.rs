@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+use crate::models::automation::{Condition, RuleGraph};
+use crate::models::swarm::GeoBounds;
+
+fn default_severity() -> String {
+    "warning".to_string()
+}
+
+fn default_cooldown() -> u32 {
+    15
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// The desired state of a user's fleet configuration. Resources are identified by name. A
+/// section that is present is authoritative: anything missing from it is removed. An omitted
+/// section is left alone.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FleetConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fleets: Option<Vec<FleetSpec>>,
+    /// Tags per device id; devices not listed end up without tags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<BTreeMap<Uuid, Vec<String>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geofences: Option<Vec<GeofenceSpec>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alert_rules: Option<Vec<AlertRuleSpec>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub automations: Option<Vec<AutomationSpec>>,
+}
+
+/// A named group of devices
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FleetSpec {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub devices: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct GeofenceSpec {
+    pub name: String,
+    /// Fleet name; every device when omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fleet: Option<String>,
+    pub bounds: GeoBounds,
+    #[serde(default = "default_severity")]
+    pub severity: String,
+    #[serde(default = "default_cooldown")]
+    pub cooldown_minutes: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AlertRuleSpec {
+    pub name: String,
+    /// Fleet name; every device when omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fleet: Option<String>,
+    /// Evaluated on each stored sample, with the sample's `device_id` added
+    pub condition: Condition,
+    #[serde(default = "default_severity")]
+    pub severity: String,
+    #[serde(default = "default_cooldown")]
+    pub cooldown_minutes: u32,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AutomationSpec {
+    pub name: String,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    pub graph: RuleGraph,
+}
+
+/// One step of a plan. `changes` names the fields an update touches.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PlannedChange {
+    pub resource: &'static str, // fleet, tags, geofence, alert_rule, automation
+    pub name: String,
+    pub action: &'static str, // create, update, delete
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<&'static str>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApplyConfigQuery {
+    /// Only return the plan
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportConfigQuery {
+    /// `json` (default) or `yaml`
+    #[serde(default)]
+    pub format: Option<String>,
+}
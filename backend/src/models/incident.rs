@@ -0,0 +1,45 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// A critical telemetry alert that needs a human, with any repeats folded in
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Incident {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub device_id: Uuid,
+    pub processor_id: Option<Uuid>,
+    pub severity: String,
+    pub title: String,
+    pub status: String, // open, acknowledged, resolved
+    pub alert_count: i32,
+    pub last_alert_at: DateTime<Utc>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    pub acknowledged_by: Option<Uuid>,
+    pub acknowledged_via: Option<String>, // api, email
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct IncidentMessage {
+    pub id: Uuid,
+    pub incident_id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub source: String, // api, email
+    pub from_address: Option<String>,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct IncidentQuery {
+    pub status: Option<String>,
+    pub device_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IncidentMessageRequest {
+    pub body: String,
+}
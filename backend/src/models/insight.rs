@@ -0,0 +1,35 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// An AI health summary of a device's recent telemetry
+#[derive(Debug, Serialize, FromRow)]
+pub struct DeviceInsight {
+    pub device_id: Uuid,
+    /// The hour the summary is cached for
+    pub hour_start: DateTime<Utc>,
+    /// good, fair, poor, critical, or unknown when the model did not say
+    pub health: String,
+    pub summary: String,
+    pub recommendations: sqlx::types::Json<Vec<String>>,
+    pub model: String,
+    pub provider: String,
+    pub created_at: DateTime<Utc>,
+    /// Served from this hour's cache rather than generated for this request
+    #[sqlx(default)]
+    pub cached: bool,
+}
+
+/// Telemetry of one hour, as summarized for the model
+#[derive(Debug, FromRow)]
+pub struct TelemetryHour {
+    pub hour_start: DateTime<Utc>,
+    pub samples: i64,
+    pub battery_avg: f64,
+    pub battery_min: i16,
+    pub battery_max: i16,
+    pub altitude_avg: Option<f64>,
+    pub cpu_temp_avg: Option<f64>,
+    pub signal_strength_avg: Option<f64>,
+}
@@ -0,0 +1,48 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct IntegrationToken {
+    pub id: Uuid,
+    pub name: String,
+    pub token_prefix: String,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateIntegrationTokenRequest {
+    /// Where the token is used, e.g. "Zapier"
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct IntegrationHook {
+    pub id: Uuid,
+    pub event: String, // new_device, new_alert, command_completed
+    pub target_url: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// REST hook subscription; Zapier sends the URL as `hookUrl`
+#[derive(Debug, Deserialize)]
+pub struct SubscribeHookRequest {
+    #[serde(alias = "hookUrl")]
+    pub target_url: String,
+    pub event: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SendCommandAction {
+    pub device_id: Uuid,
+    pub command: String,
+    #[serde(default)]
+    pub parameters: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AcknowledgeIncidentAction {
+    pub incident_id: Uuid,
+}
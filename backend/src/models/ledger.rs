@@ -0,0 +1,74 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// An account with what has been posted to it. Debits are positive and credits negative;
+/// `balance` is signed the way the account kind grows, so a liability owed is positive.
+#[derive(Debug, Serialize, FromRow)]
+pub struct AccountBalance {
+    pub id: i64,
+    pub code: String,
+    /// asset, liability, revenue, expense
+    pub kind: String,
+    pub currency: String,
+    pub user_id: Option<Uuid>,
+    pub debits: Decimal,
+    pub credits: Decimal,
+    pub balance: Decimal,
+}
+
+/// Debits and credits across every account in one currency
+#[derive(Debug, Serialize)]
+pub struct CurrencyTotals {
+    pub currency: String,
+    pub debits: Decimal,
+    pub credits: Decimal,
+    pub balanced: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrialBalance {
+    pub accounts: Vec<AccountBalance>,
+    pub totals: Vec<CurrencyTotals>,
+}
+
+/// The caller's credits in one currency, as the ledger has them
+#[derive(Debug, Serialize, FromRow)]
+pub struct LedgerBalance {
+    pub currency: String,
+    pub balance: Decimal,
+}
+
+/// A posting to one of the caller's accounts
+#[derive(Debug, Serialize, FromRow)]
+pub struct LedgerLine {
+    pub entry_id: i64,
+    pub reference: String,
+    pub description: String,
+    pub currency: String,
+    /// Positive when credits were added, negative when taken
+    pub amount: Decimal,
+    pub payout_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LedgerLineQuery {
+    /// Lines before this entry id, for the next page
+    pub before: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// Something recorded elsewhere that the ledger does not agree with
+#[derive(Debug, Serialize, FromRow)]
+pub struct LedgerDiscrepancy {
+    /// credits, payment, refund, payout
+    pub kind: String,
+    /// The event's ledger reference, or the credits account for balance mismatches
+    pub reference: String,
+    pub currency: String,
+    pub expected: Decimal,
+    pub recorded: Decimal,
+}
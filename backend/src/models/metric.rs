@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// A derived KPI such as `distance() / -delta(battery_level)`, computed per device and hour
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct OrgMetric {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub unit: Option<String>,
+    pub formula: String,
+    pub computed_through: Option<DateTime<Utc>>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateMetricRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub unit: Option<String>,
+    pub formula: String,
+}
+
+/// Changing the formula discards computed values and recomputes the backfill window
+#[derive(Debug, Deserialize)]
+pub struct UpdateMetricRequest {
+    pub description: Option<String>,
+    pub unit: Option<String>,
+    pub formula: Option<String>,
+}
+
+/// Evaluate a formula against one device's recent telemetry without saving it
+#[derive(Debug, Deserialize)]
+pub struct PreviewMetricRequest {
+    pub formula: String,
+    pub device_id: Uuid,
+    pub hours: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SeriesQuery {
+    /// Without a device the series aggregates every device in the org
+    pub device_id: Option<Uuid>,
+    /// Aggregate only the devices currently in this region
+    pub region_id: Option<Uuid>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct MetricPoint {
+    pub bucket_start: DateTime<Utc>,
+    pub value: f64,
+    pub samples: i32,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct AggregatePoint {
+    pub bucket_start: DateTime<Utc>,
+    pub avg: f64,
+    pub min: f64,
+    pub max: f64,
+    pub devices: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct DeviceValue {
+    pub device_id: Uuid,
+    pub value: f64,
+    pub samples: i32,
+}
+
+/// Dashboard tile: the latest completed hour of a metric
+#[derive(Debug, Serialize)]
+pub struct MetricSummary {
+    #[serde(flatten)]
+    pub metric: OrgMetric,
+    pub latest: Option<AggregatePoint>,
+    pub devices: Vec<DeviceValue>,
+}
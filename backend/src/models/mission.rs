@@ -0,0 +1,71 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Waypoint {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Cruise altitude for drones (meters); ignored by ground devices
+    pub altitude: Option<f64>,
+}
+
+/// A device command run at a leg's rendezvous or destination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegAction {
+    pub command: String,
+    #[serde(default)]
+    pub parameters: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateLegRequest {
+    pub device_id: Uuid,
+    pub target: Waypoint,
+    /// Commands run on reaching the previous leg's target (e.g. `grab`); not allowed on the first leg
+    #[serde(default)]
+    pub pickup_actions: Vec<LegAction>,
+    /// Commands run on reaching this leg's target (e.g. `release`, `land`)
+    #[serde(default)]
+    pub actions: Vec<LegAction>,
+    /// Fraction of the device's top speed, 0.05-1.0
+    pub speed: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateFleetMissionRequest {
+    pub name: String,
+    pub legs: Vec<CreateLegRequest>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct FleetMission {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub status: String, // active, awaiting_handoff, completed, failed, cancelled
+    pub current_leg: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct MissionLeg {
+    pub id: Uuid,
+    pub mission_id: Uuid,
+    pub leg_index: i32,
+    pub device_id: Uuid,
+    pub target: sqlx::types::Json<Waypoint>,
+    pub pickup_actions: sqlx::types::Json<Vec<LegAction>>,
+    pub actions: sqlx::types::Json<Vec<LegAction>>,
+    pub speed: f32,
+    pub status: String, // pending, dispatched, completed, failed, cancelled
+    pub readiness: Option<serde_json::Value>,
+    pub command_count: i32,
+    pub dispatched_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
@@ -1,3 +1,42 @@
 pub mod user;
 pub mod device;
 pub mod transaction;
+pub mod org;
+pub mod notification;
+pub mod security;
+pub mod swarm;
+pub mod firmware;
+pub mod compliance;
+pub mod mission;
+pub mod sensor;
+pub mod promotion;
+pub mod processor;
+pub mod webhook;
+pub mod automation;
+pub mod template;
+pub mod metric;
+pub mod attachment;
+pub mod incident;
+pub mod calendar;
+pub mod integration;
+pub mod fleet_config;
+pub mod backup;
+pub mod constraint;
+pub mod subscription;
+pub mod open_data;
+pub mod region;
+pub mod support;
+pub mod escrow;
+pub mod onchain_event;
+pub mod typed_data;
+pub mod payout;
+pub mod spending;
+pub mod ledger;
+pub mod coupon;
+pub mod dispute;
+pub mod conversation;
+pub mod embedding;
+pub mod vision;
+pub mod speech;
+pub mod insight;
+pub mod fine_tune;
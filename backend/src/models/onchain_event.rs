@@ -0,0 +1,38 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct OnchainEvent {
+    pub id: i64,
+    pub chain_id: i64,
+    pub contract_address: String,
+    /// token_transfer, device_minted, device_transferred, device_burned
+    pub event: String,
+    pub block_number: i64,
+    pub tx_hash: String,
+    pub log_index: i64,
+    pub from_address: String,
+    pub to_address: String,
+    /// Token transfers: the value in base units
+    pub amount: Option<String>,
+    pub token_id: Option<String>,
+    pub device_id: Option<Uuid>,
+    pub indexed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OnchainEventQuery {
+    pub chain_id: Option<u64>,
+    pub contract: Option<String>,
+    pub event: Option<String>,
+    /// Events sent from or to this address
+    pub address: Option<String>,
+    pub device_id: Option<Uuid>,
+    pub from_block: Option<u64>,
+    pub to_block: Option<u64>,
+    /// Only events older than this id, to page through results
+    pub before: Option<i64>,
+    pub limit: Option<i64>,
+}
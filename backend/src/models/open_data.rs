@@ -0,0 +1,100 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// A dataset as its publisher sees it
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct OpenDataset {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub slug: String,
+    pub title: String,
+    pub description: String,
+    pub license: String,
+    pub device_ids: Vec<Uuid>,
+    pub device_types: Vec<String>,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub location_decimals: i16,
+    pub status: String, // building, published, failed, withdrawn
+    pub row_count: Option<i64>,
+    pub size_bytes: Option<i64>,
+    pub sha256: Option<String>,
+    pub failure_reason: Option<String>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub withdrawn_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A published dataset as the public catalog lists it: nothing identifies the publisher
+/// or their devices
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct CatalogDataset {
+    pub slug: String,
+    pub title: String,
+    pub description: String,
+    pub license: String,
+    pub device_types: Vec<String>,
+    pub device_count: i32,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub location_decimals: i16,
+    pub row_count: Option<i64>,
+    pub size_bytes: Option<i64>,
+    pub sha256: Option<String>,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct License {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub url: &'static str,
+}
+
+/// One catalog entry with its license spelled out and, on the detail view, a time-limited
+/// download link
+#[derive(Debug, Serialize)]
+pub struct CatalogEntry {
+    #[serde(flatten)]
+    pub dataset: CatalogDataset,
+    pub license_details: Option<License>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download_expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CatalogPage {
+    pub datasets: Vec<CatalogEntry>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CatalogQuery {
+    pub license: Option<String>,
+    pub device_type: Option<String>,
+    /// Matched against titles and descriptions
+    pub q: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PublishDatasetRequest {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub license: String,
+    /// Devices to include; all of the publisher's devices when omitted
+    pub device_ids: Option<Vec<Uuid>>,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Decimal places kept on coordinates, 0-4 (defaults to 2, ~1km)
+    pub location_decimals: Option<i16>,
+}
@@ -0,0 +1,208 @@
+use secrecy::SecretString;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use validator::Validate;
+
+/// Roles a user can hold inside an organization, in descending privilege order.
+/// `auditor` is read-only and ranks below `member` for write access.
+pub const ORG_ROLES: &[&str] = &["owner", "admin", "member", "auditor"];
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[allow(dead_code)]
+pub struct Organization {
+    pub id: Uuid,
+    pub name: String,
+    pub slug: String,
+    pub sso_enforced: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[allow(dead_code)]
+pub struct OrgMembership {
+    pub org_id: Uuid,
+    pub user_id: Uuid,
+    pub role: String, // owner, admin, member, auditor
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[allow(dead_code)]
+pub struct CreateOrgRequest {
+    #[validate(length(min = 2, max = 100, message = "Organization name must be 2-100 characters"))]
+    pub name: String,
+
+    #[validate(length(min = 2, max = 60, message = "Slug must be 2-60 characters"))]
+    pub slug: String,
+}
+
+#[derive(Serialize, Deserialize, FromRow)]
+#[allow(dead_code)]
+pub struct SsoConfig {
+    pub org_id: Uuid,
+    pub protocol: String, // oidc, saml
+    pub enabled: bool,
+    pub issuer_url: Option<String>,
+    pub client_id: Option<String>,
+    #[serde(skip_serializing)]
+    pub client_secret: Option<String>,
+    pub idp_entity_id: Option<String>,
+    pub idp_sso_url: Option<String>,
+    pub idp_public_key_pem: Option<String>,
+    pub role_mapping: serde_json::Value,
+    pub default_role: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+// Hand-written so the OIDC client secret never reaches logs
+impl std::fmt::Debug for SsoConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SsoConfig")
+            .field("org_id", &self.org_id)
+            .field("protocol", &self.protocol)
+            .field("enabled", &self.enabled)
+            .field("issuer_url", &self.issuer_url)
+            .field("client_id", &self.client_id)
+            .field("client_secret", &self.client_secret.as_ref().map(|_| "[REDACTED]"))
+            .field("idp_entity_id", &self.idp_entity_id)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct UpdateSsoConfigRequest {
+    pub protocol: String,
+    pub enabled: bool,
+    pub enforced: bool,
+    pub issuer_url: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<SecretString>,
+    pub idp_entity_id: Option<String>,
+    pub idp_sso_url: Option<String>,
+    pub idp_public_key_pem: Option<String>,
+    /// Maps IdP group names to org roles, e.g. `{"robotics-admins": "admin"}`
+    pub role_mapping: Option<serde_json::Value>,
+    pub default_role: Option<String>,
+}
+
+/// An email domain an org has claimed for SSO and SCIM provisioning
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[allow(dead_code)]
+pub struct OrgDomain {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub domain: String,
+    pub verification_token: String,
+    pub verified_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct AddOrgDomainRequest {
+    pub domain: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct OidcCallbackQuery {
+    pub code: String,
+    pub state: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct SamlAcsForm {
+    #[serde(rename = "SAMLResponse")]
+    pub saml_response: String,
+    #[serde(rename = "RelayState")]
+    pub relay_state: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct AuditLog {
+    pub id: i64,
+    pub org_id: Option<Uuid>,
+    pub actor_id: Option<Uuid>,
+    pub action: String,
+    pub resource_type: String,
+    pub resource_id: Option<String>,
+    pub details: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct AuditQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub action: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct ExportQuery {
+    pub resource: String, // audit_logs, transactions, device_history
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CapacityQuery {
+    pub format: Option<String>, // json (default), csv
+    /// Charging stations available; projected charging peaks above it are flagged
+    pub chargers: Option<u32>,
+    /// Only the devices currently in this region
+    pub region_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct BreakGlassCredential {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub label: String,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct BreakGlassSession {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub credential_id: Uuid,
+    pub requested_by: Uuid,
+    pub approved_by: Option<Uuid>,
+    pub reason: String,
+    pub duration_minutes: i32,
+    pub status: String, // pending, active, revoked
+    pub requested_at: DateTime<Utc>,
+    pub activated_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[allow(dead_code)]
+pub struct CreateBreakGlassCredentialRequest {
+    #[validate(length(min = 1, max = 100, message = "Label must be 1-100 characters"))]
+    pub label: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct BreakGlassActivationRequest {
+    pub secret: SecretString,
+    pub reason: String,
+    pub duration_minutes: Option<i32>,
+}
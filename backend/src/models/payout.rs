@@ -0,0 +1,146 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct KycVerification {
+    pub user_id: Uuid,
+    /// pending, verified, rejected
+    pub status: String,
+    pub legal_name: String,
+    pub country: String,
+    pub document_type: String,
+    pub document_reference: String,
+    pub rejection_reason: Option<String>,
+    pub reviewed_by: Option<Uuid>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub submitted_at: DateTime<Utc>,
+}
+
+/// Identity details checked by the identity provider; submitting again replaces a rejected
+/// verification
+#[derive(Debug, Deserialize)]
+pub struct SubmitKycRequest {
+    pub legal_name: String,
+    /// ISO 3166-1 alpha-2
+    pub country: String,
+    /// passport, national_id, driving_license
+    pub document_type: String,
+    pub document_reference: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KycQueueQuery {
+    /// Defaults to pending
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewKycRequest {
+    pub decision: String, // verify, reject
+    /// Required when rejecting; shown to the user
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct CreditEntry {
+    pub id: i64,
+    pub user_id: Uuid,
+    /// Positive when earned or put back, negative when paid out
+    pub amount: Decimal,
+    pub currency: String,
+    pub source: String,
+    pub payout_id: Option<Uuid>,
+    pub note: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The caller's credits with their latest entries
+#[derive(Debug, Serialize)]
+pub struct CreditBalance {
+    pub balance: Decimal,
+    pub currency: String,
+    pub entries: Vec<CreditEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreditAdjustmentRequest {
+    pub user_id: Uuid,
+    /// Negative to take credits back
+    pub amount: Decimal,
+    /// Defaults to adjustment
+    pub source: Option<String>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Payout {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub amount: Decimal,
+    pub currency: String,
+    /// bank, stripe, crypto
+    pub method: String,
+    pub destination: String,
+    /// requested, approved, processing, paid, failed, rejected, cancelled
+    pub status: String,
+    pub provider_reference: Option<String>,
+    pub transaction_id: Option<Uuid>,
+    pub failure_reason: Option<String>,
+    pub reviewed_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct PayoutEvent {
+    pub id: i64,
+    pub payout_id: Uuid,
+    pub action: String,
+    /// `None` for the request
+    pub from_status: Option<String>,
+    pub to_status: String,
+    /// `None` for changes the platform made
+    pub actor_id: Option<Uuid>,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A payout with its state history
+#[derive(Debug, Serialize)]
+pub struct PayoutDetails {
+    pub payout: Payout,
+    pub events: Vec<PayoutEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePayoutRequest {
+    /// In the credits currency
+    pub amount: Decimal,
+    pub method: String, // bank, stripe, crypto
+    /// Bank account reference or Stripe connected account (`acct_...`); crypto payouts go to the
+    /// linked wallet
+    pub destination: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PayoutQueueQuery {
+    /// Defaults to requested
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewPayoutRequest {
+    pub decision: String, // approve, reject
+    /// Required when rejecting; shown to the user
+    pub note: Option<String>,
+}
+
+/// Confirmation that a bank payout was sent outside the platform
+#[derive(Debug, Deserialize)]
+pub struct MarkPayoutPaidRequest {
+    pub reference: String,
+}
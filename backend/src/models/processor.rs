@@ -0,0 +1,51 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// A WASM module run over a user's incoming telemetry. The module bytes are not loaded here.
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct TelemetryProcessor {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub module_sha256: String,
+    pub module_size: i32,
+    pub device_ids: Option<Vec<Uuid>>,
+    pub enabled: bool,
+    pub invocations: i64,
+    pub consecutive_failures: i32,
+    pub last_error: Option<String>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateProcessorRequest {
+    pub name: String,
+    pub description: Option<String>,
+    /// Base64-encoded WASM binary
+    pub module: String,
+    /// Devices to run on; omitted or empty means all of the caller's devices
+    pub device_ids: Option<Vec<Uuid>>,
+    pub enabled: Option<bool>,
+}
+
+/// Partial update; a new module replaces the old one and clears its failure count
+#[derive(Debug, Deserialize)]
+pub struct UpdateProcessorRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub module: Option<String>,
+    pub device_ids: Option<Vec<Uuid>>,
+    pub enabled: Option<bool>,
+}
+
+/// Run a processor against a sample without storing anything
+#[derive(Debug, Deserialize)]
+pub struct TestProcessorRequest {
+    pub telemetry: serde_json::Value,
+}
@@ -0,0 +1,66 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::collections::HashMap;
+use uuid::Uuid;
+use crate::models::mission::LegAction;
+use crate::models::swarm::GeoBounds;
+
+/// A named command sequence, developed against simulated devices
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct CommandMacro {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub device_type: String,
+    pub steps: sqlx::types::Json<Vec<LegAction>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateMacroRequest {
+    pub name: String,
+    pub device_type: String,
+    pub steps: Vec<LegAction>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RunMacroRequest {
+    /// Must be a simulated device; real hardware goes through promotion
+    pub device_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePromotionRequest {
+    pub source_kind: String, // macro, fleet_mission
+    pub source_id: Uuid,
+    /// Simulated device id -> production device id
+    pub device_map: HashMap<Uuid, Uuid>,
+    pub geofence: Option<GeoBounds>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReviewPromotionRequest {
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct SimPromotion {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub source_kind: String,
+    pub source_id: Uuid,
+    pub device_map: serde_json::Value,
+    pub geofence: Option<sqlx::types::Json<GeoBounds>>,
+    pub status: String, // invalid, pending_approval, approved, rejected, executed
+    pub checks: serde_json::Value,
+    pub diff: serde_json::Value,
+    pub reviewed_by: Option<Uuid>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub review_note: Option<String>,
+    pub executed_ref: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
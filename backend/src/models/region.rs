@@ -0,0 +1,95 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GeoPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// A named area of an org's operations. Devices inside it are assigned to it automatically.
+#[derive(Debug, Serialize, FromRow)]
+pub struct Region {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub polygon: sqlx::types::Json<Vec<GeoPoint>>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A region with how many devices it currently holds and who is on its team
+#[derive(Debug, Serialize, FromRow)]
+pub struct RegionSummary {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub region: Region,
+    pub device_count: i64,
+    pub team: Vec<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRegionRequest {
+    pub name: String,
+    pub description: Option<String>,
+    /// At least three vertices; the last connects back to the first
+    pub polygon: Vec<GeoPoint>,
+}
+
+/// Changing the polygon reassigns every device of the org
+#[derive(Debug, Deserialize)]
+pub struct UpdateRegionRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub polygon: Option<Vec<GeoPoint>>,
+}
+
+/// Org members notified of alerts raised by devices in the region; replaces the current team
+#[derive(Debug, Deserialize)]
+pub struct RegionTeamRequest {
+    pub user_ids: Vec<Uuid>,
+}
+
+/// Narrows a fleet endpoint to the devices currently in one region
+#[derive(Debug, Default, Deserialize)]
+pub struct RegionFilter {
+    pub region_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct RegionDevice {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub device_name: String,
+    pub device_type: String,
+    pub status: String,
+    pub battery_level: Option<i16>,
+    pub last_latitude: Option<f64>,
+    pub last_longitude: Option<f64>,
+    pub last_seen: Option<DateTime<Utc>>,
+    pub entered_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct StatusCount {
+    pub status: String,
+    pub devices: i64,
+}
+
+/// Fleet overview of one region
+#[derive(Debug, Serialize)]
+pub struct RegionDashboard {
+    pub region: Region,
+    pub device_count: usize,
+    pub by_status: Vec<StatusCount>,
+    pub average_battery: Option<f64>,
+    /// Devices below 20% battery
+    pub low_battery: usize,
+    /// Devices not heard from in the last hour
+    pub stale: usize,
+    pub devices: Vec<RegionDevice>,
+}
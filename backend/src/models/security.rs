@@ -0,0 +1,63 @@
+use secrecy::SecretString;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[allow(dead_code)]
+pub struct AuthEvent {
+    pub id: i64,
+    pub user_id: Option<Uuid>,
+    pub event: String, // login, login_failed, step_up_failed, ...
+    pub success: bool,
+    pub ip: Option<String>,
+    pub country: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub user_agent: Option<String>,
+    pub fingerprint: Option<String>,
+    pub risk_score: i16,
+    pub risk_reasons: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct StepUpVerifyRequest {
+    pub challenge_id: Uuid,
+    pub code: SecretString,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct UserSession {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub auth_method: String, // password, sso
+    pub ip: Option<String>,
+    pub country: Option<String>,
+    pub fingerprint: Option<String>,
+    pub risk_score: i16,
+    pub risk_reasons: serde_json::Value,
+    pub step_up_verified: bool,
+    pub status: String, // active, revoked
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct RiskySessionQuery {
+    pub min_risk: Option<i16>,
+    pub include_revoked: Option<bool>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct UpdateRiskPolicyRequest {
+    pub step_up_threshold: i16,
+    pub block_threshold: Option<i16>,
+}
@@ -0,0 +1,34 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// A sensor a device is declared to carry
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct DeviceSensor {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub sensor_type: String,
+    pub unit: String,
+    pub sampling_rate_hz: f32,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSensorRequest {
+    pub sensor_type: String,
+    pub unit: String,
+    pub sampling_rate_hz: f32,
+    pub enabled: Option<bool>,
+}
+
+/// Partial update; the sensor type is fixed once declared
+#[derive(Debug, Deserialize)]
+pub struct UpdateSensorRequest {
+    pub unit: Option<String>,
+    pub sampling_rate_hz: Option<f32>,
+    pub enabled: Option<bool>,
+}
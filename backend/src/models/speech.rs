@@ -0,0 +1,24 @@
+use serde::Deserialize;
+
+/// Options of a transcription; the audio is the raw request body, its type the Content-Type
+#[derive(Debug, Deserialize)]
+pub struct TranscribeQuery {
+    /// ISO-639-1 code of the spoken language, e.g. `en`; detected when left out
+    pub language: Option<String>,
+    /// Words to expect, e.g. robot names and command vocabulary
+    pub prompt: Option<String>,
+    pub provider: Option<String>,
+}
+
+/// Text to read out: given directly, or an assistant message of one of the caller's
+/// conversations
+#[derive(Debug, Deserialize)]
+pub struct SpeakRequest {
+    pub text: Option<String>,
+    pub message_id: Option<i64>,
+    /// A voice of the provider; the configured one when left out
+    pub voice: Option<String>,
+    /// mp3 (the default), opus, aac, flac or wav
+    pub format: Option<String>,
+    pub provider: Option<String>,
+}
@@ -0,0 +1,72 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// A user's own limits; `None` takes the platform default and 0 means no limit
+#[derive(Debug, Serialize, FromRow)]
+pub struct SpendingLimits {
+    pub user_id: Uuid,
+    pub daily_limit: Option<Decimal>,
+    pub monthly_limit: Option<Decimal>,
+    pub updated_by: Option<Uuid>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Spend against one limit, in USD
+#[derive(Debug, Serialize)]
+pub struct SpendingWindow {
+    /// `None` when there is no limit
+    pub limit: Option<Decimal>,
+    pub spent: Decimal,
+    pub remaining: Option<Decimal>,
+    pub resets_at: DateTime<Utc>,
+}
+
+/// The caller's spend this UTC day and month
+#[derive(Debug, Serialize)]
+pub struct SpendingStatus {
+    pub currency: String,
+    pub daily: SpendingWindow,
+    pub monthly: SpendingWindow,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetSpendingLimitsRequest {
+    /// In USD; omit for the platform default, 0 for no limit
+    pub daily_limit: Option<Decimal>,
+    pub monthly_limit: Option<Decimal>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct PaymentReview {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// card, crypto_invoice, token_transfer, escrow_funding
+    pub kind: String,
+    pub amount: Decimal,
+    pub currency: String,
+    pub amount_usd: Option<Decimal>,
+    pub country: Option<String>,
+    /// The rules matched, e.g. `[{"rule": "rapid_retries", "attempts": 5, "minutes": 10}]`
+    pub flags: serde_json::Value,
+    /// open, approved, rejected
+    pub status: String,
+    pub review_note: Option<String>,
+    pub reviewed_by: Option<Uuid>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PaymentReviewQuery {
+    /// Defaults to open
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResolvePaymentReviewRequest {
+    pub decision: String, // approve, reject
+    pub note: Option<String>,
+}
@@ -0,0 +1,95 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct SubscriptionPlan {
+    pub id: Uuid,
+    pub code: String,
+    pub name: String,
+    pub amount: Decimal,
+    pub currency: String,
+    pub billing_interval: String, // month, year
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Subscription {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub plan_id: Uuid,
+    pub status: String, // incomplete, active, past_due, canceled, incomplete_expired
+    pub current_period_start: Option<DateTime<Utc>>,
+    pub current_period_end: Option<DateTime<Utc>>,
+    pub cancel_at_period_end: bool,
+    pub credit_balance: Decimal,
+    pub canceled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct SubscriptionInvoice {
+    pub id: Uuid,
+    pub subscription_id: Uuid,
+    pub kind: String, // initial, renewal, proration
+    pub amount: Decimal,
+    pub currency: String,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub status: String, // open, paid, void, uncollectible
+    pub attempt_count: i32,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The current user's subscription as `GET /api/billing/subscription` reports it
+#[derive(Debug, Serialize)]
+pub struct SubscriptionDetails {
+    #[serde(flatten)]
+    pub subscription: Subscription,
+    pub plan: SubscriptionPlan,
+    /// An invoice still waiting to be paid, if any
+    pub open_invoice: Option<SubscriptionInvoice>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubscribeRequest {
+    pub plan_code: String,
+}
+
+/// Start a subscription: the first invoice's PaymentIntent is confirmed client-side with
+/// `client_secret`, and its webhook activates the subscription
+#[derive(Debug, Serialize)]
+pub struct SubscribeResponse {
+    pub subscription: Subscription,
+    pub invoice: SubscriptionInvoice,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangePlanRequest {
+    pub plan_code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePlanRequest {
+    pub code: String,
+    pub name: String,
+    pub amount: Decimal,
+    pub currency: String,
+    pub billing_interval: String,
+}
+
+/// Price changes apply from each subscriber's next renewal
+#[derive(Debug, Deserialize)]
+pub struct UpdatePlanRequest {
+    pub name: Option<String>,
+    pub amount: Option<Decimal>,
+    pub active: Option<bool>,
+}
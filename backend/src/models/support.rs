@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct SupportTicket {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub subject: String,
+    pub category: String, // general, device, billing, account, incident
+    pub priority: String, // low, normal, high, urgent
+    pub status: String, // open, waiting_on_customer, resolved, closed
+    /// standard or priority, from the user's plan when the ticket was opened
+    pub support_tier: String,
+    pub device_id: Option<Uuid>,
+    pub incident_id: Option<Uuid>,
+    pub transaction_id: Option<Uuid>,
+    pub assigned_to: Option<Uuid>,
+    pub first_response_due_at: DateTime<Utc>,
+    pub resolution_due_at: DateTime<Utc>,
+    pub first_responded_at: Option<DateTime<Utc>>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub first_response_breached_at: Option<DateTime<Utc>>,
+    pub resolution_breached_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct SupportMessage {
+    pub id: Uuid,
+    pub ticket_id: Uuid,
+    pub author_id: Option<Uuid>,
+    pub from_staff: bool,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct SupportAttachment {
+    pub id: Uuid,
+    pub ticket_id: Uuid,
+    pub message_id: Uuid,
+    pub uploaded_by: Option<Uuid>,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    #[serde(skip_serializing)]
+    pub object_key: String,
+    pub created_at: DateTime<Utc>,
+    /// Presigned link valid for a few minutes, when storage is configured
+    #[sqlx(skip)]
+    pub download_url: Option<String>,
+}
+
+/// A ticket with its whole conversation
+#[derive(Debug, Serialize)]
+pub struct SupportTicketDetails {
+    pub ticket: SupportTicket,
+    pub messages: Vec<SupportMessage>,
+    pub attachments: Vec<SupportAttachment>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTicketRequest {
+    pub subject: String,
+    /// The first message
+    pub body: String,
+    pub category: Option<String>,
+    pub priority: Option<String>,
+    pub device_id: Option<Uuid>,
+    pub incident_id: Option<Uuid>,
+    pub transaction_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TicketMessageRequest {
+    pub body: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TicketQuery {
+    pub status: Option<String>,
+}
+
+/// Filters of the staff queue; open and waiting tickets by default
+#[derive(Debug, Default, Deserialize)]
+pub struct TicketQueueQuery {
+    pub status: Option<String>,
+    pub assigned_to: Option<Uuid>,
+    /// Only tickets nobody is assigned to
+    #[serde(default)]
+    pub unassigned: bool,
+    /// Only tickets past an SLA target
+    #[serde(default)]
+    pub breached: bool,
+    pub limit: Option<i64>,
+}
+
+/// `None` unassigns
+#[derive(Debug, Deserialize)]
+pub struct AssignTicketRequest {
+    pub assignee_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateTicketRequest {
+    pub status: Option<String>,
+    pub priority: Option<String>,
+}
+
+/// Query of an attachment upload; the body is the raw file and `Content-Type` its type
+#[derive(Debug, Deserialize)]
+pub struct AttachmentUploadQuery {
+    pub file_name: String,
+    /// Message posted with the file; defaults to naming it
+    pub note: Option<String>,
+}
@@ -0,0 +1,69 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Axis-aligned area in degrees
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GeoBounds {
+    pub min_lat: f64,
+    pub min_lng: f64,
+    pub max_lat: f64,
+    pub max_lng: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct SwarmCommandRequest {
+    pub device_ids: Vec<Uuid>,
+    pub command: String, // survey
+    pub area: GeoBounds,
+    /// Survey altitude for drones (meters)
+    pub altitude: Option<f64>,
+    /// Distance between survey lanes (meters)
+    pub lane_spacing_m: Option<f64>,
+    /// Fraction of each device's top speed, 0.05-1.0
+    pub speed: Option<f64>,
+}
+
+/// Send one command to many devices at once
+#[derive(Debug, Deserialize)]
+pub struct SwarmFanOutRequest {
+    #[serde(default)]
+    pub device_ids: Vec<Uuid>,
+    /// Also target every device whose `metadata.tags` contains this tag
+    pub tag: Option<String>,
+    /// Also target every device currently in this region
+    pub region_id: Option<Uuid>,
+    pub command: String,
+    #[serde(default)]
+    pub parameters: serde_json::Value,
+    /// Devices that must accept the command; below this every dispatched device is stopped
+    pub min_success: Option<usize>,
+    /// Concurrent per-device dispatches
+    pub max_parallel: Option<usize>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct SwarmMission {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub command: String,
+    pub parameters: serde_json::Value,
+    pub status: String, // dispatched, cancelled, compensated
+    /// Fan-out report for direct fleet commands
+    pub report: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct SwarmAssignment {
+    pub device_id: Uuid,
+    pub region: sqlx::types::Json<GeoBounds>,
+    pub transit_altitude: Option<f64>,
+    pub start_offset_ms: i64,
+    pub command_count: i32,
+    pub estimated_duration_ms: i64,
+}
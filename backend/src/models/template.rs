@@ -0,0 +1,113 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// A value the importer supplies; `${name}` marks where it goes in the content
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Placeholder {
+    pub name: String,
+    pub kind: String, // device, string, number, boolean
+    pub description: Option<String>,
+    pub default: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Template {
+    pub id: Uuid,
+    pub publisher_id: Option<Uuid>,
+    pub kind: String, // automation, fleet_mission, macro
+    pub name: String,
+    pub description: Option<String>,
+    pub content: serde_json::Value,
+    pub placeholders: sqlx::types::Json<Vec<Placeholder>>,
+    pub content_sha256: String,
+    pub source_id: Uuid,
+    pub source_version: Option<i32>,
+    pub parent_template_id: Option<Uuid>,
+    pub status: String, // pending_review, published, hidden, removed, withdrawn
+    pub moderation_flags: Vec<String>,
+    pub moderation_note: Option<String>,
+    pub rating: Option<f64>,
+    pub rating_count: i32,
+    pub import_count: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PublishTemplateRequest {
+    pub kind: String,
+    pub source_id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    /// Extra placeholders already written into the source as `${name}`; device ids are
+    /// turned into placeholders automatically
+    #[serde(default)]
+    pub placeholders: Vec<Placeholder>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TemplateQuery {
+    pub kind: Option<String>,
+    /// Matched against name and description
+    pub q: Option<String>,
+    pub sort: Option<String>, // recent, rating, imports
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportTemplateRequest {
+    /// Defaults to the template name
+    pub name: Option<String>,
+    #[serde(default)]
+    pub values: HashMap<String, serde_json::Value>,
+    /// Return the filled-in content without creating anything
+    #[serde(default)]
+    pub preview: bool,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TemplateImport {
+    pub id: Uuid,
+    pub template_id: Uuid,
+    pub user_id: Uuid,
+    pub target_kind: String,
+    pub target_id: Uuid,
+    pub content_sha256: String,
+    pub imported_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RateTemplateRequest {
+    pub stars: i16,
+    pub review: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TemplateRating {
+    pub user_id: Uuid,
+    pub stars: i16,
+    pub review: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReportTemplateRequest {
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TemplateReport {
+    pub id: Uuid,
+    pub template_id: Uuid,
+    pub reporter_id: Uuid,
+    pub reason: String,
+    pub status: String, // open, resolved
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModerateTemplateRequest {
+    pub action: String, // publish, hide, remove
+    pub note: Option<String>,
+}
@@ -2,19 +2,29 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use crate::models::typed_data::TypedData;
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 #[allow(dead_code)]
 pub struct Transaction {
     pub id: Uuid,
     pub user_id: Uuid,
-    pub amount: f64,
+    pub amount: Decimal,
     pub currency: String,
     pub payment_method: String, // stripe, razorpay, crypto
     pub payment_id: String,
-    pub status: String, // pending, completed, failed
+    pub status: String, // pending, completed, failed, partially_refunded, refunded
     pub product_type: String, // software_license, documentation, hardware_guide
     pub blockchain_tx_hash: Option<String>,
+    /// Chain `blockchain_tx_hash` was sent on; `None` for the configured default chain
+    pub chain_id: Option<i64>,
+    /// Blocks mined on top of the one including `blockchain_tx_hash`, counting that block
+    pub confirmations: i32,
+    pub block_number: Option<i64>,
+    /// The coupon applied; `amount` is the price after `discount_amount` came off
+    pub coupon_id: Option<Uuid>,
+    pub discount_amount: Decimal,
     pub created_at: DateTime<Utc>,
 }
 
@@ -23,6 +33,7 @@ pub struct Transaction {
 pub struct CreatePaymentRequest {
     pub payment_method: String,
     pub product_type: String,
+    pub coupon_code: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -30,6 +41,222 @@ pub struct CreatePaymentRequest {
 pub struct PaymentResponse {
     pub payment_id: String,
     pub client_secret: Option<String>,
-    pub amount: f64,
+    pub amount: Decimal,
     pub currency: String,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRazorpayOrderRequest {
+    pub product_type: String,
+    pub coupon_code: Option<String>,
+}
+
+/// What Razorpay Checkout hands the page after a successful payment
+#[derive(Debug, Deserialize)]
+pub struct RazorpayPaymentCallback {
+    pub razorpay_order_id: String,
+    pub razorpay_payment_id: String,
+    pub razorpay_signature: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ProductEntitlement {
+    pub id: Uuid,
+    pub product_type: String,
+    pub transaction_id: Option<Uuid>,
+    pub granted_at: DateTime<Utc>,
+    /// Set while the payment is disputed; a frozen entitlement grants nothing
+    pub frozen_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Refund {
+    pub id: Uuid,
+    pub transaction_id: Uuid,
+    pub amount: Decimal,
+    pub currency: String,
+    pub provider: Option<String>,
+    pub provider_refund_id: Option<String>,
+    pub status: String, // requested, pending, succeeded, failed
+    pub manual: bool,
+    pub reason: Option<String>,
+    pub failure_reason: Option<String>,
+    pub requested_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefundRequest {
+    /// Defaults to everything not yet refunded
+    #[serde(default)]
+    pub amount: Option<Decimal>,
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Admin only: record a refund made outside the payment provider, e.g. for crypto payments
+    #[serde(default)]
+    pub manual: bool,
+}
+
+/// A refund as the provider reports it on creation
+#[derive(Debug, Deserialize)]
+pub struct ProviderRefund {
+    pub id: String,
+    pub status: String,
+}
+
+/// A PaymentIntent as Stripe reports it on creation
+#[derive(Debug, Deserialize)]
+pub struct ProviderPaymentIntent {
+    pub id: String,
+    pub status: String,
+    pub client_secret: Option<String>,
+}
+
+/// A token transfer out of the caller's linked wallet, submitted by the relayer
+#[derive(Debug, Deserialize)]
+pub struct TransferRequest {
+    pub to: String,
+    /// In whole tokens, e.g. `"12.5"`
+    pub amount: String,
+    /// Defaults to the configured default chain
+    pub chain_id: Option<u64>,
+}
+
+/// The transfer again, with the typed data issued for it by the prepare step signed by the wallet
+#[derive(Debug, Deserialize)]
+pub struct RelayedTransferRequest {
+    #[serde(flatten)]
+    pub transfer: TransferRequest,
+    pub typed_data: TypedData,
+    /// 65-byte `eth_signTypedData_v4` signature, hex with `0x`
+    pub signature: String,
+}
+
+/// A page of the caller's transactions, newest first. Pass the id of a page's last transaction
+/// as `before` for the next page, or of its first as `after` to go back.
+#[derive(Debug, Deserialize)]
+pub struct TransactionListQuery {
+    pub before: Option<Uuid>,
+    pub after: Option<Uuid>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransactionPage {
+    pub transactions: Vec<Transaction>,
+    /// All of the caller's transactions
+    pub total: i64,
+    /// Whether more transactions lie past this page in the direction paged
+    pub has_more: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransactionExportQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub format: Option<String>, // csv (default)
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct CryptoInvoice {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub transaction_id: Uuid,
+    pub chain_id: i64,
+    pub token_contract: String,
+    pub deposit_address: String,
+    /// Exactly this much must arrive; in whole tokens
+    pub amount: String,
+    pub raw_amount: String,
+    pub symbol: String,
+    pub status: String, // open, paid, expired
+    pub payer_address: Option<String>,
+    pub tx_hash: Option<String>,
+    pub expires_at: DateTime<Utc>,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    /// EIP-681 payment request for wallets and QR codes
+    #[sqlx(skip)]
+    pub payment_uri: Option<String>,
+    /// The amount in the fiat currency asked for
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fiat: Option<FiatAmount>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FiatAmount {
+    pub currency: String,
+    pub amount: Decimal,
+    /// Priced from a rate older than a minute
+    pub stale: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateInvoiceRequest {
+    pub product_type: String,
+    /// Defaults to the configured default chain
+    pub chain_id: Option<u64>,
+    /// Fiat currency to show the amount in as well, e.g. `eur`
+    pub currency: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InvoiceQuery {
+    pub currency: Option<String>,
+}
+
+/// Admin listing of transactions across all users, newest first
+#[derive(Debug, Deserialize)]
+pub struct AdminTransactionQuery {
+    pub user_id: Option<Uuid>,
+    /// stripe, razorpay or crypto
+    pub payment_method: Option<String>,
+    pub status: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// Only flagged (`true`) or unflagged (`false`) transactions
+    pub discrepancy: Option<bool>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// A transaction with the settlement and discrepancy details staff reconcile against
+#[derive(Debug, Serialize, FromRow)]
+pub struct ReconciliationTransaction {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub transaction: Transaction,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub failure_reason: Option<String>,
+    pub discrepancy_note: Option<String>,
+    pub discrepancy_flagged_by: Option<Uuid>,
+    pub discrepancy_flagged_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiscrepancyRequest {
+    /// What does not match, e.g. the amount the provider settled
+    pub note: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecheckStuckRequest {
+    /// Pending for at least this long; defaults to 30
+    pub older_than_minutes: Option<i64>,
+}
+
+/// What asking the provider about a pending transaction found
+#[derive(Debug, Serialize)]
+pub struct RecheckResult {
+    pub transaction_id: Uuid,
+    pub payment_method: String,
+    /// The provider's own status: the PaymentIntent's, the order's latest payment's, or the
+    /// on-chain outcome
+    pub provider_status: Option<String>,
+    /// completed, failed, mismatch (paid a different amount), pending, ignored (settled
+    /// meanwhile) or error
+    pub outcome: &'static str,
+    pub error: Option<String>,
+}
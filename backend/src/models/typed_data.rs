@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A member of an EIP-712 struct type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypedField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// EIP-712 typed data in the JSON form `eth_signTypedData_v4` takes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypedData {
+    /// Struct types by name, including `EIP712Domain`
+    pub types: BTreeMap<String, Vec<TypedField>>,
+    pub primary_type: String,
+    pub domain: serde_json::Value,
+    pub message: serde_json::Value,
+}
+
+/// Typed data issued by a prepare step, as the wallet signed it
+#[derive(Debug, Deserialize)]
+pub struct SignedTypedData {
+    pub typed_data: TypedData,
+    /// 65-byte `eth_signTypedData_v4` signature, hex with `0x`
+    pub signature: String,
+}
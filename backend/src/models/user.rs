@@ -12,6 +12,12 @@ pub struct User {
     pub username: String,
     pub password_hash: String,
     pub wallet_address: Option<String>,
+    /// Chain the wallet last signed in on
+    #[sqlx(default)]
+    pub wallet_chain_id: Option<i64>,
+    /// Primary ENS name of the wallet, verified by forward resolution
+    #[sqlx(default)]
+    pub ens_name: Option<String>,
     pub is_verified: bool,
     pub is_premium: bool,
     pub created_at: DateTime<Utc>,
@@ -55,6 +61,43 @@ pub struct UserResponse {
     pub email: String,
     pub username: String,
     pub wallet_address: Option<String>,
+    #[sqlx(default)]
+    pub wallet_chain_id: Option<i64>,
+    #[sqlx(default)]
+    pub ens_name: Option<String>,
     pub is_verified: bool,
     pub is_premium: bool,
 }
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SiweNonceRequest {
+    /// When given, the response includes a ready-to-sign message for this address. An ENS name
+    /// such as `name.eth` is resolved to its address first.
+    pub address: Option<String>,
+    pub chain_id: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SiweLoginRequest {
+    /// The EIP-4361 message exactly as signed
+    pub message: String,
+    /// 65-byte `personal_sign` signature, hex with `0x`
+    pub signature: String,
+}
+
+/// A change to the account's linked wallet, authorized by the wallet itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WalletAction {
+    Link,
+    Unlink,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WalletAuthorizationRequest {
+    pub action: WalletAction,
+    /// The wallet to link; unlinking always concerns the linked wallet
+    pub address: Option<String>,
+    /// The chain the wallet is on. Defaults to the linked wallet's chain, else the default chain.
+    pub chain_id: Option<u64>,
+}
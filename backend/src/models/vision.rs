@@ -0,0 +1,15 @@
+use serde::Deserialize;
+
+/// An image analysis request: a JSON body naming the image by URL, or a multipart form with
+/// the same fields and the image uploaded as `image`
+#[derive(Debug, Default, Deserialize)]
+pub struct VisionRequest {
+    /// What to look for, e.g. "Is there damage on this solar panel?"
+    pub prompt: String,
+    /// Public https URL of the image, fetched by the provider
+    pub image_url: Option<String>,
+    /// A vision-capable model; the provider's vision model when left out
+    pub model: Option<String>,
+    pub provider: Option<String>,
+    pub max_tokens: Option<u32>,
+}
@@ -0,0 +1,96 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, FromRow)]
+#[allow(dead_code)]
+pub struct DeviceWebhook {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub url: String,
+    pub description: Option<String>,
+    pub events: Vec<String>,
+    pub device_ids: Option<Vec<Uuid>>,
+    pub enabled: bool,
+    /// Bumped by each secret rotation
+    pub secret_version: i32,
+    #[serde(skip_serializing)]
+    pub previous_secret_version: Option<i32>,
+    /// Deliveries are also signed with the previous secret until then
+    pub previous_secret_expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    pub description: Option<String>,
+    pub events: Vec<String>,
+    /// Devices to report on; omitted or empty means all of the caller's devices. Transaction
+    /// events are sent regardless.
+    pub device_ids: Option<Vec<Uuid>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateWebhookRequest {
+    pub url: Option<String>,
+    pub description: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub device_ids: Option<Vec<Uuid>>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: String,
+    pub payload: serde_json::Value,
+    pub status: String, // pending, delivered, failed
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WebhookDeliveryAttempt {
+    pub delivery_id: Uuid,
+    pub attempt: i32,
+    /// Replayed by the owner rather than sent by the delivery job
+    pub manual: bool,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+    /// Snapshots of the exchange; bodies are cut off at 8 KiB
+    pub request_headers: Option<serde_json::Value>,
+    pub request_body: Option<String>,
+    pub response_headers: Option<serde_json::Value>,
+    pub response_body: Option<String>,
+    pub attempted_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DeliveryListQuery {
+    pub status: Option<String>, // pending, delivered, failed
+    pub event: Option<String>,
+    /// Only deliveries created before this time, for paging back through the log
+    pub before: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DeliverySummaryQuery {
+    /// Window to summarize, in hours; defaults to 24
+    pub hours: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RotateSecretRequest {
+    /// How long the previous secret keeps signing deliveries alongside the new one; defaults to 24
+    pub grace_period_hours: Option<i64>,
+}
@@ -0,0 +1,55 @@
+use actix_web::web;
+use crate::controllers::{
+    compliance_ctrl, coupon_ctrl, deprecation_ctrl, dispute_ctrl, escrow_ctrl, key_ctrl, kyc_ctrl, ledger_ctrl,
+    payout_ctrl, reconciliation_ctrl, spending_ctrl, support_ctrl, template_ctrl,
+};
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/admin")
+            .route("/keys", web::get().to(key_ctrl::list_keys))
+            .route("/keys/{purpose}/rotate", web::post().to(key_ctrl::rotate_key))
+            .route("/deprecations", web::get().to(deprecation_ctrl::get_deprecation_report))
+            .route("/geo-blocks", web::get().to(compliance_ctrl::list_blocked_attempts))
+            .route("/geo-allowlist", web::get().to(compliance_ctrl::list_allowlist))
+            .route("/geo-allowlist", web::post().to(compliance_ctrl::create_allowlist_entry))
+            .route("/geo-allowlist/{entry_id}/approve", web::post().to(compliance_ctrl::approve_override))
+            .route("/geo-allowlist/{entry_id}/reject", web::post().to(compliance_ctrl::reject_override))
+            .route("/templates/review", web::get().to(template_ctrl::list_review_queue))
+            .route("/templates/{template_id}/moderate", web::post().to(template_ctrl::moderate_template))
+            .route("/support/tickets", web::get().to(support_ctrl::list_queue))
+            .route("/support/tickets/{ticket_id}", web::get().to(support_ctrl::admin_get_ticket))
+            .route("/support/tickets/{ticket_id}", web::patch().to(support_ctrl::update_ticket))
+            .route("/support/tickets/{ticket_id}/assign", web::post().to(support_ctrl::assign_ticket))
+            .route("/support/tickets/{ticket_id}/messages", web::post().to(support_ctrl::staff_reply))
+            .route("/escrows", web::get().to(escrow_ctrl::list_queue))
+            .route("/escrows/{escrow_id}", web::get().to(escrow_ctrl::admin_get_escrow))
+            .route("/escrows/{escrow_id}/resolve", web::post().to(escrow_ctrl::resolve_escrow))
+            .route("/transactions", web::get().to(reconciliation_ctrl::list_transactions))
+            .route("/transactions/recheck", web::post().to(reconciliation_ctrl::recheck_stuck))
+            .route("/transactions/{transaction_id}", web::get().to(reconciliation_ctrl::get_transaction))
+            .route("/transactions/{transaction_id}/discrepancy", web::put().to(reconciliation_ctrl::flag_discrepancy))
+            .route("/transactions/{transaction_id}/discrepancy", web::delete().to(reconciliation_ctrl::clear_discrepancy))
+            .route("/transactions/{transaction_id}/recheck", web::post().to(reconciliation_ctrl::recheck_transaction))
+            .route("/kyc", web::get().to(kyc_ctrl::list_queue))
+            .route("/kyc/{user_id}/review", web::post().to(kyc_ctrl::review_kyc))
+            .route("/payouts", web::get().to(payout_ctrl::list_queue))
+            .route("/payouts/{payout_id}", web::get().to(payout_ctrl::admin_get_payout))
+            .route("/payouts/{payout_id}/review", web::post().to(payout_ctrl::review_payout))
+            .route("/payouts/{payout_id}/paid", web::post().to(payout_ctrl::mark_paid))
+            .route("/credits", web::post().to(payout_ctrl::adjust_credits))
+            .route("/payment-reviews", web::get().to(spending_ctrl::list_reviews))
+            .route("/payment-reviews/{review_id}/resolve", web::post().to(spending_ctrl::resolve_review))
+            .route("/spending-limits/{user_id}", web::get().to(spending_ctrl::admin_get_spending))
+            .route("/spending-limits/{user_id}", web::put().to(spending_ctrl::set_limits))
+            .route("/ledger/trial-balance", web::get().to(ledger_ctrl::trial_balance))
+            .route("/ledger/reconcile", web::get().to(ledger_ctrl::reconcile))
+            .route("/coupons", web::get().to(coupon_ctrl::list_coupons))
+            .route("/coupons", web::post().to(coupon_ctrl::create_coupon))
+            .route("/coupons/{coupon_id}", web::patch().to(coupon_ctrl::update_coupon))
+            .route("/disputes", web::get().to(dispute_ctrl::list_disputes))
+            .route("/disputes/{dispute_id}", web::get().to(dispute_ctrl::get_dispute))
+            .route("/disputes/{dispute_id}/evidence", web::post().to(dispute_ctrl::submit_evidence))
+            .route("/disputes/{dispute_id}/resolve", web::post().to(dispute_ctrl::resolve_dispute))
+    );
+}
@@ -1,5 +1,12 @@
 use actix_web::web;
-use crate::controllers::ai_ctrl;
+use crate::controllers::{
+    ai_budget_ctrl, ai_ctrl, conversation_ctrl, embedding_ctrl, fine_tune_ctrl, insight_ctrl, rag_ctrl, speech_ctrl,
+    vision_ctrl,
+};
+use crate::services::fine_tune_services::MAX_TRAINING_BYTES;
+use crate::services::rag_services::MAX_UPLOAD_BYTES;
+use crate::services::transcription_services::MAX_AUDIO_BYTES;
+use crate::services::vision_services::MAX_IMAGE_BYTES;
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -9,5 +16,41 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             .route("/embeddings", web::post().to(ai_ctrl::generate_embeddings))
             .route("/models", web::get().to(ai_ctrl::get_models))
             .route("/health", web::get().to(ai_ctrl::health_check))
+            .route("/budget", web::get().to(ai_budget_ctrl::get_budget))
+            .route("/estimate", web::post().to(ai_budget_ctrl::estimate_cost))
+            .route("/conversations", web::get().to(conversation_ctrl::list_conversations))
+            .route("/conversations/{conversation_id}", web::get().to(conversation_ctrl::get_conversation))
+            .route("/conversations/{conversation_id}", web::delete().to(conversation_ctrl::delete_conversation))
+            .route("/index", web::post().to(embedding_ctrl::index_documents))
+            .route("/search", web::post().to(embedding_ctrl::search))
+            .service(
+                web::resource("/documents")
+                    .app_data(web::PayloadConfig::new(MAX_UPLOAD_BYTES))
+                    .route(web::get().to(rag_ctrl::list_documents))
+                    .route(web::post().to(rag_ctrl::upload_document)),
+            )
+            .route("/documents/{document_id}", web::delete().to(rag_ctrl::delete_document))
+            .route("/ask", web::post().to(rag_ctrl::ask))
+            .service(
+                web::resource("/vision")
+                    // Room for the image and the rest of the form
+                    .app_data(web::PayloadConfig::new(MAX_IMAGE_BYTES + 64 * 1024))
+                    .route(web::post().to(vision_ctrl::analyze_image)),
+            )
+            .service(
+                web::resource("/transcribe")
+                    .app_data(web::PayloadConfig::new(MAX_AUDIO_BYTES))
+                    .route(web::post().to(speech_ctrl::transcribe)),
+            )
+            .route("/speak", web::post().to(speech_ctrl::speak))
+            .route("/insights/{device_id}", web::post().to(insight_ctrl::device_insight))
+            .service(
+                web::resource("/fine-tunes/files")
+                    .app_data(web::PayloadConfig::new(MAX_TRAINING_BYTES))
+                    .route(web::post().to(fine_tune_ctrl::upload_training_file)),
+            )
+            .route("/fine-tunes", web::get().to(fine_tune_ctrl::list_fine_tunes))
+            .route("/fine-tunes", web::post().to(fine_tune_ctrl::start_fine_tune))
+            .route("/fine-tunes/{fine_tune_id}", web::get().to(fine_tune_ctrl::get_fine_tune))
     );
 }
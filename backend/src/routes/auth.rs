@@ -1,5 +1,5 @@
 use actix_web::web;
-use crate::controllers::auth_ctrl;
+use crate::controllers::{auth_ctrl, security_ctrl, siwe_ctrl};
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -9,5 +9,9 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             .route("/profile", web::get().to(auth_ctrl::get_profile))
             .route("/send-verification-email", web::post().to(auth_ctrl::send_verification_email))
             .route("/verify-email", web::post().to(auth_ctrl::verify_email))
+            .route("/siwe/nonce", web::post().to(siwe_ctrl::get_nonce))
+            .route("/siwe/verify", web::post().to(siwe_ctrl::verify))
+            .route("/step-up/verify", web::post().to(security_ctrl::verify_step_up))
+            .route("/events", web::get().to(security_ctrl::list_auth_events))
     );
 }
@@ -0,0 +1,19 @@
+use actix_web::web;
+use crate::controllers::automation_ctrl;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/automations")
+            .route("", web::get().to(automation_ctrl::list_automations))
+            .route("", web::post().to(automation_ctrl::create_automation))
+            .route("/flags", web::get().to(automation_ctrl::list_flags))
+            .route("/hooks/{token}", web::post().to(automation_ctrl::receive_hook))
+            .route("/{automation_id}", web::get().to(automation_ctrl::get_automation))
+            .route("/{automation_id}", web::patch().to(automation_ctrl::update_automation))
+            .route("/{automation_id}", web::delete().to(automation_ctrl::delete_automation))
+            .route("/{automation_id}/versions", web::get().to(automation_ctrl::list_versions))
+            .route("/{automation_id}/runs", web::get().to(automation_ctrl::list_runs))
+            .route("/{automation_id}/dry-run", web::post().to(automation_ctrl::dry_run))
+            .route("/{automation_id}/hook-token", web::post().to(automation_ctrl::rotate_hook_token))
+    );
+}
@@ -0,0 +1,19 @@
+use actix_web::web;
+use crate::controllers::billing_ctrl;
+
+/// Subscription plans and the current user's subscription. Payments are settled by the Stripe
+/// webhook under /api/blockchain.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/billing")
+            .route("/plans", web::get().to(billing_ctrl::list_plans))
+            .route("/plans", web::post().to(billing_ctrl::create_plan))
+            .route("/plans/{plan_id}", web::patch().to(billing_ctrl::update_plan))
+            .route("/subscription", web::get().to(billing_ctrl::get_subscription))
+            .route("/subscription", web::post().to(billing_ctrl::subscribe))
+            .route("/subscription", web::patch().to(billing_ctrl::change_plan))
+            .route("/subscription", web::delete().to(billing_ctrl::cancel_subscription))
+            .route("/subscription/resume", web::post().to(billing_ctrl::resume_subscription))
+            .route("/invoices", web::get().to(billing_ctrl::list_invoices))
+    );
+}
@@ -1,16 +1,57 @@
-use actix_web::web;
-use crate::controllers::blockchain_ctrl;
+use actix_web::{middleware::from_fn, web};
+use crate::controllers::{
+    blockchain_ctrl, chain_ctrl, coupon_ctrl, device_certificate_ctrl, invoice_ctrl, onchain_event_ctrl,
+    payment_webhook_ctrl, receipt_ctrl, spending_ctrl, transaction_ctrl, transaction_export_ctrl, transfer_ctrl,
+    wallet_ctrl,
+};
+use crate::middleware::idempotency;
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/api/blockchain")
             .route("/nonce", web::post().to(blockchain_ctrl::get_nonce))
             .route("/verify-signature", web::post().to(blockchain_ctrl::verify_signature))
-            .route("/link-wallet", web::post().to(blockchain_ctrl::link_wallet))
-            .route("/transactions", web::get().to(blockchain_ctrl::get_transactions))
-            .route("/payment", web::post().to(blockchain_ctrl::create_payment))
+            .route("/link-wallet", web::post().to(wallet_ctrl::link_wallet))
+            .route("/wallet", web::delete().to(wallet_ctrl::unlink_wallet))
+            .route("/wallet/authorization", web::post().to(wallet_ctrl::prepare_authorization))
+            .route("/transactions", web::get().to(transaction_ctrl::get_transactions))
+            .route("/transactions/export", web::get().to(transaction_export_ctrl::export_transactions))
+            .route("/transactions/{transaction_id}/refund", web::post().to(payment_webhook_ctrl::refund_transaction))
+            .route("/transactions/{transaction_id}/receipt.pdf", web::get().to(receipt_ctrl::download_receipt))
+            // Retries carrying the same Idempotency-Key replay the first response
+            .service(
+                web::resource("/payment")
+                    .wrap(from_fn(idempotency))
+                    .route(web::post().to(blockchain_ctrl::create_payment)),
+            )
+            .service(
+                web::resource("/invoices")
+                    .wrap(from_fn(idempotency))
+                    .route(web::post().to(invoice_ctrl::create_invoice)),
+            )
+            .route("/invoices/{invoice_id}", web::get().to(invoice_ctrl::get_invoice))
+            .route("/spending", web::get().to(spending_ctrl::get_spending))
+            .route("/coupons/validate", web::post().to(coupon_ctrl::validate_coupon))
+            .route("/transfer/prepare", web::post().to(transfer_ctrl::prepare))
+            .service(
+                web::resource("/transfer")
+                    .wrap(from_fn(idempotency))
+                    .route(web::post().to(transfer_ctrl::transfer)),
+            )
             .route("/verify-tx/{tx_hash}", web::get().to(blockchain_ctrl::verify_transaction))
             .route("/balance", web::get().to(blockchain_ctrl::get_balance))
             .route("/health", web::get().to(blockchain_ctrl::health_check))
+            .route("/chains", web::get().to(chain_ctrl::list_chains))
+            .route("/gas", web::get().to(chain_ctrl::get_gas))
+            .route("/rates", web::get().to(chain_ctrl::get_rates))
+            .route("/chains/{chain_id}/balance", web::get().to(chain_ctrl::get_balance))
+            .route("/chains/{chain_id}/transactions/{tx_hash}", web::get().to(chain_ctrl::verify_transaction))
+            .route("/events", web::get().to(onchain_event_ctrl::list_events))
+            .route("/nft/devices/{device_id}", web::get().to(device_certificate_ctrl::get_token_metadata))
+            .route("/entitlements", web::get().to(payment_webhook_ctrl::list_entitlements))
+            .route("/razorpay/orders", web::post().to(payment_webhook_ctrl::create_razorpay_order))
+            .route("/razorpay/verify", web::post().to(payment_webhook_ctrl::razorpay_callback))
+            .route("/webhooks/stripe", web::post().to(payment_webhook_ctrl::stripe_webhook))
+            .route("/webhooks/razorpay", web::post().to(payment_webhook_ctrl::razorpay_webhook))
     );
 }
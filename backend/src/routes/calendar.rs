@@ -0,0 +1,28 @@
+use actix_web::web;
+use crate::controllers::calendar_ctrl;
+
+/// Maintenance windows, on-call rotations and their calendar feeds. The `.ics` feed, the Google
+/// OAuth callback and Google push notifications authenticate without a session.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/calendar")
+            .route("/feeds", web::get().to(calendar_ctrl::list_feeds))
+            .route("/feeds", web::post().to(calendar_ctrl::create_feed))
+            .route("/feeds/{feed_id}.ics", web::get().to(calendar_ctrl::get_feed_ics))
+            .route("/feeds/{feed_id}", web::delete().to(calendar_ctrl::delete_feed))
+            .route("/feeds/{feed_id}/rotate", web::post().to(calendar_ctrl::rotate_feed_url))
+            .route("/google", web::get().to(calendar_ctrl::get_google_connection))
+            .route("/google", web::delete().to(calendar_ctrl::disconnect_google))
+            .route("/google/callback", web::get().to(calendar_ctrl::google_callback))
+            .route("/google/connect", web::post().to(calendar_ctrl::connect_google))
+            .route("/google/notifications", web::post().to(calendar_ctrl::google_notification))
+            .route("/maintenance", web::get().to(calendar_ctrl::list_windows))
+            .route("/maintenance", web::post().to(calendar_ctrl::create_window))
+            .route("/maintenance/{window_id}", web::patch().to(calendar_ctrl::update_window))
+            .route("/maintenance/{window_id}", web::delete().to(calendar_ctrl::cancel_window))
+            .route("/on-call", web::get().to(calendar_ctrl::list_rotations))
+            .route("/on-call", web::post().to(calendar_ctrl::create_rotation))
+            .route("/on-call/{rotation_id}", web::delete().to(calendar_ctrl::delete_rotation))
+            .route("/on-call/{rotation_id}/shifts", web::get().to(calendar_ctrl::list_shifts))
+    );
+}
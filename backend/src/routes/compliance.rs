@@ -0,0 +1,9 @@
+use actix_web::web;
+use crate::controllers::compliance_ctrl;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/compliance")
+            .route("/appeals", web::post().to(compliance_ctrl::submit_appeal))
+    );
+}
@@ -1,5 +1,5 @@
 use actix_web::web;
-use crate::controllers::dashboard_ctrl;
+use crate::controllers::{dashboard_ctrl, public_stats_ctrl};
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -7,6 +7,6 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             .route("/overview", web::get().to(dashboard_ctrl::get_overview))
             .route("/activity", web::get().to(dashboard_ctrl::get_activity))
             .route("/quick-stats", web::get().to(dashboard_ctrl::get_quick_stats))
-            .route("/public-stats", web::get().to(dashboard_ctrl::get_public_stats))
+            .route("/public-stats", web::get().to(public_stats_ctrl::get_public_stats))
     );
 }
@@ -0,0 +1,25 @@
+use actix_web::{middleware::from_fn, web};
+use crate::controllers::escrow_ctrl;
+use crate::middleware::idempotency;
+
+/// Escrows between users. Admins resolve disputes under /api/admin/escrows.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/escrows")
+            .route("", web::get().to(escrow_ctrl::list_escrows))
+            .route("", web::post().to(escrow_ctrl::create_escrow))
+            .route("/{escrow_id}", web::get().to(escrow_ctrl::get_escrow))
+            .route("/{escrow_id}/fund/prepare", web::post().to(escrow_ctrl::prepare_funding))
+            // Retries carrying the same Idempotency-Key replay the first response
+            .service(
+                web::resource("/{escrow_id}/fund")
+                    .wrap(from_fn(idempotency))
+                    .route(web::post().to(escrow_ctrl::fund_escrow)),
+            )
+            .route("/{escrow_id}/fulfill", web::post().to(escrow_ctrl::fulfill_escrow))
+            .route("/{escrow_id}/release", web::post().to(escrow_ctrl::release_escrow))
+            .route("/{escrow_id}/refund", web::post().to(escrow_ctrl::refund_escrow))
+            .route("/{escrow_id}/dispute", web::post().to(escrow_ctrl::dispute_escrow))
+            .route("/{escrow_id}/cancel", web::post().to(escrow_ctrl::cancel_escrow))
+    );
+}
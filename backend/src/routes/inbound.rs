@@ -0,0 +1,19 @@
+use actix_web::web;
+use crate::controllers::inbound_email_ctrl;
+
+/// Provider webhooks for inbound email, which authenticate by signature or shared token
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/inbound/email")
+            .service(
+                web::resource("/mailgun")
+                    .app_data(web::FormConfig::default().limit(1024 * 1024))
+                    .route(web::post().to(inbound_email_ctrl::mailgun_inbound)),
+            )
+            .service(
+                web::resource("/ses")
+                    .app_data(web::PayloadConfig::new(1024 * 1024))
+                    .route(web::post().to(inbound_email_ctrl::ses_inbound)),
+            )
+    );
+}
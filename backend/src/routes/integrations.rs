@@ -0,0 +1,21 @@
+use actix_web::web;
+use crate::controllers::integration_ctrl;
+
+/// Integration tokens are managed with a session; everything under `/zapier` authenticates with
+/// one of those tokens in `X-API-Key`.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/integrations")
+            .route("/tokens", web::get().to(integration_ctrl::list_tokens))
+            .route("/tokens", web::post().to(integration_ctrl::create_token))
+            .route("/tokens/{token_id}", web::delete().to(integration_ctrl::delete_token))
+            .route("/zapier/me", web::get().to(integration_ctrl::me))
+            .route("/zapier/triggers/new-devices", web::get().to(integration_ctrl::poll_new_devices))
+            .route("/zapier/triggers/new-alerts", web::get().to(integration_ctrl::poll_new_alerts))
+            .route("/zapier/triggers/completed-commands", web::get().to(integration_ctrl::poll_completed_commands))
+            .route("/zapier/hooks", web::post().to(integration_ctrl::subscribe_hook))
+            .route("/zapier/hooks/{hook_id}", web::delete().to(integration_ctrl::unsubscribe_hook))
+            .route("/zapier/actions/send-command", web::post().to(integration_ctrl::send_command))
+            .route("/zapier/actions/acknowledge-incident", web::post().to(integration_ctrl::acknowledge_incident))
+    );
+}
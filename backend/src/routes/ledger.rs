@@ -0,0 +1,12 @@
+use actix_web::web;
+use crate::controllers::ledger_ctrl;
+
+/// The caller's credits as the double-entry ledger has them. Admins check the whole ledger
+/// under /api/admin/ledger.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/ledger")
+            .route("/balance", web::get().to(ledger_ctrl::get_balance))
+            .route("/entries", web::get().to(ledger_ctrl::list_lines)),
+    );
+}
@@ -3,3 +3,20 @@ pub mod ai;
 pub mod robotics;
 pub mod blockchain;
 pub mod dashboard;
+pub mod orgs;
+pub mod sso;
+pub mod scim;
+pub mod notifications;
+pub mod admin;
+pub mod compliance;
+pub mod automations;
+pub mod templates;
+pub mod inbound;
+pub mod calendar;
+pub mod integrations;
+pub mod billing;
+pub mod open_data;
+pub mod support;
+pub mod escrows;
+pub mod payouts;
+pub mod ledger;
@@ -0,0 +1,10 @@
+use actix_web::web;
+use crate::controllers::notification_ctrl;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/notifications")
+            .route("", web::get().to(notification_ctrl::list_notifications))
+            .route("/{notification_id}/read", web::post().to(notification_ctrl::mark_read))
+    );
+}
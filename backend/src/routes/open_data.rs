@@ -0,0 +1,29 @@
+use actix_governor::{Governor, GovernorConfigBuilder};
+use actix_web::web;
+use crate::controllers::open_data_ctrl;
+
+/// The public research dataset catalog, and publishing for users who opted in. Rate limited
+/// more tightly than the rest of the API since the catalog needs no account.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    // One request every 2 seconds per IP, in bursts of up to 30
+    let governor_conf = GovernorConfigBuilder::default()
+        .per_second(2)
+        .burst_size(30)
+        .finish()
+        .unwrap();
+
+    cfg.service(
+        web::scope("/api/open-data")
+            .wrap(Governor::new(&governor_conf))
+            .route("/licenses", web::get().to(open_data_ctrl::list_licenses))
+            .route("/stats", web::get().to(open_data_ctrl::get_stats))
+            .route("/datasets", web::get().to(open_data_ctrl::list_datasets))
+            .route("/datasets/{slug}", web::get().to(open_data_ctrl::get_dataset))
+            .route("/datasets/{slug}/download", web::get().to(open_data_ctrl::download_dataset))
+            .route("/opt-in", web::post().to(open_data_ctrl::opt_in))
+            .route("/opt-in", web::delete().to(open_data_ctrl::opt_out))
+            .route("/my-datasets", web::get().to(open_data_ctrl::list_my_datasets))
+            .route("/my-datasets", web::post().to(open_data_ctrl::publish_dataset))
+            .route("/my-datasets/{dataset_id}", web::delete().to(open_data_ctrl::withdraw_dataset))
+    );
+}
@@ -0,0 +1,68 @@
+use actix_web::web;
+use crate::controllers::{
+    audit_ctrl, backup_ctrl, break_glass_ctrl, capacity_ctrl, firmware_ctrl, metric_ctrl, org_ctrl, region_ctrl,
+    scim_ctrl, session_ctrl,
+};
+use crate::services::backup_services::MAX_BACKUP_BYTES;
+use crate::services::firmware_services::MAX_FIRMWARE_BYTES;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/orgs")
+            .route("", web::get().to(org_ctrl::list_orgs))
+            .route("", web::post().to(org_ctrl::create_org))
+            .route("/{org_id}/privacy", web::get().to(org_ctrl::get_privacy_settings))
+            .route("/{org_id}/privacy", web::put().to(org_ctrl::update_privacy_settings))
+            // One resource for both methods: a second resource on the same path would never see POSTs
+            .service(
+                web::resource("/{org_id}/firmware")
+                    .app_data(web::PayloadConfig::new(MAX_FIRMWARE_BYTES))
+                    .route(web::get().to(firmware_ctrl::list_releases))
+                    .route(web::post().to(firmware_ctrl::upload_firmware)),
+            )
+            .route("/{org_id}/firmware/signing-keys", web::get().to(firmware_ctrl::list_signing_keys))
+            .route("/{org_id}/firmware/signing-keys", web::post().to(firmware_ctrl::add_signing_key))
+            .route("/{org_id}/firmware/signing-keys/{key_id}", web::delete().to(firmware_ctrl::revoke_signing_key))
+            .route("/{org_id}/scim-tokens", web::post().to(scim_ctrl::create_token))
+            .route("/{org_id}/scim-tokens/{token_id}", web::delete().to(scim_ctrl::revoke_token))
+            .route("/{org_id}/audit-logs", web::get().to(audit_ctrl::list_audit_logs))
+            .route("/{org_id}/transactions", web::get().to(audit_ctrl::list_transactions))
+            .route("/{org_id}/devices/{device_id}/history", web::get().to(audit_ctrl::get_device_history))
+            .route("/{org_id}/export", web::get().to(audit_ctrl::export))
+            .route("/{org_id}/capacity-report", web::get().to(capacity_ctrl::get_capacity_report))
+            .route("/{org_id}/backups", web::get().to(backup_ctrl::list_backups))
+            .route("/{org_id}/backups", web::post().to(backup_ctrl::create_backup))
+            .route("/{org_id}/backups/{backup_id}", web::get().to(backup_ctrl::download_backup))
+            .route("/{org_id}/backups/{backup_id}/restore", web::post().to(backup_ctrl::restore_backup))
+            .service(
+                web::resource("/{org_id}/restore")
+                    .app_data(web::PayloadConfig::new(MAX_BACKUP_BYTES))
+                    .route(web::post().to(backup_ctrl::restore_document)),
+            )
+            .route("/{org_id}/metrics", web::get().to(metric_ctrl::list_metrics))
+            .route("/{org_id}/metrics", web::post().to(metric_ctrl::create_metric))
+            .route("/{org_id}/metrics/preview", web::post().to(metric_ctrl::preview_metric))
+            .route("/{org_id}/metrics/dashboard", web::get().to(metric_ctrl::get_dashboard))
+            .route("/{org_id}/metrics/{metric_id}", web::patch().to(metric_ctrl::update_metric))
+            .route("/{org_id}/metrics/{metric_id}", web::delete().to(metric_ctrl::delete_metric))
+            .route("/{org_id}/metrics/{metric_id}/series", web::get().to(metric_ctrl::get_series))
+            .route("/{org_id}/regions", web::get().to(region_ctrl::list_regions))
+            .route("/{org_id}/regions", web::post().to(region_ctrl::create_region))
+            .route("/{org_id}/regions/{region_id}", web::patch().to(region_ctrl::update_region))
+            .route("/{org_id}/regions/{region_id}", web::delete().to(region_ctrl::delete_region))
+            .route("/{org_id}/regions/{region_id}/team", web::put().to(region_ctrl::set_team))
+            .route("/{org_id}/regions/{region_id}/dashboard", web::get().to(region_ctrl::get_dashboard))
+            .route("/{org_id}/sessions", web::get().to(session_ctrl::list_risky_sessions))
+            .route("/{org_id}/sessions/{session_id}/revoke", web::post().to(session_ctrl::revoke_session))
+            .route("/{org_id}/session-policy", web::get().to(session_ctrl::get_risk_policy))
+            .route("/{org_id}/session-policy", web::put().to(session_ctrl::update_risk_policy))
+            .route("/{org_id}/break-glass/credentials", web::get().to(break_glass_ctrl::list_credentials))
+            .route("/{org_id}/break-glass/credentials", web::post().to(break_glass_ctrl::create_credential))
+            .route("/{org_id}/break-glass/credentials/{credential_id}", web::delete().to(break_glass_ctrl::revoke_credential))
+            .route("/{org_id}/break-glass/sessions", web::get().to(break_glass_ctrl::list_sessions))
+            .route("/{org_id}/break-glass/sessions", web::post().to(break_glass_ctrl::request_access))
+            .route("/{org_id}/break-glass/sessions/{session_id}/approve", web::post().to(break_glass_ctrl::approve_session))
+            .route("/{org_id}/break-glass/sessions/{session_id}/token", web::post().to(break_glass_ctrl::issue_token))
+            .route("/{org_id}/break-glass/sessions/{session_id}/revoke", web::post().to(break_glass_ctrl::revoke_session))
+    );
+}
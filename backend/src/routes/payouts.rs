@@ -0,0 +1,25 @@
+use actix_web::{middleware::from_fn, web};
+use crate::controllers::{kyc_ctrl, payout_ctrl};
+use crate::middleware::idempotency;
+
+/// Identity verification and payouts of earned credits. Admins review both under /api/admin.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/kyc")
+            .route("", web::get().to(kyc_ctrl::get_kyc))
+            .route("", web::post().to(kyc_ctrl::submit_kyc)),
+    )
+    .service(
+        web::scope("/api/payouts")
+            // Payout requests retried with the same Idempotency-Key replay the first response
+            .service(
+                web::resource("")
+                    .wrap(from_fn(idempotency))
+                    .route(web::get().to(payout_ctrl::list_payouts))
+                    .route(web::post().to(payout_ctrl::request_payout)),
+            )
+            .route("/credits", web::get().to(payout_ctrl::get_credits))
+            .route("/{payout_id}", web::get().to(payout_ctrl::get_payout))
+            .route("/{payout_id}/cancel", web::post().to(payout_ctrl::cancel_payout)),
+    );
+}
@@ -1,16 +1,119 @@
 use actix_web::web;
-use crate::controllers::robotics_ctrl;
+use crate::controllers::{
+    robotics_ctrl, attachment_ctrl, command_ctrl, constraint_ctrl, device_certificate_ctrl, device_import_ctrl,
+    energy_ctrl, firmware_ctrl, fleet_config_ctrl, geo_ctrl, incident_ctrl, mission_ctrl, metadata_ctrl, path_ctrl,
+    processor_ctrl, promotion_ctrl, provisioning_ctrl, sensor_ctrl, stream_ctrl, swarm_ctrl, telemetry_ctrl,
+    uptime_ctrl, webhook_ctrl,
+};
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/api/robotics")
+            .route("/config", web::get().to(fleet_config_ctrl::get_config))
+            .route("/config", web::put().to(fleet_config_ctrl::apply_config))
+            .route("/constraints", web::get().to(constraint_ctrl::list_constraints))
+            .route("/constraints", web::post().to(constraint_ctrl::create_constraint))
+            .route("/constraints/{constraint_id}", web::patch().to(constraint_ctrl::update_constraint))
+            .route("/constraints/{constraint_id}", web::delete().to(constraint_ctrl::delete_constraint))
             .route("/devices", web::get().to(robotics_ctrl::get_devices))
             .route("/devices", web::post().to(robotics_ctrl::register_device))
+            .route("/devices/import", web::post().to(device_import_ctrl::import_devices))
+            .route("/devices/nearby", web::get().to(geo_ctrl::get_nearby_devices))
             .route("/devices/{device_id}", web::get().to(robotics_ctrl::get_device))
             .route("/devices/{device_id}", web::delete().to(robotics_ctrl::delete_device))
-            .route("/devices/{device_id}/command", web::post().to(robotics_ctrl::send_command))
+            .route("/devices/{device_id}/attachments", web::get().to(attachment_ctrl::list_attachments))
+            .route("/devices/{device_id}/attachments", web::post().to(attachment_ctrl::create_attachment))
+            .route("/devices/{device_id}/attachments/{attachment_id}", web::patch().to(attachment_ctrl::update_attachment))
+            .route("/devices/{device_id}/attachments/{attachment_id}", web::delete().to(attachment_ctrl::delete_attachment))
+            .route("/devices/{device_id}/command", web::post().to(command_ctrl::send_command))
+            .route("/devices/{device_id}/commands", web::get().to(command_ctrl::list_commands))
+            .route("/devices/{device_id}/commands/batch", web::post().to(command_ctrl::send_command_batch))
+            .route("/devices/{device_id}/commands/pending", web::get().to(command_ctrl::poll_commands))
+            .route("/devices/{device_id}/commands/ws", web::get().to(command_ctrl::command_socket))
+            .route("/devices/{device_id}/commands/{command_id}/ack", web::post().to(command_ctrl::ack_command))
+            .route("/devices/{device_id}/battery/forecast", web::get().to(telemetry_ctrl::get_battery_forecast))
+            .route("/devices/{device_id}/battery/simulate", web::post().to(telemetry_ctrl::simulate_battery))
+            .route("/devices/{device_id}/certificate", web::get().to(device_certificate_ctrl::get_certificate))
+            .route("/devices/{device_id}/certificate", web::post().to(device_certificate_ctrl::mint_certificate))
+            .route("/devices/{device_id}/credentials", web::post().to(provisioning_ctrl::rotate_device_key))
+            .route("/devices/{device_id}/energy", web::get().to(energy_ctrl::get_energy_report))
+            .route("/devices/{device_id}/firmware", web::get().to(firmware_ctrl::get_firmware_update))
+            .route("/devices/{device_id}/firmware/{release_id}/artifact", web::get().to(firmware_ctrl::download_artifact))
+            .route("/devices/{device_id}/firmware/{release_id}/report", web::post().to(firmware_ctrl::report_installation))
+            .route("/devices/{device_id}/metadata", web::put().to(metadata_ctrl::replace_metadata))
+            .route("/devices/{device_id}/metadata", web::patch().to(metadata_ctrl::patch_metadata))
+            .route("/devices/{device_id}/track", web::get().to(path_ctrl::get_track))
+            .route("/devices/{device_id}/paths", web::get().to(path_ctrl::list_paths))
+            .route("/devices/{device_id}/paths", web::post().to(path_ctrl::record_path))
+            .route("/devices/{device_id}/paths/{path_id}", web::get().to(path_ctrl::get_path))
+            .route("/devices/{device_id}/paths/{path_id}", web::delete().to(path_ctrl::delete_path))
+            .route("/devices/{device_id}/replay/{path_id}", web::post().to(path_ctrl::replay_path))
+            .route("/devices/{device_id}/sensors", web::get().to(sensor_ctrl::list_sensors))
+            .route("/devices/{device_id}/sensors", web::post().to(sensor_ctrl::create_sensor))
+            .route("/devices/{device_id}/sensors/{sensor_id}", web::patch().to(sensor_ctrl::update_sensor))
+            .route("/devices/{device_id}/sensors/{sensor_id}", web::delete().to(sensor_ctrl::delete_sensor))
             .route("/devices/{device_id}/status", web::patch().to(robotics_ctrl::update_status))
+            .route("/devices/{device_id}/transport", web::patch().to(command_ctrl::update_transport))
+            .route("/devices/{device_id}/transfer", web::post().to(device_certificate_ctrl::transfer_device))
             .route("/devices/{device_id}/telemetry", web::get().to(robotics_ctrl::get_telemetry))
+            .route("/devices/{device_id}/telemetry", web::post().to(telemetry_ctrl::ingest_telemetry))
+            .route("/devices/{device_id}/uptime", web::get().to(uptime_ctrl::get_uptime))
+            .route("/devices/{device_id}/stream/offer", web::post().to(stream_ctrl::create_offer))
+            .route("/devices/{device_id}/stream/offer", web::get().to(stream_ctrl::get_pending_offer))
+            .route("/devices/{device_id}/stream/{session_id}", web::get().to(stream_ctrl::get_session))
+            .route("/devices/{device_id}/stream/{session_id}", web::delete().to(stream_ctrl::close_session))
+            .route("/devices/{device_id}/stream/{session_id}/answer", web::post().to(stream_ctrl::post_answer))
+            .route("/devices/{device_id}/stream/{session_id}/candidates", web::get().to(stream_ctrl::list_candidates))
+            .route("/devices/{device_id}/stream/{session_id}/candidates", web::post().to(stream_ctrl::add_candidate))
+            .route("/claim-codes", web::get().to(provisioning_ctrl::list_claim_codes))
+            .route("/claim-codes", web::post().to(provisioning_ctrl::create_claim_code))
+            .route("/claim-codes/{claim_id}", web::delete().to(provisioning_ctrl::revoke_claim_code))
+            .route("/device-types/{device_type}/metadata-schema", web::get().to(metadata_ctrl::get_metadata_schema))
+            .route("/firmware/trust-root", web::get().to(firmware_ctrl::get_trust_root))
+            .route("/fleet-missions", web::get().to(mission_ctrl::list_missions))
+            .route("/fleet-missions", web::post().to(mission_ctrl::create_mission))
+            .route("/fleet-missions/{mission_id}", web::get().to(mission_ctrl::get_mission))
+            .route("/fleet-missions/{mission_id}/advance", web::post().to(mission_ctrl::advance_mission))
+            .route("/fleet-missions/{mission_id}/cancel", web::post().to(mission_ctrl::cancel_mission))
+            .route("/incidents", web::get().to(incident_ctrl::list_incidents))
+            .route("/incidents/{incident_id}", web::get().to(incident_ctrl::get_incident))
+            .route("/incidents/{incident_id}/acknowledge", web::post().to(incident_ctrl::acknowledge_incident))
+            .route("/incidents/{incident_id}/messages", web::post().to(incident_ctrl::add_incident_message))
+            .route("/incidents/{incident_id}/resolve", web::post().to(incident_ctrl::resolve_incident))
+            .route("/macros", web::get().to(promotion_ctrl::list_macros))
+            .route("/macros", web::post().to(promotion_ctrl::create_macro))
+            .route("/macros/{macro_id}", web::delete().to(promotion_ctrl::delete_macro))
+            .route("/macros/{macro_id}/run", web::post().to(promotion_ctrl::run_macro))
+            .route("/processors", web::get().to(processor_ctrl::list_processors))
+            .route("/processors", web::post().to(processor_ctrl::create_processor))
+            .route("/processors/{processor_id}", web::get().to(processor_ctrl::get_processor))
+            .route("/processors/{processor_id}", web::patch().to(processor_ctrl::update_processor))
+            .route("/processors/{processor_id}", web::delete().to(processor_ctrl::delete_processor))
+            .route("/processors/{processor_id}/test", web::post().to(processor_ctrl::test_processor))
+            .route("/promotions", web::get().to(promotion_ctrl::list_promotions))
+            .route("/promotions", web::post().to(promotion_ctrl::create_promotion))
+            .route("/promotions/pending", web::get().to(promotion_ctrl::list_pending_promotions))
+            .route("/promotions/{promotion_id}", web::get().to(promotion_ctrl::get_promotion))
+            .route("/promotions/{promotion_id}/approve", web::post().to(promotion_ctrl::approve_promotion))
+            .route("/promotions/{promotion_id}/reject", web::post().to(promotion_ctrl::reject_promotion))
+            .route("/swarm/commands", web::post().to(swarm_ctrl::fan_out_command))
+            .route("/swarm/missions", web::get().to(swarm_ctrl::list_missions))
+            .route("/swarm/missions", web::post().to(swarm_ctrl::create_mission))
+            .route("/swarm/missions/{mission_id}", web::get().to(swarm_ctrl::get_mission))
+            .route("/webhooks", web::get().to(webhook_ctrl::list_webhooks))
+            .route("/webhooks", web::post().to(webhook_ctrl::create_webhook))
+            .route("/webhooks/{webhook_id}", web::get().to(webhook_ctrl::get_webhook))
+            .route("/webhooks/{webhook_id}", web::patch().to(webhook_ctrl::update_webhook))
+            .route("/webhooks/{webhook_id}", web::delete().to(webhook_ctrl::delete_webhook))
+            .route("/webhooks/{webhook_id}/deliveries", web::get().to(webhook_ctrl::list_deliveries))
+            .route("/webhooks/{webhook_id}/deliveries/summary", web::get().to(webhook_ctrl::get_delivery_summary))
+            .route("/webhooks/{webhook_id}/deliveries/{delivery_id}", web::get().to(webhook_ctrl::get_delivery))
+            .route("/webhooks/{webhook_id}/deliveries/{delivery_id}/replay", web::post().to(webhook_ctrl::replay_delivery))
+            .route("/webhooks/{webhook_id}/deliveries/{delivery_id}/retry", web::post().to(webhook_ctrl::retry_delivery))
+            .route("/webhooks/{webhook_id}/secret", web::get().to(webhook_ctrl::get_signing_secret))
+            .route("/webhooks/{webhook_id}/secret/rotate", web::post().to(webhook_ctrl::rotate_signing_secret))
+            .route("/webhooks/{webhook_id}/test", web::post().to(webhook_ctrl::test_webhook))
+            .route("/provision", web::post().to(provisioning_ctrl::provision_device))
             .route("/health", web::get().to(robotics_ctrl::health_check))
     );
 }
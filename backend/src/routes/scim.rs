@@ -0,0 +1,21 @@
+use actix_web::web;
+use crate::controllers::scim_ctrl;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/scim/v2")
+            .route("/ServiceProviderConfig", web::get().to(scim_ctrl::service_provider_config))
+            .route("/Users", web::get().to(scim_ctrl::list_users))
+            .route("/Users", web::post().to(scim_ctrl::create_user))
+            .route("/Users/{id}", web::get().to(scim_ctrl::get_user))
+            .route("/Users/{id}", web::put().to(scim_ctrl::replace_user))
+            .route("/Users/{id}", web::patch().to(scim_ctrl::patch_user))
+            .route("/Users/{id}", web::delete().to(scim_ctrl::delete_user))
+            .route("/Groups", web::get().to(scim_ctrl::list_groups))
+            .route("/Groups", web::post().to(scim_ctrl::create_group))
+            .route("/Groups/{id}", web::get().to(scim_ctrl::get_group))
+            .route("/Groups/{id}", web::put().to(scim_ctrl::replace_group))
+            .route("/Groups/{id}", web::patch().to(scim_ctrl::patch_group))
+            .route("/Groups/{id}", web::delete().to(scim_ctrl::delete_group))
+    );
+}
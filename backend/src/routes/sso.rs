@@ -0,0 +1,17 @@
+use actix_web::web;
+use crate::controllers::sso_ctrl;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/sso")
+            .route("/orgs/{org_id}/config", web::get().to(sso_ctrl::get_config))
+            .route("/orgs/{org_id}/config", web::put().to(sso_ctrl::update_config))
+            .route("/orgs/{org_id}/domains", web::get().to(sso_ctrl::list_domains))
+            .route("/orgs/{org_id}/domains", web::post().to(sso_ctrl::add_domain))
+            .route("/orgs/{org_id}/domains/{domain_id}/verify", web::post().to(sso_ctrl::verify_domain))
+            .route("/orgs/{org_id}/domains/{domain_id}", web::delete().to(sso_ctrl::delete_domain))
+            .route("/{org_slug}/login", web::get().to(sso_ctrl::start_login))
+            .route("/{org_slug}/oidc/callback", web::get().to(sso_ctrl::oidc_callback))
+            .route("/{org_slug}/saml/acs", web::post().to(sso_ctrl::saml_acs))
+    );
+}
@@ -0,0 +1,20 @@
+use actix_web::web;
+use crate::controllers::support_ctrl;
+use crate::services::support_services::MAX_ATTACHMENT_BYTES;
+
+/// Support tickets of the current user. Staff work the queue under /api/admin/support.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/support")
+            .route("/tickets", web::get().to(support_ctrl::list_tickets))
+            .route("/tickets", web::post().to(support_ctrl::create_ticket))
+            .route("/tickets/{ticket_id}", web::get().to(support_ctrl::get_ticket))
+            .route("/tickets/{ticket_id}/messages", web::post().to(support_ctrl::add_message))
+            .route("/tickets/{ticket_id}/close", web::post().to(support_ctrl::close_ticket))
+            .service(
+                web::resource("/tickets/{ticket_id}/attachments")
+                    .app_data(web::PayloadConfig::new(MAX_ATTACHMENT_BYTES))
+                    .route(web::post().to(support_ctrl::upload_attachment)),
+            )
+    );
+}
@@ -0,0 +1,18 @@
+use actix_web::web;
+use crate::controllers::template_ctrl;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/templates")
+            .route("", web::get().to(template_ctrl::list_templates))
+            .route("", web::post().to(template_ctrl::publish_template))
+            .route("/imports", web::get().to(template_ctrl::list_imports))
+            .route("/{template_id}", web::get().to(template_ctrl::get_template))
+            .route("/{template_id}", web::delete().to(template_ctrl::withdraw_template))
+            .route("/{template_id}/import", web::post().to(template_ctrl::import_template))
+            .route("/{template_id}/provenance", web::get().to(template_ctrl::get_provenance))
+            .route("/{template_id}/ratings", web::get().to(template_ctrl::list_ratings))
+            .route("/{template_id}/rating", web::put().to(template_ctrl::rate_template))
+            .route("/{template_id}/report", web::post().to(template_ctrl::report_template))
+    );
+}
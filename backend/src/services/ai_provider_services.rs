@@ -0,0 +1,932 @@
+//! Chat model providers. `AIService` sends each request to one of these: the default set by
+//! `AI_PROVIDER`, or the one the request names. OpenAI and Azure OpenAI share the chat
+//! completions format; Anthropic and a self-hosted Ollama speak their own APIs and are
+//! translated here.
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::LazyLock;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::services::ai_services::{
+    AudioInput, ChatMessage, ChatRequest, ChatResponse, FineTuneJob, ImageInput, Moderation, SpeechAudio, SpeechInput,
+    TokenUsage, TrainingData, TranscriptSegment, Transcription, DEFAULT_MAX_TOKENS,
+};
+use crate::utils::crypto::base64_encode;
+
+pub const DEFAULT_OPENAI_URL: &str = "https://api.openai.com/v1";
+pub const DEFAULT_ANTHROPIC_URL: &str = "https://api.anthropic.com/v1";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const DEFAULT_ANTHROPIC_MODEL: &str = "claude-3-5-haiku-latest";
+const DEFAULT_AZURE_API_VERSION: &str = "2024-06-01";
+const DEFAULT_OLLAMA_MODEL: &str = "llama3.1";
+const DEFAULT_OLLAMA_EMBEDDING_MODEL: &str = "nomic-embed-text";
+const OPENAI_EMBEDDING_MODEL: &str = "text-embedding-ada-002";
+const OPENAI_MODERATION_MODEL: &str = "omni-moderation-latest";
+const OPENAI_VISION_MODEL: &str = "gpt-4o";
+const DEFAULT_TRANSCRIPTION_MODEL: &str = "whisper-1";
+const DEFAULT_SPEECH_MODEL: &str = "tts-1";
+const ANTHROPIC_VISION_MODEL: &str = "claude-3-5-sonnet-latest";
+const DEFAULT_OLLAMA_VISION_MODEL: &str = "llava";
+const DEFAULT_TEMPERATURE: f32 = 0.7;
+
+static AI_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        // Long completions from a local model can take a while
+        .timeout(std::time::Duration::from_secs(120))
+        .build()
+        .expect("Failed to build AI HTTP client")
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderKind {
+    OpenAi,
+    AzureOpenAi,
+    Anthropic,
+    Ollama,
+}
+
+impl ProviderKind {
+    pub const ALL: [ProviderKind; 4] =
+        [ProviderKind::OpenAi, ProviderKind::AzureOpenAi, ProviderKind::Anthropic, ProviderKind::Ollama];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProviderKind::OpenAi => "openai",
+            ProviderKind::AzureOpenAi => "azure_openai",
+            ProviderKind::Anthropic => "anthropic",
+            ProviderKind::Ollama => "ollama",
+        }
+    }
+
+    pub fn parse(value: &str) -> ApiResult<Self> {
+        Self::ALL.into_iter().find(|k| k.as_str() == value).ok_or_else(|| {
+            let names: Vec<&str> = Self::ALL.iter().map(|k| k.as_str()).collect();
+            ApiError::ValidationError(format!("Unknown AI provider '{}'. Valid providers: {:?}", value, names))
+        })
+    }
+
+    /// Runs on our own hardware, so requests cost nothing against the AI budget
+    pub fn is_self_hosted(&self) -> bool {
+        matches!(self, ProviderKind::Ollama)
+    }
+}
+
+#[async_trait]
+pub trait ChatProvider: Send + Sync {
+    fn kind(&self) -> ProviderKind;
+
+    /// Model (or Azure deployment) used when a request names none
+    fn default_model(&self) -> &str;
+
+    async fn chat(&self, request: &ChatRequest, model: &str) -> ApiResult<ChatResponse>;
+
+    /// Model used for requests with images that name none; `None` when the provider takes no
+    /// images
+    fn vision_model(&self) -> Option<&str> {
+        None
+    }
+
+    /// The model `embed` uses, when the provider has one
+    fn embedding_model(&self) -> Option<&str> {
+        None
+    }
+
+    async fn embed(&self, _text: &str) -> ApiResult<Vec<f32>> {
+        Err(ApiError::AIServiceError(format!("{} does not provide embeddings", self.kind().as_str())))
+    }
+
+    /// The model `transcribe` uses, when the provider transcribes audio
+    fn transcription_model(&self) -> Option<&str> {
+        None
+    }
+
+    async fn transcribe(&self, _audio: AudioInput) -> ApiResult<Transcription> {
+        Err(ApiError::AIServiceError(format!("{} does not transcribe audio", self.kind().as_str())))
+    }
+
+    /// The model `speak` uses, when the provider synthesizes speech
+    fn speech_model(&self) -> Option<&str> {
+        None
+    }
+
+    async fn speak(&self, _speech: &SpeechInput) -> ApiResult<SpeechAudio> {
+        Err(ApiError::AIServiceError(format!("{} does not synthesize speech", self.kind().as_str())))
+    }
+
+    /// Moderation verdicts for `texts`, one per text in order
+    async fn moderate(&self, _texts: &[&str]) -> ApiResult<Vec<Moderation>> {
+        Err(ApiError::AIServiceError(format!("{} does not provide moderation", self.kind().as_str())))
+    }
+
+    /// Whether the provider fine-tunes models with the methods below
+    fn fine_tunes(&self) -> bool {
+        false
+    }
+
+    /// Upload fine-tuning examples, returning the provider's file ID
+    async fn upload_training_data(&self, _training: TrainingData) -> ApiResult<String> {
+        Err(ApiError::AIServiceError(format!("{} does not fine-tune models", self.kind().as_str())))
+    }
+
+    /// Start training `base_model` on an uploaded file; `suffix` goes into the new model's ID
+    async fn start_fine_tune(
+        &self,
+        _file_id: &str,
+        _base_model: &str,
+        _suffix: Option<&str>,
+    ) -> ApiResult<FineTuneJob> {
+        Err(ApiError::AIServiceError(format!("{} does not fine-tune models", self.kind().as_str())))
+    }
+
+    async fn fine_tune(&self, _job_id: &str) -> ApiResult<FineTuneJob> {
+        Err(ApiError::AIServiceError(format!("{} does not fine-tune models", self.kind().as_str())))
+    }
+}
+
+/// POST `payload` and parse the JSON reply, turning transport and API errors into AI errors
+async fn send<T: serde::de::DeserializeOwned>(request: reqwest::RequestBuilder, payload: &Value) -> ApiResult<T> {
+    receive(request.json(payload)).await
+}
+
+/// Send a request with its body set and parse the JSON reply
+async fn receive<T: serde::de::DeserializeOwned>(request: reqwest::RequestBuilder) -> ApiResult<T> {
+    let response = open(request).await?;
+    response.json().await.map_err(|e| ApiError::AIServiceError(format!("Failed to parse response: {}", e)))
+}
+
+/// Send a request, failing unless the provider accepts it; the body is left to read
+async fn open(request: reqwest::RequestBuilder) -> ApiResult<reqwest::Response> {
+    let response = request.send().await.map_err(|e| ApiError::AIServiceError(format!("Request failed: {}", e)))?;
+    if !response.status().is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(ApiError::AIServiceError(format!("AI API error: {}", error_text)));
+    }
+    Ok(response)
+}
+
+/// POST an OpenAI-format speech request and stream the audio back
+async fn stream_speech(request: reqwest::RequestBuilder, payload: &Value, model: &str) -> ApiResult<SpeechAudio> {
+    let response = open(request.json(payload)).await?;
+    let stream = response
+        .bytes_stream()
+        .map(|chunk| chunk.map_err(|e| ApiError::AIServiceError(format!("Audio stream failed: {}", e))))
+        .boxed();
+    Ok(SpeechAudio { model: model.to_string(), stream })
+}
+
+fn speech_payload(speech: &SpeechInput, model: Option<&str>) -> Value {
+    let mut payload = serde_json::json!({
+        "input": speech.text,
+        "voice": speech.voice,
+        "response_format": speech.format,
+    });
+    if let Some(model) = model {
+        payload["model"] = Value::from(model);
+    }
+    payload
+}
+
+#[derive(Debug, Deserialize)]
+struct UploadedFile {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FineTuningJobResponse {
+    id: String,
+    status: String,
+    fine_tuned_model: Option<String>,
+    trained_tokens: Option<i64>,
+    error: Option<FineTuningError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FineTuningError {
+    message: Option<String>,
+}
+
+impl From<FineTuningJobResponse> for FineTuneJob {
+    fn from(job: FineTuningJobResponse) -> Self {
+        FineTuneJob {
+            id: job.id,
+            status: job.status,
+            fine_tuned_model: job.fine_tuned_model,
+            trained_tokens: job.trained_tokens,
+            error: job.error.and_then(|e| e.message).filter(|m| !m.is_empty()),
+        }
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+// Chat completions format, shared by OpenAI and Azure OpenAI
+#[derive(Debug, Deserialize)]
+struct OpenAIChatResponse {
+    id: String,
+    model: String,
+    choices: Vec<OpenAIChoice>,
+    usage: Option<OpenAIUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIChoice {
+    message: OpenAIMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIMessage {
+    content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIUsage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    total_tokens: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModerationResponse {
+    results: Vec<ModerationVerdict>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModerationVerdict {
+    flagged: bool,
+    categories: std::collections::BTreeMap<String, bool>,
+}
+
+/// `messages` as JSON, with the request's images attached to the last user message by `attach`
+fn message_values<'a>(
+    messages: impl IntoIterator<Item = &'a ChatMessage>,
+    images: &[ImageInput],
+    attach: impl Fn(&ChatMessage, &[ImageInput]) -> Value,
+) -> Vec<Value> {
+    let messages: Vec<&ChatMessage> = messages.into_iter().collect();
+    let last_user = messages.iter().rposition(|m| m.role == "user").filter(|_| !images.is_empty());
+    messages
+        .iter()
+        .enumerate()
+        .map(|(i, m)| if Some(i) == last_user { attach(m, images) } else { serde_json::json!(m) })
+        .collect()
+}
+
+// Whisper's verbose transcription format, shared by OpenAI, Azure OpenAI and compatible servers
+#[derive(Debug, Deserialize)]
+struct WhisperResponse {
+    text: String,
+    language: Option<String>,
+    duration: Option<f64>,
+    #[serde(default)]
+    segments: Vec<WhisperSegment>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WhisperSegment {
+    start: f64,
+    end: f64,
+    text: String,
+}
+
+/// The multipart form of a Whisper transcription request; `model` is left out for Azure, which
+/// takes it from the deployment
+fn whisper_form(audio: AudioInput, model: Option<&str>) -> ApiResult<reqwest::multipart::Form> {
+    let file = reqwest::multipart::Part::bytes(audio.data)
+        .file_name(audio.file_name)
+        .mime_str(&audio.media_type)
+        .map_err(|e| ApiError::ValidationError(format!("Invalid audio type: {}", e)))?;
+    let mut form = reqwest::multipart::Form::new()
+        .part("file", file)
+        .text("response_format", "verbose_json")
+        .text("timestamp_granularities[]", "segment");
+    if let Some(model) = model {
+        form = form.text("model", model.to_string());
+    }
+    if let Some(language) = audio.language {
+        form = form.text("language", language);
+    }
+    if let Some(prompt) = audio.prompt {
+        form = form.text("prompt", prompt);
+    }
+    Ok(form)
+}
+
+fn from_whisper(kind: ProviderKind, model: &str, response: WhisperResponse) -> Transcription {
+    Transcription {
+        text: response.text.trim().to_string(),
+        language: response.language,
+        duration_seconds: response.duration,
+        segments: response
+            .segments
+            .into_iter()
+            .map(|s| TranscriptSegment { start: s.start, end: s.end, text: s.text.trim().to_string() })
+            .collect(),
+        model: model.to_string(),
+        provider: kind.as_str().to_string(),
+        cost: None,
+    }
+}
+
+fn chat_completions_payload(request: &ChatRequest, model: Option<&str>) -> Value {
+    let messages = message_values(&request.messages, &request.images, |message, images| {
+        let mut content = vec![serde_json::json!({ "type": "text", "text": message.content })];
+        content.extend(
+            images.iter().map(|i| serde_json::json!({ "type": "image_url", "image_url": { "url": i.url() } })),
+        );
+        serde_json::json!({ "role": message.role, "content": content })
+    });
+    let mut payload = serde_json::json!({
+        "messages": messages,
+        "temperature": request.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+        "max_tokens": request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+    });
+    // Azure takes the model from the deployment in the URL
+    if let Some(model) = model {
+        payload["model"] = Value::from(model);
+    }
+    if request.json {
+        payload["response_format"] = serde_json::json!({ "type": "json_object" });
+    }
+    payload
+}
+
+fn from_chat_completions(kind: ProviderKind, response: OpenAIChatResponse) -> ChatResponse {
+    ChatResponse {
+        id: response.id,
+        message: response.choices.into_iter().next().and_then(|c| c.message.content).unwrap_or_default(),
+        model: response.model,
+        provider: kind.as_str().to_string(),
+        usage: response.usage.map(|u| TokenUsage {
+            prompt_tokens: u.prompt_tokens,
+            completion_tokens: u.completion_tokens,
+            total_tokens: u.total_tokens,
+        }),
+        routing: None,
+        cost: None,
+        conversation_id: None,
+    }
+}
+
+fn first_embedding(response: EmbeddingResponse) -> ApiResult<Vec<f32>> {
+    response
+        .data
+        .into_iter()
+        .next()
+        .map(|d| d.embedding)
+        .ok_or_else(|| ApiError::AIServiceError("No embedding returned".to_string()))
+}
+
+/// OpenAI, or any server exposing its API at `AI_API_URL`
+pub struct OpenAiProvider {
+    api_key: SecretString,
+    pub(crate) base_url: String,
+    transcription_model: String,
+}
+
+impl OpenAiProvider {
+    pub fn new(api_key: SecretString, base_url: Option<String>) -> Self {
+        let base_url = base_url.unwrap_or_else(|| DEFAULT_OPENAI_URL.to_string());
+        Self {
+            api_key,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            transcription_model: DEFAULT_TRANSCRIPTION_MODEL.to_string(),
+        }
+    }
+
+    /// `AI_API_KEY` and `AI_API_URL`, with `AI_TRANSCRIPTION_MODEL` optional for Whisper-compatible
+    /// servers that name their models differently
+    pub fn from_env() -> Option<Self> {
+        let mut provider = Self::new(SecretString::from(env_var("AI_API_KEY")?), env_var("AI_API_URL"));
+        if let Some(model) = env_var("AI_TRANSCRIPTION_MODEL") {
+            provider.transcription_model = model;
+        }
+        Some(provider)
+    }
+
+    fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        AI_CLIENT.post(format!("{}/{}", self.base_url, path)).bearer_auth(self.api_key.expose_secret())
+    }
+
+    fn get(&self, path: &str) -> reqwest::RequestBuilder {
+        AI_CLIENT.get(format!("{}/{}", self.base_url, path)).bearer_auth(self.api_key.expose_secret())
+    }
+}
+
+#[async_trait]
+impl ChatProvider for OpenAiProvider {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::OpenAi
+    }
+
+    fn default_model(&self) -> &str {
+        crate::services::ai_services::DEFAULT_CHAT_MODEL
+    }
+
+    async fn chat(&self, request: &ChatRequest, model: &str) -> ApiResult<ChatResponse> {
+        let payload = chat_completions_payload(request, Some(model));
+        let response: OpenAIChatResponse = send(self.post("chat/completions"), &payload).await?;
+        Ok(from_chat_completions(self.kind(), response))
+    }
+
+    fn vision_model(&self) -> Option<&str> {
+        Some(OPENAI_VISION_MODEL)
+    }
+
+    fn embedding_model(&self) -> Option<&str> {
+        Some(OPENAI_EMBEDDING_MODEL)
+    }
+
+    async fn embed(&self, text: &str) -> ApiResult<Vec<f32>> {
+        let payload = serde_json::json!({ "model": OPENAI_EMBEDDING_MODEL, "input": text });
+        first_embedding(send(self.post("embeddings"), &payload).await?)
+    }
+
+    fn transcription_model(&self) -> Option<&str> {
+        Some(&self.transcription_model)
+    }
+
+    async fn transcribe(&self, audio: AudioInput) -> ApiResult<Transcription> {
+        let form = whisper_form(audio, Some(&self.transcription_model))?;
+        let response: WhisperResponse = receive(self.post("audio/transcriptions").multipart(form)).await?;
+        Ok(from_whisper(self.kind(), &self.transcription_model, response))
+    }
+
+    fn speech_model(&self) -> Option<&str> {
+        Some(DEFAULT_SPEECH_MODEL)
+    }
+
+    async fn speak(&self, speech: &SpeechInput) -> ApiResult<SpeechAudio> {
+        let payload = speech_payload(speech, Some(DEFAULT_SPEECH_MODEL));
+        stream_speech(self.post("audio/speech"), &payload, DEFAULT_SPEECH_MODEL).await
+    }
+
+    async fn moderate(&self, texts: &[&str]) -> ApiResult<Vec<Moderation>> {
+        let payload = serde_json::json!({ "model": OPENAI_MODERATION_MODEL, "input": texts });
+        let response: ModerationResponse = send(self.post("moderations"), &payload).await?;
+        Ok(response
+            .results
+            .into_iter()
+            .map(|r| Moderation {
+                flagged: r.flagged,
+                categories: r.categories.into_iter().filter(|(_, hit)| *hit).map(|(name, _)| name).collect(),
+            })
+            .collect())
+    }
+
+    fn fine_tunes(&self) -> bool {
+        true
+    }
+
+    async fn upload_training_data(&self, training: TrainingData) -> ApiResult<String> {
+        let file = reqwest::multipart::Part::bytes(training.data)
+            .file_name(training.file_name)
+            .mime_str("application/jsonl")
+            .map_err(|e| ApiError::AIServiceError(format!("Invalid training file: {}", e)))?;
+        let form = reqwest::multipart::Form::new().text("purpose", "fine-tune").part("file", file);
+        let uploaded: UploadedFile = receive(self.post("files").multipart(form)).await?;
+        Ok(uploaded.id)
+    }
+
+    async fn start_fine_tune(&self, file_id: &str, base_model: &str, suffix: Option<&str>) -> ApiResult<FineTuneJob> {
+        let mut payload = serde_json::json!({ "training_file": file_id, "model": base_model });
+        if let Some(suffix) = suffix {
+            payload["suffix"] = Value::from(suffix);
+        }
+        let job: FineTuningJobResponse = send(self.post("fine_tuning/jobs"), &payload).await?;
+        Ok(job.into())
+    }
+
+    async fn fine_tune(&self, job_id: &str) -> ApiResult<FineTuneJob> {
+        let job: FineTuningJobResponse = receive(self.get(&format!("fine_tuning/jobs/{}", job_id))).await?;
+        Ok(job.into())
+    }
+}
+
+/// Azure OpenAI. Models are deployments of the resource; a request's `model` names the
+/// deployment.
+pub struct AzureOpenAiProvider {
+    endpoint: String,
+    api_key: SecretString,
+    api_version: String,
+    deployment: String,
+    embedding_deployment: Option<String>,
+    transcription_deployment: Option<String>,
+    speech_deployment: Option<String>,
+}
+
+impl AzureOpenAiProvider {
+    /// `AZURE_OPENAI_ENDPOINT`, `AZURE_OPENAI_API_KEY` and `AZURE_OPENAI_DEPLOYMENT`, with
+    /// `AZURE_OPENAI_API_VERSION`, `AZURE_OPENAI_EMBEDDING_DEPLOYMENT`,
+    /// `AZURE_OPENAI_TRANSCRIPTION_DEPLOYMENT` and `AZURE_OPENAI_SPEECH_DEPLOYMENT` optional
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            endpoint: env_var("AZURE_OPENAI_ENDPOINT")?.trim_end_matches('/').to_string(),
+            api_key: SecretString::from(env_var("AZURE_OPENAI_API_KEY")?),
+            api_version: env_var("AZURE_OPENAI_API_VERSION").unwrap_or_else(|| DEFAULT_AZURE_API_VERSION.to_string()),
+            deployment: env_var("AZURE_OPENAI_DEPLOYMENT")?,
+            embedding_deployment: env_var("AZURE_OPENAI_EMBEDDING_DEPLOYMENT"),
+            transcription_deployment: env_var("AZURE_OPENAI_TRANSCRIPTION_DEPLOYMENT"),
+            speech_deployment: env_var("AZURE_OPENAI_SPEECH_DEPLOYMENT"),
+        })
+    }
+
+    fn post(&self, deployment: &str, operation: &str) -> reqwest::RequestBuilder {
+        AI_CLIENT
+            .post(format!(
+                "{}/openai/deployments/{}/{}?api-version={}",
+                self.endpoint, deployment, operation, self.api_version
+            ))
+            .header("api-key", self.api_key.expose_secret())
+    }
+}
+
+#[async_trait]
+impl ChatProvider for AzureOpenAiProvider {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::AzureOpenAi
+    }
+
+    fn default_model(&self) -> &str {
+        &self.deployment
+    }
+
+    async fn chat(&self, request: &ChatRequest, model: &str) -> ApiResult<ChatResponse> {
+        let payload = chat_completions_payload(request, None);
+        let response: OpenAIChatResponse = send(self.post(model, "chat/completions"), &payload).await?;
+        Ok(from_chat_completions(self.kind(), response))
+    }
+
+    /// The chat deployment; deployments of vision models such as gpt-4o take images
+    fn vision_model(&self) -> Option<&str> {
+        Some(&self.deployment)
+    }
+
+    fn embedding_model(&self) -> Option<&str> {
+        self.embedding_deployment.as_deref()
+    }
+
+    async fn embed(&self, text: &str) -> ApiResult<Vec<f32>> {
+        let deployment = self.embedding_deployment.as_deref().ok_or_else(|| {
+            ApiError::AIServiceError("AZURE_OPENAI_EMBEDDING_DEPLOYMENT is not configured".to_string())
+        })?;
+        let payload = serde_json::json!({ "input": text });
+        first_embedding(send(self.post(deployment, "embeddings"), &payload).await?)
+    }
+
+    fn transcription_model(&self) -> Option<&str> {
+        self.transcription_deployment.as_deref()
+    }
+
+    async fn transcribe(&self, audio: AudioInput) -> ApiResult<Transcription> {
+        let deployment = self.transcription_deployment.as_deref().ok_or_else(|| {
+            ApiError::AIServiceError("AZURE_OPENAI_TRANSCRIPTION_DEPLOYMENT is not configured".to_string())
+        })?;
+        let form = whisper_form(audio, None)?;
+        let response: WhisperResponse =
+            receive(self.post(deployment, "audio/transcriptions").multipart(form)).await?;
+        Ok(from_whisper(self.kind(), deployment, response))
+    }
+
+    fn speech_model(&self) -> Option<&str> {
+        self.speech_deployment.as_deref()
+    }
+
+    async fn speak(&self, speech: &SpeechInput) -> ApiResult<SpeechAudio> {
+        let deployment = self.speech_deployment.as_deref().ok_or_else(|| {
+            ApiError::AIServiceError("AZURE_OPENAI_SPEECH_DEPLOYMENT is not configured".to_string())
+        })?;
+        stream_speech(self.post(deployment, "audio/speech"), &speech_payload(speech, None), deployment).await
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicResponse {
+    id: String,
+    model: String,
+    content: Vec<AnthropicContent>,
+    usage: AnthropicUsage,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicContent {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicUsage {
+    input_tokens: u32,
+    output_tokens: u32,
+}
+
+/// The Messages API takes system prompts apart from the conversation. It has no JSON mode; a
+/// JSON reply rests on the prompt asking for one.
+pub fn anthropic_payload(request: &ChatRequest, model: &str) -> Value {
+    let (system, turns): (Vec<&ChatMessage>, Vec<&ChatMessage>) =
+        request.messages.iter().partition(|m| m.role == "system");
+    let turns = message_values(turns, &request.images, |message, images| {
+        let mut content: Vec<Value> = images
+            .iter()
+            .map(|image| match image {
+                ImageInput::Url(url) => serde_json::json!({ "type": "image", "source": { "type": "url", "url": url } }),
+                ImageInput::Data { media_type, data } => serde_json::json!({
+                    "type": "image",
+                    "source": { "type": "base64", "media_type": media_type, "data": base64_encode(data) },
+                }),
+            })
+            .collect();
+        content.push(serde_json::json!({ "type": "text", "text": message.content }));
+        serde_json::json!({ "role": message.role, "content": content })
+    });
+    let mut payload = serde_json::json!({
+        "model": model,
+        "messages": turns,
+        "max_tokens": request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+        "temperature": request.temperature.unwrap_or(DEFAULT_TEMPERATURE).min(1.0),
+    });
+    if !system.is_empty() {
+        let system: Vec<&str> = system.iter().map(|m| m.content.as_str()).collect();
+        payload["system"] = Value::from(system.join("\n\n"));
+    }
+    payload
+}
+
+pub struct AnthropicProvider {
+    api_key: SecretString,
+    base_url: String,
+    default_model: String,
+}
+
+impl AnthropicProvider {
+    /// `ANTHROPIC_API_KEY`, with `ANTHROPIC_API_URL` and `ANTHROPIC_MODEL` optional
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            api_key: SecretString::from(env_var("ANTHROPIC_API_KEY")?),
+            base_url: env_var("ANTHROPIC_API_URL")
+                .unwrap_or_else(|| DEFAULT_ANTHROPIC_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            default_model: env_var("ANTHROPIC_MODEL").unwrap_or_else(|| DEFAULT_ANTHROPIC_MODEL.to_string()),
+        })
+    }
+}
+
+#[async_trait]
+impl ChatProvider for AnthropicProvider {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::Anthropic
+    }
+
+    fn default_model(&self) -> &str {
+        &self.default_model
+    }
+
+    fn vision_model(&self) -> Option<&str> {
+        Some(ANTHROPIC_VISION_MODEL)
+    }
+
+    async fn chat(&self, request: &ChatRequest, model: &str) -> ApiResult<ChatResponse> {
+        let http = AI_CLIENT
+            .post(format!("{}/messages", self.base_url))
+            .header("x-api-key", self.api_key.expose_secret())
+            .header("anthropic-version", ANTHROPIC_VERSION);
+        let response: AnthropicResponse = send(http, &anthropic_payload(request, model)).await?;
+        let text: Vec<String> = response.content.into_iter().filter(|c| c.kind == "text").map(|c| c.text).collect();
+        Ok(ChatResponse {
+            id: response.id,
+            message: text.concat(),
+            model: response.model,
+            provider: self.kind().as_str().to_string(),
+            usage: Some(TokenUsage {
+                prompt_tokens: response.usage.input_tokens,
+                completion_tokens: response.usage.output_tokens,
+                total_tokens: response.usage.input_tokens + response.usage.output_tokens,
+            }),
+            routing: None,
+            cost: None,
+            conversation_id: None,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaChatResponse {
+    model: String,
+    message: OllamaMessage,
+    prompt_eval_count: Option<u32>,
+    eval_count: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaMessage {
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaEmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+/// A self-hosted Ollama server
+pub struct OllamaProvider {
+    base_url: String,
+    default_model: String,
+    vision_model: String,
+    embedding_model: String,
+}
+
+impl OllamaProvider {
+    /// `OLLAMA_URL`, with `OLLAMA_MODEL`, `OLLAMA_VISION_MODEL` and `OLLAMA_EMBEDDING_MODEL` optional
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            base_url: env_var("OLLAMA_URL")?.trim_end_matches('/').to_string(),
+            default_model: env_var("OLLAMA_MODEL").unwrap_or_else(|| DEFAULT_OLLAMA_MODEL.to_string()),
+            vision_model: env_var("OLLAMA_VISION_MODEL").unwrap_or_else(|| DEFAULT_OLLAMA_VISION_MODEL.to_string()),
+            embedding_model: env_var("OLLAMA_EMBEDDING_MODEL")
+                .unwrap_or_else(|| DEFAULT_OLLAMA_EMBEDDING_MODEL.to_string()),
+        })
+    }
+}
+
+#[async_trait]
+impl ChatProvider for OllamaProvider {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::Ollama
+    }
+
+    fn default_model(&self) -> &str {
+        &self.default_model
+    }
+
+    fn vision_model(&self) -> Option<&str> {
+        Some(&self.vision_model)
+    }
+
+    async fn chat(&self, request: &ChatRequest, model: &str) -> ApiResult<ChatResponse> {
+        // The server has no business fetching URLs on a user's behalf
+        if request.images.iter().any(|i| matches!(i, ImageInput::Url(_))) {
+            return Err(ApiError::ValidationError("ollama takes uploaded images, not image URLs".to_string()));
+        }
+        let messages = message_values(&request.messages, &request.images, |message, images| {
+            let images: Vec<String> = images
+                .iter()
+                .filter_map(|i| match i {
+                    ImageInput::Data { data, .. } => Some(base64_encode(data)),
+                    ImageInput::Url(_) => None,
+                })
+                .collect();
+            serde_json::json!({ "role": message.role, "content": message.content, "images": images })
+        });
+        let mut payload = serde_json::json!({
+            "model": model,
+            "messages": messages,
+            "stream": false,
+            "options": {
+                "temperature": request.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+                "num_predict": request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            },
+        });
+        if request.json {
+            payload["format"] = Value::from("json");
+        }
+        let response: OllamaChatResponse =
+            send(AI_CLIENT.post(format!("{}/api/chat", self.base_url)), &payload).await?;
+        let usage = match (response.prompt_eval_count, response.eval_count) {
+            (Some(prompt), Some(completion)) => Some(TokenUsage {
+                prompt_tokens: prompt,
+                completion_tokens: completion,
+                total_tokens: prompt + completion,
+            }),
+            _ => None,
+        };
+        Ok(ChatResponse {
+            // Ollama does not identify its replies
+            id: format!("ollama-{}", Uuid::new_v4()),
+            message: response.message.content,
+            model: response.model,
+            provider: self.kind().as_str().to_string(),
+            usage,
+            routing: None,
+            cost: None,
+            conversation_id: None,
+        })
+    }
+
+    fn embedding_model(&self) -> Option<&str> {
+        Some(&self.embedding_model)
+    }
+
+    async fn embed(&self, text: &str) -> ApiResult<Vec<f32>> {
+        let payload = serde_json::json!({ "model": self.embedding_model, "input": text });
+        let response: OllamaEmbedResponse =
+            send(AI_CLIENT.post(format!("{}/api/embed", self.base_url)), &payload).await?;
+        response
+            .embeddings
+            .into_iter()
+            .next()
+            .ok_or_else(|| ApiError::AIServiceError("No embedding returned".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_kind() {
+        for kind in ProviderKind::ALL {
+            assert_eq!(ProviderKind::parse(kind.as_str()).unwrap(), kind);
+        }
+        assert!(ProviderKind::parse("gemini").is_err());
+        assert!(ProviderKind::Ollama.is_self_hosted());
+        assert!(!ProviderKind::Anthropic.is_self_hosted());
+    }
+
+    #[test]
+    fn test_anthropic_payload() {
+        let request = ChatRequest {
+            messages: vec![
+                ChatMessage { role: "system".to_string(), content: "Be brief".to_string() },
+                ChatMessage { role: "user".to_string(), content: "Hi".to_string() },
+            ],
+            model: None,
+            provider: None,
+            temperature: Some(1.5),
+            max_tokens: None,
+            conversation_id: None,
+            images: Vec::new(),
+            json: false,
+        };
+        let payload = anthropic_payload(&request, "claude-3-5-haiku-latest");
+        assert_eq!(payload["system"], "Be brief");
+        assert_eq!(payload["messages"], serde_json::json!([{ "role": "user", "content": "Hi" }]));
+        assert_eq!(payload["max_tokens"], DEFAULT_MAX_TOKENS);
+        // Anthropic's temperature tops out at 1
+        assert_eq!(payload["temperature"], 1.0);
+    }
+
+    #[test]
+    fn test_chat_completions_payload() {
+        let request = ChatRequest {
+            messages: vec![ChatMessage { role: "user".to_string(), content: "Hi".to_string() }],
+            model: None,
+            provider: None,
+            temperature: None,
+            max_tokens: Some(50),
+            conversation_id: None,
+            images: Vec::new(),
+            json: false,
+        };
+        assert_eq!(chat_completions_payload(&request, Some("gpt-4o"))["model"], "gpt-4o");
+        assert!(chat_completions_payload(&request, None).get("model").is_none());
+        assert_eq!(chat_completions_payload(&request, None)["max_tokens"], 50);
+        assert_eq!(chat_completions_payload(&request, None)["messages"][0]["content"], "Hi");
+        assert!(chat_completions_payload(&request, None).get("response_format").is_none());
+    }
+
+    #[test]
+    fn test_image_payloads() {
+        let request = ChatRequest {
+            messages: vec![
+                ChatMessage { role: "system".to_string(), content: "Inspect solar panels".to_string() },
+                ChatMessage { role: "user".to_string(), content: "Is this panel damaged?".to_string() },
+            ],
+            model: None,
+            provider: None,
+            temperature: None,
+            max_tokens: None,
+            conversation_id: None,
+            images: vec![ImageInput::Data { media_type: "image/png".to_string(), data: vec![1, 2, 3] }],
+            json: false,
+        };
+
+        let openai = chat_completions_payload(&request, Some("gpt-4o"));
+        assert_eq!(openai["messages"][0]["content"], "Inspect solar panels");
+        assert_eq!(openai["messages"][1]["content"][0]["text"], "Is this panel damaged?");
+        assert_eq!(openai["messages"][1]["content"][1]["image_url"]["url"], "data:image/png;base64,AQID");
+
+        let anthropic = anthropic_payload(&request, ANTHROPIC_VISION_MODEL);
+        let content = &anthropic["messages"][0]["content"];
+        let source = serde_json::json!({ "type": "base64", "media_type": "image/png", "data": "AQID" });
+        assert_eq!(content[0]["source"], source);
+        assert_eq!(content[1]["text"], "Is this panel damaged?");
+    }
+}
@@ -0,0 +1,499 @@
+//! Budget-aware routing of AI chat requests.
+//!
+//! Each user has a monthly AI budget in USD (larger for premium accounts). Every request is
+//! priced from the model's per-token rates and recorded. Once the remaining budget drops
+//! below the configured fraction, or would not cover the request, it is sent to the economy
+//! model instead; an exhausted budget refuses requests until the month turns over.
+//! A request that fails over is routed again on its fallback model, which may not cost more
+//! than the route that failed while the budget is low.
+//! Concurrent requests may overshoot the budget by at most their own cost.
+
+use chrono::{DateTime, Datelike, Months, TimeZone, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::config::{AiFailoverRoute, AppConfig};
+use crate::errors::{ApiError, ApiResult};
+use crate::services::ai_services::{
+    AIService, ChatMessage, ChatRequest, ChatResponse, CostEstimate, FailedRoute, RoutingDecision,
+    DEFAULT_MAX_TOKENS,
+};
+use crate::services::fine_tune_services::{self, FINE_TUNED_PREFIX};
+
+/// USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ModelPrice {
+    pub model: &'static str,
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+pub const MODEL_PRICES: &[ModelPrice] = &[
+    ModelPrice { model: "gpt-4", input_per_million: 30.0, output_per_million: 60.0 },
+    ModelPrice { model: "gpt-4-turbo", input_per_million: 10.0, output_per_million: 30.0 },
+    ModelPrice { model: "gpt-4o", input_per_million: 2.5, output_per_million: 10.0 },
+    ModelPrice { model: "gpt-4o-mini", input_per_million: 0.15, output_per_million: 0.6 },
+    ModelPrice { model: "gpt-3.5-turbo", input_per_million: 0.5, output_per_million: 1.5 },
+    ModelPrice { model: "claude-3-5-haiku-latest", input_per_million: 0.8, output_per_million: 4.0 },
+    ModelPrice { model: "claude-3-5-sonnet-latest", input_per_million: 3.0, output_per_million: 15.0 },
+];
+
+/// Rates of models fine-tuned from each base model, by the base model's name prefix; more
+/// specific prefixes first
+pub const FINE_TUNED_PRICES: &[ModelPrice] = &[
+    ModelPrice { model: "gpt-4o-mini", input_per_million: 0.3, output_per_million: 1.2 },
+    ModelPrice { model: "gpt-4o", input_per_million: 3.75, output_per_million: 15.0 },
+    ModelPrice { model: "gpt-3.5-turbo", input_per_million: 3.0, output_per_million: 6.0 },
+];
+
+pub const REASON_REQUESTED: &str = "requested";
+pub const REASON_LOW_BUDGET: &str = "low_budget";
+/// Answered by a self-hosted model, which the budget does not meter
+pub const REASON_SELF_HOSTED: &str = "self_hosted";
+
+/// Prompt tokens an image is estimated at, about what a detailed 1024px image costs
+const IMAGE_PROMPT_TOKENS: u32 = 1_000;
+
+/// Name of the price charged for models missing from the price tables
+pub const UNPRICED_MODEL: &str = "unknown";
+
+const SELF_HOSTED_PRICE: ModelPrice =
+    ModelPrice { model: "self_hosted", input_per_million: 0.0, output_per_million: 0.0 };
+
+/// A model's rates; models we have no price for are charged as the most expensive one.
+/// Fine-tuned models, `ft:<base model>:...`, are priced by their base model.
+pub fn model_price(model: &str) -> ModelPrice {
+    let fine_tuned = model.strip_prefix(FINE_TUNED_PREFIX).and_then(|rest| {
+        let base = rest.split(':').next().unwrap_or_default();
+        FINE_TUNED_PRICES.iter().find(|p| base.starts_with(p.model))
+    });
+    fine_tuned.or_else(|| MODEL_PRICES.iter().find(|p| p.model == model)).copied().unwrap_or_else(|| {
+        let highest = MODEL_PRICES.iter().max_by(|a, b| a.output_per_million.total_cmp(&b.output_per_million));
+        ModelPrice { model: UNPRICED_MODEL, ..*highest.expect("price table is not empty") }
+    })
+}
+
+pub fn cost_usd(price: &ModelPrice, prompt_tokens: u32, completion_tokens: u32) -> f64 {
+    (prompt_tokens as f64 * price.input_per_million + completion_tokens as f64 * price.output_per_million)
+        / 1_000_000.0
+}
+
+/// Rough prompt size: ~4 characters per token plus per-message framing
+pub fn estimate_prompt_tokens(messages: &[ChatMessage]) -> u32 {
+    let tokens: usize = messages.iter().map(|m| (m.role.len() + m.content.len()).div_ceil(4) + 4).sum();
+    (tokens + 3).min(u32::MAX as usize) as u32
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RoutingPolicy<'a> {
+    pub economy_model: &'a str,
+    pub economy_below_fraction: f64,
+}
+
+impl<'a> RoutingPolicy<'a> {
+    pub fn from_config(config: &'a AppConfig) -> Self {
+        Self { economy_model: &config.ai_economy_model, economy_below_fraction: config.ai_economy_below_fraction }
+    }
+}
+
+fn budget_used_up(budget: f64) -> ApiError {
+    ApiError::Forbidden(format!(
+        "The monthly AI budget of ${:.2} is used up; it resets at the start of next month",
+        budget
+    ))
+}
+
+/// Refuse requests once the month's budget is used up, for requests priced only after they run
+pub fn ensure_budget_left(status: &AiBudget) -> ApiResult<()> {
+    if status.remaining_usd <= 0.0 {
+        return Err(budget_used_up(status.monthly_budget_usd));
+    }
+    Ok(())
+}
+
+/// Pick the model for a request of `prompt_tokens` and up to `max_tokens` given what is
+/// left of the month's budget
+pub fn route(
+    policy: RoutingPolicy<'_>,
+    requested_model: &str,
+    budget: f64,
+    spent: f64,
+    prompt_tokens: u32,
+    max_tokens: u32,
+) -> ApiResult<RoutingDecision> {
+    let remaining = (budget - spent).max(0.0);
+    if remaining <= 0.0 {
+        return Err(budget_used_up(budget));
+    }
+
+    let estimate = |model: &str| cost_usd(&model_price(model), prompt_tokens, max_tokens);
+    let low = remaining < budget * policy.economy_below_fraction || estimate(requested_model) > remaining;
+    let (model, reason) = if low && estimate(policy.economy_model) < estimate(requested_model) {
+        (policy.economy_model, REASON_LOW_BUDGET)
+    } else {
+        (requested_model, REASON_REQUESTED)
+    };
+
+    Ok(RoutingDecision {
+        requested_model: requested_model.to_string(),
+        model: model.to_string(),
+        reason: reason.to_string(),
+        monthly_budget_usd: budget,
+        spent_usd: spent,
+        remaining_usd: remaining,
+        failover: None,
+    })
+}
+
+/// Route a request failing over to `fallback_model` as [`route`] would a new one. When the
+/// route that failed was metered, `policy` has the failed model as its economy model, and a
+/// fallback the budget would swap for it is skipped (`None`): the failed model lives on another
+/// provider, so the request cannot be sent to it instead.
+pub fn route_fallback(
+    policy: RoutingPolicy<'_>,
+    fallback_model: &str,
+    failed_metered: bool,
+    budget: f64,
+    spent: f64,
+    prompt_tokens: u32,
+    max_tokens: u32,
+) -> ApiResult<Option<RoutingDecision>> {
+    let decision = route(policy, fallback_model, budget, spent, prompt_tokens, max_tokens)?;
+    if failed_metered && decision.model != fallback_model {
+        return Ok(None);
+    }
+    Ok(Some(decision))
+}
+
+/// Start of the current calendar month in UTC; budgets reset then
+pub fn month_start(now: DateTime<Utc>) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0).single().unwrap_or(now)
+}
+
+/// The user's AI budget this month
+#[derive(Debug, Serialize)]
+pub struct AiBudget {
+    pub monthly_budget_usd: f64,
+    pub spent_usd: f64,
+    pub remaining_usd: f64,
+    pub requests: i64,
+    pub resets_at: DateTime<Utc>,
+    pub economy_model: String,
+    /// Requests go to the economy model while this is set
+    pub economy_active: bool,
+    pub prices: &'static [ModelPrice],
+}
+
+pub async fn budget(pool: &PgPool, config: &AppConfig, user_id: Uuid) -> ApiResult<AiBudget> {
+    let since = month_start(Utc::now());
+    let (premium, spent, requests): (bool, f64, i64) = sqlx::query_as(
+        "SELECT u.is_premium, COALESCE(SUM(a.cost_usd), 0)::DOUBLE PRECISION, COUNT(a.id) FROM users u \
+         LEFT JOIN ai_usage a ON a.user_id = u.id AND a.created_at >= $2 WHERE u.id = $1 GROUP BY u.id",
+    )
+    .bind(user_id)
+    .bind(since)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+
+    let monthly_budget_usd =
+        if premium { config.ai_premium_monthly_budget_usd } else { config.ai_free_monthly_budget_usd };
+    let remaining_usd = (monthly_budget_usd - spent).max(0.0);
+    Ok(AiBudget {
+        monthly_budget_usd,
+        spent_usd: spent,
+        remaining_usd,
+        requests,
+        resets_at: since + Months::new(1),
+        economy_model: config.ai_economy_model.clone(),
+        economy_active: remaining_usd < monthly_budget_usd * config.ai_economy_below_fraction,
+        prices: MODEL_PRICES,
+    })
+}
+
+/// One request charged against a user's AI budget
+#[derive(Debug)]
+pub struct UsageRecord<'a> {
+    pub provider: &'a str,
+    pub requested_model: &'a str,
+    pub model: &'a str,
+    pub reason: &'a str,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub estimated_usd: f64,
+    /// From what the provider reported; without it the estimate, an upper bound, is charged
+    pub actual_usd: Option<f64>,
+    /// `provider:model` of the route that failed, when the request failed over
+    pub failover_from: Option<&'a str>,
+}
+
+pub async fn record_usage(pool: &PgPool, user_id: Uuid, usage: &UsageRecord<'_>) -> ApiResult<()> {
+    sqlx::query(
+        "INSERT INTO ai_usage (user_id, provider, requested_model, model, routing_reason, prompt_tokens, \
+         completion_tokens, estimated_cost_usd, cost_usd, failover_from) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+    )
+    .bind(user_id)
+    .bind(usage.provider)
+    .bind(usage.requested_model)
+    .bind(usage.model)
+    .bind(usage.reason)
+    .bind(usage.prompt_tokens as i32)
+    .bind(usage.completion_tokens as i32)
+    .bind(usage.estimated_usd)
+    .bind(usage.actual_usd.unwrap_or(usage.estimated_usd))
+    .bind(usage.failover_from)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// The failover route of a request to `provider`'s `model`: the one for the model, else the
+/// one for the provider
+pub fn failover_route<'a>(routes: &'a [AiFailoverRoute], provider: &str, model: &str) -> Option<&'a AiFailoverRoute> {
+    let mut candidates = routes.iter().filter(|r| r.provider == provider);
+    candidates.clone().find(|r| r.model.as_deref() == Some(model)).or_else(|| candidates.find(|r| r.model.is_none()))
+}
+
+/// Answer a chat request, failing it when the provider takes longer than `secs`
+async fn answer_within(ai: &AIService, request: &ChatRequest, secs: u64) -> ApiResult<ChatResponse> {
+    tokio::time::timeout(std::time::Duration::from_secs(secs), ai.chat_completion(request))
+        .await
+        .unwrap_or_else(|_| Err(ApiError::AIServiceError(format!("No answer within {} seconds", secs))))
+}
+
+/// Answer a chat request on the model the user's budget allows, recording what it cost.
+/// The response says which model answered and why, with the estimated and actual cost. The
+/// economy model belongs to the default provider, so requests to another provider keep their
+/// model, as do requests with images; a self-hosted provider is not metered at all. When the
+/// provider fails or, with a failover route, times out, the request is retried on the route's
+/// fallback, and the response and usage record say which route failed.
+pub async fn routed_chat(
+    pool: &PgPool,
+    config: &AppConfig,
+    ai: &AIService,
+    user_id: Uuid,
+    mut request: ChatRequest,
+) -> ApiResult<ChatResponse> {
+    let status = budget(pool, config, user_id).await?;
+    let provider = ai.provider(request.provider.as_deref())?;
+    let kind = provider.kind();
+    let default_model = if request.images.is_empty() {
+        provider.default_model()
+    } else {
+        provider.vision_model().ok_or_else(|| {
+            ApiError::ValidationError(format!("AI provider {} does not accept images", kind.as_str()))
+        })?
+    };
+    let requested_model = request.model.clone().unwrap_or_else(|| default_model.to_string());
+    fine_tune_services::ensure_model_usable(pool, user_id, &requested_model).await?;
+    let prompt_tokens = estimate_prompt_tokens(&request.messages)
+        .saturating_add(IMAGE_PROMPT_TOKENS.saturating_mul(request.images.len() as u32));
+    let max_tokens = request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS);
+    let mut decision = if kind.is_self_hosted() {
+        RoutingDecision {
+            requested_model: requested_model.clone(),
+            model: requested_model.clone(),
+            reason: REASON_SELF_HOSTED.to_string(),
+            monthly_budget_usd: status.monthly_budget_usd,
+            spent_usd: status.spent_usd,
+            remaining_usd: status.remaining_usd,
+            failover: None,
+        }
+    } else {
+        let mut policy = RoutingPolicy::from_config(config);
+        // The economy model may not take images
+        if kind != ai.default_provider() || !request.images.is_empty() {
+            policy.economy_model = &requested_model;
+        }
+        route(policy, &requested_model, status.monthly_budget_usd, status.spent_usd, prompt_tokens, max_tokens)?
+    };
+
+    request.model = Some(decision.model.clone());
+    request.provider = Some(kind.as_str().to_string());
+    let route = failover_route(&config.ai_failover_routes, kind.as_str(), &decision.model);
+    let answer = match route {
+        Some(_) => answer_within(ai, &request, config.ai_failover_timeout_secs).await,
+        None => ai.chat_completion(&request).await,
+    };
+    // Only provider failures fail over; a request the provider refused would be refused again
+    let (mut response, kind, failover_from) = match (answer, route) {
+        (Err(ApiError::AIServiceError(error)), Some(route)) => {
+            let Ok(fallback) = ai.provider(Some(&route.fallback_provider)) else {
+                return Err(ApiError::AIServiceError(error));
+            };
+            let mut model = match (&route.fallback_model, request.images.is_empty()) {
+                (Some(model), _) => model.clone(),
+                (None, true) => fallback.default_model().to_string(),
+                (None, false) => fallback.vision_model().unwrap_or(fallback.default_model()).to_string(),
+            };
+            tracing::warn!(
+                "AI chat on {}:{} failed, failing over to {}:{}: {}",
+                kind.as_str(),
+                decision.model,
+                fallback.kind().as_str(),
+                model,
+                error
+            );
+            // A metered fallback must fit the budget like the original request did
+            if !fallback.kind().is_self_hosted() {
+                let failed_metered = !kind.is_self_hosted();
+                let mut policy = RoutingPolicy::from_config(config);
+                if failed_metered {
+                    policy.economy_model = &decision.model;
+                } else if fallback.kind() != ai.default_provider() || !request.images.is_empty() {
+                    policy.economy_model = &model;
+                }
+                let routed = route_fallback(
+                    policy,
+                    &model,
+                    failed_metered,
+                    status.monthly_budget_usd,
+                    status.spent_usd,
+                    prompt_tokens,
+                    max_tokens,
+                )?;
+                let Some(routed) = routed else {
+                    tracing::warn!("Skipping failover to {}: it does not fit the remaining AI budget", model);
+                    return Err(ApiError::AIServiceError(error));
+                };
+                if !failed_metered {
+                    decision.reason = routed.reason;
+                }
+                model = routed.model;
+            }
+            let failed_from = format!("{}:{}", kind.as_str(), decision.model);
+            decision.failover =
+                Some(FailedRoute { provider: kind.as_str().to_string(), model: decision.model.clone(), error });
+            decision.model = model;
+            request.model = Some(decision.model.clone());
+            request.provider = Some(fallback.kind().as_str().to_string());
+            (ai.chat_completion(&request).await?, fallback.kind(), Some(failed_from))
+        }
+        (answer, _) => (answer?, kind, None),
+    };
+
+    let price = if kind.is_self_hosted() { SELF_HOSTED_PRICE } else { model_price(&decision.model) };
+    let estimated_usd = cost_usd(&price, prompt_tokens, max_tokens);
+    let actual_usd = response.usage.as_ref().map(|u| cost_usd(&price, u.prompt_tokens, u.completion_tokens));
+    let (prompt_used, completion_used) =
+        response.usage.as_ref().map_or((prompt_tokens, max_tokens), |u| (u.prompt_tokens, u.completion_tokens));
+
+    record_usage(
+        pool,
+        user_id,
+        &UsageRecord {
+            provider: kind.as_str(),
+            requested_model: &decision.requested_model,
+            model: &decision.model,
+            reason: &decision.reason,
+            prompt_tokens: prompt_used,
+            completion_tokens: completion_used,
+            estimated_usd,
+            actual_usd,
+            failover_from: failover_from.as_deref(),
+        },
+    )
+    .await?;
+
+    response.routing = Some(decision);
+    response.cost = Some(CostEstimate { estimated_usd, actual_usd });
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: RoutingPolicy<'static> = RoutingPolicy { economy_model: "gpt-4o-mini", economy_below_fraction: 0.2 };
+
+    #[test]
+    fn test_cost_and_prices() {
+        let gpt4 = model_price("gpt-4");
+        assert!((cost_usd(&gpt4, 1000, 500) - 0.06).abs() < 1e-12);
+        // Unknown models are charged at the highest rate
+        assert_eq!(model_price("some-new-model").output_per_million, 60.0);
+        assert_eq!(model_price("ft:gpt-4o-mini-2024-07-18:acme:dock:9xYz").output_per_million, 1.2);
+        assert_eq!(model_price("ft:gpt-4o-2024-08-06:acme::9xYz").output_per_million, 15.0);
+        assert_eq!(model_price("ft:davinci-002:acme::9xYz").output_per_million, 60.0);
+    }
+
+    #[test]
+    fn test_estimate_prompt_tokens() {
+        let messages = vec![ChatMessage { role: "user".to_string(), content: "a".repeat(40) }];
+        assert_eq!(estimate_prompt_tokens(&messages), 11 + 4 + 3);
+        assert_eq!(estimate_prompt_tokens(&[]), 3);
+    }
+
+    #[test]
+    fn test_route() {
+        let decision = route(POLICY, "gpt-4", 20.0, 5.0, 500, 1000).unwrap();
+        assert_eq!((decision.model.as_str(), decision.reason.as_str()), ("gpt-4", REASON_REQUESTED));
+        assert_eq!(decision.remaining_usd, 15.0);
+
+        // Below 20% of the budget left
+        let decision = route(POLICY, "gpt-4", 20.0, 17.0, 500, 1000).unwrap();
+        assert_eq!((decision.model.as_str(), decision.reason.as_str()), ("gpt-4o-mini", REASON_LOW_BUDGET));
+        assert_eq!(decision.requested_model, "gpt-4");
+
+        // Plenty left in relative terms, but not enough for this request
+        let decision = route(POLICY, "gpt-4", 1.0, 0.0, 10_000, 20_000).unwrap();
+        assert_eq!(decision.reason, REASON_LOW_BUDGET);
+
+        // Already on a cheaper model than the economy one
+        let decision = route(POLICY, "gpt-4o-mini", 20.0, 19.0, 500, 1000).unwrap();
+        assert_eq!(decision.reason, REASON_REQUESTED);
+
+        assert!(matches!(route(POLICY, "gpt-4", 20.0, 20.0, 500, 1000), Err(ApiError::Forbidden(_))));
+        assert!(matches!(route(POLICY, "gpt-4", 0.0, 0.0, 500, 1000), Err(ApiError::Forbidden(_))));
+    }
+
+    #[test]
+    fn test_route_fallback() {
+        // Failed on the economy model for low budget; a pricier fallback is skipped
+        let failed = RoutingPolicy { economy_model: "gpt-4o-mini", ..POLICY };
+        assert!(route_fallback(failed, "gpt-4o", true, 20.0, 17.0, 500, 1000).unwrap().is_none());
+        // A fallback no pricier than the failed model is fine
+        let fallback = route_fallback(failed, "gpt-4o-mini", true, 20.0, 17.0, 500, 1000).unwrap().unwrap();
+        assert_eq!(fallback.model, "gpt-4o-mini");
+        // With budget to spare the fallback may cost more
+        let failed = RoutingPolicy { economy_model: "gpt-4o", ..POLICY };
+        let fallback = route_fallback(failed, "gpt-4", true, 20.0, 5.0, 500, 1000).unwrap().unwrap();
+        assert_eq!((fallback.model.as_str(), fallback.reason.as_str()), ("gpt-4", REASON_REQUESTED));
+        // Not enough left for a pricier fallback, even above the economy threshold
+        assert!(route_fallback(failed, "gpt-4", true, 1.0, 0.0, 10_000, 20_000).unwrap().is_none());
+
+        // An unmetered route that failed is routed like a new request
+        let fallback = route_fallback(POLICY, "gpt-4", false, 20.0, 17.0, 500, 1000).unwrap().unwrap();
+        assert_eq!((fallback.model.as_str(), fallback.reason.as_str()), ("gpt-4o-mini", REASON_LOW_BUDGET));
+        assert!(matches!(
+            route_fallback(POLICY, "gpt-4", false, 20.0, 20.0, 500, 1000),
+            Err(ApiError::Forbidden(_))
+        ));
+    }
+
+    #[test]
+    fn test_failover_route() {
+        let route = |provider: &str, model: Option<&str>, fallback: &str| AiFailoverRoute {
+            provider: provider.to_string(),
+            model: model.map(str::to_string),
+            fallback_provider: fallback.to_string(),
+            fallback_model: None,
+        };
+        let routes = [
+            route("openai", None, "azure_openai"),
+            route("openai", Some("gpt-4o"), "anthropic"),
+            route("ollama", None, "openai"),
+        ];
+        assert_eq!(failover_route(&routes, "openai", "gpt-4o").unwrap().fallback_provider, "anthropic");
+        assert_eq!(failover_route(&routes, "openai", "gpt-4o-mini").unwrap().fallback_provider, "azure_openai");
+        assert_eq!(failover_route(&routes, "ollama", "llama3.1").unwrap().fallback_provider, "openai");
+        assert!(failover_route(&routes, "anthropic", "claude-3-5-haiku-latest").is_none());
+    }
+
+    #[test]
+    fn test_month_start() {
+        let now = Utc.with_ymd_and_hms(2026, 10, 16, 13, 45, 0).unwrap();
+        assert_eq!(month_start(now), Utc.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap());
+    }
+}
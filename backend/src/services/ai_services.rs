@@ -1,111 +1,141 @@
+use actix_web::web::Bytes;
+use futures::stream::BoxStream;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
 use crate::errors::{ApiError, ApiResult};
-
-/// AI Service for handling AI-related operations
+use crate::services::ai_provider_services::{
+    AnthropicProvider, AzureOpenAiProvider, ChatProvider, OllamaProvider, OpenAiProvider, ProviderKind,
+};
+use crate::services::secret_scan_services::{scan_and_redact, SecretFinding};
+use crate::utils::crypto::base64_encode;
+
+/// The reply format code analysis asks for
+const ANALYSIS_FORMAT: &str = "Respond with a JSON object with these keys: \"analysis\", a summary of your \
+     findings as a string; \"suggestions\", \"safety_concerns\" and \"optimization_tips\", each an array of \
+     short strings, empty when there is nothing to report.";
+
+/// Model used when a chat request to OpenAI names none
+pub const DEFAULT_CHAT_MODEL: &str = "gpt-3.5-turbo";
+/// Completion length used when a chat request sets none
+pub const DEFAULT_MAX_TOKENS: u32 = 1000;
+
+/// AI Service for handling AI-related operations. Requests go to the provider set by
+/// `AI_PROVIDER` (OpenAI by default) unless they name another configured one.
 pub struct AIService {
-    api_key: Option<String>,
-    base_url: String,
+    default: ProviderKind,
+    providers: Vec<Arc<dyn ChatProvider>>,
 }
 
 impl AIService {
     pub fn new() -> Self {
-        Self {
-            api_key: std::env::var("AI_API_KEY").ok(),
-            base_url: std::env::var("AI_API_URL")
-                .unwrap_or_else(|_| "https://api.openai.com/v1".to_string()),
+        let mut providers: Vec<Arc<dyn ChatProvider>> = Vec::new();
+        if let Some(provider) = OpenAiProvider::from_env() {
+            providers.push(Arc::new(provider));
+        }
+        if let Some(provider) = AzureOpenAiProvider::from_env() {
+            providers.push(Arc::new(provider));
+        }
+        if let Some(provider) = AnthropicProvider::from_env() {
+            providers.push(Arc::new(provider));
         }
+        if let Some(provider) = OllamaProvider::from_env() {
+            providers.push(Arc::new(provider));
+        }
+        let default = match std::env::var("AI_PROVIDER").ok().filter(|p| !p.trim().is_empty()) {
+            Some(name) => ProviderKind::parse(name.trim()).unwrap_or_else(|e| {
+                tracing::warn!("{}; using openai", e);
+                ProviderKind::OpenAi
+            }),
+            None => ProviderKind::OpenAi,
+        };
+        Self::with_providers(default, providers)
+    }
+
+    pub fn with_providers(default: ProviderKind, providers: Vec<Arc<dyn ChatProvider>>) -> Self {
+        Self { default, providers }
     }
 
     /// Check if AI service is configured
     pub fn is_configured(&self) -> bool {
-        self.api_key.is_some()
+        self.providers.iter().any(|p| p.kind() == self.default)
+    }
+
+    pub fn default_provider(&self) -> ProviderKind {
+        self.default
+    }
+
+    /// The providers configured, in the order `AIService::new` checks them
+    pub fn providers(&self) -> impl Iterator<Item = &dyn ChatProvider> {
+        self.providers.iter().map(|p| p.as_ref())
+    }
+
+    /// The provider named, or the default one
+    pub fn provider(&self, name: Option<&str>) -> ApiResult<&dyn ChatProvider> {
+        let kind = match name {
+            Some(name) => ProviderKind::parse(name)?,
+            None => self.default,
+        };
+        self.providers
+            .iter()
+            .find(|p| p.kind() == kind)
+            .map(|p| p.as_ref())
+            .ok_or_else(|| ApiError::AIServiceError(format!("AI provider {} is not configured", kind.as_str())))
     }
 
     /// Generate chat completion
     pub async fn chat_completion(&self, request: &ChatRequest) -> ApiResult<ChatResponse> {
-        let api_key = self.api_key.as_ref()
-            .ok_or_else(|| ApiError::AIServiceError("AI service not configured".to_string()))?;
-
-        let client = reqwest::Client::new();
-        
-        let payload = serde_json::json!({
-            "model": request.model.as_deref().unwrap_or("gpt-3.5-turbo"),
-            "messages": request.messages,
-            "temperature": request.temperature.unwrap_or(0.7),
-            "max_tokens": request.max_tokens.unwrap_or(1000),
-        });
-
-        let response = client
-            .post(format!("{}/chat/completions", self.base_url))
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&payload)
-            .send()
-            .await
-            .map_err(|e| ApiError::AIServiceError(format!("Request failed: {}", e)))?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(ApiError::AIServiceError(format!("AI API error: {}", error_text)));
-        }
-
-        let api_response: OpenAIChatResponse = response.json().await
-            .map_err(|e| ApiError::AIServiceError(format!("Failed to parse response: {}", e)))?;
-
-        Ok(ChatResponse {
-            id: api_response.id,
-            message: api_response.choices.first()
-                .map(|c| c.message.content.clone())
-                .unwrap_or_default(),
-            model: api_response.model,
-            usage: api_response.usage.map(|u| TokenUsage {
-                prompt_tokens: u.prompt_tokens,
-                completion_tokens: u.completion_tokens,
-                total_tokens: u.total_tokens,
-            }),
-        })
+        let provider = self.provider(request.provider.as_deref())?;
+        let model = request.model.as_deref().unwrap_or(provider.default_model());
+        provider.chat(request, model).await
     }
 
-    /// Generate text embeddings
+    /// Generate text embeddings with the default provider
     pub async fn generate_embeddings(&self, text: &str) -> ApiResult<Vec<f32>> {
-        let api_key = self.api_key.as_ref()
-            .ok_or_else(|| ApiError::AIServiceError("AI service not configured".to_string()))?;
-
-        let client = reqwest::Client::new();
-        
-        let payload = serde_json::json!({
-            "model": "text-embedding-ada-002",
-            "input": text,
-        });
-
-        let response = client
-            .post(format!("{}/embeddings", self.base_url))
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&payload)
-            .send()
-            .await
-            .map_err(|e| ApiError::AIServiceError(format!("Request failed: {}", e)))?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(ApiError::AIServiceError(format!("AI API error: {}", error_text)));
-        }
+        self.provider(None)?.embed(text).await
+    }
 
-        let api_response: EmbeddingResponse = response.json().await
-            .map_err(|e| ApiError::AIServiceError(format!("Failed to parse response: {}", e)))?;
+    /// Which embeddings `generate_embeddings` makes, as `provider:model`. Vectors from
+    /// different models are not comparable.
+    pub fn embedding_model(&self) -> ApiResult<String> {
+        let provider = self.provider(None)?;
+        let model = provider.embedding_model().ok_or_else(|| {
+            ApiError::AIServiceError(format!("{} does not provide embeddings", provider.kind().as_str()))
+        })?;
+        Ok(format!("{}:{}", provider.kind().as_str(), model))
+    }
 
-        api_response.data.first()
-            .map(|d| d.embedding.clone())
-            .ok_or_else(|| ApiError::AIServiceError("No embedding returned".to_string()))
+    /// Moderation verdicts for `texts` from the provider named, one per text in order
+    pub async fn moderate(&self, provider: &str, texts: &[&str]) -> ApiResult<Vec<Moderation>> {
+        let verdicts = self.provider(Some(provider))?.moderate(texts).await?;
+        if verdicts.len() != texts.len() {
+            return Err(ApiError::AIServiceError(format!(
+                "Moderation returned {} verdicts for {} texts",
+                verdicts.len(),
+                texts.len()
+            )));
+        }
+        Ok(verdicts)
     }
 
-    /// Analyze code for robotics applications
+    /// Analyze code for robotics applications.
+    /// Embedded credentials are redacted before the code is sent to the provider. The model is
+    /// asked for JSON; a reply in prose is still mined for its suggestions, safety concerns and
+    /// optimization tips.
     pub async fn analyze_robotics_code(&self, code: &str, language: &str) -> ApiResult<CodeAnalysis> {
+        let scan = scan_and_redact(code);
+        if !scan.findings.is_empty() {
+            tracing::warn!(findings = scan.findings.len(), "Redacted secrets from code submitted for AI analysis");
+        }
+        let code = scan.redacted.as_str();
+
         let messages = vec![
             ChatMessage {
                 role: "system".to_string(),
-                content: "You are an expert robotics and embedded systems engineer. Analyze the provided code for potential issues, optimizations, and safety concerns.".to_string(),
+                content: format!(
+                    "You are an expert robotics and embedded systems engineer. Analyze the provided code for potential issues, optimizations, and safety concerns. {}",
+                    ANALYSIS_FORMAT
+                ),
             },
             ChatMessage {
                 role: "user".to_string(),
@@ -115,18 +145,26 @@ impl AIService {
 
         let request = ChatRequest {
             messages,
-            model: Some("gpt-4".to_string()),
+            // Other providers answer with their own default model. gpt-4 itself has no JSON mode.
+            model: (self.default == ProviderKind::OpenAi).then(|| "gpt-4o".to_string()),
+            provider: None,
             temperature: Some(0.3),
             max_tokens: Some(2000),
+            conversation_id: None,
+            images: Vec::new(),
+            json: true,
         };
 
         let response = self.chat_completion(&request).await?;
+        let parsed = parse_code_analysis(&response.message);
 
         Ok(CodeAnalysis {
-            analysis: response.message,
-            suggestions: vec![],
-            safety_concerns: vec![],
-            optimization_tips: vec![],
+            analysis: parsed.analysis,
+            suggestions: parsed.suggestions,
+            safety_concerns: parsed.safety_concerns,
+            optimization_tips: parsed.optimization_tips,
+            secrets_warning: scan.warning(),
+            redacted_secrets: scan.findings,
         })
     }
 }
@@ -138,7 +176,7 @@ impl Default for AIService {
 }
 
 // Request/Response types
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: String,
     pub content: String,
@@ -148,8 +186,38 @@ pub struct ChatMessage {
 pub struct ChatRequest {
     pub messages: Vec<ChatMessage>,
     pub model: Option<String>,
+    /// openai, azure_openai, anthropic or ollama; the configured default when left out
+    pub provider: Option<String>,
     pub temperature: Option<f32>,
     pub max_tokens: Option<u32>,
+    /// Continue this conversation, its earlier messages sent as context; a new one is started
+    /// when left out
+    pub conversation_id: Option<Uuid>,
+    /// Images sent with the last user message; only the vision endpoint attaches them
+    #[serde(skip)]
+    pub images: Vec<ImageInput>,
+    /// Ask for the reply as a JSON object, on providers with a JSON mode. The prompt must still
+    /// ask for JSON and describe it.
+    #[serde(skip)]
+    pub json: bool,
+}
+
+/// An image sent to a vision model
+#[derive(Debug, Clone)]
+pub enum ImageInput {
+    /// Fetched by the provider
+    Url(String),
+    Data { media_type: String, data: Vec<u8> },
+}
+
+impl ImageInput {
+    /// The image as a URL: its own, or a `data:` URL of its bytes
+    pub fn url(&self) -> String {
+        match self {
+            ImageInput::Url(url) => url.clone(),
+            ImageInput::Data { media_type, data } => format!("data:{};base64,{}", media_type, base64_encode(data)),
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -157,7 +225,48 @@ pub struct ChatResponse {
     pub id: String,
     pub message: String,
     pub model: String,
+    /// The provider that answered
+    pub provider: String,
     pub usage: Option<TokenUsage>,
+    /// Why this model answered, when the request was routed against the user's AI budget
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub routing: Option<RoutingDecision>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost: Option<CostEstimate>,
+    /// The conversation the exchange was saved to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RoutingDecision {
+    pub requested_model: String,
+    pub model: String,
+    /// `requested`, `low_budget` when a cheaper model was substituted, or `self_hosted` when
+    /// the budget does not apply
+    pub reason: String,
+    pub monthly_budget_usd: f64,
+    /// Spent this month before this request
+    pub spent_usd: f64,
+    pub remaining_usd: f64,
+    /// The route that failed, when the request was answered by its failover route instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failover: Option<FailedRoute>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedRoute {
+    pub provider: String,
+    pub model: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CostEstimate {
+    /// Upper bound from the prompt length and `max_tokens`, made before the request
+    pub estimated_usd: f64,
+    /// From the token usage the provider reported
+    pub actual_usd: Option<f64>,
 }
 
 #[derive(Debug, Serialize)]
@@ -167,48 +276,163 @@ pub struct TokenUsage {
     pub total_tokens: u32,
 }
 
+/// Recorded audio to transcribe
+#[derive(Debug, Clone)]
+pub struct AudioInput {
+    /// With the extension matching the format, which is how Whisper APIs tell formats apart
+    pub file_name: String,
+    pub media_type: String,
+    pub data: Vec<u8>,
+    /// ISO-639-1 code of the spoken language; detected when left out
+    pub language: Option<String>,
+    /// Vocabulary to expect, e.g. robot and command names
+    pub prompt: Option<String>,
+}
+
 #[derive(Debug, Serialize)]
-pub struct CodeAnalysis {
-    pub analysis: String,
-    pub suggestions: Vec<String>,
-    pub safety_concerns: Vec<String>,
-    pub optimization_tips: Vec<String>,
+pub struct Transcription {
+    pub text: String,
+    pub language: Option<String>,
+    pub duration_seconds: Option<f64>,
+    pub segments: Vec<TranscriptSegment>,
+    pub model: String,
+    pub provider: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost: Option<CostEstimate>,
 }
 
-// OpenAI API response structures
-#[derive(Debug, Deserialize)]
-struct OpenAIChatResponse {
-    id: String,
-    model: String,
-    choices: Vec<OpenAIChoice>,
-    usage: Option<OpenAIUsage>,
+/// A stretch of the transcript with its time in the recording, in seconds
+#[derive(Debug, Serialize)]
+pub struct TranscriptSegment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
 }
 
-#[derive(Debug, Deserialize)]
-struct OpenAIChoice {
-    message: OpenAIMessage,
+/// Text to read out
+#[derive(Debug, Clone)]
+pub struct SpeechInput {
+    pub text: String,
+    /// A voice of the provider, e.g. `alloy`
+    pub voice: String,
+    /// Audio format, e.g. `mp3` or `opus`
+    pub format: String,
 }
 
-#[derive(Debug, Deserialize)]
-struct OpenAIMessage {
-    content: String,
+/// Synthesized audio, streamed as the provider produces it
+pub struct SpeechAudio {
+    pub model: String,
+    pub stream: BoxStream<'static, ApiResult<Bytes>>,
 }
 
-#[derive(Debug, Deserialize)]
-struct OpenAIUsage {
-    prompt_tokens: u32,
-    completion_tokens: u32,
-    total_tokens: u32,
+/// Fine-tuning examples in the provider's JSONL chat format
+#[derive(Debug, Clone)]
+pub struct TrainingData {
+    pub file_name: String,
+    pub data: Vec<u8>,
 }
 
-#[derive(Debug, Deserialize)]
-struct EmbeddingResponse {
-    data: Vec<EmbeddingData>,
+/// A fine-tuning job as the provider reports it
+#[derive(Debug, Clone)]
+pub struct FineTuneJob {
+    pub id: String,
+    /// e.g. `queued`, `running`, `succeeded` or `failed`
+    pub status: String,
+    /// The trained model's ID, once the job has succeeded
+    pub fine_tuned_model: Option<String>,
+    pub trained_tokens: Option<i64>,
+    pub error: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
-struct EmbeddingData {
-    embedding: Vec<f32>,
+#[derive(Debug, Clone, Default)]
+pub struct Moderation {
+    pub flagged: bool,
+    /// Categories the text was flagged for, e.g. `harassment` or `violence`
+    pub categories: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CodeAnalysis {
+    pub analysis: String,
+    pub suggestions: Vec<String>,
+    pub safety_concerns: Vec<String>,
+    pub optimization_tips: Vec<String>,
+    pub secrets_warning: Option<String>,
+    pub redacted_secrets: Vec<SecretFinding>,
+}
+
+/// The fields of a code analysis reply
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ParsedAnalysis {
+    pub analysis: String,
+    pub suggestions: Vec<String>,
+    pub safety_concerns: Vec<String>,
+    pub optimization_tips: Vec<String>,
+}
+
+/// Read a code analysis reply: the JSON object asked for, possibly wrapped in a code fence, or
+/// failing that prose, whose bulleted lists under headings about suggestions, safety and
+/// optimization fill those fields
+pub fn parse_code_analysis(reply: &str) -> ParsedAnalysis {
+    let json = reply
+        .find('{')
+        .zip(reply.rfind('}'))
+        .filter(|(start, end)| start < end)
+        .and_then(|(start, end)| serde_json::from_str::<ParsedAnalysis>(&reply[start..=end]).ok())
+        // Any object parses; one with none of the fields is something else, e.g. code in prose
+        .filter(|parsed| *parsed != ParsedAnalysis::default());
+    if let Some(mut parsed) = json {
+        for list in [&mut parsed.suggestions, &mut parsed.safety_concerns, &mut parsed.optimization_tips] {
+            list.retain(|item| !item.trim().is_empty());
+        }
+        return parsed;
+    }
+
+    let mut parsed = ParsedAnalysis { analysis: reply.trim().to_string(), ..Default::default() };
+    let mut section: Option<usize> = None;
+    for line in reply.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let item = bullet_text(line);
+        if item.is_none() || line.starts_with('#') {
+            // A heading, or prose that ends the list before it
+            let heading =
+                line.trim_matches(|c: char| c == '#' || c == '*' || c == ':' || c.is_whitespace()).to_lowercase();
+            section = if heading.len() > 60 {
+                None
+            } else if heading.contains("safety") || heading.contains("risk") || heading.contains("hazard") {
+                Some(1)
+            } else if heading.contains("optimi") || heading.contains("performance") {
+                Some(2)
+            } else if heading.contains("suggest") || heading.contains("recommend") || heading.contains("improve") {
+                Some(0)
+            } else {
+                None
+            };
+            continue;
+        }
+        let list = match section {
+            Some(0) => &mut parsed.suggestions,
+            Some(1) => &mut parsed.safety_concerns,
+            Some(2) => &mut parsed.optimization_tips,
+            _ => continue,
+        };
+        list.extend(item.map(str::to_string));
+    }
+    parsed
+}
+
+/// The text of a bulleted or numbered list item
+fn bullet_text(line: &str) -> Option<&str> {
+    let rest = line
+        .strip_prefix("- ")
+        .or_else(|| line.strip_prefix("* "))
+        .or_else(|| line.strip_prefix("• "))
+        .or_else(|| {
+            let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let after = &line[digits..];
+            (digits > 0).then(|| after.strip_prefix(". ").or_else(|| after.strip_prefix(") "))).flatten()
+        })?;
+    Some(rest.trim()).filter(|r| !r.is_empty())
 }
 
 #[cfg(test)]
@@ -217,9 +441,38 @@ mod tests {
 
     #[test]
     fn test_ai_service_creation() {
-        let service = AIService::new();
         // Service should be created even without API key
-        assert!(service.base_url.contains("openai"));
+        let _service = AIService::new();
+        let openai = OpenAiProvider::new(secrecy::SecretString::from("sk-test"), None);
+        assert!(openai.base_url.contains("openai"));
+
+        let service = AIService::with_providers(ProviderKind::Anthropic, vec![Arc::new(openai)]);
+        assert!(!service.is_configured());
+        assert_eq!(service.provider(Some("openai")).unwrap().kind(), ProviderKind::OpenAi);
+        assert!(matches!(service.provider(None), Err(ApiError::AIServiceError(_))));
+        assert!(matches!(service.provider(Some("gemini")), Err(ApiError::ValidationError(_))));
+    }
+
+    #[test]
+    fn test_parse_code_analysis() {
+        let reply = "```json\n{\"analysis\": \"Mostly sound\", \"suggestions\": [\"Name the magic numbers\", \"\"], \
+                     \"safety_concerns\": [\"No watchdog on the motor loop\"]}\n```";
+        let parsed = parse_code_analysis(reply);
+        assert_eq!(parsed.analysis, "Mostly sound");
+        assert_eq!(parsed.suggestions, ["Name the magic numbers"]);
+        assert_eq!(parsed.safety_concerns, ["No watchdog on the motor loop"]);
+        assert!(parsed.optimization_tips.is_empty());
+
+        let prose = "The loop works.\n\n## Safety Concerns\n- No emergency stop\n- Unchecked PWM duty cycle\n\n\
+                     **Optimization tips:**\n1. Cache the sensor reads\n\n\
+                     Suggestions\n* Add unit tests\n\nOverall fine.";
+        let parsed = parse_code_analysis(prose);
+        assert_eq!(parsed.analysis, prose);
+        assert_eq!(parsed.safety_concerns, ["No emergency stop", "Unchecked PWM duty cycle"]);
+        assert_eq!(parsed.optimization_tips, ["Cache the sensor reads"]);
+        assert_eq!(parsed.suggestions, ["Add unit tests"]);
+
+        assert_eq!(parse_code_analysis("No issues found.").analysis, "No issues found.");
     }
 
     #[test]
@@ -0,0 +1,98 @@
+//! Peripherals mounted on devices and the command checks they drive
+
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::services::robotics_services::device_type_spec;
+
+/// Peripherals a device can carry
+pub const ATTACHMENT_TYPES: &[&str] = &["gripper", "lidar", "sprayer"];
+
+/// Attachments one device may mount
+pub const MAX_ATTACHMENTS_PER_DEVICE: usize = 16;
+
+pub const ATTACHMENT_COLUMNS: &str = "id, device_id, attachment_type, name, model, serial_number, config, \
+     enabled, created_at, updated_at";
+
+/// Select-list expression for the enabled attachment types of the device aliased `d`
+pub const ATTACHED_TYPES_SQL: &str =
+    "ARRAY(SELECT DISTINCT attachment_type FROM device_attachments WHERE device_id = d.id AND enabled)";
+
+pub fn validate_attachment_type(attachment_type: &str) -> ApiResult<()> {
+    if !ATTACHMENT_TYPES.contains(&attachment_type) {
+        return Err(ApiError::ValidationError(format!(
+            "Unknown attachment type '{}'. Valid types: {:?}",
+            attachment_type, ATTACHMENT_TYPES
+        )));
+    }
+    Ok(())
+}
+
+pub fn validate_label(field: &str, value: &str) -> ApiResult<()> {
+    if value.trim().is_empty() || value.chars().count() > 64 {
+        return Err(ApiError::ValidationError(format!("{} must be 1-64 characters", field)));
+    }
+    Ok(())
+}
+
+pub fn validate_config(config: &serde_json::Value) -> ApiResult<()> {
+    if !config.is_object() {
+        return Err(ApiError::ValidationError("config must be a JSON object".to_string()));
+    }
+    Ok(())
+}
+
+/// Reject a command whose capability needs an attachment the device does not have enabled.
+/// Unknown device types and commands are left to `validate_command`.
+pub fn require_attachment(device_type: &str, command: &str, attached: &[String]) -> ApiResult<()> {
+    let required = device_type_spec(device_type)
+        .and_then(|spec| spec.commands.iter().find(|c| c.command == command))
+        .and_then(|c| c.requires_attachment);
+    match required {
+        Some(attachment) if !attached.iter().any(|a| a == attachment) => Err(ApiError::ValidationError(format!(
+            "Command '{}' requires an enabled {} attachment",
+            command, attachment
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Enabled attachment types of a device
+pub async fn attached_types(executor: impl sqlx::PgExecutor<'_>, device_id: Uuid) -> ApiResult<Vec<String>> {
+    let types = sqlx::query_scalar(
+        "SELECT DISTINCT attachment_type FROM device_attachments WHERE device_id = $1 AND enabled",
+    )
+    .bind(device_id)
+    .fetch_all(executor)
+    .await?;
+    Ok(types)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_require_attachment() {
+        let gripper = vec!["gripper".to_string()];
+        let lidar = vec!["lidar".to_string()];
+
+        assert!(require_attachment("robot", "grab", &gripper).is_ok());
+        assert!(require_attachment("robot", "release", &gripper).is_ok());
+        let err = require_attachment("robot", "grab", &lidar).unwrap_err();
+        assert!(err.to_string().contains("gripper"));
+        assert!(require_attachment("robot", "release", &[]).is_err());
+        // Commands without a tool need nothing mounted
+        assert!(require_attachment("robot", "move_forward", &[]).is_ok());
+        assert!(require_attachment("drone", "takeoff", &[]).is_ok());
+    }
+
+    #[test]
+    fn test_definition_validation() {
+        assert!(validate_attachment_type("lidar").is_ok());
+        assert!(validate_attachment_type("laser").is_err());
+        assert!(validate_label("name", "front gripper").is_ok());
+        assert!(validate_label("name", " ").is_err());
+        assert!(validate_config(&serde_json::json!({ "max_force_n": 40 })).is_ok());
+        assert!(validate_config(&serde_json::json!([1, 2])).is_err());
+    }
+}
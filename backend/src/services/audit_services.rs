@@ -0,0 +1,67 @@
+//! Audit trail recording for org-scoped changes
+
+use sqlx::PgConnection;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+
+/// A single audit trail entry
+pub struct AuditEntry<'a> {
+    pub org_id: Option<Uuid>,
+    pub actor_id: Option<Uuid>,
+    pub action: &'a str,
+    pub resource_type: &'a str,
+    pub resource_id: Option<String>,
+    pub details: serde_json::Value,
+}
+
+/// Append an entry to the audit log, returning its id.
+/// Takes a connection so it can join the caller's transaction.
+pub async fn record(conn: &mut PgConnection, entry: AuditEntry<'_>) -> ApiResult<i64> {
+    let id = sqlx::query_scalar(
+        "INSERT INTO audit_logs (org_id, actor_id, action, resource_type, resource_id, details) \
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+    )
+    .bind(entry.org_id)
+    .bind(entry.actor_id)
+    .bind(entry.action)
+    .bind(entry.resource_type)
+    .bind(&entry.resource_id)
+    .bind(&entry.details)
+    .fetch_one(conn)
+    .await?;
+
+    Ok(id)
+}
+
+/// Render rows as CSV with a header line
+pub fn to_csv(headers: &[&str], rows: impl IntoIterator<Item = Vec<String>>) -> ApiResult<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let csv_error = |e: csv::Error| ApiError::InternalError(format!("CSV export failed: {}", e));
+
+    writer.write_record(headers).map_err(csv_error)?;
+    for row in rows {
+        writer.write_record(&row).map_err(csv_error)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| ApiError::InternalError(format!("CSV export failed: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_csv_quotes_fields() {
+        let out = to_csv(
+            &["action", "details"],
+            vec![vec!["sso.config_updated".to_string(), r#"{"a":1,"b":2}"#.to_string()]],
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "action,details\nsso.config_updated,\"{\"\"a\"\":1,\"\"b\"\":2}\"\n"
+        );
+    }
+}
@@ -0,0 +1,819 @@
+//! Rule-based automations: a trigger starts a walk over a graph of condition and action nodes.
+//!
+//! Graphs are immutable once saved; editing one stores a new version and every run records
+//! the version it executed. Evaluation (`plan`) is pure, so a dry run is the same walk with
+//! the actions left unperformed.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::automation::{Action, Automation, AutomationRun, Condition, NodeKind, RuleGraph, Trigger};
+use crate::services::command_services::issue_command;
+use crate::services::device_services::{get_owned_device, owned_device_scope};
+use crate::services::notification_services::notify_user;
+use crate::services::transport_services::TransportRegistry;
+use crate::services::webhook_services::validate_url;
+use crate::utils::crypto::{generate_random_hex, sha256_hash};
+
+pub const AUTOMATION_COLUMNS: &str =
+    "id, user_id, name, enabled, current_version, trigger_kind, last_triggered_at, created_at, updated_at";
+
+pub const RUN_COLUMNS: &str =
+    "id, automation_id, version, trigger_kind, event, dry_run, status, steps, error, started_at, finished_at";
+
+pub const MAX_AUTOMATIONS_PER_USER: i64 = 50;
+
+pub const MAX_NODES: usize = 50;
+
+pub const CONDITION_OPS: &[&str] = &["eq", "ne", "lt", "lte", "gt", "gte", "contains", "exists"];
+
+/// Telemetry automations fire at most this often unless the trigger sets its own cooldown
+pub const DEFAULT_TELEMETRY_COOLDOWN_MINUTES: u32 = 5;
+
+const MAX_SCHEDULE_MINUTES: u32 = 7 * 24 * 60;
+const JOB_INTERVAL_SECS: u64 = 30;
+const EVENT_BATCH: i64 = 100;
+const CALL_TIMEOUT_SECS: u64 = 10;
+
+/// One visited node of a run: conditions record whether they passed, actions the rendered
+/// action and, unless dry-run, how performing it went
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RunStep {
+    pub node_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<Action>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// An enabled automation with the graph of its current version
+#[derive(Debug, FromRow)]
+struct ActiveAutomation {
+    #[sqlx(flatten)]
+    automation: Automation,
+    graph: sqlx::types::Json<RuleGraph>,
+}
+
+/// Structural checks on a graph before it is stored
+pub fn validate_graph(graph: &RuleGraph) -> ApiResult<()> {
+    let invalid = |msg: String| Err(ApiError::ValidationError(msg));
+
+    match &graph.trigger {
+        Trigger::Telemetry { condition, .. } | Trigger::Metric { condition, .. } => validate_condition(condition)?,
+        Trigger::Schedule { every_minutes } if *every_minutes == 0 || *every_minutes > MAX_SCHEDULE_MINUTES => {
+            return invalid(format!("every_minutes must be between 1 and {}", MAX_SCHEDULE_MINUTES));
+        }
+        Trigger::PaymentCompleted { min_amount: Some(amount), .. } if *amount < 0.0 => {
+            return invalid("min_amount cannot be negative".to_string());
+        }
+        _ => {}
+    }
+
+    if graph.nodes.is_empty() || graph.nodes.len() > MAX_NODES {
+        return invalid(format!("A graph needs between 1 and {} nodes", MAX_NODES));
+    }
+    let mut nodes: HashMap<&str, &NodeKind> = HashMap::new();
+    for node in &graph.nodes {
+        if node.id.is_empty() || node.id.len() > 64 || node.id == "trigger" {
+            return invalid(format!("Invalid node id '{}'", node.id));
+        }
+        if nodes.insert(node.id.as_str(), &node.kind).is_some() {
+            return invalid(format!("Duplicate node id '{}'", node.id));
+        }
+        match &node.kind {
+            NodeKind::Condition { condition } => validate_condition(condition)?,
+            NodeKind::Action { action } => validate_action(action)?,
+        }
+    }
+    if !nodes.values().any(|kind| matches!(kind, NodeKind::Action { .. })) {
+        return invalid("A graph needs at least one action".to_string());
+    }
+
+    for edge in &graph.edges {
+        let from_condition = match nodes.get(edge.from.as_str()) {
+            Some(kind) => matches!(kind, NodeKind::Condition { .. }),
+            None if edge.from == "trigger" => false,
+            None => return invalid(format!("Edge from unknown node '{}'", edge.from)),
+        };
+        if !nodes.contains_key(edge.to.as_str()) {
+            return invalid(format!("Edge to unknown node '{}'", edge.to));
+        }
+        if from_condition != edge.when.is_some() {
+            return invalid(format!(
+                "Edge {} -> {}: edges leaving a condition must set `when`, others must not",
+                edge.from, edge.to
+            ));
+        }
+    }
+
+    // Kahn's algorithm: every node must be placed, otherwise there is a cycle
+    let mut indegree: HashMap<&str, usize> = nodes.keys().map(|id| (*id, 0)).collect();
+    for edge in graph.edges.iter().filter(|e| e.from != "trigger") {
+        *indegree.entry(edge.to.as_str()).or_default() += 1;
+    }
+    let mut ready: Vec<&str> = indegree.iter().filter(|(_, d)| **d == 0).map(|(id, _)| *id).collect();
+    let mut placed = 0;
+    while let Some(id) = ready.pop() {
+        placed += 1;
+        for edge in graph.edges.iter().filter(|e| e.from == id) {
+            let d = indegree.get_mut(edge.to.as_str()).expect("edge targets are known nodes");
+            *d -= 1;
+            if *d == 0 {
+                ready.push(edge.to.as_str());
+            }
+        }
+    }
+    if placed != nodes.len() {
+        return invalid("The graph contains a cycle".to_string());
+    }
+    Ok(())
+}
+
+pub fn validate_condition(condition: &Condition) -> ApiResult<()> {
+    if condition.field.trim().is_empty() {
+        return Err(ApiError::ValidationError("Condition field is required".to_string()));
+    }
+    if !CONDITION_OPS.contains(&condition.op.as_str()) {
+        return Err(ApiError::ValidationError(format!(
+            "Unknown condition op '{}'; expected one of {}",
+            condition.op,
+            CONDITION_OPS.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+fn validate_action(action: &Action) -> ApiResult<()> {
+    match action {
+        Action::SendCommand { command, .. } if command.trim().is_empty() => {
+            Err(ApiError::ValidationError("send_command needs a command".to_string()))
+        }
+        Action::Notify { title, .. } if title.trim().is_empty() || title.len() > 200 => {
+            Err(ApiError::ValidationError("notify title must be 1-200 characters".to_string()))
+        }
+        Action::CallWebhook { url, .. } => validate_url(url),
+        Action::ToggleFeature { flag, .. }
+            if flag.is_empty()
+                || flag.len() > 64
+                || !flag.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "_.-".contains(c)) =>
+        {
+            Err(ApiError::ValidationError(
+                "flag must be 1-64 lowercase letters, digits, '_', '.' or '-'".to_string(),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Devices a graph sends commands to or watches, for ownership checks
+pub fn command_targets(graph: &RuleGraph) -> Vec<Uuid> {
+    graph
+        .nodes
+        .iter()
+        .filter_map(|node| match &node.kind {
+            NodeKind::Action { action: Action::SendCommand { device_id, .. } } => Some(*device_id),
+            _ => None,
+        })
+        .chain(match &graph.trigger {
+            Trigger::Telemetry { device_id, .. } | Trigger::Metric { device_id, .. } => *device_id,
+            _ => None,
+        })
+        .collect()
+}
+
+/// Graph checks plus ownership of every device it refers to; a metric trigger must belong
+/// to an org the user is an active member of
+pub async fn check_graph(pool: &PgPool, user_id: Uuid, graph: &RuleGraph) -> ApiResult<()> {
+    validate_graph(graph)?;
+    owned_device_scope(pool, user_id, Some(&command_targets(graph))).await?;
+    if let Trigger::Metric { metric_id, .. } = &graph.trigger {
+        let visible: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM org_metrics m JOIN org_memberships om ON om.org_id = m.org_id \
+             WHERE m.id = $1 AND om.user_id = $2 AND om.active)",
+        )
+        .bind(metric_id)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+        if !visible {
+            return Err(ApiError::NotFound("Metric not found".to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// Inbound hook token and the hash stored for it
+pub fn new_hook_token() -> (String, String) {
+    let token = generate_random_hex(32);
+    let hash = sha256_hash(token.as_bytes());
+    (token, hash)
+}
+
+/// Store a checked graph as version 1 of a new automation. Returns the automation and the
+/// token for its inbound hook, which is not retrievable later.
+pub async fn create_automation(
+    pool: &PgPool,
+    user_id: Uuid,
+    name: &str,
+    graph: &RuleGraph,
+    enabled: bool,
+) -> ApiResult<(Automation, String)> {
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM automations WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+    if count >= MAX_AUTOMATIONS_PER_USER {
+        return Err(ApiError::ValidationError(format!(
+            "At most {} automations per account",
+            MAX_AUTOMATIONS_PER_USER
+        )));
+    }
+
+    let (token, token_hash) = new_hook_token();
+    let mut tx = pool.begin().await?;
+    let automation = sqlx::query_as::<_, Automation>(&format!(
+        "INSERT INTO automations (user_id, name, enabled, trigger_kind, hook_token_hash) \
+         VALUES ($1, $2, $3, $4, $5) RETURNING {}",
+        AUTOMATION_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(name)
+    .bind(enabled)
+    .bind(graph.trigger.kind())
+    .bind(&token_hash)
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query("INSERT INTO automation_versions (automation_id, version, graph, created_by) VALUES ($1, 1, $2, $3)")
+        .bind(automation.id)
+        .bind(sqlx::types::Json(graph))
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok((automation, token))
+}
+
+/// Resolve a dotted path (`position.latitude`, `sensors.0.value`) inside an event
+pub fn lookup<'a>(event: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('.').try_fold(event, |value, segment| match value {
+        serde_json::Value::Object(map) => map.get(segment),
+        serde_json::Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => None,
+    })
+}
+
+pub fn evaluate(condition: &Condition, event: &serde_json::Value) -> bool {
+    let Some(actual) = lookup(event, &condition.field).filter(|v| !v.is_null()) else {
+        return false;
+    };
+    let expected = &condition.value;
+    let numbers = actual.as_f64().zip(expected.as_f64());
+    match condition.op.as_str() {
+        "exists" => true,
+        "eq" => numbers.map_or(actual == expected, |(a, b)| a == b),
+        "ne" => numbers.map_or(actual != expected, |(a, b)| a != b),
+        "lt" => numbers.is_some_and(|(a, b)| a < b),
+        "lte" => numbers.is_some_and(|(a, b)| a <= b),
+        "gt" => numbers.is_some_and(|(a, b)| a > b),
+        "gte" => numbers.is_some_and(|(a, b)| a >= b),
+        "contains" => match (actual, expected) {
+            (serde_json::Value::String(s), serde_json::Value::String(needle)) => s.contains(needle.as_str()),
+            (serde_json::Value::Array(items), needle) => items.contains(needle),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Fill `{{path}}` placeholders from the event; missing values render as empty
+pub fn render(template: &str, event: &serde_json::Value) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        match lookup(event, rest[start + 2..start + 2 + len].trim()) {
+            Some(serde_json::Value::String(s)) => out.push_str(s),
+            Some(serde_json::Value::Null) | None => {}
+            Some(other) => out.push_str(&other.to_string()),
+        }
+        rest = &rest[start + 2 + len + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn render_value(value: &serde_json::Value, event: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::String(s) => serde_json::Value::String(render(s, event)),
+        serde_json::Value::Array(items) => items.iter().map(|v| render_value(v, event)).collect(),
+        serde_json::Value::Object(map) => {
+            map.iter().map(|(k, v)| (k.clone(), render_value(v, event))).collect::<serde_json::Map<_, _>>().into()
+        }
+        other => other.clone(),
+    }
+}
+
+fn render_action(action: &Action, event: &serde_json::Value) -> Action {
+    match action {
+        Action::SendCommand { device_id, command, parameters } => Action::SendCommand {
+            device_id: *device_id,
+            command: command.clone(),
+            parameters: render_value(parameters, event),
+        },
+        Action::Notify { title, body } => Action::Notify { title: render(title, event), body: render(body, event) },
+        Action::CallWebhook { url, body } => Action::CallWebhook {
+            url: url.clone(),
+            body: body.as_ref().map(|b| render_value(b, event)),
+        },
+        Action::ToggleFeature { .. } => action.clone(),
+    }
+}
+
+/// Whether an event satisfies the trigger's own filter
+pub fn trigger_matches(trigger: &Trigger, event: &serde_json::Value) -> bool {
+    match trigger {
+        Trigger::Telemetry { device_id, condition, .. } => {
+            let device_ok = device_id.is_none_or(|id| {
+                lookup(event, "device_id").and_then(|v| v.as_str()) == Some(id.to_string().as_str())
+            });
+            device_ok && evaluate(condition, event)
+        }
+        Trigger::Metric { metric_id, device_id, condition } => {
+            let is = |path: &str, id: &Uuid| {
+                lookup(event, path).and_then(|v| v.as_str()) == Some(id.to_string().as_str())
+            };
+            is("metric_id", metric_id)
+                && device_id.is_none_or(|id| is("device_id", &id))
+                && evaluate(condition, event)
+        }
+        Trigger::PaymentCompleted { product_type, min_amount } => {
+            product_type
+                .as_deref()
+                .is_none_or(|p| lookup(event, "product_type").and_then(|v| v.as_str()) == Some(p))
+                && min_amount.is_none_or(|min| lookup(event, "amount").and_then(|v| v.as_f64()).is_some_and(|a| a >= min))
+        }
+        Trigger::Schedule { .. } | Trigger::Webhook => true,
+    }
+}
+
+/// Walk the graph breadth-first from the trigger. Conditions choose which of their edges
+/// are followed; each node runs at most once even when several branches reach it.
+pub fn plan(graph: &RuleGraph, event: &serde_json::Value) -> Vec<RunStep> {
+    let nodes: HashMap<&str, &NodeKind> = graph.nodes.iter().map(|n| (n.id.as_str(), &n.kind)).collect();
+    let mut branch: HashMap<&str, bool> = HashMap::new();
+    let mut visited: HashSet<&str> = HashSet::new();
+    let mut queue: VecDeque<&str> = VecDeque::from(["trigger"]);
+    let mut steps = Vec::new();
+
+    while let Some(from) = queue.pop_front() {
+        for edge in graph.edges.iter().filter(|e| e.from == from) {
+            if edge.when.is_some() && edge.when != branch.get(from).copied() {
+                continue;
+            }
+            let Some(kind) = nodes.get(edge.to.as_str()) else {
+                continue;
+            };
+            if !visited.insert(edge.to.as_str()) {
+                continue;
+            }
+            let mut step = RunStep { node_id: edge.to.clone(), passed: None, action: None, output: None, error: None };
+            match kind {
+                NodeKind::Condition { condition } => {
+                    let passed = evaluate(condition, event);
+                    branch.insert(edge.to.as_str(), passed);
+                    step.passed = Some(passed);
+                }
+                NodeKind::Action { action } => step.action = Some(render_action(action, event)),
+            }
+            steps.push(step);
+            queue.push_back(edge.to.as_str());
+        }
+    }
+    steps
+}
+
+async fn perform(
+    pool: &PgPool,
+    transports: &TransportRegistry,
+    automation: &Automation,
+    action: &Action,
+    event: &serde_json::Value,
+) -> ApiResult<serde_json::Value> {
+    match action {
+        Action::SendCommand { device_id, command, parameters } => {
+            let device = get_owned_device(pool, *device_id, automation.user_id).await?;
+            let result =
+                issue_command(pool, transports, automation.user_id, &device, command, parameters, false).await?;
+            Ok(serde_json::json!({ "command_id": result.command_id, "status": result.status }))
+        }
+        Action::Notify { title, body } => {
+            let mut conn = pool.acquire().await?;
+            notify_user(
+                &mut conn,
+                automation.user_id,
+                "automation",
+                title,
+                body,
+                serde_json::json!({ "automation_id": automation.id }),
+            )
+            .await?;
+            Ok(serde_json::json!({ "notified": true }))
+        }
+        Action::CallWebhook { url, body } => {
+            validate_url(url)?;
+            let client = reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(CALL_TIMEOUT_SECS))
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .map_err(|e| ApiError::InternalError(format!("HTTP client error: {}", e)))?;
+            let payload = body
+                .clone()
+                .unwrap_or_else(|| serde_json::json!({ "automation_id": automation.id, "event": event }));
+            let response = client
+                .post(url)
+                .header("X-Automation-Id", automation.id.to_string())
+                .json(&payload)
+                .send()
+                .await
+                .map_err(|e| ApiError::ExternalServiceError(format!("Webhook call failed: {}", e)))?;
+            let status = response.status();
+            if !status.is_success() {
+                return Err(ApiError::ExternalServiceError(format!("Webhook returned {}", status)));
+            }
+            Ok(serde_json::json!({ "status_code": status.as_u16() }))
+        }
+        Action::ToggleFeature { flag, enabled } => {
+            sqlx::query(
+                "INSERT INTO user_feature_flags (user_id, flag, enabled) VALUES ($1, $2, $3) \
+                 ON CONFLICT (user_id, flag) DO UPDATE SET enabled = EXCLUDED.enabled, updated_at = NOW()",
+            )
+            .bind(automation.user_id)
+            .bind(flag)
+            .bind(enabled)
+            .execute(pool)
+            .await?;
+            Ok(serde_json::json!({ "flag": flag, "enabled": enabled }))
+        }
+    }
+}
+
+/// Evaluate a graph against an event and record the run. Actions are independent: one
+/// failing does not stop the others, but marks the run failed. A dry run only records the plan.
+pub async fn execute(
+    pool: &PgPool,
+    transports: &TransportRegistry,
+    automation: &Automation,
+    version: i32,
+    graph: &RuleGraph,
+    event: &serde_json::Value,
+    dry_run: bool,
+) -> ApiResult<AutomationRun> {
+    let started_at = Utc::now();
+    let matched = trigger_matches(&graph.trigger, event);
+    let mut steps = if matched { plan(graph, event) } else { Vec::new() };
+
+    let mut first_error = None;
+    if !dry_run {
+        for step in &mut steps {
+            let Some(action) = &step.action else {
+                continue;
+            };
+            match perform(pool, transports, automation, action, event).await {
+                Ok(output) => step.output = Some(output),
+                Err(e) => {
+                    let message = e.to_string();
+                    first_error.get_or_insert_with(|| format!("{}: {}", step.node_id, message));
+                    step.error = Some(message);
+                }
+            }
+        }
+    }
+    let status = match (matched, &first_error) {
+        (false, _) => "skipped",
+        (true, Some(_)) => "failed",
+        (true, None) => "succeeded",
+    };
+
+    let run = sqlx::query_as::<_, AutomationRun>(&format!(
+        "INSERT INTO automation_runs \
+         (automation_id, version, trigger_kind, event, dry_run, status, steps, error, started_at, finished_at) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NOW()) RETURNING {}",
+        RUN_COLUMNS
+    ))
+    .bind(automation.id)
+    .bind(version)
+    .bind(graph.trigger.kind())
+    .bind(event)
+    .bind(dry_run)
+    .bind(status)
+    .bind(sqlx::types::Json(&steps))
+    .bind(first_error)
+    .bind(started_at)
+    .fetch_one(pool)
+    .await?;
+    Ok(run)
+}
+
+pub async fn load_graph(pool: &PgPool, automation_id: Uuid, version: i32) -> ApiResult<RuleGraph> {
+    let graph: Option<sqlx::types::Json<RuleGraph>> =
+        sqlx::query_scalar("SELECT graph FROM automation_versions WHERE automation_id = $1 AND version = $2")
+            .bind(automation_id)
+            .bind(version)
+            .fetch_optional(pool)
+            .await?;
+    graph
+        .map(|g| g.0)
+        .ok_or_else(|| ApiError::NotFound("Automation version not found".to_string()))
+}
+
+async fn active(pool: &PgPool, trigger_kind: &str, user_id: Option<Uuid>) -> ApiResult<Vec<ActiveAutomation>> {
+    let rows = sqlx::query_as::<_, ActiveAutomation>(&format!(
+        "SELECT {}, (SELECT graph FROM automation_versions v \
+                     WHERE v.automation_id = automations.id AND v.version = automations.current_version) AS graph \
+         FROM automations WHERE enabled AND trigger_kind = $1 AND ($2::uuid IS NULL OR user_id = $2)",
+        AUTOMATION_COLUMNS
+    ))
+    .bind(trigger_kind)
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+/// Take the next firing slot; fails when another worker or request fired it first
+async fn claim(pool: &PgPool, automation: &Automation) -> ApiResult<bool> {
+    let claimed = sqlx::query(
+        "UPDATE automations SET last_triggered_at = NOW() \
+         WHERE id = $1 AND last_triggered_at IS NOT DISTINCT FROM $2",
+    )
+    .bind(automation.id)
+    .bind(automation.last_triggered_at)
+    .execute(pool)
+    .await?;
+    Ok(claimed.rows_affected() == 1)
+}
+
+fn elapsed_since(last: Option<DateTime<Utc>>, minutes: u32, now: DateTime<Utc>) -> bool {
+    last.is_none_or(|at| now - at >= Duration::minutes(minutes as i64))
+}
+
+/// Run a user's matching automations on one event of the given trigger kind
+async fn dispatch(
+    pool: &PgPool,
+    transports: &TransportRegistry,
+    user_id: Uuid,
+    trigger_kind: &str,
+    event: &serde_json::Value,
+) -> ApiResult<usize> {
+    let mut fired = 0;
+    for ActiveAutomation { automation, graph } in active(pool, trigger_kind, Some(user_id)).await? {
+        if !trigger_matches(&graph.trigger, event) {
+            continue;
+        }
+        if let Trigger::Telemetry { cooldown_minutes, .. } = &graph.trigger
+            && !elapsed_since(
+                automation.last_triggered_at,
+                cooldown_minutes.unwrap_or(DEFAULT_TELEMETRY_COOLDOWN_MINUTES),
+                Utc::now(),
+            )
+        {
+            continue;
+        }
+        if !claim(pool, &automation).await? {
+            continue;
+        }
+        execute(pool, transports, &automation, automation.current_version, &graph, event, false).await?;
+        fired += 1;
+    }
+    Ok(fired)
+}
+
+/// Telemetry automations for a freshly stored sample; the event is the sample with its device id
+pub async fn on_telemetry(
+    pool: &PgPool,
+    transports: &TransportRegistry,
+    user_id: Uuid,
+    device_id: Uuid,
+    sample: &serde_json::Value,
+) -> ApiResult<usize> {
+    let mut event = sample.clone();
+    if let Some(map) = event.as_object_mut() {
+        map.insert("device_id".to_string(), serde_json::json!(device_id));
+    }
+    dispatch(pool, transports, user_id, "telemetry", &event).await
+}
+
+/// Fire an automation from its inbound hook; the request body is the event
+pub async fn on_webhook(
+    pool: &PgPool,
+    transports: &TransportRegistry,
+    automation: &Automation,
+    event: &serde_json::Value,
+) -> ApiResult<AutomationRun> {
+    let graph = load_graph(pool, automation.id, automation.current_version).await?;
+    sqlx::query("UPDATE automations SET last_triggered_at = NOW() WHERE id = $1")
+        .bind(automation.id)
+        .execute(pool)
+        .await?;
+    execute(pool, transports, automation, automation.current_version, &graph, event, false).await
+}
+
+/// Run schedules that are due and drain queued payment and metric events
+pub async fn run_due(pool: &PgPool, transports: &TransportRegistry) -> ApiResult<usize> {
+    let now = Utc::now();
+    let mut fired = 0;
+
+    for ActiveAutomation { automation, graph } in active(pool, "schedule", None).await? {
+        let Trigger::Schedule { every_minutes } = graph.trigger else {
+            continue;
+        };
+        if !elapsed_since(automation.last_triggered_at, every_minutes, now) || !claim(pool, &automation).await? {
+            continue;
+        }
+        let event = serde_json::json!({ "scheduled_at": now });
+        if let Err(e) = execute(pool, transports, &automation, automation.current_version, &graph, &event, false).await {
+            tracing::error!("Scheduled automation {} failed: {}", automation.id, e);
+        }
+        fired += 1;
+    }
+
+    #[derive(FromRow)]
+    struct QueuedEvent {
+        user_id: Uuid,
+        kind: String,
+        payload: serde_json::Value,
+    }
+    let events = sqlx::query_as::<_, QueuedEvent>(
+        "UPDATE automation_events SET processed_at = NOW() WHERE id IN ( \
+             SELECT id FROM automation_events WHERE processed_at IS NULL ORDER BY id LIMIT $1 \
+             FOR UPDATE SKIP LOCKED) \
+         RETURNING user_id, kind, payload",
+    )
+    .bind(EVENT_BATCH)
+    .fetch_all(pool)
+    .await?;
+    for event in events {
+        match dispatch(pool, transports, event.user_id, &event.kind, &event.payload).await {
+            Ok(n) => fired += n,
+            Err(e) => tracing::error!("Automation dispatch for {} event failed: {}", event.kind, e),
+        }
+    }
+    Ok(fired)
+}
+
+/// Start the background job for scheduled and event-driven automations
+pub fn spawn_automation_job(pool: Arc<PgPool>, transports: Arc<TransportRegistry>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(JOB_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = run_due(&pool, &transports).await {
+                tracing::error!("Automation job failed: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::automation::{RuleEdge, RuleNode};
+
+    fn condition(field: &str, op: &str, value: serde_json::Value) -> Condition {
+        Condition { field: field.to_string(), op: op.to_string(), value }
+    }
+
+    fn node(id: &str, kind: NodeKind) -> RuleNode {
+        RuleNode { id: id.to_string(), kind }
+    }
+
+    fn edge(from: &str, to: &str, when: Option<bool>) -> RuleEdge {
+        RuleEdge { from: from.to_string(), to: to.to_string(), when }
+    }
+
+    fn notify(title: &str) -> NodeKind {
+        NodeKind::Action { action: Action::Notify { title: title.to_string(), body: String::new() } }
+    }
+
+    /// trigger -> low battery? -> (true) notify, (false) toggle
+    fn battery_graph() -> RuleGraph {
+        RuleGraph {
+            trigger: Trigger::Webhook,
+            nodes: vec![
+                node("low", NodeKind::Condition { condition: condition("battery_level", "lt", serde_json::json!(20)) }),
+                node("alert", notify("Battery at {{battery_level}}% on {{device.name}}")),
+                node(
+                    "ok",
+                    NodeKind::Action { action: Action::ToggleFeature { flag: "charging".to_string(), enabled: false } },
+                ),
+            ],
+            edges: vec![edge("trigger", "low", None), edge("low", "alert", Some(true)), edge("low", "ok", Some(false))],
+        }
+    }
+
+    #[test]
+    fn test_evaluate_conditions() {
+        let event = serde_json::json!({ "battery_level": 15, "mode": "patrol", "tags": ["a", "b"], "pos": { "lat": 1.5 } });
+        assert!(evaluate(&condition("battery_level", "lt", serde_json::json!(20)), &event));
+        assert!(evaluate(&condition("battery_level", "eq", serde_json::json!(15.0)), &event));
+        assert!(!evaluate(&condition("battery_level", "gt", serde_json::json!("10")), &event));
+        assert!(evaluate(&condition("mode", "contains", serde_json::json!("trol")), &event));
+        assert!(evaluate(&condition("tags", "contains", serde_json::json!("b")), &event));
+        assert!(evaluate(&condition("pos.lat", "gte", serde_json::json!(1.5)), &event));
+        assert!(evaluate(&condition("tags.1", "eq", serde_json::json!("b")), &event));
+        assert!(!evaluate(&condition("missing", "exists", serde_json::Value::Null), &event));
+        assert!(!evaluate(&condition("missing", "ne", serde_json::json!(1)), &event));
+    }
+
+    #[test]
+    fn test_render_placeholders() {
+        let event = serde_json::json!({ "battery_level": 12, "device": { "name": "rover-1" } });
+        assert_eq!(render("Battery at {{battery_level}}% on {{ device.name }}", &event), "Battery at 12% on rover-1");
+        assert_eq!(render("{{missing}}!", &event), "!");
+        assert_eq!(render("unterminated {{battery_level", &event), "unterminated {{battery_level");
+    }
+
+    #[test]
+    fn test_plan_follows_branches() {
+        let graph = battery_graph();
+        let steps = plan(&graph, &serde_json::json!({ "battery_level": 12, "device": { "name": "rover-1" } }));
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].passed, Some(true));
+        assert_eq!(
+            steps[1].action,
+            Some(Action::Notify { title: "Battery at 12% on rover-1".to_string(), body: String::new() })
+        );
+
+        let steps = plan(&graph, &serde_json::json!({ "battery_level": 80 }));
+        assert_eq!(steps[0].passed, Some(false));
+        assert_eq!(steps[1].node_id, "ok");
+    }
+
+    #[test]
+    fn test_validate_graph() {
+        assert!(validate_graph(&battery_graph()).is_ok());
+
+        let mut cyclic = battery_graph();
+        cyclic.nodes.push(node("gate", NodeKind::Condition { condition: condition("x", "exists", serde_json::Value::Null) }));
+        cyclic.edges.push(edge("low", "gate", Some(true)));
+        cyclic.edges.push(edge("gate", "low", Some(true)));
+        assert!(validate_graph(&cyclic).is_err());
+
+        let mut missing_branch = battery_graph();
+        missing_branch.edges[1].when = None;
+        assert!(validate_graph(&missing_branch).is_err());
+
+        let mut dangling = battery_graph();
+        dangling.edges.push(edge("alert", "nowhere", None));
+        assert!(validate_graph(&dangling).is_err());
+
+        let mut internal = battery_graph();
+        internal.nodes[1] = node(
+            "alert",
+            NodeKind::Action { action: Action::CallWebhook { url: "https://127.0.0.1/hook".to_string(), body: None } },
+        );
+        assert!(validate_graph(&internal).is_err());
+
+        let mut bad_schedule = battery_graph();
+        bad_schedule.trigger = Trigger::Schedule { every_minutes: 0 };
+        assert!(validate_graph(&bad_schedule).is_err());
+    }
+
+    #[test]
+    fn test_trigger_matches() {
+        let device = Uuid::new_v4();
+        let trigger = Trigger::Telemetry {
+            device_id: Some(device),
+            condition: condition("battery_level", "lt", serde_json::json!(20)),
+            cooldown_minutes: None,
+        };
+        assert!(trigger_matches(&trigger, &serde_json::json!({ "device_id": device, "battery_level": 5 })));
+        assert!(!trigger_matches(&trigger, &serde_json::json!({ "device_id": Uuid::new_v4(), "battery_level": 5 })));
+
+        let trigger = Trigger::PaymentCompleted { product_type: Some("software_license".to_string()), min_amount: Some(10.0) };
+        assert!(trigger_matches(&trigger, &serde_json::json!({ "product_type": "software_license", "amount": 25.0 })));
+        assert!(!trigger_matches(&trigger, &serde_json::json!({ "product_type": "software_license", "amount": 5.0 })));
+        assert!(!trigger_matches(&trigger, &serde_json::json!({ "product_type": "documentation", "amount": 25.0 })));
+
+        let metric = Uuid::new_v4();
+        let trigger = Trigger::Metric {
+            metric_id: metric,
+            device_id: None,
+            condition: condition("value", "lt", serde_json::json!(50)),
+        };
+        assert!(trigger_matches(&trigger, &serde_json::json!({ "metric_id": metric, "device_id": device, "value": 12.5 })));
+        assert!(!trigger_matches(&trigger, &serde_json::json!({ "metric_id": metric, "value": 80.0 })));
+        assert!(!trigger_matches(&trigger, &serde_json::json!({ "metric_id": Uuid::new_v4(), "value": 12.5 })));
+    }
+}
@@ -0,0 +1,725 @@
+//! Logical org backups and restores.
+//!
+//! A backup is a JSON document of the org's metrics and privacy settings and, per active
+//! member, their devices (with sensors and attachments) and fleet configuration. Raw
+//! telemetry, device credentials and hardware ids are never included, so restored devices
+//! have to be provisioned again before they can connect.
+//!
+//! A restore only adds and updates: records missing from the document are left alone.
+//! Records that exist and differ are resolved with a [`ConflictStrategy`], and the whole
+//! restore runs in one transaction.
+
+use serde_json::Value;
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::backup::{
+    BackupDocument, ConflictStrategy, DeviceBackup, MemberBackup, MetricBackup, OrgBackup, RestoreReport,
+    BACKUP_FORMAT,
+};
+use crate::models::fleet_config::FleetConfig;
+use crate::services::attachment_services::validate_attachment_type;
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::automation_services::command_targets;
+use crate::services::fleet_config_services::{self, apply_in, export, load_state};
+use crate::services::metric_services::validate_formula;
+use crate::services::object_storage_services::ObjectStore;
+use crate::services::robotics_services::device_type_spec;
+use crate::services::sensor_services::{validate_sampling_rate, validate_sensor_type, validate_unit};
+use crate::services::transport_services::TransportKind;
+use crate::utils::redaction::RedactionPolicy;
+use crate::utils::sha256_hash;
+
+/// Largest document accepted for a restore
+pub const MAX_BACKUP_BYTES: usize = 64 * 1024 * 1024;
+
+pub const BACKUP_COLUMNS: &str = "id, org_id, created_by, size_bytes, sha256, counts, created_at";
+
+const DEVICE_BACKUP_COLUMNS: &str = "id, device_name, device_type, firmware_version, transport, metadata";
+
+#[derive(FromRow)]
+struct Member {
+    user_id: Uuid,
+    email: String,
+    role: String,
+}
+
+async fn active_members(conn: &mut PgConnection, org_id: Uuid) -> ApiResult<Vec<Member>> {
+    Ok(sqlx::query_as::<_, Member>(
+        "SELECT u.id AS user_id, LOWER(u.email) AS email, m.role FROM org_memberships m \
+         JOIN users u ON u.id = m.user_id WHERE m.org_id = $1 AND m.active ORDER BY LOWER(u.email)",
+    )
+    .bind(org_id)
+    .fetch_all(conn)
+    .await?)
+}
+
+async fn load_details(conn: &mut PgConnection, device: &mut DeviceBackup) -> ApiResult<()> {
+    device.sensors = sqlx::query_as(
+        "SELECT sensor_type, unit, sampling_rate_hz, enabled FROM device_sensors \
+         WHERE device_id = $1 ORDER BY sensor_type",
+    )
+    .bind(device.id)
+    .fetch_all(&mut *conn)
+    .await?;
+    device.attachments = sqlx::query_as(
+        "SELECT attachment_type, name, model, serial_number, config, enabled FROM device_attachments \
+         WHERE device_id = $1 ORDER BY name",
+    )
+    .bind(device.id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+async fn load_device(conn: &mut PgConnection, device_id: Uuid) -> ApiResult<DeviceBackup> {
+    let mut device = sqlx::query_as::<_, DeviceBackup>(&format!(
+        "SELECT {} FROM devices WHERE id = $1",
+        DEVICE_BACKUP_COLUMNS
+    ))
+    .bind(device_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    load_details(conn, &mut device).await?;
+    Ok(device)
+}
+
+/// Snapshot the org as a backup document
+pub async fn build_document(pool: &PgPool, org_id: Uuid) -> ApiResult<BackupDocument> {
+    let mut tx = pool.begin().await?;
+    // One snapshot, so the configuration agrees with the devices it refers to
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+
+    let privacy_settings: Value = sqlx::query_scalar("SELECT privacy_settings FROM organizations WHERE id = $1")
+        .bind(org_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Organization not found".to_string()))?;
+    let metrics = sqlx::query_as::<_, MetricBackup>(
+        "SELECT id, name, description, unit, formula FROM org_metrics WHERE org_id = $1 ORDER BY name",
+    )
+    .bind(org_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut members = Vec::new();
+    for member in active_members(&mut tx, org_id).await? {
+        let mut devices = sqlx::query_as::<_, DeviceBackup>(&format!(
+            "SELECT {} FROM devices WHERE user_id = $1 ORDER BY created_at, id",
+            DEVICE_BACKUP_COLUMNS
+        ))
+        .bind(member.user_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        for device in &mut devices {
+            load_details(&mut tx, device).await?;
+        }
+        let config = export(load_state(&mut tx, member.user_id).await?);
+        members.push(MemberBackup { email: member.email, role: member.role, devices, config });
+    }
+    tx.commit().await?;
+
+    Ok(BackupDocument {
+        format: BACKUP_FORMAT,
+        org_id,
+        created_at: chrono::Utc::now(),
+        privacy_settings,
+        metrics,
+        members,
+    })
+}
+
+/// Records per kind, kept with the backup for listing
+fn counts(document: &BackupDocument) -> Value {
+    let configs = document.members.iter().map(|m| &m.config);
+    let count = |section: fn(&FleetConfig) -> usize| configs.clone().map(section).sum::<usize>();
+    serde_json::json!({
+        "members": document.members.len(),
+        "devices": document.members.iter().map(|m| m.devices.len()).sum::<usize>(),
+        "metrics": document.metrics.len(),
+        "fleets": count(|c| c.fleets.as_ref().map_or(0, Vec::len)),
+        "geofences": count(|c| c.geofences.as_ref().map_or(0, Vec::len)),
+        "alert_rules": count(|c| c.alert_rules.as_ref().map_or(0, Vec::len)),
+        "automations": count(|c| c.automations.as_ref().map_or(0, Vec::len)),
+    })
+}
+
+/// Dump the org to object storage and record the backup
+pub async fn create_backup(pool: &PgPool, store: &ObjectStore, org_id: Uuid, user_id: Uuid) -> ApiResult<OrgBackup> {
+    let document = build_document(pool, org_id).await?;
+    let body = serde_json::to_vec(&document)
+        .map_err(|e| ApiError::InternalError(format!("Failed to encode backup: {}", e)))?;
+    let backup_id = Uuid::new_v4();
+    let object_key = format!("org-backups/{}/{}.json", org_id, backup_id);
+    let (size, digest) = (body.len() as i64, sha256_hash(&body));
+    store.put_object(&object_key, body, "application/json").await?;
+
+    let mut tx = pool.begin().await?;
+    let backup = sqlx::query_as::<_, OrgBackup>(&format!(
+        "INSERT INTO org_backups (id, org_id, created_by, object_key, size_bytes, sha256, counts) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING {}",
+        BACKUP_COLUMNS
+    ))
+    .bind(backup_id)
+    .bind(org_id)
+    .bind(user_id)
+    .bind(&object_key)
+    .bind(size)
+    .bind(&digest)
+    .bind(counts(&document))
+    .fetch_one(&mut *tx)
+    .await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user_id),
+            action: "backup.created",
+            resource_type: "org_backup",
+            resource_id: Some(backup_id.to_string()),
+            details: backup.counts.clone(),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(backup)
+}
+
+/// A backup's document, checked against the digest recorded when it was taken
+pub async fn fetch_backup(pool: &PgPool, store: &ObjectStore, org_id: Uuid, backup_id: Uuid) -> ApiResult<Vec<u8>> {
+    let (object_key, digest): (String, String) =
+        sqlx::query_as("SELECT object_key, sha256 FROM org_backups WHERE id = $1 AND org_id = $2")
+            .bind(backup_id)
+            .bind(org_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| ApiError::NotFound("Backup not found".to_string()))?;
+
+    let body = store.get_object(&object_key).await?;
+    if sha256_hash(&body) != digest {
+        return Err(ApiError::InternalError("Stored backup does not match its recorded digest".to_string()));
+    }
+    Ok(body)
+}
+
+fn validate_device(device: &DeviceBackup) -> ApiResult<()> {
+    let invalid = |msg: String| Err(ApiError::ValidationError(msg));
+    if device.device_name.trim().is_empty() || device.device_name.len() > 100 {
+        return invalid(format!("Device {}: device_name must be 1-100 characters", device.id));
+    }
+    if device_type_spec(&device.device_type).is_none() {
+        return invalid(format!("Device {}: unknown device type '{}'", device.id, device.device_type));
+    }
+    TransportKind::parse(&device.transport)?;
+    if !device.metadata.is_object() {
+        return invalid(format!("Device {}: metadata must be an object", device.id));
+    }
+    for sensor in &device.sensors {
+        validate_sensor_type(&sensor.sensor_type)?;
+        validate_unit(&sensor.unit)?;
+        validate_sampling_rate(sensor.sampling_rate_hz)?;
+    }
+    for attachment in &device.attachments {
+        validate_attachment_type(&attachment.attachment_type)?;
+        if attachment.name.trim().is_empty() || attachment.name.len() > 64 {
+            return invalid(format!("Device {}: attachment names must be 1-64 characters", device.id));
+        }
+    }
+    Ok(())
+}
+
+/// Parse and check a backup document. Documents may come from another environment, so
+/// everything is validated as if entered through the API.
+pub fn parse_document(body: &[u8]) -> ApiResult<BackupDocument> {
+    let document: BackupDocument = serde_json::from_slice(body)
+        .map_err(|e| ApiError::ValidationError(format!("Invalid backup document: {}", e)))?;
+    if document.format != BACKUP_FORMAT {
+        return Err(ApiError::ValidationError(format!(
+            "Unsupported backup format {}; expected {}",
+            document.format, BACKUP_FORMAT
+        )));
+    }
+
+    serde_json::from_value::<RedactionPolicy>(document.privacy_settings.clone())
+        .map_err(|e| ApiError::ValidationError(format!("Invalid privacy settings: {}", e)))?;
+    let mut metric_names = HashSet::new();
+    for metric in &document.metrics {
+        if metric.name.trim().is_empty() || metric.name.len() > 64 || !metric_names.insert(&metric.name) {
+            return Err(ApiError::ValidationError(format!(
+                "Metric names must be unique and 1-64 characters: '{}'",
+                metric.name
+            )));
+        }
+        validate_formula(&metric.formula)?;
+    }
+
+    let mut device_ids = HashSet::new();
+    for member in &document.members {
+        for device in &member.devices {
+            if !device_ids.insert(device.id) {
+                return Err(ApiError::ValidationError(format!("Device {} appears more than once", device.id)));
+            }
+            validate_device(device)?;
+        }
+        fleet_config_services::validate(&member.config)?;
+    }
+    Ok(document)
+}
+
+/// Replace every id in `ids`, wherever it appears as a string or an object key
+pub fn remap_ids(value: &mut Value, ids: &HashMap<Uuid, Uuid>) {
+    let remapped = |s: &str| Uuid::parse_str(s).ok().and_then(|id| ids.get(&id)).map(Uuid::to_string);
+    match value {
+        Value::String(s) => {
+            if let Some(new) = remapped(s) {
+                *s = new;
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| remap_ids(item, ids)),
+        Value::Object(map) => {
+            for (key, mut item) in std::mem::take(map) {
+                remap_ids(&mut item, ids);
+                map.insert(remapped(&key).unwrap_or(key), item);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Settle a record that exists and differs from the backup: whether to write the backup's
+fn resolve(strategy: ConflictStrategy, what: impl FnOnce() -> String) -> ApiResult<bool> {
+    match strategy {
+        ConflictStrategy::Skip => Ok(false),
+        ConflictStrategy::Overwrite => Ok(true),
+        ConflictStrategy::Fail => {
+            Err(ApiError::Conflict(format!("{} already exists and differs from the backup", what())))
+        }
+    }
+}
+
+fn merge_named<T: PartialEq>(
+    current: Option<Vec<T>>,
+    backup: Option<Vec<T>>,
+    name: fn(&T) -> &str,
+    resource: &str,
+    strategy: ConflictStrategy,
+) -> ApiResult<Option<Vec<T>>> {
+    let Some(backup) = backup else { return Ok(None) };
+    let mut merged = current.unwrap_or_default();
+    for item in backup {
+        match merged.iter().position(|existing| name(existing) == name(&item)) {
+            Some(i) if merged[i] != item => {
+                if resolve(strategy, || format!("{} '{}'", resource, name(&item)))? {
+                    merged[i] = item;
+                }
+            }
+            Some(_) => {}
+            None => merged.push(item),
+        }
+    }
+    Ok(Some(merged))
+}
+
+/// Merge a member's backed-up configuration into their current one (as exported), giving
+/// the document to apply. Sections the backup lacks are left alone.
+pub fn merge_config(current: FleetConfig, backup: FleetConfig, strategy: ConflictStrategy) -> ApiResult<FleetConfig> {
+    let tags = match backup.tags {
+        None => None,
+        Some(backup_tags) => {
+            let mut merged: BTreeMap<Uuid, Vec<String>> = current.tags.unwrap_or_default();
+            for (device_id, tags) in backup_tags {
+                match merged.get(&device_id) {
+                    Some(existing) if *existing != tags => {
+                        if resolve(strategy, || format!("Tags of device {}", device_id))? {
+                            merged.insert(device_id, tags);
+                        }
+                    }
+                    Some(_) => {}
+                    None => {
+                        merged.insert(device_id, tags);
+                    }
+                }
+            }
+            Some(merged)
+        }
+    };
+
+    Ok(FleetConfig {
+        fleets: merge_named(current.fleets, backup.fleets, |f| &f.name, "Fleet", strategy)?,
+        tags,
+        geofences: merge_named(current.geofences, backup.geofences, |g| &g.name, "Geofence", strategy)?,
+        alert_rules: merge_named(current.alert_rules, backup.alert_rules, |r| &r.name, "Alert rule", strategy)?,
+        automations: merge_named(current.automations, backup.automations, |a| &a.name, "Automation", strategy)?,
+    })
+}
+
+/// Drop references to devices the member does not own. Automations commanding such devices
+/// are left out entirely; their names are returned.
+fn prune(config: &mut FleetConfig, owned: &HashSet<Uuid>) -> Vec<String> {
+    for fleet in config.fleets.iter_mut().flatten() {
+        fleet.devices.retain(|id| owned.contains(id));
+    }
+    if let Some(tags) = &mut config.tags {
+        tags.retain(|id, _| owned.contains(id));
+    }
+    let mut skipped = Vec::new();
+    if let Some(automations) = &mut config.automations {
+        automations.retain(|a| {
+            let keep = command_targets(&a.graph).iter().all(|id| owned.contains(id));
+            if !keep {
+                skipped.push(a.name.clone());
+            }
+            keep
+        });
+    }
+    skipped
+}
+
+async fn write_details(conn: &mut PgConnection, device_id: Uuid, device: &DeviceBackup) -> ApiResult<()> {
+    sqlx::query("DELETE FROM device_sensors WHERE device_id = $1")
+        .bind(device_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM device_attachments WHERE device_id = $1")
+        .bind(device_id)
+        .execute(&mut *conn)
+        .await?;
+    for sensor in &device.sensors {
+        sqlx::query(
+            "INSERT INTO device_sensors (device_id, sensor_type, unit, sampling_rate_hz, enabled) \
+             VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(device_id)
+        .bind(&sensor.sensor_type)
+        .bind(&sensor.unit)
+        .bind(sensor.sampling_rate_hz)
+        .bind(sensor.enabled)
+        .execute(&mut *conn)
+        .await?;
+    }
+    for attachment in &device.attachments {
+        sqlx::query(
+            "INSERT INTO device_attachments (device_id, attachment_type, name, model, serial_number, config, enabled) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(device_id)
+        .bind(&attachment.attachment_type)
+        .bind(&attachment.name)
+        .bind(&attachment.model)
+        .bind(&attachment.serial_number)
+        .bind(&attachment.config)
+        .bind(attachment.enabled)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Create or overwrite a device under `device_id`; new devices start offline
+async fn write_device(
+    conn: &mut PgConnection,
+    device_id: Uuid,
+    user_id: Uuid,
+    device: &DeviceBackup,
+    exists: bool,
+) -> ApiResult<()> {
+    let sql = if exists {
+        "UPDATE devices SET user_id = $2, device_name = $3, device_type = $4, firmware_version = $5, \
+         metadata = $6, transport = $7 WHERE id = $1"
+    } else {
+        "INSERT INTO devices (id, user_id, device_name, device_type, firmware_version, status, metadata, transport) \
+         VALUES ($1, $2, $3, $4, $5, 'offline', $6, $7)"
+    };
+    sqlx::query(sql)
+        .bind(device_id)
+        .bind(user_id)
+        .bind(&device.device_name)
+        .bind(&device.device_type)
+        .bind(&device.firmware_version)
+        .bind(&device.metadata)
+        .bind(&device.transport)
+        .execute(&mut *conn)
+        .await?;
+    write_details(conn, device_id, device).await
+}
+
+async fn restore_metrics(
+    conn: &mut PgConnection,
+    org_id: Uuid,
+    user_id: Uuid,
+    metrics: &[MetricBackup],
+    strategy: ConflictStrategy,
+    ids: &mut HashMap<Uuid, Uuid>,
+    report: &mut RestoreReport,
+) -> ApiResult<()> {
+    for metric in metrics {
+        let existing = sqlx::query_as::<_, MetricBackup>(
+            "SELECT id, name, description, unit, formula FROM org_metrics WHERE org_id = $1 AND name = $2 FOR UPDATE",
+        )
+        .bind(org_id)
+        .bind(&metric.name)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        let Some(existing) = existing else {
+            let id: Uuid = sqlx::query_scalar(
+                "INSERT INTO org_metrics (org_id, name, description, unit, formula, created_by) \
+                 VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+            )
+            .bind(org_id)
+            .bind(&metric.name)
+            .bind(&metric.description)
+            .bind(&metric.unit)
+            .bind(metric.formula.trim())
+            .bind(user_id)
+            .fetch_one(&mut *conn)
+            .await?;
+            ids.insert(metric.id, id);
+            report.metrics_created += 1;
+            continue;
+        };
+
+        ids.insert(metric.id, existing.id);
+        let labels = (&metric.description, &metric.unit, &metric.formula);
+        if (&existing.description, &existing.unit, &existing.formula) == labels {
+            continue;
+        }
+        if !resolve(strategy, || format!("Metric '{}'", metric.name))? {
+            report.metrics_skipped += 1;
+            continue;
+        }
+        // As when editing a metric, values computed with another formula are discarded
+        if existing.formula != metric.formula {
+            sqlx::query("DELETE FROM metric_values WHERE metric_id = $1")
+                .bind(existing.id)
+                .execute(&mut *conn)
+                .await?;
+        }
+        sqlx::query(
+            "UPDATE org_metrics SET description = $2, unit = $3, formula = $4, \
+             computed_through = CASE WHEN formula = $4 THEN computed_through END, updated_at = NOW() WHERE id = $1",
+        )
+        .bind(existing.id)
+        .bind(&metric.description)
+        .bind(&metric.unit)
+        .bind(&metric.formula)
+        .execute(&mut *conn)
+        .await?;
+        report.metrics_updated += 1;
+    }
+    Ok(())
+}
+
+/// Restore a document into the org. Members are matched by email among the org's active
+/// members; devices whose id is taken outside the org get a new id, and references to them
+/// are rewritten. Nothing is changed unless the whole restore succeeds.
+pub async fn restore(
+    pool: &PgPool,
+    org_id: Uuid,
+    user_id: Uuid,
+    backup_id: Option<Uuid>,
+    document: BackupDocument,
+    strategy: ConflictStrategy,
+) -> ApiResult<RestoreReport> {
+    let mut report = RestoreReport::default();
+    let mut tx = pool.begin().await?;
+    // One restore per org at a time
+    let privacy_settings: Value =
+        sqlx::query_scalar("SELECT privacy_settings FROM organizations WHERE id = $1 FOR UPDATE")
+            .bind(org_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| ApiError::NotFound("Organization not found".to_string()))?;
+
+    if privacy_settings != document.privacy_settings
+        && (privacy_settings == serde_json::json!({}) || resolve(strategy, || "Privacy settings".to_string())?)
+    {
+        sqlx::query("UPDATE organizations SET privacy_settings = $2 WHERE id = $1")
+            .bind(org_id)
+            .bind(&document.privacy_settings)
+            .execute(&mut *tx)
+            .await?;
+        report.privacy_settings_restored = true;
+    }
+
+    // Backup id -> id in this org, for metrics and devices that could not keep theirs
+    let mut ids = HashMap::new();
+    restore_metrics(&mut tx, org_id, user_id, &document.metrics, strategy, &mut ids, &mut report).await?;
+
+    let members: HashMap<String, Uuid> =
+        active_members(&mut tx, org_id).await?.into_iter().map(|m| (m.email, m.user_id)).collect();
+    let member_ids: HashSet<Uuid> = members.values().copied().collect();
+    let mut skipped_devices = HashSet::new();
+    for member in &document.members {
+        let Some(&owner) = members.get(&member.email.to_lowercase()) else {
+            report.members_skipped.push(member.email.clone());
+            continue;
+        };
+        for device in &member.devices {
+            let current_owner: Option<Uuid> = sqlx::query_scalar("SELECT user_id FROM devices WHERE id = $1 FOR UPDATE")
+                .bind(device.id)
+                .fetch_optional(&mut *tx)
+                .await?;
+            match current_owner {
+                None => {
+                    write_device(&mut tx, device.id, owner, device, false).await?;
+                    report.devices_created += 1;
+                }
+                Some(current_owner) if member_ids.contains(&current_owner) => {
+                    let current = load_device(&mut tx, device.id).await?;
+                    if current == *device && current_owner == owner {
+                        report.devices_unchanged += 1;
+                    } else if resolve(strategy, || format!("Device {}", device.id))? {
+                        write_device(&mut tx, device.id, owner, device, true).await?;
+                        report.devices_updated += 1;
+                    } else {
+                        skipped_devices.insert(device.id);
+                        report.devices_skipped += 1;
+                    }
+                }
+                Some(_) => {
+                    let new_id = Uuid::new_v4();
+                    write_device(&mut tx, new_id, owner, device, false).await?;
+                    ids.insert(device.id, new_id);
+                    report.devices_remapped.push((device.id, new_id));
+                    report.devices_created += 1;
+                }
+            }
+        }
+    }
+
+    // Configuration last, once every device it may refer to exists
+    for member in document.members {
+        let Some(&owner) = members.get(&member.email.to_lowercase()) else { continue };
+        let mut backup = member.config;
+        if let Some(tags) = &mut backup.tags {
+            tags.retain(|id, _| !skipped_devices.contains(id));
+        }
+        let mut value = serde_json::to_value(&backup)
+            .map_err(|e| ApiError::InternalError(format!("Failed to encode configuration: {}", e)))?;
+        remap_ids(&mut value, &ids);
+        let mut backup: FleetConfig = serde_json::from_value(value)
+            .map_err(|e| ApiError::InternalError(format!("Failed to decode configuration: {}", e)))?;
+
+        let state = load_state(&mut tx, owner).await?;
+        let owned: HashSet<Uuid> = state.tags.keys().copied().collect();
+        report.automations_skipped.extend(prune(&mut backup, &owned));
+        let desired = merge_config(export(state), backup, strategy)?;
+        report.config_changes += apply_in(&mut tx, owner, &desired, false).await?.len();
+    }
+
+    let details = serde_json::to_value(&report)
+        .map_err(|e| ApiError::InternalError(format!("Failed to encode restore report: {}", e)))?;
+    let restore_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO org_restores (org_id, backup_id, source_org_id, requested_by, strategy, report) \
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+    )
+    .bind(org_id)
+    .bind(backup_id)
+    .bind(document.org_id)
+    .bind(user_id)
+    .bind(strategy.as_str())
+    .bind(&details)
+    .fetch_one(&mut *tx)
+    .await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor_id: Some(user_id),
+            action: "backup.restored",
+            resource_type: "org_restore",
+            resource_id: Some(restore_id.to_string()),
+            details: serde_json::json!({
+                "backup_id": backup_id,
+                "source_org_id": document.org_id,
+                "strategy": strategy.as_str(),
+            }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fleet_config::FleetSpec;
+
+    fn fleet(name: &str, devices: Vec<Uuid>) -> FleetSpec {
+        FleetSpec { name: name.to_string(), description: None, devices }
+    }
+
+    #[test]
+    fn test_remap_ids() {
+        let (old, new, other) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let ids = HashMap::from([(old, new)]);
+        let mut value = serde_json::json!({
+            "tags": { old.to_string(): ["field"], other.to_string(): [] },
+            "fleets": [{ "name": "north", "devices": [old, other] }],
+        });
+        remap_ids(&mut value, &ids);
+
+        assert!(value["tags"].get(new.to_string()).is_some());
+        assert!(value["tags"].get(old.to_string()).is_none());
+        assert!(value["tags"].get(other.to_string()).is_some());
+        assert_eq!(value["fleets"][0]["devices"], serde_json::json!([new, other]));
+        assert_eq!(value["fleets"][0]["name"], "north");
+    }
+
+    #[test]
+    fn test_merge_config_strategies() {
+        let device = Uuid::new_v4();
+        let current = || FleetConfig {
+            fleets: Some(vec![fleet("north", vec![device]), fleet("depot", vec![])]),
+            tags: Some(BTreeMap::from([(device, vec!["field".to_string()])])),
+            ..Default::default()
+        };
+        let backup = || FleetConfig {
+            fleets: Some(vec![fleet("north", vec![]), fleet("south", vec![device])]),
+            ..Default::default()
+        };
+
+        let skipped = merge_config(current(), backup(), ConflictStrategy::Skip).unwrap();
+        assert_eq!(
+            skipped.fleets,
+            Some(vec![fleet("north", vec![device]), fleet("depot", vec![]), fleet("south", vec![device])])
+        );
+        // Sections the backup does not have are left alone
+        assert_eq!(skipped.tags, None);
+
+        let overwritten = merge_config(current(), backup(), ConflictStrategy::Overwrite).unwrap();
+        assert_eq!(overwritten.fleets.unwrap()[0], fleet("north", vec![]));
+
+        assert!(matches!(
+            merge_config(current(), backup(), ConflictStrategy::Fail),
+            Err(ApiError::Conflict(_))
+        ));
+        // Identical records are not conflicts
+        assert!(merge_config(current(), current(), ConflictStrategy::Fail).is_ok());
+    }
+
+    #[test]
+    fn test_parse_document_checks_format() {
+        let document = |format: u32| {
+            serde_json::json!({
+                "format": format,
+                "org_id": Uuid::new_v4(),
+                "created_at": "2026-10-16T00:00:00Z",
+                "privacy_settings": {},
+                "metrics": [],
+                "members": [],
+            })
+            .to_string()
+        };
+        assert!(parse_document(document(BACKUP_FORMAT).as_bytes()).is_ok());
+        assert!(matches!(parse_document(document(99).as_bytes()), Err(ApiError::ValidationError(_))));
+        assert!(matches!(parse_document(b"{}"), Err(ApiError::ValidationError(_))));
+    }
+}
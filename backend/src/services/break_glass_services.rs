@@ -0,0 +1,60 @@
+//! Break-glass emergency access: sealed credentials, two-person activation and time-boxed sessions
+
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+
+/// Default length of an emergency session
+pub const DEFAULT_DURATION_MINUTES: i32 = 60;
+/// Longest emergency session that can be requested
+pub const MAX_DURATION_MINUTES: i32 = 240;
+/// How long a request waits for a second approver before it lapses
+pub const PENDING_TTL_MINUTES: i64 = 15;
+
+/// Validate an activation request, returning the session length in minutes
+pub fn validate_activation(reason: &str, duration_minutes: Option<i32>) -> ApiResult<i32> {
+    if reason.trim().len() < 10 {
+        return Err(ApiError::ValidationError(
+            "A reason of at least 10 characters is required for emergency access".to_string(),
+        ));
+    }
+
+    let duration = duration_minutes.unwrap_or(DEFAULT_DURATION_MINUTES);
+    if !(1..=MAX_DURATION_MINUTES).contains(&duration) {
+        return Err(ApiError::ValidationError(format!(
+            "duration_minutes must be between 1 and {}",
+            MAX_DURATION_MINUTES
+        )));
+    }
+
+    Ok(duration)
+}
+
+/// The org an active, unexpired break-glass session grants access to
+pub async fn active_session_org(pool: &PgPool, session_id: Uuid) -> ApiResult<Option<Uuid>> {
+    let org_id = sqlx::query_scalar(
+        "SELECT org_id FROM break_glass_sessions WHERE id = $1 AND status = 'active' AND expires_at > NOW()",
+    )
+    .bind(session_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(org_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_activation() {
+        assert_eq!(
+            validate_activation("IdP outage, restoring access", None).unwrap(),
+            DEFAULT_DURATION_MINUTES
+        );
+        assert_eq!(validate_activation("IdP outage, restoring access", Some(30)).unwrap(), 30);
+        assert!(validate_activation("because", None).is_err());
+        assert!(validate_activation("IdP outage, restoring access", Some(0)).is_err());
+        assert!(validate_activation("IdP outage, restoring access", Some(MAX_DURATION_MINUTES + 1)).is_err());
+    }
+}
@@ -0,0 +1,285 @@
+//! Maintenance windows and on-call rotations, published as iCal feeds at signed URLs so
+//! operations teams see robot downtime and who is on call in the calendars they already use.
+
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::calendar::{CalendarFeed, MaintenanceWindow, OnCallRotation};
+use crate::utils::ical::{self, Event};
+use crate::utils::secure_compare;
+
+pub const WINDOW_COLUMNS: &str = "id, user_id, device_id, title, notes, starts_at, ends_at, status, sequence, \
+     updated_via, google_event_id, created_at, updated_at";
+
+pub const ROTATION_COLUMNS: &str = "id, user_id, name, members, shift_hours, starts_at, created_at, updated_at";
+
+pub const FEED_COLUMNS: &str = "id, user_id, kind, device_id, rotation_id, url_version, last_fetched_at, created_at";
+
+pub const FEED_KINDS: &[&str] = &["maintenance", "on_call"];
+
+pub const MAX_WINDOW_DAYS: i64 = 14;
+pub const MAX_ROTATION_MEMBERS: usize = 50;
+pub const MAX_SHIFT_HOURS: i32 = 14 * 24;
+pub const MAX_FEEDS_PER_USER: i64 = 20;
+/// Feeds cover this much history and look this far ahead
+const FEED_PAST_DAYS: i64 = 30;
+const FEED_FUTURE_DAYS: i64 = 180;
+/// Shifts listed or published in one go
+pub const MAX_SHIFTS: usize = 1000;
+
+pub fn validate_window(title: &str, starts_at: DateTime<Utc>, ends_at: DateTime<Utc>) -> ApiResult<()> {
+    if title.is_empty() || title.chars().count() > 255 {
+        return Err(ApiError::ValidationError("title must be 1-255 characters".to_string()));
+    }
+    if ends_at <= starts_at {
+        return Err(ApiError::ValidationError("ends_at must be after starts_at".to_string()));
+    }
+    if ends_at - starts_at > Duration::days(MAX_WINDOW_DAYS) {
+        return Err(ApiError::ValidationError(format!(
+            "A maintenance window may last at most {} days",
+            MAX_WINDOW_DAYS
+        )));
+    }
+    Ok(())
+}
+
+pub fn validate_rotation(name: &str, members: &[String], shift_hours: i32) -> ApiResult<()> {
+    if name.is_empty() || name.len() > 100 {
+        return Err(ApiError::ValidationError("name must be 1-100 characters".to_string()));
+    }
+    if members.is_empty() || members.len() > MAX_ROTATION_MEMBERS {
+        return Err(ApiError::ValidationError(format!(
+            "A rotation needs 1-{} members",
+            MAX_ROTATION_MEMBERS
+        )));
+    }
+    if members.iter().any(|m| m.trim().is_empty() || m.len() > 255) {
+        return Err(ApiError::ValidationError("members must be 1-255 characters each".to_string()));
+    }
+    if !(1..=MAX_SHIFT_HOURS).contains(&shift_hours) {
+        return Err(ApiError::ValidationError(format!(
+            "shift_hours must be between 1 and {}",
+            MAX_SHIFT_HOURS
+        )));
+    }
+    Ok(())
+}
+
+/// One member's turn on call
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Shift {
+    /// Shifts are numbered from the rotation's start, which keeps their feed UIDs stable
+    pub index: i64,
+    pub member: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+}
+
+/// Shifts overlapping `[from, to)`, at most [`MAX_SHIFTS`]
+pub fn rotation_shifts(rotation: &OnCallRotation, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<Shift> {
+    if rotation.members.is_empty() || rotation.shift_hours <= 0 || to <= rotation.starts_at {
+        return Vec::new();
+    }
+    let length = Duration::hours(rotation.shift_hours as i64);
+    let mut index = ((from - rotation.starts_at).num_seconds() / length.num_seconds()).max(0);
+    let mut shifts = Vec::new();
+    while shifts.len() < MAX_SHIFTS {
+        let starts_at = rotation.starts_at + Duration::seconds(length.num_seconds() * index);
+        if starts_at >= to {
+            break;
+        }
+        let ends_at = starts_at + length;
+        if ends_at > from {
+            shifts.push(Shift {
+                index,
+                member: rotation.members[(index as usize) % rotation.members.len()].clone(),
+                starts_at,
+                ends_at,
+            });
+        }
+        index += 1;
+    }
+    shifts
+}
+
+/// Hex HMAC over the feed id and URL version; bumping the version invalidates old URLs
+pub fn feed_signature(secret: &[u8], feed_id: Uuid, url_version: i32) -> String {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(format!("calendar-feed:{}:{}", feed_id, url_version).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+pub fn verify_feed_signature(secret: &[u8], feed: &CalendarFeed, signature: &str) -> bool {
+    secure_compare(&feed_signature(secret, feed.id, feed.url_version), &signature.to_ascii_lowercase())
+}
+
+/// The URL calendar apps subscribe to; it is the only credential the feed needs
+pub fn feed_url(api_base_url: &str, secret: &[u8], feed: &CalendarFeed) -> String {
+    format!(
+        "{}/api/calendar/feeds/{}.ics?sig={}",
+        api_base_url.trim_end_matches('/'),
+        feed.id,
+        feed_signature(secret, feed.id, feed.url_version)
+    )
+}
+
+pub fn window_event(window: &MaintenanceWindow, device_name: Option<&str>) -> Event {
+    let scope = device_name.unwrap_or("Fleet");
+    Event {
+        uid: format!("maintenance-{}@roboveda", window.id),
+        sequence: window.sequence,
+        starts_at: window.starts_at,
+        ends_at: window.ends_at,
+        summary: format!("{}: {}", scope, window.title),
+        description: window.notes.clone(),
+        cancelled: window.status == "cancelled",
+        updated_at: window.updated_at,
+    }
+}
+
+pub fn shift_event(rotation: &OnCallRotation, shift: &Shift) -> Event {
+    Event {
+        uid: format!("on-call-{}-{}@roboveda", rotation.id, shift.index),
+        sequence: 0,
+        starts_at: shift.starts_at,
+        ends_at: shift.ends_at,
+        summary: format!("On call: {} ({})", shift.member, rotation.name),
+        description: None,
+        cancelled: false,
+        updated_at: rotation.updated_at,
+    }
+}
+
+/// Render a feed's calendar over its window of history and look-ahead
+pub async fn render_feed(pool: &PgPool, feed: &CalendarFeed) -> ApiResult<String> {
+    let now = Utc::now();
+    let (from, to) = (now - Duration::days(FEED_PAST_DAYS), now + Duration::days(FEED_FUTURE_DAYS));
+
+    if feed.kind == "on_call" {
+        let rotations = sqlx::query_as::<_, OnCallRotation>(&format!(
+            "SELECT {} FROM on_call_rotations WHERE user_id = $1 AND ($2::uuid IS NULL OR id = $2) ORDER BY name",
+            ROTATION_COLUMNS
+        ))
+        .bind(feed.user_id)
+        .bind(feed.rotation_id)
+        .fetch_all(pool)
+        .await?;
+        let events: Vec<Event> = rotations
+            .iter()
+            .flat_map(|rotation| {
+                rotation_shifts(rotation, from, to).into_iter().map(move |shift| shift_event(rotation, &shift))
+            })
+            .collect();
+        let name = match (feed.rotation_id, rotations.first()) {
+            (Some(_), Some(rotation)) => format!("On call: {}", rotation.name),
+            _ => "On call".to_string(),
+        };
+        return Ok(ical::render(&name, &events));
+    }
+
+    // Whole-fleet windows appear in every device's feed
+    let windows = sqlx::query_as::<_, MaintenanceWindow>(&format!(
+        "SELECT {} FROM maintenance_windows WHERE user_id = $1 \
+         AND ($2::uuid IS NULL OR device_id = $2 OR device_id IS NULL) \
+         AND ends_at > $3 AND starts_at < $4 ORDER BY starts_at",
+        WINDOW_COLUMNS
+    ))
+    .bind(feed.user_id)
+    .bind(feed.device_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+    let names: HashMap<Uuid, String> = sqlx::query_as("SELECT id, device_name FROM devices WHERE user_id = $1")
+        .bind(feed.user_id)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .collect();
+    let events: Vec<Event> = windows
+        .iter()
+        .map(|w| window_event(w, w.device_id.and_then(|id| names.get(&id)).map(String::as_str)))
+        .collect();
+    let name = match feed.device_id.and_then(|id| names.get(&id)) {
+        Some(device) => format!("Maintenance: {}", device),
+        None => "Fleet maintenance".to_string(),
+    };
+    Ok(ical::render(&name, &events))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn rotation(members: &[&str], shift_hours: i32) -> OnCallRotation {
+        let at = Utc.with_ymd_and_hms(2026, 10, 5, 9, 0, 0).unwrap();
+        OnCallRotation {
+            id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            name: "Ops".to_string(),
+            members: members.iter().map(|m| m.to_string()).collect(),
+            shift_hours,
+            starts_at: at,
+            created_at: at,
+            updated_at: at,
+        }
+    }
+
+    #[test]
+    fn test_rotation_shifts() {
+        let rotation = rotation(&["ana", "ben", "cy"], 24);
+        let from = Utc.with_ymd_and_hms(2026, 10, 9, 12, 0, 0).unwrap();
+        let shifts = rotation_shifts(&rotation, from, from + Duration::days(2));
+
+        // The shift already under way at `from` is included
+        assert_eq!(shifts.len(), 3);
+        assert_eq!(shifts[0].index, 4);
+        assert_eq!(shifts[0].member, "ben");
+        assert_eq!(shifts[0].starts_at, Utc.with_ymd_and_hms(2026, 10, 9, 9, 0, 0).unwrap());
+        assert_eq!(shifts[1].member, "cy");
+        assert_eq!(shifts[2].member, "ana");
+
+        // Nothing before the rotation starts
+        let before = Utc.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap();
+        let early = rotation_shifts(&rotation, before, before + Duration::days(5));
+        assert_eq!(early.len(), 1);
+        assert_eq!(early[0].index, 0);
+    }
+
+    #[test]
+    fn test_feed_signature() {
+        let feed = CalendarFeed {
+            id: Uuid::new_v4(),
+            user_id: Uuid::nil(),
+            kind: "maintenance".to_string(),
+            device_id: None,
+            rotation_id: None,
+            url_version: 1,
+            last_fetched_at: None,
+            created_at: Utc::now(),
+        };
+        let url = feed_url("https://api.example.com/", b"secret", &feed);
+        let signature = url.rsplit_once("sig=").unwrap().1;
+
+        assert!(url.starts_with(&format!("https://api.example.com/api/calendar/feeds/{}.ics?", feed.id)));
+        assert!(verify_feed_signature(b"secret", &feed, signature));
+        assert!(!verify_feed_signature(b"other", &feed, signature));
+        let rotated = CalendarFeed { url_version: 2, ..feed };
+        assert!(!verify_feed_signature(b"secret", &rotated, signature));
+    }
+
+    #[test]
+    fn test_validate_window() {
+        let at = Utc::now();
+        assert!(validate_window("Wheel swap", at, at + Duration::hours(2)).is_ok());
+        assert!(validate_window("Wheel swap", at, at).is_err());
+        assert!(validate_window("", at, at + Duration::hours(2)).is_err());
+        assert!(validate_window("Refit", at, at + Duration::days(MAX_WINDOW_DAYS + 1)).is_err());
+    }
+}
@@ -0,0 +1,323 @@
+//! Limited mode: which subsystems this instance can currently use. The registry is seeded
+//! from configuration at startup and kept current by a background probe; the
+//! `capability_gate` middleware answers requests that need a missing subsystem with a 503
+//! naming it instead of failing deep inside a handler.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::services::ai_services::AIService;
+use crate::services::crypto_services::BlockchainService;
+use crate::services::transport_services::TransportRegistry;
+
+const PROBE_INTERVAL_SECS: u64 = 30;
+/// A probe slower than this counts as a failure
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    Database,
+    Ai,
+    Blockchain,
+    Mqtt,
+    Redis,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 5] =
+        [Capability::Database, Capability::Ai, Capability::Blockchain, Capability::Mqtt, Capability::Redis];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Capability::Database => "database",
+            Capability::Ai => "ai",
+            Capability::Blockchain => "blockchain",
+            Capability::Mqtt => "mqtt",
+            Capability::Redis => "redis",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CapabilityStatus {
+    pub name: &'static str,
+    /// Unconfigured optional subsystems are unavailable without the instance being degraded
+    pub configured: bool,
+    pub available: bool,
+    /// Why the capability is unavailable
+    pub reason: Option<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+/// Current availability of every capability
+#[derive(Debug)]
+pub struct CapabilityRegistry {
+    states: RwLock<HashMap<Capability, CapabilityStatus>>,
+}
+
+impl CapabilityRegistry {
+    /// Everything unavailable until set
+    pub fn new() -> Self {
+        let now = Utc::now();
+        let states = Capability::ALL
+            .into_iter()
+            .map(|capability| {
+                let status = CapabilityStatus {
+                    name: capability.as_str(),
+                    configured: true,
+                    available: false,
+                    reason: Some("not checked yet".to_string()),
+                    checked_at: now,
+                };
+                (capability, status)
+            })
+            .collect();
+        Self { states: RwLock::new(states) }
+    }
+
+    /// What configuration alone tells: unconfigured subsystems are unavailable, configured
+    /// ones are assumed up until a probe says otherwise
+    pub fn from_config(config: &AppConfig, database: bool) -> Self {
+        let registry = Self::new();
+        registry.set(Capability::Database, if database { Ok(()) } else { Err("database connection failed".into()) });
+        for (capability, configured, setting) in [
+            (Capability::Ai, AIService::new().is_configured(), "AI provider (AI_PROVIDER)"),
+            (Capability::Blockchain, config.chains.iter().any(|c| c.has_provider()), "chain RPC provider"),
+            (Capability::Mqtt, config.mqtt_broker_url.is_some(), "MQTT_BROKER_URL"),
+            (Capability::Redis, config.redis_url.is_some(), "REDIS_URL"),
+        ] {
+            if configured {
+                registry.set(capability, Ok(()));
+            } else {
+                registry.set_unconfigured(capability, format!("no {} is configured", setting));
+            }
+        }
+        registry
+    }
+
+    /// Mark an optional subsystem as switched off by configuration
+    pub fn set_unconfigured(&self, capability: Capability, reason: String) {
+        let status = CapabilityStatus {
+            name: capability.as_str(),
+            configured: false,
+            available: false,
+            reason: Some(reason),
+            checked_at: Utc::now(),
+        };
+        self.states.write().unwrap_or_else(|e| e.into_inner()).insert(capability, status);
+    }
+
+    /// Record a check's result
+    pub fn set(&self, capability: Capability, result: Result<(), String>) {
+        let mut states = self.states.write().unwrap_or_else(|e| e.into_inner());
+        let was_available = states.get(&capability).is_some_and(|s| s.available);
+        let status = CapabilityStatus {
+            name: capability.as_str(),
+            configured: true,
+            available: result.is_ok(),
+            reason: result.err(),
+            checked_at: Utc::now(),
+        };
+        match (&status.reason, was_available) {
+            (Some(reason), true) => tracing::warn!(capability = status.name, "Capability lost: {}", reason),
+            (None, false) => tracing::info!(capability = status.name, "Capability available"),
+            _ => {}
+        }
+        states.insert(capability, status);
+    }
+
+    pub fn is_available(&self, capability: Capability) -> bool {
+        self.states.read().unwrap_or_else(|e| e.into_inner()).get(&capability).is_some_and(|s| s.available)
+    }
+
+    /// A 503 naming the first of `capabilities` that is unavailable
+    pub fn require(&self, capabilities: &[Capability]) -> ApiResult<()> {
+        let states = self.states.read().unwrap_or_else(|e| e.into_inner());
+        for capability in capabilities {
+            let Some(status) = states.get(capability).filter(|s| !s.available) else {
+                continue;
+            };
+            return Err(ApiError::CapabilityUnavailable {
+                capability: capability.as_str(),
+                reason: status.reason.clone().unwrap_or_default(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Whether a configured subsystem is unavailable
+    pub fn is_degraded(&self) -> bool {
+        let states = self.states.read().unwrap_or_else(|e| e.into_inner());
+        states.values().any(|s| s.configured && !s.available)
+    }
+
+    /// Every capability, in a stable order
+    pub fn snapshot(&self) -> Vec<CapabilityStatus> {
+        let states = self.states.read().unwrap_or_else(|e| e.into_inner());
+        Capability::ALL.iter().filter_map(|c| states.get(c).cloned()).collect()
+    }
+}
+
+impl Default for CapabilityRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Routes and what they need, most specific first; the first matching prefix wins
+const ROUTE_REQUIREMENTS: &[(&str, &[Capability])] = &[
+    ("/api/health", &[]),
+    ("/api/version", &[]),
+    // Subsystem health endpoints report on their subsystem rather than fail with it
+    ("/api/ai/health", &[]),
+    ("/api/blockchain/health", &[]),
+    ("/api/blockchain/gas", &[Capability::Blockchain]),
+    ("/api/blockchain/rates", &[]),
+    ("/api/ai/chat", &[Capability::Database, Capability::Ai]),
+    ("/api/ai/analyze", &[Capability::Database, Capability::Ai]),
+    ("/api/ai/embeddings", &[Capability::Database, Capability::Ai]),
+    ("/api/ai/index", &[Capability::Database, Capability::Ai]),
+    ("/api/ai/search", &[Capability::Database, Capability::Ai]),
+    ("/api/ai/ask", &[Capability::Database, Capability::Ai]),
+    ("/api/ai/vision", &[Capability::Database, Capability::Ai]),
+    ("/api/ai/transcribe", &[Capability::Database, Capability::Ai]),
+    ("/api/ai/speak", &[Capability::Database, Capability::Ai]),
+    ("/api/ai/insights", &[Capability::Database, Capability::Ai]),
+    ("/api/ai/fine-tunes", &[Capability::Database, Capability::Ai]),
+    ("/api/blockchain/verify-tx/", &[Capability::Database, Capability::Blockchain]),
+    ("/api/blockchain/balance", &[Capability::Database, Capability::Blockchain]),
+    ("/api/blockchain/chains/", &[Capability::Database, Capability::Blockchain]),
+    ("/api/blockchain/transfer", &[Capability::Database, Capability::Blockchain]),
+    ("/api/escrows", &[Capability::Database, Capability::Blockchain]),
+    ("/api/blockchain/invoices", &[Capability::Database, Capability::Blockchain]),
+    ("/api/", &[Capability::Database]),
+    ("/scim/", &[Capability::Database]),
+];
+
+/// What a request path needs to be served
+pub fn required_capabilities(path: &str) -> &'static [Capability] {
+    ROUTE_REQUIREMENTS
+        .iter()
+        .find(|(prefix, _)| path.starts_with(prefix))
+        .map(|(_, required)| *required)
+        .unwrap_or(&[])
+}
+
+async fn probe_database(pool: &PgPool) -> Result<(), String> {
+    match tokio::time::timeout(PROBE_TIMEOUT, sqlx::query("SELECT 1").execute(pool)).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(format!("database query failed: {}", e)),
+        Err(_) => Err("database did not answer in time".to_string()),
+    }
+}
+
+async fn probe_blockchain(chain: &BlockchainService) -> Result<(), String> {
+    match tokio::time::timeout(PROBE_TIMEOUT, chain.block_number()).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(format!("chain {} RPC failed: {}", chain.chain_id(), e)),
+        Err(_) => Err(format!("chain {} RPC did not answer in time", chain.chain_id())),
+    }
+}
+
+/// Whether a TCP connection to the Redis server can be opened
+async fn probe_redis(url: &str) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("invalid REDIS_URL: {}", e))?;
+    let host = parsed.host_str().ok_or("REDIS_URL has no host")?;
+    let address = (host, parsed.port().unwrap_or(6379));
+    match tokio::time::timeout(PROBE_TIMEOUT, tokio::net::TcpStream::connect(address)).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(format!("Redis unreachable: {}", e)),
+        Err(_) => Err("Redis did not accept a connection in time".to_string()),
+    }
+}
+
+/// Re-check the configured subsystems in the background. Unconfigured ones stay unavailable.
+pub fn spawn_probe_job(
+    registry: Arc<CapabilityRegistry>,
+    pool: Option<Arc<PgPool>>,
+    transports: Arc<TransportRegistry>,
+    config: AppConfig,
+) {
+    // The default chain stands in for the blockchain capability
+    let chain = config
+        .chain(config.default_chain_id)
+        .filter(|c| c.has_provider())
+        .or_else(|| config.chains.iter().find(|c| c.has_provider()))
+        .map(BlockchainService::for_chain);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(PROBE_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            // Without a pool at startup there is nothing to reconnect; limited mode lasts until restart
+            if let Some(pool) = &pool {
+                registry.set(Capability::Database, probe_database(pool).await);
+            }
+            if let Some(chain) = &chain {
+                registry.set(Capability::Blockchain, probe_blockchain(chain).await);
+            }
+            if let Some(connected) = transports.mqtt_connected() {
+                let result = if connected { Ok(()) } else { Err("MQTT broker disconnected".to_string()) };
+                registry.set(Capability::Mqtt, result);
+            }
+            if let Some(url) = &config.redis_url {
+                registry.set(Capability::Redis, probe_redis(url).await);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_capabilities() {
+        assert!(required_capabilities("/health").is_empty());
+        assert!(required_capabilities("/api/health").is_empty());
+        assert!(required_capabilities("/api/ai/health").is_empty());
+        assert_eq!(required_capabilities("/api/ai/chat"), [Capability::Database, Capability::Ai]);
+        assert_eq!(required_capabilities("/api/ai/budget"), [Capability::Database]);
+        assert_eq!(
+            required_capabilities("/api/blockchain/chains/137/balance"),
+            [Capability::Database, Capability::Blockchain]
+        );
+        assert_eq!(required_capabilities("/api/blockchain/chains"), [Capability::Database]);
+        assert_eq!(required_capabilities("/api/blockchain/gas"), [Capability::Blockchain]);
+        assert!(required_capabilities("/api/blockchain/rates").is_empty());
+        assert_eq!(required_capabilities("/scim/v2/Users"), [Capability::Database]);
+    }
+
+    #[test]
+    fn test_require() {
+        let registry = CapabilityRegistry::new();
+        registry.set(Capability::Database, Ok(()));
+        registry.set(Capability::Blockchain, Ok(()));
+        registry.set(Capability::Mqtt, Ok(()));
+        registry.set_unconfigured(Capability::Redis, "no REDIS_URL is configured".to_string());
+        registry.set(Capability::Ai, Err("AI provider timed out".to_string()));
+        assert!(registry.is_degraded());
+
+        assert!(registry.require(&[Capability::Database]).is_ok());
+        assert!(registry.require(&[]).is_ok());
+        match registry.require(&[Capability::Database, Capability::Ai]) {
+            Err(ApiError::CapabilityUnavailable { capability, reason }) => {
+                assert_eq!(capability, "ai");
+                assert_eq!(reason, "AI provider timed out");
+            }
+            other => panic!("expected capability_unavailable, got {:?}", other),
+        }
+
+        registry.set(Capability::Ai, Ok(()));
+        assert!(registry.is_available(Capability::Ai));
+        // Redis is off by configuration, which is not a degradation
+        assert!(!registry.is_degraded());
+        assert!(registry.require(&[Capability::Redis]).is_err());
+        let names: Vec<&str> = registry.snapshot().iter().map(|s| s.name).collect();
+        assert_eq!(names, ["database", "ai", "blockchain", "mqtt", "redis"]);
+    }
+}
@@ -0,0 +1,350 @@
+//! Fleet capacity planning: daily utilization, charging concurrency and maintenance load over
+//! the last 90 days, extrapolated 30 and 90 days ahead with a least-squares trend.
+
+use chrono::{DateTime, Duration, DurationRound, Timelike, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::sync::LazyLock;
+use uuid::Uuid;
+use crate::errors::ApiResult;
+use crate::services::region_services::region_filter;
+
+/// Days of history the trends are fitted on
+pub const LOOKBACK_DAYS: usize = 90;
+
+/// Projection horizons reported, in days
+pub const HORIZONS: [i64; 2] = [30, 90];
+
+/// Projected utilization above this share of device-hours leaves no headroom (percent)
+const UTILIZATION_ALERT_PCT: f64 = 80.0;
+
+/// Days averaged for the "current" value
+const CURRENT_WINDOW_DAYS: usize = 7;
+
+/// Restricts devices to those owned by active members of the org bound as $1 and, unless $2
+/// is NULL, to those in the region bound as $2
+static ORG_DEVICE_FILTER: LazyLock<String> = LazyLock::new(|| {
+    format!(
+        "d.user_id IN (SELECT user_id FROM org_memberships WHERE org_id = $1 AND active) AND {}",
+        region_filter("d.id", 2)
+    )
+});
+
+/// One daily series and where its trend leads
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Projection {
+    pub metric: &'static str,
+    pub unit: &'static str,
+    /// Average over the last week
+    pub current: f64,
+    pub trend_per_day: f64,
+    /// Projected daily value 30 and 90 days from now
+    pub in_30_days: f64,
+    pub in_90_days: f64,
+    /// Projected sum over the next 30 and 90 days, for quantities that add up (events, hours)
+    pub total_next_30_days: Option<f64>,
+    pub total_next_90_days: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CapacityAlert {
+    pub metric: &'static str,
+    pub horizon_days: i64,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CapacityReport {
+    pub org_id: Uuid,
+    pub generated_at: DateTime<Utc>,
+    pub history_days: usize,
+    pub projections: Vec<Projection>,
+    /// Hours of the day (UTC) with the most devices charging at once, busiest first
+    pub peak_charging_hours: Vec<u32>,
+    pub chargers: Option<u32>,
+    pub alerts: Vec<CapacityAlert>,
+}
+
+/// Least-squares line through `values` indexed by day; returns (slope, intercept)
+pub fn linear_trend(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    if values.len() < 2 {
+        return (0.0, values.first().copied().unwrap_or(0.0));
+    }
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = values.iter().sum::<f64>() / n;
+    let (mut sxy, mut sxx) = (0.0, 0.0);
+    for (x, y) in values.iter().enumerate() {
+        let dx = x as f64 - mean_x;
+        sxy += dx * (y - mean_y);
+        sxx += dx * dx;
+    }
+    let slope = sxy / sxx;
+    (slope, mean_y - slope * mean_x)
+}
+
+/// Fit a daily series (oldest first, ending yesterday) and project it. Projected values never
+/// go below zero; `additive` series also report their projected totals.
+pub fn project(metric: &'static str, unit: &'static str, values: &[f64], additive: bool) -> Projection {
+    let (slope, intercept) = linear_trend(values);
+    let at = |day: i64| (intercept + slope * (values.len() as i64 - 1 + day) as f64).max(0.0);
+    let total = |days: i64| additive.then(|| (1..=days).map(at).sum::<f64>());
+    let recent = &values[values.len().saturating_sub(CURRENT_WINDOW_DAYS)..];
+
+    Projection {
+        metric,
+        unit,
+        current: if recent.is_empty() { 0.0 } else { recent.iter().sum::<f64>() / recent.len() as f64 },
+        trend_per_day: slope,
+        in_30_days: at(HORIZONS[0]),
+        in_90_days: at(HORIZONS[1]),
+        total_next_30_days: total(HORIZONS[0]),
+        total_next_90_days: total(HORIZONS[1]),
+    }
+}
+
+/// Where the projections run out of headroom
+pub fn alerts(projections: &[Projection], chargers: Option<u32>) -> Vec<CapacityAlert> {
+    let mut alerts = Vec::new();
+    for p in projections {
+        for (horizon, value) in [(HORIZONS[0], p.in_30_days), (HORIZONS[1], p.in_90_days)] {
+            let message = match p.metric {
+                "utilization" if value > UTILIZATION_ALERT_PCT => format!(
+                    "Fleet utilization is projected at {:.0}% of device-hours in {} days",
+                    value, horizon
+                ),
+                "charging_peak" if chargers.is_some_and(|c| value > c as f64) => format!(
+                    "{:.1} devices are projected to charge at once in {} days but only {} chargers exist",
+                    value,
+                    horizon,
+                    chargers.unwrap_or_default()
+                ),
+                _ => continue,
+            };
+            alerts.push(CapacityAlert { metric: p.metric, horizon_days: horizon, message });
+            // The nearest horizon is enough to flag a metric
+            break;
+        }
+    }
+    alerts
+}
+
+/// Rows for the CSV export, one per metric
+pub fn report_rows(report: &CapacityReport) -> Vec<Vec<String>> {
+    let opt = |v: Option<f64>| v.map(|v| format!("{:.2}", v)).unwrap_or_default();
+    report
+        .projections
+        .iter()
+        .map(|p| {
+            vec![
+                p.metric.to_string(),
+                p.unit.to_string(),
+                format!("{:.2}", p.current),
+                format!("{:.4}", p.trend_per_day),
+                format!("{:.2}", p.in_30_days),
+                format!("{:.2}", p.in_90_days),
+                opt(p.total_next_30_days),
+                opt(p.total_next_90_days),
+            ]
+        })
+        .collect()
+}
+
+pub const REPORT_CSV_HEADERS: &[&str] = &[
+    "metric",
+    "unit",
+    "current",
+    "trend_per_day",
+    "in_30_days",
+    "in_90_days",
+    "total_next_30_days",
+    "total_next_90_days",
+];
+
+/// Build the report from the org's command, telemetry and status history
+pub async fn capacity_report(
+    pool: &PgPool,
+    org_id: Uuid,
+    region_id: Option<Uuid>,
+    chargers: Option<u32>,
+) -> ApiResult<CapacityReport> {
+    let now = Utc::now();
+    let end = now.duration_trunc(Duration::days(1)).unwrap_or(now);
+    let start = end - Duration::days(LOOKBACK_DAYS as i64);
+    let day_of = |at: DateTime<Utc>| -> Option<usize> {
+        let day = (at - start).num_days();
+        (at >= start && day < LOOKBACK_DAYS as i64).then_some(day as usize)
+    };
+
+    // Fleet size at the end of each day (devices are counted from creation)
+    let created: Vec<DateTime<Utc>> =
+        sqlx::query_scalar(&format!("SELECT d.created_at FROM devices d WHERE {}", *ORG_DEVICE_FILTER))
+            .bind(org_id)
+            .bind(region_id)
+            .fetch_all(pool)
+            .await?;
+    let fleet: Vec<f64> = (0..LOOKBACK_DAYS)
+        .map(|day| {
+            let day_end = start + Duration::days(day as i64 + 1);
+            created.iter().filter(|c| **c < day_end).count() as f64
+        })
+        .collect();
+
+    let busy_rows: Vec<(DateTime<Utc>, f64)> = sqlx::query_as(&format!(
+        "SELECT date_trunc('day', c.created_at), \
+                SUM(COALESCE(c.actual_duration_ms, c.estimated_duration_ms))::float8 / 3600000.0 \
+         FROM device_commands c JOIN devices d ON d.id = c.device_id \
+         WHERE {} AND c.status IN ('sent', 'succeeded', 'failed') AND c.created_at >= $3 AND c.created_at < $4 \
+         GROUP BY 1",
+        *ORG_DEVICE_FILTER
+    ))
+    .bind(org_id)
+    .bind(region_id)
+    .bind(start)
+    .bind(end)
+    .fetch_all(pool)
+    .await?;
+    let mut busy = vec![0.0; LOOKBACK_DAYS];
+    for (day, hours) in busy_rows {
+        if let Some(i) = day_of(day) {
+            busy[i] = hours;
+        }
+    }
+    let utilization: Vec<f64> = busy
+        .iter()
+        .zip(&fleet)
+        .map(|(hours, devices)| if *devices > 0.0 { hours / (devices * 24.0) * 100.0 } else { 0.0 })
+        .collect();
+
+    // A device is charging in an hour when a reading rose from the one before it
+    let charging_rows: Vec<(DateTime<Utc>, i64)> = sqlx::query_as(&format!(
+        "SELECT hour, COUNT(DISTINCT device_id) FROM ( \
+             SELECT dt.device_id, date_trunc('hour', dt.recorded_at) AS hour, \
+                    dt.battery_level > LAG(dt.battery_level) OVER w \
+                    AND dt.recorded_at - LAG(dt.recorded_at) OVER w <= INTERVAL '30 minutes' AS charging \
+             FROM device_telemetry dt JOIN devices d ON d.id = dt.device_id \
+             WHERE {} AND dt.recorded_at >= $3 AND dt.recorded_at < $4 \
+             WINDOW w AS (PARTITION BY dt.device_id ORDER BY dt.recorded_at) \
+         ) s WHERE charging GROUP BY hour",
+        *ORG_DEVICE_FILTER
+    ))
+    .bind(org_id)
+    .bind(region_id)
+    .bind(start)
+    .bind(end)
+    .fetch_all(pool)
+    .await?;
+    let mut charging_peak = vec![0.0_f64; LOOKBACK_DAYS];
+    let mut by_hour_of_day = [0.0; 24];
+    for (hour, devices) in charging_rows {
+        if let Some(i) = day_of(hour) {
+            charging_peak[i] = charging_peak[i].max(devices as f64);
+            by_hour_of_day[hour.hour() as usize] += devices as f64;
+        }
+    }
+    let mut peak_charging_hours: Vec<u32> = (0..24).filter(|h| by_hour_of_day[*h as usize] > 0.0).collect();
+    peak_charging_hours.sort_by(|a, b| by_hour_of_day[*b as usize].total_cmp(&by_hour_of_day[*a as usize]));
+    peak_charging_hours.truncate(3);
+
+    // Maintenance stints, attributed to the day they began
+    let maintenance_rows: Vec<(DateTime<Utc>, f64)> = sqlx::query_as(&format!(
+        "SELECT changed_at, EXTRACT(EPOCH FROM (COALESCE(next_at, NOW()) - changed_at))::float8 / 3600.0 FROM ( \
+             SELECT h.status, h.changed_at, \
+                    LEAD(h.changed_at) OVER (PARTITION BY h.device_id ORDER BY h.changed_at) AS next_at \
+             FROM device_status_history h JOIN devices d ON d.id = h.device_id \
+             WHERE {} AND h.changed_at >= $3 \
+         ) s WHERE status = 'maintenance' AND changed_at < $4",
+        *ORG_DEVICE_FILTER
+    ))
+    .bind(org_id)
+    .bind(region_id)
+    .bind(start)
+    .bind(end)
+    .fetch_all(pool)
+    .await?;
+    let mut maintenance_events = vec![0.0; LOOKBACK_DAYS];
+    let mut maintenance_hours = vec![0.0; LOOKBACK_DAYS];
+    for (began, hours) in maintenance_rows {
+        if let Some(i) = day_of(began) {
+            maintenance_events[i] += 1.0;
+            maintenance_hours[i] += hours;
+        }
+    }
+
+    let projections = vec![
+        project("fleet_size", "devices", &fleet, false),
+        project("busy_hours", "device-hours/day", &busy, true),
+        project("utilization", "percent", &utilization, false),
+        project("charging_peak", "devices charging at once", &charging_peak, false),
+        project("maintenance_events", "events/day", &maintenance_events, true),
+        project("maintenance_hours", "device-hours/day", &maintenance_hours, true),
+    ];
+    let alerts = alerts(&projections, chargers);
+
+    Ok(CapacityReport {
+        org_id,
+        generated_at: now,
+        history_days: LOOKBACK_DAYS,
+        projections,
+        peak_charging_hours,
+        chargers,
+        alerts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear_trend() {
+        let (slope, intercept) = linear_trend(&[1.0, 3.0, 5.0, 7.0]);
+        assert!((slope - 2.0).abs() < 1e-9);
+        assert!((intercept - 1.0).abs() < 1e-9);
+        assert_eq!(linear_trend(&[4.0]), (0.0, 4.0));
+        assert_eq!(linear_trend(&[]), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_project() {
+        // One more busy hour every day, ending at 89 yesterday
+        let values: Vec<f64> = (0..90).map(|d| d as f64).collect();
+        let p = project("busy_hours", "device-hours/day", &values, true);
+        assert!((p.current - 86.0).abs() < 1e-9);
+        assert!((p.in_30_days - 119.0).abs() < 1e-9);
+        assert!((p.in_90_days - 179.0).abs() < 1e-9);
+        // 90 + 91 + ... + 119
+        assert!((p.total_next_30_days.unwrap() - 3135.0).abs() < 1e-6);
+
+        // A shrinking series bottoms out at zero and non-additive ones have no totals
+        let falling: Vec<f64> = (0..90).map(|d| 90.0 - d as f64).collect();
+        let p = project("fleet_size", "devices", &falling, false);
+        assert_eq!(p.in_90_days, 0.0);
+        assert!(p.total_next_90_days.is_none());
+    }
+
+    #[test]
+    fn test_alerts() {
+        let projection = |metric, in_30_days, in_90_days| Projection {
+            metric,
+            unit: "",
+            current: 0.0,
+            trend_per_day: 0.0,
+            in_30_days,
+            in_90_days,
+            total_next_30_days: None,
+            total_next_90_days: None,
+        };
+        let projections = [
+            projection("utilization", 60.0, 85.0),
+            projection("charging_peak", 5.0, 7.0),
+            projection("maintenance_hours", 500.0, 900.0),
+        ];
+
+        let found = alerts(&projections, Some(4));
+        let flagged: Vec<(&str, i64)> = found.iter().map(|a| (a.metric, a.horizon_days)).collect();
+        assert_eq!(flagged, [("utilization", 90), ("charging_peak", 30)]);
+        // Without a charger count there is nothing to compare charging against
+        assert_eq!(alerts(&projections, None).len(), 1);
+    }
+}
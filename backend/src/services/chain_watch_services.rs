@@ -0,0 +1,210 @@
+//! Confirmation tracking for crypto payments. Pending transactions carrying a
+//! `blockchain_tx_hash` are polled for their receipt on their own chain; the block they were
+//! mined in and their depth are recorded, and they complete once the chain's required number
+//! of blocks confirm them. Reverted transactions, and ones never mined within a day, fail.
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::ApiResult;
+use crate::models::transaction::Transaction;
+use crate::services::crypto_services::{confirmations, BlockchainService, TxReceipt};
+use crate::services::payment_services::{complete_transaction, fail_transaction, TRANSACTION_COLUMNS};
+
+/// Roughly one Ethereum block
+const JOB_INTERVAL_SECS: u64 = 15;
+/// Transactions checked per run; the least recently checked go first
+const MAX_CHECKS_PER_RUN: i64 = 100;
+/// A transaction with no receipt after this long is taken to have been dropped
+pub const DROPPED_AFTER_HOURS: i64 = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// Not mined yet, or not deep enough
+    Waiting { confirmations: u32, block_number: Option<u64> },
+    Confirmed { confirmations: u32, block_number: u64 },
+    Reverted { block_number: u64 },
+    Dropped,
+}
+
+/// What to do with a transaction submitted at `submitted_at`, given its receipt and the head
+/// of the chain
+pub fn outcome(
+    receipt: Option<TxReceipt>,
+    head: u64,
+    required: u32,
+    submitted_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Outcome {
+    match receipt {
+        None if now - submitted_at >= Duration::hours(DROPPED_AFTER_HOURS) => Outcome::Dropped,
+        None => Outcome::Waiting { confirmations: 0, block_number: None },
+        Some(receipt) if !receipt.succeeded => Outcome::Reverted { block_number: receipt.block_number },
+        Some(receipt) => {
+            let depth = confirmations(head, receipt.block_number);
+            if depth >= required {
+                Outcome::Confirmed { confirmations: depth, block_number: receipt.block_number }
+            } else {
+                Outcome::Waiting { confirmations: depth, block_number: Some(receipt.block_number) }
+            }
+        }
+    }
+}
+
+/// Check the pending transactions sent on `chain` against it, returning how many were settled.
+/// Transactions without a chain are taken to be on `default_chain_id`.
+pub async fn check_pending(
+    pool: &PgPool,
+    chain: &BlockchainService,
+    required: u32,
+    default_chain_id: u64,
+) -> ApiResult<usize> {
+    let pending = sqlx::query_as::<_, Transaction>(&format!(
+        "SELECT {} FROM transactions WHERE status = 'pending' AND blockchain_tx_hash IS NOT NULL \
+         AND COALESCE(chain_id, $2) = $3 ORDER BY chain_checked_at NULLS FIRST LIMIT $1",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(MAX_CHECKS_PER_RUN)
+    .bind(default_chain_id as i64)
+    .bind(chain.chain_id() as i64)
+    .fetch_all(pool)
+    .await?;
+    if pending.is_empty() {
+        return Ok(0);
+    }
+
+    // One head for the whole run keeps depths consistent between transactions
+    let head = chain.block_number().await?;
+    let mut settled = 0;
+    for transaction in pending {
+        let hash = transaction.blockchain_tx_hash.as_deref().unwrap_or_default();
+        let receipt = if BlockchainService::is_valid_tx_hash(hash) {
+            match chain.transaction_receipt(hash).await {
+                Ok(receipt) => receipt,
+                Err(e) => {
+                    tracing::warn!(transaction_id = %transaction.id, "Receipt lookup failed: {}", e);
+                    continue;
+                }
+            }
+        } else {
+            // Never minable; left to be dropped like any transaction that is never mined
+            None
+        };
+
+        let applied = apply_receipt(pool, transaction.id, receipt, head, required).await?;
+        if applied.is_some_and(|(_, changed)| changed) {
+            settled += 1;
+        }
+    }
+    Ok(settled)
+}
+
+/// Record what `receipt` says about a pending transaction and settle it once the outcome is
+/// final. Returns the outcome and whether the transaction was settled, or `None` when it was
+/// no longer pending.
+pub async fn apply_receipt(
+    pool: &PgPool,
+    transaction_id: Uuid,
+    receipt: Option<TxReceipt>,
+    head: u64,
+    required: u32,
+) -> ApiResult<Option<(Outcome, bool)>> {
+    let mut tx = pool.begin().await?;
+    let Some(current) = sqlx::query_as::<_, Transaction>(&format!(
+        "SELECT {} FROM transactions WHERE id = $1 AND status = 'pending' FOR UPDATE",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(transaction_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        // Settled some other way since it was read
+        return Ok(None);
+    };
+
+    let decided = outcome(receipt, head, required, current.created_at, Utc::now());
+    let (depth, block_number) = match decided {
+        Outcome::Waiting { confirmations, block_number } => (confirmations, block_number),
+        Outcome::Confirmed { confirmations, block_number } => (confirmations, Some(block_number)),
+        Outcome::Reverted { block_number } => (0, Some(block_number)),
+        Outcome::Dropped => (0, None),
+    };
+    sqlx::query(
+        "UPDATE transactions SET confirmations = $2, block_number = $3, chain_checked_at = NOW() WHERE id = $1",
+    )
+    .bind(current.id)
+    .bind(depth.min(i32::MAX as u32) as i32)
+    .bind(block_number.map(|b| b as i64))
+    .execute(&mut *tx)
+    .await?;
+
+    let changed = match decided {
+        Outcome::Waiting { .. } => false,
+        Outcome::Confirmed { .. } => complete_transaction(&mut tx, &current).await?,
+        Outcome::Reverted { .. } => fail_transaction(&mut tx, &current, "Transaction reverted on chain").await?,
+        Outcome::Dropped => {
+            let reason = format!("Transaction not mined within {} hours", DROPPED_AFTER_HOURS);
+            fail_transaction(&mut tx, &current, &reason).await?
+        }
+    };
+    tx.commit().await?;
+    Ok(Some((decided, changed)))
+}
+
+/// Poll for confirmations in the background on every chain with a JSON-RPC provider.
+/// Does nothing when none has one.
+pub fn spawn_confirmation_watcher(pool: Arc<PgPool>, config: AppConfig) {
+    let chains: Vec<(BlockchainService, u32)> = config
+        .chains
+        .iter()
+        .filter(|chain| chain.has_provider())
+        .map(|chain| (BlockchainService::for_chain(chain), chain.required_confirmations))
+        .collect();
+    if chains.is_empty() {
+        tracing::info!("No Web3 provider configured; crypto confirmations are not tracked");
+        return;
+    }
+    let default_chain_id = config.default_chain_id;
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(JOB_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            for (chain, required) in &chains {
+                match check_pending(&pool, chain, *required, default_chain_id).await {
+                    Ok(0) => {}
+                    Ok(settled) => tracing::info!(chain_id = chain.chain_id(), settled, "Settled crypto transactions"),
+                    Err(e) => tracing::error!(chain_id = chain.chain_id(), "Confirmation watcher failed: {}", e),
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outcome() {
+        let now = Utc::now();
+        let mined = |block_number, succeeded| Some(TxReceipt { block_number, succeeded });
+
+        assert_eq!(
+            outcome(None, 100, 12, now - Duration::minutes(5), now),
+            Outcome::Waiting { confirmations: 0, block_number: None }
+        );
+        assert_eq!(outcome(None, 100, 12, now - Duration::hours(DROPPED_AFTER_HOURS), now), Outcome::Dropped);
+        assert_eq!(
+            outcome(mined(95, true), 100, 12, now, now),
+            Outcome::Waiting { confirmations: 6, block_number: Some(95) }
+        );
+        assert_eq!(
+            outcome(mined(89, true), 100, 12, now, now),
+            Outcome::Confirmed { confirmations: 12, block_number: 89 }
+        );
+        // A revert fails the payment however deep it is
+        assert_eq!(outcome(mined(50, false), 100, 12, now, now), Outcome::Reverted { block_number: 50 });
+    }
+}
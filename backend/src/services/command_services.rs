@@ -0,0 +1,437 @@
+//! Issuing commands to devices, shared by the command API and everything that acts on a
+//! user's behalf (automations, missions)
+
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::device::Device;
+use crate::models::mission::LegAction;
+use crate::models::swarm::GeoBounds;
+use crate::services::attachment_services::{attached_types, require_attachment};
+use crate::services::constraint_services::{self, constraint_states, ConstraintState};
+use crate::services::promotion_services::{
+    check_steps, device_checks, device_profiles, validate_geofence, DeviceProfile, PlanEstimate, PromotionCheck,
+};
+use crate::services::robotics_services::{CommandParams, CommandResult, RoboticsService};
+use crate::services::transport_services::{self, OutboundCommand, TransportRegistry};
+
+/// What sending a command would do, without sending it
+#[derive(Debug, Serialize)]
+pub struct CommandPreview {
+    pub dry_run: bool,
+    /// Whether a real send would be accepted; failing advisory checks (battery, geofence,
+    /// operating limits) do not block a real send, failing constraints do unless overridden
+    pub would_dispatch: bool,
+    pub device_id: Uuid,
+    pub command: String,
+    pub parameters: CommandParams,
+    pub transport: String,
+    pub estimated_duration_ms: u64,
+    pub estimated_battery_drain: f32,
+    /// Drain and reach adjusted by how far the device's recent commands strayed from estimates
+    pub projected: PlanEstimate,
+    pub checks: Vec<PromotionCheck>,
+}
+
+/// Dry run of `issue_command`: the same validation and estimates plus battery, geofence and
+/// constraint checks, with nothing recorded or dispatched. Invalid commands fail exactly as a
+/// real send would.
+pub async fn preview_command(
+    pool: &PgPool,
+    user_id: Uuid,
+    device: &Device,
+    command: &str,
+    parameters: &serde_json::Value,
+    geofence: Option<&GeoBounds>,
+    override_constraints: bool,
+) -> ApiResult<CommandPreview> {
+    if let Some(bounds) = geofence {
+        validate_geofence(bounds)?;
+    }
+    let attached = attached_types(pool, device.id).await?;
+    let profile = device_profiles(pool, user_id, &[device.id])
+        .await?
+        .remove(&device.id)
+        .ok_or_else(|| ApiError::NotFound("Device not found".to_string()))?;
+    let constraints = constraint_states(pool, device.id, &[command]).await?;
+
+    plan_preview(
+        device,
+        &profile,
+        &attached,
+        &constraints,
+        command,
+        parameters,
+        geofence,
+        override_constraints,
+    )
+}
+
+/// The preview of a command from the device's state read by `preview_command`. Takes no
+/// connection or transport, so a dry run cannot write or send anything.
+#[allow(clippy::too_many_arguments)]
+fn plan_preview(
+    device: &Device,
+    profile: &DeviceProfile,
+    attached: &[String],
+    constraints: &[ConstraintState],
+    command: &str,
+    parameters: &serde_json::Value,
+    geofence: Option<&GeoBounds>,
+    override_constraints: bool,
+) -> ApiResult<CommandPreview> {
+    let service = RoboticsService::new();
+    service.validate_command(&device.device_type, &device.firmware_version, command)?;
+    require_attachment(&device.device_type, command, attached)?;
+    let params = service.parse_command_params(command, parameters)?;
+    let estimated_duration_ms = service.estimate_duration_ms(&params);
+    let estimated_battery_drain = service.estimate_battery_drain(command, &params);
+
+    let plan = vec![(command.to_string(), parameters.clone())];
+    let (problems, projected) = check_steps(profile, &plan);
+
+    let online = device.status != "offline";
+    let mut checks = vec![PromotionCheck {
+        device_id: device.id,
+        check: "device_online",
+        passed: online,
+        detail: format!("Device is {}", device.status),
+    }];
+    // Waypoint-free: the geofence check uses the command's worst-case reach
+    checks.extend(
+        device_checks(profile, &problems, &projected, &[], geofence)
+            .into_iter()
+            .filter(|c| c.check != "production_device"),
+    );
+    let mut constrained = false;
+    for state in constraints {
+        let violation = state.violation();
+        constrained |= violation.is_some();
+        checks.push(PromotionCheck {
+            device_id: device.id,
+            check: "constraint",
+            passed: violation.is_none(),
+            detail: match violation {
+                Some(violation) => format!("'{}': {}", state.name, violation.describe()),
+                None => format!("'{}' holds", state.name),
+            },
+        });
+    }
+
+    Ok(CommandPreview {
+        dry_run: true,
+        would_dispatch: online && (!constrained || override_constraints),
+        device_id: device.id,
+        command: command.to_string(),
+        parameters: params,
+        transport: device.transport.clone(),
+        estimated_duration_ms,
+        estimated_battery_drain,
+        projected,
+        checks,
+    })
+}
+
+/// Validate a command against the device and its constraints, record it with its estimates
+/// and push it through the device's transport. `override_constraints` dispatches past failing
+/// constraints and must only be set for admins; the override is audited.
+pub async fn issue_command(
+    pool: &PgPool,
+    transports: &TransportRegistry,
+    user_id: Uuid,
+    device: &Device,
+    command: &str,
+    parameters: &serde_json::Value,
+    override_constraints: bool,
+) -> ApiResult<CommandResult> {
+    if device.status == "offline" {
+        return Err(ApiError::BadRequest("Device is offline".to_string()));
+    }
+
+    let service = RoboticsService::new();
+    service.validate_command(&device.device_type, &device.firmware_version, command)?;
+    require_attachment(&device.device_type, command, &attached_types(pool, device.id).await?)?;
+    let params = service.parse_command_params(command, parameters)?;
+    let estimated_duration_ms = service.estimate_duration_ms(&params);
+    let estimated_battery_drain = service.estimate_battery_drain(command, &params);
+    let overridden = constraint_services::enforce(pool, device.id, &[command], override_constraints).await?;
+
+    let command_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO device_commands \
+         (device_id, user_id, command, parameters, estimated_duration_ms, estimated_battery_drain) \
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+    )
+    .bind(device.id)
+    .bind(user_id)
+    .bind(command)
+    .bind(parameters)
+    .bind(estimated_duration_ms as i64)
+    .bind(estimated_battery_drain)
+    .fetch_one(pool)
+    .await?;
+    if !overridden.is_empty() {
+        constraint_services::record_override(pool, user_id, device.id, &[command_id], &overridden).await?;
+    }
+
+    let outbound = OutboundCommand {
+        command_id,
+        device_id: device.id,
+        command: command.to_string(),
+        parameters: parameters.clone(),
+        issued_at: Utc::now(),
+    };
+    let status = transport_services::deliver(
+        pool,
+        transports,
+        &device.transport,
+        &outbound,
+        (estimated_duration_ms, estimated_battery_drain),
+    )
+    .await?;
+
+    Ok(CommandResult {
+        command_id,
+        status: status.to_string(),
+        executed_at: Utc::now(),
+        estimated_duration_ms,
+        estimated_battery_drain,
+    })
+}
+
+/// Most commands accepted in one batch
+pub const MAX_BATCH_COMMANDS: usize = 100;
+
+/// Commands enqueued together by `issue_batch`
+#[derive(Debug, Serialize)]
+pub struct BatchResult {
+    pub batch_id: Uuid,
+    pub device_id: Uuid,
+    pub commands: Vec<CommandResult>,
+    pub estimated_duration_ms: u64,
+    pub estimated_battery_drain: f64,
+}
+
+/// Check every command of a batch against the device, reporting all failures at once
+pub fn validate_batch(device: &DeviceProfile, commands: &[LegAction]) -> ApiResult<PlanEstimate> {
+    if commands.is_empty() || commands.len() > MAX_BATCH_COMMANDS {
+        return Err(ApiError::ValidationError(format!(
+            "A batch needs 1-{} commands",
+            MAX_BATCH_COMMANDS
+        )));
+    }
+    let plan = commands.iter().map(|c| (c.command.clone(), c.parameters.clone())).collect();
+    let (problems, estimate) = check_steps(device, &plan);
+    if !problems.is_empty() {
+        return Err(ApiError::ValidationError(format!("Batch rejected: {}", problems.join("; "))));
+    }
+    Ok(estimate)
+}
+
+/// Validate a batch as a whole and enqueue it in one transaction, so either every command is
+/// recorded or none is. Commands share a batch id and are dispatched in order. Constraints
+/// are checked for every command up front, as for `issue_command`.
+pub async fn issue_batch(
+    pool: &PgPool,
+    transports: &TransportRegistry,
+    user_id: Uuid,
+    device: &Device,
+    commands: &[LegAction],
+    override_constraints: bool,
+) -> ApiResult<BatchResult> {
+    if device.status == "offline" {
+        return Err(ApiError::BadRequest("Device is offline".to_string()));
+    }
+    let profile = device_profiles(pool, user_id, &[device.id])
+        .await?
+        .remove(&device.id)
+        .ok_or_else(|| ApiError::NotFound("Device not found".to_string()))?;
+    let estimate = validate_batch(&profile, commands)?;
+    let names: Vec<&str> = commands.iter().map(|c| c.command.as_str()).collect();
+    let overridden = constraint_services::enforce(pool, device.id, &names, override_constraints).await?;
+
+    let service = RoboticsService::new();
+    let mut queued = Vec::with_capacity(commands.len());
+    for step in commands {
+        let params = service.parse_command_params(&step.command, &step.parameters)?;
+        let drain = service.estimate_battery_drain(&step.command, &params);
+        queued.push((step, service.estimate_duration_ms(&params), drain));
+    }
+
+    let batch_id = Uuid::new_v4();
+    let mut tx = pool.begin().await?;
+    let mut ids = Vec::with_capacity(queued.len());
+    for (sequence, (step, duration, drain)) in queued.iter().enumerate() {
+        let id: Uuid = sqlx::query_scalar(
+            "INSERT INTO device_commands \
+             (device_id, user_id, command, parameters, estimated_duration_ms, estimated_battery_drain, \
+              batch_id, sequence) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
+        )
+        .bind(device.id)
+        .bind(user_id)
+        .bind(&step.command)
+        .bind(&step.parameters)
+        .bind(*duration as i64)
+        .bind(*drain)
+        .bind(batch_id)
+        .bind(sequence as i32)
+        .fetch_one(&mut *tx)
+        .await?;
+        ids.push(id);
+    }
+    tx.commit().await?;
+    if !overridden.is_empty() {
+        constraint_services::record_override(pool, user_id, device.id, &ids, &overridden).await?;
+    }
+
+    let mut results = Vec::with_capacity(ids.len());
+    for (command_id, (step, duration, drain)) in ids.into_iter().zip(&queued) {
+        let outbound = OutboundCommand {
+            command_id,
+            device_id: device.id,
+            command: step.command.clone(),
+            parameters: step.parameters.clone(),
+            issued_at: Utc::now(),
+        };
+        let status =
+            transport_services::deliver(pool, transports, &device.transport, &outbound, (*duration, *drain)).await?;
+        results.push(CommandResult {
+            command_id,
+            status: status.to_string(),
+            executed_at: Utc::now(),
+            estimated_duration_ms: *duration,
+            estimated_battery_drain: *drain,
+        });
+    }
+
+    Ok(BatchResult {
+        batch_id,
+        device_id: device.id,
+        commands: results,
+        estimated_duration_ms: estimate.duration_ms,
+        estimated_battery_drain: estimate.battery_drain,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn robot() -> DeviceProfile {
+        DeviceProfile {
+            id: Uuid::new_v4(),
+            device_type: "robot".to_string(),
+            firmware_version: "1.3.0".to_string(),
+            transport: "simulated".to_string(),
+            last_latitude: None,
+            last_longitude: None,
+            battery_level: Some(80),
+            drain_correction: None,
+            attachments: Vec::new(),
+        }
+    }
+
+    fn step(command: &str, parameters: serde_json::Value) -> LegAction {
+        LegAction { command: command.to_string(), parameters }
+    }
+
+    #[test]
+    fn test_validate_batch_reports_every_failure() {
+        let valid = [
+            step("move_forward", serde_json::json!({ "speed": 0.5, "duration_ms": 2000 })),
+            step("stop", serde_json::Value::Null),
+        ];
+        let estimate = validate_batch(&robot(), &valid).unwrap();
+        assert_eq!(estimate.commands, 2);
+        assert!(estimate.duration_ms > 0);
+
+        let invalid = [
+            step("move_forward", serde_json::json!({ "speed": 0.5, "duration_ms": 2000 })),
+            step("takeoff", serde_json::Value::Null),
+            step("grab", serde_json::Value::Null),
+        ];
+        let message = validate_batch(&robot(), &invalid).unwrap_err().to_string();
+        assert!(message.contains("step 1 (takeoff)"), "{}", message);
+        assert!(message.contains("step 2 (grab)"), "{}", message);
+
+        assert!(validate_batch(&robot(), &[]).is_err());
+    }
+
+    fn device(profile: &DeviceProfile, status: &str) -> Device {
+        Device {
+            id: profile.id,
+            user_id: Uuid::new_v4(),
+            device_name: "rover-1".to_string(),
+            device_type: profile.device_type.clone(),
+            firmware_version: profile.firmware_version.clone(),
+            status: status.to_string(),
+            last_seen: None,
+            metadata: serde_json::json!({}),
+            transport: profile.transport.clone(),
+            nft_chain_id: None,
+            nft_token_id: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn gate_closed() -> ConstraintState {
+        ConstraintState {
+            id: Uuid::new_v4(),
+            name: "gate open".to_string(),
+            command: "move_forward".to_string(),
+            depends_on_device_id: Uuid::new_v4(),
+            condition: "status".to_string(),
+            field: None,
+            expected: "online".to_string(),
+            device_name: "gate".to_string(),
+            status: "offline".to_string(),
+            metadata: serde_json::json!({}),
+            last_command: None,
+            last_command_status: None,
+        }
+    }
+
+    // plan_preview has no connection or transport to write to; these check what it reports
+    #[test]
+    fn test_dry_run_reports_plan_without_dispatching() {
+        let profile = robot();
+        let parameters = serde_json::json!({ "speed": 0.5, "duration_ms": 2000 });
+        let preview =
+            plan_preview(&device(&profile, "online"), &profile, &[], &[], "move_forward", &parameters, None, false)
+                .unwrap();
+        assert!(preview.dry_run);
+        assert!(preview.would_dispatch);
+        assert_eq!(preview.device_id, profile.id);
+        assert_eq!(preview.command, "move_forward");
+        assert_eq!(preview.transport, "simulated");
+        assert_eq!(preview.projected.commands, 1);
+
+        // The estimates a real send would record
+        let service = RoboticsService::new();
+        let params = service.parse_command_params("move_forward", &parameters).unwrap();
+        assert_eq!(preview.estimated_duration_ms, service.estimate_duration_ms(&params));
+        assert_eq!(preview.estimated_battery_drain, service.estimate_battery_drain("move_forward", &params));
+        assert!(preview.checks.iter().any(|c| c.check == "device_online" && c.passed));
+
+        // Offline devices and failing constraints are reported, not refused
+        let offline = device(&profile, "offline");
+        let preview = plan_preview(&offline, &profile, &[], &[], "move_forward", &parameters, None, false).unwrap();
+        assert!(!preview.would_dispatch);
+        assert!(preview.checks.iter().any(|c| c.check == "device_online" && !c.passed));
+
+        let online = device(&profile, "online");
+        let constraints = [gate_closed()];
+        let preview =
+            plan_preview(&online, &profile, &[], &constraints, "move_forward", &parameters, None, false).unwrap();
+        assert!(!preview.would_dispatch);
+        assert!(preview.checks.iter().any(|c| c.check == "constraint" && !c.passed));
+        let preview =
+            plan_preview(&online, &profile, &[], &constraints, "move_forward", &parameters, None, true).unwrap();
+        assert!(preview.would_dispatch);
+
+        // Invalid commands fail as a real send would
+        assert!(plan_preview(&online, &profile, &[], &[], "takeoff", &serde_json::Value::Null, None, false).is_err());
+    }
+}
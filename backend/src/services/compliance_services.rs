@@ -0,0 +1,95 @@
+//! Export compliance: geo-IP blocking of sanctioned regions on sensitive endpoints,
+//! with reviewed allowlist overrides for false positives
+
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::errors::ApiResult;
+
+/// Endpoints refused to requests from blocked locations (method, exact path)
+pub const GEO_RESTRICTED_ENDPOINTS: &[(&str, &str)] = &[
+    ("POST", "/api/auth/register"),
+    ("POST", "/api/blockchain/payment"),
+];
+
+/// Audit action recorded for every refused request; its id is the appeal reference
+pub const GEO_BLOCKED_ACTION: &str = "compliance.geo_blocked";
+
+/// Lifetime of an approved override unless the reviewer picks another (days)
+pub const DEFAULT_OVERRIDE_DAYS: i64 = 90;
+pub const MAX_OVERRIDE_DAYS: i64 = 365;
+
+pub fn is_restricted_endpoint(method: &str, path: &str) -> bool {
+    let path = path.trim_end_matches('/');
+    GEO_RESTRICTED_ENDPOINTS
+        .iter()
+        .any(|(m, p)| m.eq_ignore_ascii_case(method) && *p == path)
+}
+
+/// ISO 3166-2 code from the country and the edge's subdivision header, which may or
+/// may not already carry the country prefix (`43` or `UA-43`)
+pub fn region_code(country: &str, region: &str) -> String {
+    let region = region.trim().to_uppercase();
+    if region.contains('-') { region } else { format!("{}-{}", country, region) }
+}
+
+/// The blocked country or region a request comes from, if any
+pub fn blocked_location(
+    blocked_countries: &[String],
+    blocked_regions: &[String],
+    country: Option<&str>,
+    region: Option<&str>,
+) -> Option<String> {
+    let country = country?;
+    if blocked_countries.iter().any(|c| c == country) {
+        return Some(country.to_string());
+    }
+    let region = region.filter(|r| !r.trim().is_empty()).map(|r| region_code(country, r))?;
+    blocked_regions.contains(&region).then_some(region)
+}
+
+/// Whether an approved, unexpired override covers this IP or user
+pub async fn is_allowlisted(pool: &PgPool, ip: Option<&str>, user_id: Option<Uuid>) -> ApiResult<bool> {
+    if ip.is_none() && user_id.is_none() {
+        return Ok(false);
+    }
+    let allowed = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM geo_block_allowlist \
+         WHERE status = 'approved' AND (expires_at IS NULL OR expires_at > NOW()) \
+           AND ((ip IS NOT NULL AND ip = $1) OR (user_id IS NOT NULL AND user_id = $2)))",
+    )
+    .bind(ip)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(allowed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(list: &[&str]) -> Vec<String> {
+        list.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn test_restricted_endpoints() {
+        assert!(is_restricted_endpoint("POST", "/api/auth/register"));
+        assert!(is_restricted_endpoint("post", "/api/blockchain/payment/"));
+        assert!(!is_restricted_endpoint("GET", "/api/blockchain/payment"));
+        assert!(!is_restricted_endpoint("POST", "/api/auth/login"));
+    }
+
+    #[test]
+    fn test_blocked_location() {
+        let countries = codes(&["IR", "KP"]);
+        let regions = codes(&["UA-43"]);
+        assert_eq!(blocked_location(&countries, &regions, Some("IR"), None).as_deref(), Some("IR"));
+        assert_eq!(blocked_location(&countries, &regions, Some("UA"), Some("43")).as_deref(), Some("UA-43"));
+        assert_eq!(blocked_location(&countries, &regions, Some("UA"), Some("ua-43")).as_deref(), Some("UA-43"));
+        assert_eq!(blocked_location(&countries, &regions, Some("UA"), Some("30")), None);
+        assert_eq!(blocked_location(&countries, &regions, Some("DE"), None), None);
+        // No geo-IP data: nothing to block on
+        assert_eq!(blocked_location(&countries, &regions, None, Some("43")), None);
+    }
+}
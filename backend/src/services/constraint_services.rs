@@ -0,0 +1,246 @@
+//! Cross-device dependency constraints. A constraint guards one command on one device with a
+//! condition on another device's state; the dispatcher refuses the command while any enabled
+//! constraint on it does not hold, unless an admin overrides them.
+
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::constraint::ConstraintViolation;
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::robotics_services::device_type_spec;
+
+pub const CONSTRAINT_COLUMNS: &str = "id, user_id, name, device_id, command, depends_on_device_id, condition, \
+     field, expected, enabled, created_at, updated_at";
+
+/// What of the dependency a constraint compares: its status, the last command it completed
+/// or a metadata key it reports (e.g. `dock_state`)
+pub const CONDITIONS: &[&str] = &["status", "last_command", "metadata"];
+
+/// Constraints one user may declare
+pub const MAX_CONSTRAINTS_PER_USER: i64 = 200;
+
+pub fn validate_name(name: &str) -> ApiResult<()> {
+    if name.trim().is_empty() || name.chars().count() > 64 {
+        return Err(ApiError::ValidationError("name must be 1-64 characters".to_string()));
+    }
+    Ok(())
+}
+
+/// A metadata condition needs the key it compares; the others take none
+pub fn validate_condition(condition: &str, field: Option<&str>, expected: &str) -> ApiResult<()> {
+    if !CONDITIONS.contains(&condition) {
+        return Err(ApiError::ValidationError(format!(
+            "Unknown condition '{}'. Valid conditions: {:?}",
+            condition, CONDITIONS
+        )));
+    }
+    match (condition, field) {
+        ("metadata", None) => {
+            return Err(ApiError::ValidationError("field is required for metadata conditions".to_string()));
+        }
+        ("metadata", Some(field)) if field.is_empty() || field.len() > 64 => {
+            return Err(ApiError::ValidationError("field must be 1-64 characters".to_string()));
+        }
+        ("metadata", Some(_)) => {}
+        (_, Some(_)) => {
+            return Err(ApiError::ValidationError("field only applies to metadata conditions".to_string()));
+        }
+        (_, None) => {}
+    }
+    if expected.is_empty() || expected.len() > 100 {
+        return Err(ApiError::ValidationError("expected must be 1-100 characters".to_string()));
+    }
+    Ok(())
+}
+
+/// The guarded command must be one the device type understands
+pub fn validate_guarded_command(device_type: &str, command: &str) -> ApiResult<()> {
+    let known = device_type_spec(device_type).is_some_and(|spec| spec.commands.iter().any(|c| c.command == command));
+    if !known {
+        return Err(ApiError::ValidationError(format!(
+            "'{}' is not a command of device type '{}'",
+            command, device_type
+        )));
+    }
+    Ok(())
+}
+
+/// The dependency's current value for a condition. `last_command` is the latest command only
+/// once it succeeded, so a command still running or failed satisfies nothing.
+pub fn actual_state(
+    condition: &str,
+    field: Option<&str>,
+    status: &str,
+    metadata: &Value,
+    last_command: Option<(&str, &str)>,
+) -> Option<String> {
+    match condition {
+        "status" => Some(status.to_string()),
+        "last_command" => last_command.filter(|(_, status)| *status == "succeeded").map(|(c, _)| c.to_string()),
+        "metadata" => match metadata.get(field?)? {
+            Value::Null => None,
+            Value::String(value) => Some(value.clone()),
+            value => Some(value.to_string()),
+        },
+        _ => None,
+    }
+}
+
+/// An enabled constraint on a command, joined with the current state of its dependency
+#[derive(Debug, FromRow)]
+pub struct ConstraintState {
+    pub id: Uuid,
+    pub name: String,
+    pub command: String,
+    pub depends_on_device_id: Uuid,
+    pub condition: String,
+    pub field: Option<String>,
+    pub expected: String,
+    pub device_name: String,
+    pub status: String,
+    pub metadata: Value,
+    pub last_command: Option<String>,
+    pub last_command_status: Option<String>,
+}
+
+impl ConstraintState {
+    /// `None` when the constraint holds
+    pub fn violation(&self) -> Option<ConstraintViolation> {
+        let last_command = self.last_command.as_deref().zip(self.last_command_status.as_deref());
+        let actual = actual_state(&self.condition, self.field.as_deref(), &self.status, &self.metadata, last_command);
+        if actual.as_deref() == Some(self.expected.as_str()) {
+            return None;
+        }
+        Some(ConstraintViolation {
+            constraint_id: self.id,
+            name: self.name.clone(),
+            command: self.command.clone(),
+            depends_on_device_id: self.depends_on_device_id,
+            depends_on_device_name: self.device_name.clone(),
+            condition: self.condition.clone(),
+            field: self.field.clone(),
+            expected: self.expected.clone(),
+            actual,
+        })
+    }
+}
+
+/// Enabled constraints guarding any of `commands` on the device
+pub async fn constraint_states(pool: &PgPool, device_id: Uuid, commands: &[&str]) -> ApiResult<Vec<ConstraintState>> {
+    Ok(sqlx::query_as::<_, ConstraintState>(
+        "SELECT c.id, c.name, c.command, c.depends_on_device_id, c.condition, c.field, c.expected, \
+         d.device_name, d.status, d.metadata, last.command AS last_command, last.status AS last_command_status \
+         FROM command_constraints c \
+         JOIN devices d ON d.id = c.depends_on_device_id \
+         LEFT JOIN LATERAL (SELECT command, status FROM device_commands \
+             WHERE device_id = d.id ORDER BY created_at DESC LIMIT 1) last ON TRUE \
+         WHERE c.device_id = $1 AND c.command = ANY($2) AND c.enabled \
+         ORDER BY c.name",
+    )
+    .bind(device_id)
+    .bind(commands)
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Refuse `commands` on the device while a constraint on them does not hold. With
+/// `override_constraints` (admins only, checked by the caller) the violations are returned
+/// instead, for the caller to record once the command is issued.
+pub async fn enforce(
+    pool: &PgPool,
+    device_id: Uuid,
+    commands: &[&str],
+    override_constraints: bool,
+) -> ApiResult<Vec<ConstraintViolation>> {
+    let violations: Vec<ConstraintViolation> =
+        constraint_states(pool, device_id, commands).await?.iter().filter_map(ConstraintState::violation).collect();
+    if !violations.is_empty() && !override_constraints {
+        return Err(ApiError::ConstraintBlocked(violations));
+    }
+    Ok(violations)
+}
+
+/// Audit an admin dispatching commands past the constraints that would have blocked them
+pub async fn record_override(
+    pool: &PgPool,
+    actor_id: Uuid,
+    device_id: Uuid,
+    command_ids: &[Uuid],
+    violations: &[ConstraintViolation],
+) -> ApiResult<()> {
+    let mut conn = pool.acquire().await?;
+    audit_services::record(
+        &mut conn,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(actor_id),
+            action: "command.constraints_overridden",
+            resource_type: "device",
+            resource_id: Some(device_id.to_string()),
+            details: serde_json::json!({ "command_ids": command_ids, "violations": violations }),
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_condition() {
+        assert!(validate_condition("status", None, "online").is_ok());
+        assert!(validate_condition("last_command", None, "land").is_ok());
+        assert!(validate_condition("metadata", Some("dock_state"), "docked").is_ok());
+
+        assert!(validate_condition("metadata", None, "docked").is_err());
+        assert!(validate_condition("status", Some("dock_state"), "online").is_err());
+        assert!(validate_condition("battery", None, "full").is_err());
+        assert!(validate_condition("status", None, "").is_err());
+    }
+
+    #[test]
+    fn test_actual_state() {
+        let metadata = serde_json::json!({ "dock_state": "docked", "bay": 3, "cleared": null });
+        assert_eq!(actual_state("status", None, "online", &metadata, None).as_deref(), Some("online"));
+        assert_eq!(actual_state("metadata", Some("dock_state"), "online", &metadata, None).as_deref(), Some("docked"));
+        assert_eq!(actual_state("metadata", Some("bay"), "online", &metadata, None).as_deref(), Some("3"));
+        assert_eq!(actual_state("metadata", Some("cleared"), "online", &metadata, None), None);
+        assert_eq!(actual_state("metadata", Some("missing"), "online", &metadata, None), None);
+
+        // Only a completed last command counts
+        let landed = Some(("land", "succeeded"));
+        assert_eq!(actual_state("last_command", None, "online", &metadata, landed).as_deref(), Some("land"));
+        assert_eq!(actual_state("last_command", None, "online", &metadata, Some(("land", "sent"))), None);
+        assert_eq!(actual_state("last_command", None, "online", &metadata, Some(("land", "failed"))), None);
+        assert_eq!(actual_state("last_command", None, "online", &metadata, None), None);
+    }
+
+    #[test]
+    fn test_violation_names_the_constraint() {
+        let state = ConstraintState {
+            id: Uuid::new_v4(),
+            name: "rover docked before takeoff".to_string(),
+            command: "takeoff".to_string(),
+            depends_on_device_id: Uuid::new_v4(),
+            condition: "metadata".to_string(),
+            field: Some("dock_state".to_string()),
+            expected: "docked".to_string(),
+            device_name: "rover-b".to_string(),
+            status: "online".to_string(),
+            metadata: serde_json::json!({ "dock_state": "deployed" }),
+            last_command: None,
+            last_command_status: None,
+        };
+        let violation = state.violation().unwrap();
+        assert_eq!(violation.actual.as_deref(), Some("deployed"));
+        let message = ApiError::ConstraintBlocked(vec![violation]).to_string();
+        assert!(message.contains("'rover docked before takeoff'"), "{}", message);
+        assert!(message.contains("rover-b metadata.dock_state = docked, is deployed"), "{}", message);
+
+        let docked = ConstraintState { metadata: serde_json::json!({ "dock_state": "docked" }), ..state };
+        assert!(docked.violation().is_none());
+    }
+}
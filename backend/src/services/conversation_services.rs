@@ -0,0 +1,301 @@
+//! Persisted AI conversations. A chat request naming a conversation is answered with as much of
+//! its history as fits the context budget, newest messages kept first; one naming none starts a
+//! new conversation. The user's messages and the reply are saved once the model has answered,
+//! so a failed request leaves the conversation as it was.
+
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::conversation::{Conversation, ConversationDetails, ConversationMessage};
+use crate::services::ai_routing_services::{estimate_prompt_tokens, routed_chat};
+use crate::services::ai_services::{AIService, ChatMessage, ChatRequest, ChatResponse};
+use crate::services::moderation_services;
+
+const CONVERSATION_COLUMNS: &str = "c.id, c.user_id, c.title, c.model, c.system_prompt, \
+     (SELECT COUNT(*) FROM messages m WHERE m.conversation_id = c.id) AS message_count, c.created_at, c.updated_at";
+const MESSAGE_COLUMNS: &str =
+    "id, conversation_id, role, content, model, prompt_tokens, completion_tokens, created_at";
+
+/// Estimated prompt tokens of history sent along with a request
+const HISTORY_TOKEN_BUDGET: u32 = 4_000;
+/// Messages read back when assembling the context
+const MAX_HISTORY_MESSAGES: i64 = 100;
+const MAX_TITLE_CHARS: usize = 80;
+const ROLES: &[&str] = &["system", "user", "assistant"];
+
+/// A title from the first user message: its first line, cut at a word where possible
+pub fn title_for(messages: &[ChatMessage]) -> String {
+    let first = messages.iter().find(|m| m.role == "user").map_or("", |m| m.content.trim());
+    let line = first.lines().next().unwrap_or("").split_whitespace().collect::<Vec<_>>().join(" ");
+    if line.is_empty() {
+        return "New conversation".to_string();
+    }
+    if line.chars().count() <= MAX_TITLE_CHARS {
+        return line;
+    }
+    let cut: String = line.chars().take(MAX_TITLE_CHARS - 1).collect();
+    let cut = cut.rsplit_once(' ').map_or(cut.as_str(), |(head, _)| head);
+    format!("{}…", cut.trim_end())
+}
+
+/// The messages sent to the model: the request's system messages (or the conversation's saved
+/// prompt when it sends none), then the newest history that fits `budget`, then the request's
+/// other messages
+pub fn assemble(
+    system_prompt: Option<&str>,
+    history: &[ChatMessage],
+    request: &[ChatMessage],
+    budget: u32,
+) -> Vec<ChatMessage> {
+    let (system, turns): (Vec<&ChatMessage>, Vec<&ChatMessage>) = request.iter().partition(|m| m.role == "system");
+    let mut messages: Vec<ChatMessage> = system.into_iter().cloned().collect();
+    if let Some(prompt) = system_prompt.filter(|_| messages.is_empty()) {
+        messages.push(ChatMessage { role: "system".to_string(), content: prompt.to_string() });
+    }
+
+    let mut kept = 0;
+    let mut used = 0u32;
+    for message in history.iter().rev() {
+        used = used.saturating_add(estimate_prompt_tokens(std::slice::from_ref(message)));
+        if used > budget {
+            break;
+        }
+        kept += 1;
+    }
+    messages.extend(history[history.len() - kept..].iter().cloned());
+    messages.extend(turns.into_iter().cloned());
+    messages
+}
+
+fn validate(request: &ChatRequest) -> ApiResult<()> {
+    if let Some(message) = request.messages.iter().find(|m| !ROLES.contains(&m.role.as_str())) {
+        return Err(ApiError::ValidationError(format!(
+            "Unknown message role {}; expected one of {}",
+            message.role,
+            ROLES.join(", ")
+        )));
+    }
+    if !request.messages.iter().any(|m| m.role != "system") {
+        return Err(ApiError::ValidationError("messages must include a user message".to_string()));
+    }
+    Ok(())
+}
+
+/// The user's conversation
+pub async fn owned(pool: &PgPool, user_id: Uuid, conversation_id: Uuid) -> ApiResult<Conversation> {
+    sqlx::query_as::<_, Conversation>(&format!(
+        "SELECT {} FROM conversations c WHERE c.id = $1 AND c.user_id = $2",
+        CONVERSATION_COLUMNS
+    ))
+    .bind(conversation_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Conversation not found".to_string()))
+}
+
+/// Answer a chat request within a conversation, starting one when the request names none, and
+/// save the exchange. Routed and charged against the user's AI budget like any chat request.
+pub async fn chat(
+    pool: &PgPool,
+    config: &AppConfig,
+    ai: &AIService,
+    user_id: Uuid,
+    mut request: ChatRequest,
+) -> ApiResult<ChatResponse> {
+    validate(&request)?;
+    // Before anything is saved, so redactions are what the history keeps
+    moderation_services::screen_input(config, ai, &mut request.messages).await?;
+    let conversation = match request.conversation_id {
+        Some(id) => Some(owned(pool, user_id, id).await?),
+        None => None,
+    };
+    let history: Vec<ChatMessage> = match &conversation {
+        Some(conversation) => {
+            let mut rows: Vec<(String, String)> = sqlx::query_as(
+                "SELECT role, content FROM messages WHERE conversation_id = $1 ORDER BY id DESC LIMIT $2",
+            )
+            .bind(conversation.id)
+            .bind(MAX_HISTORY_MESSAGES)
+            .fetch_all(pool)
+            .await?;
+            rows.reverse();
+            rows.into_iter().map(|(role, content)| ChatMessage { role, content }).collect()
+        }
+        None => Vec::new(),
+    };
+
+    let sent = std::mem::take(&mut request.messages);
+    let system_prompt = conversation.as_ref().and_then(|c| c.system_prompt.as_deref());
+    request.messages = assemble(system_prompt, &history, &sent, HISTORY_TOKEN_BUDGET);
+    let mut response = routed_chat(pool, config, ai, user_id, request).await?;
+    moderation_services::screen_output(config, ai, &mut response.message).await?;
+
+    let mut tx = pool.begin().await?;
+    let new_prompt = sent.iter().rev().find(|m| m.role == "system").map(|m| m.content.as_str());
+    let conversation_id = match &conversation {
+        Some(conversation) => {
+            let updated = sqlx::query(
+                "UPDATE conversations SET model = $2, system_prompt = COALESCE($3, system_prompt), \
+                 updated_at = NOW() WHERE id = $1",
+            )
+            .bind(conversation.id)
+            .bind(&response.model)
+            .bind(new_prompt)
+            .execute(&mut *tx)
+            .await?;
+            // Deleted while the model was answering
+            if updated.rows_affected() == 0 {
+                return Err(ApiError::NotFound("Conversation not found".to_string()));
+            }
+            conversation.id
+        }
+        None => {
+            let id = Uuid::new_v4();
+            sqlx::query(
+                "INSERT INTO conversations (id, user_id, title, model, system_prompt) VALUES ($1, $2, $3, $4, $5)",
+            )
+            .bind(id)
+            .bind(user_id)
+            .bind(title_for(&sent))
+            .bind(&response.model)
+            .bind(new_prompt)
+            .execute(&mut *tx)
+            .await?;
+            id
+        }
+    };
+
+    for message in sent.iter().filter(|m| m.role != "system") {
+        sqlx::query("INSERT INTO messages (conversation_id, role, content) VALUES ($1, $2, $3)")
+            .bind(conversation_id)
+            .bind(&message.role)
+            .bind(&message.content)
+            .execute(&mut *tx)
+            .await?;
+    }
+    let usage = response.usage.as_ref();
+    sqlx::query(
+        "INSERT INTO messages (conversation_id, role, content, model, prompt_tokens, completion_tokens) \
+         VALUES ($1, 'assistant', $2, $3, $4, $5)",
+    )
+    .bind(conversation_id)
+    .bind(&response.message)
+    .bind(&response.model)
+    .bind(usage.map(|u| u.prompt_tokens as i32))
+    .bind(usage.map(|u| u.completion_tokens as i32))
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    response.conversation_id = Some(conversation_id);
+    Ok(response)
+}
+
+/// An answer from one of the user's conversations
+pub async fn assistant_message(pool: &PgPool, user_id: Uuid, message_id: i64) -> ApiResult<String> {
+    sqlx::query_scalar(
+        "SELECT m.content FROM messages m JOIN conversations c ON c.id = m.conversation_id \
+         WHERE m.id = $1 AND c.user_id = $2 AND m.role = 'assistant'",
+    )
+    .bind(message_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Message not found".to_string()))
+}
+
+/// The user's conversations, most recently active first
+pub async fn list(pool: &PgPool, user_id: Uuid) -> ApiResult<Vec<Conversation>> {
+    let conversations = sqlx::query_as::<_, Conversation>(&format!(
+        "SELECT {} FROM conversations c WHERE c.user_id = $1 ORDER BY c.updated_at DESC LIMIT 100",
+        CONVERSATION_COLUMNS
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(conversations)
+}
+
+pub async fn details(pool: &PgPool, user_id: Uuid, conversation_id: Uuid) -> ApiResult<ConversationDetails> {
+    let conversation = owned(pool, user_id, conversation_id).await?;
+    let messages = sqlx::query_as::<_, ConversationMessage>(&format!(
+        "SELECT {} FROM messages WHERE conversation_id = $1 ORDER BY id",
+        MESSAGE_COLUMNS
+    ))
+    .bind(conversation.id)
+    .fetch_all(pool)
+    .await?;
+    Ok(ConversationDetails { conversation, messages })
+}
+
+/// Delete a conversation with its messages
+pub async fn delete(pool: &PgPool, user_id: Uuid, conversation_id: Uuid) -> ApiResult<()> {
+    let deleted = sqlx::query("DELETE FROM conversations WHERE id = $1 AND user_id = $2")
+        .bind(conversation_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Conversation not found".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: &str) -> ChatMessage {
+        ChatMessage { role: role.to_string(), content: content.to_string() }
+    }
+
+    #[test]
+    fn test_title_for() {
+        let messages = [message("system", "Be brief"), message("user", "  How do  servos work?\nThanks")];
+        assert_eq!(title_for(&messages), "How do servos work?");
+        assert_eq!(title_for(&[message("system", "Be brief")]), "New conversation");
+
+        let long = title_for(&[message("user", &"word ".repeat(40))]);
+        assert!(long.chars().count() <= MAX_TITLE_CHARS);
+        assert!(long.ends_with("word…"));
+    }
+
+    #[test]
+    fn test_assemble() {
+        let history = vec![message("user", "first"), message("assistant", "one"), message("user", "second")];
+        let request = vec![message("user", "third")];
+
+        let all = assemble(Some("Be brief"), &history, &request, HISTORY_TOKEN_BUDGET);
+        let contents: Vec<&str> = all.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["Be brief", "first", "one", "second", "third"]);
+
+        // A system message in the request replaces the saved prompt
+        let request = vec![message("system", "Be thorough"), message("user", "third")];
+        assert_eq!(assemble(Some("Be brief"), &history, &request, HISTORY_TOKEN_BUDGET)[0].content, "Be thorough");
+
+        // Only the newest history that fits; each of these messages is estimated at 10 tokens
+        let trimmed = assemble(None, &history, &request[1..], 20);
+        let contents: Vec<&str> = trimmed.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["one", "second", "third"]);
+        assert_eq!(assemble(None, &history, &request[1..], 0).len(), 1);
+    }
+
+    #[test]
+    fn test_validate() {
+        let request = |messages: Vec<ChatMessage>| ChatRequest {
+            messages,
+            model: None,
+            provider: None,
+            temperature: None,
+            max_tokens: None,
+            conversation_id: None,
+            images: Vec::new(),
+            json: false,
+        };
+        assert!(validate(&request(vec![message("user", "hi")])).is_ok());
+        assert!(validate(&request(vec![message("system", "Be brief")])).is_err());
+        assert!(validate(&request(vec![message("tool", "{}")])).is_err());
+    }
+}
@@ -0,0 +1,316 @@
+//! Coupon codes. A coupon takes a percentage or a fixed amount off a product's price, at most
+//! the whole price. It may be limited to some products, to a number of payments in total and
+//! per user, and may expire. Each payment a coupon is applied to is kept as a redemption; one
+//! whose payment failed no longer counts towards the limits.
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::coupon::{Coupon, CouponQuote, CreateCouponRequest, UpdateCouponRequest};
+use crate::services::payment_services::{from_minor_units, minor_units, PRODUCT_TYPES};
+
+const COUPON_COLUMNS: &str = "c.id, c.code, c.description, c.kind, c.value, c.currency, c.product_types, \
+     c.max_redemptions, c.max_per_user, c.expires_at, c.active, \
+     (SELECT COUNT(*) FROM coupon_redemptions r JOIN transactions t ON t.id = r.transaction_id \
+      WHERE r.coupon_id = c.id AND t.status <> 'failed') AS redemptions, \
+     c.created_by, c.created_at, c.updated_at";
+
+pub const KINDS: &[&str] = &["percentage", "fixed"];
+/// Payment method of a payment a coupon covered in full
+pub const FREE_PAYMENT_METHOD: &str = "coupon";
+const MAX_CODE_CHARS: usize = 40;
+const MAX_DESCRIPTION_CHARS: usize = 500;
+
+/// The code as stored: trimmed and upper-cased
+pub fn normalize_code(code: &str) -> ApiResult<String> {
+    let code = code.trim().to_uppercase();
+    let valid_chars = code.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if code.len() < 3 || code.len() > MAX_CODE_CHARS || !valid_chars {
+        return Err(ApiError::ValidationError(format!(
+            "code must be 3 to {} letters, digits, dashes or underscores",
+            MAX_CODE_CHARS
+        )));
+    }
+    Ok(code)
+}
+
+fn validate_limits(max_redemptions: Option<i32>, max_per_user: Option<i32>) -> ApiResult<()> {
+    if max_redemptions.is_some_and(|n| n <= 0) || max_per_user.is_some_and(|n| n <= 0) {
+        return Err(ApiError::ValidationError("Redemption limits must be positive".to_string()));
+    }
+    Ok(())
+}
+
+fn validate_description(description: Option<&str>) -> ApiResult<()> {
+    if description.is_some_and(|d| d.chars().count() > MAX_DESCRIPTION_CHARS) {
+        return Err(ApiError::ValidationError(format!(
+            "description must be at most {} characters",
+            MAX_DESCRIPTION_CHARS
+        )));
+    }
+    Ok(())
+}
+
+/// Check a new coupon's terms
+pub fn validate(request: &CreateCouponRequest) -> ApiResult<()> {
+    if !KINDS.contains(&request.kind.as_str()) {
+        return Err(ApiError::ValidationError(format!("kind must be one of {}", KINDS.join(", "))));
+    }
+    if request.value <= Decimal::ZERO {
+        return Err(ApiError::ValidationError("value must be positive".to_string()));
+    }
+    if request.kind == "percentage" && request.value > Decimal::ONE_HUNDRED {
+        return Err(ApiError::ValidationError("A percentage coupon takes at most 100% off".to_string()));
+    }
+    if request.kind == "fixed" && request.currency.as_deref().is_none_or(|c| c.trim().is_empty()) {
+        return Err(ApiError::ValidationError("currency is required for fixed coupons".to_string()));
+    }
+    let unknown = request.product_types.iter().flatten().find(|t| !PRODUCT_TYPES.contains(&t.as_str()));
+    if let Some(product_type) = unknown {
+        return Err(ApiError::ValidationError(format!("Unknown product type {}", product_type)));
+    }
+    validate_limits(request.max_redemptions, request.max_per_user)?;
+    validate_description(request.description.as_deref())
+}
+
+/// What `coupon` takes off `price` in `currency`, in whole minor units and at most the price;
+/// `None` when a fixed coupon is in another currency
+pub fn discount(coupon: &Coupon, price: Decimal, currency: &str) -> Option<Decimal> {
+    let discount = match coupon.kind.as_str() {
+        "percentage" => price * coupon.value / Decimal::ONE_HUNDRED,
+        _ if coupon.currency.as_deref().is_some_and(|c| c.eq_ignore_ascii_case(currency)) => coupon.value,
+        _ => return None,
+    };
+    Some(from_minor_units(minor_units(discount, currency), currency).min(price))
+}
+
+/// Refuse a coupon that is off, expired, used up or not for `product_type`
+pub fn check_usable(coupon: &Coupon, product_type: &str, now: DateTime<Utc>) -> ApiResult<()> {
+    if !coupon.active || coupon.expires_at.is_some_and(|at| at <= now) {
+        return Err(ApiError::Conflict("This coupon is no longer valid".to_string()));
+    }
+    if coupon.product_types.as_ref().is_some_and(|types| !types.iter().any(|t| t == product_type)) {
+        return Err(ApiError::ValidationError("This coupon does not apply to this product".to_string()));
+    }
+    if coupon.max_redemptions.is_some_and(|max| coupon.redemptions >= i64::from(max)) {
+        return Err(ApiError::Conflict("This coupon has been used up".to_string()));
+    }
+    Ok(())
+}
+
+/// What the coupon `code` takes off `price` for the user. The coupon stays locked until the
+/// caller's transaction ends, so payments made at the same time cannot go over its limits.
+pub async fn quote(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    code: &str,
+    product_type: &str,
+    price: Decimal,
+    currency: &str,
+) -> ApiResult<CouponQuote> {
+    let not_found = || ApiError::NotFound("Coupon not found".to_string());
+    let code = normalize_code(code).map_err(|_| not_found())?;
+    let coupon = sqlx::query_as::<_, Coupon>(&format!(
+        "SELECT {} FROM coupons c WHERE c.code = $1 FOR UPDATE",
+        COUPON_COLUMNS
+    ))
+    .bind(&code)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(not_found)?;
+    check_usable(&coupon, product_type, Utc::now())?;
+    if let Some(max) = coupon.max_per_user {
+        let used: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM coupon_redemptions r JOIN transactions t ON t.id = r.transaction_id \
+             WHERE r.coupon_id = $1 AND r.user_id = $2 AND t.status <> 'failed'",
+        )
+        .bind(coupon.id)
+        .bind(user_id)
+        .fetch_one(&mut *conn)
+        .await?;
+        if used >= i64::from(max) {
+            return Err(ApiError::Conflict("You have already used this coupon".to_string()));
+        }
+    }
+    let discount = discount(&coupon, price, currency).ok_or_else(|| {
+        ApiError::ValidationError(format!("This coupon cannot be used for payments in {}", currency.to_uppercase()))
+    })?;
+    Ok(CouponQuote {
+        coupon_id: coupon.id,
+        code: coupon.code,
+        price,
+        discount,
+        total: price - discount,
+        currency: currency.to_string(),
+    })
+}
+
+/// Count `quote` against the payment it was applied to
+pub async fn redeem(
+    conn: &mut PgConnection,
+    quote: &CouponQuote,
+    user_id: Uuid,
+    transaction_id: Uuid,
+) -> ApiResult<()> {
+    sqlx::query(
+        "INSERT INTO coupon_redemptions (coupon_id, user_id, transaction_id, discount) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(quote.coupon_id)
+    .bind(user_id)
+    .bind(transaction_id)
+    .bind(quote.discount)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+pub async fn create(pool: &PgPool, admin_id: Uuid, request: &CreateCouponRequest) -> ApiResult<Coupon> {
+    validate(request)?;
+    let code = normalize_code(&request.code)?;
+    let description = request.description.as_deref().map(str::trim).filter(|d| !d.is_empty());
+    let currency = request.currency.as_deref().map(|c| c.trim().to_lowercase()).filter(|c| !c.is_empty());
+    sqlx::query_as::<_, Coupon>(&format!(
+        "INSERT INTO coupons AS c (id, code, description, kind, value, currency, product_types, max_redemptions, \
+             max_per_user, expires_at, created_by) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) ON CONFLICT (code) DO NOTHING RETURNING {}",
+        COUPON_COLUMNS
+    ))
+    .bind(Uuid::new_v4())
+    .bind(&code)
+    .bind(description)
+    .bind(&request.kind)
+    .bind(request.value)
+    .bind(currency)
+    .bind(&request.product_types)
+    .bind(request.max_redemptions)
+    .bind(request.max_per_user)
+    .bind(request.expires_at)
+    .bind(admin_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::Conflict(format!("A coupon with code {} already exists", code)))
+}
+
+pub async fn update(pool: &PgPool, coupon_id: Uuid, request: &UpdateCouponRequest) -> ApiResult<Coupon> {
+    validate_limits(request.max_redemptions, request.max_per_user)?;
+    validate_description(request.description.as_deref())?;
+    sqlx::query_as::<_, Coupon>(&format!(
+        "UPDATE coupons c SET active = COALESCE($2, active), description = COALESCE($3, description), \
+             max_redemptions = COALESCE($4, max_redemptions), max_per_user = COALESCE($5, max_per_user), \
+             expires_at = COALESCE($6, expires_at), updated_at = NOW() \
+         WHERE c.id = $1 RETURNING {}",
+        COUPON_COLUMNS
+    ))
+    .bind(coupon_id)
+    .bind(request.active)
+    .bind(request.description.as_deref().map(str::trim))
+    .bind(request.max_redemptions)
+    .bind(request.max_per_user)
+    .bind(request.expires_at)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Coupon not found".to_string()))
+}
+
+/// Coupons, newest first
+pub async fn list(pool: &PgPool, active: Option<bool>) -> ApiResult<Vec<Coupon>> {
+    let coupons = sqlx::query_as::<_, Coupon>(&format!(
+        "SELECT {} FROM coupons c WHERE ($1::BOOLEAN IS NULL OR c.active = $1) ORDER BY c.created_at DESC LIMIT 200",
+        COUPON_COLUMNS
+    ))
+    .bind(active)
+    .fetch_all(pool)
+    .await?;
+    Ok(coupons)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn coupon(kind: &str, value: Decimal, currency: Option<&str>) -> Coupon {
+        Coupon {
+            id: Uuid::nil(),
+            code: "SPRING".to_string(),
+            description: None,
+            kind: kind.to_string(),
+            value,
+            currency: currency.map(str::to_string),
+            product_types: None,
+            max_redemptions: None,
+            max_per_user: None,
+            expires_at: None,
+            active: true,
+            redemptions: 0,
+            created_by: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_normalize_code() {
+        assert_eq!(normalize_code(" spring-25 ").unwrap(), "SPRING-25");
+        assert!(normalize_code("ab").is_err());
+        assert!(normalize_code("no spaces").is_err());
+    }
+
+    #[test]
+    fn test_discount() {
+        let price = Decimal::new(16, 1);
+        // 15% of 1.60 is 0.24
+        let percent = coupon("percentage", Decimal::from(15), None);
+        assert_eq!(discount(&percent, price, "usd"), Some(Decimal::new(24, 2)));
+        // Rounded to the cent: 33% of 1.60 is 0.528
+        let third = coupon("percentage", Decimal::from(33), None);
+        assert_eq!(discount(&third, price, "usd"), Some(Decimal::new(53, 2)));
+        // No more than the price
+        let fixed = coupon("fixed", Decimal::from(5), Some("usd"));
+        assert_eq!(discount(&fixed, price, "USD"), Some(price));
+        assert_eq!(discount(&fixed, price, "eur"), None);
+    }
+
+    #[test]
+    fn test_check_usable() {
+        let now = Utc::now();
+        let mut c = coupon("percentage", Decimal::TEN, None);
+        assert!(check_usable(&c, "documentation", now).is_ok());
+
+        c.product_types = Some(vec!["software_license".to_string()]);
+        assert!(matches!(check_usable(&c, "documentation", now), Err(ApiError::ValidationError(_))));
+        c.product_types = None;
+
+        c.max_redemptions = Some(3);
+        c.redemptions = 3;
+        assert!(matches!(check_usable(&c, "documentation", now), Err(ApiError::Conflict(_))));
+        c.redemptions = 2;
+        assert!(check_usable(&c, "documentation", now).is_ok());
+
+        c.expires_at = Some(now - Duration::minutes(1));
+        assert!(check_usable(&c, "documentation", now).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let request = |kind: &str, value: Decimal, currency: Option<&str>| CreateCouponRequest {
+            code: "SPRING".to_string(),
+            description: None,
+            kind: kind.to_string(),
+            value,
+            currency: currency.map(str::to_string),
+            product_types: None,
+            max_redemptions: None,
+            max_per_user: None,
+            expires_at: None,
+        };
+        assert!(validate(&request("percentage", Decimal::from(100), None)).is_ok());
+        assert!(validate(&request("percentage", Decimal::from(101), None)).is_err());
+        assert!(validate(&request("fixed", Decimal::from(5), None)).is_err());
+        assert!(validate(&request("fixed", Decimal::from(5), Some("usd"))).is_ok());
+        assert!(validate(&request("fixed", Decimal::ZERO, Some("usd"))).is_err());
+        assert!(validate(&request("bogo", Decimal::ONE, None)).is_err());
+    }
+}
@@ -1,9 +1,54 @@
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
+use sha3::Keccak256;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use crate::config::chains::{ChainConfig, ETHEREUM_CHAIN_ID};
+use crate::config::AppConfig;
 use crate::errors::{ApiError, ApiResult};
 
-/// Blockchain/Crypto service for handling Web3 operations
+static RPC_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(15))
+        .build()
+        .expect("Failed to build JSON-RPC HTTP client")
+});
+
+/// How long a looked-up token balance is served from memory
+const BALANCE_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// (chain, contract, holder), addresses lower-case; the native coin's contract is empty
+type BalanceKey = (u64, String, String);
+/// Raw balances with when they were read
+static BALANCE_CACHE: LazyLock<Mutex<HashMap<BalanceKey, (Instant, String)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+/// (chain, contract), the contract lower-case
+type TokenKey = (u64, String);
+/// Decimals and symbol by token; a deployed token's never change
+static TOKEN_METADATA: LazyLock<Mutex<HashMap<TokenKey, (u8, String)>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// ERC-20 function selectors: the first 4 bytes of keccak256 of the signature
+const BALANCE_OF_SELECTOR: &str = "70a08231"; // balanceOf(address)
+const DECIMALS_SELECTOR: &str = "313ce567"; // decimals()
+const SYMBOL_SELECTOR: &str = "95d89b41"; // symbol()
+
+/// Symbol reported for tokens whose contract does not implement the optional `symbol()`
+const FALLBACK_SYMBOL: &str = "RBV";
+
+/// keccak256("Transfer(address,address,uint256)"), the first topic of ERC-20 and ERC-721
+/// transfer logs
+pub const TRANSFER_TOPIC: &str = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+
+/// Native coins of all supported chains use 18 decimals
+const NATIVE_DECIMALS: u8 = 18;
+
+/// Blockchain/Crypto service for handling Web3 operations on one EVM chain
 pub struct BlockchainService {
+    chain_id: u64,
+    native_symbol: String,
     provider_url: String,
     contract_address: Option<String>,
 }
@@ -11,33 +56,135 @@ pub struct BlockchainService {
 impl BlockchainService {
     pub fn new() -> Self {
         Self {
+            chain_id: ETHEREUM_CHAIN_ID,
+            native_symbol: "ETH".to_string(),
             provider_url: std::env::var("WEB3_PROVIDER_URL")
                 .unwrap_or_else(|_| "https://mainnet.infura.io/v3/YOUR_KEY".to_string()),
             contract_address: std::env::var("CONTRACT_ADDRESS").ok(),
         }
     }
 
+    pub fn for_chain(chain: &ChainConfig) -> Self {
+        Self {
+            chain_id: chain.chain_id,
+            native_symbol: chain.native_symbol.clone(),
+            provider_url: chain.rpc_url.clone().unwrap_or_default(),
+            contract_address: chain.token_contract.clone(),
+        }
+    }
+
+    /// The service for the configured default chain
+    pub fn from_config(config: &AppConfig) -> Self {
+        match config.chain(config.default_chain_id) {
+            Some(chain) => Self::for_chain(chain),
+            None => Self {
+                chain_id: config.default_chain_id,
+                native_symbol: "ETH".to_string(),
+                provider_url: config.web3_provider_url.clone(),
+                contract_address: Some(config.contract_address.clone()).filter(|c| !c.is_empty()),
+            },
+        }
+    }
+
+    /// The service for a chain of the registry, or a validation error naming the chains supported
+    pub fn for_chain_id(config: &AppConfig, chain_id: u64) -> ApiResult<Self> {
+        config.chain(chain_id).map(Self::for_chain).ok_or_else(|| {
+            let supported: Vec<String> = config.chains.iter().map(|c| c.chain_id.to_string()).collect();
+            ApiError::ValidationError(format!(
+                "Unsupported chain id {}; supported chains are {}",
+                chain_id,
+                supported.join(", ")
+            ))
+        })
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    pub fn native_symbol(&self) -> &str {
+        &self.native_symbol
+    }
+
     /// Check if blockchain service is configured
     pub fn is_configured(&self) -> bool {
-        !self.provider_url.contains("YOUR_KEY") && self.contract_address.is_some()
+        self.has_provider() && self.contract_address.is_some()
+    }
+
+    /// Whether a JSON-RPC provider is set, which is all reading chain state needs
+    pub fn has_provider(&self) -> bool {
+        !self.provider_url.is_empty() && !self.provider_url.contains("YOUR_KEY")
     }
 
-    /// Verify wallet signature (EIP-191)
+    /// Verify wallet signature (EIP-191): recover the signer of a `personal_sign` signature and
+    /// compare it with the claimed address, ignoring checksum case
     pub fn verify_signature(&self, message: &str, signature: &str, address: &str) -> ApiResult<bool> {
-        // In production, use ethers-rs or web3 crate for proper verification
-        // This is a simplified placeholder
-        
         if signature.len() != 132 || !signature.starts_with("0x") {
             return Err(ApiError::ValidationError("Invalid signature format".to_string()));
         }
-        
+
         if !Self::is_valid_eth_address(address) {
             return Err(ApiError::ValidationError("Invalid Ethereum address".to_string()));
         }
 
-        // Placeholder: In production, implement proper ECDSA recovery
-        log::info!("Verifying signature for address: {}", address);
-        Ok(true)
+        let bytes = hex::decode(&signature[2..])
+            .map_err(|_| ApiError::ValidationError("Invalid signature format".to_string()))?;
+        let recovered = Self::recover_address(&Self::eip191_hash(message), &bytes)?;
+        Ok(recovered.eq_ignore_ascii_case(address))
+    }
+
+    /// Digest signed by `personal_sign`: keccak256("\x19Ethereum Signed Message:\n" + len + message)
+    pub fn eip191_hash(message: &str) -> [u8; 32] {
+        let mut hasher = Keccak256::new();
+        hasher.update(format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes());
+        hasher.update(message.as_bytes());
+        hasher.finalize().into()
+    }
+
+    /// Address (lower-case hex) whose key produced a 65-byte `r || s || v` signature over `digest`.
+    /// `v` may be 27/28 or 0/1; high-S signatures are normalized as the `ecrecover` precompile allows them.
+    pub fn recover_address(digest: &[u8; 32], signature: &[u8]) -> ApiResult<String> {
+        let invalid = || ApiError::ValidationError("Invalid signature".to_string());
+        if signature.len() != 65 {
+            return Err(invalid());
+        }
+        let v = match signature[64] {
+            v @ (27 | 28) => v - 27,
+            v @ (0 | 1) => v,
+            _ => return Err(invalid()),
+        };
+        let mut sig = Signature::from_slice(&signature[..64]).map_err(|_| invalid())?;
+        let mut recovery_id = RecoveryId::from_byte(v).ok_or_else(invalid)?;
+        if let Some(normalized) = sig.normalize_s() {
+            sig = normalized;
+            recovery_id = RecoveryId::new(!recovery_id.is_y_odd(), recovery_id.is_x_reduced());
+        }
+
+        let key = VerifyingKey::recover_from_prehash(digest, &sig, recovery_id).map_err(|_| invalid())?;
+        Ok(Self::address_of(&key))
+    }
+
+    /// Ethereum address of a public key: the last 20 bytes of keccak256 of the uncompressed point
+    pub fn address_of(key: &VerifyingKey) -> String {
+        let point = key.to_encoded_point(false);
+        let hash = Keccak256::digest(&point.as_bytes()[1..]);
+        format!("0x{}", hex::encode(&hash[12..]))
+    }
+
+    /// EIP-55 mixed-case checksum form of a hex address: a letter is upper-cased when the
+    /// matching nibble of keccak256(lower-case hex) is 8 or more
+    pub fn to_checksum_address(address: &str) -> String {
+        let lower = address.trim_start_matches("0x").to_ascii_lowercase();
+        let hash = Keccak256::digest(lower.as_bytes());
+        let checksummed: String = lower
+            .chars()
+            .enumerate()
+            .map(|(i, c)| {
+                let nibble = (hash[i / 2] >> if i % 2 == 0 { 4 } else { 0 }) & 0x0f;
+                if nibble >= 8 { c.to_ascii_uppercase() } else { c }
+            })
+            .collect();
+        format!("0x{}", checksummed)
     }
 
     /// Validate Ethereum address format
@@ -49,6 +196,21 @@ impl BlockchainService {
         hex_part.len() == 40 && hex_part.chars().all(|c| c.is_ascii_hexdigit())
     }
 
+    /// Reject malformed addresses and mixed-case ones whose EIP-55 checksum does not match,
+    /// which usually means a mistyped address. All-lower or all-upper case carries no checksum.
+    pub fn validate_address(address: &str) -> ApiResult<()> {
+        if !Self::is_valid_eth_address(address) {
+            return Err(ApiError::ValidationError("Invalid Ethereum address".to_string()));
+        }
+        let hex_part = &address[2..];
+        let mixed_case = hex_part.chars().any(|c| c.is_ascii_lowercase())
+            && hex_part.chars().any(|c| c.is_ascii_uppercase());
+        if mixed_case && Self::to_checksum_address(address) != address {
+            return Err(ApiError::ValidationError("Address checksum does not match".to_string()));
+        }
+        Ok(())
+    }
+
     /// Generate message for wallet signature
     pub fn generate_sign_message(nonce: &str) -> String {
         format!(
@@ -74,39 +236,382 @@ impl BlockchainService {
         format!("{:x}", hasher.finalize())
     }
 
-    /// Verify transaction on blockchain (placeholder)
+    /// Where a transaction stands on chain: not yet mined, reverted, or mined with how many
+    /// blocks confirm it
     pub async fn verify_transaction(&self, tx_hash: &str) -> ApiResult<TransactionStatus> {
-        if !tx_hash.starts_with("0x") || tx_hash.len() != 66 {
+        if !Self::is_valid_tx_hash(tx_hash) {
             return Err(ApiError::ValidationError("Invalid transaction hash format".to_string()));
         }
 
-        // In production, query the blockchain
-        log::info!("Verifying transaction: {}", tx_hash);
-        
+        let Some(receipt) = self.transaction_receipt(tx_hash).await? else {
+            return Ok(TransactionStatus {
+                hash: tx_hash.to_string(),
+                status: "pending".to_string(),
+                confirmations: 0,
+                block_number: None,
+            });
+        };
+        let head = self.block_number().await?;
         Ok(TransactionStatus {
             hash: tx_hash.to_string(),
-            status: "pending".to_string(),
-            confirmations: 0,
-            block_number: None,
+            status: if receipt.succeeded { "confirmed" } else { "reverted" }.to_string(),
+            confirmations: confirmations(head, receipt.block_number),
+            block_number: Some(receipt.block_number),
         })
     }
 
-    /// Get token balance for address (placeholder)
-    pub async fn get_token_balance(&self, address: &str) -> ApiResult<TokenBalance> {
-        if !Self::is_valid_eth_address(address) {
-            return Err(ApiError::ValidationError("Invalid Ethereum address".to_string()));
+    /// `0x` followed by 64 hex digits
+    pub fn is_valid_tx_hash(tx_hash: &str) -> bool {
+        tx_hash.len() == 66
+            && tx_hash.starts_with("0x")
+            && tx_hash[2..].chars().all(|c| c.is_ascii_hexdigit())
+    }
+
+    /// Number of the latest block
+    pub async fn block_number(&self) -> ApiResult<u64> {
+        let result = self.rpc_call("eth_blockNumber", serde_json::json!([])).await?;
+        parse_quantity(&result)
+            .ok_or_else(|| ApiError::BlockchainError("eth_blockNumber returned malformed data".to_string()))
+    }
+
+    /// The transaction's receipt, or `None` while it is not mined
+    pub async fn transaction_receipt(&self, tx_hash: &str) -> ApiResult<Option<TxReceipt>> {
+        let Some(receipt) = self.rpc_call_optional("eth_getTransactionReceipt", serde_json::json!([tx_hash])).await?
+        else {
+            return Ok(None);
+        };
+        // Pending blocks on some nodes report a receipt without a block number yet
+        let Some(block_number) = receipt.get("blockNumber").and_then(parse_quantity) else {
+            return Ok(None);
+        };
+        Ok(Some(TxReceipt {
+            block_number,
+            // Pre-Byzantium receipts carry no status; they are taken to have succeeded
+            succeeded: receipt.get("status").and_then(parse_quantity).is_none_or(|status| status == 1),
+        }))
+    }
+
+    /// Call a JSON-RPC method on the provider, returning its `result`
+    pub async fn rpc_call(&self, method: &str, params: serde_json::Value) -> ApiResult<serde_json::Value> {
+        self.rpc_call_optional(method, params)
+            .await?
+            .ok_or_else(|| ApiError::BlockchainError(format!("{} returned no result", method)))
+    }
+
+    /// Call a JSON-RPC method whose `result` may be `null`
+    pub async fn rpc_call_optional(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> ApiResult<Option<serde_json::Value>> {
+        let response = RPC_CLIENT
+            .post(&self.provider_url)
+            .json(&serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+            .send()
+            .await
+            .map_err(|e| ApiError::BlockchainError(format!("{} request failed: {}", method, e)))?;
+        if !response.status().is_success() {
+            return Err(ApiError::BlockchainError(format!("{} returned HTTP {}", method, response.status())));
+        }
+        let mut body: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| ApiError::BlockchainError(format!("Invalid {} response: {}", method, e)))?;
+        if let Some(error) = body.get("error") {
+            let message = error.get("message").and_then(|m| m.as_str()).unwrap_or("unknown error");
+            return Err(ApiError::BlockchainError(format!("{} failed: {}", method, message)));
+        }
+        Ok(body.get_mut("result").map(serde_json::Value::take).filter(|result| !result.is_null()))
+    }
+
+    /// `eth_call` against the latest block, returning the raw return data
+    pub async fn eth_call(&self, to: &str, data: &str) -> ApiResult<Vec<u8>> {
+        let result = self.rpc_call("eth_call", serde_json::json!([{ "to": to, "data": data }, "latest"])).await?;
+        result
+            .as_str()
+            .and_then(|r| r.strip_prefix("0x"))
+            .and_then(|r| hex::decode(r).ok())
+            .ok_or_else(|| ApiError::BlockchainError("eth_call returned malformed data".to_string()))
+    }
+
+    /// Logs emitted by `address` in blocks `from..=to` whose topics match `topics` (`null`
+    /// matches any), in chain order
+    pub async fn get_logs(&self, address: &str, topics: serde_json::Value, from: u64, to: u64) -> ApiResult<Vec<Log>> {
+        let result = self
+            .rpc_call(
+                "eth_getLogs",
+                serde_json::json!([{
+                    "address": address,
+                    "topics": topics,
+                    "fromBlock": format!("0x{:x}", from),
+                    "toBlock": format!("0x{:x}", to),
+                }]),
+            )
+            .await?;
+        let malformed = || ApiError::BlockchainError("eth_getLogs returned malformed data".to_string());
+        let entries = result.as_array().ok_or_else(malformed)?;
+        entries
+            .iter()
+            // Logs of a block being reorganized away are flagged removed
+            .filter(|entry| entry.get("removed").and_then(|r| r.as_bool()) != Some(true))
+            .map(|entry| {
+                let hex_field = |key: &str| entry.get(key).and_then(|v| v.as_str()).map(str::to_ascii_lowercase);
+                Some(Log {
+                    block_number: entry.get("blockNumber").and_then(parse_quantity)?,
+                    tx_hash: hex_field("transactionHash")?,
+                    log_index: entry.get("logIndex").and_then(parse_quantity)?,
+                    topics: entry
+                        .get("topics")?
+                        .as_array()?
+                        .iter()
+                        .map(|t| t.as_str().map(str::to_ascii_lowercase))
+                        .collect::<Option<_>>()?,
+                    data: hex::decode(hex_field("data")?.strip_prefix("0x")?).ok()?,
+                })
+            })
+            .map(|log| log.ok_or_else(malformed))
+            .collect()
+    }
+
+    /// The chain's ERC-20 token contract; 503 when none is configured
+    pub fn token_contract(&self) -> ApiResult<&str> {
+        match self.contract_address.as_deref() {
+            Some(contract) if Self::is_valid_eth_address(contract) && self.is_configured() => Ok(contract),
+            _ => Err(ApiError::ServiceUnavailable("Token contract is not configured".to_string())),
+        }
+    }
+
+    /// The token's decimals and symbol, read from the contract once
+    pub async fn token_metadata(&self, contract: &str) -> ApiResult<(u8, String)> {
+        let key = (self.chain_id, contract.to_ascii_lowercase());
+        if let Some(metadata) = TOKEN_METADATA.lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+            return Ok(metadata.clone());
+        }
+
+        let decimals = self.eth_call(contract, &format!("0x{}", DECIMALS_SELECTOR)).await?;
+        let decimals = decode_uint(&decimals)
+            .filter(|d| d.len() <= 1)
+            .map(|d| d.first().copied().unwrap_or(0))
+            .ok_or_else(|| ApiError::BlockchainError("Token reported invalid decimals".to_string()))?;
+        let symbol = match self.eth_call(contract, &format!("0x{}", SYMBOL_SELECTOR)).await {
+            Ok(data) => decode_abi_string(&data),
+            Err(e) => {
+                tracing::warn!("symbol() on {} failed: {}", contract, e);
+                None
+            }
+        };
+        let Some(symbol) = symbol else {
+            // Not cached, so a provider hiccup does not pin the fallback
+            return Ok((decimals, FALLBACK_SYMBOL.to_string()));
+        };
+
+        TOKEN_METADATA
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, (decimals, symbol.clone()));
+        Ok((decimals, symbol))
+    }
+
+    /// A raw balance from the cache, or read with `read` and cached
+    async fn cached_balance<F>(&self, key: BalanceKey, read: F) -> ApiResult<String>
+    where
+        F: Future<Output = ApiResult<String>>,
+    {
+        let cached = BALANCE_CACHE
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&key)
+            .filter(|(read_at, _)| read_at.elapsed() < BALANCE_CACHE_TTL)
+            .map(|(_, raw)| raw.clone());
+        if let Some(raw) = cached {
+            return Ok(raw);
         }
 
-        // In production, query the blockchain/contract
+        let raw = read.await?;
+        let mut cache = BALANCE_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        cache.retain(|_, (read_at, _)| read_at.elapsed() < BALANCE_CACHE_TTL);
+        cache.insert(key, (Instant::now(), raw.clone()));
+        Ok(raw)
+    }
+
+    /// ERC-20 balance of `address` in the chain's token contract, read with `balanceOf`
+    /// through the provider and cached briefly
+    pub async fn get_token_balance(&self, address: &str) -> ApiResult<TokenBalance> {
+        Self::validate_address(address)?;
+        let contract = self.token_contract()?;
+        let (decimals, symbol) = self.token_metadata(contract).await?;
+
+        let holder = address.to_ascii_lowercase();
+        let data = format!("0x{}{:0>64}", BALANCE_OF_SELECTOR, &holder[2..]);
+        let key = (self.chain_id, contract.to_ascii_lowercase(), holder);
+        let raw_balance = self
+            .cached_balance(key, async {
+                let result = self.eth_call(contract, &data).await?;
+                decode_uint(&result)
+                    .map(|digits| uint_to_decimal(&digits))
+                    .ok_or_else(|| ApiError::BlockchainError("balanceOf returned malformed data".to_string()))
+            })
+            .await?;
+
         Ok(TokenBalance {
+            chain_id: self.chain_id,
+            address: address.to_string(),
+            contract_address: contract.to_string(),
+            balance: format_units(&raw_balance, decimals),
+            raw_balance,
+            symbol,
+            decimals,
+        })
+    }
+
+    /// Balance of `address` in the chain's native coin, read with `eth_getBalance` and cached briefly
+    pub async fn get_native_balance(&self, address: &str) -> ApiResult<NativeBalance> {
+        Self::validate_address(address)?;
+        if !self.has_provider() {
+            return Err(ApiError::ServiceUnavailable(format!("Chain {} has no RPC provider", self.chain_id)));
+        }
+
+        let key = (self.chain_id, String::new(), address.to_ascii_lowercase());
+        let raw_balance = self
+            .cached_balance(key, async {
+                let result = self.rpc_call("eth_getBalance", serde_json::json!([address, "latest"])).await?;
+                result
+                    .as_str()
+                    .and_then(|r| r.strip_prefix("0x"))
+                    .and_then(|r| hex::decode(format!("{:0>64}", r)).ok())
+                    .and_then(|bytes| decode_uint(&bytes))
+                    .map(|digits| uint_to_decimal(&digits))
+                    .ok_or_else(|| ApiError::BlockchainError("eth_getBalance returned malformed data".to_string()))
+            })
+            .await?;
+
+        Ok(NativeBalance {
+            chain_id: self.chain_id,
             address: address.to_string(),
-            balance: "0".to_string(),
-            symbol: "RBV".to_string(),
-            decimals: 18,
+            balance: format_units(&raw_balance, NATIVE_DECIMALS),
+            raw_balance,
+            symbol: self.native_symbol.clone(),
+            decimals: NATIVE_DECIMALS,
         })
     }
 }
 
+/// A JSON-RPC quantity such as `"0x1b4"`
+pub fn parse_quantity(value: &serde_json::Value) -> Option<u64> {
+    u64::from_str_radix(value.as_str()?.strip_prefix("0x")?, 16).ok()
+}
+
+/// Blocks confirming a transaction mined in `block` when the chain is at `head`, counting
+/// its own block
+pub fn confirmations(head: u64, block: u64) -> u32 {
+    head.checked_sub(block).map_or(0, |depth| depth.saturating_add(1).min(u32::MAX as u64) as u32)
+}
+
+/// A `uint256` return value as big-endian bytes without leading zeros; `None` unless the
+/// data is exactly one 32-byte word
+fn decode_uint(data: &[u8]) -> Option<Vec<u8>> {
+    if data.len() != 32 {
+        return None;
+    }
+    Some(data.iter().copied().skip_while(|b| *b == 0).collect())
+}
+
+/// A `uint256` word as a decimal integer string
+pub fn decode_uint_decimal(data: &[u8]) -> Option<String> {
+    decode_uint(data).map(|digits| uint_to_decimal(&digits))
+}
+
+/// The address in an indexed event topic, such as the sender of a `Transfer` log
+pub fn topic_address(topic: &str) -> Option<String> {
+    let hex = topic.strip_prefix("0x")?;
+    (hex.len() == 64).then(|| format!("0x{}", &hex[24..]))
+}
+
+/// Big-endian bytes as a decimal integer string
+fn uint_to_decimal(bytes: &[u8]) -> String {
+    // Decimal digits, least significant first; each byte shifts them by 256
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in bytes {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            let value = *digit as u32 * 256 + carry;
+            *digit = (value % 10) as u8;
+            carry = value / 10;
+        }
+        while carry > 0 {
+            digits.push((carry % 10) as u8);
+            carry /= 10;
+        }
+    }
+    if digits.is_empty() {
+        return "0".to_string();
+    }
+    digits.iter().rev().map(|d| char::from(b'0' + d)).collect()
+}
+
+/// An integer amount of the smallest unit as a decimal in whole tokens, without trailing
+/// zeros: `format_units("1500000000000000000", 18)` is `"1.5"`
+pub fn format_units(raw: &str, decimals: u8) -> String {
+    let decimals = decimals as usize;
+    let padded = format!("{:0>width$}", raw, width = decimals + 1);
+    let (whole, fraction) = padded.split_at(padded.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() { whole.to_string() } else { format!("{}.{}", whole, fraction) }
+}
+
+/// A decimal amount in whole tokens as an integer amount of the smallest unit, the inverse of
+/// [`format_units`]: `parse_units("1.5", 18)` is 1.5e18. More fractional digits than the token
+/// has are refused rather than rounded.
+pub fn parse_units(amount: &str, decimals: u8) -> ApiResult<u128> {
+    let invalid = || ApiError::ValidationError(format!("Invalid amount: {}", amount));
+    let amount = amount.trim();
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if whole.is_empty() && fraction.is_empty()
+        || !whole.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+    if fraction.len() > decimals as usize {
+        return Err(ApiError::ValidationError(format!("Amounts have at most {} decimal places", decimals)));
+    }
+    let digits = format!("{}{:0<width$}", whole, fraction, width = decimals as usize);
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    digits.parse().map_err(|_| invalid())
+}
+
+/// A decimal token amount for a NUMERIC money column. An amount that does not parse is an
+/// error rather than zero, so a bad value is never recorded as a free payment.
+pub fn decimal_amount(amount: &str) -> ApiResult<Decimal> {
+    amount
+        .trim()
+        .parse::<Decimal>()
+        .ok()
+        .filter(|value| !value.is_sign_negative())
+        .ok_or_else(|| ApiError::ValidationError(format!("Invalid amount: {}", amount)))
+}
+
+/// A `string` return value. Some early tokens return `bytes32` instead, which is accepted too.
+pub fn decode_abi_string(data: &[u8]) -> Option<String> {
+    let text = if data.len() == 32 {
+        data.iter().copied().take_while(|b| *b != 0).collect::<Vec<_>>()
+    } else {
+        let word = |at: usize| -> Option<usize> {
+            let word = data.get(at..at + 32)?;
+            if word[..24].iter().any(|b| *b != 0) {
+                return None;
+            }
+            Some(u64::from_be_bytes(word[24..].try_into().ok()?) as usize)
+        };
+        let offset = word(0)?;
+        let len = word(offset)?;
+        data.get(offset + 32..(offset + 32).checked_add(len)?)?.to_vec()
+    };
+    String::from_utf8(text).ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
 impl Default for BlockchainService {
     fn default() -> Self {
         Self::new()
@@ -122,10 +627,43 @@ pub struct TransactionStatus {
     pub block_number: Option<u64>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TxReceipt {
+    pub block_number: u64,
+    pub succeeded: bool,
+}
+
+/// An event log from `eth_getLogs`; hex fields lower-case
+#[derive(Debug, Clone, PartialEq)]
+pub struct Log {
+    pub block_number: u64,
+    pub tx_hash: String,
+    pub log_index: u64,
+    pub topics: Vec<String>,
+    pub data: Vec<u8>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct TokenBalance {
+    pub chain_id: u64,
+    pub address: String,
+    pub contract_address: String,
+    /// In whole tokens, e.g. `"12.5"`
+    pub balance: String,
+    /// In the token's smallest unit, as returned by `balanceOf`
+    pub raw_balance: String,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NativeBalance {
+    pub chain_id: u64,
     pub address: String,
+    /// In whole coins, e.g. `"0.25"`
     pub balance: String,
+    /// In wei
+    pub raw_balance: String,
     pub symbol: String,
     pub decimals: u8,
 }
@@ -148,6 +686,15 @@ pub struct SignatureVerifyRequest {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_topic_address() {
+        assert_eq!(
+            topic_address("0x000000000000000000000000fb6916095ca1df60bb79ce92ce3ea74c37c5d359").as_deref(),
+            Some("0xfb6916095ca1df60bb79ce92ce3ea74c37c5d359")
+        );
+        assert_eq!(topic_address("0xfb6916095ca1df60bb79ce92ce3ea74c37c5d359"), None);
+    }
+
     #[test]
     fn test_valid_eth_address() {
         assert!(BlockchainService::is_valid_eth_address("0x742d35Cc6634C0532925a3b844Bc9e7595f5E4E1"));
@@ -156,6 +703,143 @@ mod tests {
         assert!(!BlockchainService::is_valid_eth_address("0x742d35Cc6634C0532925a3b844Bc9e7595f5E4EG")); // Invalid hex
     }
 
+    #[test]
+    fn test_validate_address() {
+        assert!(BlockchainService::validate_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").is_ok());
+        assert!(BlockchainService::validate_address("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").is_ok());
+        assert!(BlockchainService::validate_address("0x5AAEB6053F3E94C9B9A09F33669435E7EF1BEAED").is_ok());
+        // One letter's case flipped
+        assert!(BlockchainService::validate_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD").is_err());
+        assert!(BlockchainService::validate_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeA").is_err());
+    }
+
+    #[test]
+    fn test_for_chain() {
+        let chains = crate::config::chains::registry(|_| None);
+        let polygon = chains.iter().find(|c| c.chain_id == 137).map(BlockchainService::for_chain).unwrap();
+        assert_eq!(polygon.chain_id(), 137);
+        assert_eq!(polygon.native_symbol, "POL");
+        assert!(!polygon.has_provider());
+    }
+
+    #[test]
+    fn test_to_checksum_address() {
+        for address in [
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        ] {
+            assert_eq!(BlockchainService::to_checksum_address(&address.to_lowercase()), address);
+        }
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let service = BlockchainService::new();
+        let address = "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23";
+        let signature = "0xb91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd\
+                         6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c";
+
+        assert_eq!(
+            hex::encode(BlockchainService::eip191_hash("Some data")),
+            "1da44b586eb0729ff70a73c326926f6ed5a25f5b056e7f47fbc6e58d86871655"
+        );
+        assert!(service.verify_signature("Some data", signature, address).unwrap());
+        assert!(service.verify_signature("Some data", signature, &address.to_lowercase()).unwrap());
+        assert!(!service.verify_signature("Other data", signature, address).unwrap());
+        let other = "0x742d35Cc6634C0532925a3b844Bc9e7595f5E4E1";
+        assert!(!service.verify_signature("Some data", signature, other).unwrap());
+
+        let bad_v = format!("{}1f", &signature[..130]);
+        assert!(service.verify_signature("Some data", &bad_v, address).is_err());
+    }
+
+    #[test]
+    fn test_recover_address_accepts_high_s() {
+        let key = k256::ecdsa::SigningKey::from_slice(&[7u8; 32]).unwrap();
+        let digest = BlockchainService::eip191_hash("link wallet");
+        let (sig, recovery_id) = key.sign_prehash_recoverable(&digest).unwrap();
+        let expected = BlockchainService::address_of(key.verifying_key());
+
+        // The same signature with s replaced by n - s and the recovery parity flipped
+        let (r, s) = sig.split_scalars();
+        let high = Signature::from_scalars(r, -*s).unwrap();
+        let mut bytes = high.to_bytes().to_vec();
+        bytes.push(27 + (recovery_id.to_byte() ^ 1));
+        assert_eq!(BlockchainService::recover_address(&digest, &bytes).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_uint_decoding_and_formatting() {
+        let mut word = [0u8; 32];
+        word[24..].copy_from_slice(&1_500_000_000_000_000_000u64.to_be_bytes());
+        let raw = uint_to_decimal(&decode_uint(&word).unwrap());
+        assert_eq!(raw, "1500000000000000000");
+        assert_eq!(format_units(&raw, 18), "1.5");
+        assert_eq!(uint_to_decimal(&decode_uint(&[0u8; 32]).unwrap()), "0");
+        assert_eq!(
+            uint_to_decimal(&[0xff; 32]),
+            "115792089237316195423570985008687907853269984665640564039457584007913129639935"
+        );
+        assert!(decode_uint(&[0u8; 31]).is_none());
+
+        assert_eq!(format_units("0", 18), "0");
+        assert_eq!(format_units("1", 18), "0.000000000000000001");
+        assert_eq!(format_units("1234500", 6), "1.2345");
+        assert_eq!(format_units("42", 0), "42");
+
+        assert_eq!(parse_units("1.5", 18).unwrap(), 1_500_000_000_000_000_000);
+        assert_eq!(parse_units(".25", 2).unwrap(), 25);
+        assert_eq!(parse_units("42", 0).unwrap(), 42);
+        assert_eq!(parse_units("0.000", 6).unwrap(), 0);
+        assert_eq!(format_units(&parse_units("1.2345", 6).unwrap().to_string(), 6), "1.2345");
+        for invalid in ["", ".", "1.2.3", "-1", "1e18", "0.0000001"] {
+            assert!(parse_units(invalid, 6).is_err(), "{} was accepted", invalid);
+        }
+    }
+
+    #[test]
+    fn test_decimal_amount() {
+        assert_eq!(decimal_amount("1.5").unwrap(), Decimal::new(15, 1));
+        assert_eq!(decimal_amount(" 0.000001 ").unwrap(), Decimal::new(1, 6));
+        assert_eq!(decimal_amount("42").unwrap(), Decimal::from(42));
+        assert_eq!(
+            decimal_amount(&format_units("1234500", 6)).unwrap(),
+            Decimal::new(12345, 4)
+        );
+        for invalid in ["", "abc", "1.2.3", "-1", "1e18", "99999999999999999999999999999999"] {
+            assert!(decimal_amount(invalid).is_err(), "{} was accepted", invalid);
+        }
+    }
+
+    #[test]
+    fn test_confirmations() {
+        assert_eq!(confirmations(100, 100), 1);
+        assert_eq!(confirmations(111, 100), 12);
+        // A node lagging behind the one that reported the receipt
+        assert_eq!(confirmations(99, 100), 0);
+        assert_eq!(parse_quantity(&serde_json::json!("0x1b4")), Some(436));
+        assert_eq!(parse_quantity(&serde_json::json!("1b4")), None);
+        assert_eq!(parse_quantity(&serde_json::json!(436)), None);
+    }
+
+    #[test]
+    fn test_decode_abi_string() {
+        let mut data = vec![0u8; 96];
+        data[31] = 32;
+        data[63] = 3;
+        data[64..67].copy_from_slice(b"RBV");
+        assert_eq!(decode_abi_string(&data).as_deref(), Some("RBV"));
+
+        // bytes32 symbols, as returned by some early tokens
+        let mut word = [0u8; 32];
+        word[..3].copy_from_slice(b"MKR");
+        assert_eq!(decode_abi_string(&word).as_deref(), Some("MKR"));
+
+        data[63] = 200; // length past the end
+        assert!(decode_abi_string(&data).is_none());
+    }
+
     #[test]
     fn test_generate_nonce() {
         let nonce1 = BlockchainService::generate_nonce();
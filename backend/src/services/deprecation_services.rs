@@ -0,0 +1,332 @@
+//! Deprecated endpoints: the notices attached to routes in the route table, the response headers
+//! they produce and per-consumer usage of each, so a route is only removed once nobody calls it.
+//!
+//! Usage is counted in memory on the request path and flushed to `deprecated_route_usage`
+//! periodically, keeping deprecated routes as cheap to serve as any other.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+use crate::errors::ApiResult;
+
+const FLUSH_INTERVAL_SECS: u64 = 60;
+
+/// A route scheduled for removal
+#[derive(Debug)]
+pub struct Deprecation {
+    pub method: &'static str,
+    /// Route pattern as written in the route table, e.g. `/api/orgs/{org_id}/export`
+    pub path: &'static str,
+    /// ISO dates (UTC midnight): when the route was deprecated and when it stops being served
+    pub deprecated_on: &'static str,
+    pub sunset_on: &'static str,
+    /// Route that replaces this one
+    pub successor: Option<&'static str>,
+    /// Migration guide
+    pub link: Option<&'static str>,
+}
+
+/// `/api/health` duplicates `/health`, which load balancers already probe
+pub const API_HEALTH_ALIAS: Deprecation = Deprecation {
+    method: "GET",
+    path: "/api/health",
+    deprecated_on: "2026-10-16",
+    sunset_on: "2027-04-16",
+    successor: Some("/health"),
+    link: None,
+};
+
+/// Every notice in use, for the admin report. A route wrapped with `deprecated` must be listed here.
+pub const DEPRECATED_ROUTES: &[&Deprecation] = &[&API_HEALTH_ALIAS];
+
+fn date(value: &str) -> DateTime<Utc> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| d.and_utc())
+        .unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
+
+impl Deprecation {
+    pub fn deprecated_at(&self) -> DateTime<Utc> {
+        date(self.deprecated_on)
+    }
+
+    pub fn sunset_at(&self) -> DateTime<Utc> {
+        date(self.sunset_on)
+    }
+
+    /// `Deprecation` (RFC 9745), `Sunset` (RFC 8594) and `Link` headers, names lowercased as sent
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        let mut links = Vec::new();
+        if let Some(link) = self.link {
+            links.push(format!("<{}>; rel=\"deprecation\"; type=\"text/html\"", link));
+        }
+        if let Some(successor) = self.successor {
+            links.push(format!("<{}>; rel=\"successor-version\"", successor));
+        }
+
+        let mut headers = vec![
+            ("deprecation", format!("@{}", self.deprecated_at().timestamp())),
+            ("sunset", self.sunset_at().format("%a, %d %b %Y %H:%M:%S GMT").to_string()),
+        ];
+        if !links.is_empty() {
+            headers.push(("link", links.join(", ")));
+        }
+        headers
+    }
+}
+
+/// Who called a deprecated route: the authenticated user, or the client address otherwise
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UsageKey {
+    pub method: &'static str,
+    pub path: &'static str,
+    pub consumer: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct UsageCount {
+    pub user_id: Option<Uuid>,
+    pub user_agent: Option<String>,
+    pub calls: i64,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+/// Calls counted since the last flush
+#[derive(Default)]
+pub struct DeprecationTracker {
+    pending: Mutex<HashMap<UsageKey, UsageCount>>,
+}
+
+impl DeprecationTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(
+        &self,
+        notice: &'static Deprecation,
+        user_id: Option<Uuid>,
+        ip: Option<&str>,
+        user_agent: Option<&str>,
+        at: DateTime<Utc>,
+    ) {
+        let consumer = match (user_id, ip) {
+            (Some(user_id), _) => format!("user:{}", user_id),
+            (None, Some(ip)) => format!("ip:{}", ip),
+            (None, None) => "anonymous".to_string(),
+        };
+        let key = UsageKey { method: notice.method, path: notice.path, consumer };
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        let count = pending.entry(key).or_insert_with(|| UsageCount {
+            user_id,
+            user_agent: None,
+            calls: 0,
+            first_seen_at: at,
+            last_seen_at: at,
+        });
+        count.calls += 1;
+        count.last_seen_at = at;
+        if let Some(user_agent) = user_agent {
+            count.user_agent = Some(user_agent.chars().take(255).collect());
+        }
+    }
+
+    /// Take everything counted so far
+    pub fn drain(&self) -> HashMap<UsageKey, UsageCount> {
+        std::mem::take(&mut *self.pending.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Put counts back after a failed flush, merging with calls made in the meantime
+    fn restore(&self, counts: HashMap<UsageKey, UsageCount>) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        for (key, count) in counts {
+            match pending.get_mut(&key) {
+                Some(newer) => {
+                    newer.calls += count.calls;
+                    newer.first_seen_at = count.first_seen_at;
+                    newer.user_agent = newer.user_agent.take().or(count.user_agent);
+                }
+                None => {
+                    pending.insert(key, count);
+                }
+            }
+        }
+    }
+}
+
+/// Write pending counts to the usage table
+pub async fn flush(pool: &PgPool, tracker: &DeprecationTracker) -> ApiResult<usize> {
+    let counts = tracker.drain();
+    if counts.is_empty() {
+        return Ok(0);
+    }
+
+    // Counts are handed back on failure and retried with the next flush
+    let written: Result<usize, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+        for (key, count) in &counts {
+            sqlx::query(
+                "INSERT INTO deprecated_route_usage \
+                 (method, path, consumer, user_id, user_agent, calls, first_seen_at, last_seen_at) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+                 ON CONFLICT (method, path, consumer) DO UPDATE SET \
+                     calls = deprecated_route_usage.calls + EXCLUDED.calls, \
+                     user_agent = COALESCE(EXCLUDED.user_agent, deprecated_route_usage.user_agent), \
+                     last_seen_at = GREATEST(deprecated_route_usage.last_seen_at, EXCLUDED.last_seen_at)",
+            )
+            .bind(key.method)
+            .bind(key.path)
+            .bind(&key.consumer)
+            .bind(count.user_id)
+            .bind(&count.user_agent)
+            .bind(count.calls)
+            .bind(count.first_seen_at)
+            .bind(count.last_seen_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(counts.len())
+    }
+    .await;
+
+    written.map_err(|e| {
+        tracker.restore(counts);
+        e.into()
+    })
+}
+
+/// Start the background job persisting deprecated-route usage
+pub fn spawn_usage_flush_job(pool: Arc<PgPool>, tracker: Arc<DeprecationTracker>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(FLUSH_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = flush(&pool, &tracker).await {
+                tracing::error!("Deprecated route usage flush failed: {}", e);
+            }
+        }
+    });
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ConsumerUsage {
+    pub consumer: String,
+    pub user_id: Option<Uuid>,
+    pub email: Option<String>,
+    pub user_agent: Option<String>,
+    pub calls: i64,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+/// One deprecated route and the clients that called it within the report window
+#[derive(Debug, Serialize)]
+pub struct DeprecationUsage {
+    pub method: &'static str,
+    pub path: &'static str,
+    pub deprecated_at: DateTime<Utc>,
+    pub sunset_at: DateTime<Utc>,
+    pub days_until_sunset: i64,
+    pub successor: Option<&'static str>,
+    pub total_calls: i64,
+    pub consumers: Vec<ConsumerUsage>,
+}
+
+/// Usage of every registered deprecation by consumers seen since `since`, busiest first
+pub async fn usage_report(pool: &PgPool, since: DateTime<Utc>) -> ApiResult<Vec<DeprecationUsage>> {
+    let now = Utc::now();
+    let mut report = Vec::with_capacity(DEPRECATED_ROUTES.len());
+    for notice in DEPRECATED_ROUTES {
+        let consumers = sqlx::query_as::<_, ConsumerUsage>(
+            "SELECT u.consumer, u.user_id, usr.email, u.user_agent, u.calls, u.first_seen_at, u.last_seen_at \
+             FROM deprecated_route_usage u LEFT JOIN users usr ON usr.id = u.user_id \
+             WHERE u.method = $1 AND u.path = $2 AND u.last_seen_at >= $3 \
+             ORDER BY u.calls DESC, u.last_seen_at DESC LIMIT 500",
+        )
+        .bind(notice.method)
+        .bind(notice.path)
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
+
+        report.push(DeprecationUsage {
+            method: notice.method,
+            path: notice.path,
+            deprecated_at: notice.deprecated_at(),
+            sunset_at: notice.sunset_at(),
+            days_until_sunset: (notice.sunset_at() - now).num_days(),
+            successor: notice.successor,
+            total_calls: consumers.iter().map(|c| c.calls).sum(),
+            consumers,
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_dates_are_valid() {
+        for notice in DEPRECATED_ROUTES {
+            assert!(notice.deprecated_at() > DateTime::<Utc>::MIN_UTC, "{}", notice.path);
+            assert!(notice.sunset_at() > notice.deprecated_at(), "{}", notice.path);
+        }
+    }
+
+    #[test]
+    fn test_headers() {
+        let notice = Deprecation {
+            method: "GET",
+            path: "/api/old",
+            deprecated_on: "2026-10-16",
+            sunset_on: "2027-04-16",
+            successor: Some("/api/new"),
+            link: Some("https://example.com/migrate"),
+        };
+        let headers = notice.headers();
+        assert_eq!(headers[0], ("deprecation", "@1792108800".to_string()));
+        assert_eq!(headers[1], ("sunset", "Fri, 16 Apr 2027 00:00:00 GMT".to_string()));
+        assert_eq!(
+            headers[2].1,
+            concat!(
+                "<https://example.com/migrate>; rel=\"deprecation\"; type=\"text/html\", ",
+                "</api/new>; rel=\"successor-version\""
+            )
+        );
+
+        let bare = Deprecation { successor: None, link: None, ..notice };
+        assert_eq!(bare.headers().len(), 2);
+    }
+
+    #[test]
+    fn test_tracker_counts_per_consumer() {
+        let tracker = DeprecationTracker::new();
+        let user = Uuid::new_v4();
+        let now = Utc::now();
+        tracker.record(&API_HEALTH_ALIAS, Some(user), Some("10.0.0.1"), Some("cli/1.0"), now);
+        tracker.record(&API_HEALTH_ALIAS, Some(user), Some("10.0.0.2"), None, now);
+        tracker.record(&API_HEALTH_ALIAS, None, Some("10.0.0.1"), None, now);
+
+        let counts = tracker.drain();
+        assert_eq!(counts.len(), 2);
+        let by_user = &counts[&UsageKey { method: "GET", path: "/api/health", consumer: format!("user:{}", user) }];
+        assert_eq!(by_user.calls, 2);
+        assert_eq!(by_user.user_agent.as_deref(), Some("cli/1.0"));
+        assert!(tracker.drain().is_empty());
+
+        // A failed flush hands its counts back without losing newer calls
+        tracker.record(&API_HEALTH_ALIAS, None, Some("10.0.0.1"), None, now);
+        tracker.restore(counts);
+        let ip = UsageKey { method: "GET", path: "/api/health", consumer: "ip:10.0.0.1".to_string() };
+        assert_eq!(tracker.drain()[&ip].calls, 2);
+    }
+}
@@ -0,0 +1,46 @@
+//! Shared device lookups used across robotics controllers
+
+use sqlx::PgPool;
+use std::collections::HashSet;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::device::Device;
+
+pub const DEVICE_COLUMNS: &str =
+    "id, user_id, device_name, device_type, firmware_version, status, last_seen, metadata, transport, nft_chain_id, \
+     nft_token_id, created_at";
+
+/// Fetch a device, failing with 404 unless it belongs to `user_id`
+pub async fn get_owned_device(pool: &PgPool, device_id: Uuid, user_id: Uuid) -> ApiResult<Device> {
+    sqlx::query_as::<_, Device>(&format!(
+        "SELECT {} FROM devices WHERE id = $1 AND user_id = $2",
+        DEVICE_COLUMNS
+    ))
+    .bind(device_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Device not found".to_string()))
+}
+
+/// Normalize an optional device filter: `None` (or empty) covers every device of the user,
+/// otherwise the deduplicated ids, all of which must belong to `user_id`
+pub async fn owned_device_scope(
+    pool: &PgPool,
+    user_id: Uuid,
+    device_ids: Option<&[Uuid]>,
+) -> ApiResult<Option<Vec<Uuid>>> {
+    let Some(ids) = device_ids.filter(|ids| !ids.is_empty()) else {
+        return Ok(None);
+    };
+    let unique: Vec<Uuid> = ids.iter().copied().collect::<HashSet<_>>().into_iter().collect();
+    let owned: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM devices WHERE user_id = $1 AND id = ANY($2)")
+        .bind(user_id)
+        .bind(&unique)
+        .fetch_one(pool)
+        .await?;
+    if owned as usize != unique.len() {
+        return Err(ApiError::NotFound("One or more devices not found".to_string()));
+    }
+    Ok(Some(unique))
+}
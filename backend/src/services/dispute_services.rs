@@ -0,0 +1,300 @@
+//! Chargebacks. A dispute reported by the payment provider is recorded against the disputed
+//! transaction and freezes what the payment unlocked. Admins note the evidence sent to the
+//! provider and close the dispute when the provider does not report the outcome itself: a won
+//! dispute thaws the entitlements, a lost one revokes them, marks the transaction charged_back
+//! and posts the money taken back to the ledger.
+
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::dispute::{Dispute, ProviderDispute};
+use crate::models::transaction::Transaction;
+use crate::services::notification_services::notify_user;
+use crate::services::payment_services::{from_minor_units, revoke_product, sync_premium, TRANSACTION_COLUMNS};
+use crate::services::ledger_services;
+
+const DISPUTE_COLUMNS: &str = "id, transaction_id, user_id, provider, provider_dispute_id, amount, currency, reason, \
+     provider_status, status, evidence_due_by, evidence_note, evidence_submitted_by, evidence_submitted_at, \
+     resolution_note, resolved_by, resolved_at, created_at, updated_at";
+
+pub const STATUSES: &[&str] = &["open", "evidence_submitted", "won", "lost"];
+const MAX_NOTE_CHARS: usize = 5_000;
+
+/// Whether the dispute can still move
+pub fn is_open(status: &str) -> bool {
+    matches!(status, "open" | "evidence_submitted")
+}
+
+/// The status a provider's dispute status moves a dispute to, if any. Stripe's warning_*
+/// statuses are inquiries that close without money moving, so one that closes counts as won.
+pub fn status_for(provider_status: &str) -> Option<&'static str> {
+    match provider_status {
+        "won" | "warning_closed" => Some("won"),
+        "lost" => Some("lost"),
+        "under_review" | "warning_under_review" => Some("evidence_submitted"),
+        _ => None,
+    }
+}
+
+fn validate_note(note: Option<&str>) -> ApiResult<Option<&str>> {
+    let note = note.map(str::trim).filter(|n| !n.is_empty());
+    if note.is_some_and(|n| n.chars().count() > MAX_NOTE_CHARS) {
+        return Err(ApiError::ValidationError(format!("note must be at most {} characters", MAX_NOTE_CHARS)));
+    }
+    Ok(note)
+}
+
+/// Record a dispute the provider reported against the (locked) transaction, opening it the
+/// first time it is seen and closing it when the provider reports the outcome. Returns
+/// `dispute_opened`, `dispute_updated`, `dispute_won` or `dispute_lost`.
+pub async fn ingest(
+    conn: &mut PgConnection,
+    provider: &str,
+    transaction: &Transaction,
+    reported: &ProviderDispute,
+) -> ApiResult<&'static str> {
+    let existing: Option<Uuid> =
+        sqlx::query_scalar("SELECT id FROM disputes WHERE provider = $1 AND provider_dispute_id = $2 FOR UPDATE")
+            .bind(provider)
+            .bind(&reported.id)
+            .fetch_optional(&mut *conn)
+            .await?;
+    let submitted = status_for(&reported.status) == Some("evidence_submitted");
+
+    let dispute = match existing {
+        Some(id) => {
+            sqlx::query_as::<_, Dispute>(&format!(
+                "UPDATE disputes SET provider_status = $2, reason = COALESCE($3, reason), \
+                 evidence_due_by = COALESCE($4, evidence_due_by), \
+                 status = CASE WHEN status = 'open' AND $5 THEN 'evidence_submitted' ELSE status END, \
+                 updated_at = NOW() WHERE id = $1 RETURNING {}",
+                DISPUTE_COLUMNS
+            ))
+            .bind(id)
+            .bind(&reported.status)
+            .bind(&reported.reason)
+            .bind(reported.evidence_due_by)
+            .bind(submitted)
+            .fetch_one(&mut *conn)
+            .await?
+        }
+        None => {
+            let dispute = sqlx::query_as::<_, Dispute>(&format!(
+                "INSERT INTO disputes (id, transaction_id, user_id, provider, provider_dispute_id, amount, currency, \
+                 reason, provider_status, status, evidence_due_by) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING {}",
+                DISPUTE_COLUMNS
+            ))
+            .bind(Uuid::new_v4())
+            .bind(transaction.id)
+            .bind(transaction.user_id)
+            .bind(provider)
+            .bind(&reported.id)
+            .bind(from_minor_units(reported.amount, &reported.currency))
+            .bind(reported.currency.to_lowercase())
+            .bind(&reported.reason)
+            .bind(&reported.status)
+            .bind(if submitted { "evidence_submitted" } else { "open" })
+            .bind(reported.evidence_due_by)
+            .fetch_one(&mut *conn)
+            .await?;
+            open(conn, transaction, &dispute).await?;
+            dispute
+        }
+    };
+
+    match status_for(&reported.status) {
+        Some("won") if is_open(&dispute.status) => {
+            settle(conn, transaction, &dispute, true, None, None).await?;
+            Ok("dispute_won")
+        }
+        Some("lost") if is_open(&dispute.status) => {
+            settle(conn, transaction, &dispute, false, None, None).await?;
+            Ok("dispute_lost")
+        }
+        _ if existing.is_none() => Ok("dispute_opened"),
+        _ => Ok("dispute_updated"),
+    }
+}
+
+/// Freeze what the disputed payment unlocked and tell the user
+async fn open(conn: &mut PgConnection, transaction: &Transaction, dispute: &Dispute) -> ApiResult<()> {
+    sqlx::query("UPDATE product_entitlements SET frozen_at = NOW() WHERE transaction_id = $1 AND frozen_at IS NULL")
+        .bind(transaction.id)
+        .execute(&mut *conn)
+        .await?;
+    sync_premium(conn, transaction.user_id).await?;
+    notify_user(
+        conn,
+        transaction.user_id,
+        "payment_disputed",
+        "Payment disputed",
+        &format!(
+            "Your payment of {:.2} {} for {} was disputed with your bank; it stays locked until the dispute closes.",
+            dispute.amount,
+            dispute.currency.to_uppercase(),
+            transaction.product_type.replace('_', " ")
+        ),
+        serde_json::json!({ "transaction_id": transaction.id, "dispute_id": dispute.id }),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Close an open dispute as won or lost, once. A won dispute thaws the entitlements unless
+/// another dispute over the payment is still open; a lost one takes them back.
+async fn settle(
+    conn: &mut PgConnection,
+    transaction: &Transaction,
+    dispute: &Dispute,
+    won: bool,
+    admin_id: Option<Uuid>,
+    note: Option<&str>,
+) -> ApiResult<Dispute> {
+    let dispute = sqlx::query_as::<_, Dispute>(&format!(
+        "UPDATE disputes SET status = $2, resolution_note = COALESCE($3, resolution_note), resolved_by = $4, \
+         resolved_at = NOW(), updated_at = NOW() \
+         WHERE id = $1 AND status IN ('open', 'evidence_submitted') RETURNING {}",
+        DISPUTE_COLUMNS
+    ))
+    .bind(dispute.id)
+    .bind(if won { "won" } else { "lost" })
+    .bind(note)
+    .bind(admin_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| ApiError::Conflict("This dispute is already closed".to_string()))?;
+
+    let product = transaction.product_type.replace('_', " ");
+    let body = if won {
+        sqlx::query(
+            "UPDATE product_entitlements SET frozen_at = NULL WHERE transaction_id = $1 AND NOT EXISTS \
+             (SELECT 1 FROM disputes WHERE transaction_id = $1 AND status IN ('open', 'evidence_submitted'))",
+        )
+        .bind(transaction.id)
+        .execute(&mut *conn)
+        .await?;
+        sync_premium(conn, transaction.user_id).await?;
+        format!("The dispute over your payment for {} was closed and it is unlocked again.", product)
+    } else {
+        sqlx::query("UPDATE transactions SET status = 'charged_back' WHERE id = $1")
+            .bind(transaction.id)
+            .execute(&mut *conn)
+            .await?;
+        revoke_product(conn, transaction).await?;
+        ledger_services::post_chargeback(conn, transaction, &dispute).await?;
+        format!(
+            "The dispute over your payment for {} was decided for you and {:.2} {} was returned by your bank.",
+            product,
+            dispute.amount,
+            dispute.currency.to_uppercase()
+        )
+    };
+    notify_user(
+        conn,
+        transaction.user_id,
+        &format!("payment_dispute.{}", dispute.status),
+        "Payment dispute closed",
+        &body,
+        serde_json::json!({ "transaction_id": transaction.id, "dispute_id": dispute.id, "status": dispute.status }),
+    )
+    .await?;
+    Ok(dispute)
+}
+
+/// Disputes in `status`, or every open one, with the soonest evidence deadline first
+pub async fn queue(pool: &PgPool, status: Option<&str>) -> ApiResult<Vec<Dispute>> {
+    if status.is_some_and(|s| !STATUSES.contains(&s)) {
+        return Err(ApiError::ValidationError(format!("status must be one of {}", STATUSES.join(", "))));
+    }
+    let disputes = sqlx::query_as::<_, Dispute>(&format!(
+        "SELECT {} FROM disputes \
+         WHERE ($1::TEXT IS NULL AND status IN ('open', 'evidence_submitted')) OR status = $1 \
+         ORDER BY evidence_due_by NULLS LAST, created_at LIMIT 200",
+        DISPUTE_COLUMNS
+    ))
+    .bind(status)
+    .fetch_all(pool)
+    .await?;
+    Ok(disputes)
+}
+
+pub async fn get(pool: &PgPool, dispute_id: Uuid) -> ApiResult<Dispute> {
+    sqlx::query_as::<_, Dispute>(&format!("SELECT {} FROM disputes WHERE id = $1", DISPUTE_COLUMNS))
+        .bind(dispute_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Dispute not found".to_string()))
+}
+
+/// Note the evidence sent to the provider for an open dispute
+pub async fn submit_evidence(pool: &PgPool, admin_id: Uuid, dispute_id: Uuid, note: &str) -> ApiResult<Dispute> {
+    let note = validate_note(Some(note))?
+        .ok_or_else(|| ApiError::ValidationError("note must not be empty".to_string()))?;
+    sqlx::query_as::<_, Dispute>(&format!(
+        "UPDATE disputes SET status = 'evidence_submitted', evidence_note = $2, evidence_submitted_by = $3, \
+         evidence_submitted_at = NOW(), updated_at = NOW() \
+         WHERE id = $1 AND status IN ('open', 'evidence_submitted') RETURNING {}",
+        DISPUTE_COLUMNS
+    ))
+    .bind(dispute_id)
+    .bind(note)
+    .bind(admin_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::Conflict("No open dispute with this id".to_string()))
+}
+
+/// Close an open dispute as won or lost by hand
+pub async fn resolve(
+    pool: &PgPool,
+    admin_id: Uuid,
+    dispute_id: Uuid,
+    won: bool,
+    note: Option<&str>,
+) -> ApiResult<Dispute> {
+    let note = validate_note(note)?;
+    let mut tx = pool.begin().await?;
+    let dispute = sqlx::query_as::<_, Dispute>(&format!(
+        "SELECT {} FROM disputes WHERE id = $1 AND status IN ('open', 'evidence_submitted')",
+        DISPUTE_COLUMNS
+    ))
+    .bind(dispute_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::Conflict("No open dispute with this id".to_string()))?;
+    let transaction = sqlx::query_as::<_, Transaction>(&format!(
+        "SELECT {} FROM transactions WHERE id = $1 FOR UPDATE",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(dispute.transaction_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let dispute = settle(&mut tx, &transaction, &dispute, won, Some(admin_id), note).await?;
+    tx.commit().await?;
+    Ok(dispute)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_for() {
+        assert_eq!(status_for("needs_response"), None);
+        assert_eq!(status_for("warning_needs_response"), None);
+        assert_eq!(status_for("under_review"), Some("evidence_submitted"));
+        assert_eq!(status_for("won"), Some("won"));
+        assert_eq!(status_for("warning_closed"), Some("won"));
+        assert_eq!(status_for("lost"), Some("lost"));
+    }
+
+    #[test]
+    fn test_is_open() {
+        assert!(is_open("open"));
+        assert!(is_open("evidence_submitted"));
+        assert!(!is_open("won"));
+        assert!(!is_open("lost"));
+    }
+}
@@ -0,0 +1,313 @@
+//! EIP-712 typed structured data, signed by wallets with `eth_signTypedData_v4`. Unlike a
+//! free-text message, the wallet shows every field with its type along with the domain (site
+//! and chain) the signature is bound to, so it cannot be replayed elsewhere. The server issues
+//! each structure it accepts, and a signed one is only trusted when it hashes to the same
+//! structure reissued from the request.
+
+use chrono::{DateTime, Utc};
+use sha3::{Digest, Keccak256};
+use std::collections::{BTreeMap, BTreeSet};
+use crate::errors::{ApiError, ApiResult};
+use crate::models::typed_data::{TypedData, TypedField};
+use crate::services::crypto_services::BlockchainService;
+use crate::services::relayer_services::{address_word, uint_word};
+
+pub const DOMAIN_NAME: &str = "RoboVeda";
+pub const DOMAIN_VERSION: &str = "1";
+const DOMAIN_TYPE: &str = "EIP712Domain";
+/// Structs nested deeper than this are refused
+const MAX_DEPTH: usize = 8;
+
+type Types = BTreeMap<String, Vec<TypedField>>;
+
+fn invalid(reason: &str) -> ApiError {
+    ApiError::ValidationError(format!("Invalid typed data: {}", reason))
+}
+
+/// Typed data whose primary type has `fields` (name, type), in this site's domain on `chain_id`
+pub fn typed_data(chain_id: u64, primary_type: &str, fields: &[(&str, &str)], message: serde_json::Value) -> TypedData {
+    let members = |fields: &[(&str, &str)]| -> Vec<TypedField> {
+        fields
+            .iter()
+            .map(|(name, kind)| TypedField { name: name.to_string(), kind: kind.to_string() })
+            .collect()
+    };
+    let mut types = BTreeMap::new();
+    types.insert(
+        DOMAIN_TYPE.to_string(),
+        members(&[("name", "string"), ("version", "string"), ("chainId", "uint256")]),
+    );
+    types.insert(primary_type.to_string(), members(fields));
+    TypedData {
+        types,
+        primary_type: primary_type.to_string(),
+        domain: serde_json::json!({ "name": DOMAIN_NAME, "version": DOMAIN_VERSION, "chainId": chain_id }),
+        message,
+    }
+}
+
+/// The element type of an array type such as `uint256[]` or `Person[2]`
+fn array_item(kind: &str) -> Option<&str> {
+    kind.strip_suffix(']').and_then(|kind| kind.rsplit_once('[')).map(|(item, _)| item)
+}
+
+/// Struct types `name` refers to, directly or through other structs, including itself
+fn collect_dependencies(types: &Types, name: &str, found: &mut BTreeSet<String>) {
+    let name = name.split('[').next().unwrap_or(name);
+    let Some(fields) = types.get(name) else {
+        return;
+    };
+    if !found.insert(name.to_string()) {
+        return;
+    }
+    for field in fields {
+        collect_dependencies(types, &field.kind, found);
+    }
+}
+
+/// `encodeType`: the struct's signature followed by those of its dependencies, sorted by name
+pub fn encode_type(types: &Types, name: &str) -> ApiResult<String> {
+    let mut dependencies = BTreeSet::new();
+    collect_dependencies(types, name, &mut dependencies);
+    if !dependencies.remove(name) {
+        return Err(invalid(&format!("unknown type {}", name)));
+    }
+    let mut encoded = String::new();
+    for name in std::iter::once(name).chain(dependencies.iter().map(String::as_str)) {
+        let members: Vec<String> = types[name].iter().map(|f| format!("{} {}", f.kind, f.name)).collect();
+        encoded.push_str(&format!("{}({})", name, members.join(",")));
+    }
+    Ok(encoded)
+}
+
+/// An unsigned integer given as a JSON number or a decimal or `0x` hex string
+fn parse_uint(value: &serde_json::Value) -> Option<u128> {
+    match value {
+        serde_json::Value::Number(n) => n.as_u64().map(u128::from),
+        serde_json::Value::String(s) => match s.strip_prefix("0x") {
+            Some(hex) => u128::from_str_radix(hex, 16).ok(),
+            None => s.parse().ok(),
+        },
+        _ => None,
+    }
+}
+
+fn hex_bytes(value: &serde_json::Value) -> Option<Vec<u8>> {
+    hex::decode(value.as_str()?.strip_prefix("0x")?).ok()
+}
+
+/// One encoded member: atomic values as a word, dynamic ones and structs as their hash
+fn encode_value(types: &Types, kind: &str, value: &serde_json::Value, depth: usize) -> ApiResult<[u8; 32]> {
+    if depth > MAX_DEPTH {
+        return Err(invalid("structs are nested too deeply"));
+    }
+    let mismatch = || invalid(&format!("expected a {} value", kind));
+    if let Some(item) = array_item(kind) {
+        let mut hasher = Keccak256::new();
+        for element in value.as_array().ok_or_else(mismatch)? {
+            hasher.update(encode_value(types, item, element, depth + 1)?);
+        }
+        return Ok(hasher.finalize().into());
+    }
+    if types.contains_key(kind) {
+        return hash_struct_at(types, kind, value, depth + 1);
+    }
+    match kind {
+        "string" => Ok(Keccak256::digest(value.as_str().ok_or_else(mismatch)?.as_bytes()).into()),
+        "bytes" => Ok(Keccak256::digest(hex_bytes(value).ok_or_else(mismatch)?).into()),
+        "bool" => Ok(uint_word(value.as_bool().ok_or_else(mismatch)? as u128)),
+        "address" => address_word(value.as_str().ok_or_else(mismatch)?),
+        kind if kind.starts_with("uint") => Ok(uint_word(parse_uint(value).ok_or_else(mismatch)?)),
+        kind if kind.starts_with("bytes") => {
+            let size: usize = kind["bytes".len()..].parse().map_err(|_| mismatch())?;
+            let bytes = hex_bytes(value).filter(|b| b.len() == size && size <= 32).ok_or_else(mismatch)?;
+            let mut word = [0u8; 32];
+            word[..size].copy_from_slice(&bytes);
+            Ok(word)
+        }
+        _ => Err(invalid(&format!("unsupported type {}", kind))),
+    }
+}
+
+fn hash_struct_at(types: &Types, name: &str, value: &serde_json::Value, depth: usize) -> ApiResult<[u8; 32]> {
+    let fields = types.get(name).ok_or_else(|| invalid(&format!("unknown type {}", name)))?;
+    let object = value.as_object().ok_or_else(|| invalid(&format!("expected a {} object", name)))?;
+    let mut hasher = Keccak256::new();
+    hasher.update(Keccak256::digest(encode_type(types, name)?.as_bytes()));
+    for field in fields {
+        let member = object
+            .get(&field.name)
+            .ok_or_else(|| invalid(&format!("{} is missing {}", name, field.name)))?;
+        hasher.update(encode_value(types, &field.kind, member, depth)?);
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// `hashStruct` of `value` as a `name`
+pub fn hash_struct(types: &Types, name: &str, value: &serde_json::Value) -> ApiResult<[u8; 32]> {
+    hash_struct_at(types, name, value, 0)
+}
+
+/// The digest a wallet signs: keccak256("\x19\x01" || domainSeparator || hashStruct(message))
+pub fn signing_hash(data: &TypedData) -> ApiResult<[u8; 32]> {
+    let mut hasher = Keccak256::new();
+    hasher.update([0x19, 0x01]);
+    hasher.update(hash_struct(&data.types, DOMAIN_TYPE, &data.domain)?);
+    hasher.update(hash_struct(&data.types, &data.primary_type, &data.message)?);
+    Ok(hasher.finalize().into())
+}
+
+/// Address (lower-case) that signed `data` with `eth_signTypedData_v4`
+pub fn recover_signer(data: &TypedData, signature: &str) -> ApiResult<String> {
+    let bytes = signature
+        .strip_prefix("0x")
+        .and_then(|hex_sig| hex::decode(hex_sig).ok())
+        .ok_or_else(|| ApiError::ValidationError("Invalid signature format".to_string()))?;
+    BlockchainService::recover_address(&signing_hash(data)?, &bytes)
+}
+
+/// Check the signed `data` is the structure the server issued as `expected`, and return its
+/// signer. Values may be written differently (a number as a string) as long as they hash the same.
+pub fn verify(data: &TypedData, signature: &str, expected: &TypedData) -> ApiResult<String> {
+    if data.primary_type != expected.primary_type || data.types != expected.types {
+        return Err(ApiError::ValidationError(format!("Expected a {} authorization", expected.primary_type)));
+    }
+    if signing_hash(data)? != signing_hash(expected)? {
+        return Err(ApiError::ValidationError("The signed data does not match this request".to_string()));
+    }
+    recover_signer(expected, signature)
+}
+
+/// A string member of the message
+pub fn message_str<'a>(data: &'a TypedData, name: &str) -> ApiResult<&'a str> {
+    data.message.get(name).and_then(|v| v.as_str()).ok_or_else(|| invalid(&format!("missing {}", name)))
+}
+
+/// A member of the message holding Unix seconds
+pub fn message_time(data: &TypedData, name: &str) -> ApiResult<DateTime<Utc>> {
+    data.message
+        .get(name)
+        .and_then(parse_uint)
+        .and_then(|secs| i64::try_from(secs).ok())
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .ok_or_else(|| invalid(&format!("missing {}", name)))
+}
+
+/// The chain the domain binds the signature to
+pub fn chain_id(data: &TypedData) -> ApiResult<u64> {
+    data.domain
+        .get("chainId")
+        .and_then(parse_uint)
+        .and_then(|id| u64::try_from(id).ok())
+        .ok_or_else(|| invalid("missing chainId"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The `Mail` example of the EIP-712 specification
+    fn mail() -> TypedData {
+        serde_json::from_value(serde_json::json!({
+            "types": {
+                "EIP712Domain": [
+                    { "name": "name", "type": "string" },
+                    { "name": "version", "type": "string" },
+                    { "name": "chainId", "type": "uint256" },
+                    { "name": "verifyingContract", "type": "address" }
+                ],
+                "Person": [
+                    { "name": "name", "type": "string" },
+                    { "name": "wallet", "type": "address" }
+                ],
+                "Mail": [
+                    { "name": "from", "type": "Person" },
+                    { "name": "to", "type": "Person" },
+                    { "name": "contents", "type": "string" }
+                ]
+            },
+            "primaryType": "Mail",
+            "domain": {
+                "name": "Ether Mail",
+                "version": "1",
+                "chainId": 1,
+                "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+            },
+            "message": {
+                "from": { "name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826" },
+                "to": { "name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB" },
+                "contents": "Hello, Bob!"
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_encode_type() {
+        let data = mail();
+        assert_eq!(
+            encode_type(&data.types, "Mail").unwrap(),
+            "Mail(Person from,Person to,string contents)Person(string name,address wallet)"
+        );
+        assert!(encode_type(&data.types, "Letter").is_err());
+    }
+
+    #[test]
+    fn test_signing_hash() {
+        let data = mail();
+        assert_eq!(
+            hex::encode(hash_struct(&data.types, DOMAIN_TYPE, &data.domain).unwrap()),
+            "f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f"
+        );
+        assert_eq!(
+            hex::encode(hash_struct(&data.types, "Mail", &data.message).unwrap()),
+            "c52c0ee5d84264471806290a3f2c4cecfc5490626bf912d01f240d7a274b371e"
+        );
+        assert_eq!(
+            hex::encode(signing_hash(&data).unwrap()),
+            "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        let data = mail();
+        let signature = "0x4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d\
+                         07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b915621c";
+        let signer = verify(&data, signature, &data).unwrap();
+        assert_eq!(signer, "0xcd2a3d9f938e13cd947ec05abc7fe734df8dd826");
+
+        // The same value written another way still matches
+        let mut renumbered = data.clone();
+        renumbered.domain["chainId"] = serde_json::json!("1");
+        assert_eq!(verify(&renumbered, signature, &data).unwrap(), signer);
+
+        let mut altered = data.clone();
+        altered.message["contents"] = serde_json::json!("Hello, Eve!");
+        assert!(verify(&altered, signature, &data).is_err());
+        let mut other_chain = data.clone();
+        other_chain.domain["chainId"] = serde_json::json!(5);
+        assert!(verify(&other_chain, signature, &data).is_err());
+        let mut missing = data.clone();
+        missing.message.as_object_mut().unwrap().remove("contents");
+        assert!(verify(&missing, signature, &data).is_err());
+    }
+
+    #[test]
+    fn test_typed_data() {
+        let data = typed_data(
+            137,
+            "Approval",
+            &[("nonce", "string"), ("expiresAt", "uint256")],
+            serde_json::json!({ "nonce": "abc", "expiresAt": 1_800_000_000 }),
+        );
+        assert_eq!(chain_id(&data).unwrap(), 137);
+        assert_eq!(message_str(&data, "nonce").unwrap(), "abc");
+        assert_eq!(message_time(&data, "expiresAt").unwrap().timestamp(), 1_800_000_000);
+        assert!(message_str(&data, "other").is_err());
+        assert!(signing_hash(&data).is_ok());
+        let json = serde_json::to_value(&data).unwrap();
+        assert_eq!(json["primaryType"], "Approval");
+        assert_eq!(json["types"]["EIP712Domain"][2]["type"], "uint256");
+    }
+}
@@ -0,0 +1,221 @@
+//! Embedding store and semantic search on pgvector. Each indexed document is embedded with the
+//! default AI provider's embedding model and kept with the model's name; a search embeds the
+//! query the same way and ranks the user's documents from that model by cosine similarity.
+//! Vectors are passed to Postgres in pgvector's text form.
+
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::embedding::{IndexRequest, IndexedDocument, SearchResult};
+use crate::services::ai_services::AIService;
+
+pub const DEFAULT_COLLECTION: &str = "default";
+const MAX_DOCUMENTS_PER_REQUEST: usize = 50;
+/// About 2,000 tokens, within every supported embedding model's input limit
+const MAX_CONTENT_CHARS: usize = 8_000;
+const MAX_QUERY_CHARS: usize = 2_000;
+const MAX_COLLECTION_CHARS: usize = 100;
+const MAX_SOURCE_CHARS: usize = 500;
+const DEFAULT_SEARCH_LIMIT: i64 = 5;
+const MAX_SEARCH_LIMIT: i64 = 50;
+
+/// pgvector's text form of a vector, e.g. `[0.1,-2,3.5]`
+pub fn vector_literal(vector: &[f32]) -> ApiResult<String> {
+    if vector.is_empty() || vector.iter().any(|v| !v.is_finite()) {
+        return Err(ApiError::AIServiceError("The embedding returned is empty or not finite".to_string()));
+    }
+    let values: Vec<String> = vector.iter().map(|v| v.to_string()).collect();
+    Ok(format!("[{}]", values.join(",")))
+}
+
+pub fn validate_collection(collection: Option<&str>) -> ApiResult<String> {
+    let collection = collection.map(str::trim).filter(|c| !c.is_empty()).unwrap_or(DEFAULT_COLLECTION);
+    if collection.chars().count() > MAX_COLLECTION_CHARS {
+        return Err(ApiError::ValidationError(format!(
+            "collection must be at most {} characters",
+            MAX_COLLECTION_CHARS
+        )));
+    }
+    Ok(collection.to_string())
+}
+
+/// A text to store with its embedding
+#[derive(Debug)]
+pub struct NewEmbedding<'a> {
+    pub collection: &'a str,
+    /// The uploaded document the text is a chunk of
+    pub document_id: Option<Uuid>,
+    pub chunk_index: Option<i32>,
+    pub source: Option<&'a str>,
+    pub content: &'a str,
+    pub metadata: serde_json::Value,
+}
+
+/// Embed `text`, returning the vector in pgvector's text form with its dimensions
+pub async fn embed(ai: &AIService, text: &str) -> ApiResult<(String, i32)> {
+    let vector = ai.generate_embeddings(text).await?;
+    Ok((vector_literal(&vector)?, vector.len() as i32))
+}
+
+pub async fn insert(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    model: &str,
+    embedding: &NewEmbedding<'_>,
+    (vector, dimensions): (String, i32),
+) -> ApiResult<IndexedDocument> {
+    let stored = sqlx::query_as::<_, IndexedDocument>(
+        "INSERT INTO embeddings (id, user_id, collection, document_id, chunk_index, source, content, metadata, \
+         model, dimensions, embedding) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11::vector) \
+         RETURNING id, collection, source, model, dimensions, created_at",
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(embedding.collection)
+    .bind(embedding.document_id)
+    .bind(embedding.chunk_index)
+    .bind(embedding.source)
+    .bind(embedding.content)
+    .bind(&embedding.metadata)
+    .bind(model)
+    .bind(dimensions)
+    .bind(vector)
+    .fetch_one(conn)
+    .await?;
+    Ok(stored)
+}
+
+fn validate(request: &IndexRequest) -> ApiResult<()> {
+    if request.documents.is_empty() || request.documents.len() > MAX_DOCUMENTS_PER_REQUEST {
+        return Err(ApiError::ValidationError(format!(
+            "documents must hold between 1 and {} documents",
+            MAX_DOCUMENTS_PER_REQUEST
+        )));
+    }
+    for (i, document) in request.documents.iter().enumerate() {
+        let chars = document.content.trim().chars().count();
+        if chars == 0 || chars > MAX_CONTENT_CHARS {
+            return Err(ApiError::ValidationError(format!(
+                "documents[{}].content must be between 1 and {} characters",
+                i, MAX_CONTENT_CHARS
+            )));
+        }
+        if document.source.as_ref().is_some_and(|s| s.chars().count() > MAX_SOURCE_CHARS) {
+            return Err(ApiError::ValidationError(format!(
+                "documents[{}].source must be at most {} characters",
+                i, MAX_SOURCE_CHARS
+            )));
+        }
+        if document.metadata.as_ref().is_some_and(|m| !m.is_object()) {
+            return Err(ApiError::ValidationError(format!("documents[{}].metadata must be an object", i)));
+        }
+    }
+    Ok(())
+}
+
+/// Embed and store documents. Every document is embedded before any is stored, so a provider
+/// failure stores nothing.
+pub async fn index(
+    pool: &PgPool,
+    ai: &AIService,
+    user_id: Uuid,
+    request: &IndexRequest,
+) -> ApiResult<Vec<IndexedDocument>> {
+    validate(request)?;
+    let collection = validate_collection(request.collection.as_deref())?;
+    let model = ai.embedding_model()?;
+
+    let mut vectors = Vec::with_capacity(request.documents.len());
+    for document in &request.documents {
+        vectors.push(embed(ai, document.content.trim()).await?);
+    }
+
+    let mut tx = pool.begin().await?;
+    let mut indexed = Vec::with_capacity(vectors.len());
+    for (document, vector) in request.documents.iter().zip(vectors) {
+        let embedding = NewEmbedding {
+            collection: &collection,
+            document_id: None,
+            chunk_index: None,
+            source: document.source.as_deref(),
+            content: document.content.trim(),
+            metadata: document.metadata.clone().unwrap_or_else(|| serde_json::json!({})),
+        };
+        indexed.push(insert(&mut tx, user_id, &model, &embedding, vector).await?);
+    }
+    tx.commit().await?;
+    Ok(indexed)
+}
+
+/// The user's documents closest to `query`, most similar first
+pub async fn search(
+    pool: &PgPool,
+    ai: &AIService,
+    user_id: Uuid,
+    query: &str,
+    collection: Option<&str>,
+    limit: Option<i64>,
+) -> ApiResult<Vec<SearchResult>> {
+    let query = query.trim();
+    if query.is_empty() || query.chars().count() > MAX_QUERY_CHARS {
+        return Err(ApiError::ValidationError(format!(
+            "query must be between 1 and {} characters",
+            MAX_QUERY_CHARS
+        )));
+    }
+    let collection = match collection {
+        Some(collection) => Some(validate_collection(Some(collection))?),
+        None => None,
+    };
+    let limit = limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_SEARCH_LIMIT);
+    let model = ai.embedding_model()?;
+    let vector = ai.generate_embeddings(query).await?;
+
+    let results = sqlx::query_as::<_, SearchResult>(
+        "SELECT id, collection, document_id, chunk_index, source, content, metadata, \
+         (1 - (embedding <=> $1::vector))::DOUBLE PRECISION AS score, created_at \
+         FROM embeddings WHERE user_id = $2 AND model = $3 AND dimensions = $4 \
+         AND ($5::TEXT IS NULL OR collection = $5) \
+         ORDER BY embedding <=> $1::vector LIMIT $6",
+    )
+    .bind(vector_literal(&vector)?)
+    .bind(user_id)
+    .bind(&model)
+    .bind(vector.len() as i32)
+    .bind(collection)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::embedding::IndexDocument;
+
+    #[test]
+    fn test_vector_literal() {
+        assert_eq!(vector_literal(&[0.5, -2.0, 3.25]).unwrap(), "[0.5,-2,3.25]");
+        assert!(vector_literal(&[]).is_err());
+        assert!(vector_literal(&[1.0, f32::NAN]).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let document = |content: &str| IndexDocument { content: content.to_string(), source: None, metadata: None };
+        let request = |documents| IndexRequest { collection: None, documents };
+
+        assert!(validate(&request(vec![document("Servo calibration steps")])).is_ok());
+        assert!(validate(&request(vec![])).is_err());
+        assert!(validate(&request(vec![document("  ")])).is_err());
+        assert!(validate(&request(vec![document(&"x".repeat(MAX_CONTENT_CHARS + 1))])).is_err());
+
+        let mut with_metadata = document("text");
+        with_metadata.metadata = Some(serde_json::json!(["not", "an", "object"]));
+        assert!(validate(&request(vec![with_metadata])).is_err());
+
+        assert_eq!(validate_collection(None).unwrap(), DEFAULT_COLLECTION);
+        assert_eq!(validate_collection(Some(" manuals ")).unwrap(), "manuals");
+    }
+}
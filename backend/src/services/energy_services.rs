@@ -0,0 +1,315 @@
+//! Energy used by device commands and what recharging it costs, for planning charging schedules.
+//!
+//! Command drain is recorded as a percentage of the battery, so it is converted to grid energy
+//! through the device's battery capacity and the charger's efficiency.
+
+use chrono::{DateTime, NaiveDate, Timelike, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+
+/// Share of grid energy that ends up in the battery
+pub const CHARGING_EFFICIENCY: f64 = 0.9;
+pub const DEFAULT_PRICE_PER_KWH: f64 = 0.15;
+pub const DEFAULT_CURRENCY: &str = "USD";
+/// Length of the suggested charging window, in hours
+pub const CHARGING_WINDOW_HOURS: usize = 4;
+
+/// Battery capacity assumed per device type when metadata does not say
+fn default_capacity_wh(device_type: &str) -> f64 {
+    match device_type {
+        "drone" => 100.0,
+        "robot" => 500.0,
+        "rover" => 1000.0,
+        _ => 250.0,
+    }
+}
+
+/// Capacity from `metadata.battery_capacity_wh`, falling back to the device type's default
+pub fn battery_capacity_wh(device_type: &str, metadata: &Value) -> f64 {
+    metadata
+        .get("battery_capacity_wh")
+        .and_then(Value::as_f64)
+        .filter(|wh| *wh > 0.0)
+        .unwrap_or_else(|| default_capacity_wh(device_type))
+}
+
+/// Grid energy needed to put back `drain_percent` of a `capacity_wh` battery
+pub fn drain_to_kwh(drain_percent: f64, capacity_wh: f64) -> f64 {
+    drain_percent / 100.0 * capacity_wh / 1000.0 / CHARGING_EFFICIENCY
+}
+
+pub fn validate_tariff(price_per_kwh: f64, currency: &str) -> ApiResult<()> {
+    if !price_per_kwh.is_finite() || !(0.0..=100.0).contains(&price_per_kwh) {
+        return Err(ApiError::ValidationError("price_per_kwh must be between 0 and 100".to_string()));
+    }
+    if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(ApiError::ValidationError("currency must be an ISO 4217 code, e.g. USD".to_string()));
+    }
+    Ok(())
+}
+
+/// Commands of one kind completed within one hour
+#[derive(Debug, Clone, FromRow)]
+pub struct EnergyBucket {
+    pub command: String,
+    pub hour: DateTime<Utc>,
+    pub commands: i64,
+    /// Commands that reported their actual drain; the rest count at their estimate
+    pub measured: i64,
+    pub drain_percent: f64,
+    pub estimated_drain_percent: f64,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct CommandEnergy {
+    pub command: String,
+    pub commands: i64,
+    pub drain_percent: f64,
+    pub avg_drain_percent: f64,
+    /// Actual drain over the estimator's figure; above 1.0 the estimate is optimistic
+    pub drain_vs_estimate: Option<f64>,
+    pub energy_kwh: f64,
+    pub cost: f64,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct DailyEnergy {
+    pub day: NaiveDate,
+    pub commands: i64,
+    pub drain_percent: f64,
+    pub energy_kwh: f64,
+    pub cost: f64,
+}
+
+/// The quietest stretch of the day, when charging interrupts the least work
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ChargingWindow {
+    /// UTC hour the window starts at; it may wrap past midnight
+    pub start_hour: u32,
+    pub hours: usize,
+    /// Share of the period's drain that fell inside the window
+    pub drain_share: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EnergyReport {
+    pub device_id: Uuid,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub battery_capacity_wh: f64,
+    pub charging_efficiency: f64,
+    pub price_per_kwh: f64,
+    pub currency: String,
+    pub commands: i64,
+    /// Share of commands whose drain was measured rather than estimated
+    pub measured_share: f64,
+    pub drain_percent: f64,
+    pub energy_kwh: f64,
+    pub cost: f64,
+    pub avg_daily_cost: f64,
+    pub by_command: Vec<CommandEnergy>,
+    pub by_day: Vec<DailyEnergy>,
+    /// Drain per UTC hour of day, index 0 is 00:00-01:00
+    pub hourly_drain_percent: Vec<f64>,
+    pub charging_window: Option<ChargingWindow>,
+}
+
+fn round(value: f64, places: i32) -> f64 {
+    let factor = 10f64.powi(places);
+    (value * factor).round() / factor
+}
+
+/// The `hours`-long window (wrapping midnight) with the least drain; earliest start wins ties
+pub fn quietest_window(hourly: &[f64; 24], hours: usize) -> Option<ChargingWindow> {
+    let total: f64 = hourly.iter().sum();
+    if total <= 0.0 || hours == 0 || hours >= 24 {
+        return None;
+    }
+    let (start_hour, drain) = (0..24)
+        .map(|start| (start, (start..start + hours).map(|h| hourly[h % 24]).sum::<f64>()))
+        .fold((0, f64::INFINITY), |best, candidate| if candidate.1 < best.1 { candidate } else { best });
+    Some(ChargingWindow { start_hour: start_hour as u32, hours, drain_share: round(drain / total, 3) })
+}
+
+/// Roll hourly buckets up per command, per day and per hour of day, priced at the tariff
+pub fn build_report(
+    device_id: Uuid,
+    (from, to): (DateTime<Utc>, DateTime<Utc>),
+    buckets: &[EnergyBucket],
+    capacity_wh: f64,
+    price_per_kwh: f64,
+    currency: &str,
+) -> EnergyReport {
+    let kwh = |drain: f64| drain_to_kwh(drain, capacity_wh);
+    let mut by_command: BTreeMap<&str, (i64, f64, f64, f64)> = BTreeMap::new();
+    let mut by_day: BTreeMap<NaiveDate, (i64, f64)> = BTreeMap::new();
+    let mut hourly = [0.0_f64; 24];
+    let (mut commands, mut measured, mut drain) = (0, 0, 0.0);
+
+    for bucket in buckets {
+        let entry = by_command.entry(&bucket.command).or_default();
+        entry.0 += bucket.commands;
+        entry.1 += bucket.drain_percent;
+        // Only buckets where every command was measured say anything about the estimator
+        if bucket.measured == bucket.commands {
+            entry.2 += bucket.drain_percent;
+            entry.3 += bucket.estimated_drain_percent;
+        }
+        let day = by_day.entry(bucket.hour.date_naive()).or_default();
+        day.0 += bucket.commands;
+        day.1 += bucket.drain_percent;
+        hourly[bucket.hour.hour() as usize] += bucket.drain_percent;
+        commands += bucket.commands;
+        measured += bucket.measured;
+        drain += bucket.drain_percent;
+    }
+
+    let mut by_command: Vec<CommandEnergy> = by_command
+        .into_iter()
+        .map(|(command, (count, drain, measured_drain, estimated))| CommandEnergy {
+            command: command.to_string(),
+            commands: count,
+            drain_percent: round(drain, 2),
+            avg_drain_percent: round(drain / count.max(1) as f64, 3),
+            drain_vs_estimate: (estimated > 0.0 && measured_drain > 0.0).then(|| round(measured_drain / estimated, 3)),
+            energy_kwh: round(kwh(drain), 4),
+            cost: round(kwh(drain) * price_per_kwh, 4),
+        })
+        .collect();
+    by_command.sort_by(|a, b| b.energy_kwh.total_cmp(&a.energy_kwh).then_with(|| a.command.cmp(&b.command)));
+
+    let by_day: Vec<DailyEnergy> = by_day
+        .into_iter()
+        .map(|(day, (count, drain))| DailyEnergy {
+            day,
+            commands: count,
+            drain_percent: round(drain, 2),
+            energy_kwh: round(kwh(drain), 4),
+            cost: round(kwh(drain) * price_per_kwh, 4),
+        })
+        .collect();
+
+    let days = ((to - from).num_seconds() as f64 / 86_400.0).max(1.0);
+    EnergyReport {
+        device_id,
+        from,
+        to,
+        battery_capacity_wh: capacity_wh,
+        charging_efficiency: CHARGING_EFFICIENCY,
+        price_per_kwh,
+        currency: currency.to_string(),
+        commands,
+        measured_share: if commands > 0 { round(measured as f64 / commands as f64, 3) } else { 0.0 },
+        drain_percent: round(drain, 2),
+        energy_kwh: round(kwh(drain), 4),
+        cost: round(kwh(drain) * price_per_kwh, 4),
+        avg_daily_cost: round(kwh(drain) * price_per_kwh / days, 4),
+        by_command,
+        by_day,
+        hourly_drain_percent: hourly.iter().map(|d| round(*d, 2)).collect(),
+        charging_window: quietest_window(&hourly, CHARGING_WINDOW_HOURS),
+    }
+}
+
+/// Hourly drain of commands the device completed in `[from, to)`. Cancelled and unacknowledged
+/// commands never ran; the rest count at their reported drain, or the estimate without one.
+pub async fn energy_buckets(
+    pool: &PgPool,
+    device_id: Uuid,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> ApiResult<Vec<EnergyBucket>> {
+    let buckets = sqlx::query_as::<_, EnergyBucket>(
+        "SELECT command, date_trunc('hour', acked_at) AS hour, COUNT(*) AS commands, \
+                COUNT(actual_battery_drain) AS measured, \
+                COALESCE(SUM(COALESCE(actual_battery_drain, estimated_battery_drain)), 0)::float8 AS drain_percent, \
+                COALESCE(SUM(estimated_battery_drain), 0)::float8 AS estimated_drain_percent \
+         FROM device_commands \
+         WHERE device_id = $1 AND status IN ('succeeded', 'failed') AND acked_at >= $2 AND acked_at < $3 \
+         GROUP BY command, date_trunc('hour', acked_at) \
+         ORDER BY hour",
+    )
+    .bind(device_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+    Ok(buckets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    fn bucket(command: &str, hour: DateTime<Utc>, counts: (i64, i64), drain: f64, estimated: f64) -> EnergyBucket {
+        EnergyBucket {
+            command: command.to_string(),
+            hour,
+            commands: counts.0,
+            measured: counts.1,
+            drain_percent: drain,
+            estimated_drain_percent: estimated,
+        }
+    }
+
+    #[test]
+    fn test_capacity_and_conversion() {
+        assert_eq!(battery_capacity_wh("rover", &json!({})), 1000.0);
+        assert_eq!(battery_capacity_wh("rover", &json!({ "battery_capacity_wh": 720 })), 720.0);
+        assert_eq!(battery_capacity_wh("drone", &json!({ "battery_capacity_wh": -5 })), 100.0);
+        // 45% of a 1 kWh pack is 0.45 kWh in the battery, 0.5 kWh from the grid
+        assert!((drain_to_kwh(45.0, 1000.0) - 0.5).abs() < 1e-9);
+
+        assert!(validate_tariff(0.3, "EUR").is_ok());
+        assert!(validate_tariff(-1.0, "EUR").is_err());
+        assert!(validate_tariff(0.3, "eur").is_err());
+    }
+
+    #[test]
+    fn test_quietest_window_wraps_midnight() {
+        let mut hourly = [5.0; 24];
+        for h in [22, 23, 0, 1] {
+            hourly[h] = 0.0;
+        }
+        let window = quietest_window(&hourly, 4).unwrap();
+        assert_eq!(window.start_hour, 22);
+        assert_eq!(window.drain_share, 0.0);
+        assert!(quietest_window(&[0.0; 24], 4).is_none());
+    }
+
+    #[test]
+    fn test_build_report() {
+        let from = Utc.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2026, 10, 3, 0, 0, 0).unwrap();
+        let buckets = [
+            bucket("move", Utc.with_ymd_and_hms(2026, 10, 1, 9, 0, 0).unwrap(), (4, 4), 18.0, 12.0),
+            bucket("move", Utc.with_ymd_and_hms(2026, 10, 2, 14, 0, 0).unwrap(), (2, 1), 9.0, 6.0),
+            bucket("scan", Utc.with_ymd_and_hms(2026, 10, 2, 9, 0, 0).unwrap(), (3, 0), 0.9, 0.9),
+        ];
+        let report = build_report(Uuid::nil(), (from, to), &buckets, 1000.0, 0.2, "USD");
+
+        assert_eq!(report.commands, 9);
+        assert_eq!(report.measured_share, 0.556);
+        assert_eq!(report.drain_percent, 27.9);
+        assert_eq!(report.energy_kwh, 0.31);
+        assert_eq!(report.cost, 0.062);
+        assert_eq!(report.avg_daily_cost, 0.031);
+
+        assert_eq!(report.by_command[0].command, "move");
+        assert_eq!(report.by_command[0].avg_drain_percent, 4.5);
+        // Only the fully measured bucket is compared with its estimate
+        assert_eq!(report.by_command[0].drain_vs_estimate, Some(1.5));
+        assert_eq!(report.by_command[1].drain_vs_estimate, None);
+
+        assert_eq!(report.by_day.len(), 2);
+        assert_eq!(report.by_day[1].commands, 5);
+        assert_eq!(report.hourly_drain_percent[9], 18.9);
+        assert_eq!(report.charging_window.unwrap().start_hour, 0);
+    }
+}
@@ -0,0 +1,198 @@
+//! ENS names on Ethereum mainnet: forward resolution of `name.eth` to an address, and the
+//! primary name of a linked wallet by reverse lookup. A reverse record is only trusted when the
+//! name resolves back to the same address, since anyone can claim any name in their own
+//! reverse record. Names are normalized by case folding only, a subset of ENSIP-15.
+
+use chrono::{Duration, Utc};
+use sha3::{Digest, Keccak256};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::chains::ETHEREUM_CHAIN_ID;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::services::crypto_services::{decode_abi_string, BlockchainService};
+use crate::services::relayer_services::selector;
+
+/// The ENS registry, at the same address on mainnet and its testnets
+const ENS_REGISTRY: &str = "0x00000000000c2e074ec69a0dfb2997ba6c7d2e1e";
+const RESOLVER_SIGNATURE: &str = "resolver(bytes32)";
+const ADDR_SIGNATURE: &str = "addr(bytes32)";
+const NAME_SIGNATURE: &str = "name(bytes32)";
+const MAX_NAME_BYTES: usize = 255;
+/// How long a wallet's cached name is used before it is looked up again
+const CACHE_HOURS: i64 = 24;
+
+/// Whether `input` is meant as an ENS name rather than a hex address
+pub fn is_ens_name(input: &str) -> bool {
+    let input = input.trim();
+    !input.starts_with("0x") && input.contains('.')
+}
+
+/// A name lower-cased, with every label non-empty and made of letters, digits and hyphens
+pub fn normalize(name: &str) -> ApiResult<String> {
+    let name = name.trim().to_lowercase();
+    let valid_label = |label: &str| !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '-');
+    if name.len() > MAX_NAME_BYTES || !name.split('.').all(valid_label) {
+        return Err(ApiError::ValidationError(format!("Invalid ENS name: {}", name)));
+    }
+    Ok(name)
+}
+
+/// EIP-137 namehash of a normalized name
+pub fn namehash(name: &str) -> [u8; 32] {
+    let mut node = [0u8; 32];
+    if name.is_empty() {
+        return node;
+    }
+    for label in name.rsplit('.') {
+        let mut hasher = Keccak256::new();
+        hasher.update(node);
+        hasher.update(Keccak256::digest(label.as_bytes()));
+        node = hasher.finalize().into();
+    }
+    node
+}
+
+fn mainnet(config: &AppConfig) -> ApiResult<BlockchainService> {
+    let chain = BlockchainService::for_chain_id(config, ETHEREUM_CHAIN_ID)?;
+    if !chain.has_provider() {
+        return Err(ApiError::ServiceUnavailable("ENS needs an Ethereum mainnet RPC provider".to_string()));
+    }
+    Ok(chain)
+}
+
+/// Call `signature` with `node` on `contract`
+async fn call_with_node(
+    chain: &BlockchainService,
+    contract: &str,
+    signature: &str,
+    node: [u8; 32],
+) -> ApiResult<Vec<u8>> {
+    let mut data = selector(signature).to_vec();
+    data.extend_from_slice(&node);
+    chain.eth_call(contract, &format!("0x{}", hex::encode(data))).await
+}
+
+/// The address in a returned word, `None` when it is zero
+fn word_address(word: &[u8]) -> Option<String> {
+    let word = word.get(..32)?;
+    (word[12..].iter().any(|b| *b != 0)).then(|| format!("0x{}", hex::encode(&word[12..])))
+}
+
+async fn resolver(chain: &BlockchainService, node: [u8; 32]) -> ApiResult<Option<String>> {
+    let result = call_with_node(chain, ENS_REGISTRY, RESOLVER_SIGNATURE, node).await?;
+    Ok(word_address(&result))
+}
+
+/// The address `name` resolves to, lower-case
+async fn resolve_on(chain: &BlockchainService, name: &str) -> ApiResult<Option<String>> {
+    let node = namehash(name);
+    let Some(resolver) = resolver(chain, node).await? else {
+        return Ok(None);
+    };
+    let result = call_with_node(chain, &resolver, ADDR_SIGNATURE, node).await?;
+    Ok(word_address(&result))
+}
+
+/// The address `name` resolves to, lower-case; not found when it resolves to none
+pub async fn resolve(config: &AppConfig, name: &str) -> ApiResult<String> {
+    let name = normalize(name)?;
+    let chain = mainnet(config)?;
+    resolve_on(&chain, &name)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("{} does not resolve to an address", name)))
+}
+
+/// The primary name of `address`, if its reverse record names one that resolves back to it
+pub async fn reverse(config: &AppConfig, address: &str) -> ApiResult<Option<String>> {
+    BlockchainService::validate_address(address)?;
+    let chain = mainnet(config)?;
+    let address = address.to_ascii_lowercase();
+    let node = namehash(&format!("{}.addr.reverse", &address[2..]));
+    let Some(resolver) = resolver(&chain, node).await? else {
+        return Ok(None);
+    };
+    let result = call_with_node(&chain, &resolver, NAME_SIGNATURE, node).await?;
+    let Some(name) = decode_abi_string(&result).and_then(|name| normalize(&name).ok()) else {
+        return Ok(None);
+    };
+    let forward = resolve_on(&chain, &name).await?;
+    Ok(forward.filter(|forward| *forward == address).map(|_| name))
+}
+
+/// Look up and cache the ENS name of the user's linked wallet unless it was looked up recently
+pub async fn refresh_ens_name(pool: &PgPool, config: &AppConfig, user_id: Uuid) -> ApiResult<()> {
+    let (wallet, fresh): (Option<String>, bool) = sqlx::query_as(
+        "SELECT wallet_address, COALESCE(ens_resolved_at > $2, FALSE) FROM users WHERE id = $1",
+    )
+    .bind(user_id)
+    .bind(Utc::now() - Duration::hours(CACHE_HOURS))
+    .fetch_one(pool)
+    .await?;
+    let Some(wallet) = wallet.filter(|_| !fresh) else {
+        return Ok(());
+    };
+    let name = reverse(config, &wallet).await?;
+    // The wallet may have been unlinked meanwhile
+    sqlx::query(
+        "UPDATE users SET ens_name = $3, ens_resolved_at = NOW() \
+         WHERE id = $1 AND LOWER(wallet_address) = LOWER($2)",
+    )
+    .bind(user_id)
+    .bind(&wallet)
+    .bind(&name)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Refresh the cached name in the background, so linking a wallet never waits on the lookup.
+/// Does nothing without a mainnet provider.
+pub fn spawn_refresh(pool: Arc<PgPool>, config: AppConfig, user_id: Uuid) {
+    if mainnet(&config).is_err() {
+        return;
+    }
+    tokio::spawn(async move {
+        if let Err(e) = refresh_ens_name(&pool, &config, user_id).await {
+            tracing::warn!(%user_id, "ENS lookup failed: {}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_namehash() {
+        assert_eq!(namehash(""), [0u8; 32]);
+        assert_eq!(
+            hex::encode(namehash("eth")),
+            "93cdeb708b7545dc668eb9280176169d1c33cfd8ed6f04690a0bcc88a93fc4ae"
+        );
+        assert_eq!(
+            hex::encode(namehash("foo.eth")),
+            "de9b09fd7c5f901e23a3f19fecc54828e9c848539801e86591bd9801b019f84f"
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(" Vitalik.ETH ").unwrap(), "vitalik.eth");
+        assert_eq!(normalize("robo-fleet.eth").unwrap(), "robo-fleet.eth");
+        assert!(normalize("bad..eth").is_err());
+        assert!(normalize("spaced name.eth").is_err());
+        assert!(is_ens_name("vitalik.eth"));
+        assert!(!is_ens_name("0xfb6916095ca1df60bb79ce92ce3ea74c37c5d359"));
+    }
+
+    #[test]
+    fn test_word_address() {
+        let mut word = [0u8; 32];
+        assert_eq!(word_address(&word), None);
+        word[31] = 0x59;
+        assert_eq!(word_address(&word).as_deref(), Some("0x0000000000000000000000000000000000000059"));
+        assert_eq!(word_address(&word[..20]), None);
+    }
+}
@@ -0,0 +1,694 @@
+//! Escrow between platform users. The buyer funds an escrow with a relayed token transfer into
+//! the relayer account, which holds the tokens until the seller fulfills; the buyer then
+//! releases them to the seller, or they are released automatically a week after fulfillment.
+//! Either party may dispute a funded escrow, and an admin resolves it by releasing or refunding.
+//! Payouts are sent by the relayer and settled by the confirmation watcher; one that fails puts
+//! the escrow in dispute so an admin can retry it.
+//!
+//! ```text
+//! awaiting_funding -> funding -> funded -> fulfilled -> released
+//!        |              |          |          |
+//!    cancelled   awaiting_funding  +-> disputed / refunded
+//! ```
+
+use chrono::{Duration, Utc};
+use sqlx::{PgConnection, PgPool};
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::escrow::{CreateEscrowRequest, Escrow, EscrowDetails, EscrowEvent, EscrowListQuery};
+use crate::models::transaction::{RelayedTransferRequest, Transaction, TransferRequest};
+use crate::models::typed_data::SignedTypedData;
+use crate::services::crypto_services::{decimal_amount, format_units, parse_units, BlockchainService};
+use crate::services::notification_services::notify_user;
+use crate::services::payment_services::{fail_transaction, TRANSACTION_COLUMNS};
+use crate::services::relayer_services::Relayer;
+use crate::services::transfer_services::{self, transfer_calldata, PreparedTransfer};
+use crate::utils::log_blockchain_event;
+
+pub const ESCROW_COLUMNS: &str = "id, buyer_id, seller_id, description, device_id, chain_id, amount, symbol, status, \
+     funding_transaction_id, payout_transaction_id, dispute_reason, disputed_by, funded_at, fulfilled_at, \
+     auto_release_at, settled_at, created_at, updated_at";
+
+const EVENT_COLUMNS: &str = "id, escrow_id, action, from_status, to_status, actor_id, note, created_at";
+
+/// Product types of the transactions moving escrowed tokens in and out
+pub const FUNDING_PRODUCT_TYPE: &str = "escrow_funding";
+pub const PAYOUT_PRODUCT_TYPE: &str = "escrow_payout";
+
+pub const STATUSES: &[&str] =
+    &["awaiting_funding", "funding", "funded", "fulfilled", "disputed", "released", "refunded", "cancelled"];
+
+/// Days after fulfillment the buyer has to release or dispute before the escrow releases itself
+pub const AUTO_RELEASE_DAYS: i64 = 7;
+const MAX_DESCRIPTION_CHARS: usize = 2_000;
+const MAX_NOTE_CHARS: usize = 2_000;
+const JOB_INTERVAL_SECS: u64 = 300;
+/// Escrows released per run of the auto-release job
+const MAX_RELEASES_PER_RUN: i64 = 20;
+
+/// Who is acting on an escrow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Buyer,
+    Seller,
+    Admin,
+    /// The watchers and jobs
+    System,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Fund,
+    Funded,
+    FundingFailed,
+    Fulfill,
+    Release,
+    Refund,
+    Dispute,
+    Cancel,
+    PayoutFailed,
+}
+
+impl Action {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Action::Fund => "fund",
+            Action::Funded => "funded",
+            Action::FundingFailed => "funding_failed",
+            Action::Fulfill => "fulfill",
+            Action::Release => "release",
+            Action::Refund => "refund",
+            Action::Dispute => "dispute",
+            Action::Cancel => "cancel",
+            Action::PayoutFailed => "payout_failed",
+        }
+    }
+}
+
+/// The status `action` by `role` moves an escrow in `status` to, if it is allowed
+fn next_status(status: &str, action: Action, role: Role) -> Option<&'static str> {
+    use Action::*;
+    use Role::*;
+    let next = match (status, action, role) {
+        ("awaiting_funding", Fund, Buyer) => "funding",
+        ("awaiting_funding", Cancel, Buyer | Seller) => "cancelled",
+        // A funding transfer that failed can still be confirmed late
+        ("funding" | "awaiting_funding", Funded, System) => "funded",
+        ("funding", FundingFailed, System) => "awaiting_funding",
+        ("funded", Fulfill, Seller) => "fulfilled",
+        ("funded" | "fulfilled", Release, Buyer) => "released",
+        ("fulfilled", Release, System) => "released",
+        ("funded" | "fulfilled", Refund, Seller) => "refunded",
+        ("funded" | "fulfilled", Dispute, Buyer | Seller) => "disputed",
+        ("disputed", Release, Admin) => "released",
+        ("disputed", Refund, Admin) => "refunded",
+        ("released" | "refunded", PayoutFailed, System) => "disputed",
+        _ => return None,
+    };
+    Some(next)
+}
+
+/// The status `action` by `role` moves an escrow in `status` to. Forbidden when another party
+/// could take the action, a conflict when nobody can in this status.
+pub fn transition(status: &str, action: Action, role: Role) -> ApiResult<&'static str> {
+    if let Some(next) = next_status(status, action, role) {
+        return Ok(next);
+    }
+    if [Role::Buyer, Role::Seller, Role::Admin].iter().any(|r| next_status(status, action, *r).is_some()) {
+        return Err(ApiError::Forbidden(format!("You cannot {} this escrow", action.as_str())));
+    }
+    Err(ApiError::Conflict(format!("Cannot {} an escrow that is {}", action.as_str(), status.replace('_', " "))))
+}
+
+fn checked_note(note: Option<&str>) -> ApiResult<Option<String>> {
+    let note = note.map(str::trim).filter(|n| !n.is_empty());
+    if note.is_some_and(|n| n.chars().count() > MAX_NOTE_CHARS) {
+        return Err(ApiError::ValidationError(format!("note must be at most {} characters", MAX_NOTE_CHARS)));
+    }
+    Ok(note.map(str::to_string))
+}
+
+async fn record_event(
+    conn: &mut PgConnection,
+    escrow_id: Uuid,
+    action: &str,
+    from_status: Option<&str>,
+    to_status: &str,
+    actor_id: Option<Uuid>,
+    note: Option<&str>,
+) -> ApiResult<()> {
+    sqlx::query(
+        "INSERT INTO escrow_events (escrow_id, action, from_status, to_status, actor_id, note) \
+         VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(escrow_id)
+    .bind(action)
+    .bind(from_status)
+    .bind(to_status)
+    .bind(actor_id)
+    .bind(note)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Tell the parties other than `actor_id` that the escrow moved to its current status
+async fn announce(
+    conn: &mut PgConnection,
+    escrow: &Escrow,
+    actor_id: Option<Uuid>,
+    note: Option<&str>,
+) -> ApiResult<()> {
+    let status = escrow.status.replace('_', " ");
+    let mut body = format!(
+        "The escrow of {} {} for \"{}\" is now {}.",
+        escrow.amount, escrow.symbol, escrow.description, status
+    );
+    if let Some(note) = note {
+        body = format!("{} {}", body, note);
+    }
+    for user_id in [escrow.buyer_id, escrow.seller_id] {
+        if Some(user_id) == actor_id {
+            continue;
+        }
+        notify_user(
+            conn,
+            user_id,
+            &format!("escrow.{}", escrow.status),
+            &format!("Escrow {}", status),
+            &body,
+            serde_json::json!({ "escrow_id": escrow.id, "status": escrow.status }),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Lock an escrow for a state change
+async fn lock(conn: &mut PgConnection, escrow_id: Uuid) -> ApiResult<Escrow> {
+    sqlx::query_as::<_, Escrow>(&format!("SELECT {} FROM escrows WHERE id = $1 FOR UPDATE", ESCROW_COLUMNS))
+        .bind(escrow_id)
+        .fetch_optional(conn)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Escrow not found".to_string()))
+}
+
+/// Apply `action` by `role` to a locked escrow, stamping the times that go with its new status,
+/// recording the event and telling the other party
+async fn apply(
+    conn: &mut PgConnection,
+    escrow: &Escrow,
+    action: Action,
+    role: Role,
+    actor_id: Option<Uuid>,
+    note: Option<&str>,
+) -> ApiResult<Escrow> {
+    let next = transition(&escrow.status, action, role)?;
+    let updated = sqlx::query_as::<_, Escrow>(&format!(
+        "UPDATE escrows SET status = $2, updated_at = NOW(), \
+             funded_at = CASE WHEN $2 = 'funded' THEN NOW() ELSE funded_at END, \
+             fulfilled_at = CASE WHEN $2 = 'fulfilled' THEN NOW() ELSE fulfilled_at END, \
+             auto_release_at = CASE WHEN $2 = 'fulfilled' THEN $3 ELSE auto_release_at END, \
+             settled_at = CASE WHEN $2 IN ('released', 'refunded', 'cancelled') THEN NOW() ELSE settled_at END, \
+             dispute_reason = CASE WHEN $2 = 'disputed' THEN $4 ELSE dispute_reason END, \
+             disputed_by = CASE WHEN $2 = 'disputed' THEN $5 ELSE disputed_by END \
+         WHERE id = $1 RETURNING {}",
+        ESCROW_COLUMNS
+    ))
+    .bind(escrow.id)
+    .bind(next)
+    .bind(Utc::now() + Duration::days(AUTO_RELEASE_DAYS))
+    .bind(note)
+    .bind(actor_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    record_event(conn, escrow.id, action.as_str(), Some(&escrow.status), next, actor_id, note).await?;
+    // Funding is announced once it settles
+    if action != Action::Fund {
+        announce(conn, &updated, actor_id, note).await?;
+    }
+    Ok(updated)
+}
+
+/// Open an escrow paying `request.amount` of the chain's token to the seller
+pub async fn create(
+    pool: &PgPool,
+    config: &AppConfig,
+    buyer_id: Uuid,
+    request: &CreateEscrowRequest,
+) -> ApiResult<Escrow> {
+    let description = request.description.trim();
+    if description.is_empty() || description.chars().count() > MAX_DESCRIPTION_CHARS {
+        return Err(ApiError::ValidationError(format!(
+            "description must be 1-{} characters",
+            MAX_DESCRIPTION_CHARS
+        )));
+    }
+    let (seller_id, seller_wallet): (Uuid, Option<String>) =
+        sqlx::query_as("SELECT id, wallet_address FROM users WHERE LOWER(email) = LOWER($1)")
+            .bind(request.seller_email.trim())
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| ApiError::NotFound("No account has that email".to_string()))?;
+    if seller_id == buyer_id {
+        return Err(ApiError::ValidationError("You cannot open an escrow with yourself".to_string()));
+    }
+    if seller_wallet.is_none() {
+        return Err(ApiError::Conflict("The seller must link a wallet to be paid through escrow".to_string()));
+    }
+    if let Some(device_id) = request.device_id {
+        let owner: Option<Uuid> = sqlx::query_scalar("SELECT user_id FROM devices WHERE id = $1")
+            .bind(device_id)
+            .fetch_optional(pool)
+            .await?;
+        if owner != Some(seller_id) {
+            return Err(ApiError::ValidationError("The device must belong to the seller".to_string()));
+        }
+    }
+
+    // The relayer holds the funds, so escrow is off without one
+    Relayer::from_config(config)?;
+    let chain_id = request.chain_id.unwrap_or(config.default_chain_id);
+    let chain = BlockchainService::for_chain_id(config, chain_id)?;
+    if !chain.has_provider() {
+        return Err(ApiError::ServiceUnavailable(format!("Chain {} has no RPC provider", chain_id)));
+    }
+    let (decimals, symbol) = chain.token_metadata(chain.token_contract()?).await?;
+    let raw = parse_units(&request.amount, decimals)?;
+    if raw == 0 {
+        return Err(ApiError::ValidationError("amount must be greater than zero".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+    let escrow = sqlx::query_as::<_, Escrow>(&format!(
+        "INSERT INTO escrows (id, buyer_id, seller_id, description, device_id, chain_id, amount, symbol) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING {}",
+        ESCROW_COLUMNS
+    ))
+    .bind(Uuid::new_v4())
+    .bind(buyer_id)
+    .bind(seller_id)
+    .bind(description)
+    .bind(request.device_id)
+    .bind(chain_id as i64)
+    .bind(format_units(&raw.to_string(), decimals))
+    .bind(&symbol)
+    .fetch_one(&mut *tx)
+    .await?;
+    record_event(&mut tx, escrow.id, "create", None, &escrow.status, Some(buyer_id), None).await?;
+    announce(&mut tx, &escrow, Some(buyer_id), None).await?;
+    tx.commit().await?;
+    Ok(escrow)
+}
+
+/// An escrow with the role the user plays in it; not found for anyone else
+pub async fn party_escrow(pool: &PgPool, escrow_id: Uuid, user_id: Uuid) -> ApiResult<(Escrow, Role)> {
+    let escrow = get_escrow(pool, escrow_id).await?;
+    let role = if escrow.buyer_id == user_id {
+        Role::Buyer
+    } else if escrow.seller_id == user_id {
+        Role::Seller
+    } else {
+        return Err(ApiError::NotFound("Escrow not found".to_string()));
+    };
+    Ok((escrow, role))
+}
+
+pub async fn get_escrow(pool: &PgPool, escrow_id: Uuid) -> ApiResult<Escrow> {
+    sqlx::query_as::<_, Escrow>(&format!("SELECT {} FROM escrows WHERE id = $1", ESCROW_COLUMNS))
+        .bind(escrow_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Escrow not found".to_string()))
+}
+
+/// An escrow with its history, oldest first
+pub async fn details(pool: &PgPool, escrow: Escrow) -> ApiResult<EscrowDetails> {
+    let events = sqlx::query_as::<_, EscrowEvent>(&format!(
+        "SELECT {} FROM escrow_events WHERE escrow_id = $1 ORDER BY id",
+        EVENT_COLUMNS
+    ))
+    .bind(escrow.id)
+    .fetch_all(pool)
+    .await?;
+    Ok(EscrowDetails { escrow, events })
+}
+
+pub fn validate_status(status: &str) -> ApiResult<()> {
+    if !STATUSES.contains(&status) {
+        return Err(ApiError::ValidationError(format!("status must be one of {}", STATUSES.join(", "))));
+    }
+    Ok(())
+}
+
+/// The user's escrows as buyer, seller or either, newest first
+pub async fn list(pool: &PgPool, user_id: Uuid, query: &EscrowListQuery) -> ApiResult<Vec<Escrow>> {
+    if let Some(status) = &query.status {
+        validate_status(status)?;
+    }
+    let party = match query.role.as_deref() {
+        None => "(buyer_id = $1 OR seller_id = $1)",
+        Some("buyer") => "buyer_id = $1",
+        Some("seller") => "seller_id = $1",
+        Some(_) => return Err(ApiError::ValidationError("role must be buyer or seller".to_string())),
+    };
+    let escrows = sqlx::query_as::<_, Escrow>(&format!(
+        "SELECT {} FROM escrows WHERE {} AND ($2::text IS NULL OR status = $2) ORDER BY created_at DESC LIMIT 100",
+        ESCROW_COLUMNS, party
+    ))
+    .bind(user_id)
+    .bind(&query.status)
+    .fetch_all(pool)
+    .await?;
+    Ok(escrows)
+}
+
+/// Escrows of every user in `status` (disputed by default), longest waiting first
+pub async fn queue(pool: &PgPool, status: Option<&str>) -> ApiResult<Vec<Escrow>> {
+    let status = status.unwrap_or("disputed");
+    validate_status(status)?;
+    let escrows = sqlx::query_as::<_, Escrow>(&format!(
+        "SELECT {} FROM escrows WHERE status = $1 ORDER BY updated_at LIMIT 200",
+        ESCROW_COLUMNS
+    ))
+    .bind(status)
+    .fetch_all(pool)
+    .await?;
+    Ok(escrows)
+}
+
+/// The transfer that funds the escrow, from the buyer's wallet to the relayer
+fn funding_transfer(config: &AppConfig, escrow: &Escrow) -> ApiResult<TransferRequest> {
+    Ok(TransferRequest {
+        to: Relayer::from_config(config)?.address().to_string(),
+        amount: escrow.amount.clone(),
+        chain_id: Some(escrow.chain_id as u64),
+    })
+}
+
+/// Check the buyer can fund the escrow and issue the message authorizing the transfer
+pub async fn prepare_funding(
+    pool: &PgPool,
+    config: &AppConfig,
+    escrow_id: Uuid,
+    buyer_id: Uuid,
+) -> ApiResult<PreparedTransfer> {
+    let (escrow, role) = party_escrow(pool, escrow_id, buyer_id).await?;
+    transition(&escrow.status, Action::Fund, role)?;
+    transfer_services::prepare(pool, config, buyer_id, &funding_transfer(config, &escrow)?).await
+}
+
+/// Relay the buyer's signed funding transfer. The escrow is funded once the chain confirms it.
+pub async fn fund(
+    pool: &PgPool,
+    config: &AppConfig,
+    escrow_id: Uuid,
+    buyer_id: Uuid,
+    request: &SignedTypedData,
+    country: Option<&str>,
+) -> ApiResult<Escrow> {
+    let (escrow, role) = party_escrow(pool, escrow_id, buyer_id).await?;
+    let transfer = RelayedTransferRequest {
+        transfer: funding_transfer(config, &escrow)?,
+        typed_data: request.typed_data.clone(),
+        signature: request.signature.clone(),
+    };
+
+    // Claimed first, so the escrow is funded at most once
+    let mut tx = pool.begin().await?;
+    let escrow = lock(&mut tx, escrow.id).await?;
+    apply(&mut tx, &escrow, Action::Fund, role, Some(buyer_id), None).await?;
+    tx.commit().await?;
+
+    let relayed = transfer_services::relay(pool, config, buyer_id, &transfer, FUNDING_PRODUCT_TYPE, country).await;
+    let transaction = match relayed {
+        Ok(transaction) => transaction,
+        Err(e) => {
+            let mut tx = pool.begin().await?;
+            let escrow = lock(&mut tx, escrow.id).await?;
+            let note = e.to_string();
+            apply(&mut tx, &escrow, Action::FundingFailed, Role::System, None, Some(&note)).await?;
+            tx.commit().await?;
+            return Err(e);
+        }
+    };
+    let escrow = sqlx::query_as::<_, Escrow>(&format!(
+        "UPDATE escrows SET funding_transaction_id = $2, updated_at = NOW() WHERE id = $1 RETURNING {}",
+        ESCROW_COLUMNS
+    ))
+    .bind(escrow.id)
+    .bind(transaction.id)
+    .fetch_one(pool)
+    .await?;
+    Ok(escrow)
+}
+
+/// Take an action that moves no tokens: fulfill, dispute or cancel. A device escrow is only
+/// fulfilled once the device is the buyer's.
+pub async fn act(
+    pool: &PgPool,
+    escrow_id: Uuid,
+    action: Action,
+    role: Role,
+    actor_id: Uuid,
+    note: Option<&str>,
+) -> ApiResult<Escrow> {
+    let note = checked_note(note)?;
+    if action == Action::Dispute && note.is_none() {
+        return Err(ApiError::ValidationError("reason is required".to_string()));
+    }
+    let mut tx = pool.begin().await?;
+    let escrow = lock(&mut tx, escrow_id).await?;
+    if let (Action::Fulfill, Some(device_id)) = (action, escrow.device_id) {
+        let owner: Option<Uuid> = sqlx::query_scalar("SELECT user_id FROM devices WHERE id = $1")
+            .bind(device_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if owner != Some(escrow.buyer_id) {
+            return Err(ApiError::Conflict(format!(
+                "Transfer the device to the buyer first (POST /api/robotics/devices/{}/transfer)",
+                device_id
+            )));
+        }
+    }
+    let escrow = apply(&mut tx, &escrow, action, role, Some(actor_id), note.as_deref()).await?;
+    tx.commit().await?;
+    Ok(escrow)
+}
+
+/// Release the tokens to the seller or refund them to the buyer, then send them from the
+/// relayer. A payout the node refuses puts the escrow in dispute and is returned as the error.
+pub async fn settle(
+    pool: &PgPool,
+    config: &AppConfig,
+    escrow_id: Uuid,
+    action: Action,
+    role: Role,
+    actor_id: Option<Uuid>,
+    note: Option<&str>,
+) -> ApiResult<Escrow> {
+    let note = checked_note(note)?;
+    let relayer = Relayer::from_config(config)?;
+    let mut tx = pool.begin().await?;
+    let escrow = lock(&mut tx, escrow_id).await?;
+    let next = transition(&escrow.status, action, role)?;
+    let recipient_id = if next == "released" { escrow.seller_id } else { escrow.buyer_id };
+    let wallet: Option<String> = sqlx::query_scalar("SELECT wallet_address FROM users WHERE id = $1")
+        .bind(recipient_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let wallet = wallet.ok_or_else(|| {
+        ApiError::Conflict(format!(
+            "The {} has no linked wallet to pay out to",
+            if recipient_id == escrow.seller_id { "seller" } else { "buyer" }
+        ))
+    })?;
+    let chain = BlockchainService::for_chain_id(config, escrow.chain_id as u64)?;
+    let token = chain.token_contract()?.to_string();
+    let (decimals, _) = chain.token_metadata(&token).await?;
+    let calldata = transfer_calldata(&wallet, parse_units(&escrow.amount, decimals)?)?;
+    let amount = decimal_amount(&escrow.amount)?;
+
+    let escrow = apply(&mut tx, &escrow, action, role, actor_id, note.as_deref()).await?;
+    let transaction = sqlx::query_as::<_, Transaction>(&format!(
+        "INSERT INTO transactions (id, user_id, amount, currency, payment_method, payment_id, status, product_type, \
+         chain_id) VALUES ($1, $2, $3, $4, 'crypto', $5, 'pending', $6, $7) RETURNING {}",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(Uuid::new_v4())
+    .bind(recipient_id)
+    .bind(amount)
+    .bind(&escrow.symbol)
+    .bind(format!("escrow_{}", escrow.id))
+    .bind(PAYOUT_PRODUCT_TYPE)
+    .bind(escrow.chain_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    let escrow = sqlx::query_as::<_, Escrow>(&format!(
+        "UPDATE escrows SET payout_transaction_id = $2 WHERE id = $1 RETURNING {}",
+        ESCROW_COLUMNS
+    ))
+    .bind(escrow.id)
+    .bind(transaction.id)
+    .fetch_one(&mut *tx)
+    .await?;
+    // Recorded before sending, so a payout the node accepted is never lost
+    tx.commit().await?;
+
+    match relayer.send(&chain, &token, calldata, 0).await {
+        Ok(tx_hash) => {
+            sqlx::query("UPDATE transactions SET blockchain_tx_hash = $2 WHERE id = $1")
+                .bind(transaction.id)
+                .bind(&tx_hash)
+                .execute(pool)
+                .await?;
+            log_blockchain_event("escrow_payout", Some(&tx_hash), escrow.amount.parse().ok(), "submitted");
+            Ok(escrow)
+        }
+        Err(e) => {
+            let mut tx = pool.begin().await?;
+            fail_transaction(&mut tx, &transaction, &e.to_string()).await?;
+            tx.commit().await?;
+            log_blockchain_event("escrow_payout", None, escrow.amount.parse().ok(), "failed");
+            Err(e)
+        }
+    }
+}
+
+/// Mark the escrow a funding transaction belongs to funded, or open for funding again when
+/// it failed; called when the watcher settles the transaction
+pub async fn funding_settled(
+    conn: &mut PgConnection,
+    transaction: &Transaction,
+    failure: Option<&str>,
+) -> ApiResult<()> {
+    let escrow_id: Option<Uuid> = sqlx::query_scalar("SELECT id FROM escrows WHERE funding_transaction_id = $1")
+        .bind(transaction.id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    let Some(escrow_id) = escrow_id else {
+        return Ok(());
+    };
+    let escrow = lock(conn, escrow_id).await?;
+    let action = if failure.is_some() { Action::FundingFailed } else { Action::Funded };
+    if next_status(&escrow.status, action, Role::System).is_none() {
+        tracing::warn!(escrow_id = %escrow.id, status = %escrow.status, "Escrow funding settled out of turn");
+        return Ok(());
+    }
+    apply(conn, &escrow, action, Role::System, None, failure).await?;
+    Ok(())
+}
+
+/// Tell the recipient their payout arrived, or put the escrow in dispute when it failed
+pub async fn payout_settled(
+    conn: &mut PgConnection,
+    transaction: &Transaction,
+    failure: Option<&str>,
+) -> ApiResult<()> {
+    let escrow_id: Option<Uuid> = sqlx::query_scalar("SELECT id FROM escrows WHERE payout_transaction_id = $1")
+        .bind(transaction.id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    let Some(escrow_id) = escrow_id else {
+        return Ok(());
+    };
+    let escrow = lock(conn, escrow_id).await?;
+    match failure {
+        None => {
+            notify_user(
+                conn,
+                transaction.user_id,
+                "escrow.paid_out",
+                "Escrow paid out",
+                &format!("{} {} from escrow reached your wallet.", escrow.amount, escrow.symbol),
+                serde_json::json!({ "escrow_id": escrow.id, "transaction_id": transaction.id }),
+            )
+            .await
+        }
+        Some(reason) if next_status(&escrow.status, Action::PayoutFailed, Role::System).is_some() => {
+            let note = format!("The payout failed and awaits an admin: {}", reason);
+            apply(conn, &escrow, Action::PayoutFailed, Role::System, None, Some(&note)).await?;
+            Ok(())
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+/// Release fulfilled escrows the buyer left alone past their auto-release time
+pub async fn auto_release(pool: &PgPool, config: &AppConfig) -> ApiResult<usize> {
+    let due: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM escrows WHERE status = 'fulfilled' AND auto_release_at <= NOW() \
+         ORDER BY auto_release_at LIMIT $1",
+    )
+    .bind(MAX_RELEASES_PER_RUN)
+    .fetch_all(pool)
+    .await?;
+    let mut released = 0;
+    for escrow_id in due {
+        let note = "Released automatically: the buyer neither released nor disputed in time.";
+        match settle(pool, config, escrow_id, Action::Release, Role::System, None, Some(note)).await {
+            Ok(_) => released += 1,
+            Err(e) => tracing::warn!(%escrow_id, "Escrow auto-release failed: {}", e),
+        }
+    }
+    Ok(released)
+}
+
+pub fn spawn_auto_release_job(pool: Arc<PgPool>, config: AppConfig) {
+    if config.relayer_private_key.is_none() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(JOB_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            match auto_release(&pool, &config).await {
+                Ok(0) => {}
+                Ok(released) => tracing::info!(released, "Released escrows automatically"),
+                Err(e) => tracing::error!("Escrow auto-release job failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transition() {
+        assert_eq!(transition("awaiting_funding", Action::Fund, Role::Buyer).unwrap(), "funding");
+        assert_eq!(transition("funding", Action::Funded, Role::System).unwrap(), "funded");
+        assert_eq!(transition("funding", Action::FundingFailed, Role::System).unwrap(), "awaiting_funding");
+        assert_eq!(transition("funded", Action::Fulfill, Role::Seller).unwrap(), "fulfilled");
+        assert_eq!(transition("fulfilled", Action::Release, Role::Buyer).unwrap(), "released");
+        assert_eq!(transition("fulfilled", Action::Release, Role::System).unwrap(), "released");
+        assert_eq!(transition("fulfilled", Action::Dispute, Role::Seller).unwrap(), "disputed");
+        assert_eq!(transition("disputed", Action::Refund, Role::Admin).unwrap(), "refunded");
+        assert_eq!(transition("released", Action::PayoutFailed, Role::System).unwrap(), "disputed");
+        assert_eq!(transition("awaiting_funding", Action::Cancel, Role::Seller).unwrap(), "cancelled");
+    }
+
+    #[test]
+    fn test_transition_refusals() {
+        // Allowed for someone else in this status
+        assert!(matches!(transition("funded", Action::Release, Role::Seller), Err(ApiError::Forbidden(_))));
+        assert!(matches!(transition("funded", Action::Fulfill, Role::Buyer), Err(ApiError::Forbidden(_))));
+        assert!(matches!(transition("disputed", Action::Release, Role::Buyer), Err(ApiError::Forbidden(_))));
+        // Allowed for nobody
+        assert!(matches!(transition("funded", Action::Cancel, Role::Buyer), Err(ApiError::Conflict(_))));
+        assert!(matches!(transition("released", Action::Dispute, Role::Buyer), Err(ApiError::Conflict(_))));
+        assert!(matches!(transition("awaiting_funding", Action::Release, Role::Admin), Err(ApiError::Conflict(_))));
+        // Only the auto-release job releases without the buyer, and only after fulfillment
+        assert!(transition("funded", Action::Release, Role::System).is_err());
+    }
+
+    #[test]
+    fn test_checked_note() {
+        assert_eq!(checked_note(Some("  ")).unwrap(), None);
+        assert_eq!(checked_note(Some(" late ")).unwrap().as_deref(), Some("late"));
+        assert!(checked_note(Some(&"x".repeat(MAX_NOTE_CHARS + 1))).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+//! Cost estimates of chat requests before they are sent, so a user can see what an expensive
+//! model would cost first. Prompts are tokenized locally with the tokenizers OpenAI models use;
+//! other providers' tokenizers are not public, so their counts are approximated with
+//! cl100k_base. Every model the user can chat with is priced for the prompt and the longest
+//! answer allowed, and checked against what is left of the month's budget.
+
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+use tiktoken_rs::CoreBPE;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::services::ai_provider_services::ProviderKind;
+use crate::services::ai_routing_services::{budget, cost_usd, model_price, MODEL_PRICES, UNPRICED_MODEL};
+use crate::services::ai_services::{AIService, ChatMessage, DEFAULT_MAX_TOKENS};
+use crate::services::fine_tune_services::{self, FINE_TUNED_PREFIX};
+
+/// About 100k tokens, more than most models' context
+const MAX_PROMPT_CHARS: usize = 400_000;
+/// Tokens OpenAI adds around each message, and to prime the answer
+const MESSAGE_OVERHEAD_TOKENS: u32 = 3;
+const REPLY_PRIMING_TOKENS: u32 = 3;
+
+#[derive(Debug, Deserialize)]
+pub struct EstimateRequest {
+    pub messages: Vec<ChatMessage>,
+    /// Answer length to price; the chat default when left out
+    pub max_tokens: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModelEstimate {
+    pub provider: String,
+    pub model: String,
+    /// The tokenizer the prompt was counted with
+    pub tokenizer: &'static str,
+    /// Whether that is the model's own tokenizer rather than an approximation
+    pub exact: bool,
+    pub prompt_tokens: u32,
+    pub max_completion_tokens: u32,
+    pub estimated_usd: f64,
+    /// Whether the model's own rates were used, rather than the most expensive model's for a
+    /// model (or fine-tuned base model) we have no price for
+    pub priced: bool,
+    /// Whether the estimate fits in what is left of the month's budget
+    pub within_budget: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EstimateResponse {
+    /// Cheapest first
+    pub estimates: Vec<ModelEstimate>,
+    pub remaining_usd: f64,
+}
+
+/// The tokenizer a model counts with, and whether it is the model's own
+fn tokenizer(model: &str) -> (Tokenizer, bool) {
+    let base = match model.strip_prefix(FINE_TUNED_PREFIX) {
+        Some(rest) => rest.split(':').next().unwrap_or_default(),
+        None => model,
+    };
+    match get_tokenizer(base) {
+        Some(tokenizer @ (Tokenizer::O200kBase | Tokenizer::Cl100kBase)) => (tokenizer, true),
+        _ => (Tokenizer::Cl100kBase, false),
+    }
+}
+
+fn tokenizer_name(tokenizer: Tokenizer) -> &'static str {
+    match tokenizer {
+        Tokenizer::O200kBase => "o200k_base",
+        _ => "cl100k_base",
+    }
+}
+
+/// Prompt tokens of `messages` as OpenAI counts them: each message's role and content with its
+/// overhead, and the answer's priming
+pub fn count_prompt_tokens(bpe: &CoreBPE, messages: &[ChatMessage]) -> u32 {
+    let tokens: usize = messages
+        .iter()
+        .map(|m| {
+            MESSAGE_OVERHEAD_TOKENS as usize
+                + bpe.encode_with_special_tokens(&m.role).len()
+                + bpe.encode_with_special_tokens(&m.content).len()
+        })
+        .sum();
+    (tokens + REPLY_PRIMING_TOKENS as usize).min(u32::MAX as usize) as u32
+}
+
+/// Price each of `models`, given as provider and model, cheapest first
+pub fn estimate_models(
+    models: &[(ProviderKind, String)],
+    messages: &[ChatMessage],
+    max_tokens: u32,
+    remaining_usd: f64,
+) -> Vec<ModelEstimate> {
+    // Each tokenizer is run once, however many models share it
+    let mut counts: Vec<(Tokenizer, u32)> = Vec::new();
+    let mut estimates: Vec<ModelEstimate> = models
+        .iter()
+        .map(|(kind, model)| {
+            let (tokenizer, exact) = tokenizer(model);
+            let prompt_tokens = match counts.iter().find(|(t, _)| *t == tokenizer) {
+                Some((_, count)) => *count,
+                None => {
+                    let bpe = match tokenizer {
+                        Tokenizer::O200kBase => tiktoken_rs::o200k_base_singleton(),
+                        _ => tiktoken_rs::cl100k_base_singleton(),
+                    };
+                    let count = count_prompt_tokens(&bpe.lock(), messages);
+                    counts.push((tokenizer, count));
+                    count
+                }
+            };
+            // Fine-tuned models are priced at their base model's fine-tuned rates
+            let price = model_price(model);
+            let priced = kind.is_self_hosted() || price.model != UNPRICED_MODEL;
+            let estimated_usd = if kind.is_self_hosted() { 0.0 } else { cost_usd(&price, prompt_tokens, max_tokens) };
+            ModelEstimate {
+                provider: kind.as_str().to_string(),
+                model: model.clone(),
+                tokenizer: tokenizer_name(tokenizer),
+                exact: exact && !kind.is_self_hosted(),
+                prompt_tokens,
+                max_completion_tokens: max_tokens,
+                estimated_usd,
+                priced,
+                within_budget: kind.is_self_hosted() || estimated_usd <= remaining_usd,
+            }
+        })
+        .collect();
+    estimates.sort_by(|a, b| a.estimated_usd.total_cmp(&b.estimated_usd));
+    estimates
+}
+
+/// The models the user can chat with: the priced models of the configured providers, each
+/// provider's default model, and the user's fine-tuned models
+async fn available_models(pool: &PgPool, ai: &AIService, user_id: Uuid) -> ApiResult<Vec<(ProviderKind, String)>> {
+    let configured: Vec<ProviderKind> = ai.providers().map(|p| p.kind()).collect();
+    let mut models: Vec<(ProviderKind, String)> = MODEL_PRICES
+        .iter()
+        .map(|p| {
+            let kind = if p.model.starts_with("claude") { ProviderKind::Anthropic } else { ProviderKind::OpenAi };
+            (kind, p.model.to_string())
+        })
+        .filter(|(kind, _)| configured.contains(kind))
+        .collect();
+    for provider in ai.providers() {
+        let model = (provider.kind(), provider.default_model().to_string());
+        if !models.contains(&model) {
+            models.push(model);
+        }
+    }
+    if configured.contains(&ProviderKind::OpenAi) {
+        let fine_tuned = fine_tune_services::fine_tuned_models(pool, user_id).await?;
+        models.extend(fine_tuned.into_iter().map(|model| (ProviderKind::OpenAi, model)));
+    }
+    Ok(models)
+}
+
+/// What a chat request would cost on each model available to the user
+pub async fn estimate(
+    pool: &PgPool,
+    config: &AppConfig,
+    ai: &AIService,
+    user_id: Uuid,
+    request: EstimateRequest,
+) -> ApiResult<EstimateResponse> {
+    if request.messages.is_empty() {
+        return Err(ApiError::ValidationError("messages must not be empty".to_string()));
+    }
+    let chars: usize = request.messages.iter().map(|m| m.role.len() + m.content.len()).sum();
+    if chars > MAX_PROMPT_CHARS {
+        return Err(ApiError::ValidationError(format!("messages must be at most {} characters", MAX_PROMPT_CHARS)));
+    }
+    let max_tokens = request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS);
+    let models = available_models(pool, ai, user_id).await?;
+    let remaining_usd = budget(pool, config, user_id).await?.remaining_usd;
+
+    // Tokenizing is CPU-bound, and loading a tokenizer the first time takes a moment
+    let messages = request.messages;
+    let estimates =
+        tokio::task::spawn_blocking(move || estimate_models(&models, &messages, max_tokens, remaining_usd))
+            .await
+            .map_err(|e| ApiError::InternalError(format!("Cost estimate failed: {}", e)))?;
+    Ok(EstimateResponse { estimates, remaining_usd })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages() -> Vec<ChatMessage> {
+        vec![
+            ChatMessage { role: "system".to_string(), content: "You are a helpful assistant.".to_string() },
+            ChatMessage { role: "user".to_string(), content: "Hello, world!".to_string() },
+        ]
+    }
+
+    #[test]
+    fn test_count_prompt_tokens() {
+        let bpe = tiktoken_rs::cl100k_base().unwrap();
+        // system (1) + 6 content tokens, user (1) + 4, with 3 overhead each and 3 for priming
+        assert_eq!(count_prompt_tokens(&bpe, &messages()), 7 + 3 + 5 + 3 + 3);
+        assert_eq!(count_prompt_tokens(&bpe, &[]), REPLY_PRIMING_TOKENS);
+    }
+
+    #[test]
+    fn test_estimate_models() {
+        let models = [
+            (ProviderKind::OpenAi, "gpt-4".to_string()),
+            (ProviderKind::OpenAi, "gpt-4o-mini".to_string()),
+            (ProviderKind::Anthropic, "claude-3-5-haiku-latest".to_string()),
+            (ProviderKind::Ollama, "llama3.1".to_string()),
+        ];
+        let estimates = estimate_models(&models, &messages(), 1_000, 0.05);
+        let names: Vec<&str> = estimates.iter().map(|e| e.model.as_str()).collect();
+        assert_eq!(names, ["llama3.1", "gpt-4o-mini", "claude-3-5-haiku-latest", "gpt-4"]);
+
+        let gpt4 = &estimates[3];
+        assert_eq!((gpt4.tokenizer, gpt4.exact, gpt4.prompt_tokens), ("cl100k_base", true, 21));
+        assert!((gpt4.estimated_usd - (21.0 * 30.0 + 1_000.0 * 60.0) / 1_000_000.0).abs() < 1e-12);
+        assert!(!gpt4.within_budget);
+        assert_eq!((estimates[1].tokenizer, estimates[1].exact), ("o200k_base", true));
+        assert!(!estimates[2].exact);
+        assert!(estimates[0].within_budget && estimates[0].estimated_usd == 0.0);
+        assert!(estimates.iter().all(|e| e.priced));
+    }
+
+    #[test]
+    fn test_estimate_fine_tuned_models() {
+        let models = [
+            (ProviderKind::OpenAi, "ft:gpt-4o-mini-2024-07-18:acme::9abcDEF".to_string()),
+            (ProviderKind::OpenAi, "ft:davinci-002:acme::9abcDEF".to_string()),
+        ];
+        let estimates = estimate_models(&models, &messages(), 1_000, 1.0);
+
+        // Priced at gpt-4o-mini's fine-tuned rates, not its base rates or the default
+        let mini = &estimates[0];
+        assert_eq!((mini.tokenizer, mini.exact, mini.priced), ("o200k_base", true, true));
+        let expected = (mini.prompt_tokens as f64 * 0.3 + 1_000.0 * 1.2) / 1_000_000.0;
+        assert!((mini.estimated_usd - expected).abs() < 1e-12);
+
+        // No fine-tuned price for the base model: charged as the most expensive, and flagged
+        let davinci = &estimates[1];
+        assert!(!davinci.priced);
+        assert!(davinci.estimated_usd > mini.estimated_usd);
+    }
+}
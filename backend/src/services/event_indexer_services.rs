@@ -0,0 +1,325 @@
+//! Background indexer of `Transfer` events from each chain's token contract and device
+//! certificate contract into `onchain_events`, so transfer history and certificate mints are
+//! answered from the database instead of per-request `eth_getLogs` calls. Each contract keeps a
+//! cursor of the last block indexed, and only blocks with the chain's required confirmations
+//! are indexed, so a stored event is never reorganized away.
+
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::onchain_event::{OnchainEvent, OnchainEventQuery};
+use crate::services::crypto_services::{decode_uint_decimal, topic_address, BlockchainService, Log, TRANSFER_TOPIC};
+
+const EVENT_COLUMNS: &str = "id, chain_id, contract_address, event, block_number, tx_hash, log_index, \
+     from_address, to_address, amount, token_id, device_id, indexed_at";
+
+pub const EVENTS: &[&str] = &["token_transfer", "device_minted", "device_transferred", "device_burned"];
+
+const JOB_INTERVAL_SECS: u64 = 30;
+/// Blocks fetched per `eth_getLogs` call; providers cap the range
+const MAX_SCAN_BLOCKS: u64 = 1_000;
+/// Ranges indexed per contract per run, so a backfill does not hold up the other contracts
+const MAX_RANGES_PER_RUN: usize = 10;
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 500;
+const ZERO_ADDRESS: &str = "0x0000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContractKind {
+    /// The chain's ERC-20 token
+    Token,
+    /// The ERC-721 contract device ownership certificates are minted in
+    DeviceCertificate,
+}
+
+/// An indexed event decoded from a log, before it is stored
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedEvent {
+    pub event: &'static str,
+    pub from_address: String,
+    pub to_address: String,
+    pub amount: Option<String>,
+    pub token_id: Option<String>,
+    /// The device a certificate token id stands for
+    pub device_id: Option<Uuid>,
+}
+
+/// The device whose certificate has token id `word`, the device id read as an integer
+fn token_device(word: &[u8]) -> Option<Uuid> {
+    let word: &[u8; 32] = word.try_into().ok()?;
+    if word[..16].iter().any(|b| *b != 0) {
+        return None;
+    }
+    Some(Uuid::from_u128(u128::from_be_bytes(word[16..].try_into().ok()?)))
+}
+
+/// Decode a `Transfer` log of a `kind` contract. ERC-20 and ERC-721 share the event signature
+/// and differ in whether the value is indexed, so the topic count must match the kind.
+pub fn decode(kind: ContractKind, log: &Log) -> Option<DecodedEvent> {
+    if log.topics.first().map(String::as_str) != Some(TRANSFER_TOPIC) {
+        return None;
+    }
+    let from_address = topic_address(log.topics.get(1)?)?;
+    let to_address = topic_address(log.topics.get(2)?)?;
+    match (kind, log.topics.len()) {
+        (ContractKind::Token, 3) => Some(DecodedEvent {
+            event: "token_transfer",
+            amount: Some(decode_uint_decimal(&log.data)?),
+            token_id: None,
+            device_id: None,
+            from_address,
+            to_address,
+        }),
+        (ContractKind::DeviceCertificate, 4) => {
+            let word = hex::decode(log.topics[3].strip_prefix("0x")?).ok()?;
+            let event = if from_address == ZERO_ADDRESS {
+                "device_minted"
+            } else if to_address == ZERO_ADDRESS {
+                "device_burned"
+            } else {
+                "device_transferred"
+            };
+            Some(DecodedEvent {
+                event,
+                amount: None,
+                token_id: Some(decode_uint_decimal(&word)?),
+                device_id: token_device(&word),
+                from_address,
+                to_address,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The newest block with `required` confirmations at `head`
+pub fn final_block(head: u64, required: u32) -> Option<u64> {
+    (head + 1).checked_sub(required.max(1) as u64)
+}
+
+/// A contract the indexer follows
+struct Watched {
+    chain: BlockchainService,
+    contract: String,
+    kind: ContractKind,
+    required_confirmations: u32,
+}
+
+/// Index the next confirmed blocks of one contract, returning how many events were stored
+async fn index_contract(pool: &PgPool, watched: &Watched, backfill_blocks: u64) -> ApiResult<u64> {
+    let chain_id = watched.chain.chain_id() as i64;
+    let head = watched.chain.block_number().await?;
+    let Some(safe) = final_block(head, watched.required_confirmations) else {
+        return Ok(0);
+    };
+    let last: Option<i64> = sqlx::query_scalar(
+        "SELECT last_block FROM onchain_index_cursors WHERE chain_id = $1 AND contract_address = $2",
+    )
+    .bind(chain_id)
+    .bind(&watched.contract)
+    .fetch_optional(pool)
+    .await?;
+    let mut from = match last {
+        Some(last) => last as u64 + 1,
+        None => safe.saturating_sub(backfill_blocks),
+    };
+
+    let mut indexed = 0;
+    for _ in 0..MAX_RANGES_PER_RUN {
+        if from > safe {
+            break;
+        }
+        let to = safe.min(from + MAX_SCAN_BLOCKS - 1);
+        let topics = serde_json::json!([TRANSFER_TOPIC]);
+        let logs = watched.chain.get_logs(&watched.contract, topics, from, to).await?;
+
+        // The events and the cursor move together, so a failed range is fetched again
+        let mut tx = pool.begin().await?;
+        for log in &logs {
+            let Some(event) = decode(watched.kind, log) else {
+                continue;
+            };
+            indexed += sqlx::query(
+                "INSERT INTO onchain_events (chain_id, contract_address, event, block_number, tx_hash, log_index, \
+                                             from_address, to_address, amount, token_id, device_id) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, (SELECT id FROM devices WHERE id = $11)) \
+                 ON CONFLICT (chain_id, tx_hash, log_index) DO NOTHING",
+            )
+            .bind(chain_id)
+            .bind(&watched.contract)
+            .bind(event.event)
+            .bind(log.block_number as i64)
+            .bind(&log.tx_hash)
+            .bind(log.log_index as i64)
+            .bind(&event.from_address)
+            .bind(&event.to_address)
+            .bind(&event.amount)
+            .bind(&event.token_id)
+            .bind(event.device_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        sqlx::query(
+            "INSERT INTO onchain_index_cursors (chain_id, contract_address, last_block) VALUES ($1, $2, $3) \
+             ON CONFLICT (chain_id, contract_address) DO UPDATE SET last_block = $3, updated_at = NOW()",
+        )
+        .bind(chain_id)
+        .bind(&watched.contract)
+        .bind(to as i64)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        from = to + 1;
+    }
+    Ok(indexed)
+}
+
+/// Indexed events matching `query`, newest first
+pub async fn query(pool: &PgPool, query: &OnchainEventQuery) -> ApiResult<Vec<OnchainEvent>> {
+    if query.event.as_deref().is_some_and(|event| !EVENTS.contains(&event)) {
+        return Err(ApiError::ValidationError(format!("event must be one of {}", EVENTS.join(", "))));
+    }
+    let lowercase = |address: &Option<String>| -> ApiResult<Option<String>> {
+        address
+            .as_deref()
+            .map(|address| {
+                BlockchainService::validate_address(address)?;
+                Ok(address.to_ascii_lowercase())
+            })
+            .transpose()
+    };
+    let contract = lowercase(&query.contract)?;
+    let address = lowercase(&query.address)?;
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let events = sqlx::query_as::<_, OnchainEvent>(&format!(
+        "SELECT {} FROM onchain_events \
+         WHERE ($1::bigint IS NULL OR chain_id = $1) \
+           AND ($2::text IS NULL OR contract_address = $2) \
+           AND ($3::text IS NULL OR event = $3) \
+           AND ($4::text IS NULL OR from_address = $4 OR to_address = $4) \
+           AND ($5::uuid IS NULL OR device_id = $5) \
+           AND ($6::bigint IS NULL OR block_number >= $6) \
+           AND ($7::bigint IS NULL OR block_number <= $7) \
+           AND ($8::bigint IS NULL OR id < $8) \
+         ORDER BY id DESC LIMIT $9",
+        EVENT_COLUMNS
+    ))
+    .bind(query.chain_id.map(|id| id as i64))
+    .bind(&contract)
+    .bind(&query.event)
+    .bind(&address)
+    .bind(query.device_id)
+    .bind(query.from_block.map(|b| b as i64))
+    .bind(query.to_block.map(|b| b as i64))
+    .bind(query.before)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(events)
+}
+
+/// Index the token and certificate contracts of every chain with a provider in the background.
+/// Does nothing unless `EVENT_INDEXER_ENABLED` is set.
+pub fn spawn_indexer(pool: Arc<PgPool>, config: AppConfig) {
+    if !config.event_indexer_enabled {
+        tracing::info!("Event indexer is disabled");
+        return;
+    }
+    let mut watched = Vec::new();
+    for chain in config.chains.iter().filter(|chain| chain.has_provider()) {
+        let contracts = [
+            (chain.token_contract.as_deref(), ContractKind::Token),
+            (chain.nft_contract.as_deref(), ContractKind::DeviceCertificate),
+        ];
+        for (contract, kind) in contracts {
+            let Some(contract) = contract.filter(|c| BlockchainService::is_valid_eth_address(c)) else {
+                continue;
+            };
+            watched.push(Watched {
+                chain: BlockchainService::for_chain(chain),
+                contract: contract.to_ascii_lowercase(),
+                kind,
+                required_confirmations: chain.required_confirmations,
+            });
+        }
+    }
+    if watched.is_empty() {
+        tracing::info!("No chain has a provider and a contract to index");
+        return;
+    }
+    let backfill_blocks = config.event_indexer_backfill_blocks;
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(JOB_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            for contract in &watched {
+                let chain_id = contract.chain.chain_id();
+                match index_contract(&pool, contract, backfill_blocks).await {
+                    Ok(0) => {}
+                    Ok(indexed) => tracing::info!(chain_id, contract = %contract.contract, indexed, "Indexed events"),
+                    Err(e) => tracing::error!(chain_id, contract = %contract.contract, "Event indexer failed: {}", e),
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topic(address_byte: u8) -> String {
+        format!("0x{:0>64}", format!("{:02x}", address_byte))
+    }
+
+    fn log(topics: Vec<String>, data: Vec<u8>) -> Log {
+        Log { block_number: 100, tx_hash: "0xab".to_string(), log_index: 0, topics, data }
+    }
+
+    #[test]
+    fn test_decode_token_transfer() {
+        let mut value = [0u8; 32];
+        value[31] = 0x2a;
+        let transfer = log(vec![TRANSFER_TOPIC.to_string(), topic(1), topic(2)], value.to_vec());
+        let event = decode(ContractKind::Token, &transfer).unwrap();
+        assert_eq!(event.event, "token_transfer");
+        assert_eq!(event.from_address, "0x0000000000000000000000000000000000000001");
+        assert_eq!(event.to_address, "0x0000000000000000000000000000000000000002");
+        assert_eq!(event.amount.as_deref(), Some("42"));
+        // A token log never passes as a certificate event
+        assert_eq!(decode(ContractKind::DeviceCertificate, &transfer), None);
+    }
+
+    #[test]
+    fn test_decode_device_certificate() {
+        let device_id = Uuid::parse_str("5f0c2a9e-1b7d-4c3e-9a2f-0123456789ab").unwrap();
+        let token = format!("0x{:064x}", device_id.as_u128());
+        let mint = log(vec![TRANSFER_TOPIC.to_string(), topic(0), topic(2), token.clone()], Vec::new());
+        let event = decode(ContractKind::DeviceCertificate, &mint).unwrap();
+        assert_eq!(event.event, "device_minted");
+        assert_eq!(event.device_id, Some(device_id));
+        assert_eq!(event.token_id, Some(device_id.as_u128().to_string()));
+        assert_eq!(decode(ContractKind::Token, &mint), None);
+
+        let handover = log(vec![TRANSFER_TOPIC.to_string(), topic(2), topic(3), token], Vec::new());
+        assert_eq!(decode(ContractKind::DeviceCertificate, &handover).unwrap().event, "device_transferred");
+
+        // Token ids wider than a device id stand for no device
+        let foreign = log(vec![TRANSFER_TOPIC.to_string(), topic(2), topic(0), format!("0x1{:063x}", 0)], Vec::new());
+        let event = decode(ContractKind::DeviceCertificate, &foreign).unwrap();
+        assert_eq!(event.event, "device_burned");
+        assert_eq!(event.device_id, None);
+    }
+
+    #[test]
+    fn test_final_block() {
+        assert_eq!(final_block(100, 12), Some(89));
+        assert_eq!(final_block(100, 1), Some(100));
+        assert_eq!(final_block(100, 0), Some(100));
+        assert_eq!(final_block(5, 12), None);
+    }
+}
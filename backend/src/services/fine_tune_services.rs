@@ -0,0 +1,325 @@
+//! Fine-tuning of chat models on users' own examples, e.g. robot command phrasing. Examples are
+//! checked locally and uploaded to the provider as a JSONL training file; a job then trains one
+//! of the fine-tunable base models on it. Jobs are not tracked in the background: polling a job
+//! refreshes it from the provider until it finishes. A succeeded job's model is registered to the
+//! user, who alone may name it in chat requests. Training is charged against the AI budget by the
+//! trained token once the job succeeds.
+
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::fine_tune::{FineTune, StartFineTuneRequest, TrainingFile, TrainingFileQuery};
+use crate::services::ai_routing_services::{budget, ensure_budget_left, record_usage, UsageRecord, REASON_REQUESTED};
+use crate::services::ai_services::{AIService, FineTuneJob, TrainingData};
+use crate::services::support_services::sanitize_file_name;
+
+const TRAINING_FILE_COLUMNS: &str =
+    "id, user_id, provider, provider_file_id, file_name, size_bytes, example_count, created_at";
+const FINE_TUNE_COLUMNS: &str = "id, user_id, training_file_id, provider, provider_job_id, base_model, suffix, \
+     status, fine_tuned_model, trained_tokens, error, created_at, updated_at";
+
+pub const MAX_TRAINING_BYTES: usize = 20 * 1024 * 1024;
+/// The fewest examples OpenAI trains on
+const MIN_EXAMPLES: usize = 10;
+const MAX_SUFFIX_CHARS: usize = 40;
+/// Base models that can be fine-tuned, with their training rate in USD per million tokens
+pub const FINE_TUNABLE_MODELS: &[(&str, f64)] =
+    &[("gpt-4o-mini-2024-07-18", 3.0), ("gpt-4o-2024-08-06", 25.0), ("gpt-3.5-turbo-0125", 8.0)];
+const FINISHED_STATUSES: &[&str] = &["succeeded", "failed", "cancelled"];
+const SUCCEEDED: &str = "succeeded";
+/// Prefix of OpenAI's fine-tuned model IDs
+pub const FINE_TUNED_PREFIX: &str = "ft:";
+
+/// Check JSONL chat examples, one `{"messages": [...]}` object per line each ending in an
+/// assistant answer, returning how many there are
+pub fn validate_training_data(data: &[u8]) -> ApiResult<usize> {
+    let text = std::str::from_utf8(data)
+        .map_err(|_| ApiError::ValidationError("Training data must be UTF-8 JSONL".to_string()))?;
+    let mut examples = 0;
+    for (i, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let invalid = |reason: &str| ApiError::ValidationError(format!("Line {}: {}", i + 1, reason));
+        let example: serde_json::Value = serde_json::from_str(line).map_err(|_| invalid("not valid JSON"))?;
+        let messages = example
+            .get("messages")
+            .and_then(|m| m.as_array())
+            .filter(|m| !m.is_empty())
+            .ok_or_else(|| invalid("expected an object with a non-empty \"messages\" array"))?;
+        for message in messages {
+            let role = message.get("role").and_then(|r| r.as_str());
+            if !matches!(role, Some("system" | "user" | "assistant")) {
+                return Err(invalid("each message needs a role of system, user or assistant"));
+            }
+            if !message.get("content").is_some_and(|c| c.is_string()) {
+                return Err(invalid("each message needs text content"));
+            }
+        }
+        if messages.last().and_then(|m| m.get("role")).and_then(|r| r.as_str()) != Some("assistant") {
+            return Err(invalid("the last message must be the assistant's answer"));
+        }
+        examples += 1;
+    }
+    if examples < MIN_EXAMPLES {
+        return Err(ApiError::ValidationError(format!(
+            "Training data needs at least {} examples, got {}",
+            MIN_EXAMPLES, examples
+        )));
+    }
+    Ok(examples)
+}
+
+pub fn validate_suffix(suffix: Option<&str>) -> ApiResult<Option<String>> {
+    match suffix.map(str::trim).filter(|s| !s.is_empty()) {
+        None => Ok(None),
+        Some(s)
+            if s.len() <= MAX_SUFFIX_CHARS && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') =>
+        {
+            Ok(Some(s.to_ascii_lowercase()))
+        }
+        Some(_) => Err(ApiError::ValidationError(format!(
+            "suffix must be up to {} letters, digits, - or _",
+            MAX_SUFFIX_CHARS
+        ))),
+    }
+}
+
+/// The training rate of a fine-tunable base model
+pub fn training_rate(base_model: &str) -> ApiResult<f64> {
+    FINE_TUNABLE_MODELS.iter().find(|(model, _)| *model == base_model).map(|(_, rate)| *rate).ok_or_else(|| {
+        let names: Vec<&str> = FINE_TUNABLE_MODELS.iter().map(|(model, _)| *model).collect();
+        ApiError::ValidationError(format!("base_model must be one of {}", names.join(", ")))
+    })
+}
+
+/// Upload JSONL training examples to the provider
+pub async fn upload(
+    pool: &PgPool,
+    ai: &AIService,
+    user_id: Uuid,
+    query: &TrainingFileQuery,
+    data: Vec<u8>,
+) -> ApiResult<TrainingFile> {
+    let file_name = sanitize_file_name(&query.file_name)?;
+    if data.is_empty() || data.len() > MAX_TRAINING_BYTES {
+        return Err(ApiError::ValidationError(format!("Training data must be 1-{} bytes", MAX_TRAINING_BYTES)));
+    }
+    let example_count = validate_training_data(&data)?;
+    let provider = ai.provider(query.provider.as_deref())?;
+    if !provider.fine_tunes() {
+        return Err(ApiError::ValidationError(format!(
+            "AI provider {} does not fine-tune models",
+            provider.kind().as_str()
+        )));
+    }
+
+    let size_bytes = data.len() as i64;
+    let provider_file_id = provider.upload_training_data(TrainingData { file_name: file_name.clone(), data }).await?;
+    let file = sqlx::query_as::<_, TrainingFile>(&format!(
+        "INSERT INTO ai_training_files (id, user_id, provider, provider_file_id, file_name, size_bytes, example_count) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING {}",
+        TRAINING_FILE_COLUMNS
+    ))
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(provider.kind().as_str())
+    .bind(&provider_file_id)
+    .bind(&file_name)
+    .bind(size_bytes)
+    .bind(example_count as i32)
+    .fetch_one(pool)
+    .await?;
+    Ok(file)
+}
+
+/// Start a fine-tuning job on one of the caller's training files
+pub async fn start(
+    pool: &PgPool,
+    config: &AppConfig,
+    ai: &AIService,
+    user_id: Uuid,
+    request: &StartFineTuneRequest,
+) -> ApiResult<FineTune> {
+    let base_model = request.base_model.trim();
+    training_rate(base_model)?;
+    let suffix = validate_suffix(request.suffix.as_deref())?;
+    let file = sqlx::query_as::<_, TrainingFile>(&format!(
+        "SELECT {} FROM ai_training_files WHERE id = $1 AND user_id = $2",
+        TRAINING_FILE_COLUMNS
+    ))
+    .bind(request.training_file_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Training file not found".to_string()))?;
+    // The cost is only known once trained, so the budget only needs to have something left
+    ensure_budget_left(&budget(pool, config, user_id).await?)?;
+
+    let provider = ai.provider(Some(&file.provider))?;
+    let job = provider.start_fine_tune(&file.provider_file_id, base_model, suffix.as_deref()).await?;
+    let fine_tune = sqlx::query_as::<_, FineTune>(&format!(
+        "INSERT INTO ai_fine_tunes (id, user_id, training_file_id, provider, provider_job_id, base_model, suffix, \
+             status, fine_tuned_model, trained_tokens, error) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING {}",
+        FINE_TUNE_COLUMNS
+    ))
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(file.id)
+    .bind(&file.provider)
+    .bind(&job.id)
+    .bind(base_model)
+    .bind(&suffix)
+    .bind(&job.status)
+    .bind(&job.fine_tuned_model)
+    .bind(job.trained_tokens)
+    .bind(&job.error)
+    .fetch_one(pool)
+    .await?;
+    Ok(fine_tune)
+}
+
+/// The caller's fine-tuning jobs, newest first, as last seen
+pub async fn list(pool: &PgPool, user_id: Uuid) -> ApiResult<Vec<FineTune>> {
+    let fine_tunes = sqlx::query_as::<_, FineTune>(&format!(
+        "SELECT {} FROM ai_fine_tunes WHERE user_id = $1 ORDER BY created_at DESC",
+        FINE_TUNE_COLUMNS
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(fine_tunes)
+}
+
+async fn owned(pool: &PgPool, user_id: Uuid, fine_tune_id: Uuid) -> ApiResult<FineTune> {
+    sqlx::query_as::<_, FineTune>(&format!(
+        "SELECT {} FROM ai_fine_tunes WHERE id = $1 AND user_id = $2",
+        FINE_TUNE_COLUMNS
+    ))
+    .bind(fine_tune_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Fine-tuning job not found".to_string()))
+}
+
+/// A fine-tuning job, refreshed from the provider while it is unfinished. The poll that sees it
+/// succeed registers the model and charges the training.
+pub async fn poll(pool: &PgPool, ai: &AIService, user_id: Uuid, fine_tune_id: Uuid) -> ApiResult<FineTune> {
+    let fine_tune = owned(pool, user_id, fine_tune_id).await?;
+    if FINISHED_STATUSES.contains(&fine_tune.status.as_str()) {
+        return Ok(fine_tune);
+    }
+    let provider = ai.provider(Some(&fine_tune.provider))?;
+    let job: FineTuneJob = provider.fine_tune(&fine_tune.provider_job_id).await?;
+    // Only an unfinished job is updated, so a job is charged by one poll however many race
+    let updated = sqlx::query_as::<_, FineTune>(&format!(
+        "UPDATE ai_fine_tunes SET status = $2, fine_tuned_model = $3, trained_tokens = $4, error = $5, \
+             updated_at = NOW() \
+         WHERE id = $1 AND status <> ALL($6) RETURNING {}",
+        FINE_TUNE_COLUMNS
+    ))
+    .bind(fine_tune.id)
+    .bind(&job.status)
+    .bind(&job.fine_tuned_model)
+    .bind(job.trained_tokens)
+    .bind(&job.error)
+    .bind(FINISHED_STATUSES)
+    .fetch_optional(pool)
+    .await?;
+    let Some(updated) = updated else {
+        return owned(pool, user_id, fine_tune_id).await;
+    };
+
+    if updated.status == SUCCEEDED {
+        let rate = training_rate(&updated.base_model).unwrap_or_default();
+        let cost = updated.trained_tokens.unwrap_or_default() as f64 * rate / 1_000_000.0;
+        record_usage(
+            pool,
+            user_id,
+            &UsageRecord {
+                provider: provider.kind().as_str(),
+                requested_model: &updated.base_model,
+                model: updated.fine_tuned_model.as_deref().unwrap_or(&updated.base_model),
+                reason: REASON_REQUESTED,
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                estimated_usd: cost,
+                actual_usd: Some(cost),
+                failover_from: None,
+            },
+        )
+        .await?;
+    }
+    Ok(updated)
+}
+
+/// The models the user has fine-tuned, listed by `get_models` alongside the providers' models
+pub async fn fine_tuned_models(pool: &PgPool, user_id: Uuid) -> ApiResult<Vec<String>> {
+    let models = sqlx::query_scalar::<_, String>(
+        "SELECT fine_tuned_model FROM ai_fine_tunes \
+         WHERE user_id = $1 AND status = $2 AND fine_tuned_model IS NOT NULL ORDER BY created_at DESC",
+    )
+    .bind(user_id)
+    .bind(SUCCEEDED)
+    .fetch_all(pool)
+    .await?;
+    Ok(models)
+}
+
+/// Fine-tuned models share the provider account, so a chat request may only name the user's own
+pub async fn ensure_model_usable(pool: &PgPool, user_id: Uuid, model: &str) -> ApiResult<()> {
+    if !model.starts_with(FINE_TUNED_PREFIX) {
+        return Ok(());
+    }
+    let owned = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM ai_fine_tunes WHERE user_id = $1 AND fine_tuned_model = $2 AND status = $3)",
+    )
+    .bind(user_id)
+    .bind(model)
+    .bind(SUCCEEDED)
+    .fetch_one(pool)
+    .await?;
+    if !owned {
+        return Err(ApiError::ValidationError(format!("{} is not one of your fine-tuned models", model)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example(answer_role: &str) -> String {
+        format!(
+            "{{\"messages\": [{{\"role\": \"user\", \"content\": \"Dock now\"}}, \
+             {{\"role\": \"{}\", \"content\": \"Returning to dock\"}}]}}\n",
+            answer_role
+        )
+    }
+
+    #[test]
+    fn test_validate_training_data() {
+        let valid = example("assistant").repeat(MIN_EXAMPLES) + "\n";
+        assert_eq!(validate_training_data(valid.as_bytes()).unwrap(), MIN_EXAMPLES);
+        assert!(validate_training_data(example("assistant").repeat(MIN_EXAMPLES - 1).as_bytes()).is_err());
+
+        let unanswered = example("assistant").repeat(MIN_EXAMPLES) + &example("user");
+        match validate_training_data(unanswered.as_bytes()) {
+            Err(ApiError::ValidationError(message)) => {
+                assert_eq!(message, "Line 11: the last message must be the assistant's answer")
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+        assert!(validate_training_data(b"{\"prompt\": \"x\", \"completion\": \"y\"}").is_err());
+        assert!(validate_training_data(&[0xFF, 0xFE]).is_err());
+    }
+
+    #[test]
+    fn test_validate_suffix_and_rate() {
+        assert_eq!(validate_suffix(None).unwrap(), None);
+        assert_eq!(validate_suffix(Some(" Dock-Commands ")).unwrap().as_deref(), Some("dock-commands"));
+        assert!(validate_suffix(Some("dock commands")).is_err());
+        assert_eq!(training_rate("gpt-4o-mini-2024-07-18").unwrap(), 3.0);
+        assert!(training_rate("gpt-4").is_err());
+    }
+}
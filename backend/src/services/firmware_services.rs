@@ -0,0 +1,163 @@
+//! Firmware supply-chain checks: Ed25519 signing keys, artifact signatures and the
+//! platform endorsement that lets devices chain a release back to a pinned trust root.
+//!
+//! The chain a device verifies before applying an update:
+//! 1. the device CA public key (pinned at provisioning, see the trust-root endpoint)
+//!    signed [`endorsement_message`] for the org signing key's fingerprint;
+//! 2. that signing key produced the detached signature over the artifact bytes;
+//! 3. the downloaded artifact hashes to the manifest's sha256.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::services::key_services::ManagedKey;
+use crate::utils::{base64_decode, base64_encode};
+
+/// Header carrying the base64 detached signature on firmware uploads
+pub const FIRMWARE_SIGNATURE_HEADER: &str = "X-Firmware-Signature";
+
+/// Largest accepted firmware artifact (bytes)
+pub const MAX_FIRMWARE_BYTES: usize = 32 * 1024 * 1024;
+
+pub fn parse_public_key(encoded: &str) -> ApiResult<VerifyingKey> {
+    let bytes = base64_decode(encoded.trim())
+        .ok()
+        .and_then(|b| <[u8; 32]>::try_from(b).ok())
+        .ok_or_else(|| ApiError::ValidationError("public_key must be a base64 32-byte Ed25519 key".to_string()))?;
+    VerifyingKey::from_bytes(&bytes)
+        .map_err(|_| ApiError::ValidationError("public_key is not a valid Ed25519 point".to_string()))
+}
+
+pub fn parse_signature(encoded: &str) -> ApiResult<Signature> {
+    base64_decode(encoded.trim())
+        .ok()
+        .and_then(|b| <[u8; 64]>::try_from(b).ok())
+        .map(|b| Signature::from_bytes(&b))
+        .ok_or_else(|| ApiError::ValidationError("Signature must be a base64 64-byte Ed25519 signature".to_string()))
+}
+
+/// sha256 hex of the raw public key
+pub fn key_fingerprint(key: &VerifyingKey) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+pub fn artifact_sha256(artifact: &[u8]) -> String {
+    hex::encode(Sha256::digest(artifact))
+}
+
+/// Strict verification rejects malleable signatures and small-order keys
+pub fn verify_artifact(key: &VerifyingKey, artifact: &[u8], signature: &Signature) -> bool {
+    key.verify_strict(artifact, signature).is_ok()
+}
+
+/// What the device CA signs to vouch for an org's firmware signing key
+pub fn endorsement_message(org_id: Uuid, fingerprint: &str) -> String {
+    format!("roboveda-firmware-key:v1:{}:{}", org_id, fingerprint)
+}
+
+fn ca_signing_key(ca: &ManagedKey) -> ApiResult<SigningKey> {
+    let seed = <[u8; 32]>::try_from(ca.material.as_slice())
+        .map_err(|_| ApiError::InternalError("Device CA key has the wrong length".to_string()))?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+/// Public half of a device CA key version, for devices to pin
+pub fn ca_public_key(ca: &ManagedKey) -> ApiResult<VerifyingKey> {
+    Ok(ca_signing_key(ca)?.verifying_key())
+}
+
+/// Base64 device CA signature over [`endorsement_message`]
+pub fn endorse_signing_key(ca: &ManagedKey, org_id: Uuid, fingerprint: &str) -> ApiResult<String> {
+    let signature = ca_signing_key(ca)?.sign(endorsement_message(org_id, fingerprint).as_bytes());
+    Ok(base64_encode(&signature.to_bytes()))
+}
+
+pub fn verify_endorsement(ca_public: &VerifyingKey, org_id: Uuid, fingerprint: &str, endorsement: &str) -> bool {
+    parse_signature(endorsement)
+        .is_ok_and(|sig| ca_public.verify_strict(endorsement_message(org_id, fingerprint).as_bytes(), &sig).is_ok())
+}
+
+pub fn validate_version(version: &str) -> ApiResult<()> {
+    let valid = !version.is_empty()
+        && version.len() <= 32
+        && version.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'));
+    if !valid {
+        return Err(ApiError::ValidationError(
+            "Firmware version must be 1-32 characters of letters, digits, '.', '-' or '+'".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Dotted version ordering: numeric components compare as numbers, anything else as text.
+/// A leading `v` is ignored, so `v1.10.0` > `1.9.3`.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |v: &str| -> Vec<String> {
+        v.trim_start_matches(['v', 'V']).split(['.', '-', '+']).map(str::to_string).collect()
+    };
+    let (a, b) = (parts(a), parts(b));
+    for (x, y) in a.iter().zip(b.iter()) {
+        let ord = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            _ => x.cmp(y),
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::key_services::KeyPurpose;
+    use zeroize::Zeroizing;
+
+    fn signing_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    #[test]
+    fn test_artifact_signature() {
+        let key = signing_key(7);
+        let artifact = b"\x7fELF firmware image";
+        let signature = base64_encode(&key.sign(artifact).to_bytes());
+
+        let public = parse_public_key(&base64_encode(key.verifying_key().as_bytes())).unwrap();
+        let parsed = parse_signature(&signature).unwrap();
+        assert!(verify_artifact(&public, artifact, &parsed));
+        assert!(!verify_artifact(&public, b"tampered image", &parsed));
+        assert!(!verify_artifact(&signing_key(8).verifying_key(), artifact, &parsed));
+
+        assert!(parse_public_key("not base64!").is_err());
+        assert!(parse_signature(&base64_encode(&[0u8; 10])).is_err());
+        assert_eq!(key_fingerprint(&public).len(), 64);
+    }
+
+    #[test]
+    fn test_endorsement_chain() {
+        let ca = ManagedKey { purpose: KeyPurpose::DeviceCa, version: 1, material: Zeroizing::new(vec![3u8; 32]) };
+        let org_id = Uuid::new_v4();
+        let fingerprint = key_fingerprint(&signing_key(7).verifying_key());
+
+        let endorsement = endorse_signing_key(&ca, org_id, &fingerprint).unwrap();
+        let ca_public = ca_public_key(&ca).unwrap();
+        assert!(verify_endorsement(&ca_public, org_id, &fingerprint, &endorsement));
+        // Bound to both the org and the key
+        assert!(!verify_endorsement(&ca_public, Uuid::new_v4(), &fingerprint, &endorsement));
+        assert!(!verify_endorsement(&ca_public, org_id, &"0".repeat(64), &endorsement));
+    }
+
+    #[test]
+    fn test_versions() {
+        assert_eq!(compare_versions("1.10.0", "1.9.3"), Ordering::Greater);
+        assert_eq!(compare_versions("v2.0", "2.0"), Ordering::Equal);
+        assert_eq!(compare_versions("2.0", "2.0.1"), Ordering::Less);
+        assert!(validate_version("1.2.3-rc1").is_ok());
+        assert!(validate_version("1.2 beta").is_err());
+        assert!(validate_version("").is_err());
+    }
+}
@@ -0,0 +1,882 @@
+//! Declarative fleet configuration. A document describing fleets, device tags, geofences, alert
+//! rules and automations is diffed by name against what the user has, giving a plan that is
+//! applied in one transaction. Geofences and alert rules are evaluated on stored telemetry.
+
+use chrono::Utc;
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::automation::{Condition, RuleGraph, Trigger};
+use crate::models::fleet_config::{AlertRuleSpec, AutomationSpec, FleetConfig, FleetSpec, GeofenceSpec, PlannedChange};
+use crate::models::swarm::GeoBounds;
+use crate::services::automation_services::{
+    command_targets, evaluate, lookup, new_hook_token, validate_condition, validate_graph, MAX_AUTOMATIONS_PER_USER,
+};
+use crate::services::notification_services::notify_user;
+use crate::services::promotion_services::{bounds_contain, validate_geofence};
+use crate::services::region_services;
+
+pub const MAX_FLEETS: usize = 100;
+pub const MAX_GEOFENCES: usize = 100;
+pub const MAX_ALERT_RULES: usize = 100;
+const MAX_FLEET_DEVICES: usize = 1000;
+const MAX_TAGS_PER_DEVICE: usize = 32;
+const MAX_COOLDOWN_MINUTES: u32 = 7 * 24 * 60;
+pub const SEVERITIES: &[&str] = &["info", "warning", "critical"];
+
+/// A resource managed by name
+trait Named {
+    const RESOURCE: &'static str;
+    fn name(&self) -> &str;
+    /// Fields that differ from `current`
+    fn changes(&self, current: &Self) -> Vec<&'static str>;
+}
+
+fn changed<T: PartialEq>(field: &'static str, desired: &T, current: &T, changes: &mut Vec<&'static str>) {
+    if desired != current {
+        changes.push(field);
+    }
+}
+
+impl Named for FleetSpec {
+    const RESOURCE: &'static str = "fleet";
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn changes(&self, current: &Self) -> Vec<&'static str> {
+        let mut changes = Vec::new();
+        changed("description", &self.description, &current.description, &mut changes);
+        changed("devices", &self.devices, &current.devices, &mut changes);
+        changes
+    }
+}
+
+impl Named for GeofenceSpec {
+    const RESOURCE: &'static str = "geofence";
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn changes(&self, current: &Self) -> Vec<&'static str> {
+        let mut changes = Vec::new();
+        changed("fleet", &self.fleet, &current.fleet, &mut changes);
+        changed("bounds", &self.bounds, &current.bounds, &mut changes);
+        changed("severity", &self.severity, &current.severity, &mut changes);
+        changed("cooldown_minutes", &self.cooldown_minutes, &current.cooldown_minutes, &mut changes);
+        changes
+    }
+}
+
+impl Named for AlertRuleSpec {
+    const RESOURCE: &'static str = "alert_rule";
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn changes(&self, current: &Self) -> Vec<&'static str> {
+        let mut changes = Vec::new();
+        changed("fleet", &self.fleet, &current.fleet, &mut changes);
+        changed("condition", &self.condition, &current.condition, &mut changes);
+        changed("severity", &self.severity, &current.severity, &mut changes);
+        changed("cooldown_minutes", &self.cooldown_minutes, &current.cooldown_minutes, &mut changes);
+        changed("enabled", &self.enabled, &current.enabled, &mut changes);
+        changes
+    }
+}
+
+impl Named for AutomationSpec {
+    const RESOURCE: &'static str = "automation";
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn changes(&self, current: &Self) -> Vec<&'static str> {
+        let mut changes = Vec::new();
+        changed("enabled", &self.enabled, &current.enabled, &mut changes);
+        changed("graph", &self.graph, &current.graph, &mut changes);
+        changes
+    }
+}
+
+/// What the user has now, with ids. `tags` covers every device they own.
+#[derive(Debug, Default, Clone)]
+pub struct CurrentState {
+    pub fleets: Vec<(Uuid, FleetSpec)>,
+    pub tags: BTreeMap<Uuid, Vec<String>>,
+    pub geofences: Vec<(Uuid, GeofenceSpec)>,
+    pub alert_rules: Vec<(Uuid, AlertRuleSpec)>,
+    pub automations: Vec<(Uuid, AutomationSpec)>,
+}
+
+/// Parse a JSON or, when the content type says so, YAML document
+pub fn parse_document(content_type: &str, body: &[u8]) -> ApiResult<FleetConfig> {
+    let mut config: FleetConfig = if content_type.contains("yaml") {
+        serde_yaml::from_slice(body).map_err(|e| ApiError::ValidationError(format!("Invalid configuration: {}", e)))?
+    } else {
+        serde_json::from_slice(body).map_err(|e| ApiError::ValidationError(format!("Invalid configuration: {}", e)))?
+    };
+    // Membership is a set; keep it in the order it is stored in
+    for fleet in config.fleets.iter_mut().flatten() {
+        fleet.devices.sort();
+        fleet.devices.dedup();
+    }
+    Ok(config)
+}
+
+fn validate_names<'a>(resource: &str, names: impl Iterator<Item = &'a str>) -> ApiResult<()> {
+    let mut seen = HashSet::new();
+    for name in names {
+        if name.trim().is_empty() || name.len() > 100 || name.trim() != name {
+            return Err(ApiError::ValidationError(format!("{} name '{}' must be 1-100 characters", resource, name)));
+        }
+        if !seen.insert(name) {
+            return Err(ApiError::ValidationError(format!("Duplicate {} name '{}'", resource, name)));
+        }
+    }
+    Ok(())
+}
+
+fn validate_alerting(resource: &str, name: &str, severity: &str, cooldown_minutes: u32) -> ApiResult<()> {
+    if !SEVERITIES.contains(&severity) {
+        return Err(ApiError::ValidationError(format!(
+            "{} '{}': severity must be one of {}",
+            resource,
+            name,
+            SEVERITIES.join(", ")
+        )));
+    }
+    if cooldown_minutes == 0 || cooldown_minutes > MAX_COOLDOWN_MINUTES {
+        return Err(ApiError::ValidationError(format!(
+            "{} '{}': cooldown_minutes must be between 1 and {}",
+            resource, name, MAX_COOLDOWN_MINUTES
+        )));
+    }
+    Ok(())
+}
+
+fn valid_tag(tag: &str) -> bool {
+    !tag.is_empty()
+        && tag.len() <= 32
+        && tag.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | ':' | '-'))
+}
+
+/// Checks that need nothing but the document
+pub fn validate(config: &FleetConfig) -> ApiResult<()> {
+    let invalid = |msg: String| Err(ApiError::ValidationError(msg));
+
+    if let Some(fleets) = &config.fleets {
+        if fleets.len() > MAX_FLEETS {
+            return invalid(format!("At most {} fleets", MAX_FLEETS));
+        }
+        validate_names("fleet", fleets.iter().map(|f| f.name.as_str()))?;
+        for fleet in fleets {
+            if fleet.devices.len() > MAX_FLEET_DEVICES {
+                return invalid(format!("Fleet '{}' has more than {} devices", fleet.name, MAX_FLEET_DEVICES));
+            }
+            if fleet.description.as_ref().is_some_and(|d| d.len() > 500) {
+                return invalid(format!("Fleet '{}': description must be at most 500 characters", fleet.name));
+            }
+        }
+    }
+    if let Some(tags) = &config.tags {
+        for (device_id, device_tags) in tags {
+            let unique: HashSet<&String> = device_tags.iter().collect();
+            if device_tags.len() > MAX_TAGS_PER_DEVICE || unique.len() != device_tags.len() {
+                return invalid(format!(
+                    "Device {} needs at most {} distinct tags",
+                    device_id, MAX_TAGS_PER_DEVICE
+                ));
+            }
+            if let Some(tag) = device_tags.iter().find(|tag| !valid_tag(tag)) {
+                return invalid(format!("Tag '{}' must be 1-32 of [A-Za-z0-9_.:-]", tag));
+            }
+        }
+    }
+    if let Some(geofences) = &config.geofences {
+        if geofences.len() > MAX_GEOFENCES {
+            return invalid(format!("At most {} geofences", MAX_GEOFENCES));
+        }
+        validate_names("geofence", geofences.iter().map(|g| g.name.as_str()))?;
+        for geofence in geofences {
+            validate_geofence(&geofence.bounds)?;
+            validate_alerting("Geofence", &geofence.name, &geofence.severity, geofence.cooldown_minutes)?;
+        }
+    }
+    if let Some(rules) = &config.alert_rules {
+        if rules.len() > MAX_ALERT_RULES {
+            return invalid(format!("At most {} alert rules", MAX_ALERT_RULES));
+        }
+        validate_names("alert rule", rules.iter().map(|r| r.name.as_str()))?;
+        for rule in rules {
+            validate_condition(&rule.condition)?;
+            validate_alerting("Alert rule", &rule.name, &rule.severity, rule.cooldown_minutes)?;
+        }
+    }
+    if let Some(automations) = &config.automations {
+        if automations.len() as i64 > MAX_AUTOMATIONS_PER_USER {
+            return invalid(format!("At most {} automations per account", MAX_AUTOMATIONS_PER_USER));
+        }
+        validate_names("automation", automations.iter().map(|a| a.name.as_str()))?;
+        for automation in automations {
+            validate_graph(&automation.graph)?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks against the current state: devices must be owned, and every fleet referred to must
+/// exist once the document is applied
+pub fn check_references(config: &FleetConfig, current: &CurrentState) -> ApiResult<()> {
+    let fleet_devices = config.fleets.iter().flatten().flat_map(|f| f.devices.iter().copied());
+    let tagged_devices = config.tags.iter().flat_map(|tags| tags.keys().copied());
+    let automation_devices = config.automations.iter().flatten().flat_map(|a| command_targets(&a.graph));
+    let mut devices = fleet_devices.chain(tagged_devices).chain(automation_devices);
+    if let Some(device_id) = devices.find(|id| !current.tags.contains_key(id)) {
+        return Err(ApiError::NotFound(format!("Device {} not found", device_id)));
+    }
+
+    let fleets: HashSet<&str> = match &config.fleets {
+        Some(fleets) => fleets.iter().map(|f| f.name.as_str()).collect(),
+        None => current.fleets.iter().map(|(_, f)| f.name.as_str()).collect(),
+    };
+    let geofences = match &config.geofences {
+        Some(geofences) => geofences.iter().collect::<Vec<_>>(),
+        None => current.geofences.iter().map(|(_, g)| g).collect(),
+    };
+    let rules = match &config.alert_rules {
+        Some(rules) => rules.iter().collect::<Vec<_>>(),
+        None => current.alert_rules.iter().map(|(_, r)| r).collect(),
+    };
+    let references = geofences
+        .iter()
+        .map(|g| ("Geofence", &g.name, &g.fleet))
+        .chain(rules.iter().map(|r| ("Alert rule", &r.name, &r.fleet)));
+    for (resource, name, fleet) in references {
+        if let Some(fleet) = fleet
+            && !fleets.contains(fleet.as_str())
+        {
+            return Err(ApiError::ValidationError(format!(
+                "{} '{}' refers to unknown fleet '{}'",
+                resource, name, fleet
+            )));
+        }
+    }
+
+    if config.automations.is_some() {
+        let mut seen = HashSet::new();
+        if let Some((_, duplicate)) = current.automations.iter().find(|(_, a)| !seen.insert(a.name.as_str())) {
+            return Err(ApiError::Conflict(format!(
+                "Several automations are named '{}'; rename them before managing automations by configuration",
+                duplicate.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn diff<T: Named>(desired: Option<&Vec<T>>, current: &[(Uuid, T)], plan: &mut Vec<PlannedChange>) {
+    let Some(desired) = desired else {
+        return;
+    };
+    let existing: HashMap<&str, &T> = current.iter().map(|(_, item)| (item.name(), item)).collect();
+    for item in desired {
+        let (action, changes) = match existing.get(item.name()) {
+            None => ("create", vec![]),
+            Some(current) => {
+                let changes = item.changes(current);
+                if changes.is_empty() {
+                    continue;
+                }
+                ("update", changes)
+            }
+        };
+        plan.push(PlannedChange { resource: T::RESOURCE, name: item.name().to_string(), action, changes });
+    }
+    let wanted: HashSet<&str> = desired.iter().map(|item| item.name()).collect();
+    for (_, item) in current {
+        if !wanted.contains(item.name()) {
+            plan.push(PlannedChange {
+                resource: T::RESOURCE,
+                name: item.name().to_string(),
+                action: "delete",
+                changes: vec![],
+            });
+        }
+    }
+}
+
+/// The changes that turn the current state into the desired one. Tag changes are named by
+/// device id.
+pub fn plan(desired: &FleetConfig, current: &CurrentState) -> Vec<PlannedChange> {
+    let mut plan = Vec::new();
+    diff(desired.fleets.as_ref(), &current.fleets, &mut plan);
+    if let Some(tags) = &desired.tags {
+        for (device_id, current_tags) in &current.tags {
+            let wanted = tags.get(device_id).map(Vec::as_slice).unwrap_or_default();
+            if wanted != current_tags.as_slice() {
+                plan.push(PlannedChange {
+                    resource: "tags",
+                    name: device_id.to_string(),
+                    action: "update",
+                    changes: vec!["tags"],
+                });
+            }
+        }
+    }
+    diff(desired.geofences.as_ref(), &current.geofences, &mut plan);
+    diff(desired.alert_rules.as_ref(), &current.alert_rules, &mut plan);
+    diff(desired.automations.as_ref(), &current.automations, &mut plan);
+    plan
+}
+
+fn strip<T>(items: Vec<(Uuid, T)>) -> Option<Vec<T>> {
+    Some(items.into_iter().map(|(_, item)| item).collect())
+}
+
+/// The current state as a document that applies without changes
+pub fn export(current: CurrentState) -> FleetConfig {
+    FleetConfig {
+        fleets: strip(current.fleets),
+        tags: Some(current.tags.into_iter().filter(|(_, tags)| !tags.is_empty()).collect()),
+        geofences: strip(current.geofences),
+        alert_rules: strip(current.alert_rules),
+        automations: strip(current.automations),
+    }
+}
+
+#[derive(FromRow)]
+struct FleetRow {
+    id: Uuid,
+    name: String,
+    description: Option<String>,
+    device_ids: Vec<Uuid>,
+}
+
+#[derive(FromRow)]
+struct GeofenceRow {
+    id: Uuid,
+    name: String,
+    fleet: Option<String>,
+    bounds: sqlx::types::Json<GeoBounds>,
+    severity: String,
+    cooldown_minutes: i32,
+}
+
+#[derive(FromRow)]
+struct AlertRuleRow {
+    id: Uuid,
+    name: String,
+    fleet: Option<String>,
+    condition: sqlx::types::Json<Condition>,
+    severity: String,
+    cooldown_minutes: i32,
+    enabled: bool,
+}
+
+#[derive(FromRow)]
+struct AutomationRow {
+    id: Uuid,
+    name: String,
+    enabled: bool,
+    graph: sqlx::types::Json<RuleGraph>,
+}
+
+pub async fn load_state(conn: &mut PgConnection, user_id: Uuid) -> ApiResult<CurrentState> {
+    let fleets = sqlx::query_as::<_, FleetRow>(
+        "SELECT id, name, description, ARRAY(SELECT unnest(device_ids) ORDER BY 1) AS device_ids \
+         FROM fleets WHERE user_id = $1 ORDER BY name",
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    let tags: Vec<(Uuid, sqlx::types::Json<Vec<String>>)> = sqlx::query_as(
+        "SELECT id, CASE WHEN jsonb_typeof(metadata -> 'tags') = 'array' THEN metadata -> 'tags' ELSE '[]' END \
+         FROM devices WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    let geofences = sqlx::query_as::<_, GeofenceRow>(
+        "SELECT g.id, g.name, f.name AS fleet, g.bounds, g.severity, g.cooldown_minutes \
+         FROM geofences g LEFT JOIN fleets f ON f.id = g.fleet_id WHERE g.user_id = $1 ORDER BY g.name",
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    let alert_rules = sqlx::query_as::<_, AlertRuleRow>(
+        "SELECT r.id, r.name, f.name AS fleet, r.condition, r.severity, r.cooldown_minutes, r.enabled \
+         FROM alert_rules r LEFT JOIN fleets f ON f.id = r.fleet_id WHERE r.user_id = $1 ORDER BY r.name",
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    let automations = sqlx::query_as::<_, AutomationRow>(
+        "SELECT a.id, a.name, a.enabled, v.graph FROM automations a \
+         JOIN automation_versions v ON v.automation_id = a.id AND v.version = a.current_version \
+         WHERE a.user_id = $1 ORDER BY a.name, a.created_at",
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(CurrentState {
+        fleets: fleets
+            .into_iter()
+            .map(|f| (f.id, FleetSpec { name: f.name, description: f.description, devices: f.device_ids }))
+            .collect(),
+        tags: tags.into_iter().map(|(id, tags)| (id, tags.0)).collect(),
+        geofences: geofences
+            .into_iter()
+            .map(|g| {
+                let spec = GeofenceSpec {
+                    name: g.name,
+                    fleet: g.fleet,
+                    bounds: g.bounds.0,
+                    severity: g.severity,
+                    cooldown_minutes: g.cooldown_minutes as u32,
+                };
+                (g.id, spec)
+            })
+            .collect(),
+        alert_rules: alert_rules
+            .into_iter()
+            .map(|r| {
+                let spec = AlertRuleSpec {
+                    name: r.name,
+                    fleet: r.fleet,
+                    condition: r.condition.0,
+                    severity: r.severity,
+                    cooldown_minutes: r.cooldown_minutes as u32,
+                    enabled: r.enabled,
+                };
+                (r.id, spec)
+            })
+            .collect(),
+        automations: automations
+            .into_iter()
+            .map(|a| (a.id, AutomationSpec { name: a.name, enabled: a.enabled, graph: a.graph.0 }))
+            .collect(),
+    })
+}
+
+fn find<'a, T: Named>(items: &'a [T], name: &str) -> &'a T {
+    items.iter().find(|item| item.name() == name).expect("planned resources come from the document")
+}
+
+fn find_id<T: Named>(items: &[(Uuid, T)], name: &str) -> Uuid {
+    items.iter().find(|(_, item)| item.name() == name).map(|(id, _)| *id).expect("planned resources exist")
+}
+
+/// Apply a plan made from `desired` and `current`. Fleets are created before the geofences
+/// and alert rules that use them and deleted after.
+async fn apply(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    desired: &FleetConfig,
+    current: &CurrentState,
+    plan: &[PlannedChange],
+) -> ApiResult<()> {
+    let of = |resource: &'static str| plan.iter().filter(move |change| change.resource == resource);
+
+    for change in of("fleet").filter(|c| c.action != "delete") {
+        let fleet = find(desired.fleets.as_deref().unwrap_or_default(), &change.name);
+        sqlx::query(
+            "INSERT INTO fleets (user_id, name, description, device_ids) VALUES ($1, $2, $3, $4) \
+             ON CONFLICT (user_id, name) DO UPDATE SET description = EXCLUDED.description, \
+                 device_ids = EXCLUDED.device_ids, updated_at = NOW()",
+        )
+        .bind(user_id)
+        .bind(&fleet.name)
+        .bind(&fleet.description)
+        .bind(&fleet.devices)
+        .execute(&mut *conn)
+        .await?;
+    }
+    let fleet_ids: HashMap<String, Uuid> =
+        sqlx::query_as::<_, (String, Uuid)>("SELECT name, id FROM fleets WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .collect();
+    let fleet_id = |fleet: &Option<String>| fleet.as_ref().and_then(|name| fleet_ids.get(name)).copied();
+
+    for change in of("geofence") {
+        if change.action == "delete" {
+            let id = find_id(&current.geofences, &change.name);
+            sqlx::query("DELETE FROM fleet_alert_state WHERE source_id = $1").bind(id).execute(&mut *conn).await?;
+            sqlx::query("DELETE FROM geofences WHERE id = $1").bind(id).execute(&mut *conn).await?;
+            continue;
+        }
+        let geofence = find(desired.geofences.as_deref().unwrap_or_default(), &change.name);
+        sqlx::query(
+            "INSERT INTO geofences (user_id, name, fleet_id, bounds, severity, cooldown_minutes) \
+             VALUES ($1, $2, $3, $4, $5, $6) \
+             ON CONFLICT (user_id, name) DO UPDATE SET fleet_id = EXCLUDED.fleet_id, bounds = EXCLUDED.bounds, \
+                 severity = EXCLUDED.severity, cooldown_minutes = EXCLUDED.cooldown_minutes, updated_at = NOW()",
+        )
+        .bind(user_id)
+        .bind(&geofence.name)
+        .bind(fleet_id(&geofence.fleet))
+        .bind(sqlx::types::Json(&geofence.bounds))
+        .bind(&geofence.severity)
+        .bind(geofence.cooldown_minutes as i32)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    for change in of("alert_rule") {
+        if change.action == "delete" {
+            let id = find_id(&current.alert_rules, &change.name);
+            sqlx::query("DELETE FROM fleet_alert_state WHERE source_id = $1").bind(id).execute(&mut *conn).await?;
+            sqlx::query("DELETE FROM alert_rules WHERE id = $1").bind(id).execute(&mut *conn).await?;
+            continue;
+        }
+        let rule = find(desired.alert_rules.as_deref().unwrap_or_default(), &change.name);
+        sqlx::query(
+            "INSERT INTO alert_rules (user_id, name, fleet_id, condition, severity, cooldown_minutes, enabled) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7) \
+             ON CONFLICT (user_id, name) DO UPDATE SET fleet_id = EXCLUDED.fleet_id, condition = EXCLUDED.condition, \
+                 severity = EXCLUDED.severity, cooldown_minutes = EXCLUDED.cooldown_minutes, \
+                 enabled = EXCLUDED.enabled, updated_at = NOW()",
+        )
+        .bind(user_id)
+        .bind(&rule.name)
+        .bind(fleet_id(&rule.fleet))
+        .bind(sqlx::types::Json(&rule.condition))
+        .bind(&rule.severity)
+        .bind(rule.cooldown_minutes as i32)
+        .bind(rule.enabled)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    for change in of("fleet").filter(|c| c.action == "delete") {
+        sqlx::query("DELETE FROM fleets WHERE id = $1")
+            .bind(find_id(&current.fleets, &change.name))
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    for change in of("tags") {
+        let device_id: Uuid = change.name.parse().expect("tag changes are named by device id");
+        let tags = desired.tags.as_ref().and_then(|tags| tags.get(&device_id)).cloned().unwrap_or_default();
+        sqlx::query(
+            "UPDATE devices SET metadata = CASE WHEN jsonb_array_length($2) = 0 THEN metadata - 'tags' \
+             ELSE jsonb_set(metadata, '{tags}', $2) END WHERE id = $1",
+        )
+        .bind(device_id)
+        .bind(sqlx::types::Json(tags))
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    for change in of("automation") {
+        match change.action {
+            "delete" => {
+                sqlx::query("DELETE FROM automations WHERE id = $1")
+                    .bind(find_id(&current.automations, &change.name))
+                    .execute(&mut *conn)
+                    .await?;
+            }
+            "create" => {
+                let automation = find(desired.automations.as_deref().unwrap_or_default(), &change.name);
+                // The inbound hook token can be rotated later to learn it
+                let (_, token_hash) = new_hook_token();
+                let id: Uuid = sqlx::query_scalar(
+                    "INSERT INTO automations (user_id, name, enabled, trigger_kind, hook_token_hash) \
+                     VALUES ($1, $2, $3, $4, $5) RETURNING id",
+                )
+                .bind(user_id)
+                .bind(&automation.name)
+                .bind(automation.enabled)
+                .bind(automation.graph.trigger.kind())
+                .bind(&token_hash)
+                .fetch_one(&mut *conn)
+                .await?;
+                sqlx::query(
+                    "INSERT INTO automation_versions (automation_id, version, graph, created_by) \
+                     VALUES ($1, 1, $2, $3)",
+                )
+                .bind(id)
+                .bind(sqlx::types::Json(&automation.graph))
+                .bind(user_id)
+                .execute(&mut *conn)
+                .await?;
+            }
+            _ => {
+                let automation = find(desired.automations.as_deref().unwrap_or_default(), &change.name);
+                let id = find_id(&current.automations, &change.name);
+                if change.changes.contains(&"graph") {
+                    sqlx::query(
+                        "INSERT INTO automation_versions (automation_id, version, graph, created_by) \
+                         SELECT id, current_version + 1, $2, $3 FROM automations WHERE id = $1",
+                    )
+                    .bind(id)
+                    .bind(sqlx::types::Json(&automation.graph))
+                    .bind(user_id)
+                    .execute(&mut *conn)
+                    .await?;
+                }
+                sqlx::query(
+                    "UPDATE automations SET enabled = $2, trigger_kind = $3, updated_at = NOW(), \
+                     current_version = current_version + CASE WHEN $4 THEN 1 ELSE 0 END WHERE id = $1",
+                )
+                .bind(id)
+                .bind(automation.enabled)
+                .bind(automation.graph.trigger.kind())
+                .bind(change.changes.contains(&"graph"))
+                .execute(&mut *conn)
+                .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Metric triggers must name a metric of one of the user's orgs
+async fn check_metric(conn: &mut PgConnection, user_id: Uuid, graph: &RuleGraph) -> ApiResult<()> {
+    if let Trigger::Metric { metric_id, .. } = &graph.trigger {
+        let visible: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM org_metrics m JOIN org_memberships om ON om.org_id = m.org_id \
+             WHERE m.id = $1 AND om.user_id = $2 AND om.active)",
+        )
+        .bind(metric_id)
+        .bind(user_id)
+        .fetch_one(conn)
+        .await?;
+        if !visible {
+            return Err(ApiError::NotFound("Metric not found".to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// Plan a document against the user's current state within the caller's transaction and,
+/// unless `dry_run`, apply it there. The user row stays locked until the transaction ends.
+pub async fn apply_in(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    desired: &FleetConfig,
+    dry_run: bool,
+) -> ApiResult<Vec<PlannedChange>> {
+    validate(desired)?;
+    // One apply per user at a time, so the plan stays valid until it is applied
+    sqlx::query("SELECT id FROM users WHERE id = $1 FOR UPDATE")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+    let current = load_state(conn, user_id).await?;
+    check_references(desired, &current)?;
+    let plan = plan(desired, &current);
+    for change in plan.iter().filter(|c| c.resource == "automation" && c.action != "delete") {
+        let automation = find(desired.automations.as_deref().unwrap_or_default(), &change.name);
+        check_metric(conn, user_id, &automation.graph).await?;
+    }
+
+    if !dry_run && !plan.is_empty() {
+        apply(conn, user_id, desired, &current, &plan).await?;
+    }
+    Ok(plan)
+}
+
+/// Plan a document against the user's current state and, unless `dry_run`, apply it. Nothing
+/// is changed unless every step succeeds.
+pub async fn apply_config(
+    pool: &PgPool,
+    user_id: Uuid,
+    desired: &FleetConfig,
+    dry_run: bool,
+) -> ApiResult<Vec<PlannedChange>> {
+    let mut tx = pool.begin().await?;
+    let plan = apply_in(&mut tx, user_id, desired, dry_run).await?;
+    if !dry_run && !plan.is_empty() {
+        tx.commit().await?;
+    }
+    Ok(plan)
+}
+
+#[derive(FromRow)]
+struct ActiveAlert {
+    id: Uuid,
+    name: String,
+    severity: String,
+    cooldown_minutes: i32,
+    condition: Option<sqlx::types::Json<Condition>>,
+    bounds: Option<sqlx::types::Json<GeoBounds>>,
+}
+
+/// Whether an alert may fire for a device now; records it when so
+async fn claim_alert(pool: &PgPool, source_id: Uuid, device_id: Uuid, cooldown_minutes: i32) -> ApiResult<bool> {
+    let claimed = sqlx::query(
+        "INSERT INTO fleet_alert_state (source_id, device_id, last_alert_at) VALUES ($1, $2, $3) \
+         ON CONFLICT (source_id, device_id) DO UPDATE SET last_alert_at = EXCLUDED.last_alert_at \
+         WHERE fleet_alert_state.last_alert_at <= EXCLUDED.last_alert_at - make_interval(mins => $4)",
+    )
+    .bind(source_id)
+    .bind(device_id)
+    .bind(Utc::now())
+    .bind(cooldown_minutes)
+    .execute(pool)
+    .await?;
+    Ok(claimed.rows_affected() == 1)
+}
+
+/// Evaluate the geofences and alert rules covering a device on a freshly stored sample and
+/// notify the owner, and the teams of the regions the device is in, of each that fires.
+/// Returns how many fired.
+pub async fn evaluate_alerts(
+    pool: &PgPool,
+    user_id: Uuid,
+    device_id: Uuid,
+    sample: &serde_json::Value,
+) -> ApiResult<usize> {
+    let alerts = sqlx::query_as::<_, ActiveAlert>(
+        "SELECT r.id, r.name, r.severity, r.cooldown_minutes, r.condition, NULL::jsonb AS bounds \
+         FROM alert_rules r LEFT JOIN fleets f ON f.id = r.fleet_id \
+         WHERE r.user_id = $1 AND r.enabled AND (r.fleet_id IS NULL OR $2 = ANY(f.device_ids)) \
+         UNION ALL \
+         SELECT g.id, g.name, g.severity, g.cooldown_minutes, NULL::jsonb, g.bounds \
+         FROM geofences g LEFT JOIN fleets f ON f.id = g.fleet_id \
+         WHERE g.user_id = $1 AND (g.fleet_id IS NULL OR $2 = ANY(f.device_ids))",
+    )
+    .bind(user_id)
+    .bind(device_id)
+    .fetch_all(pool)
+    .await?;
+    if alerts.is_empty() {
+        return Ok(0);
+    }
+
+    let mut event = sample.clone();
+    if let Some(map) = event.as_object_mut() {
+        map.insert("device_id".to_string(), serde_json::json!(device_id));
+    }
+    let position = (
+        lookup(&event, "position.latitude").and_then(|v| v.as_f64()),
+        lookup(&event, "position.longitude").and_then(|v| v.as_f64()),
+    );
+
+    let regional_team = region_services::alert_recipients(pool, device_id, user_id).await?;
+    let mut fired = 0;
+    for alert in alerts {
+        let (title, message, data) = match (&alert.condition, &alert.bounds, position) {
+            (Some(condition), _, _) if evaluate(condition, &event) => (
+                format!("{} alert: {}", alert.severity, alert.name),
+                format!(
+                    "Alert rule '{}' matched: {} {} {}",
+                    alert.name, condition.field, condition.op, condition.value
+                ),
+                serde_json::json!({ "device_id": device_id, "alert_rule_id": alert.id, "severity": alert.severity }),
+            ),
+            (None, Some(bounds), (Some(latitude), Some(longitude))) if !bounds_contain(bounds, latitude, longitude) => (
+                format!("{} alert: {}", alert.severity, alert.name),
+                format!("Device left geofence '{}' at {:.5}, {:.5}", alert.name, latitude, longitude),
+                serde_json::json!({ "device_id": device_id, "geofence_id": alert.id, "severity": alert.severity }),
+            ),
+            _ => continue,
+        };
+        if !claim_alert(pool, alert.id, device_id, alert.cooldown_minutes).await? {
+            continue;
+        }
+        let mut conn = pool.acquire().await?;
+        notify_user(&mut conn, user_id, "telemetry_alert", &title, &message, data.clone()).await?;
+        // Regional teams hear about devices in their regions too
+        for recipient in &regional_team {
+            notify_user(&mut conn, *recipient, "telemetry_alert", &title, &message, data.clone()).await?;
+        }
+        fired += 1;
+    }
+    Ok(fired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fleet(name: &str, devices: Vec<Uuid>) -> FleetSpec {
+        FleetSpec { name: name.to_string(), description: None, devices }
+    }
+
+    #[test]
+    fn test_parse_document() {
+        let yaml = b"fleets:\n  - name: north\n    devices: []\n\
+                     alert_rules:\n  - name: low battery\n    fleet: north\n    \
+                     condition: { field: battery_level, op: lt, value: 20 }\n";
+        let config = parse_document("application/yaml", yaml).unwrap();
+        assert_eq!(config.fleets.as_ref().unwrap()[0].name, "north");
+        let rule = &config.alert_rules.as_ref().unwrap()[0];
+        assert_eq!((rule.severity.as_str(), rule.cooldown_minutes, rule.enabled), ("warning", 15, true));
+        assert!(config.tags.is_none() && config.automations.is_none());
+
+        let json = br#"{ "fleets": [{ "name": "north" }] }"#;
+        assert!(parse_document("application/json", json).unwrap().fleets.unwrap()[0].devices.is_empty());
+        // Typos are rejected rather than silently ignored
+        assert!(parse_document("application/json", br#"{ "fleet": [] }"#).is_err());
+    }
+
+    #[test]
+    fn test_plan() {
+        let device = Uuid::new_v4();
+        let current = CurrentState {
+            fleets: vec![(Uuid::new_v4(), fleet("north", vec![])), (Uuid::new_v4(), fleet("south", vec![]))],
+            tags: BTreeMap::from([(device, vec!["old".to_string()])]),
+            ..Default::default()
+        };
+        let desired = FleetConfig {
+            fleets: Some(vec![fleet("north", vec![device]), fleet("east", vec![])]),
+            tags: Some(BTreeMap::new()),
+            ..Default::default()
+        };
+
+        let changes: Vec<(&str, String, &str)> =
+            plan(&desired, &current).into_iter().map(|c| (c.resource, c.name, c.action)).collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("fleet", "north".to_string(), "update"),
+                ("fleet", "east".to_string(), "create"),
+                ("fleet", "south".to_string(), "delete"),
+                ("tags", device.to_string(), "update"),
+            ]
+        );
+
+        // Omitted sections are left alone, and an exported state plans to nothing
+        assert!(plan(&FleetConfig::default(), &current).is_empty());
+        assert!(plan(&export(current.clone()), &current).is_empty());
+    }
+
+    #[test]
+    fn test_check_references() {
+        let device = Uuid::new_v4();
+        let current = CurrentState { tags: BTreeMap::from([(device, vec![])]), ..Default::default() };
+        let rule = AlertRuleSpec {
+            name: "hot".to_string(),
+            fleet: Some("north".to_string()),
+            condition: Condition { field: "temperature".to_string(), op: "gt".to_string(), value: 60.into() },
+            severity: "critical".to_string(),
+            cooldown_minutes: 15,
+            enabled: true,
+        };
+
+        let mut config = FleetConfig { alert_rules: Some(vec![rule]), ..Default::default() };
+        assert!(check_references(&config, &current).is_err());
+        config.fleets = Some(vec![fleet("north", vec![device])]);
+        assert!(check_references(&config, &current).is_ok());
+        config.fleets = Some(vec![fleet("north", vec![Uuid::new_v4()])]);
+        assert!(matches!(check_references(&config, &current), Err(ApiError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = |tags: Vec<&str>| FleetConfig {
+            tags: Some(BTreeMap::from([(Uuid::nil(), tags.into_iter().map(String::from).collect())])),
+            ..Default::default()
+        };
+        assert!(validate(&config(vec!["zone:3", "north"])).is_ok());
+        assert!(validate(&config(vec!["bad tag"])).is_err());
+        assert!(validate(&config(vec!["a", "a"])).is_err());
+
+        let duplicate =
+            FleetConfig { fleets: Some(vec![fleet("a", vec![]), fleet("a", vec![])]), ..Default::default() };
+        assert!(validate(&duplicate).is_err());
+    }
+}
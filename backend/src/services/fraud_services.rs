@@ -0,0 +1,290 @@
+//! Screening of payments before they start. Each card payment, crypto invoice and token transfer
+//! is checked against the user's spend limits and a few fraud rules: many attempts in a short
+//! time, repeated card failures, and a payment from a country the user never signed in from. An
+//! attempt matching a rule is not approved silently; it is refused and kept as a payment review.
+//! While a review is open the user cannot pay; once an admin approves it the rules are waived
+//! for a day so the payment can be retried.
+
+use chrono::{Duration, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::spending::PaymentReview;
+use crate::services::notification_services::notify_user;
+use crate::services::{razorpay_services, spending_services, stripe_services};
+use crate::utils::log_security_event;
+
+const REVIEW_COLUMNS: &str = "id, user_id, kind, amount, currency, amount_usd, country, flags, status, review_note, \
+     reviewed_by, reviewed_at, created_at";
+
+pub const STATUSES: &[&str] = &["open", "approved", "rejected"];
+const MAX_NOTE_CHARS: usize = 2_000;
+
+/// Payments started within this many minutes count as retries of each other
+const RETRY_WINDOW_MINUTES: i64 = 10;
+const MAX_RETRIES: i64 = 5;
+const FAILED_CARD_WINDOW_HOURS: i64 = 24;
+const MAX_FAILED_CARDS: i64 = 3;
+const CARD_PROVIDERS: &[&str] = &[stripe_services::PROVIDER, razorpay_services::PROVIDER];
+/// Sign-ins whose countries count as the user's own
+const LOGIN_HISTORY_DAYS: i64 = 90;
+/// How long an approved review waives the rules
+const APPROVAL_GRACE_HOURS: i64 = 24;
+
+/// A payment about to start
+#[derive(Debug, Clone)]
+pub struct PaymentAttempt<'a> {
+    pub user_id: Uuid,
+    /// card, crypto_invoice, or the product type of a relayed transfer
+    pub kind: &'a str,
+    pub amount: Decimal,
+    /// Fiat code or token symbol
+    pub currency: &'a str,
+    /// Where the request came from, per the edge
+    pub country: Option<&'a str>,
+}
+
+/// What the rules look at
+#[derive(Debug, Clone, Default)]
+pub struct Signals {
+    /// Payments the user started in the retry window, not counting this one
+    pub recent_attempts: i64,
+    pub failed_card_payments: i64,
+    pub country: Option<String>,
+    /// Countries of the user's recent successful sign-ins
+    pub login_countries: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum FraudFlag {
+    RapidRetries { attempts: i64, minutes: i64 },
+    FailedCards { failures: i64, hours: i64 },
+    GeoMismatch { country: String, login_countries: Vec<String> },
+}
+
+impl FraudFlag {
+    pub fn describe(&self) -> String {
+        match self {
+            FraudFlag::RapidRetries { attempts, minutes } => {
+                format!("{} payments started in the last {} minutes", attempts, minutes)
+            }
+            FraudFlag::FailedCards { failures, hours } => {
+                format!("{} failed card payments in the last {} hours", failures, hours)
+            }
+            FraudFlag::GeoMismatch { country, .. } => {
+                format!("Payment from {}, where the account has not signed in from", country)
+            }
+        }
+    }
+}
+
+/// The rules an attempt with these signals matches
+pub fn evaluate(signals: &Signals) -> Vec<FraudFlag> {
+    let mut flags = Vec::new();
+    if signals.recent_attempts + 1 >= MAX_RETRIES {
+        flags.push(FraudFlag::RapidRetries {
+            attempts: signals.recent_attempts + 1,
+            minutes: RETRY_WINDOW_MINUTES,
+        });
+    }
+    if signals.failed_card_payments >= MAX_FAILED_CARDS {
+        flags.push(FraudFlag::FailedCards {
+            failures: signals.failed_card_payments,
+            hours: FAILED_CARD_WINDOW_HOURS,
+        });
+    }
+    // Without a sign-in history there is nothing to compare with
+    let known = &signals.login_countries;
+    if let Some(country) = signals.country.as_ref().filter(|c| !known.is_empty() && !known.contains(c)) {
+        flags.push(FraudFlag::GeoMismatch {
+            country: country.clone(),
+            login_countries: known.clone(),
+        });
+    }
+    flags
+}
+
+async fn signals(pool: &PgPool, user_id: Uuid, country: Option<&str>) -> ApiResult<Signals> {
+    let now = Utc::now();
+    let (recent_attempts, failed_card_payments): (i64, i64) = sqlx::query_as(
+        "SELECT COUNT(*) FILTER (WHERE created_at >= $2), \
+             COUNT(*) FILTER (WHERE status = 'failed' AND payment_method = ANY($4)) \
+         FROM transactions WHERE user_id = $1 AND created_at >= LEAST($2, $3)",
+    )
+    .bind(user_id)
+    .bind(now - Duration::minutes(RETRY_WINDOW_MINUTES))
+    .bind(now - Duration::hours(FAILED_CARD_WINDOW_HOURS))
+    .bind(CARD_PROVIDERS)
+    .fetch_one(pool)
+    .await?;
+    let login_countries: Vec<String> = sqlx::query_scalar(
+        "SELECT DISTINCT country FROM auth_events \
+         WHERE user_id = $1 AND event = 'login' AND success AND country IS NOT NULL AND created_at >= $2",
+    )
+    .bind(user_id)
+    .bind(now - Duration::days(LOGIN_HISTORY_DAYS))
+    .fetch_all(pool)
+    .await?;
+    Ok(Signals {
+        recent_attempts,
+        failed_card_payments,
+        country: country.map(str::to_uppercase),
+        login_countries,
+    })
+}
+
+/// Let a payment start, or refuse it: over a spend limit, while a review is open, or because it
+/// matches a fraud rule, in which case it is held as a new review
+pub async fn screen(pool: &PgPool, config: &AppConfig, attempt: &PaymentAttempt<'_>) -> ApiResult<()> {
+    let latest: Option<(String, bool)> = sqlx::query_as(
+        "SELECT status, status = 'approved' AND reviewed_at >= $2 FROM payment_reviews \
+         WHERE user_id = $1 ORDER BY created_at DESC LIMIT 1",
+    )
+    .bind(attempt.user_id)
+    .bind(Utc::now() - Duration::hours(APPROVAL_GRACE_HOURS))
+    .fetch_optional(pool)
+    .await?;
+    if latest.as_ref().is_some_and(|(status, _)| status == "open") {
+        return Err(ApiError::Forbidden(
+            "A payment of yours is awaiting review; try again once it is cleared".to_string(),
+        ));
+    }
+
+    let amount_usd = spending_services::value_usd(config, attempt.amount, attempt.currency).await;
+    spending_services::check(pool, config, attempt.user_id, amount_usd.unwrap_or_default()).await?;
+    if latest.is_some_and(|(_, waived)| waived) {
+        return Ok(());
+    }
+
+    let flags = evaluate(&signals(pool, attempt.user_id, attempt.country).await?);
+    if flags.is_empty() {
+        return Ok(());
+    }
+    let review_id = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO payment_reviews (id, user_id, kind, amount, currency, amount_usd, country, flags) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(review_id)
+    .bind(attempt.user_id)
+    .bind(attempt.kind)
+    .bind(attempt.amount)
+    .bind(attempt.currency)
+    .bind(amount_usd)
+    .bind(attempt.country)
+    .bind(serde_json::to_value(&flags).unwrap_or_default())
+    .execute(pool)
+    .await?;
+
+    let reasons: Vec<String> = flags.iter().map(FraudFlag::describe).collect();
+    log_security_event(
+        "payment_held",
+        None,
+        &format!("review {} for user {}: {}", review_id, attempt.user_id, reasons.join("; ")),
+    );
+    Err(ApiError::Forbidden(
+        "This payment has been held for review; you will be notified once it is cleared".to_string(),
+    ))
+}
+
+/// Reviews in `status`, open by default, oldest first
+pub async fn queue(pool: &PgPool, status: Option<&str>) -> ApiResult<Vec<PaymentReview>> {
+    let status = status.unwrap_or("open");
+    if !STATUSES.contains(&status) {
+        return Err(ApiError::ValidationError(format!("status must be one of {}", STATUSES.join(", "))));
+    }
+    let reviews = sqlx::query_as::<_, PaymentReview>(&format!(
+        "SELECT {} FROM payment_reviews WHERE status = $1 ORDER BY created_at LIMIT 200",
+        REVIEW_COLUMNS
+    ))
+    .bind(status)
+    .fetch_all(pool)
+    .await?;
+    Ok(reviews)
+}
+
+/// Approve an open review, letting the user pay again without the rules for a day, or reject it,
+/// and tell the user
+pub async fn resolve(
+    pool: &PgPool,
+    admin_id: Uuid,
+    review_id: Uuid,
+    approve: bool,
+    note: Option<&str>,
+) -> ApiResult<PaymentReview> {
+    let note = note.map(str::trim).filter(|n| !n.is_empty());
+    if note.is_some_and(|n| n.chars().count() > MAX_NOTE_CHARS) {
+        return Err(ApiError::ValidationError(format!("note must be at most {} characters", MAX_NOTE_CHARS)));
+    }
+
+    let mut tx = pool.begin().await?;
+    let review = sqlx::query_as::<_, PaymentReview>(&format!(
+        "UPDATE payment_reviews SET status = $2, review_note = $3, reviewed_by = $4, reviewed_at = NOW() \
+         WHERE id = $1 AND status = 'open' RETURNING {}",
+        REVIEW_COLUMNS
+    ))
+    .bind(review_id)
+    .bind(if approve { "approved" } else { "rejected" })
+    .bind(note)
+    .bind(admin_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::Conflict("No open payment review with this id".to_string()))?;
+
+    let (title, body) = if approve {
+        let body = format!("Your payment of {} {} was reviewed; you can retry it now.", review.amount, review.currency);
+        ("Payment cleared", body)
+    } else {
+        let body = format!("Your payment of {} {} was declined after review.", review.amount, review.currency);
+        ("Payment declined", body)
+    };
+    notify_user(
+        &mut tx,
+        review.user_id,
+        &format!("payment_review.{}", review.status),
+        title,
+        &body,
+        serde_json::json!({ "review_id": review.id, "status": review.status }),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(review)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        assert!(evaluate(&Signals::default()).is_empty());
+
+        let retries = Signals { recent_attempts: 4, ..Default::default() };
+        assert_eq!(evaluate(&retries), vec![FraudFlag::RapidRetries { attempts: 5, minutes: 10 }]);
+        assert!(evaluate(&Signals { recent_attempts: 3, ..Default::default() }).is_empty());
+
+        let failures = Signals { failed_card_payments: 3, ..Default::default() };
+        assert_eq!(evaluate(&failures), vec![FraudFlag::FailedCards { failures: 3, hours: 24 }]);
+    }
+
+    #[test]
+    fn test_evaluate_geo_mismatch() {
+        let signals = |country: Option<&str>, known: &[&str]| Signals {
+            country: country.map(str::to_string),
+            login_countries: known.iter().map(|c| c.to_string()).collect(),
+            ..Default::default()
+        };
+        assert!(evaluate(&signals(Some("GB"), &["GB", "FR"])).is_empty());
+        // No edge country, or no sign-in history to compare with
+        assert!(evaluate(&signals(None, &["GB"])).is_empty());
+        assert!(evaluate(&signals(Some("BR"), &[])).is_empty());
+
+        let flags = evaluate(&signals(Some("BR"), &["GB"]));
+        assert!(matches!(flags.as_slice(), [FraudFlag::GeoMismatch { country, .. }] if country == "BR"));
+        assert_eq!(serde_json::to_value(&flags[0]).unwrap()["rule"], "geo_mismatch");
+    }
+}
@@ -0,0 +1,183 @@
+//! EIP-1559 fee estimation from `eth_feeHistory`: the next block's base fee, and priority fee
+//! tiers taken from what recent blocks actually paid.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use crate::errors::{ApiError, ApiResult};
+use crate::services::crypto_services::{format_units, parse_quantity, BlockchainService};
+
+/// Blocks of history the priority fee tiers are drawn from
+const FEE_HISTORY_BLOCKS: u64 = 20;
+/// Reward percentiles of each block backing the slow, standard and fast tiers
+const TIER_PERCENTILES: [(&str, f64); 3] = [("slow", 10.0), ("standard", 50.0), ("fast", 90.0)];
+/// Typical gas of an ERC-20 `transfer`; a first transfer to an empty balance costs the most
+pub const TOKEN_TRANSFER_GAS: u64 = 65_000;
+/// Roughly one block on the slowest supported chain
+const GAS_CACHE_TTL: Duration = Duration::from_secs(12);
+
+/// Estimates by chain, with when they were made
+static GAS_CACHE: LazyLock<Mutex<HashMap<u64, (Instant, GasEstimate)>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeTier {
+    pub name: &'static str,
+    /// In wei
+    pub max_priority_fee_per_gas: String,
+    /// Twice the base fee plus the tip, which survives several full blocks of base fee increases
+    pub max_fee_per_gas: String,
+    /// Cost of a token transfer at the current base fee, in whole native coins
+    pub estimated_transfer_cost: String,
+    /// The most a token transfer can cost with these settings, in whole native coins
+    pub max_transfer_cost: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GasEstimate {
+    pub chain_id: u64,
+    pub native_symbol: String,
+    /// The latest block the history ends with
+    pub block_number: u64,
+    /// Base fee of the next block, in wei
+    pub base_fee_per_gas: String,
+    pub base_fee_gwei: String,
+    pub token_transfer_gas: u64,
+    pub tiers: Vec<FeeTier>,
+}
+
+/// The median of the per-block values; `0` without any
+fn median(mut values: Vec<u64>) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        ((values[mid - 1] as u128 + values[mid] as u128) / 2) as u64
+    } else {
+        values[mid]
+    }
+}
+
+/// Fee tiers for a transaction using `gas` at `base_fee`, from each recent block's rewards at
+/// the tier percentiles (one column per tier)
+pub fn fee_tiers(base_fee: u64, rewards: &[Vec<u64>], gas: u64) -> Vec<FeeTier> {
+    let wei = |amount: u128| format_units(&amount.to_string(), 18);
+    TIER_PERCENTILES
+        .iter()
+        .enumerate()
+        .map(|(column, (name, _))| {
+            // Empty blocks report a zero reward, which says nothing about the going tip
+            let tips: Vec<u64> =
+                rewards.iter().filter_map(|block| block.get(column).copied()).filter(|t| *t > 0).collect();
+            let tip = median(tips) as u128;
+            let max_fee = 2 * base_fee as u128 + tip;
+            FeeTier {
+                name,
+                max_priority_fee_per_gas: tip.to_string(),
+                max_fee_per_gas: max_fee.to_string(),
+                estimated_transfer_cost: wei((base_fee as u128 + tip) * gas as u128),
+                max_transfer_cost: wei(max_fee * gas as u128),
+            }
+        })
+        .collect()
+}
+
+/// Read the fee history and build the estimate
+async fn estimate(chain: &BlockchainService) -> ApiResult<GasEstimate> {
+    let percentiles: Vec<f64> = TIER_PERCENTILES.iter().map(|(_, p)| *p).collect();
+    let history = chain
+        .rpc_call(
+            "eth_feeHistory",
+            serde_json::json!([format!("0x{:x}", FEE_HISTORY_BLOCKS), "latest", percentiles]),
+        )
+        .await?;
+    let malformed = || ApiError::BlockchainError("eth_feeHistory returned malformed data".to_string());
+
+    // One more base fee than blocks: the last is the next block's
+    let base_fee = history
+        .get("baseFeePerGas")
+        .and_then(|fees| fees.as_array()?.last())
+        .and_then(parse_quantity)
+        .ok_or_else(malformed)?;
+    let oldest = history.get("oldestBlock").and_then(parse_quantity).ok_or_else(malformed)?;
+    let rewards: Vec<Vec<u64>> = history
+        .get("reward")
+        .and_then(|r| r.as_array())
+        .map(|blocks| {
+            blocks
+                .iter()
+                .map(|block| {
+                    block.as_array().map(|tips| tips.iter().filter_map(parse_quantity).collect()).unwrap_or_default()
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(GasEstimate {
+        chain_id: chain.chain_id(),
+        native_symbol: chain.native_symbol().to_string(),
+        block_number: oldest + (rewards.len() as u64).saturating_sub(1),
+        base_fee_per_gas: base_fee.to_string(),
+        base_fee_gwei: format_units(&base_fee.to_string(), 9),
+        token_transfer_gas: TOKEN_TRANSFER_GAS,
+        tiers: fee_tiers(base_fee, &rewards, TOKEN_TRANSFER_GAS),
+    })
+}
+
+/// Current fees on the chain, cached for about a block
+pub async fn gas_estimate(chain: &BlockchainService) -> ApiResult<GasEstimate> {
+    let cached = GAS_CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&chain.chain_id())
+        .filter(|(read_at, _)| read_at.elapsed() < GAS_CACHE_TTL)
+        .map(|(_, estimate)| estimate.clone());
+    if let Some(estimate) = cached {
+        return Ok(estimate);
+    }
+
+    let estimate = estimate(chain).await?;
+    GAS_CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(chain.chain_id(), (Instant::now(), estimate.clone()));
+    Ok(estimate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(vec![]), 0);
+        assert_eq!(median(vec![5, 1, 3]), 3);
+        assert_eq!(median(vec![4, 1, 3, 2]), 2);
+    }
+
+    #[test]
+    fn test_fee_tiers() {
+        let gwei = 1_000_000_000;
+        let rewards = vec![
+            vec![gwei, 2 * gwei, 5 * gwei],
+            vec![0, 0, 0], // an empty block
+            vec![gwei, 2 * gwei, 3 * gwei],
+            vec![gwei, 2 * gwei, 4 * gwei],
+        ];
+        let tiers = fee_tiers(10 * gwei, &rewards, 50_000);
+
+        let names: Vec<&str> = tiers.iter().map(|t| t.name).collect();
+        assert_eq!(names, ["slow", "standard", "fast"]);
+        assert_eq!(tiers[1].max_priority_fee_per_gas, (2 * gwei).to_string());
+        assert_eq!(tiers[2].max_priority_fee_per_gas, (4 * gwei).to_string());
+        assert_eq!(tiers[1].max_fee_per_gas, (22 * gwei).to_string());
+        // (10 + 2) gwei * 50,000 gas and (20 + 2) gwei * 50,000 gas
+        assert_eq!(tiers[1].estimated_transfer_cost, "0.0006");
+        assert_eq!(tiers[1].max_transfer_cost, "0.0011");
+
+        let quiet = fee_tiers(gwei, &[], 21_000);
+        assert!(quiet.iter().all(|t| t.max_priority_fee_per_gas == "0"));
+    }
+}
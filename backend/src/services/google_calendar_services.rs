@@ -0,0 +1,662 @@
+//! Two-way sync of maintenance windows with a user's Google Calendar.
+//!
+//! Windows are written to the calendar as events tagged with the window id. Google announces
+//! changes to the calendar on a push notification channel; the changed events are then fetched
+//! incrementally with a sync token, and moved, renamed or deleted events adjust their windows.
+//! Channels expire, so a background job renews them and catches up on missed notifications.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use secrecy::ExposeSecret;
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::{PgConnection, PgPool};
+use std::sync::{Arc, LazyLock};
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::calendar::MaintenanceWindow;
+use crate::services::calendar_services::{validate_window, WINDOW_COLUMNS};
+use crate::services::key_services::{decrypt_field, encrypt_field, KeyManager};
+use crate::utils::{generate_random_hex, sha256_hash};
+
+const AUTHORIZATION_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const API_BASE: &str = "https://www.googleapis.com/calendar/v3";
+const SCOPE: &str = "https://www.googleapis.com/auth/calendar.events";
+
+pub const CONNECTION_COLUMNS: &str =
+    "id, user_id, calendar_id, status, channel_expires_at, last_synced_at, last_error, created_at";
+
+/// Private extended property linking a Google event to its maintenance window
+pub const WINDOW_PROPERTY: &str = "roboveda_window_id";
+/// How long a connect link stays valid
+pub const OAUTH_STATE_MINUTES: i64 = 15;
+/// Channel lifetime asked of Google; it may grant less
+const CHANNEL_TTL_SECS: i64 = 7 * 24 * 60 * 60;
+/// Channels expiring within this window are renewed
+const CHANNEL_RENEW_HOURS: i64 = 24;
+const JOB_INTERVAL_SECS: u64 = 60 * 60;
+/// Event pages fetched per sync, bounding a full resync of a large calendar
+const MAX_SYNC_PAGES: usize = 40;
+
+static GOOGLE_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(15))
+        .build()
+        .expect("Failed to build Google Calendar HTTP client")
+});
+
+/// The configured OAuth client, or `None` when Google Calendar sync is not set up
+pub struct GoogleOAuth {
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+}
+
+impl GoogleOAuth {
+    pub fn from_config(config: &AppConfig) -> Option<Self> {
+        Some(Self {
+            client_id: config.google_calendar_client_id.clone()?,
+            client_secret: config.google_calendar_client_secret.as_ref()?.expose_secret().to_string(),
+            redirect_uri: format!("{}/api/calendar/google/callback", config.api_base_url.trim_end_matches('/')),
+        })
+    }
+
+    /// Consent screen asking for offline access, so a refresh token is returned
+    pub fn authorization_url(&self, state: &str) -> String {
+        reqwest::Url::parse_with_params(
+            AUTHORIZATION_URL,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.client_id.as_str()),
+                ("redirect_uri", self.redirect_uri.as_str()),
+                ("scope", SCOPE),
+                ("access_type", "offline"),
+                ("prompt", "consent"),
+                ("state", state),
+            ],
+        )
+        .expect("authorization URL is valid")
+        .to_string()
+    }
+
+    async fn token(&self, params: &[(&str, &str)]) -> ApiResult<TokenResponse> {
+        let mut form = vec![("client_id", self.client_id.as_str()), ("client_secret", self.client_secret.as_str())];
+        form.extend_from_slice(params);
+        let response = GOOGLE_CLIENT.post(TOKEN_URL).form(&form).send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(ApiError::ExternalServiceError(format!("Google token request failed ({}): {}", status, body)));
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Exchange the consent code for a refresh token
+    pub async fn exchange_code(&self, code: &str) -> ApiResult<String> {
+        let token = self
+            .token(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", self.redirect_uri.as_str()),
+            ])
+            .await?;
+        token
+            .refresh_token
+            .ok_or_else(|| ApiError::ExternalServiceError("Google did not return a refresh token".to_string()))
+    }
+
+    pub async fn access_token(&self, refresh_token: &str) -> ApiResult<String> {
+        let token = self.token(&[("grant_type", "refresh_token"), ("refresh_token", refresh_token)]).await?;
+        Ok(token.access_token)
+    }
+}
+
+fn api_url(calendar_id: &str, path: &[&str]) -> reqwest::Url {
+    let mut url = reqwest::Url::parse(API_BASE).expect("API base is valid");
+    url.path_segments_mut()
+        .expect("API base has a path")
+        .extend(["calendars", calendar_id])
+        .extend(path);
+    url
+}
+
+async fn checked(response: reqwest::Response, action: &str) -> ApiResult<reqwest::Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    Err(ApiError::ExternalServiceError(format!("Google Calendar {} failed ({}): {}", action, status, body)))
+}
+
+/// A change to a tagged event, as it applies to its window
+#[derive(Debug, PartialEq)]
+pub enum WindowChange {
+    /// Deleted events come back with only their id, so either may identify the window
+    Cancelled { window_id: Option<Uuid>, event_id: Option<String> },
+    Updated { window_id: Uuid, title: Option<String>, starts_at: DateTime<Utc>, ends_at: DateTime<Utc> },
+}
+
+/// `start`/`end` of an event: a `dateTime`, or a `date` for all-day events (midnight UTC)
+fn event_time(value: &Value) -> Option<DateTime<Utc>> {
+    if let Some(at) = value["dateTime"].as_str() {
+        return DateTime::parse_from_rfc3339(at).ok().map(|at| at.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value["date"].as_str()?, "%Y-%m-%d").ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+/// What a changed Google event means for its window; events we did not create are ignored
+pub fn window_change(event: &Value) -> Option<WindowChange> {
+    let window_id: Option<Uuid> = event["extendedProperties"]["private"][WINDOW_PROPERTY]
+        .as_str()
+        .and_then(|id| id.parse().ok());
+    if event["status"].as_str() == Some("cancelled") {
+        let event_id = event["id"].as_str().map(str::to_string);
+        return (window_id.is_some() || event_id.is_some()).then_some(WindowChange::Cancelled { window_id, event_id });
+    }
+    Some(WindowChange::Updated {
+        window_id: window_id?,
+        title: event["summary"].as_str().map(str::trim).filter(|t| !t.is_empty()).map(str::to_string),
+        starts_at: event_time(&event["start"])?,
+        ends_at: event_time(&event["end"])?,
+    })
+}
+
+/// Apply a change made in Google to the user's window; returns whether the window changed.
+/// Changes that would break window limits are skipped.
+pub async fn apply_change(conn: &mut PgConnection, user_id: Uuid, change: &WindowChange) -> ApiResult<bool> {
+    let updated = match change {
+        WindowChange::Cancelled { window_id, event_id } => {
+            sqlx::query(
+                "UPDATE maintenance_windows SET status = 'cancelled', sequence = sequence + 1, \
+                 updated_via = 'google', updated_at = NOW() \
+                 WHERE user_id = $2 AND status <> 'cancelled' AND (id = $1 OR google_event_id = $3)",
+            )
+            .bind(window_id)
+            .bind(user_id)
+            .bind(event_id)
+            .execute(conn)
+            .await?
+        }
+        WindowChange::Updated { window_id, title, starts_at, ends_at } => {
+            if let Err(e) = validate_window(title.as_deref().unwrap_or("-"), *starts_at, *ends_at) {
+                tracing::warn!(%window_id, "Skipped Google Calendar change: {}", e);
+                return Ok(false);
+            }
+            sqlx::query(
+                "UPDATE maintenance_windows SET title = COALESCE($3, title), starts_at = $4, ends_at = $5, \
+                 sequence = sequence + 1, updated_via = 'google', updated_at = NOW() \
+                 WHERE id = $1 AND user_id = $2 AND status = 'scheduled' \
+                 AND (title <> COALESCE($3, title) OR starts_at <> $4 OR ends_at <> $5)",
+            )
+            .bind(window_id)
+            .bind(user_id)
+            .bind(title)
+            .bind(starts_at)
+            .bind(ends_at)
+            .execute(conn)
+            .await?
+        }
+    };
+    Ok(updated.rows_affected() > 0)
+}
+
+fn event_body(window: &MaintenanceWindow, device_name: Option<&str>) -> Value {
+    let mut description = window.notes.clone().unwrap_or_default();
+    if !description.is_empty() {
+        description.push_str("\n\n");
+    }
+    description.push_str(&format!("Devices: {}", device_name.unwrap_or("whole fleet")));
+    serde_json::json!({
+        "summary": window.title,
+        "description": description,
+        "start": { "dateTime": window.starts_at.to_rfc3339() },
+        "end": { "dateTime": window.ends_at.to_rfc3339() },
+        "extendedProperties": { "private": { WINDOW_PROPERTY: window.id.to_string() } },
+    })
+}
+
+#[derive(sqlx::FromRow)]
+struct ActiveConnection {
+    id: Uuid,
+    user_id: Uuid,
+    calendar_id: String,
+    refresh_token: String,
+    channel_id: Option<Uuid>,
+    resource_id: Option<String>,
+    sync_token: Option<String>,
+}
+
+const ACTIVE_CONNECTION_COLUMNS: &str =
+    "id, user_id, calendar_id, refresh_token, channel_id, resource_id, sync_token";
+
+async fn active_connection(pool: &PgPool, user_id: Uuid) -> ApiResult<Option<ActiveConnection>> {
+    let connection = sqlx::query_as::<_, ActiveConnection>(&format!(
+        "SELECT {} FROM google_calendar_connections \
+         WHERE user_id = $1 AND status <> 'pending' AND refresh_token IS NOT NULL",
+        ACTIVE_CONNECTION_COLUMNS
+    ))
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(connection)
+}
+
+async fn access_for(oauth: &GoogleOAuth, keys: &dyn KeyManager, connection: &ActiveConnection) -> ApiResult<String> {
+    let refresh_token = String::from_utf8(decrypt_field(keys, &connection.refresh_token).await?)
+        .map_err(|_| ApiError::InternalError("Stored refresh token is not UTF-8".to_string()))?;
+    oauth.access_token(&refresh_token).await
+}
+
+/// Write a window to the owner's connected calendar: create or update its event, or delete it
+/// once the window is cancelled. Does nothing without a connection.
+pub async fn push_window(pool: &PgPool, config: &AppConfig, keys: &dyn KeyManager, window_id: Uuid) -> ApiResult<()> {
+    let Some(oauth) = GoogleOAuth::from_config(config) else {
+        return Ok(());
+    };
+    let window = sqlx::query_as::<_, MaintenanceWindow>(&format!(
+        "SELECT {} FROM maintenance_windows WHERE id = $1",
+        WINDOW_COLUMNS
+    ))
+    .bind(window_id)
+    .fetch_one(pool)
+    .await?;
+    let Some(connection) = active_connection(pool, window.user_id).await? else {
+        return Ok(());
+    };
+    let access = access_for(&oauth, keys, &connection).await?;
+
+    match (&window.google_event_id, window.status.as_str()) {
+        (Some(event_id), "cancelled") => {
+            let response = GOOGLE_CLIENT
+                .delete(api_url(&connection.calendar_id, &["events", event_id]))
+                .bearer_auth(&access)
+                .send()
+                .await?;
+            // Already deleted on Google's side
+            if !matches!(response.status().as_u16(), 404 | 410) {
+                checked(response, "event delete").await?;
+            }
+        }
+        (None, "cancelled") => {}
+        (event_id, _) => {
+            let device_name: Option<String> = match window.device_id {
+                Some(device_id) => sqlx::query_scalar("SELECT device_name FROM devices WHERE id = $1")
+                    .bind(device_id)
+                    .fetch_optional(pool)
+                    .await?,
+                None => None,
+            };
+            let body = event_body(&window, device_name.as_deref());
+            let request = match event_id {
+                Some(event_id) => GOOGLE_CLIENT.patch(api_url(&connection.calendar_id, &["events", event_id])),
+                None => GOOGLE_CLIENT.post(api_url(&connection.calendar_id, &["events"])),
+            };
+            let response = checked(request.bearer_auth(&access).json(&body).send().await?, "event write").await?;
+            let event: Value = response.json().await?;
+            if event_id.is_none()
+                && let Some(id) = event["id"].as_str()
+            {
+                sqlx::query("UPDATE maintenance_windows SET google_event_id = $2 WHERE id = $1")
+                    .bind(window.id)
+                    .bind(id)
+                    .execute(pool)
+                    .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Push a window in the background; the window is saved either way
+pub fn spawn_push_window(pool: Arc<PgPool>, config: AppConfig, keys: Arc<dyn KeyManager>, window_id: Uuid) {
+    tokio::spawn(async move {
+        if let Err(e) = push_window(&pool, &config, keys.as_ref(), window_id).await {
+            tracing::warn!(%window_id, "Pushing maintenance window to Google Calendar failed: {}", e);
+        }
+    });
+}
+
+/// Fetch events changed since the stored sync token and apply them. Without a token (or when
+/// Google has expired it) the calendar is listed in full just to obtain a fresh token.
+async fn sync_connection(pool: &PgPool, access: &str, connection: &ActiveConnection) -> ApiResult<usize> {
+    let mut sync_token = connection.sync_token.clone();
+    let mut page_token: Option<String> = None;
+    let mut applied = 0;
+    for _ in 0..MAX_SYNC_PAGES {
+        let mut url = api_url(&connection.calendar_id, &["events"]);
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("maxResults", "250");
+            if let Some(token) = &sync_token {
+                query.append_pair("syncToken", token);
+            }
+            if let Some(token) = &page_token {
+                query.append_pair("pageToken", token);
+            }
+        }
+        let response = GOOGLE_CLIENT.get(url).bearer_auth(access).send().await?;
+        if response.status().as_u16() == 410 {
+            // Sync token expired: start over with a full listing
+            sync_token = None;
+            page_token = None;
+            continue;
+        }
+        let page: Value = checked(response, "event list").await?.json().await?;
+
+        // A full listing only establishes the token; there is nothing to catch up on
+        if sync_token.is_some() {
+            let mut conn = pool.acquire().await?;
+            for event in page["items"].as_array().into_iter().flatten() {
+                if let Some(change) = window_change(event)
+                    && apply_change(&mut conn, connection.user_id, &change).await?
+                {
+                    applied += 1;
+                }
+            }
+        }
+
+        if let Some(next) = page["nextPageToken"].as_str() {
+            page_token = Some(next.to_string());
+            continue;
+        }
+        let next_sync = page["nextSyncToken"].as_str();
+        sqlx::query(
+            "UPDATE google_calendar_connections SET sync_token = COALESCE($2, sync_token), \
+             last_synced_at = NOW(), last_error = NULL, status = 'active' WHERE id = $1",
+        )
+        .bind(connection.id)
+        .bind(next_sync)
+        .execute(pool)
+        .await?;
+        return Ok(applied);
+    }
+    Err(ApiError::ExternalServiceError("Google Calendar sync did not finish within the page limit".to_string()))
+}
+
+/// Open a push channel for the calendar, replacing (and stopping) any previous one
+async fn open_channel(pool: &PgPool, config: &AppConfig, access: &str, connection: &ActiveConnection) -> ApiResult<()> {
+    let channel_id = Uuid::new_v4();
+    let token = generate_random_hex(24);
+    let address = format!("{}/api/calendar/google/notifications", config.api_base_url.trim_end_matches('/'));
+    let body = serde_json::json!({
+        "id": channel_id,
+        "type": "web_hook",
+        "address": address,
+        "token": token,
+        "params": { "ttl": CHANNEL_TTL_SECS.to_string() },
+    });
+    let response = GOOGLE_CLIENT
+        .post(api_url(&connection.calendar_id, &["events", "watch"]))
+        .bearer_auth(access)
+        .json(&body)
+        .send()
+        .await?;
+    let channel: Value = checked(response, "watch").await?.json().await?;
+    let expires_at = channel["expiration"]
+        .as_str()
+        .and_then(|ms| ms.parse::<i64>().ok())
+        .and_then(DateTime::<Utc>::from_timestamp_millis)
+        .unwrap_or_else(|| Utc::now() + Duration::seconds(CHANNEL_TTL_SECS));
+
+    sqlx::query(
+        "UPDATE google_calendar_connections SET channel_id = $2, channel_token_hash = $3, resource_id = $4, \
+         channel_expires_at = $5 WHERE id = $1",
+    )
+    .bind(connection.id)
+    .bind(channel_id)
+    .bind(sha256_hash(token.as_bytes()))
+    .bind(channel["resourceId"].as_str())
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+
+    if let (Some(old_id), Some(resource_id)) = (connection.channel_id, &connection.resource_id) {
+        stop_channel(access, old_id, resource_id).await;
+    }
+    Ok(())
+}
+
+/// Best effort: an unstopped channel simply expires
+async fn stop_channel(access: &str, channel_id: Uuid, resource_id: &str) {
+    let result = GOOGLE_CLIENT
+        .post(format!("{}/channels/stop", API_BASE))
+        .bearer_auth(access)
+        .json(&serde_json::json!({ "id": channel_id, "resourceId": resource_id }))
+        .send()
+        .await;
+    if let Err(e) = result {
+        tracing::warn!(%channel_id, "Stopping Google Calendar channel failed: {}", e);
+    }
+}
+
+async fn record_error(pool: &PgPool, connection_id: Uuid, error: &ApiError) {
+    let result = sqlx::query("UPDATE google_calendar_connections SET status = 'error', last_error = $2 WHERE id = $1")
+        .bind(connection_id)
+        .bind(error.to_string())
+        .execute(pool)
+        .await;
+    if let Err(e) = result {
+        tracing::error!(%connection_id, "Recording Google Calendar error failed: {}", e);
+    }
+}
+
+/// Finish the consent flow: store the refresh token, take the initial sync token, watch the
+/// calendar and write upcoming windows to it. Returns the connected user.
+pub async fn complete_connection(
+    pool: &PgPool,
+    config: &AppConfig,
+    keys: &dyn KeyManager,
+    state: &str,
+    code: &str,
+) -> ApiResult<Uuid> {
+    let oauth = GoogleOAuth::from_config(config)
+        .ok_or_else(|| ApiError::ServiceUnavailable("Google Calendar sync is not configured".to_string()))?;
+    let (connection_id, user_id): (Uuid, Uuid) = sqlx::query_as(
+        "UPDATE google_calendar_connections SET oauth_state = NULL, oauth_state_expires_at = NULL \
+         WHERE oauth_state = $1 AND oauth_state_expires_at > NOW() RETURNING id, user_id",
+    )
+    .bind(state)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::Unauthorized("Google Calendar connect link is invalid or expired".to_string()))?;
+
+    let refresh_token = oauth.exchange_code(code).await?;
+    let sealed = encrypt_field(keys, refresh_token.as_bytes()).await?;
+    sqlx::query(
+        "UPDATE google_calendar_connections SET refresh_token = $2, sync_token = NULL, status = 'active', \
+         last_error = NULL WHERE id = $1",
+    )
+    .bind(connection_id)
+    .bind(&sealed)
+    .execute(pool)
+    .await?;
+
+    let connection = active_connection(pool, user_id)
+        .await?
+        .ok_or_else(|| ApiError::InternalError("Google Calendar connection vanished".to_string()))?;
+    let access = oauth.access_token(&refresh_token).await?;
+    let setup = async {
+        sync_connection(pool, &access, &connection).await?;
+        open_channel(pool, config, &access, &connection).await
+    };
+    if let Err(e) = setup.await {
+        record_error(pool, connection_id, &e).await;
+        return Err(e);
+    }
+
+    let upcoming: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM maintenance_windows WHERE user_id = $1 AND status = 'scheduled' \
+         AND google_event_id IS NULL AND ends_at > NOW() ORDER BY starts_at LIMIT 100",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    for window_id in upcoming {
+        if let Err(e) = push_window(pool, config, keys, window_id).await {
+            tracing::warn!(%window_id, "Pushing maintenance window to Google Calendar failed: {}", e);
+        }
+    }
+    Ok(user_id)
+}
+
+/// Stop watching the calendar and forget the connection; events already written stay
+pub async fn disconnect(pool: &PgPool, config: &AppConfig, keys: &dyn KeyManager, user_id: Uuid) -> ApiResult<bool> {
+    if let (Some(oauth), Some(connection)) = (GoogleOAuth::from_config(config), active_connection(pool, user_id).await?)
+        && let (Some(channel_id), Some(resource_id)) = (connection.channel_id, &connection.resource_id)
+    {
+        match access_for(&oauth, keys, &connection).await {
+            Ok(access) => stop_channel(&access, channel_id, resource_id).await,
+            Err(e) => tracing::warn!(%channel_id, "Could not stop Google Calendar channel: {}", e),
+        }
+    }
+    let deleted = sqlx::query("DELETE FROM google_calendar_connections WHERE user_id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(deleted.rows_affected() > 0)
+}
+
+/// Authenticate a push notification by its channel token; returns the connection's user
+pub async fn notification_user(pool: &PgPool, channel_id: Uuid, token: &str) -> ApiResult<Option<Uuid>> {
+    let user_id: Option<Uuid> = sqlx::query_scalar(
+        "SELECT user_id FROM google_calendar_connections WHERE channel_id = $1 AND channel_token_hash = $2",
+    )
+    .bind(channel_id)
+    .bind(sha256_hash(token.as_bytes()))
+    .fetch_optional(pool)
+    .await?;
+    Ok(user_id)
+}
+
+/// Catch up on a user's calendar, renewing the push channel when it is close to expiring
+pub async fn sync_user(pool: &PgPool, config: &AppConfig, keys: &dyn KeyManager, user_id: Uuid) -> ApiResult<usize> {
+    let Some(oauth) = GoogleOAuth::from_config(config) else {
+        return Ok(0);
+    };
+    let Some(connection) = active_connection(pool, user_id).await? else {
+        return Ok(0);
+    };
+    let result = async {
+        let access = access_for(&oauth, keys, &connection).await?;
+        let applied = sync_connection(pool, &access, &connection).await?;
+        let expires_soon: bool = sqlx::query_scalar(
+            "SELECT channel_expires_at IS NULL OR channel_expires_at < NOW() + make_interval(hours => $2::int) \
+             FROM google_calendar_connections WHERE id = $1",
+        )
+        .bind(connection.id)
+        .bind(CHANNEL_RENEW_HOURS as i32)
+        .fetch_one(pool)
+        .await?;
+        if expires_soon {
+            open_channel(pool, config, &access, &connection).await?;
+        }
+        Ok(applied)
+    }
+    .await;
+    if let Err(e) = &result {
+        record_error(pool, connection.id, e).await;
+    }
+    result
+}
+
+/// Hourly: renew expiring channels and apply anything a lost notification left behind
+pub fn spawn_calendar_sync_job(pool: Arc<PgPool>, config: AppConfig, keys: Arc<dyn KeyManager>) {
+    if GoogleOAuth::from_config(&config).is_none() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(JOB_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            let users: Vec<Uuid> = match sqlx::query_scalar(
+                "SELECT user_id FROM google_calendar_connections \
+                 WHERE status <> 'pending' AND refresh_token IS NOT NULL",
+            )
+            .fetch_all(pool.as_ref())
+            .await
+            {
+                Ok(users) => users,
+                Err(e) => {
+                    tracing::error!("Google Calendar sync job failed: {}", e);
+                    continue;
+                }
+            };
+            for user_id in users {
+                if let Err(e) = sync_user(&pool, &config, keys.as_ref(), user_id).await {
+                    tracing::warn!(%user_id, "Google Calendar sync failed: {}", e);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    #[test]
+    fn test_window_change() {
+        let window_id = Uuid::new_v4();
+        let tagged = |extra: Value| {
+            let mut event = json!({
+                "id": "evt1",
+                "status": "confirmed",
+                "summary": " Battery swap ",
+                "start": { "dateTime": "2026-10-20T10:00:00+02:00" },
+                "end": { "dateTime": "2026-10-20T12:30:00+02:00" },
+                "extendedProperties": { "private": { WINDOW_PROPERTY: window_id.to_string() } },
+            });
+            for (key, value) in extra.as_object().unwrap() {
+                event[key] = value.clone();
+            }
+            event
+        };
+
+        assert_eq!(
+            window_change(&tagged(json!({}))),
+            Some(WindowChange::Updated {
+                window_id,
+                title: Some("Battery swap".to_string()),
+                starts_at: Utc.with_ymd_and_hms(2026, 10, 20, 8, 0, 0).unwrap(),
+                ends_at: Utc.with_ymd_and_hms(2026, 10, 20, 10, 30, 0).unwrap(),
+            })
+        );
+        assert_eq!(
+            window_change(&tagged(json!({ "status": "cancelled" }))),
+            Some(WindowChange::Cancelled { window_id: Some(window_id), event_id: Some("evt1".to_string()) })
+        );
+        assert_eq!(
+            window_change(&json!({ "id": "evt2", "status": "cancelled" })),
+            Some(WindowChange::Cancelled { window_id: None, event_id: Some("evt2".to_string()) })
+        );
+
+        let all_day = tagged(json!({ "start": { "date": "2026-10-21" }, "end": { "date": "2026-10-22" } }));
+        match window_change(&all_day) {
+            Some(WindowChange::Updated { starts_at, ends_at, .. }) => {
+                assert_eq!(ends_at - starts_at, Duration::days(1))
+            }
+            other => panic!("expected an update, got {:?}", other),
+        }
+
+        assert_eq!(window_change(&json!({ "status": "confirmed", "summary": "Lunch" })), None);
+    }
+
+    #[test]
+    fn test_api_url_escapes_calendar_id() {
+        let url = api_url("ops@example.com", &["events", "watch"]);
+        assert_eq!(url.as_str(), "https://www.googleapis.com/calendar/v3/calendars/ops@example.com/events/watch");
+        let url = api_url("team#ops", &["events"]);
+        assert_eq!(url.as_str(), "https://www.googleapis.com/calendar/v3/calendars/team%23ops/events");
+    }
+}
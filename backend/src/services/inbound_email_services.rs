@@ -0,0 +1,346 @@
+//! Inbound email from Mailgun routes and SES receipt rules (via SNS), turned into incident
+//! acknowledgments and messages.
+//!
+//! A reply is only applied when it was sent to an incident's signed reply-to address and comes
+//! from the incident owner's email address; everything else is recorded and ignored.
+
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::Sha256;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::services::incident_services::{
+    acknowledge, add_message, email_address, parse_reply, parse_reply_address, verify_reply_signature,
+};
+use crate::utils::{base64_decode, mime, secure_compare};
+
+/// How far a Mailgun webhook timestamp may be from now, in seconds
+pub const MAILGUN_MAX_SKEW_SECS: i64 = 15 * 60;
+
+/// An email as the providers hand it to us, reduced to what replies need
+#[derive(Debug, Clone, PartialEq)]
+pub struct InboundEmail {
+    pub provider: &'static str,
+    pub message_id: String,
+    pub sender: String,
+    pub recipients: Vec<String>,
+    pub subject: Option<String>,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InboundOutcome {
+    pub outcome: String, // acknowledged, message, ignored, rejected, duplicate
+    pub incident_id: Option<Uuid>,
+    pub detail: Option<String>,
+}
+
+/// Mailgun signs `timestamp || token` with the account's webhook signing key
+pub fn verify_mailgun_signature(key: &[u8], timestamp: &str, token: &str, signature: &str, now: i64) -> bool {
+    let Ok(sent_at) = timestamp.parse::<i64>() else {
+        return false;
+    };
+    if (now - sent_at).abs() > MAILGUN_MAX_SKEW_SECS {
+        return false;
+    }
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(timestamp.as_bytes());
+    mac.update(token.as_bytes());
+    secure_compare(&hex::encode(mac.finalize().into_bytes()), &signature.to_ascii_lowercase())
+}
+
+/// The fields of a Mailgun route's `forward()` post. The header `From` is preferred over the
+/// envelope sender, since that is what the reply-to address was matched against.
+pub fn mailgun_email(form: &HashMap<String, String>) -> ApiResult<InboundEmail> {
+    let field = |name: &str| form.get(name).map(|v| v.trim()).filter(|v| !v.is_empty());
+    let sender = field("from").or_else(|| field("sender"));
+    let recipient = field("recipient");
+    let (Some(sender), Some(recipient)) = (sender, recipient) else {
+        return Err(ApiError::BadRequest("sender and recipient are required".to_string()));
+    };
+    let message_id = field("Message-Id")
+        .or_else(|| field("token"))
+        .ok_or_else(|| ApiError::BadRequest("Message-Id is required".to_string()))?;
+    Ok(InboundEmail {
+        provider: "mailgun",
+        message_id: message_id.to_string(),
+        sender: sender.to_string(),
+        recipients: recipient.split(',').map(|r| r.trim().to_string()).filter(|r| !r.is_empty()).collect(),
+        subject: field("subject").map(str::to_string),
+        text: field("body-plain").or_else(|| field("stripped-text")).unwrap_or_default().to_string(),
+    })
+}
+
+/// What an SNS delivery to the SES endpoint carries
+#[derive(Debug, PartialEq)]
+pub enum SnsMessage {
+    SubscriptionConfirmation { subscribe_url: String },
+    Notification(Box<InboundEmail>),
+    /// A notification we store nothing for, with the reason
+    Skipped(String),
+}
+
+/// Subscription confirmations are only followed to SNS itself
+pub fn is_sns_url(url: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(url) else {
+        return false;
+    };
+    let host = url.host_str().unwrap_or_default();
+    let labels: Vec<&str> = host.split('.').collect();
+    url.scheme() == "https"
+        && labels.len() == 4
+        && labels[0] == "sns"
+        && host.ends_with(".amazonaws.com")
+        && !labels[1].is_empty()
+}
+
+fn verdict_passed(receipt: &Value, name: &str) -> bool {
+    receipt[name]["status"].as_str() == Some("PASS")
+}
+
+/// An SES `Received` notification with the raw message included (the SNS action of a receipt
+/// rule). Mail failing both SPF and DKIM, or carrying a virus, is skipped.
+pub fn ses_email(notification: &Value) -> Result<InboundEmail, String> {
+    if notification["notificationType"].as_str() != Some("Received") {
+        return Err(format!(
+            "notification type {} is not handled",
+            notification["notificationType"].as_str().unwrap_or("unknown")
+        ));
+    }
+    let (mail, receipt) = (&notification["mail"], &notification["receipt"]);
+    if !verdict_passed(receipt, "spfVerdict") && !verdict_passed(receipt, "dkimVerdict") {
+        return Err("sender failed both SPF and DKIM".to_string());
+    }
+    if receipt["virusVerdict"]["status"].as_str() == Some("FAIL") {
+        return Err("message carries a virus".to_string());
+    }
+
+    let content = notification["content"].as_str().ok_or("notification has no message content")?;
+    let raw = if receipt["action"]["encoding"].as_str() == Some("BASE64") {
+        let compact: String = content.chars().filter(|c| !c.is_whitespace()).collect();
+        let bytes = base64_decode(&compact).map_err(|_| "message content is not valid base64")?;
+        String::from_utf8_lossy(&bytes).into_owned()
+    } else {
+        content.to_string()
+    };
+
+    let headers = &mail["commonHeaders"];
+    let sender = headers["from"][0].as_str().or_else(|| mail["source"].as_str()).ok_or("message has no sender")?;
+    Ok(InboundEmail {
+        provider: "ses",
+        message_id: mail["messageId"].as_str().ok_or("message has no id")?.to_string(),
+        sender: sender.to_string(),
+        recipients: receipt["recipients"]
+            .as_array()
+            .map(|r| r.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            .unwrap_or_default(),
+        subject: headers["subject"].as_str().map(str::to_string),
+        text: mime::plain_text(&raw).unwrap_or_default(),
+    })
+}
+
+/// Parse an SNS HTTP delivery
+pub fn parse_sns(body: &[u8]) -> ApiResult<SnsMessage> {
+    let envelope: Value = serde_json::from_slice(body)
+        .map_err(|_| ApiError::BadRequest("SNS message must be JSON".to_string()))?;
+    match envelope["Type"].as_str() {
+        Some("SubscriptionConfirmation") => {
+            let url = envelope["SubscribeURL"].as_str().unwrap_or_default();
+            if !is_sns_url(url) {
+                return Err(ApiError::BadRequest("SubscribeURL is not an SNS endpoint".to_string()));
+            }
+            Ok(SnsMessage::SubscriptionConfirmation { subscribe_url: url.to_string() })
+        }
+        Some("Notification") => {
+            let notification: Value = envelope["Message"]
+                .as_str()
+                .and_then(|m| serde_json::from_str(m).ok())
+                .ok_or_else(|| ApiError::BadRequest("SNS notification must carry a JSON message".to_string()))?;
+            Ok(match ses_email(&notification) {
+                Ok(email) => SnsMessage::Notification(Box::new(email)),
+                Err(reason) => SnsMessage::Skipped(reason),
+            })
+        }
+        Some(other) => Ok(SnsMessage::Skipped(format!("SNS message type {} is not handled", other))),
+        None => Err(ApiError::BadRequest("SNS message has no Type".to_string())),
+    }
+}
+
+/// Record an inbound email and apply it to the incident its reply-to address names. Redelivered
+/// messages are answered with the original outcome instead of being applied twice.
+pub async fn process_inbound(
+    pool: &PgPool,
+    secret: &[u8],
+    domain: &str,
+    email: &InboundEmail,
+) -> ApiResult<InboundOutcome> {
+    let mut tx = pool.begin().await?;
+    let recipient = email.recipients.first().map(String::as_str).unwrap_or_default();
+    let inbound_id: Option<Uuid> = sqlx::query_scalar(
+        "INSERT INTO inbound_emails (provider, message_id, sender, recipient, subject) \
+         VALUES ($1, $2, $3, $4, $5) ON CONFLICT (provider, message_id) DO NOTHING RETURNING id",
+    )
+    .bind(email.provider)
+    .bind(&email.message_id)
+    .bind(truncate(&email.sender, 255))
+    .bind(truncate(recipient, 255))
+    .bind(email.subject.as_deref().map(|s| truncate(s, 255)))
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(inbound_id) = inbound_id else {
+        let (outcome, incident_id): (String, Option<Uuid>) = sqlx::query_as(
+            "SELECT outcome, incident_id FROM inbound_emails WHERE provider = $1 AND message_id = $2",
+        )
+        .bind(email.provider)
+        .bind(&email.message_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        return Ok(InboundOutcome {
+            outcome: "duplicate".to_string(),
+            incident_id,
+            detail: Some(format!("already processed as {}", outcome)),
+        });
+    };
+
+    let outcome = apply_reply(&mut tx, secret, domain, email).await?;
+    sqlx::query("UPDATE inbound_emails SET incident_id = $2, outcome = $3, detail = $4 WHERE id = $1")
+        .bind(inbound_id)
+        .bind(outcome.incident_id)
+        .bind(&outcome.outcome)
+        .bind(&outcome.detail)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    tracing::info!(
+        provider = email.provider,
+        outcome = %outcome.outcome,
+        incident_id = ?outcome.incident_id,
+        "Inbound email processed"
+    );
+    Ok(outcome)
+}
+
+fn truncate(value: &str, max_chars: usize) -> String {
+    value.chars().take(max_chars).collect()
+}
+
+async fn apply_reply(
+    conn: &mut sqlx::PgConnection,
+    secret: &[u8],
+    domain: &str,
+    email: &InboundEmail,
+) -> ApiResult<InboundOutcome> {
+    let outcome = |outcome: &str, incident_id: Option<Uuid>, detail: &str| InboundOutcome {
+        outcome: outcome.to_string(),
+        incident_id,
+        detail: (!detail.is_empty()).then(|| detail.to_string()),
+    };
+
+    let Some((incident_id, signature)) = email.recipients.iter().find_map(|r| parse_reply_address(r, domain)) else {
+        return Ok(outcome("ignored", None, "not addressed to an incident reply address"));
+    };
+    let incident: Option<(Uuid, String, String)> = sqlx::query_as(
+        "SELECT i.user_id, i.status, u.email FROM incidents i JOIN users u ON u.id = i.user_id WHERE i.id = $1",
+    )
+    .bind(incident_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some((owner_id, status, owner_email)) = incident else {
+        return Ok(outcome("rejected", None, "incident not found"));
+    };
+    if !verify_reply_signature(secret, incident_id, owner_id, &signature) {
+        return Ok(outcome("rejected", Some(incident_id), "reply address signature does not match"));
+    }
+    if email_address(&email.sender) != owner_email.to_lowercase() {
+        return Ok(outcome("rejected", Some(incident_id), "sender is not the incident owner"));
+    }
+
+    let reply = parse_reply(&email.text);
+    let mut applied = Vec::new();
+    let mut acknowledged = false;
+    if reply.acknowledge {
+        acknowledged = acknowledge(&mut *conn, incident_id, owner_id, "email").await?;
+        applied.push(match (acknowledged, status.as_str()) {
+            (true, _) => "acknowledged",
+            (false, "resolved") => "already resolved",
+            (false, _) => "already acknowledged",
+        });
+    }
+    if let Some(message) = &reply.message {
+        let from = email_address(&email.sender);
+        add_message(&mut *conn, incident_id, Some(owner_id), "email", Some(&from), message).await?;
+        applied.push("message added");
+    }
+
+    Ok(match (acknowledged, reply.message.is_some(), applied.is_empty()) {
+        (_, _, true) => outcome("ignored", Some(incident_id), "reply has no new text"),
+        (true, _, _) => outcome("acknowledged", Some(incident_id), &applied.join(", ")),
+        (false, true, _) => outcome("message", Some(incident_id), &applied.join(", ")),
+        (false, false, _) => outcome("ignored", Some(incident_id), &applied.join(", ")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_mailgun_signature() {
+        let now = 1_760_000_000;
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(b"key-123").unwrap();
+        mac.update(b"1760000000token-abc");
+        let signature = hex::encode(mac.finalize().into_bytes());
+
+        assert!(verify_mailgun_signature(b"key-123", "1760000000", "token-abc", &signature, now));
+        assert!(!verify_mailgun_signature(b"key-124", "1760000000", "token-abc", &signature, now));
+        assert!(!verify_mailgun_signature(b"key-123", "1760000000", "token-abd", &signature, now));
+        assert!(!verify_mailgun_signature(b"key-123", "1760000000", "token-abc", &signature, now + 3600));
+    }
+
+    #[test]
+    fn test_sns_url() {
+        assert!(is_sns_url("https://sns.us-east-1.amazonaws.com/?Action=ConfirmSubscription&Token=x"));
+        assert!(!is_sns_url("http://sns.us-east-1.amazonaws.com/"));
+        assert!(!is_sns_url("https://sns.us-east-1.amazonaws.com.evil.com/"));
+        assert!(!is_sns_url("https://evil.com/sns.us-east-1.amazonaws.com"));
+    }
+
+    #[test]
+    fn test_ses_notification() {
+        let raw = "From: Jane <jane@example.com>\r\nContent-Type: text/plain\r\n\r\nack\r\n";
+        let notification = json!({
+            "notificationType": "Received",
+            "mail": {
+                "messageId": "abc123",
+                "source": "bounce@example.com",
+                "commonHeaders": { "from": ["Jane <jane@example.com>"], "subject": "Re: alert" },
+            },
+            "receipt": {
+                "recipients": ["reply+x.y@reply.example.com"],
+                "spfVerdict": { "status": "FAIL" },
+                "dkimVerdict": { "status": "PASS" },
+                "virusVerdict": { "status": "PASS" },
+                "action": { "type": "SNS", "encoding": "BASE64" },
+            },
+            "content": crate::utils::base64_encode(raw.as_bytes()),
+        });
+        let envelope = json!({ "Type": "Notification", "Message": notification.to_string() });
+
+        let SnsMessage::Notification(email) = parse_sns(envelope.to_string().as_bytes()).unwrap() else {
+            panic!("expected a notification");
+        };
+        assert_eq!(email.message_id, "abc123");
+        assert_eq!(email.sender, "Jane <jane@example.com>");
+        assert_eq!(email.recipients, vec!["reply+x.y@reply.example.com"]);
+        assert_eq!(email.text, "ack");
+
+        let mut spoofed = notification.clone();
+        spoofed["receipt"]["dkimVerdict"]["status"] = json!("FAIL");
+        assert!(ses_email(&spoofed).is_err());
+    }
+}
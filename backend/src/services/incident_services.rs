@@ -0,0 +1,272 @@
+//! Incidents opened by critical telemetry alerts, and the alert emails sent for them.
+//!
+//! Alert emails carry a reply-to address naming the incident and signed with the inbound email
+//! secret, so a reply can only land on the incident it was sent for. Replies come back through
+//! the inbound email webhook as acknowledgments or messages on the incident.
+
+use hmac::{Hmac, Mac};
+use secrecy::ExposeSecret;
+use sha2::Sha256;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::incident::Incident;
+use crate::services::mail_services::queue_email;
+use crate::utils::secure_compare;
+
+pub const INCIDENT_COLUMNS: &str = "id, user_id, device_id, processor_id, severity, title, status, alert_count, \
+     last_alert_at, acknowledged_at, acknowledged_by, acknowledged_via, resolved_at, created_at";
+
+pub const MESSAGE_COLUMNS: &str = "id, incident_id, user_id, source, from_address, body, created_at";
+
+pub const INCIDENT_STATUSES: &[&str] = &["open", "acknowledged", "resolved"];
+
+pub const MAX_MESSAGE_CHARS: usize = 10_000;
+
+const REPLY_PREFIX: &str = "reply+";
+/// Hex characters of the HMAC kept in the address; the local part must stay within 64 characters
+const REPLY_SIGNATURE_LEN: usize = 24;
+
+/// Alerts of this severity open an incident; lesser ones only notify
+pub fn opens_incident(severity: &str) -> bool {
+    severity == "critical"
+}
+
+fn reply_signature(secret: &[u8], incident_id: Uuid, user_id: Uuid) -> String {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(format!("incident-reply:{}:{}", incident_id, user_id).as_bytes());
+    let mut signature = hex::encode(mac.finalize().into_bytes());
+    signature.truncate(REPLY_SIGNATURE_LEN);
+    signature
+}
+
+/// `reply+<incident id>.<signature>@<domain>`, valid only for replies about this incident from its owner
+pub fn reply_address(secret: &[u8], domain: &str, incident_id: Uuid, user_id: Uuid) -> String {
+    format!(
+        "{}{}.{}@{}",
+        REPLY_PREFIX,
+        incident_id.simple(),
+        reply_signature(secret, incident_id, user_id),
+        domain
+    )
+}
+
+/// The incident and signature named by a reply address on our domain
+pub fn parse_reply_address(address: &str, domain: &str) -> Option<(Uuid, String)> {
+    let (local, host) = email_address(address).rsplit_once('@').map(|(l, h)| (l.to_string(), h.to_string()))?;
+    if !host.eq_ignore_ascii_case(domain) {
+        return None;
+    }
+    let (id, signature) = local.strip_prefix(REPLY_PREFIX)?.split_once('.')?;
+    Some((Uuid::parse_str(id).ok()?, signature.to_string()))
+}
+
+pub fn verify_reply_signature(secret: &[u8], incident_id: Uuid, user_id: Uuid, signature: &str) -> bool {
+    secure_compare(&reply_signature(secret, incident_id, user_id), &signature.to_ascii_lowercase())
+}
+
+/// The bare, lower-cased address from `Name <user@example.com>` or `user@example.com`
+pub fn email_address(value: &str) -> String {
+    let value = value.trim();
+    let bare = match (value.rfind('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &value[start + 1..end],
+        _ => value,
+    };
+    bare.trim().to_lowercase()
+}
+
+/// What a reply asks for
+#[derive(Debug, PartialEq)]
+pub struct ReplyIntent {
+    pub acknowledge: bool,
+    pub message: Option<String>,
+}
+
+/// The new text of a reply: everything above the quoted original or the signature
+pub fn strip_quoted(text: &str) -> String {
+    let mut kept = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim();
+        let quote_header = trimmed.starts_with("On ") && trimmed.ends_with("wrote:");
+        if trimmed.starts_with('>')
+            || quote_header
+            || trimmed == "--"
+            || trimmed.starts_with("-----Original Message-----")
+            || trimmed.starts_with("________________________________")
+        {
+            break;
+        }
+        kept.push(line.trim_end());
+    }
+    kept.join("\n").trim().to_string()
+}
+
+/// A first line of just `ack` (or `acknowledge`, `acknowledged`) acknowledges the incident;
+/// any other text becomes a message on it
+pub fn parse_reply(text: &str) -> ReplyIntent {
+    let text = strip_quoted(text);
+    let (first, rest) = text.split_once('\n').unwrap_or((&text, ""));
+    let command = first.trim().trim_end_matches(['.', '!']).to_lowercase();
+    let acknowledge = matches!(command.as_str(), "ack" | "acknowledge" | "acknowledged");
+    let message = if acknowledge { rest.trim() } else { text.as_str() };
+    ReplyIntent {
+        acknowledge,
+        message: (!message.is_empty()).then(|| message.chars().take(MAX_MESSAGE_CHARS).collect()),
+    }
+}
+
+/// Fold an alert into the unresolved incident for its device and processor, or open one.
+/// Returns the id of a newly opened incident.
+pub async fn record_alert(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    device_id: Uuid,
+    processor_id: Uuid,
+    severity: &str,
+    title: &str,
+) -> ApiResult<Option<Uuid>> {
+    let (id, opened): (Uuid, bool) = sqlx::query_as(
+        "INSERT INTO incidents (user_id, device_id, processor_id, severity, title) VALUES ($1, $2, $3, $4, $5) \
+         ON CONFLICT (device_id, processor_id) WHERE status <> 'resolved' DO UPDATE SET \
+             alert_count = incidents.alert_count + 1, last_alert_at = NOW() \
+         RETURNING id, (xmax = 0) AS opened",
+    )
+    .bind(user_id)
+    .bind(device_id)
+    .bind(processor_id)
+    .bind(severity)
+    .bind(title)
+    .fetch_one(conn)
+    .await?;
+    Ok(opened.then_some(id))
+}
+
+fn alert_email(incident: &Incident, device_name: &str, message: &str, replies: bool) -> (String, String) {
+    let subject = format!("[{}] {} on {}", incident.severity.to_uppercase(), incident.title, device_name);
+    let mut body = format!(
+        "{}\n\nDevice: {}\nIncident: {}\nOpened: {}\n",
+        message,
+        device_name,
+        incident.id,
+        incident.created_at.format("%Y-%m-%d %H:%M UTC")
+    );
+    if replies {
+        body.push_str(
+            "\nReply with \"ack\" on the first line to acknowledge this incident. \
+             Anything else you write is added to the incident as a message.\n",
+        );
+    }
+    (subject, body)
+}
+
+/// Email the owner about a newly opened incident, with a signed reply-to address when inbound
+/// email is configured
+pub async fn send_alert_email(pool: &PgPool, config: &AppConfig, incident_id: Uuid, message: &str) -> ApiResult<()> {
+    let incident = sqlx::query_as::<_, Incident>(&format!("SELECT {} FROM incidents WHERE id = $1", INCIDENT_COLUMNS))
+        .bind(incident_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Incident not found".to_string()))?;
+    let (email, device_name): (String, String) = sqlx::query_as(
+        "SELECT u.email, d.device_name FROM users u JOIN devices d ON d.id = $2 WHERE u.id = $1",
+    )
+    .bind(incident.user_id)
+    .bind(incident.device_id)
+    .fetch_one(pool)
+    .await?;
+
+    let reply_to = match (&config.inbound_email_domain, &config.inbound_email_secret) {
+        (Some(domain), Some(secret)) => Some(reply_address(
+            secret.expose_secret().as_bytes(),
+            domain,
+            incident.id,
+            incident.user_id,
+        )),
+        _ => None,
+    };
+    let (subject, body) = alert_email(&incident, &device_name, message, reply_to.is_some());
+
+    let mut conn = pool.acquire().await?;
+    queue_email(&mut conn, &email, &subject, &body, reply_to.as_deref()).await
+}
+
+/// Mark an open incident acknowledged; returns false if it already was, or is resolved
+pub async fn acknowledge(conn: &mut PgConnection, incident_id: Uuid, user_id: Uuid, via: &str) -> ApiResult<bool> {
+    let updated = sqlx::query(
+        "UPDATE incidents SET status = 'acknowledged', acknowledged_at = NOW(), acknowledged_by = $2, \
+         acknowledged_via = $3 WHERE id = $1 AND status = 'open'",
+    )
+    .bind(incident_id)
+    .bind(user_id)
+    .bind(via)
+    .execute(conn)
+    .await?;
+    Ok(updated.rows_affected() > 0)
+}
+
+pub async fn add_message(
+    conn: &mut PgConnection,
+    incident_id: Uuid,
+    user_id: Option<Uuid>,
+    source: &str,
+    from_address: Option<&str>,
+    body: &str,
+) -> ApiResult<Uuid> {
+    let id: Uuid = sqlx::query_scalar(
+        "INSERT INTO incident_messages (incident_id, user_id, source, from_address, body) \
+         VALUES ($1, $2, $3, $4, $5) RETURNING id",
+    )
+    .bind(incident_id)
+    .bind(user_id)
+    .bind(source)
+    .bind(from_address)
+    .bind(body)
+    .fetch_one(conn)
+    .await?;
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reply_address_round_trip() {
+        let (incident, owner) = (Uuid::new_v4(), Uuid::new_v4());
+        let address = reply_address(b"secret", "reply.example.com", incident, owner);
+        assert!(address.split('@').next().unwrap().len() <= 64, "{}", address);
+
+        let (parsed, signature) = parse_reply_address(&format!("Ops <{}>", address.to_uppercase()), "reply.example.com")
+            .unwrap();
+        assert_eq!(parsed, incident);
+        assert!(verify_reply_signature(b"secret", incident, owner, &signature));
+        assert!(!verify_reply_signature(b"secret", incident, Uuid::new_v4(), &signature));
+        assert!(!verify_reply_signature(b"other", incident, owner, &signature));
+
+        assert!(parse_reply_address(&address, "elsewhere.com").is_none());
+        assert!(parse_reply_address("support@reply.example.com", "reply.example.com").is_none());
+    }
+
+    #[test]
+    fn test_parse_reply() {
+        let reply = "Ack.\r\nOn it, rebooting now.\r\n\r\n\
+                     On Fri, 16 Oct 2026 at 10:00, RoboVeda wrote:\r\n> Battery fire";
+        assert_eq!(
+            parse_reply(reply),
+            ReplyIntent { acknowledge: true, message: Some("On it, rebooting now.".to_string()) }
+        );
+        assert_eq!(parse_reply("ACK\n-- \nSent from my phone"), ReplyIntent { acknowledge: true, message: None });
+        assert_eq!(
+            parse_reply("Can someone check the lidar?\n> quoted"),
+            ReplyIntent { acknowledge: false, message: Some("Can someone check the lidar?".to_string()) }
+        );
+        assert_eq!(parse_reply("> only quoted text"), ReplyIntent { acknowledge: false, message: None });
+    }
+
+    #[test]
+    fn test_email_address() {
+        assert_eq!(email_address("Jane Doe <Jane@Example.com>"), "jane@example.com");
+        assert_eq!(email_address(" ops@example.com "), "ops@example.com");
+    }
+}
@@ -0,0 +1,236 @@
+//! AI health summaries of device telemetry. The last day of a device's telemetry, aggregated by
+//! hour, is described to the model, which answers with a health rating, a summary and
+//! recommendations. A summary is generated at most once per device per clock hour; later
+//! requests in the hour are served from the cache without another model call.
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::device::Device;
+use crate::models::insight::{DeviceInsight, TelemetryHour};
+use crate::services::ai_routing_services::routed_chat;
+use crate::services::ai_services::{AIService, ChatMessage, ChatRequest};
+use crate::services::{device_services, moderation_services};
+
+const INSIGHT_COLUMNS: &str =
+    "device_id, hour_start, health, summary, recommendations, model, provider, created_at";
+/// Hours of telemetry summarized; raw samples are kept at least this long
+const WINDOW_HOURS: i64 = 24;
+pub const HEALTH_RATINGS: &[&str] = &["good", "fair", "poor", "critical"];
+const UNKNOWN_HEALTH: &str = "unknown";
+const MAX_RECOMMENDATIONS: usize = 10;
+
+const SYSTEM_PROMPT: &str = "You monitor the health of robots and drones from their telemetry. Given a device and its \
+     hourly telemetry, assess its condition: battery level and drain, CPU temperature, signal strength, gaps in \
+     reporting and anything unusual. Respond with a JSON object with these keys: \"health\", one of good, fair, \
+     poor or critical; \"summary\", two to four sentences for the operator; \"recommendations\", an array of short \
+     actionable strings, empty when none are needed.";
+
+/// The model's reply
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct InsightReply {
+    pub health: String,
+    pub summary: String,
+    pub recommendations: Vec<String>,
+}
+
+/// Read the model's reply: the JSON asked for, or failing that the prose as the summary
+pub fn parse_reply(reply: &str) -> InsightReply {
+    let parsed = reply
+        .find('{')
+        .zip(reply.rfind('}'))
+        .filter(|(start, end)| start < end)
+        .and_then(|(start, end)| serde_json::from_str::<InsightReply>(&reply[start..=end]).ok())
+        .filter(|parsed| !parsed.summary.trim().is_empty());
+    let mut parsed = parsed.unwrap_or_else(|| InsightReply { summary: reply.to_string(), ..Default::default() });
+    parsed.health = parsed.health.trim().to_lowercase();
+    if !HEALTH_RATINGS.contains(&parsed.health.as_str()) {
+        parsed.health = UNKNOWN_HEALTH.to_string();
+    }
+    parsed.summary = parsed.summary.trim().to_string();
+    parsed.recommendations.retain(|r| !r.trim().is_empty());
+    parsed.recommendations.truncate(MAX_RECOMMENDATIONS);
+    parsed
+}
+
+fn optional(label: &str, value: Option<f64>) -> String {
+    value.map(|v| format!(", {} {:.1}", label, v)).unwrap_or_default()
+}
+
+/// The device and its telemetry as the user message
+pub fn describe(device: &Device, hours: &[TelemetryHour], now: DateTime<Utc>) -> String {
+    let last_seen = device.last_seen.map_or("never".to_string(), |t| t.to_rfc3339());
+    let mut text = format!(
+        "Device: {} ({}), firmware {}, status {}, last seen {}. Now: {}.\nHourly telemetry, oldest first:\n",
+        device.device_name,
+        device.device_type,
+        device.firmware_version,
+        device.status,
+        last_seen,
+        now.to_rfc3339()
+    );
+    for hour in hours {
+        text.push_str(&format!(
+            "{}: {} samples, battery avg {:.1}% (min {}, max {}){}{}{}\n",
+            hour.hour_start.format("%Y-%m-%d %H:00Z"),
+            hour.samples,
+            hour.battery_avg,
+            hour.battery_min,
+            hour.battery_max,
+            optional("CPU temp °C", hour.cpu_temp_avg),
+            optional("signal dBm", hour.signal_strength_avg),
+            optional("altitude m", hour.altitude_avg),
+        ));
+    }
+    text
+}
+
+async fn cached(pool: &PgPool, device_id: Uuid, hour_start: DateTime<Utc>) -> ApiResult<Option<DeviceInsight>> {
+    let insight = sqlx::query_as::<_, DeviceInsight>(&format!(
+        "SELECT {}, TRUE AS cached FROM device_insights WHERE device_id = $1 AND hour_start = $2",
+        INSIGHT_COLUMNS
+    ))
+    .bind(device_id)
+    .bind(hour_start)
+    .fetch_optional(pool)
+    .await?;
+    Ok(insight)
+}
+
+/// The device's health summary for the current hour, generated unless already cached
+pub async fn insight(
+    pool: &PgPool,
+    config: &AppConfig,
+    ai: &AIService,
+    user_id: Uuid,
+    device_id: Uuid,
+) -> ApiResult<DeviceInsight> {
+    let device = device_services::get_owned_device(pool, device_id, user_id).await?;
+    let now = Utc::now();
+    let hour_start = now.duration_trunc(Duration::hours(1)).unwrap_or(now);
+    if let Some(insight) = cached(pool, device.id, hour_start).await? {
+        return Ok(insight);
+    }
+
+    // Aggregated from raw samples so the current, not yet rolled up, hour is included
+    let hours = sqlx::query_as::<_, TelemetryHour>(
+        "SELECT date_trunc('hour', recorded_at) AS hour_start, COUNT(*) AS samples, \
+             AVG(battery_level)::DOUBLE PRECISION AS battery_avg, MIN(battery_level) AS battery_min, \
+             MAX(battery_level) AS battery_max, AVG(altitude) AS altitude_avg, \
+             AVG(CASE WHEN jsonb_typeof(payload -> 'cpu_temp') = 'number' \
+                 THEN (payload ->> 'cpu_temp')::float8 END) AS cpu_temp_avg, \
+             AVG(CASE WHEN jsonb_typeof(payload -> 'signal_strength') = 'number' \
+                 THEN (payload ->> 'signal_strength')::float8 END) AS signal_strength_avg \
+         FROM device_telemetry WHERE device_id = $1 AND recorded_at >= $2 \
+         GROUP BY 1 ORDER BY 1",
+    )
+    .bind(device.id)
+    .bind(now - Duration::hours(WINDOW_HOURS))
+    .fetch_all(pool)
+    .await?;
+    if hours.is_empty() {
+        return Err(ApiError::NotFound(format!(
+            "The device has reported no telemetry in the last {} hours",
+            WINDOW_HOURS
+        )));
+    }
+
+    let request = ChatRequest {
+        messages: vec![
+            ChatMessage { role: "system".to_string(), content: SYSTEM_PROMPT.to_string() },
+            ChatMessage { role: "user".to_string(), content: describe(&device, &hours, now) },
+        ],
+        model: None,
+        provider: None,
+        temperature: Some(0.2),
+        max_tokens: Some(600),
+        conversation_id: None,
+        images: Vec::new(),
+        json: true,
+    };
+    let mut response = routed_chat(pool, config, ai, user_id, request).await?;
+    moderation_services::screen_output(config, ai, &mut response.message).await?;
+    let reply = parse_reply(&response.message);
+
+    // A concurrent request may have cached the hour first; its summary is kept
+    sqlx::query(
+        "INSERT INTO device_insights (device_id, hour_start, health, summary, recommendations, model, provider) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT (device_id, hour_start) DO NOTHING",
+    )
+    .bind(device.id)
+    .bind(hour_start)
+    .bind(&reply.health)
+    .bind(&reply.summary)
+    .bind(sqlx::types::Json(&reply.recommendations))
+    .bind(&response.model)
+    .bind(&response.provider)
+    .execute(pool)
+    .await?;
+    Ok(DeviceInsight {
+        device_id: device.id,
+        hour_start,
+        health: reply.health,
+        summary: reply.summary,
+        recommendations: sqlx::types::Json(reply.recommendations),
+        model: response.model,
+        provider: response.provider,
+        created_at: now,
+        cached: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reply() {
+        let reply = "{\"health\": \"Fair\", \"summary\": \"Battery drains fast.\", \
+                     \"recommendations\": [\"Replace the battery\", \" \"]}";
+        let parsed = parse_reply(reply);
+        assert_eq!(parsed.health, "fair");
+        assert_eq!(parsed.summary, "Battery drains fast.");
+        assert_eq!(parsed.recommendations, ["Replace the battery"]);
+
+        let prose = parse_reply("  The drone looks healthy.  ");
+        assert_eq!(prose.health, UNKNOWN_HEALTH);
+        assert_eq!(prose.summary, "The drone looks healthy.");
+        assert!(prose.recommendations.is_empty());
+    }
+
+    #[test]
+    fn test_describe() {
+        let device = Device {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            device_name: "Survey drone".to_string(),
+            device_type: "drone".to_string(),
+            firmware_version: "2.1.0".to_string(),
+            status: "online".to_string(),
+            last_seen: None,
+            metadata: serde_json::json!({}),
+            transport: "mqtt".to_string(),
+            nft_chain_id: None,
+            nft_token_id: None,
+            created_at: Utc::now(),
+        };
+        let now = Utc::now();
+        let hours = [TelemetryHour {
+            hour_start: now.duration_trunc(Duration::hours(1)).unwrap(),
+            samples: 12,
+            battery_avg: 78.25,
+            battery_min: 76,
+            battery_max: 81,
+            altitude_avg: None,
+            cpu_temp_avg: Some(54.16),
+            signal_strength_avg: None,
+        }];
+        let text = describe(&device, &hours, now);
+        assert!(text.starts_with("Device: Survey drone (drone), firmware 2.1.0, status online, last seen never."));
+        assert!(text.contains("12 samples, battery avg 78.2% (min 76, max 81), CPU temp °C 54.2\n"));
+    }
+}
@@ -0,0 +1,196 @@
+//! No-code integrations in the style Zapier expects: polling triggers that list the newest items
+//! first, REST hook subscriptions that receive the same items as they happen, and a few actions.
+//!
+//! Items are built in SQL (`integration_*_item`), where triggers also queue them for REST hooks.
+
+use chrono::Utc;
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::services::webhook_services::retry_delay;
+
+pub const INTEGRATION_EVENTS: &[&str] = &["new_device", "new_alert", "command_completed"];
+
+pub const MAX_TOKENS_PER_USER: i64 = 10;
+pub const MAX_HOOKS_PER_USER: i64 = 50;
+/// Items a polling trigger returns; Zapier deduplicates by `id`
+pub const TRIGGER_PAGE: i64 = 50;
+/// Characters of a token kept for display
+pub const TOKEN_PREFIX_LEN: usize = 12;
+const HOOK_MAX_ATTEMPTS: i32 = 5;
+const DELIVERY_INTERVAL_SECS: u64 = 5;
+const DELIVERY_BATCH: i64 = 50;
+const CLAIM_LEASE_SECS: i64 = 60;
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+
+pub fn validate_event(event: &str) -> ApiResult<()> {
+    if !INTEGRATION_EVENTS.contains(&event) {
+        return Err(ApiError::ValidationError(format!(
+            "event must be one of {}",
+            INTEGRATION_EVENTS.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// The newest items of a trigger, newest first
+pub async fn trigger_items(pool: &PgPool, user_id: Uuid, event: &str) -> ApiResult<Vec<Value>> {
+    let query = match event {
+        "new_device" => {
+            "SELECT integration_device_item(d) FROM devices d WHERE d.user_id = $1 \
+             ORDER BY d.created_at DESC LIMIT $2"
+        }
+        "new_alert" => {
+            "SELECT integration_alert_item(n) FROM notifications n WHERE n.user_id = $1 AND n.kind = 'telemetry_alert' \
+             ORDER BY n.created_at DESC LIMIT $2"
+        }
+        "command_completed" => {
+            "SELECT integration_command_item(c) FROM device_commands c \
+             WHERE c.user_id = $1 AND c.status IN ('succeeded', 'failed') \
+             ORDER BY c.acked_at DESC NULLS LAST LIMIT $2"
+        }
+        _ => return Err(ApiError::NotFound("Unknown trigger".to_string())),
+    };
+    let items = sqlx::query_scalar(query)
+        .bind(user_id)
+        .bind(TRIGGER_PAGE)
+        .fetch_all(pool)
+        .await?;
+    Ok(items)
+}
+
+/// What to do with a delivery after the receiver answered `status`
+#[derive(Debug, PartialEq)]
+pub enum HookOutcome {
+    Delivered,
+    /// 410 Gone is how REST hook receivers say the subscription no longer exists
+    Unsubscribed,
+    Retry,
+}
+
+pub fn hook_outcome(status: Option<u16>) -> HookOutcome {
+    match status {
+        Some(200..=299) => HookOutcome::Delivered,
+        Some(410) => HookOutcome::Unsubscribed,
+        _ => HookOutcome::Retry,
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct DueHookDelivery {
+    id: i64,
+    hook_id: Uuid,
+    payload: Value,
+    attempts: i32,
+    target_url: String,
+}
+
+async fn deliver(pool: &PgPool, client: &reqwest::Client, delivery: DueHookDelivery) -> ApiResult<()> {
+    let response = client.post(&delivery.target_url).json(&delivery.payload).send().await;
+    let (status, error) = match response {
+        Ok(response) => (Some(response.status().as_u16()), format!("Receiver responded {}", response.status())),
+        Err(e) => (None, e.to_string()),
+    };
+
+    match hook_outcome(status) {
+        HookOutcome::Delivered => {
+            sqlx::query("DELETE FROM integration_hook_deliveries WHERE id = $1")
+                .bind(delivery.id)
+                .execute(pool)
+                .await?;
+        }
+        HookOutcome::Unsubscribed => {
+            tracing::info!(hook_id = %delivery.hook_id, "Integration hook receiver is gone; unsubscribing");
+            sqlx::query("DELETE FROM integration_hooks WHERE id = $1")
+                .bind(delivery.hook_id)
+                .execute(pool)
+                .await?;
+        }
+        HookOutcome::Retry if delivery.attempts + 1 >= HOOK_MAX_ATTEMPTS => {
+            tracing::warn!(hook_id = %delivery.hook_id, "Integration hook delivery failed permanently: {}", error);
+            sqlx::query("DELETE FROM integration_hook_deliveries WHERE id = $1")
+                .bind(delivery.id)
+                .execute(pool)
+                .await?;
+        }
+        HookOutcome::Retry => {
+            let attempts = delivery.attempts + 1;
+            sqlx::query(
+                "UPDATE integration_hook_deliveries SET attempts = $2, next_attempt_at = $3, last_error = $4 \
+                 WHERE id = $1",
+            )
+            .bind(delivery.id)
+            .bind(attempts)
+            .bind(Utc::now() + retry_delay(attempts))
+            .bind(&error)
+            .execute(pool)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Push every due hook delivery once. Returns how many were attempted.
+pub async fn deliver_due_hooks(pool: &PgPool, client: &reqwest::Client) -> ApiResult<usize> {
+    let due = sqlx::query_as::<_, DueHookDelivery>(
+        "UPDATE integration_hook_deliveries d SET next_attempt_at = NOW() + make_interval(secs => $2) \
+         FROM integration_hooks h \
+         WHERE h.id = d.hook_id AND d.id IN ( \
+             SELECT id FROM integration_hook_deliveries WHERE next_attempt_at <= NOW() \
+             ORDER BY next_attempt_at LIMIT $1 FOR UPDATE SKIP LOCKED) \
+         RETURNING d.id, d.hook_id, d.payload, d.attempts, h.target_url",
+    )
+    .bind(DELIVERY_BATCH)
+    .bind(CLAIM_LEASE_SECS as f64)
+    .fetch_all(pool)
+    .await?;
+
+    let count = due.len();
+    for delivery in due {
+        if let Err(e) = deliver(pool, client, delivery).await {
+            tracing::error!("Recording integration hook delivery failed: {}", e);
+        }
+    }
+    Ok(count)
+}
+
+/// Start the background job pushing queued items to REST hook subscribers
+pub fn spawn_hook_delivery_job(pool: Arc<PgPool>) {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("HTTP client must build");
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(DELIVERY_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = deliver_due_hooks(&pool, &client).await {
+                tracing::error!("Integration hook delivery job failed: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hook_outcome() {
+        assert_eq!(hook_outcome(Some(200)), HookOutcome::Delivered);
+        assert_eq!(hook_outcome(Some(204)), HookOutcome::Delivered);
+        assert_eq!(hook_outcome(Some(410)), HookOutcome::Unsubscribed);
+        assert_eq!(hook_outcome(Some(404)), HookOutcome::Retry);
+        assert_eq!(hook_outcome(Some(503)), HookOutcome::Retry);
+        assert_eq!(hook_outcome(None), HookOutcome::Retry);
+    }
+
+    #[test]
+    fn test_validate_event() {
+        assert!(validate_event("new_alert").is_ok());
+        assert!(validate_event("device.online").is_err());
+    }
+}
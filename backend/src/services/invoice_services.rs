@@ -0,0 +1,375 @@
+//! Crypto payment invoices. An invoice asks for an exact amount of the chain's token, paid to
+//! the deposit wallet before it expires, and carries a pending `crypto` transaction. The price
+//! is nudged in its sixth decimal so open invoices on a token never share an amount; the
+//! watcher scans `Transfer` logs into the deposit wallet, matches each by amount, and hands the
+//! transaction its tx hash so the confirmation watcher completes it and unlocks the product.
+
+use chrono::{Duration, Utc};
+use rand::Rng;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::transaction::{CryptoInvoice, FiatAmount, Transaction};
+use crate::services::crypto_services::{
+    decimal_amount, decode_uint_decimal, format_units, parse_units, topic_address, BlockchainService, TRANSFER_TOPIC,
+};
+use crate::services::fraud_services::{self, PaymentAttempt};
+use crate::services::notification_services::notify_user;
+use crate::services::payment_services::{fail_transaction, validate_product_type, TRANSACTION_COLUMNS};
+use crate::services::rate_services;
+use crate::services::relayer_services::address_word;
+
+const INVOICE_COLUMNS: &str = "id, user_id, transaction_id, chain_id, token_contract, deposit_address, amount, \
+     raw_amount, symbol, status, payer_address, tx_hash, expires_at, paid_at, created_at";
+
+const JOB_INTERVAL_SECS: u64 = 15;
+/// Blocks scanned per `eth_getLogs` call; providers cap the range
+const MAX_SCAN_BLOCKS: u64 = 1_000;
+/// Decimal place of the amount nudge, and how many nudges there are: up to 0.009999 tokens
+const NUDGE_DECIMALS: u8 = 6;
+const AMOUNT_SLOTS: u128 = 10_000;
+/// Free amounts tried before giving up on creating an invoice
+const MAX_AMOUNT_ATTEMPTS: usize = 8;
+/// Invoices outlive their expiry by this long before they are closed, so a payment mined just
+/// in time is still matched
+const EXPIRY_GRACE_MINUTES: i64 = 5;
+/// Invoices expired per run
+const MAX_EXPIRIES_PER_RUN: i64 = 100;
+
+/// The invoice amount in slot `slot` (1-based) for a price of `price` smallest units
+pub fn nudged_amount(price: u128, decimals: u8, slot: u128) -> ApiResult<u128> {
+    if decimals < NUDGE_DECIMALS {
+        return Err(ApiError::ServiceUnavailable(
+            "The token has too few decimals to tell invoices apart".to_string(),
+        ));
+    }
+    let unit = 10u128.pow((decimals - NUDGE_DECIMALS) as u32);
+    price
+        .checked_add(slot * unit)
+        .ok_or_else(|| ApiError::ValidationError("Invoice amount is out of range".to_string()))
+}
+
+/// EIP-681 request to transfer `raw_amount` of `token` to `deposit_address` on `chain_id`
+pub fn payment_uri(token: &str, chain_id: i64, deposit_address: &str, raw_amount: &str) -> String {
+    format!(
+        "ethereum:{}@{}/transfer?address={}&uint256={}",
+        BlockchainService::to_checksum_address(token),
+        chain_id,
+        BlockchainService::to_checksum_address(deposit_address),
+        raw_amount
+    )
+}
+
+fn with_payment_uri(mut invoice: CryptoInvoice) -> CryptoInvoice {
+    invoice.payment_uri = Some(payment_uri(
+        &invoice.token_contract,
+        invoice.chain_id,
+        &invoice.deposit_address,
+        &invoice.raw_amount,
+    ));
+    invoice
+}
+
+/// Value the invoice in `currency` as well. Without a price for its token, or with the rate
+/// source down, the invoice is returned as it is.
+pub async fn with_fiat(config: &AppConfig, mut invoice: CryptoInvoice, currency: &str) -> ApiResult<CryptoInvoice> {
+    match rate_services::rates(config, std::slice::from_ref(&invoice.symbol), currency).await {
+        Ok(rates) => {
+            invoice.fiat = rates.value_of(&invoice.symbol, &invoice.amount).map(|amount| FiatAmount {
+                currency: rates.currency.clone(),
+                amount,
+                stale: rates.stale,
+            });
+        }
+        Err(ApiError::ValidationError(message)) => return Err(ApiError::ValidationError(message)),
+        Err(e) => tracing::warn!(invoice_id = %invoice.id, "Invoice shown without a fiat amount: {}", e),
+    }
+    Ok(invoice)
+}
+
+/// Open an invoice for `product_type` on `chain_id`, with its pending transaction, once the
+/// payment passes screening
+pub async fn create_invoice(
+    pool: &PgPool,
+    config: &AppConfig,
+    user_id: Uuid,
+    product_type: &str,
+    chain_id: u64,
+    country: Option<&str>,
+) -> ApiResult<CryptoInvoice> {
+    validate_product_type(product_type)?;
+    let deposit_address = config
+        .crypto_deposit_address
+        .as_deref()
+        .filter(|a| BlockchainService::is_valid_eth_address(a))
+        .ok_or_else(|| ApiError::ServiceUnavailable("Crypto invoices are not configured".to_string()))?;
+    let chain = BlockchainService::for_chain_id(config, chain_id)?;
+    if !chain.has_provider() {
+        return Err(ApiError::ServiceUnavailable(format!("Chain {} has no RPC provider", chain_id)));
+    }
+    let token = chain.token_contract()?.to_ascii_lowercase();
+    let (decimals, symbol) = chain.token_metadata(&token).await?;
+    let price = parse_units(&config.crypto_product_price, decimals)?;
+    let attempt = PaymentAttempt {
+        user_id,
+        kind: "crypto_invoice",
+        amount: decimal_amount(&config.crypto_product_price)?,
+        currency: &symbol,
+        country,
+    };
+    fraud_services::screen(pool, config, &attempt).await?;
+
+    let invoice_id = Uuid::new_v4();
+    let transaction_id = Uuid::new_v4();
+    let expires_at = Utc::now() + Duration::minutes(config.crypto_invoice_ttl_minutes as i64);
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO transactions (id, user_id, amount, currency, payment_method, payment_id, status, product_type, \
+         chain_id) VALUES ($1, $2, 0, $3, 'crypto', $4, 'pending', $5, $6)",
+    )
+    .bind(transaction_id)
+    .bind(user_id)
+    .bind(&symbol)
+    .bind(invoice_id.to_string())
+    .bind(product_type)
+    .bind(chain_id as i64)
+    .execute(&mut *tx)
+    .await?;
+
+    let mut invoice = None;
+    for _ in 0..MAX_AMOUNT_ATTEMPTS {
+        let slot = rand::thread_rng().gen_range(1..AMOUNT_SLOTS);
+        let raw = nudged_amount(price, decimals, slot)?;
+        invoice = sqlx::query_as::<_, CryptoInvoice>(&format!(
+            "INSERT INTO crypto_invoices (id, user_id, transaction_id, chain_id, token_contract, deposit_address, \
+             amount, raw_amount, symbol, expires_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) \
+             ON CONFLICT (chain_id, token_contract, raw_amount) WHERE status = 'open' DO NOTHING RETURNING {}",
+            INVOICE_COLUMNS
+        ))
+        .bind(invoice_id)
+        .bind(user_id)
+        .bind(transaction_id)
+        .bind(chain_id as i64)
+        .bind(&token)
+        .bind(deposit_address)
+        .bind(format_units(&raw.to_string(), decimals))
+        .bind(raw.to_string())
+        .bind(&symbol)
+        .bind(expires_at)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if invoice.is_some() {
+            break;
+        }
+    }
+    let invoice = invoice.ok_or_else(|| {
+        ApiError::Conflict("Too many invoices are open right now; try again shortly".to_string())
+    })?;
+
+    sqlx::query("UPDATE transactions SET amount = $2 WHERE id = $1")
+        .bind(transaction_id)
+        .bind(decimal_amount(&invoice.amount)?)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(with_payment_uri(invoice))
+}
+
+/// One of the user's invoices
+pub async fn get_invoice(pool: &PgPool, user_id: Uuid, invoice_id: Uuid) -> ApiResult<CryptoInvoice> {
+    let invoice = sqlx::query_as::<_, CryptoInvoice>(&format!(
+        "SELECT {} FROM crypto_invoices WHERE id = $1 AND user_id = $2",
+        INVOICE_COLUMNS
+    ))
+    .bind(invoice_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Invoice not found".to_string()))?;
+    Ok(with_payment_uri(invoice))
+}
+
+/// Scan the next blocks of `chain` for transfers of its token into `deposit_address` and match
+/// them to open invoices, returning how many were paid
+pub async fn match_deposits(pool: &PgPool, chain: &BlockchainService, deposit_address: &str) -> ApiResult<usize> {
+    let token = chain.token_contract()?.to_ascii_lowercase();
+    let chain_id = chain.chain_id() as i64;
+    let head = chain.block_number().await?;
+    let last: Option<i64> = sqlx::query_scalar("SELECT last_block FROM crypto_invoice_scans WHERE chain_id = $1")
+        .bind(chain_id)
+        .fetch_optional(pool)
+        .await?;
+    let waiting: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM crypto_invoices WHERE chain_id = $1 AND status = 'open')",
+    )
+    .bind(chain_id)
+    .fetch_one(pool)
+    .await?;
+
+    // Nothing can be paid before an invoice is open, so idle scans just move to the head
+    let from = match last {
+        Some(last) => last as u64 + 1,
+        None => head.saturating_sub(MAX_SCAN_BLOCKS - 1),
+    };
+    let to = if waiting { head.min(from + MAX_SCAN_BLOCKS - 1) } else { head };
+    let mut paid = 0;
+    if waiting && from <= to {
+        let deposit_topic = format!("0x{}", hex::encode(address_word(deposit_address)?));
+        let topics = serde_json::json!([TRANSFER_TOPIC, null, deposit_topic]);
+        for log in chain.get_logs(&token, topics, from, to).await? {
+            let (Some(raw_amount), Some(payer)) =
+                (decode_uint_decimal(&log.data), log.topics.get(1).and_then(|t| topic_address(t)))
+            else {
+                continue;
+            };
+
+            let mut tx = pool.begin().await?;
+            let Some((invoice_id, transaction_id, user_id)) = sqlx::query_as::<_, (Uuid, Uuid, Uuid)>(
+                "UPDATE crypto_invoices SET status = 'paid', payer_address = $4, tx_hash = $5, log_index = $6, \
+                 paid_at = NOW() \
+                 WHERE chain_id = $1 AND token_contract = $2 AND raw_amount = $3 AND status = 'open' \
+                   AND NOT EXISTS (SELECT 1 FROM crypto_invoices WHERE chain_id = $1 AND tx_hash = $5 \
+                                   AND log_index = $6) \
+                 RETURNING id, transaction_id, user_id",
+            )
+            .bind(chain_id)
+            .bind(&token)
+            .bind(&raw_amount)
+            .bind(&payer)
+            .bind(&log.tx_hash)
+            .bind(log.log_index as i64)
+            .fetch_optional(&mut *tx)
+            .await?
+            else {
+                continue;
+            };
+            sqlx::query("UPDATE transactions SET blockchain_tx_hash = $2 WHERE id = $1 AND status = 'pending'")
+                .bind(transaction_id)
+                .bind(&log.tx_hash)
+                .execute(&mut *tx)
+                .await?;
+            notify_user(
+                &mut tx,
+                user_id,
+                "crypto_payment_detected",
+                "Payment detected",
+                "We have seen your payment on-chain; it completes once the network confirms it.",
+                serde_json::json!({
+                    "invoice_id": invoice_id,
+                    "transaction_id": transaction_id,
+                    "tx_hash": log.tx_hash,
+                }),
+            )
+            .await?;
+            tx.commit().await?;
+            paid += 1;
+        }
+    }
+
+    sqlx::query(
+        "INSERT INTO crypto_invoice_scans (chain_id, last_block) VALUES ($1, $2) \
+         ON CONFLICT (chain_id) DO UPDATE SET last_block = EXCLUDED.last_block, updated_at = NOW()",
+    )
+    .bind(chain_id)
+    .bind(to.max(from.saturating_sub(1)) as i64)
+    .execute(pool)
+    .await?;
+    Ok(paid)
+}
+
+/// Close invoices past their expiry (and grace) and fail their transactions
+pub async fn expire_invoices(pool: &PgPool) -> ApiResult<usize> {
+    let mut tx = pool.begin().await?;
+    let transaction_ids: Vec<Uuid> = sqlx::query_scalar(
+        "UPDATE crypto_invoices SET status = 'expired' WHERE id IN \
+            (SELECT id FROM crypto_invoices WHERE status = 'open' AND expires_at < $1 \
+             ORDER BY expires_at LIMIT $2 FOR UPDATE SKIP LOCKED) \
+         RETURNING transaction_id",
+    )
+    .bind(Utc::now() - Duration::minutes(EXPIRY_GRACE_MINUTES))
+    .bind(MAX_EXPIRIES_PER_RUN)
+    .fetch_all(&mut *tx)
+    .await?;
+    for transaction_id in &transaction_ids {
+        let transaction = sqlx::query_as::<_, Transaction>(&format!(
+            "SELECT {} FROM transactions WHERE id = $1",
+            TRANSACTION_COLUMNS
+        ))
+        .bind(transaction_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        fail_transaction(&mut tx, &transaction, "The invoice expired before payment arrived").await?;
+    }
+    tx.commit().await?;
+    Ok(transaction_ids.len())
+}
+
+/// Match deposits and expire invoices in the background. Does nothing without a deposit
+/// address or a chain that has both a provider and a token contract.
+pub fn spawn_invoice_watcher(pool: Arc<PgPool>, config: AppConfig) {
+    let Some(deposit_address) = config.crypto_deposit_address.clone() else {
+        tracing::info!("No crypto deposit address configured; crypto invoices are off");
+        return;
+    };
+    let chains: Vec<BlockchainService> = config
+        .chains
+        .iter()
+        .map(BlockchainService::for_chain)
+        .filter(|chain| chain.has_provider() && chain.token_contract().is_ok())
+        .collect();
+    if chains.is_empty() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(JOB_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            for chain in &chains {
+                match match_deposits(&pool, chain, &deposit_address).await {
+                    Ok(0) => {}
+                    Ok(paid) => tracing::info!(chain_id = chain.chain_id(), paid, "Matched crypto invoice payments"),
+                    Err(e) => tracing::error!(chain_id = chain.chain_id(), "Invoice watcher failed: {}", e),
+                }
+            }
+            match expire_invoices(&pool).await {
+                Ok(0) => {}
+                Ok(expired) => tracing::info!(expired, "Expired crypto invoices"),
+                Err(e) => tracing::error!("Expiring crypto invoices failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nudged_amount() {
+        let price = parse_units("1.6", 18).unwrap();
+        let amount = nudged_amount(price, 18, 437).unwrap();
+        assert_eq!(format_units(&amount.to_string(), 18), "1.600437");
+        assert_eq!(
+            format_units(&nudged_amount(price, 18, AMOUNT_SLOTS - 1).unwrap().to_string(), 18),
+            "1.609999"
+        );
+        assert_eq!(nudged_amount(1_600_000, 6, 12).unwrap(), 1_600_012);
+        assert!(nudged_amount(160, 2, 1).is_err());
+    }
+
+    #[test]
+    fn test_payment_uri() {
+        assert_eq!(
+            payment_uri(
+                "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed",
+                137,
+                "0xfb6916095ca1df60bb79ce92ce3ea74c37c5d359",
+                "1600437000000000000"
+            ),
+            "ethereum:0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed@137/transfer\
+             ?address=0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359&uint256=1600437000000000000"
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! Identity verification gating payouts. Users submit the details and document reference the
+//! identity provider checked; an admin verifies or rejects them. A rejected or pending
+//! submission may be replaced, a verified one may not.
+
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::payout::{KycVerification, SubmitKycRequest};
+use crate::services::notification_services::notify_user;
+
+const KYC_COLUMNS: &str = "user_id, status, legal_name, country, document_type, document_reference, \
+     rejection_reason, reviewed_by, reviewed_at, submitted_at";
+
+pub const STATUSES: &[&str] = &["pending", "verified", "rejected"];
+pub const DOCUMENT_TYPES: &[&str] = &["passport", "national_id", "driving_license"];
+const MAX_NAME_CHARS: usize = 200;
+const MAX_REFERENCE_CHARS: usize = 200;
+const MAX_REASON_CHARS: usize = 2_000;
+
+/// The submission trimmed, with the country upper-cased
+pub fn validate(request: &SubmitKycRequest) -> ApiResult<SubmitKycRequest> {
+    let legal_name = request.legal_name.trim();
+    if legal_name.is_empty() || legal_name.chars().count() > MAX_NAME_CHARS {
+        return Err(ApiError::ValidationError(format!("legal_name must be 1 to {} characters", MAX_NAME_CHARS)));
+    }
+    let country = request.country.trim().to_uppercase();
+    if country.len() != 2 || !country.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(ApiError::ValidationError("country must be an ISO 3166-1 alpha-2 code".to_string()));
+    }
+    if !DOCUMENT_TYPES.contains(&request.document_type.as_str()) {
+        return Err(ApiError::ValidationError(format!(
+            "document_type must be one of {}",
+            DOCUMENT_TYPES.join(", ")
+        )));
+    }
+    let document_reference = request.document_reference.trim();
+    if document_reference.is_empty() || document_reference.chars().count() > MAX_REFERENCE_CHARS {
+        return Err(ApiError::ValidationError(format!(
+            "document_reference must be 1 to {} characters",
+            MAX_REFERENCE_CHARS
+        )));
+    }
+    Ok(SubmitKycRequest {
+        legal_name: legal_name.to_string(),
+        country,
+        document_type: request.document_type.clone(),
+        document_reference: document_reference.to_string(),
+    })
+}
+
+/// Submit identity details for review, replacing a pending or rejected submission
+pub async fn submit(pool: &PgPool, user_id: Uuid, request: &SubmitKycRequest) -> ApiResult<KycVerification> {
+    let request = validate(request)?;
+    sqlx::query_as::<_, KycVerification>(&format!(
+        "INSERT INTO kyc_verifications (user_id, legal_name, country, document_type, document_reference) \
+         VALUES ($1, $2, $3, $4, $5) \
+         ON CONFLICT (user_id) DO UPDATE SET status = 'pending', legal_name = $2, country = $3, \
+             document_type = $4, document_reference = $5, rejection_reason = NULL, reviewed_by = NULL, \
+             reviewed_at = NULL, submitted_at = NOW() \
+         WHERE kyc_verifications.status <> 'verified' RETURNING {}",
+        KYC_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(&request.legal_name)
+    .bind(&request.country)
+    .bind(&request.document_type)
+    .bind(&request.document_reference)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::Conflict("Your identity is already verified".to_string()))
+}
+
+pub async fn get(pool: &PgPool, user_id: Uuid) -> ApiResult<KycVerification> {
+    sqlx::query_as::<_, KycVerification>(&format!(
+        "SELECT {} FROM kyc_verifications WHERE user_id = $1",
+        KYC_COLUMNS
+    ))
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("No identity verification submitted".to_string()))
+}
+
+/// Submissions in `status`, pending by default, oldest first
+pub async fn queue(pool: &PgPool, status: Option<&str>) -> ApiResult<Vec<KycVerification>> {
+    let status = status.unwrap_or("pending");
+    if !STATUSES.contains(&status) {
+        return Err(ApiError::ValidationError(format!("status must be one of {}", STATUSES.join(", "))));
+    }
+    let verifications = sqlx::query_as::<_, KycVerification>(&format!(
+        "SELECT {} FROM kyc_verifications WHERE status = $1 ORDER BY submitted_at LIMIT 200",
+        KYC_COLUMNS
+    ))
+    .bind(status)
+    .fetch_all(pool)
+    .await?;
+    Ok(verifications)
+}
+
+/// Verify or reject a pending submission and tell the user
+pub async fn review(
+    pool: &PgPool,
+    admin_id: Uuid,
+    user_id: Uuid,
+    verify: bool,
+    reason: Option<&str>,
+) -> ApiResult<KycVerification> {
+    let reason = reason.map(str::trim).filter(|r| !r.is_empty());
+    if !verify && reason.is_none() {
+        return Err(ApiError::ValidationError("reason is required when rejecting".to_string()));
+    }
+    if reason.is_some_and(|r| r.chars().count() > MAX_REASON_CHARS) {
+        return Err(ApiError::ValidationError(format!("reason must be at most {} characters", MAX_REASON_CHARS)));
+    }
+
+    let mut tx = pool.begin().await?;
+    let verification = sqlx::query_as::<_, KycVerification>(&format!(
+        "UPDATE kyc_verifications SET status = $2, rejection_reason = $3, reviewed_by = $4, reviewed_at = NOW() \
+         WHERE user_id = $1 AND status = 'pending' RETURNING {}",
+        KYC_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(if verify { "verified" } else { "rejected" })
+    .bind(if verify { None } else { reason })
+    .bind(admin_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::Conflict("No pending identity verification for this user".to_string()))?;
+
+    let (title, body) = if verify {
+        ("Identity verified", "Your identity is verified; you can now withdraw your credits.".to_string())
+    } else {
+        ("Identity verification rejected", format!("Your identity could not be verified: {}", reason.unwrap_or("")))
+    };
+    notify_user(
+        &mut tx,
+        user_id,
+        &format!("kyc.{}", verification.status),
+        title,
+        &body,
+        serde_json::json!({ "status": verification.status }),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(verification)
+}
+
+/// Refuse users whose identity is not verified
+pub async fn require_verified(conn: &mut PgConnection, user_id: Uuid) -> ApiResult<()> {
+    let status: Option<String> = sqlx::query_scalar("SELECT status FROM kyc_verifications WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_optional(conn)
+        .await?;
+    match status.as_deref() {
+        Some("verified") => Ok(()),
+        Some("pending") => Err(ApiError::Forbidden("Your identity verification is still under review".to_string())),
+        _ => Err(ApiError::Forbidden("Verify your identity before withdrawing (POST /api/kyc)".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(country: &str, document_type: &str) -> SubmitKycRequest {
+        SubmitKycRequest {
+            legal_name: " Ada Lovelace ".to_string(),
+            country: country.to_string(),
+            document_type: document_type.to_string(),
+            document_reference: "idv_7Hk2".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let valid = validate(&request(" gb ", "passport")).unwrap();
+        assert_eq!(valid.legal_name, "Ada Lovelace");
+        assert_eq!(valid.country, "GB");
+
+        assert!(validate(&request("GBR", "passport")).is_err());
+        assert!(validate(&request("G1", "passport")).is_err());
+        assert!(validate(&request("GB", "utility_bill")).is_err());
+        let mut blank = request("GB", "passport");
+        blank.document_reference = "  ".to_string();
+        assert!(validate(&blank).is_err());
+    }
+}
@@ -0,0 +1,468 @@
+//! Double-entry ledger. Each event the platform owes or holds money for is posted once as a
+//! journal entry whose lines sum to zero, debits positive and credits negative:
+//!
+//! ```text
+//! payment completed   Dr cash:<method>        Cr revenue:<product type>
+//! refund              Dr refunds              Cr cash:<method>
+//! dispute lost        Dr chargebacks          Cr cash:<method>
+//! credits granted     Dr credits_issued       Cr credits:<user>       (reversed when taken back)
+//! payout requested    Dr credits:<user>       Cr payouts_pending      (reversed when it ends unpaid)
+//! payout paid         Dr payouts_pending      Cr cash:<method>
+//! ```
+//!
+//! Entries are keyed by the event they record, so posting one again does nothing. Token
+//! transfers and escrows move users' own tokens and are not posted.
+
+use rust_decimal::Decimal;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::dispute::Dispute;
+use crate::models::ledger::{
+    AccountBalance, CurrencyTotals, LedgerBalance, LedgerDiscrepancy, LedgerLine, LedgerLineQuery, TrialBalance,
+};
+use crate::models::payout::{CreditEntry, Payout};
+use crate::models::transaction::{Refund, Transaction};
+use crate::services::{escrow_services, payout_services, transfer_services};
+
+/// Product types whose transactions are not the platform's money
+const UNPOSTED_PRODUCT_TYPES: &[&str] = &[
+    transfer_services::PRODUCT_TYPE,
+    escrow_services::FUNDING_PRODUCT_TYPE,
+    escrow_services::PAYOUT_PRODUCT_TYPE,
+    payout_services::PRODUCT_TYPE,
+];
+
+const DEFAULT_LINES: i64 = 50;
+const MAX_LINES: i64 = 200;
+
+/// An account to post to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Account {
+    pub code: String,
+    /// asset, liability, revenue, expense
+    pub kind: &'static str,
+    pub user_id: Option<Uuid>,
+}
+
+impl Account {
+    fn new(code: String, kind: &'static str) -> Self {
+        Account { code, kind, user_id: None }
+    }
+
+    /// Money held with a payment provider, on chain or in the bank
+    pub fn cash(method: &str) -> Self {
+        Account::new(format!("cash:{}", method), "asset")
+    }
+
+    pub fn revenue(product_type: &str) -> Self {
+        Account::new(format!("revenue:{}", product_type), "revenue")
+    }
+
+    pub fn refunds() -> Self {
+        Account::new("refunds".to_string(), "expense")
+    }
+
+    /// Credits the platform owes the user
+    pub fn credits(user_id: Uuid) -> Self {
+        Account { code: format!("credits:{}", user_id), kind: "liability", user_id: Some(user_id) }
+    }
+
+    /// Payments taken back by the card network after a lost dispute
+    pub fn chargebacks() -> Self {
+        Account::new("chargebacks".to_string(), "expense")
+    }
+
+    /// What granting credits has cost the platform
+    pub fn credits_issued() -> Self {
+        Account::new("credits_issued".to_string(), "expense")
+    }
+
+    /// Credits on their way out in open payouts
+    pub fn payouts_pending() -> Self {
+        Account::new("payouts_pending".to_string(), "liability")
+    }
+}
+
+/// A journal entry to post
+#[derive(Debug)]
+pub struct Entry<'a> {
+    /// The event recorded; an entry is posted once per reference
+    pub reference: String,
+    pub description: String,
+    pub currency: &'a str,
+    pub transaction_id: Option<Uuid>,
+    pub payout_id: Option<Uuid>,
+    pub created_by: Option<Uuid>,
+    pub lines: Vec<(Account, Decimal)>,
+}
+
+/// Lines moving `amount` out of `credit` into `debit`; the other way round when negative
+pub fn movement(debit: Account, credit: Account, amount: Decimal) -> Vec<(Account, Decimal)> {
+    if amount.is_sign_negative() {
+        return vec![(credit, -amount), (debit, amount)];
+    }
+    vec![(debit, amount), (credit, -amount)]
+}
+
+/// Refuse lines that are not a balanced entry
+pub fn check_balanced(lines: &[(Account, Decimal)]) -> ApiResult<()> {
+    if lines.len() < 2 {
+        return Err(ApiError::InternalError("A journal entry needs at least two lines".to_string()));
+    }
+    if lines.iter().any(|(_, amount)| amount.is_zero()) {
+        return Err(ApiError::InternalError("Journal lines must be non-zero amounts".to_string()));
+    }
+    let sum: Decimal = lines.iter().map(|(_, amount)| amount).sum();
+    if !sum.is_zero() {
+        return Err(ApiError::InternalError(format!("Journal entry is off balance by {}", sum)));
+    }
+    Ok(())
+}
+
+/// Post `entry`, opening its accounts as needed. False when its reference was already posted.
+pub async fn post(conn: &mut PgConnection, entry: &Entry<'_>) -> ApiResult<bool> {
+    check_balanced(&entry.lines)?;
+    let entry_id: Option<i64> = sqlx::query_scalar(
+        "INSERT INTO journal_entries (reference, description, transaction_id, payout_id, created_by) \
+         VALUES ($1, $2, $3, $4, $5) ON CONFLICT (reference) DO NOTHING RETURNING id",
+    )
+    .bind(&entry.reference)
+    .bind(&entry.description)
+    .bind(entry.transaction_id)
+    .bind(entry.payout_id)
+    .bind(entry.created_by)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(entry_id) = entry_id else {
+        return Ok(false);
+    };
+
+    for (account, amount) in &entry.lines {
+        // The no-op update makes RETURNING give the id of an existing account too
+        let account_id: i64 = sqlx::query_scalar(
+            "INSERT INTO accounts (code, kind, currency, user_id) VALUES ($1, $2, $3, $4) \
+             ON CONFLICT (code, currency) DO UPDATE SET code = EXCLUDED.code RETURNING id",
+        )
+        .bind(&account.code)
+        .bind(account.kind)
+        .bind(entry.currency)
+        .bind(account.user_id)
+        .fetch_one(&mut *conn)
+        .await?;
+        sqlx::query("INSERT INTO journal_lines (entry_id, account_id, amount) VALUES ($1, $2, $3)")
+            .bind(entry_id)
+            .bind(account_id)
+            .bind(amount)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(true)
+}
+
+/// Whether a transaction of `product_type` is the platform's revenue
+pub fn is_revenue(product_type: &str) -> bool {
+    !UNPOSTED_PRODUCT_TYPES.contains(&product_type)
+}
+
+/// Post a completed payment as cash in and revenue earned
+pub async fn post_payment(conn: &mut PgConnection, transaction: &Transaction) -> ApiResult<()> {
+    if !is_revenue(&transaction.product_type) || transaction.amount <= Decimal::ZERO {
+        return Ok(());
+    }
+    let entry = Entry {
+        reference: format!("transaction:{}:completed", transaction.id),
+        description: format!("Payment for {}", transaction.product_type),
+        currency: &transaction.currency,
+        transaction_id: Some(transaction.id),
+        payout_id: None,
+        created_by: None,
+        lines: movement(
+            Account::cash(&transaction.payment_method),
+            Account::revenue(&transaction.product_type),
+            transaction.amount,
+        ),
+    };
+    post(conn, &entry).await?;
+    Ok(())
+}
+
+/// Post a refund as cash going back out
+pub async fn post_refund(conn: &mut PgConnection, transaction: &Transaction, refund: &Refund) -> ApiResult<()> {
+    let entry = Entry {
+        reference: format!("refund:{}", refund.id),
+        description: format!("Refund for {}", transaction.product_type),
+        currency: &transaction.currency,
+        transaction_id: Some(transaction.id),
+        payout_id: None,
+        created_by: refund.requested_by,
+        lines: movement(Account::refunds(), Account::cash(&transaction.payment_method), refund.amount),
+    };
+    post(conn, &entry).await?;
+    Ok(())
+}
+
+/// Post a lost dispute as cash the card network took back
+pub async fn post_chargeback(conn: &mut PgConnection, transaction: &Transaction, dispute: &Dispute) -> ApiResult<()> {
+    let entry = Entry {
+        reference: format!("dispute:{}:lost", dispute.id),
+        description: format!("Chargeback for {}", transaction.product_type),
+        currency: &dispute.currency,
+        transaction_id: Some(transaction.id),
+        payout_id: None,
+        created_by: dispute.resolved_by,
+        lines: movement(Account::chargebacks(), Account::cash(&transaction.payment_method), dispute.amount),
+    };
+    post(conn, &entry).await?;
+    Ok(())
+}
+
+/// The accounts a credit entry moves credits between, as (debit, credit) for a positive amount
+fn credit_accounts(entry: &CreditEntry) -> (Account, Account) {
+    match entry.source.as_str() {
+        // Held for the payout, then put back when it ends unpaid
+        "payout" | "payout_reversal" => (Account::payouts_pending(), Account::credits(entry.user_id)),
+        _ => (Account::credits_issued(), Account::credits(entry.user_id)),
+    }
+}
+
+/// Post a change to a user's credits
+pub async fn post_credit_entry(conn: &mut PgConnection, credit: &CreditEntry) -> ApiResult<()> {
+    let (debit, credit_account) = credit_accounts(credit);
+    let entry = Entry {
+        reference: format!("credit_entry:{}", credit.id),
+        description: format!("Credits: {}", credit.source),
+        currency: &credit.currency,
+        transaction_id: None,
+        payout_id: credit.payout_id,
+        created_by: credit.created_by,
+        // A negative entry takes credits from the user: the movement runs the other way
+        lines: movement(debit, credit_account, credit.amount),
+    };
+    post(conn, &entry).await?;
+    Ok(())
+}
+
+/// Post a payout leaving the platform
+pub async fn post_payout_paid(conn: &mut PgConnection, payout: &Payout) -> ApiResult<()> {
+    let entry = Entry {
+        reference: format!("payout:{}:paid", payout.id),
+        description: format!("Payout by {}", payout.method),
+        currency: &payout.currency,
+        transaction_id: payout.transaction_id,
+        payout_id: Some(payout.id),
+        created_by: payout.reviewed_by,
+        lines: movement(Account::payouts_pending(), Account::cash(&payout.method), payout.amount),
+    };
+    post(conn, &entry).await?;
+    Ok(())
+}
+
+/// The user's credits per currency, summed from the ledger
+pub async fn balances(pool: &PgPool, user_id: Uuid) -> ApiResult<Vec<LedgerBalance>> {
+    let balances = sqlx::query_as::<_, LedgerBalance>(
+        "SELECT a.currency, -COALESCE(SUM(l.amount), 0) AS balance FROM accounts a \
+         LEFT JOIN journal_lines l ON l.account_id = a.id \
+         WHERE a.code = $1 GROUP BY a.currency ORDER BY a.currency",
+    )
+    .bind(Account::credits(user_id).code)
+    .fetch_all(pool)
+    .await?;
+    Ok(balances)
+}
+
+/// Postings to the user's credits, newest first
+pub async fn lines(pool: &PgPool, user_id: Uuid, query: &LedgerLineQuery) -> ApiResult<Vec<LedgerLine>> {
+    let limit = query.limit.unwrap_or(DEFAULT_LINES).clamp(1, MAX_LINES);
+    let lines = sqlx::query_as::<_, LedgerLine>(
+        "SELECT e.id AS entry_id, e.reference, e.description, a.currency, -l.amount AS amount, e.payout_id, \
+             e.created_at \
+         FROM journal_lines l JOIN accounts a ON a.id = l.account_id JOIN journal_entries e ON e.id = l.entry_id \
+         WHERE a.code = $1 AND ($2::BIGINT IS NULL OR e.id < $2) ORDER BY e.id DESC LIMIT $3",
+    )
+    .bind(Account::credits(user_id).code)
+    .bind(query.before)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(lines)
+}
+
+/// Debits and credits per currency, and whether they match
+pub fn totals(accounts: &[AccountBalance]) -> Vec<CurrencyTotals> {
+    let mut totals: Vec<CurrencyTotals> = Vec::new();
+    for account in accounts {
+        let index = match totals.iter().position(|t| t.currency == account.currency) {
+            Some(index) => index,
+            None => {
+                totals.push(CurrencyTotals {
+                    currency: account.currency.clone(),
+                    debits: Decimal::ZERO,
+                    credits: Decimal::ZERO,
+                    balanced: true,
+                });
+                totals.len() - 1
+            }
+        };
+        totals[index].debits += account.debits;
+        totals[index].credits += account.credits;
+    }
+    for total in &mut totals {
+        total.balanced = total.debits == total.credits;
+    }
+    totals
+}
+
+/// Every account with its balance, and the debit and credit totals per currency
+pub async fn trial_balance(pool: &PgPool) -> ApiResult<TrialBalance> {
+    let accounts = sqlx::query_as::<_, AccountBalance>(
+        "SELECT a.id, a.code, a.kind, a.currency, a.user_id, \
+             COALESCE(SUM(l.amount) FILTER (WHERE l.amount > 0), 0) AS debits, \
+             -COALESCE(SUM(l.amount) FILTER (WHERE l.amount < 0), 0) AS credits, \
+             CASE WHEN a.kind IN ('asset', 'expense') THEN 1 ELSE -1 END * COALESCE(SUM(l.amount), 0) AS balance \
+         FROM accounts a LEFT JOIN journal_lines l ON l.account_id = a.id \
+         GROUP BY a.id ORDER BY a.currency, a.kind, a.code",
+    )
+    .fetch_all(pool)
+    .await?;
+    let totals = totals(&accounts);
+    Ok(TrialBalance { accounts, totals })
+}
+
+/// Credit balances, payments, refunds and payouts the ledger disagrees with
+pub async fn reconcile(pool: &PgPool) -> ApiResult<Vec<LedgerDiscrepancy>> {
+    let discrepancies = sqlx::query_as::<_, LedgerDiscrepancy>(
+        "WITH posted AS ( \
+             SELECT e.reference, SUM(l.amount) FILTER (WHERE l.amount > 0) AS amount \
+             FROM journal_entries e JOIN journal_lines l ON l.entry_id = e.id GROUP BY e.id \
+         ), \
+         owed AS ( \
+             SELECT 'credits:' || user_id AS code, currency, SUM(amount) AS amount \
+             FROM credit_entries GROUP BY user_id, currency \
+         ), \
+         booked AS ( \
+             SELECT a.code, a.currency, -COALESCE(SUM(l.amount), 0) AS amount \
+             FROM accounts a LEFT JOIN journal_lines l ON l.account_id = a.id \
+             WHERE a.code LIKE 'credits:%' GROUP BY a.id \
+         ), \
+         expected AS ( \
+             SELECT 'payment' AS kind, 'transaction:' || id || ':completed' AS reference, currency, amount \
+             FROM transactions \
+             WHERE status IN ('completed', 'partially_refunded', 'refunded', 'charged_back') AND amount > 0 \
+                 AND NOT (product_type = ANY($1)) \
+             UNION ALL \
+             SELECT 'refund', 'refund:' || r.id, t.currency, r.amount \
+             FROM refunds r JOIN transactions t ON t.id = r.transaction_id WHERE r.status <> 'failed' \
+             UNION ALL \
+             SELECT 'payout', 'payout:' || id || ':paid', currency, amount FROM payouts WHERE status = 'paid' \
+             UNION ALL \
+             SELECT 'chargeback', 'dispute:' || id || ':lost', currency, amount FROM disputes WHERE status = 'lost' \
+         ) \
+         SELECT 'credits' AS kind, COALESCE(o.code, b.code) AS reference, \
+             COALESCE(o.currency, b.currency) AS currency, COALESCE(o.amount, 0) AS expected, \
+             COALESCE(b.amount, 0) AS recorded \
+         FROM owed o FULL JOIN booked b ON b.code = o.code AND b.currency = o.currency \
+         WHERE COALESCE(o.amount, 0) <> COALESCE(b.amount, 0) \
+         UNION ALL \
+         SELECT x.kind, x.reference, x.currency, x.amount, COALESCE(p.amount, 0) \
+         FROM expected x LEFT JOIN posted p ON p.reference = x.reference \
+         WHERE x.amount <> COALESCE(p.amount, 0) \
+         ORDER BY kind, reference LIMIT 500",
+    )
+    .bind(UNPOSTED_PRODUCT_TYPES)
+    .fetch_all(pool)
+    .await?;
+    Ok(discrepancies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::ToPrimitive;
+
+    fn credit_entry(amount: i64, source: &str) -> CreditEntry {
+        CreditEntry {
+            id: 7,
+            user_id: Uuid::nil(),
+            amount: Decimal::from(amount),
+            currency: "usd".to_string(),
+            source: source.to_string(),
+            payout_id: None,
+            note: None,
+            created_by: None,
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    fn lines_for(credit: &CreditEntry) -> Vec<(String, i64)> {
+        let (debit, credit_account) = credit_accounts(credit);
+        movement(debit, credit_account, credit.amount)
+            .into_iter()
+            .map(|(a, amount)| (a.code, amount.to_i64().unwrap_or_default()))
+            .collect()
+    }
+
+    #[test]
+    fn test_check_balanced() {
+        let (refunds, cash) = (Account::refunds(), Account::cash("stripe"));
+        let price = Decimal::new(999, 2);
+        assert!(check_balanced(&movement(cash.clone(), Account::revenue("premium"), price)).is_ok());
+        let five = Decimal::from(5);
+        assert!(check_balanced(&[(refunds.clone(), five)]).is_err());
+        assert!(check_balanced(&[(refunds.clone(), five), (cash.clone(), -Decimal::from(4))]).is_err());
+        assert!(check_balanced(&[(refunds.clone(), Decimal::ZERO), (cash.clone(), Decimal::ZERO)]).is_err());
+        // Exact amounts: 0.1 + 0.2 is 0.3
+        let lines = [(refunds, Decimal::new(1, 1) + Decimal::new(2, 1)), (cash, -Decimal::new(3, 1))];
+        assert!(check_balanced(&lines).is_ok());
+    }
+
+    #[test]
+    fn test_credit_entry_lines() {
+        let user = format!("credits:{}", Uuid::nil());
+        assert_eq!(
+            lines_for(&credit_entry(25, "device_lease")),
+            vec![("credits_issued".to_string(), 25), (user.clone(), -25)]
+        );
+        // Taken back by an admin
+        assert_eq!(
+            lines_for(&credit_entry(-5, "adjustment")),
+            vec![(user.clone(), 5), ("credits_issued".to_string(), -5)]
+        );
+        // Held for a payout, then put back
+        assert_eq!(
+            lines_for(&credit_entry(-10, "payout")),
+            vec![(user.clone(), 10), ("payouts_pending".to_string(), -10)]
+        );
+        assert_eq!(
+            lines_for(&credit_entry(10, "payout_reversal")),
+            vec![("payouts_pending".to_string(), 10), (user, -10)]
+        );
+    }
+
+    #[test]
+    fn test_is_revenue() {
+        assert!(is_revenue("premium_features"));
+        assert!(is_revenue("subscription"));
+        assert!(!is_revenue(transfer_services::PRODUCT_TYPE));
+        assert!(!is_revenue(payout_services::PRODUCT_TYPE));
+    }
+
+    #[test]
+    fn test_totals() {
+        let account = |currency: &str, debits: i64, credits: i64| AccountBalance {
+            id: 1,
+            code: "cash:stripe".to_string(),
+            kind: "asset".to_string(),
+            currency: currency.to_string(),
+            user_id: None,
+            debits: Decimal::from(debits),
+            credits: Decimal::from(credits),
+            balance: Decimal::from(debits - credits),
+        };
+        let totals = totals(&[account("usd", 10, 0), account("usd", 0, 10), account("USDC", 3, 0)]);
+        assert_eq!(totals.len(), 2);
+        assert!(totals[0].balanced);
+        assert_eq!(totals[0].debits, Decimal::TEN);
+        assert!(!totals[1].balanced);
+    }
+}
@@ -0,0 +1,23 @@
+//! Outbound email queue; a separate mailer drains `email_outbox`
+
+use sqlx::PgConnection;
+use crate::errors::ApiResult;
+
+/// Queue an email for delivery, optionally with a Reply-To address
+pub async fn queue_email(
+    conn: &mut PgConnection,
+    to: &str,
+    subject: &str,
+    body: &str,
+    reply_to: Option<&str>,
+) -> ApiResult<()> {
+    sqlx::query("INSERT INTO email_outbox (to_address, subject, body, reply_to) VALUES ($1, $2, $3, $4)")
+        .bind(to)
+        .bind(subject)
+        .bind(body)
+        .bind(reply_to)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,160 @@
+//! Per-device-type JSON Schemas for `devices.metadata` and the checks run on every update
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use validator::{ValidationError, ValidationErrors};
+use crate::errors::{ApiError, ApiResult};
+use crate::services::robotics_services::DEVICE_TYPES;
+use crate::utils::json_schema;
+
+/// Largest metadata document accepted, in bytes of compact JSON
+pub const MAX_METADATA_BYTES: usize = 16 * 1024;
+
+/// Keys every device type accepts; `custom` holds free-form integration data
+fn common_properties() -> Value {
+    json!({
+        "tags": {
+            "type": "array",
+            "maxItems": 32,
+            "uniqueItems": true,
+            "items": { "type": "string", "minLength": 1, "maxLength": 32, "pattern": "^[A-Za-z0-9_.:-]+$" }
+        },
+        "camera": { "type": "boolean" },
+        "location": { "type": "string", "maxLength": 128 },
+        "serial_number": { "type": "string", "maxLength": 64 },
+        "battery_capacity_wh": { "type": "number", "minimum": 1, "maximum": 100000 },
+        "custom": { "type": "object", "maxProperties": 32 }
+    })
+}
+
+fn type_properties(device_type: &str) -> Value {
+    match device_type {
+        "drone" => json!({
+            "max_altitude_m": { "type": "number", "minimum": 0, "maximum": 500 },
+            "max_payload_kg": { "type": "number", "minimum": 0, "maximum": 100 },
+            "max_flight_time_min": { "type": "integer", "minimum": 1, "maximum": 600 }
+        }),
+        "robot" => json!({
+            "max_payload_kg": { "type": "number", "minimum": 0, "maximum": 1000 },
+            "reach_m": { "type": "number", "minimum": 0, "maximum": 10 }
+        }),
+        "rover" => json!({
+            "wheel_count": { "type": "integer", "minimum": 2, "maximum": 16 },
+            "terrain": { "enum": ["indoor", "urban", "offroad", "agricultural"] },
+            "max_payload_kg": { "type": "number", "minimum": 0, "maximum": 1000 }
+        }),
+        _ => json!({}),
+    }
+}
+
+static SCHEMAS: LazyLock<HashMap<&'static str, Value>> = LazyLock::new(|| {
+    DEVICE_TYPES
+        .iter()
+        .map(|spec| {
+            let mut properties = common_properties();
+            if let (Value::Object(all), Value::Object(specific)) =
+                (&mut properties, type_properties(spec.device_type))
+            {
+                all.extend(specific);
+            }
+            let schema = json!({
+                "$schema": "https://json-schema.org/draft/2020-12/schema",
+                "title": format!("{} metadata", spec.device_type),
+                "type": "object",
+                "additionalProperties": false,
+                "properties": properties
+            });
+            (spec.device_type, schema)
+        })
+        .collect()
+});
+
+/// The metadata schema for a device type, served to clients so they can validate up front
+pub fn metadata_schema(device_type: &str) -> Option<&'static Value> {
+    SCHEMAS.get(device_type)
+}
+
+/// Validate a full metadata document, reporting every failing field by its JSON Pointer
+pub fn validate_metadata(device_type: &str, metadata: &Value) -> ApiResult<()> {
+    let schema = metadata_schema(device_type)
+        .ok_or_else(|| ApiError::ValidationError(format!("Unknown device type: {}", device_type)))?;
+    if metadata.to_string().len() > MAX_METADATA_BYTES {
+        return Err(ApiError::ValidationError(format!(
+            "metadata must be at most {} bytes",
+            MAX_METADATA_BYTES
+        )));
+    }
+
+    let failures = json_schema::validate(schema, metadata);
+    if failures.is_empty() {
+        return Ok(());
+    }
+    let mut errors = ValidationErrors::new();
+    for failure in failures {
+        let pointer = if failure.path.is_empty() { "/".to_string() } else { failure.path };
+        let mut error = ValidationError::new("schema").with_message(format!("{} {}", pointer, failure.message).into());
+        error.add_param("path".into(), &pointer);
+        errors.add("metadata", error);
+    }
+    Err(errors.into())
+}
+
+/// Apply a JSON Merge Patch (RFC 7396): objects merge recursively and `null` removes a key
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = json!({});
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schemas_cover_every_device_type() {
+        for spec in DEVICE_TYPES {
+            let schema = metadata_schema(spec.device_type).unwrap();
+            assert!(schema["properties"]["tags"].is_object());
+            assert!(validate_metadata(spec.device_type, &json!({})).is_ok());
+        }
+        assert!(validate_metadata("submarine", &json!({})).is_err());
+    }
+
+    #[test]
+    fn test_validate_metadata_reports_each_field() {
+        let valid = json!({ "tags": ["north", "zone:3"], "camera": true, "wheel_count": 6, "terrain": "offroad" });
+        assert!(validate_metadata("rover", &valid).is_ok());
+
+        let invalid = json!({ "tags": ["bad tag"], "camera": "yes", "max_altitude_m": 100 });
+        let message = validate_metadata("rover", &invalid).unwrap_err().to_string();
+        assert!(message.contains("/camera must be of type boolean"), "{}", message);
+        assert!(message.contains("/max_altitude_m is not an allowed property"), "{}", message);
+        assert!(message.contains("/tags/0 must match pattern"), "{}", message);
+        // Drones do take an altitude ceiling
+        assert!(validate_metadata("drone", &json!({ "max_altitude_m": 100 })).is_ok());
+
+        let message = validate_metadata("robot", &json!(["not", "an", "object"])).unwrap_err().to_string();
+        assert!(message.contains("metadata: / must be of type object"), "{}", message);
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        let mut metadata = json!({ "camera": true, "tags": ["a"], "custom": { "x": 1, "y": 2 } });
+        merge_patch(&mut metadata, &json!({ "camera": null, "tags": ["b"], "custom": { "y": null, "z": 3 } }));
+        assert_eq!(metadata, json!({ "tags": ["b"], "custom": { "x": 1, "z": 3 } }));
+    }
+}
@@ -0,0 +1,388 @@
+//! Org-defined metrics: formulas over telemetry fields evaluated per device and hourly bucket.
+//!
+//! Inside a formula a bare field path is its average over the hour; calls aggregate a field
+//! (`avg min max sum first last delta`) or the window itself (`count distance duration`).
+//! The background job rolls up completed hours, stores one value per device and queues a
+//! `metric` event for owners with metric automations.
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use sqlx::{FromRow, PgPool};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::metric::{MetricPoint, OrgMetric};
+use crate::services::automation_services::lookup;
+use crate::utils::formula::{self, Expr};
+use crate::utils::geo::haversine_distance_m;
+
+pub const METRIC_COLUMNS: &str =
+    "id, org_id, name, description, unit, formula, computed_through, created_by, created_at, updated_at";
+
+pub const MAX_METRICS_PER_ORG: i64 = 50;
+
+/// Functions taking a field path, and those describing the window as a whole
+pub const FIELD_FUNCTIONS: &[&str] = &["avg", "min", "max", "sum", "first", "last", "delta"];
+pub const WINDOW_FUNCTIONS: &[&str] = &["count", "distance", "duration"];
+
+/// How far back a new or edited metric is computed
+pub const BACKFILL_HOURS: i64 = 24;
+
+/// Telemetry may arrive late; an hour is rolled up only once this much time has passed after it
+const LATE_ARRIVAL_MINUTES: i64 = 5;
+const MAX_BUCKETS_PER_RUN: usize = 24;
+const JOB_INTERVAL_SECS: u64 = 300;
+
+/// Restricts devices to those owned by active members of the org bound as $1
+const ORG_DEVICE_FILTER: &str =
+    "d.user_id IN (SELECT user_id FROM org_memberships WHERE org_id = $1 AND active)";
+
+/// One telemetry row as the formula sees it
+#[derive(Debug, Clone, FromRow)]
+pub struct Sample {
+    pub device_id: Uuid,
+    pub recorded_at: DateTime<Utc>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub payload: serde_json::Value,
+}
+
+/// Parse a formula and check it only calls known functions with the right arguments
+pub fn validate_formula(src: &str) -> ApiResult<Expr> {
+    let expr = formula::parse(src).map_err(|e| ApiError::ValidationError(format!("Invalid formula: {}", e)))?;
+    let mut error = None;
+    expr.visit(&mut |node| {
+        if error.is_some() {
+            return;
+        }
+        if let Expr::Call(name, arg) = node {
+            error = match (arg, FIELD_FUNCTIONS.contains(&name.as_str()), WINDOW_FUNCTIONS.contains(&name.as_str())) {
+                (Some(_), true, _) | (None, _, true) => None,
+                (None, true, _) => Some(format!("{}() needs a field, e.g. {}(battery_level)", name, name)),
+                (Some(_), _, true) => Some(format!("{}() takes no arguments", name)),
+                _ => Some(format!(
+                    "Unknown function '{}'; expected one of {}, {}",
+                    name,
+                    FIELD_FUNCTIONS.join(", "),
+                    WINDOW_FUNCTIONS.join(", ")
+                )),
+            };
+        }
+    });
+    match error {
+        Some(msg) => Err(ApiError::ValidationError(format!("Invalid formula: {}", msg))),
+        None => Ok(expr),
+    }
+}
+
+/// `battery_level` and `latitude`/`longitude` are stored as columns but also read from the payload
+fn field(sample: &Sample, path: &str) -> Option<f64> {
+    match path {
+        "latitude" => Some(sample.latitude),
+        "longitude" => Some(sample.longitude),
+        _ => lookup(&sample.payload, path).and_then(|v| v.as_f64()),
+    }
+}
+
+fn aggregate(func: &str, path: Option<&str>, samples: &[Sample]) -> Option<f64> {
+    let Some(path) = path else {
+        return match func {
+            "count" => Some(samples.len() as f64),
+            "duration" => {
+                let (first, last) = (samples.first()?, samples.last()?);
+                Some((last.recorded_at - first.recorded_at).num_milliseconds() as f64 / 1000.0)
+            }
+            "distance" => Some(
+                samples
+                    .windows(2)
+                    .map(|w| haversine_distance_m(w[0].latitude, w[0].longitude, w[1].latitude, w[1].longitude))
+                    .sum(),
+            ),
+            _ => None,
+        };
+    };
+    let values: Vec<f64> = samples.iter().filter_map(|s| field(s, path)).collect();
+    let (first, last) = (*values.first()?, *values.last()?);
+    match func {
+        "avg" => Some(values.iter().sum::<f64>() / values.len() as f64),
+        "min" => values.iter().copied().reduce(f64::min),
+        "max" => values.iter().copied().reduce(f64::max),
+        "sum" => Some(values.iter().sum()),
+        "first" => Some(first),
+        "last" => Some(last),
+        "delta" => Some(last - first),
+        _ => None,
+    }
+}
+
+/// Value of a formula over one device's samples in time order; `None` when the window has
+/// no samples, a field is missing or the arithmetic is undefined (e.g. division by zero)
+pub fn evaluate_window(expr: &Expr, samples: &[Sample]) -> Option<f64> {
+    if samples.is_empty() {
+        return None;
+    }
+    expr.eval(&mut |func, path| aggregate(func.unwrap_or("avg"), path, samples))
+}
+
+/// Start of the hour containing `at`
+pub fn hour_start(at: DateTime<Utc>) -> DateTime<Utc> {
+    at.duration_trunc(Duration::hours(1)).unwrap_or(at)
+}
+
+/// Telemetry of the org's devices in `[from, to)`, grouped by device and in time order
+async fn org_samples(
+    pool: &PgPool,
+    org_id: Uuid,
+    device_id: Option<Uuid>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> ApiResult<HashMap<Uuid, Vec<Sample>>> {
+    let rows = sqlx::query_as::<_, Sample>(&format!(
+        "SELECT dt.device_id, dt.recorded_at, dt.latitude, dt.longitude, dt.payload \
+         FROM device_telemetry dt JOIN devices d ON d.id = dt.device_id \
+         WHERE {} AND ($2::uuid IS NULL OR dt.device_id = $2) \
+           AND dt.recorded_at >= $3 AND dt.recorded_at < $4 \
+         ORDER BY dt.device_id, dt.recorded_at",
+        ORG_DEVICE_FILTER
+    ))
+    .bind(org_id)
+    .bind(device_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+
+    let mut by_device: HashMap<Uuid, Vec<Sample>> = HashMap::new();
+    for row in rows {
+        by_device.entry(row.device_id).or_default().push(row);
+    }
+    Ok(by_device)
+}
+
+/// Whether a device is owned by an active member of the org
+pub async fn device_in_org(pool: &PgPool, org_id: Uuid, device_id: Uuid) -> ApiResult<bool> {
+    let in_org: bool = sqlx::query_scalar(&format!(
+        "SELECT EXISTS (SELECT 1 FROM devices d WHERE d.id = $2 AND {})",
+        ORG_DEVICE_FILTER
+    ))
+    .bind(org_id)
+    .bind(device_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(in_org)
+}
+
+/// Hourly values of an unsaved formula for one device, computed from raw telemetry
+pub async fn preview(
+    pool: &PgPool,
+    org_id: Uuid,
+    expr: &Expr,
+    device_id: Uuid,
+    hours: i64,
+) -> ApiResult<Vec<MetricPoint>> {
+    let to = hour_start(Utc::now()) + Duration::hours(1);
+    let from = to - Duration::hours(hours);
+    let samples = org_samples(pool, org_id, Some(device_id), from, to)
+        .await?
+        .remove(&device_id)
+        .unwrap_or_default();
+
+    let mut points = Vec::new();
+    let mut start = from;
+    while start < to {
+        let end = start + Duration::hours(1);
+        let window: Vec<Sample> =
+            samples.iter().filter(|s| s.recorded_at >= start && s.recorded_at < end).cloned().collect();
+        if let Some(value) = evaluate_window(expr, &window) {
+            points.push(MetricPoint { bucket_start: start, value, samples: window.len() as i32 });
+        }
+        start = end;
+    }
+    Ok(points)
+}
+
+/// Roll up the completed hours an org's metrics have not covered yet. Metrics are locked
+/// for the duration so concurrent workers skip the org instead of computing it twice.
+async fn compute_org(pool: &PgPool, org_id: Uuid, through: DateTime<Utc>) -> ApiResult<usize> {
+    let mut tx = pool.begin().await?;
+    let metrics = sqlx::query_as::<_, OrgMetric>(&format!(
+        "SELECT {} FROM org_metrics WHERE org_id = $1 AND (computed_through IS NULL OR computed_through < $2) \
+         FOR UPDATE SKIP LOCKED",
+        METRIC_COLUMNS
+    ))
+    .bind(org_id)
+    .bind(through)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let backfill_from = through - Duration::hours(BACKFILL_HOURS);
+    let mut parsed: Vec<(OrgMetric, Expr, DateTime<Utc>)> = Vec::new();
+    for metric in metrics {
+        match validate_formula(&metric.formula) {
+            Ok(expr) => {
+                let from = metric.computed_through.unwrap_or(backfill_from);
+                parsed.push((metric, expr, from));
+            }
+            Err(e) => tracing::warn!("Skipping metric {}: {}", metric.id, e),
+        }
+    }
+    let Some(earliest) = parsed.iter().map(|(_, _, from)| *from).min() else {
+        return Ok(0);
+    };
+
+    // Owners who have metric automations get an event per computed value
+    let watchers: HashMap<Uuid, Uuid> = sqlx::query_as::<_, (Uuid, Uuid)>(&format!(
+        "SELECT d.id, d.user_id FROM devices d WHERE {} AND EXISTS ( \
+             SELECT 1 FROM automations a WHERE a.user_id = d.user_id AND a.enabled AND a.trigger_kind = 'metric')",
+        ORG_DEVICE_FILTER
+    ))
+    .bind(org_id)
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .collect();
+
+    let mut stored = 0;
+    let mut start = earliest;
+    let mut buckets = 0;
+    while start < through && buckets < MAX_BUCKETS_PER_RUN {
+        let end = start + Duration::hours(1);
+        let samples = org_samples(pool, org_id, None, start, end).await?;
+        for (metric, expr, from) in &parsed {
+            if start < *from {
+                continue;
+            }
+            for (device_id, window) in &samples {
+                let Some(value) = evaluate_window(expr, window) else {
+                    continue;
+                };
+                sqlx::query(
+                    "INSERT INTO metric_values (metric_id, device_id, bucket_start, value, samples) \
+                     VALUES ($1, $2, $3, $4, $5) \
+                     ON CONFLICT (metric_id, device_id, bucket_start) \
+                     DO UPDATE SET value = EXCLUDED.value, samples = EXCLUDED.samples",
+                )
+                .bind(metric.id)
+                .bind(device_id)
+                .bind(start)
+                .bind(value)
+                .bind(window.len() as i32)
+                .execute(&mut *tx)
+                .await?;
+                stored += 1;
+
+                if let Some(owner) = watchers.get(device_id) {
+                    sqlx::query("INSERT INTO automation_events (user_id, kind, payload) VALUES ($1, 'metric', $2)")
+                        .bind(owner)
+                        .bind(serde_json::json!({
+                            "metric_id": metric.id,
+                            "metric": metric.name,
+                            "org_id": org_id,
+                            "device_id": device_id,
+                            "value": value,
+                            "unit": metric.unit,
+                            "samples": window.len(),
+                            "bucket_start": start,
+                        }))
+                        .execute(&mut *tx)
+                        .await?;
+                }
+            }
+        }
+        start = end;
+        buckets += 1;
+    }
+
+    for (metric, _, from) in &parsed {
+        sqlx::query("UPDATE org_metrics SET computed_through = $2 WHERE id = $1")
+            .bind(metric.id)
+            .bind(start.max(*from))
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(stored)
+}
+
+/// Compute every org with metrics behind the last completed hour
+pub async fn compute_due(pool: &PgPool) -> ApiResult<usize> {
+    let through = hour_start(Utc::now() - Duration::minutes(LATE_ARRIVAL_MINUTES));
+    let orgs: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT DISTINCT org_id FROM org_metrics WHERE computed_through IS NULL OR computed_through < $1",
+    )
+    .bind(through)
+    .fetch_all(pool)
+    .await?;
+
+    let mut stored = 0;
+    for org_id in orgs {
+        match compute_org(pool, org_id, through).await {
+            Ok(n) => stored += n,
+            Err(e) => tracing::error!("Metric aggregation for org {} failed: {}", org_id, e),
+        }
+    }
+    Ok(stored)
+}
+
+/// Start the background job rolling telemetry up into metric values
+pub fn spawn_metric_job(pool: Arc<PgPool>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(JOB_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = compute_due(&pool).await {
+                tracing::error!("Metric job failed: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(minute: i64, lat: f64, battery: i64) -> Sample {
+        Sample {
+            device_id: Uuid::nil(),
+            recorded_at: DateTime::<Utc>::from_timestamp(1_700_000_000, 0).unwrap() + Duration::minutes(minute),
+            latitude: lat,
+            longitude: 0.0,
+            payload: serde_json::json!({ "battery_level": battery, "velocity": { "x": minute as f64 } }),
+        }
+    }
+
+    #[test]
+    fn test_validate_formula() {
+        assert!(validate_formula("distance() / -delta(battery_level)").is_ok());
+        assert!(validate_formula("avg(velocity.x) * 3.6 + count()").is_ok());
+        assert!(validate_formula("battery_level").is_ok());
+        assert!(validate_formula("median(battery_level)").is_err());
+        assert!(validate_formula("delta()").is_err());
+        assert!(validate_formula("count(battery_level)").is_err());
+        assert!(validate_formula("1 +").is_err());
+    }
+
+    #[test]
+    fn test_power_efficiency() {
+        // 0.01 degrees of latitude is about 1112 m; battery drops 90 -> 80
+        let samples = vec![sample(0, 0.0, 90), sample(30, 0.005, 85), sample(59, 0.01, 80)];
+        let expr = validate_formula("distance() / -delta(battery_level)").unwrap();
+        let value = evaluate_window(&expr, &samples).unwrap();
+        assert!((value - 111.2).abs() < 0.5, "got {}", value);
+
+        let expr = validate_formula("battery_level").unwrap();
+        assert_eq!(evaluate_window(&expr, &samples), Some(85.0));
+        let expr = validate_formula("duration() / 60 + max(velocity.x) - count()").unwrap();
+        assert_eq!(evaluate_window(&expr, &samples), Some(59.0 + 59.0 - 3.0));
+    }
+
+    #[test]
+    fn test_undefined_windows() {
+        let expr = validate_formula("distance() / -delta(battery_level)").unwrap();
+        assert_eq!(evaluate_window(&expr, &[]), None);
+        // No battery drop: division by zero has no value rather than infinity
+        assert_eq!(evaluate_window(&expr, &[sample(0, 0.0, 90), sample(5, 0.01, 90)]), None);
+        let expr = validate_formula("avg(missing.field)").unwrap();
+        assert_eq!(evaluate_window(&expr, &[sample(0, 0.0, 90)]), None);
+    }
+}
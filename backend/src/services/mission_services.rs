@@ -0,0 +1,618 @@
+//! Multi-leg fleet missions: each leg runs on its own device, and the next leg is only
+//! dispatched once readiness checks confirm the handoff can happen (previous leg done,
+//! payload at the rendezvous, next device online with enough charge).
+
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::device::PathPoint;
+use crate::models::mission::{CreateLegRequest, FleetMission, LegAction, MissionLeg, Waypoint};
+use crate::services::attachment_services::{require_attachment, ATTACHED_TYPES_SQL};
+use crate::services::device_services::owned_device_scope;
+use crate::services::path_services::{bearing_deg, distance_m, path_to_commands};
+use crate::services::robotics_services::{RoboticsService, BATTERY_RESERVE_LEVEL};
+use crate::utils::geo::{haversine_distance_m, is_valid_coordinate};
+
+pub const MAX_MISSION_LEGS: usize = 10;
+
+/// Commands allowed in one leg's pickup or destination action list
+pub const MAX_LEG_ACTIONS: usize = 20;
+
+/// How close the delivering device must be to the rendezvous for a handoff (meters)
+pub const HANDOFF_RADIUS_M: f64 = 10.0;
+
+/// Drone cruise altitude when a waypoint doesn't give one (meters)
+const DEFAULT_CRUISE_ALTITUDE_M: f64 = 30.0;
+
+pub const LEG_COLUMNS: &str = "id, mission_id, leg_index, device_id, target, pickup_actions, actions, speed, \
+     status, readiness, command_count, dispatched_at, completed_at";
+
+/// Command name and parameters, in dispatch order
+pub type CommandPlan = Vec<(String, serde_json::Value)>;
+
+pub const MISSION_COLUMNS: &str = "id, user_id, name, status, current_leg, created_at, updated_at, completed_at";
+
+/// Outcome of one readiness check before a leg is dispatched
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ReadinessCheck {
+    pub check: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// What the readiness checks look at
+#[derive(Debug, Clone, Default)]
+pub struct HandoffState {
+    /// Status of the previous leg; `None` for the first leg
+    pub previous_leg_status: Option<String>,
+    /// Last reported position of the previous leg's device
+    pub previous_device_position: Option<(f64, f64)>,
+    /// Where the previous leg was to leave the payload
+    pub rendezvous: Option<Waypoint>,
+    pub device_status: String,
+    pub device_position: Option<(f64, f64)>,
+    pub battery_level: Option<f64>,
+    /// Estimated drain of the leg's commands (percent)
+    pub required_drain: f64,
+}
+
+pub fn validate_legs(legs: &[CreateLegRequest]) -> ApiResult<()> {
+    if legs.len() < 2 || legs.len() > MAX_MISSION_LEGS {
+        return Err(ApiError::ValidationError(format!(
+            "A multi-leg mission needs 2-{} legs",
+            MAX_MISSION_LEGS
+        )));
+    }
+    for (i, leg) in legs.iter().enumerate() {
+        if !is_valid_coordinate(leg.target.latitude, leg.target.longitude) {
+            return Err(ApiError::ValidationError(format!("Leg {} target is out of range", i)));
+        }
+        if leg.target.altitude.is_some_and(|a| !(2.0..=120.0).contains(&a)) {
+            return Err(ApiError::ValidationError(format!("Leg {} altitude must be between 2 and 120 m", i)));
+        }
+        if leg.speed.is_some_and(|s| !(0.05..=1.0).contains(&s)) {
+            return Err(ApiError::ValidationError(format!("Leg {} speed must be between 0.05 and 1.0", i)));
+        }
+        if i == 0 && !leg.pickup_actions.is_empty() {
+            return Err(ApiError::ValidationError("The first leg has nothing to pick up".to_string()));
+        }
+        if leg.pickup_actions.len() > MAX_LEG_ACTIONS || leg.actions.len() > MAX_LEG_ACTIONS {
+            return Err(ApiError::ValidationError(format!(
+                "Leg {} may have at most {} actions per stop",
+                i, MAX_LEG_ACTIONS
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn point(latitude: f64, longitude: f64, altitude: Option<f64>) -> PathPoint {
+    PathPoint { latitude, longitude, altitude, recorded_at: Utc::now() }
+}
+
+/// Commands to travel `from` -> `to`, turning from `heading` first when it is known.
+/// Also returns the heading on arrival.
+fn travel(
+    device_type: &str,
+    from: (f64, f64),
+    to: &Waypoint,
+    heading: Option<f64>,
+    speed: f64,
+) -> ApiResult<(CommandPlan, Option<f64>)> {
+    let altitude = (device_type == "drone").then(|| to.altitude.unwrap_or(DEFAULT_CRUISE_ALTITUDE_M));
+    let route = [point(from.0, from.1, altitude), point(to.latitude, to.longitude, altitude)];
+    let commands = path_to_commands(device_type, &route, speed, heading)?
+        .into_iter()
+        .map(|(command, params)| (command.to_string(), params))
+        .collect();
+    let arrived = if distance_m(&route[0], &route[1]) < f64::EPSILON {
+        heading
+    } else {
+        Some(bearing_deg(&route[0], &route[1]))
+    };
+    Ok((commands, arrived))
+}
+
+fn with_actions(actions: &[LegAction]) -> impl Iterator<Item = (String, serde_json::Value)> + '_ {
+    actions.iter().map(|a| {
+        let params = if a.parameters.is_null() { serde_json::json!({}) } else { a.parameters.clone() };
+        (a.command.clone(), params)
+    })
+}
+
+/// The command sequence for a leg: travel to the rendezvous and run the pickup actions
+/// (legs after the first), then travel to the target and run the destination actions
+pub fn leg_commands(
+    device_type: &str,
+    position: (f64, f64),
+    rendezvous: Option<&Waypoint>,
+    target: &Waypoint,
+    pickup_actions: &[LegAction],
+    actions: &[LegAction],
+    speed: f64,
+) -> ApiResult<CommandPlan> {
+    let mut commands = Vec::new();
+    let (mut from, mut heading) = (position, None);
+    if let Some(rendezvous) = rendezvous {
+        let (travel_commands, arrived) = travel(device_type, from, rendezvous, heading, speed)?;
+        commands.extend(travel_commands);
+        commands.extend(with_actions(pickup_actions));
+        from = (rendezvous.latitude, rendezvous.longitude);
+        heading = arrived;
+    }
+    commands.extend(travel(device_type, from, target, heading, speed)?.0);
+    commands.extend(with_actions(actions));
+    Ok(commands)
+}
+
+/// Everything a planned mission has one device do, leg by leg. The device starts its first
+/// leg from `position` and each later one from where its previous leg ended.
+pub fn device_plan(
+    device_type: &str,
+    device_id: Uuid,
+    position: (f64, f64),
+    legs: &[CreateLegRequest],
+) -> ApiResult<CommandPlan> {
+    let mut plan = Vec::new();
+    let mut from = position;
+    for (i, leg) in legs.iter().enumerate().filter(|(_, l)| l.device_id == device_id) {
+        let rendezvous = i.checked_sub(1).map(|p| &legs[p].target);
+        plan.extend(leg_commands(
+            device_type,
+            from,
+            rendezvous,
+            &leg.target,
+            &leg.pickup_actions,
+            &leg.actions,
+            leg.speed.unwrap_or(0.5),
+        )?);
+        from = (leg.target.latitude, leg.target.longitude);
+    }
+    Ok(plan)
+}
+
+pub fn check_readiness(state: &HandoffState) -> Vec<ReadinessCheck> {
+    let mut checks = Vec::new();
+
+    if let Some(status) = &state.previous_leg_status {
+        checks.push(ReadinessCheck {
+            check: "previous_leg_completed",
+            passed: status == "completed",
+            detail: format!("Previous leg is {}", status),
+        });
+        let handoff = match (state.previous_device_position, state.rendezvous) {
+            (Some((lat, lng)), Some(at)) => {
+                let distance = haversine_distance_m(lat, lng, at.latitude, at.longitude);
+                ReadinessCheck {
+                    check: "payload_at_rendezvous",
+                    passed: distance <= HANDOFF_RADIUS_M,
+                    detail: format!("Delivering device is {:.1} m from the rendezvous", distance),
+                }
+            }
+            _ => ReadinessCheck {
+                check: "payload_at_rendezvous",
+                passed: false,
+                detail: "Delivering device has not reported its position".to_string(),
+            },
+        };
+        checks.push(handoff);
+    }
+
+    checks.push(ReadinessCheck {
+        check: "device_online",
+        passed: state.device_status == "online",
+        detail: format!("Device is {}", state.device_status),
+    });
+    checks.push(ReadinessCheck {
+        check: "device_position_known",
+        passed: state.device_position.is_some(),
+        detail: if state.device_position.is_some() {
+            "Position reported".to_string()
+        } else {
+            "Device has not reported its position".to_string()
+        },
+    });
+    checks.push(match state.battery_level {
+        Some(level) => {
+            let needed = state.required_drain + BATTERY_RESERVE_LEVEL;
+            ReadinessCheck {
+                check: "battery",
+                passed: level >= needed,
+                detail: format!("Battery {:.0}%, leg needs {:.1}% plus {:.0}% reserve", level, state.required_drain, BATTERY_RESERVE_LEVEL),
+            }
+        }
+        None => ReadinessCheck {
+            check: "battery",
+            passed: false,
+            detail: "No battery telemetry".to_string(),
+        },
+    });
+
+    checks
+}
+
+/// Combined progress (0-1): finished legs count fully, the running leg by its acked commands
+pub fn mission_progress(legs: &[(String, i64, i64)]) -> f64 {
+    if legs.is_empty() {
+        return 0.0;
+    }
+    let done: f64 = legs
+        .iter()
+        .map(|(status, total, acked)| match status.as_str() {
+            "completed" => 1.0,
+            "dispatched" if *total > 0 => *acked as f64 / *total as f64,
+            _ => 0.0,
+        })
+        .sum();
+    done / legs.len() as f64
+}
+
+/// Every leg device must belong to the user, and consecutive legs must hand off between devices
+pub async fn check_leg_devices(pool: &PgPool, user_id: Uuid, legs: &[CreateLegRequest]) -> ApiResult<()> {
+    let device_ids: Vec<Uuid> = legs.iter().map(|l| l.device_id).collect();
+    owned_device_scope(pool, user_id, Some(&device_ids)).await?;
+    if legs.windows(2).any(|w| w[0].device_id == w[1].device_id) {
+        return Err(ApiError::ValidationError(
+            "Consecutive legs must hand off to a different device".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Record a mission and its legs; nothing is dispatched until [`try_advance`]
+pub async fn insert_mission(
+    pool: &PgPool,
+    user_id: Uuid,
+    name: &str,
+    legs: &[CreateLegRequest],
+) -> ApiResult<FleetMission> {
+    let mut tx = pool.begin().await?;
+    let mission = sqlx::query_as::<_, FleetMission>(&format!(
+        "INSERT INTO fleet_missions (user_id, name) VALUES ($1, $2) RETURNING {}",
+        MISSION_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(name)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    for (index, leg) in legs.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO mission_legs (mission_id, leg_index, device_id, target, pickup_actions, actions, speed) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(mission.id)
+        .bind(index as i32)
+        .bind(leg.device_id)
+        .bind(sqlx::types::Json(leg.target))
+        .bind(sqlx::types::Json(&leg.pickup_actions))
+        .bind(sqlx::types::Json(&leg.actions))
+        .bind(leg.speed.unwrap_or(0.5) as f32)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(mission)
+}
+
+#[derive(FromRow)]
+struct LegDeviceRow {
+    device_type: String,
+    firmware_version: String,
+    status: String,
+    last_latitude: Option<f64>,
+    last_longitude: Option<f64>,
+    battery_level: Option<i16>,
+    attachments: Vec<String>,
+}
+
+async fn leg_device(conn: &mut PgConnection, device_id: Uuid) -> ApiResult<LegDeviceRow> {
+    let row = sqlx::query_as::<_, LegDeviceRow>(&format!(
+        "SELECT d.device_type, d.firmware_version, d.status, d.last_latitude, d.last_longitude, \
+                (SELECT battery_level FROM device_telemetry WHERE device_id = d.id \
+                 ORDER BY recorded_at DESC LIMIT 1) AS battery_level, \
+                {} AS attachments \
+         FROM devices d WHERE d.id = $1",
+        ATTACHED_TYPES_SQL
+    ))
+    .bind(device_id)
+    .fetch_optional(conn)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Mission device no longer exists".to_string()))?;
+    Ok(row)
+}
+
+/// Run readiness checks for the mission's current leg and dispatch it if they all pass.
+/// Returns the checks; a failing report leaves the mission `awaiting_handoff`.
+pub async fn try_advance(pool: &PgPool, mission_id: Uuid, user_id: Uuid) -> ApiResult<Vec<ReadinessCheck>> {
+    let mut tx = pool.begin().await?;
+
+    let (status, current_leg): (String, i32) =
+        sqlx::query_as("SELECT status, current_leg FROM fleet_missions WHERE id = $1 AND user_id = $2 FOR UPDATE")
+            .bind(mission_id)
+            .bind(user_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| ApiError::NotFound("Mission not found".to_string()))?;
+    if status != "awaiting_handoff" {
+        return Err(ApiError::Conflict(format!("Mission is {}", status)));
+    }
+
+    let legs = sqlx::query_as::<_, MissionLeg>(&format!(
+        "SELECT {} FROM mission_legs WHERE mission_id = $1 AND leg_index IN ($2 - 1, $2) ORDER BY leg_index",
+        LEG_COLUMNS
+    ))
+    .bind(mission_id)
+    .bind(current_leg)
+    .fetch_all(&mut *tx)
+    .await?;
+    let (previous, leg) = match legs.as_slice() {
+        [leg] => (None, leg),
+        [previous, leg] => (Some(previous), leg),
+        _ => return Err(ApiError::InternalError("Mission legs are inconsistent".to_string())),
+    };
+
+    let device = leg_device(&mut tx, leg.device_id).await?;
+    let mut state = HandoffState {
+        device_status: device.status.clone(),
+        device_position: device.last_latitude.zip(device.last_longitude),
+        battery_level: device.battery_level.map(f64::from),
+        ..Default::default()
+    };
+    if let Some(previous) = previous {
+        let delivering = leg_device(&mut tx, previous.device_id).await?;
+        state.previous_leg_status = Some(previous.status.clone());
+        state.previous_device_position = delivering.last_latitude.zip(delivering.last_longitude);
+        state.rendezvous = Some(*previous.target);
+    }
+
+    // Plan the commands up front: the battery check needs their estimated drain
+    let service = RoboticsService::new();
+    let mut planned = Vec::new();
+    if let Some(position) = state.device_position {
+        let commands = leg_commands(
+            &device.device_type,
+            position,
+            state.rendezvous.as_ref(),
+            &leg.target,
+            &leg.pickup_actions,
+            &leg.actions,
+            leg.speed as f64,
+        )?;
+        for (command, parameters) in commands {
+            service.validate_command(&device.device_type, &device.firmware_version, &command)?;
+            require_attachment(&device.device_type, &command, &device.attachments)?;
+            let params = service.parse_command_params(&command, &parameters)?;
+            let duration = service.estimate_duration_ms(&params);
+            let drain = service.estimate_battery_drain(&command, &params);
+            state.required_drain += drain as f64;
+            planned.push((command, parameters, duration, drain));
+        }
+    }
+
+    let checks = check_readiness(&state);
+    let ready = checks.iter().all(|c| c.passed);
+    let report = serde_json::json!({ "checked_at": Utc::now(), "ready": ready, "checks": checks });
+
+    if !ready {
+        sqlx::query("UPDATE mission_legs SET readiness = $2 WHERE id = $1")
+            .bind(leg.id)
+            .bind(&report)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        return Ok(checks);
+    }
+
+    for (sequence, (command, parameters, duration, drain)) in planned.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO device_commands \
+             (device_id, user_id, command, parameters, estimated_duration_ms, estimated_battery_drain, \
+              mission_leg_id, sequence) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(leg.device_id)
+        .bind(user_id)
+        .bind(command)
+        .bind(parameters)
+        .bind(*duration as i64)
+        .bind(*drain)
+        .bind(leg.id)
+        .bind(sequence as i32)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    sqlx::query(
+        "UPDATE mission_legs SET status = 'dispatched', readiness = $2, command_count = $3, dispatched_at = NOW() \
+         WHERE id = $1",
+    )
+    .bind(leg.id)
+    .bind(&report)
+    .bind(planned.len() as i32)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("UPDATE fleet_missions SET status = 'active', updated_at = NOW() WHERE id = $1")
+        .bind(mission_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(checks)
+}
+
+#[derive(FromRow)]
+struct LegAckRow {
+    mission_id: Uuid,
+    leg_index: i32,
+    user_id: Uuid,
+    total: i64,
+    succeeded: i64,
+    failed: i64,
+}
+
+/// Fold a command acknowledgement into its leg. A failed command fails the leg and the
+/// mission; once every command succeeded the leg completes and the next one is attempted.
+pub async fn record_leg_ack(pool: &PgPool, leg_id: Uuid) -> ApiResult<()> {
+    let mut tx = pool.begin().await?;
+
+    let row = sqlx::query_as::<_, LegAckRow>(
+        "SELECT l.mission_id, l.leg_index, m.user_id, \
+                COUNT(c.id) AS total, COUNT(c.id) FILTER (WHERE c.status = 'succeeded') AS succeeded, \
+                COUNT(c.id) FILTER (WHERE c.status = 'failed') AS failed \
+         FROM mission_legs l JOIN fleet_missions m ON m.id = l.mission_id \
+         LEFT JOIN device_commands c ON c.mission_leg_id = l.id \
+         WHERE l.id = $1 AND l.status = 'dispatched' \
+         GROUP BY l.mission_id, l.leg_index, m.user_id",
+    )
+    .bind(leg_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    // Acks arriving after a cancel or failure no longer move the mission
+    let Some(LegAckRow { mission_id, leg_index, user_id, total, succeeded, failed }) = row else {
+        return Ok(());
+    };
+
+    if failed > 0 {
+        sqlx::query("UPDATE mission_legs SET status = 'failed', completed_at = NOW() WHERE id = $1")
+            .bind(leg_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE fleet_missions SET status = 'failed', updated_at = NOW() WHERE id = $1")
+            .bind(mission_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        return Ok(());
+    }
+    if succeeded < total {
+        return Ok(());
+    }
+
+    sqlx::query("UPDATE mission_legs SET status = 'completed', completed_at = NOW() WHERE id = $1")
+        .bind(leg_id)
+        .execute(&mut *tx)
+        .await?;
+    let has_next: bool =
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM mission_legs WHERE mission_id = $1 AND leg_index = $2)")
+            .bind(mission_id)
+            .bind(leg_index + 1)
+            .fetch_one(&mut *tx)
+            .await?;
+    sqlx::query(
+        "UPDATE fleet_missions SET status = $2, current_leg = $3, updated_at = NOW(), \
+         completed_at = CASE WHEN $2 = 'completed' THEN NOW() END WHERE id = $1",
+    )
+    .bind(mission_id)
+    .bind(if has_next { "awaiting_handoff" } else { "completed" })
+    .bind(if has_next { leg_index + 1 } else { leg_index })
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    if has_next {
+        try_advance(pool, mission_id, user_id).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waypoint(latitude: f64, longitude: f64) -> Waypoint {
+        Waypoint { latitude, longitude, altitude: None }
+    }
+
+    fn action(command: &str) -> LegAction {
+        LegAction { command: command.to_string(), parameters: serde_json::Value::Null }
+    }
+
+    fn ready_state() -> HandoffState {
+        HandoffState {
+            previous_leg_status: Some("completed".to_string()),
+            previous_device_position: Some((12.00001, 77.0)),
+            rendezvous: Some(waypoint(12.0, 77.0)),
+            device_status: "online".to_string(),
+            device_position: Some((12.001, 77.001)),
+            battery_level: Some(80.0),
+            required_drain: 10.0,
+        }
+    }
+
+    #[test]
+    fn test_readiness_checks() {
+        assert!(check_readiness(&ready_state()).iter().all(|c| c.passed));
+
+        let failing = |state: HandoffState| -> Vec<&'static str> {
+            check_readiness(&state).into_iter().filter(|c| !c.passed).map(|c| c.check).collect()
+        };
+        assert_eq!(
+            failing(HandoffState { previous_device_position: Some((12.001, 77.0)), ..ready_state() }),
+            ["payload_at_rendezvous"]
+        );
+        assert_eq!(
+            failing(HandoffState { previous_leg_status: Some("dispatched".to_string()), ..ready_state() }),
+            ["previous_leg_completed"]
+        );
+        assert_eq!(failing(HandoffState { battery_level: Some(20.0), ..ready_state() }), ["battery"]);
+        assert_eq!(
+            failing(HandoffState { device_status: "offline".to_string(), device_position: None, ..ready_state() }),
+            ["device_online", "device_position_known"]
+        );
+
+        // The first leg has no handoff to check
+        let first = HandoffState { previous_leg_status: None, rendezvous: None, ..ready_state() };
+        assert!(check_readiness(&first).iter().all(|c| c.check != "payload_at_rendezvous"));
+    }
+
+    #[test]
+    fn test_leg_commands_visit_rendezvous_first() {
+        let commands = leg_commands(
+            "robot",
+            (0.0, 0.0),
+            Some(&waypoint(0.001, 0.0)),
+            &waypoint(0.001, 0.001),
+            &[action("grab")],
+            &[action("release")],
+            0.5,
+        )
+        .unwrap();
+        let names: Vec<&str> = commands.iter().map(|(c, _)| c.as_str()).collect();
+        assert_eq!(names, ["move_forward", "grab", "turn_right", "move_forward", "release"]);
+        assert_eq!(commands[1].1, serde_json::json!({}));
+    }
+
+    #[test]
+    fn test_device_plan_covers_only_its_legs() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let leg = |device_id, latitude, pickup: &[&str]| CreateLegRequest {
+            device_id,
+            target: waypoint(latitude, 0.0),
+            pickup_actions: pickup.iter().map(|c| action(c)).collect(),
+            actions: vec![action("release")],
+            speed: None,
+        };
+        let legs = [leg(a, 0.001, &[]), leg(b, 0.002, &["grab"]), leg(a, 0.003, &["grab"])];
+
+        let plan = device_plan("robot", a, (0.0, 0.0), &legs).unwrap();
+        let names: Vec<&str> = plan.iter().map(|(c, _)| c.as_str()).collect();
+        // Its second leg starts where the first ended and picks up at b's target
+        assert_eq!(names, ["move_forward", "release", "move_forward", "grab", "move_forward", "release"]);
+        assert!(device_plan("robot", Uuid::new_v4(), (0.0, 0.0), &legs).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_mission_progress() {
+        let legs = vec![
+            ("completed".to_string(), 4, 4),
+            ("dispatched".to_string(), 4, 2),
+            ("pending".to_string(), 0, 0),
+            ("pending".to_string(), 0, 0),
+        ];
+        assert!((mission_progress(&legs) - 0.375).abs() < 1e-9);
+        assert_eq!(mission_progress(&[]), 0.0);
+    }
+}
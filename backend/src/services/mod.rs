@@ -1,3 +1,93 @@
 pub mod ai_services;
 pub mod crypto_services;
 pub mod robotics_services;
+pub mod sso_services;
+pub mod org_services;
+pub mod scim_services;
+pub mod device_services;
+pub mod policy_services;
+pub mod audit_services;
+pub mod notification_services;
+pub mod break_glass_services;
+pub mod mail_services;
+pub mod security_services;
+pub mod provisioning_services;
+pub mod secret_scan_services;
+pub mod stream_services;
+pub mod key_services;
+pub mod path_services;
+pub mod swarm_services;
+pub mod firmware_services;
+pub mod compliance_services;
+pub mod mission_services;
+pub mod sensor_services;
+pub mod attachment_services;
+pub mod metadata_services;
+pub mod transport_services;
+pub mod promotion_services;
+pub mod processor_services;
+pub mod webhook_services;
+pub mod uptime_services;
+pub mod command_services;
+pub mod automation_services;
+pub mod template_services;
+pub mod metric_services;
+pub mod retention_services;
+pub mod capacity_services;
+pub mod energy_services;
+pub mod deprecation_services;
+pub mod incident_services;
+pub mod inbound_email_services;
+pub mod calendar_services;
+pub mod google_calendar_services;
+pub mod siwe_services;
+pub mod payment_services;
+pub mod stripe_services;
+pub mod integration_services;
+pub mod razorpay_services;
+pub mod fleet_config_services;
+pub mod object_storage_services;
+pub mod backup_services;
+pub mod refund_services;
+pub mod constraint_services;
+pub mod subscription_services;
+pub mod open_data_services;
+pub mod ai_routing_services;
+pub mod public_stats_services;
+pub mod chain_watch_services;
+pub mod region_services;
+pub mod capability_services;
+pub mod wallet_services;
+pub mod gas_services;
+pub mod support_services;
+pub mod relayer_services;
+pub mod nft_services;
+pub mod transfer_services;
+pub mod transaction_export_services;
+pub mod invoice_services;
+pub mod rate_services;
+pub mod escrow_services;
+pub mod receipt_services;
+pub mod ens_services;
+pub mod event_indexer_services;
+pub mod eip712_services;
+pub mod transaction_services;
+pub mod reconciliation_services;
+pub mod kyc_services;
+pub mod payout_services;
+pub mod spending_services;
+pub mod fraud_services;
+pub mod ledger_services;
+pub mod coupon_services;
+pub mod dispute_services;
+pub mod conversation_services;
+pub mod ai_provider_services;
+pub mod embedding_services;
+pub mod rag_services;
+pub mod moderation_services;
+pub mod vision_services;
+pub mod transcription_services;
+pub mod speech_services;
+pub mod insight_services;
+pub mod fine_tune_services;
+pub mod estimate_services;
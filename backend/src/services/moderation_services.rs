@@ -0,0 +1,98 @@
+//! Content moderation of AI chat, configured per deployment. With `AI_MODERATE_INPUTS` the
+//! user's messages and system prompts are checked by the moderation provider before they reach
+//! a model; with `AI_MODERATE_OUTPUTS` the model's answer is checked before it is returned.
+//! Flagged content fails the request, or with `AI_MODERATION_ACTION=redact` is replaced by a
+//! placeholder. A moderation provider that cannot be reached fails the request rather than
+//! letting content through unchecked.
+
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::services::ai_services::{AIService, ChatMessage, Moderation};
+
+/// What redacted content is replaced by
+pub const REDACTED: &str = "[removed by content moderation]";
+
+/// The texts to redact, by position, or the error rejecting them. `labels` name the texts in
+/// the error.
+pub fn verdict(labels: &[String], verdicts: &[Moderation], redact: bool) -> ApiResult<Vec<usize>> {
+    let flagged: Vec<usize> = (0..verdicts.len()).filter(|i| verdicts[*i].flagged).collect();
+    if flagged.is_empty() || redact {
+        return Ok(flagged);
+    }
+    let described: Vec<String> = flagged
+        .iter()
+        .map(|i| match verdicts[*i].categories.as_slice() {
+            [] => labels[*i].clone(),
+            categories => format!("{} ({})", labels[*i], categories.join(", ")),
+        })
+        .collect();
+    Err(ApiError::ValidationError(format!("Content flagged by moderation: {}", described.join("; "))))
+}
+
+/// Moderate `texts` in place, each named by its label
+async fn screen(config: &AppConfig, ai: &AIService, texts: Vec<(String, &mut String)>) -> ApiResult<()> {
+    if texts.is_empty() {
+        return Ok(());
+    }
+    let (labels, mut texts): (Vec<String>, Vec<&mut String>) = texts.into_iter().unzip();
+    let verdicts = {
+        let borrowed: Vec<&str> = texts.iter().map(|t| t.as_str()).collect();
+        ai.moderate(&config.ai_moderation_provider, &borrowed).await?
+    };
+    for i in verdict(&labels, &verdicts, config.ai_moderation_redact)? {
+        tracing::warn!(text = %labels[i], categories = ?verdicts[i].categories, "Redacted flagged AI content");
+        *texts[i] = REDACTED.to_string();
+    }
+    Ok(())
+}
+
+/// Moderate the user-written messages of a chat request: everything but earlier answers
+pub async fn screen_input(config: &AppConfig, ai: &AIService, messages: &mut [ChatMessage]) -> ApiResult<()> {
+    if !config.ai_moderate_inputs {
+        return Ok(());
+    }
+    let texts = messages
+        .iter_mut()
+        .enumerate()
+        .filter(|(_, m)| m.role != "assistant")
+        .map(|(i, m)| (format!("messages[{}]", i), &mut m.content))
+        .collect();
+    screen(config, ai, texts).await
+}
+
+/// Moderate a prompt sent outside a chat, named by its request field
+pub async fn screen_prompt(config: &AppConfig, ai: &AIService, field: &str, prompt: &mut String) -> ApiResult<()> {
+    if !config.ai_moderate_inputs {
+        return Ok(());
+    }
+    screen(config, ai, vec![(field.to_string(), prompt)]).await
+}
+
+/// Moderate a model's answer
+pub async fn screen_output(config: &AppConfig, ai: &AIService, answer: &mut String) -> ApiResult<()> {
+    if !config.ai_moderate_outputs {
+        return Ok(());
+    }
+    screen(config, ai, vec![("answer".to_string(), answer)]).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verdict() {
+        let labels = vec!["messages[0]".to_string(), "messages[2]".to_string()];
+        let clean = Moderation::default();
+        let flagged = Moderation { flagged: true, categories: vec!["harassment".to_string(), "violence".to_string()] };
+
+        assert!(verdict(&labels, &[clean.clone(), clean.clone()], false).unwrap().is_empty());
+        assert_eq!(verdict(&labels, &[clean.clone(), flagged.clone()], true).unwrap(), [1]);
+        match verdict(&labels, &[clean, flagged], false) {
+            Err(ApiError::ValidationError(message)) => {
+                assert_eq!(message, "Content flagged by moderation: messages[2] (harassment, violence)")
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+}
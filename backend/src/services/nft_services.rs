@@ -0,0 +1,91 @@
+//! Device ownership certificates: one ERC-721 token per registered device, minted by the
+//! relayer to the owner's wallet. The token id is the device id read as an integer, so a
+//! device maps to the same token on every chain and no receipt has to be parsed to learn it.
+
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::device::Device;
+use crate::services::crypto_services::BlockchainService;
+use crate::services::relayer_services::{address_word, selector, uint_word};
+
+const SAFE_MINT_SIGNATURE: &str = "safeMint(address,uint256,string)";
+const OWNER_OF_SIGNATURE: &str = "ownerOf(uint256)";
+
+/// Token id of a device's certificate, in decimal
+pub fn token_id(device_id: Uuid) -> String {
+    device_id.as_u128().to_string()
+}
+
+/// Calldata of `safeMint(to, tokenId, uri)`
+pub fn mint_calldata(to: &str, device_id: Uuid, uri: &str) -> ApiResult<Vec<u8>> {
+    let mut data = selector(SAFE_MINT_SIGNATURE).to_vec();
+    data.extend_from_slice(&address_word(to)?);
+    data.extend_from_slice(&uint_word(device_id.as_u128()));
+    // The string lives after the three head words
+    data.extend_from_slice(&uint_word(3 * 32));
+    data.extend_from_slice(&uint_word(uri.len() as u128));
+    data.extend_from_slice(uri.as_bytes());
+    data.resize(data.len() + (32 - uri.len() % 32) % 32, 0);
+    Ok(data)
+}
+
+/// Current holder of a device's certificate, in lower-case hex
+pub async fn owner_of(chain: &BlockchainService, contract: &str, device_id: Uuid) -> ApiResult<String> {
+    let data = format!(
+        "0x{}{}",
+        hex::encode(selector(OWNER_OF_SIGNATURE)),
+        hex::encode(uint_word(device_id.as_u128()))
+    );
+    let result = chain.eth_call(contract, &data).await?;
+    if result.len() != 32 || result[..12].iter().any(|b| *b != 0) {
+        return Err(ApiError::BlockchainError("ownerOf returned malformed data".to_string()));
+    }
+    Ok(format!("0x{}", hex::encode(&result[12..])))
+}
+
+/// Public metadata URL of a device's certificate, which the contract stores as its token URI
+pub fn token_uri(api_base_url: &str, device_id: Uuid) -> String {
+    format!("{}/api/blockchain/nft/devices/{}", api_base_url.trim_end_matches('/'), device_id)
+}
+
+/// ERC-721 metadata of a device's certificate. It is public, so it carries only the device's
+/// identity, never its free-form metadata or location.
+pub fn metadata(device: &Device, frontend_url: &str) -> serde_json::Value {
+    serde_json::json!({
+        "name": format!("{} ownership certificate", device.device_name),
+        "description": format!(
+            "Certifies ownership of the {} \"{}\". Transferring this token is how the device changes hands.",
+            device.device_type, device.device_name
+        ),
+        "external_url": frontend_url,
+        "attributes": [
+            { "trait_type": "Device type", "value": device.device_type },
+            { "trait_type": "Firmware", "value": device.firmware_version },
+            { "trait_type": "Registered", "display_type": "date", "value": device.created_at.timestamp() },
+        ],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mint_calldata() {
+        let device_id = Uuid::from_u128(0x1234);
+        let uri = "https://api.example.com/api/blockchain/nft/devices/1";
+        let data = mint_calldata("0x00000000000000000000000000000000000000aa", device_id, uri).unwrap();
+
+        assert_eq!(&data[..4], selector(SAFE_MINT_SIGNATURE));
+        let word = |i: usize| &data[4 + 32 * i..4 + 32 * (i + 1)];
+        assert_eq!(word(0)[31], 0xaa);
+        assert_eq!(&word(1)[30..], [0x12, 0x34]);
+        assert_eq!(word(2)[31], 0x60);
+        assert_eq!(word(3)[31] as usize, uri.len());
+        assert_eq!(&data[4 + 128..4 + 128 + uri.len()], uri.as_bytes());
+        assert_eq!((data.len() - 4) % 32, 0);
+
+        assert_eq!(token_id(device_id), "4660");
+        assert!(mint_calldata("0x1234", device_id, uri).is_err());
+    }
+}
@@ -0,0 +1,53 @@
+//! In-app notifications delivered to users
+
+use sqlx::PgConnection;
+use uuid::Uuid;
+use crate::errors::ApiResult;
+
+/// Notify every active owner of an organization. Returns how many owners were notified.
+pub async fn notify_org_owners(
+    conn: &mut PgConnection,
+    org_id: Uuid,
+    kind: &str,
+    title: &str,
+    body: &str,
+    data: serde_json::Value,
+) -> ApiResult<u64> {
+    let result = sqlx::query(
+        "INSERT INTO notifications (user_id, kind, title, body, data) \
+         SELECT user_id, $2, $3, $4, $5 FROM org_memberships \
+         WHERE org_id = $1 AND role = 'owner' AND active",
+    )
+    .bind(org_id)
+    .bind(kind)
+    .bind(title)
+    .bind(body)
+    .bind(&data)
+    .execute(conn)
+    .await?;
+
+    tracing::warn!(%org_id, kind, notified = result.rows_affected(), "{}", title);
+
+    Ok(result.rows_affected())
+}
+
+/// Notify a single user
+pub async fn notify_user(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    kind: &str,
+    title: &str,
+    body: &str,
+    data: serde_json::Value,
+) -> ApiResult<()> {
+    sqlx::query("INSERT INTO notifications (user_id, kind, title, body, data) VALUES ($1, $2, $3, $4, $5)")
+        .bind(user_id)
+        .bind(kind)
+        .bind(title)
+        .bind(body)
+        .bind(&data)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,667 @@
+//! Open data: opted-in users publish anonymized telemetry datasets to a public catalog.
+//!
+//! A dataset is a CSV snapshot of the publisher's telemetry over a period, built in the
+//! background and uploaded to object storage. Devices appear under pseudonyms that differ
+//! between datasets, coordinates are rounded to the chosen precision, timestamps to the
+//! minute, and only numeric readings known to carry no personal data are kept.
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::open_data::{
+    CatalogDataset, CatalogEntry, CatalogPage, CatalogQuery, License, OpenDataset, PublishDatasetRequest,
+};
+use crate::services::audit_services::{self, to_csv, AuditEntry};
+use crate::services::notification_services::notify_user;
+use crate::services::object_storage_services::ObjectStore;
+use crate::services::robotics_services::DEVICE_TYPES;
+use crate::utils::privacy::{PrivacyNotice, PrivacyParams, Release, ReleaseCache};
+use crate::utils::redaction::round_coordinate;
+use crate::utils::sha256_hash;
+
+pub const DATASET_COLUMNS: &str = "id, user_id, slug, title, description, license, device_ids, device_types, \
+     period_start, period_end, location_decimals, status, row_count, size_bytes, sha256, failure_reason, \
+     published_at, withdrawn_at, created_at, updated_at";
+
+const CATALOG_COLUMNS: &str = "slug, title, description, license, device_types, \
+     cardinality(device_ids) AS device_count, period_start, period_end, location_decimals, row_count, size_bytes, \
+     sha256, published_at";
+
+/// Licenses a dataset may be published under
+pub const LICENSES: &[License] = &[
+    License {
+        id: "CC0-1.0",
+        name: "Creative Commons Zero v1.0 Universal",
+        url: "https://creativecommons.org/publicdomain/zero/1.0/",
+    },
+    License {
+        id: "CC-BY-4.0",
+        name: "Creative Commons Attribution 4.0 International",
+        url: "https://creativecommons.org/licenses/by/4.0/",
+    },
+    License {
+        id: "CC-BY-SA-4.0",
+        name: "Creative Commons Attribution Share Alike 4.0 International",
+        url: "https://creativecommons.org/licenses/by-sa/4.0/",
+    },
+    License {
+        id: "CC-BY-NC-4.0",
+        name: "Creative Commons Attribution Non Commercial 4.0 International",
+        url: "https://creativecommons.org/licenses/by-nc/4.0/",
+    },
+    License {
+        id: "ODbL-1.0",
+        name: "Open Data Commons Open Database License v1.0",
+        url: "https://opendatacommons.org/licenses/odbl/1-0/",
+    },
+];
+
+pub const CSV_HEADERS: &[&str] = &[
+    "device",
+    "device_type",
+    "recorded_at",
+    "battery_level",
+    "latitude",
+    "longitude",
+    "altitude_m",
+    "cpu_temp",
+    "signal_strength",
+];
+
+/// Datasets larger than this are refused; publish a shorter period instead
+pub const MAX_DATASET_ROWS: i64 = 500_000;
+pub const MAX_PERIOD_DAYS: i64 = 366;
+/// ~1km, the precision application logs are kept at
+pub const DEFAULT_LOCATION_DECIMALS: i16 = 2;
+/// ~10m; anything finer would locate individual homes
+pub const MAX_LOCATION_DECIMALS: i16 = 4;
+/// How long a catalog download link stays valid
+pub const DOWNLOAD_URL_TTL_SECS: u32 = 15 * 60;
+/// A build still running after this long was lost to a restart
+const BUILD_TIMEOUT_MINUTES: i32 = 60;
+
+pub fn license(id: &str) -> Option<License> {
+    LICENSES.iter().find(|l| l.id == id).copied()
+}
+
+/// Check a publish request, returning the location precision to use
+pub fn validate_publish(request: &PublishDatasetRequest) -> ApiResult<i16> {
+    let invalid = |msg: &str| Err(ApiError::ValidationError(msg.to_string()));
+    let title = request.title.trim();
+    if title.is_empty() || title.len() > 200 {
+        return invalid("title must be 1-200 characters");
+    }
+    if request.description.len() > 5000 {
+        return invalid("description must be at most 5000 characters");
+    }
+    if license(&request.license).is_none() {
+        return Err(ApiError::ValidationError(format!(
+            "Unknown license '{}'; expected one of {}",
+            request.license,
+            LICENSES.iter().map(|l| l.id).collect::<Vec<_>>().join(", ")
+        )));
+    }
+    if request.from >= request.to {
+        return invalid("from must be before to");
+    }
+    if request.to > Utc::now() {
+        return invalid("to must not be in the future");
+    }
+    if request.to - request.from > Duration::days(MAX_PERIOD_DAYS) {
+        return Err(ApiError::ValidationError(format!(
+            "A dataset can cover at most {} days",
+            MAX_PERIOD_DAYS
+        )));
+    }
+    if request.device_ids.as_ref().is_some_and(Vec::is_empty) {
+        return invalid("device_ids must not be empty; omit it to include every device");
+    }
+    let decimals = request.location_decimals.unwrap_or(DEFAULT_LOCATION_DECIMALS);
+    if !(0..=MAX_LOCATION_DECIMALS).contains(&decimals) {
+        return Err(ApiError::ValidationError(format!(
+            "location_decimals must be between 0 and {}",
+            MAX_LOCATION_DECIMALS
+        )));
+    }
+    Ok(decimals)
+}
+
+/// URL-safe name for a dataset: its title in kebab case, made unique by the dataset id
+pub fn slugify(title: &str, dataset_id: Uuid) -> String {
+    let mut slug = String::new();
+    for c in title.chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.len() >= 60 {
+            break;
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    let suffix = &dataset_id.simple().to_string()[..8];
+    if slug.is_empty() { format!("dataset-{}", suffix) } else { format!("{}-{}", slug, suffix) }
+}
+
+/// A device's name within one dataset. Stable inside the dataset, unlinkable across datasets.
+pub fn device_pseudonym(dataset_id: Uuid, device_id: Uuid) -> String {
+    let digest = sha256_hash(format!("{}:{}", dataset_id, device_id).as_bytes());
+    format!("dev-{}", &digest[..12])
+}
+
+#[derive(Debug, FromRow)]
+struct TelemetryRow {
+    device_id: Uuid,
+    device_type: String,
+    recorded_at: DateTime<Utc>,
+    battery_level: i16,
+    latitude: f64,
+    longitude: f64,
+    altitude: Option<f64>,
+    cpu_temp: Option<f64>,
+    signal_strength: Option<f64>,
+}
+
+fn anonymize(row: &TelemetryRow, dataset_id: Uuid, location_decimals: i16) -> Vec<String> {
+    let optional = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+    let recorded_at = row.recorded_at.duration_trunc(Duration::minutes(1)).unwrap_or(row.recorded_at);
+    vec![
+        device_pseudonym(dataset_id, row.device_id),
+        row.device_type.clone(),
+        recorded_at.to_rfc3339(),
+        row.battery_level.to_string(),
+        round_coordinate(row.latitude, location_decimals as u8).to_string(),
+        round_coordinate(row.longitude, location_decimals as u8).to_string(),
+        optional(row.altitude.map(f64::round)),
+        optional(row.cpu_temp),
+        optional(row.signal_strength),
+    ]
+}
+
+/// The dataset's anonymized CSV and its row count
+async fn build_csv(pool: &PgPool, dataset: &OpenDataset) -> ApiResult<(Vec<u8>, i64)> {
+    let number = |key: &str| {
+        format!(
+            "CASE WHEN jsonb_typeof(t.payload->'{0}') = 'number' THEN (t.payload->>'{0}')::DOUBLE PRECISION END",
+            key
+        )
+    };
+    // Devices are re-checked against the publisher in case one changed hands since
+    let rows = sqlx::query_as::<_, TelemetryRow>(&format!(
+        "SELECT t.device_id, d.device_type, t.recorded_at, t.battery_level, t.latitude, t.longitude, t.altitude, \
+         {} AS cpu_temp, {} AS signal_strength FROM device_telemetry t JOIN devices d ON d.id = t.device_id \
+         WHERE d.user_id = $1 AND t.device_id = ANY($2) AND t.recorded_at >= $3 AND t.recorded_at < $4 \
+         ORDER BY t.recorded_at, t.device_id LIMIT $5",
+        number("cpu_temp"),
+        number("signal_strength")
+    ))
+    .bind(dataset.user_id)
+    .bind(&dataset.device_ids)
+    .bind(dataset.period_start)
+    .bind(dataset.period_end)
+    .bind(MAX_DATASET_ROWS + 1)
+    .fetch_all(pool)
+    .await?;
+
+    let count = rows.len() as i64;
+    if count == 0 {
+        return Err(ApiError::ValidationError("There is no telemetry in this period".to_string()));
+    }
+    if count > MAX_DATASET_ROWS {
+        return Err(ApiError::ValidationError(format!(
+            "The period holds more than {} samples; publish a shorter one",
+            MAX_DATASET_ROWS
+        )));
+    }
+    let csv = to_csv(CSV_HEADERS, rows.iter().map(|row| anonymize(row, dataset.id, dataset.location_decimals)))?;
+    Ok((csv, count))
+}
+
+fn object_key(dataset: &OpenDataset) -> String {
+    format!("open-data/{}.csv", dataset.slug)
+}
+
+/// Build and upload the dataset, then publish it or record why it failed
+async fn build(pool: &PgPool, store: &ObjectStore, dataset: &OpenDataset) -> ApiResult<()> {
+    let built = async {
+        let (csv, rows) = build_csv(pool, dataset).await?;
+        let (size, digest) = (csv.len() as i64, sha256_hash(&csv));
+        store.put_object(&object_key(dataset), csv, "text/csv").await?;
+        Ok::<_, ApiError>((rows, size, digest))
+    }
+    .await;
+
+    let mut tx = pool.begin().await?;
+    // Withdrawn while building: leave it withdrawn
+    let updated = match &built {
+        Ok((rows, size, digest)) => sqlx::query(
+            "UPDATE open_datasets SET status = 'published', row_count = $2, size_bytes = $3, sha256 = $4, \
+             object_key = $5, published_at = NOW(), updated_at = NOW() WHERE id = $1 AND status = 'building'",
+        )
+        .bind(dataset.id)
+        .bind(rows)
+        .bind(size)
+        .bind(digest)
+        .bind(object_key(dataset))
+        .execute(&mut *tx)
+        .await?,
+        Err(e) => sqlx::query(
+            "UPDATE open_datasets SET status = 'failed', failure_reason = $2, updated_at = NOW() \
+             WHERE id = $1 AND status = 'building'",
+        )
+        .bind(dataset.id)
+        .bind(e.to_string())
+        .execute(&mut *tx)
+        .await?,
+    };
+    if updated.rows_affected() == 0 {
+        if built.is_ok() {
+            store.delete_object(&object_key(dataset)).await?;
+        }
+        return Ok(());
+    }
+
+    let (title, body) = match &built {
+        Ok((rows, _, _)) => (
+            "Dataset published",
+            format!("\"{}\" is now in the open data catalog with {} samples.", dataset.title, rows),
+        ),
+        Err(e) => ("Dataset not published", format!("\"{}\" could not be built: {}", dataset.title, e)),
+    };
+    notify_user(
+        &mut tx,
+        dataset.user_id,
+        "open_data_dataset",
+        title,
+        &body,
+        serde_json::json!({ "dataset_id": dataset.id, "slug": dataset.slug }),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Record a dataset and build it in the background. The publisher must have opted in.
+pub async fn publish(
+    pool: Arc<PgPool>,
+    store: ObjectStore,
+    user_id: Uuid,
+    request: &PublishDatasetRequest,
+) -> ApiResult<OpenDataset> {
+    let location_decimals = validate_publish(request)?;
+    let opted_in: Option<DateTime<Utc>> = sqlx::query_scalar("SELECT open_data_opt_in_at FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(pool.as_ref())
+        .await?;
+    if opted_in.is_none() {
+        return Err(ApiError::Forbidden("Opt in to the open data program before publishing".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "UPDATE open_datasets SET status = 'failed', failure_reason = 'Build was interrupted', updated_at = NOW() \
+         WHERE user_id = $1 AND status = 'building' AND created_at < NOW() - make_interval(mins => $2)",
+    )
+    .bind(user_id)
+    .bind(BUILD_TIMEOUT_MINUTES)
+    .execute(&mut *tx)
+    .await?;
+    let building: bool =
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM open_datasets WHERE user_id = $1 AND status = 'building')")
+            .bind(user_id)
+            .fetch_one(&mut *tx)
+            .await?;
+    if building {
+        return Err(ApiError::Conflict("Another dataset is still being built".to_string()));
+    }
+
+    let (device_ids, device_types): (Vec<Uuid>, Vec<String>) = sqlx::query_as(
+        "SELECT COALESCE(array_agg(id ORDER BY id), '{}'), \
+         COALESCE(array_agg(DISTINCT device_type::VARCHAR), '{}') FROM devices \
+         WHERE user_id = $1 AND ($2::uuid[] IS NULL OR id = ANY($2))",
+    )
+    .bind(user_id)
+    .bind(&request.device_ids)
+    .fetch_one(&mut *tx)
+    .await?;
+    if device_ids.is_empty() {
+        return Err(ApiError::ValidationError("No devices to publish telemetry from".to_string()));
+    }
+    if let Some(requested) = &request.device_ids
+        && let Some(missing) = requested.iter().find(|id| !device_ids.contains(id))
+    {
+        return Err(ApiError::NotFound(format!("Device {} not found", missing)));
+    }
+
+    let id = Uuid::new_v4();
+    let title = request.title.trim();
+    let dataset = sqlx::query_as::<_, OpenDataset>(&format!(
+        "INSERT INTO open_datasets (id, user_id, slug, title, description, license, device_ids, device_types, \
+         period_start, period_end, location_decimals) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING {}",
+        DATASET_COLUMNS
+    ))
+    .bind(id)
+    .bind(user_id)
+    .bind(slugify(title, id))
+    .bind(title)
+    .bind(request.description.trim())
+    .bind(&request.license)
+    .bind(&device_ids)
+    .bind(&device_types)
+    .bind(request.from)
+    .bind(request.to)
+    .bind(location_decimals)
+    .fetch_one(&mut *tx)
+    .await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(user_id),
+            action: "open_data.published",
+            resource_type: "open_dataset",
+            resource_id: Some(dataset.id.to_string()),
+            details: serde_json::json!({
+                "slug": dataset.slug,
+                "license": dataset.license,
+                "devices": dataset.device_ids.len(),
+                "location_decimals": dataset.location_decimals,
+            }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    let building = dataset.clone();
+    tokio::spawn(async move {
+        if let Err(e) = build(&pool, &store, &building).await {
+            tracing::error!("Building open dataset {} failed: {}", building.id, e);
+        }
+    });
+    Ok(dataset)
+}
+
+/// Take a dataset out of the catalog and delete its file
+pub async fn withdraw(
+    pool: &PgPool,
+    store: Option<&ObjectStore>,
+    user_id: Uuid,
+    dataset_id: Uuid,
+) -> ApiResult<OpenDataset> {
+    let mut tx = pool.begin().await?;
+    let (dataset, object_key) = sqlx::query_as::<_, (Uuid, Option<String>)>(
+        "UPDATE open_datasets SET status = 'withdrawn', withdrawn_at = NOW(), updated_at = NOW() \
+         WHERE id = $1 AND user_id = $2 AND status <> 'withdrawn' RETURNING id, object_key",
+    )
+    .bind(dataset_id)
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Dataset not found or already withdrawn".to_string()))?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(user_id),
+            action: "open_data.withdrawn",
+            resource_type: "open_dataset",
+            resource_id: Some(dataset.to_string()),
+            details: serde_json::json!({}),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    // Outstanding download links die with the file
+    if let (Some(store), Some(key)) = (store, object_key)
+        && let Err(e) = store.delete_object(&key).await
+    {
+        tracing::warn!("Deleting withdrawn dataset {} from storage failed: {}", dataset, e);
+    }
+    get_dataset(pool, user_id, dataset).await
+}
+
+pub async fn get_dataset(pool: &PgPool, user_id: Uuid, dataset_id: Uuid) -> ApiResult<OpenDataset> {
+    sqlx::query_as::<_, OpenDataset>(&format!(
+        "SELECT {} FROM open_datasets WHERE id = $1 AND user_id = $2",
+        DATASET_COLUMNS
+    ))
+    .bind(dataset_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Dataset not found".to_string()))
+}
+
+/// Leave the program; everything the user published is withdrawn
+pub async fn opt_out(pool: &PgPool, store: Option<&ObjectStore>, user_id: Uuid) -> ApiResult<usize> {
+    sqlx::query("UPDATE users SET open_data_opt_in_at = NULL WHERE id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    let datasets: Vec<Uuid> =
+        sqlx::query_scalar("SELECT id FROM open_datasets WHERE user_id = $1 AND status <> 'withdrawn'")
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?;
+    for &dataset_id in &datasets {
+        withdraw(pool, store, user_id, dataset_id).await?;
+    }
+    Ok(datasets.len())
+}
+
+fn entry(dataset: CatalogDataset) -> CatalogEntry {
+    let license_details = license(&dataset.license);
+    CatalogEntry { dataset, license_details, download_url: None, download_expires_at: None }
+}
+
+/// One page of the public catalog, newest first
+pub async fn catalog(pool: &PgPool, query: &CatalogQuery) -> ApiResult<CatalogPage> {
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+    let offset = query.offset.unwrap_or(0).max(0);
+    let search = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty()).map(|q| {
+        format!("%{}%", q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"))
+    });
+    let filter = "status = 'published' AND ($1::varchar IS NULL OR license = $1) \
+         AND ($2::varchar IS NULL OR $2 = ANY(device_types)) \
+         AND ($3::text IS NULL OR title ILIKE $3 OR description ILIKE $3)";
+
+    let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM open_datasets WHERE {}", filter))
+        .bind(&query.license)
+        .bind(&query.device_type)
+        .bind(&search)
+        .fetch_one(pool)
+        .await?;
+    let datasets = sqlx::query_as::<_, CatalogDataset>(&format!(
+        "SELECT {} FROM open_datasets WHERE {} ORDER BY published_at DESC, id LIMIT $4 OFFSET $5",
+        CATALOG_COLUMNS, filter
+    ))
+    .bind(&query.license)
+    .bind(&query.device_type)
+    .bind(&search)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(CatalogPage { datasets: datasets.into_iter().map(entry).collect(), total, limit, offset })
+}
+
+/// A published dataset with a fresh download link
+pub async fn catalog_entry(pool: &PgPool, store: &ObjectStore, slug: &str) -> ApiResult<CatalogEntry> {
+    let (dataset, object_key) = sqlx::query_as::<_, (Uuid, String)>(
+        "SELECT id, object_key FROM open_datasets WHERE slug = $1 AND status = 'published'",
+    )
+    .bind(slug)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Dataset not found".to_string()))?;
+    let dataset = sqlx::query_as::<_, CatalogDataset>(&format!(
+        "SELECT {} FROM open_datasets WHERE id = $1",
+        CATALOG_COLUMNS
+    ))
+    .bind(dataset)
+    .fetch_one(pool)
+    .await?;
+
+    let mut entry = entry(dataset);
+    entry.download_url = Some(store.presigned_get_url(&object_key, DOWNLOAD_URL_TTL_SECS)?);
+    entry.download_expires_at = Some(Utc::now() + Duration::seconds(DOWNLOAD_URL_TTL_SECS as i64));
+    Ok(entry)
+}
+
+/// Most datasets one publisher adds to any catalog count
+pub const DATASETS_PER_PUBLISHER_CAP: i64 = 20;
+
+static STATS_RELEASE: ReleaseCache<CatalogStats> = ReleaseCache::new(std::time::Duration::from_secs(60 * 60));
+
+/// Noisy catalog figures; `null` where too few publishers contributed
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogStats {
+    pub datasets: Option<i64>,
+    pub datasets_by_license: BTreeMap<&'static str, Option<i64>>,
+    pub datasets_by_device_type: BTreeMap<&'static str, Option<i64>>,
+    pub privacy: PrivacyNotice,
+}
+
+#[derive(Debug, FromRow)]
+struct GroupCount {
+    grouping: String,
+    total: i64,
+    publishers: i64,
+}
+
+/// Counts of published datasets, overall and per license and device type, with clipped
+/// contributions per publisher (an overall group with the key `""`)
+async fn catalog_counts(pool: &PgPool) -> ApiResult<Vec<GroupCount>> {
+    Ok(sqlx::query_as::<_, GroupCount>(
+        "SELECT grouping, SUM(LEAST(n, $1))::BIGINT AS total, COUNT(*) AS publishers FROM ( \
+           SELECT '' AS grouping, user_id, COUNT(*) AS n FROM open_datasets WHERE status = 'published' \
+           GROUP BY user_id \
+           UNION ALL SELECT 'license:' || license, user_id, COUNT(*) FROM open_datasets WHERE status = 'published' \
+           GROUP BY license, user_id \
+           UNION ALL SELECT 'device_type:' || t, user_id, COUNT(*) FROM open_datasets, unnest(device_types) t \
+           WHERE status = 'published' GROUP BY t, user_id \
+         ) per_publisher GROUP BY grouping",
+    )
+    .bind(DATASETS_PER_PUBLISHER_CAP)
+    .fetch_all(pool)
+    .await?)
+}
+
+fn release_catalog_stats(params: PrivacyParams, counts: &[GroupCount]) -> CatalogStats {
+    let mut release = Release::new(params, 1 + LICENSES.len() + DEVICE_TYPES.len());
+    let mut count = |grouping: String| {
+        let group = counts.iter().find(|g| g.grouping == grouping);
+        group.and_then(|g| release.count(g.total, g.publishers, DATASETS_PER_PUBLISHER_CAP as f64))
+    };
+    let datasets = count(String::new());
+    let datasets_by_license = LICENSES.iter().map(|l| (l.id, count(format!("license:{}", l.id)))).collect();
+    let datasets_by_device_type =
+        DEVICE_TYPES.iter().map(|t| (t.device_type, count(format!("device_type:{}", t.device_type)))).collect();
+    CatalogStats { datasets, datasets_by_license, datasets_by_device_type, privacy: release.notice() }
+}
+
+/// Catalog-wide figures, released with differential privacy and redrawn at most once an hour
+pub async fn catalog_stats(pool: &PgPool, params: PrivacyParams) -> ApiResult<CatalogStats> {
+    if let Some(stats) = STATS_RELEASE.get() {
+        return Ok(stats);
+    }
+    let counts = catalog_counts(pool).await?;
+    Ok(STATS_RELEASE.put(release_catalog_stats(params, &counts)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn request() -> PublishDatasetRequest {
+        PublishDatasetRequest {
+            title: "Rover fleet, spring".to_string(),
+            description: String::new(),
+            license: "CC-BY-4.0".to_string(),
+            device_ids: None,
+            from: Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap(),
+            to: Utc.with_ymd_and_hms(2026, 6, 1, 0, 0, 0).unwrap(),
+            location_decimals: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_publish() {
+        assert_eq!(validate_publish(&request()).unwrap(), DEFAULT_LOCATION_DECIMALS);
+        assert_eq!(validate_publish(&PublishDatasetRequest { location_decimals: Some(0), ..request() }).unwrap(), 0);
+
+        let invalid = [
+            PublishDatasetRequest { license: "GPL-3.0".to_string(), ..request() },
+            PublishDatasetRequest { title: "  ".to_string(), ..request() },
+            PublishDatasetRequest { to: request().from, ..request() },
+            PublishDatasetRequest { to: Utc::now() + Duration::days(1), ..request() },
+            PublishDatasetRequest { from: request().to - Duration::days(400), ..request() },
+            PublishDatasetRequest { device_ids: Some(Vec::new()), ..request() },
+            PublishDatasetRequest { location_decimals: Some(5), ..request() },
+        ];
+        for request in invalid {
+            assert!(matches!(validate_publish(&request), Err(ApiError::ValidationError(_))));
+        }
+    }
+
+    #[test]
+    fn test_slugify() {
+        let id = Uuid::parse_str("3f1c9a52-6b1e-4a8e-9c59-2f4d1e0b7a11").unwrap();
+        assert_eq!(slugify("Rover fleet, spring 2026!", id), "rover-fleet-spring-2026-3f1c9a52");
+        assert_eq!(slugify("Über —", id), "ber-3f1c9a52");
+        assert_eq!(slugify("???", id), "dataset-3f1c9a52");
+        assert!(slugify(&"a".repeat(300), id).len() <= 69);
+    }
+
+    #[test]
+    fn test_device_pseudonym_differs_between_datasets() {
+        let (device, a, b) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        assert_eq!(device_pseudonym(a, device), device_pseudonym(a, device));
+        assert_ne!(device_pseudonym(a, device), device_pseudonym(b, device));
+        assert!(!device_pseudonym(a, device).contains(&device.simple().to_string()[..8]));
+    }
+
+    #[test]
+    fn test_anonymize() {
+        let dataset = Uuid::new_v4();
+        let row = TelemetryRow {
+            device_id: Uuid::new_v4(),
+            device_type: "rover".to_string(),
+            recorded_at: Utc.with_ymd_and_hms(2026, 3, 4, 10, 15, 42).unwrap(),
+            battery_level: 81,
+            latitude: 52.520008,
+            longitude: 13.404954,
+            altitude: Some(34.6),
+            cpu_temp: Some(48.5),
+            signal_strength: None,
+        };
+        let record = anonymize(&row, dataset, 2);
+        assert_eq!(record.len(), CSV_HEADERS.len());
+        assert_eq!(record[0], device_pseudonym(dataset, row.device_id));
+        assert_eq!(record[2], "2026-03-04T10:15:00+00:00");
+        assert_eq!(&record[4..], ["52.52", "13.4", "35", "48.5", ""]);
+    }
+
+    #[test]
+    fn test_release_catalog_stats() {
+        let group =
+            |grouping: &str, total, publishers| GroupCount { grouping: grouping.to_string(), total, publishers };
+        let counts = [group("", 300, 120), group("license:CC-BY-4.0", 250, 100), group("device_type:drone", 12, 2)];
+        let stats = release_catalog_stats(PrivacyParams { epsilon: 1.0, min_cohort: 10 }, &counts);
+
+        assert!(stats.datasets.is_some());
+        assert!(stats.datasets_by_license["CC-BY-4.0"].is_some());
+        assert_eq!(stats.datasets_by_license["CC0-1.0"], None);
+        assert_eq!(stats.datasets_by_device_type["drone"], None);
+        assert_eq!(stats.datasets_by_license.len(), LICENSES.len());
+    }
+}
@@ -0,0 +1,219 @@
+//! Organization membership and access helpers shared by org-scoped controllers
+
+use chrono::Utc;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::org::{OrgMembership, ORG_ROLES};
+use crate::services::break_glass_services::active_session_org;
+use crate::services::policy_services::{role_allows, OrgAction};
+use crate::services::sso_services::email_domain;
+use crate::utils::{generate_random_hex, generate_random_string, RedactionPolicy};
+
+/// Whether `role` grants at least the privileges of `minimum`
+pub fn role_at_least(role: &str, minimum: &str) -> bool {
+    match (
+        ORG_ROLES.iter().position(|r| *r == role),
+        ORG_ROLES.iter().position(|r| *r == minimum),
+    ) {
+        (Some(have), Some(need)) => have <= need,
+        _ => false,
+    }
+}
+
+/// Look up a user's membership in an organization
+pub async fn get_membership(pool: &PgPool, org_id: Uuid, user_id: Uuid) -> ApiResult<Option<OrgMembership>> {
+    let membership = sqlx::query_as::<_, OrgMembership>(
+        "SELECT org_id, user_id, role, active, created_at FROM org_memberships WHERE org_id = $1 AND user_id = $2",
+    )
+    .bind(org_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(membership)
+}
+
+/// Require an active membership in the org.
+/// Orgs that enforce SSO only accept sessions established through their own identity provider.
+/// An active break-glass session acts as the org's owner for its lifetime.
+async fn require_active_member(pool: &PgPool, org_id: Uuid, user: &AuthenticatedUser) -> ApiResult<OrgMembership> {
+    if user.claims.auth_method.as_deref() == Some("break_glass") {
+        let session_org = match user.claims.session_id {
+            Some(session_id) => active_session_org(pool, session_id).await?,
+            None => None,
+        };
+        if session_org != Some(org_id) {
+            return Err(ApiError::Forbidden("Break-glass session is not valid for this organization".to_string()));
+        }
+        return Ok(OrgMembership {
+            org_id,
+            user_id: user.user_id,
+            role: "owner".to_string(),
+            active: true,
+            created_at: Utc::now(),
+        });
+    }
+
+    let membership = get_membership(pool, org_id, user.user_id)
+        .await?
+        .filter(|m| m.active)
+        .ok_or_else(|| ApiError::Forbidden("Not a member of this organization".to_string()))?;
+
+    let sso_enforced: bool = sqlx::query_scalar("SELECT sso_enforced FROM organizations WHERE id = $1")
+        .bind(org_id)
+        .fetch_one(pool)
+        .await?;
+
+    let own_sso = user.claims.auth_method.as_deref() == Some("sso") && user.claims.sso_org_id == Some(org_id);
+    if sso_enforced && !own_sso {
+        return Err(ApiError::Forbidden("This organization requires SSO sign-in".to_string()));
+    }
+
+    Ok(membership)
+}
+
+/// Require the user to be a member of the org with at least `minimum` role
+pub async fn require_org_role(
+    pool: &PgPool,
+    org_id: Uuid,
+    user: &AuthenticatedUser,
+    minimum: &str,
+) -> ApiResult<OrgMembership> {
+    let membership = require_active_member(pool, org_id, user).await?;
+
+    if !role_at_least(&membership.role, minimum) {
+        return Err(ApiError::Forbidden(format!("Organization {} role required", minimum)));
+    }
+
+    Ok(membership)
+}
+
+/// Require the user's org role to permit `action` under the org policy
+pub async fn require_org_permission(
+    pool: &PgPool,
+    org_id: Uuid,
+    user: &AuthenticatedUser,
+    action: OrgAction,
+) -> ApiResult<OrgMembership> {
+    let membership = require_active_member(pool, org_id, user).await?;
+
+    if !role_allows(&membership.role, action) {
+        return Err(ApiError::Forbidden(format!(
+            "Role '{}' is not permitted to {}",
+            membership.role,
+            action.as_str()
+        )));
+    }
+
+    Ok(membership)
+}
+
+/// Find an existing user by email, case-insensitively
+pub async fn find_user_by_email(conn: &mut PgConnection, email: &str) -> ApiResult<Option<Uuid>> {
+    let id = sqlx::query_scalar("SELECT id FROM users WHERE LOWER(email) = $1")
+        .bind(email.trim().to_lowercase())
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    Ok(id)
+}
+
+/// Create a verified password-less account for an externally managed identity.
+/// Used by SSO and SCIM provisioning, which never adopt an existing account.
+pub async fn create_external_user(conn: &mut PgConnection, email: &str) -> ApiResult<Uuid> {
+    let email = email.trim().to_lowercase();
+    if find_user_by_email(conn, &email).await?.is_some() {
+        return Err(ApiError::Conflict("An account with this email already exists".to_string()));
+    }
+
+    // Externally managed users never use a password; store an unusable random hash
+    let password_hash = bcrypt::hash(generate_random_string(48), bcrypt::DEFAULT_COST)?;
+    let local_part: String = email
+        .split('@')
+        .next()
+        .unwrap_or("user")
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .take(13)
+        .collect();
+    let username = format!("{}_{}", local_part, generate_random_hex(3));
+
+    let id = sqlx::query_scalar(
+        "INSERT INTO users (email, username, password_hash, is_verified) \
+         VALUES ($1, $2, $3, TRUE) RETURNING id",
+    )
+    .bind(&email)
+    .bind(&username)
+    .bind(&password_hash)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(id)
+}
+
+/// Whether the email's domain is one the org has verified it owns
+pub async fn email_domain_verified(conn: &mut PgConnection, org_id: Uuid, email: &str) -> ApiResult<bool> {
+    let Some(domain) = email_domain(email) else {
+        return Ok(false);
+    };
+
+    let verified: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM org_domains \
+         WHERE org_id = $1 AND domain = $2 AND verified_at IS NOT NULL)",
+    )
+    .bind(org_id)
+    .bind(&domain)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(verified)
+}
+
+/// Reject password logins for users who belong to an SSO-enforced organization
+pub async fn ensure_password_login_allowed(pool: &PgPool, user_id: Uuid) -> ApiResult<()> {
+    let enforced: Option<String> = sqlx::query_scalar(
+        "SELECT o.slug FROM organizations o \
+         JOIN org_memberships m ON m.org_id = o.id \
+         WHERE m.user_id = $1 AND m.active AND o.sso_enforced LIMIT 1",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    match enforced {
+        Some(slug) => Err(ApiError::Forbidden(format!(
+            "Password login is disabled; sign in via SSO at /api/sso/{}/login",
+            slug
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// The org's PII redaction settings; unset fields fall back to the defaults
+pub async fn privacy_policy(pool: &PgPool, org_id: Uuid) -> ApiResult<RedactionPolicy> {
+    let settings: Option<serde_json::Value> =
+        sqlx::query_scalar("SELECT privacy_settings FROM organizations WHERE id = $1")
+            .bind(org_id)
+            .fetch_optional(pool)
+            .await?;
+
+    let settings = settings.ok_or_else(|| ApiError::NotFound("Organization not found".to_string()))?;
+    serde_json::from_value(settings)
+        .map_err(|e| ApiError::InternalError(format!("Invalid privacy settings: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_at_least() {
+        assert!(role_at_least("owner", "admin"));
+        assert!(role_at_least("admin", "admin"));
+        assert!(!role_at_least("member", "admin"));
+        assert!(!role_at_least("auditor", "member"));
+        assert!(!role_at_least("superuser", "member"));
+    }
+}
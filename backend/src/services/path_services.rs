@@ -0,0 +1,232 @@
+//! Recorded GPS paths and their conversion into movement command sequences
+
+use crate::errors::{ApiError, ApiResult};
+use crate::models::device::PathPoint;
+use crate::services::robotics_services::MAX_COMMAND_DURATION_MS;
+use crate::utils::geo::haversine_distance_m;
+
+/// Most points kept in one recorded path
+pub const MAX_PATH_POINTS: usize = 1000;
+
+/// Default spacing between kept samples when recording (meters)
+pub const DEFAULT_MIN_SPACING_M: f64 = 2.0;
+
+/// Heading changes smaller than this are not worth a turn command (degrees)
+const MIN_TURN_DEGREES: f64 = 2.0;
+
+/// Altitude changes smaller than this are flown level (meters)
+const MIN_ALTITUDE_CHANGE_M: f64 = 0.5;
+
+/// Top ground speed by device type, used to turn distance into movement duration
+pub fn max_speed_mps(device_type: &str) -> f64 {
+    match device_type {
+        "drone" => 15.0,
+        "rover" => 3.0,
+        _ => 1.5,
+    }
+}
+
+/// Drop samples closer than `min_spacing_m` to the last kept point (the final sample is always kept)
+pub fn simplify_track(samples: &[PathPoint], min_spacing_m: f64) -> Vec<PathPoint> {
+    let mut kept: Vec<PathPoint> = Vec::new();
+    for (i, point) in samples.iter().enumerate() {
+        let is_last = i + 1 == samples.len();
+        match kept.last() {
+            Some(prev) if !is_last && distance_m(prev, point) < min_spacing_m => continue,
+            Some(prev) if is_last && distance_m(prev, point) < f64::EPSILON => continue,
+            _ => kept.push(point.clone()),
+        }
+    }
+    kept
+}
+
+pub fn distance_m(a: &PathPoint, b: &PathPoint) -> f64 {
+    haversine_distance_m(a.latitude, a.longitude, b.latitude, b.longitude)
+}
+
+pub fn path_length_m(points: &[PathPoint]) -> f64 {
+    points.windows(2).map(|w| distance_m(&w[0], &w[1])).sum()
+}
+
+/// A GeoJSON Feature for a track, ready for Leaflet or Mapbox. Positions are `[lng, lat]`
+/// (plus altitude when every point has one); their timestamps go in the `times` property.
+/// Fewer than two points cannot form a LineString, so the geometry is then a Point or null.
+pub fn track_feature(points: &[PathPoint], properties: serde_json::Value) -> serde_json::Value {
+    let with_altitude = points.iter().all(|p| p.altitude.is_some());
+    let coordinates: Vec<Vec<f64>> = points
+        .iter()
+        .map(|p| match p.altitude {
+            Some(altitude) if with_altitude => vec![p.longitude, p.latitude, altitude],
+            _ => vec![p.longitude, p.latitude],
+        })
+        .collect();
+    let geometry = match coordinates.len() {
+        0 => serde_json::Value::Null,
+        1 => serde_json::json!({ "type": "Point", "coordinates": coordinates[0] }),
+        _ => serde_json::json!({ "type": "LineString", "coordinates": coordinates }),
+    };
+
+    let mut properties = properties;
+    if let Some(map) = properties.as_object_mut() {
+        map.insert("point_count".to_string(), serde_json::json!(points.len()));
+        map.insert("distance_m".to_string(), serde_json::json!(path_length_m(points)));
+        map.insert(
+            "times".to_string(),
+            serde_json::json!(points.iter().map(|p| p.recorded_at).collect::<Vec<_>>()),
+        );
+    }
+    serde_json::json!({ "type": "Feature", "geometry": geometry, "properties": properties })
+}
+
+/// Initial great-circle bearing from `a` to `b`, degrees clockwise from north in [0, 360)
+pub fn bearing_deg(a: &PathPoint, b: &PathPoint) -> f64 {
+    let (lat1, lat2) = (a.latitude.to_radians(), b.latitude.to_radians());
+    let d_lng = (b.longitude - a.longitude).to_radians();
+    let y = d_lng.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * d_lng.cos();
+    (y.atan2(x).to_degrees() + 360.0) % 360.0
+}
+
+/// Signed turn from one heading to another in (-180, 180]; positive is clockwise (right)
+pub fn turn_deg(from: f64, to: f64) -> f64 {
+    let delta = (to - from).rem_euclid(360.0);
+    if delta > 180.0 { delta - 360.0 } else { delta }
+}
+
+/// Translate a path into the device type's own turn/move commands, leg by leg.
+/// The device is assumed to start at the first point facing `initial_heading`.
+pub fn path_to_commands(
+    device_type: &str,
+    points: &[PathPoint],
+    speed: f64,
+    initial_heading: Option<f64>,
+) -> ApiResult<Vec<(&'static str, serde_json::Value)>> {
+    if !(0.05..=1.0).contains(&speed) {
+        return Err(ApiError::ValidationError("Replay speed must be between 0.05 and 1.0".to_string()));
+    }
+    if points.len() < 2 {
+        return Err(ApiError::ValidationError("Path needs at least two points".to_string()));
+    }
+
+    let ground_speed = max_speed_mps(device_type) * speed;
+    let mut heading = initial_heading.unwrap_or_else(|| bearing_deg(&points[0], &points[1]));
+    let mut altitude = points[0].altitude;
+    let mut commands = Vec::new();
+
+    for leg in points.windows(2) {
+        let (from, to) = (&leg[0], &leg[1]);
+
+        if device_type == "drone"
+            && let Some(target) = to.altitude
+            && altitude.is_none_or(|current| (target - current).abs() >= MIN_ALTITUDE_CHANGE_M)
+        {
+            commands.push(("hover", serde_json::json!({ "altitude": target })));
+            altitude = Some(target);
+        }
+
+        let distance = distance_m(from, to);
+        if distance < f64::EPSILON {
+            continue;
+        }
+
+        let bearing = bearing_deg(from, to);
+        let turn = turn_deg(heading, bearing);
+        if turn.abs() >= MIN_TURN_DEGREES {
+            let turn = (turn * 10.0).round() / 10.0;
+            commands.push(match device_type {
+                "drone" => ("rotate", serde_json::json!({ "degrees": turn, "speed": 0.5 })),
+                "rover" => ("turn", serde_json::json!({ "degrees": turn, "speed": 0.5 })),
+                _ if turn > 0.0 => ("turn_right", serde_json::json!({ "degrees": turn, "speed": 0.5 })),
+                _ => ("turn_left", serde_json::json!({ "degrees": -turn, "speed": 0.5 })),
+            });
+        }
+        heading = bearing;
+
+        let duration_ms = ((distance / ground_speed) * 1000.0).round() as u64;
+        if duration_ms > MAX_COMMAND_DURATION_MS {
+            return Err(ApiError::ValidationError("Path leg is too long to replay".to_string()));
+        }
+        let command = match device_type {
+            "drone" => "move",
+            "rover" => "drive",
+            _ => "move_forward",
+        };
+        commands.push((
+            command,
+            serde_json::json!({ "speed": speed, "direction": "forward", "duration_ms": duration_ms }),
+        ));
+    }
+
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn point(latitude: f64, longitude: f64, altitude: Option<f64>) -> PathPoint {
+        PathPoint { latitude, longitude, altitude, recorded_at: Utc::now() }
+    }
+
+    #[test]
+    fn test_simplify_track() {
+        // ~1.1m steps north; only every other sample clears a 2m spacing
+        let samples: Vec<PathPoint> = (0..6).map(|i| point(10.0 + i as f64 * 0.00001, 20.0, None)).collect();
+        let kept = simplify_track(&samples, DEFAULT_MIN_SPACING_M);
+        assert_eq!(kept.len(), 4);
+        assert_eq!(kept.last().unwrap().latitude, samples[5].latitude);
+    }
+
+    #[test]
+    fn test_track_feature() {
+        let track = [point(10.0, 20.0, Some(5.0)), point(10.001, 20.0, Some(6.0))];
+        let feature = track_feature(&track, serde_json::json!({ "name": "patrol" }));
+        assert_eq!(feature["geometry"]["type"], "LineString");
+        assert_eq!(feature["geometry"]["coordinates"][0], serde_json::json!([20.0, 10.0, 5.0]));
+        assert_eq!(feature["properties"]["name"], "patrol");
+        assert_eq!(feature["properties"]["times"].as_array().unwrap().len(), 2);
+
+        // Mixed altitude data falls back to 2D positions
+        let track = [point(10.0, 20.0, Some(5.0)), point(10.001, 20.0, None)];
+        let feature = track_feature(&track, serde_json::json!({}));
+        assert_eq!(feature["geometry"]["coordinates"][0], serde_json::json!([20.0, 10.0]));
+        assert_eq!(track_feature(&track[..1], serde_json::json!({}))["geometry"]["type"], "Point");
+        assert!(track_feature(&[], serde_json::json!({}))["geometry"].is_null());
+    }
+
+    #[test]
+    fn test_bearing_and_turn() {
+        let origin = point(0.0, 0.0, None);
+        assert!((bearing_deg(&origin, &point(1.0, 0.0, None)) - 0.0).abs() < 1e-6);
+        assert!((bearing_deg(&origin, &point(0.0, 1.0, None)) - 90.0).abs() < 1e-6);
+        assert!((turn_deg(350.0, 10.0) - 20.0).abs() < 1e-9);
+        assert!((turn_deg(10.0, 350.0) + 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_path_to_commands() {
+        // North ~111m, then east ~111m
+        let path = [point(0.0, 0.0, None), point(0.001, 0.0, None), point(0.001, 0.001, None)];
+
+        let rover = path_to_commands("rover", &path, 0.5, None).unwrap();
+        let names: Vec<&str> = rover.iter().map(|(c, _)| *c).collect();
+        assert_eq!(names, ["drive", "turn", "drive"]);
+        assert!((rover[1].1["degrees"].as_f64().unwrap() - 90.0).abs() < 0.5);
+        // 111m at 1.5 m/s
+        let duration = rover[0].1["duration_ms"].as_u64().unwrap();
+        assert!((74_000..=74_300).contains(&duration));
+
+        let robot = path_to_commands("robot", &path, 0.5, Some(180.0)).unwrap();
+        assert_eq!(robot[0].0, "turn_right");
+        assert_eq!(robot[0].1["degrees"].as_f64().unwrap(), 180.0);
+
+        let climbing = [point(0.0, 0.0, Some(10.0)), point(0.001, 0.0, Some(30.0))];
+        let drone = path_to_commands("drone", &climbing, 1.0, None).unwrap();
+        assert_eq!(drone[0].0, "hover");
+        assert_eq!(drone[1].0, "move");
+
+        assert!(path_to_commands("rover", &path[..1], 0.5, None).is_err());
+        assert!(path_to_commands("rover", &path, 1.5, None).is_err());
+    }
+}
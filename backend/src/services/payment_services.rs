@@ -0,0 +1,276 @@
+//! Settling transactions once the payment provider reports the outcome, and unlocking what
+//! was bought.
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use sqlx::PgConnection;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::transaction::Transaction;
+use crate::services::notification_services::notify_user;
+use crate::services::{escrow_services, ledger_services, payout_services, subscription_services, transfer_services};
+
+pub const TRANSACTION_COLUMNS: &str = "id, user_id, amount, currency, payment_method, payment_id, status, \
+     product_type, blockchain_tx_hash, chain_id, confirmations, block_number, coupon_id, discount_amount, created_at";
+
+pub const PRODUCT_TYPES: &[&str] = &["software_license", "documentation", "hardware_guide"];
+
+/// Currencies Stripe and most providers count in whole units rather than cents
+const ZERO_DECIMAL_CURRENCIES: &[&str] = &[
+    "bif", "clp", "djf", "gnf", "jpy", "kmf", "krw", "mga", "pyg", "rwf", "ugx", "vnd", "vuv", "xaf", "xof", "xpf",
+];
+
+/// Decimal places of the currency's smallest unit
+fn minor_places(currency: &str) -> u32 {
+    if ZERO_DECIMAL_CURRENCIES.contains(&currency.to_ascii_lowercase().as_str()) { 0 } else { 2 }
+}
+
+/// A transaction's amount in the currency's smallest unit, as providers report it
+pub fn minor_units(amount: Decimal, currency: &str) -> i64 {
+    let places = minor_places(currency);
+    let rounded = amount.round_dp_with_strategy(places, RoundingStrategy::MidpointAwayFromZero);
+    (rounded * Decimal::from(10i64.pow(places))).to_i64().unwrap_or_default()
+}
+
+/// Inverse of `minor_units`
+pub fn from_minor_units(minor: i64, currency: &str) -> Decimal {
+    Decimal::new(minor, minor_places(currency))
+}
+
+pub fn validate_product_type(product_type: &str) -> ApiResult<()> {
+    if !PRODUCT_TYPES.contains(&product_type) {
+        return Err(ApiError::ValidationError(format!("product_type must be one of {}", PRODUCT_TYPES.join(", "))));
+    }
+    Ok(())
+}
+
+/// Record a provider event before acting on it; false when it was already handled
+pub async fn record_provider_event(
+    conn: &mut PgConnection,
+    provider: &str,
+    event_id: &str,
+    event_type: &str,
+    transaction_id: Option<Uuid>,
+    outcome: &str,
+) -> ApiResult<bool> {
+    let inserted = sqlx::query(
+        "INSERT INTO payment_provider_events (provider, event_id, event_type, transaction_id, outcome) \
+         VALUES ($1, $2, $3, $4, $5) ON CONFLICT (provider, event_id) DO NOTHING",
+    )
+    .bind(provider)
+    .bind(event_id)
+    .bind(event_type)
+    .bind(transaction_id)
+    .bind(outcome)
+    .execute(conn)
+    .await?;
+    Ok(inserted.rows_affected() == 1)
+}
+
+/// The transaction a provider payment belongs to, locked for settling
+pub async fn find_provider_transaction(
+    conn: &mut PgConnection,
+    payment_method: &str,
+    payment_id: &str,
+) -> ApiResult<Option<Transaction>> {
+    let transaction = sqlx::query_as::<_, Transaction>(&format!(
+        "SELECT {} FROM transactions WHERE payment_method = $1 AND payment_id = $2 FOR UPDATE",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(payment_method)
+    .bind(payment_id)
+    .fetch_optional(conn)
+    .await?;
+    Ok(transaction)
+}
+
+/// Mark a pending (or previously failed, since a payment can be retried) transaction
+/// completed and unlock its product, or pay the subscription invoice it was charged for.
+/// False when it was already completed.
+pub async fn complete_transaction(conn: &mut PgConnection, transaction: &Transaction) -> ApiResult<bool> {
+    let updated = sqlx::query(
+        "UPDATE transactions SET status = 'completed', completed_at = NOW(), failure_reason = NULL \
+         WHERE id = $1 AND status IN ('pending', 'failed')",
+    )
+    .bind(transaction.id)
+    .execute(&mut *conn)
+    .await?;
+    if updated.rows_affected() == 0 {
+        return Ok(false);
+    }
+    ledger_services::post_payment(conn, transaction).await?;
+    if transaction.product_type == subscription_services::PRODUCT_TYPE {
+        // Announced by the subscription the payment is for
+        subscription_services::invoice_paid(conn, transaction).await?;
+        return Ok(true);
+    }
+    if transaction.product_type == transfer_services::PRODUCT_TYPE {
+        transfer_services::notify_settled(conn, transaction, None).await?;
+        return Ok(true);
+    }
+    if transaction.product_type == escrow_services::FUNDING_PRODUCT_TYPE {
+        escrow_services::funding_settled(conn, transaction, None).await?;
+        return Ok(true);
+    }
+    if transaction.product_type == escrow_services::PAYOUT_PRODUCT_TYPE {
+        escrow_services::payout_settled(conn, transaction, None).await?;
+        return Ok(true);
+    }
+    if transaction.product_type == payout_services::PRODUCT_TYPE {
+        payout_services::payout_settled(conn, transaction, None).await?;
+        return Ok(true);
+    }
+
+    unlock_product(conn, transaction.user_id, &transaction.product_type, transaction.id).await?;
+    notify_user(
+        conn,
+        transaction.user_id,
+        "payment_completed",
+        "Payment received",
+        &format!(
+            "Your payment of {:.2} {} went through and {} is unlocked.",
+            transaction.amount,
+            transaction.currency.to_uppercase(),
+            transaction.product_type.replace('_', " ")
+        ),
+        serde_json::json!({ "transaction_id": transaction.id, "product_type": transaction.product_type }),
+    )
+    .await?;
+    Ok(true)
+}
+
+/// Mark a pending transaction failed. False when it was no longer pending.
+pub async fn fail_transaction(conn: &mut PgConnection, transaction: &Transaction, reason: &str) -> ApiResult<bool> {
+    let updated = sqlx::query(
+        "UPDATE transactions SET status = 'failed', failure_reason = $2 WHERE id = $1 AND status = 'pending'",
+    )
+    .bind(transaction.id)
+    .bind(reason)
+    .execute(&mut *conn)
+    .await?;
+    if updated.rows_affected() == 0 {
+        return Ok(false);
+    }
+    if transaction.product_type == subscription_services::PRODUCT_TYPE {
+        subscription_services::invoice_failed(conn, transaction, reason).await?;
+    }
+    if transaction.product_type == transfer_services::PRODUCT_TYPE {
+        transfer_services::notify_settled(conn, transaction, Some(reason)).await?;
+        return Ok(true);
+    }
+    if transaction.product_type == escrow_services::FUNDING_PRODUCT_TYPE {
+        escrow_services::funding_settled(conn, transaction, Some(reason)).await?;
+        return Ok(true);
+    }
+    if transaction.product_type == escrow_services::PAYOUT_PRODUCT_TYPE {
+        escrow_services::payout_settled(conn, transaction, Some(reason)).await?;
+        return Ok(true);
+    }
+    if transaction.product_type == payout_services::PRODUCT_TYPE {
+        payout_services::payout_settled(conn, transaction, Some(reason)).await?;
+        return Ok(true);
+    }
+
+    notify_user(
+        conn,
+        transaction.user_id,
+        "payment_failed",
+        "Payment failed",
+        &format!("Your payment for {} did not go through: {}", transaction.product_type.replace('_', " "), reason),
+        serde_json::json!({ "transaction_id": transaction.id, "product_type": transaction.product_type }),
+    )
+    .await?;
+    Ok(true)
+}
+
+/// Grant the buyer the product; buying it again keeps the original grant. A software license
+/// also makes the account premium.
+pub async fn unlock_product(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    product_type: &str,
+    transaction_id: Uuid,
+) -> ApiResult<()> {
+    sqlx::query(
+        "INSERT INTO product_entitlements (user_id, product_type, transaction_id) VALUES ($1, $2, $3) \
+         ON CONFLICT (user_id, product_type) DO NOTHING",
+    )
+    .bind(user_id)
+    .bind(product_type)
+    .bind(transaction_id)
+    .execute(&mut *conn)
+    .await?;
+
+    if product_type == "software_license" {
+        sqlx::query("UPDATE users SET is_premium = TRUE, updated_at = NOW() WHERE id = $1")
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Take back what a refunded transaction unlocked, unless another paid purchase of the same
+/// product still covers it
+pub async fn revoke_product(conn: &mut PgConnection, transaction: &Transaction) -> ApiResult<()> {
+    let revoked = sqlx::query("DELETE FROM product_entitlements WHERE transaction_id = $1")
+        .bind(transaction.id)
+        .execute(&mut *conn)
+        .await?;
+    if revoked.rows_affected() == 0 {
+        return Ok(());
+    }
+
+    let other: Option<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM transactions WHERE user_id = $1 AND product_type = $2 AND id <> $3 \
+         AND status IN ('completed', 'partially_refunded') ORDER BY created_at LIMIT 1",
+    )
+    .bind(transaction.user_id)
+    .bind(&transaction.product_type)
+    .bind(transaction.id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    match other {
+        Some(other) => unlock_product(conn, transaction.user_id, &transaction.product_type, other).await?,
+        None if transaction.product_type == "software_license" => sync_premium(conn, transaction.user_id).await?,
+        None => {}
+    }
+    Ok(())
+}
+
+/// Premium comes with a software license that is not frozen by a dispute or a subscription
+/// that is paid up or being retried
+pub async fn sync_premium(conn: &mut PgConnection, user_id: Uuid) -> ApiResult<()> {
+    sqlx::query(
+        "UPDATE users SET is_premium = \
+         EXISTS (SELECT 1 FROM product_entitlements WHERE user_id = $1 AND product_type = 'software_license' \
+         AND frozen_at IS NULL) \
+         OR EXISTS (SELECT 1 FROM subscriptions WHERE user_id = $1 AND status IN ('active', 'past_due')), \
+         updated_at = NOW() WHERE id = $1",
+    )
+    .bind(user_id)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minor_units() {
+        assert_eq!(minor_units(Decimal::new(16, 1), "usd"), 160);
+        assert_eq!(minor_units(Decimal::new(1999, 2), "EUR"), 1999);
+        assert_eq!(minor_units(Decimal::new(10005, 3), "usd"), 1001);
+        assert_eq!(minor_units(Decimal::from(500), "JPY"), 500);
+        assert_eq!(from_minor_units(1999, "eur"), Decimal::new(1999, 2));
+        assert_eq!(from_minor_units(500, "jpy"), Decimal::from(500));
+    }
+
+    #[test]
+    fn test_validate_product_type() {
+        assert!(validate_product_type("documentation").is_ok());
+        assert!(validate_product_type("gift_card").is_err());
+    }
+}
@@ -0,0 +1,771 @@
+//! Payouts of platform credits. Credits are entries in `credit_entries` whose sum is a user's
+//! balance. A user with a verified identity requests a payout, which takes its amount off the
+//! balance at once; an admin approves or rejects it. Approved Stripe payouts are transferred to
+//! the user's Connect account straight away, or settled by the reconciliation job when Stripe's
+//! answer was lost. Crypto payouts are sent by the relayer and settled by the confirmation
+//! watcher, while bank payouts are sent outside the platform and marked paid by an admin. A
+//! payout that is rejected, cancelled or fails puts its credits back.
+//!
+//! ```text
+//! requested -> approved -> processing -> paid
+//!     |            |            |
+//! rejected /    paid /       failed
+//! cancelled     failed
+//! ```
+
+use rust_decimal::Decimal;
+use sqlx::{PgConnection, PgPool};
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::payout::{
+    CreatePayoutRequest, CreditAdjustmentRequest, CreditBalance, CreditEntry, Payout, PayoutDetails, PayoutEvent,
+};
+use crate::models::transaction::Transaction;
+use crate::services::crypto_services::{decimal_amount, parse_units, BlockchainService};
+use crate::services::{kyc_services, ledger_services};
+use crate::services::notification_services::notify_user;
+use crate::services::payment_services::{fail_transaction, minor_units, TRANSACTION_COLUMNS};
+use crate::services::relayer_services::Relayer;
+use crate::services::stripe_services::{self, TransferOutcome};
+use crate::services::transfer_services::transfer_calldata;
+use crate::utils::log_blockchain_event;
+
+const PAYOUT_COLUMNS: &str = "id, user_id, amount, currency, method, destination, status, provider_reference, \
+     transaction_id, failure_reason, reviewed_by, created_at, updated_at";
+
+const EVENT_COLUMNS: &str = "id, payout_id, action, from_status, to_status, actor_id, note, created_at";
+
+const ENTRY_COLUMNS: &str = "id, user_id, amount, currency, source, payout_id, note, created_by, created_at";
+
+/// Product type of the on-chain transfer sending a crypto payout
+pub const PRODUCT_TYPE: &str = "credit_payout";
+
+pub const METHODS: &[&str] = &["bank", "stripe", "crypto"];
+pub const STATUSES: &[&str] = &["requested", "approved", "processing", "paid", "failed", "rejected", "cancelled"];
+
+const MAX_DESTINATION_CHARS: usize = 200;
+const MAX_NOTE_CHARS: usize = 2_000;
+/// Credit entries shown with the balance
+const RECENT_ENTRIES: i64 = 50;
+/// How long after sending a Stripe payout its idempotency key is still safe to retry with
+const STRIPE_IDEMPOTENCY_WINDOW_HOURS: i32 = 23;
+const JOB_INTERVAL_SECS: u64 = 300;
+
+/// Who is acting on a payout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    User,
+    Admin,
+    /// The provider calls and the confirmation watcher
+    System,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Approve,
+    Reject,
+    Cancel,
+    Send,
+    Paid,
+    Fail,
+}
+
+impl Action {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Action::Approve => "approve",
+            Action::Reject => "reject",
+            Action::Cancel => "cancel",
+            Action::Send => "send",
+            Action::Paid => "paid",
+            Action::Fail => "fail",
+        }
+    }
+}
+
+/// The status `action` by `role` moves a payout in `status` to, if it is allowed
+fn next_status(status: &str, action: Action, role: Role) -> Option<&'static str> {
+    use Action::*;
+    use Role::*;
+    let next = match (status, action, role) {
+        ("requested", Approve, Admin) => "approved",
+        ("requested", Reject, Admin) => "rejected",
+        ("requested", Cancel, User) => "cancelled",
+        ("approved", Send, System) => "processing",
+        // Bank payouts are confirmed by an admin, the others by the provider or the chain
+        ("approved", Paid, Admin | System) => "paid",
+        ("processing", Paid, System) => "paid",
+        ("approved", Fail, System) => "failed",
+        ("processing", Fail, System) => "failed",
+        _ => return None,
+    };
+    Some(next)
+}
+
+/// The status `action` by `role` moves a payout in `status` to. Forbidden when another party
+/// could take the action, a conflict when nobody can in this status.
+pub fn transition(status: &str, action: Action, role: Role) -> ApiResult<&'static str> {
+    if let Some(next) = next_status(status, action, role) {
+        return Ok(next);
+    }
+    if [Role::User, Role::Admin].iter().any(|r| next_status(status, action, *r).is_some()) {
+        return Err(ApiError::Forbidden(format!("You cannot {} this payout", action.as_str())));
+    }
+    Err(ApiError::Conflict(format!("Cannot {} a payout that is {}", action.as_str(), status)))
+}
+
+/// Whether a payout in `status` has given its credits back
+fn returns_credits(status: &str) -> bool {
+    matches!(status, "rejected" | "cancelled" | "failed")
+}
+
+fn checked_note(note: Option<&str>) -> ApiResult<Option<String>> {
+    let note = note.map(str::trim).filter(|n| !n.is_empty());
+    if note.is_some_and(|n| n.chars().count() > MAX_NOTE_CHARS) {
+        return Err(ApiError::ValidationError(format!("note must be at most {} characters", MAX_NOTE_CHARS)));
+    }
+    Ok(note.map(str::to_string))
+}
+
+/// The user's credit balance. Lock the user's row first when the balance decides a change.
+pub async fn balance(conn: &mut PgConnection, user_id: Uuid) -> ApiResult<Decimal> {
+    let balance: Decimal = sqlx::query_scalar("SELECT COALESCE(SUM(amount), 0) FROM credit_entries WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(conn)
+        .await?;
+    Ok(balance)
+}
+
+/// Add `amount` credits (negative to take them) to the user's balance, posted to the ledger
+#[allow(clippy::too_many_arguments)]
+pub async fn add_entry(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    amount: Decimal,
+    currency: &str,
+    source: &str,
+    payout_id: Option<Uuid>,
+    note: Option<&str>,
+    created_by: Option<Uuid>,
+) -> ApiResult<CreditEntry> {
+    let entry = sqlx::query_as::<_, CreditEntry>(&format!(
+        "INSERT INTO credit_entries (user_id, amount, currency, source, payout_id, note, created_by) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING {}",
+        ENTRY_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(amount)
+    .bind(currency)
+    .bind(source)
+    .bind(payout_id)
+    .bind(note)
+    .bind(created_by)
+    .fetch_one(&mut *conn)
+    .await?;
+    ledger_services::post_credit_entry(conn, &entry).await?;
+    Ok(entry)
+}
+
+/// Serialize changes to one user's balance
+async fn lock_balance(conn: &mut PgConnection, user_id: Uuid) -> ApiResult<()> {
+    sqlx::query("SELECT id FROM users WHERE id = $1 FOR UPDATE")
+        .bind(user_id)
+        .fetch_optional(conn)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+    Ok(())
+}
+
+/// The user's balance with their latest credit entries
+pub async fn credits(pool: &PgPool, config: &AppConfig, user_id: Uuid) -> ApiResult<CreditBalance> {
+    let mut conn = pool.acquire().await?;
+    let balance = balance(&mut conn, user_id).await?;
+    let entries = sqlx::query_as::<_, CreditEntry>(&format!(
+        "SELECT {} FROM credit_entries WHERE user_id = $1 ORDER BY id DESC LIMIT $2",
+        ENTRY_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(RECENT_ENTRIES)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(CreditBalance { balance, currency: config.credits_currency.clone(), entries })
+}
+
+/// Grant credits, or take them back with a negative amount, leaving the balance non-negative
+pub async fn adjust(
+    pool: &PgPool,
+    config: &AppConfig,
+    admin_id: Uuid,
+    request: &CreditAdjustmentRequest,
+) -> ApiResult<CreditEntry> {
+    if request.amount.is_zero() {
+        return Err(ApiError::ValidationError("amount must be a non-zero number".to_string()));
+    }
+    let source = request.source.as_deref().map(str::trim).unwrap_or("adjustment");
+    if source.is_empty() || source.len() > 32 || !source.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+        return Err(ApiError::ValidationError("source must be 1 to 32 lower-case letters or underscores".to_string()));
+    }
+    if source.starts_with("payout") {
+        return Err(ApiError::ValidationError("Payout entries are made by payouts".to_string()));
+    }
+    let note = checked_note(request.note.as_deref())?;
+
+    let mut tx = pool.begin().await?;
+    lock_balance(&mut tx, request.user_id).await?;
+    if balance(&mut tx, request.user_id).await? + request.amount < Decimal::ZERO {
+        return Err(ApiError::Conflict("The user's balance cannot go below zero".to_string()));
+    }
+    let entry = add_entry(
+        &mut tx,
+        request.user_id,
+        request.amount,
+        &config.credits_currency,
+        source,
+        None,
+        note.as_deref(),
+        Some(admin_id),
+    )
+    .await?;
+    if request.amount > Decimal::ZERO {
+        notify_user(
+            &mut tx,
+            request.user_id,
+            "credits.added",
+            "Credits added",
+            &format!("{:.2} {} in credits were added to your balance.", request.amount, entry.currency.to_uppercase()),
+            serde_json::json!({ "entry_id": entry.id }),
+        )
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(entry)
+}
+
+async fn record_event(
+    conn: &mut PgConnection,
+    payout_id: Uuid,
+    action: &str,
+    from_status: Option<&str>,
+    to_status: &str,
+    actor_id: Option<Uuid>,
+    note: Option<&str>,
+) -> ApiResult<()> {
+    sqlx::query(
+        "INSERT INTO payout_events (payout_id, action, from_status, to_status, actor_id, note) \
+         VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(payout_id)
+    .bind(action)
+    .bind(from_status)
+    .bind(to_status)
+    .bind(actor_id)
+    .bind(note)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Lock a payout for a state change
+async fn lock(conn: &mut PgConnection, payout_id: Uuid) -> ApiResult<Payout> {
+    sqlx::query_as::<_, Payout>(&format!("SELECT {} FROM payouts WHERE id = $1 FOR UPDATE", PAYOUT_COLUMNS))
+        .bind(payout_id)
+        .fetch_optional(conn)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Payout not found".to_string()))
+}
+
+/// Apply `action` by `role` to a locked payout, recording the event, putting the credits back
+/// when it ends unpaid and telling the user about changes they did not make
+async fn apply(
+    conn: &mut PgConnection,
+    payout: &Payout,
+    action: Action,
+    role: Role,
+    actor_id: Option<Uuid>,
+    note: Option<&str>,
+) -> ApiResult<Payout> {
+    let next = transition(&payout.status, action, role)?;
+    let updated = sqlx::query_as::<_, Payout>(&format!(
+        "UPDATE payouts SET status = $2, updated_at = NOW(), \
+             failure_reason = CASE WHEN $2 = 'failed' THEN $3 ELSE failure_reason END, \
+             reviewed_by = CASE WHEN $2 IN ('approved', 'rejected') THEN $4 ELSE reviewed_by END \
+         WHERE id = $1 RETURNING {}",
+        PAYOUT_COLUMNS
+    ))
+    .bind(payout.id)
+    .bind(next)
+    .bind(note)
+    .bind(actor_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    record_event(conn, payout.id, action.as_str(), Some(&payout.status), next, actor_id, note).await?;
+    if next == "paid" {
+        ledger_services::post_payout_paid(conn, &updated).await?;
+    }
+
+    if returns_credits(next) {
+        let reason = format!("Payout {}", next);
+        add_entry(
+            conn,
+            payout.user_id,
+            payout.amount,
+            &payout.currency,
+            "payout_reversal",
+            Some(payout.id),
+            Some(&reason),
+            None,
+        )
+        .await?;
+    }
+    if actor_id != Some(payout.user_id) && action != Action::Send {
+        let mut body = format!(
+            "Your payout of {:.2} {} is now {}.",
+            payout.amount,
+            payout.currency.to_uppercase(),
+            next
+        );
+        if returns_credits(next) {
+            body.push_str(" The credits are back in your balance.");
+        }
+        if let Some(note) = note {
+            body = format!("{} {}", body, note);
+        }
+        notify_user(
+            conn,
+            payout.user_id,
+            &format!("payout.{}", next),
+            &format!("Payout {}", next),
+            &body,
+            serde_json::json!({ "payout_id": payout.id, "status": next }),
+        )
+        .await?;
+    }
+    Ok(updated)
+}
+
+/// Request a payout of `amount` credits, taking them off the balance until it is paid or ends
+pub async fn request(
+    pool: &PgPool,
+    config: &AppConfig,
+    user_id: Uuid,
+    request: &CreatePayoutRequest,
+) -> ApiResult<Payout> {
+    if !METHODS.contains(&request.method.as_str()) {
+        return Err(ApiError::ValidationError(format!("method must be one of {}", METHODS.join(", "))));
+    }
+    if request.amount < config.payout_min_amount || request.amount <= Decimal::ZERO {
+        return Err(ApiError::ValidationError(format!(
+            "amount must be at least {} {}",
+            config.payout_min_amount,
+            config.credits_currency.to_uppercase()
+        )));
+    }
+    let destination = request.destination.as_deref().map(str::trim).filter(|d| !d.is_empty());
+
+    let mut tx = pool.begin().await?;
+    kyc_services::require_verified(&mut tx, user_id).await?;
+    let destination = match request.method.as_str() {
+        "crypto" => {
+            if config.payout_tokens_per_credit <= Decimal::ZERO {
+                return Err(ApiError::ServiceUnavailable("Crypto payouts are not offered".to_string()));
+            }
+            let wallet: Option<String> = sqlx::query_scalar("SELECT wallet_address FROM users WHERE id = $1")
+                .bind(user_id)
+                .fetch_one(&mut *tx)
+                .await?;
+            wallet.ok_or_else(|| ApiError::Conflict("Link a wallet to be paid in crypto".to_string()))?
+        }
+        "stripe" => match destination {
+            Some(account) if account.starts_with("acct_") && account.len() <= MAX_DESTINATION_CHARS => {
+                account.to_string()
+            }
+            _ => {
+                return Err(ApiError::ValidationError(
+                    "destination must be your Stripe connected account id (acct_...)".to_string(),
+                ));
+            }
+        },
+        _ => match destination {
+            Some(account) if account.chars().count() <= MAX_DESTINATION_CHARS => account.to_string(),
+            _ => {
+                return Err(ApiError::ValidationError(format!(
+                    "destination must be a bank account reference of 1 to {} characters",
+                    MAX_DESTINATION_CHARS
+                )));
+            }
+        },
+    };
+
+    lock_balance(&mut tx, user_id).await?;
+    if balance(&mut tx, user_id).await? < request.amount {
+        return Err(ApiError::Conflict("Not enough credits for this payout".to_string()));
+    }
+    let payout = sqlx::query_as::<_, Payout>(&format!(
+        "INSERT INTO payouts (id, user_id, amount, currency, method, destination) \
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING {}",
+        PAYOUT_COLUMNS
+    ))
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(request.amount)
+    .bind(&config.credits_currency)
+    .bind(&request.method)
+    .bind(&destination)
+    .fetch_one(&mut *tx)
+    .await?;
+    add_entry(&mut tx, user_id, -payout.amount, &payout.currency, "payout", Some(payout.id), None, None).await?;
+    record_event(&mut tx, payout.id, "request", None, &payout.status, Some(user_id), None).await?;
+    tx.commit().await?;
+    Ok(payout)
+}
+
+/// The user's payouts, newest first
+pub async fn list(pool: &PgPool, user_id: Uuid) -> ApiResult<Vec<Payout>> {
+    let payouts = sqlx::query_as::<_, Payout>(&format!(
+        "SELECT {} FROM payouts WHERE user_id = $1 ORDER BY created_at DESC LIMIT 200",
+        PAYOUT_COLUMNS
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(payouts)
+}
+
+pub async fn get_payout(pool: &PgPool, payout_id: Uuid) -> ApiResult<Payout> {
+    sqlx::query_as::<_, Payout>(&format!("SELECT {} FROM payouts WHERE id = $1", PAYOUT_COLUMNS))
+        .bind(payout_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Payout not found".to_string()))
+}
+
+/// One of the user's payouts
+pub async fn user_payout(pool: &PgPool, payout_id: Uuid, user_id: Uuid) -> ApiResult<Payout> {
+    let payout = get_payout(pool, payout_id).await?;
+    if payout.user_id != user_id {
+        return Err(ApiError::NotFound("Payout not found".to_string()));
+    }
+    Ok(payout)
+}
+
+pub async fn details(pool: &PgPool, payout: Payout) -> ApiResult<PayoutDetails> {
+    let events = sqlx::query_as::<_, PayoutEvent>(&format!(
+        "SELECT {} FROM payout_events WHERE payout_id = $1 ORDER BY id",
+        EVENT_COLUMNS
+    ))
+    .bind(payout.id)
+    .fetch_all(pool)
+    .await?;
+    Ok(PayoutDetails { payout, events })
+}
+
+/// Payouts in `status`, requested by default, longest waiting first
+pub async fn queue(pool: &PgPool, status: Option<&str>) -> ApiResult<Vec<Payout>> {
+    let status = status.unwrap_or("requested");
+    if !STATUSES.contains(&status) {
+        return Err(ApiError::ValidationError(format!("status must be one of {}", STATUSES.join(", "))));
+    }
+    let payouts = sqlx::query_as::<_, Payout>(&format!(
+        "SELECT {} FROM payouts WHERE status = $1 ORDER BY created_at LIMIT 200",
+        PAYOUT_COLUMNS
+    ))
+    .bind(status)
+    .fetch_all(pool)
+    .await?;
+    Ok(payouts)
+}
+
+/// Cancel a payout the user requested and no admin has reviewed yet
+pub async fn cancel(pool: &PgPool, payout_id: Uuid, user_id: Uuid) -> ApiResult<Payout> {
+    let mut tx = pool.begin().await?;
+    let payout = lock(&mut tx, payout_id).await?;
+    if payout.user_id != user_id {
+        return Err(ApiError::NotFound("Payout not found".to_string()));
+    }
+    let payout = apply(&mut tx, &payout, Action::Cancel, Role::User, Some(user_id), None).await?;
+    tx.commit().await?;
+    Ok(payout)
+}
+
+/// Reject a requested payout, or approve it and send Stripe and crypto payouts. A payout the
+/// provider refuses fails, putting the credits back, and is returned as the error.
+pub async fn review(
+    pool: &PgPool,
+    config: &AppConfig,
+    admin_id: Uuid,
+    payout_id: Uuid,
+    approve: bool,
+    note: Option<&str>,
+) -> ApiResult<Payout> {
+    let note = checked_note(note)?;
+    if !approve && note.is_none() {
+        return Err(ApiError::ValidationError("note is required when rejecting".to_string()));
+    }
+    let mut tx = pool.begin().await?;
+    let payout = lock(&mut tx, payout_id).await?;
+    let action = if approve { Action::Approve } else { Action::Reject };
+    let payout = apply(&mut tx, &payout, action, Role::Admin, Some(admin_id), note.as_deref()).await?;
+    tx.commit().await?;
+
+    match payout.method.as_str() {
+        "stripe" if approve => send_stripe(pool, config, payout).await,
+        "crypto" if approve => send_crypto(pool, config, payout).await,
+        _ => Ok(payout),
+    }
+}
+
+/// Fail an approved payout the provider refused
+async fn fail(pool: &PgPool, payout_id: Uuid, reason: &str) -> ApiResult<()> {
+    let mut tx = pool.begin().await?;
+    let payout = lock(&mut tx, payout_id).await?;
+    apply(&mut tx, &payout, Action::Fail, Role::System, None, Some(reason)).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Transfer the payout to the user's Connect account. The payout is marked processing before
+/// Stripe is called.
+async fn send_stripe(pool: &PgPool, config: &AppConfig, payout: Payout) -> ApiResult<Payout> {
+    let mut tx = pool.begin().await?;
+    let locked = lock(&mut tx, payout.id).await?;
+    let payout = apply(&mut tx, &locked, Action::Send, Role::System, None, None).await?;
+    tx.commit().await?;
+    settle_stripe(pool, config, payout).await
+}
+
+/// Request the processing payout's Stripe transfer and settle it. Only a definite refusal fails
+/// the payout and puts the credits back. When the outcome is unknown the payout stays
+/// processing for [`reconcile_stripe`], which asks again with the same idempotency key.
+async fn settle_stripe(pool: &PgPool, config: &AppConfig, payout: Payout) -> ApiResult<Payout> {
+    let amount = minor_units(payout.amount, &payout.currency);
+    let idempotency_key = format!("payout-{}", payout.id);
+    let outcome =
+        stripe_services::create_transfer(config, &payout.destination, amount, &payout.currency, &idempotency_key)
+            .await;
+
+    let mut tx = pool.begin().await?;
+    let locked = lock(&mut tx, payout.id).await?;
+    // Settled meanwhile by the request or the reconciliation job
+    if locked.status != "processing" {
+        return Ok(locked);
+    }
+    match outcome {
+        TransferOutcome::Created(transfer_id) => {
+            sqlx::query("UPDATE payouts SET provider_reference = $2 WHERE id = $1")
+                .bind(payout.id)
+                .bind(&transfer_id)
+                .execute(&mut *tx)
+                .await?;
+            let payout = apply(&mut tx, &locked, Action::Paid, Role::System, None, None).await?;
+            tx.commit().await?;
+            Ok(payout)
+        }
+        TransferOutcome::Rejected(reason) => {
+            apply(&mut tx, &locked, Action::Fail, Role::System, None, Some(&reason)).await?;
+            tx.commit().await?;
+            Err(ApiError::PaymentError(reason))
+        }
+        TransferOutcome::Unknown(reason) => {
+            tracing::warn!(payout_id = %payout.id, "Stripe payout outcome unknown, left to reconcile: {}", reason);
+            Ok(locked)
+        }
+    }
+}
+
+/// Settle Stripe payouts whose transfer outcome was unknown by asking again with their
+/// idempotency key. Stripe keeps keys for at least a day; older payouts are left for an admin
+/// to check against the Stripe dashboard rather than risk a second transfer.
+pub async fn reconcile_stripe(pool: &PgPool, config: &AppConfig) -> ApiResult<usize> {
+    let pending = sqlx::query_as::<_, Payout>(&format!(
+        "SELECT {} FROM payouts WHERE method = 'stripe' AND status = 'processing' \
+         AND updated_at < NOW() - INTERVAL '1 minute' \
+         AND updated_at > NOW() - make_interval(hours => $1) \
+         ORDER BY updated_at LIMIT 100",
+        PAYOUT_COLUMNS
+    ))
+    .bind(STRIPE_IDEMPOTENCY_WINDOW_HOURS)
+    .fetch_all(pool)
+    .await?;
+
+    let mut settled = 0;
+    for payout in pending {
+        match settle_stripe(pool, config, payout).await {
+            Ok(payout) if payout.status == "processing" => {}
+            Ok(_) | Err(ApiError::PaymentError(_)) => settled += 1,
+            Err(e) => tracing::error!("Stripe payout reconciliation failed: {}", e),
+        }
+    }
+    Ok(settled)
+}
+
+pub fn spawn_stripe_reconcile_job(pool: Arc<PgPool>, config: AppConfig) {
+    if !stripe_services::is_configured(&config) {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(JOB_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            match reconcile_stripe(&pool, &config).await {
+                Ok(0) => {}
+                Ok(settled) => tracing::info!(settled, "Reconciled Stripe payouts"),
+                Err(e) => tracing::error!("Stripe payout reconciliation job failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Send the payout's tokens from the relayer to the user's wallet; the confirmation watcher
+/// settles it
+async fn send_crypto(pool: &PgPool, config: &AppConfig, payout: Payout) -> ApiResult<Payout> {
+    let prepared = async {
+        if config.payout_tokens_per_credit <= Decimal::ZERO {
+            return Err(ApiError::ServiceUnavailable("Crypto payouts are not offered".to_string()));
+        }
+        let relayer = Relayer::from_config(config)?;
+        let chain = BlockchainService::for_chain_id(config, config.default_chain_id)?;
+        let token = chain.token_contract()?.to_string();
+        let (decimals, symbol) = chain.token_metadata(&token).await?;
+        let tokens = payout.amount * config.payout_tokens_per_credit;
+        let amount = format!("{:.*}", decimals.min(6) as usize, tokens);
+        let calldata = transfer_calldata(&payout.destination, parse_units(&amount, decimals)?)?;
+        let value = decimal_amount(&amount)?;
+        Ok((relayer, chain, token, symbol, amount, value, calldata))
+    }
+    .await;
+    let (relayer, chain, token, symbol, amount, value, calldata) = match prepared {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            fail(pool, payout.id, &e.to_string()).await?;
+            return Err(e);
+        }
+    };
+
+    let mut tx = pool.begin().await?;
+    let locked = lock(&mut tx, payout.id).await?;
+    let transaction = sqlx::query_as::<_, Transaction>(&format!(
+        "INSERT INTO transactions (id, user_id, amount, currency, payment_method, payment_id, status, product_type, \
+         chain_id) VALUES ($1, $2, $3, $4, 'crypto', $5, 'pending', $6, $7) RETURNING {}",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(Uuid::new_v4())
+    .bind(payout.user_id)
+    .bind(value)
+    .bind(&symbol)
+    .bind(format!("payout_{}", payout.id))
+    .bind(PRODUCT_TYPE)
+    .bind(chain.chain_id() as i64)
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query("UPDATE payouts SET transaction_id = $2 WHERE id = $1")
+        .bind(payout.id)
+        .bind(transaction.id)
+        .execute(&mut *tx)
+        .await?;
+    let payout = apply(&mut tx, &locked, Action::Send, Role::System, None, None).await?;
+    // Recorded before sending, so a payout the node accepted is never lost
+    tx.commit().await?;
+
+    match relayer.send(&chain, &token, calldata, 0).await {
+        Ok(tx_hash) => {
+            sqlx::query("UPDATE transactions SET blockchain_tx_hash = $2 WHERE id = $1")
+                .bind(transaction.id)
+                .bind(&tx_hash)
+                .execute(pool)
+                .await?;
+            log_blockchain_event("credit_payout", Some(&tx_hash), amount.parse().ok(), "submitted");
+            Ok(payout)
+        }
+        Err(e) => {
+            // Fails the payout too, through payout_settled
+            let mut tx = pool.begin().await?;
+            fail_transaction(&mut tx, &transaction, &e.to_string()).await?;
+            tx.commit().await?;
+            log_blockchain_event("credit_payout", None, amount.parse().ok(), "failed");
+            Err(e)
+        }
+    }
+}
+
+/// Mark an approved bank payout paid with the bank's transfer reference
+pub async fn mark_paid(pool: &PgPool, admin_id: Uuid, payout_id: Uuid, reference: &str) -> ApiResult<Payout> {
+    let reference = reference.trim();
+    if reference.is_empty() || reference.chars().count() > MAX_DESTINATION_CHARS {
+        return Err(ApiError::ValidationError(format!(
+            "reference must be 1 to {} characters",
+            MAX_DESTINATION_CHARS
+        )));
+    }
+    let mut tx = pool.begin().await?;
+    let payout = lock(&mut tx, payout_id).await?;
+    if payout.method != "bank" {
+        return Err(ApiError::Conflict(format!("{} payouts are confirmed by the provider", payout.method)));
+    }
+    sqlx::query("UPDATE payouts SET provider_reference = $2 WHERE id = $1")
+        .bind(payout.id)
+        .bind(reference)
+        .execute(&mut *tx)
+        .await?;
+    let payout = apply(&mut tx, &payout, Action::Paid, Role::Admin, Some(admin_id), None).await?;
+    tx.commit().await?;
+    Ok(payout)
+}
+
+/// Mark the payout a crypto transfer belongs to paid, or failed with its credits put back;
+/// called when the watcher settles the transaction
+pub async fn payout_settled(
+    conn: &mut PgConnection,
+    transaction: &Transaction,
+    failure: Option<&str>,
+) -> ApiResult<()> {
+    let payout_id: Option<Uuid> = sqlx::query_scalar("SELECT id FROM payouts WHERE transaction_id = $1")
+        .bind(transaction.id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    let Some(payout_id) = payout_id else {
+        return Ok(());
+    };
+    let payout = lock(conn, payout_id).await?;
+    let action = if failure.is_some() { Action::Fail } else { Action::Paid };
+    if next_status(&payout.status, action, Role::System).is_none() {
+        tracing::warn!(payout_id = %payout.id, status = %payout.status, "Payout settled out of turn");
+        return Ok(());
+    }
+    apply(conn, &payout, action, Role::System, None, failure).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transition() {
+        assert_eq!(transition("requested", Action::Approve, Role::Admin).unwrap(), "approved");
+        assert_eq!(transition("requested", Action::Cancel, Role::User).unwrap(), "cancelled");
+        assert_eq!(transition("approved", Action::Send, Role::System).unwrap(), "processing");
+        assert_eq!(transition("approved", Action::Paid, Role::Admin).unwrap(), "paid");
+        assert_eq!(transition("processing", Action::Fail, Role::System).unwrap(), "failed");
+    }
+
+    #[test]
+    fn test_transition_refusals() {
+        // Only an admin approves, and only the user cancels
+        assert!(matches!(transition("requested", Action::Approve, Role::User), Err(ApiError::Forbidden(_))));
+        assert!(matches!(transition("requested", Action::Cancel, Role::Admin), Err(ApiError::Forbidden(_))));
+        // Once approved the user can no longer cancel, and a sent payout is settled by the chain
+        assert!(matches!(transition("approved", Action::Cancel, Role::User), Err(ApiError::Conflict(_))));
+        assert!(matches!(transition("processing", Action::Paid, Role::Admin), Err(ApiError::Conflict(_))));
+        assert!(matches!(transition("paid", Action::Fail, Role::System), Err(ApiError::Conflict(_))));
+    }
+
+    #[test]
+    fn test_returns_credits() {
+        assert!(returns_credits("rejected"));
+        assert!(returns_credits("failed"));
+        assert!(!returns_credits("paid"));
+        assert!(!returns_credits("processing"));
+    }
+}
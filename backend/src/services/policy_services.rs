@@ -0,0 +1,137 @@
+//! Org role policy: which roles may perform which actions inside an organization
+
+/// Actions that can be performed within an organization
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrgAction {
+    ViewMembers,
+    ManageMembers,
+    ManageSso,
+    ManageIntegrations,
+    ManageDevices,
+    ReadAuditLogs,
+    ReadTransactions,
+    ReadDeviceHistory,
+    ExportData,
+    ReviewSessions,
+    ManageSessions,
+    ManagePrivacy,
+    ManageFirmware,
+    ManageMetrics,
+    ManageBackups,
+    ManageRegions,
+    ViewSecrets,
+}
+
+impl OrgAction {
+    pub const ALL: &'static [OrgAction] = &[
+        OrgAction::ViewMembers,
+        OrgAction::ManageMembers,
+        OrgAction::ManageSso,
+        OrgAction::ManageIntegrations,
+        OrgAction::ManageDevices,
+        OrgAction::ReadAuditLogs,
+        OrgAction::ReadTransactions,
+        OrgAction::ReadDeviceHistory,
+        OrgAction::ExportData,
+        OrgAction::ReviewSessions,
+        OrgAction::ManageSessions,
+        OrgAction::ManagePrivacy,
+        OrgAction::ManageFirmware,
+        OrgAction::ManageMetrics,
+        OrgAction::ManageBackups,
+        OrgAction::ManageRegions,
+        OrgAction::ViewSecrets,
+    ];
+
+    /// Whether the action changes state
+    pub fn is_mutation(self) -> bool {
+        matches!(
+            self,
+            OrgAction::ManageMembers
+                | OrgAction::ManageSso
+                | OrgAction::ManageIntegrations
+                | OrgAction::ManageDevices
+                | OrgAction::ManageSessions
+                | OrgAction::ManagePrivacy
+                | OrgAction::ManageFirmware
+                | OrgAction::ManageMetrics
+                | OrgAction::ManageBackups
+                | OrgAction::ManageRegions
+        )
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OrgAction::ViewMembers => "view_members",
+            OrgAction::ManageMembers => "manage_members",
+            OrgAction::ManageSso => "manage_sso",
+            OrgAction::ManageIntegrations => "manage_integrations",
+            OrgAction::ManageDevices => "manage_devices",
+            OrgAction::ReadAuditLogs => "read_audit_logs",
+            OrgAction::ReadTransactions => "read_transactions",
+            OrgAction::ReadDeviceHistory => "read_device_history",
+            OrgAction::ExportData => "export_data",
+            OrgAction::ReviewSessions => "review_sessions",
+            OrgAction::ManageSessions => "manage_sessions",
+            OrgAction::ManagePrivacy => "manage_privacy",
+            OrgAction::ManageFirmware => "manage_firmware",
+            OrgAction::ManageMetrics => "manage_metrics",
+            OrgAction::ManageBackups => "manage_backups",
+            OrgAction::ManageRegions => "manage_regions",
+            OrgAction::ViewSecrets => "view_secrets",
+        }
+    }
+}
+
+/// Evaluate whether an org role may perform an action
+pub fn role_allows(role: &str, action: OrgAction) -> bool {
+    match role {
+        "owner" | "admin" => true,
+        "member" => matches!(
+            action,
+            OrgAction::ViewMembers | OrgAction::ManageDevices | OrgAction::ReadDeviceHistory
+        ),
+        // Auditors see everything that is not a secret, and change nothing
+        "auditor" => !action.is_mutation() && action != OrgAction::ViewSecrets,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auditor_can_read_and_export() {
+        for action in [
+            OrgAction::ViewMembers,
+            OrgAction::ReadAuditLogs,
+            OrgAction::ReadTransactions,
+            OrgAction::ReadDeviceHistory,
+            OrgAction::ExportData,
+        ] {
+            assert!(role_allows("auditor", action), "auditor should be allowed to {}", action.as_str());
+        }
+    }
+
+    #[test]
+    fn test_auditor_cannot_mutate_or_view_secrets() {
+        for action in OrgAction::ALL.iter().copied().filter(|a| a.is_mutation()) {
+            assert!(!role_allows("auditor", action), "auditor must not be allowed to {}", action.as_str());
+        }
+        assert!(!role_allows("auditor", OrgAction::ViewSecrets));
+    }
+
+    #[test]
+    fn test_member_and_admin_policies() {
+        assert!(role_allows("member", OrgAction::ManageDevices));
+        assert!(!role_allows("member", OrgAction::ReadAuditLogs));
+        assert!(!role_allows("member", OrgAction::ExportData));
+
+        for action in OrgAction::ALL {
+            assert!(role_allows("admin", *action));
+            assert!(role_allows("owner", *action));
+            assert!(!role_allows("unknown", *action));
+        }
+    }
+}
@@ -0,0 +1,475 @@
+//! User-supplied telemetry processors: small WASM modules run on each incoming sample
+//! to filter it, add derived metrics or raise alerts.
+//!
+//! Modules run in an interpreter with no host imports, a fuel budget bounding CPU time and
+//! a memory cap. The ABI is JSON in, JSON out:
+//! - export `memory`, `alloc(len: i32) -> i32` and `process(ptr: i32, len: i32) -> i64`
+//! - the input is `{"device_id": ..., "telemetry": {...}}`, written to the buffer `alloc` returned
+//! - `process` returns `(ptr << 32) | len` of an output document, or 0 for no changes
+//! - output: `{"drop": bool, "metrics": {"name": number}, "alerts": [{"severity", "message"}]}`
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgPool};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+use wasmi::core::{TrapCode, ValType};
+use wasmi::{Config, Engine, ExternType, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+use crate::errors::{ApiError, ApiResult};
+use crate::services::incident_services::{opens_incident, record_alert};
+use crate::services::notification_services::notify_user;
+
+pub const PROCESSOR_COLUMNS: &str = "id, user_id, name, description, module_sha256, module_size, device_ids, \
+     enabled, invocations, consecutive_failures, last_error, last_run_at, created_at, updated_at";
+
+pub const MAX_MODULE_BYTES: usize = 512 * 1024;
+pub const MAX_PROCESSORS_PER_USER: i64 = 20;
+/// Instructions (roughly) one invocation may execute
+pub const FUEL_PER_RUN: u64 = 5_000_000;
+/// Linear memory a module may grow to
+pub const MAX_MEMORY_BYTES: usize = 16 * 1024 * 1024;
+const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+const MAX_METRICS: usize = 32;
+const MAX_ALERTS: usize = 5;
+/// A processor that fails this many runs in a row is disabled
+pub const MAX_CONSECUTIVE_FAILURES: i32 = 10;
+/// Compiled modules kept in memory, keyed by content hash
+const MODULE_CACHE_SIZE: usize = 256;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProcessorAlert {
+    pub severity: String, // info, warning, critical
+    pub message: String,
+}
+
+/// What a processor asked for on one sample
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ProcessorOutput {
+    #[serde(default)]
+    pub drop: bool,
+    #[serde(default)]
+    pub metrics: BTreeMap<String, f64>,
+    #[serde(default)]
+    pub alerts: Vec<ProcessorAlert>,
+}
+
+/// Result of running a device's processors over one sample
+#[derive(Debug, Default, Serialize)]
+pub struct PipelineResult {
+    /// Name of the processor that filtered the sample out
+    pub dropped_by: Option<String>,
+    pub alerts: usize,
+    pub failed: Vec<String>,
+    /// Incidents newly opened by critical alerts on this sample
+    pub opened_incidents: Vec<OpenedIncident>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OpenedIncident {
+    pub id: Uuid,
+    pub message: String,
+}
+
+pub fn decode_module(encoded: &str) -> ApiResult<Vec<u8>> {
+    let bytes = BASE64
+        .decode(encoded.trim())
+        .map_err(|_| ApiError::ValidationError("module must be base64-encoded".to_string()))?;
+    if bytes.is_empty() || bytes.len() > MAX_MODULE_BYTES {
+        return Err(ApiError::ValidationError(format!(
+            "module must be between 1 byte and {} KiB",
+            MAX_MODULE_BYTES / 1024
+        )));
+    }
+    Ok(bytes)
+}
+
+pub fn module_sha256(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+pub fn validate_output(output: &ProcessorOutput) -> Result<(), String> {
+    if output.metrics.len() > MAX_METRICS {
+        return Err(format!("at most {} metrics per sample", MAX_METRICS));
+    }
+    for (name, value) in &output.metrics {
+        let valid_name = !name.is_empty()
+            && name.len() <= 64
+            && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !valid_name {
+            return Err(format!("metric name '{}' must be 1-64 of [a-z0-9_]", name));
+        }
+        if !value.is_finite() {
+            return Err(format!("metric '{}' is not a finite number", name));
+        }
+    }
+    if output.alerts.len() > MAX_ALERTS {
+        return Err(format!("at most {} alerts per sample", MAX_ALERTS));
+    }
+    for alert in &output.alerts {
+        if !matches!(alert.severity.as_str(), "info" | "warning" | "critical") {
+            return Err(format!("alert severity '{}' must be info, warning or critical", alert.severity));
+        }
+        if alert.message.trim().is_empty() || alert.message.len() > 500 {
+            return Err("alert message must be 1-500 characters".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Sandboxed WASM engine shared by all processors
+pub struct ProcessorRuntime {
+    engine: Engine,
+    modules: Mutex<HashMap<String, Arc<Module>>>,
+}
+
+impl Default for ProcessorRuntime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProcessorRuntime {
+    pub fn new() -> Self {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        Self { engine: Engine::new(&config), modules: Mutex::new(HashMap::new()) }
+    }
+
+    /// Compile a module and check it against the processor ABI
+    pub fn compile(&self, bytes: &[u8]) -> ApiResult<Module> {
+        let module = Module::new(&self.engine, bytes)
+            .map_err(|e| ApiError::ValidationError(format!("module is not valid WebAssembly: {}", e)))?;
+
+        if let Some(import) = module.imports().next() {
+            return Err(ApiError::ValidationError(format!(
+                "module imports {}::{}; processors may not import anything",
+                import.module(),
+                import.name()
+            )));
+        }
+        let exports: HashMap<&str, ExternType> = module.exports().map(|e| (e.name(), e.ty().clone())).collect();
+        let func_is = |name: &str, params: &[ValType], results: &[ValType]| {
+            matches!(exports.get(name), Some(ExternType::Func(f)) if f.params() == params && f.results() == results)
+        };
+        if !matches!(exports.get("memory"), Some(ExternType::Memory(_))) {
+            return Err(ApiError::ValidationError("module must export its memory as 'memory'".to_string()));
+        }
+        if !func_is("alloc", &[ValType::I32], &[ValType::I32]) {
+            return Err(ApiError::ValidationError("module must export alloc(i32) -> i32".to_string()));
+        }
+        if !func_is("process", &[ValType::I32, ValType::I32], &[ValType::I64]) {
+            return Err(ApiError::ValidationError("module must export process(i32, i32) -> i64".to_string()));
+        }
+        Ok(module)
+    }
+
+    fn cached(&self, sha256: &str) -> Option<Arc<Module>> {
+        self.modules.lock().ok()?.get(sha256).cloned()
+    }
+
+    fn cache(&self, sha256: &str, module: Arc<Module>) {
+        if let Ok(mut modules) = self.modules.lock() {
+            if modules.len() >= MODULE_CACHE_SIZE {
+                modules.clear();
+            }
+            modules.insert(sha256.to_string(), module);
+        }
+    }
+
+    /// Run a module on one input document, off the async executor
+    pub async fn run(&self, module: Arc<Module>, input: &serde_json::Value) -> Result<ProcessorOutput, String> {
+        let input = serde_json::to_vec(input).map_err(|e| e.to_string())?;
+        let engine = self.engine.clone();
+        tokio::task::spawn_blocking(move || execute(&engine, &module, &input))
+            .await
+            .map_err(|e| format!("processor task failed: {}", e))?
+    }
+}
+
+fn describe(error: wasmi::Error) -> String {
+    match error.as_trap_code() {
+        Some(TrapCode::OutOfFuel) => "exceeded its CPU budget".to_string(),
+        Some(TrapCode::GrowthOperationLimited) => "exceeded its memory limit".to_string(),
+        _ => error.to_string(),
+    }
+}
+
+fn execute(engine: &Engine, module: &Module, input: &[u8]) -> Result<ProcessorOutput, String> {
+    let limits = StoreLimitsBuilder::new()
+        .memory_size(MAX_MEMORY_BYTES)
+        .memories(1)
+        .instances(1)
+        .tables(1)
+        .table_elements(10_000)
+        .trap_on_grow_failure(true)
+        .build();
+    let mut store: Store<StoreLimits> = Store::new(engine, limits);
+    store.limiter(|limits| limits);
+    store.set_fuel(FUEL_PER_RUN).map_err(|e| e.to_string())?;
+
+    let instance = Linker::<StoreLimits>::new(engine)
+        .instantiate(&mut store, module)
+        .and_then(|pre| pre.start(&mut store))
+        .map_err(describe)?;
+    let memory = instance.get_memory(&store, "memory").ok_or("module does not export memory")?;
+    let alloc = instance.get_typed_func::<i32, i32>(&store, "alloc").map_err(describe)?;
+    let process = instance.get_typed_func::<(i32, i32), i64>(&store, "process").map_err(describe)?;
+
+    let len = i32::try_from(input.len()).map_err(|_| "input too large")?;
+    let ptr = alloc.call(&mut store, len).map_err(describe)?;
+    memory
+        .write(&mut store, ptr as u32 as usize, input)
+        .map_err(|_| "alloc returned a buffer outside memory")?;
+
+    let packed = process.call(&mut store, (ptr, len)).map_err(describe)? as u64;
+    if packed == 0 {
+        return Ok(ProcessorOutput::default());
+    }
+    let (out_ptr, out_len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+    if out_len > MAX_OUTPUT_BYTES {
+        return Err(format!("output exceeds {} KiB", MAX_OUTPUT_BYTES / 1024));
+    }
+    let mut buffer = vec![0u8; out_len];
+    memory
+        .read(&store, out_ptr, &mut buffer)
+        .map_err(|_| "output points outside memory")?;
+
+    let output: ProcessorOutput =
+        serde_json::from_slice(&buffer).map_err(|e| format!("output is not a valid document: {}", e))?;
+    validate_output(&output)?;
+    Ok(output)
+}
+
+#[derive(Debug, FromRow)]
+struct ActiveProcessor {
+    id: Uuid,
+    name: String,
+    module_sha256: String,
+}
+
+/// Load (compiling on a cache miss) a stored processor's module
+pub async fn load_module(
+    pool: &PgPool,
+    runtime: &ProcessorRuntime,
+    id: Uuid,
+    sha256: &str,
+) -> ApiResult<Arc<Module>> {
+    if let Some(module) = runtime.cached(sha256) {
+        return Ok(module);
+    }
+    let bytes: Vec<u8> = sqlx::query_scalar("SELECT module FROM telemetry_processors WHERE id = $1")
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+    let module = Arc::new(runtime.compile(&bytes)?);
+    runtime.cache(sha256, module.clone());
+    Ok(module)
+}
+
+/// Run the owner's enabled processors for a device over a sample, in creation order.
+/// Metrics land under `derived.<processor name>` in the payload, so later processors see them.
+/// A failing processor is skipped rather than rejecting the sample.
+pub async fn apply(
+    pool: &PgPool,
+    runtime: &ProcessorRuntime,
+    user_id: Uuid,
+    device_id: Uuid,
+    payload: &mut serde_json::Value,
+) -> ApiResult<PipelineResult> {
+    let processors = sqlx::query_as::<_, ActiveProcessor>(
+        "SELECT id, name, module_sha256 FROM telemetry_processors \
+         WHERE user_id = $1 AND enabled AND (device_ids IS NULL OR $2 = ANY(device_ids)) \
+         ORDER BY created_at",
+    )
+    .bind(user_id)
+    .bind(device_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut result = PipelineResult::default();
+    for processor in processors {
+        let input = serde_json::json!({ "device_id": device_id, "telemetry": &*payload });
+        let outcome = match load_module(pool, runtime, processor.id, &processor.module_sha256).await {
+            Ok(module) => runtime.run(module, &input).await,
+            Err(e) => Err(e.to_string()),
+        };
+
+        let output = match outcome {
+            Ok(output) => output,
+            Err(error) => {
+                record_failure(pool, user_id, &processor, &error).await?;
+                result.failed.push(processor.name);
+                continue;
+            }
+        };
+        sqlx::query(
+            "UPDATE telemetry_processors SET invocations = invocations + 1, consecutive_failures = 0, \
+             last_run_at = NOW() WHERE id = $1",
+        )
+        .bind(processor.id)
+        .execute(pool)
+        .await?;
+
+        if !output.alerts.is_empty() {
+            let mut conn = pool.acquire().await?;
+            for alert in &output.alerts {
+                notify_user(
+                    &mut conn,
+                    user_id,
+                    "telemetry_alert",
+                    &format!("{} alert from {}", alert.severity, processor.name),
+                    &alert.message,
+                    serde_json::json!({
+                        "device_id": device_id,
+                        "processor_id": processor.id,
+                        "severity": alert.severity,
+                    }),
+                )
+                .await?;
+                if opens_incident(&alert.severity) {
+                    let title: String = alert.message.chars().take(200).collect();
+                    if let Some(id) =
+                        record_alert(&mut conn, user_id, device_id, processor.id, &alert.severity, &title).await?
+                    {
+                        result.opened_incidents.push(OpenedIncident { id, message: alert.message.clone() });
+                    }
+                }
+            }
+            result.alerts += output.alerts.len();
+        }
+        if !output.metrics.is_empty()
+            && let Some(object) = payload.as_object_mut()
+        {
+            let derived = object.entry("derived").or_insert_with(|| serde_json::json!({}));
+            derived[processor.name.as_str()] = serde_json::json!(output.metrics);
+        }
+        if output.drop {
+            result.dropped_by = Some(processor.name);
+            break;
+        }
+    }
+    Ok(result)
+}
+
+async fn record_failure(pool: &PgPool, user_id: Uuid, processor: &ActiveProcessor, error: &str) -> ApiResult<()> {
+    tracing::warn!(processor_id = %processor.id, error, "Telemetry processor failed");
+    let disabled: bool = sqlx::query_scalar(
+        "UPDATE telemetry_processors SET invocations = invocations + 1, \
+         consecutive_failures = consecutive_failures + 1, last_error = $2, last_run_at = NOW(), \
+         enabled = consecutive_failures + 1 < $3 WHERE id = $1 RETURNING NOT enabled",
+    )
+    .bind(processor.id)
+    .bind(error)
+    .bind(MAX_CONSECUTIVE_FAILURES)
+    .fetch_one(pool)
+    .await?;
+
+    if disabled {
+        let mut conn = pool.acquire().await?;
+        notify_user(
+            &mut conn,
+            user_id,
+            "telemetry_processor_disabled",
+            &format!("Telemetry processor {} was disabled", processor.name),
+            &format!("It failed {} runs in a row. Last error: {}", MAX_CONSECUTIVE_FAILURES, error),
+            serde_json::json!({ "processor_id": processor.id }),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A module whose `process` returns the given output document
+    fn module_returning(output: &str) -> String {
+        format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 16) "{}")
+                (func (export "alloc") (param i32) (result i32) (i32.const 4096))
+                (func (export "process") (param i32 i32) (result i64)
+                  (i64.or (i64.shl (i64.const 16) (i64.const 32)) (i64.const {}))))"#,
+            output.replace('"', "\\\""),
+            output.len()
+        )
+    }
+
+    async fn run_wat(source: &str) -> Result<ProcessorOutput, String> {
+        let runtime = ProcessorRuntime::new();
+        let module = runtime.compile(&wat::parse_str(source).unwrap()).map_err(|e| e.to_string())?;
+        let module = Arc::new(module);
+        runtime.run(module, &serde_json::json!({ "telemetry": { "battery_level": 50 } })).await
+    }
+
+    #[tokio::test]
+    async fn test_metrics_and_alerts_returned() {
+        let output = run_wat(&module_returning(
+            r#"{"metrics":{"temp_f":98.6},"alerts":[{"severity":"warning","message":"hot"}]}"#,
+        ))
+        .await
+        .unwrap();
+        assert_eq!(output.metrics["temp_f"], 98.6);
+        assert_eq!(output.alerts[0].message, "hot");
+        assert!(!output.drop);
+
+        let output = run_wat(&module_returning(r#"{"drop":true}"#)).await.unwrap();
+        assert!(output.drop);
+    }
+
+    #[tokio::test]
+    async fn test_cpu_and_memory_limits_enforced() {
+        let spin = r#"(module
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32) (i32.const 0))
+            (func (export "process") (param i32 i32) (result i64) (loop (br 0)) (i64.const 0)))"#;
+        assert_eq!(run_wat(spin).await.unwrap_err(), "exceeded its CPU budget");
+
+        let grow = r#"(module
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32) (i32.const 0))
+            (func (export "process") (param i32 i32) (result i64)
+              (drop (memory.grow (i32.const 1024))) (i64.const 0)))"#;
+        assert_eq!(run_wat(grow).await.unwrap_err(), "exceeded its memory limit");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_output_rejected() {
+        let err = run_wat(&module_returning(r#"{"metrics":{"Bad Name":1}}"#)).await.unwrap_err();
+        assert!(err.contains("Bad Name"), "{}", err);
+        let err = run_wat(&module_returning("not json")).await.unwrap_err();
+        assert!(err.contains("not a valid document"), "{}", err);
+    }
+
+    #[test]
+    fn test_abi_checked_at_upload() {
+        let runtime = ProcessorRuntime::new();
+        let importing = wat::parse_str(
+            r#"(module (import "env" "now" (func)) (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) (i32.const 0))
+                (func (export "process") (param i32 i32) (result i64) (i64.const 0)))"#,
+        )
+        .unwrap();
+        assert!(runtime.compile(&importing).unwrap_err().to_string().contains("env::now"));
+
+        let wrong_signature = wat::parse_str(
+            r#"(module (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) (i32.const 0))
+                (func (export "process") (param i32 i32) (result i32) (i32.const 0)))"#,
+        )
+        .unwrap();
+        assert!(runtime.compile(&wrong_signature).is_err());
+        assert!(runtime.compile(b"\0asm garbage").is_err());
+    }
+
+    #[test]
+    fn test_decode_module() {
+        assert!(decode_module("AGFzbQEAAAA=").is_ok());
+        assert!(decode_module("not base64!").is_err());
+        assert!(decode_module("").is_err());
+    }
+}
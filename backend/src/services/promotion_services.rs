@@ -0,0 +1,663 @@
+//! Simulation-to-production promotion: a macro or fleet mission proven on simulated devices
+//! is re-planned for the production devices it maps to, checked against their real specs
+//! (firmware, parameter ranges, geofence, battery) and diffed before an admin approves it.
+
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use std::collections::HashMap;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::mission::{CreateLegRequest, LegAction, MissionLeg, Waypoint};
+use crate::models::promotion::CommandMacro;
+use crate::models::swarm::GeoBounds;
+use crate::services::attachment_services::{require_attachment, ATTACHED_TYPES_SQL};
+use crate::services::constraint_services;
+use crate::services::mission_services::{leg_commands, CommandPlan, LEG_COLUMNS};
+use crate::services::path_services::max_speed_mps;
+use crate::services::robotics_services::{
+    device_type_spec, CommandParams, RoboticsService, BATTERY_RESERVE_LEVEL, MAX_COMMAND_DURATION_MS,
+};
+use crate::services::transport_services::{self, OutboundCommand, TransportRegistry};
+
+/// Steps one macro may hold
+pub const MAX_MACRO_STEPS: usize = 200;
+
+pub const MACRO_COLUMNS: &str = "id, user_id, name, device_type, steps, created_at, updated_at";
+
+pub const PROMOTION_COLUMNS: &str = "id, user_id, source_kind, source_id, device_map, geofence, status, checks, \
+     diff, reviewed_by, reviewed_at, review_note, executed_ref, created_at";
+
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// A device as the promotion checks see it
+#[derive(Debug, Clone, FromRow)]
+pub struct DeviceProfile {
+    pub id: Uuid,
+    pub device_type: String,
+    pub firmware_version: String,
+    pub transport: String,
+    pub last_latitude: Option<f64>,
+    pub last_longitude: Option<f64>,
+    pub battery_level: Option<i16>,
+    /// Actual / estimated drain over recent acked commands
+    pub drain_correction: Option<f64>,
+    /// Enabled attachment types
+    pub attachments: Vec<String>,
+}
+
+impl DeviceProfile {
+    pub fn is_simulated(&self) -> bool {
+        self.transport == "simulated"
+    }
+
+    pub fn position(&self) -> Option<(f64, f64)> {
+        self.last_latitude.zip(self.last_longitude)
+    }
+
+    fn correction(&self) -> f64 {
+        self.drain_correction.unwrap_or(1.0).clamp(0.5, 3.0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PromotionCheck {
+    pub device_id: Uuid,
+    pub check: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl PromotionCheck {
+    fn new(device_id: Uuid, check: &'static str, passed: bool, detail: impl Into<String>) -> Self {
+        Self { device_id, check, passed, detail: detail.into() }
+    }
+}
+
+/// What a command plan costs on a given device
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct PlanEstimate {
+    pub commands: usize,
+    pub duration_ms: u64,
+    /// Battery percent, scaled by the device's observed drain correction
+    pub battery_drain: f64,
+    /// Furthest the plan can carry the device from where it starts (meters)
+    pub reach_m: f64,
+}
+
+/// Checks and diff for one promotion request
+#[derive(Debug, Serialize)]
+pub struct Evaluation {
+    pub passed: bool,
+    pub checks: Vec<PromotionCheck>,
+    pub diff: Vec<serde_json::Value>,
+}
+
+pub fn bounds_contain(bounds: &GeoBounds, latitude: f64, longitude: f64) -> bool {
+    (bounds.min_lat..=bounds.max_lat).contains(&latitude) && (bounds.min_lng..=bounds.max_lng).contains(&longitude)
+}
+
+/// Whether a circle of `radius_m` around a point stays inside the bounds
+pub fn circle_within(bounds: &GeoBounds, latitude: f64, longitude: f64, radius_m: f64) -> bool {
+    let d_lat = radius_m / METERS_PER_DEGREE;
+    let d_lng = radius_m / (METERS_PER_DEGREE * latitude.to_radians().cos().max(1e-6));
+    bounds_contain(bounds, latitude - d_lat, longitude - d_lng) && bounds_contain(bounds, latitude + d_lat, longitude + d_lng)
+}
+
+pub fn validate_geofence(bounds: &GeoBounds) -> ApiResult<()> {
+    let in_range = (-90.0..=90.0).contains(&bounds.min_lat)
+        && (-90.0..=90.0).contains(&bounds.max_lat)
+        && (-180.0..=180.0).contains(&bounds.min_lng)
+        && (-180.0..=180.0).contains(&bounds.max_lng);
+    if !in_range || bounds.min_lat >= bounds.max_lat || bounds.min_lng >= bounds.max_lng {
+        return Err(ApiError::ValidationError("geofence must be a non-empty area within valid coordinates".to_string()));
+    }
+    Ok(())
+}
+
+pub fn validate_macro_steps(device_type: &str, steps: &[LegAction]) -> ApiResult<()> {
+    let spec = device_type_spec(device_type)
+        .ok_or_else(|| ApiError::ValidationError(format!("Unknown device type: {}", device_type)))?;
+    if steps.is_empty() || steps.len() > MAX_MACRO_STEPS {
+        return Err(ApiError::ValidationError(format!("A macro needs 1-{} steps", MAX_MACRO_STEPS)));
+    }
+    let service = RoboticsService::new();
+    for (i, step) in steps.iter().enumerate() {
+        // Firmware gating is checked against each device the macro runs on
+        if !spec.commands.iter().any(|c| c.command == step.command) {
+            return Err(ApiError::ValidationError(format!(
+                "Step {}: '{}' is not a {} command",
+                i, step.command, device_type
+            )));
+        }
+        service
+            .parse_command_params(&step.command, &step.parameters)
+            .map_err(|e| ApiError::ValidationError(format!("Step {}: {}", i, e)))?;
+    }
+    Ok(())
+}
+
+/// Steps as a command plan; missing parameters become an empty object
+pub fn macro_plan(steps: &[LegAction]) -> CommandPlan {
+    steps
+        .iter()
+        .map(|s| {
+            let params = if s.parameters.is_null() { serde_json::json!({}) } else { s.parameters.clone() };
+            (s.command.clone(), params)
+        })
+        .collect()
+}
+
+/// Check a plan against a production device's firmware and operating limits and estimate its cost.
+/// Returns the failing step messages (empty when every step is acceptable) and the estimate.
+pub fn check_steps(device: &DeviceProfile, plan: &CommandPlan) -> (Vec<String>, PlanEstimate) {
+    let service = RoboticsService::new();
+    let mut problems = Vec::new();
+    let mut estimate = PlanEstimate { commands: plan.len(), ..Default::default() };
+
+    for (i, (command, parameters)) in plan.iter().enumerate() {
+        let params = match service
+            .validate_command(&device.device_type, &device.firmware_version, command)
+            .and_then(|_| require_attachment(&device.device_type, command, &device.attachments))
+            .and_then(|_| service.parse_command_params(command, parameters))
+        {
+            Ok(params) => params,
+            Err(e) => {
+                problems.push(format!("step {} ({}): {}", i, command, e));
+                continue;
+            }
+        };
+        match &params {
+            CommandParams::Movement { speed, duration_ms, .. } => {
+                if *duration_ms > MAX_COMMAND_DURATION_MS {
+                    problems.push(format!("step {} ({}): duration exceeds the device limit", i, command));
+                }
+                estimate.reach_m += max_speed_mps(&device.device_type) * *speed as f64 * *duration_ms as f64 / 1000.0;
+            }
+            CommandParams::Rotation { degrees, .. } if degrees.abs() > 360.0 => {
+                problems.push(format!("step {} ({}): rotation must be within ±360°", i, command));
+            }
+            CommandParams::Hover { altitude } if !(2.0..=120.0).contains(altitude) => {
+                problems.push(format!("step {} ({}): altitude must be between 2 and 120 m", i, command));
+            }
+            _ => {}
+        }
+        estimate.duration_ms += service.estimate_duration_ms(&params);
+        estimate.battery_drain += service.estimate_battery_drain(command, &params) as f64 * device.correction();
+    }
+
+    (problems, estimate)
+}
+
+/// Production-readiness checks for one device's share of the promoted work
+pub fn device_checks(
+    device: &DeviceProfile,
+    problems: &[String],
+    estimate: &PlanEstimate,
+    waypoints: &[Waypoint],
+    geofence: Option<&GeoBounds>,
+) -> Vec<PromotionCheck> {
+    let mut checks = vec![
+        PromotionCheck::new(
+            device.id,
+            "production_device",
+            !device.is_simulated(),
+            format!("Transport is {}", device.transport),
+        ),
+        PromotionCheck::new(
+            device.id,
+            "parameter_ranges",
+            problems.is_empty(),
+            if problems.is_empty() { "All steps accepted".to_string() } else { problems.join("; ") },
+        ),
+    ];
+
+    if let Some(bounds) = geofence {
+        let check = match device.position() {
+            None => PromotionCheck::new(device.id, "geofence", false, "Device has not reported its position"),
+            Some((lat, lng)) => {
+                let outside = waypoints.iter().filter(|w| !bounds_contain(bounds, w.latitude, w.longitude)).count();
+                let passed = if waypoints.is_empty() {
+                    // Relative moves: the worst-case reach must stay inside
+                    circle_within(bounds, lat, lng, estimate.reach_m)
+                } else {
+                    bounds_contain(bounds, lat, lng) && outside == 0
+                };
+                let detail = if waypoints.is_empty() {
+                    format!("Worst-case reach {:.0} m from the current position", estimate.reach_m)
+                } else {
+                    format!("{} of {} waypoints outside the geofence", outside, waypoints.len())
+                };
+                PromotionCheck::new(device.id, "geofence", passed, detail)
+            }
+        };
+        checks.push(check);
+    }
+
+    checks.push(match device.battery_level {
+        Some(level) => {
+            let projected = level as f64 - estimate.battery_drain;
+            PromotionCheck::new(
+                device.id,
+                "battery",
+                projected >= BATTERY_RESERVE_LEVEL,
+                format!(
+                    "Battery {}% would end at {:.1}% (reserve {:.0}%)",
+                    level, projected, BATTERY_RESERVE_LEVEL
+                ),
+            )
+        }
+        None => PromotionCheck::new(device.id, "battery", false, "No battery telemetry"),
+    });
+
+    checks
+}
+
+/// Differences between running the work on the simulated device and on production
+pub fn device_diff(
+    simulated: &DeviceProfile,
+    production: &DeviceProfile,
+    sim_estimate: &PlanEstimate,
+    prod_estimate: &PlanEstimate,
+) -> serde_json::Value {
+    let mut changes = Vec::new();
+    let mut compare = |field: &str, sim: serde_json::Value, prod: serde_json::Value| {
+        if sim != prod {
+            changes.push(serde_json::json!({ "field": field, "simulated": sim, "production": prod }));
+        }
+    };
+    compare("device_type", simulated.device_type.as_str().into(), production.device_type.as_str().into());
+    compare("firmware_version", simulated.firmware_version.as_str().into(), production.firmware_version.as_str().into());
+    compare("commands", sim_estimate.commands.into(), prod_estimate.commands.into());
+    compare("duration_ms", sim_estimate.duration_ms.into(), prod_estimate.duration_ms.into());
+    compare(
+        "battery_drain",
+        round2(sim_estimate.battery_drain).into(),
+        round2(prod_estimate.battery_drain).into(),
+    );
+    compare("reach_m", round2(sim_estimate.reach_m).into(), round2(prod_estimate.reach_m).into());
+
+    serde_json::json!({
+        "simulated_device_id": simulated.id,
+        "production_device_id": production.id,
+        "changes": changes,
+    })
+}
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+pub async fn device_profiles(pool: &PgPool, user_id: Uuid, ids: &[Uuid]) -> ApiResult<HashMap<Uuid, DeviceProfile>> {
+    let rows = sqlx::query_as::<_, DeviceProfile>(&format!(
+        "SELECT d.id, d.device_type, d.firmware_version, d.transport, d.last_latitude, d.last_longitude, \
+                (SELECT battery_level FROM device_telemetry WHERE device_id = d.id \
+                 ORDER BY recorded_at DESC LIMIT 1) AS battery_level, \
+                (SELECT SUM(actual_battery_drain)::float8 / NULLIF(SUM(estimated_battery_drain), 0)::float8 \
+                 FROM (SELECT actual_battery_drain, estimated_battery_drain FROM device_commands \
+                       WHERE device_id = d.id AND status = 'succeeded' AND actual_battery_drain IS NOT NULL \
+                       ORDER BY acked_at DESC LIMIT 50) recent) AS drain_correction, \
+                {} AS attachments \
+         FROM devices d WHERE d.user_id = $1 AND d.id = ANY($2)",
+        ATTACHED_TYPES_SQL
+    ))
+    .bind(user_id)
+    .bind(ids)
+    .fetch_all(pool)
+    .await?;
+    let profiles: HashMap<Uuid, DeviceProfile> = rows.into_iter().map(|d| (d.id, d)).collect();
+    if ids.iter().any(|id| !profiles.contains_key(id)) {
+        return Err(ApiError::NotFound("One or more devices not found".to_string()));
+    }
+    Ok(profiles)
+}
+
+pub async fn insert_macro(
+    pool: &PgPool,
+    user_id: Uuid,
+    name: &str,
+    device_type: &str,
+    steps: &[LegAction],
+) -> ApiResult<CommandMacro> {
+    let command_macro = sqlx::query_as::<_, CommandMacro>(&format!(
+        "INSERT INTO command_macros (user_id, name, device_type, steps) VALUES ($1, $2, $3, $4) RETURNING {}",
+        MACRO_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(name)
+    .bind(device_type)
+    .bind(sqlx::types::Json(steps))
+    .fetch_one(pool)
+    .await?;
+    Ok(command_macro)
+}
+
+pub async fn owned_macro(pool: &PgPool, macro_id: Uuid, user_id: Uuid) -> ApiResult<CommandMacro> {
+    sqlx::query_as::<_, CommandMacro>(&format!(
+        "SELECT {} FROM command_macros WHERE id = $1 AND user_id = $2",
+        MACRO_COLUMNS
+    ))
+    .bind(macro_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Macro not found".to_string()))
+}
+
+pub async fn mission_legs(pool: &PgPool, mission_id: Uuid, user_id: Uuid) -> ApiResult<(String, Vec<MissionLeg>)> {
+    let (name, status): (String, String) =
+        sqlx::query_as("SELECT name, status FROM fleet_missions WHERE id = $1 AND user_id = $2")
+            .bind(mission_id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| ApiError::NotFound("Mission not found".to_string()))?;
+    if status != "completed" {
+        return Err(ApiError::BadRequest(format!(
+            "Only missions that completed in simulation can be promoted (mission is {})",
+            status
+        )));
+    }
+    let legs = sqlx::query_as::<_, MissionLeg>(&format!(
+        "SELECT {} FROM mission_legs WHERE mission_id = $1 ORDER BY leg_index",
+        LEG_COLUMNS
+    ))
+    .bind(mission_id)
+    .fetch_all(pool)
+    .await?;
+    Ok((name, legs))
+}
+
+/// The mission's legs with simulated devices swapped for their production counterparts
+pub fn remap_legs(legs: &[MissionLeg], device_map: &HashMap<Uuid, Uuid>) -> ApiResult<Vec<CreateLegRequest>> {
+    legs.iter()
+        .map(|leg| {
+            let device_id = *device_map.get(&leg.device_id).ok_or_else(|| {
+                ApiError::ValidationError(format!("device_map has no production device for {}", leg.device_id))
+            })?;
+            Ok(CreateLegRequest {
+                device_id,
+                target: *leg.target,
+                pickup_actions: leg.pickup_actions.0.clone(),
+                actions: leg.actions.0.clone(),
+                speed: Some(leg.speed as f64),
+            })
+        })
+        .collect()
+}
+
+/// Validate a promotion against the current state of the production devices
+pub async fn evaluate(
+    pool: &PgPool,
+    user_id: Uuid,
+    source_kind: &str,
+    source_id: Uuid,
+    device_map: &HashMap<Uuid, Uuid>,
+    geofence: Option<&GeoBounds>,
+) -> ApiResult<Evaluation> {
+    if device_map.is_empty() {
+        return Err(ApiError::ValidationError("device_map must map at least one simulated device".to_string()));
+    }
+    let ids: Vec<Uuid> = device_map.iter().flat_map(|(sim, prod)| [*sim, *prod]).collect();
+    let profiles = device_profiles(pool, user_id, &ids).await?;
+
+    let mut checks = Vec::new();
+    let mut diff = Vec::new();
+    for sim_id in device_map.keys() {
+        let sim = &profiles[sim_id];
+        checks.push(PromotionCheck::new(
+            sim.id,
+            "simulated_source",
+            sim.is_simulated(),
+            format!("Source device transport is {}", sim.transport),
+        ));
+    }
+
+    match source_kind {
+        "macro" => {
+            let command_macro = owned_macro(pool, source_id, user_id).await?;
+            let plan = macro_plan(&command_macro.steps);
+            for (sim_id, prod_id) in device_map {
+                let (sim, prod) = (&profiles[sim_id], &profiles[prod_id]);
+                // Every step of the current macro must have succeeded there, with no failures
+                let (succeeded, failed): (i64, i64) = sqlx::query_as(
+                    "SELECT COUNT(*) FILTER (WHERE status = 'succeeded'), COUNT(*) FILTER (WHERE status = 'failed') \
+                     FROM device_commands WHERE macro_id = $1 AND device_id = $2 AND created_at >= $3",
+                )
+                .bind(command_macro.id)
+                .bind(sim.id)
+                .bind(command_macro.updated_at)
+                .fetch_one(pool)
+                .await?;
+                let ran = failed == 0 && succeeded as usize >= plan.len();
+                checks.push(PromotionCheck::new(
+                    sim.id,
+                    "simulated_run",
+                    ran,
+                    if ran { "Macro ran successfully in simulation" } else { "No successful simulated run" },
+                ));
+
+                let (_, sim_estimate) = check_steps(sim, &plan);
+                let (problems, prod_estimate) = check_steps(prod, &plan);
+                checks.extend(device_checks(prod, &problems, &prod_estimate, &[], geofence));
+                diff.push(device_diff(sim, prod, &sim_estimate, &prod_estimate));
+            }
+        }
+        "fleet_mission" => {
+            let (_, legs) = mission_legs(pool, source_id, user_id).await?;
+            let remapped = remap_legs(&legs, device_map)?;
+
+            // A device may run several legs; its checks cover all of them together
+            let mut per_device: HashMap<Uuid, (Vec<String>, PlanEstimate, Vec<Waypoint>, PlanEstimate)> =
+                HashMap::new();
+            for (i, (leg, prod_leg)) in legs.iter().zip(&remapped).enumerate() {
+                let (sim, prod) = (&profiles[&leg.device_id], &profiles[&prod_leg.device_id]);
+                let rendezvous = i.checked_sub(1).map(|p| *legs[p].target);
+                let entry = per_device.entry(prod.id).or_default();
+                entry.2.extend(rendezvous.iter().copied().chain([*leg.target]));
+
+                let plan_for = |device: &DeviceProfile| -> ApiResult<Option<CommandPlan>> {
+                    device
+                        .position()
+                        .map(|position| {
+                            leg_commands(
+                                &device.device_type,
+                                position,
+                                rendezvous.as_ref(),
+                                &leg.target,
+                                &leg.pickup_actions,
+                                &leg.actions,
+                                leg.speed as f64,
+                            )
+                        })
+                        .transpose()
+                };
+                match plan_for(prod) {
+                    Ok(Some(plan)) => {
+                        let (problems, estimate) = check_steps(prod, &plan);
+                        entry.0.extend(problems.into_iter().map(|p| format!("leg {} {}", i, p)));
+                        add_estimate(&mut entry.1, &estimate);
+                    }
+                    Ok(None) => entry.0.push(format!("leg {}: device has not reported its position", i)),
+                    Err(e) => entry.0.push(format!("leg {}: {}", i, e)),
+                }
+                if let Ok(Some(plan)) = plan_for(sim) {
+                    add_estimate(&mut entry.3, &check_steps(sim, &plan).1);
+                }
+            }
+
+            for (sim_id, prod_id) in device_map {
+                let Some((problems, estimate, waypoints, sim_estimate)) = per_device.get(prod_id) else {
+                    continue;
+                };
+                let prod = &profiles[prod_id];
+                checks.extend(device_checks(prod, problems, estimate, waypoints, geofence));
+                diff.push(device_diff(&profiles[sim_id], prod, sim_estimate, estimate));
+            }
+        }
+        _ => {
+            return Err(ApiError::ValidationError("source_kind must be macro or fleet_mission".to_string()));
+        }
+    }
+
+    Ok(Evaluation { passed: checks.iter().all(|c| c.passed), checks, diff })
+}
+
+fn add_estimate(total: &mut PlanEstimate, estimate: &PlanEstimate) {
+    total.commands += estimate.commands;
+    total.duration_ms += estimate.duration_ms;
+    total.battery_drain += estimate.battery_drain;
+    total.reach_m += estimate.reach_m;
+}
+
+/// Queue a macro's steps on a device and push them through its transport
+pub async fn queue_macro(
+    pool: &PgPool,
+    transports: &TransportRegistry,
+    user_id: Uuid,
+    command_macro: &CommandMacro,
+    device: &DeviceProfile,
+) -> ApiResult<Vec<Uuid>> {
+    let service = RoboticsService::new();
+    let plan = macro_plan(&command_macro.steps);
+    let mut queued = Vec::with_capacity(plan.len());
+    for (command, parameters) in &plan {
+        service.validate_command(&device.device_type, &device.firmware_version, command)?;
+        require_attachment(&device.device_type, command, &device.attachments)?;
+        let params = service.parse_command_params(command, parameters)?;
+        queued.push((command, parameters, service.estimate_duration_ms(&params), service.estimate_battery_drain(command, &params)));
+    }
+    let commands: Vec<&str> = plan.iter().map(|(command, _)| command.as_str()).collect();
+    constraint_services::enforce(pool, device.id, &commands, false).await?;
+
+    let mut tx = pool.begin().await?;
+    let mut ids = Vec::with_capacity(queued.len());
+    for (sequence, (command, parameters, duration, drain)) in queued.iter().enumerate() {
+        let id: Uuid = sqlx::query_scalar(
+            "INSERT INTO device_commands \
+             (device_id, user_id, command, parameters, estimated_duration_ms, estimated_battery_drain, \
+              macro_id, sequence) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
+        )
+        .bind(device.id)
+        .bind(user_id)
+        .bind(command)
+        .bind(parameters)
+        .bind(*duration as i64)
+        .bind(*drain)
+        .bind(command_macro.id)
+        .bind(sequence as i32)
+        .fetch_one(&mut *tx)
+        .await?;
+        ids.push(id);
+    }
+    tx.commit().await?;
+
+    for (id, (command, parameters, duration, drain)) in ids.iter().zip(&queued) {
+        let outbound = OutboundCommand {
+            command_id: *id,
+            device_id: device.id,
+            command: command.to_string(),
+            parameters: (*parameters).clone(),
+            issued_at: Utc::now(),
+        };
+        transport_services::deliver(pool, transports, &device.transport, &outbound, (*duration, *drain)).await?;
+    }
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(device_type: &str, firmware: &str, transport: &str) -> DeviceProfile {
+        DeviceProfile {
+            id: Uuid::new_v4(),
+            device_type: device_type.to_string(),
+            firmware_version: firmware.to_string(),
+            transport: transport.to_string(),
+            last_latitude: Some(12.0),
+            last_longitude: Some(77.0),
+            battery_level: Some(90),
+            drain_correction: None,
+            attachments: Vec::new(),
+        }
+    }
+
+    fn step(command: &str, parameters: serde_json::Value) -> LegAction {
+        LegAction { command: command.to_string(), parameters }
+    }
+
+    fn fence() -> GeoBounds {
+        GeoBounds { min_lat: 11.99, min_lng: 76.99, max_lat: 12.01, max_lng: 77.01 }
+    }
+
+    #[test]
+    fn test_firmware_and_ranges_checked_on_production_device() {
+        let plan = macro_plan(&[
+            step("takeoff", serde_json::Value::Null),
+            step("hover", serde_json::json!({ "altitude": 200.0 })),
+            step("rotate", serde_json::json!({ "degrees": 90.0 })),
+        ]);
+        let (problems, estimate) = check_steps(&profile("drone", "1.0.0", "mqtt"), &plan);
+        assert_eq!(estimate.commands, 3);
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert!(problems[0].contains("altitude"));
+        assert!(problems[1].contains("firmware 1.2.0"));
+
+        let (problems, _) = check_steps(&profile("drone", "1.2.0", "mqtt"), &macro_plan(&[step("takeoff", serde_json::json!({}))]));
+        assert!(problems.is_empty());
+    }
+
+    #[test]
+    fn test_tool_commands_need_their_attachment() {
+        let plan = macro_plan(&[step("move_forward", serde_json::json!({})), step("grab", serde_json::json!({}))]);
+        let mut robot = profile("robot", "1.3.0", "mqtt");
+        let (problems, _) = check_steps(&robot, &plan);
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert!(problems[0].starts_with("step 1 (grab)") && problems[0].contains("gripper"));
+
+        robot.attachments.push("gripper".to_string());
+        assert!(check_steps(&robot, &plan).0.is_empty());
+    }
+
+    #[test]
+    fn test_geofence_and_battery_checks() {
+        let device = profile("rover", "2.0.0", "long_poll");
+        let short = PlanEstimate { commands: 1, duration_ms: 1000, battery_drain: 5.0, reach_m: 100.0 };
+        let far = PlanEstimate { reach_m: 5_000.0, battery_drain: 80.0, ..short.clone() };
+
+        let passed = |estimate: &PlanEstimate, waypoints: &[Waypoint]| -> Vec<&'static str> {
+            device_checks(&device, &[], estimate, waypoints, Some(&fence()))
+                .into_iter()
+                .filter(|c| !c.passed)
+                .map(|c| c.check)
+                .collect()
+        };
+        assert!(passed(&short, &[]).is_empty());
+        assert_eq!(passed(&far, &[]), ["geofence", "battery"]);
+        let outside = Waypoint { latitude: 12.5, longitude: 77.0, altitude: None };
+        assert_eq!(passed(&short, &[outside]), ["geofence"]);
+
+        let simulated = profile("rover", "2.0.0", "simulated");
+        let checks = device_checks(&simulated, &[], &short, &[], None);
+        assert!(checks.iter().any(|c| c.check == "production_device" && !c.passed));
+    }
+
+    #[test]
+    fn test_device_diff_lists_changes_only() {
+        let sim = profile("rover", "2.1.0", "simulated");
+        let prod = profile("rover", "2.0.0", "mqtt");
+        let estimate = PlanEstimate { commands: 2, duration_ms: 2000, battery_drain: 1.0, reach_m: 3.0 };
+        let diff = device_diff(&sim, &prod, &estimate, &PlanEstimate { battery_drain: 1.5, ..estimate.clone() });
+        let fields: Vec<&str> = diff["changes"].as_array().unwrap().iter().map(|c| c["field"].as_str().unwrap()).collect();
+        assert_eq!(fields, ["firmware_version", "battery_drain"]);
+    }
+
+    #[test]
+    fn test_validate_macro_steps() {
+        assert!(validate_macro_steps("robot", &[step("grab", serde_json::json!({}))]).is_ok());
+        assert!(validate_macro_steps("robot", &[step("takeoff", serde_json::json!({}))]).is_err());
+        assert!(validate_macro_steps("robot", &[]).is_err());
+    }
+}
@@ -0,0 +1,59 @@
+//! Claim-code device provisioning
+
+use rand::Rng;
+
+/// Claim codes avoid characters that are easy to misread on a label or screen (0/O, 1/I/L)
+const CLAIM_CODE_ALPHABET: &[u8] = b"23456789ABCDEFGHJKMNPQRSTUVWXYZ";
+const CLAIM_CODE_LENGTH: usize = 8;
+/// How long a generated claim code stays valid
+pub const CLAIM_CODE_TTL_HOURS: i64 = 24;
+
+/// Generate a short human-typable claim code, e.g. `7KQ2-M9XD`
+pub fn generate_claim_code() -> String {
+    let mut rng = rand::thread_rng();
+    let chars: String = (0..CLAIM_CODE_LENGTH)
+        .map(|_| CLAIM_CODE_ALPHABET[rng.gen_range(0..CLAIM_CODE_ALPHABET.len())] as char)
+        .collect();
+    format!("{}-{}", &chars[..4], &chars[4..])
+}
+
+/// Canonical form used for hashing: uppercase, separators and whitespace removed
+pub fn normalize_claim_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Hardware IDs are printable ASCII without whitespace, up to 128 characters
+pub fn is_valid_hardware_id(hardware_id: &str) -> bool {
+    !hardware_id.is_empty() && hardware_id.len() <= 128 && hardware_id.chars().all(|c| c.is_ascii_graphic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_claim_code() {
+        let code = generate_claim_code();
+        assert_eq!(code.len(), 9);
+        assert_eq!(&code[4..5], "-");
+        assert_eq!(normalize_claim_code(&code).len(), CLAIM_CODE_LENGTH);
+        assert!(!code.contains(['0', 'O', '1', 'I', 'L']));
+    }
+
+    #[test]
+    fn test_normalize_claim_code() {
+        assert_eq!(normalize_claim_code(" 7kq2-m9xd "), "7KQ2M9XD");
+        assert_eq!(normalize_claim_code("7KQ2M9XD"), "7KQ2M9XD");
+    }
+
+    #[test]
+    fn test_is_valid_hardware_id() {
+        assert!(is_valid_hardware_id("esp32-24:6F:28:AA:BB:CC"));
+        assert!(!is_valid_hardware_id(""));
+        assert!(!is_valid_hardware_id("has space"));
+        assert!(!is_valid_hardware_id(&"x".repeat(129)));
+    }
+}
@@ -0,0 +1,173 @@
+//! Platform-wide figures for the public stats endpoint, released with differential privacy
+//! (see [`crate::utils::privacy`]) so a small tenant's fleet cannot be read off them.
+
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use crate::errors::ApiResult;
+use crate::services::robotics_services::DEVICE_TYPES;
+use crate::utils::privacy::{PrivacyNotice, PrivacyParams, Release, ReleaseCache};
+
+/// Most devices one user adds to any device count
+pub const DEVICES_PER_USER_CAP: i64 = 25;
+/// Most commands one user adds to the 30-day command count
+pub const COMMANDS_PER_USER_CAP: i64 = 1_000;
+/// Most samples one user adds to the 30-day telemetry count
+pub const SAMPLES_PER_USER_CAP: i64 = 10_000;
+
+static RELEASE: ReleaseCache<PublicStats> = ReleaseCache::new(Duration::from_secs(60 * 60));
+
+/// Noisy platform figures; `null` where too few users contributed
+#[derive(Debug, Clone, Serialize)]
+pub struct PublicStats {
+    pub users: Option<i64>,
+    pub organizations: Option<i64>,
+    pub devices: Option<i64>,
+    pub devices_by_type: BTreeMap<&'static str, Option<i64>>,
+    pub commands_last_30_days: Option<i64>,
+    pub telemetry_samples_last_30_days: Option<i64>,
+    pub privacy: PrivacyNotice,
+}
+
+/// A clipped sum and how many distinct users contributed to it
+#[derive(Debug, Default, FromRow)]
+pub struct Contribution {
+    pub total: i64,
+    pub contributors: i64,
+}
+
+#[derive(Debug, FromRow)]
+struct TypeContribution {
+    device_type: String,
+    total: i64,
+    contributors: i64,
+}
+
+/// Everything the release is computed from
+#[derive(Debug, Default)]
+pub struct PlatformCounts {
+    pub users: i64,
+    pub organizations: i64,
+    pub devices: Contribution,
+    pub devices_by_type: BTreeMap<String, Contribution>,
+    pub commands: Contribution,
+    pub samples: Contribution,
+}
+
+/// Draw the noisy release from exact counts. Every device type is listed, so which types
+/// are in use is not revealed by the keys.
+pub fn release(params: PrivacyParams, counts: &PlatformCounts) -> PublicStats {
+    let mut release = Release::new(params, 5 + DEVICE_TYPES.len());
+    let devices_cap = DEVICES_PER_USER_CAP as f64;
+    let devices_by_type = DEVICE_TYPES
+        .iter()
+        .map(|spec| {
+            let group = counts.devices_by_type.get(spec.device_type);
+            let count = group.and_then(|g| release.count(g.total, g.contributors, devices_cap));
+            (spec.device_type, count)
+        })
+        .collect();
+
+    PublicStats {
+        users: release.count(counts.users, counts.users, 1.0),
+        // An org counts as its own contributor: the total is withheld only while there are few
+        organizations: release.count(counts.organizations, counts.organizations, 1.0),
+        devices: release.count(counts.devices.total, counts.devices.contributors, devices_cap),
+        devices_by_type,
+        commands_last_30_days: release.count(
+            counts.commands.total,
+            counts.commands.contributors,
+            COMMANDS_PER_USER_CAP as f64,
+        ),
+        telemetry_samples_last_30_days: release.count(
+            counts.samples.total,
+            counts.samples.contributors,
+            SAMPLES_PER_USER_CAP as f64,
+        ),
+        privacy: release.notice(),
+    }
+}
+
+async fn load_counts(pool: &PgPool) -> ApiResult<PlatformCounts> {
+    let users: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users").fetch_one(pool).await?;
+    let organizations: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM organizations").fetch_one(pool).await?;
+    let clipped = |per_user: &str| {
+        format!(
+            "SELECT COALESCE(SUM(LEAST(n, $1)), 0)::BIGINT AS total, COUNT(*) AS contributors FROM ({}) per_user",
+            per_user
+        )
+    };
+
+    let devices = sqlx::query_as::<_, Contribution>(&clipped("SELECT COUNT(*) AS n FROM devices GROUP BY user_id"))
+        .bind(DEVICES_PER_USER_CAP)
+        .fetch_one(pool)
+        .await?;
+    let devices_by_type = sqlx::query_as::<_, TypeContribution>(
+        "SELECT device_type::VARCHAR AS device_type, SUM(LEAST(n, $1))::BIGINT AS total, COUNT(*) AS contributors \
+         FROM (SELECT user_id, device_type, COUNT(*) AS n FROM devices GROUP BY user_id, device_type) per_user \
+         GROUP BY device_type",
+    )
+    .bind(DEVICES_PER_USER_CAP)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|t| (t.device_type, Contribution { total: t.total, contributors: t.contributors }))
+    .collect();
+    let commands = sqlx::query_as::<_, Contribution>(&clipped(
+        "SELECT COUNT(*) AS n FROM device_commands WHERE created_at >= NOW() - INTERVAL '30 days' GROUP BY user_id",
+    ))
+    .bind(COMMANDS_PER_USER_CAP)
+    .fetch_one(pool)
+    .await?;
+    let samples = sqlx::query_as::<_, Contribution>(&clipped(
+        "SELECT COUNT(*) AS n FROM device_telemetry t JOIN devices d ON d.id = t.device_id \
+         WHERE t.recorded_at >= NOW() - INTERVAL '30 days' GROUP BY d.user_id",
+    ))
+    .bind(SAMPLES_PER_USER_CAP)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(PlatformCounts { users, organizations, devices, devices_by_type, commands, samples })
+}
+
+/// The current release, drawn at most once an hour
+pub async fn public_stats(pool: &PgPool, params: PrivacyParams) -> ApiResult<PublicStats> {
+    if let Some(stats) = RELEASE.get() {
+        return Ok(stats);
+    }
+    let counts = load_counts(pool).await?;
+    Ok(RELEASE.put(release(params, &counts)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_withholds_small_groups() {
+        let params = PrivacyParams { epsilon: 1.0, min_cohort: 10 };
+        let mut counts = PlatformCounts {
+            users: 5_000,
+            organizations: 4,
+            devices: Contribution { total: 12_000, contributors: 3_000 },
+            commands: Contribution { total: 0, contributors: 0 },
+            samples: Contribution { total: 2_000_000, contributors: 2_500 },
+            ..Default::default()
+        };
+        counts.devices_by_type.insert("drone".to_string(), Contribution { total: 9_000, contributors: 2_000 });
+        counts.devices_by_type.insert("rover".to_string(), Contribution { total: 40, contributors: 3 });
+
+        let stats = release(params, &counts);
+        assert!(stats.users.is_some());
+        assert!(stats.devices.is_some());
+        assert!(stats.devices_by_type["drone"].is_some());
+        // Too few owners, too few orgs, and no one at all
+        assert_eq!(stats.devices_by_type["rover"], None);
+        assert_eq!(stats.organizations, None);
+        assert_eq!(stats.commands_last_30_days, None);
+        // Every known type is listed whether or not anyone has one
+        assert_eq!(stats.devices_by_type.len(), DEVICE_TYPES.len());
+        assert_eq!(stats.privacy.epsilon, 1.0);
+    }
+}
@@ -0,0 +1,385 @@
+//! Retrieval-augmented answers over uploaded documentation. An uploaded text file is split into
+//! overlapping chunks at paragraph and word boundaries, and each chunk is embedded into the
+//! embedding store. A question retrieves the closest chunks, which are numbered into the system
+//! prompt; the model is asked to cite them as `[n]`, and the answer lists the chunks it was
+//! given with whether it cited each.
+
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::embedding::{AskRequest, RagDocument, SearchResult};
+use crate::services::ai_routing_services::routed_chat;
+use crate::services::ai_services::{AIService, ChatMessage, ChatRequest, CostEstimate, RoutingDecision, TokenUsage};
+use crate::services::embedding_services::{self, NewEmbedding};
+use crate::services::moderation_services;
+use crate::services::support_services::sanitize_file_name;
+
+const DOCUMENT_COLUMNS: &str =
+    "id, user_id, collection, file_name, content_type, size_bytes, chunk_count, created_at";
+
+pub const MAX_UPLOAD_BYTES: usize = 1024 * 1024;
+/// Each chunk is one embedding request
+const MAX_CHUNKS: usize = 200;
+const CHUNK_CHARS: usize = 1_500;
+/// Text repeated from the end of one chunk at the start of the next, so a passage cut between
+/// chunks is whole in one of them
+const CHUNK_OVERLAP_CHARS: usize = 200;
+/// Non-text types accepted besides `text/*`
+const DOCUMENT_TYPES: &[&str] = &["application/json", "application/xml", "application/x-yaml"];
+const DEFAULT_TOP_K: i64 = 4;
+const MAX_TOP_K: i64 = 10;
+const EXCERPT_CHARS: usize = 300;
+const ANSWER_TEMPERATURE: f32 = 0.2;
+
+/// A chunk the answer was given from; `index` is the number the answer cites it by
+#[derive(Debug, Serialize)]
+pub struct AnswerSource {
+    pub index: usize,
+    pub document_id: Option<Uuid>,
+    pub source: Option<String>,
+    pub chunk_index: Option<i32>,
+    pub score: f64,
+    pub excerpt: String,
+    /// Whether the answer cites it
+    pub cited: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AskResponse {
+    pub answer: String,
+    pub sources: Vec<AnswerSource>,
+    pub model: String,
+    pub provider: String,
+    pub usage: Option<TokenUsage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub routing: Option<RoutingDecision>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost: Option<CostEstimate>,
+}
+
+pub fn validate_content_type(content_type: &str) -> ApiResult<String> {
+    let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    if essence.starts_with("text/") || DOCUMENT_TYPES.contains(&essence.as_str()) {
+        return Ok(essence);
+    }
+    Err(ApiError::ValidationError(format!(
+        "Documents must be text or one of {}; extract the text of PDFs and other binary files first",
+        DOCUMENT_TYPES.join(", ")
+    )))
+}
+
+/// `text` split at word boundaries into pieces of at most `limit` characters
+fn split_words(text: &str, limit: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        // Longer than a whole piece: cut it
+        while word.len() > limit {
+            if !current.is_empty() {
+                pieces.push(std::mem::take(&mut current));
+            }
+            pieces.push(word.drain(..limit).collect());
+        }
+        let word: String = word.into_iter().collect();
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > limit {
+            pieces.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(&word);
+    }
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    pieces
+}
+
+/// The last `chars` characters of `text` or less, starting at a word
+fn tail(text: &str, chars: usize) -> String {
+    let count = text.chars().count();
+    if count <= chars {
+        return text.to_string();
+    }
+    let end: String = text.chars().skip(count - chars).collect();
+    match end.find(char::is_whitespace) {
+        Some(at) => end[at..].trim_start().to_string(),
+        None => end,
+    }
+}
+
+/// Paragraphs packed into chunks of at most `max_chars`, each starting with up to `overlap`
+/// characters from the end of the one before
+pub fn chunk_text(text: &str, max_chars: usize, overlap: usize) -> Vec<String> {
+    let text = text.replace("\r\n", "\n");
+    // Room for the carried overlap and its separator
+    let piece_limit = max_chars.saturating_sub(overlap + 2).max(1);
+    let pieces = text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()).flat_map(|paragraph| {
+        if paragraph.chars().count() <= piece_limit {
+            vec![paragraph.to_string()]
+        } else {
+            split_words(paragraph, piece_limit)
+        }
+    });
+
+    let mut chunks: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut fresh = true;
+    for piece in pieces {
+        if !fresh && current.chars().count() + 2 + piece.chars().count() > max_chars {
+            let carried = tail(&current, overlap);
+            chunks.push(std::mem::replace(&mut current, carried));
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(&piece);
+        fresh = false;
+    }
+    if !fresh {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// The source numbers an answer cites, as in `[2]` or `[1, 3]`
+pub fn cited_indices(answer: &str) -> Vec<usize> {
+    let mut cited = Vec::new();
+    for group in answer.split('[').skip(1) {
+        let Some((inside, _)) = group.split_once(']') else { continue };
+        let numbers: Option<Vec<usize>> = inside.split(',').map(|n| n.trim().parse().ok()).collect();
+        for n in numbers.unwrap_or_default() {
+            if !cited.contains(&n) {
+                cited.push(n);
+            }
+        }
+    }
+    cited
+}
+
+fn excerpt(content: &str) -> String {
+    if content.chars().count() <= EXCERPT_CHARS {
+        return content.to_string();
+    }
+    let cut: String = content.chars().take(EXCERPT_CHARS).collect();
+    format!("{}…", cut.trim_end())
+}
+
+/// The system prompt putting the retrieved chunks, numbered, in front of the model
+pub fn system_prompt(results: &[SearchResult]) -> String {
+    let mut prompt = String::from(
+        "Answer the user's question using only the numbered documentation excerpts below. Cite the excerpts \
+         you use by their number in square brackets, like [1]. If the excerpts do not contain the answer, \
+         say so instead of guessing.\n",
+    );
+    for (i, result) in results.iter().enumerate() {
+        let source = result.source.as_deref().unwrap_or("untitled");
+        prompt.push_str(&format!("\n[{}] {}\n{}\n", i + 1, source, result.content));
+    }
+    prompt
+}
+
+/// Store an uploaded text file as embedded chunks. Every chunk is embedded before anything is
+/// stored, so a provider failure stores nothing.
+pub async fn upload(
+    pool: &PgPool,
+    ai: &AIService,
+    user_id: Uuid,
+    file_name: &str,
+    collection: Option<&str>,
+    content_type: &str,
+    data: Vec<u8>,
+) -> ApiResult<RagDocument> {
+    let file_name = sanitize_file_name(file_name)?;
+    let collection = embedding_services::validate_collection(collection)?;
+    let content_type = validate_content_type(content_type)?;
+    if data.is_empty() || data.len() > MAX_UPLOAD_BYTES {
+        return Err(ApiError::ValidationError(format!("Documents must be 1-{} bytes", MAX_UPLOAD_BYTES)));
+    }
+    let size_bytes = data.len() as i64;
+    let text = String::from_utf8(data)
+        .map_err(|_| ApiError::ValidationError("Documents must be UTF-8 text".to_string()))?;
+    let chunks = chunk_text(&text, CHUNK_CHARS, CHUNK_OVERLAP_CHARS);
+    if chunks.is_empty() {
+        return Err(ApiError::ValidationError("The document has no text".to_string()));
+    }
+    if chunks.len() > MAX_CHUNKS {
+        return Err(ApiError::ValidationError(format!(
+            "The document is too long: it makes {} chunks, at most {} are indexed per file",
+            chunks.len(),
+            MAX_CHUNKS
+        )));
+    }
+
+    let model = ai.embedding_model()?;
+    let mut vectors = Vec::with_capacity(chunks.len());
+    for chunk in &chunks {
+        vectors.push(embedding_services::embed(ai, chunk).await?);
+    }
+
+    let mut tx = pool.begin().await?;
+    let document = sqlx::query_as::<_, RagDocument>(&format!(
+        "INSERT INTO ai_documents (id, user_id, collection, file_name, content_type, size_bytes, chunk_count) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING {}",
+        DOCUMENT_COLUMNS
+    ))
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(&collection)
+    .bind(&file_name)
+    .bind(&content_type)
+    .bind(size_bytes)
+    .bind(chunks.len() as i32)
+    .fetch_one(&mut *tx)
+    .await?;
+    for (i, (chunk, vector)) in chunks.iter().zip(vectors).enumerate() {
+        let embedding = NewEmbedding {
+            collection: &collection,
+            document_id: Some(document.id),
+            chunk_index: Some(i as i32),
+            source: Some(&file_name),
+            content: chunk,
+            metadata: serde_json::json!({ "file_name": file_name, "content_type": content_type }),
+        };
+        embedding_services::insert(&mut tx, user_id, &model, &embedding, vector).await?;
+    }
+    tx.commit().await?;
+    Ok(document)
+}
+
+/// The user's uploaded documents, newest first
+pub async fn list(pool: &PgPool, user_id: Uuid, collection: Option<&str>) -> ApiResult<Vec<RagDocument>> {
+    let documents = sqlx::query_as::<_, RagDocument>(&format!(
+        "SELECT {} FROM ai_documents WHERE user_id = $1 AND ($2::TEXT IS NULL OR collection = $2) \
+         ORDER BY created_at DESC LIMIT 200",
+        DOCUMENT_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(collection)
+    .fetch_all(pool)
+    .await?;
+    Ok(documents)
+}
+
+/// Delete a document with its chunks
+pub async fn delete(pool: &PgPool, user_id: Uuid, document_id: Uuid) -> ApiResult<()> {
+    let deleted = sqlx::query("DELETE FROM ai_documents WHERE id = $1 AND user_id = $2")
+        .bind(document_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Document not found".to_string()));
+    }
+    Ok(())
+}
+
+/// Answer a question from the user's documentation, citing the chunks used. Routed and charged
+/// against the user's AI budget like any chat request.
+pub async fn ask(
+    pool: &PgPool,
+    config: &AppConfig,
+    ai: &AIService,
+    user_id: Uuid,
+    request: &AskRequest,
+) -> ApiResult<AskResponse> {
+    let top_k = request.top_k.unwrap_or(DEFAULT_TOP_K).clamp(1, MAX_TOP_K);
+    let mut question = request.question.trim().to_string();
+    moderation_services::screen_prompt(config, ai, "question", &mut question).await?;
+    let results = embedding_services::search(
+        pool,
+        ai,
+        user_id,
+        &question,
+        request.collection.as_deref(),
+        Some(top_k),
+    )
+    .await?;
+    if results.is_empty() {
+        return Err(ApiError::NotFound("No indexed documentation to answer from".to_string()));
+    }
+
+    let chat = ChatRequest {
+        messages: vec![
+            ChatMessage { role: "system".to_string(), content: system_prompt(&results) },
+            ChatMessage { role: "user".to_string(), content: question },
+        ],
+        model: request.model.clone(),
+        provider: request.provider.clone(),
+        temperature: Some(ANSWER_TEMPERATURE),
+        max_tokens: None,
+        conversation_id: None,
+        images: Vec::new(),
+        json: false,
+    };
+    let mut response = routed_chat(pool, config, ai, user_id, chat).await?;
+    moderation_services::screen_output(config, ai, &mut response.message).await?;
+
+    let cited = cited_indices(&response.message);
+    let sources = results
+        .into_iter()
+        .enumerate()
+        .map(|(i, result)| AnswerSource {
+            index: i + 1,
+            document_id: result.document_id,
+            source: result.source,
+            chunk_index: result.chunk_index,
+            score: result.score,
+            excerpt: excerpt(&result.content),
+            cited: cited.contains(&(i + 1)),
+        })
+        .collect();
+    Ok(AskResponse {
+        answer: response.message,
+        sources,
+        model: response.model,
+        provider: response.provider,
+        usage: response.usage,
+        routing: response.routing,
+        cost: response.cost,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_text() {
+        assert!(chunk_text("  \n\n  ", 100, 20).is_empty());
+        assert_eq!(chunk_text("One paragraph.", 100, 20), ["One paragraph."]);
+
+        let text = "First paragraph here.\r\n\r\nSecond paragraph here.\n\nThird paragraph here.";
+        let chunks = chunk_text(text, 50, 10);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0], "First paragraph here.\n\nSecond paragraph here.");
+        // The next chunk repeats the end of the one before
+        assert_eq!(chunks[1], "here.\n\nThird paragraph here.");
+
+        let long = "word ".repeat(100);
+        let chunks = chunk_text(&long, 60, 10);
+        assert!(chunks.iter().all(|c| c.chars().count() <= 60));
+        assert!(chunks.iter().all(|c| c.split_whitespace().all(|w| w == "word")));
+
+        // A word longer than a chunk is cut
+        let chunks = chunk_text(&"x".repeat(120), 50, 10);
+        assert!(chunks.iter().all(|c| c.chars().count() <= 50));
+    }
+
+    #[test]
+    fn test_cited_indices() {
+        assert_eq!(cited_indices("Torque is 2 Nm [1]. Calibrate first [3][1]."), [1, 3]);
+        assert_eq!(cited_indices("See [2, 4] and [note]."), [2, 4]);
+        assert!(cited_indices("No sources cover this.").is_empty());
+    }
+
+    #[test]
+    fn test_validate_content_type() {
+        assert_eq!(validate_content_type("text/markdown; charset=utf-8").unwrap(), "text/markdown");
+        assert!(validate_content_type("application/json").is_ok());
+        assert!(validate_content_type("application/pdf").is_err());
+    }
+}
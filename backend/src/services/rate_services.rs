@@ -0,0 +1,212 @@
+//! Fiat prices of the tokens and coins the platform deals in, read from CoinGecko's
+//! `simple/price` API or any source answering it the same way. Prices are cached briefly; when
+//! the source is unreachable, a price up to an hour old stands in and is flagged stale.
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use secrecy::ExposeSecret;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+
+static RATE_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("Failed to build exchange rate HTTP client")
+});
+
+/// How long a price is served without asking the source again
+const RATE_FRESH_FOR: Duration = Duration::from_secs(60);
+/// How long a price may stand in while the source is unreachable
+const RATE_STALE_FOR: Duration = Duration::from_secs(3600);
+pub const DEFAULT_CURRENCY: &str = "usd";
+
+/// (asset id, fiat currency), both lower-case
+type RateKey = (String, String);
+/// Prices with when they were read
+static RATE_CACHE: LazyLock<Mutex<HashMap<RateKey, (Instant, f64)>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExchangeRates {
+    pub currency: String,
+    /// Price of one whole token by symbol
+    pub rates: BTreeMap<String, f64>,
+    /// Symbols asked for that have no price
+    pub unpriced: Vec<String>,
+    /// Whether some price is older than a minute because the source could not be reached
+    pub stale: bool,
+}
+
+impl ExchangeRates {
+    /// `amount` whole tokens of `symbol` in the fiat currency, to the cent
+    pub fn value_of(&self, symbol: &str, amount: &str) -> Option<Decimal> {
+        let rate = Decimal::try_from(*self.rates.get(&symbol.to_uppercase())?).ok()?;
+        let amount: Decimal = amount.trim().parse().ok()?;
+        Some((amount * rate).round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero))
+    }
+}
+
+/// A fiat currency code as the source expects it: three letters, lower-case
+pub fn normalize_currency(code: &str) -> ApiResult<String> {
+    let code = code.trim().to_lowercase();
+    if code.len() != 3 || !code.bytes().all(|b| b.is_ascii_lowercase()) {
+        return Err(ApiError::ValidationError(format!("Invalid currency code: {}", code)));
+    }
+    Ok(code)
+}
+
+/// Prices in `currency` by asset id from a `simple/price` response; ids without one are left out
+fn parse_prices(body: &serde_json::Value, currency: &str) -> HashMap<String, f64> {
+    body.as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(id, prices)| {
+            let price = prices.get(currency)?.as_f64().filter(|p| p.is_finite() && *p >= 0.0)?;
+            Some((id.to_lowercase(), price))
+        })
+        .collect()
+}
+
+async fn fetch_prices(config: &AppConfig, ids: &[&str], currency: &str) -> ApiResult<HashMap<String, f64>> {
+    let mut request = RATE_CLIENT
+        .get(format!("{}/simple/price", config.exchange_rate_api_url))
+        .query(&[("ids", ids.join(",").as_str()), ("vs_currencies", currency)]);
+    if let Some(key) = &config.exchange_rate_api_key {
+        // Paid plans are served from a separate host and take a different header
+        let header = if config.exchange_rate_api_url.contains("pro-api") {
+            "x-cg-pro-api-key"
+        } else {
+            "x-cg-demo-api-key"
+        };
+        request = request.header(header, key.expose_secret());
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| ApiError::ExternalServiceError(format!("Exchange rate request failed: {}", e)))?;
+    if !response.status().is_success() {
+        return Err(ApiError::ExternalServiceError(format!("Exchange rate source returned HTTP {}", response.status())));
+    }
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| ApiError::ExternalServiceError(format!("Invalid exchange rate response: {}", e)))?;
+    Ok(parse_prices(&body, currency))
+}
+
+/// Prices of `symbols` (every configured asset when empty) in `currency`
+pub async fn rates(config: &AppConfig, symbols: &[String], currency: &str) -> ApiResult<ExchangeRates> {
+    let currency = normalize_currency(currency)?;
+    let mut symbols: Vec<String> = if symbols.is_empty() {
+        config.exchange_rate_assets.keys().cloned().collect()
+    } else {
+        symbols.iter().map(|s| s.trim().to_uppercase()).filter(|s| !s.is_empty()).collect()
+    };
+    symbols.sort();
+    symbols.dedup();
+
+    let ids: Vec<(&str, &str)> = symbols
+        .iter()
+        .filter_map(|symbol| Some((symbol.as_str(), config.exchange_rate_assets.get(symbol)?.as_str())))
+        .collect();
+    let cached: HashMap<&str, (Instant, f64)> = {
+        let cache = RATE_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        ids.iter()
+            .filter_map(|(_, id)| Some((*id, *cache.get(&(id.to_string(), currency.clone()))?)))
+            .collect()
+    };
+    let mut to_fetch: Vec<&str> = ids
+        .iter()
+        .map(|(_, id)| *id)
+        .filter(|id| cached.get(id).is_none_or(|(read_at, _)| read_at.elapsed() >= RATE_FRESH_FOR))
+        .collect();
+    to_fetch.sort_unstable();
+    to_fetch.dedup();
+
+    let mut fetched = HashMap::new();
+    let mut fetch_error = None;
+    if !to_fetch.is_empty() {
+        match fetch_prices(config, &to_fetch, &currency).await {
+            Ok(prices) => {
+                let mut cache = RATE_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+                cache.retain(|_, (read_at, _)| read_at.elapsed() < RATE_STALE_FOR);
+                for (id, price) in &prices {
+                    cache.insert((id.clone(), currency.clone()), (Instant::now(), *price));
+                }
+                fetched = prices;
+            }
+            Err(e) => {
+                tracing::warn!("Exchange rates unavailable, using cached prices: {}", e);
+                fetch_error = Some(e);
+            }
+        }
+    }
+
+    let mut result = ExchangeRates { currency, rates: BTreeMap::new(), unpriced: Vec::new(), stale: false };
+    for symbol in &symbols {
+        let id = config.exchange_rate_assets.get(symbol).map(String::as_str);
+        let price = match id.and_then(|id| fetched.get(id)) {
+            Some(price) => Some(*price),
+            None => id
+                .and_then(|id| cached.get(id))
+                .filter(|(read_at, _)| read_at.elapsed() < RATE_STALE_FOR)
+                .map(|(read_at, price)| {
+                    result.stale |= read_at.elapsed() >= RATE_FRESH_FOR;
+                    *price
+                }),
+        };
+        match price {
+            Some(price) => {
+                result.rates.insert(symbol.clone(), price);
+            }
+            None => result.unpriced.push(symbol.clone()),
+        }
+    }
+    // Nothing to show for it: the outage is the answer
+    if let (Some(e), true) = (fetch_error, result.rates.is_empty()) {
+        return Err(e);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_currency() {
+        assert_eq!(normalize_currency(" EUR ").unwrap(), "eur");
+        assert!(normalize_currency("euro").is_err());
+        assert!(normalize_currency("e1r").is_err());
+    }
+
+    #[test]
+    fn test_parse_prices() {
+        let body = serde_json::json!({
+            "ethereum": { "usd": 3120.55, "eur": 2890.1 },
+            "polygon-ecosystem-token": { "eur": 0.41 },
+            "broken": { "usd": "n/a" },
+        });
+        let prices = parse_prices(&body, "usd");
+        assert_eq!(prices.len(), 1);
+        assert_eq!(prices["ethereum"], 3120.55);
+        assert!(parse_prices(&serde_json::json!([]), "usd").is_empty());
+    }
+
+    #[test]
+    fn test_value_of() {
+        let rates = ExchangeRates {
+            currency: "usd".to_string(),
+            rates: BTreeMap::from([("ETH".to_string(), 3120.55)]),
+            unpriced: vec!["RBV".to_string()],
+            stale: false,
+        };
+        assert_eq!(rates.value_of("eth", "0.5"), Some(Decimal::new(156028, 2)));
+        assert_eq!(rates.value_of("ETH", "0.000000000000000001"), Some(Decimal::ZERO));
+        assert_eq!(rates.value_of("RBV", "10"), None);
+        assert_eq!(rates.value_of("ETH", "lots"), None);
+    }
+}
@@ -0,0 +1,283 @@
+//! Razorpay Checkout. An order is created server-side for the product's price; once the buyer
+//! pays, Checkout hands back the payment id with a signature made with the key secret. That,
+//! or a signed `payment.captured`/`order.paid` webhook, settles the order's transaction once
+//! the payment is captured for exactly the order's amount.
+
+use hmac::{Hmac, Mac};
+use secrecy::ExposeSecret;
+use serde::Deserialize;
+use sha2::Sha256;
+use sqlx::PgPool;
+use std::sync::LazyLock;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::transaction::ProviderRefund;
+use crate::services::payment_services::{
+    complete_transaction, find_provider_transaction, minor_units, record_provider_event,
+};
+use crate::utils::secure_compare;
+
+pub const PROVIDER: &str = "razorpay";
+const API_BASE: &str = "https://api.razorpay.com/v1";
+
+static RAZORPAY_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(15))
+        .build()
+        .expect("Failed to build Razorpay HTTP client")
+});
+
+#[derive(Debug, Deserialize)]
+pub struct RazorpayOrder {
+    pub id: String,
+    pub amount: i64,
+    pub currency: String,
+}
+
+/// A payment made against an order
+#[derive(Debug, Deserialize)]
+pub struct RazorpayPayment {
+    pub id: String,
+    pub status: String, // created, authorized, captured, refunded, failed
+    pub amount: i64,
+    pub currency: String,
+    pub order_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RazorpayEntity<T> {
+    entity: T,
+}
+
+#[derive(Debug, Deserialize)]
+struct RazorpayWebhookPayload {
+    payment: Option<RazorpayEntity<RazorpayPayment>>,
+}
+
+/// A webhook delivery; `payment.captured` and `order.paid` both carry the payment
+#[derive(Debug, Deserialize)]
+pub struct RazorpayWebhookEvent {
+    pub event: String,
+    payload: RazorpayWebhookPayload,
+}
+
+impl RazorpayWebhookEvent {
+    pub fn payment(&self) -> Option<&RazorpayPayment> {
+        self.payload.payment.as_ref().map(|p| &p.entity)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RazorpayCollection<T> {
+    items: Vec<T>,
+}
+
+pub fn is_configured(config: &AppConfig) -> bool {
+    !config.razorpay_key_id.is_empty() && !config.razorpay_key_secret.expose_secret().is_empty()
+}
+
+/// Create an order for `amount` minor units; `receipt` ties it to our transaction in the dashboard
+pub async fn create_order(config: &AppConfig, amount: i64, currency: &str, receipt: &str) -> ApiResult<RazorpayOrder> {
+    let response = RAZORPAY_CLIENT
+        .post(format!("{}/orders", API_BASE))
+        .basic_auth(&config.razorpay_key_id, Some(config.razorpay_key_secret.expose_secret()))
+        .json(&serde_json::json!({
+            "amount": amount,
+            "currency": currency.to_uppercase(),
+            "receipt": receipt,
+        }))
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(ApiError::PaymentError(format!("Razorpay order creation failed ({}): {}", status, body)));
+    }
+    Ok(response.json().await?)
+}
+
+/// Look up a payment
+pub async fn fetch_payment(config: &AppConfig, payment_id: &str) -> ApiResult<RazorpayPayment> {
+    let response = RAZORPAY_CLIENT
+        .get(format!("{}/payments/{}", API_BASE, payment_id))
+        .basic_auth(&config.razorpay_key_id, Some(config.razorpay_key_secret.expose_secret()))
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(ApiError::PaymentError(format!("Razorpay payment lookup failed ({}): {}", status, body)));
+    }
+    Ok(response.json().await?)
+}
+
+/// Every payment attempted against an order, failed ones included
+pub async fn order_payments(config: &AppConfig, order_id: &str) -> ApiResult<Vec<RazorpayPayment>> {
+    let response = RAZORPAY_CLIENT
+        .get(format!("{}/orders/{}/payments", API_BASE, order_id))
+        .basic_auth(&config.razorpay_key_id, Some(config.razorpay_key_secret.expose_secret()))
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(ApiError::PaymentError(format!("Razorpay payment lookup failed ({}): {}", status, body)));
+    }
+    let payments: RazorpayCollection<RazorpayPayment> = response.json().await?;
+    Ok(payments.items)
+}
+
+/// Refund `amount` minor units of a captured payment; `receipt` ties it to our refund
+pub async fn create_refund(
+    config: &AppConfig,
+    payment_id: &str,
+    amount: i64,
+    receipt: &str,
+) -> ApiResult<ProviderRefund> {
+    let response = RAZORPAY_CLIENT
+        .post(format!("{}/payments/{}/refund", API_BASE, payment_id))
+        .basic_auth(&config.razorpay_key_id, Some(config.razorpay_key_secret.expose_secret()))
+        .json(&serde_json::json!({ "amount": amount, "receipt": receipt }))
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(ApiError::PaymentError(format!("Razorpay refund failed ({}): {}", status, body)));
+    }
+    Ok(response.json().await?)
+}
+
+/// Check the `razorpay_signature` Checkout returns: hex HMAC-SHA256 of `order_id|payment_id`
+/// keyed with the key secret
+pub fn verify_payment_signature(secret: &[u8], order_id: &str, payment_id: &str, signature: &str) -> bool {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(order_id.as_bytes());
+    mac.update(b"|");
+    mac.update(payment_id.as_bytes());
+    let expected = hex::encode(mac.finalize().into_bytes());
+    secure_compare(&expected, &signature.to_ascii_lowercase())
+}
+
+/// Check the `X-Razorpay-Signature` of a webhook: hex HMAC-SHA256 of the raw body keyed with
+/// the webhook secret
+pub fn verify_webhook_signature(secret: &[u8], body: &[u8], signature: &str) -> bool {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(body);
+    let expected = hex::encode(mac.finalize().into_bytes());
+    secure_compare(&expected, &signature.trim().to_ascii_lowercase())
+}
+
+/// How a payment settles an order of `amount` minor units: `completed` when it is captured for
+/// exactly that amount, `pending` while it is not captured yet, or `mismatch`
+pub fn payment_outcome(payment: &RazorpayPayment, amount: i64, currency: &str) -> &'static str {
+    let amount_matches = payment.amount == amount && payment.currency.eq_ignore_ascii_case(currency);
+    match payment.status.as_str() {
+        "captured" if amount_matches => "completed",
+        "captured" => "mismatch",
+        "created" | "authorized" => "pending",
+        _ => "mismatch",
+    }
+}
+
+/// Settle the order's transaction with a payment fetched from Razorpay or taken from a signed
+/// webhook. Returns the transaction id and what was done: `completed`, `ignored` (already
+/// settled), `pending` (not captured yet), `mismatch` (not a capture of the order's amount) or
+/// `duplicate` (payment seen before).
+pub async fn settle_payment(pool: &PgPool, payment: &RazorpayPayment) -> ApiResult<(Uuid, &'static str)> {
+    let order_id = payment.order_id.as_deref()
+        .ok_or_else(|| ApiError::BadRequest("Razorpay payment is not for an order".to_string()))?;
+    let mut tx = pool.begin().await?;
+    let transaction = find_provider_transaction(&mut tx, PROVIDER, order_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Order not found".to_string()))?;
+
+    let amount = minor_units(transaction.amount, &transaction.currency);
+    let result = match payment_outcome(payment, amount, &transaction.currency) {
+        // Not recorded, so the capture can still settle it
+        "pending" => return Ok((transaction.id, "pending")),
+        "completed" if complete_transaction(&mut tx, &transaction).await? => "completed",
+        "completed" => "ignored",
+        other => {
+            tracing::warn!(
+                transaction_id = %transaction.id,
+                payment_id = %payment.id,
+                status = %payment.status,
+                amount = payment.amount,
+                currency = %payment.currency,
+                "Razorpay payment does not match its order"
+            );
+            other
+        }
+    };
+    if !record_provider_event(&mut tx, PROVIDER, &payment.id, "payment.captured", Some(transaction.id), result).await? {
+        tx.rollback().await?;
+        return Ok((transaction.id, "duplicate"));
+    }
+    tx.commit().await?;
+    Ok((transaction.id, result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_payment_signature() {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(b"key_secret").unwrap();
+        mac.update(b"order_IluGWxBm9U8zJ8|pay_IluGXOnr9yBo6k");
+        let signature = hex::encode(mac.finalize().into_bytes());
+
+        assert!(verify_payment_signature(b"key_secret", "order_IluGWxBm9U8zJ8", "pay_IluGXOnr9yBo6k", &signature));
+        assert!(!verify_payment_signature(b"other_secret", "order_IluGWxBm9U8zJ8", "pay_IluGXOnr9yBo6k", &signature));
+        assert!(!verify_payment_signature(b"key_secret", "order_other", "pay_IluGXOnr9yBo6k", &signature));
+        assert!(!verify_payment_signature(b"key_secret", "order_IluGWxBm9U8zJ8", "pay_IluGXOnr9yBo6k", "deadbeef"));
+    }
+
+    #[test]
+    fn test_verify_webhook_signature() {
+        let body = br#"{"event":"payment.captured"}"#;
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(b"webhook_secret").unwrap();
+        mac.update(body);
+        let signature = hex::encode(mac.finalize().into_bytes());
+
+        assert!(verify_webhook_signature(b"webhook_secret", body, &signature));
+        assert!(!verify_webhook_signature(b"key_secret", body, &signature));
+        assert!(!verify_webhook_signature(b"webhook_secret", br#"{"event":"order.paid"}"#, &signature));
+    }
+
+    #[test]
+    fn test_webhook_event_payment() {
+        let event: RazorpayWebhookEvent = serde_json::from_value(serde_json::json!({
+            "entity": "event",
+            "event": "order.paid",
+            "payload": {
+                "payment": { "entity": {
+                    "id": "pay_1", "status": "captured", "amount": 160, "currency": "USD", "order_id": "order_1"
+                } },
+                "order": { "entity": { "id": "order_1", "amount": 160, "currency": "USD" } }
+            }
+        }))
+        .unwrap();
+        let payment = event.payment().unwrap();
+        assert_eq!((payment.id.as_str(), payment.order_id.as_deref()), ("pay_1", Some("order_1")));
+    }
+
+    #[test]
+    fn test_payment_outcome() {
+        let payment = |status: &str, amount: i64, currency: &str| RazorpayPayment {
+            id: "pay_1".to_string(),
+            status: status.to_string(),
+            amount,
+            currency: currency.to_string(),
+            order_id: Some("order_1".to_string()),
+        };
+
+        assert_eq!(payment_outcome(&payment("captured", 160, "USD"), 160, "usd"), "completed");
+        assert_eq!(payment_outcome(&payment("authorized", 160, "USD"), 160, "usd"), "pending");
+        assert_eq!(payment_outcome(&payment("captured", 100, "USD"), 160, "usd"), "mismatch");
+        assert_eq!(payment_outcome(&payment("captured", 160, "INR"), 160, "usd"), "mismatch");
+        assert_eq!(payment_outcome(&payment("failed", 160, "USD"), 160, "usd"), "mismatch");
+    }
+}
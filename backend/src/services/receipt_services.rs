@@ -0,0 +1,241 @@
+//! PDF receipts for completed purchases. Prices include tax at the configured rate, which the
+//! receipt itemizes; refunds made since the purchase are listed under the total.
+
+use chrono::{DateTime, Utc};
+use rust_decimal::{Decimal, RoundingStrategy};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::transaction::Transaction;
+use crate::services::escrow_services;
+use crate::services::payment_services::TRANSACTION_COLUMNS;
+use crate::services::subscription_services;
+use crate::utils::pdf::{self, Font, Page, A4_HEIGHT, A4_WIDTH};
+
+/// Statuses of transactions a receipt is issued for
+const RECEIPT_STATUSES: &[&str] = &["completed", "partially_refunded", "refunded"];
+
+const MARGIN: f32 = 50.0;
+
+/// What a receipt shows besides the transaction itself
+#[derive(Debug)]
+pub struct ReceiptData {
+    pub transaction: Transaction,
+    pub issued_at: DateTime<Utc>,
+    pub customer_name: String,
+    pub customer_email: String,
+    /// Successful refunds, in the transaction's currency
+    pub refunded: Decimal,
+    /// Name of the chain a crypto payment was made on
+    pub chain_name: Option<String>,
+}
+
+/// The line item for a product type; token transfers and escrow payouts are not purchases
+fn product_label(product_type: &str) -> Option<&'static str> {
+    match product_type {
+        "software_license" => Some("Premium software license"),
+        "documentation" => Some("Documentation access"),
+        "hardware_guide" => Some("Hardware guide"),
+        t if t == subscription_services::PRODUCT_TYPE => Some("Subscription"),
+        t if t == escrow_services::FUNDING_PRODUCT_TYPE => Some("Escrow payment"),
+        _ => None,
+    }
+}
+
+fn payment_method_label(method: &str) -> &str {
+    match method {
+        "stripe" => "Card (Stripe)",
+        "razorpay" => "Razorpay",
+        "crypto" => "Cryptocurrency",
+        other => other,
+    }
+}
+
+/// Receipt number derived from the transaction, stable across downloads
+pub fn receipt_number(transaction_id: Uuid) -> String {
+    format!("R-{}", transaction_id.simple().to_string()[..12].to_uppercase())
+}
+
+/// Decimal places amounts are shown with: cents for fiat, more for tokens
+fn decimals(transaction: &Transaction) -> usize {
+    if transaction.payment_method == "crypto" { 6 } else { 2 }
+}
+
+fn money(amount: Decimal, currency: &str, decimals: usize) -> String {
+    format!("{:.*} {}", decimals, amount, currency.to_uppercase())
+}
+
+/// The net price and the tax included in `total` at `rate_percent`, rounded to `decimals`
+pub fn tax_split(total: Decimal, rate_percent: Decimal, decimals: usize) -> (Decimal, Decimal) {
+    let round = |amount: Decimal| {
+        amount.round_dp_with_strategy(decimals as u32, RoundingStrategy::MidpointAwayFromZero)
+    };
+    let net = round(total / (Decimal::ONE + rate_percent / Decimal::ONE_HUNDRED));
+    (net, round(total - net))
+}
+
+/// One line of `label` and `value`, the value right-aligned
+fn row(page: &mut Page, y: f32, font: Font, label: &str, value: &str) {
+    page.text(MARGIN, y, 10.0, font, label);
+    page.text_right(A4_WIDTH - MARGIN, y, 10.0, font, value);
+}
+
+pub fn render(config: &AppConfig, data: &ReceiptData) -> ApiResult<Vec<u8>> {
+    let transaction = &data.transaction;
+    let label = product_label(&transaction.product_type)
+        .ok_or_else(|| ApiError::ValidationError("No receipt is issued for this transaction".to_string()))?;
+    let number = receipt_number(transaction.id);
+    let places = decimals(transaction);
+    let right = A4_WIDTH - MARGIN;
+    let mut page = Page::new();
+
+    // Issuer on the left, receipt number and date on the right
+    let mut y = A4_HEIGHT - 70.0;
+    page.text(MARGIN, y, 16.0, Font::Bold, &config.receipt_issuer);
+    page.text_right(right, y, 20.0, Font::Bold, "RECEIPT");
+    y -= 18.0;
+    if let Some(address) = &config.receipt_issuer_address {
+        page.text(MARGIN, y, 9.0, Font::Regular, address);
+    }
+    page.text_right(right, y, 10.0, Font::Regular, &format!("Receipt no. {}", number));
+    y -= 14.0;
+    if let Some(tax_id) = &config.receipt_tax_id {
+        page.text(MARGIN, y, 9.0, Font::Regular, &format!("Tax ID: {}", tax_id));
+    }
+    page.text_right(right, y, 10.0, Font::Regular, &format!("Date: {}", data.issued_at.format("%Y-%m-%d")));
+
+    y -= 40.0;
+    page.text(MARGIN, y, 10.0, Font::Bold, "Billed to");
+    y -= 14.0;
+    page.text(MARGIN, y, 10.0, Font::Regular, &data.customer_name);
+    y -= 13.0;
+    page.text(MARGIN, y, 10.0, Font::Regular, &data.customer_email);
+
+    y -= 36.0;
+    row(&mut page, y, Font::Bold, "Description", "Amount");
+    y -= 8.0;
+    page.rule(MARGIN, right, y, 0.75);
+    y -= 16.0;
+    row(&mut page, y, Font::Regular, label, &money(transaction.amount, &transaction.currency, places));
+    y -= 10.0;
+    page.rule(MARGIN, right, y, 0.5);
+
+    y -= 18.0;
+    if config.receipt_tax_rate_percent > Decimal::ZERO {
+        let (net, tax) = tax_split(transaction.amount, config.receipt_tax_rate_percent, places);
+        row(&mut page, y, Font::Regular, "Subtotal", &money(net, &transaction.currency, places));
+        y -= 15.0;
+        let tax_label = format!("Tax ({}%)", config.receipt_tax_rate_percent);
+        row(&mut page, y, Font::Regular, &tax_label, &money(tax, &transaction.currency, places));
+        y -= 15.0;
+    }
+    row(&mut page, y, Font::Bold, "Total paid", &money(transaction.amount, &transaction.currency, places));
+    if data.refunded > Decimal::ZERO {
+        y -= 15.0;
+        let refunded = format!("-{}", money(data.refunded, &transaction.currency, places));
+        row(&mut page, y, Font::Regular, "Refunded", &refunded);
+    }
+
+    y -= 40.0;
+    page.text(MARGIN, y, 10.0, Font::Bold, "Payment");
+    let mut details = vec![
+        ("Method", payment_method_label(&transaction.payment_method).to_string()),
+        ("Reference", transaction.payment_id.clone()),
+        ("Status", transaction.status.replace('_', " ")),
+    ];
+    if let Some(chain) = &data.chain_name {
+        details.push(("Network", chain.clone()));
+    }
+    if let Some(tx_hash) = &transaction.blockchain_tx_hash {
+        details.push(("Transaction hash", tx_hash.clone()));
+    }
+    for (name, value) in details {
+        y -= 15.0;
+        page.text(MARGIN, y, 9.0, Font::Regular, name);
+        page.text(MARGIN + 110.0, y, 9.0, Font::Regular, &value);
+    }
+
+    let thanks = "Thank you for your purchase. Keep this receipt as proof of payment.";
+    page.text(MARGIN, 60.0, 9.0, Font::Regular, thanks);
+    page.text(MARGIN, 46.0, 8.0, Font::Regular, &format!("Transaction {}", transaction.id));
+    Ok(pdf::render(&format!("Receipt {}", number), &[page]))
+}
+
+/// The receipt of one of the user's purchases, with its file name
+pub async fn receipt(
+    pool: &PgPool,
+    config: &AppConfig,
+    user_id: Uuid,
+    transaction_id: Uuid,
+) -> ApiResult<(String, Vec<u8>)> {
+    let transaction = sqlx::query_as::<_, Transaction>(&format!(
+        "SELECT {} FROM transactions WHERE id = $1 AND user_id = $2",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(transaction_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Transaction not found".to_string()))?;
+    if product_label(&transaction.product_type).is_none() {
+        return Err(ApiError::ValidationError("No receipt is issued for this transaction".to_string()));
+    }
+    if !RECEIPT_STATUSES.contains(&transaction.status.as_str()) {
+        return Err(ApiError::Conflict("A receipt is available once the payment completes".to_string()));
+    }
+
+    let (completed_at, customer_name, customer_email, refunded): (Option<DateTime<Utc>>, String, String, Decimal) =
+        sqlx::query_as(
+            "SELECT t.completed_at, u.username, u.email, \
+                    COALESCE((SELECT SUM(amount) FROM refunds \
+                              WHERE transaction_id = t.id AND status = 'succeeded'), 0) \
+             FROM transactions t JOIN users u ON u.id = t.user_id WHERE t.id = $1",
+        )
+        .bind(transaction.id)
+        .fetch_one(pool)
+        .await?;
+    let chain_name = match transaction.payment_method.as_str() {
+        "crypto" => config
+            .chain(transaction.chain_id.map_or(config.default_chain_id, |id| id as u64))
+            .map(|chain| chain.name.clone()),
+        _ => None,
+    };
+    let data = ReceiptData {
+        issued_at: completed_at.unwrap_or(transaction.created_at),
+        customer_name,
+        customer_email,
+        refunded,
+        chain_name,
+        transaction,
+    };
+    let pdf = render(config, &data)?;
+    Ok((format!("receipt-{}.pdf", receipt_number(data.transaction.id)), pdf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tax_split() {
+        let split = |total: Decimal, rate: i64, decimals| tax_split(total, Decimal::from(rate), decimals);
+        assert_eq!(split(Decimal::from(12), 20, 2), (Decimal::TEN, Decimal::TWO));
+        assert_eq!(split(Decimal::new(1999, 2), 18, 2), (Decimal::new(1694, 2), Decimal::new(305, 2)));
+        assert_eq!(split(Decimal::new(16, 1), 0, 6), (Decimal::new(16, 1), Decimal::ZERO));
+    }
+
+    #[test]
+    fn test_receipt_number() {
+        let id = Uuid::parse_str("5f0c2a9e-1b7d-4c3e-9a2f-0123456789ab").unwrap();
+        assert_eq!(receipt_number(id), "R-5F0C2A9E1B7D");
+    }
+
+    #[test]
+    fn test_product_label() {
+        assert_eq!(product_label("documentation"), Some("Documentation access"));
+        assert_eq!(product_label(subscription_services::PRODUCT_TYPE), Some("Subscription"));
+        assert_eq!(product_label("token_transfer"), None);
+        assert_eq!(product_label(escrow_services::PAYOUT_PRODUCT_TYPE), None);
+    }
+}
@@ -0,0 +1,284 @@
+//! Admin payment reconciliation: transactions across all users, discrepancy flags staff set
+//! when our records and the provider's disagree, and on-demand status checks with the provider
+//! for payments stuck in `pending` because a webhook or callback never arrived.
+
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::transaction::{AdminTransactionQuery, ReconciliationTransaction, RecheckResult, Transaction};
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::chain_watch_services::{self, Outcome};
+use crate::services::crypto_services::BlockchainService;
+use crate::services::payment_services::{find_provider_transaction, TRANSACTION_COLUMNS};
+use crate::services::{razorpay_services, stripe_services};
+
+const RECONCILIATION_COLUMNS: &str =
+    "completed_at, failure_reason, discrepancy_note, discrepancy_flagged_by, discrepancy_flagged_at";
+
+const MAX_NOTE_CHARS: usize = 2000;
+const DEFAULT_STUCK_MINUTES: i64 = 30;
+/// Provider lookups made per bulk recheck; the longest pending go first
+const MAX_RECHECKS_PER_CALL: i64 = 50;
+
+/// Transactions of every user matching the filters, newest first
+pub async fn list(pool: &PgPool, query: &AdminTransactionQuery) -> ApiResult<Vec<ReconciliationTransaction>> {
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    let transactions = sqlx::query_as::<_, ReconciliationTransaction>(&format!(
+        "SELECT {}, {} FROM transactions \
+         WHERE ($1::uuid IS NULL OR user_id = $1) \
+           AND ($2::text IS NULL OR payment_method = $2) \
+           AND ($3::text IS NULL OR status = $3) \
+           AND ($4::timestamptz IS NULL OR created_at >= $4) \
+           AND ($5::timestamptz IS NULL OR created_at < $5) \
+           AND ($6::boolean IS NULL OR (discrepancy_note IS NOT NULL) = $6) \
+         ORDER BY created_at DESC, id DESC LIMIT $7 OFFSET $8",
+        TRANSACTION_COLUMNS, RECONCILIATION_COLUMNS
+    ))
+    .bind(query.user_id)
+    .bind(&query.payment_method)
+    .bind(&query.status)
+    .bind(query.from)
+    .bind(query.to)
+    .bind(query.discrepancy)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+    Ok(transactions)
+}
+
+pub async fn get(pool: &PgPool, transaction_id: Uuid) -> ApiResult<ReconciliationTransaction> {
+    sqlx::query_as::<_, ReconciliationTransaction>(&format!(
+        "SELECT {}, {} FROM transactions WHERE id = $1",
+        TRANSACTION_COLUMNS, RECONCILIATION_COLUMNS
+    ))
+    .bind(transaction_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Transaction not found".to_string()))
+}
+
+/// Flag a transaction as not matching the provider's records, or replace the note of one
+/// already flagged
+pub async fn flag_discrepancy(
+    pool: &PgPool,
+    admin_id: Uuid,
+    transaction_id: Uuid,
+    note: &str,
+) -> ApiResult<ReconciliationTransaction> {
+    let note = note.trim();
+    if note.is_empty() || note.chars().count() > MAX_NOTE_CHARS {
+        return Err(ApiError::ValidationError(format!("note must be 1 to {} characters", MAX_NOTE_CHARS)));
+    }
+
+    let mut tx = pool.begin().await?;
+    let flagged = sqlx::query(
+        "UPDATE transactions SET discrepancy_note = $2, discrepancy_flagged_by = $3, discrepancy_flagged_at = NOW() \
+         WHERE id = $1",
+    )
+    .bind(transaction_id)
+    .bind(note)
+    .bind(admin_id)
+    .execute(&mut *tx)
+    .await?;
+    if flagged.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Transaction not found".to_string()));
+    }
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin_id),
+            action: "transaction.discrepancy_flagged",
+            resource_type: "transaction",
+            resource_id: Some(transaction_id.to_string()),
+            details: serde_json::json!({ "note": note }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    get(pool, transaction_id).await
+}
+
+/// Clear a transaction's discrepancy flag once it is reconciled
+pub async fn clear_discrepancy(
+    pool: &PgPool,
+    admin_id: Uuid,
+    transaction_id: Uuid,
+) -> ApiResult<ReconciliationTransaction> {
+    let mut tx = pool.begin().await?;
+    let previous: Option<Option<String>> =
+        sqlx::query_scalar("SELECT discrepancy_note FROM transactions WHERE id = $1 FOR UPDATE")
+            .bind(transaction_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+    let Some(note) = previous.ok_or_else(|| ApiError::NotFound("Transaction not found".to_string()))? else {
+        return Err(ApiError::Conflict("The transaction is not flagged".to_string()));
+    };
+    sqlx::query(
+        "UPDATE transactions SET discrepancy_note = NULL, discrepancy_flagged_by = NULL, \
+         discrepancy_flagged_at = NULL WHERE id = $1",
+    )
+    .bind(transaction_id)
+    .execute(&mut *tx)
+    .await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(admin_id),
+            action: "transaction.discrepancy_cleared",
+            resource_type: "transaction",
+            resource_id: Some(transaction_id.to_string()),
+            details: serde_json::json!({ "note": note }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    get(pool, transaction_id).await
+}
+
+/// The provider's status and what settling on it did
+type Checked = (Option<String>, &'static str);
+
+/// Fetch the PaymentIntent and settle on its status as its webhook would have
+async fn recheck_stripe(pool: &PgPool, config: &AppConfig, transaction: &Transaction) -> ApiResult<Checked> {
+    if !stripe_services::is_configured(config) {
+        return Err(ApiError::ServiceUnavailable("Stripe is not configured".to_string()));
+    }
+    let intent = stripe_services::retrieve_payment_intent(config, &transaction.payment_id).await?;
+    let status = intent["status"].as_str().map(String::from);
+    let Some(outcome) = stripe_services::intent_outcome(&intent) else {
+        return Ok((status, "pending"));
+    };
+
+    let mut tx = pool.begin().await?;
+    let Some(current) = find_provider_transaction(&mut tx, stripe_services::PROVIDER, &transaction.payment_id).await?
+    else {
+        return Err(ApiError::NotFound("Transaction not found".to_string()));
+    };
+    let result = stripe_services::settle_intent(&mut tx, &current, &outcome, &intent).await?;
+    tx.commit().await?;
+    Ok((status, result))
+}
+
+/// Settle the order with its captured payment, if one was made
+async fn recheck_razorpay(pool: &PgPool, config: &AppConfig, transaction: &Transaction) -> ApiResult<Checked> {
+    if !razorpay_services::is_configured(config) {
+        return Err(ApiError::ServiceUnavailable("Razorpay is not configured".to_string()));
+    }
+    let payments = razorpay_services::order_payments(config, &transaction.payment_id).await?;
+    let Some(captured) = payments.iter().find(|payment| payment.status == "captured") else {
+        // Failed attempts leave the order open for another try
+        return Ok((payments.last().map(|payment| payment.status.clone()), "pending"));
+    };
+    let (_, result) = razorpay_services::settle_payment(pool, captured).await?;
+    // The checkout callback already handled this payment
+    let result = if result == "duplicate" { "ignored" } else { result };
+    Ok((Some(captured.status.clone()), result))
+}
+
+/// Look the transaction hash up on its chain as the confirmation watcher does
+async fn recheck_crypto(pool: &PgPool, config: &AppConfig, transaction: &Transaction) -> ApiResult<Checked> {
+    let Some(hash) = transaction.blockchain_tx_hash.as_deref() else {
+        return Err(ApiError::Conflict("No transaction hash has been submitted for this payment".to_string()));
+    };
+    let chain_id = transaction.chain_id.map_or(config.default_chain_id, |id| id as u64);
+    let chain = BlockchainService::for_chain_id(config, chain_id)?;
+    if !chain.has_provider() {
+        return Err(ApiError::ServiceUnavailable(format!("No RPC provider is configured for chain {}", chain_id)));
+    }
+    let required = config.chain(chain_id).map_or(1, |chain| chain.required_confirmations);
+
+    let head = chain.block_number().await?;
+    let receipt = if BlockchainService::is_valid_tx_hash(hash) { chain.transaction_receipt(hash).await? } else { None };
+    let applied = chain_watch_services::apply_receipt(pool, transaction.id, receipt, head, required).await?;
+    let Some((decided, changed)) = applied else {
+        return Ok((None, "ignored"));
+    };
+    let status = match decided {
+        Outcome::Waiting { confirmations, .. } => format!("{} of {} confirmations", confirmations, required),
+        Outcome::Confirmed { .. } => "confirmed".to_string(),
+        Outcome::Reverted { .. } => "reverted".to_string(),
+        Outcome::Dropped => "dropped".to_string(),
+    };
+    let result = match decided {
+        Outcome::Waiting { .. } => "pending",
+        _ if !changed => "ignored",
+        Outcome::Confirmed { .. } => "completed",
+        Outcome::Reverted { .. } | Outcome::Dropped => "failed",
+    };
+    Ok((Some(status), result))
+}
+
+/// Ask the provider what became of a pending transaction and settle it accordingly
+pub async fn recheck(pool: &PgPool, config: &AppConfig, transaction_id: Uuid) -> ApiResult<RecheckResult> {
+    let transaction = sqlx::query_as::<_, Transaction>(&format!(
+        "SELECT {} FROM transactions WHERE id = $1",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(transaction_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Transaction not found".to_string()))?;
+    if transaction.status != "pending" {
+        return Err(ApiError::Conflict(format!("The transaction is already {}", transaction.status)));
+    }
+
+    let (provider_status, outcome) = match transaction.payment_method.as_str() {
+        stripe_services::PROVIDER => recheck_stripe(pool, config, &transaction).await?,
+        razorpay_services::PROVIDER => recheck_razorpay(pool, config, &transaction).await?,
+        "crypto" => recheck_crypto(pool, config, &transaction).await?,
+        other => {
+            return Err(ApiError::ValidationError(format!("Payments made with {} cannot be rechecked", other)));
+        }
+    };
+    Ok(RecheckResult {
+        transaction_id,
+        payment_method: transaction.payment_method,
+        provider_status,
+        outcome,
+        error: None,
+    })
+}
+
+/// Recheck the transactions pending for at least `older_than_minutes`, longest waiting first.
+/// A failed lookup is reported in its result rather than stopping the rest.
+pub async fn recheck_stuck(
+    pool: &PgPool,
+    config: &AppConfig,
+    older_than_minutes: Option<i64>,
+) -> ApiResult<Vec<RecheckResult>> {
+    let minutes = older_than_minutes.unwrap_or(DEFAULT_STUCK_MINUTES).max(0);
+    let stuck: Vec<(Uuid, String)> = sqlx::query_as(
+        "SELECT id, payment_method FROM transactions WHERE status = 'pending' AND created_at <= $1 \
+         ORDER BY created_at LIMIT $2",
+    )
+    .bind(Utc::now() - Duration::minutes(minutes))
+    .bind(MAX_RECHECKS_PER_CALL)
+    .fetch_all(pool)
+    .await?;
+
+    let mut results = Vec::with_capacity(stuck.len());
+    for (transaction_id, payment_method) in stuck {
+        let result = match recheck(pool, config, transaction_id).await {
+            Ok(result) => result,
+            Err(e) => {
+                tracing::warn!(%transaction_id, "Payment recheck failed: {}", e);
+                RecheckResult {
+                    transaction_id,
+                    payment_method,
+                    provider_status: None,
+                    outcome: "error",
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+        results.push(result);
+    }
+    Ok(results)
+}
@@ -0,0 +1,288 @@
+//! Refunds of completed transactions. A refund is reserved in the database before the
+//! provider is called, so concurrent requests cannot refund more than was paid, and settled
+//! with the provider's answer. A transaction refunded in full loses what it unlocked.
+
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::transaction::{Refund, RefundRequest, Transaction};
+use crate::services::notification_services::notify_user;
+use crate::services::payment_services::{from_minor_units, minor_units, revoke_product, TRANSACTION_COLUMNS};
+use crate::services::{
+    escrow_services, ledger_services, payout_services, razorpay_services, stripe_services, transfer_services,
+};
+
+pub const REFUND_COLUMNS: &str = "id, transaction_id, amount, currency, provider, provider_refund_id, status, manual, \
+     reason, failure_reason, requested_by, created_at, updated_at";
+
+/// Buyers may refund their own purchases for this long after paying; admins at any time
+pub const SELF_SERVICE_REFUND_DAYS: i64 = 14;
+
+/// The amount to refund: as requested, or everything not yet refunded. A requested amount
+/// must be a whole number of the currency's minor units, as providers refund in those.
+pub fn refund_amount(
+    requested: Option<Decimal>,
+    paid: Decimal,
+    refunded: Decimal,
+    currency: &str,
+) -> ApiResult<Decimal> {
+    let remaining = minor_units(paid, currency) - minor_units(refunded, currency);
+    if remaining <= 0 {
+        return Err(ApiError::Conflict("Transaction is already fully refunded".to_string()));
+    }
+    match requested {
+        None => Ok(paid - refunded),
+        Some(amount)
+            if from_minor_units(minor_units(amount, currency), currency) == amount
+                && (1..=remaining).contains(&minor_units(amount, currency)) =>
+        {
+            Ok(amount)
+        }
+        Some(_) => Err(ApiError::ValidationError(format!(
+            "amount must be positive and at most the {:.2} {} not yet refunded",
+            paid - refunded,
+            currency.to_uppercase()
+        ))),
+    }
+}
+
+/// Our status for a provider's refund status
+pub fn refund_status(provider_status: &str) -> &'static str {
+    match provider_status {
+        "succeeded" | "processed" => "succeeded",
+        "failed" | "canceled" => "failed",
+        _ => "pending",
+    }
+}
+
+async fn refunded_total(conn: &mut PgConnection, transaction_id: Uuid) -> ApiResult<Decimal> {
+    Ok(sqlx::query_scalar(
+        "SELECT COALESCE(SUM(amount), 0) FROM refunds \
+         WHERE transaction_id = $1 AND status <> 'failed'",
+    )
+    .bind(transaction_id)
+    .fetch_one(conn)
+    .await?)
+}
+
+async fn lock_transaction(conn: &mut PgConnection, transaction_id: Uuid) -> ApiResult<Transaction> {
+    sqlx::query_as::<_, Transaction>(&format!(
+        "SELECT {} FROM transactions WHERE id = $1 FOR UPDATE",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(transaction_id)
+    .fetch_optional(conn)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Transaction not found".to_string()))
+}
+
+/// Bring the transaction's status in line with its refunds and tell the buyer
+async fn apply_refund(conn: &mut PgConnection, transaction: &Transaction, refund: &Refund) -> ApiResult<()> {
+    let refunded = refunded_total(conn, transaction.id).await?;
+    let full = refunded >= transaction.amount;
+    sqlx::query("UPDATE transactions SET status = $2 WHERE id = $1")
+        .bind(transaction.id)
+        .bind(if full { "refunded" } else { "partially_refunded" })
+        .execute(&mut *conn)
+        .await?;
+    ledger_services::post_refund(conn, transaction, refund).await?;
+    if full {
+        revoke_product(conn, transaction).await?;
+    }
+
+    notify_user(
+        conn,
+        transaction.user_id,
+        "payment_refunded",
+        "Refund issued",
+        &format!(
+            "{:.2} {} of your payment for {} is being refunded.",
+            refund.amount,
+            refund.currency.to_uppercase(),
+            transaction.product_type.replace('_', " ")
+        ),
+        serde_json::json!({ "transaction_id": transaction.id, "refund_id": refund.id }),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Refund a completed transaction through its payment provider, or record a manual refund.
+/// Buyers may refund their own recent purchases; admins any purchase, and only they may
+/// record manual refunds.
+pub async fn refund_transaction(
+    pool: &PgPool,
+    config: &AppConfig,
+    transaction_id: Uuid,
+    actor_id: Uuid,
+    is_admin: bool,
+    request: &RefundRequest,
+) -> ApiResult<Refund> {
+    if request.manual && !is_admin {
+        return Err(ApiError::Forbidden("Only admins can record manual refunds".to_string()));
+    }
+    if request.reason.as_ref().is_some_and(|r| r.len() > 500) {
+        return Err(ApiError::ValidationError("reason must be at most 500 characters".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+    let transaction = lock_transaction(&mut tx, transaction_id).await?;
+    if !is_admin && transaction.user_id != actor_id {
+        return Err(ApiError::NotFound("Transaction not found".to_string()));
+    }
+    if transaction.product_type == transfer_services::PRODUCT_TYPE {
+        return Err(ApiError::ValidationError("Token transfers are final and cannot be refunded".to_string()));
+    }
+    let escrow_types = [escrow_services::FUNDING_PRODUCT_TYPE, escrow_services::PAYOUT_PRODUCT_TYPE];
+    if escrow_types.contains(&transaction.product_type.as_str()) {
+        return Err(ApiError::ValidationError("Escrow payments are refunded through their escrow".to_string()));
+    }
+    if transaction.product_type == payout_services::PRODUCT_TYPE {
+        return Err(ApiError::ValidationError("Payouts cannot be refunded".to_string()));
+    }
+    if !matches!(transaction.status.as_str(), "completed" | "partially_refunded") {
+        return Err(ApiError::Conflict(format!(
+            "Only completed transactions can be refunded; this one is {}",
+            transaction.status
+        )));
+    }
+    if !is_admin {
+        let paid_at: Option<DateTime<Utc>> = sqlx::query_scalar("SELECT completed_at FROM transactions WHERE id = $1")
+            .bind(transaction.id)
+            .fetch_one(&mut *tx)
+            .await?;
+        if paid_at.unwrap_or(transaction.created_at) < Utc::now() - Duration::days(SELF_SERVICE_REFUND_DAYS) {
+            return Err(ApiError::Forbidden(format!(
+                "Refunds can be requested within {} days of payment",
+                SELF_SERVICE_REFUND_DAYS
+            )));
+        }
+    }
+    let refunded = refunded_total(&mut tx, transaction.id).await?;
+    let amount = refund_amount(request.amount, transaction.amount, refunded, &transaction.currency)?;
+
+    // The provider's id for the payment being refunded
+    let provider_payment = if request.manual {
+        None
+    } else {
+        match transaction.payment_method.as_str() {
+            stripe_services::PROVIDER if stripe_services::is_configured(config) => Some(transaction.payment_id.clone()),
+            razorpay_services::PROVIDER if razorpay_services::is_configured(config) => {
+                let payment_id: Option<String> = sqlx::query_scalar(
+                    "SELECT event_id FROM payment_provider_events WHERE provider = $1 AND transaction_id = $2 \
+                     AND event_type = 'payment.captured' AND outcome = 'completed'",
+                )
+                .bind(razorpay_services::PROVIDER)
+                .bind(transaction.id)
+                .fetch_optional(&mut *tx)
+                .await?;
+                Some(payment_id.ok_or_else(|| {
+                    ApiError::Conflict("No captured Razorpay payment is recorded for this transaction".to_string())
+                })?)
+            }
+            provider @ (stripe_services::PROVIDER | razorpay_services::PROVIDER) => {
+                return Err(ApiError::ServiceUnavailable(format!("{} is not configured", provider)));
+            }
+            method => {
+                return Err(ApiError::ValidationError(format!(
+                    "{} payments cannot be refunded through a provider; an admin can record a manual refund",
+                    method
+                )));
+            }
+        }
+    };
+
+    let refund = sqlx::query_as::<_, Refund>(&format!(
+        "INSERT INTO refunds (transaction_id, amount, currency, provider, status, manual, reason, requested_by) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING {}",
+        REFUND_COLUMNS
+    ))
+    .bind(transaction.id)
+    .bind(amount)
+    .bind(&transaction.currency)
+    .bind(provider_payment.as_ref().map(|_| transaction.payment_method.as_str()))
+    .bind(if request.manual { "succeeded" } else { "requested" })
+    .bind(request.manual)
+    .bind(&request.reason)
+    .bind(actor_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    let Some(provider_payment) = provider_payment else {
+        apply_refund(&mut tx, &transaction, &refund).await?;
+        tx.commit().await?;
+        return Ok(refund);
+    };
+    // Reserved: later requests count this refund until it is known to have failed
+    tx.commit().await?;
+
+    let minor = minor_units(amount, &transaction.currency);
+    let receipt = refund.id.to_string();
+    let result = match transaction.payment_method.as_str() {
+        stripe_services::PROVIDER => stripe_services::create_refund(config, &provider_payment, minor, &receipt).await,
+        _ => razorpay_services::create_refund(config, &provider_payment, minor, &receipt).await,
+    };
+
+    let mut tx = pool.begin().await?;
+    let transaction = lock_transaction(&mut tx, transaction.id).await?;
+    let (status, provider_refund_id, failure_reason) = match &result {
+        Ok(provider_refund) => (refund_status(&provider_refund.status), Some(provider_refund.id.as_str()), None),
+        Err(e) => ("failed", None, Some(e.to_string())),
+    };
+    let refund = sqlx::query_as::<_, Refund>(&format!(
+        "UPDATE refunds SET status = $2, provider_refund_id = $3, failure_reason = $4, updated_at = NOW() \
+         WHERE id = $1 RETURNING {}",
+        REFUND_COLUMNS
+    ))
+    .bind(refund.id)
+    .bind(status)
+    .bind(provider_refund_id)
+    .bind(&failure_reason)
+    .fetch_one(&mut *tx)
+    .await?;
+    if status != "failed" {
+        apply_refund(&mut tx, &transaction, &refund).await?;
+    }
+    tx.commit().await?;
+
+    match result {
+        Err(e) => Err(e),
+        Ok(_) if status == "failed" => Err(ApiError::PaymentError("The provider declined the refund".to_string())),
+        Ok(_) => Ok(refund),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refund_amount() {
+        let paid = Decimal::new(16, 1);
+        let half = Decimal::new(5, 1);
+        assert_eq!(refund_amount(None, paid, Decimal::ZERO, "usd").unwrap(), paid);
+        assert_eq!(refund_amount(None, paid, half, "usd").unwrap(), Decimal::new(11, 1));
+        assert_eq!(refund_amount(Some(half), paid, Decimal::ZERO, "usd").unwrap(), half);
+        assert!(refund_amount(Some(Decimal::new(110, 2)), paid, half, "usd").is_ok());
+
+        let invalid = |requested: Decimal, refunded: Decimal| {
+            matches!(refund_amount(Some(requested), paid, refunded, "usd"), Err(ApiError::ValidationError(_)))
+        };
+        assert!(invalid(Decimal::new(12, 1), half));
+        assert!(invalid(Decimal::ZERO, Decimal::ZERO));
+        // Less than a cent cannot be refunded
+        assert!(invalid(Decimal::new(1005, 3), Decimal::ZERO));
+        assert!(matches!(refund_amount(None, paid, paid, "usd"), Err(ApiError::Conflict(_))));
+    }
+
+    #[test]
+    fn test_refund_status() {
+        assert_eq!(refund_status("succeeded"), "succeeded");
+        assert_eq!(refund_status("processed"), "succeeded");
+        assert_eq!(refund_status("pending"), "pending");
+        assert_eq!(refund_status("requires_action"), "pending");
+        assert_eq!(refund_status("canceled"), "failed");
+    }
+}
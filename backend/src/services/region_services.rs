@@ -0,0 +1,299 @@
+//! Geographic regions of an org. A device belongs to every region of its owner's orgs whose
+//! polygon contains its last reported position; membership is kept current as telemetry
+//! arrives and recomputed when a region's polygon changes. Fleet endpoints accept a
+//! `region_id` to narrow them to the devices in a region, and alerts raised by those devices
+//! also reach the region's team.
+
+use chrono::{Duration, Utc};
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::collections::HashSet;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::middleware::AuthenticatedUser;
+use crate::models::region::{GeoPoint, Region, RegionDashboard, RegionDevice, StatusCount};
+use crate::services::org_services::require_org_permission;
+use crate::services::policy_services::OrgAction;
+use crate::utils::geo::{is_valid_coordinate, polygon_area_deg2, polygon_bounds, polygon_contains};
+
+pub const REGION_COLUMNS: &str = "id, org_id, name, description, polygon, created_by, created_at, updated_at";
+
+pub const MAX_REGIONS_PER_ORG: i64 = 100;
+pub const MAX_VERTICES: usize = 500;
+pub const MAX_TEAM_SIZE: usize = 50;
+/// Battery percentage below which the dashboard counts a device as low
+const LOW_BATTERY_PERCENT: i16 = 20;
+
+/// SQL condition keeping rows whose device id `column` is in the region bound at `param`, or
+/// every row when it is bound to NULL
+pub fn region_filter(column: &str, param: usize) -> String {
+    format!(
+        "(${1}::uuid IS NULL OR {0} IN (SELECT device_id FROM device_regions WHERE region_id = ${1}))",
+        column, param
+    )
+}
+
+/// Check a polygon and return its vertices as (lat, lng)
+pub fn validate_polygon(polygon: &[GeoPoint]) -> ApiResult<Vec<(f64, f64)>> {
+    if polygon.len() < 3 || polygon.len() > MAX_VERTICES {
+        return Err(ApiError::ValidationError(format!("polygon needs 3-{} vertices", MAX_VERTICES)));
+    }
+    if polygon.iter().any(|p| !is_valid_coordinate(p.latitude, p.longitude)) {
+        return Err(ApiError::ValidationError("polygon has a vertex out of range".to_string()));
+    }
+    let vertices: Vec<(f64, f64)> = polygon.iter().map(|p| (p.latitude, p.longitude)).collect();
+    let (_, _, min_lng, max_lng) = polygon_bounds(&vertices);
+    // Containment is computed on plain degrees, which goes the long way round across ±180°
+    if max_lng - min_lng > 180.0 {
+        return Err(ApiError::ValidationError(
+            "polygon may not span more than 180° of longitude; split regions crossing the antimeridian".to_string(),
+        ));
+    }
+    if polygon_area_deg2(&vertices) < 1e-10 {
+        return Err(ApiError::ValidationError("polygon encloses no area".to_string()));
+    }
+    Ok(vertices)
+}
+
+pub fn validate_name(name: &str) -> ApiResult<&str> {
+    let name = name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return Err(ApiError::ValidationError("name must be 1-100 characters".to_string()));
+    }
+    Ok(name)
+}
+
+pub async fn org_region(pool: &PgPool, org_id: Uuid, region_id: Uuid) -> ApiResult<Region> {
+    sqlx::query_as::<_, Region>(&format!("SELECT {} FROM regions WHERE id = $1 AND org_id = $2", REGION_COLUMNS))
+        .bind(region_id)
+        .bind(org_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Region not found".to_string()))
+}
+
+/// Check a `region_id` filter on an org endpoint belongs to that org
+pub async fn require_org_filter(pool: &PgPool, org_id: Uuid, region_id: Option<Uuid>) -> ApiResult<Option<Uuid>> {
+    if let Some(region_id) = region_id {
+        org_region(pool, org_id, region_id).await?;
+    }
+    Ok(region_id)
+}
+
+/// Check the user may filter their own devices by a region: they must be able to read the
+/// device history of the org the region belongs to
+pub async fn require_user_filter(
+    pool: &PgPool,
+    user: &AuthenticatedUser,
+    region_id: Option<Uuid>,
+) -> ApiResult<Option<Uuid>> {
+    let Some(region_id) = region_id else {
+        return Ok(None);
+    };
+    let org_id: Uuid = sqlx::query_scalar("SELECT org_id FROM regions WHERE id = $1")
+        .bind(region_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Region not found".to_string()))?;
+    require_org_permission(pool, org_id, user, OrgAction::ReadDeviceHistory).await?;
+    Ok(Some(region_id))
+}
+
+#[derive(FromRow)]
+struct Candidate {
+    id: Uuid,
+    polygon: sqlx::types::Json<Vec<GeoPoint>>,
+}
+
+fn contains(polygon: &[GeoPoint], latitude: f64, longitude: f64) -> bool {
+    let vertices: Vec<(f64, f64)> = polygon.iter().map(|p| (p.latitude, p.longitude)).collect();
+    polygon_contains(&vertices, latitude, longitude)
+}
+
+/// Put a device in the regions containing its new position and take it out of the rest.
+/// Regions it stays in keep their `entered_at`.
+pub async fn assign_device(conn: &mut PgConnection, device_id: Uuid, latitude: f64, longitude: f64) -> ApiResult<()> {
+    let candidates = sqlx::query_as::<_, Candidate>(
+        "SELECT r.id, r.polygon FROM regions r \
+         WHERE r.org_id IN (SELECT m.org_id FROM org_memberships m JOIN devices d ON d.user_id = m.user_id \
+                            WHERE d.id = $1 AND m.active) \
+           AND $2 BETWEEN r.min_lat AND r.max_lat AND $3 BETWEEN r.min_lng AND r.max_lng",
+    )
+    .bind(device_id)
+    .bind(latitude)
+    .bind(longitude)
+    .fetch_all(&mut *conn)
+    .await?;
+    let inside: Vec<Uuid> =
+        candidates.into_iter().filter(|c| contains(&c.polygon, latitude, longitude)).map(|c| c.id).collect();
+
+    sqlx::query("DELETE FROM device_regions WHERE device_id = $1 AND NOT (region_id = ANY($2))")
+        .bind(device_id)
+        .bind(&inside)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query(
+        "INSERT INTO device_regions (device_id, region_id) SELECT $1, UNNEST($2::uuid[]) \
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(device_id)
+    .bind(&inside)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Recompute which of the org's devices are in a region after it was created or reshaped.
+/// Returns how many devices it now holds.
+pub async fn reassign_region(conn: &mut PgConnection, region_id: Uuid) -> ApiResult<usize> {
+    let positions: Vec<(Uuid, f64, f64)> = sqlx::query_as(
+        "SELECT d.id, d.last_latitude, d.last_longitude FROM devices d JOIN regions r ON r.id = $1 \
+         WHERE d.user_id IN (SELECT user_id FROM org_memberships WHERE org_id = r.org_id AND active) \
+           AND d.last_latitude BETWEEN r.min_lat AND r.max_lat AND d.last_longitude BETWEEN r.min_lng AND r.max_lng",
+    )
+    .bind(region_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    let polygon: sqlx::types::Json<Vec<GeoPoint>> = sqlx::query_scalar("SELECT polygon FROM regions WHERE id = $1")
+        .bind(region_id)
+        .fetch_one(&mut *conn)
+        .await?;
+    let inside: Vec<Uuid> = positions
+        .into_iter()
+        .filter(|(_, latitude, longitude)| contains(&polygon, *latitude, *longitude))
+        .map(|(id, _, _)| id)
+        .collect();
+
+    sqlx::query("DELETE FROM device_regions WHERE region_id = $1 AND NOT (device_id = ANY($2))")
+        .bind(region_id)
+        .bind(&inside)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query(
+        "INSERT INTO device_regions (device_id, region_id) SELECT UNNEST($2::uuid[]), $1 \
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(region_id)
+    .bind(&inside)
+    .execute(&mut *conn)
+    .await?;
+    Ok(inside.len())
+}
+
+/// Team members of the regions a device is in, other than `exclude`. Members who have since
+/// left the region's org are skipped.
+pub async fn alert_recipients(pool: &PgPool, device_id: Uuid, exclude: Uuid) -> ApiResult<Vec<Uuid>> {
+    let recipients: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT DISTINCT rm.user_id FROM device_regions dr \
+         JOIN regions r ON r.id = dr.region_id \
+         JOIN region_members rm ON rm.region_id = r.id \
+         JOIN org_memberships m ON m.org_id = r.org_id AND m.user_id = rm.user_id AND m.active \
+         WHERE dr.device_id = $1 AND rm.user_id <> $2",
+    )
+    .bind(device_id)
+    .bind(exclude)
+    .fetch_all(pool)
+    .await?;
+    Ok(recipients)
+}
+
+/// Replace a region's team. Every user must be an active member of the region's org.
+pub async fn set_team(conn: &mut PgConnection, region: &Region, user_ids: &[Uuid]) -> ApiResult<Vec<Uuid>> {
+    let team: Vec<Uuid> = user_ids.iter().copied().collect::<HashSet<_>>().into_iter().collect();
+    if team.len() > MAX_TEAM_SIZE {
+        return Err(ApiError::ValidationError(format!("A region team has at most {} members", MAX_TEAM_SIZE)));
+    }
+    let members: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM org_memberships WHERE org_id = $1 AND active AND user_id = ANY($2)",
+    )
+    .bind(region.org_id)
+    .bind(&team)
+    .fetch_one(&mut *conn)
+    .await?;
+    if members as usize != team.len() {
+        return Err(ApiError::ValidationError("Every team member must belong to the organization".to_string()));
+    }
+
+    sqlx::query("DELETE FROM region_members WHERE region_id = $1 AND NOT (user_id = ANY($2))")
+        .bind(region.id)
+        .bind(&team)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query(
+        "INSERT INTO region_members (region_id, user_id) SELECT $1, UNNEST($2::uuid[]) ON CONFLICT DO NOTHING",
+    )
+    .bind(region.id)
+    .bind(&team)
+    .execute(&mut *conn)
+    .await?;
+    Ok(team)
+}
+
+/// Status, battery and freshness of the devices in a region
+pub async fn dashboard(pool: &PgPool, region: Region) -> ApiResult<RegionDashboard> {
+    let devices = sqlx::query_as::<_, RegionDevice>(
+        "SELECT d.id, d.user_id, d.device_name, d.device_type, d.status, \
+                (SELECT t.battery_level FROM device_telemetry t WHERE t.device_id = d.id \
+                 ORDER BY t.recorded_at DESC LIMIT 1) AS battery_level, \
+                d.last_latitude, d.last_longitude, d.last_seen, dr.entered_at \
+         FROM device_regions dr JOIN devices d ON d.id = dr.device_id \
+         WHERE dr.region_id = $1 \
+           AND d.user_id IN (SELECT user_id FROM org_memberships WHERE org_id = $2 AND active) \
+         ORDER BY d.device_name",
+    )
+    .bind(region.id)
+    .bind(region.org_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut by_status: Vec<StatusCount> = Vec::new();
+    for device in &devices {
+        match by_status.iter_mut().find(|s| s.status == device.status) {
+            Some(count) => count.devices += 1,
+            None => by_status.push(StatusCount { status: device.status.clone(), devices: 1 }),
+        }
+    }
+    by_status.sort_by(|a, b| a.status.cmp(&b.status));
+    let batteries: Vec<f64> = devices.iter().filter_map(|d| d.battery_level).map(f64::from).collect();
+    let stale_before = Utc::now() - Duration::hours(1);
+
+    Ok(RegionDashboard {
+        device_count: devices.len(),
+        by_status,
+        average_battery: (!batteries.is_empty()).then(|| batteries.iter().sum::<f64>() / batteries.len() as f64),
+        low_battery: devices.iter().filter(|d| d.battery_level.is_some_and(|b| b < LOW_BATTERY_PERCENT)).count(),
+        stale: devices.iter().filter(|d| d.last_seen.is_none_or(|seen| seen < stale_before)).count(),
+        region,
+        devices,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(latitude: f64, longitude: f64) -> GeoPoint {
+        GeoPoint { latitude, longitude }
+    }
+
+    #[test]
+    fn test_validate_polygon() {
+        let square = [point(0.0, 0.0), point(0.0, 1.0), point(1.0, 1.0), point(1.0, 0.0)];
+        assert_eq!(validate_polygon(&square).unwrap().len(), 4);
+        assert!(contains(&square, 0.5, 0.5));
+
+        assert!(validate_polygon(&square[..2]).is_err());
+        assert!(validate_polygon(&[point(0.0, 0.0), point(0.0, 1.0), point(91.0, 1.0)]).is_err());
+        // Collinear vertices
+        assert!(validate_polygon(&[point(0.0, 0.0), point(1.0, 1.0), point(2.0, 2.0)]).is_err());
+        // Across the antimeridian
+        assert!(validate_polygon(&[point(0.0, 170.0), point(0.0, -170.0), point(1.0, -170.0)]).is_err());
+    }
+
+    #[test]
+    fn test_region_filter() {
+        assert_eq!(
+            region_filter("d.id", 4),
+            "($4::uuid IS NULL OR d.id IN (SELECT device_id FROM device_regions WHERE region_id = $4))"
+        );
+    }
+}
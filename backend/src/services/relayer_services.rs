@@ -0,0 +1,306 @@
+//! Transactions the platform sends itself, such as device certificate mints. They are signed
+//! as EIP-1559 (type 2) transactions with the relayer key from `RELAYER_PRIVATE_KEY`, which
+//! pays their gas.
+
+use k256::ecdsa::SigningKey;
+use secrecy::ExposeSecret;
+use sha3::{Digest, Keccak256};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::services::crypto_services::{parse_quantity, BlockchainService};
+use crate::services::gas_services;
+
+/// Headroom over `eth_estimateGas`, in percent, for state changing between estimate and inclusion
+const GAS_LIMIT_MARGIN_PERCENT: u64 = 20;
+/// Floor of the tip, so a quiet fee history does not leave the transaction unattractive
+const MIN_PRIORITY_FEE_WEI: u128 = 1_000_000_000;
+
+/// One send at a time per chain, so concurrent sends do not take the same nonce
+static SEND_LOCKS: LazyLock<Mutex<HashMap<u64, Arc<tokio::sync::Mutex<()>>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Append an RLP string header for a payload of `len` bytes (`offset` 0x80) or a list
+/// header (`offset` 0xc0)
+fn rlp_header(out: &mut Vec<u8>, offset: u8, len: usize) {
+    if len < 56 {
+        out.push(offset + len as u8);
+    } else {
+        let len_bytes: Vec<u8> = len.to_be_bytes().into_iter().skip_while(|b| *b == 0).collect();
+        out.push(offset + 55 + len_bytes.len() as u8);
+        out.extend_from_slice(&len_bytes);
+    }
+}
+
+pub fn rlp_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        out.push(bytes[0]);
+    } else {
+        rlp_header(out, 0x80, bytes.len());
+        out.extend_from_slice(bytes);
+    }
+}
+
+/// An integer is its big-endian bytes without leading zeros; zero is the empty string
+pub fn rlp_uint(out: &mut Vec<u8>, value: u128) {
+    let bytes: Vec<u8> = value.to_be_bytes().into_iter().skip_while(|b| *b == 0).collect();
+    rlp_bytes(out, &bytes);
+}
+
+/// Wrap already-encoded items in a list
+pub fn rlp_list(items: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(items.len() + 9);
+    rlp_header(&mut out, 0xc0, items.len());
+    out.extend_from_slice(items);
+    out
+}
+
+/// An EIP-1559 transaction without an access list
+#[derive(Debug, Clone)]
+pub struct Eip1559Transaction {
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub max_priority_fee_per_gas: u128,
+    pub max_fee_per_gas: u128,
+    pub gas_limit: u64,
+    pub to: [u8; 20],
+    pub value: u128,
+    pub data: Vec<u8>,
+}
+
+impl Eip1559Transaction {
+    fn encode_fields(&self, out: &mut Vec<u8>) {
+        rlp_uint(out, self.chain_id as u128);
+        rlp_uint(out, self.nonce as u128);
+        rlp_uint(out, self.max_priority_fee_per_gas);
+        rlp_uint(out, self.max_fee_per_gas);
+        rlp_uint(out, self.gas_limit as u128);
+        rlp_bytes(out, &self.to);
+        rlp_uint(out, self.value);
+        rlp_bytes(out, &self.data);
+        out.extend_from_slice(&rlp_list(&[]));
+    }
+
+    /// keccak256(0x02 || rlp(fields)), the digest the sender signs
+    pub fn signing_hash(&self) -> [u8; 32] {
+        let mut fields = Vec::new();
+        self.encode_fields(&mut fields);
+        let mut hasher = Keccak256::new();
+        hasher.update([0x02]);
+        hasher.update(rlp_list(&fields));
+        hasher.finalize().into()
+    }
+
+    /// The signed transaction as sent with `eth_sendRawTransaction`
+    pub fn sign(&self, key: &SigningKey) -> ApiResult<Vec<u8>> {
+        let (signature, recovery_id) = key
+            .sign_prehash_recoverable(&self.signing_hash())
+            .map_err(|e| ApiError::InternalError(format!("Signing the transaction failed: {}", e)))?;
+        let (r, s) = signature.split_bytes();
+
+        let mut fields = Vec::new();
+        self.encode_fields(&mut fields);
+        rlp_uint(&mut fields, recovery_id.is_y_odd() as u128);
+        // r and s are integers too: no leading zeros
+        rlp_bytes(&mut fields, trim_leading_zeros(&r));
+        rlp_bytes(&mut fields, trim_leading_zeros(&s));
+
+        let mut raw = vec![0x02];
+        raw.extend(rlp_list(&fields));
+        Ok(raw)
+    }
+}
+
+fn trim_leading_zeros(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+/// A hex address as its 20 bytes
+pub fn address_bytes(address: &str) -> ApiResult<[u8; 20]> {
+    BlockchainService::validate_address(address)?;
+    let bytes = hex::decode(&address[2..]).map_err(|_| ApiError::ValidationError("Invalid address".to_string()))?;
+    bytes.try_into().map_err(|_| ApiError::ValidationError("Invalid address".to_string()))
+}
+
+/// First four bytes of keccak256 of a function signature
+pub fn selector(signature: &str) -> [u8; 4] {
+    let hash = Keccak256::digest(signature.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// An integer as a 32-byte ABI word
+pub fn uint_word(value: u128) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[16..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+/// An address as a 32-byte ABI word
+pub fn address_word(address: &str) -> ApiResult<[u8; 32]> {
+    let mut word = [0u8; 32];
+    word[12..].copy_from_slice(&address_bytes(address)?);
+    Ok(word)
+}
+
+/// The account behind `RELAYER_PRIVATE_KEY`
+pub struct Relayer {
+    key: SigningKey,
+    address: String,
+}
+
+impl Relayer {
+    /// The configured relayer; 503 when there is none
+    pub fn from_config(config: &AppConfig) -> ApiResult<Self> {
+        let unavailable = |reason: &str| ApiError::ServiceUnavailable(format!("Transaction relayer {}", reason));
+        let secret = config.relayer_private_key.as_ref().ok_or_else(|| unavailable("is not configured"))?;
+        let hex_key = secret.expose_secret().trim();
+        let bytes = hex::decode(hex_key.strip_prefix("0x").unwrap_or(hex_key))
+            .map_err(|_| unavailable("key is not valid hex"))?;
+        let key = SigningKey::from_slice(&bytes).map_err(|_| unavailable("key is not a valid secp256k1 key"))?;
+        Ok(Self::new(key))
+    }
+
+    pub fn new(key: SigningKey) -> Self {
+        let address = BlockchainService::address_of(key.verifying_key());
+        Self { key, address }
+    }
+
+    /// Lower-case hex address the relayer sends from
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Sign and submit a call of `to` with `data` (and `value` wei of native coin), paying the
+    /// standard fee tier. Returns the transaction hash once the node has accepted it; it may
+    /// still fail on-chain.
+    pub async fn send(&self, chain: &BlockchainService, to: &str, data: Vec<u8>, value: u128) -> ApiResult<String> {
+        if !chain.has_provider() {
+            return Err(ApiError::ServiceUnavailable(format!("Chain {} has no RPC provider", chain.chain_id())));
+        }
+        let to_bytes = address_bytes(to)?;
+        let lock = SEND_LOCKS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(chain.chain_id())
+            .or_default()
+            .clone();
+        let _guard = lock.lock().await;
+
+        let call = serde_json::json!({
+            "from": self.address,
+            "to": to,
+            "data": format!("0x{}", hex::encode(&data)),
+            "value": format!("0x{:x}", value),
+        });
+        let estimated = chain.rpc_call("eth_estimateGas", serde_json::json!([call])).await?;
+        let estimated = parse_quantity(&estimated)
+            .ok_or_else(|| ApiError::BlockchainError("eth_estimateGas returned malformed data".to_string()))?;
+        let nonce = chain
+            .rpc_call("eth_getTransactionCount", serde_json::json!([self.address, "pending"]))
+            .await?;
+        let nonce = parse_quantity(&nonce)
+            .ok_or_else(|| ApiError::BlockchainError("eth_getTransactionCount returned malformed data".to_string()))?;
+
+        let fees = gas_services::gas_estimate(chain).await?;
+        let wei = |amount: &str| amount.parse::<u128>().unwrap_or(0);
+        let base_fee = wei(&fees.base_fee_per_gas);
+        let tip = fees
+            .tiers
+            .iter()
+            .find(|tier| tier.name == "standard")
+            .map_or(0, |tier| wei(&tier.max_priority_fee_per_gas))
+            .max(MIN_PRIORITY_FEE_WEI);
+
+        let transaction = Eip1559Transaction {
+            chain_id: chain.chain_id(),
+            nonce,
+            max_priority_fee_per_gas: tip,
+            max_fee_per_gas: 2 * base_fee + tip,
+            gas_limit: estimated + estimated * GAS_LIMIT_MARGIN_PERCENT / 100,
+            to: to_bytes,
+            value,
+            data,
+        };
+        let raw = transaction.sign(&self.key)?;
+        let tx_hash = format!("0x{}", hex::encode(Keccak256::digest(&raw)));
+        chain
+            .rpc_call("eth_sendRawTransaction", serde_json::json!([format!("0x{}", hex::encode(&raw))]))
+            .await?;
+        Ok(tx_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rlp(value: u128) -> Vec<u8> {
+        let mut out = Vec::new();
+        rlp_uint(&mut out, value);
+        out
+    }
+
+    #[test]
+    fn test_rlp() {
+        let mut dog = Vec::new();
+        rlp_bytes(&mut dog, b"dog");
+        assert_eq!(dog, [0x83, b'd', b'o', b'g']);
+        assert_eq!(rlp_list(&[]), [0xc0]);
+        assert_eq!(rlp(0), [0x80]);
+        assert_eq!(rlp(15), [0x0f]);
+        assert_eq!(rlp(1024), [0x82, 0x04, 0x00]);
+
+        let mut long = Vec::new();
+        rlp_bytes(&mut long, &[0xaa; 56]);
+        assert_eq!(&long[..2], [0xb8, 56]);
+        assert_eq!(long.len(), 58);
+    }
+
+    #[test]
+    fn test_abi_words() {
+        assert_eq!(hex::encode(selector("ownerOf(uint256)")), "6352211e");
+        assert_eq!(hex::encode(selector("transfer(address,uint256)")), "a9059cbb");
+        assert_eq!(uint_word(0x1234)[30..], [0x12, 0x34]);
+        let word = address_word("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").unwrap();
+        assert_eq!(word[..12], [0; 12]);
+        assert_eq!(word[12], 0x5a);
+        assert!(address_word("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD").is_err());
+    }
+
+    #[test]
+    fn test_signed_transaction_recovers_relayer() {
+        let relayer = Relayer::new(SigningKey::from_slice(&[0x46; 32]).unwrap());
+        assert_eq!(relayer.address(), "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f");
+
+        let transaction = Eip1559Transaction {
+            chain_id: 11155111,
+            nonce: 7,
+            max_priority_fee_per_gas: 1_500_000_000,
+            max_fee_per_gas: 30_000_000_000,
+            gas_limit: 120_000,
+            to: address_bytes("0x00000000000000000000000000000000000000aa").unwrap(),
+            value: 0,
+            data: vec![0xde, 0xad, 0xbe, 0xef],
+        };
+        let raw = transaction.sign(&relayer.key).unwrap();
+        assert_eq!(raw[0], 0x02);
+
+        // The signature fields close the list: y parity, then r and s of up to 32 bytes each
+        let fields_end = raw.len();
+        let s_len = (raw[fields_end - 33] - 0x80) as usize;
+        let s_start = fields_end - s_len;
+        let r_header = s_start - 1 - 33;
+        assert_eq!(s_len, 32, "fixture signature has no leading zeros");
+        let r = &raw[r_header + 1..r_header + 33];
+        let s = &raw[s_start..];
+        let parity = raw[r_header - 1];
+
+        let mut signature = r.to_vec();
+        signature.extend_from_slice(s);
+        signature.push(if parity == 0x80 { 0 } else { 1 });
+        let signer = BlockchainService::recover_address(&transaction.signing_hash(), &signature).unwrap();
+        assert_eq!(signer, relayer.address());
+    }
+}
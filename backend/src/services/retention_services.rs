@@ -0,0 +1,201 @@
+//! Telemetry retention: raw samples are rolled up into hourly aggregates and deleted once they
+//! are older than the raw window; rollups and metric values expire after the aggregate window.
+//!
+//! Raw rows are only deleted once the hour they fall in has been rolled up, so a stalled rollup
+//! delays pruning instead of losing data.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::config::AppConfig;
+use crate::errors::ApiResult;
+use crate::services::metric_services::hour_start;
+
+const JOB_INTERVAL_SECS: u64 = 600;
+/// Hours rolled up per pass, so a large backlog is worked off over several runs
+const ROLLUP_SPAN_HOURS: i64 = 7 * 24;
+/// Rows deleted per statement, keeping locks and WAL bursts short
+const DELETE_BATCH: i64 = 10_000;
+const LATE_ARRIVAL_MINUTES: i64 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetentionPolicy {
+    pub raw_days: u32,
+    pub aggregate_days: u32,
+}
+
+impl RetentionPolicy {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            raw_days: config.telemetry_raw_retention_days,
+            aggregate_days: config.telemetry_aggregate_retention_days,
+        }
+    }
+
+    pub fn raw_cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now - Duration::days(self.raw_days as i64)
+    }
+
+    pub fn aggregate_cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now - Duration::days(self.aggregate_days.max(self.raw_days) as i64)
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RetentionReport {
+    pub rollups_written: u64,
+    pub raw_deleted: u64,
+    pub rollups_deleted: u64,
+    pub metric_values_deleted: u64,
+    pub events_deleted: u64,
+}
+
+/// Hours to roll up next: from the hour of the oldest sample not yet covered up to the last
+/// completed hour, capped at `ROLLUP_SPAN_HOURS`
+pub fn rollup_window(
+    oldest_pending: Option<DateTime<Utc>>,
+    through: DateTime<Utc>,
+) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let from = hour_start(oldest_pending?);
+    let to = through.min(from + Duration::hours(ROLLUP_SPAN_HOURS));
+    (from < to).then_some((from, to))
+}
+
+/// Aggregate raw telemetry into `telemetry_hourly`. The latest rolled-up hour is recomputed
+/// on every pass so samples that arrived late for it are included.
+pub async fn roll_up(pool: &PgPool, through: DateTime<Utc>) -> ApiResult<u64> {
+    let oldest_pending: Option<DateTime<Utc>> = sqlx::query_scalar(
+        "SELECT MIN(recorded_at) FROM device_telemetry \
+         WHERE recorded_at >= COALESCE((SELECT MAX(bucket_start) FROM telemetry_hourly), '-infinity'::timestamptz)",
+    )
+    .fetch_one(pool)
+    .await?;
+    let Some((from, to)) = rollup_window(oldest_pending, through) else {
+        return Ok(0);
+    };
+
+    let rolled = sqlx::query(
+        "INSERT INTO telemetry_hourly (device_id, bucket_start, samples, first_recorded_at, last_recorded_at, \
+             battery_avg, battery_min, battery_max, latitude_avg, longitude_avg, altitude_avg, \
+             cpu_temp_avg, signal_strength_avg) \
+         SELECT device_id, date_trunc('hour', recorded_at), COUNT(*), MIN(recorded_at), MAX(recorded_at), \
+             AVG(battery_level), MIN(battery_level), MAX(battery_level), AVG(latitude), AVG(longitude), AVG(altitude), \
+             AVG(CASE WHEN jsonb_typeof(payload -> 'cpu_temp') = 'number' THEN (payload ->> 'cpu_temp')::float8 END), \
+             AVG(CASE WHEN jsonb_typeof(payload -> 'signal_strength') = 'number' \
+                 THEN (payload ->> 'signal_strength')::float8 END) \
+         FROM device_telemetry WHERE recorded_at >= $1 AND recorded_at < $2 \
+         GROUP BY device_id, date_trunc('hour', recorded_at) \
+         ON CONFLICT (device_id, bucket_start) DO UPDATE SET \
+             samples = EXCLUDED.samples, first_recorded_at = EXCLUDED.first_recorded_at, \
+             last_recorded_at = EXCLUDED.last_recorded_at, battery_avg = EXCLUDED.battery_avg, \
+             battery_min = EXCLUDED.battery_min, battery_max = EXCLUDED.battery_max, \
+             latitude_avg = EXCLUDED.latitude_avg, longitude_avg = EXCLUDED.longitude_avg, \
+             altitude_avg = EXCLUDED.altitude_avg, cpu_temp_avg = EXCLUDED.cpu_temp_avg, \
+             signal_strength_avg = EXCLUDED.signal_strength_avg",
+    )
+    .bind(from)
+    .bind(to)
+    .execute(pool)
+    .await?;
+    Ok(rolled.rows_affected())
+}
+
+/// Delete rows of `table` whose `column` is before the cutoff, one batch at a time
+async fn delete_before(
+    pool: &PgPool,
+    table: &str,
+    key: &str,
+    column: &str,
+    cutoff: DateTime<Utc>,
+) -> ApiResult<u64> {
+    let sql = format!(
+        "DELETE FROM {table} WHERE ({key}) IN (SELECT {key} FROM {table} WHERE {column} < $1 LIMIT $2)",
+    );
+    let mut deleted = 0;
+    loop {
+        let batch = sqlx::query(&sql).bind(cutoff).bind(DELETE_BATCH).execute(pool).await?.rows_affected();
+        deleted += batch;
+        if batch < DELETE_BATCH as u64 {
+            return Ok(deleted);
+        }
+    }
+}
+
+/// One retention pass: roll up completed hours, then prune everything past its window
+pub async fn enforce(pool: &PgPool, policy: RetentionPolicy) -> ApiResult<RetentionReport> {
+    let now = Utc::now();
+    let mut report = RetentionReport {
+        rollups_written: roll_up(pool, hour_start(now - Duration::minutes(LATE_ARRIVAL_MINUTES))).await?,
+        ..Default::default()
+    };
+
+    // Only hours before the latest rollup are final; raw rows after it are kept regardless of age
+    let rolled_through: Option<DateTime<Utc>> =
+        sqlx::query_scalar("SELECT MAX(bucket_start) FROM telemetry_hourly").fetch_one(pool).await?;
+    if let Some(rolled_through) = rolled_through {
+        let cutoff = policy.raw_cutoff(now).min(rolled_through);
+        report.raw_deleted = delete_before(pool, "device_telemetry", "id", "recorded_at", cutoff).await?;
+    }
+
+    let cutoff = policy.aggregate_cutoff(now);
+    report.rollups_deleted =
+        delete_before(pool, "telemetry_hourly", "device_id, bucket_start", "bucket_start", cutoff).await?;
+    report.metric_values_deleted =
+        delete_before(pool, "metric_values", "metric_id, device_id, bucket_start", "bucket_start", cutoff).await?;
+
+    // Automation events are only needed until dispatched
+    report.events_deleted =
+        delete_before(pool, "automation_events", "id", "processed_at", policy.raw_cutoff(now)).await?;
+
+    Ok(report)
+}
+
+/// Start the background job enforcing the retention policy
+pub fn spawn_retention_job(pool: Arc<PgPool>, policy: RetentionPolicy) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(JOB_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            match enforce(&pool, policy).await {
+                Ok(report) if report.raw_deleted > 0 || report.rollups_deleted > 0 => {
+                    tracing::info!("Telemetry retention: {:?}", report);
+                }
+                Ok(_) => {}
+                Err(e) => tracing::error!("Telemetry retention job failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: i64, minute: i64) -> DateTime<Utc> {
+        DateTime::<Utc>::from_timestamp(1_700_000_000 / 3600 * 3600, 0).unwrap()
+            + Duration::hours(hour)
+            + Duration::minutes(minute)
+    }
+
+    #[test]
+    fn test_rollup_window() {
+        assert_eq!(rollup_window(None, at(5, 0)), None);
+        assert_eq!(rollup_window(Some(at(2, 40)), at(5, 0)), Some((at(2, 0), at(5, 0))));
+        // Nothing completed yet beyond the pending sample's own hour
+        assert_eq!(rollup_window(Some(at(5, 10)), at(5, 0)), None);
+        // A long backlog is worked off in capped spans
+        assert_eq!(rollup_window(Some(at(0, 0)), at(1000, 0)), Some((at(0, 0), at(ROLLUP_SPAN_HOURS, 0))));
+    }
+
+    #[test]
+    fn test_aggregate_window_never_shorter_than_raw() {
+        let now = at(0, 0);
+        let policy = RetentionPolicy { raw_days: 30, aggregate_days: 365 };
+        assert_eq!(policy.raw_cutoff(now), now - Duration::days(30));
+        assert_eq!(policy.aggregate_cutoff(now), now - Duration::days(365));
+
+        let inverted = RetentionPolicy { raw_days: 90, aggregate_days: 7 };
+        assert_eq!(inverted.aggregate_cutoff(now), now - Duration::days(90));
+    }
+}
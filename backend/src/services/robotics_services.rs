@@ -1,7 +1,108 @@
 use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::collections::HashMap;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use crate::errors::{ApiError, ApiResult};
+use crate::models::device::{CommandAckRequest, RegisterDeviceRequest};
+use crate::services::firmware_services::compare_versions;
+
+/// Maximum number of rows accepted by a single bulk device import
+pub const MAX_IMPORT_ROWS: usize = 500;
+
+/// Longest execution time a device may report for one command (24 hours)
+pub const MAX_COMMAND_DURATION_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// Battery level bands the discharge curve is learned over (Li-ion cells drain faster near either end)
+pub const BATTERY_BANDS: [(f64, f64); 4] = [(80.0, 100.0), (40.0, 80.0), (15.0, 40.0), (0.0, 15.0)];
+
+/// Charge a device must keep in hand after a mission to return safely
+pub const BATTERY_RESERVE_LEVEL: f64 = 15.0;
+
+/// Telemetry gaps longer than this are treated as the device being off, not discharging
+const MAX_SAMPLE_GAP_SECS: i64 = 30 * 60;
+
+/// Acked runs of a command needed before its own drain history is trusted over the curve
+pub const MIN_COMMAND_HISTORY: usize = 3;
+
+/// Relative spread assumed for a discharge-curve rate, which carries no variance of its own
+const CURVE_RATE_RELATIVE_STD: f64 = 0.3;
+
+/// z-score of the two-sided 90% interval reported by simulations
+const INTERVAL_Z_90: f64 = 1.645;
+
+/// A command a device type understands and the firmware release that introduced it
+#[derive(Debug)]
+pub struct CommandCapability {
+    pub command: &'static str,
+    /// Oldest firmware accepting the command; `None` means every version
+    pub min_firmware: Option<&'static str>,
+    /// Attachment type that must be mounted and enabled for the command to be accepted
+    pub requires_attachment: Option<&'static str>,
+}
+
+/// Device type registry entry: the capability matrix for one type
+#[derive(Debug)]
+pub struct DeviceTypeSpec {
+    pub device_type: &'static str,
+    pub commands: &'static [CommandCapability],
+}
+
+const fn cap(command: &'static str, min_firmware: Option<&'static str>) -> CommandCapability {
+    CommandCapability { command, min_firmware, requires_attachment: None }
+}
+
+/// A command that drives an attachment rather than the device itself
+const fn tool(
+    command: &'static str,
+    min_firmware: Option<&'static str>,
+    attachment: &'static str,
+) -> CommandCapability {
+    CommandCapability { command, min_firmware, requires_attachment: Some(attachment) }
+}
+
+/// Supported device types and their commands
+pub const DEVICE_TYPES: &[DeviceTypeSpec] = &[
+    DeviceTypeSpec {
+        device_type: "drone",
+        commands: &[
+            cap("takeoff", None),
+            cap("land", None),
+            cap("hover", None),
+            cap("move", None),
+            cap("rotate", Some("1.2.0")),
+            cap("return_home", Some("1.1.0")),
+            cap("emergency_stop", None),
+        ],
+    },
+    DeviceTypeSpec {
+        device_type: "robot",
+        commands: &[
+            cap("move_forward", None),
+            cap("move_backward", None),
+            cap("turn_left", None),
+            cap("turn_right", None),
+            cap("stop", None),
+            tool("grab", Some("1.3.0"), "gripper"),
+            tool("release", Some("1.3.0"), "gripper"),
+        ],
+    },
+    DeviceTypeSpec {
+        device_type: "rover",
+        commands: &[
+            cap("drive", None),
+            cap("stop", None),
+            cap("turn", None),
+            cap("scan", Some("2.0.0")),
+            cap("deploy_sensor", Some("2.1.0")),
+            cap("retract_sensor", Some("2.1.0")),
+        ],
+    },
+];
+
+pub fn device_type_spec(device_type: &str) -> Option<&'static DeviceTypeSpec> {
+    DEVICE_TYPES.iter().find(|spec| spec.device_type == device_type)
+}
 
 /// Robotics service for managing devices and commands
 pub struct RoboticsService;
@@ -11,35 +112,72 @@ impl RoboticsService {
         Self
     }
 
-    /// Validate device command
-    pub fn validate_command(&self, device_type: &str, command: &str) -> ApiResult<bool> {
-        let valid_commands: &[&str] = match device_type {
-            "drone" => &["takeoff", "land", "hover", "move", "rotate", "return_home", "emergency_stop"],
-            "robot" => &["move_forward", "move_backward", "turn_left", "turn_right", "stop", "grab", "release"],
-            "rover" => &["drive", "stop", "turn", "scan", "deploy_sensor", "retract_sensor"],
-            _ => return Err(ApiError::ValidationError(format!("Unknown device type: {}", device_type))),
-        };
+    /// Validate a command against the device type registry and the firmware the device runs
+    pub fn validate_command(&self, device_type: &str, firmware_version: &str, command: &str) -> ApiResult<bool> {
+        let spec = device_type_spec(device_type)
+            .ok_or_else(|| ApiError::ValidationError(format!("Unknown device type: {}", device_type)))?;
 
-        if valid_commands.contains(&command) {
-            Ok(true)
-        } else {
-            Err(ApiError::ValidationError(format!(
+        let Some(capability) = spec.commands.iter().find(|c| c.command == command) else {
+            let valid_commands: Vec<&str> = spec.commands.iter().map(|c| c.command).collect();
+            return Err(ApiError::ValidationError(format!(
                 "Invalid command '{}' for device type '{}'. Valid commands: {:?}",
                 command, device_type, valid_commands
-            )))
+            )));
+        };
+
+        if let Some(min_firmware) = capability.min_firmware
+            && compare_versions(firmware_version, min_firmware).is_lt()
+        {
+            return Err(ApiError::ValidationError(format!(
+                "Command '{}' requires {} firmware {} or later (device runs {})",
+                command, device_type, min_firmware, firmware_version
+            )));
         }
+
+        Ok(true)
+    }
+
+    /// Validate a device registration payload
+    pub fn validate_registration(&self, request: &RegisterDeviceRequest) -> ApiResult<()> {
+        let name = request.device_name.trim();
+        if name.is_empty() || name.len() > 100 {
+            return Err(ApiError::ValidationError("Device name must be 1-100 characters".to_string()));
+        }
+
+        if device_type_spec(&request.device_type).is_none() {
+            return Err(ApiError::ValidationError(format!("Unknown device type: {}", request.device_type)));
+        }
+
+        if request.firmware_version.trim().is_empty() {
+            return Err(ApiError::ValidationError("Firmware version is required".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Parse a CSV device import (header: device_name,device_type,firmware_version).
+    /// Each row is parsed independently so one bad line doesn't reject the whole file.
+    pub fn parse_import_csv(&self, data: &str) -> Vec<ApiResult<RegisterDeviceRequest>> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(data.as_bytes());
+
+        reader
+            .deserialize::<RegisterDeviceRequest>()
+            .map(|row| row.map_err(|e| ApiError::ValidationError(format!("Malformed CSV row: {}", e))))
+            .collect()
     }
 
     /// Parse and validate command parameters
     pub fn parse_command_params(&self, command: &str, params: &serde_json::Value) -> ApiResult<CommandParams> {
         match command {
-            "move" | "drive" => {
+            "move" | "drive" | "move_forward" | "move_backward" => {
                 let speed = params.get("speed")
                     .and_then(|v| v.as_f64())
                     .unwrap_or(0.5);
                 let direction = params.get("direction")
                     .and_then(|v| v.as_str())
-                    .unwrap_or("forward");
+                    .unwrap_or(if command == "move_backward" { "backward" } else { "forward" });
                 let duration_ms = params.get("duration_ms")
                     .and_then(|v| v.as_u64())
                     .unwrap_or(1000);
@@ -132,6 +270,224 @@ impl RoboticsService {
             CommandParams::Simple => 0.01,
         }
     }
+
+    /// Estimate how long a command will take to execute
+    pub fn estimate_duration_ms(&self, params: &CommandParams) -> u64 {
+        match params {
+            CommandParams::Movement { duration_ms, .. } => *duration_ms,
+            CommandParams::Rotation { degrees, speed } => {
+                // Full speed turns at roughly 180°/s
+                let degrees_per_sec = 180.0 * speed.max(0.05);
+                ((degrees.abs() / degrees_per_sec) * 1000.0) as u64
+            }
+            CommandParams::Hover { altitude } => (altitude.max(0.0) * 1000.0) as u64 + 2000,
+            CommandParams::Simple => 500,
+        }
+    }
+
+    /// Validate the execution report a device sends back for a command
+    pub fn validate_ack(&self, ack: &CommandAckRequest) -> ApiResult<()> {
+        if !(0.0..=100.0).contains(&ack.actual_battery_drain) {
+            return Err(ApiError::ValidationError("actual_battery_drain must be between 0 and 100".to_string()));
+        }
+        if ack.actual_duration_ms > MAX_COMMAND_DURATION_MS {
+            return Err(ApiError::ValidationError("actual_duration_ms is implausibly large".to_string()));
+        }
+        if !ack.success && ack.error.as_deref().map(str::trim).unwrap_or_default().is_empty() {
+            return Err(ApiError::ValidationError("Failed commands must include an error".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Learn a per-band discharge rate from a device's telemetry history.
+    /// Intervals where the level rises (charging) or the gap is too long are ignored.
+    pub fn learn_discharge_curve(&self, samples: &[BatterySample]) -> BatteryModel {
+        let mut drop = [0.0f64; BATTERY_BANDS.len()];
+        let mut hours = [0.0f64; BATTERY_BANDS.len()];
+        let mut intervals = [0usize; BATTERY_BANDS.len()];
+        let mut segments = 0;
+        let mut in_segment = false;
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by_key(|s| s.recorded_at);
+
+        for pair in sorted.windows(2) {
+            let (prev, next) = (pair[0], pair[1]);
+            let gap = (next.recorded_at - prev.recorded_at).num_seconds();
+            if gap <= 0 || gap > MAX_SAMPLE_GAP_SECS || next.level > prev.level {
+                in_segment = false;
+                continue;
+            }
+            if !in_segment {
+                segments += 1;
+                in_segment = true;
+            }
+            let band = band_index((prev.level + next.level) / 2.0);
+            drop[band] += prev.level - next.level;
+            hours[band] += gap as f64 / 3600.0;
+            intervals[band] += 1;
+        }
+
+        let rate = |d: f64, h: f64| (h >= 0.05 && d > 0.0).then(|| d / h);
+        let bands = BATTERY_BANDS
+            .iter()
+            .enumerate()
+            .map(|(i, &(lower, upper))| BandRate {
+                lower,
+                upper,
+                pct_per_hour: rate(drop[i], hours[i]),
+                intervals: intervals[i],
+            })
+            .collect();
+
+        BatteryModel {
+            bands,
+            average_pct_per_hour: rate(drop.iter().sum(), hours.iter().sum()),
+            discharge_segments: segments,
+        }
+    }
+
+    /// Project whether the device can run its queued commands and still keep the reserve.
+    /// `drain_correction` scales command estimates by how far off acked commands have been.
+    pub fn forecast_mission(
+        &self,
+        model: &BatteryModel,
+        current_level: f64,
+        queued: &[(f32, u64)],
+        drain_correction: f64,
+    ) -> MissionForecast {
+        let estimated_duration_ms: u64 = queued.iter().map(|(_, ms)| ms).sum();
+        let command_drain: f64 = queued.iter().map(|(d, _)| *d as f64).sum::<f64>() * drain_correction;
+        // Never assume a mission drains less than the device's observed background rate over its length
+        let baseline_drain = model
+            .average_pct_per_hour
+            .map(|r| r * estimated_duration_ms as f64 / 3_600_000.0)
+            .unwrap_or(0.0);
+        let estimated_drain = command_drain.max(baseline_drain);
+        let projected_level = current_level - estimated_drain;
+
+        MissionForecast {
+            queued_commands: queued.len(),
+            estimated_duration_ms,
+            estimated_drain,
+            drain_correction,
+            projected_level,
+            feasible: projected_level >= BATTERY_RESERVE_LEVEL,
+        }
+    }
+
+    /// What-if run of a command sequence against the device's own history rather than the
+    /// static estimator. Each step drains at the rate observed for that command on this device
+    /// (mean and spread of acked runs), or at the learned discharge curve's rate for the
+    /// current level. Steps are treated as independent, so their variances add up.
+    pub fn simulate_battery(
+        &self,
+        model: &BatteryModel,
+        history: &[DrainObservation],
+        start_level: f64,
+        steps: &[(String, u64)],
+    ) -> BatterySimulation {
+        let mut by_command: HashMap<&str, Vec<f64>> = HashMap::new();
+        for obs in history.iter().filter(|o| o.duration_ms > 0) {
+            by_command
+                .entry(obs.command.as_str())
+                .or_default()
+                .push(obs.drain / (obs.duration_ms as f64 / 3_600_000.0));
+        }
+        let command_rates: HashMap<&str, (f64, f64, usize)> = by_command
+            .into_iter()
+            .filter(|(_, rates)| rates.len() >= MIN_COMMAND_HISTORY)
+            .map(|(command, rates)| {
+                let n = rates.len() as f64;
+                let mean = rates.iter().sum::<f64>() / n;
+                let variance = rates.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0);
+                (command, (mean, variance.sqrt(), rates.len()))
+            })
+            .collect();
+
+        let mut level = start_level;
+        let mut variance = 0.0;
+        let mut simulated = Vec::with_capacity(steps.len());
+        let mut unknown = 0;
+        for (command, duration_ms) in steps {
+            let hours = *duration_ms as f64 / 3_600_000.0;
+            let rate = match command_rates.get(command.as_str()) {
+                Some(&(mean, std_dev, runs)) => Some((mean, std_dev, "command_history", Some(runs))),
+                None => model.bands[band_index(level)]
+                    .pct_per_hour
+                    .or(model.average_pct_per_hour)
+                    .map(|r| (r, r * CURVE_RATE_RELATIVE_STD, "discharge_curve", None)),
+            };
+            let (drain, std_dev, source, history_runs) = match rate {
+                Some((rate, std_dev, source, runs)) => (Some(rate * hours), Some(std_dev * hours), source, runs),
+                None => {
+                    unknown += 1;
+                    (None, None, "no_history", None)
+                }
+            };
+            level -= drain.unwrap_or(0.0);
+            variance += std_dev.unwrap_or(0.0).powi(2);
+            simulated.push(SimulatedStep {
+                command: command.clone(),
+                duration_ms: *duration_ms,
+                expected_drain: drain,
+                std_dev,
+                source,
+                history_runs,
+                level_after: level,
+            });
+        }
+
+        let margin = INTERVAL_Z_90 * variance.sqrt();
+        let (low, high) = ((level - margin).max(0.0), (level + margin).min(100.0));
+        let (recommendation, reason) = if unknown > 0 {
+            ("insufficient_history", format!("{} step(s) have no drain history or discharge curve", unknown))
+        } else if low >= BATTERY_RESERVE_LEVEL {
+            ("go", format!("Even the low end of the interval stays above the {}% reserve", BATTERY_RESERVE_LEVEL))
+        } else if level < BATTERY_RESERVE_LEVEL {
+            ("no_go", format!("Expected to end below the {}% reserve", BATTERY_RESERVE_LEVEL))
+        } else {
+            ("marginal", format!("The interval dips below the {}% reserve", BATTERY_RESERVE_LEVEL))
+        };
+
+        BatterySimulation {
+            start_level,
+            expected_drain: start_level - level,
+            projected_level: level,
+            interval_low: low,
+            interval_high: high,
+            confidence: 0.9,
+            reserve_level: BATTERY_RESERVE_LEVEL,
+            recommendation,
+            reason,
+            steps: simulated,
+        }
+    }
+}
+
+fn band_index(level: f64) -> usize {
+    BATTERY_BANDS
+        .iter()
+        .position(|&(lower, _)| level >= lower)
+        .unwrap_or(BATTERY_BANDS.len() - 1)
+}
+
+impl BatteryModel {
+    /// Hours until the battery falls from `level` to `floor`, walking down the learned bands.
+    /// Bands without enough data fall back to the average rate.
+    pub fn remaining_runtime_hours(&self, level: f64, floor: f64) -> Option<f64> {
+        let mut total = 0.0;
+        for band in &self.bands {
+            let top = level.min(band.upper);
+            let bottom = floor.max(band.lower);
+            if top <= bottom {
+                continue;
+            }
+            let rate = band.pct_per_hour.or(self.average_pct_per_hour)?;
+            total += (top - bottom) / rate;
+        }
+        Some(total)
+    }
 }
 
 impl Default for RoboticsService {
@@ -207,6 +563,81 @@ pub struct DeviceStats {
     pub last_maintenance: Option<DateTime<Utc>>,
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct BatterySample {
+    pub recorded_at: DateTime<Utc>,
+    pub level: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BandRate {
+    pub lower: f64,
+    pub upper: f64,
+    pub pct_per_hour: Option<f64>,
+    pub intervals: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatteryModel {
+    pub bands: Vec<BandRate>,
+    pub average_pct_per_hour: Option<f64>,
+    pub discharge_segments: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MissionForecast {
+    pub queued_commands: usize,
+    pub estimated_duration_ms: u64,
+    pub estimated_drain: f64,
+    pub drain_correction: f64,
+    pub projected_level: f64,
+    pub feasible: bool,
+}
+
+/// An acked command's measured cost
+#[derive(Debug, Clone, FromRow)]
+pub struct DrainObservation {
+    pub command: String,
+    pub drain: f64,
+    pub duration_ms: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimulatedStep {
+    pub command: String,
+    pub duration_ms: u64,
+    pub expected_drain: Option<f64>,
+    pub std_dev: Option<f64>,
+    pub source: &'static str, // command_history, discharge_curve, no_history
+    pub history_runs: Option<usize>,
+    pub level_after: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatterySimulation {
+    pub start_level: f64,
+    pub expected_drain: f64,
+    pub projected_level: f64,
+    pub interval_low: f64,
+    pub interval_high: f64,
+    pub confidence: f64,
+    pub reserve_level: f64,
+    pub recommendation: &'static str, // go, marginal, no_go, insufficient_history
+    pub reason: String,
+    pub steps: Vec<SimulatedStep>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatteryForecast {
+    pub device_id: Uuid,
+    pub current_level: Option<f64>,
+    pub as_of: Option<DateTime<Utc>>,
+    pub remaining_runtime_minutes: Option<f64>,
+    pub reserve_level: f64,
+    pub model: BatteryModel,
+    pub queued_mission: Option<MissionForecast>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -215,17 +646,30 @@ mod tests {
     fn test_validate_command() {
         let service = RoboticsService::new();
         
-        assert!(service.validate_command("drone", "takeoff").is_ok());
-        assert!(service.validate_command("drone", "land").is_ok());
-        assert!(service.validate_command("drone", "invalid").is_err());
+        assert!(service.validate_command("drone", "1.0.0", "takeoff").is_ok());
+        assert!(service.validate_command("drone", "1.0.0", "land").is_ok());
+        assert!(service.validate_command("drone", "1.0.0", "invalid").is_err());
         
-        assert!(service.validate_command("robot", "move_forward").is_ok());
-        assert!(service.validate_command("robot", "grab").is_ok());
+        assert!(service.validate_command("robot", "1.0.0", "move_forward").is_ok());
+        assert!(service.validate_command("robot", "1.3.0", "grab").is_ok());
         
-        assert!(service.validate_command("rover", "drive").is_ok());
-        assert!(service.validate_command("rover", "scan").is_ok());
+        assert!(service.validate_command("rover", "1.0.0", "drive").is_ok());
+        assert!(service.validate_command("rover", "2.4.1", "scan").is_ok());
         
-        assert!(service.validate_command("unknown", "any").is_err());
+        assert!(service.validate_command("unknown", "1.0.0", "any").is_err());
+    }
+
+    #[test]
+    fn test_validate_command_firmware_gating() {
+        let service = RoboticsService::new();
+
+        let err = service.validate_command("rover", "1.9.9", "scan").unwrap_err();
+        assert!(matches!(err, ApiError::ValidationError(ref m) if m.contains("2.0.0")));
+        assert!(service.validate_command("drone", "1.1.5", "rotate").is_err());
+        assert!(service.validate_command("drone", "v1.10", "rotate").is_ok());
+        assert!(service.validate_command("robot", "1.2.9", "release").is_err());
+        // Commands without a minimum work on any firmware
+        assert!(service.validate_command("rover", "0.1", "stop").is_ok());
     }
 
     #[test]
@@ -248,6 +692,156 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_estimate_duration_ms() {
+        let service = RoboticsService::new();
+
+        let movement = CommandParams::Movement { speed: 0.5, direction: "forward".to_string(), duration_ms: 2000 };
+        assert_eq!(service.estimate_duration_ms(&movement), 2000);
+
+        let rotation = CommandParams::Rotation { degrees: 180.0, speed: 1.0 };
+        assert_eq!(service.estimate_duration_ms(&rotation), 1000);
+    }
+
+    #[test]
+    fn test_validate_ack() {
+        let service = RoboticsService::new();
+        let mut ack = CommandAckRequest {
+            success: true,
+            actual_duration_ms: 2100,
+            actual_battery_drain: 0.12,
+            error: None,
+        };
+        assert!(service.validate_ack(&ack).is_ok());
+
+        ack.success = false;
+        assert!(service.validate_ack(&ack).is_err());
+        ack.error = Some("Obstacle detected".to_string());
+        assert!(service.validate_ack(&ack).is_ok());
+
+        ack.actual_battery_drain = 150.0;
+        assert!(service.validate_ack(&ack).is_err());
+    }
+
+    fn discharge(start: DateTime<Utc>, levels: &[f64], step_mins: i64) -> Vec<BatterySample> {
+        levels
+            .iter()
+            .enumerate()
+            .map(|(i, &level)| BatterySample {
+                recorded_at: start + chrono::Duration::minutes(step_mins * i as i64),
+                level,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_learn_discharge_curve() {
+        let service = RoboticsService::new();
+        let start = Utc::now();
+
+        // 1% per 6 minutes (10%/h) above 40, then a charge, then 2% per 6 minutes below 40
+        let mut samples = discharge(start, &[60.0, 59.0, 58.0, 57.0, 56.0, 55.0], 6);
+        samples.extend(discharge(start + chrono::Duration::hours(2), &[38.0, 36.0, 34.0, 32.0], 6));
+
+        let model = service.learn_discharge_curve(&samples);
+        assert_eq!(model.discharge_segments, 2);
+        assert!((model.bands[1].pct_per_hour.unwrap() - 10.0).abs() < 1e-6);
+        assert!((model.bands[2].pct_per_hour.unwrap() - 20.0).abs() < 1e-6);
+        assert!(model.bands[0].pct_per_hour.is_none());
+
+        // 50 -> 40 at 10%/h, 40 -> 15 at 20%/h
+        let hours = model.remaining_runtime_hours(50.0, BATTERY_RESERVE_LEVEL).unwrap();
+        assert!((hours - 2.25).abs() < 1e-6);
+
+        let empty = service.learn_discharge_curve(&[]);
+        assert!(empty.remaining_runtime_hours(50.0, BATTERY_RESERVE_LEVEL).is_none());
+    }
+
+    #[test]
+    fn test_forecast_mission() {
+        let service = RoboticsService::new();
+        let model = service.learn_discharge_curve(&discharge(Utc::now(), &[90.0, 89.0, 88.0, 87.0], 6));
+
+        let queued = [(10.0, 60_000), (5.0, 60_000)];
+        let forecast = service.forecast_mission(&model, 40.0, &queued, 1.5);
+        assert!((forecast.estimated_drain - 22.5).abs() < 1e-6);
+        assert!(forecast.feasible);
+
+        let forecast = service.forecast_mission(&model, 30.0, &queued, 1.5);
+        assert!(!forecast.feasible);
+
+        // A long mission is bounded below by the background drain rate
+        let forecast = service.forecast_mission(&model, 90.0, &[(0.01, 3_600_000)], 1.0);
+        assert!((forecast.estimated_drain - 10.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_simulate_battery() {
+        let service = RoboticsService::new();
+        let model = service.learn_discharge_curve(&discharge(Utc::now(), &[90.0, 89.0, 88.0, 87.0], 6));
+        // drive: 8, 10 and 12 %/h over six-minute runs -> mean 10, std dev 2
+        let history: Vec<DrainObservation> = [0.8, 1.0, 1.2]
+            .iter()
+            .map(|&drain| DrainObservation { command: "drive".to_string(), drain, duration_ms: 360_000 })
+            .collect();
+        let steps = |list: &[(&str, u64)]| -> Vec<(String, u64)> {
+            list.iter().map(|(c, ms)| (c.to_string(), *ms)).collect()
+        };
+
+        let plan = steps(&[("drive", 1_800_000), ("scan", 3_600_000)]);
+        let sim = service.simulate_battery(&model, &history, 90.0, &plan);
+        assert_eq!(sim.steps[0].source, "command_history");
+        assert_eq!(sim.steps[0].history_runs, Some(3));
+        // scan has no history of its own and runs at the curve's 10 %/h
+        assert_eq!(sim.steps[1].source, "discharge_curve");
+        assert!((sim.projected_level - 75.0).abs() < 1e-6);
+        assert!(sim.interval_low < 75.0 && sim.interval_high > 75.0);
+        assert_eq!(sim.recommendation, "go");
+
+        let drive = |start: f64, ms: u64| service.simulate_battery(&model, &history, start, &steps(&[("drive", ms)]));
+        assert_eq!(drive(30.0, 5_040_000).recommendation, "marginal");
+        assert_eq!(drive(20.0, 3_600_000).recommendation, "no_go");
+
+        let empty = service.learn_discharge_curve(&[]);
+        let sim = service.simulate_battery(&empty, &[], 90.0, &steps(&[("scan", 60_000)]));
+        assert_eq!(sim.recommendation, "insufficient_history");
+        assert!(sim.steps[0].expected_drain.is_none());
+    }
+
+    #[test]
+    fn test_validate_registration() {
+        let service = RoboticsService::new();
+
+        let mut request = RegisterDeviceRequest {
+            device_name: "Scout-1".to_string(),
+            device_type: "rover".to_string(),
+            firmware_version: "1.2.0".to_string(),
+        };
+        assert!(service.validate_registration(&request).is_ok());
+
+        request.device_type = "submarine".to_string();
+        assert!(service.validate_registration(&request).is_err());
+
+        request.device_type = "drone".to_string();
+        request.device_name = "   ".to_string();
+        assert!(service.validate_registration(&request).is_err());
+    }
+
+    #[test]
+    fn test_parse_import_csv() {
+        let service = RoboticsService::new();
+        let csv = "device_name,device_type,firmware_version\n\
+                   Scout-1, rover, 1.0.0\n\
+                   Broken-row,drone\n\
+                   Hawk,drone,2.1.0\n";
+
+        let rows = service.parse_import_csv(csv);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].as_ref().unwrap().device_type, "rover");
+        assert!(rows[1].is_err());
+        assert_eq!(rows[2].as_ref().unwrap().device_name, "Hawk");
+    }
+
     #[test]
     fn test_generate_telemetry() {
         let service = RoboticsService::new();
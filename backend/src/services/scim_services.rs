@@ -0,0 +1,345 @@
+//! SCIM 2.0 (RFC 7643/7644) protocol helpers
+//!
+//! Parsing of filters and PATCH operations plus resource rendering. Persistence
+//! lives in the SCIM controller; users map onto org memberships and SCIM groups
+//! feed the org's SSO role mapping.
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+
+pub const USER_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+pub const GROUP_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+pub const LIST_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+pub const PATCH_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:PatchOp";
+pub const ERROR_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+
+/// Largest page an IdP may request in one call
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+/// A simple `attribute eq "value"` filter — the only form IdPs use for lookups
+#[derive(Debug, PartialEq)]
+pub struct ScimFilter {
+    pub attribute: String,
+    pub value: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimListQuery {
+    pub filter: Option<String>,
+    pub start_index: Option<i64>,
+    pub count: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScimEmail {
+    pub value: String,
+    #[serde(default)]
+    pub primary: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimUserRequest {
+    pub user_name: String,
+    #[serde(default)]
+    pub emails: Vec<ScimEmail>,
+    pub active: Option<bool>,
+    pub external_id: Option<String>,
+}
+
+impl ScimUserRequest {
+    /// The email to provision: primary email if given, otherwise the userName
+    pub fn email(&self) -> ApiResult<String> {
+        let email = self.emails.iter()
+            .find(|e| e.primary)
+            .or_else(|| self.emails.first())
+            .map(|e| e.value.clone())
+            .unwrap_or_else(|| self.user_name.clone())
+            .trim()
+            .to_lowercase();
+
+        if !email.contains('@') {
+            return Err(ApiError::ValidationError("userName or emails must contain an email address".to_string()));
+        }
+        Ok(email)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScimMemberRef {
+    pub value: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimGroupRequest {
+    pub display_name: String,
+    #[serde(default)]
+    pub members: Vec<ScimMemberRef>,
+    pub external_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScimPatchOp {
+    pub op: String,
+    pub path: Option<String>,
+    pub value: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScimPatchRequest {
+    #[serde(rename = "Operations")]
+    pub operations: Vec<ScimPatchOp>,
+}
+
+/// A change to a SCIM group derived from PATCH operations
+#[derive(Debug, PartialEq)]
+pub enum GroupChange {
+    Rename(String),
+    AddMembers(Vec<Uuid>),
+    RemoveMembers(Vec<Uuid>),
+    ReplaceMembers(Vec<Uuid>),
+}
+
+/// Parse `attribute eq "value"`; other operators are rejected
+pub fn parse_filter(filter: &str) -> ApiResult<ScimFilter> {
+    let invalid = || ApiError::BadRequest(format!("Unsupported SCIM filter: {}", filter));
+    let mut parts = filter.trim().splitn(3, ' ');
+
+    let attribute = parts.next().ok_or_else(invalid)?;
+    let operator = parts.next().ok_or_else(invalid)?;
+    let value = parts.next().ok_or_else(invalid)?.trim();
+
+    if !operator.eq_ignore_ascii_case("eq") || value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return Err(invalid());
+    }
+
+    Ok(ScimFilter {
+        attribute: attribute.to_string(),
+        value: value[1..value.len() - 1].replace("\\\"", "\""),
+    })
+}
+
+/// Convert SCIM's 1-based `startIndex`/`count` into a SQL offset and limit
+pub fn page_bounds(query: &ScimListQuery) -> (i64, i64) {
+    let start_index = query.start_index.unwrap_or(1).max(1);
+    let count = query.count.unwrap_or(100).clamp(0, MAX_PAGE_SIZE);
+    (start_index - 1, count)
+}
+
+/// IdPs send booleans either as JSON booleans or as "True"/"False" strings
+fn as_bool(value: &serde_json::Value) -> Option<bool> {
+    match value {
+        serde_json::Value::Bool(b) => Some(*b),
+        serde_json::Value::String(s) => s.to_ascii_lowercase().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Extract the `active` flag from user PATCH operations; other attributes are ignored
+pub fn patched_active(operations: &[ScimPatchOp]) -> ApiResult<Option<bool>> {
+    let mut active = None;
+
+    for op in operations {
+        if !matches!(op.op.to_ascii_lowercase().as_str(), "replace" | "add") {
+            continue;
+        }
+        let value = op.value.as_ref();
+        let candidate = match op.path.as_deref() {
+            Some("active") => value.and_then(as_bool),
+            None => value.and_then(|v| v.get("active")).and_then(as_bool),
+            _ => continue,
+        };
+        match candidate {
+            Some(flag) => active = Some(flag),
+            None if op.path.as_deref() == Some("active") => {
+                return Err(ApiError::BadRequest("active must be a boolean".to_string()));
+            }
+            None => {}
+        }
+    }
+
+    Ok(active)
+}
+
+fn member_ids(value: Option<&serde_json::Value>) -> ApiResult<Vec<Uuid>> {
+    let items = match value {
+        Some(serde_json::Value::Array(items)) => items.clone(),
+        Some(single @ serde_json::Value::Object(_)) => vec![single.clone()],
+        None => Vec::new(),
+        _ => return Err(ApiError::BadRequest("members must be an array".to_string())),
+    };
+
+    items
+        .iter()
+        .map(|item| {
+            item.get("value")
+                .and_then(|v| v.as_str())
+                .and_then(|v| Uuid::parse_str(v).ok())
+                .ok_or_else(|| ApiError::BadRequest("Invalid member reference".to_string()))
+        })
+        .collect()
+}
+
+/// Translate group PATCH operations into membership changes
+pub fn group_changes(operations: &[ScimPatchOp]) -> ApiResult<Vec<GroupChange>> {
+    let mut changes = Vec::new();
+
+    for op in operations {
+        let path = op.path.as_deref().unwrap_or_default();
+        match (op.op.to_ascii_lowercase().as_str(), path) {
+            ("add", "members") => changes.push(GroupChange::AddMembers(member_ids(op.value.as_ref())?)),
+            ("replace", "members") => changes.push(GroupChange::ReplaceMembers(member_ids(op.value.as_ref())?)),
+            ("remove", "members") => changes.push(GroupChange::RemoveMembers(member_ids(op.value.as_ref())?)),
+            ("remove", p) if p.starts_with("members[") => {
+                // members[value eq "<id>"]
+                let inner = p.trim_start_matches("members[").trim_end_matches(']');
+                let filter = parse_filter(inner)?;
+                let id = Uuid::parse_str(&filter.value)
+                    .map_err(|_| ApiError::BadRequest("Invalid member reference".to_string()))?;
+                changes.push(GroupChange::RemoveMembers(vec![id]));
+            }
+            ("replace", "displayName") => {
+                let name = op.value.as_ref().and_then(|v| v.as_str())
+                    .ok_or_else(|| ApiError::BadRequest("displayName must be a string".to_string()))?;
+                changes.push(GroupChange::Rename(name.to_string()));
+            }
+            ("replace", "") => {
+                // Azure AD sends the changed attributes as an object without a path
+                if let Some(name) = op.value.as_ref().and_then(|v| v.get("displayName")).and_then(|v| v.as_str()) {
+                    changes.push(GroupChange::Rename(name.to_string()));
+                }
+                if let Some(members) = op.value.as_ref().and_then(|v| v.get("members")) {
+                    changes.push(GroupChange::ReplaceMembers(member_ids(Some(members))?));
+                }
+            }
+            (other, _) => {
+                return Err(ApiError::BadRequest(format!("Unsupported group PATCH operation: {} {}", other, path)));
+            }
+        }
+    }
+
+    Ok(changes)
+}
+
+pub fn user_resource(base_url: &str, id: Uuid, email: &str, active: bool, created_at: DateTime<Utc>) -> serde_json::Value {
+    serde_json::json!({
+        "schemas": [USER_SCHEMA],
+        "id": id,
+        "userName": email,
+        "emails": [{ "value": email, "primary": true }],
+        "active": active,
+        "meta": {
+            "resourceType": "User",
+            "created": created_at.to_rfc3339(),
+            "location": format!("{}/scim/v2/Users/{}", base_url, id),
+        }
+    })
+}
+
+pub fn group_resource(
+    base_url: &str,
+    id: Uuid,
+    display_name: &str,
+    members: &[(Uuid, String)],
+    created_at: DateTime<Utc>,
+) -> serde_json::Value {
+    serde_json::json!({
+        "schemas": [GROUP_SCHEMA],
+        "id": id,
+        "displayName": display_name,
+        "members": members.iter().map(|(id, email)| serde_json::json!({
+            "value": id,
+            "display": email,
+            "$ref": format!("{}/scim/v2/Users/{}", base_url, id),
+        })).collect::<Vec<_>>(),
+        "meta": {
+            "resourceType": "Group",
+            "created": created_at.to_rfc3339(),
+            "location": format!("{}/scim/v2/Groups/{}", base_url, id),
+        }
+    })
+}
+
+pub fn list_response(resources: Vec<serde_json::Value>, total: i64, start_index: i64) -> serde_json::Value {
+    serde_json::json!({
+        "schemas": [LIST_SCHEMA],
+        "totalResults": total,
+        "startIndex": start_index,
+        "itemsPerPage": resources.len(),
+        "Resources": resources,
+    })
+}
+
+pub fn error_body(status: u16, detail: &str) -> serde_json::Value {
+    serde_json::json!({
+        "schemas": [ERROR_SCHEMA],
+        "status": status.to_string(),
+        "detail": detail,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ops(json: serde_json::Value) -> Vec<ScimPatchOp> {
+        serde_json::from_value::<ScimPatchRequest>(json).unwrap().operations
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        let filter = parse_filter(r#"userName eq "ada@example.com""#).unwrap();
+        assert_eq!(filter.attribute, "userName");
+        assert_eq!(filter.value, "ada@example.com");
+
+        assert!(parse_filter(r#"userName co "ada""#).is_err());
+        assert!(parse_filter("userName eq").is_err());
+    }
+
+    #[test]
+    fn test_patched_active() {
+        let okta = ops(serde_json::json!({
+            "Operations": [{ "op": "replace", "value": { "active": false } }]
+        }));
+        assert_eq!(patched_active(&okta).unwrap(), Some(false));
+
+        let azure = ops(serde_json::json!({
+            "Operations": [{ "op": "Replace", "path": "active", "value": "True" }]
+        }));
+        assert_eq!(patched_active(&azure).unwrap(), Some(true));
+
+        let unrelated = ops(serde_json::json!({
+            "Operations": [{ "op": "replace", "path": "name.givenName", "value": "Ada" }]
+        }));
+        assert_eq!(patched_active(&unrelated).unwrap(), None);
+    }
+
+    #[test]
+    fn test_group_changes() {
+        let id = Uuid::new_v4();
+        let patch = ops(serde_json::json!({
+            "Operations": [
+                { "op": "add", "path": "members", "value": [{ "value": id.to_string() }] },
+                { "op": "remove", "path": format!("members[value eq \"{}\"]", id) },
+                { "op": "replace", "path": "displayName", "value": "Field Ops" }
+            ]
+        }));
+
+        let changes = group_changes(&patch).unwrap();
+        assert_eq!(changes, vec![
+            GroupChange::AddMembers(vec![id]),
+            GroupChange::RemoveMembers(vec![id]),
+            GroupChange::Rename("Field Ops".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_page_bounds() {
+        let query = ScimListQuery { filter: None, start_index: Some(11), count: Some(5000) };
+        assert_eq!(page_bounds(&query), (10, MAX_PAGE_SIZE));
+    }
+}
@@ -0,0 +1,562 @@
+//! Login anomaly detection: persisted auth events, device fingerprints, risk-scored sessions
+//! and step-up verification
+
+use actix_web::HttpRequest;
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use serde::Serialize;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use crate::errors::ApiResult;
+use crate::services::mail_services::queue_email;
+use crate::services::notification_services::notify_user;
+use crate::utils::geo::{haversine_distance_m, is_valid_coordinate};
+use crate::utils::{create_step_up_email, log_auth_event, sha256_hash};
+
+/// Fastest plausible travel between two logins (roughly a commercial flight)
+pub const MAX_TRAVEL_SPEED_KMH: f64 = 900.0;
+/// Geo-IP is imprecise; jumps shorter than this never count as impossible travel
+const MIN_TRAVEL_DISTANCE_KM: f64 = 500.0;
+/// How many recent successful logins form the user's baseline
+const HISTORY_SIZE: i64 = 50;
+pub const STEP_UP_TTL_MINUTES: i64 = 10;
+pub const STEP_UP_MAX_ATTEMPTS: i16 = 5;
+
+/// Client fingerprint signals accepted in `X-Client-Signals` (`key=value;key=value`)
+const FINGERPRINT_SIGNALS: &[&str] = &["tz", "screen", "platform", "lang", "cores", "memory", "touch", "canvas", "webgl"];
+
+/// Hash a stable device fingerprint from browser headers plus the whitelisted client signals.
+/// Signals are sorted so the hash does not depend on the order the client sent them in.
+pub fn fingerprint_from_signals(user_agent: Option<&str>, accept_language: Option<&str>, signals: Option<&str>) -> Option<String> {
+    let mut parts: Vec<String> = signals
+        .unwrap_or_default()
+        .split(';')
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            let key = key.trim().to_ascii_lowercase();
+            let value = value.trim();
+            (FINGERPRINT_SIGNALS.contains(&key.as_str()) && !value.is_empty() && value.len() <= 200)
+                .then(|| format!("{}={}", key, value))
+        })
+        .collect();
+    parts.sort();
+    parts.dedup_by(|a, b| a.split('=').next() == b.split('=').next());
+
+    if user_agent.is_none() && parts.is_empty() {
+        return None;
+    }
+
+    let material = format!(
+        "ua={}|al={}|{}",
+        user_agent.unwrap_or_default(),
+        accept_language.unwrap_or_default(),
+        parts.join("|")
+    );
+    Some(sha256_hash(material.as_bytes()))
+}
+
+/// ISO country code from the edge proxy's geo-IP header (`CF-IPCountry`).
+/// Unknown (`XX`) and Tor (`T1`) are treated as no country.
+pub fn edge_country(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get("CF-IPCountry")
+        .and_then(|v| v.to_str().ok())
+        .map(|c| c.trim().to_uppercase())
+        .filter(|c| c.len() == 2 && c != "XX" && c != "T1")
+}
+
+/// Where and from what a login was attempted
+#[derive(Debug, Clone, Default)]
+pub struct LoginContext {
+    pub ip: Option<String>,
+    pub country: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub user_agent: Option<String>,
+    pub fingerprint: Option<String>,
+}
+
+impl LoginContext {
+    /// Build from request headers. Location comes from the edge proxy's geo-IP headers
+    /// (`CF-IPCountry`, `CF-IPLatitude`, `CF-IPLongitude`); the device fingerprint is the
+    /// client-supplied `X-Device-Fingerprint`, or else a hash of browser headers and `X-Client-Signals`.
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let header = |name: &str| {
+            req.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(String::from)
+        };
+
+        let country = edge_country(req);
+        let latitude = header("CF-IPLatitude").and_then(|v| v.parse::<f64>().ok());
+        let longitude = header("CF-IPLongitude").and_then(|v| v.parse::<f64>().ok());
+        let (latitude, longitude) = match (latitude, longitude) {
+            (Some(lat), Some(lng)) if is_valid_coordinate(lat, lng) => (Some(lat), Some(lng)),
+            _ => (None, None),
+        };
+
+        let user_agent = header("User-Agent");
+        let fingerprint = header("X-Device-Fingerprint")
+            .map(|f| sha256_hash(f.as_bytes()))
+            .or_else(|| {
+                fingerprint_from_signals(
+                    user_agent.as_deref(),
+                    header("Accept-Language").as_deref(),
+                    header("X-Client-Signals").as_deref(),
+                )
+            });
+
+        Self {
+            ip: req.connection_info().realip_remote_addr().map(String::from),
+            country,
+            latitude,
+            longitude,
+            user_agent,
+            fingerprint,
+        }
+    }
+}
+
+/// A previous successful login used as the baseline
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct KnownLogin {
+    pub ip: Option<String>,
+    pub country: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub fingerprint: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RiskReason {
+    NewCountry { country: String },
+    NewIp,
+    NewDevice,
+    ImpossibleTravel { distance_km: f64, speed_kmh: f64 },
+}
+
+impl RiskReason {
+    fn weight(&self) -> i16 {
+        match self {
+            RiskReason::NewCountry { .. } => 30,
+            RiskReason::NewIp => 10,
+            RiskReason::NewDevice => 25,
+            RiskReason::ImpossibleTravel { .. } => 60,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            RiskReason::NewCountry { country } => format!("Sign-in from a new country ({})", country),
+            RiskReason::NewIp => "Sign-in from a new IP address".to_string(),
+            RiskReason::NewDevice => "Sign-in from a new device or browser".to_string(),
+            RiskReason::ImpossibleTravel { distance_km, .. } => format!(
+                "Sign-in {:.0} km away from your previous one, too soon to have travelled",
+                distance_km
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RiskAssessment {
+    pub score: i16,
+    pub reasons: Vec<RiskReason>,
+}
+
+/// What to do with a session at a given risk score
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RiskAction {
+    Allow,
+    StepUp,
+    Block,
+}
+
+/// Session risk thresholds, configurable per organization
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::FromRow)]
+pub struct RiskPolicy {
+    pub step_up_threshold: i16,
+    /// `None` never blocks
+    pub block_threshold: Option<i16>,
+}
+
+impl Default for RiskPolicy {
+    fn default() -> Self {
+        Self { step_up_threshold: 50, block_threshold: None }
+    }
+}
+
+impl RiskPolicy {
+    pub fn action_for(&self, score: i16) -> RiskAction {
+        match self.block_threshold {
+            Some(block) if score >= block => RiskAction::Block,
+            _ if score >= self.step_up_threshold => RiskAction::StepUp,
+            _ => RiskAction::Allow,
+        }
+    }
+
+    /// The stricter of two policies (lower thresholds win)
+    pub fn strictest(self, other: RiskPolicy) -> RiskPolicy {
+        RiskPolicy {
+            step_up_threshold: self.step_up_threshold.min(other.step_up_threshold),
+            block_threshold: match (self.block_threshold, other.block_threshold) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+        }
+    }
+}
+
+/// Score a login against the user's recent successful logins (newest first).
+/// A user with no history has nothing to compare against and is not flagged.
+pub fn assess(history: &[KnownLogin], current: &LoginContext, now: DateTime<Utc>) -> RiskAssessment {
+    let mut reasons = Vec::new();
+
+    if history.is_empty() {
+        return RiskAssessment { score: 0, reasons };
+    }
+
+    if let Some(country) = &current.country
+        && history.iter().any(|h| h.country.is_some())
+        && !history.iter().any(|h| h.country.as_ref() == Some(country))
+    {
+        reasons.push(RiskReason::NewCountry { country: country.clone() });
+    }
+
+    if let Some(ip) = &current.ip
+        && !history.iter().any(|h| h.ip.as_ref() == Some(ip))
+    {
+        reasons.push(RiskReason::NewIp);
+    }
+
+    if let Some(fingerprint) = &current.fingerprint
+        && !history.iter().any(|h| h.fingerprint.as_ref() == Some(fingerprint))
+    {
+        reasons.push(RiskReason::NewDevice);
+    }
+
+    if let (Some(lat), Some(lng)) = (current.latitude, current.longitude)
+        && let Some(previous) = history.iter().find(|h| h.latitude.is_some() && h.longitude.is_some())
+    {
+        let distance_km =
+            haversine_distance_m(previous.latitude.unwrap_or_default(), previous.longitude.unwrap_or_default(), lat, lng)
+                / 1000.0;
+        // Floor the elapsed time so back-to-back logins do not divide by ~0
+        let hours = ((now - previous.created_at).num_seconds().max(60) as f64) / 3600.0;
+        let speed_kmh = distance_km / hours;
+        if distance_km >= MIN_TRAVEL_DISTANCE_KM && speed_kmh > MAX_TRAVEL_SPEED_KMH {
+            reasons.push(RiskReason::ImpossibleTravel {
+                distance_km: distance_km.round(),
+                speed_kmh: speed_kmh.round(),
+            });
+        }
+    }
+
+    let score = reasons.iter().map(RiskReason::weight).sum::<i16>().min(100);
+    RiskAssessment { score, reasons }
+}
+
+/// Outcome of evaluating a login
+#[derive(Debug)]
+pub enum LoginDecision {
+    Allow { session_id: Uuid },
+    StepUp { challenge_id: Uuid },
+    Blocked,
+}
+
+/// Risk policy for a login. SSO logins use their org's policy; other logins use the
+/// strictest policy across all of the user's active org memberships.
+pub async fn effective_risk_policy(pool: &PgPool, user_id: Uuid, org_id: Option<Uuid>) -> ApiResult<RiskPolicy> {
+    let policies = sqlx::query_as::<_, RiskPolicy>(
+        "SELECT o.risk_step_up_threshold AS step_up_threshold, o.risk_block_threshold AS block_threshold \
+         FROM organizations o JOIN org_memberships m ON m.org_id = o.id \
+         WHERE m.user_id = $1 AND m.active AND ($2::uuid IS NULL OR o.id = $2)",
+    )
+    .bind(user_id)
+    .bind(org_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut policies = policies.into_iter();
+    Ok(match policies.next() {
+        Some(first) => policies.fold(first, RiskPolicy::strictest),
+        None => RiskPolicy::default(),
+    })
+}
+
+/// Persist a non-login auth event (failures, verification attempts, ...)
+pub async fn record_auth_event(
+    pool: &PgPool,
+    user_id: Option<Uuid>,
+    event: &str,
+    success: bool,
+    context: &LoginContext,
+) -> ApiResult<()> {
+    sqlx::query(
+        "INSERT INTO auth_events (user_id, event, success, ip, country, latitude, longitude, user_agent, fingerprint) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+    )
+    .bind(user_id)
+    .bind(event)
+    .bind(success)
+    .bind(&context.ip)
+    .bind(&context.country)
+    .bind(context.latitude)
+    .bind(context.longitude)
+    .bind(&context.user_agent)
+    .bind(&context.fingerprint)
+    .execute(pool)
+    .await?;
+
+    log_auth_event(event, user_id.map(|id| id.to_string()).as_deref(), success, context.ip.as_deref());
+    Ok(())
+}
+
+/// Open a risk-scored session for a login event, carrying over the event's context and score
+pub async fn create_session(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    auth_event_id: i64,
+    auth_method: &str,
+    step_up_verified: bool,
+    ttl_seconds: i64,
+) -> ApiResult<Uuid> {
+    let session_id = sqlx::query_scalar(
+        "INSERT INTO user_sessions \
+         (user_id, auth_event_id, auth_method, ip, country, fingerprint, risk_score, risk_reasons, step_up_verified, expires_at) \
+         SELECT $1, id, $3, ip, country, fingerprint, risk_score, risk_reasons, $4, $5 \
+         FROM auth_events WHERE id = $2 RETURNING id",
+    )
+    .bind(user_id)
+    .bind(auth_event_id)
+    .bind(auth_method)
+    .bind(step_up_verified)
+    .bind(Utc::now() + Duration::seconds(ttl_seconds))
+    .fetch_one(conn)
+    .await?;
+
+    Ok(session_id)
+}
+
+/// Evaluate a credential-verified login: persist it, alert the user about anything unusual,
+/// and apply the org risk policy: open a session, send a step-up challenge (code by email),
+/// or block the login outright. `auth_method` is the session type to issue (password, sso, wallet);
+/// `org_id` is set for org-scoped (SSO) logins.
+pub async fn evaluate_login(
+    pool: &PgPool,
+    user_id: Uuid,
+    auth_method: &str,
+    org_id: Option<Uuid>,
+    context: &LoginContext,
+    session_ttl_seconds: i64,
+) -> ApiResult<LoginDecision> {
+    let history = sqlx::query_as::<_, KnownLogin>(
+        "SELECT ip, country, latitude, longitude, fingerprint, created_at FROM auth_events \
+         WHERE user_id = $1 AND event = 'login' AND success \
+         ORDER BY created_at DESC LIMIT $2",
+    )
+    .bind(user_id)
+    .bind(HISTORY_SIZE)
+    .fetch_all(pool)
+    .await?;
+
+    let assessment = assess(&history, context, Utc::now());
+    let policy = effective_risk_policy(pool, user_id, org_id).await?;
+    let action = policy.action_for(assessment.score);
+
+    let mut tx = pool.begin().await?;
+    // Challenged and blocked logins are stored unsuccessful, so they never join the baseline early
+    let event_id: i64 = sqlx::query_scalar(
+        "INSERT INTO auth_events \
+         (user_id, event, success, ip, country, latitude, longitude, user_agent, fingerprint, risk_score, risk_reasons) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING id",
+    )
+    .bind(user_id)
+    .bind(if action == RiskAction::Block { "login_blocked" } else { "login" })
+    .bind(action == RiskAction::Allow)
+    .bind(&context.ip)
+    .bind(&context.country)
+    .bind(context.latitude)
+    .bind(context.longitude)
+    .bind(&context.user_agent)
+    .bind(&context.fingerprint)
+    .bind(assessment.score)
+    .bind(serde_json::to_value(&assessment.reasons).unwrap_or_default())
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let descriptions: Vec<String> = assessment.reasons.iter().map(RiskReason::describe).collect();
+    if !assessment.reasons.is_empty() {
+        notify_user(
+            &mut tx,
+            user_id,
+            "security_alert",
+            if action == RiskAction::Block { "Sign-in blocked" } else { "Unusual sign-in detected" },
+            &descriptions.join("; "),
+            serde_json::json!({
+                "auth_event_id": event_id,
+                "ip": context.ip,
+                "country": context.country,
+                "risk_score": assessment.score,
+                "action": format!("{:?}", action).to_lowercase(),
+            }),
+        )
+        .await?;
+    }
+
+    let decision = match action {
+        RiskAction::Allow => LoginDecision::Allow {
+            session_id: create_session(&mut tx, user_id, event_id, auth_method, false, session_ttl_seconds).await?,
+        },
+        RiskAction::StepUp => {
+            let code = format!("{:06}", rand::thread_rng().gen_range(0..1_000_000));
+            let challenge_id: Uuid = sqlx::query_scalar(
+                "INSERT INTO step_up_challenges (user_id, auth_event_id, auth_method, org_id, code_hash, expires_at) \
+                 VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+            )
+            .bind(user_id)
+            .bind(event_id)
+            .bind(auth_method)
+            .bind(org_id)
+            .bind(sha256_hash(code.as_bytes()))
+            .bind(Utc::now() + Duration::minutes(STEP_UP_TTL_MINUTES))
+            .fetch_one(&mut *tx)
+            .await?;
+
+            let (email, username): (String, String) =
+                sqlx::query_as("SELECT email, username FROM users WHERE id = $1")
+                    .bind(user_id)
+                    .fetch_one(&mut *tx)
+                    .await?;
+            let (subject, body) = create_step_up_email(&username, &code, &descriptions);
+            queue_email(&mut tx, &email, &subject, &body, None).await?;
+
+            LoginDecision::StepUp { challenge_id }
+        }
+        RiskAction::Block => LoginDecision::Blocked,
+    };
+
+    tx.commit().await?;
+
+    log_auth_event(
+        match action {
+            RiskAction::Allow => "login",
+            RiskAction::StepUp => "login_step_up_required",
+            RiskAction::Block => "login_blocked",
+        },
+        Some(&user_id.to_string()),
+        action != RiskAction::Block,
+        Some(&format!("risk_score={}", assessment.score)),
+    );
+
+    Ok(decision)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn known(country: &str, ip: &str, fingerprint: &str, lat: f64, lng: f64, at: DateTime<Utc>) -> KnownLogin {
+        KnownLogin {
+            ip: Some(ip.to_string()),
+            country: Some(country.to_string()),
+            latitude: Some(lat),
+            longitude: Some(lng),
+            fingerprint: Some(fingerprint.to_string()),
+            created_at: at,
+        }
+    }
+
+    fn context(country: &str, ip: &str, fingerprint: &str, lat: f64, lng: f64) -> LoginContext {
+        LoginContext {
+            ip: Some(ip.to_string()),
+            country: Some(country.to_string()),
+            latitude: Some(lat),
+            longitude: Some(lng),
+            user_agent: None,
+            fingerprint: Some(fingerprint.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_first_login_is_not_flagged() {
+        let assessment = assess(&[], &context("IN", "1.2.3.4", "fp", 12.97, 77.59), Utc::now());
+        assert_eq!(assessment.score, 0);
+        assert_eq!(RiskPolicy::default().action_for(assessment.score), RiskAction::Allow);
+    }
+
+    #[test]
+    fn test_familiar_login_is_clean() {
+        let now = Utc::now();
+        let history = [known("IN", "1.2.3.4", "fp", 12.97, 77.59, now - Duration::days(1))];
+        let assessment = assess(&history, &context("IN", "1.2.3.4", "fp", 12.97, 77.59), now);
+        assert!(assessment.reasons.is_empty());
+    }
+
+    #[test]
+    fn test_impossible_travel_requires_step_up() {
+        let now = Utc::now();
+        // Bengaluru one hour ago, New York now
+        let history = [known("IN", "1.2.3.4", "fp", 12.97, 77.59, now - Duration::hours(1))];
+        let assessment = assess(&history, &context("US", "5.6.7.8", "fp", 40.71, -74.0), now);
+
+        assert!(assessment.reasons.contains(&RiskReason::NewCountry { country: "US".to_string() }));
+        assert!(assessment.reasons.iter().any(|r| matches!(r, RiskReason::ImpossibleTravel { .. })));
+        assert_eq!(RiskPolicy::default().action_for(assessment.score), RiskAction::StepUp);
+    }
+
+    #[test]
+    fn test_plausible_travel_is_not_impossible() {
+        let now = Utc::now();
+        // Same trip, but a day later
+        let history = [known("IN", "1.2.3.4", "fp", 12.97, 77.59, now - Duration::days(1))];
+        let assessment = assess(&history, &context("US", "5.6.7.8", "fp", 40.71, -74.0), now);
+
+        assert!(!assessment.reasons.iter().any(|r| matches!(r, RiskReason::ImpossibleTravel { .. })));
+        assert_eq!(RiskPolicy::default().action_for(assessment.score), RiskAction::Allow);
+    }
+
+    #[test]
+    fn test_new_device_and_country_require_step_up() {
+        let now = Utc::now();
+        let history = [known("IN", "1.2.3.4", "fp", 12.97, 77.59, now - Duration::days(3))];
+        let assessment = assess(&history, &context("DE", "9.9.9.9", "other", 52.52, 13.40), now);
+
+        assert!(assessment.reasons.contains(&RiskReason::NewDevice));
+        assert!(assessment.reasons.contains(&RiskReason::NewIp));
+        assert_eq!(assessment.score, 65);
+        assert_eq!(RiskPolicy::default().action_for(assessment.score), RiskAction::StepUp);
+    }
+
+    #[test]
+    fn test_risk_policy_actions() {
+        let policy = RiskPolicy { step_up_threshold: 30, block_threshold: Some(80) };
+        assert_eq!(policy.action_for(10), RiskAction::Allow);
+        assert_eq!(policy.action_for(30), RiskAction::StepUp);
+        assert_eq!(policy.action_for(85), RiskAction::Block);
+
+        let lenient = RiskPolicy::default();
+        assert_eq!(lenient.action_for(100), RiskAction::StepUp);
+
+        let strictest = lenient.strictest(policy);
+        assert_eq!(strictest, RiskPolicy { step_up_threshold: 30, block_threshold: Some(80) });
+    }
+
+    #[test]
+    fn test_fingerprint_from_signals() {
+        let ua = Some("Mozilla/5.0");
+        let a = fingerprint_from_signals(ua, Some("en-IN"), Some("tz=Asia/Kolkata;screen=1920x1080"));
+        let b = fingerprint_from_signals(ua, Some("en-IN"), Some("screen=1920x1080; tz=Asia/Kolkata;bogus=1"));
+        let c = fingerprint_from_signals(ua, Some("en-IN"), Some("tz=Europe/Berlin;screen=1920x1080"));
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a.unwrap().len(), 64);
+        assert!(fingerprint_from_signals(None, None, None).is_none());
+    }
+}
@@ -0,0 +1,120 @@
+//! Per-device sensor definitions and the telemetry checks they drive
+
+use std::collections::HashSet;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::sensor::DeviceSensor;
+use crate::services::robotics_services::SensorReading;
+
+/// Sensors one device may declare
+pub const MAX_SENSORS_PER_DEVICE: usize = 32;
+
+pub const SENSOR_COLUMNS: &str =
+    "id, device_id, sensor_type, unit, sampling_rate_hz, enabled, created_at, updated_at";
+
+/// Lowercase identifier such as `temperature` or `soil_moisture`
+pub fn validate_sensor_type(sensor_type: &str) -> ApiResult<()> {
+    let valid = !sensor_type.is_empty()
+        && sensor_type.len() <= 32
+        && sensor_type.starts_with(|c: char| c.is_ascii_lowercase())
+        && sensor_type.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid {
+        return Err(ApiError::ValidationError(
+            "sensor_type must be 1-32 lowercase letters, digits or '_', starting with a letter".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_unit(unit: &str) -> ApiResult<()> {
+    if unit.trim().is_empty() || unit.chars().count() > 16 {
+        return Err(ApiError::ValidationError("unit must be 1-16 characters".to_string()));
+    }
+    Ok(())
+}
+
+pub fn validate_sampling_rate(hz: f32) -> ApiResult<()> {
+    if !(0.001..=1000.0).contains(&hz) {
+        return Err(ApiError::ValidationError("sampling_rate_hz must be between 0.001 and 1000".to_string()));
+    }
+    Ok(())
+}
+
+/// Every reading must come from a declared, enabled sensor, in its declared unit,
+/// and each sensor may appear at most once per sample
+pub fn validate_readings(readings: &[SensorReading], sensors: &[DeviceSensor]) -> ApiResult<()> {
+    let mut seen = HashSet::new();
+    for reading in readings {
+        let sensor = sensors
+            .iter()
+            .find(|s| s.sensor_type == reading.sensor_type)
+            .ok_or_else(|| ApiError::ValidationError(format!("Undeclared sensor: {}", reading.sensor_type)))?;
+        if !sensor.enabled {
+            return Err(ApiError::ValidationError(format!("Sensor {} is disabled", sensor.sensor_type)));
+        }
+        if reading.unit != sensor.unit {
+            return Err(ApiError::ValidationError(format!(
+                "Sensor {} reports in {}, got {}",
+                sensor.sensor_type, sensor.unit, reading.unit
+            )));
+        }
+        if !reading.value.is_finite() {
+            return Err(ApiError::ValidationError(format!("Sensor {} value is not a number", sensor.sensor_type)));
+        }
+        if !seen.insert(reading.sensor_type.as_str()) {
+            return Err(ApiError::ValidationError(format!("Duplicate reading for sensor {}", sensor.sensor_type)));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn sensor(sensor_type: &str, unit: &str, enabled: bool) -> DeviceSensor {
+        DeviceSensor {
+            id: Uuid::new_v4(),
+            device_id: Uuid::nil(),
+            sensor_type: sensor_type.to_string(),
+            unit: unit.to_string(),
+            sampling_rate_hz: 1.0,
+            enabled,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn reading(sensor_type: &str, value: f64, unit: &str) -> SensorReading {
+        SensorReading { sensor_type: sensor_type.to_string(), value, unit: unit.to_string() }
+    }
+
+    #[test]
+    fn test_validate_readings() {
+        let sensors = vec![sensor("temperature", "°C", true), sensor("humidity", "%", false)];
+
+        assert!(validate_readings(&[reading("temperature", 21.5, "°C")], &sensors).is_ok());
+        assert!(validate_readings(&[], &sensors).is_ok());
+        assert!(validate_readings(&[reading("pressure", 1013.0, "hPa")], &sensors).is_err());
+        assert!(validate_readings(&[reading("humidity", 40.0, "%")], &sensors).is_err());
+        assert!(validate_readings(&[reading("temperature", 70.0, "°F")], &sensors).is_err());
+        assert!(validate_readings(&[reading("temperature", f64::NAN, "°C")], &sensors).is_err());
+        assert!(
+            validate_readings(&[reading("temperature", 21.0, "°C"), reading("temperature", 22.0, "°C")], &sensors)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_definition_validation() {
+        assert!(validate_sensor_type("soil_moisture").is_ok());
+        assert!(validate_sensor_type("CO2").is_err());
+        assert!(validate_sensor_type("2nd_temp").is_err());
+        assert!(validate_unit("ppm").is_ok());
+        assert!(validate_unit(" ").is_err());
+        assert!(validate_sampling_rate(0.5).is_ok());
+        assert!(validate_sampling_rate(0.0).is_err());
+        assert!(validate_sampling_rate(5000.0).is_err());
+    }
+}
@@ -0,0 +1,367 @@
+//! Sign-In with Ethereum (EIP-4361).
+//!
+//! The server issues a single-use nonce; the wallet signs a SIWE message carrying it with
+//! `personal_sign`. A sign-in is accepted when the message names this site's domain, is within
+//! its validity window, was signed by the address it names, and its nonce is still unused.
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use sqlx::{PgConnection, PgPool};
+use std::fmt;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::services::crypto_services::BlockchainService;
+use crate::services::wallet_services;
+use crate::utils::{generate_random_hex, generate_random_string};
+
+const PREAMBLE: &str = " wants you to sign in with your Ethereum account:";
+pub const NONCE_TTL_MINUTES: i64 = 10;
+/// Tolerated clock skew for a wallet's `Issued At`
+const MAX_CLOCK_SKEW_SECS: i64 = 300;
+pub const MAX_MESSAGE_BYTES: usize = 4096;
+pub const DEFAULT_STATEMENT: &str = "Sign in to RoboVeda.";
+
+/// A parsed EIP-4361 message
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiweMessage {
+    pub scheme: Option<String>,
+    /// `host[:port]` of the site asking for the signature
+    pub domain: String,
+    /// EIP-55 checksummed
+    pub address: String,
+    pub statement: Option<String>,
+    pub uri: String,
+    pub version: String,
+    pub chain_id: u64,
+    pub nonce: String,
+    pub issued_at: DateTime<Utc>,
+    pub expiration_time: Option<DateTime<Utc>>,
+    pub not_before: Option<DateTime<Utc>>,
+    pub request_id: Option<String>,
+    pub resources: Vec<String>,
+}
+
+fn invalid(reason: &str) -> ApiError {
+    ApiError::ValidationError(format!("Invalid sign-in message: {}", reason))
+}
+
+fn timestamp(value: &str, field: &str) -> ApiResult<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|_| invalid(&format!("{} is not an RFC 3339 timestamp", field)))
+}
+
+impl SiweMessage {
+    pub fn parse(text: &str) -> ApiResult<Self> {
+        let mut lines = text.split('\n').peekable();
+
+        let header = lines.next().unwrap_or_default();
+        let origin = header.strip_suffix(PREAMBLE).ok_or_else(|| invalid("missing preamble"))?;
+        let (scheme, domain) = match origin.split_once("://") {
+            Some((scheme, domain)) => (Some(scheme.to_string()), domain),
+            None => (None, origin),
+        };
+        if domain.is_empty() || domain.contains(char::is_whitespace) {
+            return Err(invalid("bad domain"));
+        }
+
+        let address = lines.next().unwrap_or_default();
+        if !BlockchainService::is_valid_eth_address(address) {
+            return Err(invalid("bad address"));
+        }
+        if BlockchainService::to_checksum_address(address) != address {
+            return Err(invalid("address is not EIP-55 checksummed"));
+        }
+        if lines.next() != Some("") {
+            return Err(invalid("expected a blank line after the address"));
+        }
+
+        // An optional statement line, then a blank line
+        let statement = match lines.next() {
+            Some("") => None,
+            Some(statement) if lines.next() == Some("") => Some(statement.to_string()),
+            _ => return Err(invalid("expected a blank line after the statement")),
+        };
+
+        let mut required = |tag: &str| -> ApiResult<String> {
+            lines
+                .next()
+                .and_then(|line| line.strip_prefix(tag))
+                .map(str::to_string)
+                .ok_or_else(|| invalid(&format!("missing `{}`", tag.trim_end())))
+        };
+        let uri = required("URI: ")?;
+        let version = required("Version: ")?;
+        let chain_id = required("Chain ID: ")?.parse().map_err(|_| invalid("bad chain id"))?;
+        let nonce = required("Nonce: ")?;
+        let issued_at = timestamp(&required("Issued At: ")?, "Issued At")?;
+
+        let mut optional = |tag: &str| {
+            lines.next_if(|line| line.starts_with(tag)).map(|line| line[tag.len()..].to_string())
+        };
+        let expiration_time = optional("Expiration Time: ").map(|t| timestamp(&t, "Expiration Time")).transpose()?;
+        let not_before = optional("Not Before: ").map(|t| timestamp(&t, "Not Before")).transpose()?;
+        let request_id = optional("Request ID: ");
+        let mut resources = Vec::new();
+        if lines.next_if_eq(&"Resources:").is_some() {
+            while let Some(line) = lines.next_if(|line| line.starts_with("- ")) {
+                resources.push(line[2..].to_string());
+            }
+        }
+        if lines.any(|line| !line.is_empty()) {
+            return Err(invalid("unexpected trailing content"));
+        }
+
+        if version != "1" {
+            return Err(invalid("unsupported version"));
+        }
+        if nonce.len() < 8 || !nonce.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(invalid("nonce must be at least 8 alphanumeric characters"));
+        }
+
+        Ok(Self {
+            scheme,
+            domain: domain.to_string(),
+            address: address.to_string(),
+            statement,
+            uri,
+            version,
+            chain_id,
+            nonce,
+            issued_at,
+            expiration_time,
+            not_before,
+            request_id,
+            resources,
+        })
+    }
+
+    /// Check the message was meant for `domain` and is valid at `now`
+    pub fn check(&self, domain: &str, now: DateTime<Utc>) -> ApiResult<()> {
+        if !self.domain.eq_ignore_ascii_case(domain) {
+            return Err(ApiError::Unauthorized("Sign-in message is for a different site".to_string()));
+        }
+        if self.issued_at > now + Duration::seconds(MAX_CLOCK_SKEW_SECS) {
+            return Err(ApiError::Unauthorized("Sign-in message is issued in the future".to_string()));
+        }
+        if self.expiration_time.is_some_and(|t| t <= now) {
+            return Err(ApiError::Unauthorized("Sign-in message has expired".to_string()));
+        }
+        if self.not_before.is_some_and(|t| t > now) {
+            return Err(ApiError::Unauthorized("Sign-in message is not valid yet".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SiweMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rfc3339 = |t: &DateTime<Utc>| t.to_rfc3339_opts(SecondsFormat::Secs, true);
+        if let Some(scheme) = &self.scheme {
+            write!(f, "{}://", scheme)?;
+        }
+        writeln!(f, "{}{}", self.domain, PREAMBLE)?;
+        writeln!(f, "{}", self.address)?;
+        writeln!(f)?;
+        if let Some(statement) = &self.statement {
+            writeln!(f, "{}", statement)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "URI: {}", self.uri)?;
+        writeln!(f, "Version: {}", self.version)?;
+        writeln!(f, "Chain ID: {}", self.chain_id)?;
+        writeln!(f, "Nonce: {}", self.nonce)?;
+        write!(f, "Issued At: {}", rfc3339(&self.issued_at))?;
+        if let Some(expiration_time) = &self.expiration_time {
+            write!(f, "\nExpiration Time: {}", rfc3339(expiration_time))?;
+        }
+        if let Some(not_before) = &self.not_before {
+            write!(f, "\nNot Before: {}", rfc3339(not_before))?;
+        }
+        if let Some(request_id) = &self.request_id {
+            write!(f, "\nRequest ID: {}", request_id)?;
+        }
+        if !self.resources.is_empty() {
+            write!(f, "\nResources:")?;
+            for resource in &self.resources {
+                write!(f, "\n- {}", resource)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The `host[:port]` wallets must see in messages: the frontend's, where users sign in
+pub fn expected_domain(config: &AppConfig) -> String {
+    reqwest::Url::parse(&config.frontend_url)
+        .ok()
+        .and_then(|url| {
+            let host = url.host_str()?.to_string();
+            Some(match url.port() {
+                Some(port) => format!("{}:{}", host, port),
+                None => host,
+            })
+        })
+        .unwrap_or_else(|| config.frontend_url.clone())
+}
+
+/// Address (lower-case) that signed `message` with `personal_sign`
+pub fn recover_signer(message: &str, signature: &str) -> ApiResult<String> {
+    let bytes = signature
+        .strip_prefix("0x")
+        .and_then(|hex_sig| hex::decode(hex_sig).ok())
+        .ok_or_else(|| ApiError::ValidationError("Invalid signature format".to_string()))?;
+    BlockchainService::recover_address(&BlockchainService::eip191_hash(message), &bytes)
+}
+
+/// Store a fresh nonce, clearing out expired ones
+pub async fn issue_nonce(pool: &PgPool) -> ApiResult<(String, DateTime<Utc>)> {
+    sqlx::query("DELETE FROM wallet_login_nonces WHERE expires_at <= NOW()")
+        .execute(pool)
+        .await?;
+
+    let nonce = generate_random_hex(16);
+    let expires_at = Utc::now() + Duration::minutes(NONCE_TTL_MINUTES);
+    sqlx::query("INSERT INTO wallet_login_nonces (nonce, expires_at) VALUES ($1, $2)")
+        .bind(&nonce)
+        .bind(expires_at)
+        .execute(pool)
+        .await?;
+
+    Ok((nonce, expires_at))
+}
+
+/// Use up a nonce; false when it is unknown, expired or already used
+pub async fn consume_nonce(conn: &mut PgConnection, nonce: &str) -> ApiResult<bool> {
+    let consumed = sqlx::query("DELETE FROM wallet_login_nonces WHERE nonce = $1 AND expires_at > NOW()")
+        .bind(nonce)
+        .execute(conn)
+        .await?;
+    Ok(consumed.rows_affected() == 1)
+}
+
+/// The account bound to a wallet, created on its first sign-in, recording the chain it signed
+/// in on. Returns whether it was created. A wallet unlinked within the cooldown gets no account.
+/// New accounts get an unusable password and a placeholder, unverified email address.
+pub async fn find_or_create_wallet_user(
+    conn: &mut PgConnection,
+    address: &str,
+    chain_id: u64,
+    relink_cooldown_hours: u32,
+) -> ApiResult<(Uuid, bool)> {
+    let address = address.to_ascii_lowercase();
+    let existing: Option<Uuid> =
+        sqlx::query_scalar("UPDATE users SET wallet_chain_id = $2 WHERE LOWER(wallet_address) = $1 RETURNING id")
+            .bind(&address)
+            .bind(chain_id as i64)
+            .fetch_optional(&mut *conn)
+            .await?;
+    if let Some(id) = existing {
+        return Ok((id, false));
+    }
+    wallet_services::ensure_can_link(&mut *conn, None, &address, relink_cooldown_hours).await?;
+
+    let password_hash = bcrypt::hash(generate_random_string(48), bcrypt::DEFAULT_COST)?;
+    let username = format!("eth_{}_{}", &address[2..8], generate_random_hex(3));
+    let id = sqlx::query_scalar(
+        "INSERT INTO users (email, username, password_hash, wallet_address, wallet_chain_id, is_verified) \
+         VALUES ($1, $2, $3, $4, $5, FALSE) RETURNING id",
+    )
+    .bind(format!("{}@wallet.invalid", address))
+    .bind(&username)
+    .bind(&password_hash)
+    .bind(BlockchainService::to_checksum_address(&address))
+    .bind(chain_id as i64)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok((id, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use k256::ecdsa::SigningKey;
+
+    fn message(address: &str) -> SiweMessage {
+        let issued_at = Utc.with_ymd_and_hms(2026, 10, 16, 9, 0, 0).unwrap();
+        SiweMessage {
+            scheme: None,
+            domain: "app.roboveda.io".to_string(),
+            address: address.to_string(),
+            statement: Some(DEFAULT_STATEMENT.to_string()),
+            uri: "https://app.roboveda.io".to_string(),
+            version: "1".to_string(),
+            chain_id: 1,
+            nonce: "a1b2c3d4e5f60718".to_string(),
+            issued_at,
+            expiration_time: Some(issued_at + Duration::minutes(NONCE_TTL_MINUTES)),
+            not_before: None,
+            request_id: None,
+            resources: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        let text = "https://app.roboveda.io wants you to sign in with your Ethereum account:\n\
+                    0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed\n\n\n\
+                    URI: https://app.roboveda.io/login\n\
+                    Version: 1\n\
+                    Chain ID: 137\n\
+                    Nonce: 32891756abcdef01\n\
+                    Issued At: 2026-10-16T09:00:00Z\n\
+                    Request ID: req-1\n\
+                    Resources:\n\
+                    - ipfs://bafybeiemxf5abjwjbikoz4mc3a3dla6ual3jsgpdr4cjr3oz3evfyavhwq\n\
+                    - https://app.roboveda.io/terms";
+        let parsed = SiweMessage::parse(text).unwrap();
+
+        assert_eq!(parsed.scheme.as_deref(), Some("https"));
+        assert_eq!(parsed.domain, "app.roboveda.io");
+        assert_eq!(parsed.statement, None);
+        assert_eq!(parsed.chain_id, 137);
+        assert_eq!(parsed.expiration_time, None);
+        assert_eq!(parsed.request_id.as_deref(), Some("req-1"));
+        assert_eq!(parsed.resources.len(), 2);
+        assert_eq!(parsed.to_string(), text);
+
+        let with_statement = message("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed");
+        assert_eq!(SiweMessage::parse(&with_statement.to_string()).unwrap(), with_statement);
+
+        // Lower-case addresses are not valid EIP-4361
+        let checksummed = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        let lower = text.replace(checksummed, &checksummed.to_lowercase());
+        assert!(SiweMessage::parse(&lower).is_err());
+        assert!(SiweMessage::parse(&text.replace("Version: 1", "Version: 2")).is_err());
+        assert!(SiweMessage::parse(&text.replace("Nonce: 32891756abcdef01", "Nonce: abc")).is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let message = message("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed");
+        let now = message.issued_at + Duration::minutes(1);
+
+        assert!(message.check("app.roboveda.io", now).is_ok());
+        assert!(message.check("evil.example", now).is_err());
+        assert!(message.check("app.roboveda.io", now + Duration::minutes(NONCE_TTL_MINUTES)).is_err());
+        assert!(message.check("app.roboveda.io", message.issued_at - Duration::hours(1)).is_err());
+    }
+
+    #[test]
+    fn test_recover_signer() {
+        let key = SigningKey::from_slice(&[7u8; 32]).unwrap();
+        let address = BlockchainService::address_of(key.verifying_key());
+        let text = message(&BlockchainService::to_checksum_address(&address)).to_string();
+
+        let (sig, recovery_id) = key.sign_prehash_recoverable(&BlockchainService::eip191_hash(&text)).unwrap();
+        let mut bytes = sig.to_bytes().to_vec();
+        bytes.push(27 + recovery_id.to_byte());
+        let signature = format!("0x{}", hex::encode(bytes));
+
+        assert_eq!(recover_signer(&text, &signature).unwrap(), address);
+        assert_ne!(recover_signer(&text.replace("Chain ID: 1", "Chain ID: 5"), &signature).unwrap(), address);
+        assert!(recover_signer(&text, "not-a-signature").is_err());
+    }
+}
@@ -0,0 +1,139 @@
+//! Text-to-speech for hands-free operators: assistant answers, or any text, read out by the
+//! provider's speech model and streamed back as audio while it is synthesized. Speech is priced
+//! by the character, so the cost is known and charged before the audio starts.
+
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::speech::SpeakRequest;
+use crate::services::ai_routing_services::{
+    budget, ensure_budget_left, record_usage, UsageRecord, REASON_REQUESTED, REASON_SELF_HOSTED,
+};
+use crate::services::ai_services::{AIService, SpeechAudio, SpeechInput};
+use crate::services::{conversation_services, moderation_services};
+
+/// The providers' input limit
+const MAX_TEXT_CHARS: usize = 4_096;
+const MAX_VOICE_CHARS: usize = 50;
+/// OpenAI's tts-1 rate
+const USD_PER_MILLION_CHARS: f64 = 15.0;
+const DEFAULT_FORMAT: &str = "mp3";
+/// Output formats with their media types
+const FORMATS: &[(&str, &str)] = &[
+    ("mp3", "audio/mpeg"),
+    ("opus", "audio/ogg"),
+    ("aac", "audio/aac"),
+    ("flac", "audio/flac"),
+    ("wav", "audio/wav"),
+];
+
+/// The format asked for with its media type
+pub fn output_format(format: Option<&str>) -> ApiResult<(&'static str, &'static str)> {
+    let format = format.map(|f| f.trim().to_ascii_lowercase()).filter(|f| !f.is_empty());
+    let format = format.as_deref().unwrap_or(DEFAULT_FORMAT);
+    FORMATS.iter().find(|(name, _)| *name == format).copied().ok_or_else(|| {
+        let names: Vec<&str> = FORMATS.iter().map(|(name, _)| *name).collect();
+        ApiError::ValidationError(format!("format must be one of {}", names.join(", ")))
+    })
+}
+
+/// Voices differ by provider and grow over time, so only their shape is checked
+pub fn validate_voice(voice: &str) -> ApiResult<String> {
+    let voice = voice.trim();
+    let valid = !voice.is_empty()
+        && voice.len() <= MAX_VOICE_CHARS
+        && voice.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(ApiError::ValidationError("voice must be a voice name such as alloy".to_string()));
+    }
+    Ok(voice.to_string())
+}
+
+pub fn cost_usd(chars: usize) -> f64 {
+    chars as f64 * USD_PER_MILLION_CHARS / 1_000_000.0
+}
+
+/// Synthesize speech, returning the audio's media type and stream
+pub async fn speak(
+    pool: &PgPool,
+    config: &AppConfig,
+    ai: &AIService,
+    user_id: Uuid,
+    request: &SpeakRequest,
+) -> ApiResult<(&'static str, SpeechAudio)> {
+    let text = match (request.text.as_deref(), request.message_id) {
+        (Some(text), None) => {
+            let mut text = text.to_string();
+            moderation_services::screen_prompt(config, ai, "text", &mut text).await?;
+            text
+        }
+        // Answers were moderated when they were given
+        (None, Some(message_id)) => conversation_services::assistant_message(pool, user_id, message_id).await?,
+        _ => return Err(ApiError::ValidationError("Give either text or message_id".to_string())),
+    };
+    let text = text.trim().to_string();
+    let chars = text.chars().count();
+    if chars == 0 || chars > MAX_TEXT_CHARS {
+        return Err(ApiError::ValidationError(format!(
+            "The text must be between 1 and {} characters",
+            MAX_TEXT_CHARS
+        )));
+    }
+    let (format, media_type) = output_format(request.format.as_deref())?;
+    let voice = validate_voice(request.voice.as_deref().unwrap_or(&config.ai_speech_voice))?;
+
+    let provider = ai.provider(request.provider.as_deref())?;
+    let kind = provider.kind();
+    let model = provider
+        .speech_model()
+        .ok_or_else(|| ApiError::ValidationError(format!("AI provider {} does not synthesize speech", kind.as_str())))?
+        .to_string();
+    let status = budget(pool, config, user_id).await?;
+    if !kind.is_self_hosted() {
+        ensure_budget_left(&status)?;
+    }
+
+    let speech = SpeechInput { text, voice, format: format.to_string() };
+    let audio = provider.speak(&speech).await?;
+    let (reason, cost) =
+        if kind.is_self_hosted() { (REASON_SELF_HOSTED, 0.0) } else { (REASON_REQUESTED, cost_usd(chars)) };
+    record_usage(
+        pool,
+        user_id,
+        &UsageRecord {
+            provider: kind.as_str(),
+            requested_model: &model,
+            model: &audio.model,
+            reason,
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            estimated_usd: cost,
+            actual_usd: Some(cost),
+            failover_from: None,
+        },
+    )
+    .await?;
+    Ok((media_type, audio))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_format() {
+        assert_eq!(output_format(None).unwrap(), ("mp3", "audio/mpeg"));
+        assert_eq!(output_format(Some(" OPUS ")).unwrap(), ("opus", "audio/ogg"));
+        assert!(output_format(Some("midi")).is_err());
+    }
+
+    #[test]
+    fn test_validate_voice() {
+        assert_eq!(validate_voice(" nova ").unwrap(), "nova");
+        assert!(validate_voice("en-US-JennyNeural").is_ok());
+        assert!(validate_voice("").is_err());
+        assert!(validate_voice("../voices").is_err());
+        assert!((cost_usd(2_000) - 0.03).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,189 @@
+//! Per-user spend limits. Card and crypto payments a user starts are valued in USD and added up
+//! per UTC day and calendar month; a payment that would take either total over the user's limit
+//! is refused. Limits default to the configured ones and an admin may override them per user.
+
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::spending::{SetSpendingLimitsRequest, SpendingLimits, SpendingStatus, SpendingWindow};
+use crate::services::rate_services::{self, DEFAULT_CURRENCY};
+use crate::services::{escrow_services, payout_services};
+
+/// Transactions that count towards spend: everything not failed or refunded in full
+const COUNTED_STATUSES: &[&str] = &["pending", "completed", "partially_refunded"];
+
+/// Money the platform sends out rather than the user spending it
+const UNCOUNTED_PRODUCT_TYPES: &[&str] = &[payout_services::PRODUCT_TYPE, escrow_services::PAYOUT_PRODUCT_TYPE];
+
+/// A configured or overridden limit; 0 means none
+fn limit(value: Decimal) -> Option<Decimal> {
+    (value > Decimal::ZERO).then_some(value)
+}
+
+/// Start of a period and of the next one
+type Period = (DateTime<Utc>, DateTime<Utc>);
+
+/// The current UTC day and calendar month
+pub fn periods(now: DateTime<Utc>) -> (Period, Period) {
+    let day = Utc.from_utc_datetime(&now.date_naive().and_hms_opt(0, 0, 0).unwrap_or_default());
+    let month = Utc
+        .with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+        .single()
+        .unwrap_or(day);
+    let next_month = if now.month() == 12 {
+        Utc.with_ymd_and_hms(now.year() + 1, 1, 1, 0, 0, 0)
+    } else {
+        Utc.with_ymd_and_hms(now.year(), now.month() + 1, 1, 0, 0, 0)
+    };
+    ((day, day + Duration::days(1)), (month, next_month.single().unwrap_or(month + Duration::days(31))))
+}
+
+/// Whether spending `amount` more on top of `spent` goes over `limit`
+pub fn exceeds(spent: Decimal, amount: Decimal, limit: Option<Decimal>) -> bool {
+    limit.is_some_and(|limit| spent + amount > limit)
+}
+
+/// The user's daily and monthly limits in USD, `None` where there is no limit
+pub async fn limits(pool: &PgPool, config: &AppConfig, user_id: Uuid) -> ApiResult<(Option<Decimal>, Option<Decimal>)> {
+    let own: Option<(Option<Decimal>, Option<Decimal>)> =
+        sqlx::query_as("SELECT daily_limit, monthly_limit FROM spending_limits WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+    let (daily, monthly) = own.unwrap_or_default();
+    Ok((
+        limit(daily.unwrap_or(config.spend_limit_daily_usd)),
+        limit(monthly.unwrap_or(config.spend_limit_monthly_usd)),
+    ))
+}
+
+/// `amount` of `currency` (a fiat code or a token symbol) in USD. Amounts without a price, or
+/// priced while the rate source is down, count as nothing rather than blocking payments.
+pub async fn value_usd(config: &AppConfig, amount: Decimal, currency: &str) -> Option<Decimal> {
+    if currency.eq_ignore_ascii_case(DEFAULT_CURRENCY) {
+        return Some(amount);
+    }
+    let symbol = currency.to_uppercase();
+    match rate_services::rates(config, std::slice::from_ref(&symbol), DEFAULT_CURRENCY).await {
+        Ok(rates) => rates.value_of(&symbol, &amount.to_string()),
+        Err(e) => {
+            tracing::warn!(currency = %currency, "Spend valued without a rate: {}", e);
+            None
+        }
+    }
+}
+
+/// What the user has spent since `since`, in USD
+async fn spent_since(pool: &PgPool, config: &AppConfig, user_id: Uuid, since: DateTime<Utc>) -> ApiResult<Decimal> {
+    let totals: Vec<(String, Decimal)> = sqlx::query_as(
+        "SELECT currency, COALESCE(SUM(amount), 0) FROM transactions \
+         WHERE user_id = $1 AND created_at >= $2 AND status = ANY($3) AND NOT (product_type = ANY($4)) \
+         GROUP BY currency",
+    )
+    .bind(user_id)
+    .bind(since)
+    .bind(COUNTED_STATUSES)
+    .bind(UNCOUNTED_PRODUCT_TYPES)
+    .fetch_all(pool)
+    .await?;
+    let mut spent = Decimal::ZERO;
+    for (currency, amount) in totals {
+        spent += value_usd(config, amount, &currency).await.unwrap_or_default();
+    }
+    Ok(spent.round_dp(2))
+}
+
+fn window(limit: Option<Decimal>, spent: Decimal, resets_at: DateTime<Utc>) -> SpendingWindow {
+    SpendingWindow {
+        limit,
+        spent,
+        remaining: limit.map(|limit| (limit - spent).max(Decimal::ZERO).round_dp(2)),
+        resets_at,
+    }
+}
+
+/// The user's spend this UTC day and month against their limits
+pub async fn status(pool: &PgPool, config: &AppConfig, user_id: Uuid) -> ApiResult<SpendingStatus> {
+    let ((day, next_day), (month, next_month)) = periods(Utc::now());
+    let (daily_limit, monthly_limit) = limits(pool, config, user_id).await?;
+    let daily = spent_since(pool, config, user_id, day).await?;
+    let monthly = spent_since(pool, config, user_id, month).await?;
+    Ok(SpendingStatus {
+        currency: DEFAULT_CURRENCY.to_string(),
+        daily: window(daily_limit, daily, next_day),
+        monthly: window(monthly_limit, monthly, next_month),
+    })
+}
+
+/// Refuse a payment of `amount_usd` that would take the user over a limit
+pub async fn check(pool: &PgPool, config: &AppConfig, user_id: Uuid, amount_usd: Decimal) -> ApiResult<()> {
+    let status = status(pool, config, user_id).await?;
+    for (name, window) in [("daily", &status.daily), ("monthly", &status.monthly)] {
+        if exceeds(window.spent, amount_usd, window.limit) {
+            return Err(ApiError::Forbidden(format!(
+                "This payment would exceed your {} spending limit of {:.2} USD ({:.2} left)",
+                name,
+                window.limit.unwrap_or_default(),
+                window.remaining.unwrap_or_default()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Override a user's limits; a limit left out goes back to the platform default
+pub async fn set_limits(
+    pool: &PgPool,
+    admin_id: Uuid,
+    user_id: Uuid,
+    request: &SetSpendingLimitsRequest,
+) -> ApiResult<SpendingLimits> {
+    for value in [request.daily_limit, request.monthly_limit].into_iter().flatten() {
+        if value.is_sign_negative() {
+            return Err(ApiError::ValidationError("Limits must be zero or more".to_string()));
+        }
+    }
+    let limits = sqlx::query_as::<_, SpendingLimits>(
+        "INSERT INTO spending_limits (user_id, daily_limit, monthly_limit, updated_by) \
+         SELECT id, $2, $3, $4 FROM users WHERE id = $1 \
+         ON CONFLICT (user_id) DO UPDATE SET daily_limit = $2, monthly_limit = $3, updated_by = $4, \
+             updated_at = NOW() \
+         RETURNING user_id, daily_limit, monthly_limit, updated_by, updated_at",
+    )
+    .bind(user_id)
+    .bind(request.daily_limit)
+    .bind(request.monthly_limit)
+    .bind(admin_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+    Ok(limits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_periods() {
+        let now = Utc.with_ymd_and_hms(2026, 12, 31, 18, 30, 0).unwrap();
+        let ((day, next_day), (month, next_month)) = periods(now);
+        assert_eq!(day, Utc.with_ymd_and_hms(2026, 12, 31, 0, 0, 0).unwrap());
+        assert_eq!(next_day, Utc.with_ymd_and_hms(2027, 1, 1, 0, 0, 0).unwrap());
+        assert_eq!(month, Utc.with_ymd_and_hms(2026, 12, 1, 0, 0, 0).unwrap());
+        assert_eq!(next_month, Utc.with_ymd_and_hms(2027, 1, 1, 0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_exceeds() {
+        let (spent, limit_1000) = (Decimal::from(900), Some(Decimal::from(1_000)));
+        assert!(!exceeds(spent, Decimal::from(100), limit_1000));
+        assert!(exceeds(spent, Decimal::new(10001, 2), limit_1000));
+        assert!(!exceeds(Decimal::from(1_000_000_000), Decimal::ONE, None));
+        assert_eq!(limit(Decimal::ZERO), None);
+        assert_eq!(limit(Decimal::from(250)), Some(Decimal::from(250)));
+    }
+}
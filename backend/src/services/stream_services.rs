@@ -0,0 +1,91 @@
+//! WebRTC signaling helpers for device camera streams
+
+use secrecy::ExposeSecret;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::device::{Device, IceCandidate};
+
+/// Largest SDP blob accepted for an offer or answer
+pub const MAX_SDP_BYTES: usize = 64 * 1024;
+/// How long a signaling session stays open before it is considered abandoned
+pub const STREAM_SESSION_TTL_MINUTES: i64 = 60;
+
+/// Drones carry cameras; other device types opt in with `metadata.camera = true`
+pub fn has_camera(device: &Device) -> bool {
+    device.device_type == "drone"
+        || device.metadata.get("camera").and_then(|v| v.as_bool()).unwrap_or(false)
+}
+
+/// Basic structural check that a blob is a session description (RFC 8866)
+pub fn validate_sdp(sdp: &str) -> ApiResult<()> {
+    if sdp.len() > MAX_SDP_BYTES {
+        return Err(ApiError::ValidationError("SDP is too large".to_string()));
+    }
+    if !sdp.starts_with("v=0") || !sdp.lines().any(|l| l.starts_with("m=video") || l.starts_with("m=application")) {
+        return Err(ApiError::ValidationError(
+            "SDP must be a session description with a video or data section".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// An ICE candidate line, or the empty end-of-candidates marker
+pub fn validate_candidate(candidate: &IceCandidate) -> ApiResult<()> {
+    if candidate.candidate.len() > 1024 {
+        return Err(ApiError::ValidationError("ICE candidate is too large".to_string()));
+    }
+    if !candidate.candidate.is_empty() && !candidate.candidate.starts_with("candidate:") {
+        return Err(ApiError::ValidationError("Malformed ICE candidate".to_string()));
+    }
+    Ok(())
+}
+
+/// ICE servers in RTCConfiguration form for both peers
+pub fn ice_servers(config: &AppConfig) -> serde_json::Value {
+    let servers: Vec<serde_json::Value> = config
+        .webrtc_ice_servers
+        .iter()
+        .map(|url| {
+            if url.starts_with("turn:") || url.starts_with("turns:") {
+                serde_json::json!({
+                    "urls": url,
+                    "username": config.webrtc_turn_username,
+                    "credential": config.webrtc_turn_credential.as_ref().map(|c| c.expose_secret()),
+                })
+            } else {
+                serde_json::json!({ "urls": url })
+            }
+        })
+        .collect();
+    serde_json::Value::Array(servers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_sdp() {
+        let offer = "v=0\r\no=- 4611 2 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\nm=video 9 UDP/TLS/RTP/SAVPF 96\r\n";
+        assert!(validate_sdp(offer).is_ok());
+        assert!(validate_sdp("hello").is_err());
+        assert!(validate_sdp("v=0\r\ns=-\r\nm=audio 9 RTP/AVP 0\r\n").is_err());
+        assert!(validate_sdp(&format!("{}{}", offer, "a".repeat(MAX_SDP_BYTES))).is_err());
+    }
+
+    #[test]
+    fn test_validate_candidate() {
+        let mut candidate = IceCandidate {
+            candidate: "candidate:842163049 1 udp 1677729535 203.0.113.5 46154 typ srflx".to_string(),
+            sdp_mid: Some("0".to_string()),
+            sdp_mline_index: Some(0),
+        };
+        assert!(validate_candidate(&candidate).is_ok());
+
+        candidate.candidate = String::new();
+        assert!(validate_candidate(&candidate).is_ok());
+
+        candidate.candidate = "<script>".to_string();
+        assert!(validate_candidate(&candidate).is_err());
+    }
+}
@@ -0,0 +1,570 @@
+//! Stripe API calls and webhooks. Events are signed with the endpoint's secret; PaymentIntent
+//! events settle the transaction created for the intent, and dispute events open and close
+//! chargebacks against it.
+
+use chrono::{TimeZone, Utc};
+use hmac::{Hmac, Mac};
+use secrecy::ExposeSecret;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::Sha256;
+use sqlx::{PgConnection, PgPool};
+use std::sync::LazyLock;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::dispute::ProviderDispute;
+use crate::models::transaction::{ProviderPaymentIntent, ProviderRefund, Transaction};
+use crate::services::payment_services::{
+    complete_transaction, fail_transaction, find_provider_transaction, minor_units, record_provider_event,
+};
+use crate::services::{dispute_services, subscription_services};
+use crate::utils::secure_compare;
+
+pub const PROVIDER: &str = "stripe";
+/// Stripe's own libraries reject signatures older than this
+const SIGNATURE_TOLERANCE_SECS: i64 = 300;
+const API_BASE: &str = "https://api.stripe.com/v1";
+
+static STRIPE_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(15))
+        .build()
+        .expect("Failed to build Stripe HTTP client")
+});
+
+pub fn is_configured(config: &AppConfig) -> bool {
+    !config.stripe_secret_key.expose_secret().is_empty()
+}
+
+/// POST a form to the Stripe API. `idempotency_key` makes a retried call return what the
+/// first one created instead of creating it twice.
+async fn post_form<T: serde::de::DeserializeOwned>(
+    config: &AppConfig,
+    path: &str,
+    form: &[(&str, &str)],
+    idempotency_key: &str,
+) -> ApiResult<T> {
+    let response = STRIPE_CLIENT
+        .post(format!("{}/{}", API_BASE, path))
+        .bearer_auth(config.stripe_secret_key.expose_secret())
+        .header("Idempotency-Key", idempotency_key)
+        .form(form)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or_default();
+        let message = body["error"]["message"].as_str().unwrap_or("no details");
+        return Err(ApiError::PaymentError(format!("Stripe {} failed ({}): {}", path, status, message)));
+    }
+    Ok(response.json().await?)
+}
+
+/// Refund `amount` minor units of a PaymentIntent
+pub async fn create_refund(
+    config: &AppConfig,
+    payment_intent: &str,
+    amount: i64,
+    idempotency_key: &str,
+) -> ApiResult<ProviderRefund> {
+    let amount = amount.to_string();
+    post_form(config, "refunds", &[("payment_intent", payment_intent), ("amount", &amount)], idempotency_key).await
+}
+
+/// Create the Stripe customer that saved payment methods and subscription charges belong to
+pub async fn create_customer(config: &AppConfig, email: &str, user_id: Uuid) -> ApiResult<String> {
+    let user_id = user_id.to_string();
+    let customer: Value = post_form(
+        config,
+        "customers",
+        &[("email", email), ("metadata[user_id]", &user_id)],
+        &format!("customer-{}", user_id),
+    )
+    .await?;
+    customer["id"]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| ApiError::PaymentError("Stripe returned a customer without an id".to_string()))
+}
+
+/// A PaymentIntent the customer confirms in the app, saving the card for off-session charges
+pub async fn create_payment_intent(
+    config: &AppConfig,
+    customer: &str,
+    amount: i64,
+    currency: &str,
+    transaction_id: Uuid,
+) -> ApiResult<ProviderPaymentIntent> {
+    let transaction_id = transaction_id.to_string();
+    post_form(
+        config,
+        "payment_intents",
+        &[
+            ("amount", &amount.to_string()),
+            ("currency", currency),
+            ("customer", customer),
+            ("setup_future_usage", "off_session"),
+            ("automatic_payment_methods[enabled]", "true"),
+            ("metadata[transaction_id]", &transaction_id),
+        ],
+        &transaction_id,
+    )
+    .await
+}
+
+/// Charge a saved payment method with the customer away, e.g. for a renewal. A declined card
+/// is an error; cards needing authentication come back `requires_action`.
+pub async fn charge_off_session(
+    config: &AppConfig,
+    customer: &str,
+    payment_method: &str,
+    amount: i64,
+    currency: &str,
+    transaction_id: Uuid,
+) -> ApiResult<ProviderPaymentIntent> {
+    let transaction_id = transaction_id.to_string();
+    post_form(
+        config,
+        "payment_intents",
+        &[
+            ("amount", &amount.to_string()),
+            ("currency", currency),
+            ("customer", customer),
+            ("payment_method", payment_method),
+            ("off_session", "true"),
+            ("confirm", "true"),
+            ("metadata[transaction_id]", &transaction_id),
+        ],
+        &transaction_id,
+    )
+    .await
+}
+
+/// What became of a transfer request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferOutcome {
+    /// The transfer id
+    Created(String),
+    /// Stripe refused the transfer; nothing was moved
+    Rejected(String),
+    /// The transfer may or may not have been made, e.g. after a timeout or a 5xx
+    Unknown(String),
+}
+
+/// Whether Stripe answering with `status` means the request was refused and nothing was
+/// created. A 409 is the first request with the same idempotency key still running, and a 429
+/// is worth retrying, so neither is final.
+pub fn is_definite_rejection(status: u16) -> bool {
+    (400..500).contains(&status) && !matches!(status, 409 | 429)
+}
+
+/// Move `amount` minor units from the platform balance to a Connect account. Retrying with
+/// the same `idempotency_key` returns the transfer the first call made instead of a second one.
+pub async fn create_transfer(
+    config: &AppConfig,
+    destination: &str,
+    amount: i64,
+    currency: &str,
+    idempotency_key: &str,
+) -> TransferOutcome {
+    let amount = amount.to_string();
+    let response = match STRIPE_CLIENT
+        .post(format!("{}/transfers", API_BASE))
+        .bearer_auth(config.stripe_secret_key.expose_secret())
+        .header("Idempotency-Key", idempotency_key)
+        .form(&[("amount", amount.as_str()), ("currency", currency), ("destination", destination)])
+        .send()
+        .await
+    {
+        Ok(response) => response,
+        Err(e) => return TransferOutcome::Unknown(format!("Stripe transfers request failed: {}", e)),
+    };
+
+    let status = response.status();
+    let body: Value = response.json().await.unwrap_or_default();
+    if status.is_success() {
+        return match body["id"].as_str() {
+            Some(id) => TransferOutcome::Created(id.to_string()),
+            None => TransferOutcome::Unknown("Stripe returned a transfer without an id".to_string()),
+        };
+    }
+
+    let message = format!(
+        "Stripe transfers failed ({}): {}",
+        status,
+        body["error"]["message"].as_str().unwrap_or("no details")
+    );
+    if is_definite_rejection(status.as_u16()) {
+        TransferOutcome::Rejected(message)
+    } else {
+        TransferOutcome::Unknown(message)
+    }
+}
+
+/// Cancel a PaymentIntent that has not been paid
+pub async fn cancel_payment_intent(config: &AppConfig, payment_intent: &str) -> ApiResult<()> {
+    let _: Value = post_form(
+        config,
+        &format!("payment_intents/{}/cancel", payment_intent),
+        &[],
+        &format!("cancel-{}", payment_intent),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Check a `Stripe-Signature` header (`t=<unix time>,v1=<hex hmac>[,v1=...]`) against the raw
+/// body. Any `v1` may match, which covers the overlap while an endpoint secret is rolled.
+pub fn verify_signature(secret: &[u8], header: &str, payload: &[u8], now: i64) -> bool {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", t)) => timestamp = Some(t),
+            Some(("v1", signature)) => signatures.push(signature),
+            _ => {}
+        }
+    }
+    let Some(timestamp) = timestamp else {
+        return false;
+    };
+    let Ok(sent_at) = timestamp.parse::<i64>() else {
+        return false;
+    };
+    if (now - sent_at).abs() > SIGNATURE_TOLERANCE_SECS {
+        return false;
+    }
+
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(payload);
+    let expected = hex::encode(mac.finalize().into_bytes());
+    signatures.iter().any(|signature| secure_compare(&expected, &signature.to_ascii_lowercase()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StripeEvent {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub data: StripeEventData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StripeEventData {
+    pub object: Value,
+}
+
+/// What a PaymentIntent event says about its payment
+#[derive(Debug, PartialEq)]
+pub enum PaymentOutcome {
+    Succeeded { amount: i64, currency: String },
+    Failed { reason: String },
+}
+
+fn succeeded(intent: &Value) -> Option<PaymentOutcome> {
+    Some(PaymentOutcome::Succeeded {
+        amount: intent["amount_received"].as_i64().or_else(|| intent["amount"].as_i64())?,
+        currency: intent["currency"].as_str()?.to_string(),
+    })
+}
+
+fn declined(intent: &Value) -> PaymentOutcome {
+    PaymentOutcome::Failed {
+        reason: intent["last_payment_error"]["message"]
+            .as_str()
+            .unwrap_or("The payment was declined")
+            .to_string(),
+    }
+}
+
+fn canceled(intent: &Value) -> PaymentOutcome {
+    PaymentOutcome::Failed {
+        reason: match intent["cancellation_reason"].as_str() {
+            Some(reason) => format!("The payment was canceled ({})", reason.replace('_', " ")),
+            None => "The payment was canceled".to_string(),
+        },
+    }
+}
+
+/// The PaymentIntent id and outcome of the events that settle a payment
+pub fn payment_outcome(event: &StripeEvent) -> Option<(String, PaymentOutcome)> {
+    let intent = &event.data.object;
+    let id = intent["id"].as_str()?.to_string();
+    let outcome = match event.event_type.as_str() {
+        "payment_intent.succeeded" => succeeded(intent)?,
+        "payment_intent.payment_failed" => declined(intent),
+        "payment_intent.canceled" => canceled(intent),
+        _ => return None,
+    };
+    Some((id, outcome))
+}
+
+/// The dispute a `charge.dispute.*` event is about, with its PaymentIntent
+pub fn dispute_event(event: &StripeEvent) -> Option<ProviderDispute> {
+    if !event.event_type.starts_with("charge.dispute.") {
+        return None;
+    }
+    let dispute = &event.data.object;
+    // Expanded into the intent object when the endpoint asks for it
+    let payment_id = dispute["payment_intent"].as_str().or_else(|| dispute["payment_intent"]["id"].as_str())?;
+    Some(ProviderDispute {
+        id: dispute["id"].as_str()?.to_string(),
+        payment_id: payment_id.to_string(),
+        amount: dispute["amount"].as_i64()?,
+        currency: dispute["currency"].as_str()?.to_string(),
+        reason: dispute["reason"].as_str().map(str::to_string),
+        status: dispute["status"].as_str()?.to_string(),
+        evidence_due_by: dispute["evidence_details"]["due_by"]
+            .as_i64()
+            .and_then(|at| Utc.timestamp_opt(at, 0).single()),
+    })
+}
+
+/// The outcome a PaymentIntent's current status settles on; `None` while it can still be paid.
+/// A declined attempt leaves the intent `requires_payment_method` with the error attached.
+pub fn intent_outcome(intent: &Value) -> Option<PaymentOutcome> {
+    match intent["status"].as_str()? {
+        "succeeded" => succeeded(intent),
+        "canceled" => Some(canceled(intent)),
+        "requires_payment_method" if intent["last_payment_error"].is_object() => Some(declined(intent)),
+        _ => None,
+    }
+}
+
+/// Fetch a PaymentIntent as it stands now
+pub async fn retrieve_payment_intent(config: &AppConfig, payment_intent: &str) -> ApiResult<Value> {
+    let response = STRIPE_CLIENT
+        .get(format!("{}/payment_intents/{}", API_BASE, payment_intent))
+        .bearer_auth(config.stripe_secret_key.expose_secret())
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or_default();
+        let message = body["error"]["message"].as_str().unwrap_or("no details");
+        return Err(ApiError::PaymentError(format!("Stripe payment intent lookup failed ({}): {}", status, message)));
+    }
+    Ok(response.json().await?)
+}
+
+/// Settle the intent's locked transaction on its outcome. Returns `completed`, `failed`,
+/// `mismatch` (the amount paid differs from the transaction's) or `ignored`.
+pub async fn settle_intent(
+    conn: &mut PgConnection,
+    transaction: &Transaction,
+    outcome: &PaymentOutcome,
+    intent: &Value,
+) -> ApiResult<&'static str> {
+    let result = match outcome {
+        PaymentOutcome::Succeeded { amount, currency } => {
+            let expected = minor_units(transaction.amount, &transaction.currency);
+            if *amount != expected || !currency.eq_ignore_ascii_case(&transaction.currency) {
+                tracing::warn!(
+                    transaction_id = %transaction.id,
+                    "Stripe payment of {} {} does not match the transaction's {} {}",
+                    amount, currency, expected, transaction.currency
+                );
+                let reason = "The amount paid does not match the order";
+                fail_transaction(conn, transaction, reason).await?;
+                "mismatch"
+            } else if complete_transaction(conn, transaction).await? {
+                if transaction.product_type == subscription_services::PRODUCT_TYPE
+                    && let Some(payment_method) = intent["payment_method"].as_str()
+                {
+                    subscription_services::save_payment_method(conn, transaction, payment_method).await?;
+                }
+                "completed"
+            } else {
+                "ignored"
+            }
+        }
+        PaymentOutcome::Failed { reason } => {
+            let failed = fail_transaction(conn, transaction, reason).await?;
+            if failed { "failed" } else { "ignored" }
+        }
+    };
+    Ok(result)
+}
+
+/// Apply an event once. Returns what was done: `completed`, `failed`, `mismatch` (the amount
+/// paid differs from the transaction's), one of the dispute outcomes, `ignored` or `duplicate`.
+pub async fn process_event(pool: &PgPool, event: &StripeEvent) -> ApiResult<&'static str> {
+    let mut tx = pool.begin().await?;
+    let dispute = dispute_event(event);
+    let (transaction, outcome) = match (payment_outcome(event), &dispute) {
+        (Some((intent_id, outcome)), _) => {
+            (find_provider_transaction(&mut tx, PROVIDER, &intent_id).await?, Some(outcome))
+        }
+        (None, Some(dispute)) => (find_provider_transaction(&mut tx, PROVIDER, &dispute.payment_id).await?, None),
+        (None, None) => (None, None),
+    };
+
+    let result = match (&transaction, &outcome, &dispute) {
+        (Some(transaction), Some(outcome), _) => {
+            settle_intent(&mut tx, transaction, outcome, &event.data.object).await?
+        }
+        (Some(transaction), None, Some(dispute)) => {
+            dispute_services::ingest(&mut tx, PROVIDER, transaction, dispute).await?
+        }
+        _ => "ignored",
+    };
+
+    let transaction_id: Option<Uuid> = transaction.as_ref().map(|t| t.id);
+    if !record_provider_event(&mut tx, PROVIDER, &event.id, &event.event_type, transaction_id, result).await? {
+        // Already handled: drop whatever this delivery changed
+        tx.rollback().await?;
+        return Ok("duplicate");
+    }
+    tx.commit().await?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sign(secret: &[u8], timestamp: i64, payload: &[u8]) -> String {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(secret).unwrap();
+        mac.update(format!("{}.", timestamp).as_bytes());
+        mac.update(payload);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let payload = br#"{"id":"evt_1","type":"payment_intent.succeeded"}"#;
+        let now = 1_792_000_000;
+        let good = sign(b"whsec_test", now, payload);
+        let header = format!("t={},v1={},v0=legacy", now, good);
+
+        assert!(verify_signature(b"whsec_test", &header, payload, now + 10));
+        assert!(!verify_signature(b"whsec_other", &header, payload, now));
+        assert!(!verify_signature(b"whsec_test", &header, b"{}", now));
+        assert!(!verify_signature(b"whsec_test", &header, payload, now + SIGNATURE_TOLERANCE_SECS + 1));
+        assert!(!verify_signature(b"whsec_test", &format!("v1={}", good), payload, now));
+
+        // During a secret roll Stripe signs with both secrets
+        let rolled = format!("t={},v1={},v1={}", now, sign(b"whsec_old", now, payload), good);
+        assert!(verify_signature(b"whsec_test", &rolled, payload, now));
+    }
+
+    #[test]
+    fn test_payment_outcome() {
+        let event = |event_type: &str, object: Value| StripeEvent {
+            id: "evt_1".to_string(),
+            event_type: event_type.to_string(),
+            data: StripeEventData { object },
+        };
+
+        let succeeded = event(
+            "payment_intent.succeeded",
+            serde_json::json!({ "id": "pi_1", "amount": 160, "amount_received": 160, "currency": "usd" }),
+        );
+        assert_eq!(
+            payment_outcome(&succeeded),
+            Some(("pi_1".to_string(), PaymentOutcome::Succeeded { amount: 160, currency: "usd".to_string() }))
+        );
+
+        let failed = event(
+            "payment_intent.payment_failed",
+            serde_json::json!({ "id": "pi_2", "last_payment_error": { "message": "Your card was declined." } }),
+        );
+        assert_eq!(
+            payment_outcome(&failed),
+            Some(("pi_2".to_string(), PaymentOutcome::Failed { reason: "Your card was declined.".to_string() }))
+        );
+
+        let canceled = event("payment_intent.canceled", serde_json::json!({ "id": "pi_3" }));
+        assert!(matches!(payment_outcome(&canceled), Some((_, PaymentOutcome::Failed { .. }))));
+        assert_eq!(payment_outcome(&event("charge.refunded", serde_json::json!({ "id": "ch_1" }))), None);
+    }
+
+    #[test]
+    fn test_dispute_event() {
+        let event = |event_type: &str, object: Value| StripeEvent {
+            id: "evt_1".to_string(),
+            event_type: event_type.to_string(),
+            data: StripeEventData { object },
+        };
+
+        let created = event(
+            "charge.dispute.created",
+            serde_json::json!({
+                "id": "dp_1",
+                "payment_intent": "pi_1",
+                "amount": 160,
+                "currency": "usd",
+                "reason": "fraudulent",
+                "status": "needs_response",
+                "evidence_details": { "due_by": 1_792_000_000 },
+            }),
+        );
+        assert_eq!(
+            dispute_event(&created),
+            Some(ProviderDispute {
+                id: "dp_1".to_string(),
+                payment_id: "pi_1".to_string(),
+                amount: 160,
+                currency: "usd".to_string(),
+                reason: Some("fraudulent".to_string()),
+                status: "needs_response".to_string(),
+                evidence_due_by: Utc.timestamp_opt(1_792_000_000, 0).single(),
+            })
+        );
+
+        let closed = event(
+            "charge.dispute.closed",
+            serde_json::json!({
+                "id": "dp_1", "payment_intent": { "id": "pi_1" }, "amount": 160, "currency": "usd", "status": "lost",
+            }),
+        );
+        let closed = dispute_event(&closed).unwrap();
+        assert_eq!((closed.payment_id.as_str(), closed.status.as_str()), ("pi_1", "lost"));
+        assert_eq!(closed.evidence_due_by, None);
+
+        // A dispute on a charge made outside a PaymentIntent has nothing to match
+        let charge_only = serde_json::json!({ "id": "dp_2", "charge": "ch_1", "amount": 1, "currency": "usd" });
+        assert_eq!(dispute_event(&event("charge.dispute.created", charge_only)), None);
+        assert_eq!(dispute_event(&event("charge.refunded", serde_json::json!({ "id": "ch_1" }))), None);
+    }
+
+    #[test]
+    fn test_intent_outcome() {
+        let paid = serde_json::json!({ "status": "succeeded", "amount": 1999, "currency": "eur" });
+        let expected = PaymentOutcome::Succeeded { amount: 1999, currency: "eur".to_string() };
+        assert_eq!(intent_outcome(&paid), Some(expected));
+
+        let declined = serde_json::json!({
+            "status": "requires_payment_method",
+            "last_payment_error": { "message": "Insufficient funds." },
+        });
+        let expected = PaymentOutcome::Failed { reason: "Insufficient funds.".to_string() };
+        assert_eq!(intent_outcome(&declined), Some(expected));
+
+        let canceled = serde_json::json!({ "status": "canceled", "cancellation_reason": "abandoned" });
+        assert_eq!(
+            intent_outcome(&canceled),
+            Some(PaymentOutcome::Failed { reason: "The payment was canceled (abandoned)".to_string() })
+        );
+
+        // Not paid yet, or awaiting 3-D Secure
+        assert_eq!(intent_outcome(&serde_json::json!({ "status": "requires_payment_method" })), None);
+        assert_eq!(intent_outcome(&serde_json::json!({ "status": "requires_action" })), None);
+    }
+
+    #[test]
+    fn test_is_definite_rejection() {
+        assert!(is_definite_rejection(400));
+        assert!(is_definite_rejection(402));
+        assert!(is_definite_rejection(404));
+        // In flight under the same idempotency key, or rate limited
+        assert!(!is_definite_rejection(409));
+        assert!(!is_definite_rejection(429));
+        assert!(!is_definite_rejection(500));
+        assert!(!is_definite_rejection(503));
+        assert!(!is_definite_rejection(200));
+    }
+}
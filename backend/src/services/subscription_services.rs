@@ -0,0 +1,839 @@
+//! Subscription billing. A subscription is started, renewed and prorated by invoices, each
+//! charged as a Stripe PaymentIntent recorded as a transaction. The first invoice is paid in
+//! the app, which saves the card; later ones are charged off-session by the renewal job.
+//! Invoices settle when their transaction does, normally through the Stripe webhook.
+
+use chrono::{DateTime, Duration, Months, Utc};
+use rust_decimal::Decimal;
+use sqlx::{PgConnection, PgPool};
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::subscription::{
+    SubscribeResponse, Subscription, SubscriptionDetails, SubscriptionInvoice, SubscriptionPlan,
+};
+use crate::models::transaction::Transaction;
+use crate::services::notification_services::notify_user;
+use crate::services::payment_services::{
+    complete_transaction, from_minor_units, minor_units, sync_premium, TRANSACTION_COLUMNS,
+};
+use crate::services::stripe_services;
+
+/// `transactions.product_type` of subscription charges
+pub const PRODUCT_TYPE: &str = "subscription";
+
+pub const BILLING_INTERVALS: &[&str] = &["month", "year"];
+
+pub const PLAN_COLUMNS: &str = "id, code, name, amount, currency, billing_interval, active, created_at, updated_at";
+
+pub const SUBSCRIPTION_COLUMNS: &str = "id, user_id, plan_id, status, current_period_start, current_period_end, \
+     cancel_at_period_end, credit_balance, canceled_at, created_at, updated_at";
+
+pub const INVOICE_COLUMNS: &str = "id, subscription_id, kind, amount, currency, period_start, period_end, status, \
+     attempt_count, next_attempt_at, paid_at, created_at";
+
+/// Statuses of a subscription that is still running; a user has at most one
+pub const LIVE_STATUSES: &[&str] = &["incomplete", "active", "past_due"];
+
+/// Days after each failed renewal or proration charge that it is retried. The subscription is
+/// past due meanwhile and canceled once the last retry fails.
+pub const RETRY_SCHEDULE_DAYS: [i64; 3] = [1, 3, 5];
+
+const JOB_INTERVAL_SECS: u64 = 300;
+/// Invoices charged per job run, so one run cannot hold the job for long
+const MAX_CHARGES_PER_RUN: usize = 100;
+
+pub fn validate_plan(code: &str, name: &str, amount: Decimal, currency: &str, billing_interval: &str) -> ApiResult<()> {
+    let code_chars = code.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_');
+    if code.is_empty() || code.len() > 50 || !code_chars {
+        return Err(ApiError::ValidationError(
+            "code must be 1-50 lowercase letters, digits or underscores".to_string(),
+        ));
+    }
+    if name.trim().is_empty() || name.chars().count() > 100 {
+        return Err(ApiError::ValidationError("name must be 1-100 characters".to_string()));
+    }
+    if minor_units(amount, currency) <= 0 {
+        return Err(ApiError::ValidationError("amount must be positive".to_string()));
+    }
+    if currency.len() != 3 || !currency.bytes().all(|b| b.is_ascii_lowercase()) {
+        return Err(ApiError::ValidationError("currency must be a lowercase ISO 4217 code, e.g. usd".to_string()));
+    }
+    if !BILLING_INTERVALS.contains(&billing_interval) {
+        return Err(ApiError::ValidationError(format!(
+            "billing_interval must be one of {}",
+            BILLING_INTERVALS.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// End of a billing period starting at `start`. Month ends clamp, so a period starting on
+/// January 31st ends on the last day of February.
+pub fn period_end(start: DateTime<Utc>, billing_interval: &str) -> DateTime<Utc> {
+    let months = if billing_interval == "year" { 12 } else { 1 };
+    start.checked_add_months(Months::new(months)).unwrap_or(start)
+}
+
+/// Delay before retrying an invoice that has failed `attempt_count` times; `None` once the
+/// retries are used up
+pub fn retry_delay(attempt_count: i32) -> Option<Duration> {
+    let retry = usize::try_from(attempt_count).ok()?.checked_sub(1)?;
+    RETRY_SCHEDULE_DAYS.get(retry).map(|days| Duration::days(*days))
+}
+
+/// The effect of moving a subscription to another plan part way through a period
+#[derive(Debug, PartialEq)]
+pub struct Proration {
+    /// Charged now
+    pub charge: Decimal,
+    /// Added to the subscription's credit, taken off the next renewal
+    pub credit: Decimal,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+}
+
+/// Credit the unused part of the current plan against the new one. Within the same interval
+/// the period is kept and only its remainder is priced at the new plan; a new interval starts
+/// a fresh period at the new plan's full price.
+pub fn prorate(
+    current: &SubscriptionPlan,
+    new: &SubscriptionPlan,
+    period_start: DateTime<Utc>,
+    period: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Proration {
+    let total = (period - period_start).num_seconds().max(1);
+    let remaining = i128::from((period - now).num_seconds().clamp(0, total));
+    let total = i128::from(total);
+    let unused = i128::from(minor_units(current.amount, &current.currency)) * remaining / total;
+    let new_amount = i128::from(minor_units(new.amount, &new.currency));
+
+    let (cost, period_start, period) = if current.billing_interval == new.billing_interval {
+        (new_amount * remaining / total, period_start, period)
+    } else {
+        (new_amount, now, period_end(now, &new.billing_interval))
+    };
+    let net = (cost - unused) as i64;
+    Proration {
+        charge: from_minor_units(net.max(0), &new.currency),
+        credit: from_minor_units((-net).max(0), &new.currency),
+        period_start,
+        period_end: period,
+    }
+}
+
+fn require_stripe(config: &AppConfig) -> ApiResult<()> {
+    if !stripe_services::is_configured(config) {
+        return Err(ApiError::ServiceUnavailable("Subscription billing is not configured".to_string()));
+    }
+    Ok(())
+}
+
+async fn plan_by_code(pool: &PgPool, code: &str) -> ApiResult<SubscriptionPlan> {
+    sqlx::query_as::<_, SubscriptionPlan>(&format!(
+        "SELECT {} FROM subscription_plans WHERE code = $1 AND active",
+        PLAN_COLUMNS
+    ))
+    .bind(code)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound(format!("No active plan '{}'", code)))
+}
+
+async fn plan_by_id(conn: &mut PgConnection, plan_id: Uuid) -> ApiResult<SubscriptionPlan> {
+    Ok(sqlx::query_as::<_, SubscriptionPlan>(&format!("SELECT {} FROM subscription_plans WHERE id = $1", PLAN_COLUMNS))
+        .bind(plan_id)
+        .fetch_one(conn)
+        .await?)
+}
+
+/// The user's live subscription, locked for changing
+async fn lock_live_subscription(conn: &mut PgConnection, user_id: Uuid) -> ApiResult<Option<Subscription>> {
+    Ok(sqlx::query_as::<_, Subscription>(&format!(
+        "SELECT {} FROM subscriptions WHERE user_id = $1 AND status = ANY($2) FOR UPDATE",
+        SUBSCRIPTION_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(LIVE_STATUSES)
+    .fetch_optional(conn)
+    .await?)
+}
+
+async fn lock_subscription(conn: &mut PgConnection, subscription_id: Uuid) -> ApiResult<Subscription> {
+    Ok(sqlx::query_as::<_, Subscription>(&format!(
+        "SELECT {} FROM subscriptions WHERE id = $1 FOR UPDATE",
+        SUBSCRIPTION_COLUMNS
+    ))
+    .bind(subscription_id)
+    .fetch_one(conn)
+    .await?)
+}
+
+/// The user's live subscription, or else their most recent one, with its plan and any
+/// invoice still waiting to be paid
+pub async fn current_subscription(pool: &PgPool, user_id: Uuid) -> ApiResult<Option<SubscriptionDetails>> {
+    let subscription = sqlx::query_as::<_, Subscription>(&format!(
+        "SELECT {} FROM subscriptions WHERE user_id = $1 \
+         ORDER BY status = ANY($2) DESC, created_at DESC LIMIT 1",
+        SUBSCRIPTION_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(LIVE_STATUSES)
+    .fetch_optional(pool)
+    .await?;
+    let Some(subscription) = subscription else {
+        return Ok(None);
+    };
+
+    let mut conn = pool.acquire().await?;
+    let plan = plan_by_id(&mut conn, subscription.plan_id).await?;
+    let open_invoice = sqlx::query_as::<_, SubscriptionInvoice>(&format!(
+        "SELECT {} FROM subscription_invoices WHERE subscription_id = $1 AND status = 'open' \
+         ORDER BY created_at LIMIT 1",
+        INVOICE_COLUMNS
+    ))
+    .bind(subscription.id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(Some(SubscriptionDetails { subscription, plan, open_invoice }))
+}
+
+/// The user's Stripe customer, created on first use
+async fn stripe_customer(pool: &PgPool, config: &AppConfig, user_id: Uuid) -> ApiResult<String> {
+    let (email, customer): (String, Option<String>) =
+        sqlx::query_as("SELECT email, stripe_customer_id FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await?;
+    if let Some(customer) = customer {
+        return Ok(customer);
+    }
+    // Stripe's idempotency key makes concurrent first uses create one customer
+    let customer = stripe_services::create_customer(config, &email, user_id).await?;
+    Ok(sqlx::query_scalar(
+        "UPDATE users SET stripe_customer_id = COALESCE(stripe_customer_id, $2) WHERE id = $1 \
+         RETURNING stripe_customer_id",
+    )
+    .bind(user_id)
+    .bind(&customer)
+    .fetch_one(pool)
+    .await?)
+}
+
+async fn record_charge(
+    conn: &mut PgConnection,
+    transaction_id: Uuid,
+    invoice: &SubscriptionInvoice,
+    user_id: Uuid,
+    payment_intent: &str,
+) -> ApiResult<Transaction> {
+    Ok(sqlx::query_as::<_, Transaction>(&format!(
+        "INSERT INTO transactions \
+         (id, user_id, amount, currency, payment_method, payment_id, status, product_type, subscription_invoice_id) \
+         VALUES ($1, $2, $3, $4, $5, $6, 'pending', $7, $8) RETURNING {}",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(transaction_id)
+    .bind(user_id)
+    .bind(invoice.amount)
+    .bind(&invoice.currency)
+    .bind(stripe_services::PROVIDER)
+    .bind(payment_intent)
+    .bind(PRODUCT_TYPE)
+    .bind(invoice.id)
+    .fetch_one(conn)
+    .await?)
+}
+
+/// Start a subscription to a plan. It stays `incomplete` until the first invoice is paid with
+/// the returned client secret, which also saves the card for renewals.
+pub async fn subscribe(
+    pool: &PgPool,
+    config: &AppConfig,
+    user_id: Uuid,
+    plan_code: &str,
+) -> ApiResult<SubscribeResponse> {
+    require_stripe(config)?;
+    let plan = plan_by_code(pool, plan_code).await?;
+    let live_conflict = || ApiError::Conflict("You already have a subscription; change its plan instead".to_string());
+    let live: bool =
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM subscriptions WHERE user_id = $1 AND status = ANY($2))")
+            .bind(user_id)
+            .bind(LIVE_STATUSES)
+            .fetch_one(pool)
+            .await?;
+    if live {
+        return Err(live_conflict());
+    }
+
+    let customer = stripe_customer(pool, config, user_id).await?;
+    let transaction_id = Uuid::new_v4();
+    let intent = stripe_services::create_payment_intent(
+        config,
+        &customer,
+        minor_units(plan.amount, &plan.currency),
+        &plan.currency,
+        transaction_id,
+    )
+    .await?;
+
+    let mut tx = pool.begin().await?;
+    // Serializes subscribing with the live-subscription check below
+    sqlx::query("SELECT id FROM users WHERE id = $1 FOR UPDATE").bind(user_id).execute(&mut *tx).await?;
+    if lock_live_subscription(&mut tx, user_id).await?.is_some() {
+        drop(tx);
+        if let Err(e) = stripe_services::cancel_payment_intent(config, &intent.id).await {
+            tracing::warn!(payment_intent = %intent.id, "Failed to cancel unused PaymentIntent: {}", e);
+        }
+        return Err(live_conflict());
+    }
+    let now = Utc::now();
+    let subscription = sqlx::query_as::<_, Subscription>(&format!(
+        "INSERT INTO subscriptions (user_id, plan_id) VALUES ($1, $2) RETURNING {}",
+        SUBSCRIPTION_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(plan.id)
+    .fetch_one(&mut *tx)
+    .await?;
+    let invoice = sqlx::query_as::<_, SubscriptionInvoice>(&format!(
+        "INSERT INTO subscription_invoices \
+         (subscription_id, user_id, kind, amount, currency, period_start, period_end, attempt_count) \
+         VALUES ($1, $2, 'initial', $3, $4, $5, $6, 1) RETURNING {}",
+        INVOICE_COLUMNS
+    ))
+    .bind(subscription.id)
+    .bind(user_id)
+    .bind(plan.amount)
+    .bind(&plan.currency)
+    .bind(now)
+    .bind(period_end(now, &plan.billing_interval))
+    .fetch_one(&mut *tx)
+    .await?;
+    record_charge(&mut tx, transaction_id, &invoice, user_id, &intent.id).await?;
+    tx.commit().await?;
+
+    Ok(SubscribeResponse { subscription, invoice, client_secret: intent.client_secret })
+}
+
+/// Move the live subscription to another plan now. An upgrade charges the prorated difference
+/// to the saved card; a downgrade leaves credit for the next renewal.
+pub async fn change_plan(
+    pool: &PgPool,
+    config: &AppConfig,
+    user_id: Uuid,
+    plan_code: &str,
+) -> ApiResult<SubscriptionDetails> {
+    require_stripe(config)?;
+    let new_plan = plan_by_code(pool, plan_code).await?;
+
+    let mut tx = pool.begin().await?;
+    let subscription = lock_live_subscription(&mut tx, user_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("No subscription to change".to_string()))?;
+    if subscription.status != "active" {
+        return Err(ApiError::Conflict(format!(
+            "The plan of a {} subscription cannot be changed",
+            subscription.status.replace('_', " ")
+        )));
+    }
+    let current_plan = plan_by_id(&mut tx, subscription.plan_id).await?;
+    if current_plan.id == new_plan.id {
+        return Err(ApiError::Conflict(format!("You are already on {}", new_plan.name)));
+    }
+    if current_plan.currency != new_plan.currency {
+        return Err(ApiError::ValidationError("Plans billed in another currency cannot be switched to".to_string()));
+    }
+    let (Some(start), Some(end)) = (subscription.current_period_start, subscription.current_period_end) else {
+        return Err(ApiError::InternalError("Active subscription has no billing period".to_string()));
+    };
+
+    let proration = prorate(&current_plan, &new_plan, start, end, Utc::now());
+    sqlx::query(
+        "UPDATE subscriptions SET plan_id = $2, current_period_start = $3, current_period_end = $4, \
+         credit_balance = credit_balance + $5, updated_at = NOW() WHERE id = $1",
+    )
+    .bind(subscription.id)
+    .bind(new_plan.id)
+    .bind(proration.period_start)
+    .bind(proration.period_end)
+    .bind(proration.credit)
+    .execute(&mut *tx)
+    .await?;
+    let invoice = if proration.charge > Decimal::ZERO {
+        // Claimed for charging right away rather than by the job
+        Some(
+            sqlx::query_as::<_, SubscriptionInvoice>(&format!(
+                "INSERT INTO subscription_invoices \
+                 (subscription_id, user_id, kind, amount, currency, period_start, period_end, attempt_count) \
+                 VALUES ($1, $2, 'proration', $3, $4, NOW(), $5, 1) RETURNING {}",
+                INVOICE_COLUMNS
+            ))
+            .bind(subscription.id)
+            .bind(user_id)
+            .bind(proration.charge)
+            .bind(&new_plan.currency)
+            .bind(proration.period_end)
+            .fetch_one(&mut *tx)
+            .await?,
+        )
+    } else {
+        None
+    };
+    tx.commit().await?;
+
+    if let Some(invoice) = invoice {
+        charge_invoice(pool, config, &invoice).await?;
+    }
+    current_subscription(pool, user_id)
+        .await?
+        .ok_or_else(|| ApiError::InternalError("Subscription disappeared".to_string()))
+}
+
+/// Cancel the live subscription at the end of its period. One still awaiting its first
+/// payment is abandoned at once.
+pub async fn cancel(pool: &PgPool, config: &AppConfig, user_id: Uuid) -> ApiResult<Subscription> {
+    let mut tx = pool.begin().await?;
+    let subscription = lock_live_subscription(&mut tx, user_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("No subscription to cancel".to_string()))?;
+
+    if subscription.status != "incomplete" {
+        let subscription = sqlx::query_as::<_, Subscription>(&format!(
+            "UPDATE subscriptions SET cancel_at_period_end = TRUE, updated_at = NOW() WHERE id = $1 RETURNING {}",
+            SUBSCRIPTION_COLUMNS
+        ))
+        .bind(subscription.id)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        return Ok(subscription);
+    }
+
+    let subscription = sqlx::query_as::<_, Subscription>(&format!(
+        "UPDATE subscriptions SET status = 'incomplete_expired', canceled_at = NOW(), updated_at = NOW() \
+         WHERE id = $1 RETURNING {}",
+        SUBSCRIPTION_COLUMNS
+    ))
+    .bind(subscription.id)
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query("UPDATE subscription_invoices SET status = 'void' WHERE subscription_id = $1 AND status = 'open'")
+        .bind(subscription.id)
+        .execute(&mut *tx)
+        .await?;
+    let intents: Vec<String> = sqlx::query_scalar(
+        "UPDATE transactions t SET status = 'failed', failure_reason = 'Subscription abandoned' \
+         FROM subscription_invoices i \
+         WHERE t.subscription_invoice_id = i.id AND i.subscription_id = $1 AND t.status IN ('pending', 'failed') \
+         RETURNING t.payment_id",
+    )
+    .bind(subscription.id)
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    for intent in intents {
+        if let Err(e) = stripe_services::cancel_payment_intent(config, &intent).await {
+            tracing::warn!(payment_intent = %intent, "Failed to cancel abandoned PaymentIntent: {}", e);
+        }
+    }
+    Ok(subscription)
+}
+
+/// Keep a subscription that was set to cancel at the end of its period
+pub async fn resume(pool: &PgPool, user_id: Uuid) -> ApiResult<Subscription> {
+    sqlx::query_as::<_, Subscription>(&format!(
+        "UPDATE subscriptions SET cancel_at_period_end = FALSE, updated_at = NOW() \
+         WHERE user_id = $1 AND status IN ('active', 'past_due') AND cancel_at_period_end RETURNING {}",
+        SUBSCRIPTION_COLUMNS
+    ))
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("No subscription is set to cancel".to_string()))
+}
+
+/// Keep the card a subscription's first payment was made with, for charging renewals
+pub async fn save_payment_method(
+    conn: &mut PgConnection,
+    transaction: &Transaction,
+    payment_method: &str,
+) -> ApiResult<()> {
+    sqlx::query(
+        "UPDATE subscriptions s SET stripe_payment_method_id = $2, updated_at = NOW() \
+         FROM subscription_invoices i, transactions t \
+         WHERE t.id = $1 AND i.id = t.subscription_invoice_id AND s.id = i.subscription_id",
+    )
+    .bind(transaction.id)
+    .bind(payment_method)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn transaction_invoice(conn: &mut PgConnection, transaction: &Transaction) -> ApiResult<Option<Uuid>> {
+    Ok(sqlx::query_scalar("SELECT subscription_invoice_id FROM transactions WHERE id = $1")
+        .bind(transaction.id)
+        .fetch_one(conn)
+        .await?)
+}
+
+/// Settle the invoice a completed subscription charge paid for
+pub async fn invoice_paid(conn: &mut PgConnection, transaction: &Transaction) -> ApiResult<()> {
+    match transaction_invoice(conn, transaction).await? {
+        Some(invoice_id) => settle_paid(conn, invoice_id).await,
+        None => Ok(()),
+    }
+}
+
+/// Schedule a retry of the invoice a failed subscription charge was for
+pub async fn invoice_failed(conn: &mut PgConnection, transaction: &Transaction, reason: &str) -> ApiResult<()> {
+    match transaction_invoice(conn, transaction).await? {
+        Some(invoice_id) => settle_failed(conn, invoice_id, reason).await,
+        None => Ok(()),
+    }
+}
+
+async fn settle_paid(conn: &mut PgConnection, invoice_id: Uuid) -> ApiResult<()> {
+    let invoice = sqlx::query_as::<_, SubscriptionInvoice>(&format!(
+        "UPDATE subscription_invoices SET status = 'paid', paid_at = NOW(), next_attempt_at = NULL \
+         WHERE id = $1 AND status = 'open' RETURNING {}",
+        INVOICE_COLUMNS
+    ))
+    .bind(invoice_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(invoice) = invoice else {
+        tracing::warn!(%invoice_id, "Payment received for a subscription invoice that is no longer open");
+        return Ok(());
+    };
+    let subscription = lock_subscription(conn, invoice.subscription_id).await?;
+    let plan = plan_by_id(conn, subscription.plan_id).await?;
+
+    let (title, body) = match invoice.kind.as_str() {
+        "initial" => {
+            // The period starts when it is paid for, not when the subscription was requested
+            let now = Utc::now();
+            sqlx::query(
+                "UPDATE subscriptions SET status = 'active', current_period_start = $2, current_period_end = $3, \
+                 updated_at = NOW() WHERE id = $1 AND status = 'incomplete'",
+            )
+            .bind(subscription.id)
+            .bind(now)
+            .bind(period_end(now, &plan.billing_interval))
+            .execute(&mut *conn)
+            .await?;
+            ("Subscription started", format!("You are now subscribed to {}.", plan.name))
+        }
+        "renewal" => {
+            sqlx::query(
+                "UPDATE subscriptions SET status = 'active', current_period_start = $2, current_period_end = $3, \
+                 updated_at = NOW() WHERE id = $1 AND status IN ('active', 'past_due')",
+            )
+            .bind(subscription.id)
+            .bind(invoice.period_start)
+            .bind(invoice.period_end)
+            .execute(&mut *conn)
+            .await?;
+            (
+                "Subscription renewed",
+                format!("{} is renewed until {}.", plan.name, invoice.period_end.format("%B %-d, %Y")),
+            )
+        }
+        _ => {
+            sqlx::query(
+                "UPDATE subscriptions SET status = 'active', updated_at = NOW() WHERE id = $1 AND status = 'past_due' \
+                 AND NOT EXISTS (SELECT 1 FROM subscription_invoices WHERE subscription_id = $1 AND status = 'open')",
+            )
+            .bind(subscription.id)
+            .execute(&mut *conn)
+            .await?;
+            ("Plan changed", format!("You are now on {}.", plan.name))
+        }
+    };
+    sync_premium(conn, subscription.user_id).await?;
+    notify_user(
+        conn,
+        subscription.user_id,
+        "subscription_paid",
+        title,
+        &body,
+        serde_json::json!({ "subscription_id": subscription.id, "invoice_id": invoice.id }),
+    )
+    .await?;
+    Ok(())
+}
+
+/// A failed first payment can simply be retried in the app. Other invoices are retried on
+/// `RETRY_SCHEDULE_DAYS` with the subscription past due, and end it when the retries run out.
+async fn settle_failed(conn: &mut PgConnection, invoice_id: Uuid, reason: &str) -> ApiResult<()> {
+    let invoice = sqlx::query_as::<_, SubscriptionInvoice>(&format!(
+        "SELECT {} FROM subscription_invoices WHERE id = $1 AND status = 'open' FOR UPDATE",
+        INVOICE_COLUMNS
+    ))
+    .bind(invoice_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(invoice) = invoice.filter(|i| i.kind != "initial") else {
+        return Ok(());
+    };
+    let subscription = lock_subscription(conn, invoice.subscription_id).await?;
+
+    if let Some(delay) = retry_delay(invoice.attempt_count) {
+        let retry_at = Utc::now() + delay;
+        sqlx::query("UPDATE subscription_invoices SET next_attempt_at = $2 WHERE id = $1")
+            .bind(invoice.id)
+            .bind(retry_at)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(
+            "UPDATE subscriptions SET status = 'past_due', updated_at = NOW() WHERE id = $1 AND status = 'active'",
+        )
+        .bind(subscription.id)
+        .execute(&mut *conn)
+        .await?;
+        notify_user(
+            conn,
+            subscription.user_id,
+            "subscription_past_due",
+            "Subscription payment failed",
+            &format!("We could not charge your card ({}). We will try again on {}.", reason, retry_at.format("%B %-d")),
+            serde_json::json!({ "subscription_id": subscription.id, "invoice_id": invoice.id }),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    sqlx::query("UPDATE subscription_invoices SET status = 'uncollectible', next_attempt_at = NULL WHERE id = $1")
+        .bind(invoice.id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query(
+        "UPDATE subscriptions SET status = 'canceled', canceled_at = NOW(), updated_at = NOW() WHERE id = $1",
+    )
+    .bind(subscription.id)
+    .execute(&mut *conn)
+    .await?;
+    sync_premium(conn, subscription.user_id).await?;
+    notify_user(
+        conn,
+        subscription.user_id,
+        "subscription_canceled",
+        "Subscription canceled",
+        "Your subscription was canceled because its payment kept failing.",
+        serde_json::json!({ "subscription_id": subscription.id, "invoice_id": invoice.id }),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Charge a claimed invoice to the subscription's saved card. Immediate outcomes are settled
+/// here; ones Stripe is still processing are settled by its webhook.
+async fn charge_invoice(pool: &PgPool, config: &AppConfig, invoice: &SubscriptionInvoice) -> ApiResult<()> {
+    if minor_units(invoice.amount, &invoice.currency) == 0 {
+        // Covered by credit
+        let mut tx = pool.begin().await?;
+        settle_paid(&mut tx, invoice.id).await?;
+        return Ok(tx.commit().await?);
+    }
+    let (user_id, customer, payment_method): (Uuid, Option<String>, Option<String>) = sqlx::query_as(
+        "SELECT s.user_id, u.stripe_customer_id, s.stripe_payment_method_id \
+         FROM subscriptions s JOIN users u ON u.id = s.user_id WHERE s.id = $1",
+    )
+    .bind(invoice.subscription_id)
+    .fetch_one(pool)
+    .await?;
+
+    let transaction_id = Uuid::new_v4();
+    let result = match (customer, payment_method) {
+        (Some(customer), Some(payment_method)) => {
+            let amount = minor_units(invoice.amount, &invoice.currency);
+            let currency = &invoice.currency;
+            stripe_services::charge_off_session(config, &customer, &payment_method, amount, currency, transaction_id)
+                .await
+        }
+        _ => Err(ApiError::PaymentError("No card is saved for the subscription".to_string())),
+    };
+
+    let mut tx = pool.begin().await?;
+    match result {
+        Ok(intent) => {
+            let transaction = record_charge(&mut tx, transaction_id, invoice, user_id, &intent.id).await?;
+            if intent.status == "succeeded" {
+                complete_transaction(&mut tx, &transaction).await?;
+            }
+        }
+        Err(e) => settle_failed(&mut tx, invoice.id, &e.to_string()).await?,
+    }
+    Ok(tx.commit().await?)
+}
+
+/// End subscriptions set to cancel whose period is over, open renewal invoices for the rest
+/// and charge invoices that are due. Returns the number of invoices charged.
+pub async fn run_renewals(pool: &PgPool, config: &AppConfig) -> ApiResult<usize> {
+    let mut tx = pool.begin().await?;
+    let ended: Vec<(Uuid, Uuid)> = sqlx::query_as(
+        "UPDATE subscriptions SET status = 'canceled', canceled_at = NOW(), updated_at = NOW() \
+         WHERE status IN ('active', 'past_due') AND cancel_at_period_end AND current_period_end <= NOW() \
+         RETURNING id, user_id",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for (subscription_id, user_id) in ended {
+        sqlx::query(
+            "UPDATE subscription_invoices SET status = 'void', next_attempt_at = NULL \
+             WHERE subscription_id = $1 AND status = 'open'",
+        )
+        .bind(subscription_id)
+        .execute(&mut *tx)
+        .await?;
+        sync_premium(&mut tx, user_id).await?;
+        notify_user(
+            &mut tx,
+            user_id,
+            "subscription_ended",
+            "Subscription ended",
+            "Your subscription has ended as requested.",
+            serde_json::json!({ "subscription_id": subscription_id }),
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    // One renewal invoice per period; credit from downgrades comes off it first
+    sqlx::query(
+        "WITH due AS ( \
+             SELECT s.id, s.user_id, s.current_period_end, s.credit_balance, p.amount, p.currency, p.billing_interval \
+             FROM subscriptions s JOIN subscription_plans p ON p.id = s.plan_id \
+             WHERE s.status = 'active' AND NOT s.cancel_at_period_end AND s.current_period_end <= NOW() \
+             FOR UPDATE OF s SKIP LOCKED \
+         ), invoiced AS ( \
+             INSERT INTO subscription_invoices \
+             (subscription_id, user_id, kind, amount, currency, period_start, period_end, next_attempt_at) \
+             SELECT id, user_id, 'renewal', GREATEST(amount - credit_balance, 0), currency, current_period_end, \
+                    current_period_end + CASE billing_interval WHEN 'year' THEN INTERVAL '1 year' \
+                                                               ELSE INTERVAL '1 month' END, NOW() \
+             FROM due \
+             ON CONFLICT (subscription_id, period_start) WHERE kind = 'renewal' DO NOTHING \
+             RETURNING subscription_id \
+         ) \
+         UPDATE subscriptions s SET credit_balance = GREATEST(s.credit_balance - due.amount, 0), updated_at = NOW() \
+         FROM due JOIN invoiced ON invoiced.subscription_id = due.id WHERE s.id = due.id",
+    )
+    .execute(pool)
+    .await?;
+
+    if !stripe_services::is_configured(config) {
+        return Ok(0);
+    }
+    let mut charged = 0;
+    while charged < MAX_CHARGES_PER_RUN {
+        // Claimed by clearing next_attempt_at, so a charge is never made twice
+        let invoice = sqlx::query_as::<_, SubscriptionInvoice>(&format!(
+            "UPDATE subscription_invoices SET attempt_count = attempt_count + 1, next_attempt_at = NULL \
+             WHERE id = (SELECT id FROM subscription_invoices \
+                 WHERE status = 'open' AND next_attempt_at <= NOW() \
+                 ORDER BY next_attempt_at LIMIT 1 FOR UPDATE SKIP LOCKED) \
+             RETURNING {}",
+            INVOICE_COLUMNS
+        ))
+        .fetch_optional(pool)
+        .await?;
+        let Some(invoice) = invoice else {
+            break;
+        };
+        if let Err(e) = charge_invoice(pool, config, &invoice).await {
+            tracing::error!(invoice_id = %invoice.id, "Charging subscription invoice failed: {}", e);
+        }
+        charged += 1;
+    }
+    Ok(charged)
+}
+
+/// Start the background job renewing subscriptions and retrying failed charges
+pub fn spawn_renewal_job(pool: Arc<PgPool>, config: AppConfig) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(JOB_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            match run_renewals(&pool, &config).await {
+                Ok(0) => {}
+                Ok(charged) => tracing::info!(charged, "Charged subscription invoices"),
+                Err(e) => tracing::error!("Subscription renewal job failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn plan(amount: i64, billing_interval: &str) -> SubscriptionPlan {
+        SubscriptionPlan {
+            id: Uuid::new_v4(),
+            code: "plan".to_string(),
+            name: "Plan".to_string(),
+            amount: Decimal::from(amount),
+            currency: "usd".to_string(),
+            billing_interval: billing_interval.to_string(),
+            active: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_period_end() {
+        let start = Utc.with_ymd_and_hms(2026, 1, 31, 12, 0, 0).unwrap();
+        assert_eq!(period_end(start, "month"), Utc.with_ymd_and_hms(2026, 2, 28, 12, 0, 0).unwrap());
+        assert_eq!(period_end(start, "year"), Utc.with_ymd_and_hms(2027, 1, 31, 12, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), Some(Duration::days(1)));
+        assert_eq!(retry_delay(3), Some(Duration::days(5)));
+        assert_eq!(retry_delay(4), None);
+        assert_eq!(retry_delay(0), None);
+    }
+
+    #[test]
+    fn test_prorate() {
+        let start = Utc.with_ymd_and_hms(2026, 4, 1, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2026, 5, 1, 0, 0, 0).unwrap();
+        let halfway = Utc.with_ymd_and_hms(2026, 4, 16, 0, 0, 0).unwrap();
+
+        // Upgrade halfway: pay half the difference, period kept
+        let upgrade = prorate(&plan(10, "month"), &plan(20, "month"), start, end, halfway);
+        let (five, zero) = (Decimal::from(5), Decimal::ZERO);
+        assert_eq!(upgrade, Proration { charge: five, credit: zero, period_start: start, period_end: end });
+
+        // Downgrade halfway: half the difference is credited
+        let downgrade = prorate(&plan(20, "month"), &plan(10, "month"), start, end, halfway);
+        assert_eq!((downgrade.charge, downgrade.credit), (zero, five));
+
+        // A new interval starts a new period, less what is left of the old one
+        let yearly = prorate(&plan(10, "month"), &plan(100, "year"), start, end, halfway);
+        assert_eq!(yearly.charge, Decimal::from(95));
+        assert_eq!((yearly.period_start, yearly.period_end), (halfway, period_end(halfway, "year")));
+
+        // Nothing left of the period: nothing is owed either way
+        let late = prorate(&plan(10, "month"), &plan(20, "month"), start, end, end);
+        assert_eq!((late.charge, late.credit), (zero, zero));
+    }
+
+    #[test]
+    fn test_validate_plan() {
+        let price = Decimal::new(999, 2);
+        assert!(validate_plan("pro_monthly", "Pro", price, "usd", "month").is_ok());
+        assert!(validate_plan("Pro Monthly", "Pro", price, "usd", "month").is_err());
+        assert!(validate_plan("pro", "Pro", Decimal::ZERO, "usd", "month").is_err());
+        assert!(validate_plan("pro", "Pro", price, "USD", "month").is_err());
+        assert!(validate_plan("pro", "Pro", price, "usd", "week").is_err());
+    }
+}
@@ -0,0 +1,570 @@
+//! Support tickets. A ticket's SLA targets come from the opener's support tier when it is
+//! opened: premium users get the priority tier. Staff replies count as the first response and
+//! put the ticket back in the customer's court; customer replies reopen it. A background job
+//! reports missed targets to the assignee once.
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{PgConnection, PgPool};
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::support::{
+    CreateTicketRequest, SupportAttachment, SupportMessage, SupportTicket, SupportTicketDetails, TicketQueueQuery,
+};
+use crate::services::notification_services::notify_user;
+use crate::services::object_storage_services::ObjectStore;
+
+pub const TICKET_COLUMNS: &str = "id, user_id, subject, category, priority, status, support_tier, device_id, \
+     incident_id, transaction_id, assigned_to, first_response_due_at, resolution_due_at, first_responded_at, \
+     resolved_at, first_response_breached_at, resolution_breached_at, created_at, updated_at";
+
+pub const MESSAGE_COLUMNS: &str = "id, ticket_id, author_id, from_staff, body, created_at";
+
+pub const ATTACHMENT_COLUMNS: &str =
+    "id, ticket_id, message_id, uploaded_by, file_name, content_type, size_bytes, object_key, created_at";
+
+pub const CATEGORIES: &[&str] = &["general", "device", "billing", "account", "incident"];
+pub const PRIORITIES: &[&str] = &["low", "normal", "high", "urgent"];
+pub const STATUSES: &[&str] = &["open", "waiting_on_customer", "resolved", "closed"];
+/// Statuses of tickets still in the staff queue
+pub const OPEN_STATUSES: &[&str] = &["open", "waiting_on_customer"];
+
+const MAX_SUBJECT_CHARS: usize = 200;
+const MAX_BODY_CHARS: usize = 10_000;
+pub const MAX_OPEN_TICKETS_PER_USER: i64 = 20;
+pub const MAX_ATTACHMENT_BYTES: usize = 10 * 1024 * 1024;
+pub const MAX_ATTACHMENTS_PER_TICKET: i64 = 20;
+/// Types accepted for attachments; `image/*` and `text/*` are accepted too
+const ATTACHMENT_TYPES: &[&str] = &["application/pdf", "application/json", "application/zip", "application/gzip"];
+/// How long a download link stays valid
+pub const DOWNLOAD_URL_TTL_SECS: u32 = 300;
+
+const JOB_INTERVAL_SECS: u64 = 300;
+
+/// Hours to the first staff response and to resolution
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlaTargets {
+    pub first_response_hours: i64,
+    pub resolution_hours: i64,
+}
+
+/// Support tier of a user by plan
+pub fn support_tier(is_premium: bool) -> &'static str {
+    if is_premium { "priority" } else { "standard" }
+}
+
+pub fn sla_targets(tier: &str) -> SlaTargets {
+    match tier {
+        "priority" => SlaTargets { first_response_hours: 4, resolution_hours: 48 },
+        _ => SlaTargets { first_response_hours: 48, resolution_hours: 168 },
+    }
+}
+
+/// Which targets the ticket has missed by `now` that are not reported yet: (first response, resolution)
+pub fn new_breaches(ticket: &SupportTicket, now: DateTime<Utc>) -> (bool, bool) {
+    let open = OPEN_STATUSES.contains(&ticket.status.as_str());
+    let first_response = open
+        && ticket.first_responded_at.is_none()
+        && ticket.first_response_breached_at.is_none()
+        && ticket.first_response_due_at <= now;
+    let resolution = open && ticket.resolution_breached_at.is_none() && ticket.resolution_due_at <= now;
+    (first_response, resolution)
+}
+
+fn one_of(field: &str, value: &str, allowed: &[&str]) -> ApiResult<()> {
+    if allowed.contains(&value) {
+        Ok(())
+    } else {
+        Err(ApiError::ValidationError(format!("{} must be one of {}", field, allowed.join(", "))))
+    }
+}
+
+pub fn validate_category(category: &str) -> ApiResult<()> {
+    one_of("category", category, CATEGORIES)
+}
+
+pub fn validate_priority(priority: &str) -> ApiResult<()> {
+    one_of("priority", priority, PRIORITIES)
+}
+
+pub fn validate_status(status: &str) -> ApiResult<()> {
+    one_of("status", status, STATUSES)
+}
+
+pub fn validate_subject(subject: &str) -> ApiResult<&str> {
+    let subject = subject.trim();
+    if subject.is_empty() || subject.chars().count() > MAX_SUBJECT_CHARS {
+        return Err(ApiError::ValidationError(format!("subject must be 1-{} characters", MAX_SUBJECT_CHARS)));
+    }
+    Ok(subject)
+}
+
+pub fn validate_body(body: &str) -> ApiResult<&str> {
+    let body = body.trim();
+    if body.is_empty() || body.chars().count() > MAX_BODY_CHARS {
+        return Err(ApiError::ValidationError(format!("body must be 1-{} characters", MAX_BODY_CHARS)));
+    }
+    Ok(body)
+}
+
+/// A file name safe to store and to offer as a download name
+pub fn sanitize_file_name(name: &str) -> ApiResult<String> {
+    // Keep only the last path segment of names some clients send with a path
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    let clean: String = base
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | ' ') { c } else { '_' })
+        .take(255)
+        .collect();
+    if clean.is_empty() || clean.chars().all(|c| c == '.') {
+        return Err(ApiError::ValidationError("file_name is required".to_string()));
+    }
+    Ok(clean)
+}
+
+pub fn validate_content_type(content_type: &str) -> ApiResult<()> {
+    let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    if essence.starts_with("image/") || essence.starts_with("text/") || ATTACHMENT_TYPES.contains(&essence.as_str()) {
+        return Ok(());
+    }
+    Err(ApiError::ValidationError(format!(
+        "Attachments must be images, text, or one of {}",
+        ATTACHMENT_TYPES.join(", ")
+    )))
+}
+
+pub fn attachment_key(ticket_id: Uuid, attachment_id: Uuid, file_name: &str) -> String {
+    format!("support/{}/{}/{}", ticket_id, attachment_id, file_name)
+}
+
+/// Refuse links to a device, incident or transaction the user does not own
+async fn validate_links(conn: &mut PgConnection, user_id: Uuid, request: &CreateTicketRequest) -> ApiResult<()> {
+    let links = [
+        ("device", "devices", request.device_id),
+        ("incident", "incidents", request.incident_id),
+        ("transaction", "transactions", request.transaction_id),
+    ];
+    for (what, table, id) in links {
+        let Some(id) = id else {
+            continue;
+        };
+        let exists: bool =
+            sqlx::query_scalar(&format!("SELECT EXISTS (SELECT 1 FROM {} WHERE id = $1 AND user_id = $2)", table))
+                .bind(id)
+                .bind(user_id)
+                .fetch_one(&mut *conn)
+                .await?;
+        if !exists {
+            return Err(ApiError::ValidationError(format!("{}_id does not name one of your {}", what, table)));
+        }
+    }
+    Ok(())
+}
+
+/// Open a ticket with its first message
+pub async fn create_ticket(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    request: &CreateTicketRequest,
+) -> ApiResult<SupportTicket> {
+    let subject = validate_subject(&request.subject)?;
+    let body = validate_body(&request.body)?;
+    let category = request.category.as_deref().unwrap_or("general");
+    validate_category(category)?;
+    let priority = request.priority.as_deref().unwrap_or("normal");
+    validate_priority(priority)?;
+    validate_links(&mut *conn, user_id, request).await?;
+
+    let (is_premium, open): (bool, i64) = sqlx::query_as(
+        "SELECT u.is_premium, (SELECT COUNT(*) FROM support_tickets t WHERE t.user_id = u.id \
+         AND t.status = ANY($2)) FROM users u WHERE u.id = $1",
+    )
+    .bind(user_id)
+    .bind(OPEN_STATUSES)
+    .fetch_one(&mut *conn)
+    .await?;
+    if open >= MAX_OPEN_TICKETS_PER_USER {
+        return Err(ApiError::ValidationError(format!(
+            "You can have at most {} open tickets",
+            MAX_OPEN_TICKETS_PER_USER
+        )));
+    }
+
+    let tier = support_tier(is_premium);
+    let targets = sla_targets(tier);
+    let now = Utc::now();
+    let ticket = sqlx::query_as::<_, SupportTicket>(&format!(
+        "INSERT INTO support_tickets (user_id, subject, category, priority, support_tier, device_id, incident_id, \
+         transaction_id, first_response_due_at, resolution_due_at) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) RETURNING {}",
+        TICKET_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(subject)
+    .bind(category)
+    .bind(priority)
+    .bind(tier)
+    .bind(request.device_id)
+    .bind(request.incident_id)
+    .bind(request.transaction_id)
+    .bind(now + Duration::hours(targets.first_response_hours))
+    .bind(now + Duration::hours(targets.resolution_hours))
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO support_ticket_messages (ticket_id, author_id, from_staff, body) VALUES ($1, $2, FALSE, $3)",
+    )
+    .bind(ticket.id)
+    .bind(user_id)
+    .bind(body)
+    .execute(&mut *conn)
+    .await?;
+    Ok(ticket)
+}
+
+/// The user's own ticket
+pub async fn owned_ticket(pool: &PgPool, ticket_id: Uuid, user_id: Uuid) -> ApiResult<SupportTicket> {
+    sqlx::query_as::<_, SupportTicket>(&format!(
+        "SELECT {} FROM support_tickets WHERE id = $1 AND user_id = $2",
+        TICKET_COLUMNS
+    ))
+    .bind(ticket_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Ticket not found".to_string()))
+}
+
+/// Any ticket, for staff
+pub async fn get_ticket(pool: &PgPool, ticket_id: Uuid) -> ApiResult<SupportTicket> {
+    sqlx::query_as::<_, SupportTicket>(&format!("SELECT {} FROM support_tickets WHERE id = $1", TICKET_COLUMNS))
+        .bind(ticket_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Ticket not found".to_string()))
+}
+
+/// The ticket with its messages and attachments, with download links when storage is configured
+pub async fn details(
+    pool: &PgPool,
+    store: Option<&ObjectStore>,
+    ticket: SupportTicket,
+) -> ApiResult<SupportTicketDetails> {
+    let messages = sqlx::query_as::<_, SupportMessage>(&format!(
+        "SELECT {} FROM support_ticket_messages WHERE ticket_id = $1 ORDER BY created_at",
+        MESSAGE_COLUMNS
+    ))
+    .bind(ticket.id)
+    .fetch_all(pool)
+    .await?;
+    let mut attachments = sqlx::query_as::<_, SupportAttachment>(&format!(
+        "SELECT {} FROM support_ticket_attachments WHERE ticket_id = $1 ORDER BY created_at",
+        ATTACHMENT_COLUMNS
+    ))
+    .bind(ticket.id)
+    .fetch_all(pool)
+    .await?;
+    if let Some(store) = store {
+        for attachment in &mut attachments {
+            attachment.download_url = Some(store.presigned_get_url(&attachment.object_key, DOWNLOAD_URL_TTL_SECS)?);
+        }
+    }
+    Ok(SupportTicketDetails { ticket, messages, attachments })
+}
+
+/// Add a reply and move the ticket along: a staff reply is the first response if there was
+/// none and waits on the customer; a customer reply reopens the ticket. Closed tickets take no
+/// replies. The other side is notified.
+pub async fn add_message(
+    conn: &mut PgConnection,
+    ticket: &SupportTicket,
+    author_id: Uuid,
+    from_staff: bool,
+    body: &str,
+) -> ApiResult<SupportMessage> {
+    let body = validate_body(body)?;
+    if ticket.status == "closed" {
+        return Err(ApiError::Conflict("Ticket is closed; open a new one".to_string()));
+    }
+
+    let message = sqlx::query_as::<_, SupportMessage>(&format!(
+        "INSERT INTO support_ticket_messages (ticket_id, author_id, from_staff, body) VALUES ($1, $2, $3, $4) \
+         RETURNING {}",
+        MESSAGE_COLUMNS
+    ))
+    .bind(ticket.id)
+    .bind(author_id)
+    .bind(from_staff)
+    .bind(body)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if from_staff {
+        sqlx::query(
+            "UPDATE support_tickets SET first_responded_at = COALESCE(first_responded_at, NOW()), \
+             status = CASE WHEN status = 'open' THEN 'waiting_on_customer' ELSE status END, updated_at = NOW() \
+             WHERE id = $1",
+        )
+        .bind(ticket.id)
+        .execute(&mut *conn)
+        .await?;
+        notify_user(
+            conn,
+            ticket.user_id,
+            "support.reply",
+            &format!("Support replied: {}", ticket.subject),
+            body,
+            serde_json::json!({ "ticket_id": ticket.id }),
+        )
+        .await?;
+    } else {
+        sqlx::query(
+            "UPDATE support_tickets SET status = 'open', resolved_at = NULL, updated_at = NOW() WHERE id = $1",
+        )
+        .bind(ticket.id)
+        .execute(&mut *conn)
+        .await?;
+        if let Some(assignee) = ticket.assigned_to {
+            notify_user(
+                conn,
+                assignee,
+                "support.customer_reply",
+                &format!("Customer replied: {}", ticket.subject),
+                body,
+                serde_json::json!({ "ticket_id": ticket.id }),
+            )
+            .await?;
+        }
+    }
+    Ok(message)
+}
+
+/// Store a file and post it to the ticket as a message of its own. The object is written
+/// before the rows, so a failed upload leaves no attachment pointing at nothing.
+#[allow(clippy::too_many_arguments)]
+pub async fn add_attachment(
+    pool: &PgPool,
+    store: &ObjectStore,
+    ticket: &SupportTicket,
+    author_id: Uuid,
+    from_staff: bool,
+    file_name: &str,
+    content_type: &str,
+    data: Vec<u8>,
+    note: Option<&str>,
+) -> ApiResult<SupportAttachment> {
+    let file_name = sanitize_file_name(file_name)?;
+    validate_content_type(content_type)?;
+    if data.is_empty() || data.len() > MAX_ATTACHMENT_BYTES {
+        return Err(ApiError::ValidationError(format!("Attachments must be 1-{} bytes", MAX_ATTACHMENT_BYTES)));
+    }
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM support_ticket_attachments WHERE ticket_id = $1")
+        .bind(ticket.id)
+        .fetch_one(pool)
+        .await?;
+    if count >= MAX_ATTACHMENTS_PER_TICKET {
+        return Err(ApiError::ValidationError(format!(
+            "A ticket can have at most {} attachments",
+            MAX_ATTACHMENTS_PER_TICKET
+        )));
+    }
+
+    let attachment_id = Uuid::new_v4();
+    let key = attachment_key(ticket.id, attachment_id, &file_name);
+    let size = data.len() as i64;
+    store.put_object(&key, data, content_type).await?;
+
+    let note = note.map(str::to_string).unwrap_or_else(|| format!("Attached {}", file_name));
+    let mut tx = pool.begin().await?;
+    let message = add_message(&mut tx, ticket, author_id, from_staff, &note).await?;
+    let attachment = sqlx::query_as::<_, SupportAttachment>(&format!(
+        "INSERT INTO support_ticket_attachments \
+         (id, ticket_id, message_id, uploaded_by, file_name, content_type, size_bytes, object_key) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING {}",
+        ATTACHMENT_COLUMNS
+    ))
+    .bind(attachment_id)
+    .bind(ticket.id)
+    .bind(message.id)
+    .bind(author_id)
+    .bind(&file_name)
+    .bind(content_type)
+    .bind(size)
+    .bind(&key)
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(attachment)
+}
+
+/// Change a ticket's status; resolving records when
+pub async fn set_status(conn: &mut PgConnection, ticket_id: Uuid, status: &str) -> ApiResult<SupportTicket> {
+    validate_status(status)?;
+    let ticket = sqlx::query_as::<_, SupportTicket>(&format!(
+        "UPDATE support_tickets SET status = $2, \
+         resolved_at = CASE WHEN $2 IN ('resolved', 'closed') THEN COALESCE(resolved_at, NOW()) ELSE NULL END, \
+         updated_at = NOW() WHERE id = $1 RETURNING {}",
+        TICKET_COLUMNS
+    ))
+    .bind(ticket_id)
+    .bind(status)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(ticket)
+}
+
+/// The staff queue, soonest resolution due first
+pub async fn queue(pool: &PgPool, query: &TicketQueueQuery) -> ApiResult<Vec<SupportTicket>> {
+    let statuses: Vec<&str> = match query.status.as_deref() {
+        Some(status) => {
+            validate_status(status)?;
+            vec![status]
+        }
+        None => OPEN_STATUSES.to_vec(),
+    };
+    let tickets = sqlx::query_as::<_, SupportTicket>(&format!(
+        "SELECT {} FROM support_tickets WHERE status = ANY($1) \
+         AND ($2::uuid IS NULL OR assigned_to = $2) AND (NOT $3 OR assigned_to IS NULL) \
+         AND (NOT $4 OR first_response_breached_at IS NOT NULL OR resolution_breached_at IS NOT NULL) \
+         ORDER BY resolution_due_at LIMIT $5",
+        TICKET_COLUMNS
+    ))
+    .bind(statuses)
+    .bind(query.assigned_to)
+    .bind(query.unassigned)
+    .bind(query.breached)
+    .bind(query.limit.unwrap_or(50).clamp(1, 200))
+    .fetch_all(pool)
+    .await?;
+    Ok(tickets)
+}
+
+/// Record and report SLA targets missed since the last run; returns how many were reported
+pub async fn check_sla(pool: &PgPool) -> ApiResult<usize> {
+    let overdue = sqlx::query_as::<_, SupportTicket>(&format!(
+        "SELECT {} FROM support_tickets WHERE status = ANY($1) AND ( \
+            (first_responded_at IS NULL AND first_response_breached_at IS NULL AND first_response_due_at <= NOW()) \
+            OR (resolution_breached_at IS NULL AND resolution_due_at <= NOW())) \
+         ORDER BY resolution_due_at LIMIT 200",
+        TICKET_COLUMNS
+    ))
+    .bind(OPEN_STATUSES)
+    .fetch_all(pool)
+    .await?;
+
+    let now = Utc::now();
+    let mut reported = 0;
+    for ticket in overdue {
+        let (first_response, resolution) = new_breaches(&ticket, now);
+        if !first_response && !resolution {
+            continue;
+        }
+        let mut tx = pool.begin().await?;
+        sqlx::query(
+            "UPDATE support_tickets SET \
+             first_response_breached_at = CASE WHEN $2 THEN NOW() ELSE first_response_breached_at END, \
+             resolution_breached_at = CASE WHEN $3 THEN NOW() ELSE resolution_breached_at END WHERE id = $1",
+        )
+        .bind(ticket.id)
+        .bind(first_response)
+        .bind(resolution)
+        .execute(&mut *tx)
+        .await?;
+        if let Some(assignee) = ticket.assigned_to {
+            let target = if first_response { "first response" } else { "resolution" };
+            notify_user(
+                &mut tx,
+                assignee,
+                "support.sla_breached",
+                &format!("SLA missed: {}", ticket.subject),
+                &format!("The {} target of this {} ticket has passed.", target, ticket.support_tier),
+                serde_json::json!({ "ticket_id": ticket.id, "target": target }),
+            )
+            .await?;
+        }
+        tx.commit().await?;
+        reported += 1;
+    }
+    Ok(reported)
+}
+
+pub fn spawn_sla_job(pool: Arc<PgPool>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(JOB_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            match check_sla(&pool).await {
+                Ok(0) => {}
+                Ok(reported) => tracing::info!(reported, "Reported missed support SLAs"),
+                Err(e) => tracing::error!("Support SLA job failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticket(status: &str, opened: DateTime<Utc>, tier: &str) -> SupportTicket {
+        let targets = sla_targets(tier);
+        SupportTicket {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            subject: "Arm will not calibrate".to_string(),
+            category: "device".to_string(),
+            priority: "normal".to_string(),
+            status: status.to_string(),
+            support_tier: tier.to_string(),
+            device_id: None,
+            incident_id: None,
+            transaction_id: None,
+            assigned_to: None,
+            first_response_due_at: opened + Duration::hours(targets.first_response_hours),
+            resolution_due_at: opened + Duration::hours(targets.resolution_hours),
+            first_responded_at: None,
+            resolved_at: None,
+            first_response_breached_at: None,
+            resolution_breached_at: None,
+            created_at: opened,
+            updated_at: opened,
+        }
+    }
+
+    #[test]
+    fn test_new_breaches() {
+        let now = Utc::now();
+        assert_eq!(support_tier(true), "priority");
+
+        // Priority tickets owe a response within 4 hours
+        assert_eq!(new_breaches(&ticket("open", now - Duration::hours(3), "priority"), now), (false, false));
+        assert_eq!(new_breaches(&ticket("open", now - Duration::hours(5), "priority"), now), (true, false));
+        assert_eq!(new_breaches(&ticket("open", now - Duration::hours(5), "standard"), now), (false, false));
+
+        let mut answered = ticket("waiting_on_customer", now - Duration::hours(50), "priority");
+        answered.first_responded_at = Some(now - Duration::hours(49));
+        assert_eq!(new_breaches(&answered, now), (false, true));
+        answered.resolution_breached_at = Some(now);
+        assert_eq!(new_breaches(&answered, now), (false, false));
+
+        assert_eq!(new_breaches(&ticket("resolved", now - Duration::days(30), "standard"), now), (false, false));
+    }
+
+    #[test]
+    fn test_sanitize_file_name() {
+        assert_eq!(sanitize_file_name("C:\\logs\\robot log.txt").unwrap(), "robot log.txt");
+        assert_eq!(sanitize_file_name("../../etc/passwd").unwrap(), "passwd");
+        assert_eq!(sanitize_file_name("café?.png").unwrap(), "caf__.png");
+        assert!(sanitize_file_name("..").is_err());
+        assert!(sanitize_file_name("dir/").is_err());
+    }
+
+    #[test]
+    fn test_validate_content_type() {
+        assert!(validate_content_type("image/png").is_ok());
+        assert!(validate_content_type("text/plain; charset=utf-8").is_ok());
+        assert!(validate_content_type("application/pdf").is_ok());
+        assert!(validate_content_type("application/x-msdownload").is_err());
+    }
+}
@@ -0,0 +1,404 @@
+//! Swarm planning: split one fleet-level command into non-overlapping per-device missions
+
+use chrono::Utc;
+use serde::Serialize;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::device::PathPoint;
+use crate::models::swarm::GeoBounds;
+use crate::services::path_services::{distance_m, max_speed_mps, MAX_PATH_POINTS};
+
+/// Largest fleet a single swarm command may address
+pub const MAX_SWARM_SIZE: usize = 50;
+
+/// Longest side of a survey area (meters)
+pub const MAX_SURVEY_SIDE_M: f64 = 20_000.0;
+
+/// Vertical gap between drone transit layers (meters)
+pub const TRANSIT_ALTITUDE_SEPARATION_M: f64 = 5.0;
+
+/// Devices dispatched to at once during a fan-out unless the caller asks for fewer
+pub const DEFAULT_FANOUT_PARALLELISM: usize = 8;
+
+/// Upper bound on concurrent per-device dispatches in one fan-out
+pub const MAX_FANOUT_PARALLELISM: usize = 32;
+
+/// Extra wait after another device clears a crossing transit leg (ms)
+const CROSSING_MARGIN_MS: u64 = 5_000;
+
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// A swarm member with its last known position
+#[derive(Debug, Clone)]
+pub struct SwarmDevice {
+    pub id: Uuid,
+    pub device_type: String,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// A device's straight-line trip from its position to the start of its region
+struct Transit {
+    from: (f64, f64),
+    to: (f64, f64),
+    duration_ms: u64,
+    airborne: bool,
+}
+
+/// One device's share of a swarm command
+#[derive(Debug, Clone)]
+pub struct DeviceMission {
+    pub device_id: Uuid,
+    pub region: GeoBounds,
+    /// Full route: current position, transit to the region, then coverage lanes
+    pub points: Vec<PathPoint>,
+    pub transit_altitude: Option<f64>,
+    pub start_offset_ms: u64,
+}
+
+pub fn validate_area(area: &GeoBounds) -> ApiResult<()> {
+    if !(-90.0..=90.0).contains(&area.min_lat)
+        || !(-90.0..=90.0).contains(&area.max_lat)
+        || !(-180.0..=180.0).contains(&area.min_lng)
+        || !(-180.0..=180.0).contains(&area.max_lng)
+    {
+        return Err(ApiError::ValidationError("Survey area is out of range".to_string()));
+    }
+    if area.min_lat >= area.max_lat || area.min_lng >= area.max_lng {
+        return Err(ApiError::ValidationError("Survey area min must be below max".to_string()));
+    }
+    let mid_lat = (area.min_lat + area.max_lat) / 2.0;
+    let height = (area.max_lat - area.min_lat) * METERS_PER_DEGREE;
+    let width = (area.max_lng - area.min_lng) * METERS_PER_DEGREE * mid_lat.to_radians().cos();
+    if height > MAX_SURVEY_SIDE_M || width > MAX_SURVEY_SIDE_M {
+        return Err(ApiError::ValidationError(format!(
+            "Survey area sides must be at most {} m",
+            MAX_SURVEY_SIDE_M
+        )));
+    }
+    Ok(())
+}
+
+/// Split an area into `n` equal west-to-east strips that share only their edges
+pub fn split_strips(area: &GeoBounds, n: usize) -> Vec<GeoBounds> {
+    let width = (area.max_lng - area.min_lng) / n as f64;
+    (0..n)
+        .map(|i| GeoBounds {
+            min_lng: area.min_lng + width * i as f64,
+            max_lng: if i + 1 == n { area.max_lng } else { area.min_lng + width * (i + 1) as f64 },
+            ..*area
+        })
+        .collect()
+}
+
+/// Boustrophedon (lawnmower) coverage of a strip with north-south lanes.
+/// Starts from the south edge unless `from_north`.
+pub fn lawnmower(strip: &GeoBounds, lane_spacing_m: f64, altitude: Option<f64>, from_north: bool) -> Vec<PathPoint> {
+    let mid_lat = (strip.min_lat + strip.max_lat) / 2.0;
+    let lng_step = lane_spacing_m / (METERS_PER_DEGREE * mid_lat.to_radians().cos().max(1e-6));
+    let width = strip.max_lng - strip.min_lng;
+    let lanes = ((width / lng_step).floor() as usize).max(1);
+    // Centre the lanes within the strip
+    let offset = (width - lng_step * (lanes - 1) as f64) / 2.0;
+
+    let now = Utc::now();
+    let point = |latitude: f64, longitude: f64| PathPoint { latitude, longitude, altitude, recorded_at: now };
+
+    let mut points = Vec::with_capacity(lanes * 2);
+    for lane in 0..lanes {
+        let lng = strip.min_lng + offset + lng_step * lane as f64;
+        let northbound = (lane % 2 == 0) != from_north;
+        let (start, end) = if northbound { (strip.min_lat, strip.max_lat) } else { (strip.max_lat, strip.min_lat) };
+        points.push(point(start, lng));
+        points.push(point(end, lng));
+    }
+    points
+}
+
+/// Whether segments p1-p2 and q1-q2 cross (treating lat/lng as a plane, fine at survey scale)
+pub fn segments_intersect(p1: (f64, f64), p2: (f64, f64), q1: (f64, f64), q2: (f64, f64)) -> bool {
+    fn orient(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+        (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+    }
+    let d1 = orient(q1, q2, p1);
+    let d2 = orient(q1, q2, p2);
+    let d3 = orient(p1, p2, q1);
+    let d4 = orient(p1, p2, q2);
+    (d1 * d2 < 0.0) && (d3 * d4 < 0.0)
+}
+
+/// Plan a coverage survey: each device gets its own strip of the area, transits to it
+/// (drones on separate altitude layers), and ground devices whose transit legs cross
+/// are staggered so only one is on the crossing at a time.
+pub fn plan_survey(
+    devices: &[SwarmDevice],
+    area: &GeoBounds,
+    lane_spacing_m: f64,
+    altitude: f64,
+    speed: f64,
+) -> ApiResult<Vec<DeviceMission>> {
+    validate_area(area)?;
+    if devices.is_empty() || devices.len() > MAX_SWARM_SIZE {
+        return Err(ApiError::ValidationError(format!("A swarm needs 1-{} devices", MAX_SWARM_SIZE)));
+    }
+    if !(5.0..=500.0).contains(&lane_spacing_m) {
+        return Err(ApiError::ValidationError("lane_spacing_m must be between 5 and 500".to_string()));
+    }
+    if !(2.0..=120.0).contains(&altitude) {
+        return Err(ApiError::ValidationError("Survey altitude must be between 2 and 120 m".to_string()));
+    }
+    if !(0.05..=1.0).contains(&speed) {
+        return Err(ApiError::ValidationError("Speed must be between 0.05 and 1.0".to_string()));
+    }
+
+    // Assigning strips in west-to-east device order keeps transit legs from fanning across each other
+    let mut ordered: Vec<&SwarmDevice> = devices.iter().collect();
+    ordered.sort_by(|a, b| a.longitude.total_cmp(&b.longitude));
+    let strips = split_strips(area, ordered.len());
+
+    let now = Utc::now();
+    let mut missions: Vec<DeviceMission> = Vec::with_capacity(ordered.len());
+    let mut transits: Vec<Transit> = Vec::new();
+    let mut drone_layer = 0;
+
+    for (device, strip) in ordered.into_iter().zip(strips) {
+        let airborne = device.device_type == "drone";
+        let survey_altitude = airborne.then_some(altitude);
+        let transit_altitude = airborne.then(|| {
+            drone_layer += 1;
+            altitude + TRANSIT_ALTITUDE_SEPARATION_M * drone_layer as f64
+        });
+
+        let from_north = (device.latitude - strip.max_lat).abs() < (device.latitude - strip.min_lat).abs();
+        let lanes = lawnmower(&strip, lane_spacing_m, survey_altitude, from_north);
+
+        let origin = PathPoint {
+            latitude: device.latitude,
+            longitude: device.longitude,
+            altitude: transit_altitude,
+            recorded_at: now,
+        };
+        let entry = PathPoint { altitude: transit_altitude, ..lanes[0].clone() };
+        let transit_ms = (distance_m(&origin, &entry) / (max_speed_mps(&device.device_type) * speed) * 1000.0) as u64;
+
+        let from = (device.latitude, device.longitude);
+        let to = (entry.latitude, entry.longitude);
+        // Drones are separated vertically; everything else waits its turn at a crossing
+        let start_offset_ms = transits
+            .iter()
+            .zip(missions.iter())
+            .filter(|(other, _)| !(airborne && other.airborne) && segments_intersect(from, to, other.from, other.to))
+            .map(|(other, earlier)| earlier.start_offset_ms + other.duration_ms + CROSSING_MARGIN_MS)
+            .max()
+            .unwrap_or(0);
+        transits.push(Transit { from, to, duration_ms: transit_ms, airborne });
+
+        let mut points = vec![origin, entry];
+        points.extend(lanes);
+        if points.len() > MAX_PATH_POINTS {
+            return Err(ApiError::ValidationError(
+                "Survey is too detailed for this fleet; increase lane_spacing_m".to_string(),
+            ));
+        }
+
+        missions.push(DeviceMission {
+            device_id: device.id,
+            region: strip,
+            points,
+            transit_altitude,
+            start_offset_ms,
+        });
+    }
+
+    Ok(missions)
+}
+
+/// What happened to one device during a fan-out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FanOutStatus {
+    /// Command queued for the device
+    Dispatched,
+    /// Device can't take the command (offline, unsupported, bad parameters)
+    Rejected,
+    /// Queueing the command failed
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceOutcome {
+    pub device_id: Uuid,
+    pub status: FanOutStatus,
+    pub command_id: Option<Uuid>,
+    pub error: Option<String>,
+    /// Stop command queued when the fan-out was rolled back
+    pub compensation_command_id: Option<Uuid>,
+}
+
+/// Structured result of a fan-out
+#[derive(Debug, Clone, Serialize)]
+pub struct FanOutReport {
+    pub mission_id: Uuid,
+    pub command: String,
+    pub targeted: usize,
+    pub dispatched: usize,
+    pub min_success: usize,
+    pub threshold_met: bool,
+    /// Dispatched devices were sent a stop because the threshold wasn't met
+    pub compensated: bool,
+    pub outcomes: Vec<DeviceOutcome>,
+}
+
+impl FanOutReport {
+    pub fn new(mission_id: Uuid, command: &str, min_success: usize, mut outcomes: Vec<DeviceOutcome>) -> Self {
+        outcomes.sort_by_key(|o| o.device_id);
+        let dispatched = outcomes.iter().filter(|o| o.status == FanOutStatus::Dispatched).count();
+        Self {
+            mission_id,
+            command: command.to_string(),
+            targeted: outcomes.len(),
+            dispatched,
+            min_success,
+            threshold_met: dispatched >= min_success,
+            compensated: false,
+            outcomes,
+        }
+    }
+}
+
+pub fn fanout_parallelism(requested: Option<usize>) -> ApiResult<usize> {
+    let parallelism = requested.unwrap_or(DEFAULT_FANOUT_PARALLELISM);
+    if !(1..=MAX_FANOUT_PARALLELISM).contains(&parallelism) {
+        return Err(ApiError::ValidationError(format!(
+            "max_parallel must be between 1 and {}",
+            MAX_FANOUT_PARALLELISM
+        )));
+    }
+    Ok(parallelism)
+}
+
+/// "At least N must succeed"; defaults to every targeted device
+pub fn resolve_min_success(requested: Option<usize>, targeted: usize) -> ApiResult<usize> {
+    let min_success = requested.unwrap_or(targeted);
+    if min_success == 0 || min_success > targeted {
+        return Err(ApiError::ValidationError(format!(
+            "min_success must be between 1 and the {} targeted devices",
+            targeted
+        )));
+    }
+    Ok(min_success)
+}
+
+/// The command that halts a device in place: ground devices stop, drones hold their last
+/// known altitude (or land when it isn't known)
+pub fn stop_command(device_type: &str, last_altitude: Option<f64>) -> (&'static str, serde_json::Value) {
+    match (device_type, last_altitude) {
+        ("drone", Some(altitude)) if altitude > 0.0 => ("hover", serde_json::json!({ "altitude": altitude })),
+        ("drone", _) => ("land", serde_json::json!({})),
+        _ => ("stop", serde_json::json!({})),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area() -> GeoBounds {
+        GeoBounds { min_lat: 12.0, min_lng: 77.0, max_lat: 12.005, max_lng: 77.006 }
+    }
+
+    fn device(device_type: &str, latitude: f64, longitude: f64) -> SwarmDevice {
+        SwarmDevice { id: Uuid::new_v4(), device_type: device_type.to_string(), latitude, longitude }
+    }
+
+    #[test]
+    fn test_split_strips_do_not_overlap() {
+        let strips = split_strips(&area(), 3);
+        assert_eq!(strips.len(), 3);
+        assert_eq!(strips[0].min_lng, 77.0);
+        assert_eq!(strips[2].max_lng, 77.006);
+        for pair in strips.windows(2) {
+            assert_eq!(pair[0].max_lng, pair[1].min_lng);
+        }
+    }
+
+    #[test]
+    fn test_lawnmower_stays_inside_strip() {
+        let strip = split_strips(&area(), 2)[0];
+        let points = lawnmower(&strip, 50.0, Some(30.0), false);
+        assert!(points.len() >= 4);
+        assert_eq!(points[0].latitude, strip.min_lat);
+        assert!(points.iter().all(|p| p.longitude > strip.min_lng && p.longitude < strip.max_lng));
+
+        let reversed = lawnmower(&strip, 50.0, None, true);
+        assert_eq!(reversed[0].latitude, strip.max_lat);
+    }
+
+    #[test]
+    fn test_plan_survey_assigns_strips_west_to_east() {
+        let east = device("drone", 11.99, 77.01);
+        let west = device("drone", 11.99, 76.99);
+        let missions = plan_survey(&[east.clone(), west.clone()], &area(), 50.0, 30.0, 0.5).unwrap();
+
+        assert_eq!(missions[0].device_id, west.id);
+        assert_eq!(missions[1].device_id, east.id);
+        assert!(missions[0].region.max_lng <= missions[1].region.min_lng);
+        // Drones transit on distinct layers
+        assert_ne!(missions[0].transit_altitude, missions[1].transit_altitude);
+        assert!(missions.iter().all(|m| m.start_offset_ms == 0));
+    }
+
+    #[test]
+    fn test_crossing_ground_transits_are_staggered() {
+        // The western rover starts far north and drives down across the eastern rover's route
+        let a = device("rover", 12.02, 76.999);
+        let b = device("rover", 12.006, 76.9991);
+        let missions = plan_survey(&[a, b], &area(), 50.0, 30.0, 0.5).unwrap();
+        assert_eq!(missions[0].start_offset_ms, 0);
+        assert!(missions[1].start_offset_ms > 0);
+    }
+
+    #[test]
+    fn test_segments_intersect() {
+        assert!(segments_intersect((0.0, 0.0), (1.0, 1.0), (0.0, 1.0), (1.0, 0.0)));
+        assert!(!segments_intersect((0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)));
+    }
+
+    #[test]
+    fn test_validate_area() {
+        assert!(validate_area(&area()).is_ok());
+        assert!(validate_area(&GeoBounds { min_lat: 1.0, min_lng: 1.0, max_lat: 0.0, max_lng: 2.0 }).is_err());
+        assert!(validate_area(&GeoBounds { min_lat: 0.0, min_lng: 0.0, max_lat: 1.0, max_lng: 1.0 }).is_err());
+    }
+
+    fn outcome(status: FanOutStatus) -> DeviceOutcome {
+        DeviceOutcome { device_id: Uuid::new_v4(), status, command_id: None, error: None, compensation_command_id: None }
+    }
+
+    #[test]
+    fn test_fanout_threshold() {
+        let outcomes = vec![
+            outcome(FanOutStatus::Dispatched),
+            outcome(FanOutStatus::Rejected),
+            outcome(FanOutStatus::Dispatched),
+            outcome(FanOutStatus::Failed),
+        ];
+        let report = FanOutReport::new(Uuid::new_v4(), "stop", 2, outcomes.clone());
+        assert_eq!((report.targeted, report.dispatched), (4, 2));
+        assert!(report.threshold_met);
+        assert!(!FanOutReport::new(Uuid::new_v4(), "stop", 3, outcomes).threshold_met);
+
+        assert_eq!(resolve_min_success(None, 5).unwrap(), 5);
+        assert!(resolve_min_success(Some(0), 5).is_err());
+        assert!(resolve_min_success(Some(6), 5).is_err());
+        assert_eq!(fanout_parallelism(None).unwrap(), DEFAULT_FANOUT_PARALLELISM);
+        assert!(fanout_parallelism(Some(MAX_FANOUT_PARALLELISM + 1)).is_err());
+    }
+
+    #[test]
+    fn test_stop_command() {
+        assert_eq!(stop_command("rover", None).0, "stop");
+        assert_eq!(stop_command("drone", Some(40.0)), ("hover", serde_json::json!({ "altitude": 40.0 })));
+        assert_eq!(stop_command("drone", None).0, "land");
+    }
+}
@@ -0,0 +1,474 @@
+//! Template marketplace: automations, fleet missions and command macros published with
+//! `${name}` placeholders, imported by filling them in.
+//!
+//! Device ids in published content are always replaced by placeholders, so a template never
+//! carries the publisher's fleet. Imports are recorded with the content hash they were made
+//! from, and a template published from an imported item points back at its parent.
+
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use std::collections::{BTreeSet, HashMap};
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::automation::RuleGraph;
+use crate::models::mission::{CreateLegRequest, LegAction};
+use crate::models::template::{Placeholder, Template};
+use crate::services::automation_services::{self, check_graph, command_targets};
+use crate::services::mission_services::{self, validate_legs};
+use crate::services::promotion_services::{self, mission_legs, owned_macro, validate_macro_steps};
+use crate::utils::crypto::sha256_hash;
+
+pub const TEMPLATE_KINDS: &[&str] = &["automation", "fleet_mission", "macro"];
+
+pub const PLACEHOLDER_KINDS: &[&str] = &["device", "string", "number", "boolean"];
+
+pub const TEMPLATE_COLUMNS: &str = "id, publisher_id, kind, name, description, content, placeholders, content_sha256, \
+     source_id, source_version, parent_template_id, status, moderation_flags, moderation_note, \
+     rating_total::float8 / NULLIF(rating_count, 0) AS rating, rating_count, import_count, created_at";
+
+pub const MAX_PLACEHOLDERS: usize = 20;
+
+/// Open reports that take a template off the marketplace until an admin reviews it
+pub const REPORT_HIDE_THRESHOLD: i64 = 3;
+
+/// What an import produced
+#[derive(Debug, Serialize)]
+pub struct Instantiated {
+    pub target_kind: &'static str,
+    pub target_id: Uuid,
+    pub created: serde_json::Value,
+}
+
+/// One template in a provenance chain, starting with the template asked about
+#[derive(Debug, Serialize, FromRow)]
+pub struct ProvenanceEntry {
+    pub id: Uuid,
+    pub name: String,
+    pub publisher_id: Option<Uuid>,
+    pub content_sha256: String,
+    pub status: String,
+    pub depth: i32,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+pub fn validate_kind(kind: &str) -> ApiResult<()> {
+    if !TEMPLATE_KINDS.contains(&kind) {
+        return Err(ApiError::ValidationError(format!(
+            "kind must be one of {}",
+            TEMPLATE_KINDS.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+fn valid_placeholder_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 40 && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Placeholder names used anywhere in the content's strings
+pub fn placeholders_in(content: &serde_json::Value) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    walk_strings(content, &mut |s| {
+        let mut rest = s;
+        while let Some(start) = rest.find("${") {
+            let Some(len) = rest[start + 2..].find('}') else {
+                break;
+            };
+            names.insert(rest[start + 2..start + 2 + len].to_string());
+            rest = &rest[start + 2 + len + 1..];
+        }
+    });
+    names
+}
+
+fn walk_strings(value: &serde_json::Value, f: &mut impl FnMut(&str)) {
+    match value {
+        serde_json::Value::String(s) => f(s),
+        serde_json::Value::Array(items) => items.iter().for_each(|v| walk_strings(v, f)),
+        serde_json::Value::Object(map) => map.values().for_each(|v| walk_strings(v, f)),
+        _ => {}
+    }
+}
+
+fn map_strings(value: &serde_json::Value, f: &mut impl FnMut(&str) -> serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::String(s) => f(s),
+        serde_json::Value::Array(items) => items.iter().map(|v| map_strings(v, f)).collect(),
+        serde_json::Value::Object(map) => {
+            map.iter().map(|(k, v)| (k.clone(), map_strings(v, f))).collect::<serde_json::Map<_, _>>().into()
+        }
+        other => other.clone(),
+    }
+}
+
+/// Replace the publisher's device ids with `${device_1}`, `${device_2}`, ... in order of appearance
+pub fn templatize_devices(content: &serde_json::Value, device_ids: &[Uuid]) -> (serde_json::Value, Vec<Placeholder>) {
+    let ids: Vec<String> = device_ids.iter().map(Uuid::to_string).collect();
+    let mut assigned: Vec<String> = Vec::new();
+    let content = map_strings(content, &mut |s| {
+        if !ids.iter().any(|id| id == s) {
+            return serde_json::Value::String(s.to_string());
+        }
+        let index = match assigned.iter().position(|a| a == s) {
+            Some(i) => i,
+            None => {
+                assigned.push(s.to_string());
+                assigned.len() - 1
+            }
+        };
+        serde_json::Value::String(format!("${{device_{}}}", index + 1))
+    });
+    let placeholders = (1..=assigned.len())
+        .map(|n| Placeholder {
+            name: format!("device_{}", n),
+            kind: "device".to_string(),
+            description: Some(format!("Device #{} in the original", n)),
+            default: None,
+        })
+        .collect();
+    (content, placeholders)
+}
+
+/// Declared placeholders must match the ones used in the content exactly
+pub fn validate_placeholders(content: &serde_json::Value, placeholders: &[Placeholder]) -> ApiResult<()> {
+    if placeholders.len() > MAX_PLACEHOLDERS {
+        return Err(ApiError::ValidationError(format!("At most {} placeholders", MAX_PLACEHOLDERS)));
+    }
+    let mut declared = BTreeSet::new();
+    for p in placeholders {
+        if !valid_placeholder_name(&p.name) {
+            return Err(ApiError::ValidationError(format!(
+                "Placeholder '{}' must be 1-40 lowercase letters, digits or '_'",
+                p.name
+            )));
+        }
+        if !PLACEHOLDER_KINDS.contains(&p.kind.as_str()) {
+            return Err(ApiError::ValidationError(format!(
+                "Placeholder kind must be one of {}",
+                PLACEHOLDER_KINDS.join(", ")
+            )));
+        }
+        if p.kind == "device" && p.default.is_some() {
+            return Err(ApiError::ValidationError("Device placeholders cannot have a default".to_string()));
+        }
+        if let Some(default) = &p.default {
+            check_value(p, default)?;
+        }
+        if !declared.insert(p.name.clone()) {
+            return Err(ApiError::ValidationError(format!("Duplicate placeholder '{}'", p.name)));
+        }
+    }
+    let used = placeholders_in(content);
+    if let Some(name) = used.difference(&declared).next() {
+        return Err(ApiError::ValidationError(format!("Placeholder '{}' is used but not declared", name)));
+    }
+    if let Some(name) = declared.difference(&used).next() {
+        return Err(ApiError::ValidationError(format!("Placeholder '{}' is declared but never used", name)));
+    }
+    Ok(())
+}
+
+fn check_value(placeholder: &Placeholder, value: &serde_json::Value) -> ApiResult<()> {
+    let ok = match placeholder.kind.as_str() {
+        "device" => value.as_str().is_some_and(|s| Uuid::parse_str(s).is_ok()),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        _ => false,
+    };
+    if !ok {
+        return Err(ApiError::ValidationError(format!(
+            "Value for '{}' must be a {}",
+            placeholder.name, placeholder.kind
+        )));
+    }
+    Ok(())
+}
+
+/// The importer's values, falling back to defaults, checked against each placeholder's kind
+pub fn resolve_values(
+    placeholders: &[Placeholder],
+    provided: &HashMap<String, serde_json::Value>,
+) -> ApiResult<HashMap<String, serde_json::Value>> {
+    if let Some(unknown) = provided.keys().find(|k| !placeholders.iter().any(|p| &p.name == *k)) {
+        return Err(ApiError::ValidationError(format!("Unknown placeholder '{}'", unknown)));
+    }
+    placeholders
+        .iter()
+        .map(|p| {
+            let value = provided
+                .get(&p.name)
+                .or(p.default.as_ref())
+                .ok_or_else(|| ApiError::ValidationError(format!("A value for '{}' is required", p.name)))?;
+            check_value(p, value)?;
+            Ok((p.name.clone(), value.clone()))
+        })
+        .collect()
+}
+
+/// Fill placeholders. A string that is exactly `${name}` takes the value as-is (so numbers stay
+/// numbers); placeholders inside longer strings are spliced in as text.
+pub fn fill(content: &serde_json::Value, values: &HashMap<String, serde_json::Value>) -> serde_json::Value {
+    map_strings(content, &mut |s| {
+        if let Some(name) = s.strip_prefix("${").and_then(|r| r.strip_suffix('}'))
+            && let Some(value) = values.get(name)
+        {
+            return value.clone();
+        }
+        let mut out = s.to_string();
+        for (name, value) in values {
+            let text = match value {
+                serde_json::Value::String(v) => v.clone(),
+                other => other.to_string(),
+            };
+            out = out.replace(&format!("${{{}}}", name), &text);
+        }
+        serde_json::Value::String(out)
+    })
+}
+
+/// Moderation hook run on publish; any flag holds the template for admin review.
+/// Automations that call external URLs can ship importers' event data anywhere.
+pub fn screen(kind: &str, content: &serde_json::Value) -> Vec<String> {
+    let mut flags = Vec::new();
+    if kind == "automation"
+        && let Some(nodes) = content.pointer("/graph/nodes").and_then(|n| n.as_array())
+        && nodes.iter().any(|n| n.pointer("/action/type").and_then(|t| t.as_str()) == Some("call_webhook"))
+    {
+        flags.push("external_webhook".to_string());
+    }
+    flags
+}
+
+pub fn content_sha256(content: &serde_json::Value) -> String {
+    sha256_hash(content.to_string().as_bytes())
+}
+
+/// Content of an item the user owns, the devices it refers to and its version (automations)
+pub async fn source_content(
+    pool: &PgPool,
+    user_id: Uuid,
+    kind: &str,
+    source_id: Uuid,
+) -> ApiResult<(serde_json::Value, Vec<Uuid>, Option<i32>)> {
+    match kind {
+        "automation" => {
+            let version: i32 =
+                sqlx::query_scalar("SELECT current_version FROM automations WHERE id = $1 AND user_id = $2")
+                    .bind(source_id)
+                    .bind(user_id)
+                    .fetch_optional(pool)
+                    .await?
+                    .ok_or_else(|| ApiError::NotFound("Automation not found".to_string()))?;
+            let graph = automation_services::load_graph(pool, source_id, version).await?;
+            let devices = command_targets(&graph);
+            Ok((serde_json::json!({ "graph": graph }), devices, Some(version)))
+        }
+        "macro" => {
+            let command_macro = owned_macro(pool, source_id, user_id).await?;
+            Ok((
+                serde_json::json!({ "device_type": command_macro.device_type, "steps": command_macro.steps }),
+                Vec::new(),
+                None,
+            ))
+        }
+        "fleet_mission" => {
+            let (_, legs) = mission_legs(pool, source_id, user_id).await?;
+            let devices = legs.iter().map(|l| l.device_id).collect();
+            let legs: Vec<serde_json::Value> = legs
+                .iter()
+                .map(|l| {
+                    serde_json::json!({
+                        "device_id": l.device_id,
+                        "target": l.target,
+                        "pickup_actions": l.pickup_actions,
+                        "actions": l.actions,
+                        "speed": l.speed,
+                    })
+                })
+                .collect();
+            Ok((serde_json::json!({ "legs": legs }), devices, None))
+        }
+        _ => Err(ApiError::ValidationError("Unknown template kind".to_string())),
+    }
+}
+
+/// The template the user imported `target_id` from, if any
+pub async fn imported_from(pool: &PgPool, user_id: Uuid, target_id: Uuid) -> ApiResult<Option<Uuid>> {
+    let parent = sqlx::query_scalar(
+        "SELECT template_id FROM template_imports WHERE user_id = $1 AND target_id = $2 \
+         ORDER BY imported_at DESC LIMIT 1",
+    )
+    .bind(user_id)
+    .bind(target_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(parent)
+}
+
+fn parse<T: serde::de::DeserializeOwned>(value: Option<&serde_json::Value>, what: &str) -> ApiResult<T> {
+    serde_json::from_value(value.cloned().unwrap_or_default())
+        .map_err(|e| ApiError::ValidationError(format!("Template {} is invalid once filled in: {}", what, e)))
+}
+
+/// Create the user's own item from filled-in content, through the same checks as creating it
+/// directly. Imported automations start disabled so they can be reviewed and dry-run first;
+/// imported fleet missions start like new ones.
+pub async fn instantiate(
+    pool: &PgPool,
+    user_id: Uuid,
+    kind: &str,
+    name: &str,
+    content: &serde_json::Value,
+) -> ApiResult<Instantiated> {
+    match kind {
+        "automation" => {
+            let graph: RuleGraph = parse(content.get("graph"), "graph")?;
+            check_graph(pool, user_id, &graph).await?;
+            let (automation, hook_token) =
+                automation_services::create_automation(pool, user_id, name, &graph, false).await?;
+            Ok(Instantiated {
+                target_kind: "automation",
+                target_id: automation.id,
+                created: serde_json::json!({ "automation": automation, "graph": graph, "hook_token": hook_token }),
+            })
+        }
+        "macro" => {
+            let device_type: String = parse(content.get("device_type"), "device_type")?;
+            let steps: Vec<LegAction> = parse(content.get("steps"), "steps")?;
+            validate_macro_steps(&device_type, &steps)?;
+            let command_macro = promotion_services::insert_macro(pool, user_id, name, &device_type, &steps).await?;
+            Ok(Instantiated {
+                target_kind: "macro",
+                target_id: command_macro.id,
+                created: serde_json::json!(command_macro),
+            })
+        }
+        "fleet_mission" => {
+            let legs: Vec<CreateLegRequest> = parse(content.get("legs"), "legs")?;
+            validate_legs(&legs)?;
+            mission_services::check_leg_devices(pool, user_id, &legs).await?;
+            let mission = mission_services::insert_mission(pool, user_id, name, &legs).await?;
+            let readiness = mission_services::try_advance(pool, mission.id, user_id).await?;
+            Ok(Instantiated {
+                target_kind: "fleet_mission",
+                target_id: mission.id,
+                created: serde_json::json!({ "mission": mission, "readiness": readiness }),
+            })
+        }
+        _ => Err(ApiError::ValidationError("Unknown template kind".to_string())),
+    }
+}
+
+/// Walk parent links back to the original template
+pub async fn provenance(pool: &PgPool, template_id: Uuid) -> ApiResult<Vec<ProvenanceEntry>> {
+    let chain = sqlx::query_as::<_, ProvenanceEntry>(
+        "WITH RECURSIVE chain AS ( \
+             SELECT id, name, publisher_id, content_sha256, status, parent_template_id, 0 AS depth, created_at \
+             FROM templates WHERE id = $1 \
+             UNION ALL \
+             SELECT t.id, t.name, t.publisher_id, t.content_sha256, t.status, t.parent_template_id, c.depth + 1, \
+                    t.created_at \
+             FROM templates t JOIN chain c ON t.id = c.parent_template_id WHERE c.depth < 20) \
+         SELECT id, name, publisher_id, content_sha256, status, depth, created_at FROM chain ORDER BY depth",
+    )
+    .bind(template_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(chain)
+}
+
+/// A template the user may see: published ones, their own, or any for admins
+pub async fn visible_template(pool: &PgPool, template_id: Uuid, user_id: Uuid, is_admin: bool) -> ApiResult<Template> {
+    sqlx::query_as::<_, Template>(&format!(
+        "SELECT {} FROM templates WHERE id = $1 AND (status = 'published' OR publisher_id = $2 OR $3)",
+        TEMPLATE_COLUMNS
+    ))
+    .bind(template_id)
+    .bind(user_id)
+    .bind(is_admin)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Template not found".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholder(name: &str, kind: &str, default: Option<serde_json::Value>) -> Placeholder {
+        Placeholder { name: name.to_string(), kind: kind.to_string(), description: None, default }
+    }
+
+    #[test]
+    fn test_templatize_devices() {
+        let a = Uuid::new_v4();
+        let b = Uuid::new_v4();
+        let content = serde_json::json!({ "legs": [
+            { "device_id": a, "speed": 0.5 },
+            { "device_id": b, "speed": 0.5 },
+            { "device_id": a, "speed": 0.5 },
+        ] });
+        let (templated, placeholders) = templatize_devices(&content, &[a, b]);
+        assert_eq!(templated["legs"][0]["device_id"], "${device_1}");
+        assert_eq!(templated["legs"][1]["device_id"], "${device_2}");
+        assert_eq!(templated["legs"][2]["device_id"], "${device_1}");
+        assert_eq!(placeholders.len(), 2);
+        assert!(!templated.to_string().contains(&a.to_string()));
+    }
+
+    #[test]
+    fn test_fill_keeps_types() {
+        let device = Uuid::new_v4();
+        let content = serde_json::json!({
+            "device_id": "${device_1}",
+            "speed": "${speed}",
+            "title": "Patrol at ${speed}x",
+        });
+        let values = HashMap::from([
+            ("device_1".to_string(), serde_json::json!(device.to_string())),
+            ("speed".to_string(), serde_json::json!(0.8)),
+        ]);
+        let filled = fill(&content, &values);
+        assert_eq!(filled["device_id"], serde_json::json!(device.to_string()));
+        assert_eq!(filled["speed"], serde_json::json!(0.8));
+        assert_eq!(filled["title"], "Patrol at 0.8x");
+    }
+
+    #[test]
+    fn test_validate_placeholders() {
+        let content = serde_json::json!({ "a": "${speed}", "b": "${device_1}" });
+        let declared = vec![placeholder("speed", "number", Some(serde_json::json!(0.5))), placeholder("device_1", "device", None)];
+        assert!(validate_placeholders(&content, &declared).is_ok());
+        assert!(validate_placeholders(&content, &declared[..1]).is_err());
+        let unused = [declared.clone(), vec![placeholder("extra", "string", None)]].concat();
+        assert!(validate_placeholders(&content, &unused).is_err());
+        let bad_default = vec![placeholder("speed", "number", Some(serde_json::json!("fast"))), declared[1].clone()];
+        assert!(validate_placeholders(&content, &bad_default).is_err());
+    }
+
+    #[test]
+    fn test_resolve_values() {
+        let declared = vec![placeholder("speed", "number", Some(serde_json::json!(0.5))), placeholder("device_1", "device", None)];
+        let device = serde_json::json!(Uuid::new_v4().to_string());
+
+        let values = resolve_values(&declared, &HashMap::from([("device_1".to_string(), device.clone())])).unwrap();
+        assert_eq!(values["speed"], serde_json::json!(0.5));
+        assert!(resolve_values(&declared, &HashMap::new()).is_err());
+        assert!(resolve_values(&declared, &HashMap::from([("device_1".to_string(), serde_json::json!("nope"))])).is_err());
+        assert!(resolve_values(
+            &declared,
+            &HashMap::from([("device_1".to_string(), device), ("other".to_string(), serde_json::json!(1))])
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_screen_flags_external_calls() {
+        let content = serde_json::json!({ "graph": { "nodes": [
+            { "id": "a", "kind": "action", "action": { "type": "call_webhook", "url": "https://example.com" } },
+        ] } });
+        assert_eq!(screen("automation", &content), ["external_webhook"]);
+        assert!(screen("macro", &serde_json::json!({ "steps": [] })).is_empty());
+    }
+}
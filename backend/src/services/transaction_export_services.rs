@@ -0,0 +1,160 @@
+//! Streaming CSV export of a user's transactions. Rows are read a page at a time with a keyset
+//! cursor, so the whole history is never held in memory.
+
+use actix_web::web::Bytes;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, Stream};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::transaction::Transaction;
+use crate::services::payment_services::TRANSACTION_COLUMNS;
+
+/// Rows fetched, and sent as one chunk, per query
+const PAGE_SIZE: i64 = 500;
+
+pub const CSV_HEADERS: &[&str] = &[
+    "id", "created_at", "amount", "currency", "payment_method", "payment_id", "status", "product_type",
+    "chain_id", "blockchain_tx_hash", "block_number", "confirmations",
+];
+
+fn csv_row(t: &Transaction) -> Vec<String> {
+    vec![
+        t.id.to_string(),
+        t.created_at.to_rfc3339(),
+        t.amount.to_string(),
+        t.currency.clone(),
+        t.payment_method.clone(),
+        t.payment_id.clone(),
+        t.status.clone(),
+        t.product_type.clone(),
+        t.chain_id.map(|id| id.to_string()).unwrap_or_default(),
+        t.blockchain_tx_hash.clone().unwrap_or_default(),
+        t.block_number.map(|n| n.to_string()).unwrap_or_default(),
+        t.confirmations.to_string(),
+    ]
+}
+
+/// Render one page of transactions as CSV, led by the header line on the first page
+fn render_page(rows: &[Transaction], header: bool) -> ApiResult<Bytes> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let csv_error = |e: csv::Error| ApiError::InternalError(format!("CSV export failed: {}", e));
+
+    if header {
+        writer.write_record(CSV_HEADERS).map_err(csv_error)?;
+    }
+    for row in rows {
+        writer.write_record(csv_row(row)).map_err(csv_error)?;
+    }
+
+    writer
+        .into_inner()
+        .map(Bytes::from)
+        .map_err(|e| ApiError::InternalError(format!("CSV export failed: {}", e)))
+}
+
+struct ExportCursor {
+    pool: Arc<PgPool>,
+    user_id: Uuid,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    /// (created_at, id) of the last row sent
+    after: Option<(DateTime<Utc>, Uuid)>,
+    header: bool,
+    done: bool,
+}
+
+async fn next_page(cursor: &ExportCursor) -> ApiResult<Vec<Transaction>> {
+    let (after_at, after_id) = cursor.after.unzip();
+    let rows = sqlx::query_as::<_, Transaction>(&format!(
+        "SELECT {} FROM transactions \
+         WHERE user_id = $1 \
+           AND ($2::timestamptz IS NULL OR created_at >= $2) \
+           AND ($3::timestamptz IS NULL OR created_at < $3) \
+           AND ($4::timestamptz IS NULL OR (created_at, id) > ($4, $5)) \
+         ORDER BY created_at, id LIMIT $6",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(cursor.user_id)
+    .bind(cursor.from)
+    .bind(cursor.to)
+    .bind(after_at)
+    .bind(after_id)
+    .bind(PAGE_SIZE)
+    .fetch_all(cursor.pool.as_ref())
+    .await?;
+    Ok(rows)
+}
+
+/// The user's transactions created in `[from, to)`, oldest first, as a stream of CSV chunks.
+/// A failed query ends the stream with its error.
+pub fn export_csv(
+    pool: Arc<PgPool>,
+    user_id: Uuid,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> impl Stream<Item = ApiResult<Bytes>> {
+    let cursor = ExportCursor { pool, user_id, from, to, after: None, header: true, done: false };
+    stream::unfold(cursor, |mut cursor| async move {
+        if cursor.done {
+            return None;
+        }
+        let rows = match next_page(&cursor).await {
+            Ok(rows) => rows,
+            Err(e) => {
+                cursor.done = true;
+                return Some((Err(e), cursor));
+            }
+        };
+        cursor.done = (rows.len() as i64) < PAGE_SIZE;
+        cursor.after = rows.last().map(|t| (t.created_at, t.id)).or(cursor.after);
+        let header = std::mem::take(&mut cursor.header);
+        Some((render_page(&rows, header), cursor))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    fn transaction() -> Transaction {
+        Transaction {
+            id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            amount: Decimal::new(125, 1),
+            currency: "RBV".to_string(),
+            payment_method: "crypto".to_string(),
+            payment_id: "pay, \"one\"".to_string(),
+            status: "completed".to_string(),
+            product_type: "software_license".to_string(),
+            blockchain_tx_hash: Some("0xabc".to_string()),
+            chain_id: Some(137),
+            confirmations: 12,
+            block_number: None,
+            coupon_id: None,
+            discount_amount: Decimal::ZERO,
+            created_at: DateTime::parse_from_rfc3339("2026-01-02T03:04:05Z").unwrap().with_timezone(&Utc),
+        }
+    }
+
+    #[test]
+    fn test_render_page() {
+        let first = render_page(&[transaction()], true).unwrap();
+        let text = std::str::from_utf8(&first).unwrap();
+        let mut lines = text.lines();
+        assert_eq!(lines.next().unwrap(), CSV_HEADERS.join(","));
+        assert_eq!(
+            lines.next().unwrap(),
+            "00000000-0000-0000-0000-000000000000,2026-01-02T03:04:05+00:00,12.5,RBV,crypto,\"pay, \"\"one\"\"\",\
+             completed,software_license,137,0xabc,,12"
+        );
+        assert_eq!(lines.next(), None);
+
+        // Later pages continue the file without repeating the header
+        let next = render_page(&[transaction()], false).unwrap();
+        assert_eq!(std::str::from_utf8(&next).unwrap().lines().count(), 1);
+        assert!(render_page(&[], false).unwrap().is_empty());
+    }
+}
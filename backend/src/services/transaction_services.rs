@@ -0,0 +1,222 @@
+//! A user's transaction history, paged newest first with keyset cursors: `before` continues
+//! past the oldest transaction of a page and `after` goes back towards newer ones. Unlike an
+//! offset, a cursor stays put while new transactions arrive, and every page costs the same
+//! index scan on (user_id, created_at, id).
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::transaction::{Transaction, TransactionListQuery, TransactionPage};
+use crate::services::payment_services::TRANSACTION_COLUMNS;
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// Which way a page reads from its cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    /// `before`: older than the cursor
+    Older,
+    /// `after`: newer than the cursor
+    Newer,
+}
+
+/// The page size of a list query, and its cursor transaction and direction when paging
+fn page_params(query: &TransactionListQuery) -> ApiResult<(i64, Option<(Uuid, Direction)>)> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let cursor = match (query.before, query.after) {
+        (Some(_), Some(_)) => {
+            return Err(ApiError::ValidationError("Pass either before or after, not both".to_string()));
+        }
+        (Some(id), None) => Some((id, Direction::Older)),
+        (None, Some(id)) => Some((id, Direction::Newer)),
+        (None, None) => None,
+    };
+    Ok((limit, cursor))
+}
+
+/// Rows strictly past the cursor `($2, $3)` in (created_at, id) order, so transactions created
+/// in the same instant are split by id and never repeated or skipped. Paging back reads upwards
+/// from the cursor. Binds the user, the cursor's created_at and id, and the row limit.
+fn page_sql(direction: Direction) -> String {
+    let (comparison, order) = match direction {
+        Direction::Older => ("<", "DESC"),
+        Direction::Newer => (">", "ASC"),
+    };
+    format!(
+        "SELECT {} FROM transactions \
+         WHERE user_id = $1 AND ($2::timestamptz IS NULL OR (created_at, id) {} ($2, $3)) \
+         ORDER BY created_at {}, id {} LIMIT $4",
+        TRANSACTION_COLUMNS, comparison, order, order
+    )
+}
+
+/// Cut the page from rows fetched one past `limit`, whose extra row shows there are more, and
+/// restore newest-first order
+fn finish_page<T>(mut rows: Vec<T>, limit: i64, direction: Direction) -> (Vec<T>, bool) {
+    let has_more = rows.len() as i64 > limit;
+    rows.truncate(limit as usize);
+    if direction == Direction::Newer {
+        rows.reverse();
+    }
+    (rows, has_more)
+}
+
+/// One page of the user's transactions
+pub async fn list(pool: &PgPool, user_id: Uuid, query: &TransactionListQuery) -> ApiResult<TransactionPage> {
+    let (limit, cursor) = page_params(query)?;
+    let direction = cursor.map_or(Direction::Older, |(_, direction)| direction);
+
+    let position = match cursor {
+        Some((id, _)) => Some(
+            sqlx::query_as::<_, (DateTime<Utc>, Uuid)>(
+                "SELECT created_at, id FROM transactions WHERE id = $1 AND user_id = $2",
+            )
+            .bind(id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| ApiError::ValidationError("Unknown transaction cursor".to_string()))?,
+        ),
+        None => None,
+    };
+    let (cursor_at, cursor_id) = position.unzip();
+
+    let rows = sqlx::query_as::<_, Transaction>(&page_sql(direction))
+        .bind(user_id)
+        .bind(cursor_at)
+        .bind(cursor_id)
+        .bind(limit + 1)
+        .fetch_all(pool)
+        .await?;
+    let (transactions, has_more) = finish_page(rows, limit, direction);
+
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM transactions WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(TransactionPage { transactions, total, has_more })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    type Key = (DateTime<Utc>, Uuid);
+
+    fn query(before: Option<Uuid>, after: Option<Uuid>, limit: Option<i64>) -> TransactionListQuery {
+        TransactionListQuery { before, after, limit }
+    }
+
+    /// What the database returns for `page_sql`: rows past the cursor in its order, limit + 1
+    fn fetch(rows: &[Key], cursor: Option<Key>, direction: Direction, limit: i64) -> Vec<Key> {
+        let mut past: Vec<Key> = rows
+            .iter()
+            .copied()
+            .filter(|key| match (cursor, direction) {
+                (None, _) => true,
+                (Some(cursor), Direction::Older) => *key < cursor,
+                (Some(cursor), Direction::Newer) => *key > cursor,
+            })
+            .collect();
+        past.sort();
+        if direction == Direction::Older {
+            past.reverse();
+        }
+        past.truncate(limit as usize + 1);
+        past
+    }
+
+    /// Five transactions, three created in the same instant
+    fn history() -> Vec<Key> {
+        let at = |secs| Utc.timestamp_opt(1_760_000_000 + secs, 0).unwrap();
+        vec![
+            (at(0), Uuid::from_u128(1)),
+            (at(10), Uuid::from_u128(7)),
+            (at(10), Uuid::from_u128(3)),
+            (at(10), Uuid::from_u128(5)),
+            (at(20), Uuid::from_u128(2)),
+        ]
+    }
+
+    #[test]
+    fn test_page_params() {
+        let id = Uuid::from_u128(9);
+        assert_eq!(page_params(&query(None, None, None)).unwrap(), (DEFAULT_PAGE_SIZE, None));
+        assert_eq!(page_params(&query(Some(id), None, Some(5))).unwrap(), (5, Some((id, Direction::Older))));
+        assert_eq!(page_params(&query(None, Some(id), Some(0))).unwrap(), (1, Some((id, Direction::Newer))));
+        assert_eq!(page_params(&query(None, None, Some(1_000))).unwrap().0, MAX_PAGE_SIZE);
+        assert!(page_params(&query(Some(id), Some(id), None)).is_err());
+    }
+
+    #[test]
+    fn test_page_sql() {
+        let older = page_sql(Direction::Older);
+        assert!(older.contains("(created_at, id) < ($2, $3)"));
+        assert!(older.contains("ORDER BY created_at DESC, id DESC LIMIT $4"));
+        let newer = page_sql(Direction::Newer);
+        assert!(newer.contains("(created_at, id) > ($2, $3)"));
+        assert!(newer.contains("ORDER BY created_at ASC, id ASC LIMIT $4"));
+    }
+
+    #[test]
+    fn test_finish_page_boundaries() {
+        // Empty page
+        let (page, has_more) = finish_page(Vec::<u8>::new(), 3, Direction::Older);
+        assert!(page.is_empty());
+        assert!(!has_more);
+
+        // Exactly a page: nothing past it
+        let (page, has_more) = finish_page(vec![3, 2, 1], 3, Direction::Older);
+        assert_eq!(page, vec![3, 2, 1]);
+        assert!(!has_more);
+
+        // One row past the page
+        let (page, has_more) = finish_page(vec![4, 3, 2, 1], 3, Direction::Older);
+        assert_eq!(page, vec![4, 3, 2]);
+        assert!(has_more);
+
+        // Paging back comes up oldest first and is returned newest first
+        let (page, has_more) = finish_page(vec![1, 2, 3, 4], 3, Direction::Newer);
+        assert_eq!(page, vec![3, 2, 1]);
+        assert!(has_more);
+    }
+
+    #[test]
+    fn test_paging_through_ties() {
+        let rows = history();
+        let mut newest_first = rows.clone();
+        newest_first.sort();
+        newest_first.reverse();
+
+        // Older pages of two walk every row once, splitting the tied instant by id
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let (page, has_more) = finish_page(fetch(&rows, cursor, Direction::Older, 2), 2, Direction::Older);
+            seen.extend(page.iter().copied());
+            cursor = page.last().copied();
+            if !has_more {
+                break;
+            }
+        }
+        assert_eq!(seen, newest_first);
+
+        // From the oldest row back towards newer ones, each page newest first
+        let oldest = *newest_first.last().unwrap();
+        let (page, has_more) = finish_page(fetch(&rows, Some(oldest), Direction::Newer, 2), 2, Direction::Newer);
+        assert_eq!(page, vec![newest_first[2], newest_first[3]]);
+        assert!(has_more);
+        let (page, has_more) = finish_page(fetch(&rows, Some(page[0]), Direction::Newer, 2), 2, Direction::Newer);
+        assert_eq!(page, vec![newest_first[0], newest_first[1]]);
+        assert!(!has_more);
+
+        // Past the newest row the page is empty
+        let fetched = fetch(&rows, Some(newest_first[0]), Direction::Newer, 2);
+        let (page, has_more) = finish_page(fetched, 2, Direction::Newer);
+        assert!(page.is_empty());
+        assert!(!has_more);
+    }
+}
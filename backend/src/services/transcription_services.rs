@@ -0,0 +1,146 @@
+//! Speech-to-text for voice commands recorded on mobile. The recording goes to the provider's
+//! Whisper-compatible transcription API and comes back as text with timed segments. It is
+//! charged against the user's AI budget by the minute of audio; as its length is only known
+//! once transcribed, the request needs budget left rather than budget for a known cost.
+
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::speech::TranscribeQuery;
+use crate::services::ai_routing_services::{
+    budget, ensure_budget_left, record_usage, UsageRecord, REASON_REQUESTED, REASON_SELF_HOSTED,
+};
+use crate::services::ai_services::{AIService, AudioInput, CostEstimate, Transcription};
+
+/// Whisper APIs take files up to 25 MB
+pub const MAX_AUDIO_BYTES: usize = 25 * 1024 * 1024;
+/// Whisper's rate
+const USD_PER_MINUTE: f64 = 0.006;
+/// Bytes per second of 128 kbit/s audio, to estimate a recording's length from its size
+const ESTIMATE_BYTES_PER_SECOND: f64 = 16_000.0;
+const MAX_PROMPT_CHARS: usize = 1_000;
+
+/// Accepted audio types and the extension Whisper APIs recognise each by
+const AUDIO_TYPES: &[(&str, &str)] = &[
+    ("audio/mpeg", "mp3"),
+    ("audio/mp3", "mp3"),
+    ("audio/mp4", "m4a"),
+    ("audio/m4a", "m4a"),
+    ("audio/x-m4a", "m4a"),
+    ("audio/aac", "m4a"),
+    ("audio/wav", "wav"),
+    ("audio/x-wav", "wav"),
+    ("audio/wave", "wav"),
+    ("audio/webm", "webm"),
+    ("audio/ogg", "ogg"),
+    ("audio/flac", "flac"),
+];
+
+/// The audio type of a Content-Type header with its file extension
+pub fn audio_type(content_type: &str) -> ApiResult<(&'static str, &'static str)> {
+    let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    AUDIO_TYPES.iter().find(|(media_type, _)| *media_type == essence).copied().ok_or_else(|| {
+        ApiError::ValidationError(
+            "Audio must be MP3, M4A, WAV, WebM, Ogg or FLAC, sent with its Content-Type".to_string(),
+        )
+    })
+}
+
+pub fn validate_language(language: Option<&str>) -> ApiResult<Option<String>> {
+    match language.map(str::trim).filter(|l| !l.is_empty()) {
+        None => Ok(None),
+        Some(l) if l.len() == 2 && l.chars().all(|c| c.is_ascii_alphabetic()) => Ok(Some(l.to_ascii_lowercase())),
+        Some(_) => Err(ApiError::ValidationError("language must be an ISO-639-1 code such as en".to_string())),
+    }
+}
+
+/// Cost of `seconds` of audio
+pub fn cost_usd(seconds: f64) -> f64 {
+    seconds / 60.0 * USD_PER_MINUTE
+}
+
+/// Transcribe a recording
+pub async fn transcribe(
+    pool: &PgPool,
+    config: &AppConfig,
+    ai: &AIService,
+    user_id: Uuid,
+    content_type: &str,
+    data: Vec<u8>,
+    query: &TranscribeQuery,
+) -> ApiResult<Transcription> {
+    if data.is_empty() || data.len() > MAX_AUDIO_BYTES {
+        return Err(ApiError::ValidationError(format!("Audio must be 1-{} bytes", MAX_AUDIO_BYTES)));
+    }
+    let (media_type, extension) = audio_type(content_type)?;
+    let language = validate_language(query.language.as_deref())?;
+    let prompt = query.prompt.as_deref().map(str::trim).filter(|p| !p.is_empty());
+    if prompt.is_some_and(|p| p.chars().count() > MAX_PROMPT_CHARS) {
+        return Err(ApiError::ValidationError(format!("prompt must be at most {} characters", MAX_PROMPT_CHARS)));
+    }
+
+    let provider = ai.provider(query.provider.as_deref())?;
+    let kind = provider.kind();
+    let model = provider
+        .transcription_model()
+        .ok_or_else(|| ApiError::ValidationError(format!("AI provider {} does not transcribe audio", kind.as_str())))?
+        .to_string();
+    let status = budget(pool, config, user_id).await?;
+    if !kind.is_self_hosted() {
+        ensure_budget_left(&status)?;
+    }
+
+    let estimated_seconds = data.len() as f64 / ESTIMATE_BYTES_PER_SECOND;
+    let audio = AudioInput {
+        file_name: format!("recording.{}", extension),
+        media_type: media_type.to_string(),
+        data,
+        language,
+        prompt: prompt.map(str::to_string),
+    };
+    let mut transcription = provider.transcribe(audio).await?;
+
+    let (reason, rate) = if kind.is_self_hosted() { (REASON_SELF_HOSTED, 0.0) } else { (REASON_REQUESTED, 1.0) };
+    let estimated_usd = cost_usd(estimated_seconds) * rate;
+    let actual_usd = transcription.duration_seconds.map(|seconds| cost_usd(seconds) * rate);
+    record_usage(
+        pool,
+        user_id,
+        &UsageRecord {
+            provider: kind.as_str(),
+            requested_model: &model,
+            model: &transcription.model,
+            reason,
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            estimated_usd,
+            actual_usd,
+            failover_from: None,
+        },
+    )
+    .await?;
+    transcription.cost = Some(CostEstimate { estimated_usd, actual_usd });
+    Ok(transcription)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audio_type() {
+        assert_eq!(audio_type("audio/mp4").unwrap(), ("audio/mp4", "m4a"));
+        assert_eq!(audio_type("Audio/WebM; codecs=opus").unwrap(), ("audio/webm", "webm"));
+        assert!(audio_type("video/mp4").is_err());
+        assert!(audio_type("").is_err());
+    }
+
+    #[test]
+    fn test_validate_language() {
+        assert_eq!(validate_language(None).unwrap(), None);
+        assert_eq!(validate_language(Some(" EN ")).unwrap().as_deref(), Some("en"));
+        assert!(validate_language(Some("english")).is_err());
+        assert!((cost_usd(90.0) - 0.009).abs() < 1e-12);
+    }
+}
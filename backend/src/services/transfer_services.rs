@@ -0,0 +1,405 @@
+//! Relayed token transfers. The user approves the relayer as a spender of their tokens once,
+//! then authorizes each transfer by signing EIP-712 typed data that names it; the relayer submits
+//! `transferFrom` and pays the gas. The transfer is tracked as a `token_transfer`
+//! transaction, which the confirmation watcher settles like a crypto payment.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::transaction::{RelayedTransferRequest, Transaction, TransferRequest};
+use crate::models::typed_data::TypedData;
+use crate::services::audit_services::{self, AuditEntry};
+use crate::services::crypto_services::{decimal_amount, format_units, parse_units, BlockchainService};
+use crate::services::eip712_services;
+use crate::services::fraud_services::{self, PaymentAttempt};
+use crate::services::notification_services::notify_user;
+use crate::services::payment_services::{fail_transaction, TRANSACTION_COLUMNS};
+use crate::services::relayer_services::{address_word, selector, uint_word, Relayer};
+use crate::services::siwe_services::{consume_nonce, issue_nonce};
+use crate::utils::log_blockchain_event;
+
+/// Product type of transactions recording relayed transfers; they unlock nothing
+pub const PRODUCT_TYPE: &str = "token_transfer";
+
+const TRANSFER_SIGNATURE: &str = "transfer(address,uint256)";
+const TRANSFER_FROM_SIGNATURE: &str = "transferFrom(address,address,uint256)";
+const ALLOWANCE_SIGNATURE: &str = "allowance(address,address)";
+
+const AUTHORIZATION_TYPE: &str = "TransferAuthorization";
+const AUTHORIZATION_FIELDS: &[(&str, &str)] = &[
+    ("from", "address"),
+    ("to", "address"),
+    ("token", "address"),
+    ("amount", "string"),
+    ("symbol", "string"),
+    ("value", "uint256"),
+    ("nonce", "string"),
+    ("expiresAt", "uint256"),
+];
+
+/// A transfer checked against the sender's balance and the relayer's allowance
+#[derive(Debug, Serialize)]
+pub struct TransferQuote {
+    pub chain_id: u64,
+    pub contract_address: String,
+    /// The sender's linked wallet
+    pub from: String,
+    pub to: String,
+    /// In whole tokens, normalized
+    pub amount: String,
+    pub raw_amount: String,
+    pub symbol: String,
+    /// The spender the sender must approve
+    pub relayer: String,
+    pub balance: String,
+    pub allowance: String,
+    pub balance_sufficient: bool,
+    pub allowance_sufficient: bool,
+    #[serde(skip)]
+    pub raw: u128,
+}
+
+/// Typed data authorizing the transfer in `quote`, usable once with `nonce` until `expires_at`.
+/// The amount is shown in whole tokens and bound in base units as `value`.
+pub fn authorization(quote: &TransferQuote, nonce: &str, expires_at: DateTime<Utc>) -> TypedData {
+    eip712_services::typed_data(
+        quote.chain_id,
+        AUTHORIZATION_TYPE,
+        AUTHORIZATION_FIELDS,
+        serde_json::json!({
+            "from": BlockchainService::to_checksum_address(&quote.from),
+            "to": BlockchainService::to_checksum_address(&quote.to),
+            "token": BlockchainService::to_checksum_address(&quote.contract_address),
+            "amount": quote.amount,
+            "symbol": quote.symbol,
+            "value": quote.raw_amount,
+            "nonce": nonce,
+            "expiresAt": expires_at.timestamp(),
+        }),
+    )
+}
+
+/// Calldata of `transfer(to, amount)`, moving the sender's own tokens
+pub fn transfer_calldata(to: &str, amount: u128) -> ApiResult<Vec<u8>> {
+    let mut data = selector(TRANSFER_SIGNATURE).to_vec();
+    data.extend_from_slice(&address_word(to)?);
+    data.extend_from_slice(&uint_word(amount));
+    Ok(data)
+}
+
+/// Calldata of `transferFrom(from, to, amount)`
+pub fn transfer_from_calldata(from: &str, to: &str, amount: u128) -> ApiResult<Vec<u8>> {
+    let mut data = selector(TRANSFER_FROM_SIGNATURE).to_vec();
+    data.extend_from_slice(&address_word(from)?);
+    data.extend_from_slice(&address_word(to)?);
+    data.extend_from_slice(&uint_word(amount));
+    Ok(data)
+}
+
+/// A uint256 word as a `u128`, saturating: amounts beyond it are as good as unlimited
+fn word_to_u128(word: &[u8]) -> Option<u128> {
+    if word.len() != 32 {
+        return None;
+    }
+    if word[..16].iter().any(|b| *b != 0) {
+        return Some(u128::MAX);
+    }
+    Some(u128::from_be_bytes(word[16..].try_into().ok()?))
+}
+
+/// How much of `owner`'s tokens `spender` may move
+pub async fn allowance(chain: &BlockchainService, owner: &str, spender: &str) -> ApiResult<u128> {
+    let contract = chain.token_contract()?;
+    let mut data = selector(ALLOWANCE_SIGNATURE).to_vec();
+    data.extend_from_slice(&address_word(owner)?);
+    data.extend_from_slice(&address_word(spender)?);
+    let result = chain.eth_call(contract, &format!("0x{}", hex::encode(data))).await?;
+    word_to_u128(&result).ok_or_else(|| ApiError::BlockchainError("allowance returned malformed data".to_string()))
+}
+
+/// Check a transfer of `amount` whole tokens from `from` to `to`
+pub async fn quote(
+    chain: &BlockchainService,
+    relayer: &Relayer,
+    from: &str,
+    to: &str,
+    amount: &str,
+) -> ApiResult<TransferQuote> {
+    BlockchainService::validate_address(to)?;
+    let to = to.to_ascii_lowercase();
+    if to.eq_ignore_ascii_case(from) {
+        return Err(ApiError::ValidationError("Cannot transfer to the sending wallet".to_string()));
+    }
+    let balance = chain.get_token_balance(from).await?;
+    let raw = parse_units(amount, balance.decimals)?;
+    if raw == 0 {
+        return Err(ApiError::ValidationError("amount must be greater than zero".to_string()));
+    }
+    let raw_balance: u128 = balance.raw_balance.parse().unwrap_or(u128::MAX);
+    let allowance = allowance(chain, from, relayer.address()).await?;
+
+    Ok(TransferQuote {
+        chain_id: chain.chain_id(),
+        contract_address: balance.contract_address,
+        from: from.to_ascii_lowercase(),
+        to,
+        amount: format_units(&raw.to_string(), balance.decimals),
+        raw_amount: raw.to_string(),
+        symbol: balance.symbol,
+        relayer: relayer.address().to_string(),
+        balance: balance.balance,
+        allowance: format_units(&allowance.to_string(), balance.decimals),
+        balance_sufficient: raw_balance >= raw,
+        allowance_sufficient: allowance >= raw,
+        raw,
+    })
+}
+
+/// A checked transfer with the typed data that authorizes it
+#[derive(Debug, Serialize)]
+pub struct PreparedTransfer {
+    pub quote: TransferQuote,
+    /// To be signed with `eth_signTypedData_v4` by the sending wallet
+    pub typed_data: TypedData,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// The chain, relayer and quote of a transfer from the user's linked wallet
+async fn checked_quote(
+    pool: &PgPool,
+    config: &AppConfig,
+    user_id: Uuid,
+    request: &TransferRequest,
+) -> ApiResult<(BlockchainService, Relayer, TransferQuote)> {
+    let wallet: Option<String> = sqlx::query_scalar("SELECT wallet_address FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+    let wallet = wallet.ok_or_else(|| ApiError::ValidationError("Link a wallet to send tokens from".to_string()))?;
+
+    let chain_id = request.chain_id.unwrap_or(config.default_chain_id);
+    let chain = BlockchainService::for_chain_id(config, chain_id)?;
+    if !chain.has_provider() {
+        return Err(ApiError::ServiceUnavailable(format!("Chain {} has no RPC provider", chain_id)));
+    }
+    let relayer = Relayer::from_config(config)?;
+    let quote = quote(&chain, &relayer, &wallet, request.to.trim(), &request.amount).await?;
+    Ok((chain, relayer, quote))
+}
+
+/// Check a transfer from the user's linked wallet and issue the typed data authorizing it
+pub async fn prepare(
+    pool: &PgPool,
+    config: &AppConfig,
+    user_id: Uuid,
+    request: &TransferRequest,
+) -> ApiResult<PreparedTransfer> {
+    let (_, _, quote) = checked_quote(pool, config, user_id, request).await?;
+
+    let (nonce, expires_at) = issue_nonce(pool).await?;
+    let typed_data = authorization(&quote, &nonce, expires_at);
+    Ok(PreparedTransfer { quote, typed_data, expires_at })
+}
+
+/// Submit a prepared transfer through the relayer, recorded as a pending transaction of
+/// `product_type` with the tx hash once the node accepts it. The transfer is screened as a
+/// payment from `country` first.
+pub async fn relay(
+    pool: &PgPool,
+    config: &AppConfig,
+    user_id: Uuid,
+    request: &RelayedTransferRequest,
+    product_type: &str,
+    country: Option<&str>,
+) -> ApiResult<Transaction> {
+    let nonce = eip712_services::message_str(&request.typed_data, "nonce")?;
+    let expires_at = eip712_services::message_time(&request.typed_data, "expiresAt")?;
+    if expires_at <= Utc::now() {
+        return Err(ApiError::Unauthorized("The transfer authorization has expired".to_string()));
+    }
+
+    let (chain, relayer, quote) = checked_quote(pool, config, user_id, &request.transfer).await?;
+    // The signed data must describe exactly this transfer
+    let expected = authorization(&quote, nonce, expires_at);
+    let signer = eip712_services::verify(&request.typed_data, request.signature.trim(), &expected)?;
+    if !signer.eq_ignore_ascii_case(&quote.from) {
+        log_blockchain_event("token_transfer", None, None, "signature_mismatch");
+        return Err(ApiError::Unauthorized("The authorization must be signed by your linked wallet".to_string()));
+    }
+    if !quote.balance_sufficient {
+        return Err(ApiError::Conflict(format!("Balance of {} {} is too low", quote.balance, quote.symbol)));
+    }
+    if !quote.allowance_sufficient {
+        return Err(ApiError::Conflict(format!(
+            "Approve {} to spend at least {} {}; the current allowance is {}",
+            quote.relayer, quote.amount, quote.symbol, quote.allowance
+        )));
+    }
+    let amount = decimal_amount(&quote.amount)?;
+    let attempt = PaymentAttempt {
+        user_id,
+        kind: product_type,
+        amount,
+        currency: &quote.symbol,
+        country,
+    };
+    fraud_services::screen(pool, config, &attempt).await?;
+
+    // Recorded before sending, so a transfer the node accepted is never lost
+    let mut tx = pool.begin().await?;
+    if !consume_nonce(&mut tx, nonce).await? {
+        return Err(ApiError::Unauthorized("Nonce is invalid or has expired".to_string()));
+    }
+    let transaction = sqlx::query_as::<_, Transaction>(&format!(
+        "INSERT INTO transactions (id, user_id, amount, currency, payment_method, payment_id, status, product_type, \
+         chain_id) VALUES ($1, $2, $3, $4, 'crypto', $5, 'pending', $6, $7) RETURNING {}",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(amount)
+    .bind(&quote.symbol)
+    .bind(nonce)
+    .bind(product_type)
+    .bind(quote.chain_id as i64)
+    .fetch_one(&mut *tx)
+    .await?;
+    audit_services::record(
+        &mut tx,
+        AuditEntry {
+            org_id: None,
+            actor_id: Some(user_id),
+            action: "token.transfer_submitted",
+            resource_type: "transaction",
+            resource_id: Some(transaction.id.to_string()),
+            details: serde_json::json!({
+                "chain_id": quote.chain_id,
+                "from": quote.from,
+                "to": quote.to,
+                "amount": quote.amount,
+                "symbol": quote.symbol,
+                "relayer": quote.relayer,
+                "product_type": product_type,
+            }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    let calldata = transfer_from_calldata(&quote.from, &quote.to, quote.raw)?;
+    let tx_hash = match relayer.send(&chain, &quote.contract_address, calldata, 0).await {
+        Ok(tx_hash) => tx_hash,
+        Err(e) => {
+            let mut tx = pool.begin().await?;
+            fail_transaction(&mut tx, &transaction, &e.to_string()).await?;
+            tx.commit().await?;
+            log_blockchain_event("token_transfer", None, quote.amount.parse().ok(), "failed");
+            return Err(e);
+        }
+    };
+
+    let transaction = sqlx::query_as::<_, Transaction>(&format!(
+        "UPDATE transactions SET blockchain_tx_hash = $2 WHERE id = $1 RETURNING {}",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(transaction.id)
+    .bind(&tx_hash)
+    .fetch_one(pool)
+    .await?;
+    log_blockchain_event("token_transfer", Some(&tx_hash), quote.amount.parse().ok(), "submitted");
+    Ok(transaction)
+}
+
+/// Tell the sender how their transfer ended; called when the watcher settles it
+pub async fn notify_settled(
+    conn: &mut PgConnection,
+    transaction: &Transaction,
+    failure: Option<&str>,
+) -> ApiResult<()> {
+    let (kind, title, body) = match failure {
+        None => (
+            "transfer_completed",
+            "Transfer confirmed",
+            format!("Your transfer of {} {} is confirmed on-chain.", transaction.amount, transaction.currency),
+        ),
+        Some(reason) => (
+            "transfer_failed",
+            "Transfer failed",
+            format!("Your transfer of {} {} did not go through: {}", transaction.amount, transaction.currency, reason),
+        ),
+    };
+    notify_user(
+        conn,
+        transaction.user_id,
+        kind,
+        title,
+        &body,
+        serde_json::json!({ "transaction_id": transaction.id, "tx_hash": transaction.blockchain_tx_hash }),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transfer_from_calldata() {
+        let from = "0x00000000000000000000000000000000000000aa";
+        let to = "0x00000000000000000000000000000000000000bb";
+        let data = transfer_from_calldata(from, to, 1_500).unwrap();
+        assert_eq!(hex::encode(&data[..4]), "23b872dd");
+        assert_eq!(data.len(), 4 + 3 * 32);
+        assert_eq!(data[4 + 31], 0xaa);
+        assert_eq!(data[4 + 63], 0xbb);
+        assert_eq!(data[4 + 94..], [0x05, 0xdc]);
+
+        let data = transfer_calldata(to, 1_500).unwrap();
+        assert_eq!(hex::encode(&data[..4]), "a9059cbb");
+        assert_eq!(data.len(), 4 + 2 * 32);
+        assert_eq!(data[4 + 31], 0xbb);
+    }
+
+    #[test]
+    fn test_word_to_u128() {
+        let mut word = [0u8; 32];
+        word[31] = 7;
+        assert_eq!(word_to_u128(&word), Some(7));
+        // The usual "infinite" approval
+        assert_eq!(word_to_u128(&[0xff; 32]), Some(u128::MAX));
+        assert_eq!(word_to_u128(&[0; 31]), None);
+    }
+
+    #[test]
+    fn test_authorization() {
+        let quote = TransferQuote {
+            chain_id: 137,
+            contract_address: "0x00000000000000000000000000000000000000cc".to_string(),
+            from: "0x00000000000000000000000000000000000000aa".to_string(),
+            to: "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed".to_string(),
+            amount: "12.5".to_string(),
+            raw_amount: "12500000000000000000".to_string(),
+            symbol: "RBV".to_string(),
+            relayer: "0x00000000000000000000000000000000000000dd".to_string(),
+            balance: "20".to_string(),
+            allowance: "100".to_string(),
+            balance_sufficient: true,
+            allowance_sufficient: true,
+            raw: 12_500_000_000_000_000_000,
+        };
+        let expires_at = DateTime::from_timestamp(1_800_000_000, 0).unwrap();
+        let data = authorization(&quote, "abcdef0123456789", expires_at);
+        assert_eq!(data.primary_type, "TransferAuthorization");
+        assert_eq!(data.message["to"], "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed");
+        assert_eq!(eip712_services::chain_id(&data).unwrap(), 137);
+        assert_eq!(eip712_services::message_time(&data, "expiresAt").unwrap(), expires_at);
+
+        // Another amount or recipient is another authorization
+        let other = TransferQuote { raw_amount: "1".to_string(), ..quote };
+        let other = authorization(&other, "abcdef0123456789", expires_at);
+        assert_ne!(eip712_services::signing_hash(&data).unwrap(), eip712_services::signing_hash(&other).unwrap());
+    }
+}
@@ -0,0 +1,406 @@
+//! Device transports: how a queued command reaches the hardware. Each device picks one
+//! (`devices.transport`); the command row in `device_commands` stays the source of truth
+//! and acks arrive through the ack endpoint whatever the transport.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use secrecy::{ExposeSecret, SecretString};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, Notify};
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+
+/// Longest a device may hold a long-poll request open
+pub const MAX_LONG_POLL_SECS: u64 = 30;
+
+/// MQTT topic commands are published to; `{device_id}` is substituted
+pub const MQTT_COMMAND_TOPIC: &str = "roboveda/devices/{device_id}/commands";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportKind {
+    Mqtt,
+    WebSocket,
+    LongPoll,
+    Simulated,
+}
+
+impl TransportKind {
+    pub const ALL: [TransportKind; 4] =
+        [TransportKind::Mqtt, TransportKind::WebSocket, TransportKind::LongPoll, TransportKind::Simulated];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransportKind::Mqtt => "mqtt",
+            TransportKind::WebSocket => "websocket",
+            TransportKind::LongPoll => "long_poll",
+            TransportKind::Simulated => "simulated",
+        }
+    }
+
+    pub fn parse(value: &str) -> ApiResult<Self> {
+        Self::ALL.into_iter().find(|k| k.as_str() == value).ok_or_else(|| {
+            let names: Vec<&str> = Self::ALL.iter().map(|k| k.as_str()).collect();
+            ApiError::ValidationError(format!("Unknown transport '{}'. Valid transports: {:?}", value, names))
+        })
+    }
+}
+
+/// A command on its way to a device
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboundCommand {
+    pub command_id: Uuid,
+    pub device_id: Uuid,
+    pub command: String,
+    pub parameters: serde_json::Value,
+    pub issued_at: DateTime<Utc>,
+}
+
+/// What the transport did with a command
+#[derive(Debug, Clone, PartialEq)]
+pub enum Delivery {
+    /// Handed to the device (or its broker); the ack follows separately
+    Delivered,
+    /// Waiting for the device to fetch it
+    Queued,
+    /// Executed on the spot (simulated devices); the result is the ack
+    Completed { duration_ms: u64, battery_drain: f32 },
+}
+
+#[async_trait]
+pub trait DeviceTransport: Send + Sync {
+    fn kind(&self) -> TransportKind;
+
+    /// Push a command that has already been recorded in `device_commands`
+    async fn dispatch(&self, command: &OutboundCommand, estimate: (u64, f32)) -> ApiResult<Delivery>;
+}
+
+/// In-process rendezvous between dispatchers and devices connected to this instance:
+/// long-poll waiters and open WebSocket sessions
+#[derive(Default)]
+pub struct DeviceHub {
+    waiters: Mutex<HashMap<Uuid, Arc<Notify>>>,
+    sockets: Mutex<HashMap<Uuid, mpsc::UnboundedSender<String>>>,
+}
+
+impl DeviceHub {
+    pub fn waiter(&self, device_id: Uuid) -> Arc<Notify> {
+        self.waiters.lock().expect("hub lock").entry(device_id).or_default().clone()
+    }
+
+    pub fn notify(&self, device_id: Uuid) {
+        if let Some(waiter) = self.waiters.lock().expect("hub lock").get(&device_id) {
+            waiter.notify_waiters();
+        }
+    }
+
+    /// Register a device's WebSocket session, replacing any older one
+    pub fn connect(&self, device_id: Uuid) -> mpsc::UnboundedReceiver<String> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.sockets.lock().expect("hub lock").insert(device_id, tx);
+        rx
+    }
+
+    pub fn disconnect(&self, device_id: Uuid) {
+        let mut sockets = self.sockets.lock().expect("hub lock");
+        if sockets.get(&device_id).is_some_and(|tx| tx.is_closed()) {
+            sockets.remove(&device_id);
+        }
+    }
+
+    fn send(&self, device_id: Uuid, message: String) -> bool {
+        let mut sockets = self.sockets.lock().expect("hub lock");
+        match sockets.get(&device_id) {
+            Some(tx) if tx.send(message).is_ok() => true,
+            Some(_) => {
+                sockets.remove(&device_id);
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+fn encode(command: &OutboundCommand) -> ApiResult<String> {
+    serde_json::to_string(command)
+        .map_err(|e| ApiError::InternalError(format!("Failed to encode command: {}", e)))
+}
+
+/// Devices fetch pending commands from the long-poll endpoint; dispatch just wakes them
+pub struct LongPollTransport {
+    hub: Arc<DeviceHub>,
+}
+
+#[async_trait]
+impl DeviceTransport for LongPollTransport {
+    fn kind(&self) -> TransportKind {
+        TransportKind::LongPoll
+    }
+
+    async fn dispatch(&self, command: &OutboundCommand, _estimate: (u64, f32)) -> ApiResult<Delivery> {
+        self.hub.notify(command.device_id);
+        Ok(Delivery::Queued)
+    }
+}
+
+/// Pushes to the device's open WebSocket session on this instance
+pub struct WebSocketTransport {
+    hub: Arc<DeviceHub>,
+}
+
+#[async_trait]
+impl DeviceTransport for WebSocketTransport {
+    fn kind(&self) -> TransportKind {
+        TransportKind::WebSocket
+    }
+
+    async fn dispatch(&self, command: &OutboundCommand, _estimate: (u64, f32)) -> ApiResult<Delivery> {
+        if self.hub.send(command.device_id, encode(command)?) {
+            Ok(Delivery::Delivered)
+        } else {
+            Err(ApiError::ServiceUnavailable("Device has no open WebSocket session".to_string()))
+        }
+    }
+}
+
+/// Publishes to the device's command topic (QoS 1)
+pub struct MqttTransport {
+    client: AsyncClient,
+    /// Whether the broker has acknowledged the current connection
+    connected: Arc<AtomicBool>,
+}
+
+impl MqttTransport {
+    /// Connect to the broker and drive its event loop in the background
+    pub fn connect(url: &str, username: Option<&str>, password: Option<&SecretString>) -> ApiResult<Self> {
+        let parsed = reqwest::Url::parse(url)
+            .map_err(|e| ApiError::InternalError(format!("Invalid MQTT_BROKER_URL: {}", e)))?;
+        let host = parsed
+            .host_str()
+            .ok_or_else(|| ApiError::InternalError("MQTT_BROKER_URL has no host".to_string()))?;
+        let tls = parsed.scheme() == "mqtts";
+        let port = parsed.port().unwrap_or(if tls { 8883 } else { 1883 });
+
+        let mut options = MqttOptions::new(format!("roboveda-api-{}", Uuid::new_v4()), host, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if tls {
+            options.set_transport(rumqttc::Transport::tls_with_default_config());
+        }
+        if let Some(username) = username {
+            options.set_credentials(username, password.map(|p| p.expose_secret().to_string()).unwrap_or_default());
+        }
+
+        let (client, mut event_loop) = AsyncClient::new(options, 64);
+        let connected = Arc::new(AtomicBool::new(false));
+        let state = connected.clone();
+        tokio::spawn(async move {
+            loop {
+                match event_loop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => state.store(true, Ordering::Relaxed),
+                    Ok(_) => {}
+                    Err(e) => {
+                        state.store(false, Ordering::Relaxed);
+                        tracing::warn!(error = %e, "MQTT connection error; retrying");
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        });
+        Ok(Self { client, connected })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+}
+
+#[async_trait]
+impl DeviceTransport for MqttTransport {
+    fn kind(&self) -> TransportKind {
+        TransportKind::Mqtt
+    }
+
+    async fn dispatch(&self, command: &OutboundCommand, _estimate: (u64, f32)) -> ApiResult<Delivery> {
+        let topic = MQTT_COMMAND_TOPIC.replace("{device_id}", &command.device_id.to_string());
+        self.client
+            .publish(topic, QoS::AtLeastOnce, false, encode(command)?)
+            .await
+            .map_err(|e| ApiError::ExternalServiceError(format!("MQTT publish failed: {}", e)))?;
+        Ok(Delivery::Delivered)
+    }
+}
+
+/// Simulated devices execute instantly and exactly as estimated
+pub struct SimulatedTransport;
+
+#[async_trait]
+impl DeviceTransport for SimulatedTransport {
+    fn kind(&self) -> TransportKind {
+        TransportKind::Simulated
+    }
+
+    async fn dispatch(&self, _command: &OutboundCommand, estimate: (u64, f32)) -> ApiResult<Delivery> {
+        let (duration_ms, battery_drain) = estimate;
+        Ok(Delivery::Completed { duration_ms, battery_drain })
+    }
+}
+
+/// Every transport this instance can dispatch through
+pub struct TransportRegistry {
+    pub hub: Arc<DeviceHub>,
+    long_poll: LongPollTransport,
+    websocket: WebSocketTransport,
+    mqtt: Option<MqttTransport>,
+    simulated: SimulatedTransport,
+}
+
+impl TransportRegistry {
+    pub fn new(mqtt: Option<MqttTransport>) -> Self {
+        let hub = Arc::new(DeviceHub::default());
+        Self {
+            long_poll: LongPollTransport { hub: hub.clone() },
+            websocket: WebSocketTransport { hub: hub.clone() },
+            hub,
+            mqtt,
+            simulated: SimulatedTransport,
+        }
+    }
+
+    /// MQTT is only available when a broker is configured
+    pub fn from_config(config: &AppConfig) -> ApiResult<Self> {
+        let mqtt = config
+            .mqtt_broker_url
+            .as_deref()
+            .map(|url| MqttTransport::connect(url, config.mqtt_username.as_deref(), config.mqtt_password.as_ref()))
+            .transpose()?;
+        Ok(Self::new(mqtt))
+    }
+
+    /// Whether the MQTT broker is connected; `None` when none is configured
+    pub fn mqtt_connected(&self) -> Option<bool> {
+        self.mqtt.as_ref().map(MqttTransport::is_connected)
+    }
+
+    pub fn get(&self, kind: TransportKind) -> ApiResult<&dyn DeviceTransport> {
+        match kind {
+            TransportKind::LongPoll => Ok(&self.long_poll),
+            TransportKind::WebSocket => Ok(&self.websocket),
+            TransportKind::Simulated => Ok(&self.simulated),
+            TransportKind::Mqtt => self
+                .mqtt
+                .as_ref()
+                .map(|t| t as &dyn DeviceTransport)
+                .ok_or_else(|| ApiError::ServiceUnavailable("MQTT transport is not configured".to_string())),
+        }
+    }
+}
+
+/// Send a recorded command through the device's transport and record what happened:
+/// simulated completions are acked on the spot, delivery failures fail the command.
+/// Returns the command's resulting status.
+pub async fn deliver(
+    pool: &PgPool,
+    registry: &TransportRegistry,
+    transport: &str,
+    command: &OutboundCommand,
+    estimate: (u64, f32),
+) -> ApiResult<&'static str> {
+    let result = match TransportKind::parse(transport).and_then(|kind| registry.get(kind)) {
+        Ok(t) => {
+            let result = t.dispatch(command, estimate).await;
+            tracing::debug!(
+                transport = t.kind().as_str(),
+                command_id = %command.command_id,
+                ok = result.is_ok(),
+                "Dispatched device command"
+            );
+            result
+        }
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(Delivery::Queued) => Ok("queued"),
+        Ok(Delivery::Delivered) => Ok("delivered"),
+        Ok(Delivery::Completed { duration_ms, battery_drain }) => {
+            sqlx::query(
+                "UPDATE device_commands SET status = 'succeeded', actual_duration_ms = $2, \
+                 actual_battery_drain = $3, acked_at = NOW() WHERE id = $1 AND acked_at IS NULL",
+            )
+            .bind(command.command_id)
+            .bind(duration_ms as i64)
+            .bind(battery_drain)
+            .execute(pool)
+            .await?;
+            Ok("succeeded")
+        }
+        Err(e) => {
+            sqlx::query(
+                "UPDATE device_commands SET status = 'failed', error = $2, acked_at = NOW() \
+                 WHERE id = $1 AND acked_at IS NULL",
+            )
+            .bind(command.command_id)
+            .bind(format!("Delivery failed: {}", e))
+            .execute(pool)
+            .await?;
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(device_id: Uuid) -> OutboundCommand {
+        OutboundCommand {
+            command_id: Uuid::new_v4(),
+            device_id,
+            command: "stop".to_string(),
+            parameters: serde_json::json!({}),
+            issued_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_transport_kind_round_trip() {
+        for kind in TransportKind::ALL {
+            assert_eq!(TransportKind::parse(kind.as_str()).unwrap(), kind);
+        }
+        assert!(TransportKind::parse("carrier_pigeon").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_registry_dispatch() {
+        let registry = TransportRegistry::new(None);
+        let device_id = Uuid::new_v4();
+
+        assert!(registry.get(TransportKind::Mqtt).is_err());
+
+        let simulated = registry.get(TransportKind::Simulated).unwrap();
+        assert_eq!(
+            simulated.dispatch(&command(device_id), (1500, 0.2)).await.unwrap(),
+            Delivery::Completed { duration_ms: 1500, battery_drain: 0.2 }
+        );
+
+        // WebSocket delivery needs a connected session
+        let websocket = registry.get(TransportKind::WebSocket).unwrap();
+        assert!(websocket.dispatch(&command(device_id), (0, 0.0)).await.is_err());
+        let mut session = registry.hub.connect(device_id);
+        assert_eq!(websocket.dispatch(&command(device_id), (0, 0.0)).await.unwrap(), Delivery::Delivered);
+        assert!(session.recv().await.unwrap().contains("\"command\":\"stop\""));
+
+        // Long-poll dispatch wakes a waiting device
+        let waiter = registry.hub.waiter(device_id);
+        let woken = waiter.notified();
+        let long_poll = registry.get(TransportKind::LongPoll).unwrap();
+        assert_eq!(long_poll.dispatch(&command(device_id), (0, 0.0)).await.unwrap(), Delivery::Queued);
+        tokio::time::timeout(Duration::from_secs(1), woken).await.unwrap();
+    }
+}
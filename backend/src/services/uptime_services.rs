@@ -0,0 +1,221 @@
+//! Device uptime and SLA figures computed from the status history.
+//!
+//! Time in `maintenance` is planned downtime and excluded from the uptime percentage;
+//! time before the first recorded transition is reported as unknown.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+
+pub const DEFAULT_PERIOD: &str = "30d";
+pub const MAX_PERIOD_DAYS: i64 = 365;
+
+#[derive(Debug, Clone, FromRow)]
+pub struct StatusChange {
+    pub status: String,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// A stretch of time the device spent offline
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Incident {
+    pub started_at: DateTime<Utc>,
+    /// `None` while the device is still offline
+    pub ended_at: Option<DateTime<Utc>>,
+    pub duration_secs: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UptimeReport {
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    /// Online share of the time the device was either online or offline
+    pub uptime_pct: Option<f64>,
+    pub online_secs: i64,
+    pub offline_secs: i64,
+    pub maintenance_secs: i64,
+    pub unknown_secs: i64,
+    pub incidents: Vec<Incident>,
+    /// Mean time between failures: online time per incident
+    pub mtbf_secs: Option<f64>,
+    /// Mean time to recovery over incidents that ended
+    pub mttr_secs: Option<f64>,
+}
+
+/// `30d` / `12h` style window, up to a year
+pub fn parse_period(period: &str) -> ApiResult<Duration> {
+    let invalid = || ApiError::ValidationError("period must look like 30d or 12h".to_string());
+    let period = period.trim();
+    let (amount, unit) = period.split_at(period.len().saturating_sub(1));
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    let duration = match unit {
+        "d" => Duration::days(amount),
+        "h" => Duration::hours(amount),
+        _ => return Err(invalid()),
+    };
+    if amount <= 0 || duration > Duration::days(MAX_PERIOD_DAYS) {
+        return Err(ApiError::ValidationError(format!(
+            "period must be between 1h and {}d",
+            MAX_PERIOD_DAYS
+        )));
+    }
+    Ok(duration)
+}
+
+/// Walk the transitions inside `[start, end]`, starting from the status in effect at `start`
+pub fn uptime_report(
+    initial: Option<&str>,
+    changes: &[StatusChange],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> UptimeReport {
+    let mut report = UptimeReport {
+        period_start: start,
+        period_end: end,
+        uptime_pct: None,
+        online_secs: 0,
+        offline_secs: 0,
+        maintenance_secs: 0,
+        unknown_secs: 0,
+        incidents: Vec::new(),
+        mtbf_secs: None,
+        mttr_secs: None,
+    };
+
+    let mut current = initial.map(str::to_string);
+    let mut cursor = start;
+    let mut open_incident = (current.as_deref() == Some("offline")).then_some(start);
+
+    for change in changes.iter().filter(|c| c.changed_at > start && c.changed_at <= end) {
+        add_time(&mut report, current.as_deref(), change.changed_at - cursor);
+        cursor = change.changed_at;
+
+        match (open_incident, change.status == "offline") {
+            (None, true) => open_incident = Some(change.changed_at),
+            (Some(started_at), false) => {
+                report.incidents.push(Incident {
+                    started_at,
+                    ended_at: Some(change.changed_at),
+                    duration_secs: (change.changed_at - started_at).num_seconds(),
+                });
+                open_incident = None;
+            }
+            _ => {}
+        }
+        current = Some(change.status.clone());
+    }
+    add_time(&mut report, current.as_deref(), end - cursor);
+    if let Some(started_at) = open_incident {
+        report.incidents.push(Incident { started_at, ended_at: None, duration_secs: (end - started_at).num_seconds() });
+    }
+
+    let observed = report.online_secs + report.offline_secs;
+    if observed > 0 {
+        report.uptime_pct = Some((report.online_secs as f64 / observed as f64 * 10_000.0).round() / 100.0);
+    }
+    if !report.incidents.is_empty() {
+        report.mtbf_secs = Some(report.online_secs as f64 / report.incidents.len() as f64);
+    }
+    let recovered: Vec<i64> = report.incidents.iter().filter(|i| i.ended_at.is_some()).map(|i| i.duration_secs).collect();
+    if !recovered.is_empty() {
+        report.mttr_secs = Some(recovered.iter().sum::<i64>() as f64 / recovered.len() as f64);
+    }
+    report
+}
+
+fn add_time(report: &mut UptimeReport, status: Option<&str>, elapsed: Duration) {
+    let secs = elapsed.num_seconds().max(0);
+    match status {
+        Some("online") => report.online_secs += secs,
+        Some("offline") => report.offline_secs += secs,
+        Some("maintenance") => report.maintenance_secs += secs,
+        _ => report.unknown_secs += secs,
+    }
+}
+
+/// Uptime report for a device over the window ending now
+pub async fn device_uptime(pool: &PgPool, device_id: Uuid, period: Duration) -> ApiResult<UptimeReport> {
+    let end = Utc::now();
+    let start = end - period;
+
+    let initial: Option<String> = sqlx::query_scalar(
+        "SELECT status FROM device_status_history WHERE device_id = $1 AND changed_at <= $2 \
+         ORDER BY changed_at DESC, id DESC LIMIT 1",
+    )
+    .bind(device_id)
+    .bind(start)
+    .fetch_optional(pool)
+    .await?;
+    let changes = sqlx::query_as::<_, StatusChange>(
+        "SELECT status, changed_at FROM device_status_history \
+         WHERE device_id = $1 AND changed_at > $2 AND changed_at <= $3 ORDER BY changed_at, id",
+    )
+    .bind(device_id)
+    .bind(start)
+    .bind(end)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(uptime_report(initial.as_deref(), &changes, start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hours: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap() + Duration::hours(hours)
+    }
+
+    fn change(status: &str, hours: i64) -> StatusChange {
+        StatusChange { status: status.to_string(), changed_at: at(hours) }
+    }
+
+    #[test]
+    fn test_parse_period() {
+        assert_eq!(parse_period("30d").unwrap(), Duration::days(30));
+        assert_eq!(parse_period("12h").unwrap(), Duration::hours(12));
+        assert!(parse_period("0d").is_err());
+        assert!(parse_period("400d").is_err());
+        assert!(parse_period("30m").is_err());
+        assert!(parse_period("d").is_err());
+    }
+
+    #[test]
+    fn test_incidents_mtbf_and_mttr() {
+        // online 0-10h, offline 10-12h, online 12-20h, maintenance 20-22h, online 22-30h, offline 30-40h (ongoing)
+        let changes = [
+            change("offline", 10),
+            change("online", 12),
+            change("maintenance", 20),
+            change("online", 22),
+            change("offline", 30),
+        ];
+        let report = uptime_report(Some("online"), &changes, at(0), at(40));
+        assert_eq!(report.online_secs, 26 * 3600);
+        assert_eq!(report.offline_secs, 12 * 3600);
+        assert_eq!(report.maintenance_secs, 2 * 3600);
+        assert_eq!(report.uptime_pct, Some(68.42));
+        assert_eq!(report.incidents.len(), 2);
+        assert_eq!(report.incidents[1].ended_at, None);
+        assert_eq!(report.mtbf_secs, Some(13.0 * 3600.0));
+        assert_eq!(report.mttr_secs, Some(2.0 * 3600.0));
+    }
+
+    #[test]
+    fn test_unknown_history_and_offline_at_start() {
+        let report = uptime_report(None, &[change("online", 5)], at(0), at(10));
+        assert_eq!(report.unknown_secs, 5 * 3600);
+        assert_eq!(report.uptime_pct, Some(100.0));
+        assert!(report.incidents.is_empty());
+        assert_eq!(report.mtbf_secs, None);
+
+        let report = uptime_report(Some("offline"), &[change("online", 3)], at(0), at(10));
+        assert_eq!(report.incidents, [Incident { started_at: at(0), ended_at: Some(at(3)), duration_secs: 3 * 3600 }]);
+
+        let report = uptime_report(None, &[], at(0), at(10));
+        assert_eq!(report.uptime_pct, None);
+    }
+}
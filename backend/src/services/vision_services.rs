@@ -0,0 +1,121 @@
+//! Image analysis with vision models, e.g. checking drone photos for damage. The image, uploaded
+//! or given by URL, is attached to a chat request with the user's prompt, so it is moderated,
+//! routed and charged against the user's AI budget like any chat request. Uploads are checked by
+//! their content rather than the type the client claims.
+
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::vision::VisionRequest;
+use crate::services::ai_routing_services::routed_chat;
+use crate::services::ai_services::{AIService, ChatMessage, ChatRequest, ChatResponse, ImageInput};
+use crate::services::moderation_services;
+
+/// The smallest limit among the providers (Anthropic's)
+pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+const MAX_PROMPT_CHARS: usize = 4_000;
+const MAX_URL_CHARS: usize = 2_048;
+const ANALYSIS_TEMPERATURE: f32 = 0.2;
+
+/// The image type from its leading bytes: JPEG, PNG, GIF or WebP, the formats every provider takes
+pub fn sniff_image_type(data: &[u8]) -> Option<&'static str> {
+    match data {
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some("image/png"),
+        [b'G', b'I', b'F', b'8', ..] => Some("image/gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        _ => None,
+    }
+}
+
+pub fn validate_image_url(url: &str) -> ApiResult<String> {
+    let url = url.trim();
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|_| ApiError::ValidationError("image_url is not a valid URL".to_string()))?;
+    if parsed.scheme() != "https" || url.len() > MAX_URL_CHARS {
+        return Err(ApiError::ValidationError(format!(
+            "image_url must be an https URL of at most {} characters",
+            MAX_URL_CHARS
+        )));
+    }
+    Ok(url.to_string())
+}
+
+/// The image of a request: the uploaded one or the URL, exactly one of which must be given
+pub fn image_input(image_url: Option<&str>, upload: Option<Vec<u8>>) -> ApiResult<ImageInput> {
+    match (image_url.map(str::trim).filter(|u| !u.is_empty()), upload) {
+        (Some(url), None) => Ok(ImageInput::Url(validate_image_url(url)?)),
+        (None, Some(data)) => {
+            if data.len() > MAX_IMAGE_BYTES {
+                return Err(ApiError::ValidationError(format!("Images must be at most {} bytes", MAX_IMAGE_BYTES)));
+            }
+            let media_type = sniff_image_type(&data).ok_or_else(|| {
+                ApiError::ValidationError("The image must be a JPEG, PNG, GIF or WebP file".to_string())
+            })?;
+            Ok(ImageInput::Data { media_type: media_type.to_string(), data })
+        }
+        (Some(_), Some(_)) => Err(ApiError::ValidationError("Give either an image or image_url, not both".to_string())),
+        (None, None) => Err(ApiError::ValidationError("An image or image_url is required".to_string())),
+    }
+}
+
+/// Analyze an image as `request.prompt` asks
+pub async fn analyze(
+    pool: &PgPool,
+    config: &AppConfig,
+    ai: &AIService,
+    user_id: Uuid,
+    request: VisionRequest,
+    upload: Option<Vec<u8>>,
+) -> ApiResult<ChatResponse> {
+    let mut prompt = request.prompt.trim().to_string();
+    if prompt.is_empty() || prompt.chars().count() > MAX_PROMPT_CHARS {
+        return Err(ApiError::ValidationError(format!(
+            "prompt must be between 1 and {} characters",
+            MAX_PROMPT_CHARS
+        )));
+    }
+    let image = image_input(request.image_url.as_deref(), upload)?;
+    moderation_services::screen_prompt(config, ai, "prompt", &mut prompt).await?;
+
+    let chat = ChatRequest {
+        messages: vec![ChatMessage { role: "user".to_string(), content: prompt }],
+        model: request.model,
+        provider: request.provider,
+        temperature: Some(ANALYSIS_TEMPERATURE),
+        max_tokens: request.max_tokens,
+        conversation_id: None,
+        images: vec![image],
+        json: false,
+    };
+    let mut response = routed_chat(pool, config, ai, user_id, chat).await?;
+    moderation_services::screen_output(config, ai, &mut response.message).await?;
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_image_type() {
+        assert_eq!(sniff_image_type(&[0xFF, 0xD8, 0xFF, 0xE0, 0x00]), Some("image/jpeg"));
+        assert_eq!(sniff_image_type(b"\x89PNG\r\n\x1a\n\0\0"), Some("image/png"));
+        assert_eq!(sniff_image_type(b"RIFF\x10\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff_image_type(b"%PDF-1.7"), None);
+        assert_eq!(sniff_image_type(&[]), None);
+    }
+
+    #[test]
+    fn test_image_input() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0".to_vec();
+        let uploaded = image_input(None, Some(png.clone()));
+        assert!(matches!(uploaded, Ok(ImageInput::Data { media_type, .. }) if media_type == "image/png"));
+        assert!(matches!(image_input(Some(" https://cdn.example.com/panel.jpg "), None), Ok(ImageInput::Url(_))));
+        assert!(image_input(Some("http://cdn.example.com/panel.jpg"), None).is_err());
+        assert!(image_input(Some("https://cdn.example.com/panel.jpg"), Some(png)).is_err());
+        assert!(image_input(Some("  "), None).is_err());
+        assert!(image_input(None, Some(b"not an image".to_vec())).is_err());
+    }
+}
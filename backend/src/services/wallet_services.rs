@@ -0,0 +1,229 @@
+//! Linking and removing a wallet. Either change is authorized by the wallet signing EIP-712
+//! typed data that names the account. Unlinks are recorded so that, for a cooldown afterwards,
+//! neither the account nor the released address can link a wallet again.
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgConnection;
+use uuid::Uuid;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::typed_data::{SignedTypedData, TypedData};
+use crate::models::user::WalletAction;
+use crate::services::crypto_services::BlockchainService;
+use crate::services::eip712_services;
+
+const AUTHORIZATION_FIELDS: &[(&str, &str)] = &[
+    ("statement", "string"),
+    ("wallet", "address"),
+    ("account", "string"),
+    ("nonce", "string"),
+    ("expiresAt", "uint256"),
+];
+
+fn primary_type(action: WalletAction) -> &'static str {
+    match action {
+        WalletAction::Link => "LinkWallet",
+        WalletAction::Unlink => "UnlinkWallet",
+    }
+}
+
+fn statement(action: WalletAction) -> &'static str {
+    match action {
+        WalletAction::Link => "Link this wallet to your RoboVeda account.",
+        WalletAction::Unlink => "Unlink this wallet from your RoboVeda account.",
+    }
+}
+
+/// Typed data `wallet` signs to be linked to or unlinked from account `user_id` on `chain_id`,
+/// usable once with `nonce` until `expires_at`
+pub fn authorization(
+    action: WalletAction,
+    chain_id: u64,
+    wallet: &str,
+    user_id: Uuid,
+    nonce: &str,
+    expires_at: DateTime<Utc>,
+) -> TypedData {
+    eip712_services::typed_data(
+        chain_id,
+        primary_type(action),
+        AUTHORIZATION_FIELDS,
+        serde_json::json!({
+            "statement": statement(action),
+            "wallet": BlockchainService::to_checksum_address(wallet),
+            "account": user_id.to_string(),
+            "nonce": nonce,
+            "expiresAt": expires_at.timestamp(),
+        }),
+    )
+}
+
+/// Check `signed` is an unexpired authorization of `action` for `user_id`, signed by `wallet`.
+/// Returns its nonce, still to be consumed, and the chain it was signed on.
+pub fn check_authorization(
+    action: WalletAction,
+    user_id: Uuid,
+    wallet: &str,
+    signed: &SignedTypedData,
+    now: DateTime<Utc>,
+) -> ApiResult<(String, u64)> {
+    let data = &signed.typed_data;
+    let nonce = eip712_services::message_str(data, "nonce")?;
+    let expires_at = eip712_services::message_time(data, "expiresAt")?;
+    if expires_at <= now {
+        return Err(ApiError::Unauthorized("The wallet authorization has expired".to_string()));
+    }
+    let chain_id = eip712_services::chain_id(data)?;
+
+    let expected = authorization(action, chain_id, wallet, user_id, nonce, expires_at);
+    let signer = eip712_services::verify(data, signed.signature.trim(), &expected)?;
+    if !signer.eq_ignore_ascii_case(wallet) {
+        return Err(ApiError::Unauthorized("The authorization must be signed by the wallet it names".to_string()));
+    }
+    Ok((nonce.to_string(), chain_id))
+}
+
+/// When linking is allowed again after an unlink at `last_unlink`; `None` once the cooldown is over
+pub fn relink_available_at(
+    last_unlink: Option<DateTime<Utc>>,
+    cooldown_hours: u32,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    last_unlink.map(|at| at + Duration::hours(cooldown_hours as i64)).filter(|available_at| *available_at > now)
+}
+
+/// Refuse linking `address` (to `user_id`, or to a new account) within the cooldown of an
+/// unlink by the account or of the address
+pub async fn ensure_can_link(
+    conn: &mut PgConnection,
+    user_id: Option<Uuid>,
+    address: &str,
+    cooldown_hours: u32,
+) -> ApiResult<()> {
+    if cooldown_hours == 0 {
+        return Ok(());
+    }
+    let last_unlink: Option<DateTime<Utc>> = sqlx::query_scalar(
+        "SELECT MAX(unlinked_at) FROM wallet_unlinks WHERE user_id = $1 OR LOWER(address) = LOWER($2)",
+    )
+    .bind(user_id)
+    .bind(address)
+    .fetch_one(conn)
+    .await?;
+
+    match relink_available_at(last_unlink, cooldown_hours, Utc::now()) {
+        Some(available_at) => Err(ApiError::Conflict(format!(
+            "A wallet was unlinked recently; linking is possible again after {}",
+            available_at.to_rfc3339()
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Link `address` on `chain_id` to an account that has no wallet. Refused within the cooldown
+/// of an unlink, or when another account holds the wallet.
+pub async fn link(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    address: &str,
+    chain_id: u64,
+    cooldown_hours: u32,
+) -> ApiResult<()> {
+    ensure_can_link(&mut *conn, Some(user_id), address, cooldown_hours).await?;
+    let taken: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM users WHERE LOWER(wallet_address) = LOWER($1) AND id <> $2)",
+    )
+    .bind(address)
+    .bind(user_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    if taken {
+        return Err(ApiError::Conflict("This wallet is linked to another account".to_string()));
+    }
+
+    let linked = sqlx::query(
+        "UPDATE users SET wallet_address = $2, wallet_chain_id = $3, ens_name = NULL, ens_resolved_at = NULL, \
+         updated_at = NOW() WHERE id = $1 AND wallet_address IS NULL",
+    )
+    .bind(user_id)
+    .bind(BlockchainService::to_checksum_address(address))
+    .bind(chain_id as i64)
+    .execute(&mut *conn)
+    .await?;
+    if linked.rows_affected() == 0 {
+        return Err(ApiError::Conflict("Unlink the current wallet before linking another".to_string()));
+    }
+    Ok(())
+}
+
+/// Remove the account's wallet, record the unlink and revoke the sessions signed in with it.
+/// Returns how many sessions were revoked.
+pub async fn unlink(conn: &mut PgConnection, user_id: Uuid, address: &str, chain_id: Option<i64>) -> ApiResult<u64> {
+    sqlx::query(
+        "UPDATE users SET wallet_address = NULL, wallet_chain_id = NULL, ens_name = NULL, ens_resolved_at = NULL, \
+         updated_at = NOW() WHERE id = $1",
+    )
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("INSERT INTO wallet_unlinks (user_id, address, chain_id) VALUES ($1, $2, $3)")
+        .bind(user_id)
+        .bind(address)
+        .bind(chain_id)
+        .execute(&mut *conn)
+        .await?;
+
+    let revoked = sqlx::query(
+        "UPDATE user_sessions SET status = 'revoked', revoked_at = NOW(), revoked_by = $1 \
+         WHERE user_id = $1 AND auth_method = 'wallet' AND status = 'active'",
+    )
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(revoked.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::ecdsa::SigningKey;
+
+    #[test]
+    fn test_relink_available_at() {
+        let now = Utc::now();
+        assert_eq!(relink_available_at(None, 24, now), None);
+        assert_eq!(
+            relink_available_at(Some(now - Duration::hours(1)), 24, now),
+            Some(now + Duration::hours(23))
+        );
+        assert_eq!(relink_available_at(Some(now - Duration::hours(24)), 24, now), None);
+        assert_eq!(relink_available_at(Some(now), 0, now), None);
+    }
+
+    #[test]
+    fn test_check_authorization() {
+        let key = SigningKey::from_slice(&[7u8; 32]).unwrap();
+        let wallet = BlockchainService::address_of(key.verifying_key());
+        let user_id = Uuid::new_v4();
+        let now = Utc::now();
+        let expires_at = now + Duration::minutes(5);
+        let data = authorization(WalletAction::Link, 137, &wallet, user_id, "a1b2c3d4e5f60718", expires_at);
+        let sign = |data: &TypedData| {
+            let digest = eip712_services::signing_hash(data).unwrap();
+            let (sig, recovery_id) = key.sign_prehash_recoverable(&digest).unwrap();
+            let mut bytes = sig.to_bytes().to_vec();
+            bytes.push(27 + recovery_id.to_byte());
+            format!("0x{}", hex::encode(bytes))
+        };
+        let signed = SignedTypedData { signature: sign(&data), typed_data: data.clone() };
+
+        let (nonce, chain_id) = check_authorization(WalletAction::Link, user_id, &wallet, &signed, now).unwrap();
+        assert_eq!((nonce.as_str(), chain_id), ("a1b2c3d4e5f60718", 137));
+        // Not for another action, account or wallet, nor once expired
+        assert!(check_authorization(WalletAction::Unlink, user_id, &wallet, &signed, now).is_err());
+        assert!(check_authorization(WalletAction::Link, Uuid::new_v4(), &wallet, &signed, now).is_err());
+        let other = "0x00000000000000000000000000000000000000aa";
+        assert!(check_authorization(WalletAction::Link, user_id, other, &signed, now).is_err());
+        let later = now + Duration::minutes(6);
+        assert!(check_authorization(WalletAction::Link, user_id, &wallet, &signed, later).is_err());
+    }
+}
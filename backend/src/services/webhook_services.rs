@@ -0,0 +1,714 @@
+//! Outbound webhooks for device status changes, finished commands and transaction status changes.
+//!
+//! Database triggers queue a delivery per matching webhook; a background job sends due deliveries,
+//! retrying with exponential backoff and logging every attempt. Payloads are redacted under the
+//! owner's organization privacy settings and signed with a per-webhook secret derived from the
+//! managed webhook HMAC key, so rotating that key rotates every webhook secret. A single webhook's
+//! secret is rotated by bumping its version; the previous version keeps signing for a grace period.
+//!
+//! Every attempt stores a snapshot of the request sent and the response received, and owners can
+//! replay a failed delivery on demand.
+
+use chrono::{DateTime, Duration, Utc};
+use futures::stream::{self, StreamExt};
+use reqwest::Url;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::{FromRow, PgPool};
+use std::net::IpAddr;
+use std::sync::{Arc, LazyLock};
+use uuid::Uuid;
+use zeroize::Zeroizing;
+use crate::errors::{ApiError, ApiResult};
+use crate::services::key_services::{hmac_sha256_hex, KeyManager, KeyPurpose, ManagedKey};
+use crate::utils::redaction::{Redacted, RedactionPolicy};
+
+pub const WEBHOOK_EVENTS: &[&str] = &[
+    "device.online",
+    "device.offline",
+    "device.maintenance",
+    "command.completed",
+    "transaction.pending",
+    "transaction.completed",
+    "transaction.failed",
+];
+
+pub const WEBHOOK_COLUMNS: &str = "id, user_id, url, description, events, device_ids, enabled, secret_version, \
+     previous_secret_version, previous_secret_expires_at, created_at, updated_at";
+
+pub const DELIVERY_COLUMNS: &str = "id, webhook_id, event, payload, status, attempts, next_attempt_at, \
+     last_status_code, last_error, delivered_at, created_at";
+
+pub const ATTEMPT_COLUMNS: &str = "delivery_id, attempt, manual, status_code, error, duration_ms, request_headers, \
+     request_body, response_headers, response_body, attempted_at";
+
+pub const DELIVERY_STATUSES: &[&str] = &["pending", "delivered", "failed"];
+
+pub const MAX_WEBHOOKS_PER_USER: i64 = 10;
+/// Attempts before a delivery is given up on
+pub const MAX_ATTEMPTS: i32 = 8;
+const DELIVERY_INTERVAL_SECS: u64 = 5;
+const DELIVERY_BATCH: i64 = 50;
+const DELIVERY_CONCURRENCY: usize = 8;
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+/// How long a claimed delivery is hidden from other workers while it is being sent
+const CLAIM_LEASE_SECS: i64 = 120;
+/// Request and response bodies kept per attempt
+pub const MAX_SNAPSHOT_BYTES: usize = 8 * 1024;
+pub const DEFAULT_GRACE_PERIOD_HOURS: i64 = 24;
+pub const MAX_GRACE_PERIOD_HOURS: i64 = 168;
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+pub const KEY_ID_HEADER: &str = "X-Webhook-Key-Id";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+
+pub fn validate_events(events: &[String]) -> ApiResult<()> {
+    if events.is_empty() {
+        return Err(ApiError::ValidationError("Subscribe to at least one event".to_string()));
+    }
+    if let Some(unknown) = events.iter().find(|e| !WEBHOOK_EVENTS.contains(&e.as_str())) {
+        return Err(ApiError::ValidationError(format!(
+            "Unknown event '{}'; expected one of {}",
+            unknown,
+            WEBHOOK_EVENTS.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Webhooks must be HTTPS and may not target loopback or private addresses
+pub fn validate_url(url: &str) -> ApiResult<()> {
+    let parsed = Url::parse(url).map_err(|_| ApiError::ValidationError("url is not a valid URL".to_string()))?;
+    if parsed.scheme() != "https" {
+        return Err(ApiError::ValidationError("url must use https".to_string()));
+    }
+    let host = parsed.host_str().unwrap_or_default().trim_matches(['[', ']']);
+    let internal = match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified(),
+        Ok(IpAddr::V6(ip)) => ip.is_loopback() || ip.is_unspecified() || (ip.segments()[0] & 0xfe00) == 0xfc00,
+        Err(_) => host.is_empty() || host == "localhost" || host.ends_with(".localhost") || host.ends_with(".internal"),
+    };
+    if internal {
+        return Err(ApiError::ValidationError("url may not point at an internal address".to_string()));
+    }
+    Ok(())
+}
+
+/// Delay before retrying after `attempts` failed attempts: 30s doubling, capped at 6h
+pub fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.clamp(1, 16) - 1;
+    Duration::seconds(30 * (1i64 << exponent)).min(Duration::hours(6))
+}
+
+/// Per-webhook signing key for one of the webhook's secret versions, under a version of the
+/// managed webhook key. Version 1 is the secret every webhook starts with.
+pub fn signing_key(master: &ManagedKey, webhook_id: Uuid, secret_version: i32) -> ManagedKey {
+    let label = match secret_version {
+        1 => format!("webhook:{}", webhook_id),
+        version => format!("webhook:{}:v{}", webhook_id, version),
+    };
+    let secret = hmac_sha256_hex(master, label.as_bytes());
+    ManagedKey {
+        purpose: KeyPurpose::WebhookHmac,
+        version: master.version,
+        material: Zeroizing::new(secret.into_bytes()),
+    }
+}
+
+/// The secret receivers verify with, as handed to the webhook owner
+pub fn signing_secret(key: &ManagedKey) -> String {
+    String::from_utf8_lossy(&key.material).into_owned()
+}
+
+/// `v1=<hex hmac of "<timestamp>.<body>">`
+pub fn sign(key: &ManagedKey, timestamp: i64, body: &[u8]) -> String {
+    let mut signed = format!("{}.", timestamp).into_bytes();
+    signed.extend_from_slice(body);
+    format!("v1={}", hmac_sha256_hex(key, &signed))
+}
+
+/// Secret versions deliveries are signed with: the current one, plus the previous one while its
+/// grace period lasts
+pub fn active_secret_versions(
+    current: i32,
+    previous: Option<i32>,
+    previous_expires_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Vec<i32> {
+    let mut versions = vec![current];
+    if let (Some(previous), Some(expires_at)) = (previous, previous_expires_at)
+        && expires_at > now
+    {
+        versions.push(previous);
+    }
+    versions
+}
+
+/// One signature per key, comma-separated, newest first; receivers accept a match on any
+pub fn signature_header(keys: &[ManagedKey], timestamp: i64, body: &[u8]) -> String {
+    keys.iter().map(|key| sign(key, timestamp, body)).collect::<Vec<_>>().join(", ")
+}
+
+pub fn validate_grace_period(hours: i64) -> ApiResult<()> {
+    if !(0..=MAX_GRACE_PERIOD_HOURS).contains(&hours) {
+        return Err(ApiError::ValidationError(format!(
+            "grace_period_hours must be between 0 and {}",
+            MAX_GRACE_PERIOD_HOURS
+        )));
+    }
+    Ok(())
+}
+
+/// The first `MAX_SNAPSHOT_BYTES` of a body as text, dropping a character split by the cut
+pub fn snapshot_body(bytes: &[u8]) -> String {
+    let cut = &bytes[..bytes.len().min(MAX_SNAPSHOT_BYTES)];
+    match std::str::from_utf8(cut) {
+        Ok(text) => text.to_string(),
+        Err(e) if e.error_len().is_none() => String::from_utf8_lossy(&cut[..e.valid_up_to()]).into_owned(),
+        Err(_) => String::from_utf8_lossy(cut).into_owned(),
+    }
+}
+
+fn header_snapshot<'a>(headers: impl Iterator<Item = (&'a str, &'a [u8])>) -> Value {
+    let map: Map<String, Value> = headers
+        .map(|(name, value)| (name.to_string(), Value::String(String::from_utf8_lossy(value).into_owned())))
+        .collect();
+    Value::Object(map)
+}
+
+/// Combine several organizations' settings, masking whatever any of them masks
+pub fn strictest(policies: &[RedactionPolicy]) -> RedactionPolicy {
+    let Some(first) = policies.first() else {
+        return RedactionPolicy::default();
+    };
+    policies.iter().skip(1).fold(*first, |acc, p| RedactionPolicy {
+        mask_emails: acc.mask_emails || p.mask_emails,
+        mask_wallets: acc.mask_wallets || p.mask_wallets,
+        gps_decimals: match (acc.gps_decimals, p.gps_decimals) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        },
+    })
+}
+
+/// Redaction for a user's webhook payloads: the strictest of their organizations' settings
+pub async fn owner_policy(pool: &PgPool, user_id: Uuid) -> ApiResult<RedactionPolicy> {
+    let settings: Vec<serde_json::Value> = sqlx::query_scalar(
+        "SELECT o.privacy_settings FROM organizations o \
+         JOIN org_memberships m ON m.org_id = o.id WHERE m.user_id = $1 AND m.active",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    let policies: Vec<RedactionPolicy> = settings
+        .into_iter()
+        .map(serde_json::from_value)
+        .collect::<Result<_, _>>()
+        .map_err(|e| ApiError::InternalError(format!("Invalid privacy settings: {}", e)))?;
+    Ok(strictest(&policies))
+}
+
+const DUE_DELIVERY_RETURNING: &str = "d.id, d.event, d.payload, d.attempts, w.id AS webhook_id, w.url, w.user_id, \
+     w.enabled, w.secret_version, w.previous_secret_version, w.previous_secret_expires_at";
+
+#[derive(Debug, FromRow)]
+struct DueDelivery {
+    id: Uuid,
+    event: String,
+    payload: serde_json::Value,
+    attempts: i32,
+    webhook_id: Uuid,
+    url: String,
+    user_id: Uuid,
+    enabled: bool,
+    secret_version: i32,
+    previous_secret_version: Option<i32>,
+    previous_secret_expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct AttemptOutcome {
+    status_code: Option<i32>,
+    error: Option<String>,
+    duration_ms: i32,
+    request_headers: Option<Value>,
+    request_body: Option<String>,
+    response_headers: Option<Value>,
+    response_body: Option<String>,
+}
+
+impl AttemptOutcome {
+    fn succeeded(&self) -> bool {
+        self.error.is_none() && self.status_code.is_some_and(|c| (200..300).contains(&c))
+    }
+}
+
+/// Read at most `MAX_SNAPSHOT_BYTES` of a response body
+async fn read_snapshot(mut response: reqwest::Response) -> String {
+    let mut body = Vec::new();
+    while body.len() < MAX_SNAPSHOT_BYTES {
+        match response.chunk().await {
+            Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+            _ => break,
+        }
+    }
+    snapshot_body(&body)
+}
+
+async fn send(
+    client: &reqwest::Client,
+    keys: &[ManagedKey],
+    delivery: &DueDelivery,
+    body: Vec<u8>,
+    now: DateTime<Utc>,
+) -> AttemptOutcome {
+    let timestamp = now.timestamp();
+    let headers = [
+        ("Content-Type", "application/json".to_string()),
+        (SIGNATURE_HEADER, signature_header(keys, timestamp, &body)),
+        (TIMESTAMP_HEADER, timestamp.to_string()),
+        (KEY_ID_HEADER, keys.first().map(ManagedKey::kid).unwrap_or_default()),
+        (EVENT_HEADER, delivery.event.clone()),
+        (DELIVERY_HEADER, delivery.id.to_string()),
+    ];
+    let request_headers = header_snapshot(headers.iter().map(|(name, value)| (*name, value.as_bytes())));
+    let request_body = snapshot_body(&body);
+
+    let started = std::time::Instant::now();
+    let request = headers
+        .iter()
+        .fold(client.post(&delivery.url), |request, (name, value)| request.header(*name, value));
+    let response = request.body(body).send().await;
+
+    let mut outcome = match response {
+        Ok(response) => {
+            let status = response.status();
+            let response_headers = header_snapshot(response.headers().iter().map(|(n, v)| (n.as_str(), v.as_bytes())));
+            AttemptOutcome {
+                status_code: Some(status.as_u16() as i32),
+                error: (!status.is_success()).then(|| format!("Receiver responded {}", status)),
+                response_headers: Some(response_headers),
+                response_body: Some(read_snapshot(response).await),
+                ..Default::default()
+            }
+        }
+        Err(e) => AttemptOutcome { error: Some(e.to_string()), ..Default::default() },
+    };
+    outcome.duration_ms = started.elapsed().as_millis().min(i32::MAX as u128) as i32;
+    outcome.request_headers = Some(request_headers);
+    outcome.request_body = Some(request_body);
+    outcome
+}
+
+/// Send a claimed delivery and record the attempt. A manual replay that fails is left failed
+/// rather than put back on the retry schedule.
+async fn attempt(
+    pool: &PgPool,
+    client: &reqwest::Client,
+    master: &ManagedKey,
+    delivery: DueDelivery,
+    manual: bool,
+) -> ApiResult<AttemptOutcome> {
+    let outcome = if delivery.enabled {
+        let policy = owner_policy(pool, delivery.user_id).await?;
+        let body = serde_json::to_vec(&Redacted::new(&delivery.payload, policy))
+            .map_err(|e| ApiError::InternalError(format!("Failed to encode webhook payload: {}", e)))?;
+        let keys: Vec<ManagedKey> = active_secret_versions(
+            delivery.secret_version,
+            delivery.previous_secret_version,
+            delivery.previous_secret_expires_at,
+            Utc::now(),
+        )
+        .into_iter()
+        .map(|version| signing_key(master, delivery.webhook_id, version))
+        .collect();
+        send(client, &keys, &delivery, body, Utc::now()).await
+    } else {
+        AttemptOutcome { error: Some("Webhook is disabled".to_string()), ..Default::default() }
+    };
+
+    let attempts = delivery.attempts + 1;
+    let (status, next_attempt_at) = if outcome.succeeded() {
+        ("delivered", Utc::now())
+    } else if attempts >= MAX_ATTEMPTS || !delivery.enabled || manual {
+        ("failed", Utc::now())
+    } else {
+        ("pending", Utc::now() + retry_delay(attempts))
+    };
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO webhook_delivery_attempts \
+         (delivery_id, attempt, manual, status_code, error, duration_ms, request_headers, request_body, \
+          response_headers, response_body) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+    )
+    .bind(delivery.id)
+    .bind(attempts)
+    .bind(manual)
+    .bind(outcome.status_code)
+    .bind(&outcome.error)
+    .bind(outcome.duration_ms)
+    .bind(&outcome.request_headers)
+    .bind(&outcome.request_body)
+    .bind(&outcome.response_headers)
+    .bind(&outcome.response_body)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "UPDATE webhook_deliveries SET status = $2, attempts = $3, next_attempt_at = $4, last_status_code = $5, \
+         last_error = $6, delivered_at = CASE WHEN $2 = 'delivered' THEN NOW() END WHERE id = $1",
+    )
+    .bind(delivery.id)
+    .bind(status)
+    .bind(attempts)
+    .bind(next_attempt_at)
+    .bind(outcome.status_code)
+    .bind(&outcome.error)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    if status == "failed" && !manual {
+        tracing::warn!(delivery_id = %delivery.id, webhook_id = %delivery.webhook_id, "Webhook delivery failed permanently");
+    }
+    Ok(outcome)
+}
+
+/// Send every due delivery once. Returns how many were attempted.
+pub async fn deliver_due(pool: &PgPool, client: &reqwest::Client, keys: &dyn KeyManager) -> ApiResult<usize> {
+    let due = sqlx::query_as::<_, DueDelivery>(&format!(
+        "UPDATE webhook_deliveries d SET next_attempt_at = NOW() + make_interval(secs => $2) \
+         FROM device_webhooks w \
+         WHERE w.id = d.webhook_id AND d.id IN ( \
+             SELECT id FROM webhook_deliveries WHERE status = 'pending' AND next_attempt_at <= NOW() \
+             ORDER BY next_attempt_at LIMIT $1 FOR UPDATE SKIP LOCKED) \
+         RETURNING {}",
+        DUE_DELIVERY_RETURNING
+    ))
+    .bind(DELIVERY_BATCH)
+    .bind(CLAIM_LEASE_SECS as f64)
+    .fetch_all(pool)
+    .await?;
+    if due.is_empty() {
+        return Ok(0);
+    }
+
+    let master = keys.active_key(KeyPurpose::WebhookHmac).await?;
+    let count = due.len();
+    let results: Vec<ApiResult<AttemptOutcome>> = stream::iter(due)
+        .map(|delivery| attempt(pool, client, &master, delivery, false))
+        .buffer_unordered(DELIVERY_CONCURRENCY)
+        .collect()
+        .await;
+    for error in results.into_iter().filter_map(Result::err) {
+        tracing::error!("Recording webhook delivery failed: {}", error);
+    }
+    Ok(count)
+}
+
+static REPLAY_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(delivery_client);
+
+/// The outcome of a manual replay, as recorded in the attempt log
+#[derive(Debug, Serialize)]
+pub struct ReplayResult {
+    pub delivery_id: Uuid,
+    pub delivered: bool,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+}
+
+/// Send a failed delivery again right away, on the caller's request. The delivery is claimed
+/// the same way the background job claims due ones, so the two never send it concurrently.
+pub async fn replay_delivery(
+    pool: &PgPool,
+    keys: &dyn KeyManager,
+    webhook_id: Uuid,
+    delivery_id: Uuid,
+) -> ApiResult<ReplayResult> {
+    let delivery = sqlx::query_as::<_, DueDelivery>(&format!(
+        "UPDATE webhook_deliveries d SET next_attempt_at = NOW() + make_interval(secs => $3) \
+         FROM device_webhooks w \
+         WHERE w.id = d.webhook_id AND d.id = $1 AND d.webhook_id = $2 AND d.status = 'failed' \
+         RETURNING {}",
+        DUE_DELIVERY_RETURNING
+    ))
+    .bind(delivery_id)
+    .bind(webhook_id)
+    .bind(CLAIM_LEASE_SECS as f64)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("No failed delivery with that id".to_string()))?;
+
+    let master = keys.active_key(KeyPurpose::WebhookHmac).await?;
+    let outcome = attempt(pool, &REPLAY_CLIENT, &master, delivery, true).await?;
+    Ok(ReplayResult {
+        delivery_id,
+        delivered: outcome.succeeded(),
+        status_code: outcome.status_code,
+        error: outcome.error,
+        duration_ms: outcome.duration_ms,
+    })
+}
+
+/// Delivery health over a window, for the webhook dashboard
+#[derive(Debug, Serialize, FromRow)]
+pub struct DeliverySummary {
+    pub total: i64,
+    pub pending: i64,
+    pub delivered: i64,
+    pub failed: i64,
+    /// Delivered over settled (delivered or failed) deliveries
+    pub success_rate: Option<f64>,
+    pub attempts: i64,
+    pub avg_duration_ms: Option<f64>,
+    pub p95_duration_ms: Option<f64>,
+    pub last_delivered_at: Option<DateTime<Utc>>,
+    pub last_failure_at: Option<DateTime<Utc>>,
+}
+
+/// Counts by status and attempt latency for deliveries created since `since`
+pub async fn delivery_summary(pool: &PgPool, webhook_id: Uuid, since: DateTime<Utc>) -> ApiResult<DeliverySummary> {
+    let summary = sqlx::query_as::<_, DeliverySummary>(
+        "WITH recent AS (SELECT id, status, delivered_at FROM webhook_deliveries \
+                         WHERE webhook_id = $1 AND created_at >= $2), \
+              tries AS (SELECT a.duration_ms, a.status_code, a.error, a.attempted_at \
+                        FROM webhook_delivery_attempts a JOIN recent r ON r.id = a.delivery_id) \
+         SELECT (SELECT COUNT(*) FROM recent) AS total, \
+                (SELECT COUNT(*) FROM recent WHERE status = 'pending') AS pending, \
+                (SELECT COUNT(*) FROM recent WHERE status = 'delivered') AS delivered, \
+                (SELECT COUNT(*) FROM recent WHERE status = 'failed') AS failed, \
+                (SELECT COUNT(*) FILTER (WHERE status = 'delivered')::float8 \
+                        / NULLIF(COUNT(*) FILTER (WHERE status <> 'pending'), 0) FROM recent) AS success_rate, \
+                (SELECT COUNT(*) FROM tries) AS attempts, \
+                (SELECT AVG(duration_ms)::float8 FROM tries) AS avg_duration_ms, \
+                (SELECT percentile_cont(0.95) WITHIN GROUP (ORDER BY duration_ms) FROM tries) AS p95_duration_ms, \
+                (SELECT MAX(delivered_at) FROM recent) AS last_delivered_at, \
+                (SELECT MAX(attempted_at) FROM tries WHERE error IS NOT NULL) AS last_failure_at",
+    )
+    .bind(webhook_id)
+    .bind(since)
+    .fetch_one(pool)
+    .await?;
+    Ok(summary)
+}
+
+/// HTTP client for deliveries: bounded, and never following redirects to unvetted hosts
+fn delivery_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("HTTP client must build")
+}
+
+/// Start the background delivery job
+pub fn spawn_delivery_job(pool: Arc<PgPool>, keys: Arc<dyn KeyManager>) {
+    let client = delivery_client();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(DELIVERY_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = deliver_due(&pool, &client, keys.as_ref()).await {
+                tracing::error!("Webhook delivery job failed: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn master(version: i32) -> ManagedKey {
+        ManagedKey { purpose: KeyPurpose::WebhookHmac, version, material: Zeroizing::new(vec![7u8; 32]) }
+    }
+
+    #[test]
+    fn test_validate_url() {
+        assert!(validate_url("https://hooks.example.com/roboveda").is_ok());
+        assert!(validate_url("http://hooks.example.com").is_err());
+        assert!(validate_url("https://localhost:8080/hook").is_err());
+        assert!(validate_url("https://10.0.0.5/hook").is_err());
+        assert!(validate_url("https://169.254.169.254/latest").is_err());
+        assert!(validate_url("https://[::1]/hook").is_err());
+        assert!(validate_url("not a url").is_err());
+    }
+
+    #[test]
+    fn test_validate_events() {
+        assert!(validate_events(&["device.offline".to_string(), "command.completed".to_string()]).is_ok());
+        assert!(validate_events(&["transaction.completed".to_string()]).is_ok());
+        assert!(validate_events(&[]).is_err());
+        assert!(validate_events(&["device.exploded".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off_to_cap() {
+        assert_eq!(retry_delay(1), Duration::seconds(30));
+        assert_eq!(retry_delay(2), Duration::seconds(60));
+        assert_eq!(retry_delay(5), Duration::seconds(480));
+        assert_eq!(retry_delay(MAX_ATTEMPTS + 20), Duration::hours(6));
+    }
+
+    #[test]
+    fn test_signing_keys_are_per_webhook_and_per_version() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let key_a = signing_key(&master(1), a, 1);
+        assert_ne!(signing_secret(&key_a), signing_secret(&signing_key(&master(1), b, 1)));
+        assert_eq!(signing_secret(&key_a), signing_secret(&signing_key(&master(1), a, 1)));
+        assert_ne!(signing_secret(&key_a), signing_secret(&signing_key(&master(1), a, 2)));
+        assert_eq!(key_a.kid(), "webhook_hmac-v1");
+
+        // A receiver holding the secret can recompute the signature
+        let body = br#"{"event":"device.offline"}"#;
+        let receiver = ManagedKey { material: Zeroizing::new(signing_secret(&key_a).into_bytes()), ..master(1) };
+        assert_eq!(sign(&key_a, 1_700_000_000, body), sign(&receiver, 1_700_000_000, body));
+        assert_ne!(sign(&key_a, 1_700_000_000, body), sign(&key_a, 1_700_000_001, body));
+    }
+
+    #[test]
+    fn test_rotation_signs_with_both_secrets_during_grace() {
+        let now = Utc::now();
+        assert_eq!(active_secret_versions(1, None, None, now), [1]);
+        assert_eq!(active_secret_versions(3, Some(2), Some(now + Duration::hours(1)), now), [3, 2]);
+        assert_eq!(active_secret_versions(3, Some(2), Some(now - Duration::seconds(1)), now), [3]);
+
+        let id = Uuid::new_v4();
+        let keys = [signing_key(&master(1), id, 3), signing_key(&master(1), id, 2)];
+        let header = signature_header(&keys, 1_700_000_000, b"{}");
+        let signatures: Vec<&str> = header.split(", ").collect();
+        assert_eq!(signatures, [sign(&keys[0], 1_700_000_000, b"{}"), sign(&keys[1], 1_700_000_000, b"{}")]);
+
+        assert!(validate_grace_period(0).is_ok());
+        assert!(validate_grace_period(MAX_GRACE_PERIOD_HOURS + 1).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_body_truncates_on_char_boundary() {
+        assert_eq!(snapshot_body(b"ok"), "ok");
+        // The cut lands inside a two-byte character, which is dropped rather than mangled
+        let long = format!("a{}", "é".repeat(MAX_SNAPSHOT_BYTES));
+        let snapshot = snapshot_body(long.as_bytes());
+        assert_eq!(snapshot.len(), MAX_SNAPSHOT_BYTES - 1);
+        assert!(snapshot.ends_with('é'));
+    }
+
+    #[test]
+    fn test_strictest_policy() {
+        let open = RedactionPolicy { mask_emails: false, mask_wallets: false, gps_decimals: None };
+        let strict = RedactionPolicy { mask_emails: true, mask_wallets: false, gps_decimals: Some(3) };
+        let coarse = RedactionPolicy { gps_decimals: Some(1), ..open };
+        assert_eq!(strictest(&[]), RedactionPolicy::default());
+        assert_eq!(strictest(&[open]), open);
+        assert_eq!(
+            strictest(&[open, strict, coarse]),
+            RedactionPolicy { mask_emails: true, mask_wallets: false, gps_decimals: Some(1) }
+        );
+    }
+
+    /// The transaction triggers, against a migrated database named by `TEST_DATABASE_URL`.
+    /// Everything is written in a transaction that is rolled back; without the variable the
+    /// test does nothing.
+    #[tokio::test]
+    async fn test_transaction_events_are_queued_with_their_payload() {
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            return;
+        };
+        let pool = PgPool::connect(&url).await.unwrap();
+        let mut tx = pool.begin().await.unwrap();
+
+        let suffix = Uuid::new_v4().simple().to_string();
+        let user_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO users (email, username, password_hash, is_verified) VALUES ($1, $2, 'x', TRUE) RETURNING id",
+        )
+        .bind(format!("{}@example.com", suffix))
+        .bind(&suffix[..20])
+        .fetch_one(&mut *tx)
+        .await
+        .unwrap();
+        // One webhook limited to a device, one covering every device, one for other events
+        let events = vec!["transaction.pending".to_string(), "transaction.completed".to_string()];
+        let mut webhooks = Vec::new();
+        for (events, device_ids) in [
+            (events.clone(), Some(vec![Uuid::new_v4()])),
+            (events, None),
+            (vec!["device.offline".to_string()], None),
+        ] {
+            let id: Uuid = sqlx::query_scalar(
+                "INSERT INTO device_webhooks (user_id, url, events, device_ids) \
+                 VALUES ($1, 'https://hooks.example.com/roboveda', $2, $3) RETURNING id",
+            )
+            .bind(user_id)
+            .bind(&events)
+            .bind(&device_ids)
+            .fetch_one(&mut *tx)
+            .await
+            .unwrap();
+            webhooks.push(id);
+        }
+
+        let transaction_id = Uuid::new_v4();
+        sqlx::query(
+            "INSERT INTO transactions (id, user_id, amount, currency, payment_method, payment_id, status, \
+             product_type) VALUES ($1, $2, 12.50, 'usd', 'stripe', $3, 'pending', 'subscription')",
+        )
+        .bind(transaction_id)
+        .bind(user_id)
+        .bind(format!("pi_{}", suffix))
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+        sqlx::query("UPDATE transactions SET status = 'completed' WHERE id = $1")
+            .bind(transaction_id)
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        // Only a change of status is an event
+        sqlx::query("UPDATE transactions SET status = 'completed' WHERE id = $1")
+            .bind(transaction_id)
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+
+        let deliveries: Vec<(Uuid, String, Value)> = sqlx::query_as(
+            "SELECT webhook_id, event, payload FROM webhook_deliveries WHERE webhook_id = ANY($1) \
+             ORDER BY created_at, event DESC",
+        )
+        .bind(&webhooks)
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+        tx.rollback().await.unwrap();
+
+        // The device filter does not hold back events that concern no device
+        for webhook_id in &webhooks[..2] {
+            let events: Vec<&str> = deliveries
+                .iter()
+                .filter(|(id, _, _)| id == webhook_id)
+                .map(|(_, event, _)| event.as_str())
+                .collect();
+            assert_eq!(events, ["transaction.pending", "transaction.completed"]);
+        }
+        assert!(deliveries.iter().all(|(id, _, _)| *id != webhooks[2]));
+
+        let (_, _, payload) = deliveries.iter().find(|(_, event, _)| event == "transaction.completed").unwrap();
+        assert_eq!(payload["event"], "transaction.completed");
+        assert!(payload["id"].is_string() && payload["occurred_at"].is_string());
+        let data = &payload["data"];
+        assert_eq!(data["transaction_id"], transaction_id.to_string());
+        assert_eq!(data["previous_status"], "pending");
+        assert_eq!(data["status"], "completed");
+        assert_eq!(data["amount"].as_f64(), Some(12.5));
+        assert_eq!(data["currency"], "usd");
+        assert_eq!(data["payment_method"], "stripe");
+        assert_eq!(data["product_type"], "subscription");
+        assert!(data["failure_reason"].is_null());
+
+        let (_, _, payload) = deliveries.iter().find(|(_, event, _)| event == "transaction.pending").unwrap();
+        assert!(payload["data"]["previous_status"].is_null());
+    }
+}
@@ -0,0 +1,254 @@
+//! Arithmetic formulas for user-defined metrics.
+//!
+//! Grammar: numbers, dotted field paths (`velocity.x`), `+ - * /`, parentheses and calls
+//! taking at most one field path (`delta(battery_level)`, `distance()`). What paths and calls
+//! mean is up to the caller's resolver.
+
+use std::fmt;
+
+pub const MAX_FORMULA_LEN: usize = 500;
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Num(f64),
+    Field(String),
+    Call(String, Option<String>),
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(f64),
+    Ident(String),
+    Op(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Num(n) => write!(f, "{}", n),
+            Token::Ident(s) => write!(f, "{}", s),
+            Token::Op(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())) {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            tokens.push(Token::Num(text.parse().map_err(|_| format!("invalid number '{}'", text))?));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                i += 1;
+            }
+            let ident: String = chars[start..i].iter().collect();
+            if ident.ends_with('.') || ident.contains("..") {
+                return Err(format!("invalid field path '{}'", ident));
+            }
+            tokens.push(Token::Ident(ident));
+        } else if "+-*/()".contains(c) {
+            tokens.push(Token::Op(c));
+            i += 1;
+        } else {
+            return Err(format!("unexpected character '{}'", c));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, op: char) -> bool {
+        if self.peek() == Some(&Token::Op(op)) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err("formula is nested too deeply".to_string());
+        }
+        let mut lhs = self.term()?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = self.peek().cloned() {
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.term()?));
+        }
+        self.depth -= 1;
+        Ok(lhs)
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Op(op @ ('*' | '/'))) = self.peek().cloned() {
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat('-') {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Num(n)) => Ok(Expr::Num(n)),
+            Some(Token::Ident(name)) => {
+                if !self.eat('(') {
+                    return Ok(Expr::Field(name));
+                }
+                let arg = match self.next() {
+                    Some(Token::Op(')')) => return Ok(Expr::Call(name, None)),
+                    Some(Token::Ident(path)) => path,
+                    other => {
+                        return Err(format!(
+                            "{}() takes a field path, found {}",
+                            name,
+                            other.map_or("end of formula".to_string(), |t| format!("'{}'", t))
+                        ));
+                    }
+                };
+                if !self.eat(')') {
+                    return Err(format!("expected ')' after {}({}", name, arg));
+                }
+                Ok(Expr::Call(name, Some(arg)))
+            }
+            Some(Token::Op('(')) => {
+                let inner = self.expr()?;
+                if !self.eat(')') {
+                    return Err("missing ')'".to_string());
+                }
+                Ok(inner)
+            }
+            Some(token) => Err(format!("unexpected '{}'", token)),
+            None => Err("formula ends unexpectedly".to_string()),
+        }
+    }
+}
+
+pub fn parse(src: &str) -> Result<Expr, String> {
+    if src.trim().is_empty() || src.len() > MAX_FORMULA_LEN {
+        return Err(format!("formula must be 1-{} characters", MAX_FORMULA_LEN));
+    }
+    let mut parser = Parser { tokens: tokenize(src)?, pos: 0, depth: 0 };
+    let expr = parser.expr()?;
+    if let Some(token) = parser.peek() {
+        return Err(format!("unexpected '{}'", token));
+    }
+    Ok(expr)
+}
+
+impl Expr {
+    /// Evaluate with `resolve(function, path)`; a bare field is passed as `(None, Some(path))`.
+    /// Any unresolved input, division by zero or non-finite result yields `None`.
+    pub fn eval(&self, resolve: &mut impl FnMut(Option<&str>, Option<&str>) -> Option<f64>) -> Option<f64> {
+        let value = match self {
+            Expr::Num(n) => Some(*n),
+            Expr::Field(path) => resolve(None, Some(path)),
+            Expr::Call(name, arg) => resolve(Some(name), arg.as_deref()),
+            Expr::Neg(inner) => inner.eval(resolve).map(|v| -v),
+            Expr::Binary(op, lhs, rhs) => {
+                let (a, b) = (lhs.eval(resolve)?, rhs.eval(resolve)?);
+                match op {
+                    '+' => Some(a + b),
+                    '-' => Some(a - b),
+                    '*' => Some(a * b),
+                    '/' if b != 0.0 => Some(a / b),
+                    _ => None,
+                }
+            }
+        };
+        value.filter(|v| v.is_finite())
+    }
+
+    /// Every call and field in the formula, for validation
+    pub fn visit(&self, f: &mut impl FnMut(&Expr)) {
+        f(self);
+        match self {
+            Expr::Neg(inner) => inner.visit(f),
+            Expr::Binary(_, lhs, rhs) => {
+                lhs.visit(f);
+                rhs.visit(f);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(src: &str) -> Option<f64> {
+        parse(src).unwrap().eval(&mut |func, path| match (func, path) {
+            (None, Some("speed")) => Some(4.0),
+            (None, Some("velocity.x")) => Some(3.0),
+            (Some("delta"), Some("battery_level")) => Some(-8.0),
+            (Some("distance"), None) => Some(1200.0),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_precedence_and_unary() {
+        assert_eq!(eval("1 + 2 * 3"), Some(7.0));
+        assert_eq!(eval("(1 + 2) * 3"), Some(9.0));
+        assert_eq!(eval("-speed + 10"), Some(6.0));
+        assert_eq!(eval("10 - 4 - 3"), Some(3.0));
+        assert_eq!(eval("speed * velocity.x / .5"), Some(24.0));
+    }
+
+    #[test]
+    fn test_calls_and_missing_values() {
+        assert_eq!(eval("distance() / -delta(battery_level)"), Some(150.0));
+        assert_eq!(eval("speed / 0"), None);
+        assert_eq!(eval("unknown + 1"), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for bad in ["", "1 +", "(1 + 2", "avg(1)", "avg(a b)", "speed $ 2", "a..b", "1 2", "a.", "max(x"] {
+            assert!(parse(bad).is_err(), "'{}' should not parse", bad);
+        }
+        assert!(parse(&"(".repeat(40)).is_err());
+        assert_eq!(
+            parse("avg(velocity.x)").unwrap(),
+            Expr::Call("avg".to_string(), Some("velocity.x".to_string()))
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Geospatial helpers for device positions
+
+/// Mean Earth radius in meters
+pub const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+/// Great-circle distance between two coordinates in meters (haversine formula)
+pub fn haversine_distance_m(lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
+    let d_lat = (lat2 - lat1).to_radians();
+    let d_lng = (lng2 - lng1).to_radians();
+
+    let a = (d_lat / 2.0).sin().powi(2)
+        + lat1.to_radians().cos() * lat2.to_radians().cos() * (d_lng / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
+/// Validate a latitude/longitude pair
+pub fn is_valid_coordinate(lat: f64, lng: f64) -> bool {
+    (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lng)
+}
+
+/// Index-friendly prefilter around a point, checked before the exact haversine distance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_lat: f64,
+    pub max_lat: f64,
+    /// Inclusive longitude ranges; a box crossing the antimeridian is split into one on each
+    /// side, otherwise both are the same range
+    pub lng_ranges: [(f64, f64); 2],
+}
+
+impl BoundingBox {
+    pub fn contains(&self, lat: f64, lng: f64) -> bool {
+        (self.min_lat..=self.max_lat).contains(&lat)
+            && self.lng_ranges.iter().any(|(min, max)| (*min..=*max).contains(&lng))
+    }
+}
+
+/// Bounding box of the points within `radius_m` of a point, on the same sphere as
+/// [`haversine_distance_m`] so that nothing the distance check accepts is cut off
+pub fn bounding_box(lat: f64, lng: f64, radius_m: f64) -> BoundingBox {
+    let angle = radius_m / EARTH_RADIUS_M;
+    let lat_delta = angle.to_degrees();
+    let min_lat = (lat - lat_delta).max(-90.0);
+    let max_lat = (lat + lat_delta).min(90.0);
+    let cos_lat = lat.to_radians().cos().abs();
+
+    // Near the poles (or for huge radii) every longitude qualifies. Elsewhere the widest point
+    // of the circle is slightly poleward of `lat`, hence asin rather than dividing by cos_lat.
+    let lng_delta = if cos_lat < 1e-6 || min_lat <= -90.0 || max_lat >= 90.0 {
+        180.0
+    } else {
+        (angle.sin() / cos_lat).min(1.0).asin().to_degrees()
+    };
+
+    let (west, east) = (lng - lng_delta, lng + lng_delta);
+    let lng_ranges = if lng_delta >= 180.0 {
+        [(-180.0, 180.0); 2]
+    } else if west < -180.0 {
+        [(west + 360.0, 180.0), (-180.0, east)]
+    } else if east > 180.0 {
+        [(west, 180.0), (-180.0, east - 360.0)]
+    } else {
+        [(west, east); 2]
+    };
+
+    BoundingBox { min_lat, max_lat, lng_ranges }
+}
+
+/// Whether a point lies inside a polygon given as (lat, lng) vertices, implicitly closed.
+/// Even-odd ray casting on plain degrees: fine for regions that do not cross the antimeridian
+/// or a pole. Points exactly on an edge may land on either side.
+pub fn polygon_contains(polygon: &[(f64, f64)], lat: f64, lng: f64) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len().wrapping_sub(1);
+    for (i, &(lat_i, lng_i)) in polygon.iter().enumerate() {
+        let (lat_j, lng_j) = polygon[j];
+        if (lat_i > lat) != (lat_j > lat) && lng < (lng_j - lng_i) * (lat - lat_i) / (lat_j - lat_i) + lng_i {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// Bounding box of a polygon as (min_lat, max_lat, min_lng, max_lng)
+pub fn polygon_bounds(polygon: &[(f64, f64)]) -> (f64, f64, f64, f64) {
+    polygon.iter().fold(
+        (f64::INFINITY, f64::NEG_INFINITY, f64::INFINITY, f64::NEG_INFINITY),
+        |(min_lat, max_lat, min_lng, max_lng), &(lat, lng)| {
+            (min_lat.min(lat), max_lat.max(lat), min_lng.min(lng), max_lng.max(lng))
+        },
+    )
+}
+
+/// Area enclosed by a polygon in square degrees (shoelace formula); zero when degenerate
+pub fn polygon_area_deg2(polygon: &[(f64, f64)]) -> f64 {
+    let n = polygon.len();
+    let twice: f64 = (0..n)
+        .map(|i| {
+            let (lat_i, lng_i) = polygon[i];
+            let (lat_j, lng_j) = polygon[(i + 1) % n];
+            lng_i * lat_j - lng_j * lat_i
+        })
+        .sum();
+    twice.abs() / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_haversine_distance() {
+        // Paris to London is roughly 344 km
+        let d = haversine_distance_m(48.8566, 2.3522, 51.5074, -0.1278);
+        assert!((d - 343_500.0).abs() < 2_000.0);
+
+        assert!(haversine_distance_m(10.0, 10.0, 10.0, 10.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_bounding_box_contains_radius() {
+        let bbox = bounding_box(45.0, 7.0, 1_000.0);
+        assert!(bbox.min_lat < 45.0 && bbox.max_lat > 45.0);
+        assert_eq!(bbox.lng_ranges[0], bbox.lng_ranges[1]);
+
+        // A point 900 m east must fall inside the box
+        let east_lng = 7.0 + (900.0 / EARTH_RADIUS_M).to_degrees() / 45f64.to_radians().cos();
+        assert!(bbox.contains(45.0, east_lng));
+        assert!(!bbox.contains(45.0, 8.0));
+    }
+
+    #[test]
+    fn test_bounding_box_covers_haversine_radius() {
+        // Just inside the radius due north
+        let bbox = bounding_box(0.0, 0.0, 100_000.0);
+        let north = (99_950.0 / EARTH_RADIUS_M).to_degrees();
+        assert!(haversine_distance_m(0.0, 0.0, north, 0.0) < 100_000.0);
+        assert!(bbox.contains(north, 0.0));
+
+        // At high latitudes the circle is widest poleward of its centre
+        let bbox = bounding_box(80.0, 0.0, 500_000.0);
+        assert!(haversine_distance_m(80.0, 0.0, 81.0, 26.5) < 500_000.0);
+        assert!(bbox.contains(81.0, 26.5));
+    }
+
+    #[test]
+    fn test_bounding_box_across_antimeridian() {
+        let bbox = bounding_box(0.0, 179.9, 50_000.0);
+        assert_eq!(bbox.lng_ranges[0].1, 180.0);
+        assert_eq!(bbox.lng_ranges[1].0, -180.0);
+
+        // 30 km east lands on the far side of the antimeridian
+        let east = 179.9 + (30_000.0 / EARTH_RADIUS_M).to_degrees() - 360.0;
+        assert!(east < -179.0);
+        assert!(haversine_distance_m(0.0, 179.9, 0.0, east) < 50_000.0);
+        assert!(bbox.contains(0.0, east));
+        assert!(bbox.contains(0.0, 179.7));
+        assert!(!bbox.contains(0.0, -179.0));
+        assert!(!bbox.contains(0.0, 179.0));
+
+        let bbox = bounding_box(0.0, -179.9, 50_000.0);
+        assert!(bbox.contains(0.0, 179.8));
+        assert!(!bbox.contains(0.0, 0.0));
+    }
+
+    #[test]
+    fn test_bounding_box_near_pole() {
+        let bbox = bounding_box(89.9, 10.0, 20_000.0);
+        assert_eq!(bbox.max_lat, 90.0);
+        assert!(bbox.contains(89.95, -170.0));
+    }
+
+    #[test]
+    fn test_is_valid_coordinate() {
+        assert!(is_valid_coordinate(0.0, 0.0));
+        assert!(!is_valid_coordinate(91.0, 0.0));
+        assert!(!is_valid_coordinate(0.0, -181.0));
+    }
+
+    #[test]
+    fn test_polygon_contains() {
+        // A concave "L": the notch at the top right is outside
+        let l_shape = [(0.0, 0.0), (0.0, 2.0), (1.0, 2.0), (1.0, 1.0), (2.0, 1.0), (2.0, 0.0)];
+        assert!(polygon_contains(&l_shape, 0.5, 0.5));
+        assert!(polygon_contains(&l_shape, 0.5, 1.5));
+        assert!(polygon_contains(&l_shape, 1.5, 0.5));
+        assert!(!polygon_contains(&l_shape, 1.5, 1.5));
+        assert!(!polygon_contains(&l_shape, -0.5, 0.5));
+        assert!(!polygon_contains(&[], 0.0, 0.0));
+
+        assert_eq!(polygon_bounds(&l_shape), (0.0, 2.0, 0.0, 2.0));
+        assert!((polygon_area_deg2(&l_shape) - 3.0).abs() < 1e-12);
+        assert_eq!(polygon_area_deg2(&[(0.0, 0.0), (1.0, 1.0), (2.0, 2.0)]), 0.0);
+    }
+}
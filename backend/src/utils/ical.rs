@@ -0,0 +1,119 @@
+//! Minimal iCalendar (RFC 5545) writer for subscribable feeds
+
+use chrono::{DateTime, Utc};
+
+/// One VEVENT; all times are written in UTC
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// Stable across feed refreshes so clients update rather than duplicate the event
+    pub uid: String,
+    /// Raised on every change so clients replace their copy
+    pub sequence: i32,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub summary: String,
+    pub description: Option<String>,
+    pub cancelled: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escape a TEXT value (backslash, semicolon, comma and newlines)
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Fold a content line at 75 octets, continuing with CRLF and a space, never splitting a character
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+/// A VCALENDAR holding the events, with `name` shown as the calendar's title by most clients
+pub fn render(name: &str, events: &[Event]) -> String {
+    let now = timestamp(Utc::now());
+    let mut out = String::new();
+    for line in ["BEGIN:VCALENDAR", "VERSION:2.0", "PRODID:-//RoboVeda//Fleet Calendar//EN", "CALSCALE:GREGORIAN"] {
+        push_line(&mut out, line);
+    }
+    push_line(&mut out, &format!("X-WR-CALNAME:{}", escape(name)));
+    for event in events {
+        push_line(&mut out, "BEGIN:VEVENT");
+        push_line(&mut out, &format!("UID:{}", event.uid));
+        push_line(&mut out, &format!("SEQUENCE:{}", event.sequence));
+        push_line(&mut out, &format!("DTSTAMP:{}", now));
+        push_line(&mut out, &format!("LAST-MODIFIED:{}", timestamp(event.updated_at)));
+        push_line(&mut out, &format!("DTSTART:{}", timestamp(event.starts_at)));
+        push_line(&mut out, &format!("DTEND:{}", timestamp(event.ends_at)));
+        push_line(&mut out, &format!("SUMMARY:{}", escape(&event.summary)));
+        if let Some(description) = &event.description {
+            push_line(&mut out, &format!("DESCRIPTION:{}", escape(description)));
+        }
+        push_line(&mut out, if event.cancelled { "STATUS:CANCELLED" } else { "STATUS:CONFIRMED" });
+        push_line(&mut out, "END:VEVENT");
+    }
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_escape_and_fold() {
+        assert_eq!(escape("Dock A; bay 3, lidar\\camera\r\nswap"), "Dock A\\; bay 3\\, lidar\\\\camera\\nswap");
+
+        let mut out = String::new();
+        push_line(&mut out, &format!("SUMMARY:{}", "é".repeat(50)));
+        let lines: Vec<&str> = out.trim_end_matches("\r\n").split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|l| l.len() <= 75));
+        assert!(lines[1].starts_with(' '));
+    }
+
+    #[test]
+    fn test_render() {
+        let at = Utc.with_ymd_and_hms(2026, 10, 20, 8, 0, 0).unwrap();
+        let event = Event {
+            uid: "window-1@roboveda".to_string(),
+            sequence: 2,
+            starts_at: at,
+            ends_at: at + chrono::Duration::hours(2),
+            summary: "Rover 7: wheel swap".to_string(),
+            description: None,
+            cancelled: true,
+            updated_at: at,
+        };
+        let calendar = render("Maintenance", &[event]);
+
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+        assert!(calendar.contains("\r\nDTSTART:20261020T080000Z\r\nDTEND:20261020T100000Z\r\n"));
+        assert!(calendar.contains("\r\nSEQUENCE:2\r\n"));
+        assert!(calendar.contains("\r\nSTATUS:CANCELLED\r\n"));
+    }
+}
@@ -0,0 +1,219 @@
+//! A JSON Schema (draft 2020-12) subset for validating user-supplied documents.
+//!
+//! Supported keywords: `type`, `enum`, `const`, `properties`, `required`, `additionalProperties`,
+//! `maxProperties`, `items`, `minItems`, `maxItems`, `uniqueItems`, `minLength`, `maxLength`,
+//! `pattern`, `minimum`, `maximum`. Unknown keywords are ignored, as the specification requires.
+
+use regex::Regex;
+use serde_json::Value;
+
+/// One failed keyword at a JSON Pointer location (`""` is the document root)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaError {
+    pub path: String,
+    pub message: String,
+}
+
+/// Every violation of `schema` in `instance`, in document order
+pub fn validate(schema: &Value, instance: &Value) -> Vec<SchemaError> {
+    let mut errors = Vec::new();
+    check(schema, instance, "", &mut errors);
+    errors
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn type_matches(expected: &str, value: &Value) -> bool {
+    let actual = type_name(value);
+    // Integers are numbers too, and 2.0 is an integer
+    actual == expected
+        || (expected == "number" && actual == "integer")
+        || (expected == "integer" && value.as_f64().is_some_and(|n| n.fract() == 0.0))
+}
+
+/// Escape a property name for use in a JSON Pointer (RFC 6901)
+fn pointer_token(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+fn check(schema: &Value, instance: &Value, path: &str, errors: &mut Vec<SchemaError>) {
+    let mut fail = |message: String| errors.push(SchemaError { path: path.to_string(), message });
+    let schema = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => return fail("is not allowed".to_string()),
+        Value::Object(schema) => schema,
+        _ => return,
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| type_matches(t, instance)) {
+            // A value of the wrong type cannot be checked against the other keywords meaningfully
+            return fail(format!("must be of type {}, got {}", allowed.join(" or "), type_name(instance)));
+        }
+    }
+    if let Some(options) = schema.get("enum").and_then(Value::as_array)
+        && !options.contains(instance)
+    {
+        let options: Vec<String> = options.iter().map(Value::to_string).collect();
+        fail(format!("must be one of {}", options.join(", ")));
+    }
+    if let Some(expected) = schema.get("const")
+        && expected != instance
+    {
+        fail(format!("must equal {}", expected));
+    }
+
+    match instance {
+        Value::String(s) => {
+            let length = s.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64)
+                && length < min
+            {
+                fail(format!("must be at least {} characters", min));
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64)
+                && length > max
+            {
+                fail(format!("must be at most {} characters", max));
+            }
+            if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+                match Regex::new(pattern) {
+                    Ok(re) if !re.is_match(s) => fail(format!("must match pattern {}", pattern)),
+                    Ok(_) => {}
+                    Err(_) => fail(format!("schema pattern {} is invalid", pattern)),
+                }
+            }
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or(f64::NAN);
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64)
+                && n < min
+            {
+                fail(format!("must be at least {}", min));
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64)
+                && n > max
+            {
+                fail(format!("must be at most {}", max));
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64)
+                && (items.len() as u64) < min
+            {
+                fail(format!("must have at least {} items", min));
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64)
+                && items.len() as u64 > max
+            {
+                fail(format!("must have at most {} items", max));
+            }
+            if schema.get("uniqueItems").and_then(Value::as_bool) == Some(true)
+                && items.iter().enumerate().any(|(i, item)| items[..i].contains(item))
+            {
+                fail("must not contain duplicate items".to_string());
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check(item_schema, item, &format!("{}/{}", path, i), errors);
+                }
+            }
+        }
+        Value::Object(object) => {
+            if let Some(max) = schema.get("maxProperties").and_then(Value::as_u64)
+                && object.len() as u64 > max
+            {
+                fail(format!("must have at most {} properties", max));
+            }
+            for name in schema.get("required").and_then(Value::as_array).into_iter().flatten() {
+                if let Some(name) = name.as_str()
+                    && !object.contains_key(name)
+                {
+                    errors.push(SchemaError {
+                        path: format!("{}/{}", path, pointer_token(name)),
+                        message: "is required".to_string(),
+                    });
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (name, value) in object {
+                let child = format!("{}/{}", path, pointer_token(name));
+                match properties.and_then(|p| p.get(name)).or_else(|| schema.get("additionalProperties")) {
+                    Some(Value::Bool(false)) => {
+                        errors.push(SchemaError { path: child, message: "is not an allowed property".to_string() })
+                    }
+                    Some(property_schema) => check(property_schema, value, &child, errors),
+                    None => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn paths(schema: &Value, instance: &Value) -> Vec<String> {
+        validate(schema, instance).into_iter().map(|e| e.path).collect()
+    }
+
+    #[test]
+    fn test_types_and_ranges() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "count": { "type": "integer", "minimum": 1, "maximum": 8 },
+                "ratio": { "type": "number" },
+                "mode": { "enum": ["indoor", "outdoor"] },
+                "label": { "type": "string", "maxLength": 4, "pattern": "^[a-z]+$" }
+            }
+        });
+        assert!(validate(&schema, &json!({ "count": 4, "ratio": 1, "mode": "indoor", "label": "abc" })).is_empty());
+        assert!(validate(&schema, &json!({ "count": 2.0 })).is_empty());
+        assert_eq!(
+            paths(&schema, &json!({ "count": 9, "ratio": "x", "mode": "space", "label": "ABCDE" })),
+            ["/count", "/label", "/label", "/mode", "/ratio"]
+        );
+        assert_eq!(validate(&schema, &json!([]))[0].message, "must be of type object, got array");
+    }
+
+    #[test]
+    fn test_objects_and_arrays() {
+        let schema = json!({
+            "type": "object",
+            "required": ["tags"],
+            "additionalProperties": false,
+            "properties": {
+                "tags": {
+                    "type": "array",
+                    "maxItems": 2,
+                    "uniqueItems": true,
+                    "items": { "type": "string", "minLength": 1 }
+                },
+                "custom": { "type": "object" }
+            }
+        });
+        assert!(validate(&schema, &json!({ "tags": ["a"], "custom": { "any": 1 } })).is_empty());
+        assert_eq!(paths(&schema, &json!({})), ["/tags"]);
+        assert_eq!(paths(&schema, &json!({ "tags": ["a", ""], "a/b": 1 })), ["/a~1b", "/tags/1"]);
+        assert_eq!(paths(&schema, &json!({ "tags": ["a", "a", "b"] })), ["/tags", "/tags"]);
+    }
+}
@@ -1,8 +1,72 @@
-use jsonwebtoken::{encode, decode, Header, Validation, EncodingKey, DecodingKey};
+use jsonwebtoken::{encode, decode, decode_header, Header, Validation, EncodingKey, DecodingKey};
+use jsonwebtoken::errors::ErrorKind;
 use serde::{Deserialize, Serialize};
 use chrono::{Utc, Duration};
 use actix_web::HttpRequest;
+use std::collections::HashMap;
+use std::sync::RwLock;
 use uuid::Uuid;
+use zeroize::Zeroizing;
+
+/// Key id of the first managed JWT key, which is seeded from `JWT_SECRET` so tokens
+/// issued before key management (which carry no `kid`) keep verifying until it is retired
+pub const LEGACY_JWT_KID: &str = "jwt-v1";
+
+/// JWT keys published by the key manager, by `kid`
+#[derive(Clone, Default)]
+pub struct JwtKeyring {
+    pub active_kid: String,
+    pub keys: HashMap<String, Zeroizing<Vec<u8>>>,
+}
+
+impl std::fmt::Debug for JwtKeyring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JwtKeyring")
+            .field("active_kid", &self.active_kid)
+            .field("kids", &self.keys.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// When no keyring is installed (no database) tokens fall back to the configured secret
+static KEYRING: RwLock<Option<JwtKeyring>> = RwLock::new(None);
+
+/// Replace the keys used to sign and verify tokens (called after every load or rotation)
+pub fn install_jwt_keyring(keyring: JwtKeyring) {
+    *KEYRING.write().unwrap_or_else(|e| e.into_inner()) = Some(keyring);
+}
+
+fn sign_with(claims: &Claims, keyring: Option<&JwtKeyring>, secret: &str) -> Result<String, jsonwebtoken::errors::Error> {
+    if let Some(ring) = keyring
+        && let Some(key) = ring.keys.get(&ring.active_kid)
+    {
+        let header = Header { kid: Some(ring.active_kid.clone()), ..Header::default() };
+        return encode(&header, claims, &EncodingKey::from_secret(key));
+    }
+    encode(&Header::default(), claims, &EncodingKey::from_secret(secret.as_ref()))
+}
+
+fn verify_with(token: &str, keyring: Option<&JwtKeyring>, secret: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+    let mut validation = Validation::default();
+    validation.leeway = 60; // Allow 60 seconds clock skew
+
+    let key = match keyring {
+        Some(ring) => {
+            let kid = decode_header(token)?.kid.unwrap_or_else(|| LEGACY_JWT_KID.to_string());
+            // Unknown or destroyed key versions cannot verify anything
+            let key = ring.keys.get(&kid).ok_or(ErrorKind::InvalidSignature)?;
+            DecodingKey::from_secret(key)
+        }
+        None => DecodingKey::from_secret(secret.as_ref()),
+    };
+
+    decode::<Claims>(token, &key, &validation).map(|data| data.claims)
+}
+
+fn sign(claims: &Claims, secret: &str) -> Result<String, jsonwebtoken::errors::Error> {
+    let keyring = KEYRING.read().unwrap_or_else(|e| e.into_inner());
+    sign_with(claims, keyring.as_ref(), secret)
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
@@ -10,6 +74,12 @@ pub struct Claims {
     pub exp: i64,         // expiration timestamp
     pub iat: i64,         // issued at timestamp
     pub role: Option<String>, // user role (admin, user, etc.)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_method: Option<String>, // how the session was established (password, sso, break_glass)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<Uuid>, // server-side session backing this token (user or break-glass session)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sso_org_id: Option<Uuid>, // org whose identity provider established an SSO session
 }
 
 /// Create a JWT token for a user
@@ -30,26 +100,63 @@ pub fn create_token_with_role(
         iat: now.timestamp(),
         exp: (now + Duration::seconds(expiration_seconds)).timestamp(),
         role: role.map(String::from),
+        auth_method: None,
+        session_id: None,
+        sso_org_id: None,
+    };
+
+    sign(&claims, secret)
+}
+
+/// Create a JWT token bound to a risk-scored server-side session
+/// (`auth_method` is how it was established: password, sso or wallet;
+/// `sso_org_id` is the org whose identity provider signed the user in)
+pub fn create_session_token(
+    user_id: &str,
+    secret: &str,
+    expiration_seconds: i64,
+    auth_method: &str,
+    session_id: Uuid,
+    sso_org_id: Option<Uuid>,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let now = Utc::now();
+    let claims = Claims {
+        sub: user_id.to_owned(),
+        iat: now.timestamp(),
+        exp: (now + Duration::seconds(expiration_seconds)).timestamp(),
+        role: None,
+        auth_method: Some(auth_method.to_string()),
+        session_id: Some(session_id),
+        sso_org_id,
     };
 
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret.as_ref()),
-    )
+    sign(&claims, secret)
 }
 
-/// Verify and decode a JWT token
+/// Create a time-boxed superadmin token for an activated break-glass session
+pub fn create_break_glass_token(
+    user_id: &str,
+    session_id: Uuid,
+    secret: &str,
+    expires_at: i64,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let claims = Claims {
+        sub: user_id.to_owned(),
+        iat: Utc::now().timestamp(),
+        exp: expires_at,
+        role: Some("admin".to_string()),
+        auth_method: Some("break_glass".to_string()),
+        session_id: Some(session_id),
+        sso_org_id: None,
+    };
+
+    sign(&claims, secret)
+}
+
+/// Verify and decode a JWT token against the managed keyring, or `secret` when none is installed
 pub fn verify_token(token: &str, secret: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-    let mut validation = Validation::default();
-    validation.leeway = 60; // Allow 60 seconds clock skew
-    
-    decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(secret.as_ref()),
-        &validation,
-    )
-    .map(|data| data.claims)
+    let keyring = KEYRING.read().unwrap_or_else(|e| e.into_inner());
+    verify_with(token, keyring.as_ref(), secret)
 }
 
 /// Extract user ID from Authorization header in request
@@ -122,6 +229,74 @@ mod tests {
         assert_eq!(claims.role, Some("admin".to_string()));
     }
 
+    #[test]
+    fn test_create_session_token() {
+        let user_id = Uuid::new_v4().to_string();
+        let session_id = Uuid::new_v4();
+        let secret = "test_secret_key_12345";
+
+        let org_id = Uuid::new_v4();
+        let token = create_session_token(&user_id, secret, 3600, "sso", session_id, Some(org_id)).unwrap();
+        let claims = verify_token(&token, secret).unwrap();
+
+        assert_eq!(claims.auth_method.as_deref(), Some("sso"));
+        assert_eq!(claims.session_id, Some(session_id));
+        assert_eq!(claims.sso_org_id, Some(org_id));
+        assert!(claims.role.is_none());
+
+        let password_token = create_token(&user_id, secret, 3600).unwrap();
+        assert!(verify_token(&password_token, secret).unwrap().auth_method.is_none());
+    }
+
+    #[test]
+    fn test_create_break_glass_token() {
+        let user_id = Uuid::new_v4().to_string();
+        let session_id = Uuid::new_v4();
+        let secret = "test_secret_key_12345";
+        let expires_at = (Utc::now() + Duration::minutes(30)).timestamp();
+
+        let token = create_break_glass_token(&user_id, session_id, secret, expires_at).unwrap();
+        let claims = verify_token(&token, secret).unwrap();
+
+        assert_eq!(claims.auth_method.as_deref(), Some("break_glass"));
+        assert_eq!(claims.role.as_deref(), Some("admin"));
+        assert_eq!(claims.session_id, Some(session_id));
+        assert_eq!(claims.exp, expires_at);
+    }
+
+    #[test]
+    fn test_keyring_rotation() {
+        let claims = Claims {
+            sub: Uuid::new_v4().to_string(),
+            exp: (Utc::now() + Duration::hours(1)).timestamp(),
+            iat: Utc::now().timestamp(),
+            role: None,
+            auth_method: None,
+            session_id: None,
+            sso_org_id: None,
+        };
+        let legacy_secret = "legacy_secret";
+        let legacy_token = sign_with(&claims, None, legacy_secret).unwrap();
+
+        let mut ring = JwtKeyring {
+            active_kid: "jwt-v2".to_string(),
+            keys: HashMap::from([
+                (LEGACY_JWT_KID.to_string(), Zeroizing::new(legacy_secret.as_bytes().to_vec())),
+                ("jwt-v2".to_string(), Zeroizing::new(b"second_key".to_vec())),
+            ]),
+        };
+        let token = sign_with(&claims, Some(&ring), "unused").unwrap();
+        assert_eq!(decode_header(&token).unwrap().kid.as_deref(), Some("jwt-v2"));
+        assert!(verify_with(&token, Some(&ring), "unused").is_ok());
+        // Tokens minted before rotation still verify while the legacy key is kept
+        assert!(verify_with(&legacy_token, Some(&ring), "unused").is_ok());
+
+        assert!(!format!("{:?}", ring).contains("second_key"));
+
+        ring.keys.remove(LEGACY_JWT_KID);
+        assert!(verify_with(&legacy_token, Some(&ring), legacy_secret).is_err());
+    }
+
     #[test]
     fn test_expired_token() {
         let user_id = Uuid::new_v4().to_string();
@@ -2,8 +2,15 @@
 //!
 //! Provides structured logging with context and helper functions.
 
+use rust_decimal::Decimal;
 use tracing::{info, warn, error, debug, instrument};
 use std::time::Instant;
+use crate::utils::redaction::{redact_str, RedactionPolicy};
+
+/// Free-text log fields can carry user input; mask PII before it reaches the log sink
+fn scrub(details: Option<&str>) -> Option<String> {
+    details.map(|d| redact_str(d, &RedactionPolicy::for_logs()).into_owned())
+}
 
 /// Log an API request with timing information
 pub struct RequestTimer {
@@ -47,6 +54,7 @@ impl RequestTimer {
 
 /// Log user authentication events
 pub fn log_auth_event(event: &str, user_id: Option<&str>, success: bool, details: Option<&str>) {
+    let details = scrub(details);
     if success {
         info!(
             event = %event,
@@ -100,6 +108,7 @@ pub fn log_external_api(service: &str, endpoint: &str, status: u16, duration_ms:
 
 /// Log security events (rate limiting, blocked requests, etc.)
 pub fn log_security_event(event_type: &str, ip: Option<&str>, details: &str) {
+    let details = redact_str(details, &RedactionPolicy::for_logs());
     warn!(
         event_type = %event_type,
         ip = ?ip,
@@ -110,6 +119,7 @@ pub fn log_security_event(event_type: &str, ip: Option<&str>, details: &str) {
 
 /// Log device/robotics events
 pub fn log_device_event(device_id: &str, event: &str, details: Option<&str>) {
+    let details = scrub(details);
     info!(
         device_id = %device_id,
         event = %event,
@@ -119,7 +129,7 @@ pub fn log_device_event(device_id: &str, event: &str, details: Option<&str>) {
 }
 
 /// Log blockchain/payment events
-pub fn log_blockchain_event(event: &str, tx_hash: Option<&str>, amount: Option<f64>, status: &str) {
+pub fn log_blockchain_event(event: &str, tx_hash: Option<&str>, amount: Option<Decimal>, status: &str) {
     info!(
         event = %event,
         tx_hash = ?tx_hash,
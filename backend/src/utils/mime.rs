@@ -0,0 +1,145 @@
+//! Just enough MIME (RFC 2045/2046) to pull the plain-text body out of a raw email
+
+use crate::utils::base64_decode;
+
+/// Unfolded headers (names lower-cased) and the body after the blank line. Input that does not
+/// start with a header block is all body.
+fn split(raw: &str) -> (Vec<(String, String)>, &str) {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    let mut rest = raw;
+    while !rest.is_empty() {
+        let (line, next) = match rest.find('\n') {
+            Some(i) => (&rest[..i], &rest[i + 1..]),
+            None => (rest, ""),
+        };
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() {
+            return (headers, next);
+        }
+        if line.starts_with([' ', '\t']) && !headers.is_empty() {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+        } else {
+            return (Vec::new(), raw);
+        }
+        rest = next;
+    }
+    (headers, "")
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+}
+
+/// A parameter of a structured header, e.g. `boundary` from `multipart/alternative; boundary="x"`
+fn param(value: &str, name: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|part| {
+        let (key, value) = part.split_once('=')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+fn decode_quoted_printable(body: &str) -> Vec<u8> {
+    let bytes = body.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'=' {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        // Soft line break
+        if bytes[i + 1..].starts_with(b"\r\n") {
+            i += 3;
+        } else if bytes[i + 1..].starts_with(b"\n") {
+            i += 2;
+        } else if let Some(byte) = body.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(b'=');
+            i += 1;
+        }
+    }
+    out
+}
+
+fn decode(body: &str, encoding: Option<&str>) -> String {
+    let bytes = match encoding.map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("base64") => {
+            let compact: String = body.chars().filter(|c| !c.is_whitespace()).collect();
+            base64_decode(&compact).unwrap_or_default()
+        }
+        Some("quoted-printable") => decode_quoted_printable(body),
+        _ => body.as_bytes().to_vec(),
+    };
+    String::from_utf8_lossy(&bytes).replace("\r\n", "\n")
+}
+
+/// The first `text/plain` body of a message, searching nested multiparts and skipping attachments
+pub fn plain_text(raw: &str) -> Option<String> {
+    let (headers, body) = split(raw);
+    let content_type = header(&headers, "content-type").unwrap_or("text/plain");
+    let mime = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    let attachment = header(&headers, "content-disposition")
+        .is_some_and(|d| d.to_ascii_lowercase().starts_with("attachment"));
+
+    if mime.starts_with("multipart/") {
+        let delimiter = format!("--{}", param(content_type, "boundary")?);
+        for part in body.split(delimiter.as_str()).skip(1) {
+            if part.starts_with("--") {
+                break;
+            }
+            let part = part.strip_prefix("\r\n").or_else(|| part.strip_prefix('\n')).unwrap_or(part);
+            if let Some(text) = plain_text(part) {
+                return Some(text);
+            }
+        }
+        None
+    } else if mime == "text/plain" && !attachment {
+        Some(decode(body, header(&headers, "content-transfer-encoding")).trim_end().to_string())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_part() {
+        let raw = "From: a@example.com\r\nSubject: Re: alert\r\n\r\nack\r\nthanks\r\n";
+        assert_eq!(plain_text(raw).as_deref(), Some("ack\nthanks"));
+        assert_eq!(plain_text("Content-Type: text/html\r\n\r\n<p>hi</p>"), None);
+    }
+
+    #[test]
+    fn test_nested_multipart_with_encodings() {
+        let raw = concat!(
+            "Content-Type: multipart/mixed;\r\n boundary=\"outer\"\r\n\r\n",
+            "preamble\r\n",
+            "--outer\r\n",
+            "Content-Type: multipart/alternative; boundary=inner\r\n\r\n",
+            "--inner\r\n",
+            "Content-Type: text/plain; charset=utf-8\r\n",
+            "Content-Transfer-Encoding: quoted-printable\r\n\r\n",
+            "Caf=C3=A9 is on fi=\r\nre\r\n",
+            "--inner\r\n",
+            "Content-Type: text/html\r\n\r\n<p>html</p>\r\n",
+            "--inner--\r\n",
+            "--outer\r\n",
+            "Content-Type: text/plain\r\nContent-Disposition: attachment; filename=log.txt\r\n\r\nlog\r\n",
+            "--outer--\r\n",
+        );
+        assert_eq!(plain_text(raw).as_deref(), Some("Café is on fire"));
+
+        let encoded = "Content-Type: text/plain\r\nContent-Transfer-Encoding: base64\r\n\r\nYWNr\r\nIG9r\r\n";
+        assert_eq!(plain_text(encoded).as_deref(), Some("ack ok"));
+    }
+}
@@ -1,6 +1,14 @@
 pub mod crypto;
+pub mod formula;
+pub mod geo;
+pub mod ical;
+pub mod json_schema;
 pub mod jwt;
 pub mod logger;
+pub mod mime;
+pub mod pdf;
+pub mod privacy;
+pub mod redaction;
 pub mod verification;
 
 // Re-export commonly used items
@@ -19,6 +27,8 @@ pub use crypto::{
 pub use jwt::{
     create_token,
     create_token_with_role,
+    create_session_token,
+    create_break_glass_token,
     verify_token,
     extract_user_id_from_request,
     extract_claims_from_request,
@@ -30,6 +40,13 @@ pub use verification::{
     generate_verification_token,
     get_token_expiration,
     create_verification_email,
+    create_step_up_email,
+};
+
+pub use redaction::{
+    Redacted,
+    RedactionPolicy,
+    redact_str,
 };
 
 pub use logger::{
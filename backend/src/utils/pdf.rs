@@ -0,0 +1,191 @@
+//! Minimal PDF 1.4 writer for generated documents such as receipts: pages of text in the
+//! standard Helvetica fonts and straight rules, with no embedded fonts or images
+
+/// A4 portrait, in points
+pub const A4_WIDTH: f32 = 595.0;
+pub const A4_HEIGHT: f32 = 842.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        }
+    }
+}
+
+/// One page's content stream
+#[derive(Debug, Default)]
+pub struct Page {
+    content: Vec<u8>,
+}
+
+/// A string in WinAnsiEncoding as a PDF literal, with delimiters escaped. Characters the
+/// encoding lacks become `?`.
+fn literal(text: &str) -> Vec<u8> {
+    let mut out = vec![b'('];
+    for c in text.chars() {
+        let byte = match c {
+            '€' => 0x80,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '–' => 0x96,
+            '—' => 0x97,
+            '\n' | '\r' | '\t' => b' ',
+            c if (' '..='~').contains(&c) || ('\u{a0}'..='\u{ff}').contains(&c) => c as u32 as u8,
+            _ => b'?',
+        };
+        if matches!(byte, b'(' | b')' | b'\\') {
+            out.push(b'\\');
+        }
+        out.push(byte);
+    }
+    out.push(b')');
+    out
+}
+
+/// Approximate width of `text` in Helvetica at `size`, enough to right-align short strings
+pub fn text_width(text: &str, size: f32) -> f32 {
+    let units: u32 = text
+        .chars()
+        .map(|c| match c {
+            ' ' | '.' | ',' | ':' | ';' | '!' | 'i' | 'j' | 'l' | 'I' | 'f' | 't' | '/' => 278,
+            'm' | 'w' | 'M' | 'W' | '@' | '%' => 833,
+            'A'..='Z' => 667,
+            _ => 556,
+        })
+        .sum();
+    units as f32 * size / 1000.0
+}
+
+impl Page {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write `text` with its baseline starting at (`x`, `y`), measured from the bottom left
+    pub fn text(&mut self, x: f32, y: f32, size: f32, font: Font, text: &str) {
+        self.content
+            .extend_from_slice(format!("BT /{} {} Tf {} {} Td ", font.resource(), size, x, y).as_bytes());
+        self.content.extend_from_slice(&literal(text));
+        self.content.extend_from_slice(b" Tj ET\n");
+    }
+
+    /// Write `text` ending at `right`
+    pub fn text_right(&mut self, right: f32, y: f32, size: f32, font: Font, text: &str) {
+        self.text(right - text_width(text, size), y, size, font, text);
+    }
+
+    /// A horizontal rule from `x1` to `x2`
+    pub fn rule(&mut self, x1: f32, x2: f32, y: f32, width: f32) {
+        self.content
+            .extend_from_slice(format!("{} w {} {} m {} {} l S\n", width, x1, y, x2, y).as_bytes());
+    }
+}
+
+/// A document of A4 pages, titled `title` in its metadata
+pub fn render(title: &str, pages: &[Page]) -> Vec<u8> {
+    // Objects: 1 catalog, 2 page tree, 3-4 fonts, 5 info, then a page and its content per page
+    let mut objects: Vec<Vec<u8>> = Vec::new();
+    let kids: Vec<String> = (0..pages.len()).map(|i| format!("{} 0 R", 6 + 2 * i)).collect();
+    objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+    objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()).into_bytes());
+    for base_font in ["Helvetica", "Helvetica-Bold"] {
+        objects.push(
+            format!("<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>", base_font)
+                .into_bytes(),
+        );
+    }
+    let mut info = b"<< /Producer (RoboVeda) /Title ".to_vec();
+    info.extend_from_slice(&literal(title));
+    info.extend_from_slice(b" >>");
+    objects.push(info);
+    for (i, page) in pages.iter().enumerate() {
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                A4_WIDTH,
+                A4_HEIGHT,
+                7 + 2 * i
+            )
+            .into_bytes(),
+        );
+        let mut stream = format!("<< /Length {} >>\nstream\n", page.content.len()).into_bytes();
+        stream.extend_from_slice(&page.content);
+        stream.extend_from_slice(b"\nendstream");
+        objects.push(stream);
+    }
+
+    let mut out = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        out.extend_from_slice(object);
+        out.extend_from_slice(b"\nendobj\n");
+    }
+    let xref_at = out.len();
+    out.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for offset in offsets {
+        out.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    out.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R /Info 5 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref_at
+        )
+        .as_bytes(),
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal() {
+        assert_eq!(literal("Total (incl. tax)"), b"(Total \\(incl. tax\\))".to_vec());
+        assert_eq!(literal("5 €, café"), b"(5 \x80, caf\xe9)".to_vec());
+        assert_eq!(literal("ロボ\\"), b"(??\\\\)".to_vec());
+    }
+
+    #[test]
+    fn test_render() {
+        let mut page = Page::new();
+        page.text(50.0, 800.0, 12.0, Font::Bold, "Receipt");
+        page.rule(50.0, 545.0, 790.0, 0.5);
+        page.text_right(545.0, 770.0, 10.0, Font::Regular, "19.99");
+        let pdf = render("Receipt R-1", &[page]);
+
+        assert!(pdf.starts_with(b"%PDF-1.4\n"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("/Count 1"));
+        assert!(text.contains("(Receipt) Tj"));
+
+        // Every xref entry points at its object
+        let at = pdf.windows(10).rposition(|w| w == b"startxref\n").unwrap();
+        let startxref: usize = String::from_utf8_lossy(&pdf[at + 10..]).lines().next().unwrap().parse().unwrap();
+        let xref = String::from_utf8_lossy(&pdf[startxref..]).to_string();
+        for (i, entry) in xref.lines().skip(3).take(7).enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj", i + 1).as_bytes()));
+        }
+    }
+
+    #[test]
+    fn test_text_width() {
+        assert!((text_width("10.00", 10.0) - 25.02).abs() < 0.001);
+    }
+}
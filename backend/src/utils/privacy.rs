@@ -0,0 +1,155 @@
+//! Differential privacy for published aggregates
+//!
+//! Counts are released with Laplace noise of scale `sensitivity / epsilon`, where each
+//! contributor's share has been clipped so the sensitivity holds. A release made of several
+//! counts splits its epsilon evenly between them. Groups with fewer contributors than the
+//! minimum cohort are withheld altogether. Releases are cached for a while, since drawing
+//! fresh noise on every request would let repeated queries average it away.
+
+use chrono::{DateTime, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Privacy parameters for one release
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PrivacyParams {
+    /// Total privacy loss of the release; smaller is more private and noisier
+    pub epsilon: f64,
+    /// Groups with fewer contributors than this are withheld
+    pub min_cohort: i64,
+}
+
+/// Published next to noisy figures so readers know how to interpret them
+#[derive(Debug, Clone, Serialize)]
+pub struct PrivacyNotice {
+    pub mechanism: &'static str,
+    pub epsilon: f64,
+    pub min_cohort: i64,
+    pub released_at: DateTime<Utc>,
+}
+
+/// A sample of the Laplace distribution centred on zero
+pub fn laplace(scale: f64, rng: &mut impl Rng) -> f64 {
+    // Inverse CDF on u in (-0.5, 0.5)
+    let u: f64 = rng.gen_range(-0.5..0.5);
+    -scale * u.signum() * (1.0 - 2.0 * u.abs()).max(f64::MIN_POSITIVE).ln()
+}
+
+/// Draws the noisy counts of one release
+pub struct Release {
+    epsilon_per_count: f64,
+    min_cohort: i64,
+    rng: StdRng,
+    notice: PrivacyNotice,
+}
+
+impl Release {
+    /// A release of `counts` figures sharing the epsilon in `params`
+    pub fn new(params: PrivacyParams, counts: usize) -> Self {
+        Self::with_rng(params, counts, StdRng::from_entropy())
+    }
+
+    pub fn with_rng(params: PrivacyParams, counts: usize, rng: StdRng) -> Self {
+        Self {
+            epsilon_per_count: params.epsilon / counts.max(1) as f64,
+            min_cohort: params.min_cohort,
+            rng,
+            notice: PrivacyNotice {
+                mechanism: "laplace",
+                epsilon: params.epsilon,
+                min_cohort: params.min_cohort,
+                released_at: Utc::now(),
+            },
+        }
+    }
+
+    /// `value` with noise, rounded and never negative, or `None` when fewer than the minimum
+    /// cohort contributed. `sensitivity` is the most any one contributor can add to `value`.
+    pub fn count(&mut self, value: i64, contributors: i64, sensitivity: f64) -> Option<i64> {
+        if contributors < self.min_cohort {
+            return None;
+        }
+        let noisy = value as f64 + laplace(sensitivity / self.epsilon_per_count, &mut self.rng);
+        Some(noisy.round().max(0.0) as i64)
+    }
+
+    pub fn notice(&self) -> PrivacyNotice {
+        self.notice.clone()
+    }
+}
+
+/// Holds the latest release until it is `ttl` old
+pub struct ReleaseCache<T> {
+    ttl: Duration,
+    slot: Mutex<Option<(Instant, T)>>,
+}
+
+impl<T: Clone> ReleaseCache<T> {
+    pub const fn new(ttl: Duration) -> Self {
+        Self { ttl, slot: Mutex::new(None) }
+    }
+
+    pub fn get(&self) -> Option<T> {
+        let slot = self.slot.lock().unwrap_or_else(|e| e.into_inner());
+        slot.as_ref().filter(|(at, _)| at.elapsed() < self.ttl).map(|(_, value)| value.clone())
+    }
+
+    /// Keep `value` unless a fresh release was stored meanwhile, returning whichever is kept
+    /// so concurrent callers publish the same figures
+    pub fn put(&self, value: T) -> T {
+        let mut slot = self.slot.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((at, existing)) = slot.as_ref()
+            && at.elapsed() < self.ttl
+        {
+            return existing.clone();
+        }
+        *slot = Some((Instant::now(), value.clone()));
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMS: PrivacyParams = PrivacyParams { epsilon: 1.0, min_cohort: 10 };
+
+    #[test]
+    fn test_laplace_is_centred_with_expected_spread() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let samples: Vec<f64> = (0..20_000).map(|_| laplace(2.0, &mut rng)).collect();
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let mean_abs = samples.iter().map(|s| s.abs()).sum::<f64>() / samples.len() as f64;
+        assert!(mean.abs() < 0.1, "mean {}", mean);
+        // E|X| equals the scale
+        assert!((mean_abs - 2.0).abs() < 0.1, "mean |x| {}", mean_abs);
+    }
+
+    #[test]
+    fn test_release_suppresses_small_cohorts() {
+        let mut release = Release::with_rng(PARAMS, 2, StdRng::seed_from_u64(1));
+        assert_eq!(release.count(500, 9, 1.0), None);
+        let noisy = release.count(500, 10, 1.0).unwrap();
+        // Scale 2 noise: far beyond 40 away is vanishingly unlikely
+        assert!((noisy - 500).abs() < 40, "noisy {}", noisy);
+        assert!(release.count(0, 50, 1.0).unwrap() >= 0);
+        assert_eq!(release.notice().epsilon, 1.0);
+    }
+
+    #[test]
+    fn test_release_cache() {
+        let cache = ReleaseCache::new(Duration::from_secs(60));
+        assert_eq!(cache.get(), None);
+        assert_eq!(cache.put(1), 1);
+        // A second release within the ttl does not replace the first
+        assert_eq!(cache.put(2), 1);
+        assert_eq!(cache.get(), Some(1));
+
+        let expired = ReleaseCache::new(Duration::ZERO);
+        expired.put(1);
+        assert_eq!(expired.get(), None);
+    }
+}
@@ -0,0 +1,192 @@
+//! PII redaction for logs, webhook payloads and data exports
+//!
+//! Wrap any serializable value in [`Redacted`] to have emails, wallet addresses and
+//! GPS coordinates masked according to a [`RedactionPolicy`] as it is serialized.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::LazyLock;
+
+static EMAIL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b([A-Za-z0-9._%+-])[A-Za-z0-9._%+-]*@([A-Za-z0-9.-]+\.[A-Za-z]{2,})\b")
+        .expect("email pattern must compile")
+});
+
+// Exactly 40 hex digits, so 64-digit transaction hashes are left alone
+static WALLET: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b0x([0-9a-fA-F]{4})[0-9a-fA-F]{32}([0-9a-fA-F]{4})\b").expect("wallet pattern must compile")
+});
+
+/// JSON keys whose numeric values are coordinates
+const COORDINATE_KEYS: &[&str] = &["latitude", "longitude", "lat", "lng", "lon", "last_latitude", "last_longitude"];
+
+/// Most decimal places a coordinate may be reported with (~1cm)
+pub const MAX_GPS_DECIMALS: u8 = 7;
+
+/// What to mask. Stored per organization as its privacy settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionPolicy {
+    pub mask_emails: bool,
+    pub mask_wallets: bool,
+    /// Decimal places kept on coordinates; `None` keeps full precision
+    pub gps_decimals: Option<u8>,
+}
+
+impl Default for RedactionPolicy {
+    fn default() -> Self {
+        Self {
+            mask_emails: true,
+            mask_wallets: true,
+            gps_decimals: None,
+        }
+    }
+}
+
+impl RedactionPolicy {
+    /// Policy applied to application logs regardless of org settings (~1km location precision)
+    pub fn for_logs() -> Self {
+        Self {
+            mask_emails: true,
+            mask_wallets: true,
+            gps_decimals: Some(2),
+        }
+    }
+
+    pub fn is_noop(&self) -> bool {
+        !self.mask_emails && !self.mask_wallets && self.gps_decimals.is_none()
+    }
+}
+
+/// `alice@example.com` -> `a***@example.com`
+pub fn mask_email(email: &str) -> String {
+    EMAIL.replace_all(email, "$1***@$2").into_owned()
+}
+
+/// `0x52908400098527886E0F7030069857D2E4169EE7` -> `0x5290…9EE7`
+pub fn mask_wallet(address: &str) -> String {
+    WALLET.replace_all(address, "0x$1…$2").into_owned()
+}
+
+/// Truncate a coordinate to the given number of decimal places
+pub fn round_coordinate(value: f64, decimals: u8) -> f64 {
+    let factor = 10f64.powi(decimals.min(MAX_GPS_DECIMALS) as i32);
+    (value * factor).round() / factor
+}
+
+/// Mask emails and wallet addresses appearing anywhere in free text
+pub fn redact_str<'a>(text: &'a str, policy: &RedactionPolicy) -> Cow<'a, str> {
+    let mut out = Cow::Borrowed(text);
+    if policy.mask_emails && EMAIL.is_match(&out) {
+        out = Cow::Owned(EMAIL.replace_all(&out, "$1***@$2").into_owned());
+    }
+    if policy.mask_wallets && WALLET.is_match(&out) {
+        out = Cow::Owned(WALLET.replace_all(&out, "0x$1…$2").into_owned());
+    }
+    out
+}
+
+/// Redact a JSON document in place
+pub fn redact_value(value: &mut serde_json::Value, policy: &RedactionPolicy) {
+    match value {
+        serde_json::Value::String(s) => {
+            if let Cow::Owned(redacted) = redact_str(s, policy) {
+                *s = redacted;
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(|v| redact_value(v, policy)),
+        serde_json::Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                if let Some(decimals) = policy.gps_decimals
+                    && COORDINATE_KEYS.contains(&key.as_str())
+                    && let Some(n) = v.as_f64()
+                {
+                    *v = serde_json::json!(round_coordinate(n, decimals));
+                } else {
+                    redact_value(v, policy);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Serializer wrapper that redacts `value` on the way out.
+/// Also implements `Display` (as JSON) so it can be dropped straight into log fields.
+pub struct Redacted<'a, T: ?Sized> {
+    value: &'a T,
+    policy: RedactionPolicy,
+}
+
+impl<'a, T: Serialize + ?Sized> Redacted<'a, T> {
+    pub fn new(value: &'a T, policy: RedactionPolicy) -> Self {
+        Self { value, policy }
+    }
+
+    /// Redacted JSON form of the wrapped value
+    pub fn to_value(&self) -> serde_json::Result<serde_json::Value> {
+        let mut value = serde_json::to_value(self.value)?;
+        if !self.policy.is_noop() {
+            redact_value(&mut value, &self.policy);
+        }
+        Ok(value)
+    }
+}
+
+impl<T: Serialize + ?Sized> Serialize for Redacted<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_value()
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<T: Serialize + ?Sized> fmt::Display for Redacted<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_value() {
+            Ok(serde_json::Value::String(s)) => f.write_str(&s),
+            Ok(value) => write!(f, "{}", value),
+            Err(_) => f.write_str("[unserializable]"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_email_and_wallet() {
+        assert_eq!(mask_email("alice@example.com"), "a***@example.com");
+        assert_eq!(
+            mask_wallet("0x52908400098527886E0F7030069857D2E4169EE7"),
+            "0x5290…9EE7"
+        );
+        // Transaction hashes are not wallet addresses
+        let tx_hash = format!("0x{}", "ab".repeat(32));
+        assert_eq!(mask_wallet(&tx_hash), tx_hash);
+    }
+
+    #[test]
+    fn test_redacted_serializer() {
+        let payload = serde_json::json!({
+            "user": { "email": "bob.smith@corp.io", "wallet": "0x52908400098527886E0F7030069857D2E4169EE7" },
+            "position": { "latitude": 12.9715987, "longitude": 77.5945627 },
+            "note": "contact bob.smith@corp.io",
+        });
+        let policy = RedactionPolicy { gps_decimals: Some(2), ..Default::default() };
+
+        let out = serde_json::to_value(Redacted::new(&payload, policy)).unwrap();
+        assert_eq!(out["user"]["email"], "b***@corp.io");
+        assert_eq!(out["user"]["wallet"], "0x5290…9EE7");
+        assert_eq!(out["position"]["latitude"], 12.97);
+        assert_eq!(out["position"]["longitude"], 77.59);
+        assert_eq!(out["note"], "contact b***@corp.io");
+
+        let off = RedactionPolicy { mask_emails: false, mask_wallets: false, gps_decimals: None };
+        assert_eq!(serde_json::to_value(Redacted::new(&payload, off)).unwrap(), payload);
+        assert_eq!(Redacted::new("x@y.com", RedactionPolicy::for_logs()).to_string(), "x***@y.com");
+    }
+}
@@ -45,6 +45,36 @@ RoboVeda Team
     (subject, body)
 }
 
+/// Create step-up verification email body for a suspicious sign-in
+pub fn create_step_up_email(username: &str, code: &str, reasons: &[String]) -> (String, String) {
+    let subject = "Confirm your RoboVeda sign-in".to_string();
+
+    let body = format!(
+        r#"
+Hello {},
+
+We noticed a sign-in to your RoboVeda account that looks unusual:
+{}
+
+If this was you, enter this code to finish signing in:
+
+    {}
+
+The code expires in 10 minutes.
+
+If this wasn't you, change your password immediately.
+
+Best regards,
+RoboVeda Team
+        "#,
+        username,
+        reasons.iter().map(|r| format!("  - {}", r)).collect::<Vec<_>>().join("\n"),
+        code
+    );
+
+    (subject, body)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;